@@ -0,0 +1,688 @@
+// Built-in assembler for the Intel-syntax text `gen_program` emits
+//
+// `assemble` reads the assembly one line at a time into sections of
+// fragments: encoded bytes (`encoder.rs`), jumps and calls to labels, and
+// alignment padding. Once every line is read, jumps to labels in their own
+// section start in the rel8 form and grow to rel32 until every displacement
+// fits, the same relaxation GNU as does. References to symbols defined in
+// the section they are used from are then resolved, unless the symbol is
+// weak, and the rest become relocations for the object writer (`coff.rs`).
+//
+// Only what codegen emits is accepted: labels, instructions, the section
+// and symbol directives, and data. `.type`, `.size`, and `.cfi_*` carry ELF
+// and DWARF information that no object written here has, and are skipped.
+// Anything else is an error rather than a silently wrong object.
+
+use std::collections::HashMap;
+
+use crate::att::{parse_int, split_operands};
+use crate::encoder::{self, Branch, Fixup, FixupKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SectionKind {
+    Code,
+    Data,
+    ReadOnly,
+    /// Zero-initialized: a size, but no bytes in the object
+    Bss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Binding {
+    Local,
+    Global,
+    Weak,
+}
+
+/// What a relocation is relative to. Labels starting with `.L` never reach
+/// the symbol table, so references to them name their section instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RelocTarget {
+    Section(usize),
+    Symbol(usize),
+}
+
+/// A field at `offset` that the linker fills with `target + addend`, less
+/// the field's own address for `Pc32`. The field itself is left zero; each
+/// object format stores the addend in its own way.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reloc {
+    pub offset: usize,
+    pub kind: FixupKind,
+    pub target: RelocTarget,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Section {
+    pub name: String,
+    pub kind: SectionKind,
+    pub align: u64,
+    /// Contents; all zeros for `Bss`, which the writer emits as a size only
+    pub data: Vec<u8>,
+    pub relocs: Vec<Reloc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Symbol {
+    pub name: String,
+    /// Defining section and offset in it; `None` for an undefined symbol
+    pub section: Option<usize>,
+    pub value: u64,
+    pub binding: Binding,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Assemble `asm` into sections, symbols, and relocations. Errors name the
+/// offending line.
+pub(crate) fn assemble(asm: &str) -> Result<Object, String> {
+    let mut asm_state = Assembler::default();
+    for (number, line) in asm.lines().enumerate() {
+        asm_state.line(line).map_err(|e| format!("line {}: {}: '{}'", number + 1, e, line.trim()))?;
+    }
+    asm_state.finish()
+}
+
+enum Fragment {
+    Bytes(Vec<u8>, Vec<Fixup>),
+    /// A jump or call to a label; `near` once it needs the rel32 form
+    Branch { kind: Branch, target: String, near: bool },
+    Align(u64),
+}
+
+struct PendingSection {
+    name: String,
+    kind: SectionKind,
+    align: u64,
+    fragments: Vec<Fragment>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    sections: Vec<PendingSection>,
+    current: Option<usize>,
+    /// Labels: section, fragment, and offset in the fragment
+    labels: HashMap<String, (usize, usize, usize)>,
+    /// Label names in definition order, which is symbol table order
+    order: Vec<String>,
+    bindings: HashMap<String, Binding>,
+    /// `.set name, target+addend`, in order
+    aliases: Vec<(String, String, i64)>,
+}
+
+impl Assembler {
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let mut text = strip_comment(line).trim();
+        // Any number of labels may start a line
+        while let Some((label, rest)) = split_label(text) {
+            self.define_label(label)?;
+            text = rest.trim_start();
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+        if text.starts_with('.') {
+            return self.directive(text);
+        }
+        self.instruction(text)
+    }
+
+    fn define_label(&mut self, name: &str) -> Result<(), String> {
+        let section = self.section()?;
+        let fragments = &mut self.sections[section].fragments;
+        let position = match fragments.last() {
+            Some(Fragment::Bytes(bytes, _)) => (section, fragments.len() - 1, bytes.len()),
+            _ => {
+                fragments.push(Fragment::Bytes(Vec::new(), Vec::new()));
+                (section, fragments.len() - 1, 0)
+            }
+        };
+        if self.labels.insert(name.to_string(), position).is_some() {
+            return Err(format!("symbol '{}' is already defined", name));
+        }
+        self.order.push(name.to_string());
+        Ok(())
+    }
+
+    /// The current section, opening `.text` if no directive chose one.
+    fn section(&mut self) -> Result<usize, String> {
+        match self.current {
+            Some(index) => Ok(index),
+            None => {
+                self.switch_section(".text", None);
+                Ok(self.current.unwrap())
+            }
+        }
+    }
+
+    fn switch_section(&mut self, name: &str, flags: Option<&str>) {
+        let index = match self.sections.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                let kind = section_kind(name, flags);
+                self.sections.push(PendingSection { name: name.to_string(), kind, align: 1, fragments: Vec::new() });
+                self.sections.len() - 1
+            }
+        };
+        self.current = Some(index);
+    }
+
+    fn emit(&mut self, bytes: &[u8], fixups: Vec<Fixup>) -> Result<(), String> {
+        let section = self.section()?;
+        let fragments = &mut self.sections[section].fragments;
+        if !matches!(fragments.last(), Some(Fragment::Bytes(..))) {
+            fragments.push(Fragment::Bytes(Vec::new(), Vec::new()));
+        }
+        let Some(Fragment::Bytes(data, existing)) = fragments.last_mut() else { unreachable!() };
+        let base = data.len();
+        existing.extend(fixups.into_iter().map(|fixup| Fixup { offset: fixup.offset + base, ..fixup }));
+        data.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn instruction(&mut self, text: &str) -> Result<(), String> {
+        if let Some((kind, target)) = encoder::branch(text) {
+            let section = self.section()?;
+            self.sections[section].fragments.push(Fragment::Branch { kind, target, near: kind.short().is_none() });
+            return Ok(());
+        }
+        let encoded = encoder::encode(text)?;
+        self.emit(&encoded.bytes, encoded.fixups)
+    }
+
+    fn directive(&mut self, text: &str) -> Result<(), String> {
+        let (name, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args = args.trim();
+        match name {
+            ".intel_syntax" => {}
+            ".att_syntax" => return Err("the built-in assembler reads Intel syntax only".to_string()),
+            ".type" | ".size" | ".file" | ".ident" => {}
+            _ if name.starts_with(".cfi_") => {}
+            ".text" | ".data" | ".bss" => self.switch_section(name, None),
+            ".section" => {
+                let parts = split_operands(args);
+                let section = parts.first().copied().filter(|s| !s.is_empty()).ok_or("missing section name")?;
+                let flags = parts.get(1).map(|flags| flags.trim_matches('"'));
+                self.switch_section(section, flags);
+            }
+            ".globl" | ".global" | ".weak" => {
+                let binding = if name == ".weak" { Binding::Weak } else { Binding::Global };
+                for symbol in split_operands(args) {
+                    self.bindings.insert(symbol.to_string(), binding);
+                }
+            }
+            ".local" => {
+                for symbol in split_operands(args) {
+                    self.bindings.insert(symbol.to_string(), Binding::Local);
+                }
+            }
+            ".align" | ".balign" | ".p2align" => {
+                let amount = split_operands(args).first().and_then(|a| parse_int(a)).ok_or("expected an alignment")?;
+                let align = if name == ".p2align" { 1u64.checked_shl(amount as u32) } else { u64::try_from(amount).ok() };
+                let align = align.filter(|a| a.is_power_of_two()).ok_or("alignment must be a power of two")?;
+                let section = self.section()?;
+                let section = &mut self.sections[section];
+                section.align = section.align.max(align);
+                section.fragments.push(Fragment::Align(align));
+            }
+            ".byte" | ".short" | ".value" | ".word" | ".2byte" | ".long" | ".int" | ".4byte" | ".quad" | ".8byte" => {
+                let size = match name {
+                    ".byte" => 1,
+                    ".short" | ".value" | ".word" | ".2byte" => 2,
+                    ".long" | ".int" | ".4byte" => 4,
+                    _ => 8,
+                };
+                for value in split_operands(args) {
+                    self.data_value(value, size)?;
+                }
+            }
+            ".zero" | ".skip" | ".space" => {
+                let parts = split_operands(args);
+                let count = parts.first().and_then(|c| parse_int(c)).ok_or("expected a byte count")?;
+                let fill = match parts.get(1) {
+                    Some(fill) => parse_int(fill).ok_or("expected a fill byte")? as u8,
+                    None => 0,
+                };
+                let count = usize::try_from(count).map_err(|_| "negative byte count".to_string())?;
+                self.emit(&vec![fill; count], Vec::new())?;
+            }
+            ".ascii" | ".asciz" | ".string" => {
+                for literal in split_strings(args)? {
+                    let mut bytes = unescape(literal)?;
+                    if name != ".ascii" {
+                        bytes.push(0);
+                    }
+                    self.emit(&bytes, Vec::new())?;
+                }
+            }
+            ".set" | ".equ" => {
+                let parts = split_operands(args);
+                let [alias, target] = parts[..] else { return Err("expected 'name, value'".to_string()) };
+                let (target, addend) = encoder::parse_symbol_expr(target).ok_or("expected 'symbol+offset'")?;
+                self.aliases.push((alias.to_string(), target, addend));
+            }
+            _ => return Err(format!("unsupported directive '{}'", name)),
+        }
+        Ok(())
+    }
+
+    /// One value of a `.byte` to `.quad` list: an integer, a symbol
+    /// address in a `.long` or `.quad`, or in a `.long` the distance of a
+    /// symbol from a label, as jump table entries are.
+    fn data_value(&mut self, text: &str, size: usize) -> Result<(), String> {
+        if let Some(value) = parse_int(text) {
+            let fits = size == 8 || (-(1i64 << (size * 8 - 1))..1i64 << (size * 8)).contains(&value);
+            if !fits {
+                return Err(format!("{} does not fit in {} bytes", value, size));
+            }
+            return self.emit(&value.to_le_bytes()[..size], Vec::new());
+        }
+        if let Some((symbol, base)) = text.split_once('-')
+            && let base = base.trim()
+            && base.starts_with(encoder::is_symbol_start)
+        {
+            // `symbol - field` plus the field's distance from `base`, which
+            // must lie before it in the same run of bytes
+            let section = self.section()?;
+            let fragments = &self.sections[section].fragments;
+            let field = match fragments.last() {
+                Some(Fragment::Bytes(bytes, _)) if size == 4 => bytes.len(),
+                _ => return Err(format!("cannot place the difference '{}' here", text)),
+            };
+            let addend = match self.labels.get(base) {
+                Some(&(in_section, fragment, offset)) if in_section == section && fragment == fragments.len() - 1 => {
+                    (field - offset) as i64
+                }
+                _ => return Err(format!("'{}' must be a label earlier in the same section", base)),
+            };
+            let fixup = Fixup { offset: 0, kind: FixupKind::Pc32, symbol: symbol.trim().to_string(), addend };
+            return self.emit(&[0; 4], vec![fixup]);
+        }
+        let (symbol, addend) = encoder::parse_symbol_expr(text).ok_or_else(|| format!("cannot parse value '{}'", text))?;
+        let kind = match size {
+            8 => FixupKind::Abs64,
+            4 => FixupKind::Abs32,
+            _ => return Err(format!("a {}-byte field cannot hold an address", size)),
+        };
+        self.emit(&vec![0; size], vec![Fixup { offset: 0, kind, symbol, addend }])
+    }
+
+    fn finish(mut self) -> Result<Object, String> {
+        if self.sections.is_empty() {
+            self.switch_section(".text", None);
+        }
+        self.relax();
+        let mut object = Object::default();
+        // Fragment start offsets, for placing labels
+        let mut starts = Vec::new();
+        for section in &self.sections {
+            let offsets = fragment_offsets(&section.fragments);
+            starts.push(offsets);
+        }
+        let address = |(section, fragment, offset): (usize, usize, usize)| (section, starts[section][fragment] + offset as u64);
+
+        // Symbol table: named labels in order, then aliases, then undefined
+        // symbols as relocations reach them
+        let mut defined: HashMap<String, (usize, u64)> = HashMap::new();
+        for name in &self.order {
+            defined.insert(name.clone(), address(self.labels[name]));
+        }
+        for (alias, target, addend) in &self.aliases {
+            let &(section, value) = defined.get(target).ok_or_else(|| format!("'.set {}' names undefined symbol '{}'", alias, target))?;
+            if defined.insert(alias.clone(), (section, value.wrapping_add(*addend as u64))).is_some() {
+                return Err(format!("symbol '{}' is already defined", alias));
+            }
+        }
+        let mut symbol_index: HashMap<String, usize> = HashMap::new();
+        let names = self.order.iter().chain(self.aliases.iter().map(|(alias, _, _)| alias));
+        for name in names {
+            if name.starts_with(".L") {
+                continue;
+            }
+            let (section, value) = defined[name];
+            symbol_index.insert(name.clone(), object.symbols.len());
+            let binding = self.bindings.get(name).copied().unwrap_or(Binding::Local);
+            object.symbols.push(Symbol { name: name.clone(), section: Some(section), value, binding });
+        }
+
+        for (index, pending) in self.sections.iter().enumerate() {
+            let mut section = Section {
+                name: pending.name.clone(),
+                kind: pending.kind,
+                align: pending.align,
+                data: Vec::new(),
+                relocs: Vec::new(),
+            };
+            let mut fixups: Vec<(usize, Fixup)> = Vec::new();
+            for fragment in &pending.fragments {
+                match fragment {
+                    Fragment::Bytes(bytes, list) => {
+                        let base = section.data.len();
+                        fixups.extend(list.iter().map(|f| (base + f.offset, f.clone())));
+                        section.data.extend_from_slice(bytes);
+                    }
+                    Fragment::Align(align) => {
+                        let padding = padding(section.data.len() as u64, *align) as usize;
+                        let fill = if section.kind == SectionKind::Code { 0x90 } else { 0 };
+                        section.data.extend(std::iter::repeat_n(fill, padding));
+                    }
+                    Fragment::Branch { kind, target, near } => {
+                        let bytes = if *near { kind.near() } else { kind.short().unwrap() };
+                        let field = section.data.len() + bytes.len() - if *near { 4 } else { 1 };
+                        section.data.extend_from_slice(&bytes);
+                        if !*near {
+                            // Relaxation only leaves the short form for a label here
+                            let (_, to) = defined[target];
+                            section.data[field] = (to as i64 - section.data.len() as i64) as i8 as u8;
+                            continue;
+                        }
+                        let fixup = Fixup { offset: 0, kind: FixupKind::Pc32, symbol: target.clone(), addend: -4 };
+                        fixups.push((field, fixup));
+                    }
+                }
+            }
+
+            for (offset, fixup) in fixups {
+                let binding = self.bindings.get(&fixup.symbol).copied().unwrap_or(Binding::Local);
+                let target = match defined.get(&fixup.symbol) {
+                    // COFF has no symbol preemption: only a weak definition
+                    // can be replaced at link time
+                    Some(&(in_section, value)) if binding != Binding::Weak => {
+                        if in_section == index && fixup.kind == FixupKind::Pc32 {
+                            let value = value as i64 + fixup.addend - offset as i64;
+                            let value = i32::try_from(value).map_err(|_| format!("'{}' is out of rel32 range", fixup.symbol))?;
+                            section.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+                            continue;
+                        }
+                        match symbol_index.get(&fixup.symbol) {
+                            Some(&symbol) => (RelocTarget::Symbol(symbol), fixup.addend),
+                            None => (RelocTarget::Section(in_section), fixup.addend + value as i64),
+                        }
+                    }
+                    _ => {
+                        let symbol = *symbol_index.entry(fixup.symbol.clone()).or_insert_with(|| {
+                            object.symbols.push(Symbol { name: fixup.symbol.clone(), section: None, value: 0, binding: Binding::Global });
+                            object.symbols.len() - 1
+                        });
+                        (RelocTarget::Symbol(symbol), fixup.addend)
+                    }
+                };
+                section.relocs.push(Reloc { offset, kind: fixup.kind, target: target.0, addend: target.1 });
+            }
+
+            if section.kind == SectionKind::Bss && (section.data.iter().any(|&b| b != 0) || !section.relocs.is_empty()) {
+                return Err(format!("section '{}' holds only zeros, but has initialized data", section.name));
+            }
+            object.sections.push(section);
+        }
+
+        // Declared but never defined or referenced
+        let mut declared: Vec<_> = self.bindings.iter().filter(|(name, binding)| {
+            **binding != Binding::Local && !symbol_index.contains_key(*name)
+        }).collect();
+        declared.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, binding) in declared {
+            object.symbols.push(Symbol { name: name.clone(), section: None, value: 0, binding: *binding });
+        }
+        Ok(object)
+    }
+
+    /// Grow short jumps whose displacement does not fit in a byte, until
+    /// none does. A jump only grows, so this ends.
+    fn relax(&mut self) {
+        // A jump is short only if it targets a non-weak label in its own section
+        for (index, section) in self.sections.iter_mut().enumerate() {
+            for fragment in &mut section.fragments {
+                if let Fragment::Branch { target, near, .. } = fragment {
+                    let local = self.labels.get(target).is_some_and(|l| l.0 == index)
+                        && self.bindings.get(target) != Some(&Binding::Weak);
+                    *near |= !local;
+                }
+            }
+        }
+        loop {
+            let mut changed = false;
+            for index in 0..self.sections.len() {
+                let starts = fragment_offsets(&self.sections[index].fragments);
+                for (i, fragment) in self.sections[index].fragments.iter_mut().enumerate() {
+                    let Fragment::Branch { target, near: near @ false, .. } = fragment else { continue };
+                    let (_, to_fragment, to_offset) = self.labels[target.as_str()];
+                    let displacement = (starts[to_fragment] + to_offset as u64) as i64 - (starts[i] + 2) as i64;
+                    if !(-128..=127).contains(&displacement) {
+                        *near = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+/// Offset of each fragment, with its current branch forms.
+fn fragment_offsets(fragments: &[Fragment]) -> Vec<u64> {
+    let mut offset = 0u64;
+    let mut starts = Vec::with_capacity(fragments.len());
+    for fragment in fragments {
+        starts.push(offset);
+        offset += match fragment {
+            Fragment::Bytes(bytes, _) => bytes.len() as u64,
+            Fragment::Align(align) => padding(offset, *align),
+            Fragment::Branch { kind, near: true, .. } => kind.near().len() as u64,
+            Fragment::Branch { kind, near: false, .. } => kind.short().unwrap().len() as u64,
+        };
+    }
+    starts
+}
+
+fn padding(offset: u64, align: u64) -> u64 {
+    offset.next_multiple_of(align) - offset
+}
+
+/// The kind of section `name` is, from `.section` flags if it had any:
+/// GNU as's ELF letters (`"ax"`, `"aw"`) and PE letters (`"dr"`, `"b"`)
+/// both read the same way.
+fn section_kind(name: &str, flags: Option<&str>) -> SectionKind {
+    match flags {
+        Some(flags) if flags.contains('x') => SectionKind::Code,
+        Some(flags) if flags.contains('b') => SectionKind::Bss,
+        Some(flags) if flags.contains('w') => SectionKind::Data,
+        Some(flags) if flags.contains('r') || flags.contains('a') || flags.is_empty() => SectionKind::ReadOnly,
+        _ if name.starts_with(".text") => SectionKind::Code,
+        _ if name.starts_with(".bss") => SectionKind::Bss,
+        _ if name.starts_with(".rdata") || name.starts_with(".rodata") => SectionKind::ReadOnly,
+        _ => SectionKind::Data,
+    }
+}
+
+/// `text` without a `#` comment outside string literals.
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// `label:` at the start of `text`, and what follows it.
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let end = text.find(|c: char| !encoder::is_symbol_char(c))?;
+    if !text.starts_with(encoder::is_symbol_start) || !text[end..].starts_with(':') {
+        return None;
+    }
+    Some((&text[..end], &text[end + 1..]))
+}
+
+/// The string literals of a `.ascii` list, still escaped.
+fn split_strings(text: &str) -> Result<Vec<&str>, String> {
+    let mut strings = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let body = rest.strip_prefix('"').ok_or("expected a string literal")?;
+        let mut escaped = false;
+        let end = body
+            .char_indices()
+            .find(|&(_, c)| {
+                let close = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                close
+            })
+            .map(|(i, _)| i)
+            .ok_or("unterminated string literal")?;
+        strings.push(&body[..end]);
+        rest = body[end + 1..].trim_start();
+        if let Some(next) = rest.strip_prefix(',') {
+            rest = next.trim_start();
+        } else if !rest.is_empty() {
+            return Err("expected ',' between string literals".to_string());
+        }
+    }
+    Ok(strings)
+}
+
+/// The bytes of an escaped string literal body.
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len());
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let c = *bytes.get(i + 1).ok_or("string ends in a backslash")?;
+        i += 2;
+        match c {
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'b' => out.push(8),
+            b'f' => out.push(12),
+            b'0'..=b'7' => {
+                let mut value = u32::from(c - b'0');
+                for _ in 0..2 {
+                    match bytes.get(i) {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(d - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                out.push(value as u8);
+            }
+            b'x' => {
+                let digits = bytes[i..].iter().take_while(|b| b.is_ascii_hexdigit()).count();
+                if digits == 0 {
+                    return Err("'\\x' without hex digits".to_string());
+                }
+                let hex = std::str::from_utf8(&bytes[i..i + digits]).unwrap();
+                out.push(u32::from_str_radix(hex, 16).map_err(|e| e.to_string())? as u8);
+                i += digits;
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section<'a>(object: &'a Object, name: &str) -> &'a Section {
+        object.sections.iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn jumps_relax_to_rel32_only_when_needed() {
+        let far = "  nop\n".repeat(130);
+        let asm = format!(".text\nf:\n  jmp .Lnear\n.Lnear:\n  jne .Lfar\n{}.Lfar:\n  ret\n", far);
+        let object = assemble(&asm).unwrap();
+        let text = section(&object, ".text");
+        assert_eq!(&text.data[..2], &[0xEB, 0x00]);
+        assert_eq!(&text.data[2..8], &[0x0F, 0x85, 130, 0, 0, 0]);
+        assert_eq!(text.data.len(), 2 + 6 + 130 + 1);
+        assert!(text.relocs.is_empty());
+    }
+
+    #[test]
+    fn calls_and_cross_section_references_relocate() {
+        let asm = ".section .rdata,\"dr\"\nstr_0: .asciz \"hi\\n\"\n.text\n.globl main\nmain:\n  lea rcx, str_0[rip]\n  call printf\n  ret\n";
+        let object = assemble(asm).unwrap();
+        let rdata = section(&object, ".rdata");
+        assert_eq!(rdata.kind, SectionKind::ReadOnly);
+        assert_eq!(rdata.data, b"hi\n\0");
+        let text = section(&object, ".text");
+        let names: Vec<&str> = object.symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["str_0", "main", "printf"]);
+        assert_eq!(object.symbols[1].binding, Binding::Global);
+        assert_eq!(object.symbols[2].section, None);
+        assert_eq!(
+            text.relocs,
+            [
+                Reloc { offset: 3, kind: FixupKind::Pc32, target: RelocTarget::Symbol(0), addend: -4 },
+                Reloc { offset: 8, kind: FixupKind::Pc32, target: RelocTarget::Symbol(2), addend: -4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn data_directives_and_aliases() {
+        let asm = ".data\n.align 8\nlong_str: .asciz \"abc\"\n.set tail, long_str + 1\n.quad tail\n.long 0xffffffff\n.short -2\n.zero 3\n.bss\nbuf:\n    .zero 16\n";
+        let object = assemble(asm).unwrap();
+        let data = section(&object, ".data");
+        assert_eq!(data.align, 8);
+        assert_eq!(&data.data[..4], b"abc\0");
+        assert_eq!(&data.data[12..], &[0xFF, 0xFF, 0xFF, 0xFF, 0xFE, 0xFF, 0, 0, 0]);
+        assert_eq!(data.relocs, [Reloc { offset: 4, kind: FixupKind::Abs64, target: RelocTarget::Symbol(2), addend: 0 }]);
+        assert_eq!(object.symbols[2], Symbol { name: "tail".to_string(), section: Some(0), value: 1, binding: Binding::Local });
+        assert_eq!(section(&object, ".bss").data.len(), 16);
+    }
+
+    #[test]
+    fn local_labels_stay_out_of_the_symbol_table() {
+        let asm = ".section .rdata,\"dr\"\n.LC0: .quad 0\n.text\nf:\n  movsd xmm0, QWORD PTR .LC0[rip]\n";
+        let object = assemble(asm).unwrap();
+        assert!(object.symbols.iter().all(|s| s.name != ".LC0"));
+        let reloc = &section(&object, ".text").relocs[0];
+        assert_eq!(reloc.target, RelocTarget::Section(0));
+    }
+
+    #[test]
+    fn label_differences_make_jump_table_entries() {
+        let asm = ".text\nf:\n  nop\nf_1:\n  ret\n.section .rdata,\"dr\"\n  .p2align 2\n.Lf_switch_0:\n  .long f_1-.Lf_switch_0\n  .long f-.Lf_switch_0\n";
+        let object = assemble(asm).unwrap();
+        let rdata = section(&object, ".rdata");
+        assert_eq!(rdata.data, [0; 8]);
+        assert_eq!(
+            rdata.relocs,
+            [
+                Reloc { offset: 0, kind: FixupKind::Pc32, target: RelocTarget::Symbol(1), addend: 0 },
+                Reloc { offset: 4, kind: FixupKind::Pc32, target: RelocTarget::Symbol(0), addend: 4 },
+            ]
+        );
+        assert!(assemble(".data\n.long f-g\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_directives_and_initialized_bss() {
+        assert!(assemble(".weird 1\n").unwrap_err().contains("line 1"));
+        assert!(assemble(".bss\n.long 1\n").is_err());
+    }
+}
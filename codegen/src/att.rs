@@ -0,0 +1,452 @@
+// AT&T syntax output
+//
+// Codegen builds, peephole-optimizes, and prints Intel-syntax instructions,
+// and `X86Instr::Raw` carries Intel text of its own (atomics, indexed `lea`,
+// inline asm templates). For `--asm-syntax=att`, `to_att` rewrites what
+// `emit_asm` printed one line at a time:
+//
+// - Operands are reversed; registers take `%` and immediates `$`.
+// - `QWORD PTR [base + index*scale - disp]` becomes `-disp(%base,%index,scale)`
+//   and `label[rip]` becomes `label(%rip)`. The size keyword becomes a
+//   mnemonic suffix when no register operand implies the size.
+// - Indirect jumps and calls take `*`, and the mnemonics whose AT&T names
+//   differ are renamed: `movzx`/`movsx`/`movsxd` name both sizes (`movzbl`,
+//   `movslq`), and `cqo`, `cdq`, `cdqe` are `cqto`, `cltd`, `cltq`.
+// - Labels and directives pass through, except that `.cfi_*` directives
+//   take `%` on the registers they name.
+//
+// A line that does not parse as an instruction is left as it is.
+
+/// Rewrite Intel-syntax assembly, as `emit_asm` prints it, in AT&T syntax.
+pub fn to_att(intel: &str) -> String {
+    let mut out = String::with_capacity(intel.len() + intel.len() / 4);
+    for line in intel.lines() {
+        out.push_str(&line_to_att(line));
+        out.push('\n');
+    }
+    out
+}
+
+fn line_to_att(line: &str) -> String {
+    let text = line.trim_start();
+    let indent = &line[..line.len() - text.len()];
+    if text.is_empty() || text.ends_with(':') {
+        return line.to_string();
+    }
+    if text.starts_with(".cfi_") {
+        let words: Vec<String> = text
+            .split(' ')
+            .map(|word| {
+                let name = word.trim_end_matches(',');
+                if register_size(name).is_some() { format!("%{}", word) } else { word.to_string() }
+            })
+            .collect();
+        return format!("{}{}", indent, words.join(" "));
+    }
+    if text.starts_with('.') {
+        return line.to_string();
+    }
+    match instruction_to_att(text) {
+        Some(att) => format!("{}{}", indent, att),
+        None => line.to_string(),
+    }
+}
+
+enum Operand {
+    /// A register and its size in bytes
+    Reg(String, u8),
+    Imm(String),
+    /// A memory reference in AT&T form, and its size keyword if it had one
+    Mem(String, Option<u8>),
+    /// A bare label: a jump or call target
+    Symbol(String),
+}
+
+pub(crate) const PREFIXES: [&str; 6] = ["lock", "rep", "repe", "repz", "repne", "repnz"];
+
+fn instruction_to_att(text: &str) -> Option<String> {
+    let mut prefixes = Vec::new();
+    let mut rest = text;
+    loop {
+        let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if PREFIXES.contains(&word) && !tail.trim().is_empty() {
+            prefixes.push(word);
+            rest = tail.trim_start();
+        } else {
+            break;
+        }
+    }
+    let (mnemonic, operand_text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let operands = split_operands(operand_text).into_iter().map(parse_operand).collect::<Option<Vec<_>>>()?;
+
+    let has_reg = operands.iter().any(|op| matches!(op, Operand::Reg(..)));
+    let mem_size = operands.iter().find_map(|op| match op {
+        Operand::Mem(_, size) => *size,
+        _ => None,
+    });
+    let size_of = |op: &Operand| match op {
+        Operand::Reg(_, size) => Some(*size),
+        Operand::Mem(_, size) => *size,
+        _ => None,
+    };
+    let is_branch = mnemonic == "call" || mnemonic.starts_with('j');
+    let mnemonic = match mnemonic {
+        "cqo" => "cqto".to_string(),
+        "cdq" => "cltd".to_string(),
+        "cdqe" => "cltq".to_string(),
+        "movsx" | "movsxd" | "movzx" => {
+            let [dest, src] = operands.as_slice() else { return None };
+            let kind = if mnemonic == "movzx" { "movz" } else { "movs" };
+            let (from, to) = (suffix(size_of(src)?)?, suffix(size_of(dest)?)?);
+            // `movsxd eax, DWORD PTR [...]` is a plain 32-bit load
+            if from == to {
+                "mov".to_string()
+            } else {
+                format!("{}{}{}", kind, from, to)
+            }
+        }
+        // The integer source's width is ambiguous in memory
+        "cvtsi2ss" | "cvtsi2sd" if operands.iter().any(|op| matches!(op, Operand::Mem(..))) => {
+            format!("{}{}", mnemonic, suffix(mem_size?)?)
+        }
+        _ if !has_reg && !is_branch && !mnemonic.starts_with("set") => match mem_size.and_then(suffix) {
+            Some(s) => format!("{}{}", mnemonic, s),
+            None => mnemonic.to_string(),
+        },
+        _ => mnemonic.to_string(),
+    };
+
+    let rendered: Vec<String> = operands
+        .iter()
+        .rev()
+        .map(|op| match op {
+            Operand::Reg(name, _) if is_branch => format!("*%{}", name),
+            Operand::Mem(text, _) if is_branch => format!("*{}", text),
+            Operand::Reg(name, _) => format!("%{}", name),
+            Operand::Imm(value) => format!("${}", value),
+            Operand::Mem(text, _) | Operand::Symbol(text) => text.clone(),
+        })
+        .collect();
+
+    let mut att = String::new();
+    for prefix in prefixes {
+        att.push_str(prefix);
+        att.push(' ');
+    }
+    att.push_str(&mnemonic);
+    if !rendered.is_empty() {
+        att.push(' ');
+        att.push_str(&rendered.join(", "));
+    }
+    Some(att)
+}
+
+/// The AT&T mnemonic suffix for an operand size.
+fn suffix(size: u8) -> Option<char> {
+    match size {
+        1 => Some('b'),
+        2 => Some('w'),
+        4 => Some('l'),
+        8 => Some('q'),
+        _ => None,
+    }
+}
+
+/// Split an operand list on the commas outside brackets.
+pub(crate) fn split_operands(text: &str) -> Vec<&str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    operands.push(text[start..].trim());
+    operands
+}
+
+const SIZE_KEYWORDS: [(&str, u8); 6] =
+    [("byte", 1), ("word", 2), ("dword", 4), ("qword", 8), ("xmmword", 16), ("ymmword", 32)];
+
+/// Split off a leading `QWORD PTR` (or bare `qword`) and its size in bytes.
+pub(crate) fn strip_size_keyword(text: &str) -> (Option<u8>, &str) {
+    let Some((word, tail)) = text.split_once(char::is_whitespace) else {
+        return (None, text);
+    };
+    let Some((_, bytes)) = SIZE_KEYWORDS.iter().find(|(kw, _)| word.eq_ignore_ascii_case(kw)) else {
+        return (None, text);
+    };
+    let tail = tail.trim_start();
+    match tail.split_once(char::is_whitespace) {
+        Some((ptr, rest)) if ptr.eq_ignore_ascii_case("ptr") => (Some(*bytes), rest.trim_start()),
+        _ => (Some(*bytes), tail),
+    }
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+    let (size, rest) = strip_size_keyword(text);
+    if let Some(open) = rest.find('[') {
+        let close = rest.rfind(']')?;
+        return Some(Operand::Mem(memory_to_att(&rest[..open], &rest[open + 1..close])?, size));
+    }
+    if let Some(bytes) = register_size(rest) {
+        return Some(Operand::Reg(rest.to_string(), bytes));
+    }
+    if parse_int(rest).is_some() {
+        return Some(Operand::Imm(rest.to_string()));
+    }
+    if let Some(symbol) = rest.strip_prefix("OFFSET ").or_else(|| rest.strip_prefix("offset ")) {
+        return Some(Operand::Imm(symbol.trim().to_string()));
+    }
+    if rest.is_empty() || rest.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(Operand::Symbol(rest.to_string()))
+}
+
+/// `symbol[base + index*scale + disp]` as `symbol+disp(%base,%index,scale)`.
+fn memory_to_att(symbol: &str, inner: &str) -> Option<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    if !symbol.trim().is_empty() {
+        symbols.push(symbol.trim().to_string());
+    }
+    let mut disp: i64 = 0;
+    let mut base: Option<&str> = None;
+    let mut index: Option<(&str, &str)> = None;
+
+    let inner = inner.trim();
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        if (c == '+' || c == '-') && i > start {
+            terms.push(&inner[start..i]);
+            start = i;
+        }
+    }
+    terms.push(&inner[start..]);
+
+    for term in terms {
+        let (negative, term) = match term.trim() {
+            t if t.starts_with('-') => (true, t[1..].trim()),
+            t if t.starts_with('+') => (false, t[1..].trim()),
+            t => (false, t),
+        };
+        if let Some((reg, scale)) = term.split_once('*') {
+            let (reg, scale) = if register_size(reg.trim()).is_some() { (reg, scale) } else { (scale, reg) };
+            if negative || register_size(reg.trim()).is_none() || index.is_some() {
+                return None;
+            }
+            index = Some((reg.trim(), scale.trim()));
+        } else if register_size(term).is_some() && !negative {
+            if base.is_none() {
+                base = Some(term);
+            } else if index.is_none() {
+                index = Some((term, "1"));
+            } else {
+                return None;
+            }
+        } else if let Some(value) = parse_int(term) {
+            disp += if negative { -value } else { value };
+        } else if !term.is_empty() && !negative {
+            symbols.push(term.to_string());
+        } else {
+            return None;
+        }
+    }
+
+    let mut att = symbols.join("+");
+    if disp != 0 {
+        if att.is_empty() {
+            att = disp.to_string();
+        } else {
+            att.push_str(&format!("{:+}", disp));
+        }
+    }
+    match (base, index) {
+        (Some(base), Some((index, scale))) => att.push_str(&format!("(%{},%{},{})", base, index, scale)),
+        (Some(base), None) => att.push_str(&format!("(%{})", base)),
+        (None, Some((index, scale))) => att.push_str(&format!("(,%{},{})", index, scale)),
+        (None, None) if att.is_empty() => att.push('0'),
+        (None, None) => {}
+    }
+    Some(att)
+}
+
+pub(crate) fn parse_int(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => digits.parse::<u64>().ok()? as i64,
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+/// The size in bytes of a register named in Intel syntax, or `None` if
+/// `name` is not a register.
+fn register_size(name: &str) -> Option<u8> {
+    const LEGACY: [[&str; 4]; 8] = [
+        ["rax", "eax", "ax", "al"],
+        ["rcx", "ecx", "cx", "cl"],
+        ["rdx", "edx", "dx", "dl"],
+        ["rbx", "ebx", "bx", "bl"],
+        ["rsp", "esp", "sp", "spl"],
+        ["rbp", "ebp", "bp", "bpl"],
+        ["rsi", "esi", "si", "sil"],
+        ["rdi", "edi", "di", "dil"],
+    ];
+    for names in LEGACY {
+        if let Some(i) = names.iter().position(|n| *n == name) {
+            return Some([8, 4, 2, 1][i]);
+        }
+    }
+    match name {
+        "rip" => return Some(8),
+        "ah" | "bh" | "ch" | "dh" => return Some(1),
+        _ => {}
+    }
+    let numbered = |prefix: &str| -> Option<&str> {
+        let digits = name.strip_prefix(prefix)?;
+        let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        let n: u8 = digits[..end].parse().ok()?;
+        (n < 16).then_some(&digits[end..])
+    };
+    if let Some("") = numbered("xmm") {
+        return Some(16);
+    }
+    if let Some("") = numbered("ymm") {
+        return Some(32);
+    }
+    let width = numbered("r")?;
+    if name[1..].len() - width.len() == 1 && !name[1..].starts_with(['8', '9']) {
+        return None;
+    }
+    match width {
+        "" => Some(8),
+        "d" => Some(4),
+        "w" => Some(2),
+        "b" => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x86::{emit_asm, X86Instr, X86Operand, X86Reg};
+
+    fn att(instrs: &[X86Instr]) -> String {
+        to_att(&emit_asm(instrs))
+    }
+
+    #[test]
+    fn operands_reverse_and_take_prefixes() {
+        let instrs = [
+            X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Imm(42)),
+            X86Instr::Add(X86Operand::Reg(X86Reg::R8d), X86Operand::Reg(X86Reg::Ecx)),
+            X86Instr::Shl(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Cl)),
+            X86Instr::Pshufd(X86Operand::Reg(X86Reg::Xmm0), X86Operand::Reg(X86Reg::Xmm1), 27),
+        ];
+        assert_eq!(
+            att(&instrs),
+            "  mov $42, %rax\n  add %ecx, %r8d\n  shl %cl, %rax\n  pshufd $27, %xmm1, %xmm0\n"
+        );
+    }
+
+    #[test]
+    fn memory_operands() {
+        let instrs = [
+            X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Rbp, -8)),
+            X86Instr::Movss(X86Operand::FloatMem(X86Reg::Rsp, 0), X86Operand::Reg(X86Reg::Xmm0)),
+            X86Instr::Lea(X86Operand::Reg(X86Reg::Rdi), X86Operand::RipRelLabel("str_0".to_string())),
+            X86Instr::Raw("lea rax, [rbp + rcx*4-16]".to_string()),
+            X86Instr::Raw("mov ecx, [r10 + rax*4]".to_string()),
+        ];
+        assert_eq!(
+            att(&instrs),
+            "  mov -8(%rbp), %rax\n  movss %xmm0, (%rsp)\n  lea str_0(%rip), %rdi\n  \
+             lea -16(%rbp,%rcx,4), %rax\n  mov (%r10,%rax,4), %ecx\n"
+        );
+    }
+
+    #[test]
+    fn size_keywords_become_suffixes_without_a_register() {
+        let instrs = [
+            X86Instr::Mov(X86Operand::DwordMem(X86Reg::Rbp, -4), X86Operand::Imm(7)),
+            X86Instr::Cmp(X86Operand::ByteMem(X86Reg::Rax, 0), X86Operand::Imm(0)),
+            X86Instr::Idiv(X86Operand::Mem(X86Reg::Rbp, -16)),
+            X86Instr::Set("e".to_string(), X86Operand::ByteMem(X86Reg::Rbp, -1)),
+            X86Instr::Raw("inc qword ptr counter_0[rip]".to_string()),
+            X86Instr::Raw("mov qword [rdx], 0".to_string()),
+        ];
+        assert_eq!(
+            att(&instrs),
+            "  movl $7, -4(%rbp)\n  cmpb $0, (%rax)\n  idivq -16(%rbp)\n  sete -1(%rbp)\n  \
+             incq counter_0(%rip)\n  movq $0, (%rdx)\n"
+        );
+    }
+
+    #[test]
+    fn renamed_mnemonics() {
+        let instrs = [
+            X86Instr::Movzx(X86Operand::Reg(X86Reg::Eax), X86Operand::Reg(X86Reg::Al)),
+            X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::DwordMem(X86Reg::Rbp, -4)),
+            X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::WordMem(X86Reg::Rbp, -2)),
+            X86Instr::Cvtsi2sd(X86Operand::Reg(X86Reg::Xmm0), X86Operand::DwordMem(X86Reg::Rbp, -4)),
+            X86Instr::Cqto,
+            X86Instr::Cdq,
+        ];
+        assert_eq!(
+            att(&instrs),
+            "  movzbl %al, %eax\n  movslq -4(%rbp), %rax\n  movswq -2(%rbp), %rax\n  \
+             cvtsi2sdl -4(%rbp), %xmm0\n  cqto\n  cltd\n"
+        );
+    }
+
+    #[test]
+    fn branches_and_prefixes() {
+        let instrs = [
+            X86Instr::Call("printf@PLT".to_string()),
+            X86Instr::CallIndirect(X86Operand::Reg(X86Reg::R10)),
+            X86Instr::Raw("    jmp rax".to_string()),
+            X86Instr::Jcc("ne".to_string(), ".Lsync_cas_3".to_string()),
+            X86Instr::Raw("lock cmpxchg [rdx], rcx".to_string()),
+            X86Instr::Raw("rep movsb".to_string()),
+        ];
+        assert_eq!(
+            att(&instrs),
+            "  call printf@PLT\n  call *%r10\n      jmp *%rax\n  jne .Lsync_cas_3\n  \
+             lock cmpxchg %rcx, (%rdx)\n  rep movsb\n"
+        );
+    }
+
+    #[test]
+    fn labels_and_directives_pass_through() {
+        let text = "main:\n.cfi_offset rbp, -16\n.cfi_def_cfa_register rbp\n  .p2align 4\n";
+        assert_eq!(to_att(text), "main:\n.cfi_offset %rbp, -16\n.cfi_def_cfa_register %rbp\n  .p2align 4\n");
+    }
+
+    #[test]
+    fn register_names() {
+        assert_eq!(register_size("r12d"), Some(4));
+        assert_eq!(register_size("r8b"), Some(1));
+        assert_eq!(register_size("sil"), Some(1));
+        assert_eq!(register_size("ymm15"), Some(32));
+        assert_eq!(register_size("r16"), None);
+        assert_eq!(register_size("r1"), None);
+        assert_eq!(register_size("str_0"), None);
+    }
+}
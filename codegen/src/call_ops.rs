@@ -1,144 +1,108 @@
+use crate::calling_convention::{AggregatePassing, ArgAllocator, ArgLocation, CallingConvention, EightbyteClass};
 use crate::function::FunctionGenerator;
+use crate::memory_ops::copy_memory;
 use crate::x86::{X86Instr, X86Operand, X86Reg};
 
 use model::Type;
 use ir::{VarId, Operand};
 
-// ─── SysV AMD64 struct classification ───────────────────────────
-
-/// How a struct argument should be passed per SysV AMD64 ABI.
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) enum StructArgClass {
-    /// Struct fits in 1 GP register (size ≤ 8 bytes)
-    OneReg,
-    /// Struct fits in 2 GP registers (8 < size ≤ 16 bytes)
-    TwoReg,
-    /// Struct must be passed in memory (size > 16 bytes)
-    Memory,
+// ─── Struct and union arguments ─────────────────────────────────
+//
+// A struct or union value is the address of an object holding it: a copy
+// the load or call producing it made, or the parameter's own. The calling
+// convention passes it by eightbytes in registers, copied into the stack
+// argument area, or as the address of a further copy.
+
+/// Whether values of `ty` are structs or unions, which the backend handles by address.
+pub(crate) fn is_aggregate(ty: &Type) -> bool {
+    matches!(ty, Type::Struct(_) | Type::Union(_))
 }
 
-/// Classify a struct for SysV AMD64 argument passing.
-/// Returns None if the type is not a struct/union, Some(class) otherwise.
-pub(crate) fn classify_struct_arg(generator: &FunctionGenerator, ty: &Type) -> Option<StructArgClass> {
-    let size = match ty {
-        Type::Struct(name) => {
-            if let Some(s_def) = generator.structs.get(name) {
-                let is_packed = s_def.attributes.iter()
-                    .any(|attr| matches!(attr, model::Attribute::Packed));
-                model::TypeLayout::new(generator.structs, generator.unions).struct_size(s_def, is_packed)
-            } else {
-                return None;
-            }
-        }
-        Type::Union(name) => {
-            if let Some(u_def) = generator.unions.get(name) {
-                u_def.fields.iter()
-                    .map(|f| model::TypeLayout::new(generator.structs, generator.unions).size_of(&f.field_type))
-                    .max()
-                    .unwrap_or(0)
-            } else {
-                return None;
-            }
-        }
-        _ => return None,
-    };
-    Some(if size <= 8 {
-        StructArgClass::OneReg
-    } else if size <= 16 {
-        StructArgClass::TwoReg
-    } else {
-        StructArgClass::Memory
-    })
+/// How the calling convention passes values of `ty`, if it is a struct or union.
+pub(crate) fn classify_aggregate(generator: &FunctionGenerator, ty: &Type) -> Option<AggregatePassing> {
+    is_aggregate(ty).then(|| generator.convention().classify_aggregate(&generator.type_layout(), ty))
 }
 
-/// Get the size of a struct/union type using the layout calculator.
-fn get_aggregate_size(generator: &FunctionGenerator, ty: &Type) -> usize {
-    model::TypeLayout::new(generator.structs, generator.unions).size_of(ty)
+/// Where one argument goes.
+pub(crate) enum ArgPassing {
+    /// A scalar, at its location
+    Scalar(ArgLocation),
+    /// A struct or union, each eightbyte at its location
+    Eightbytes(Vec<(EightbyteClass, ArgLocation)>),
+    /// A struct or union copied whole into the stack argument area, at this offset
+    Memory(i32),
+    /// The address of a copy of a struct or union, at its location
+    Reference(ArgLocation),
 }
 
-/// Pre-process call arguments for SysV AMD64 struct by-value passing.
-/// Small structs (≤16 bytes) are decomposed into 1-2 qword loads;
-/// large structs (>16 bytes) are passed by pointer (address).
-/// Returns a new flattened arg list and the emitted instructions to load struct eightbytes.
-fn flatten_struct_args(
-    generator: &mut FunctionGenerator,
-    args: &[Operand],
-) -> Vec<Operand> {
-    let mut flat_args = Vec::new();
+/// Take the location of the next argument: a struct or union of `size`
+/// bytes passed as `aggregate` says, or else a scalar.
+pub(crate) fn next_arg(args: &mut ArgAllocator, aggregate: Option<AggregatePassing>, size: usize, is_float: bool) -> ArgPassing {
+    match aggregate {
+        None => ArgPassing::Scalar(args.next(is_float)),
+        Some(AggregatePassing::Registers(classes)) => match args.next_eightbytes(&classes) {
+            Some(locations) => ArgPassing::Eightbytes(classes.into_iter().zip(locations).collect()),
+            None => ArgPassing::Memory(args.next_stack(size)),
+        },
+        Some(AggregatePassing::Memory) => ArgPassing::Memory(args.next_stack(size)),
+        Some(AggregatePassing::Reference) => ArgPassing::Reference(args.next(false)),
+    }
+}
 
-    for arg in args {
-        let arg_type = match arg {
-            Operand::Var(v) => generator.var_types.get(v).cloned(),
-            _ => None,
-        };
+/// The struct or union type of argument `arg`, if it has one.
+fn aggregate_type(generator: &FunctionGenerator, arg: &Operand) -> Option<Type> {
+    match arg {
+        Operand::Var(v) => generator.var_types.get(v).filter(|ty| is_aggregate(ty)).cloned(),
+        _ => None,
+    }
+}
 
-        let class = arg_type.as_ref().and_then(|ty| classify_struct_arg(generator, ty));
-
-        match class {
-            Some(StructArgClass::OneReg) => {
-                // Load the struct's first (and only) eightbyte into a temp var
-                if let Operand::Var(var) = arg {
-                    if let Some(&off) = generator.alloca_buffers.get(var) {
-                        // Load from the alloca buffer
-                        let temp = generator.new_temp_var();
-                        let slot = generator.get_or_create_slot(temp);
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Reg(X86Reg::Rax),
-                            X86Operand::Mem(X86Reg::Rbp, off),
-                        ));
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Mem(X86Reg::Rbp, slot),
-                            X86Operand::Reg(X86Reg::Rax),
-                        ));
-                        flat_args.push(Operand::Var(temp));
-                        continue;
-                    }
-                }
-                // Fallback: pass as-is (pointer)
-                flat_args.push(arg.clone());
-            }
-            Some(StructArgClass::TwoReg) => {
-                // Load both eightbytes into temp vars
-                if let Operand::Var(var) = arg {
-                    if let Some(&off) = generator.alloca_buffers.get(var) {
-                        let temp1 = generator.new_temp_var();
-                        let slot1 = generator.get_or_create_slot(temp1);
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Reg(X86Reg::Rax),
-                            X86Operand::Mem(X86Reg::Rbp, off),
-                        ));
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Mem(X86Reg::Rbp, slot1),
-                            X86Operand::Reg(X86Reg::Rax),
-                        ));
-
-                        let temp2 = generator.new_temp_var();
-                        let slot2 = generator.get_or_create_slot(temp2);
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Reg(X86Reg::Rax),
-                            X86Operand::Mem(X86Reg::Rbp, off + 8),
-                        ));
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Mem(X86Reg::Rbp, slot2),
-                            X86Operand::Reg(X86Reg::Rax),
-                        ));
-
-                        flat_args.push(Operand::Var(temp1));
-                        flat_args.push(Operand::Var(temp2));
-                        continue;
-                    }
-                }
-                flat_args.push(arg.clone());
-            }
-            Some(StructArgClass::Memory) | None => {
-                // Large struct → pass by pointer (existing behavior: LEA the alloca)
-                // Non-struct → pass as-is
-                flat_args.push(arg.clone());
-            }
-        }
+/// The registers eightbytes of each class are returned in, in order.
+fn return_regs(class: EightbyteClass) -> [X86Reg; 2] {
+    match class {
+        EightbyteClass::Integer => [X86Reg::Rax, X86Reg::Rdx],
+        EightbyteClass::Sse => [X86Reg::Xmm0, X86Reg::Xmm1],
     }
+}
 
-    flat_args
+/// Pair each eightbyte of a struct or union returned in registers with the
+/// register holding it.
+pub(crate) fn returned_eightbytes(classes: &[EightbyteClass]) -> Vec<(EightbyteClass, X86Reg)> {
+    let (mut ints, mut sses) = (0, 0);
+    classes.iter().map(|&class| {
+        let next = if class == EightbyteClass::Sse { &mut sses } else { &mut ints };
+        *next += 1;
+        (class, return_regs(class)[*next - 1].clone())
+    }).collect()
+}
+
+/// Move one eightbyte between a register of its class and memory.
+pub(crate) fn move_eightbyte(class: EightbyteClass, dest: X86Operand, src: X86Operand) -> X86Instr {
+    match (class, &dest) {
+        (EightbyteClass::Sse, X86Operand::Mem(base, offset)) => X86Instr::Movsd(X86Operand::DoubleMem(base.clone(), *offset), src),
+        (EightbyteClass::Sse, _) => match src {
+            X86Operand::Mem(base, offset) => X86Instr::Movsd(dest, X86Operand::DoubleMem(base, offset)),
+            src => X86Instr::Movsd(dest, src),
+        },
+        (EightbyteClass::Integer, _) => X86Instr::Mov(dest, src),
+    }
+}
+
+/// Give each constant passed to a `float` parameter a `float` temporary,
+/// since a bare float constant argument is passed as a `double`.
+fn narrow_float_constant_args(generator: &mut FunctionGenerator, param_types: &[Type], args: &[Operand]) -> Vec<Operand> {
+    args.iter().enumerate().map(|(i, arg)| match (arg, param_types.get(i)) {
+        (Operand::FloatConstant(f), Some(Type::Float)) => {
+            let temp = generator.new_temp_var();
+            generator.var_types.insert(temp, Type::Float);
+            let label = generator.get_or_create_float_const(*f, false);
+            let slot = generator.var_to_op(temp);
+            generator.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), X86Operand::RipRelLabel(label)));
+            generator.asm.push(X86Instr::Movss(slot, X86Operand::Reg(X86Reg::Xmm0)));
+            Operand::Var(temp)
+        }
+        _ => arg.clone(),
+    }).collect()
 }
 
 /// A pending assignment to a param register.
@@ -151,7 +115,8 @@ enum ParamMove {
 
 // ─── Shared helpers ─────────────────────────────────────────────
 
-/// Classify an argument as (is_float, is_double).
+/// Classify an argument as (is_float, is_double). A constant is a `double`:
+/// the lowerer passes one to a `float` parameter in a `float` variable.
 fn classify_arg(generator: &FunctionGenerator, arg: &Operand) -> (bool, bool) {
     let is_float = match arg {
         Operand::FloatConstant(_) => true,
@@ -160,6 +125,7 @@ fn classify_arg(generator: &FunctionGenerator, arg: &Operand) -> (bool, bool) {
         _ => false,
     };
     let is_double = match arg {
+        Operand::FloatConstant(_) => true,
         Operand::Var(v) => generator.var_types.get(v)
             .map_or(false, |t| matches!(t, Type::Double)),
         _ => false,
@@ -167,12 +133,20 @@ fn classify_arg(generator: &FunctionGenerator, arg: &Operand) -> (bool, bool) {
     (is_float, is_double)
 }
 
+/// The operand of a float argument, with constants at their passed width.
+fn float_arg_op(generator: &mut FunctionGenerator, arg: &Operand, is_double: bool) -> X86Operand {
+    match arg {
+        Operand::FloatConstant(f) => X86Operand::RipRelLabel(generator.get_or_create_float_const(*f, is_double)),
+        _ => generator.operand_to_op(arg),
+    }
+}
+
 /// Resolve an integer argument to its X86 operand, distinguishing allocas and globals
 /// (which need LEA to produce an address) from regular values (which use MOV).
 fn resolve_int_arg(generator: &mut FunctionGenerator, arg: &Operand) -> ParamMove {
     if let Operand::Var(var) = arg {
         if let Some(&off) = generator.alloca_buffers.get(var) {
-            return ParamMove::Lea(X86Operand::Mem(X86Reg::Rbp, off));
+            return ParamMove::Lea(X86Operand::Mem(X86Reg::Frame, off));
         }
     }
     if let Operand::Global(gname) = arg {
@@ -181,58 +155,157 @@ fn resolve_int_arg(generator: &mut FunctionGenerator, arg: &Operand) -> ParamMov
     ParamMove::Mov(generator.operand_to_op(arg))
 }
 
+/// What an argument in a register is taken from.
+enum RegisterSource<'a> {
+    /// The argument itself
+    Arg(&'a Operand),
+    /// An eightbyte of a struct or union, copied to this frame slot
+    Eightbyte(i32),
+    /// The address of this frame buffer
+    Buffer(i32),
+}
+
+/// Load the address of struct or union `value` of type `ty` into RDX. One
+/// whose size is not a multiple of eight is first copied into a buffer of
+/// whole eightbytes, so that none is read past the end of the object.
+pub(crate) fn load_eightbytes_address(generator: &mut FunctionGenerator, value: &Operand, ty: &Type) {
+    generator.load_address_into(value, X86Reg::Rdx);
+    let size = generator.get_type_size(ty);
+    if !size.is_multiple_of(8) {
+        let buffer = generator.alloc_aggregate(ty);
+        generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, buffer)));
+        copy_memory(generator, size);
+        generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rdx), X86Operand::Reg(X86Reg::Rax)));
+    }
+}
+
 /// Marshal all arguments into registers and stack slots.
-/// Float args are emitted immediately; integer param-register assignments are
-/// collected and returned for cycle-safe parallel-move resolution.
+/// Stack arguments are stored first, since they go through RAX, RCX, RDX
+/// and XMM0, along with the copies struct and union arguments need; float
+/// register arguments follow, and integer param-register assignments
+/// are collected and returned for cycle-safe parallel-move resolution,
+/// along with the number of float registers used. `return_buffer` is the
+/// frame buffer whose address a struct or union returned in memory is
+/// written to, passed ahead of the arguments.
 fn marshal_args(
     generator: &mut FunctionGenerator,
     args: &[Operand],
-    param_regs: &[X86Reg],
-    float_regs: &[X86Reg],
-    shadow_space: usize,
-) -> Vec<(usize, ParamMove)> {
-    let mut int_moves = Vec::new();
-
-    for (i, arg) in args.iter().enumerate() {
-        let (is_float, is_double) = classify_arg(generator, arg);
+    convention: &dyn CallingConvention,
+    return_buffer: Option<i32>,
+) -> (Vec<(usize, ParamMove)>, usize) {
+    let mut allocator = ArgAllocator::new(convention);
+    let mut in_registers: Vec<(ArgLocation, RegisterSource)> = Vec::new();
+    if let Some(buffer) = return_buffer {
+        in_registers.push((allocator.next(false), RegisterSource::Buffer(buffer)));
+    }
+    let passing: Vec<ArgPassing> = args.iter()
+        .map(|arg| {
+            let ty = aggregate_type(generator, arg);
+            let aggregate = ty.as_ref().and_then(|ty| classify_aggregate(generator, ty));
+            let size = ty.as_ref().map_or(8, |ty| generator.get_type_size(ty));
+            next_arg(&mut allocator, aggregate, size, classify_arg(generator, arg).0)
+        })
+        .collect();
+    generator.outgoing_args_size = generator.outgoing_args_size.max(allocator.stack_size() as i32);
+
+    let rax = X86Operand::Reg(X86Reg::Rax);
+    for (arg, passing) in args.iter().zip(passing) {
+        match passing {
+            ArgPassing::Scalar(ArgLocation::Stack(offset)) => {
+                let (is_float, is_double) = classify_arg(generator, arg);
+                if is_float {
+                    let op = float_arg_op(generator, arg, is_double);
+                    if is_double {
+                        generator.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), op));
+                        generator.asm.push(X86Instr::Movsd(
+                            X86Operand::DoubleMem(X86Reg::Rsp, offset), X86Operand::Reg(X86Reg::Xmm0)));
+                    } else {
+                        generator.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), op));
+                        generator.asm.push(X86Instr::Movss(
+                            X86Operand::FloatMem(X86Reg::Rsp, offset), X86Operand::Reg(X86Reg::Xmm0)));
+                    }
+                } else {
+                    match resolve_int_arg(generator, arg) {
+                        ParamMove::Lea(src) => generator.asm.push(X86Instr::Lea(rax.clone(), src)),
+                        ParamMove::Mov(src) => generator.asm.push(X86Instr::Mov(rax.clone(), src)),
+                    }
+                    generator.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rsp, offset), rax.clone()));
+                }
+            }
+            ArgPassing::Scalar(location) => in_registers.push((location, RegisterSource::Arg(arg))),
+            ArgPassing::Eightbytes(eightbytes) => {
+                let Some(ty) = aggregate_type(generator, arg) else { continue };
+                load_eightbytes_address(generator, arg, &ty);
+                for (i, (_, location)) in eightbytes.into_iter().enumerate() {
+                    generator.asm.push(X86Instr::Mov(rax.clone(), X86Operand::Mem(X86Reg::Rdx, 8 * i as i32)));
+                    let slot = match location {
+                        ArgLocation::Stack(offset) => {
+                            generator.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rsp, offset), rax.clone()));
+                            continue;
+                        }
+                        _ => {
+                            let temp = generator.new_temp_var();
+                            generator.get_or_create_slot(temp)
+                        }
+                    };
+                    generator.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, slot), rax.clone()));
+                    in_registers.push((location, RegisterSource::Eightbyte(slot)));
+                }
+            }
+            ArgPassing::Memory(offset) => {
+                let Some(ty) = aggregate_type(generator, arg) else { continue };
+                let size = generator.get_type_size(&ty);
+                generator.load_address_into(arg, X86Reg::Rdx);
+                generator.asm.push(X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Rsp, offset)));
+                copy_memory(generator, size);
+            }
+            ArgPassing::Reference(location) => {
+                // The callee may modify its copy
+                let Some(ty) = aggregate_type(generator, arg) else { continue };
+                let size = generator.get_type_size(&ty);
+                let buffer = generator.alloc_aggregate(&ty);
+                generator.load_address_into(arg, X86Reg::Rdx);
+                generator.asm.push(X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Frame, buffer)));
+                copy_memory(generator, size);
+                match location {
+                    ArgLocation::Stack(offset) => {
+                        generator.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rsp, offset), rax.clone()));
+                    }
+                    location => in_registers.push((location, RegisterSource::Buffer(buffer))),
+                }
+            }
+        }
+    }
 
-        if i < param_regs.len() {
-            if is_float && i < float_regs.len() {
-                let op = generator.operand_to_op(arg);
+    let float_regs = convention.float_param_regs();
+    let mut int_moves = Vec::new();
+    for (location, source) in in_registers {
+        match (location, source) {
+            (ArgLocation::FloatReg(i), RegisterSource::Arg(arg)) => {
+                let is_double = classify_arg(generator, arg).1;
+                let op = float_arg_op(generator, arg, is_double);
                 if is_double {
                     generator.asm.push(X86Instr::Movsd(X86Operand::Reg(float_regs[i].clone()), op));
                 } else {
                     generator.asm.push(X86Instr::Movss(X86Operand::Reg(float_regs[i].clone()), op));
                 }
-            } else if !is_float {
-                int_moves.push((i, resolve_int_arg(generator, arg)));
             }
-        } else {
-            // Stack-passed arguments
-            let offset = (shadow_space + (i - param_regs.len()) * 8) as i32;
-            if is_float {
-                let op = generator.operand_to_op(arg);
-                if is_double {
-                    generator.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), op));
-                    generator.asm.push(X86Instr::Movsd(
-                        X86Operand::DoubleMem(X86Reg::Rsp, offset), X86Operand::Reg(X86Reg::Xmm0)));
-                } else {
-                    generator.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), op));
-                    generator.asm.push(X86Instr::Movss(
-                        X86Operand::FloatMem(X86Reg::Rsp, offset), X86Operand::Reg(X86Reg::Xmm0)));
-                }
-            } else {
-                let rax = X86Operand::Reg(X86Reg::Rax);
-                match resolve_int_arg(generator, arg) {
-                    ParamMove::Lea(src) => generator.asm.push(X86Instr::Lea(rax.clone(), src)),
-                    ParamMove::Mov(src) => generator.asm.push(X86Instr::Mov(rax.clone(), src)),
-                }
-                generator.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rsp, offset), rax));
+            (ArgLocation::FloatReg(i), RegisterSource::Eightbyte(slot)) => {
+                generator.asm.push(X86Instr::Movsd(
+                    X86Operand::Reg(float_regs[i].clone()), X86Operand::DoubleMem(X86Reg::Frame, slot)));
+            }
+            (ArgLocation::Reg(i), RegisterSource::Arg(arg)) => int_moves.push((i, resolve_int_arg(generator, arg))),
+            (ArgLocation::Reg(i), RegisterSource::Eightbyte(slot)) => {
+                int_moves.push((i, ParamMove::Mov(X86Operand::Mem(X86Reg::Frame, slot))));
+            }
+            (ArgLocation::Reg(i), RegisterSource::Buffer(buffer)) => {
+                int_moves.push((i, ParamMove::Lea(X86Operand::Mem(X86Reg::Frame, buffer))));
             }
+            (ArgLocation::FloatReg(_), RegisterSource::Buffer(_)) | (ArgLocation::Stack(_), _) => {}
         }
     }
 
-    int_moves
+    (int_moves, allocator.float_regs_used())
 }
 
 /// Emit all integer param-register assignments using a cycle-safe parallel-move algorithm.
@@ -284,51 +357,38 @@ fn emit_parallel_int_moves(generator: &mut FunctionGenerator,
     }
 }
 
-/// Store a call's return value into the destination variable.
-fn store_call_result(generator: &mut FunctionGenerator, dest: VarId, ret_type: Option<&Type>) {
-    // Check for struct return type
-    if let Some(ty) = ret_type {
-        if let Some(class) = classify_struct_arg(generator, ty) {
-            let size = get_aggregate_size(generator, ty);
-            generator.var_types.insert(dest, ty.clone());
-            // Struct return values are stored into the dest alloca buffer
-            if let Some(&off) = generator.alloca_buffers.get(&dest) {
-                match class {
-                    StructArgClass::OneReg => {
-                        // Return value in RAX → store to alloca
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Mem(X86Reg::Rbp, off),
-                            X86Operand::Reg(X86Reg::Rax),
-                        ));
-                    }
-                    StructArgClass::TwoReg => {
-                        // Return value in RAX:RDX → store both eightbytes
-                        generator.asm.push(X86Instr::Mov(
-                            X86Operand::Mem(X86Reg::Rbp, off),
-                            X86Operand::Reg(X86Reg::Rax),
-                        ));
-                        if size > 8 {
-                            generator.asm.push(X86Instr::Mov(
-                                X86Operand::Mem(X86Reg::Rbp, off + 8),
-                                X86Operand::Reg(X86Reg::Rdx),
-                            ));
-                        }
-                    }
-                    StructArgClass::Memory => {
-                        // For MEMORY-class returns, the hidden pointer mechanism
-                        // means the callee wrote directly to our alloca via RDI.
-                        // Nothing to do here.
-                    }
-                }
-                return;
-            }
-            // No alloca — just store RAX as fallback
-            let d_op = generator.var_to_op(dest);
-            generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
-            return;
+/// The frame buffer a call returning `ret_type` leaves a struct or union
+/// result in, with how the convention returns it. A call whose result is
+/// unused needs one only to give the callee somewhere to write it.
+fn aggregate_return(generator: &mut FunctionGenerator, ret_type: Option<&Type>, dest: &Option<VarId>) -> Option<(AggregatePassing, i32)> {
+    let ty = ret_type?;
+    let passing = classify_aggregate(generator, ty)?;
+    if let Some(d) = dest {
+        generator.var_types.insert(*d, ty.clone());
+    }
+    if dest.is_none() && !passing.returns_in_memory() {
+        return None;
+    }
+    Some((passing, generator.alloc_aggregate(ty)))
+}
+
+/// Store a struct or union result into its buffer, if it came back in
+/// registers, and set the destination variable to the buffer's address.
+fn store_aggregate_result(generator: &mut FunctionGenerator, dest: &Option<VarId>, passing: AggregatePassing, buffer: i32) {
+    if let AggregatePassing::Registers(classes) = passing {
+        for (i, (class, reg)) in returned_eightbytes(&classes).into_iter().enumerate() {
+            let eightbyte = X86Operand::Mem(X86Reg::Frame, buffer + 8 * i as i32);
+            generator.asm.push(move_eightbyte(class, eightbyte, X86Operand::Reg(reg)));
         }
     }
+    if let Some(d) = dest {
+        let d_op = generator.var_to_op(*d);
+        generator.emit_lea_to(&d_op, X86Operand::Mem(X86Reg::Frame, buffer));
+    }
+}
 
+/// Store a call's return value into the destination variable.
+fn store_call_result(generator: &mut FunctionGenerator, dest: VarId, ret_type: Option<&Type>) {
     let is_float = ret_type.map_or(false, |t| matches!(t, Type::Float | Type::Double));
     let is_double = ret_type.map_or(false, |t| matches!(t, Type::Double));
 
@@ -790,15 +850,29 @@ pub fn gen_call(generator: &mut FunctionGenerator, dest: &Option<VarId>, name: &
     }
 
     let convention = generator.convention();
-    let param_regs = convention.param_regs();
-    let float_regs = convention.float_param_regs();
-    let shadow_space = convention.shadow_space_size();
 
-    // Flatten struct args: decompose small structs into register-sized values
-    let flat_args = flatten_struct_args(generator, args);
+    let func_param_types = generator.func_param_types;
+    let param_types = func_param_types.get(name).map_or(&[][..], Vec::as_slice);
+    let args = narrow_float_constant_args(generator, param_types, args);
+
+    // The IR records the call's type; functions known only by a
+    // prototype have no entry in `func_return_types`
+    let ret_type = dest.and_then(|d| generator.var_types.get(&d).cloned())
+        .or_else(|| generator.func_return_types.get(name).cloned());
+    let aggregate = aggregate_return(generator, ret_type.as_ref(), dest);
+    let return_buffer = aggregate.as_ref().filter(|(passing, _)| passing.returns_in_memory()).map(|&(_, buffer)| buffer);
 
-    let int_moves = marshal_args(generator, &flat_args, &param_regs, &float_regs, shadow_space);
-    emit_parallel_int_moves(generator, &param_regs, int_moves);
+    let (int_moves, vector_args) = marshal_args(generator, &args, convention.as_ref(), return_buffer);
+    emit_parallel_int_moves(generator, convention.param_regs(), int_moves);
+
+    // Like GCC for an unprototyped call, every call to a function defined
+    // elsewhere passes the vector count, variadic or not. A function defined
+    // here reads it only to save its variadic arguments, so it gets the
+    // count when passed more arguments than it has parameters.
+    let defined_here = generator.func_return_types.contains_key(name);
+    if convention.passes_vector_count_in_al() && (!defined_here || args.len() > param_types.len()) {
+        generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::Imm(vector_args as i64)));
+    }
 
     generator.asm.push(X86Instr::Call(if generator.target.pic_mode != model::PicMode::None {
         format!("{}@PLT", name)
@@ -806,59 +880,77 @@ pub fn gen_call(generator: &mut FunctionGenerator, dest: &Option<VarId>, name: &
         name.to_string()
     }));
 
-    if let Some(d) = dest {
-        let ret_type = generator.func_return_types.get(name).cloned();
+    if let Some((passing, buffer)) = aggregate {
+        store_aggregate_result(generator, dest, passing, buffer);
+    } else if let Some(d) = dest {
         store_call_result(generator, *d, ret_type.as_ref());
     }
 }
 
-pub fn gen_indirect_call(generator: &mut FunctionGenerator, dest: &Option<VarId>, func_ptr: &Operand, args: &[Operand]) {
+pub fn gen_indirect_call(generator: &mut FunctionGenerator, dest: &Option<VarId>, func_ptr: &Operand, args: &[Operand], is_variadic: bool) {
     let convention = generator.convention();
-    let param_regs = convention.param_regs();
-    let float_regs = convention.float_param_regs();
-    let shadow_space = convention.shadow_space_size();
 
-    // Load function pointer into R10 (not a param reg, safe from arg marshalling)
+    // The parameter types come from the pointer's type, or from the function
+    // it names once constant propagation has replaced it
+    let param_types = match func_ptr {
+        Operand::Var(v) => match generator.var_types.get(v) {
+            Some(Type::FunctionPointer { param_types, .. }) => param_types.clone(),
+            _ => Vec::new(),
+        },
+        Operand::Global(name) => generator.func_param_types.get(name).cloned().unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let args = narrow_float_constant_args(generator, &param_types, args);
+
+    let ret_type = infer_indirect_return_type(generator, func_ptr, *dest);
+    let aggregate = aggregate_return(generator, ret_type.as_ref(), dest);
+    let return_buffer = aggregate.as_ref().filter(|(passing, _)| passing.returns_in_memory()).map(|&(_, buffer)| buffer);
+
+    let (int_moves, vector_args) = marshal_args(generator, &args, convention.as_ref(), return_buffer);
+
+    // Load the function pointer into R10, which no parameter uses, once
+    // the argument copies that use it are done
     let fp_op = generator.operand_to_op(func_ptr);
     if let X86Operand::Label(name) = &fp_op {
         generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::R10), X86Operand::RipRelLabel(name.clone())));
     } else {
         generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::R10), fp_op));
     }
+    emit_parallel_int_moves(generator, convention.param_regs(), int_moves);
 
-    // Flatten struct args: decompose small structs into register-sized values
-    let flat_args = flatten_struct_args(generator, args);
-
-    let int_moves = marshal_args(generator, &flat_args, &param_regs, &float_regs, shadow_space);
-    emit_parallel_int_moves(generator, &param_regs, int_moves);
+    // After the moves, since they use RAX as scratch
+    if is_variadic && convention.passes_vector_count_in_al() {
+        generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::Imm(vector_args as i64)));
+    }
 
     generator.asm.push(X86Instr::CallIndirect(X86Operand::Reg(X86Reg::R10)));
 
-    if let Some(d) = dest {
-        // Infer return type from function pointer type, global name, or dest type
-        let ret_type = infer_indirect_return_type(generator, func_ptr, *d);
+    if let Some((passing, buffer)) = aggregate {
+        store_aggregate_result(generator, dest, passing, buffer);
+    } else if let Some(d) = dest {
         store_call_result(generator, *d, ret_type.as_ref());
     }
 }
 
-/// Infer the return type of an indirect call from its function pointer.
-fn infer_indirect_return_type(generator: &mut FunctionGenerator, func_ptr: &Operand, dest: VarId) -> Option<Type> {
-    // 1. From function pointer variable's type annotation
-    if let Operand::Var(v) = func_ptr {
-        if let Some(t) = generator.var_types.get(v).cloned() {
-            if let Type::FunctionPointer { return_type, .. } = &t {
-                generator.var_types.insert(dest, *return_type.clone());
-                return Some(*return_type.clone());
-            }
-        }
-    }
-    // 2. From global function name (after copy propagation)
-    if let Operand::Global(name) = func_ptr {
-        if let Some(ret_ty) = generator.func_return_types.get(name).cloned() {
-            generator.var_types.insert(dest, ret_ty.clone());
-            return Some(ret_ty);
-        }
+/// Infer the return type of an indirect call: the type the IR recorded for
+/// its result, or else the function pointer's.
+fn infer_indirect_return_type(generator: &mut FunctionGenerator, func_ptr: &Operand, dest: Option<VarId>) -> Option<Type> {
+    // 1. From the destination's type annotation
+    if let Some(t) = dest.and_then(|d| generator.var_types.get(&d)) {
+        return Some(t.clone());
+    }
+    let ret_ty = match func_ptr {
+        // 2. From function pointer variable's type annotation
+        Operand::Var(v) => match generator.var_types.get(v) {
+            Some(Type::FunctionPointer { return_type, .. }) => Some(*return_type.clone()),
+            _ => None,
+        },
+        // 3. From global function name (after copy propagation)
+        Operand::Global(name) => generator.func_return_types.get(name).cloned(),
+        _ => None,
+    }?;
+    if let Some(d) = dest {
+        generator.var_types.insert(d, ret_ty.clone());
     }
-    // 3. Fallback: destination type if already known
-    generator.var_types.get(&dest).cloned()
+    Some(ret_ty)
 }
@@ -1,6 +1,6 @@
 // Calling convention abstraction for cross-platform support
 use crate::x86::X86Reg;
-use model::{CallingConvention as ConventionType, Platform};
+use model::{CallingConvention as ConventionType, Platform, Type, TypeLayout};
 
 /// Trait defining platform-specific calling conventions
 pub trait CallingConvention {
@@ -24,10 +24,150 @@ pub trait CallingConvention {
     
     /// Size of shadow/home space for register parameters (in bytes)
     fn shadow_space_size(&self) -> usize;
-    
+
+    /// Whether calls to variadic functions pass an upper bound on the number
+    /// of vector registers used in AL
+    fn passes_vector_count_in_al(&self) -> bool {
+        false
+    }
+
+    /// Whether every argument takes the next position in both register
+    /// sequences (so `f(int, double)` uses the first integer and the second
+    /// float register), rather than the next register of its own class
+    fn shares_arg_positions(&self) -> bool {
+        false
+    }
+
+    /// Caller-saved registers (clobbered by a call), in allocation order
+    fn caller_saved_regs(&self) -> &'static [X86Reg];
+
     /// Callee-saved registers (must be preserved across function calls)
-    #[allow(dead_code)]
     fn callee_saved_regs(&self) -> &'static [X86Reg];
+
+    /// How a struct or union of type `ty` is passed and returned
+    fn classify_aggregate(&self, _layout: &TypeLayout, _ty: &Type) -> AggregatePassing {
+        AggregatePassing::Memory
+    }
+}
+
+/// The register class of one eightbyte of a struct or union passed in registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EightbyteClass {
+    /// The next integer register
+    Integer,
+    /// The next XMM register
+    Sse,
+}
+
+/// How a struct or union argument is passed, and its return value returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregatePassing {
+    /// In registers, one per eightbyte (returned in RAX and RDX, or XMM0 and
+    /// XMM1); a stack slot each once the registers run out
+    Registers(Vec<EightbyteClass>),
+    /// Copied whole into the stack argument area; returned through a
+    /// hidden pointer to the caller's buffer
+    Memory,
+    /// As the address of a copy the caller makes; returned through a hidden
+    /// pointer to the caller's buffer
+    Reference,
+}
+
+impl AggregatePassing {
+    /// Whether a value returned this way goes through a hidden pointer,
+    /// which the caller passes as the first integer argument
+    pub fn returns_in_memory(&self) -> bool {
+        !matches!(self, AggregatePassing::Registers(_))
+    }
+}
+
+/// Where an argument is passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgLocation {
+    /// The nth integer parameter register
+    Reg(usize),
+    /// The nth floating-point parameter register
+    FloatReg(usize),
+    /// The stack, at this offset from the stack pointer at the call
+    Stack(i32),
+}
+
+/// Assigns argument locations in order, the way a calling convention does;
+/// used for outgoing arguments and incoming parameters alike.
+pub struct ArgAllocator<'a> {
+    convention: &'a dyn CallingConvention,
+    int_regs: usize,
+    float_regs: usize,
+    stack_slots: usize,
+}
+
+impl<'a> ArgAllocator<'a> {
+    pub fn new(convention: &'a dyn CallingConvention) -> Self {
+        ArgAllocator { convention, int_regs: 0, float_regs: 0, stack_slots: 0 }
+    }
+
+    /// The location of the next argument, an integer or pointer (or one
+    /// eightbyte of a struct) unless `is_float`.
+    pub fn next(&mut self, is_float: bool) -> ArgLocation {
+        let conv = self.convention;
+        let shared = conv.shares_arg_positions();
+        let location = if is_float {
+            let index = if shared { self.int_regs } else { self.float_regs };
+            (index < conv.float_param_regs().len()).then(|| {
+                self.float_regs += 1;
+                ArgLocation::FloatReg(index)
+            })
+        } else {
+            (self.int_regs < conv.param_regs().len()).then_some(ArgLocation::Reg(self.int_regs))
+        };
+        if shared || !is_float {
+            self.int_regs += 1;
+        }
+        location.unwrap_or_else(|| {
+            self.stack_slots += 1;
+            ArgLocation::Stack((conv.shadow_space_size() + (self.stack_slots - 1) * 8) as i32)
+        })
+    }
+
+    /// The locations of the eightbytes of a struct or union passed in
+    /// registers, or `None` if too few registers of their classes remain, in
+    /// which case it goes on the stack whole and takes none of them
+    pub fn next_eightbytes(&mut self, classes: &[EightbyteClass]) -> Option<Vec<ArgLocation>> {
+        let conv = self.convention;
+        if !conv.shares_arg_positions() {
+            let sse = classes.iter().filter(|&&c| c == EightbyteClass::Sse).count();
+            let int = classes.len() - sse;
+            if self.int_regs + int > conv.param_regs().len() || self.float_regs + sse > conv.float_param_regs().len() {
+                return None;
+            }
+        }
+        Some(classes.iter().map(|&class| self.next(class == EightbyteClass::Sse)).collect())
+    }
+
+    /// The offset of `size` bytes in the stack argument area, in whole slots,
+    /// for a struct or union passed in memory
+    pub fn next_stack(&mut self, size: usize) -> i32 {
+        let offset = self.convention.shadow_space_size() + self.stack_slots * 8;
+        self.stack_slots += size.div_ceil(8);
+        offset as i32
+    }
+
+    /// Integer registers taken so far; with shared positions, the number of
+    /// arguments assigned
+    pub fn int_regs_used(&self) -> usize {
+        self.int_regs.min(self.convention.param_regs().len())
+    }
+
+    /// Floating-point registers taken so far
+    pub fn float_regs_used(&self) -> usize {
+        self.float_regs
+    }
+
+    /// Bytes of stack taken by the arguments assigned so far, not counting
+    /// the shadow space
+    pub fn stack_size(&self) -> usize {
+        self.stack_slots * 8
+    }
 }
 
 /// Windows x64 (Microsoft) calling convention
@@ -45,10 +185,27 @@ impl CallingConvention for WindowsX64Convention {
     fn shadow_space_size(&self) -> usize {
         32  // 4 registers × 8 bytes
     }
+
+    fn shares_arg_positions(&self) -> bool {
+        true
+    }
+
+    /// Structs and unions of 1, 2, 4, or 8 bytes go in an integer register;
+    /// others as the address of a copy
+    fn classify_aggregate(&self, layout: &TypeLayout, ty: &Type) -> AggregatePassing {
+        match layout.size_of(ty) {
+            1 | 2 | 4 | 8 => AggregatePassing::Registers(vec![EightbyteClass::Integer]),
+            _ => AggregatePassing::Reference,
+        }
+    }
+
+    fn caller_saved_regs(&self) -> &'static [X86Reg] {
+        &[X86Reg::Rax, X86Reg::Rcx, X86Reg::Rdx, X86Reg::R8, X86Reg::R9, X86Reg::R10, X86Reg::R11]
+    }
     
     fn callee_saved_regs(&self) -> &'static [X86Reg] {
         &[X86Reg::Rbx, X86Reg::Rsi, X86Reg::Rdi, 
-          X86Reg::R12, X86Reg::R13, X86Reg::R14, X86Reg::R15]
+          X86Reg::R12, X86Reg::R13, X86Reg::R14, X86Reg::R15, X86Reg::Rbp]
     }
 }
 
@@ -69,9 +226,116 @@ impl CallingConvention for SystemVConvention {
     fn shadow_space_size(&self) -> usize {
         0  // No shadow space in System V
     }
+
+    fn passes_vector_count_in_al(&self) -> bool {
+        true
+    }
+
+    /// Structs and unions of up to 16 bytes go in registers, each eightbyte
+    /// an XMM register if it holds only `float` and `double` fields and an
+    /// integer register otherwise; larger ones, and those with misaligned
+    /// fields, on the stack
+    fn classify_aggregate(&self, layout: &TypeLayout, ty: &Type) -> AggregatePassing {
+        let size = layout.size_of(ty);
+        let mut classes = [None; 2];
+        if size == 0 || size > 16 || !classify_eightbytes(layout, ty, 0, &mut classes) {
+            return AggregatePassing::Memory;
+        }
+        AggregatePassing::Registers(classes[..size.div_ceil(8)].iter()
+            .map(|class| class.unwrap_or(EightbyteClass::Sse))
+            .collect())
+    }
+
+    fn caller_saved_regs(&self) -> &'static [X86Reg] {
+        &[X86Reg::Rax, X86Reg::Rcx, X86Reg::Rdx, X86Reg::Rsi, X86Reg::Rdi,
+          X86Reg::R8, X86Reg::R9, X86Reg::R10, X86Reg::R11]
+    }
     
     fn callee_saved_regs(&self) -> &'static [X86Reg] {
-        &[X86Reg::Rbx, X86Reg::R12, X86Reg::R13, X86Reg::R14, X86Reg::R15]
+        &[X86Reg::Rbx, X86Reg::R12, X86Reg::R13, X86Reg::R14, X86Reg::R15, X86Reg::Rbp]
+    }
+}
+
+/// Merge the classes of the scalars of `ty`, at `offset`, into the classes of
+/// the eightbytes holding them. Returns false if a field is misaligned.
+fn classify_eightbytes(layout: &TypeLayout, ty: &Type, offset: usize, classes: &mut [Option<EightbyteClass>; 2]) -> bool {
+    match ty {
+        Type::Struct(name) => {
+            let Some(s_def) = layout.structs.get(name) else { return false };
+            let placed = layout.struct_layout(s_def);
+            s_def.fields.iter().zip(&placed.fields).all(|(field, place)| {
+                let at = offset + place.offset;
+                if place.bitfield.is_some() {
+                    merge_class(classes, at, EightbyteClass::Integer);
+                    return true;
+                }
+                at.is_multiple_of(layout.align_of(&field.field_type))
+                    && classify_eightbytes(layout, &field.field_type, at, classes)
+            })
+        }
+        Type::Union(name) => {
+            let Some(u_def) = layout.unions.get(name) else { return false };
+            u_def.fields.iter().all(|field| classify_eightbytes(layout, &field.field_type, offset, classes))
+        }
+        Type::Array(elem, len) => {
+            let size = layout.size_of(elem);
+            (0..*len).all(|i| classify_eightbytes(layout, elem, offset + i * size, classes))
+        }
+        Type::Complex(elem) => {
+            let size = layout.size_of(elem);
+            classify_eightbytes(layout, elem, offset, classes) && classify_eightbytes(layout, elem, offset + size, classes)
+        }
+        Type::Float | Type::Double => {
+            merge_class(classes, offset, EightbyteClass::Sse);
+            true
+        }
+        Type::Typedef(name) if let Some(real) = layout.typedefs.and_then(|typedefs| typedefs.get(name)) => {
+            classify_eightbytes(layout, real, offset, classes)
+        }
+        _ => {
+            merge_class(classes, offset, EightbyteClass::Integer);
+            true
+        }
+    }
+}
+
+/// An eightbyte holding any integer is of the integer class.
+fn merge_class(classes: &mut [Option<EightbyteClass>; 2], offset: usize, class: EightbyteClass) {
+    if let Some(slot) = classes.get_mut(offset / 8) {
+        *slot = match (*slot, class) {
+            (Some(EightbyteClass::Integer), _) | (_, EightbyteClass::Integer) => Some(EightbyteClass::Integer),
+            _ => Some(EightbyteClass::Sse),
+        };
+    }
+}
+
+/// System V i386 (cdecl): every argument goes on the stack, so there are
+/// no parameter registers; EBX, ESI, EDI, and EBP survive calls
+pub struct CdeclConvention;
+
+impl CallingConvention for CdeclConvention {
+    fn param_regs(&self) -> &'static [X86Reg] {
+        &[]
+    }
+
+    fn float_param_regs(&self) -> &'static [X86Reg] {
+        &[]
+    }
+
+    fn return_reg(&self) -> X86Reg {
+        X86Reg::Eax
+    }
+
+    fn shadow_space_size(&self) -> usize {
+        0
+    }
+
+    fn caller_saved_regs(&self) -> &'static [X86Reg] {
+        &[X86Reg::Eax, X86Reg::Ecx, X86Reg::Edx]
+    }
+
+    fn callee_saved_regs(&self) -> &'static [X86Reg] {
+        &[X86Reg::Ebx, X86Reg::Esi, X86Reg::Edi, X86Reg::Ebp]
     }
 }
 
@@ -80,6 +344,7 @@ pub fn get_convention(convention_type: ConventionType) -> Box<dyn CallingConvent
     match convention_type {
         ConventionType::WindowsX64 => Box::new(WindowsX64Convention),
         ConventionType::SystemV => Box::new(SystemVConvention),
+        ConventionType::Cdecl => Box::new(CdeclConvention),
     }
 }
 
@@ -115,6 +380,114 @@ mod tests {
         assert!(!conv.callee_saved_regs().contains(&X86Reg::Rsi)); // Not callee-saved in System V
     }
 
+    #[test]
+    fn test_cdecl_convention() {
+        let conv = CdeclConvention;
+        assert!(conv.param_regs().is_empty() && conv.float_param_regs().is_empty());
+        assert_eq!(conv.return_reg(), X86Reg::Eax);
+        assert!(conv.callee_saved_regs().contains(&X86Reg::Esi));
+        assert!(!conv.callee_saved_regs().contains(&X86Reg::Edx));
+    }
+
+    #[test]
+    fn test_register_sets_are_disjoint() {
+        for conv in [
+            get_convention(ConventionType::WindowsX64),
+            get_convention(ConventionType::SystemV),
+            get_convention(ConventionType::Cdecl),
+        ] {
+            assert!(conv.callee_saved_regs().iter().all(|r| !conv.caller_saved_regs().contains(r)));
+            assert!(conv.param_regs().iter().all(|r| conv.caller_saved_regs().contains(r)));
+        }
+    }
+
+    #[test]
+    fn test_systemv_args_count_each_class_separately() {
+        use ArgLocation::*;
+        // f(int, double, int, int, int, int, int, double × 8, int)
+        let conv = SystemVConvention;
+        let mut args = ArgAllocator::new(&conv);
+        let mut floats = Vec::new();
+        let ints: Vec<_> = (0..7).map(|_| args.next(false)).collect();
+        for _ in 0..9 {
+            floats.push(args.next(true));
+        }
+        assert_eq!(ints, [Reg(0), Reg(1), Reg(2), Reg(3), Reg(4), Reg(5), Stack(0)]);
+        assert_eq!(floats[..2], [FloatReg(0), FloatReg(1)]);
+        assert_eq!(floats[7..], [FloatReg(7), Stack(8)]);
+        assert_eq!(args.next(false), Stack(16));
+        assert_eq!((args.int_regs_used(), args.float_regs_used(), args.stack_size()), (6, 8, 24));
+    }
+
+    #[test]
+    fn test_windows_args_share_positions() {
+        use ArgLocation::*;
+        // f(int, double, int, double, int, double)
+        let conv = WindowsX64Convention;
+        let mut args = ArgAllocator::new(&conv);
+        let locations: Vec<_> = (0..6).map(|i| args.next(i % 2 == 1)).collect();
+        assert_eq!(locations, [Reg(0), FloatReg(1), Reg(2), FloatReg(3), Stack(32), Stack(40)]);
+        assert_eq!((args.int_regs_used(), args.float_regs_used(), args.stack_size()), (4, 2, 16));
+    }
+
+    fn record(fields: &[Type], attributes: Vec<model::Attribute>) -> model::StructDef {
+        model::StructDef {
+            name: "S".to_string(),
+            fields: fields.iter().enumerate()
+                .map(|(i, ty)| model::StructField { name: format!("f{}", i), field_type: ty.clone(), bit_width: None })
+                .collect(),
+            attributes,
+        }
+    }
+
+    fn classify(conv: &dyn CallingConvention, fields: &[Type], attributes: Vec<model::Attribute>) -> AggregatePassing {
+        let structs = std::collections::HashMap::from([("S".to_string(), record(fields, attributes))]);
+        let unions = std::collections::HashMap::new();
+        conv.classify_aggregate(&TypeLayout::new(&structs, &unions), &Type::Struct("S".to_string()))
+    }
+
+    #[test]
+    fn test_systemv_classifies_eightbytes() {
+        use EightbyteClass::*;
+        let conv = SystemVConvention;
+        let regs = |classes: &[EightbyteClass]| AggregatePassing::Registers(classes.to_vec());
+        assert_eq!(classify(&conv, &[Type::Long, Type::Long], vec![]), regs(&[Integer, Integer]));
+        assert_eq!(classify(&conv, &[Type::Float, Type::Float, Type::Float], vec![]), regs(&[Sse, Sse]));
+        assert_eq!(classify(&conv, &[Type::Long, Type::Double], vec![]), regs(&[Integer, Sse]));
+        // A float sharing an eightbyte with an int makes it an integer one
+        assert_eq!(classify(&conv, &[Type::Double, Type::Float, Type::Int], vec![]), regs(&[Sse, Integer]));
+        assert_eq!(classify(&conv, &[Type::Array(Box::new(Type::Char), 3)], vec![]), regs(&[Integer]));
+        assert_eq!(classify(&conv, &[Type::Long, Type::Long, Type::Long], vec![]), AggregatePassing::Memory);
+        // A packed struct's misaligned field puts it in memory
+        assert_eq!(classify(&conv, &[Type::Char, Type::Long], vec![model::Attribute::Packed]), AggregatePassing::Memory);
+    }
+
+    #[test]
+    fn test_windows_passes_odd_sizes_by_reference() {
+        let conv = WindowsX64Convention;
+        assert_eq!(classify(&conv, &[Type::Int, Type::Int], vec![]), AggregatePassing::Registers(vec![EightbyteClass::Integer]));
+        assert_eq!(classify(&conv, &[Type::Array(Box::new(Type::Char), 3)], vec![]), AggregatePassing::Reference);
+        assert_eq!(classify(&conv, &[Type::Double, Type::Double], vec![]), AggregatePassing::Reference);
+        assert!(AggregatePassing::Reference.returns_in_memory());
+    }
+
+    #[test]
+    fn test_systemv_struct_without_registers_goes_on_the_stack() {
+        use ArgLocation::*;
+        use EightbyteClass::*;
+        // f(long × 5, struct { long, long }, long)
+        let conv = SystemVConvention;
+        let mut args = ArgAllocator::new(&conv);
+        for _ in 0..5 {
+            args.next(false);
+        }
+        assert_eq!(args.next_eightbytes(&[Integer, Integer]), None);
+        assert_eq!(args.next_stack(16), 0);
+        assert_eq!(args.next(false), Reg(5));
+        assert_eq!(args.next_eightbytes(&[Sse, Sse]), Some(vec![FloatReg(0), FloatReg(1)]));
+        assert_eq!((args.int_regs_used(), args.float_regs_used(), args.stack_size()), (6, 2, 16));
+    }
+
     #[test]
     fn test_get_convention() {
         let windows = get_convention(ConventionType::WindowsX64);
@@ -0,0 +1,309 @@
+// COFF object files for x86-64 Windows
+//
+// `write` lays out what the assembler produced the way the PE/COFF spec and
+// GNU as do:
+//
+// - File header, section headers, then each section's contents followed by
+//   its relocations, then the symbol table and the string table.
+// - Every section gets a static symbol with an auxiliary record giving its
+//   length and relocation count, ahead of the assembler's symbols.
+// - x86-64 symbol names are undecorated: unlike 32-bit Windows there is no
+//   leading underscore. Names longer than eight bytes, for sections as well
+//   as symbols, go in the string table.
+// - A relocation's addend is stored in the field it patches. `REL32` is
+//   relative to the end of its 4-byte field, so it stores 4 more than a
+//   displacement relative to the field itself.
+// - A weak definition is a weak external whose fallback is an external
+//   definition named `.weak.<name>.default.<first global>`, as LLVM names it,
+//   so that two objects defining the same weak symbol do not clash.
+
+use crate::assembler::{Binding, Object, RelocTarget, SectionKind, Symbol};
+use crate::encoder::FixupKind;
+
+const MACHINE_AMD64: u16 = 0x8664;
+
+const SCN_CNT_CODE: u32 = 0x0000_0020;
+const SCN_CNT_INITIALIZED_DATA: u32 = 0x0000_0040;
+const SCN_CNT_UNINITIALIZED_DATA: u32 = 0x0000_0080;
+const SCN_LNK_NRELOC_OVFL: u32 = 0x0100_0000;
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const SCN_MEM_READ: u32 = 0x4000_0000;
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+const REL_AMD64_ADDR64: u16 = 0x0001;
+const REL_AMD64_ADDR32: u16 = 0x0002;
+const REL_AMD64_REL32: u16 = 0x0004;
+
+const SYM_CLASS_EXTERNAL: u8 = 2;
+const SYM_CLASS_STATIC: u8 = 3;
+const SYM_CLASS_WEAK_EXTERNAL: u8 = 105;
+/// A weak external resolves to its fallback unless a definition is linked
+const WEAK_EXTERN_SEARCH_ALIAS: u32 = 3;
+
+const FILE_HEADER_SIZE: usize = 20;
+const SECTION_HEADER_SIZE: usize = 40;
+const RELOC_SIZE: usize = 10;
+
+/// Section header flags for a kind of section aligned to `align` bytes.
+fn characteristics(kind: SectionKind, align: u64) -> u32 {
+    let contents = match kind {
+        SectionKind::Code => SCN_CNT_CODE | SCN_MEM_EXECUTE | SCN_MEM_READ,
+        SectionKind::Data => SCN_CNT_INITIALIZED_DATA | SCN_MEM_READ | SCN_MEM_WRITE,
+        SectionKind::ReadOnly => SCN_CNT_INITIALIZED_DATA | SCN_MEM_READ,
+        SectionKind::Bss => SCN_CNT_UNINITIALIZED_DATA | SCN_MEM_READ | SCN_MEM_WRITE,
+    };
+    // IMAGE_SCN_ALIGN_<n>BYTES is log2(n) + 1 in bits 20-23, up to 8192
+    let align_bits = align.clamp(1, 8192).trailing_zeros() + 1;
+    contents | align_bits << 20
+}
+
+/// The COFF object for `object`.
+pub(crate) fn write(object: &Object) -> Result<Vec<u8>, String> {
+    let mut strings = StringTable::default();
+    let section_count = u16::try_from(object.sections.len()).map_err(|_| "too many sections for COFF".to_string())?;
+
+    // Section symbols come first, two entries each with the aux record. A
+    // weak definition takes three: the weak external, its aux record, and
+    // the fallback.
+    let is_weak_definition = |symbol: &Symbol| symbol.binding == Binding::Weak && symbol.section.is_some();
+    let mut indices = Vec::with_capacity(object.symbols.len());
+    let mut symbol_count = 2 * object.sections.len();
+    for symbol in &object.symbols {
+        indices.push(symbol_count);
+        symbol_count += if is_weak_definition(symbol) { 3 } else { 1 };
+    }
+    let symbol_index = |target: RelocTarget| match target {
+        RelocTarget::Section(index) => 2 * index,
+        RelocTarget::Symbol(index) => indices[index],
+    };
+
+    let mut headers = Vec::new();
+    let mut body = Vec::new();
+    let body_start = FILE_HEADER_SIZE + SECTION_HEADER_SIZE * object.sections.len();
+    for section in &object.sections {
+        let bss = section.kind == SectionKind::Bss;
+        let mut data = section.data.clone();
+        let mut relocs = Vec::new();
+        for reloc in &section.relocs {
+            let (kind, stored) = match reloc.kind {
+                FixupKind::Pc32 => (REL_AMD64_REL32, reloc.addend + 4),
+                FixupKind::Abs32 | FixupKind::Abs32S => (REL_AMD64_ADDR32, reloc.addend),
+                FixupKind::Abs64 => (REL_AMD64_ADDR64, reloc.addend),
+            };
+            let field = &mut data[reloc.offset..reloc.offset + reloc.kind.size()];
+            field.copy_from_slice(&stored.to_le_bytes()[..field.len()]);
+            relocs.push((reloc.offset as u32, symbol_index(reloc.target) as u32, kind));
+        }
+
+        let align = if section.kind == SectionKind::Code { section.align.max(16) } else { section.align };
+        let mut flags = characteristics(section.kind, align);
+        let raw_pointer = if bss || data.is_empty() { 0 } else { body_start + body.len() };
+        if !bss {
+            body.extend_from_slice(&data);
+        }
+        let reloc_pointer = if relocs.is_empty() { 0 } else { body_start + body.len() };
+        // Past 0xFFFF relocations, the count moves to the first entry
+        let reloc_count = if relocs.len() > 0xFFFF {
+            flags |= SCN_LNK_NRELOC_OVFL;
+            body.extend(reloc_entry(relocs.len() as u32 + 1, 0, 0));
+            0xFFFF
+        } else {
+            relocs.len() as u16
+        };
+        for (offset, symbol, kind) in relocs {
+            body.extend(reloc_entry(offset, symbol, kind));
+        }
+
+        headers.extend(section_name(&section.name, &mut strings));
+        headers.extend(0u32.to_le_bytes()); // VirtualSize
+        headers.extend(0u32.to_le_bytes()); // VirtualAddress
+        headers.extend((data.len() as u32).to_le_bytes());
+        headers.extend((raw_pointer as u32).to_le_bytes());
+        headers.extend((reloc_pointer as u32).to_le_bytes());
+        headers.extend(0u32.to_le_bytes()); // PointerToLinenumbers
+        headers.extend(reloc_count.to_le_bytes());
+        headers.extend(0u16.to_le_bytes()); // NumberOfLinenumbers
+        headers.extend(flags.to_le_bytes());
+    }
+
+    let mut symbols = Vec::new();
+    for (index, section) in object.sections.iter().enumerate() {
+        symbols.extend(symbol_entry(&section.name, 0, index as u16 + 1, SYM_CLASS_STATIC, 1, &mut strings));
+        // Aux record: length, relocation count, line numbers, checksum,
+        // COMDAT number and selection, padding
+        symbols.extend((section.data.len() as u32).to_le_bytes());
+        symbols.extend((section.relocs.len().min(0xFFFF) as u16).to_le_bytes());
+        symbols.extend([0; 12]);
+    }
+    let first_global = object
+        .symbols
+        .iter()
+        .find(|s| s.binding == Binding::Global && s.section.is_some())
+        .map_or("", |s| s.name.as_str());
+    for (symbol, &index) in object.symbols.iter().zip(&indices) {
+        let section_number = match symbol.section {
+            Some(index) => index as u16 + 1,
+            None => 0,
+        };
+        let value = u32::try_from(symbol.value).map_err(|_| format!("symbol '{}' is out of range", symbol.name))?;
+        if is_weak_definition(symbol) {
+            symbols.extend(symbol_entry(&symbol.name, 0, 0, SYM_CLASS_WEAK_EXTERNAL, 1, &mut strings));
+            symbols.extend((index as u32 + 2).to_le_bytes());
+            symbols.extend(WEAK_EXTERN_SEARCH_ALIAS.to_le_bytes());
+            symbols.extend([0; 10]);
+            let fallback = format!(".weak.{}.default.{}", symbol.name, first_global);
+            symbols.extend(symbol_entry(&fallback, value, section_number, SYM_CLASS_EXTERNAL, 0, &mut strings));
+            continue;
+        }
+        let global = symbol.binding != Binding::Local || symbol.section.is_none();
+        let class = if global { SYM_CLASS_EXTERNAL } else { SYM_CLASS_STATIC };
+        symbols.extend(symbol_entry(&symbol.name, value, section_number, class, 0, &mut strings));
+    }
+
+    let mut out = Vec::with_capacity(body_start + body.len() + symbols.len() + strings.data.len() + 4);
+    out.extend(MACHINE_AMD64.to_le_bytes());
+    out.extend(section_count.to_le_bytes());
+    out.extend(0u32.to_le_bytes()); // TimeDateStamp, zero for reproducible builds
+    out.extend(((body_start + body.len()) as u32).to_le_bytes());
+    out.extend((symbol_count as u32).to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // SizeOfOptionalHeader
+    out.extend(0u16.to_le_bytes()); // Characteristics
+    out.extend(headers);
+    out.extend(body);
+    out.extend(symbols);
+    out.extend((strings.data.len() as u32 + 4).to_le_bytes());
+    out.extend(strings.data);
+    Ok(out)
+}
+
+fn reloc_entry(offset: u32, symbol: u32, kind: u16) -> [u8; RELOC_SIZE] {
+    let mut entry = [0; RELOC_SIZE];
+    entry[..4].copy_from_slice(&offset.to_le_bytes());
+    entry[4..8].copy_from_slice(&symbol.to_le_bytes());
+    entry[8..].copy_from_slice(&kind.to_le_bytes());
+    entry
+}
+
+/// A section header's name field: the name, or `/offset` into the string
+/// table for a longer one.
+fn section_name(name: &str, strings: &mut StringTable) -> [u8; 8] {
+    let mut field = [0; 8];
+    if name.len() <= 8 {
+        field[..name.len()].copy_from_slice(name.as_bytes());
+    } else {
+        let text = format!("/{}", strings.add(name));
+        field[..text.len()].copy_from_slice(text.as_bytes());
+    }
+    field
+}
+
+/// A symbol table entry, ahead of its `aux_count` auxiliary records. Names
+/// longer than eight bytes are zero and a string table offset instead.
+fn symbol_entry(name: &str, value: u32, section: u16, class: u8, aux_count: u8, strings: &mut StringTable) -> [u8; 18] {
+    let mut entry = [0; 18];
+    if name.len() <= 8 {
+        entry[..name.len()].copy_from_slice(name.as_bytes());
+    } else {
+        entry[4..8].copy_from_slice(&strings.add(name).to_le_bytes());
+    }
+    entry[8..12].copy_from_slice(&value.to_le_bytes());
+    entry[12..14].copy_from_slice(&section.to_le_bytes());
+    // Bytes 14-15, the type, stay zero as GNU as leaves them without `.def`
+    entry[16] = class;
+    entry[17] = aux_count;
+    entry
+}
+
+/// Names longer than eight bytes. Offsets count the table's 4-byte size.
+#[derive(Default)]
+struct StringTable {
+    data: Vec<u8>,
+}
+
+impl StringTable {
+    fn add(&mut self, name: &str) -> u32 {
+        let offset = self.data.len() as u32 + 4;
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_sections_and_flags() {
+        let asm = ".text\n.globl main\nmain:\n  ret\n.section .rdata,\"dr\"\n.align 8\nc: .quad 1\n.bss\nbuf:\n  .zero 64\n";
+        let coff = write(&assemble(asm).unwrap()).unwrap();
+        assert_eq!(u16_at(&coff, 0), MACHINE_AMD64);
+        assert_eq!(u16_at(&coff, 2), 3);
+        // 3 sections with aux records, then main, c, and buf
+        assert_eq!(u32_at(&coff, 12), 9);
+
+        let header = |i: usize| &coff[FILE_HEADER_SIZE + i * SECTION_HEADER_SIZE..][..SECTION_HEADER_SIZE];
+        assert_eq!(&header(0)[..8], b".text\0\0\0");
+        assert_eq!(u32_at(header(0), 36), 0x6050_0020);
+        assert_eq!(&header(1)[..8], b".rdata\0\0");
+        assert_eq!(u32_at(header(1), 36), 0x4040_0040);
+        // .bss: 64 bytes of size, no contents in the file
+        assert_eq!(u32_at(header(2), 16), 64);
+        assert_eq!(u32_at(header(2), 20), 0);
+        assert_eq!(u32_at(header(2), 36), 0xC010_0080);
+    }
+
+    #[test]
+    fn relocations_store_their_addend() {
+        let asm = ".text\nf:\n  mov eax, DWORD PTR counter_value[rip+8]\n  call printf\n";
+        let coff = write(&assemble(asm).unwrap()).unwrap();
+        let text = FILE_HEADER_SIZE + SECTION_HEADER_SIZE;
+        assert_eq!(&coff[text..text + 6], &[0x8B, 0x05, 8, 0, 0, 0]);
+        assert_eq!(&coff[text + 6..text + 11], &[0xE8, 0, 0, 0, 0]);
+
+        let relocs = text + 11;
+        assert_eq!(u16_at(&coff[FILE_HEADER_SIZE..], 32), 2);
+        assert_eq!(u32_at(&coff, relocs), 2);
+        // Symbol 0 and 1 are .text and its aux record; then f, then the
+        // undefined symbols in order of use
+        assert_eq!(u32_at(&coff, relocs + 4), 3);
+        assert_eq!(u16_at(&coff, relocs + 8), REL_AMD64_REL32);
+        assert_eq!(u32_at(&coff, relocs + 14), 4);
+
+        // `counter_value` is longer than eight bytes: zero, then its offset
+        let symbols = u32_at(&coff, 8) as usize;
+        let entry = &coff[symbols + 3 * 18..][..18];
+        assert_eq!(u32_at(entry, 0), 0);
+        let strings = symbols + 5 * 18;
+        let offset = strings + u32_at(entry, 4) as usize;
+        assert_eq!(&coff[offset..offset + 14], b"counter_value\0");
+        assert_eq!(entry[16], SYM_CLASS_EXTERNAL);
+    }
+
+    #[test]
+    fn weak_definitions_get_a_fallback() {
+        let asm = ".text\n.globl get_value\n.weak get_value\nget_value:\n  ret\n.globl main\nmain:\n  call get_value\n";
+        let coff = write(&assemble(asm).unwrap()).unwrap();
+        let symbols = u32_at(&coff, 8) as usize;
+        assert_eq!(u32_at(&coff, 12), 6);
+        let weak = &coff[symbols + 2 * 18..][..18];
+        assert_eq!(u32_at(weak, 0), 0);
+        assert_eq!(u16_at(weak, 12), 0);
+        assert_eq!((weak[16], weak[17]), (SYM_CLASS_WEAK_EXTERNAL, 1));
+        // The aux record names the fallback, the entry after it
+        assert_eq!(u32_at(&coff, symbols + 3 * 18), 4);
+        assert_eq!(u32_at(&coff, symbols + 3 * 18 + 4), WEAK_EXTERN_SEARCH_ALIAS);
+        // The call relocates against the weak external, not the definition
+        let relocs = FILE_HEADER_SIZE + SECTION_HEADER_SIZE + 6;
+        assert_eq!(u32_at(&coff, relocs + 4), 2);
+    }
+}
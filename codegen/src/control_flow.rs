@@ -3,8 +3,9 @@
 
 use crate::x86::{X86Reg, X86Operand, X86Instr};
 use model::Type;
-use ir::{Function as IrFunction, BlockId, Instruction as IrInstruction, Terminator as IrTerminator};
+use ir::{Function as IrFunction, BlockId, Instruction as IrInstruction, Operand, Terminator as IrTerminator};
 use crate::function::FunctionGenerator;
+use crate::calling_convention::AggregatePassing;
 
 impl<'a> FunctionGenerator<'a> {
     pub(crate) fn get_current_block_id(&self) -> BlockId {
@@ -23,11 +24,12 @@ impl<'a> FunctionGenerator<'a> {
                          let d_op = self.var_to_op(*dest);
                          // Handle alloca phi
                          if let Some(off) = self.alloca_buffers.get(src_var) {
-                              self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Rbp, *off)));
+                              self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, *off)));
                               self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
                          } else {
                               let s_op = self.var_to_op(*src_var);
-                              if matches!(d_op, X86Operand::FloatMem(..)) {
+                              if self.gen_xmm_move(*dest, &d_op, &s_op) {
+                              } else if matches!(d_op, X86Operand::FloatMem(..)) {
                                   self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), s_op));
                                   self.asm.push(X86Instr::Movss(d_op, X86Operand::Reg(X86Reg::Xmm0)));
                               } else if matches!(d_op, X86Operand::DoubleMem(..)) {
@@ -35,8 +37,8 @@ impl<'a> FunctionGenerator<'a> {
                                   self.asm.push(X86Instr::Movsd(d_op, X86Operand::Reg(X86Reg::Xmm0)));
                               } else {
                                   // Check if we can emit a direct move (no intermediate register needed)
-                                  let src_is_mem = matches!(s_op, X86Operand::Mem(..) | X86Operand::DwordMem(..) | X86Operand::WordMem(..) | X86Operand::ByteMem(..) | X86Operand::FloatMem(..) | X86Operand::DoubleMem(..) | X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..));
-                                  let dst_is_mem = matches!(d_op, X86Operand::Mem(..) | X86Operand::DwordMem(..) | X86Operand::WordMem(..) | X86Operand::ByteMem(..) | X86Operand::FloatMem(..) | X86Operand::DoubleMem(..) | X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..));
+                                  let src_is_mem = matches!(s_op, X86Operand::Mem(..) | X86Operand::DwordMem(..) | X86Operand::WordMem(..) | X86Operand::ByteMem(..) | X86Operand::FloatMem(..) | X86Operand::DoubleMem(..) | X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..) | X86Operand::GlobalWordMem(..) | X86Operand::GlobalByteMem(..));
+                                  let dst_is_mem = matches!(d_op, X86Operand::Mem(..) | X86Operand::DwordMem(..) | X86Operand::WordMem(..) | X86Operand::ByteMem(..) | X86Operand::FloatMem(..) | X86Operand::DoubleMem(..) | X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..) | X86Operand::GlobalWordMem(..) | X86Operand::GlobalByteMem(..));
                                   
                                   if !src_is_mem || !dst_is_mem {
                                       // At least one is a register or immediate — direct move is valid
@@ -87,54 +89,33 @@ impl<'a> FunctionGenerator<'a> {
         match term {
             IrTerminator::Ret(op) => {
                 if let Some(o) = op {
-                    // Check for struct return type
-                    let struct_class = crate::call_ops::classify_struct_arg(self, &func.return_type);
-                    if let Some(class) = struct_class {
-                        // Struct return: load eightbytes into RAX[:RDX]
-                        let val = self.operand_to_op(o);
-                        match class {
-                            crate::call_ops::StructArgClass::OneReg => {
-                                // Load first eightbyte from struct buffer
-                                if let ir::Operand::Var(v) = o {
-                                    if let Some(&off) = self.alloca_buffers.get(v) {
-                                        self.asm.push(X86Instr::Mov(
-                                            X86Operand::Reg(X86Reg::Rax),
-                                            X86Operand::Mem(X86Reg::Rbp, off),
-                                        ));
-                                    } else {
-                                        self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), val));
-                                    }
-                                } else {
-                                    self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), val));
-                                }
-                            }
-                            crate::call_ops::StructArgClass::TwoReg => {
-                                if let ir::Operand::Var(v) = o {
-                                    if let Some(&off) = self.alloca_buffers.get(v) {
-                                        self.asm.push(X86Instr::Mov(
-                                            X86Operand::Reg(X86Reg::Rax),
-                                            X86Operand::Mem(X86Reg::Rbp, off),
-                                        ));
-                                        self.asm.push(X86Instr::Mov(
-                                            X86Operand::Reg(X86Reg::Rdx),
-                                            X86Operand::Mem(X86Reg::Rbp, off + 8),
-                                        ));
-                                    } else {
-                                        self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), val));
-                                    }
-                                } else {
-                                    self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), val));
+                    // A struct or union is returned in registers, eightbyte by
+                    // eightbyte, or copied to where the hidden pointer points
+                    if let Some(passing) = crate::call_ops::classify_aggregate(self, &func.return_type) {
+                        match passing {
+                            AggregatePassing::Registers(classes) => {
+                                crate::call_ops::load_eightbytes_address(self, o, &func.return_type);
+                                for (i, (class, reg)) in crate::call_ops::returned_eightbytes(&classes).into_iter().enumerate() {
+                                    let eightbyte = X86Operand::Mem(X86Reg::Rdx, 8 * i as i32);
+                                    self.asm.push(crate::call_ops::move_eightbyte(class, X86Operand::Reg(reg), eightbyte));
                                 }
                             }
-                            crate::call_ops::StructArgClass::Memory => {
-                                // Large struct already written via hidden pointer
+                            AggregatePassing::Memory | AggregatePassing::Reference => {
+                                let size = self.get_type_size(&func.return_type);
+                                let return_pointer = self.return_pointer.expect("a hidden return pointer is saved in the prologue");
+                                self.load_address_into(o, X86Reg::Rdx);
+                                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, return_pointer)));
+                                crate::memory_ops::copy_memory(self, size);
                             }
                         }
                     } else {
                     let is_float_return = matches!(func.return_type, Type::Float | Type::Double);
                     let is_double_return = matches!(func.return_type, Type::Double);
                     if is_float_return {
-                        let label = self.operand_to_op(o);
+                        let label = match o {
+                            ir::Operand::FloatConstant(f) => X86Operand::RipRelLabel(self.get_or_create_float_const(*f, is_double_return)),
+                            _ => self.operand_to_op(o),
+                        };
                         if is_double_return {
                             self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), label));
                         } else {
@@ -158,18 +139,7 @@ impl<'a> FunctionGenerator<'a> {
                     }
                 }
                 
-                if !self.current_saved_regs.is_empty() {
-                    let offset = (self.current_saved_regs.len() * 8) as i32;
-                    self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rsp), X86Operand::Mem(X86Reg::Rbp, -offset)));
-                    for reg in self.current_saved_regs.iter().rev() {
-                        self.asm.push(X86Instr::Pop(reg.clone()));
-                    }
-                    self.asm.push(X86Instr::Pop(X86Reg::Rbp));
-                } else {
-                    self.asm.push(X86Instr::Leave);
-                }
-                
-                self.asm.push(X86Instr::Ret);
+                self.gen_epilogue();
             }
             IrTerminator::Br(id) => {
                 let current_bid = self.get_current_block_id();
@@ -184,6 +154,15 @@ impl<'a> FunctionGenerator<'a> {
             } => {
                 let c_op = self.operand_to_op(cond);
                 let current_bid = self.get_current_block_id();
+
+                // A constant condition, as unoptimized code has for
+                // `do { } while (0)`, picks its target here
+                if let X86Operand::Imm(value) = c_op {
+                    let target = if value != 0 { *then_block } else { *else_block };
+                    self.resolve_phis(target, current_bid, func);
+                    self.asm.push(X86Instr::Jmp(format!("{}_{}", func_name, target.0)));
+                    return;
+                }
                 
                 if let X86Operand::Reg(reg) = &c_op {
                     self.asm.push(X86Instr::Test(X86Operand::Reg(reg.clone()), X86Operand::Reg(reg.clone())));
@@ -199,27 +178,195 @@ impl<'a> FunctionGenerator<'a> {
                 self.resolve_phis(*then_block, current_bid, func);
                 self.asm.push(X86Instr::Jmp(format!("{}_{}", func_name, then_block.0)));
             }
-            IrTerminator::IndirectBr { target } => {
-                let t_op = self.operand_to_op(target);
-                if t_op != X86Operand::Reg(X86Reg::Rax) {
-                    self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), t_op));
+            IrTerminator::Switch { value, cases, default } => {
+                let current_bid = self.get_current_block_id();
+                if let Operand::Constant(v) = value {
+                    let target = cases.iter().find(|&&(case, _)| case == *v).map_or(*default, |&(_, b)| b);
+                    self.resolve_phis(target, current_bid, func);
+                    self.asm.push(X86Instr::Jmp(format!("{}_{}", func_name, target.0)));
+                    return;
+                }
+
+                // An edge into a block with phis goes through a label of its
+                // own, whose copies follow the dispatch
+                let has_phis = |id: BlockId| func.blocks.iter().find(|b| b.id == id)
+                    .is_some_and(|b| b.instructions.iter().any(|inst| matches!(inst, IrInstruction::Phi { .. })));
+                let targets = IrTerminator::switch_targets(cases, *default);
+                let edge_label = |id: BlockId| if has_phis(id) {
+                    format!("switch_{}_{}_{}", func_name, current_bid.0, id.0)
+                } else {
+                    format!("{}_{}", func_name, id.0)
+                };
+
+                // A 32-bit value may sit sign- or zero-extended in a 64-bit
+                // register, so it is compared in EAX against case values
+                // truncated to 32 bits
+                let dword = self.is_dword_int(value);
+                let (ax, v_op) = if dword {
+                    (X86Reg::Eax, crate::function::narrow_to_dword(self.operand_to_op(value)))
+                } else {
+                    (X86Reg::Rax, self.materialize_operand(value, X86Reg::Rax))
+                };
+                self.asm.push(X86Instr::Mov(X86Operand::Reg(ax.clone()), v_op));
+                let keyed: Vec<(i64, BlockId)> = cases.iter()
+                    .map(|&(v, b)| (if dword { i64::from(v as i32) } else { v }, b))
+                    .collect();
+
+                match jump_table(&keyed, *default) {
+                    Some((low, entries)) => {
+                        // Rebase the value on the lowest case; one unsigned
+                        // compare then sends values on either side to the default
+                        if low != 0 {
+                            if i32::try_from(low).is_ok() {
+                                self.asm.push(X86Instr::Sub(X86Operand::Reg(ax.clone()), X86Operand::Imm(low)));
+                            } else {
+                                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), X86Operand::Imm(low)));
+                                self.asm.push(X86Instr::Sub(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx)));
+                            }
+                        }
+                        self.asm.push(X86Instr::Cmp(X86Operand::Reg(ax), X86Operand::Imm(entries.len() as i64 - 1)));
+                        self.asm.push(X86Instr::Jcc("a".to_string(), edge_label(*default)));
+
+                        // Entries are offsets from the table, so it needs no
+                        // relocations at load time
+                        let table = format!(".L{}_switch_{}", func_name, current_bid.0);
+                        self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rcx), X86Operand::RipRelLabel(table.clone())));
+                        self.asm.push(X86Instr::Raw("movsxd rax, DWORD PTR [rcx + rax*4]".to_string()));
+                        self.asm.push(X86Instr::Add(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx)));
+                        self.asm.push(X86Instr::Raw("jmp rax".to_string()));
+
+                        let (rodata, text) = match self.target.platform {
+                            model::Platform::Linux => (".section .rodata".to_string(), ".text".to_string()),
+                            model::Platform::Windows => (".section .rdata,\"dr\"".to_string(), ".text".to_string()),
+                        };
+                        let text = func.attributes.iter().find_map(|attr| match attr {
+                            model::Attribute::Section(name) => Some(crate::code_section_directive(self.target.platform, name)),
+                            _ => None,
+                        }).unwrap_or(text);
+                        self.asm.push(X86Instr::Raw(rodata));
+                        self.asm.push(X86Instr::JumpTable(table, entries.into_iter().map(edge_label).collect()));
+                        self.asm.push(X86Instr::Raw(text));
+                    }
+                    None => {
+                        for (v, b) in keyed {
+                            if i32::try_from(v).is_ok() {
+                                self.asm.push(X86Instr::Cmp(X86Operand::Reg(ax.clone()), X86Operand::Imm(v)));
+                            } else {
+                                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), X86Operand::Imm(v)));
+                                self.asm.push(X86Instr::Cmp(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx)));
+                            }
+                            self.asm.push(X86Instr::Jcc("e".to_string(), edge_label(b)));
+                        }
+                        self.asm.push(X86Instr::Jmp(edge_label(*default)));
+                    }
+                }
+
+                for target in targets.into_iter().filter(|&t| has_phis(t)) {
+                    self.asm.push(X86Instr::Label(edge_label(target)));
+                    self.resolve_phis(target, current_bid, func);
+                    self.asm.push(X86Instr::Jmp(format!("{}_{}", func_name, target.0)));
                 }
+            }
+            IrTerminator::IndirectBr { target } => {
+                // `goto *&&label` jumps to the label's address, not through it
+                self.load_address_into(target, X86Reg::Rax);
                 self.asm.push(X86Instr::Raw("    jmp rax".to_string()));
             }
             _ => {
                 // Trap/Unreachable -> Ret
-                if !self.current_saved_regs.is_empty() {
-                     let offset = (self.current_saved_regs.len() * 8) as i32;
-                     self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rsp), X86Operand::Mem(X86Reg::Rbp, -offset)));
-                     for reg in self.current_saved_regs.iter().rev() {
-                         self.asm.push(X86Instr::Pop(reg.clone()));
-                     }
-                } else {
-                     self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rsp), X86Operand::Reg(X86Reg::Rbp)));
+                self.gen_epilogue();
+            }
+        }
+    }
+
+    /// Restore the callee-saved registers and return.
+    fn gen_epilogue(&mut self) {
+        let offset = (self.current_saved_regs.len() * 8) as i32;
+        if self.frame_pointer {
+            if !self.current_saved_regs.is_empty() {
+                self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rsp), X86Operand::Mem(X86Reg::Rbp, -offset)));
+                for reg in self.current_saved_regs.iter().rev() {
+                    self.asm.push(X86Instr::Pop(reg.clone()));
                 }
                 self.asm.push(X86Instr::Pop(X86Reg::Rbp));
-                self.asm.push(X86Instr::Ret);
+            } else {
+                self.asm.push(X86Instr::Leave);
+            }
+            self.asm.push(X86Instr::Ret);
+            return;
+        }
+
+        // Without a frame pointer the CFA follows RSP back up, and the code
+        // after this return still has the full frame.
+        let cfi = matches!(self.target.platform, model::Platform::Linux);
+        if cfi {
+            self.asm.push(X86Instr::Raw(".cfi_remember_state".to_string()));
+        }
+        // Just below the saved registers, where RBP would have been pushed
+        self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rsp), X86Operand::Mem(X86Reg::Frame, 8 - offset)));
+        if cfi {
+            self.asm.push(X86Instr::Raw(format!(".cfi_def_cfa_offset {}", 8 + offset)));
+        }
+        for (i, reg) in self.current_saved_regs.clone().iter().enumerate().rev() {
+            self.asm.push(X86Instr::Pop(reg.clone()));
+            if cfi {
+                self.asm.push(X86Instr::Raw(format!(".cfi_def_cfa_offset {}", 8 + 8 * i)));
             }
         }
+        self.asm.push(X86Instr::Ret);
+        if cfi {
+            self.asm.push(X86Instr::Raw(".cfi_restore_state".to_string()));
+        }
+    }
+}
+
+/// The lowest case value of a switch dense enough for a jump table, and
+/// the target of every value from there to the highest, `default` filling
+/// the gaps. `None` when a chain of compares is the better choice: for
+/// fewer than four cases, or when under a quarter of the table would hold
+/// cases.
+pub(crate) fn jump_table(cases: &[(i64, BlockId)], default: BlockId) -> Option<(i64, Vec<BlockId>)> {
+    const MIN_CASES: usize = 4;
+    const MAX_ENTRIES: i128 = 4096;
+    if cases.len() < MIN_CASES {
+        return None;
+    }
+    let low = cases.iter().map(|&(v, _)| v).min()?;
+    let high = cases.iter().map(|&(v, _)| v).max()?;
+    let span = i128::from(high) - i128::from(low) + 1;
+    if span > MAX_ENTRIES || span > 4 * cases.len() as i128 {
+        return None;
+    }
+    let mut entries = vec![default; span as usize];
+    for &(v, block) in cases {
+        entries[(i128::from(v) - i128::from(low)) as usize] = block;
+    }
+    Some((low, entries))
+}
+
+/// `func` with every label address (`&&label`, the IR's `__label_addr_NAME`
+/// global) replaced by the assembly label of that label's block. Like any
+/// global it is then materialized with `lea reg, [rip+func_N]`, giving the
+/// code address a computed goto jumps to.
+pub(crate) fn resolve_label_addresses(func: &IrFunction) -> IrFunction {
+    let mut func = func.clone();
+    let block_label = |op: &mut Operand| {
+        if let Operand::Global(name) = op {
+            if let Some(block) = name.strip_prefix("__label_addr_").and_then(|label| func.labels.get(label)) {
+                *op = Operand::Global(format!("{}_{}", func.name, block.0));
+            }
+        }
+    };
+    let mut blocks = std::mem::take(&mut func.blocks);
+    for block in &mut blocks {
+        for inst in &mut block.instructions {
+            inst.for_each_operand_mut(block_label);
+        }
+        match &mut block.terminator {
+            IrTerminator::IndirectBr { target: op } | IrTerminator::CondBr { cond: op, .. } | IrTerminator::Ret(Some(op)) => block_label(op),
+            _ => {}
+        }
     }
+    func.blocks = blocks;
+    func
 }
@@ -0,0 +1,664 @@
+// DWARF debug info for `-g`
+//
+// Line numbers are left to the assembler: every source file gets a `.file`
+// number up front, and `FunctionGenerator` emits a `.loc` wherever the source
+// line changes, from which GNU as builds `.debug_line`. The rest is written
+// here as DWARF 4:
+//
+// - `.debug_info` holds one compile unit, a subprogram per function with its
+//   parameters and locals, and the types they use. Only variables that kept
+//   their stack slot are described; those promoted to registers are not.
+// - The frame base is RBP itself (`DW_OP_breg6 0`), so a variable's location
+//   is `DW_OP_fbreg` with its slot's offset from RBP. It is valid once the
+//   prologue has set up RBP. Without a frame pointer it is RSP plus the frame
+//   size (`DW_OP_breg7`), where RBP would have pointed, valid once the
+//   prologue has allocated the frame.
+// - The compile unit covers its functions through `.debug_ranges`, since a
+//   function may sit in a section of its own.
+// - Types are numbered as they are first referenced and written after the
+//   subprograms; a struct is numbered before its members, so a struct that
+//   points to itself refers back to its own entry.
+
+use std::collections::HashMap;
+
+use ir::{Function as IrFunction, Instruction, Operand, VarId};
+use model::{FileId, SourceMap, Span, Type, TypeLayout};
+
+use crate::globals::escape_asciz;
+
+const DW_TAG_ARRAY_TYPE: u32 = 0x01;
+const DW_TAG_FORMAL_PARAMETER: u32 = 0x05;
+const DW_TAG_MEMBER: u32 = 0x0d;
+const DW_TAG_POINTER_TYPE: u32 = 0x0f;
+const DW_TAG_COMPILE_UNIT: u32 = 0x11;
+const DW_TAG_STRUCTURE_TYPE: u32 = 0x13;
+const DW_TAG_SUBROUTINE_TYPE: u32 = 0x15;
+const DW_TAG_UNION_TYPE: u32 = 0x17;
+const DW_TAG_SUBRANGE_TYPE: u32 = 0x21;
+const DW_TAG_BASE_TYPE: u32 = 0x24;
+const DW_TAG_SUBPROGRAM: u32 = 0x2e;
+const DW_TAG_VARIABLE: u32 = 0x34;
+
+const DW_AT_LOCATION: u32 = 0x02;
+const DW_AT_NAME: u32 = 0x03;
+const DW_AT_BYTE_SIZE: u32 = 0x0b;
+const DW_AT_BIT_SIZE: u32 = 0x0d;
+const DW_AT_STMT_LIST: u32 = 0x10;
+const DW_AT_LOW_PC: u32 = 0x11;
+const DW_AT_HIGH_PC: u32 = 0x12;
+const DW_AT_LANGUAGE: u32 = 0x13;
+const DW_AT_COMP_DIR: u32 = 0x1b;
+const DW_AT_COUNT: u32 = 0x37;
+const DW_AT_DATA_MEMBER_LOCATION: u32 = 0x38;
+const DW_AT_DECL_FILE: u32 = 0x3a;
+const DW_AT_DECL_LINE: u32 = 0x3b;
+const DW_AT_DECLARATION: u32 = 0x3c;
+const DW_AT_ENCODING: u32 = 0x3e;
+const DW_AT_EXTERNAL: u32 = 0x3f;
+const DW_AT_FRAME_BASE: u32 = 0x40;
+const DW_AT_TYPE: u32 = 0x49;
+const DW_AT_RANGES: u32 = 0x55;
+const DW_AT_DATA_BIT_OFFSET: u32 = 0x6b;
+
+const DW_FORM_ADDR: u32 = 0x01;
+const DW_FORM_DATA2: u32 = 0x05;
+const DW_FORM_DATA8: u32 = 0x07;
+const DW_FORM_STRING: u32 = 0x08;
+const DW_FORM_DATA1: u32 = 0x0b;
+const DW_FORM_FLAG: u32 = 0x0c;
+const DW_FORM_UDATA: u32 = 0x0f;
+const DW_FORM_REF4: u32 = 0x13;
+const DW_FORM_SEC_OFFSET: u32 = 0x17;
+const DW_FORM_EXPRLOC: u32 = 0x18;
+const DW_FORM_FLAG_PRESENT: u32 = 0x19;
+
+const DW_ATE_BOOLEAN: u8 = 0x02;
+const DW_ATE_COMPLEX_FLOAT: u8 = 0x03;
+const DW_ATE_FLOAT: u8 = 0x04;
+const DW_ATE_SIGNED: u8 = 0x05;
+const DW_ATE_SIGNED_CHAR: u8 = 0x06;
+const DW_ATE_UNSIGNED: u8 = 0x08;
+const DW_ATE_UNSIGNED_CHAR: u8 = 0x08;
+
+const DW_LANG_C99: u16 = 0x0c;
+const DW_OP_BREG6: u8 = 0x76;
+const DW_OP_BREG7: u8 = 0x77;
+const DW_OP_FBREG: u8 = 0x91;
+
+// Abbreviation codes, one per shape of entry
+const ABBREV_COMPILE_UNIT: u32 = 1;
+const ABBREV_SUBPROGRAM: u32 = 2;
+const ABBREV_VOID_SUBPROGRAM: u32 = 3;
+const ABBREV_PARAMETER: u32 = 4;
+const ABBREV_VARIABLE: u32 = 5;
+const ABBREV_BASE_TYPE: u32 = 6;
+const ABBREV_POINTER: u32 = 7;
+const ABBREV_VOID_POINTER: u32 = 8;
+const ABBREV_ARRAY: u32 = 9;
+const ABBREV_SUBRANGE: u32 = 10;
+const ABBREV_STRUCT: u32 = 11;
+const ABBREV_UNION: u32 = 12;
+const ABBREV_MEMBER: u32 = 13;
+const ABBREV_BITFIELD_MEMBER: u32 = 14;
+const ABBREV_SUBROUTINE: u32 = 15;
+const ABBREV_VOID_SUBROUTINE: u32 = 16;
+const ABBREV_STRUCT_DECLARATION: u32 = 17;
+const ABBREV_UNION_DECLARATION: u32 = 18;
+
+/// Code, tag, whether entries have children, and (attribute, form) pairs.
+type Abbrev = (u32, u32, bool, &'static [(u32, u32)]);
+
+const ABBREVS: &[Abbrev] = &[
+    (ABBREV_COMPILE_UNIT, DW_TAG_COMPILE_UNIT, true, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_LANGUAGE, DW_FORM_DATA2),
+        (DW_AT_COMP_DIR, DW_FORM_STRING),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_RANGES, DW_FORM_SEC_OFFSET),
+        (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+    ]),
+    (ABBREV_SUBPROGRAM, DW_TAG_SUBPROGRAM, true, &[
+        (DW_AT_EXTERNAL, DW_FORM_FLAG),
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECL_FILE, DW_FORM_UDATA),
+        (DW_AT_DECL_LINE, DW_FORM_UDATA),
+        (DW_AT_TYPE, DW_FORM_REF4),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_HIGH_PC, DW_FORM_DATA8),
+        (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+    ]),
+    (ABBREV_VOID_SUBPROGRAM, DW_TAG_SUBPROGRAM, true, &[
+        (DW_AT_EXTERNAL, DW_FORM_FLAG),
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECL_FILE, DW_FORM_UDATA),
+        (DW_AT_DECL_LINE, DW_FORM_UDATA),
+        (DW_AT_LOW_PC, DW_FORM_ADDR),
+        (DW_AT_HIGH_PC, DW_FORM_DATA8),
+        (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+    ]),
+    (ABBREV_PARAMETER, DW_TAG_FORMAL_PARAMETER, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECL_FILE, DW_FORM_UDATA),
+        (DW_AT_DECL_LINE, DW_FORM_UDATA),
+        (DW_AT_TYPE, DW_FORM_REF4),
+        (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+    ]),
+    (ABBREV_VARIABLE, DW_TAG_VARIABLE, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECL_FILE, DW_FORM_UDATA),
+        (DW_AT_DECL_LINE, DW_FORM_UDATA),
+        (DW_AT_TYPE, DW_FORM_REF4),
+        (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+    ]),
+    (ABBREV_BASE_TYPE, DW_TAG_BASE_TYPE, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_ENCODING, DW_FORM_DATA1),
+        (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+    ]),
+    (ABBREV_POINTER, DW_TAG_POINTER_TYPE, false, &[
+        (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+        (DW_AT_TYPE, DW_FORM_REF4),
+    ]),
+    (ABBREV_VOID_POINTER, DW_TAG_POINTER_TYPE, false, &[(DW_AT_BYTE_SIZE, DW_FORM_DATA1)]),
+    (ABBREV_ARRAY, DW_TAG_ARRAY_TYPE, true, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+    (ABBREV_SUBRANGE, DW_TAG_SUBRANGE_TYPE, false, &[(DW_AT_COUNT, DW_FORM_UDATA)]),
+    (ABBREV_STRUCT, DW_TAG_STRUCTURE_TYPE, true, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_BYTE_SIZE, DW_FORM_UDATA),
+    ]),
+    (ABBREV_UNION, DW_TAG_UNION_TYPE, true, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_BYTE_SIZE, DW_FORM_UDATA),
+    ]),
+    (ABBREV_MEMBER, DW_TAG_MEMBER, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_TYPE, DW_FORM_REF4),
+        (DW_AT_DATA_MEMBER_LOCATION, DW_FORM_UDATA),
+    ]),
+    (ABBREV_BITFIELD_MEMBER, DW_TAG_MEMBER, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_TYPE, DW_FORM_REF4),
+        (DW_AT_BIT_SIZE, DW_FORM_UDATA),
+        (DW_AT_DATA_BIT_OFFSET, DW_FORM_UDATA),
+    ]),
+    (ABBREV_SUBROUTINE, DW_TAG_SUBROUTINE_TYPE, false, &[(DW_AT_TYPE, DW_FORM_REF4)]),
+    (ABBREV_VOID_SUBROUTINE, DW_TAG_SUBROUTINE_TYPE, false, &[]),
+    (ABBREV_STRUCT_DECLARATION, DW_TAG_STRUCTURE_TYPE, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECLARATION, DW_FORM_FLAG_PRESENT),
+    ]),
+    (ABBREV_UNION_DECLARATION, DW_TAG_UNION_TYPE, false, &[
+        (DW_AT_NAME, DW_FORM_STRING),
+        (DW_AT_DECLARATION, DW_FORM_FLAG_PRESENT),
+    ]),
+];
+
+/// A parameter or local variable that lives in a stack slot.
+struct Local {
+    name: String,
+    is_parameter: bool,
+    file: u32,
+    line: usize,
+    ty: Type,
+    /// Offset of the slot from RBP
+    offset: i32,
+}
+
+struct Subprogram {
+    name: String,
+    is_external: bool,
+    file: u32,
+    line: usize,
+    return_type: Type,
+    /// Frame size above RSP when there is no frame pointer
+    frame_size: Option<i32>,
+    locals: Vec<Local>,
+}
+
+/// Debug info for one translation unit, collected while its functions are
+/// generated.
+pub(crate) struct DebugInfo {
+    sources: SourceMap,
+    comp_dir: String,
+    /// Source files in `.file` order; file `n` is `files[n - 1]`
+    files: Vec<String>,
+    /// Last `.loc` file and line emitted in the current function
+    last_line: Option<(u32, usize)>,
+    subprograms: Vec<Subprogram>,
+    /// Every function generated, in output order, for the unit's ranges
+    functions: Vec<String>,
+}
+
+/// Label closing the code of `function`, the end of its address range.
+pub(crate) fn function_end_label(function: &str) -> String {
+    format!(".Lfunc_end_{}", function)
+}
+
+impl DebugInfo {
+    /// `sources` resolves the spans of the IR; the unit is named after its
+    /// first file. `comp_dir` is the directory relative file names are from.
+    pub(crate) fn new(sources: SourceMap, comp_dir: String) -> Self {
+        Self { sources, comp_dir, files: Vec::new(), last_line: None, subprograms: Vec::new(), functions: Vec::new() }
+    }
+
+    /// Number every source file the functions' spans refer to, and return
+    /// the `.file` directives declaring them.
+    pub(crate) fn file_directives(&mut self, functions: &[IrFunction]) -> String {
+        self.files.clear();
+        self.subprograms.clear();
+        self.functions.clear();
+        let mut files: Vec<String> = Vec::new();
+        for func in functions {
+            let spans = func.blocks.iter().flat_map(|b| b.spans.iter().chain(std::iter::once(&b.terminator_span)));
+            for span in std::iter::once(&func.span).chain(spans) {
+                if let Some((name, ..)) = self.presumed(*span)
+                    && !files.iter().any(|f| f == name)
+                {
+                    files.push(name.to_string());
+                }
+            }
+        }
+        self.files = files;
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!(".file {} \"{}\"\n", i + 1, escape_asciz(name)))
+            .collect()
+    }
+
+    fn presumed(&self, span: Span) -> Option<(&str, usize, usize)> {
+        // Instructions the compiler made up carry no span
+        if span == Span::default() {
+            return None;
+        }
+        let (name, pos) = self.sources.presumed(span.file_id, span.start)?;
+        Some((name, pos.line, pos.col))
+    }
+
+    /// `.file` number, line, and column of the start of `span`.
+    fn position(&self, span: Span) -> Option<(u32, usize, usize)> {
+        let (name, line, col) = self.presumed(span)?;
+        let index = self.files.iter().position(|f| f == name)?;
+        Some((index as u32 + 1, line, col))
+    }
+
+    /// Start the line table of a new function.
+    pub(crate) fn begin_function(&mut self) {
+        self.last_line = None;
+    }
+
+    /// The `.loc` to emit before code from `span`, if it moves to another line.
+    pub(crate) fn step(&mut self, span: Span) -> Option<(u32, usize, usize)> {
+        let (file, line, col) = self.position(span)?;
+        if self.last_line == Some((file, line)) {
+            return None;
+        }
+        self.last_line = Some((file, line));
+        Some((file, line, col))
+    }
+
+    /// Describe a generated function, given the RBP offset of each alloca
+    /// that got a stack slot. A parameter's alloca is the one the entry
+    /// block stores the incoming value to. `frame_size` is given when the
+    /// function has no frame pointer.
+    pub(crate) fn add_function(&mut self, func: &IrFunction, slots: &HashMap<VarId, i32>, frame_size: Option<i32>) {
+        self.functions.push(func.name.clone());
+        let Some((file, line, _)) = self.position(func.span) else {
+            return;
+        };
+        let param_index = |var: &VarId| func.params.iter().position(|(_, p)| p == var);
+        let mut parameters = Vec::new();
+        let mut variables = Vec::new();
+        for block in &func.blocks {
+            for (i, inst) in block.instructions.iter().enumerate() {
+                let Instruction::Alloca { dest, r#type } = inst else {
+                    continue;
+                };
+                let (Some(name), Some(&offset)) = (func.local_names.get(dest), slots.get(dest)) else {
+                    continue;
+                };
+                let (file, line) = self.position(block.span_of(i)).map_or((file, line), |(f, l, _)| (f, l));
+                let param = func
+                    .blocks
+                    .iter()
+                    .find(|b| b.id == func.entry_block)
+                    .and_then(|entry| {
+                        entry.instructions.iter().find_map(|inst| match inst {
+                            Instruction::Store { addr: Operand::Var(addr), src: Operand::Var(src), .. } if addr == dest => {
+                                param_index(src)
+                            }
+                            _ => None,
+                        })
+                    });
+                let local = Local { name: name.clone(), is_parameter: param.is_some(), file, line, ty: r#type.clone(), offset };
+                match param {
+                    Some(index) => parameters.push((index, local)),
+                    None => variables.push(local),
+                }
+            }
+        }
+        parameters.sort_by_key(|(index, _)| *index);
+        let locals = parameters.into_iter().map(|(_, local)| local).chain(variables).collect();
+        self.subprograms.push(Subprogram {
+            name: func.name.clone(),
+            is_external: !func.is_static,
+            file,
+            line,
+            return_type: func.return_type.clone(),
+            frame_size,
+            locals,
+        });
+    }
+
+    /// The `.debug_abbrev`, `.debug_info`, and `.debug_ranges` sections,
+    /// and the start of `.debug_line` for the assembler to fill.
+    pub(crate) fn sections(&self, layout: &TypeLayout) -> String {
+        let mut out = String::new();
+
+        out.push_str("\n.section .debug_abbrev,\"\",@progbits\n");
+        out.push_str(".Ldebug_abbrev0:\n");
+        for (code, tag, has_children, attributes) in ABBREVS {
+            out.push_str(&format!("    .uleb128 {}\n    .uleb128 0x{:x}\n", code, tag));
+            out.push_str(&format!("    .byte {}\n", u8::from(*has_children)));
+            for (attribute, form) in *attributes {
+                out.push_str(&format!("    .uleb128 0x{:x}\n    .uleb128 0x{:x}\n", attribute, form));
+            }
+            out.push_str("    .byte 0\n    .byte 0\n");
+        }
+        out.push_str("    .byte 0\n");
+
+        out.push_str("\n.section .debug_info,\"\",@progbits\n");
+        out.push_str(".Ldebug_info0:\n");
+        out.push_str("    .long .Ldebug_info_end0 - .Ldebug_info_start0\n");
+        out.push_str(".Ldebug_info_start0:\n");
+        out.push_str("    .short 4\n");
+        out.push_str("    .long .Ldebug_abbrev0\n");
+        out.push_str("    .byte 8\n");
+        out.push_str(&format!("    .uleb128 {}\n", ABBREV_COMPILE_UNIT));
+        let unit_name = self.sources.name(FileId(0)).unwrap_or("");
+        out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(unit_name)));
+        out.push_str(&format!("    .short 0x{:x}\n", DW_LANG_C99));
+        out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(&self.comp_dir)));
+        out.push_str("    .quad 0\n");
+        out.push_str("    .long .Ldebug_ranges0\n");
+        out.push_str("    .long .Ldebug_line0\n");
+
+        let mut types = TypeTable::default();
+        for sub in &self.subprograms {
+            let return_type = types.label(&sub.return_type);
+            let abbrev = if return_type.is_some() { ABBREV_SUBPROGRAM } else { ABBREV_VOID_SUBPROGRAM };
+            out.push_str(&format!("    .uleb128 {}\n", abbrev));
+            out.push_str(&format!("    .byte {}\n", u8::from(sub.is_external)));
+            out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(&sub.name)));
+            out.push_str(&format!("    .uleb128 {}\n    .uleb128 {}\n", sub.file, sub.line));
+            if let Some(label) = return_type {
+                out.push_str(&format!("    .long {} - .Ldebug_info0\n", label));
+            }
+            out.push_str(&format!("    .quad {}\n", sub.name));
+            out.push_str(&format!("    .quad {} - {}\n", function_end_label(&sub.name), sub.name));
+            match sub.frame_size {
+                Some(size) => {
+                    let offset = sleb128(i64::from(size));
+                    let bytes: Vec<String> = offset.iter().map(|b| format!("0x{:x}", b)).collect();
+                    out.push_str(&format!("    .byte {}, 0x{:x}, {}\n", offset.len() + 1, DW_OP_BREG7, bytes.join(", ")));
+                }
+                None => out.push_str(&format!("    .byte 2, 0x{:x}, 0\n", DW_OP_BREG6)),
+            }
+            for local in &sub.locals {
+                let Some(ty) = types.label(&local.ty) else {
+                    continue;
+                };
+                let abbrev = if local.is_parameter { ABBREV_PARAMETER } else { ABBREV_VARIABLE };
+                out.push_str(&format!("    .uleb128 {}\n", abbrev));
+                out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(&local.name)));
+                out.push_str(&format!("    .uleb128 {}\n    .uleb128 {}\n", local.file, local.line));
+                out.push_str(&format!("    .long {} - .Ldebug_info0\n", ty));
+                let offset = sleb128(i64::from(local.offset));
+                let bytes: Vec<String> = offset.iter().map(|b| format!("0x{:x}", b)).collect();
+                out.push_str(&format!("    .byte {}, 0x{:x}, {}\n", offset.len() + 1, DW_OP_FBREG, bytes.join(", ")));
+            }
+            out.push_str("    .byte 0\n");
+        }
+        types.emit(&mut out, layout);
+        out.push_str("    .byte 0\n");
+        out.push_str(".Ldebug_info_end0:\n");
+
+        out.push_str("\n.section .debug_ranges,\"\",@progbits\n");
+        out.push_str(".Ldebug_ranges0:\n");
+        for name in &self.functions {
+            out.push_str(&format!("    .quad {}, {}\n", name, function_end_label(name)));
+        }
+        out.push_str("    .quad 0, 0\n");
+
+        // The assembler appends the line table built from `.loc` here
+        out.push_str("\n.section .debug_line,\"\",@progbits\n");
+        out.push_str(".Ldebug_line0:\n");
+        out
+    }
+}
+
+/// A type entry: a C type, or the function type a function pointer points to.
+#[derive(PartialEq)]
+enum TypeEntry {
+    Type(Type),
+    Subroutine(Type),
+}
+
+/// The type entries of the unit, numbered by first reference.
+#[derive(Default)]
+struct TypeTable {
+    entries: Vec<TypeEntry>,
+}
+
+impl TypeTable {
+    /// Label of the entry describing `ty`, or `None` for `void` and types
+    /// left unresolved. Pointer qualifiers and function pointer parameters
+    /// are not described, so such types share one entry.
+    fn label(&mut self, ty: &Type) -> Option<String> {
+        let ty = match ty {
+            Type::Void | Type::Typedef(_) | Type::TypeofExpr(_) => return None,
+            Type::Enum(_) => Type::Int,
+            Type::Pointer(inner, _) => Type::ptr((**inner).clone()),
+            Type::FunctionPointer { return_type, .. } => Type::FunctionPointer {
+                return_type: return_type.clone(),
+                param_types: Vec::new(),
+                is_variadic: false,
+            },
+            ty => ty.clone(),
+        };
+        Some(self.entry_label(TypeEntry::Type(ty)))
+    }
+
+    fn entry_label(&mut self, entry: TypeEntry) -> String {
+        let index = match self.entries.iter().position(|e| *e == entry) {
+            Some(index) => index,
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+        format!(".Ldebug_type{}", index)
+    }
+
+    /// Write every entry, including those its members refer to in turn.
+    fn emit(&mut self, out: &mut String, layout: &TypeLayout) {
+        let mut index = 0;
+        while index < self.entries.len() {
+            out.push_str(&format!(".Ldebug_type{}:\n", index));
+            let ty = match &self.entries[index] {
+                TypeEntry::Subroutine(return_type) => {
+                    let return_type = return_type.clone();
+                    match self.label(&return_type) {
+                        Some(label) => {
+                            out.push_str(&format!("    .uleb128 {}\n", ABBREV_SUBROUTINE));
+                            out.push_str(&format!("    .long {} - .Ldebug_info0\n", label));
+                        }
+                        None => out.push_str(&format!("    .uleb128 {}\n", ABBREV_VOID_SUBROUTINE)),
+                    }
+                    index += 1;
+                    continue;
+                }
+                TypeEntry::Type(ty) => ty.clone(),
+            };
+            self.emit_type(out, &ty, layout);
+            index += 1;
+        }
+    }
+
+    fn emit_type(&mut self, out: &mut String, ty: &Type, layout: &TypeLayout) {
+        let base = |out: &mut String, name: &str, encoding: u8| {
+            out.push_str(&format!("    .uleb128 {}\n", ABBREV_BASE_TYPE));
+            out.push_str(&format!("    .asciz \"{}\"\n", name));
+            out.push_str(&format!("    .byte 0x{:x}, {}\n", encoding, layout.size_of(ty)));
+        };
+        match ty {
+            Type::Int => base(out, "int", DW_ATE_SIGNED),
+            Type::UnsignedInt => base(out, "unsigned int", DW_ATE_UNSIGNED),
+            Type::Char => base(out, "char", DW_ATE_SIGNED_CHAR),
+            Type::UnsignedChar => base(out, "unsigned char", DW_ATE_UNSIGNED_CHAR),
+            Type::Short => base(out, "short", DW_ATE_SIGNED),
+            Type::UnsignedShort => base(out, "unsigned short", DW_ATE_UNSIGNED),
+            Type::Long => base(out, "long", DW_ATE_SIGNED),
+            Type::UnsignedLong => base(out, "unsigned long", DW_ATE_UNSIGNED),
+            Type::LongLong => base(out, "long long", DW_ATE_SIGNED),
+            Type::UnsignedLongLong => base(out, "unsigned long long", DW_ATE_UNSIGNED),
+            Type::Float => base(out, "float", DW_ATE_FLOAT),
+            Type::Double => base(out, "double", DW_ATE_FLOAT),
+            Type::Bool => base(out, "_Bool", DW_ATE_BOOLEAN),
+            Type::Complex(elem) if **elem == Type::Float => base(out, "complex float", DW_ATE_COMPLEX_FLOAT),
+            Type::Complex(_) => base(out, "complex double", DW_ATE_COMPLEX_FLOAT),
+            Type::Pointer(inner, _) => {
+                let size = layout.data.pointer_size;
+                match self.label(inner) {
+                    Some(label) => {
+                        out.push_str(&format!("    .uleb128 {}\n    .byte {}\n", ABBREV_POINTER, size));
+                        out.push_str(&format!("    .long {} - .Ldebug_info0\n", label));
+                    }
+                    None => out.push_str(&format!("    .uleb128 {}\n    .byte {}\n", ABBREV_VOID_POINTER, size)),
+                }
+            }
+            Type::FunctionPointer { return_type, .. } => {
+                let label = self.entry_label(TypeEntry::Subroutine((**return_type).clone()));
+                out.push_str(&format!("    .uleb128 {}\n    .byte {}\n", ABBREV_POINTER, layout.data.pointer_size));
+                out.push_str(&format!("    .long {} - .Ldebug_info0\n", label));
+            }
+            Type::Array(elem, count) => {
+                // An element type with no entry of its own reads as bytes
+                let elem = self.label(elem).unwrap_or_else(|| self.entry_label(TypeEntry::Type(Type::UnsignedChar)));
+                out.push_str(&format!("    .uleb128 {}\n", ABBREV_ARRAY));
+                out.push_str(&format!("    .long {} - .Ldebug_info0\n", elem));
+                out.push_str(&format!("    .uleb128 {}\n    .uleb128 {}\n", ABBREV_SUBRANGE, count));
+                out.push_str("    .byte 0\n");
+            }
+            Type::Struct(name) => match layout.structs.get(name) {
+                Some(s_def) => {
+                    let fields = layout.struct_layout(s_def).fields;
+                    out.push_str(&format!("    .uleb128 {}\n", ABBREV_STRUCT));
+                    out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(name)));
+                    out.push_str(&format!("    .uleb128 {}\n", layout.size_of(ty)));
+                    for (field, place) in s_def.fields.iter().zip(&fields) {
+                        self.emit_member(out, field, place.offset, place.bitfield.as_ref());
+                    }
+                    out.push_str("    .byte 0\n");
+                }
+                None => {
+                    out.push_str(&format!("    .uleb128 {}\n", ABBREV_STRUCT_DECLARATION));
+                    out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(name)));
+                }
+            },
+            Type::Union(name) => match layout.unions.get(name) {
+                Some(u_def) => {
+                    out.push_str(&format!("    .uleb128 {}\n", ABBREV_UNION));
+                    out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(name)));
+                    out.push_str(&format!("    .uleb128 {}\n", layout.size_of(ty)));
+                    for field in &u_def.fields {
+                        self.emit_member(out, field, 0, None);
+                    }
+                    out.push_str("    .byte 0\n");
+                }
+                None => {
+                    out.push_str(&format!("    .uleb128 {}\n", ABBREV_UNION_DECLARATION));
+                    out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(name)));
+                }
+            },
+            // `label` maps these to other entries
+            Type::Void | Type::Typedef(_) | Type::TypeofExpr(_) | Type::Enum(_) => {
+                unreachable!("no debug entry for {:?}", ty)
+            }
+        }
+    }
+
+    fn emit_member(&mut self, out: &mut String, field: &model::StructField, offset: usize, bitfield: Option<&model::BitfieldInfo>) {
+        // Unnamed bit-fields are padding
+        if field.name.is_empty() && bitfield.is_some() {
+            return;
+        }
+        let Some(ty) = self.label(&field.field_type) else {
+            return;
+        };
+        let abbrev = if bitfield.is_some() { ABBREV_BITFIELD_MEMBER } else { ABBREV_MEMBER };
+        out.push_str(&format!("    .uleb128 {}\n", abbrev));
+        out.push_str(&format!("    .asciz \"{}\"\n", escape_asciz(&field.name)));
+        out.push_str(&format!("    .long {} - .Ldebug_info0\n", ty));
+        match bitfield {
+            Some(bits) => out.push_str(&format!("    .uleb128 {}\n    .uleb128 {}\n", bits.bit_width, offset * 8 + bits.bit_offset)),
+            None => out.push_str(&format!("    .uleb128 {}\n", offset)),
+        }
+    }
+}
+
+fn sleb128(mut value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        bytes.push(if done { byte } else { byte | 0x80 });
+        if done {
+            return bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_offsets_are_sleb128() {
+        assert_eq!(sleb128(-16), [0x70]);
+        assert_eq!(sleb128(-128), [0x80, 0x7f]);
+        assert_eq!(sleb128(63), [0x3f]);
+        assert_eq!(sleb128(64), [0xc0, 0x00]);
+    }
+
+    #[test]
+    fn types_are_shared_and_may_refer_to_themselves() {
+        let node = model::StructDef {
+            name: "node".to_string(),
+            fields: vec![
+                model::StructField { field_type: Type::Int, name: "value".to_string(), bit_width: None },
+                model::StructField { field_type: Type::ptr(Type::Struct("node".to_string())), name: "next".to_string(), bit_width: None },
+            ],
+            attributes: Vec::new(),
+        };
+        let structs = HashMap::from([("node".to_string(), node)]);
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+
+        let mut types = TypeTable::default();
+        let head = types.label(&Type::ptr(Type::Struct("node".to_string()))).unwrap();
+        let qualified = Type::qualified_ptr(Type::Struct("node".to_string()), model::TypeQualifiers { is_const: true, ..Default::default() });
+        assert_eq!(types.label(&qualified), Some(head.clone()));
+        assert_eq!(types.label(&Type::Enum("color".to_string())), types.label(&Type::Int));
+        assert_eq!(types.label(&Type::Void), None);
+
+        let mut out = String::new();
+        types.emit(&mut out, &layout);
+        // The pointer, int, and the struct; `next` points back at the
+        // pointer entry instead of describing the struct again
+        assert_eq!(types.entries.len(), 3);
+        assert_eq!(out.matches(".asciz \"node\"").count(), 1);
+        assert!(out.contains(&format!(".asciz \"next\"\n    .long {} - .Ldebug_info0\n    .uleb128 8\n", head)), "{}", out);
+    }
+}
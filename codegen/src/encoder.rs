@@ -0,0 +1,1608 @@
+// x86-64 machine code for Intel-syntax instructions
+//
+// The built-in assembler (`assembler.rs`) hands every instruction line here
+// as the Intel text `emit_asm` printed. `encode` parses the operands and
+// returns the instruction's bytes, plus a fixup for each symbol it names;
+// the assembler resolves those or turns them into relocations.
+//
+// Encodings follow GNU as without optimization, so an object matches what
+// `gcc -c` assembles from the same text:
+//
+// - Register-to-register integer forms use the store opcode (`89` for `mov`),
+//   and SSE register moves the load opcode.
+// - Immediates take the sign-extended byte form when they fit (`83 /0 ib`),
+//   and otherwise the accumulator's short form if there is one (`05 id`).
+// - A 64-bit `mov` of an immediate is `C7 /0 id` unless the value needs all
+//   64 bits, which takes `movabs` (`B8+r io`).
+// - AVX instructions use the two-byte VEX prefix when nothing needs the
+//   three-byte one.
+//
+// Jumps and calls to labels are laid out by the assembler, which picks the
+// rel8 or rel32 form once label offsets are known (see `branch`).
+
+use crate::att::{parse_int, split_operands, strip_size_keyword, PREFIXES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RegClass {
+    Gpr,
+    Xmm,
+    Ymm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Reg {
+    /// Encoding number, 0-15
+    pub num: u8,
+    /// Size in bytes
+    pub size: u8,
+    pub class: RegClass,
+    /// `ah`, `ch`, `dh`, or `bh`, which no instruction with a REX prefix can name
+    pub high: bool,
+}
+
+impl Reg {
+    /// `spl`, `bpl`, `sil`, and `dil` exist only with a REX prefix.
+    fn needs_rex(&self) -> bool {
+        self.class == RegClass::Gpr && self.size == 1 && !self.high && (4..8).contains(&self.num)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mem {
+    /// From a size keyword (`DWORD PTR`)
+    pub size: Option<u8>,
+    /// `fs:` or `gs:` override prefix
+    pub segment: Option<u8>,
+    pub base: Option<Reg>,
+    /// Index register and scale
+    pub index: Option<(Reg, u8)>,
+    pub disp: i64,
+    pub symbol: Option<String>,
+    /// `symbol[rip]`: the displacement is relative to the next instruction
+    pub rip: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Operand {
+    Reg(Reg),
+    Mem(Mem),
+    Imm(i64),
+    /// `OFFSET symbol+addend`: an address as an immediate
+    Address(String, i64),
+    /// A bare symbol: the target of a jump or call. Any other instruction
+    /// reads it as memory at that absolute address.
+    Symbol(String, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FixupKind {
+    /// 32-bit displacement from the end of the field
+    Pc32,
+    /// Zero-extended 32-bit address
+    Abs32,
+    /// Sign-extended 32-bit address
+    Abs32S,
+    Abs64,
+}
+
+impl FixupKind {
+    pub(crate) fn size(self) -> usize {
+        match self {
+            FixupKind::Abs64 => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// A symbol reference in encoded bytes. The field at `offset` holds
+/// `symbol + addend`, less the field's own address for `Pc32`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Fixup {
+    pub offset: usize,
+    pub kind: FixupKind,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Encoded {
+    pub bytes: Vec<u8>,
+    pub fixups: Vec<Fixup>,
+}
+
+/// The kind of jump `mnemonic` names when its operand is a label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Branch {
+    Jmp,
+    /// Conditional jump with its condition code
+    Jcc(u8),
+    Call,
+}
+
+impl Branch {
+    /// Encoding with an 8-bit displacement, if the jump has one.
+    pub(crate) fn short(self) -> Option<Vec<u8>> {
+        match self {
+            Branch::Jmp => Some(vec![0xEB, 0]),
+            Branch::Jcc(cc) => Some(vec![0x70 + cc, 0]),
+            Branch::Call => None,
+        }
+    }
+
+    /// Encoding with a 32-bit displacement, which ends the instruction.
+    pub(crate) fn near(self) -> Vec<u8> {
+        match self {
+            Branch::Jmp => vec![0xE9, 0, 0, 0, 0],
+            Branch::Jcc(cc) => vec![0x0F, 0x80 + cc, 0, 0, 0, 0],
+            Branch::Call => vec![0xE8, 0, 0, 0, 0],
+        }
+    }
+}
+
+/// If `text` jumps to or calls a label, the kind of branch and the label.
+pub(crate) fn branch(text: &str) -> Option<(Branch, String)> {
+    let (mnemonic, operand) = text.split_once(char::is_whitespace)?;
+    let mnemonic = mnemonic.to_ascii_lowercase();
+    let kind = match mnemonic.as_str() {
+        "jmp" => Branch::Jmp,
+        "call" => Branch::Call,
+        _ => Branch::Jcc(condition(mnemonic.strip_prefix('j')?)?),
+    };
+    match parse_operand(operand.trim(), true).ok()? {
+        Operand::Symbol(name, 0) => Some((kind, name)),
+        _ => None,
+    }
+}
+
+/// Encode one instruction, prefixes included.
+pub(crate) fn encode(text: &str) -> Result<Encoded, String> {
+    let mut prefixes = Vec::new();
+    let mut rest = text.trim();
+    loop {
+        let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let word = word.to_ascii_lowercase();
+        if !PREFIXES.contains(&word.as_str()) || tail.trim().is_empty() {
+            break;
+        }
+        prefixes.push(match word.as_str() {
+            "lock" => 0xF0,
+            "repne" | "repnz" => 0xF2,
+            _ => 0xF3,
+        });
+        rest = tail.trim_start();
+    }
+    let (mnemonic, operand_text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let mnemonic = mnemonic.to_ascii_lowercase();
+    let is_branch = mnemonic == "call" || mnemonic.starts_with('j');
+    let operands = split_operands(operand_text)
+        .into_iter()
+        .map(|op| parse_operand(op, is_branch))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut encoded = encode_instruction(&mnemonic, &operands)?;
+    if !prefixes.is_empty() {
+        for fixup in &mut encoded.fixups {
+            fixup.offset += prefixes.len();
+        }
+        prefixes.append(&mut encoded.bytes);
+        encoded.bytes = prefixes;
+    }
+    Ok(encoded)
+}
+
+// ── Operands ─────────────────────────────────────────────────
+
+/// A register named in Intel syntax.
+pub(crate) fn parse_register(name: &str) -> Option<Reg> {
+    const LEGACY: [[&str; 4]; 8] = [
+        ["rax", "eax", "ax", "al"],
+        ["rcx", "ecx", "cx", "cl"],
+        ["rdx", "edx", "dx", "dl"],
+        ["rbx", "ebx", "bx", "bl"],
+        ["rsp", "esp", "sp", "spl"],
+        ["rbp", "ebp", "bp", "bpl"],
+        ["rsi", "esi", "si", "sil"],
+        ["rdi", "edi", "di", "dil"],
+    ];
+    let name = name.to_ascii_lowercase();
+    let gpr = |num: u8, size: u8| Reg { num, size, class: RegClass::Gpr, high: false };
+    for (num, names) in LEGACY.iter().enumerate() {
+        if let Some(i) = names.iter().position(|n| *n == name) {
+            return Some(gpr(num as u8, [8, 4, 2, 1][i]));
+        }
+    }
+    if let Some(num) = ["ah", "ch", "dh", "bh"].iter().position(|n| *n == name) {
+        return Some(Reg { num: num as u8 + 4, size: 1, class: RegClass::Gpr, high: true });
+    }
+    let numbered = |prefix: &str| -> Option<(u8, &str)> {
+        let digits = name.strip_prefix(prefix)?;
+        let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        if end == 0 || (end > 1 && digits.starts_with('0')) {
+            return None;
+        }
+        let num: u8 = digits[..end].parse().ok()?;
+        (num < 16).then_some((num, &digits[end..]))
+    };
+    if let Some((num, "")) = numbered("xmm") {
+        return Some(Reg { num, size: 16, class: RegClass::Xmm, high: false });
+    }
+    if let Some((num, "")) = numbered("ymm") {
+        return Some(Reg { num, size: 32, class: RegClass::Ymm, high: false });
+    }
+    let (num, width) = numbered("r")?;
+    if num < 8 {
+        return None;
+    }
+    match width {
+        "" => Some(gpr(num, 8)),
+        "d" => Some(gpr(num, 4)),
+        "w" => Some(gpr(num, 2)),
+        "b" => Some(gpr(num, 1)),
+        _ => None,
+    }
+}
+
+/// Identifiers with universal character names (`caf\u00e9`) reach the
+/// assembly as UTF-8, so any non-ASCII character can be part of a symbol.
+pub(crate) fn is_symbol_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$' || !c.is_ascii()
+}
+
+pub(crate) fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$' || c == '@' || !c.is_ascii()
+}
+
+/// `name`, `name+8`, or `name-4`.
+pub(crate) fn parse_symbol_expr(text: &str) -> Option<(String, i64)> {
+    let text = text.trim();
+    if !text.starts_with(is_symbol_start) {
+        return None;
+    }
+    let end = text.find(|c: char| !is_symbol_char(c)).unwrap_or(text.len());
+    let (name, rest) = text.split_at(end);
+    let rest = rest.trim();
+    let addend = if rest.is_empty() {
+        0
+    } else if let Some(value) = rest.strip_prefix('+') {
+        parse_int(value.trim())?
+    } else if rest.starts_with('-') {
+        parse_int(&rest.replace(' ', ""))?
+    } else {
+        return None;
+    };
+    Some((name.to_string(), addend))
+}
+
+fn parse_operand(text: &str, is_branch: bool) -> Result<Operand, String> {
+    let bad = || format!("cannot parse operand '{}'", text);
+    let (size, mut rest) = strip_size_keyword(text.trim());
+    let mut segment = None;
+    for (name, prefix) in [("fs:", 0x64), ("gs:", 0x65)] {
+        if rest.len() > 3 && rest[..3].eq_ignore_ascii_case(name) {
+            segment = Some(prefix);
+            rest = rest[3..].trim_start();
+        }
+    }
+    if let Some(open) = rest.find('[') {
+        let close = rest.rfind(']').ok_or_else(bad)?;
+        let mut mem = parse_memory(&rest[..open], &rest[open + 1..close]).ok_or_else(bad)?;
+        mem.size = size;
+        mem.segment = segment;
+        return Ok(Operand::Mem(mem));
+    }
+    if segment.is_some() {
+        // `fs:0`: an absolute address in the segment
+        let disp = parse_int(rest).ok_or_else(bad)?;
+        return Ok(Operand::Mem(Mem { size, segment, base: None, index: None, disp, symbol: None, rip: false }));
+    }
+    if let Some(reg) = parse_register(rest) {
+        return Ok(Operand::Reg(reg));
+    }
+    if let Some(value) = parse_int(rest) {
+        return Ok(Operand::Imm(value));
+    }
+    let offset = rest.split_once(char::is_whitespace).filter(|(word, _)| word.eq_ignore_ascii_case("offset"));
+    if let Some((_, target)) = offset {
+        let target = target.trim();
+        let target = target.strip_prefix("FLAT:").or_else(|| target.strip_prefix("flat:")).unwrap_or(target);
+        let (name, addend) = parse_symbol_expr(target).ok_or_else(bad)?;
+        return Ok(Operand::Address(name, addend));
+    }
+    let (name, addend) = parse_symbol_expr(rest).ok_or_else(bad)?;
+    if is_branch || size.is_none() {
+        return Ok(Operand::Symbol(name, addend));
+    }
+    Ok(Operand::Mem(Mem { size, segment: None, base: None, index: None, disp: addend, symbol: Some(name), rip: false }))
+}
+
+/// `symbol[base + index*scale + disp]`, split at the bracket.
+fn parse_memory(outside: &str, inner: &str) -> Option<Mem> {
+    let mut mem = Mem { size: None, segment: None, base: None, index: None, disp: 0, symbol: None, rip: false };
+    if !outside.trim().is_empty() {
+        let (name, addend) = parse_symbol_expr(outside)
+            .or_else(|| parse_int(outside.trim()).map(|value| (String::new(), value)))?;
+        if !name.is_empty() {
+            mem.symbol = Some(name);
+        }
+        mem.disp = addend;
+    }
+
+    let inner = inner.trim();
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        if (c == '+' || c == '-') && i > start {
+            terms.push(&inner[start..i]);
+            start = i;
+        }
+    }
+    terms.push(&inner[start..]);
+
+    for term in terms {
+        let (negative, term) = match term.trim() {
+            t if t.starts_with('-') => (true, t[1..].trim()),
+            t if t.starts_with('+') => (false, t[1..].trim()),
+            t => (false, t),
+        };
+        if let Some((a, b)) = term.split_once('*') {
+            let (reg, scale) = match parse_register(a.trim()) {
+                Some(reg) => (reg, b),
+                None => (parse_register(b.trim())?, a),
+            };
+            let scale = parse_int(scale.trim())?;
+            if negative || mem.index.is_some() || ![1, 2, 4, 8].contains(&scale) {
+                return None;
+            }
+            mem.index = Some((reg, scale as u8));
+        } else if term.eq_ignore_ascii_case("rip") && !negative {
+            mem.rip = true;
+        } else if let Some(reg) = parse_register(term) {
+            if negative {
+                return None;
+            }
+            if mem.base.is_none() {
+                mem.base = Some(reg);
+            } else if mem.index.is_none() {
+                mem.index = Some((reg, 1));
+            } else {
+                return None;
+            }
+        } else if let Some(value) = parse_int(term) {
+            mem.disp += if negative { -value } else { value };
+        } else if !negative && mem.symbol.is_none() && term.starts_with(is_symbol_start) {
+            mem.symbol = Some(term.to_string());
+        } else {
+            return None;
+        }
+    }
+    if mem.rip && (mem.base.is_some() || mem.index.is_some()) {
+        return None;
+    }
+    Some(mem)
+}
+
+/// Condition code of a `j`/`set`/`cmov` suffix.
+fn condition(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "o" => 0x0,
+        "no" => 0x1,
+        "b" | "c" | "nae" => 0x2,
+        "ae" | "nb" | "nc" => 0x3,
+        "e" | "z" => 0x4,
+        "ne" | "nz" => 0x5,
+        "be" | "na" => 0x6,
+        "a" | "nbe" => 0x7,
+        "s" => 0x8,
+        "ns" => 0x9,
+        "p" | "pe" => 0xA,
+        "np" | "po" => 0xB,
+        "l" | "nge" => 0xC,
+        "ge" | "nl" => 0xD,
+        "le" | "ng" => 0xE,
+        "g" | "nle" => 0xF,
+        _ => return None,
+    })
+}
+
+// ── Legacy and REX encoding ──────────────────────────────────
+
+/// The ModRM.reg field: a register operand, or an opcode extension (`/digit`).
+#[derive(Clone, Copy)]
+enum Field {
+    Ext(u8),
+    Reg(Reg),
+}
+
+#[derive(Clone, Copy)]
+enum Rm<'a> {
+    Reg(Reg),
+    Mem(&'a Mem),
+}
+
+enum Imm {
+    Value(i64),
+    Address(String, i64),
+}
+
+/// One instruction in the legacy (non-VEX) encoding.
+struct Inst<'a> {
+    prefixes: Vec<u8>,
+    rex_w: bool,
+    opcode: Vec<u8>,
+    reg: Field,
+    rm: Option<Rm<'a>>,
+    /// A register added to the last opcode byte (`push`, `bswap`, `mov r, imm`)
+    plus_reg: Option<Reg>,
+    imm: Option<(Imm, u8)>,
+}
+
+impl<'a> Inst<'a> {
+    fn new(opcode: &[u8]) -> Self {
+        Inst { prefixes: Vec::new(), rex_w: false, opcode: opcode.to_vec(), reg: Field::Ext(0), rm: None, plus_reg: None, imm: None }
+    }
+
+    /// The operand-size prefix or REX.W for an integer operation of `size` bytes.
+    fn sized(mut self, size: u8) -> Self {
+        match size {
+            2 => self.prefixes.push(0x66),
+            8 => self.rex_w = true,
+            _ => {}
+        }
+        self
+    }
+
+    fn prefix(mut self, prefix: u8) -> Self {
+        if prefix != 0 {
+            self.prefixes.push(prefix);
+        }
+        self
+    }
+
+    fn ext(mut self, digit: u8) -> Self {
+        self.reg = Field::Ext(digit);
+        self
+    }
+
+    fn reg(mut self, reg: Reg) -> Self {
+        self.reg = Field::Reg(reg);
+        self
+    }
+
+    fn rm(mut self, rm: Rm<'a>) -> Self {
+        self.rm = Some(rm);
+        self
+    }
+
+    fn plus(mut self, reg: Reg) -> Self {
+        self.plus_reg = Some(reg);
+        self
+    }
+
+    fn imm(mut self, value: i64, size: u8) -> Self {
+        self.imm = Some((Imm::Value(value), size));
+        self
+    }
+
+    fn imm_operand(mut self, op: &Operand, size: u8) -> Result<Self, String> {
+        self.imm = Some(match op {
+            Operand::Imm(value) => {
+                check_imm(*value, size)?;
+                (Imm::Value(*value), size)
+            }
+            Operand::Address(name, addend) if size >= 4 => (Imm::Address(name.clone(), *addend), size),
+            _ => return Err("expected an immediate".to_string()),
+        });
+        Ok(self)
+    }
+
+    fn finish(self) -> Result<Encoded, String> {
+        let mut out = Encoded::default();
+        if let Some(Rm::Mem(mem)) = self.rm {
+            if let Some(segment) = mem.segment {
+                out.bytes.push(segment);
+            }
+            if mem.base.is_some_and(|r| r.size == 4) {
+                out.bytes.push(0x67);
+            }
+        }
+        out.bytes.extend(&self.prefixes);
+
+        let reg_num = match self.reg {
+            Field::Ext(digit) => digit,
+            Field::Reg(reg) => reg.num,
+        };
+        let mut rex = 0u8;
+        if self.rex_w {
+            rex |= 8;
+        }
+        if reg_num & 8 != 0 {
+            rex |= 4;
+        }
+        let mut regs: Vec<Reg> = Vec::new();
+        if let Field::Reg(reg) = self.reg {
+            regs.push(reg);
+        }
+        match self.rm {
+            Some(Rm::Reg(reg)) => {
+                rex |= (reg.num >> 3) & 1;
+                regs.push(reg);
+            }
+            Some(Rm::Mem(mem)) => {
+                if let Some(base) = mem.base {
+                    rex |= (base.num >> 3) & 1;
+                }
+                if let Some((index, _)) = mem.index {
+                    rex |= ((index.num >> 3) & 1) << 1;
+                }
+            }
+            None => {}
+        }
+        if let Some(reg) = self.plus_reg {
+            rex |= (reg.num >> 3) & 1;
+            regs.push(reg);
+        }
+        let forced = regs.iter().any(Reg::needs_rex);
+        if regs.iter().any(|r| r.high) && (rex != 0 || forced) {
+            return Err("ah, bh, ch, and dh cannot be used with a REX prefix".to_string());
+        }
+        if rex != 0 || forced {
+            out.bytes.push(0x40 | rex);
+        }
+
+        out.bytes.extend(&self.opcode);
+        if let Some(reg) = self.plus_reg {
+            *out.bytes.last_mut().unwrap() += reg.num & 7;
+        }
+        if let Some(rm) = self.rm {
+            modrm(&mut out, reg_num & 7, rm)?;
+        }
+        if let Some((imm, size)) = self.imm {
+            push_imm(&mut out, imm, size);
+        }
+        finish_pc_fixups(&mut out);
+        Ok(out)
+    }
+}
+
+fn push_imm(out: &mut Encoded, imm: Imm, size: u8) {
+    match imm {
+        Imm::Value(value) => out.bytes.extend(&value.to_le_bytes()[..size as usize]),
+        Imm::Address(symbol, addend) => {
+            let kind = match size {
+                8 => FixupKind::Abs64,
+                _ => FixupKind::Abs32S,
+            };
+            out.fixups.push(Fixup { offset: out.bytes.len(), kind, symbol, addend });
+            out.bytes.extend(std::iter::repeat_n(0, size as usize));
+        }
+    }
+}
+
+/// A `Pc32` fixup is relative to its field's address, but the CPU adds the
+/// displacement to the end of the instruction, which may come after an
+/// immediate.
+fn finish_pc_fixups(out: &mut Encoded) {
+    let len = out.bytes.len();
+    for fixup in &mut out.fixups {
+        if fixup.kind == FixupKind::Pc32 {
+            fixup.addend -= (len - fixup.offset) as i64;
+        }
+    }
+}
+
+fn check_imm(value: i64, size: u8) -> Result<(), String> {
+    let fits = match size {
+        1 => (-128..=255).contains(&value),
+        2 => (-32768..=65535).contains(&value),
+        4 => (i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(&value),
+        _ => true,
+    };
+    if fits { Ok(()) } else { Err(format!("immediate {} does not fit in {} bytes", value, size)) }
+}
+
+fn fits_i8(value: i64) -> bool {
+    (-128..=127).contains(&value)
+}
+
+fn fits_i32(value: i64) -> bool {
+    i32::try_from(value).is_ok()
+}
+
+/// ModRM, SIB, and displacement for `reg` and `rm`.
+fn modrm(out: &mut Encoded, reg: u8, rm: Rm) -> Result<(), String> {
+    let mem = match rm {
+        Rm::Reg(r) => {
+            out.bytes.push(0xC0 | reg << 3 | (r.num & 7));
+            return Ok(());
+        }
+        Rm::Mem(mem) => mem,
+    };
+    if mem.symbol.is_none() && !fits_i32(mem.disp) {
+        return Err(format!("displacement {} does not fit in 32 bits", mem.disp));
+    }
+    let disp32 = |out: &mut Encoded, kind: FixupKind| match &mem.symbol {
+        Some(symbol) => {
+            out.fixups.push(Fixup { offset: out.bytes.len(), kind, symbol: symbol.clone(), addend: mem.disp });
+            out.bytes.extend([0; 4]);
+        }
+        None => out.bytes.extend((mem.disp as i32).to_le_bytes()),
+    };
+    if mem.rip {
+        out.bytes.push(reg << 3 | 0b101);
+        disp32(out, FixupKind::Pc32);
+        return Ok(());
+    }
+    let scale_bits = |scale: u8| match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    };
+    let index_bits = match mem.index {
+        Some((index, _)) if index.class == RegClass::Gpr && index.num == 4 => {
+            return Err("rsp cannot be an index register".to_string());
+        }
+        Some((index, scale)) => scale_bits(scale) << 6 | (index.num & 7) << 3,
+        None => 0b100 << 3,
+    };
+    match mem.base {
+        None => {
+            // Absolute address: no base, SIB with base 101 and a disp32
+            out.bytes.push(reg << 3 | 0b100);
+            out.bytes.push(index_bits | 0b101);
+            disp32(out, FixupKind::Abs32S);
+        }
+        Some(base) => {
+            let mode = if mem.symbol.is_some() {
+                2
+            } else if mem.disp == 0 && base.num & 7 != 5 {
+                0
+            } else if fits_i8(mem.disp) {
+                1
+            } else {
+                2
+            };
+            if mem.index.is_some() || base.num & 7 == 4 {
+                out.bytes.push(mode << 6 | reg << 3 | 0b100);
+                out.bytes.push(index_bits | (base.num & 7));
+            } else {
+                out.bytes.push(mode << 6 | reg << 3 | (base.num & 7));
+            }
+            match mode {
+                1 => out.bytes.push(mem.disp as u8),
+                2 => disp32(out, FixupKind::Abs32S),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+// ── VEX encoding ─────────────────────────────────────────────
+
+/// One AVX instruction. `pp` selects the implied prefix (1 = 66, 2 = F3,
+/// 3 = F2) and `map` the opcode map (1 = 0F, 2 = 0F38, 3 = 0F3A).
+struct Vex<'a> {
+    pp: u8,
+    map: u8,
+    w: bool,
+    l: bool,
+    vvvv: u8,
+    opcode: u8,
+    reg: u8,
+    rm: Rm<'a>,
+    imm: Option<u8>,
+}
+
+impl Vex<'_> {
+    fn finish(self) -> Result<Encoded, String> {
+        let mut out = Encoded::default();
+        let (x, b) = match self.rm {
+            Rm::Reg(reg) => (0, reg.num >> 3),
+            Rm::Mem(mem) => {
+                if let Some(segment) = mem.segment {
+                    out.bytes.push(segment);
+                }
+                (mem.index.map_or(0, |(index, _)| index.num >> 3), mem.base.map_or(0, |base| base.num >> 3))
+            }
+        };
+        let r = self.reg >> 3;
+        let tail = (!self.vvvv & 0xF) << 3 | u8::from(self.l) << 2 | self.pp;
+        if self.map == 1 && !self.w && x == 0 && b == 0 {
+            out.bytes.extend([0xC5, (r ^ 1) << 7 | tail]);
+        } else {
+            out.bytes.extend([0xC4, (r ^ 1) << 7 | (x ^ 1) << 6 | (b ^ 1) << 5 | self.map, u8::from(self.w) << 7 | tail]);
+        }
+        out.bytes.push(self.opcode);
+        modrm(&mut out, self.reg & 7, self.rm)?;
+        if let Some(imm) = self.imm {
+            out.bytes.push(imm);
+        }
+        finish_pc_fixups(&mut out);
+        Ok(out)
+    }
+}
+
+// ── Instruction tables ───────────────────────────────────────
+
+/// `add`-style groups: the `/digit` of `80`/`81`/`83` and the base of the
+/// register forms.
+const ALU: [(&str, u8); 8] =
+    [("add", 0), ("or", 1), ("adc", 2), ("sbb", 3), ("and", 4), ("sub", 5), ("xor", 6), ("cmp", 7)];
+
+/// Shifts and rotates: the `/digit` of `C0`/`C1`, `D0`/`D1`, and `D2`/`D3`.
+const SHIFTS: [(&str, u8); 8] =
+    [("rol", 0), ("ror", 1), ("rcl", 2), ("rcr", 3), ("shl", 4), ("sal", 4), ("shr", 5), ("sar", 7)];
+
+/// Single-operand `F6`/`F7` group.
+const UNARY: [(&str, u8); 5] = [("not", 2), ("neg", 3), ("mul", 4), ("div", 6), ("idiv", 7)];
+
+/// Instructions without operands.
+const NULLARY: &[(&str, &[u8])] = &[
+    ("ret", &[0xC3]),
+    ("leave", &[0xC9]),
+    ("nop", &[0x90]),
+    ("cqo", &[0x48, 0x99]),
+    ("cdq", &[0x99]),
+    ("cwd", &[0x66, 0x99]),
+    ("cdqe", &[0x48, 0x98]),
+    ("cwde", &[0x98]),
+    ("cbw", &[0x66, 0x98]),
+    ("hlt", &[0xF4]),
+    ("int3", &[0xCC]),
+    ("ud2", &[0x0F, 0x0B]),
+    ("pause", &[0xF3, 0x90]),
+    ("mfence", &[0x0F, 0xAE, 0xF0]),
+    ("lfence", &[0x0F, 0xAE, 0xE8]),
+    ("sfence", &[0x0F, 0xAE, 0xF8]),
+    ("cpuid", &[0x0F, 0xA2]),
+    ("rdtsc", &[0x0F, 0x31]),
+    ("syscall", &[0x0F, 0x05]),
+    ("clc", &[0xF8]),
+    ("stc", &[0xF9]),
+    ("cld", &[0xFC]),
+    ("std", &[0xFD]),
+    ("movsb", &[0xA4]),
+    ("movsw", &[0x66, 0xA5]),
+    ("movsd", &[0xA5]),
+    ("movsq", &[0x48, 0xA5]),
+    ("stosb", &[0xAA]),
+    ("stosw", &[0x66, 0xAB]),
+    ("stosd", &[0xAB]),
+    ("stosq", &[0x48, 0xAB]),
+    ("lodsb", &[0xAC]),
+    ("lodsq", &[0x48, 0xAD]),
+    ("scasb", &[0xAE]),
+    ("cmpsb", &[0xA6]),
+    ("vzeroupper", &[0xC5, 0xF8, 0x77]),
+];
+
+/// SSE `op xmm, xmm/mem`: mandatory prefix (0 for none) and the opcode after `0F`.
+const SSE: &[(&str, u8, &[u8])] = &[
+    ("addss", 0xF3, &[0x58]),
+    ("addsd", 0xF2, &[0x58]),
+    ("addps", 0, &[0x58]),
+    ("addpd", 0x66, &[0x58]),
+    ("mulss", 0xF3, &[0x59]),
+    ("mulsd", 0xF2, &[0x59]),
+    ("mulps", 0, &[0x59]),
+    ("mulpd", 0x66, &[0x59]),
+    ("subss", 0xF3, &[0x5C]),
+    ("subsd", 0xF2, &[0x5C]),
+    ("subps", 0, &[0x5C]),
+    ("subpd", 0x66, &[0x5C]),
+    ("minss", 0xF3, &[0x5D]),
+    ("minsd", 0xF2, &[0x5D]),
+    ("divss", 0xF3, &[0x5E]),
+    ("divsd", 0xF2, &[0x5E]),
+    ("divps", 0, &[0x5E]),
+    ("divpd", 0x66, &[0x5E]),
+    ("maxss", 0xF3, &[0x5F]),
+    ("maxsd", 0xF2, &[0x5F]),
+    ("sqrtss", 0xF3, &[0x51]),
+    ("sqrtsd", 0xF2, &[0x51]),
+    ("andps", 0, &[0x54]),
+    ("andpd", 0x66, &[0x54]),
+    ("andnps", 0, &[0x55]),
+    ("andnpd", 0x66, &[0x55]),
+    ("orps", 0, &[0x56]),
+    ("orpd", 0x66, &[0x56]),
+    ("xorps", 0, &[0x57]),
+    ("xorpd", 0x66, &[0x57]),
+    ("ucomiss", 0, &[0x2E]),
+    ("ucomisd", 0x66, &[0x2E]),
+    ("comiss", 0, &[0x2F]),
+    ("comisd", 0x66, &[0x2F]),
+    ("cvtss2sd", 0xF3, &[0x5A]),
+    ("cvtsd2ss", 0xF2, &[0x5A]),
+    ("cvtps2pd", 0, &[0x5A]),
+    ("cvtpd2ps", 0x66, &[0x5A]),
+    ("cvtdq2ps", 0, &[0x5B]),
+    ("cvtps2dq", 0x66, &[0x5B]),
+    ("cvttps2dq", 0xF3, &[0x5B]),
+    ("unpcklps", 0, &[0x14]),
+    ("unpcklpd", 0x66, &[0x14]),
+    ("paddb", 0x66, &[0xFC]),
+    ("paddw", 0x66, &[0xFD]),
+    ("paddd", 0x66, &[0xFE]),
+    ("paddq", 0x66, &[0xD4]),
+    ("psubb", 0x66, &[0xF8]),
+    ("psubw", 0x66, &[0xF9]),
+    ("psubd", 0x66, &[0xFA]),
+    ("psubq", 0x66, &[0xFB]),
+    ("pmullw", 0x66, &[0xD5]),
+    ("pmulld", 0x66, &[0x38, 0x40]),
+    ("pand", 0x66, &[0xDB]),
+    ("pandn", 0x66, &[0xDF]),
+    ("por", 0x66, &[0xEB]),
+    ("pxor", 0x66, &[0xEF]),
+    ("pcmpeqb", 0x66, &[0x74]),
+    ("pcmpeqw", 0x66, &[0x75]),
+    ("pcmpeqd", 0x66, &[0x76]),
+    ("pcmpgtb", 0x66, &[0x64]),
+    ("pcmpgtw", 0x66, &[0x65]),
+    ("pcmpgtd", 0x66, &[0x66]),
+];
+
+/// SSE moves: prefix, load opcode (`op xmm, xmm/mem`), store opcode (`op mem, xmm`).
+const SSE_MOVES: [(&str, u8, u8, u8); 8] = [
+    ("movss", 0xF3, 0x10, 0x11),
+    ("movsd", 0xF2, 0x10, 0x11),
+    ("movups", 0, 0x10, 0x11),
+    ("movupd", 0x66, 0x10, 0x11),
+    ("movaps", 0, 0x28, 0x29),
+    ("movapd", 0x66, 0x28, 0x29),
+    ("movdqa", 0x66, 0x6F, 0x7F),
+    ("movdqu", 0xF3, 0x6F, 0x7F),
+];
+
+/// AVX `op dst, src1, src2/mem` with `src1` in VEX.vvvv: pp, map, opcode.
+/// `vpandd`, `vpandnd`, and `vpord` without masking are `vpand`, `vpandn`, and `vpor`.
+const VEX_OPS: &[(&str, u8, u8, u8)] = &[
+    ("vaddps", 0, 1, 0x58),
+    ("vaddpd", 1, 1, 0x58),
+    ("vaddss", 2, 1, 0x58),
+    ("vaddsd", 3, 1, 0x58),
+    ("vmulps", 0, 1, 0x59),
+    ("vmulpd", 1, 1, 0x59),
+    ("vmulss", 2, 1, 0x59),
+    ("vmulsd", 3, 1, 0x59),
+    ("vsubps", 0, 1, 0x5C),
+    ("vsubpd", 1, 1, 0x5C),
+    ("vsubss", 2, 1, 0x5C),
+    ("vsubsd", 3, 1, 0x5C),
+    ("vdivps", 0, 1, 0x5E),
+    ("vdivpd", 1, 1, 0x5E),
+    ("vdivss", 2, 1, 0x5E),
+    ("vdivsd", 3, 1, 0x5E),
+    ("vandps", 0, 1, 0x54),
+    ("vandnps", 0, 1, 0x55),
+    ("vorps", 0, 1, 0x56),
+    ("vxorps", 0, 1, 0x57),
+    ("vxorpd", 1, 1, 0x57),
+    ("vpaddd", 1, 1, 0xFE),
+    ("vpsubd", 1, 1, 0xFA),
+    ("vpmulld", 1, 2, 0x40),
+    ("vpand", 1, 1, 0xDB),
+    ("vpandd", 1, 1, 0xDB),
+    ("vpandn", 1, 1, 0xDF),
+    ("vpandnd", 1, 1, 0xDF),
+    ("vpor", 1, 1, 0xEB),
+    ("vpord", 1, 1, 0xEB),
+    ("vpxor", 1, 1, 0xEF),
+    ("vpcmpeqd", 1, 1, 0x76),
+    ("vpcmpgtd", 1, 1, 0x66),
+];
+
+/// AVX moves: pp, load opcode, store opcode.
+const VEX_MOVES: [(&str, u8, u8, u8); 4] =
+    [("vmovups", 0, 0x10, 0x11), ("vmovaps", 0, 0x28, 0x29), ("vmovdqu", 2, 0x6F, 0x7F), ("vmovdqa", 1, 0x6F, 0x7F)];
+
+// ── Instructions ─────────────────────────────────────────────
+
+fn operand_count(mnemonic: &str, ops: &[Operand], count: usize) -> Result<(), String> {
+    if ops.len() == count {
+        Ok(())
+    } else {
+        Err(format!("'{}' takes {} operand(s), not {}", mnemonic, count, ops.len()))
+    }
+}
+
+/// A register or memory operand.
+fn as_rm(op: &Operand) -> Result<Rm<'_>, String> {
+    match op {
+        Operand::Reg(reg) => Ok(Rm::Reg(*reg)),
+        Operand::Mem(mem) => Ok(Rm::Mem(mem)),
+        _ => Err("expected a register or memory operand".to_string()),
+    }
+}
+
+fn as_mem(op: &Operand) -> Result<&Mem, String> {
+    match op {
+        Operand::Mem(mem) => Ok(mem),
+        _ => Err("expected a memory operand".to_string()),
+    }
+}
+
+fn as_gpr(op: &Operand) -> Result<Reg, String> {
+    match op {
+        Operand::Reg(reg) if reg.class == RegClass::Gpr => Ok(*reg),
+        _ => Err("expected a general-purpose register".to_string()),
+    }
+}
+
+fn as_vector(op: &Operand) -> Result<Reg, String> {
+    match op {
+        Operand::Reg(reg) if reg.class != RegClass::Gpr => Ok(*reg),
+        _ => Err("expected an xmm or ymm register".to_string()),
+    }
+}
+
+/// Size of a register or sized memory operand.
+fn size_of(op: &Operand) -> Option<u8> {
+    match op {
+        Operand::Reg(reg) => Some(reg.size),
+        Operand::Mem(mem) => mem.size,
+        _ => None,
+    }
+}
+
+fn imm8(op: &Operand) -> Result<u8, String> {
+    match op {
+        Operand::Imm(value) if (-128..=255).contains(value) => Ok(*value as u8),
+        _ => Err("expected an 8-bit immediate".to_string()),
+    }
+}
+
+/// Size shared by an integer instruction's register/memory operands.
+fn integer_size(ops: &[&Operand]) -> Result<u8, String> {
+    let mut size = None;
+    for op in ops {
+        if let Some(s) = size_of(op) {
+            if let Operand::Reg(reg) = op
+                && reg.class != RegClass::Gpr
+            {
+                return Err("expected a general-purpose register".to_string());
+            }
+            match size {
+                Some(prev) if prev != s => return Err("operand sizes do not match".to_string()),
+                _ => size = Some(s),
+            }
+        }
+    }
+    match size {
+        Some(s @ (1 | 2 | 4 | 8)) => Ok(s),
+        Some(_) => Err("invalid operand size".to_string()),
+        None => Err("operand size is ambiguous; add a size keyword".to_string()),
+    }
+}
+
+fn is_imm(op: &Operand) -> bool {
+    matches!(op, Operand::Imm(_) | Operand::Address(..))
+}
+
+fn is_accumulator(op: &Operand) -> bool {
+    matches!(op, Operand::Reg(reg) if reg.class == RegClass::Gpr && reg.num == 0 && !reg.high)
+}
+
+fn encode_instruction(mnemonic: &str, ops: &[Operand]) -> Result<Encoded, String> {
+    // Symbols are memory outside jumps and calls
+    let converted: Vec<Operand>;
+    let ops = if mnemonic != "call" && !mnemonic.starts_with('j') && ops.iter().any(|op| matches!(op, Operand::Symbol(..))) {
+        converted = ops
+            .iter()
+            .map(|op| match op {
+                Operand::Symbol(name, addend) => Operand::Mem(Mem {
+                    size: None,
+                    segment: None,
+                    base: None,
+                    index: None,
+                    disp: *addend,
+                    symbol: Some(name.clone()),
+                    rip: false,
+                }),
+                other => other.clone(),
+            })
+            .collect();
+        &converted[..]
+    } else {
+        ops
+    };
+
+    if let Some((_, bytes)) = NULLARY.iter().find(|(name, _)| *name == mnemonic)
+        && ops.is_empty()
+    {
+        return Ok(Encoded { bytes: bytes.to_vec(), fixups: Vec::new() });
+    }
+    if let Some((_, digit)) = ALU.iter().find(|(name, _)| *name == mnemonic) {
+        return alu(mnemonic, *digit, ops);
+    }
+    if let Some((_, digit)) = SHIFTS.iter().find(|(name, _)| *name == mnemonic) {
+        return shift(mnemonic, *digit, ops);
+    }
+    if let Some((_, digit)) = UNARY.iter().find(|(name, _)| *name == mnemonic) {
+        operand_count(mnemonic, ops, 1)?;
+        let size = integer_size(&[&ops[0]])?;
+        let opcode = if size == 1 { 0xF6 } else { 0xF7 };
+        return Inst::new(&[opcode]).sized(size).ext(*digit).rm(as_rm(&ops[0])?).finish();
+    }
+    if let Some(&(_, prefix, load, store)) = SSE_MOVES.iter().find(|(name, ..)| *name == mnemonic)
+        && !ops.is_empty()
+    {
+        operand_count(mnemonic, ops, 2)?;
+        return match (&ops[0], &ops[1]) {
+            (Operand::Mem(_), src) => {
+                Inst::new(&[0x0F, store]).prefix(prefix).reg(as_vector(src)?).rm(as_rm(&ops[0])?).finish()
+            }
+            (dst, src) => Inst::new(&[0x0F, load]).prefix(prefix).reg(as_vector(dst)?).rm(as_rm(src)?).finish(),
+        };
+    }
+    if let Some((_, prefix, opcode)) = SSE.iter().find(|(name, ..)| *name == mnemonic) {
+        operand_count(mnemonic, ops, 2)?;
+        let mut bytes = vec![0x0F];
+        bytes.extend(*opcode);
+        return Inst::new(&bytes).prefix(*prefix).reg(as_vector(&ops[0])?).rm(as_rm(&ops[1])?).finish();
+    }
+    if let Some(&(_, pp, map, opcode)) = VEX_OPS.iter().find(|(name, ..)| *name == mnemonic) {
+        operand_count(mnemonic, ops, 3)?;
+        let dst = as_vector(&ops[0])?;
+        let src1 = as_vector(&ops[1])?;
+        return Vex {
+            pp,
+            map,
+            w: false,
+            l: dst.class == RegClass::Ymm,
+            vvvv: src1.num,
+            opcode,
+            reg: dst.num,
+            rm: as_rm(&ops[2])?,
+            imm: None,
+        }
+        .finish();
+    }
+    if let Some(&(_, pp, load, store)) = VEX_MOVES.iter().find(|(name, ..)| *name == mnemonic) {
+        operand_count(mnemonic, ops, 2)?;
+        let (opcode, reg, rm) = match &ops[0] {
+            Operand::Mem(_) => (store, as_vector(&ops[1])?, as_rm(&ops[0])?),
+            _ => (load, as_vector(&ops[0])?, as_rm(&ops[1])?),
+        };
+        return Vex { pp, map: 1, w: false, l: reg.class == RegClass::Ymm, vvvv: 0, opcode, reg: reg.num, rm, imm: None }
+            .finish();
+    }
+    if let Some(cc) = mnemonic.strip_prefix("set").and_then(condition) {
+        operand_count(mnemonic, ops, 1)?;
+        if integer_size(&[&ops[0]])? != 1 {
+            return Err(format!("'{}' takes a byte operand", mnemonic));
+        }
+        return Inst::new(&[0x0F, 0x90 + cc]).rm(as_rm(&ops[0])?).finish();
+    }
+    if let Some(cc) = mnemonic.strip_prefix("cmov").and_then(condition) {
+        operand_count(mnemonic, ops, 2)?;
+        let size = integer_size(&[&ops[0], &ops[1]])?;
+        return Inst::new(&[0x0F, 0x40 + cc]).sized(size).reg(as_gpr(&ops[0])?).rm(as_rm(&ops[1])?).finish();
+    }
+
+    match mnemonic {
+        "mov" => mov(ops),
+        "movabs" => {
+            operand_count(mnemonic, ops, 2)?;
+            let dst = as_gpr(&ops[0])?;
+            if dst.size != 8 {
+                return Err("'movabs' takes a 64-bit register".to_string());
+            }
+            Inst::new(&[0xB8]).sized(8).plus(dst).imm_operand(&ops[1], 8)?.finish()
+        }
+        "lea" => {
+            operand_count(mnemonic, ops, 2)?;
+            let dst = as_gpr(&ops[0])?;
+            Inst::new(&[0x8D]).sized(dst.size).reg(dst).rm(Rm::Mem(as_mem(&ops[1])?)).finish()
+        }
+        "test" => {
+            operand_count(mnemonic, ops, 2)?;
+            let size = integer_size(&[&ops[0], &ops[1]])?;
+            let byte = size == 1;
+            match (&ops[0], &ops[1]) {
+                (dst, src) if is_imm(src) => {
+                    let imm_size = size.min(4);
+                    if is_accumulator(dst) {
+                        Inst::new(&[if byte { 0xA8 } else { 0xA9 }]).sized(size).imm_operand(src, imm_size)?.finish()
+                    } else {
+                        Inst::new(&[if byte { 0xF6 } else { 0xF7 }])
+                            .sized(size)
+                            .rm(as_rm(dst)?)
+                            .imm_operand(src, imm_size)?
+                            .finish()
+                    }
+                }
+                (Operand::Mem(_), src) | (src, Operand::Mem(_)) => {
+                    let mem = if matches!(ops[0], Operand::Mem(_)) { &ops[0] } else { &ops[1] };
+                    Inst::new(&[if byte { 0x84 } else { 0x85 }]).sized(size).reg(as_gpr(src)?).rm(as_rm(mem)?).finish()
+                }
+                (dst, src) => {
+                    Inst::new(&[if byte { 0x84 } else { 0x85 }]).sized(size).reg(as_gpr(src)?).rm(as_rm(dst)?).finish()
+                }
+            }
+        }
+        "xchg" => {
+            operand_count(mnemonic, ops, 2)?;
+            let size = integer_size(&[&ops[0], &ops[1]])?;
+            match (&ops[0], &ops[1]) {
+                (Operand::Reg(a), Operand::Reg(b)) if size > 1 && (is_accumulator(&ops[0]) || is_accumulator(&ops[1])) => {
+                    let other = if is_accumulator(&ops[0]) { *b } else { *a };
+                    Inst::new(&[0x90]).sized(size).plus(other).finish()
+                }
+                (Operand::Mem(_), reg) | (reg, _) => {
+                    let rm = if matches!(ops[0], Operand::Mem(_)) || !matches!(ops[1], Operand::Mem(_)) {
+                        &ops[0]
+                    } else {
+                        &ops[1]
+                    };
+                    let reg = if std::ptr::eq(rm, &ops[0]) { &ops[1] } else { reg };
+                    Inst::new(&[if size == 1 { 0x86 } else { 0x87 }]).sized(size).reg(as_gpr(reg)?).rm(as_rm(rm)?).finish()
+                }
+            }
+        }
+        "xadd" | "cmpxchg" => {
+            operand_count(mnemonic, ops, 2)?;
+            let size = integer_size(&[&ops[0], &ops[1]])?;
+            let opcode = match (mnemonic, size) {
+                ("xadd", 1) => 0xC0,
+                ("xadd", _) => 0xC1,
+                (_, 1) => 0xB0,
+                _ => 0xB1,
+            };
+            Inst::new(&[0x0F, opcode]).sized(size).reg(as_gpr(&ops[1])?).rm(as_rm(&ops[0])?).finish()
+        }
+        "inc" | "dec" => {
+            operand_count(mnemonic, ops, 1)?;
+            let size = integer_size(&[&ops[0]])?;
+            Inst::new(&[if size == 1 { 0xFE } else { 0xFF }])
+                .sized(size)
+                .ext(u8::from(mnemonic == "dec"))
+                .rm(as_rm(&ops[0])?)
+                .finish()
+        }
+        "imul" => imul(ops),
+        "movzx" | "movsx" | "movsxd" => extend(mnemonic, ops),
+        "push" | "pop" => {
+            operand_count(mnemonic, ops, 1)?;
+            let push = mnemonic == "push";
+            match &ops[0] {
+                Operand::Reg(reg) if reg.class == RegClass::Gpr && reg.size == 8 => {
+                    Inst::new(&[if push { 0x50 } else { 0x58 }]).plus(*reg).finish()
+                }
+                Operand::Imm(value) if push && fits_i8(*value) => Inst::new(&[0x6A]).imm(*value, 1).finish(),
+                op if push && is_imm(op) => Inst::new(&[0x68]).imm_operand(op, 4)?.finish(),
+                Operand::Mem(mem) if push => Inst::new(&[0xFF]).ext(6).rm(Rm::Mem(mem)).finish(),
+                Operand::Mem(mem) => Inst::new(&[0x8F]).ext(0).rm(Rm::Mem(mem)).finish(),
+                _ => Err(format!("'{}' takes a 64-bit register or memory operand", mnemonic)),
+            }
+        }
+        "jmp" | "call" => {
+            operand_count(mnemonic, ops, 1)?;
+            let digit = if mnemonic == "call" { 2 } else { 4 };
+            match &ops[0] {
+                Operand::Reg(reg) if reg.class == RegClass::Gpr && reg.size == 8 => {
+                    Inst::new(&[0xFF]).ext(digit).rm(Rm::Reg(*reg)).finish()
+                }
+                Operand::Mem(mem) => Inst::new(&[0xFF]).ext(digit).rm(Rm::Mem(mem)).finish(),
+                _ => Err(format!("'{}' needs a label, a 64-bit register, or memory", mnemonic)),
+            }
+        }
+        "ret" => {
+            operand_count(mnemonic, ops, 1)?;
+            match &ops[0] {
+                Operand::Imm(value) if (0..=0xFFFF).contains(value) => Inst::new(&[0xC2]).imm(*value, 2).finish(),
+                _ => Err("'ret' takes a 16-bit immediate".to_string()),
+            }
+        }
+        "nop" => {
+            operand_count(mnemonic, ops, 1)?;
+            let size = integer_size(&[&ops[0]])?;
+            Inst::new(&[0x0F, 0x1F]).sized(size).rm(as_rm(&ops[0])?).finish()
+        }
+        "bsf" | "bsr" | "popcnt" | "lzcnt" | "tzcnt" => {
+            operand_count(mnemonic, ops, 2)?;
+            let size = integer_size(&[&ops[0], &ops[1]])?;
+            let (prefix, opcode) = match mnemonic {
+                "bsf" => (0, 0xBC),
+                "bsr" => (0, 0xBD),
+                "popcnt" => (0xF3, 0xB8),
+                "lzcnt" => (0xF3, 0xBD),
+                _ => (0xF3, 0xBC),
+            };
+            // The mandatory prefix goes before the operand-size prefix
+            let mut inst = Inst::new(&[0x0F, opcode]).prefix(prefix).sized(size);
+            inst = inst.reg(as_gpr(&ops[0])?).rm(as_rm(&ops[1])?);
+            inst.finish()
+        }
+        "bswap" => {
+            operand_count(mnemonic, ops, 1)?;
+            let reg = as_gpr(&ops[0])?;
+            if reg.size < 4 {
+                return Err("'bswap' takes a 32- or 64-bit register".to_string());
+            }
+            Inst::new(&[0x0F, 0xC8]).sized(reg.size).plus(reg).finish()
+        }
+        "bt" | "bts" | "btr" | "btc" => {
+            operand_count(mnemonic, ops, 2)?;
+            let digit = match mnemonic {
+                "bt" => 4,
+                "bts" => 5,
+                "btr" => 6,
+                _ => 7,
+            };
+            if let Operand::Imm(_) = ops[1] {
+                let size = integer_size(&[&ops[0]])?;
+                Inst::new(&[0x0F, 0xBA]).sized(size).ext(digit).rm(as_rm(&ops[0])?).imm(i64::from(imm8(&ops[1])?), 1).finish()
+            } else {
+                let size = integer_size(&[&ops[0], &ops[1]])?;
+                Inst::new(&[0x0F, 0x83 + (digit - 4) * 8]).sized(size).reg(as_gpr(&ops[1])?).rm(as_rm(&ops[0])?).finish()
+            }
+        }
+        "cvtsi2ss" | "cvtsi2sd" => {
+            operand_count(mnemonic, ops, 2)?;
+            let prefix = if mnemonic == "cvtsi2ss" { 0xF3 } else { 0xF2 };
+            let wide = size_of(&ops[1]) == Some(8);
+            let mut inst = Inst::new(&[0x0F, 0x2A]).prefix(prefix).reg(as_vector(&ops[0])?).rm(as_rm(&ops[1])?);
+            inst.rex_w = wide;
+            inst.finish()
+        }
+        "cvttss2si" | "cvttsd2si" | "cvtss2si" | "cvtsd2si" => {
+            operand_count(mnemonic, ops, 2)?;
+            let prefix = if mnemonic.contains("ss2") { 0xF3 } else { 0xF2 };
+            let opcode = if mnemonic.starts_with("cvtt") { 0x2C } else { 0x2D };
+            let dst = as_gpr(&ops[0])?;
+            let mut inst = Inst::new(&[0x0F, opcode]).prefix(prefix).reg(dst).rm(as_rm(&ops[1])?);
+            inst.rex_w = dst.size == 8;
+            inst.finish()
+        }
+        "movd" | "movq" => movd(mnemonic, ops),
+        "pshufd" | "shufps" => {
+            operand_count(mnemonic, ops, 3)?;
+            let (prefix, opcode) = if mnemonic == "pshufd" { (0x66, 0x70) } else { (0, 0xC6) };
+            Inst::new(&[0x0F, opcode])
+                .prefix(prefix)
+                .reg(as_vector(&ops[0])?)
+                .rm(as_rm(&ops[1])?)
+                .imm(i64::from(imm8(&ops[2])?), 1)
+                .finish()
+        }
+        "pextrd" | "pextrq" => {
+            operand_count(mnemonic, ops, 3)?;
+            let mut inst = Inst::new(&[0x0F, 0x3A, 0x16])
+                .prefix(0x66)
+                .reg(as_vector(&ops[1])?)
+                .rm(as_rm(&ops[0])?)
+                .imm(i64::from(imm8(&ops[2])?), 1);
+            inst.rex_w = mnemonic == "pextrq";
+            inst.finish()
+        }
+        "pinsrd" | "pinsrq" => {
+            operand_count(mnemonic, ops, 3)?;
+            let mut inst = Inst::new(&[0x0F, 0x3A, 0x22])
+                .prefix(0x66)
+                .reg(as_vector(&ops[0])?)
+                .rm(as_rm(&ops[1])?)
+                .imm(i64::from(imm8(&ops[2])?), 1);
+            inst.rex_w = mnemonic == "pinsrq";
+            inst.finish()
+        }
+        "vextracti128" | "vextractf128" => {
+            operand_count(mnemonic, ops, 3)?;
+            let src = as_vector(&ops[1])?;
+            let opcode = if mnemonic == "vextracti128" { 0x39 } else { 0x19 };
+            Vex { pp: 1, map: 3, w: false, l: true, vvvv: 0, opcode, reg: src.num, rm: as_rm(&ops[0])?, imm: Some(imm8(&ops[2])?) }
+                .finish()
+        }
+        "vinserti128" | "vinsertf128" => {
+            operand_count(mnemonic, ops, 4)?;
+            let dst = as_vector(&ops[0])?;
+            let opcode = if mnemonic == "vinserti128" { 0x38 } else { 0x18 };
+            Vex {
+                pp: 1,
+                map: 3,
+                w: false,
+                l: true,
+                vvvv: as_vector(&ops[1])?.num,
+                opcode,
+                reg: dst.num,
+                rm: as_rm(&ops[2])?,
+                imm: Some(imm8(&ops[3])?),
+            }
+            .finish()
+        }
+        "vpbroadcastd" => {
+            operand_count(mnemonic, ops, 2)?;
+            let dst = as_vector(&ops[0])?;
+            Vex { pp: 1, map: 2, w: false, l: dst.class == RegClass::Ymm, vvvv: 0, opcode: 0x58, reg: dst.num, rm: as_rm(&ops[1])?, imm: None }
+                .finish()
+        }
+        "vpgatherdd" => {
+            operand_count(mnemonic, ops, 3)?;
+            let dst = as_vector(&ops[0])?;
+            let mem = as_mem(&ops[1])?;
+            if !mem.index.is_some_and(|(index, _)| index.class != RegClass::Gpr) {
+                return Err("'vpgatherdd' needs a vector index register".to_string());
+            }
+            Vex {
+                pp: 1,
+                map: 2,
+                w: false,
+                l: dst.class == RegClass::Ymm,
+                vvvv: as_vector(&ops[2])?.num,
+                opcode: 0x90,
+                reg: dst.num,
+                rm: Rm::Mem(mem),
+                imm: None,
+            }
+            .finish()
+        }
+        _ => Err(format!("unknown instruction '{}'", mnemonic)),
+    }
+}
+
+fn alu(mnemonic: &str, digit: u8, ops: &[Operand]) -> Result<Encoded, String> {
+    operand_count(mnemonic, ops, 2)?;
+    let size = integer_size(&[&ops[0], &ops[1]])?;
+    let byte = size == 1;
+    match (&ops[0], &ops[1]) {
+        (dst, Operand::Imm(value)) => {
+            let value = *value;
+            if size == 8 && !fits_i32(value) {
+                return Err(format!("immediate {} does not fit in 32 bits", value));
+            }
+            check_imm(value, size.min(4))?;
+            if byte {
+                if is_accumulator(dst) {
+                    return Inst::new(&[0x04 + digit * 8]).imm(value, 1).finish();
+                }
+                return Inst::new(&[0x80]).ext(digit).rm(as_rm(dst)?).imm(value, 1).finish();
+            }
+            // Sign-extended byte, unless a 16/32-bit value only fits unsigned
+            let as_signed = match size {
+                2 => i64::from(value as i16),
+                4 => i64::from(value as i32),
+                _ => value,
+            };
+            if fits_i8(as_signed) {
+                return Inst::new(&[0x83]).sized(size).ext(digit).rm(as_rm(dst)?).imm(as_signed, 1).finish();
+            }
+            let imm_size = size.min(4);
+            if is_accumulator(dst) {
+                return Inst::new(&[0x05 + digit * 8]).sized(size).imm(value, imm_size).finish();
+            }
+            Inst::new(&[0x81]).sized(size).ext(digit).rm(as_rm(dst)?).imm(value, imm_size).finish()
+        }
+        (dst, src @ Operand::Address(..)) => {
+            if byte {
+                return Err("an address does not fit in a byte".to_string());
+            }
+            Inst::new(&[0x81]).sized(size).ext(digit).rm(as_rm(dst)?).imm_operand(src, size.min(4))?.finish()
+        }
+        (Operand::Reg(dst), src @ Operand::Mem(_)) => {
+            let opcode = digit * 8 + if byte { 0x02 } else { 0x03 };
+            Inst::new(&[opcode]).sized(size).reg(*dst).rm(as_rm(src)?).finish()
+        }
+        (dst, src) => {
+            let opcode = digit * 8 + if byte { 0x00 } else { 0x01 };
+            Inst::new(&[opcode]).sized(size).reg(as_gpr(src)?).rm(as_rm(dst)?).finish()
+        }
+    }
+}
+
+fn shift(mnemonic: &str, digit: u8, ops: &[Operand]) -> Result<Encoded, String> {
+    let (dst, count) = match ops {
+        [dst] => (dst, &Operand::Imm(1)),
+        [dst, count] => (dst, count),
+        _ => return Err(format!("'{}' takes 1 or 2 operands", mnemonic)),
+    };
+    let size = integer_size(&[dst])?;
+    let byte = size == 1;
+    match count {
+        Operand::Imm(1) => Inst::new(&[if byte { 0xD0 } else { 0xD1 }]).sized(size).ext(digit).rm(as_rm(dst)?).finish(),
+        Operand::Imm(_) => Inst::new(&[if byte { 0xC0 } else { 0xC1 }])
+            .sized(size)
+            .ext(digit)
+            .rm(as_rm(dst)?)
+            .imm(i64::from(imm8(count)?), 1)
+            .finish(),
+        Operand::Reg(reg) if reg.class == RegClass::Gpr && reg.num == 1 && reg.size == 1 => {
+            Inst::new(&[if byte { 0xD2 } else { 0xD3 }]).sized(size).ext(digit).rm(as_rm(dst)?).finish()
+        }
+        _ => Err(format!("'{}' shifts by an immediate or cl", mnemonic)),
+    }
+}
+
+fn mov(ops: &[Operand]) -> Result<Encoded, String> {
+    operand_count("mov", ops, 2)?;
+    let size = integer_size(&[&ops[0], &ops[1]])?;
+    let byte = size == 1;
+    match (&ops[0], &ops[1]) {
+        (Operand::Reg(dst), Operand::Imm(value)) => {
+            let value = *value;
+            match size {
+                8 if fits_i32(value) => Inst::new(&[0xC7]).sized(8).ext(0).rm(Rm::Reg(*dst)).imm(value, 4).finish(),
+                8 => Inst::new(&[0xB8]).sized(8).plus(*dst).imm(value, 8).finish(),
+                _ => {
+                    check_imm(value, size)?;
+                    Inst::new(&[if byte { 0xB0 } else { 0xB8 }]).sized(size).plus(*dst).imm(value, size).finish()
+                }
+            }
+        }
+        (Operand::Reg(dst), src @ Operand::Address(..)) => match size {
+            8 => Inst::new(&[0xC7]).sized(8).ext(0).rm(Rm::Reg(*dst)).imm_operand(src, 4)?.finish(),
+            4 => {
+                let mut inst = Inst::new(&[0xB8]).plus(*dst).imm_operand(src, 4)?;
+                if let Some((Imm::Address(..), _)) = inst.imm {
+                    inst = inst.zero_extended_address();
+                }
+                inst.finish()
+            }
+            _ => Err("an address needs a 32- or 64-bit register".to_string()),
+        },
+        (dst @ Operand::Mem(_), src) if is_imm(src) => {
+            if size == 8
+                && let Operand::Imm(value) = src
+                && !fits_i32(*value)
+            {
+                return Err(format!("immediate {} does not fit in 32 bits", value));
+            }
+            Inst::new(&[if byte { 0xC6 } else { 0xC7 }]).sized(size).ext(0).rm(as_rm(dst)?).imm_operand(src, size.min(4))?.finish()
+        }
+        (Operand::Reg(dst), src @ Operand::Mem(_)) => {
+            Inst::new(&[if byte { 0x8A } else { 0x8B }]).sized(size).reg(*dst).rm(as_rm(src)?).finish()
+        }
+        (dst, src) => Inst::new(&[if byte { 0x88 } else { 0x89 }]).sized(size).reg(as_gpr(src)?).rm(as_rm(dst)?).finish(),
+    }
+}
+
+impl Inst<'_> {
+    /// `mov r32, OFFSET symbol` zero-extends the address.
+    fn zero_extended_address(mut self) -> Self {
+        self.imm = self.imm.map(|(imm, size)| match imm {
+            Imm::Address(name, addend) => (Imm::Address(format!("\0{}", name), addend), size),
+            other => (other, size),
+        });
+        self
+    }
+}
+
+fn imul(ops: &[Operand]) -> Result<Encoded, String> {
+    match ops {
+        [src] => {
+            let size = integer_size(&[src])?;
+            Inst::new(&[if size == 1 { 0xF6 } else { 0xF7 }]).sized(size).ext(5).rm(as_rm(src)?).finish()
+        }
+        [dst, src] if is_imm(src) => imul(&[dst.clone(), dst.clone(), src.clone()]),
+        [dst, src] => {
+            let size = integer_size(&[dst, src])?;
+            Inst::new(&[0x0F, 0xAF]).sized(size).reg(as_gpr(dst)?).rm(as_rm(src)?).finish()
+        }
+        [dst, src, imm] => {
+            let size = integer_size(&[dst, src])?;
+            let inst = Inst::new(&[0x69]).sized(size).reg(as_gpr(dst)?).rm(as_rm(src)?);
+            match imm {
+                Operand::Imm(value) if fits_i8(*value) => {
+                    let mut inst = inst.imm(*value, 1);
+                    inst.opcode = vec![0x6B];
+                    inst.finish()
+                }
+                Operand::Imm(value) if size == 8 && !fits_i32(*value) => {
+                    Err(format!("immediate {} does not fit in 32 bits", value))
+                }
+                _ => inst.imm_operand(imm, size.min(4))?.finish(),
+            }
+        }
+        _ => Err("'imul' takes 1 to 3 operands".to_string()),
+    }
+}
+
+fn extend(mnemonic: &str, ops: &[Operand]) -> Result<Encoded, String> {
+    operand_count(mnemonic, ops, 2)?;
+    let dst = as_gpr(&ops[0])?;
+    let src_size = match size_of(&ops[1]) {
+        Some(size) => size,
+        None if mnemonic == "movsxd" => 4,
+        None => return Err(format!("operand size of '{}' is ambiguous; add a size keyword", mnemonic)),
+    };
+    let opcode: &[u8] = match (mnemonic, src_size) {
+        ("movzx", 1) => &[0x0F, 0xB6],
+        ("movzx", 2) => &[0x0F, 0xB7],
+        ("movsx", 1) => &[0x0F, 0xBE],
+        ("movsx", 2) => &[0x0F, 0xBF],
+        ("movsx" | "movsxd", 4) => &[0x63],
+        _ => return Err(format!("'{}' cannot extend a {}-byte operand", mnemonic, src_size)),
+    };
+    if dst.size <= src_size && opcode != [0x63] {
+        return Err(format!("'{}' must widen its operand", mnemonic));
+    }
+    Inst::new(opcode).sized(dst.size).reg(dst).rm(as_rm(&ops[1])?).finish()
+}
+
+fn movd(mnemonic: &str, ops: &[Operand]) -> Result<Encoded, String> {
+    operand_count(mnemonic, ops, 2)?;
+    let wide = mnemonic == "movq";
+    match (&ops[0], &ops[1]) {
+        (Operand::Reg(dst), src) if dst.class == RegClass::Xmm => match src {
+            // movq xmm, xmm/m64
+            Operand::Reg(reg) if reg.class == RegClass::Xmm && wide => {
+                Inst::new(&[0x0F, 0x7E]).prefix(0xF3).reg(*dst).rm(Rm::Reg(*reg)).finish()
+            }
+            Operand::Mem(mem) if wide => Inst::new(&[0x0F, 0x7E]).prefix(0xF3).reg(*dst).rm(Rm::Mem(mem)).finish(),
+            _ => {
+                let mut inst = Inst::new(&[0x0F, 0x6E]).prefix(0x66).reg(*dst).rm(as_rm(src)?);
+                inst.rex_w = wide;
+                inst.finish()
+            }
+        },
+        (Operand::Mem(mem), Operand::Reg(src)) if wide && src.class == RegClass::Xmm => {
+            Inst::new(&[0x0F, 0xD6]).prefix(0x66).reg(*src).rm(Rm::Mem(mem)).finish()
+        }
+        (dst, Operand::Reg(src)) if src.class == RegClass::Xmm => {
+            let mut inst = Inst::new(&[0x0F, 0x7E]).prefix(0x66).reg(*src).rm(as_rm(dst)?);
+            inst.rex_w = wide;
+            inst.finish()
+        }
+        _ => Err(format!("'{}' moves between an xmm register and a general register or memory", mnemonic)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(text: &str) -> Vec<u8> {
+        encode(text).unwrap().bytes
+    }
+
+    #[test]
+    fn integer_forms_follow_gnu_as() {
+        assert_eq!(bytes("mov rbp, rsp"), [0x48, 0x89, 0xE5]);
+        assert_eq!(bytes("sub rsp, 32"), [0x48, 0x83, 0xEC, 0x20]);
+        assert_eq!(bytes("add eax, 1000"), [0x05, 0xE8, 0x03, 0, 0]);
+        assert_eq!(bytes("mov rsi, 0"), [0x48, 0xC7, 0xC6, 0, 0, 0, 0]);
+        assert_eq!(bytes("movabs rax, 0x123456789"), [0x48, 0xB8, 0x89, 0x67, 0x45, 0x23, 0x01, 0, 0, 0]);
+        assert_eq!(bytes("movsxd rax, DWORD PTR [rax]"), [0x48, 0x63, 0x00]);
+        assert_eq!(bytes("mov sil, 1"), [0x40, 0xB6, 0x01]);
+        assert_eq!(bytes("lock cmpxchg [rdx], rcx"), [0xF0, 0x48, 0x0F, 0xB1, 0x0A]);
+    }
+
+    #[test]
+    fn memory_operands() {
+        assert_eq!(bytes("lea rcx, [rbx + r9]"), [0x4A, 0x8D, 0x0C, 0x0B]);
+        assert_eq!(bytes("mov DWORD PTR [rbp-8], eax"), [0x89, 0x45, 0xF8]);
+        assert_eq!(bytes("mov ecx, [r10 + rax*4]"), [0x41, 0x8B, 0x0C, 0x82]);
+        assert_eq!(bytes("mov rax, QWORD PTR [rsp]"), [0x48, 0x8B, 0x04, 0x24]);
+        assert_eq!(bytes("mov rax, QWORD PTR [r13]"), [0x49, 0x8B, 0x45, 0x00]);
+    }
+
+    #[test]
+    fn rip_relative_fixups_count_trailing_immediates() {
+        let encoded = encode("mov DWORD PTR counter[rip], 5").unwrap();
+        assert_eq!(encoded.bytes, [0xC7, 0x05, 0, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(encoded.fixups, [Fixup { offset: 2, kind: FixupKind::Pc32, symbol: "counter".to_string(), addend: -8 }]);
+    }
+
+    #[test]
+    fn sse_and_avx() {
+        assert_eq!(bytes("movsd xmm0, QWORD PTR [rbp-32]"), [0xF2, 0x0F, 0x10, 0x45, 0xE0]);
+        assert_eq!(bytes("cvtsi2sd xmm0, rax"), [0xF2, 0x48, 0x0F, 0x2A, 0xC0]);
+        assert_eq!(bytes("movq rax, xmm1"), [0x66, 0x48, 0x0F, 0x7E, 0xC8]);
+        assert_eq!(bytes("vpaddd ymm0, ymm1, ymm2"), [0xC5, 0xF5, 0xFE, 0xC2]);
+    }
+
+    #[test]
+    fn branches_to_labels() {
+        assert_eq!(branch("jne f_7"), Some((Branch::Jcc(5), "f_7".to_string())));
+        assert_eq!(branch("call printf"), Some((Branch::Call, "printf".to_string())));
+        assert_eq!(branch("jmp rax"), None);
+        assert_eq!(bytes("jmp rax"), [0xFF, 0xE0]);
+    }
+
+    #[test]
+    fn rejects_invalid_operands() {
+        assert!(encode("add QWORD PTR [rbp-16], QWORD PTR [rbp-32]").is_err());
+        assert!(encode("mov ah, sil").is_err());
+        assert!(encode("frobnicate rax").is_err());
+    }
+}
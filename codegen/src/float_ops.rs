@@ -42,8 +42,19 @@ fn infer_double(generator: &FunctionGenerator, left: &Operand, right: &Operand)
     left_double || right_double
 }
 
+/// Load `var` into `reg` for an operation of the given precision. Unoptimized
+/// code can mix a `float` operand into a `double` operation, which widens it.
+fn load_fp(generator: &FunctionGenerator, is_double: bool, var: VarId, reg: X86Reg, op: X86Operand) -> X86Instr {
+    if is_double && matches!(generator.var_types.get(&var), Some(Type::Float)) {
+        X86Instr::Cvtss2sd(X86Operand::Reg(reg), op)
+    } else {
+        movfp(is_double, X86Operand::Reg(reg), op)
+    }
+}
+
 pub fn gen_float_binary_op(generator: &mut FunctionGenerator, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand) {
-    let is_double = infer_double(generator, left, right);
+    let is_double = infer_double(generator, left, right)
+        || matches!(generator.var_types.get(&dest), Some(Type::Double));
     let result_type = if is_double { Type::Double } else { Type::Float };
 
     // Load left operand into xmm0
@@ -54,7 +65,7 @@ pub fn gen_float_binary_op(generator: &mut FunctionGenerator, dest: VarId, op: &
         }
         Operand::Var(v) => {
             let left_op = generator.var_to_op(*v);
-            generator.asm.push(movfp(is_double, X86Operand::Reg(X86Reg::Xmm0), left_op));
+            generator.asm.push(load_fp(generator, is_double, *v, X86Reg::Xmm0, left_op));
         }
         Operand::Constant(c) => {
             generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::Imm(*c)));
@@ -71,7 +82,7 @@ pub fn gen_float_binary_op(generator: &mut FunctionGenerator, dest: VarId, op: &
         }
         Operand::Var(v) => {
             let right_op = generator.var_to_op(*v);
-            generator.asm.push(movfp(is_double, X86Operand::Reg(X86Reg::Xmm1), right_op));
+            generator.asm.push(load_fp(generator, is_double, *v, X86Reg::Xmm1, right_op));
         }
         Operand::Constant(c) => {
             generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::Imm(*c)));
@@ -140,10 +151,13 @@ pub fn gen_float_binary_op(generator: &mut FunctionGenerator, dest: VarId, op: &
 }
 
 pub fn gen_float_unary_op(generator: &mut FunctionGenerator, dest: VarId, op: &UnaryOp, src: &Operand) {
-    let is_double = match src {
-        Operand::Var(v) => generator.var_types.get(v).map(|t| matches!(t, Type::Double)).unwrap_or(false),
-        _ => false,
+    // A constant operand, which only unoptimized code negates, has the
+    // result's type
+    let typed = match src {
+        Operand::Var(v) => *v,
+        _ => dest,
     };
+    let is_double = generator.var_types.get(&typed).map(|t| matches!(t, Type::Double)).unwrap_or(false);
     let result_type = if is_double { Type::Double } else { Type::Float };
     generator.var_types.insert(dest, result_type);
     let d_op = generator.var_to_op(dest);
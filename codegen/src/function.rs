@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 use crate::x86::{X86Reg, X86Operand, X86Instr};
-use model::Type;
+use model::{BinaryOp, Type};
 use ir::{Function as IrFunction, VarId, BlockId, Operand, Instruction as IrInstruction, Terminator as IrTerminator, SimdOp};
-use crate::regalloc::{PhysicalReg, allocate_registers};
+use crate::regalloc::{PhysicalReg, allocate_registers_split, omits_frame_pointer};
 use crate::instructions::InstructionGenerator;
 use crate::types::TypeCalculator;
 use crate::float_ops::{gen_float_binary_op, gen_float_unary_op};
-use crate::memory_ops::{gen_load, gen_store, gen_gep};
+use crate::memory_ops::{gen_load, gen_store, gen_gep, gen_memcpy, gen_memset};
 use crate::call_ops::{gen_call, gen_indirect_call};
-use crate::calling_convention::get_convention;
+use crate::calling_convention::{get_convention, ArgAllocator, ArgLocation};
+use crate::call_ops::ArgPassing;
+use crate::debug_info::DebugInfo;
 
 /// Handles generation of code for a single function
 pub struct FunctionGenerator<'a> {
@@ -18,6 +20,8 @@ pub struct FunctionGenerator<'a> {
     pub(crate) structs: &'a HashMap<String, model::StructDef>,
     pub(crate) unions: &'a HashMap<String, model::UnionDef>,
     pub(crate) func_return_types: &'a HashMap<String, Type>,
+    /// Parameter types of the functions defined in the program
+    pub(crate) func_param_types: &'a HashMap<String, Vec<Type>>,
     pub(crate) float_constants: &'a mut HashMap<String, (f64, bool)>,
     pub(crate) next_float_const: &'a mut usize,
     pub(crate) target: &'a model::TargetConfig,
@@ -29,17 +33,33 @@ pub struct FunctionGenerator<'a> {
     pub(crate) var_types: HashMap<VarId, Type>,
     pub(crate) alloca_buffers: HashMap<VarId, i32>,
     pub(crate) current_saved_regs: Vec<X86Reg>,
+    /// Whether RBP holds the frame base, rather than RSP plus the frame size
+    pub(crate) frame_pointer: bool,
     pub(crate) enable_regalloc: bool,
     pub(crate) current_block: BlockId,
+    /// Name of the function being generated, for local labels
+    pub(crate) func_name: String,
     /// Maps IR VarIds to XMM/YMM register indices for vector operations
     pub(crate) simd_reg_map: HashMap<VarId, u8>,
     pub(crate) next_simd_reg: u8,
     /// Offset from RBP to the start of the register save area (for variadic functions)
     pub(crate) va_save_area_offset: Option<i32>,
+    /// Integer registers taken by the named parameters, which va_start skips
+    pub(crate) va_named_int_args: usize,
+    /// Floating-point registers taken by the named parameters
+    pub(crate) va_named_float_args: usize,
+    /// Bytes of stack taken by the named parameters, before the variadic ones
+    pub(crate) va_named_stack_size: usize,
+    /// Bytes of stack the calls made so far pass arguments in, not counting the shadow space
+    pub(crate) outgoing_args_size: i32,
+    /// Slot holding the address a struct or union returned in memory is written to
+    pub(crate) return_pointer: Option<i32>,
     /// Next synthetic VarId for codegen-generated temporaries
     pub(crate) next_temp_var: usize,
     pub(crate) profile_generate: bool,
     pub(crate) profile_counters: Option<&'a mut Vec<String>>,
+    /// DWARF line and variable info, under `-g`
+    pub(crate) debug_info: Option<&'a mut DebugInfo>,
 }
 
 impl<'a> FunctionGenerator<'a> {
@@ -52,18 +72,21 @@ impl<'a> FunctionGenerator<'a> {
         structs: &'a HashMap<String, model::StructDef>,
         unions: &'a HashMap<String, model::UnionDef>,
         func_return_types: &'a HashMap<String, Type>,
+        func_param_types: &'a HashMap<String, Vec<Type>>,
         float_constants: &'a mut HashMap<String, (f64, bool)>,
         next_float_const: &'a mut usize,
         enable_regalloc: bool,
         target: &'a model::TargetConfig,
         profile_generate: bool,
         profile_counters: Option<&'a mut Vec<String>>,
+        debug_info: Option<&'a mut DebugInfo>,
     ) -> Self {
         Self {
             asm: Vec::new(),
             structs,
             unions,
             func_return_types,
+            func_param_types,
             float_constants,
             next_float_const,
             target,
@@ -73,18 +96,35 @@ impl<'a> FunctionGenerator<'a> {
             var_types: HashMap::new(),
             alloca_buffers: HashMap::new(),
             current_saved_regs: Vec::new(),
+            frame_pointer: true,
             enable_regalloc,
             current_block: BlockId(0),
+            func_name: String::new(),
             simd_reg_map: HashMap::new(),
             next_simd_reg: 0,
             va_save_area_offset: None,
+            va_named_int_args: 0,
+            va_named_float_args: 0,
+            va_named_stack_size: 0,
+            outgoing_args_size: 0,
+            return_pointer: None,
             next_temp_var: 100_000,
             profile_generate,
             profile_counters,
+            debug_info,
         }
     }
 
     pub fn gen_function(mut self, func: &IrFunction) -> Vec<X86Instr> {
+        // Perform register allocation. Live range splitting may rewrite the
+        // function, which is then the one to emit.
+        let mut split = None;
+        if self.enable_regalloc {
+            (split, self.reg_alloc) = allocate_registers_split(func, self.target);
+        }
+        let func = split.as_ref().unwrap_or(func);
+
+        self.func_name = func.name.clone();
         // Seed var_types from IR-level type annotations (e.g. mem2reg phi vars)
         for (var, ty) in &func.var_types {
             self.var_types.insert(*var, ty.clone());
@@ -95,11 +135,8 @@ impl<'a> FunctionGenerator<'a> {
 
         // Get calling convention for this target
         let convention = self.convention();
-        
-        // Perform register allocation
-        if self.enable_regalloc {
-            self.reg_alloc = allocate_registers(func, self.target);
-        }
+        self.frame_pointer = !omits_frame_pointer(func, self.target);
+        let cfi = matches!(self.target.platform, model::Platform::Linux);
         
         // Identify used callee-saved registers
         self.current_saved_regs.clear();
@@ -111,26 +148,38 @@ impl<'a> FunctionGenerator<'a> {
         }
         
         self.asm.push(X86Instr::Label(func.name.clone()));
+        if let Some(debug) = self.debug_info.as_deref_mut() {
+            debug.begin_function();
+        }
+        self.mark_location(func.span);
         
         // CFI: start procedure
-        if matches!(self.target.platform, model::Platform::Linux) {
+        if cfi {
             self.asm.push(X86Instr::Raw(".cfi_startproc".to_string()));
         }
         
         // Prologue
-        self.asm.push(X86Instr::Push(X86Reg::Rbp));
-        if matches!(self.target.platform, model::Platform::Linux) {
-            self.asm.push(X86Instr::Raw(".cfi_def_cfa_offset 16".to_string()));
-            self.asm.push(X86Instr::Raw(".cfi_offset rbp, -16".to_string()));
-        }
-        self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rbp), X86Operand::Reg(X86Reg::Rsp)));
-        if matches!(self.target.platform, model::Platform::Linux) {
-            self.asm.push(X86Instr::Raw(".cfi_def_cfa_register rbp".to_string()));
+        if self.frame_pointer {
+            self.asm.push(X86Instr::Push(X86Reg::Rbp));
+            if cfi {
+                self.asm.push(X86Instr::Raw(".cfi_def_cfa_offset 16".to_string()));
+                self.asm.push(X86Instr::Raw(".cfi_offset rbp, -16".to_string()));
+            }
+            self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rbp), X86Operand::Reg(X86Reg::Rsp)));
+            if cfi {
+                self.asm.push(X86Instr::Raw(".cfi_def_cfa_register rbp".to_string()));
+            }
         }
         
-        // Push callee-saved registers
-        for reg in &self.current_saved_regs {
+        // Push callee-saved registers. Without a frame pointer the CFA stays
+        // relative to RSP, so every push moves it.
+        for (i, reg) in self.current_saved_regs.clone().iter().enumerate() {
             self.asm.push(X86Instr::Push(reg.clone()));
+            if !self.frame_pointer && cfi {
+                let offset = 16 + 8 * i;
+                self.asm.push(X86Instr::Raw(format!(".cfi_def_cfa_offset {}", offset)));
+                self.asm.push(X86Instr::Raw(format!(".cfi_offset {}, -{}", reg.to_str(), offset)));
+            }
         }
         
         // Account for pushed registers in stack slot allocation
@@ -144,23 +193,38 @@ impl<'a> FunctionGenerator<'a> {
         // additional stack slots beyond what allocate_stack_slots predicts.
         let sub_rsp_index = self.asm.len();
         self.asm.push(X86Instr::Sub(X86Operand::Reg(X86Reg::Rsp), X86Operand::Imm(0))); // placeholder
+        if !self.frame_pointer && cfi {
+            self.asm.push(X86Instr::Raw(String::new())); // CFA offset placeholder
+        }
 
-        let shadow_space = convention.shadow_space_size() as i32;
 
-        // Spill register parameters to a local save area if variadic.
-        // On SysV AMD64, we allocate 48 bytes (6 GP regs × 8) below the frame.
+        // Spill register parameters to a local save area if variadic, in
+        // ascending order so va_arg can walk it upwards. On SysV AMD64 the
+        // integer registers are followed by XMM0-7 in 16-byte slots, which
+        // are spilled only when AL says the caller passed any.
         if uses_va_start {
-            // Allocate save area slots
+            let nregs = convention.param_regs().len() as i32;
+            let fp_slots = if convention.passes_vector_count_in_al() { convention.float_param_regs().len() as i32 } else { 0 };
+            self.next_slot += nregs * 8 + fp_slots * 16;
             let save_base = self.next_slot;
             for (i, reg) in convention.param_regs().iter().enumerate() {
-                let slot_offset = save_base + (i * 8) as i32 + 8;
                 self.asm.push(X86Instr::Mov(
-                    X86Operand::Mem(X86Reg::Rbp, -(slot_offset as i32)),
+                    X86Operand::Mem(X86Reg::Frame, -save_base + (i * 8) as i32),
                     X86Operand::Reg(reg.clone())));
             }
+            if fp_slots > 0 {
+                let skip = format!("{}_va_fp_saved", func.name);
+                self.asm.push(X86Instr::Test(X86Operand::Reg(X86Reg::Al), X86Operand::Reg(X86Reg::Al)));
+                self.asm.push(X86Instr::Jcc("e".to_string(), skip.clone()));
+                for (i, reg) in convention.float_param_regs().iter().enumerate() {
+                    self.asm.push(X86Instr::Movsd(
+                        X86Operand::DoubleMem(X86Reg::Frame, -save_base + nregs * 8 + (i * 16) as i32),
+                        X86Operand::Reg(reg.clone())));
+                }
+                self.asm.push(X86Instr::Label(skip));
+            }
             // Record the save area base for va_start to reference
-            self.va_save_area_offset = Some(save_base + 8);
-            self.next_slot += (convention.param_regs().len() * 8) as i32;
+            self.va_save_area_offset = Some(save_base);
         }
 
         // Handle parameters
@@ -168,153 +232,113 @@ impl<'a> FunctionGenerator<'a> {
         let float_regs = convention.float_param_regs();
         
         // Build a list of (source_reg, dest_op) pairs to handle conflicts
-        let mut param_moves: Vec<(X86Operand, X86Operand, bool)> = Vec::new();
+        let mut param_moves: Vec<(X86Operand, X86Operand, Option<bool>)> = Vec::new();
+        // Stack parameters, loaded once the register moves are done since
+        // they go through RAX and XMM0: (offset from RBP, dest_op, float_kind)
+        let mut stack_loads: Vec<(i32, X86Operand, Option<bool>)> = Vec::new();
         
-        // Track actual register index (struct params may consume >1 register)
-        let mut reg_idx = 0usize;
-        let mut float_reg_idx = 0usize;
+        // Assigns each parameter (or struct eightbyte) its register or stack slot
+        let mut args = ArgAllocator::new(convention.as_ref());
+        // Struct and union parameters, with the address of the object each is
+        let mut aggregate_params: Vec<(VarId, X86Operand)> = Vec::new();
+
+        // A struct or union returned in memory is written where the hidden
+        // pointer passed ahead of the parameters points
+        if crate::call_ops::classify_aggregate(&self, &func.return_type).is_some_and(|passing| passing.returns_in_memory()) {
+            let temp = self.new_temp_var();
+            let slot = self.get_or_create_slot(temp);
+            self.return_pointer = Some(slot);
+            let ArgLocation::Reg(i) = args.next(false) else { unreachable!("the first argument has a register") };
+            self.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, slot), X86Operand::Reg(param_regs[i].clone())));
+        }
         
-        for (_i, (param_type, var)) in func.params.iter().enumerate() {
+        for (param_type, var) in &func.params {
             // Record parameter type for later use
             self.var_types.insert(*var, param_type.clone());
             
-            // Check if this is a struct parameter passed by value
-            let struct_class = crate::call_ops::classify_struct_arg(&self, param_type);
-            
-            if let Some(class) = struct_class {
-                // Struct parameter — assemble register values into the alloca buffer
-                let buffer_offset = self.alloca_buffers.get(var).copied()
-                    .unwrap_or_else(|| {
-                        let slot = self.get_or_create_slot(*var);
-                        slot
-                    });
-                
-                match class {
-                    crate::call_ops::StructArgClass::OneReg => {
-                        if reg_idx < param_regs.len() {
-                            param_moves.push((
-                                X86Operand::Reg(param_regs[reg_idx].clone()),
-                                X86Operand::Mem(X86Reg::Rbp, buffer_offset),
-                                false,
-                            ));
-                            reg_idx += 1;
-                        } else {
-                            // From stack
-                            let offset = 16 + shadow_space + ((reg_idx - param_regs.len()) * 8) as i32;
-                            self.asm.push(X86Instr::Mov(
-                                X86Operand::Reg(X86Reg::Rax),
-                                X86Operand::Mem(X86Reg::Rbp, offset),
-                            ));
-                            self.asm.push(X86Instr::Mov(
-                                X86Operand::Mem(X86Reg::Rbp, buffer_offset),
-                                X86Operand::Reg(X86Reg::Rax),
-                            ));
-                            reg_idx += 1;
-                        }
-                    }
-                    crate::call_ops::StructArgClass::TwoReg => {
-                        // First eightbyte
-                        if reg_idx < param_regs.len() {
-                            param_moves.push((
-                                X86Operand::Reg(param_regs[reg_idx].clone()),
-                                X86Operand::Mem(X86Reg::Rbp, buffer_offset),
-                                false,
-                            ));
-                        }
-                        reg_idx += 1;
-                        // Second eightbyte
-                        if reg_idx < param_regs.len() {
-                            param_moves.push((
-                                X86Operand::Reg(param_regs[reg_idx].clone()),
-                                X86Operand::Mem(X86Reg::Rbp, buffer_offset + 8),
-                                false,
-                            ));
-                        }
-                        reg_idx += 1;
-                    }
-                    crate::call_ops::StructArgClass::Memory => {
-                        // Large struct → pointer was passed in register
-                        if reg_idx < param_regs.len() {
-                            param_moves.push((
-                                X86Operand::Reg(param_regs[reg_idx].clone()),
-                                X86Operand::Mem(X86Reg::Rbp, self.stack_slots.get(var).copied()
-                                    .unwrap_or_else(|| self.get_or_create_slot(*var))),
-                                false,
-                            ));
+            // A struct or union passed by value: its eightbytes are stored to
+            // a buffer, or its object is where the caller left it
+            let mut reference = None;
+            if let Some(passing) = crate::call_ops::classify_aggregate(&self, param_type) {
+                let size = self.get_type_size(param_type);
+                let address = match crate::call_ops::next_arg(&mut args, Some(passing), size, false) {
+                    ArgPassing::Eightbytes(eightbytes) => match eightbytes[..] {
+                        [(_, ArgLocation::Stack(offset))] => Some(X86Operand::Mem(X86Reg::Frame, 16 + offset)),
+                        _ => {
+                            let buffer = self.alloc_aggregate(param_type);
+                            for (i, (class, location)) in eightbytes.into_iter().enumerate() {
+                                let src = match location {
+                                    ArgLocation::Reg(r) => X86Operand::Reg(param_regs[r].clone()),
+                                    ArgLocation::FloatReg(r) => X86Operand::Reg(float_regs[r].clone()),
+                                    ArgLocation::Stack(_) => unreachable!("a struct is passed wholly in registers or on the stack"),
+                                };
+                                let eightbyte = X86Operand::Mem(X86Reg::Frame, buffer + 8 * i as i32);
+                                self.asm.push(crate::call_ops::move_eightbyte(class, eightbyte, src));
+                            }
+                            Some(X86Operand::Mem(X86Reg::Frame, buffer))
                         }
-                        reg_idx += 1;
+                    },
+                    ArgPassing::Memory(offset) => Some(X86Operand::Mem(X86Reg::Frame, 16 + offset)),
+                    // Passed as a pointer to a copy, which is moved like one
+                    ArgPassing::Reference(location) | ArgPassing::Scalar(location) => {
+                        reference = Some(location);
+                        None
                     }
+                };
+                if let Some(address) = address {
+                    aggregate_params.push((*var, address));
+                    continue;
                 }
-                // Struct params live in memory (alloca buffer) for field access,
-                // so remove any GP register allocation the register allocator
-                // may have assigned — var_to_op must return the memory slot.
-                self.reg_alloc.remove(var);
-                continue;
             }
             
             // Non-struct parameter handling
-            // For non-float params with a register allocation, store directly
-            // to the allocated register to avoid redundant stack spills.
-            // Float params always use stack slots since the GP register allocator
-            // doesn't handle XMM registers.
-            let is_float = matches!(param_type, Type::Float | Type::Double);
+            // Params with a register of their class (GP, or XMM for float and
+            // double) are stored directly to it to avoid redundant stack spills.
+            let kind = float_kind(param_type);
+            let is_float = kind.is_some();
+            let reg = self.reg_alloc.get(var).copied().filter(|phys| phys.is_xmm() == is_float);
             let dest = if let Some(&buffer_offset) = self.alloca_buffers.get(var) {
-                X86Operand::Mem(X86Reg::Rbp, buffer_offset)
-            } else if !is_float {
-                if let Some(phys) = self.reg_alloc.get(var) {
-                    // Non-float parameter has a GP register — store directly to it
-                    X86Operand::Reg(phys.to_x86())
-                } else {
-                    let slot = self.stack_slots.get(var).copied().unwrap_or_else(|| self.get_or_create_slot(*var));
-                    X86Operand::Mem(X86Reg::Rbp, slot)
-                }
-            } else if let Some(var_type) = self.var_types.get(var) {
-                if matches!(var_type, Type::Float | Type::Double) {
-                    let slot = self.stack_slots.get(var).copied().unwrap_or_else(|| self.get_or_create_slot(*var));
-                    X86Operand::FloatMem(X86Reg::Rbp, slot)
-                } else {
-                    let slot = self.stack_slots.get(var).copied().unwrap_or_else(|| self.get_or_create_slot(*var));
-                    X86Operand::Mem(X86Reg::Rbp, slot)
-                }
+                X86Operand::Mem(X86Reg::Frame, buffer_offset)
+            } else if let Some(phys) = reg {
+                X86Operand::Reg(phys.to_x86())
             } else {
                 let slot = self.stack_slots.get(var).copied().unwrap_or_else(|| self.get_or_create_slot(*var));
-                X86Operand::Mem(X86Reg::Rbp, slot)
+                match kind {
+                    Some(true) => X86Operand::DoubleMem(X86Reg::Frame, slot),
+                    Some(false) => X86Operand::FloatMem(X86Reg::Frame, slot),
+                    None => X86Operand::Mem(X86Reg::Frame, slot),
+                }
             };
             
             // For float params that got GP register assignments, remove from
             // reg_alloc so var_to_op returns the float stack slot instead
-            if is_float {
+            if is_float && reg.is_none() {
                 self.reg_alloc.remove(var);
             }
             
-            if is_float {
-                if float_reg_idx < float_regs.len() {
-                    let src = X86Operand::Reg(float_regs[float_reg_idx].clone());
-                    if src != dest {
-                        param_moves.push((src, dest, true));
-                    }
-                    float_reg_idx += 1;
-                }
-                reg_idx += 1;
-            } else if reg_idx < param_regs.len() {
-                let src = X86Operand::Reg(param_regs[reg_idx].clone());
-                if src != dest {
-                    param_moves.push((src, dest, false));
-                }
-                reg_idx += 1;
-            } else {
-                // Parameters beyond register count are on the stack
-                let offset = 16 + shadow_space + ((reg_idx - param_regs.len()) * 8) as i32;
-                if is_float {
-                    self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), X86Operand::FloatMem(X86Reg::Rbp, offset as i32)));
-                    self.asm.push(X86Instr::Movss(dest, X86Operand::Reg(X86Reg::Xmm0)));
-                } else {
-                    self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Rbp, offset as i32)));
-                    self.asm.push(X86Instr::Mov(dest, X86Operand::Reg(X86Reg::Rax)));
+            // A double param whose address is taken lives in its alloca buffer
+            let dest = match dest {
+                X86Operand::Mem(base, offset) if matches!(param_type, Type::Double) => X86Operand::DoubleMem(base, offset),
+                dest => dest,
+            };
+            let src = match reference.unwrap_or_else(|| args.next(is_float)) {
+                ArgLocation::Reg(i) => X86Operand::Reg(param_regs[i].clone()),
+                ArgLocation::FloatReg(i) => X86Operand::Reg(float_regs[i].clone()),
+                ArgLocation::Stack(offset) => {
+                    // Parameters beyond register count are on the stack,
+                    // above the return address and saved RBP
+                    stack_loads.push((16 + offset, dest, kind));
+                    continue;
                 }
-                reg_idx += 1;
+            };
+            if src != dest {
+                param_moves.push((src, dest, kind));
             }
         }
+        // va_start skips the registers and stack the named parameters took
+        self.va_named_int_args = args.int_regs_used();
+        self.va_named_float_args = args.float_regs_used();
+        self.va_named_stack_size = args.stack_size();
         
         // Execute parameter moves, handling conflicts by breaking cycles
         let mut completed = vec![false; param_moves.len()];
@@ -327,7 +351,7 @@ impl<'a> FunctionGenerator<'a> {
                     continue;
                 }
                 
-                let (ref src, ref dst, is_float) = param_moves[i];
+                let (ref src, ref dst, kind) = param_moves[i];
                 
                 // Check if dst conflicts with any uncompleted src
                 let has_conflict = param_moves.iter().enumerate().any(|(j, (s, _, _))| {
@@ -336,11 +360,7 @@ impl<'a> FunctionGenerator<'a> {
                 
                 if !has_conflict {
                     // Safe to move
-                    if is_float {
-                        self.asm.push(X86Instr::Movss(dst.clone(), src.clone()));
-                    } else {
-                        self.asm.push(X86Instr::Mov(dst.clone(), src.clone()));
-                    }
+                    self.asm.push(move_of_kind(kind, dst.clone(), src.clone()));
                     completed[i] = true;
                     made_progress = true;
                 }
@@ -354,7 +374,7 @@ impl<'a> FunctionGenerator<'a> {
                         continue;
                     }
                     
-                    let (ref src_i, ref dst_i, is_float_i) = param_moves[i];
+                    let (ref src_i, ref dst_i, kind_i) = param_moves[i];
                     
                     // Look for the other move in the cycle (where dst_i == src_j)
                     for j in 0..param_moves.len() {
@@ -362,22 +382,17 @@ impl<'a> FunctionGenerator<'a> {
                             continue;
                         }
                         
-                        let (ref src_j, ref dst_j, is_float_j) = param_moves[j];
+                        let (ref src_j, ref dst_j, kind_j) = param_moves[j];
                         
                         if dst_i == src_j && src_i == dst_j {
                             // Found a 2-cycle: swap regi <-> regj
                             // Standard 3-instruction swap: temp = src_i; dst_j = src_j; dst_i = temp
-                            assert_eq!(is_float_i, is_float_j, "Float/int mismatch in cycle");
+                            assert_eq!(kind_i.is_some(), kind_j.is_some(), "Float/int mismatch in cycle");
                             
-                            if is_float_i {
-                                self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm7), src_i.clone()));
-                                self.asm.push(X86Instr::Movss(dst_j.clone(), src_j.clone()));
-                                self.asm.push(X86Instr::Movss(dst_i.clone(), X86Operand::Reg(X86Reg::Xmm7)));
-                            } else {
-                                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::R10), src_i.clone()));
-                                self.asm.push(X86Instr::Mov(dst_j.clone(), src_j.clone()));
-                                self.asm.push(X86Instr::Mov(dst_i.clone(), X86Operand::Reg(X86Reg::R10)));
-                            }
+                            let temp = X86Operand::Reg(if kind_i.is_some() { X86Reg::Xmm7 } else { X86Reg::R10 });
+                            self.asm.push(move_of_kind(kind_i, temp.clone(), src_i.clone()));
+                            self.asm.push(move_of_kind(kind_j, dst_j.clone(), src_j.clone()));
+                            self.asm.push(move_of_kind(kind_i, dst_i.clone(), temp));
                             completed[i] = true;
                             completed[j] = true;
                             made_progress = true;
@@ -395,14 +410,10 @@ impl<'a> FunctionGenerator<'a> {
                 if !made_progress {
                     for i in 0..param_moves.len() {
                         if !completed[i] {
-                            let (ref src, ref dst, is_float) = param_moves[i];
-                            if is_float {
-                                self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm7), src.clone()));
-                                self.asm.push(X86Instr::Movss(dst.clone(), X86Operand::Reg(X86Reg::Xmm7)));
-                            } else {
-                                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::R10), src.clone()));
-                                self.asm.push(X86Instr::Mov(dst.clone(), X86Operand::Reg(X86Reg::R10)));
-                            }
+                            let (ref src, ref dst, kind) = param_moves[i];
+                            let temp = X86Operand::Reg(if kind.is_some() { X86Reg::Xmm7 } else { X86Reg::R10 });
+                            self.asm.push(move_of_kind(kind, temp.clone(), src.clone()));
+                            self.asm.push(move_of_kind(kind, dst.clone(), temp));
                             completed[i] = true;
                             break;
                         }
@@ -410,6 +421,25 @@ impl<'a> FunctionGenerator<'a> {
                 }
             }
         }
+
+        for (offset, dest, kind) in stack_loads {
+            if kind == Some(true) {
+                self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), X86Operand::DoubleMem(X86Reg::Frame, offset)));
+                self.asm.push(X86Instr::Movsd(dest, X86Operand::Reg(X86Reg::Xmm0)));
+            } else if kind == Some(false) {
+                self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), X86Operand::FloatMem(X86Reg::Frame, offset)));
+                self.asm.push(X86Instr::Movss(dest, X86Operand::Reg(X86Reg::Xmm0)));
+            } else {
+                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, offset)));
+                self.asm.push(X86Instr::Mov(dest, X86Operand::Reg(X86Reg::Rax)));
+            }
+        }
+
+        // Once the registers they may be given are free
+        for (var, address) in aggregate_params {
+            let dest = self.var_to_op(var);
+            self.emit_lea_to(&dest, address);
+        }
         
         // Remove parameters from reg_alloc only if they were NOT register-allocated.
         // Register-allocated params were stored directly to their assigned register
@@ -417,9 +447,16 @@ impl<'a> FunctionGenerator<'a> {
         // need reg_alloc cleared so var_to_op returns the stack slot.
         // (Nothing to do — params without a reg_alloc entry already get stack slots.)
 
+        let branch_targets: std::collections::HashSet<BlockId> =
+            func.compute_successors().into_values().flatten().collect();
         for block in &func.blocks {
-            // Skip unreachable blocks (marked by CFG simplification)
-            if block.instructions.is_empty() && matches!(block.terminator, IrTerminator::Unreachable) {
+            // Skip unreachable blocks (marked by CFG simplification). An empty
+            // block can still be the target of a branch, e.g. after a call that
+            // does not return, so it is kept then.
+            if block.instructions.is_empty()
+                && matches!(block.terminator, IrTerminator::Unreachable)
+                && !branch_targets.contains(&block.id)
+            {
                 continue;
             }
             
@@ -437,9 +474,11 @@ impl<'a> FunctionGenerator<'a> {
                     counter
                 )));
             }
-            for inst in &block.instructions {
+            for (i, inst) in block.instructions.iter().enumerate() {
+                self.mark_location(block.span_of(i));
                 self.gen_instr(inst);
             }
+            self.mark_location(block.terminator_span);
             self.gen_terminator(&block.terminator, &func.name, func);
         }
 
@@ -449,22 +488,17 @@ impl<'a> FunctionGenerator<'a> {
         let locals_size = self.next_slot - saved_size;
         let shadow_space = convention.shadow_space_size() as i32;
         
-        // Compute maximum stack space needed for outgoing call arguments
-        let num_param_regs = convention.param_regs().len();
-        let max_call_stack_args = func.blocks.iter()
-            .flat_map(|b| b.instructions.iter())
-            .filter_map(|inst| match inst {
-                IrInstruction::Call { args, .. } => Some(args.len()),
-                IrInstruction::IndirectCall { args, .. } => Some(args.len()),
-                _ => None,
-            })
-            .map(|n| if n > num_param_regs { ((n - num_param_regs) * 8) as i32 } else { 0 })
-            .max()
-            .unwrap_or(0);
-        
-        let total_stack = saved_size + locals_size + shadow_space + max_call_stack_args;
+        let total_stack = saved_size + locals_size + shadow_space + self.outgoing_args_size;
         let aligned_total = (total_stack + 15) & !15;
-        let sub_amount = aligned_total - saved_size;
+        let mut sub_amount = aligned_total - saved_size;
+        if !self.frame_pointer {
+            // RBP was not pushed; keep the slots where `push rbp` would have put them
+            sub_amount += 8;
+            if cfi {
+                let cfa_offset = 8 + saved_size + sub_amount;
+                self.asm[sub_rsp_index + 1] = X86Instr::Raw(format!(".cfi_def_cfa_offset {}", cfa_offset));
+            }
+        }
         
         if sub_amount > 0 {
             self.asm[sub_rsp_index] = X86Instr::Sub(X86Operand::Reg(X86Reg::Rsp), X86Operand::Imm(sub_amount as i64));
@@ -473,9 +507,34 @@ impl<'a> FunctionGenerator<'a> {
             self.asm[sub_rsp_index] = X86Instr::Raw(String::new());
         }
 
+        // Frame slots are offsets from where RBP would point; without a
+        // frame pointer that is the final RSP plus the frame size.
+        let frame_base = (!self.frame_pointer).then_some(aligned_total);
+        let (base, delta) = match frame_base {
+            Some(size) => (X86Reg::Rsp, size),
+            None => (X86Reg::Rbp, 0),
+        };
+        for instr in &mut self.asm {
+            for op in instr.operands_mut() {
+                op.resolve_frame(&base, delta);
+            }
+        }
+
+        if let Some(debug) = self.debug_info.take() {
+            debug.add_function(func, &self.alloca_buffers, frame_base);
+        }
         self.asm
     }
 
+    /// Under `-g`, mark the code that follows as coming from `span`.
+    fn mark_location(&mut self, span: model::Span) {
+        if let Some(debug) = self.debug_info.as_deref_mut()
+            && let Some((file, line, column)) = debug.step(span)
+        {
+            self.asm.push(X86Instr::Loc(file, line, column));
+        }
+    }
+
     fn allocate_stack_slots(&mut self, func: &IrFunction) {
         // Only allocate stack slots for variables that:
         // 1. Need Alloca (arrays/structs) - these always need stack space
@@ -536,7 +595,8 @@ impl<'a> FunctionGenerator<'a> {
                         }
                     }
                     IrInstruction::Store { .. } | IrInstruction::VaStart { .. } |
-                    IrInstruction::VaEnd { .. } | IrInstruction::VaCopy { .. } => {}
+                    IrInstruction::VaEnd { .. } | IrInstruction::VaCopy { .. } |
+                    IrInstruction::Memcpy { .. } | IrInstruction::Memset { .. } => {}
                     IrInstruction::Simd { dest, .. } => {
                         // Vector vars use XMM/YMM registers, not GPR stack slots
                         // But we need a slot for the scalar dest of HorizontalAdd
@@ -563,22 +623,29 @@ impl<'a> FunctionGenerator<'a> {
 
     fn gen_instr(&mut self, inst: &IrInstruction) {
         match inst {
-            IrInstruction::Cast { dest, src, r#type } => {
-                self.gen_cast(*dest, src, r#type);
+            IrInstruction::Cast { dest, src, src_type, r#type } => {
+                self.gen_cast(*dest, src, src_type, r#type);
             }
             IrInstruction::Copy { dest, src} => {
                 self.gen_copy(*dest, src);
             }
             IrInstruction::Binary { dest, op, left, right } => {
-                let l_op = self.materialize_operand(left, X86Reg::R10);
-                let r_op = self.materialize_operand(right, X86Reg::R11);
+                let is_comparison = matches!(op, BinaryOp::EqualEqual | BinaryOp::NotEqual | BinaryOp::Less
+                    | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual);
+                let (l_op, r_op) = if is_comparison && self.is_dword_int(left) && self.is_dword_int(right) {
+                    // A 32-bit value may sit sign- or zero-extended in a
+                    // 64-bit register, so compare only the low 32 bits.
+                    (narrow_to_dword(self.operand_to_op(left)), narrow_to_dword(self.operand_to_op(right)))
+                } else {
+                    (self.materialize_operand(left, X86Reg::R10), self.materialize_operand(right, X86Reg::R11))
+                };
 
                 let d_op = self.var_to_op(*dest);
-                // Determine signedness for shift direction: unsigned types use shr, signed use sar
-                let is_signed = if let Operand::Var(lv) = left {
-                    !matches!(self.var_types.get(lv), Some(model::Type::UnsignedInt | model::Type::UnsignedChar | model::Type::UnsignedShort | model::Type::UnsignedLong | model::Type::UnsignedLongLong))
-                } else {
-                    true // literals and globals default to signed
+                // A shift is arithmetic unless its left operand is unsigned;
+                // division and comparison are unsigned if either operand is
+                let is_signed = match op {
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight => !self.is_unsigned_operand(left),
+                    _ => !self.is_unsigned_operand(left) && !self.is_unsigned_operand(right),
                 };
                 InstructionGenerator::gen_binary_op(&mut self.asm, *dest, op, l_op, r_op, d_op, is_signed);
             }
@@ -606,34 +673,44 @@ impl<'a> FunctionGenerator<'a> {
             IrInstruction::GetElementPtr { dest, base, index, element_type } => {
                 gen_gep(self, *dest, base, index, element_type);
             }
-            IrInstruction::Call { dest, name, args } => {
+            IrInstruction::Memcpy { dest, src, size } => {
+                gen_memcpy(self, dest, src, *size);
+            }
+            IrInstruction::Memset { dest, value, size } => {
+                gen_memset(self, dest, value, *size);
+            }
+            IrInstruction::Call { dest, name, args, .. } => {
                 gen_call(self, dest, name, args);
             }
-            IrInstruction::IndirectCall { dest, func_ptr, args } => {
-                gen_indirect_call(self, dest, func_ptr, args);
+            IrInstruction::IndirectCall { dest, func_ptr, args, is_variadic, .. } => {
+                gen_indirect_call(self, dest, func_ptr, args, *is_variadic);
             }
             IrInstruction::InlineAsm { template, outputs, inputs, output_constraints, input_constraints, clobbers, is_volatile } => {
                 self.gen_inline_asm(template, outputs, inputs, output_constraints, input_constraints, clobbers, *is_volatile);
             }
             IrInstruction::VaStart { list, arg_index } => {
+                if self.convention().passes_vector_count_in_al() {
+                    self.gen_sysv_va_start(list);
+                    return;
+                }
                 // va_list is a simple pointer to the next argument.
                 // The register save area is at known negative offsets from RBP.
-                // va_start(ap, last_fixed): ap = &save_area[arg_index + 1]
-                // where arg_index is the index of the last fixed parameter.
+                // va_start(ap, last_fixed): ap = &save_area[n], where n is the
+                // number of integer registers the named parameters took.
                 let next_index = *arg_index + 1;
                 if let Some(save_base) = self.va_save_area_offset {
                     // Point to the next argument in the register save area
-                    let offset = save_base + (next_index * 8) as i32;
+                    let offset = save_base - (self.va_named_int_args * 8) as i32;
                     self.asm.push(X86Instr::Lea(
                         X86Operand::Reg(X86Reg::Rax),
-                        X86Operand::Mem(X86Reg::Rbp, -(offset as i32)),
+                        X86Operand::Mem(X86Reg::Frame, -offset),
                     ));
                 } else {
                     // Fallback for non-variadic (shouldn't happen)
                     let offset = 16 + next_index * 8;
                     self.asm.push(X86Instr::Lea(
                         X86Operand::Reg(X86Reg::Rax),
-                        X86Operand::Mem(X86Reg::Rbp, offset as i32),
+                        X86Operand::Mem(X86Reg::Frame, offset as i32),
                     ));
                 }
                 let list_dest = self.operand_to_op(list);
@@ -643,12 +720,20 @@ impl<'a> FunctionGenerator<'a> {
                 // No-op for now
             }
             IrInstruction::VaCopy { dest, src } => {
+                if self.convention().passes_vector_count_in_al() {
+                    self.gen_sysv_va_copy(dest, src);
+                    return;
+                }
                 let s_op = self.operand_to_op(src);
                 self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), s_op));
                 let d_op = self.operand_to_op(dest);
                 self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
             }
             IrInstruction::VaArg { dest, list, r#type } => {
+                if self.convention().passes_vector_count_in_al() {
+                    self.gen_sysv_va_arg(*dest, list, r#type);
+                    return;
+                }
                 // va_arg(ap, type): load value from *ap, then advance ap by 8
                 // 1. Load current ap value (pointer to next arg)
                 let list_op = self.operand_to_op(list);
@@ -668,6 +753,96 @@ impl<'a> FunctionGenerator<'a> {
         }
     }
 
+    /// A frame buffer for a SysV `__va_list_tag`: the `gp_offset` and
+    /// `fp_offset` of the next argument in the register save area (32 bits
+    /// each), then the `overflow_arg_area` and `reg_save_area` pointers. A
+    /// `va_list` points at one.
+    fn alloc_va_list_tag(&mut self) -> i32 {
+        self.next_slot += 24;
+        -self.next_slot
+    }
+
+    /// va_start(ap, last_fixed) on SysV AMD64: fill a fresh tag past the
+    /// registers and stack the named parameters took, and point `*list` at it
+    fn gen_sysv_va_start(&mut self, list: &Operand) {
+        let save_base = self.va_save_area_offset.unwrap_or(self.next_slot);
+        let gp_offset = (self.va_named_int_args * 8) as i64;
+        let fp_offset = (self.convention().param_regs().len() * 8 + self.va_named_float_args * 16) as i64;
+        let tag = self.alloc_va_list_tag();
+        let rax = X86Operand::Reg(X86Reg::Rax);
+        self.asm.extend([
+            X86Instr::Mov(rax.clone(), X86Operand::Imm(fp_offset << 32 | gp_offset)),
+            X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, tag), rax.clone()),
+            // Stack arguments start above the return address and saved RBP
+            X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Frame, 16 + self.va_named_stack_size as i32)),
+            X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, tag + 8), rax.clone()),
+            X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Frame, -save_base)),
+            X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, tag + 16), rax.clone()),
+            X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Frame, tag)),
+        ]);
+        self.load_address_into(list, X86Reg::Rcx);
+        self.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rcx, 0), rax));
+    }
+
+    /// va_copy(dest, src) on SysV AMD64: `*dest` points at a copy of the tag
+    /// `src` points at, so the two lists advance independently
+    fn gen_sysv_va_copy(&mut self, dest: &Operand, src: &Operand) {
+        let tag = self.alloc_va_list_tag();
+        let rax = X86Operand::Reg(X86Reg::Rax);
+        self.load_address_into(src, X86Reg::Rcx);
+        for offset in [0, 8, 16] {
+            self.asm.push(X86Instr::Mov(rax.clone(), X86Operand::Mem(X86Reg::Rcx, offset)));
+            self.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Frame, tag + offset), rax.clone()));
+        }
+        self.asm.push(X86Instr::Lea(rax.clone(), X86Operand::Mem(X86Reg::Frame, tag)));
+        self.load_address_into(dest, X86Reg::Rcx);
+        self.asm.push(X86Instr::Mov(X86Operand::Mem(X86Reg::Rcx, 0), rax));
+    }
+
+    /// va_arg(ap, type) on SysV AMD64: take the next `double` from the XMM
+    /// part of the register save area and anything else from the integer
+    /// part, or the next stack slot once that part is used up
+    fn gen_sysv_va_arg(&mut self, dest: VarId, list: &Operand, r#type: &Type) {
+        let is_float = matches!(r#type, Type::Float | Type::Double);
+        let (field, limit, step) = if is_float {
+            (4, self.va_save_area_size(), 16)
+        } else {
+            (0, (self.convention().param_regs().len() * 8) as i64, 8)
+        };
+        let stack = format!("{}_va_stack_{}", self.func_name, dest.0);
+        let done = format!("{}_va_done_{}", self.func_name, dest.0);
+        let (rax, rcx, edx) = (X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx), X86Operand::Reg(X86Reg::Edx));
+        self.load_address_into(list, X86Reg::Rcx);
+        // RAX = the argument's address
+        self.asm.extend([
+            X86Instr::Mov(rcx.clone(), X86Operand::Mem(X86Reg::Rcx, 0)),
+            X86Instr::Mov(edx.clone(), X86Operand::DwordMem(X86Reg::Rcx, field)),
+            X86Instr::Cmp(edx.clone(), X86Operand::Imm(limit)),
+            X86Instr::Jcc("ae".to_string(), stack.clone()),
+            X86Instr::Mov(rax.clone(), X86Operand::Mem(X86Reg::Rcx, 16)),
+            X86Instr::Add(rax.clone(), X86Operand::Reg(X86Reg::Rdx)),
+            X86Instr::Add(edx.clone(), X86Operand::Imm(step)),
+            X86Instr::Mov(X86Operand::DwordMem(X86Reg::Rcx, field), edx),
+            X86Instr::Jmp(done.clone()),
+            X86Instr::Label(stack),
+            X86Instr::Mov(rax.clone(), X86Operand::Mem(X86Reg::Rcx, 8)),
+            X86Instr::Lea(X86Operand::Reg(X86Reg::Rdx), X86Operand::Mem(X86Reg::Rax, 8)),
+            X86Instr::Mov(X86Operand::Mem(X86Reg::Rcx, 8), X86Operand::Reg(X86Reg::Rdx)),
+            X86Instr::Label(done),
+        ]);
+        let addr = self.new_temp_var();
+        let addr_op = self.var_to_op(addr);
+        self.asm.push(X86Instr::Mov(addr_op, rax));
+        gen_load(self, dest, &Operand::Var(addr), r#type);
+    }
+
+    /// Size of the SysV register save area: the integer parameter
+    /// registers, then the XMM ones in 16-byte slots
+    fn va_save_area_size(&self) -> i64 {
+        let convention = self.convention();
+        (convention.param_regs().len() * 8 + convention.float_param_regs().len() * 16) as i64
+    }
+
     /// Create a new temporary VarId for codegen use (e.g., struct decomposition).
     pub(crate) fn new_temp_var(&mut self) -> VarId {
         let id = self.next_temp_var;
@@ -686,25 +861,36 @@ impl<'a> FunctionGenerator<'a> {
         slot
     }
 
+    /// Reserve a frame buffer for a struct or union value, in whole eightbytes.
+    pub(crate) fn alloc_aggregate(&mut self, ty: &Type) -> i32 {
+        let size = (self.get_type_size(ty).max(1) + 15) & !15;
+        self.next_slot = (self.next_slot + 15) & !15;
+        self.next_slot += size as i32;
+        -self.next_slot
+    }
+
     pub(crate) fn var_to_op(&mut self, var: VarId) -> X86Operand {
         if let Some(&buffer_offset) = self.alloca_buffers.get(&var) {
-            return X86Operand::Mem(X86Reg::Rbp, buffer_offset);
+            return X86Operand::Mem(X86Reg::Frame, buffer_offset);
+        }
+        if let Some(reg) = self.reg_alloc.get(&var).filter(|reg| reg.is_xmm()) {
+            return X86Operand::Reg(reg.to_x86());
         }
         if let Some(var_type) = self.var_types.get(&var) {
             if matches!(var_type, Type::Double) {
                 let slot = self.get_or_create_slot(var);
-                return X86Operand::DoubleMem(X86Reg::Rbp, slot);
+                return X86Operand::DoubleMem(X86Reg::Frame, slot);
             }
             if matches!(var_type, Type::Float) {
                 let slot = self.get_or_create_slot(var);
-                return X86Operand::FloatMem(X86Reg::Rbp, slot);
+                return X86Operand::FloatMem(X86Reg::Frame, slot);
             }
         }
         if let Some(reg) = self.reg_alloc.get(&var) {
             return X86Operand::Reg(reg.to_x86());
         }
         let slot = self.get_or_create_slot(var);
-        X86Operand::Mem(X86Reg::Rbp, slot)
+        X86Operand::Mem(X86Reg::Frame, slot)
     }
 
     pub(crate) fn operand_to_op(&mut self, op: &Operand) -> X86Operand {
@@ -715,9 +901,6 @@ impl<'a> FunctionGenerator<'a> {
                 X86Operand::RipRelLabel(label)
             }
             Operand::Var(v) => self.var_to_op(*v),
-            Operand::Global(s) if s.starts_with("__label_addr_") => {
-                X86Operand::GlobalQwordMem(s.clone())
-            }
             Operand::Global(s) => X86Operand::Label(s.clone()),
         }
     }
@@ -736,10 +919,16 @@ impl<'a> FunctionGenerator<'a> {
     }
 
     pub(crate) fn get_type_size(&self, r#type: &model::Type) -> usize {
-        let calculator = TypeCalculator::new(self.structs, self.unions);
+        let calculator = TypeCalculator::new(self.structs, self.unions)
+            .with_data_layout(self.target.data_layout);
         calculator.get_type_size(r#type)
     }
 
+    /// Layout calculator for the current program and target.
+    pub(crate) fn type_layout(&self) -> model::TypeLayout<'_> {
+        model::TypeLayout::new(self.structs, self.unions).with_data_layout(self.target.data_layout)
+    }
+
     /// Allocate the next available XMM register for a vector variable
     fn alloc_simd_reg(&mut self, var: VarId) -> u8 {
         if let Some(&r) = self.simd_reg_map.get(&var) {
@@ -772,7 +961,7 @@ impl<'a> FunctionGenerator<'a> {
     }
 
     /// Emit LEA into `dest`, routing through Rax if `dest` is a memory operand.
-    fn emit_lea_to(&mut self, dest: &X86Operand, src: X86Operand) {
+    pub(crate) fn emit_lea_to(&mut self, dest: &X86Operand, src: X86Operand) {
         match dest {
             X86Operand::Reg(_) => {
                 self.asm.push(X86Instr::Lea(dest.clone(), src));
@@ -789,7 +978,7 @@ impl<'a> FunctionGenerator<'a> {
     pub(crate) fn materialize_operand(&mut self, operand: &Operand, scratch_reg: X86Reg) -> X86Operand {
         if let Operand::Var(var) = operand {
             if let Some(off) = self.alloca_buffers.get(var) {
-                self.asm.push(X86Instr::Lea(X86Operand::Reg(scratch_reg.clone()), X86Operand::Mem(X86Reg::Rbp, *off)));
+                self.asm.push(X86Instr::Lea(X86Operand::Reg(scratch_reg.clone()), X86Operand::Mem(X86Reg::Frame, *off)));
                 return X86Operand::Reg(scratch_reg);
             }
         }
@@ -797,9 +986,35 @@ impl<'a> FunctionGenerator<'a> {
             self.asm.push(X86Instr::Lea(X86Operand::Reg(scratch_reg.clone()), X86Operand::RipRelLabel(name.clone())));
             return X86Operand::Reg(scratch_reg);
         }
+        // Only `mov` takes a 64-bit immediate; `cmp`, `add`, and the rest
+        // need it in a register.
+        if let Operand::Constant(c) = operand {
+            if i32::try_from(*c).is_err() {
+                self.asm.push(X86Instr::Mov(X86Operand::Reg(scratch_reg.clone()), X86Operand::Imm(*c)));
+                return X86Operand::Reg(scratch_reg);
+            }
+        }
         self.operand_to_op(operand)
     }
 
+    /// Whether `operand` is an `int` or `unsigned int` value, or a constant
+    /// that fits in 32 bits.
+    pub(crate) fn is_dword_int(&self, operand: &Operand) -> bool {
+        match operand {
+            Operand::Constant(c) => i32::try_from(*c).is_ok() || u32::try_from(*c).is_ok(),
+            Operand::Var(v) => matches!(self.var_types.get(v), Some(Type::Int | Type::UnsignedInt))
+                && !self.alloca_buffers.contains_key(v),
+            _ => false,
+        }
+    }
+
+    /// Whether `operand` is a variable of an unsigned type that integer
+    /// promotion keeps unsigned. Constants and globals count as signed.
+    pub(crate) fn is_unsigned_operand(&self, operand: &Operand) -> bool {
+        let Operand::Var(v) = operand else { return false };
+        matches!(self.var_types.get(v), Some(Type::UnsignedInt | Type::UnsignedLong | Type::UnsignedLongLong))
+    }
+
     /// Load the effective address of `operand` into `dest_reg`.
     /// Alloca buffer → LEA [rbp+off], Global → LEA name[rip], otherwise MOV from operand slot.
     pub(crate) fn load_address_into(&mut self, operand: &Operand, dest_reg: X86Reg) {
@@ -811,121 +1026,188 @@ impl<'a> FunctionGenerator<'a> {
     }
 
     /// Generate x86 instructions for an IR Cast instruction.
-    fn gen_cast(&mut self, dest: VarId, src: &Operand, r#type: &Type) {
+    /// Generate x86 instructions for an IR Cast from `src_type` to `r#type`.
+    /// Integers live in 64-bit registers and slots, so an integer conversion
+    /// re-extends the value from the narrower of the two widths, with the
+    /// signedness of the type that width belongs to.
+    fn gen_cast(&mut self, dest: VarId, src: &Operand, src_type: &Type, r#type: &Type) {
         self.var_types.insert(dest, r#type.clone());
         let d_op = self.var_to_op(dest);
 
         // Handle Alloca src (pointer cast)
         if let Operand::Var(var) = src {
             if let Some(off) = self.alloca_buffers.get(var) {
-                let mem_op = X86Operand::Mem(X86Reg::Rbp, *off);
+                let mem_op = X86Operand::Mem(X86Reg::Frame, *off);
                 self.emit_lea_to(&d_op, mem_op);
                 return;
             }
         }
 
-        let dest_is_float = matches!(r#type, Type::Float | Type::Double);
-        let src_is_float = match src {
-            Operand::FloatConstant(_) => true,
-            Operand::Var(v) => {
-                self.var_types.get(v).map(|t| matches!(t, Type::Float | Type::Double)).unwrap_or(false)
+        let s_op = match src {
+            Operand::FloatConstant(f) => {
+                X86Operand::RipRelLabel(self.get_or_create_float_const(*f, matches!(src_type, Type::Double)))
             }
-            _ => false,
+            _ => self.operand_to_op(src),
         };
-
-        let s_op = self.operand_to_op(src);
-
-        if dest_is_float && !src_is_float {
-            // Int -> Float/Double
-            let src_reg = if let X86Operand::Imm(_) = s_op {
-                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), s_op.clone()));
-                X86Operand::Reg(X86Reg::Eax)
-            } else {
-                s_op.clone()
-            };
-
-            let dest_is_double = matches!(r#type, Type::Double);
-            if dest_is_double {
-                self.asm.push(X86Instr::Cvtsi2sd(X86Operand::Reg(X86Reg::Xmm0), src_reg));
-                self.asm.push(X86Instr::Movsd(d_op, X86Operand::Reg(X86Reg::Xmm0)));
-            } else {
-                self.asm.push(X86Instr::Cvtsi2ss(X86Operand::Reg(X86Reg::Xmm0), src_reg));
-                self.asm.push(X86Instr::Movss(d_op, X86Operand::Reg(X86Reg::Xmm0)));
-            }
-        } else if !dest_is_float && src_is_float {
-            // Float/Double -> Int
-            let use_rax = !matches!(d_op, X86Operand::DwordMem(..));
-            let dst_reg = if use_rax { X86Reg::Rax } else { X86Reg::Eax };
-            let src_is_double = match src {
-                Operand::Var(v) => self.var_types.get(v).map(|t| matches!(t, Type::Double)).unwrap_or(false),
-                _ => false,
-            };
-
-            if src_is_double {
-                if matches!(s_op, X86Operand::DoubleMem(..) | X86Operand::Mem(..)) {
-                    self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                    self.asm.push(X86Instr::Cvttsd2si(X86Operand::Reg(dst_reg.clone()), X86Operand::Reg(X86Reg::Xmm0)));
+        let xmm0 = X86Operand::Reg(X86Reg::Xmm0);
+        let rax = X86Operand::Reg(X86Reg::Rax);
+
+        match (float_kind(src_type), float_kind(r#type)) {
+            (None, Some(dest_is_double)) => {
+                // Int -> Float/Double, from the value extended to 64 bits
+                let (width, signed) = int_kind(src_type);
+                self.load_extended_rax(s_op, width, signed);
+                if width == 8 && !signed {
+                    self.gen_u64_to_float(dest, dest_is_double);
+                } else if dest_is_double {
+                    self.asm.push(X86Instr::Cvtsi2sd(xmm0.clone(), rax));
                 } else {
-                    self.asm.push(X86Instr::Cvttsd2si(X86Operand::Reg(dst_reg.clone()), s_op));
+                    self.asm.push(X86Instr::Cvtsi2ss(xmm0.clone(), rax));
                 }
-            } else {
-                if matches!(s_op, X86Operand::FloatMem(..) | X86Operand::DwordMem(..)) {
-                    self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                    self.asm.push(X86Instr::Cvttss2si(X86Operand::Reg(dst_reg.clone()), X86Operand::Reg(X86Reg::Xmm0)));
+                self.asm.push(if dest_is_double { X86Instr::Movsd(d_op, xmm0) } else { X86Instr::Movss(d_op, xmm0) });
+            }
+            (Some(src_is_double), None) => {
+                // Float/Double -> Int: truncate to 64 bits, then to the destination width
+                self.asm.push(if src_is_double { X86Instr::Movsd(xmm0.clone(), s_op) } else { X86Instr::Movss(xmm0.clone(), s_op) });
+                let (width, signed) = int_kind(r#type);
+                if width == 8 && !signed {
+                    self.gen_float_to_u64(dest, src_is_double);
+                } else if src_is_double {
+                    self.asm.push(X86Instr::Cvttsd2si(rax, xmm0));
                 } else {
-                    self.asm.push(X86Instr::Cvttss2si(X86Operand::Reg(dst_reg.clone()), s_op));
+                    self.asm.push(X86Instr::Cvttss2si(rax, xmm0));
                 }
+                self.extend_rax(width, signed);
+                self.store_rax(d_op);
             }
-            self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(dst_reg)));
-        } else if dest_is_float && src_is_float {
-            // Float<->Double conversion or same-type copy
-            let dest_is_double = matches!(r#type, Type::Double);
-            let src_is_double = match src {
-                Operand::Var(v) => self.var_types.get(v).map(|t| matches!(t, Type::Double)).unwrap_or(false),
-                _ => false,
-            };
-            if !src_is_double && dest_is_double {
-                // float -> double
-                self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                self.asm.push(X86Instr::Cvtss2sd(X86Operand::Reg(X86Reg::Xmm0), X86Operand::Reg(X86Reg::Xmm0)));
-                self.asm.push(X86Instr::Movsd(d_op, X86Operand::Reg(X86Reg::Xmm0)));
-            } else if src_is_double && !dest_is_double {
-                // double -> float
-                self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                self.asm.push(X86Instr::Cvtsd2ss(X86Operand::Reg(X86Reg::Xmm0), X86Operand::Reg(X86Reg::Xmm0)));
-                self.asm.push(X86Instr::Movss(d_op, X86Operand::Reg(X86Reg::Xmm0)));
-            } else if dest_is_double {
-                self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                self.asm.push(X86Instr::Movsd(d_op, X86Operand::Reg(X86Reg::Xmm0)));
-            } else {
-                self.asm.push(X86Instr::Movss(X86Operand::Reg(X86Reg::Xmm0), s_op));
-                self.asm.push(X86Instr::Movss(d_op, X86Operand::Reg(X86Reg::Xmm0)));
+            (Some(src_is_double), Some(dest_is_double)) => {
+                let convert = match (src_is_double, dest_is_double) {
+                    (false, true) => Some(X86Instr::Cvtss2sd(xmm0.clone(), xmm0.clone())),
+                    (true, false) => Some(X86Instr::Cvtsd2ss(xmm0.clone(), xmm0.clone())),
+                    _ => None,
+                };
+                self.asm.push(if src_is_double { X86Instr::Movsd(xmm0.clone(), s_op) } else { X86Instr::Movss(xmm0.clone(), s_op) });
+                self.asm.extend(convert);
+                self.asm.push(if dest_is_double { X86Instr::Movsd(d_op, xmm0) } else { X86Instr::Movss(d_op, xmm0) });
             }
-        } else {
-            // Int-to-int: check operand sizes to avoid invalid mov instructions
-            let src_is_dword = matches!(s_op, X86Operand::DwordMem(..));
-            let dst_is_dword = matches!(d_op, X86Operand::DwordMem(..));
-
-            if src_is_dword && dst_is_dword {
-                // Both 32-bit
-                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), s_op));
-                self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Eax)));
-            } else if src_is_dword {
-                // 32-bit source to 64-bit dest - zero extend via EAX
-                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), s_op));
-                self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
-            } else if dst_is_dword {
-                // 64-bit source to 32-bit dest - truncate via RAX->EAX
-                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), s_op));
-                self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Eax)));
-            } else {
-                // Both 64-bit
-                self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), s_op));
-                self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
+            (None, None) => {
+                let (src_width, src_signed) = int_kind(src_type);
+                let (dest_width, dest_signed) = int_kind(r#type);
+                let (width, signed) = if dest_width <= src_width {
+                    (dest_width, dest_signed)
+                } else {
+                    (src_width, src_signed)
+                };
+                self.load_extended_rax(s_op, width, signed);
+                self.store_rax(d_op);
             }
         }
     }
 
+    /// Move a `float` or `double` between operands of which at least one is
+    /// an XMM register, which needs no scratch register. Returns false, and
+    /// emits nothing, when neither is.
+    pub(crate) fn gen_xmm_move(&mut self, dest: VarId, d_op: &X86Operand, s_op: &X86Operand) -> bool {
+        let is_xmm = |op: &X86Operand| matches!(op, X86Operand::Reg(reg) if reg.is_xmm());
+        if !is_xmm(d_op) && !is_xmm(s_op) {
+            return false;
+        }
+        if d_op != s_op {
+            let is_double = matches!(d_op, X86Operand::DoubleMem(..))
+                || matches!(s_op, X86Operand::DoubleMem(..))
+                || matches!(self.var_types.get(&dest), Some(Type::Double));
+            self.asm.push(move_of_kind(Some(is_double), d_op.clone(), s_op.clone()));
+        }
+        true
+    }
+
+    /// Load an integer operand into RAX, sign- or zero-extended from its low
+    /// `width` bytes.
+    fn load_extended_rax(&mut self, s_op: X86Operand, width: u8, signed: bool) {
+        match s_op {
+            X86Operand::DwordMem(..) => self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), s_op)),
+            _ => self.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), s_op)),
+        }
+        self.extend_rax(width, signed);
+    }
+
+    /// Sign- or zero-extend RAX in place from its low `width` bytes.
+    fn extend_rax(&mut self, width: u8, signed: bool) {
+        let low = match width {
+            1 => X86Reg::Al,
+            2 => X86Reg::Ax,
+            4 => X86Reg::Eax,
+            _ => return,
+        };
+        let instr = match (width, signed) {
+            (_, true) => X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(low)),
+            // Writing a 32-bit register clears the upper half
+            (4, false) => X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::Reg(X86Reg::Eax)),
+            (_, false) => X86Instr::Movzx(X86Operand::Reg(X86Reg::Eax), X86Operand::Reg(low)),
+        };
+        self.asm.push(instr);
+    }
+
+    fn store_rax(&mut self, d_op: X86Operand) {
+        let src = match d_op {
+            X86Operand::DwordMem(..) => X86Reg::Eax,
+            _ => X86Reg::Rax,
+        };
+        self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(src)));
+    }
+
+    /// XMM0 = RAX read as an unsigned 64-bit integer. Values with the top bit
+    /// set are halved (keeping the low bit for rounding), converted, and
+    /// doubled.
+    fn gen_u64_to_float(&mut self, dest: VarId, is_double: bool) {
+        let big = format!("{}_u2f_{}", self.func_name, dest.0);
+        let done = format!("{}_u2f_done_{}", self.func_name, dest.0);
+        let (rax, rcx, xmm0) = (X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx), X86Operand::Reg(X86Reg::Xmm0));
+        let convert = |src: X86Operand| if is_double { X86Instr::Cvtsi2sd(xmm0.clone(), src) } else { X86Instr::Cvtsi2ss(xmm0.clone(), src) };
+        self.asm.extend([
+            X86Instr::Test(rax.clone(), rax.clone()),
+            X86Instr::Jcc("s".to_string(), big.clone()),
+            convert(rax.clone()),
+            X86Instr::Jmp(done.clone()),
+            X86Instr::Label(big),
+            X86Instr::Mov(rcx.clone(), rax.clone()),
+            X86Instr::Shr(rcx.clone(), X86Operand::Imm(1)),
+            X86Instr::And(rax.clone(), X86Operand::Imm(1)),
+            X86Instr::Or(rcx.clone(), rax),
+            convert(rcx),
+            if is_double { X86Instr::Addsd(xmm0.clone(), xmm0.clone()) } else { X86Instr::Addss(xmm0.clone(), xmm0.clone()) },
+            X86Instr::Label(done),
+        ]);
+    }
+
+    /// RAX = XMM0 truncated to an unsigned 64-bit integer. Values of 2^63 and
+    /// up are converted after subtracting 2^63, which is then added back.
+    fn gen_float_to_u64(&mut self, dest: VarId, is_double: bool) {
+        let big = format!("{}_f2u_{}", self.func_name, dest.0);
+        let done = format!("{}_f2u_done_{}", self.func_name, dest.0);
+        let limit = X86Operand::RipRelLabel(self.get_or_create_float_const(9_223_372_036_854_775_808.0, is_double));
+        let (rax, rcx, xmm0, xmm1) = (
+            X86Operand::Reg(X86Reg::Rax),
+            X86Operand::Reg(X86Reg::Rcx),
+            X86Operand::Reg(X86Reg::Xmm0),
+            X86Operand::Reg(X86Reg::Xmm1),
+        );
+        let truncate = || if is_double { X86Instr::Cvttsd2si(rax.clone(), xmm0.clone()) } else { X86Instr::Cvttss2si(rax.clone(), xmm0.clone()) };
+        self.asm.extend([
+            if is_double { X86Instr::Movsd(xmm1.clone(), limit) } else { X86Instr::Movss(xmm1.clone(), limit) },
+            if is_double { X86Instr::Ucomisd(xmm0.clone(), xmm1.clone()) } else { X86Instr::Ucomiss(xmm0.clone(), xmm1.clone()) },
+            X86Instr::Jcc("ae".to_string(), big.clone()),
+            truncate(),
+            X86Instr::Jmp(done.clone()),
+            X86Instr::Label(big),
+            if is_double { X86Instr::Subsd(xmm0.clone(), xmm1) } else { X86Instr::Subss(xmm0.clone(), xmm1) },
+            truncate(),
+            X86Instr::Mov(rcx.clone(), X86Operand::Imm(i64::MIN)),
+            X86Instr::Xor(rax.clone(), rcx),
+            X86Instr::Label(done),
+        ]);
+    }
+
     /// Generate x86 instructions for an IR Copy instruction.
     fn gen_copy(&mut self, dest: VarId, src: &Operand) {
         if !self.var_types.contains_key(&dest) {
@@ -943,7 +1225,13 @@ impl<'a> FunctionGenerator<'a> {
             }
         }
 
-        let s_op = self.operand_to_op(src);
+        let s_op = match src {
+            // A constant copied into a double (a `?:` arm) needs an 8-byte pool entry.
+            Operand::FloatConstant(f) if matches!(self.var_types.get(&dest), Some(Type::Double)) => {
+                X86Operand::RipRelLabel(self.get_or_create_float_const(*f, true))
+            }
+            _ => self.operand_to_op(src),
+        };
         let d_op = self.var_to_op(dest);
 
         // Handle Global variables (load address)
@@ -956,24 +1244,25 @@ impl<'a> FunctionGenerator<'a> {
         // Handle Alloca addresses (arrays/structs on stack) -> LEA
         if let Operand::Var(var) = src {
             if let Some(off) = self.alloca_buffers.get(var) {
-                let mem_op = X86Operand::Mem(X86Reg::Rbp, *off);
+                let mem_op = X86Operand::Mem(X86Reg::Frame, *off);
                 self.emit_lea_to(&d_op, mem_op);
                 return;
             }
         }
 
         // If s_op is a Mem operand that matches an alloca buffer offset
-        if let X86Operand::Mem(X86Reg::Rbp, offset) = s_op {
+        if let X86Operand::Mem(X86Reg::Frame, offset) = s_op {
             let is_alloca_buffer = self.alloca_buffers.values().any(|&buf_offset| buf_offset == offset);
             if is_alloca_buffer {
-                self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Rbp, offset)));
+                self.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, offset)));
                 self.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
                 return;
             }
         }
 
         // Check if float/double
-        if matches!(s_op, X86Operand::DoubleMem(..)) || matches!(d_op, X86Operand::DoubleMem(..)) {
+        if self.gen_xmm_move(dest, &d_op, &s_op) {
+        } else if matches!(s_op, X86Operand::DoubleMem(..)) || matches!(d_op, X86Operand::DoubleMem(..)) {
             self.asm.push(X86Instr::Movsd(X86Operand::Reg(X86Reg::Xmm0), s_op));
             self.asm.push(X86Instr::Movsd(d_op, X86Operand::Reg(X86Reg::Xmm0)));
         } else if matches!(s_op, X86Operand::FloatMem(..)) || matches!(d_op, X86Operand::FloatMem(..)) {
@@ -1569,3 +1858,46 @@ impl<'a> FunctionGenerator<'a> {
         }
     }
 }
+
+/// The 32-bit form of an integer operand: the low half of a register or
+/// stack slot, or an immediate truncated to 32 bits.
+pub(crate) fn narrow_to_dword(op: X86Operand) -> X86Operand {
+    match op {
+        X86Operand::Reg(r) => X86Operand::Reg(r.to_32bit()),
+        X86Operand::Mem(base, off) => X86Operand::DwordMem(base, off),
+        X86Operand::Imm(c) => X86Operand::Imm(i64::from(c as i32)),
+        other => other,
+    }
+}
+
+/// Whether `ty` is floating point, and if so whether it is `double`.
+fn float_kind(ty: &Type) -> Option<bool> {
+    match ty {
+        Type::Float => Some(false),
+        Type::Double => Some(true),
+        _ => None,
+    }
+}
+
+/// The move for a value of the given `float_kind`.
+fn move_of_kind(kind: Option<bool>, dst: X86Operand, src: X86Operand) -> X86Instr {
+    match kind {
+        Some(true) => X86Instr::Movsd(dst, src),
+        Some(false) => X86Instr::Movss(dst, src),
+        None => X86Instr::Mov(dst, src),
+    }
+}
+
+/// The width in bytes and signedness of an integer, enum, or pointer type.
+fn int_kind(ty: &Type) -> (u8, bool) {
+    match ty {
+        Type::Char => (1, true),
+        Type::UnsignedChar | Type::Bool => (1, false),
+        Type::Short => (2, true),
+        Type::UnsignedShort => (2, false),
+        Type::Int | Type::Enum(_) => (4, true),
+        Type::UnsignedInt => (4, false),
+        Type::Long | Type::LongLong => (8, true),
+        _ => (8, false),
+    }
+}
@@ -2,7 +2,7 @@
 // Extracted from lib.rs: emit_init_list_data, find_init_item, emit_scalar_data,
 // emit_zero_data, type_size, type_alignment, struct_size
 
-use model::Type;
+use model::{ConstContext, ConstValue, Type};
 use crate::Codegen;
 
 impl Codegen {
@@ -13,21 +13,7 @@ impl Codegen {
                 // Emit each element, filling remaining with zeros
                 for i in 0..*size {
                     if let Some(item) = self.find_init_item(items, i) {
-                        match &item.value {
-                            model::Expr::InitList(nested) => {
-                                self.emit_init_list_data(output, inner, nested);
-                            }
-                            model::Expr::Constant(c) => {
-                                self.emit_scalar_data(output, inner, *c);
-                            }
-                            model::Expr::FloatConstant(f) => {
-                                let f32_val = *f as f32;
-                                output.push_str(&format!("    .long 0x{:08x}\n", f32_val.to_bits()));
-                            }
-                            _ => {
-                                self.emit_zero_data(output, inner);
-                            }
-                        }
+                        self.emit_init_value(output, inner, &item.value);
                     } else {
                         self.emit_zero_data(output, inner);
                     }
@@ -36,73 +22,147 @@ impl Codegen {
             Type::Struct(name) => {
                 if let Some(s_def) = self.structs.get(name) {
                     let s_def = s_def.clone();
-                    let is_packed = s_def.attributes.iter().any(|a| matches!(a, model::Attribute::Packed));
+                    let layout = self.type_layout().struct_layout(&s_def);
                     let mut current_offset: usize = 0;
                     let mut field_idx = 0usize;
+                    // Bytes of adjacent bit-fields, merged before being emitted: (start offset, bytes)
+                    let mut pending_bits: Option<(usize, Vec<u8>)> = None;
 
                     for item in items {
                         let target_idx = match &item.designator {
                             Some(model::Designator::Field(fname)) => {
                                 s_def.fields.iter().position(|f| &f.name == fname).unwrap_or(field_idx)
                             }
-                            _ => field_idx,
+                            _ => {
+                                // Positional initializers skip unnamed bit-fields
+                                let mut idx = field_idx;
+                                while s_def.fields.get(idx).is_some_and(|f| f.name.is_empty() && f.bit_width.is_some()) {
+                                    idx += 1;
+                                }
+                                idx
+                            }
                         };
+                        let Some(field) = s_def.fields.get(target_idx) else { break };
+                        let placement = &layout.fields[target_idx];
+                        field_idx = target_idx + 1;
 
-                        // Compute offset of target field, emit padding if needed
-                        let mut offset: usize = 0;
-                        for fi in 0..=target_idx {
-                            if !is_packed {
-                                let align = self.type_alignment(&s_def.fields[fi].field_type);
-                                offset = (offset + align - 1) / align * align;
-                            }
-                            if fi < target_idx {
-                                offset += self.type_size(&s_def.fields[fi].field_type);
-                            }
+                        if let Some(bf) = &placement.bitfield {
+                            let value = match self.eval_data(&item.value) {
+                                Some(DataValue::Int(c)) => c as u64,
+                                Some(DataValue::Float(f)) => f as i64 as u64,
+                                _ => 0,
+                            };
+                            merge_bitfield(&mut pending_bits, placement.offset, bf, value);
+                            continue;
+                        }
+                        if let Some((start, bytes)) = pending_bits.take() {
+                            current_offset = emit_bitfield_bytes(output, current_offset, start, &bytes);
                         }
+
+                        // Emit padding up to the field
+                        let offset = placement.offset;
                         if offset > current_offset {
                             output.push_str(&format!("    .zero {}\n", offset - current_offset));
                         }
 
-                        let field = &s_def.fields[target_idx];
-                        match &item.value {
-                            model::Expr::InitList(nested) => {
-                                self.emit_init_list_data(output, &field.field_type, nested);
-                            }
-                            model::Expr::Constant(c) => {
-                                self.emit_scalar_data(output, &field.field_type, *c);
-                            }
-                            model::Expr::FloatConstant(f) => {
-                                let f32_val = *f as f32;
-                                output.push_str(&format!("    .long 0x{:08x}\n", f32_val.to_bits()));
-                            }
-                            _ => {
-                                self.emit_zero_data(output, &field.field_type);
-                            }
-                        }
+                        self.emit_init_value(output, &field.field_type, &item.value);
                         current_offset = offset + self.type_size(&field.field_type);
-                        field_idx = target_idx + 1;
+                    }
+                    if let Some((start, bytes)) = pending_bits.take() {
+                        current_offset = emit_bitfield_bytes(output, current_offset, start, &bytes);
                     }
 
                     // Emit trailing padding
-                    let struct_size = self.struct_size(&s_def, is_packed);
-                    if current_offset < struct_size {
-                        output.push_str(&format!("    .zero {}\n", struct_size - current_offset));
+                    if current_offset < layout.size {
+                        output.push_str(&format!("    .zero {}\n", layout.size - current_offset));
                     }
                 }
             }
+            Type::Union(name) => {
+                // One member is initialized: the designated one, else the first
+                let Some(u_def) = self.unions.get(name) else { return };
+                let item = items.first();
+                let field = match item.and_then(|item| item.designator.as_ref()) {
+                    Some(model::Designator::Field(fname)) => u_def.fields.iter().find(|f| &f.name == fname),
+                    _ => u_def.fields.first(),
+                };
+                let mut size = 0;
+                if let (Some(item), Some(field)) = (item, field) {
+                    self.emit_init_value(output, &field.field_type, &item.value);
+                    size = self.type_size(&field.field_type);
+                }
+                let union_size = self.type_size(ty);
+                if size < union_size {
+                    output.push_str(&format!("    .zero {}\n", union_size - size));
+                }
+            }
             _ => {
                 // Scalar type with init list (unusual but valid for single-element)
                 if let Some(item) = items.first() {
-                    if let model::Expr::Constant(c) = &item.value {
-                        self.emit_scalar_data(output, ty, *c);
-                    } else {
-                        self.emit_zero_data(output, ty);
-                    }
+                    self.emit_init_value(output, ty, &item.value);
                 }
             }
         }
     }
 
+    /// Emit the data for one initializer of an object of type `ty`: a nested
+    /// list, a string filling a `char` array, or a constant expression.
+    /// Anything that is not a link-time constant is zero-filled.
+    pub(crate) fn emit_init_value(&self, output: &mut String, ty: &Type, value: &model::Expr) {
+        match (value, ty) {
+            (model::Expr::InitList(nested), _) => self.emit_init_list_data(output, ty, nested),
+            (model::Expr::StringLiteral(s), Type::Array(elem, _)) if matches!(**elem, Type::Char | Type::UnsignedChar) => {
+                let units: Vec<u32> = s.chars().map(u32::from).collect();
+                self.emit_wide_string_data(output, ty, &units);
+            }
+            _ => match self.eval_data(value) {
+                Some(value) => self.emit_data_value(output, ty, value),
+                None => self.emit_zero_data(output, ty),
+            },
+        }
+    }
+
+    /// Emit a constant as data of scalar type `ty`, converting between
+    /// integer and floating point as an assignment would.
+    fn emit_data_value(&self, output: &mut String, ty: &Type, value: DataValue) {
+        match (ty, value) {
+            (Type::Pointer(..) | Type::FunctionPointer { .. }, DataValue::Address(symbol)) => {
+                output.push_str(&format!("    {} {}\n", self.pointer_directive(), symbol));
+            }
+            // Not a constant in any other slot
+            (_, DataValue::Address(_)) => self.emit_zero_data(output, ty),
+            (Type::Float, DataValue::Int(c)) => output.push_str(&format!("    .long 0x{:08x}\n", (c as f32).to_bits())),
+            (Type::Float, DataValue::Float(f)) => output.push_str(&format!("    .long 0x{:08x}\n", (f as f32).to_bits())),
+            (Type::Double, DataValue::Int(c)) => output.push_str(&format!("    .quad 0x{:016x}\n", (c as f64).to_bits())),
+            (Type::Double, DataValue::Float(f)) => output.push_str(&format!("    .quad 0x{:016x}\n", f.to_bits())),
+            (Type::Bool, DataValue::Float(f)) => self.emit_scalar_data(output, ty, i64::from(f != 0.0)),
+            (_, DataValue::Float(f)) => self.emit_scalar_data(output, ty, f as i64),
+            (_, DataValue::Int(c)) => self.emit_scalar_data(output, ty, c),
+        }
+    }
+
+    /// Evaluate a global initializer element to a link-time constant: an
+    /// arithmetic constant expression, or the address of a global.
+    fn eval_data(&self, expr: &model::Expr) -> Option<DataValue> {
+        use model::{Expr, UnaryOp};
+        if let Ok(value) = model::consteval::eval_constant(expr, self) {
+            return Some(match value {
+                ConstValue::Int(c, _) => DataValue::Int(c),
+                ConstValue::Float(f) => DataValue::Float(f),
+            });
+        }
+        match expr {
+            // A function or array name decaying to its address
+            Expr::Variable(name) => Some(DataValue::Address(name.clone())),
+            Expr::Unary { op: UnaryOp::AddrOf, expr } => match expr.as_ref() {
+                Expr::Variable(name) => Some(DataValue::Address(name.clone())),
+                _ => None,
+            },
+            Expr::Cast(_, inner) => self.eval_data(inner),
+            _ => None,
+        }
+    }
+
     /// Find an init item for a given positional index (handles designated initializers).
     pub(crate) fn find_init_item<'b>(&self, items: &'b [model::InitItem], index: usize) -> Option<&'b model::InitItem> {
         for item in items {
@@ -136,40 +196,169 @@ impl Codegen {
     pub(crate) fn emit_scalar_data(&self, output: &mut String, ty: &Type, value: i64) {
         match ty {
             Type::Char | Type::UnsignedChar => output.push_str(&format!("    .byte {}\n", value)),
+            Type::Bool => output.push_str(&format!("    .byte {}\n", i64::from(value != 0))),
             Type::Short | Type::UnsignedShort => output.push_str(&format!("    .short {}\n", value)),
             Type::Int | Type::UnsignedInt | Type::Float | Type::Enum(_) => output.push_str(&format!("    .long {}\n", value)),
             Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong
             | Type::Pointer(_, ..) | Type::FunctionPointer { .. } => {
-                output.push_str(&format!("    .quad {}\n", value));
+                // `long` and pointers are 4 bytes under ILP32
+                let directive = if self.type_size(ty) == 8 { ".quad" } else { ".long" };
+                output.push_str(&format!("    {} {}\n", directive, value));
             }
             _ => output.push_str(&format!("    .long {}\n", value)),
         }
     }
 
     /// Emit zero-filled data for a given type.
+    /// Emit the units of a wide string literal initializing `ty`, then its
+    /// terminator and zero fill up to the array length.
+    pub(crate) fn emit_wide_string_data(&self, output: &mut String, ty: &Type, units: &[u32]) {
+        let Type::Array(elem, len) = ty else {
+            self.emit_zero_data(output, ty);
+            return;
+        };
+        for i in 0..*len {
+            let unit = units.get(i).copied().unwrap_or(0);
+            self.emit_scalar_data(output, elem, i64::from(unit));
+        }
+    }
+
     pub(crate) fn emit_zero_data(&self, output: &mut String, ty: &Type) {
         let size = self.type_size(ty);
         output.push_str(&format!("    .zero {}\n", size));
     }
 
+    /// The data directive for a pointer-sized value.
+    pub(crate) fn pointer_directive(&self) -> &'static str {
+        if self.target.data_layout.pointer_size == 4 { ".long" } else { ".quad" }
+    }
+
+    /// Layout calculator for the program's aggregates and the target.
+    pub(crate) fn type_layout(&self) -> model::TypeLayout<'_> {
+        model::TypeLayout::new(&self.structs, &self.unions).with_data_layout(self.target.data_layout)
+    }
+
     /// Get the size of a type in bytes.
     pub(crate) fn type_size(&self, ty: &Type) -> usize {
-        model::TypeLayout::new(&self.structs, &self.unions).size_of(ty)
+        self.type_layout().size_of(ty)
     }
 
     /// Get the alignment of a type in bytes.
     pub(crate) fn type_alignment(&self, ty: &Type) -> usize {
-        model::TypeLayout::new(&self.structs, &self.unions).align_of(ty)
+        self.type_layout().align_of(ty)
     }
 
-    /// Compute the total size of a struct including padding.
+    /// Test helper: the total size of a struct including padding.
+    #[cfg(test)]
     pub(crate) fn struct_size(&self, s_def: &model::StructDef, is_packed: bool) -> usize {
-        model::TypeLayout::new(&self.structs, &self.unions).struct_size(s_def, is_packed)
+        self.type_layout().struct_size(s_def, is_packed)
+    }
+}
+
+/// A link-time constant in a global initializer.
+enum DataValue {
+    Int(i64),
+    Float(f64),
+    /// The address of a global or function.
+    Address(String),
+}
+
+/// Initializers see the program's enums and the target's type layouts.
+impl ConstContext for Codegen {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.enum_constants.get(name).copied()
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.type_size(ty))
+    }
+
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.type_alignment(ty))
     }
 }
 
+/// OR a constant bit-field value into the pending bit-field bytes, starting a
+/// new run if the field does not touch the current one. Only the bytes that
+/// actually hold the field's bits are covered, so a regular field sharing the
+/// storage unit (e.g. `int a:4; char c;`) is not overwritten.
+/// Escape a narrow string literal for `.asciz`. The string holds one byte per
+/// `char`; bytes outside printable ASCII are written as octal escapes.
+/// For each string, the label and byte offset of a longer string it is the
+/// tail of (`"lo"` in `"hello"`), if any. Equal strings go to the first.
+/// A host is never itself merged, so its label is always emitted.
+pub(crate) fn merge_string_suffixes(strings: &[(String, String)]) -> Vec<Option<(String, usize)>> {
+    let len = |s: &str| s.chars().count();
+    strings
+        .iter()
+        .enumerate()
+        .map(|(i, (_, content))| {
+            let host = strings
+                .iter()
+                .enumerate()
+                .filter(|&(j, (_, other))| {
+                    j != i && other.ends_with(content.as_str()) && (len(other) > len(content) || j < i)
+                })
+                .max_by_key(|&(j, (_, other))| (len(other), std::cmp::Reverse(j)));
+            host.map(|(_, (label, other))| (label.clone(), len(other) - len(content)))
+        })
+        .collect()
+}
+
+pub(crate) fn escape_asciz(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ' '..='~' => out.push(ch),
+            _ => out.push_str(&format!("\\{:03o}", ch as u32 as u8)),
+        }
+    }
+    out
+}
+
+fn merge_bitfield(pending: &mut Option<(usize, Vec<u8>)>, unit_offset: usize, bf: &model::BitfieldInfo, value: u64) {
+    if bf.bit_width == 0 {
+        return;
+    }
+    let first_bit = unit_offset * 8 + bf.bit_offset;
+    let first_byte = first_bit / 8;
+    let end_byte = (first_bit + bf.bit_width).div_ceil(8);
+    let mask = u64::MAX >> (64 - bf.bit_width.min(64));
+    let value = u128::from(value & mask) << (first_bit % 8);
+
+    let (start, bytes) = pending.get_or_insert_with(|| (first_byte, Vec::new()));
+    if first_byte < *start {
+        return; // fields are laid out in order; an earlier byte means a designator went backwards
+    }
+    if bytes.len() < end_byte - *start {
+        bytes.resize(end_byte - *start, 0);
+    }
+    for (i, byte) in (first_byte..end_byte).enumerate() {
+        bytes[byte - *start] |= (value >> (i * 8)) as u8;
+    }
+}
+
+/// Emit padding up to `start`, then the merged bit-field bytes. Returns the new offset.
+fn emit_bitfield_bytes(output: &mut String, current_offset: usize, start: usize, bytes: &[u8]) -> usize {
+    if start > current_offset {
+        output.push_str(&format!("    .zero {}\n", start - current_offset));
+    }
+    let skip = current_offset.saturating_sub(start).min(bytes.len());
+    if skip < bytes.len() {
+        let list: Vec<String> = bytes[skip..].iter().map(|b| b.to_string()).collect();
+        output.push_str(&format!("    .byte {}\n", list.join(", ")));
+    }
+    current_offset.max(start + bytes.len())
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{escape_asciz, merge_string_suffixes};
     use crate::Codegen;
     use model::Type;
 
@@ -394,6 +583,31 @@ mod tests {
         assert_eq!(out, "    .long 100\n");
     }
 
+    #[test]
+    fn emit_wide_string_pads_to_array_length() {
+        let c = cg();
+        let mut out = String::new();
+        c.emit_wide_string_data(&mut out, &Type::Array(Box::new(Type::UnsignedShort), 3), &[0x61]);
+        assert_eq!(out, "    .short 97\n    .short 0\n    .short 0\n");
+    }
+
+    #[test]
+    fn merge_string_suffixes_points_into_hosts() {
+        let strings: Vec<(String, String)> = [("a", "hello"), ("b", "lo"), ("c", "hello"), ("d", "x"), ("e", "")]
+            .iter()
+            .map(|(l, s)| (l.to_string(), s.to_string()))
+            .collect();
+        let merged = merge_string_suffixes(&strings);
+        let host = |label: &str, offset| Some((label.to_string(), offset));
+        assert_eq!(merged, [None, host("a", 3), host("a", 0), None, host("a", 5)]);
+    }
+
+    #[test]
+    fn escape_asciz_writes_bytes() {
+        // Narrow strings hold one byte per char: "é" in UTF-8, then \xff
+        assert_eq!(escape_asciz("\u{c3}\u{a9}\u{ff}\"\n\0"), "\\303\\251\\377\\\"\\n\\000");
+    }
+
     #[test]
     fn emit_scalar_function_pointer() {
         let c = cg();
@@ -401,6 +615,7 @@ mod tests {
         let fptr_type = Type::FunctionPointer {
             return_type: Box::new(Type::Int),
             param_types: vec![Type::Int],
+            is_variadic: false,
         };
         c.emit_scalar_data(&mut out, &fptr_type, 0);
         assert_eq!(out, "    .quad 0\n");
@@ -416,12 +631,12 @@ mod tests {
     }
 
     #[test]
-    fn emit_scalar_bool_fallback() {
+    fn emit_scalar_bool_normalizes() {
         let c = cg();
         let mut out = String::new();
-        // Bool is not specifically matched → hits the fallback `.long`
-        c.emit_scalar_data(&mut out, &Type::Bool, 1);
-        assert_eq!(out, "    .long 1\n");
+        // _Bool is one byte and any nonzero initializer stores 1
+        c.emit_scalar_data(&mut out, &Type::Bool, 7);
+        assert_eq!(out, "    .byte 1\n");
     }
 
     // ─── type_size: structs, unions, typedef, typeof ────────────
@@ -536,6 +751,55 @@ mod tests {
         assert_eq!(c.type_alignment(&Type::Typedef("foo".to_string())), 4);
     }
 
+    // ─── emit_init_value ────────────────────────────────────────
+
+    #[test]
+    fn emit_init_list_converts_and_evaluates_constants() {
+        let c = cg();
+        let item = |value| model::InitItem { designator: None, value };
+        let neg = |e| model::Expr::Unary { op: model::UnaryOp::Minus, expr: Box::new(e) };
+        let mut out = String::new();
+        c.emit_init_list_data(&mut out, &Type::Array(Box::new(Type::Double), 2), &[
+            item(model::Expr::FloatConstant(1.5)),
+            item(neg(model::Expr::Constant(2))),
+        ]);
+        assert_eq!(out, "    .quad 0x3ff8000000000000\n    .quad 0xc000000000000000\n");
+
+        let mut out = String::new();
+        c.emit_init_list_data(&mut out, &Type::Array(Box::new(Type::Int), 3), &[
+            item(neg(model::Expr::Constant(1))),
+            item(model::Expr::Binary {
+                left: Box::new(model::Expr::Constant(3)),
+                op: model::BinaryOp::Mul,
+                right: Box::new(model::Expr::Constant(2)),
+            }),
+            item(model::Expr::FloatConstant(2.5)),
+        ]);
+        assert_eq!(out, "    .long -1\n    .long 6\n    .long 2\n");
+    }
+
+    #[test]
+    fn emit_init_value_addresses() {
+        let c = cg();
+        let ptr = Type::Pointer(Box::new(Type::Int), Default::default());
+        let addr_of = model::Expr::Unary { op: model::UnaryOp::AddrOf, expr: Box::new(model::Expr::Variable("g".into())) };
+        let mut out = String::new();
+        c.emit_init_value(&mut out, &ptr, &addr_of);
+        c.emit_init_value(&mut out, &ptr, &model::Expr::Variable("main".into()));
+        assert_eq!(out, "    .quad g\n    .quad main\n");
+    }
+
+    #[test]
+    fn ilp32_pointers_and_longs_are_words() {
+        let c = Codegen::with_target(model::TargetConfig::i686());
+        let ptr = Type::Pointer(Box::new(Type::Int), Default::default());
+        let mut out = String::new();
+        c.emit_init_value(&mut out, &ptr, &model::Expr::Variable("g".into()));
+        c.emit_scalar_data(&mut out, &Type::Long, 7);
+        c.emit_scalar_data(&mut out, &Type::LongLong, 8);
+        assert_eq!(out, "    .long g\n    .long 7\n    .quad 8\n");
+    }
+
     // ─── find_init_item ─────────────────────────────────────────
 
     #[test]
@@ -678,6 +942,30 @@ mod tests {
         assert_eq!(zero_count, 2);
     }
 
+    #[test]
+    fn emit_init_list_data_union_member() {
+        let mut c = cg();
+        c.add_union(model::UnionDef {
+            name: "Num".to_string(),
+            fields: vec![
+                model::StructField { field_type: Type::Int, name: "i".to_string(), bit_width: None },
+                model::StructField { field_type: Type::Double, name: "d".to_string(), bit_width: None },
+            ],
+        });
+        let ty = Type::Union("Num".to_string());
+        let item = |designator, value| model::InitItem { designator, value };
+
+        // The first member, padded to the size of the union
+        let mut out = String::new();
+        c.emit_init_list_data(&mut out, &ty, &[item(None, model::Expr::Constant(7))]);
+        assert_eq!(out, "    .long 7\n    .zero 4\n");
+
+        let mut out = String::new();
+        let designated = item(Some(model::Designator::Field("d".to_string())), model::Expr::FloatConstant(2.5));
+        c.emit_init_list_data(&mut out, &ty, &[designated]);
+        assert_eq!(out, format!("    .quad 0x{:016x}\n", 2.5f64.to_bits()));
+    }
+
     #[test]
     fn emit_init_list_data_struct_simple() {
         let mut c = cg();
@@ -897,6 +1185,7 @@ mod tests {
         let fptr = Type::FunctionPointer {
             return_type: Box::new(Type::Void),
             param_types: vec![],
+            is_variadic: false,
         };
         assert_eq!(c.type_size(&fptr), 8);
     }
@@ -909,6 +1198,7 @@ mod tests {
         let fptr = Type::FunctionPointer {
             return_type: Box::new(Type::Int),
             param_types: vec![],
+            is_variadic: false,
         };
         assert_eq!(c.type_alignment(&fptr), 8);
     }
@@ -0,0 +1,198 @@
+// Compiler builtins the i686 backend expands inline: bit counting, byte
+// swaps, and the __sync/__atomic operations on 32-bit values
+use ir::{Operand, VarId};
+use super::frame;
+use super::function::{FunctionGenerator, TEMP_OPERAND, TEMP_VALUE};
+
+impl FunctionGenerator<'_> {
+    /// Generate the call to `name` if it is a builtin; returns whether it was.
+    pub(super) fn gen_builtin(&mut self, dest: Option<VarId>, name: &str, args: &[Operand]) -> bool {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Operand::Constant(0));
+        match name {
+            // `long` is 32-bit; the `ll` forms work on the two halves
+            "__builtin_clz" | "__builtin_clzl" => {
+                self.load_int("eax", &arg(0));
+                self.emit("bsr eax, eax");
+                self.emit("xor eax, 31");
+            }
+            "__builtin_ctz" | "__builtin_ctzl" => {
+                self.load_int("eax", &arg(0));
+                self.emit("bsf eax, eax");
+            }
+            "__builtin_popcount" | "__builtin_popcountl" => {
+                self.load_int("eax", &arg(0));
+                self.popcount();
+            }
+            "__builtin_clzll" => {
+                // Count in the high half, or 32 plus the count in the low one
+                let done = self.new_label();
+                self.load_wide(&arg(0));
+                self.emit("bsr ecx, edx");
+                self.emit("xor ecx, 31");
+                self.emit("test edx, edx");
+                self.emit(format!("jne {}", done));
+                self.emit("bsr ecx, eax");
+                self.emit("xor ecx, 31");
+                self.emit("add ecx, 32");
+                self.label(&done);
+                self.emit("mov eax, ecx");
+            }
+            "__builtin_ctzll" => {
+                let done = self.new_label();
+                self.load_wide(&arg(0));
+                self.emit("bsf ecx, eax");
+                self.emit("test eax, eax");
+                self.emit(format!("jne {}", done));
+                self.emit("bsf ecx, edx");
+                self.emit("add ecx, 32");
+                self.label(&done);
+                self.emit("mov eax, ecx");
+            }
+            "__builtin_popcountll" => {
+                self.load_wide(&arg(0));
+                self.emit("push edx");
+                self.popcount();
+                self.emit("pop edx");
+                self.emit("push eax");
+                self.emit("mov eax, edx");
+                self.popcount();
+                self.emit("pop ecx");
+                self.emit("add eax, ecx");
+            }
+            "__builtin_bswap16" => {
+                self.load_int("eax", &arg(0));
+                self.emit("xchg al, ah");
+                self.emit("movzx eax, ax");
+            }
+            "__builtin_bswap32" => {
+                self.load_int("eax", &arg(0));
+                self.emit("bswap eax");
+            }
+            "__builtin_bswap64" => {
+                self.load_wide(&arg(0));
+                self.emit("bswap eax");
+                self.emit("bswap edx");
+                self.emit("xchg eax, edx");
+                if let Some(dest) = dest {
+                    if self.is_wide(&self.var_type(dest)) {
+                        self.write_wide(dest, "eax", "edx");
+                    } else {
+                        self.write_dest(dest, "eax");
+                    }
+                }
+                return true;
+            }
+            "__sync_synchronize" => {
+                self.emit("mfence");
+                return true;
+            }
+            "__sync_val_compare_and_swap" | "__atomic_compare_exchange_n" => {
+                let reports_success = name == "__atomic_compare_exchange_n";
+                // cmpxchg compares with eax; the address and the desired
+                // value wait in the scratch area while eax is set up
+                let scratch = self.scratch();
+                self.load_int("eax", &arg(0));
+                self.emit(format!("mov DWORD PTR {}, eax", frame(scratch + TEMP_VALUE)));
+                self.load_int("eax", &arg(2));
+                self.emit(format!("mov DWORD PTR {}, eax", frame(scratch + TEMP_OPERAND)));
+                self.load_int("edx", &arg(1));
+                if reports_success {
+                    // `expected` points to the expected value; keep it in ebx
+                    self.emit("push ebx");
+                    self.emit("mov ebx, edx");
+                    self.emit("mov eax, DWORD PTR [ebx]");
+                } else {
+                    self.emit("mov eax, edx");
+                }
+                self.emit(format!("mov ecx, DWORD PTR {}", frame(scratch + TEMP_OPERAND)));
+                self.emit(format!("mov edx, DWORD PTR {}", frame(scratch + TEMP_VALUE)));
+                self.emit("lock cmpxchg DWORD PTR [edx], ecx");
+                if reports_success {
+                    // The value found goes back to *expected; the result is
+                    // whether it matched
+                    self.emit("mov DWORD PTR [ebx], eax");
+                    self.emit("sete al");
+                    self.emit("movzx eax, al");
+                    self.emit("pop ebx");
+                }
+            }
+            "__sync_lock_test_and_set" | "__atomic_exchange_n" => {
+                self.load_int("eax", &arg(1));
+                self.load_int("edx", &arg(0));
+                self.emit("xchg DWORD PTR [edx], eax");
+            }
+            "__sync_lock_release" => {
+                self.load_int("edx", &arg(0));
+                self.emit("mov DWORD PTR [edx], 0");
+                return true;
+            }
+            "__sync_fetch_and_add" | "__sync_fetch_and_sub" | "__atomic_fetch_add" | "__atomic_fetch_sub" => {
+                self.load_int("eax", &arg(1));
+                if name.ends_with("sub") {
+                    self.emit("neg eax");
+                }
+                self.load_int("edx", &arg(0));
+                self.emit("lock xadd DWORD PTR [edx], eax");
+            }
+            "__sync_fetch_and_and" | "__sync_fetch_and_or" | "__sync_fetch_and_xor"
+            | "__atomic_fetch_and" | "__atomic_fetch_or" | "__atomic_fetch_xor" => {
+                // x86 has no fetching form: retry a cmpxchg of the new value
+                let op = name.rsplit('_').next().unwrap_or_default().to_string();
+                let retry = self.new_label();
+                self.load_int("eax", &arg(1));
+                self.emit("push eax");
+                self.load_int("edx", &arg(0));
+                self.emit("mov eax, DWORD PTR [edx]");
+                self.label(&retry);
+                self.emit("mov ecx, eax");
+                self.emit(format!("{} ecx, DWORD PTR [esp]", op));
+                self.emit("lock cmpxchg DWORD PTR [edx], ecx");
+                self.emit(format!("jne {}", retry));
+                self.emit("add esp, 4");
+            }
+            "__atomic_load_n" => {
+                // Aligned 32-bit loads are atomic
+                self.load_int("edx", &arg(0));
+                self.emit("mov eax, DWORD PTR [edx]");
+            }
+            "__atomic_store_n" => {
+                // xchg stores with a full barrier
+                self.load_int("eax", &arg(1));
+                self.load_int("edx", &arg(0));
+                self.emit("xchg DWORD PTR [edx], eax");
+                return true;
+            }
+            _ => return false,
+        }
+        self.builtin_result(dest);
+        true
+    }
+
+    /// eax = the number of bits set in eax, by adding bits in parallel.
+    /// Clobbers ecx.
+    fn popcount(&mut self) {
+        self.emit("mov ecx, eax");
+        self.emit("shr ecx, 1");
+        self.emit("and ecx, 0x55555555");
+        self.emit("sub eax, ecx");
+        self.emit("mov ecx, eax");
+        self.emit("shr ecx, 2");
+        self.emit("and eax, 0x33333333");
+        self.emit("and ecx, 0x33333333");
+        self.emit("add eax, ecx");
+        self.emit("mov ecx, eax");
+        self.emit("shr ecx, 4");
+        self.emit("add eax, ecx");
+        self.emit("and eax, 0x0f0f0f0f");
+        self.emit("imul eax, eax, 0x01010101");
+        self.emit("shr eax, 24");
+    }
+
+    fn builtin_result(&mut self, dest: Option<VarId>) {
+        if let Some(dest) = dest {
+            let ty = self.var_type(dest);
+            self.narrow("eax", &ty);
+            self.write_dest(dest, "eax");
+        }
+    }
+}
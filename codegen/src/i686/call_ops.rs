@@ -0,0 +1,290 @@
+// Calls, parameters, returns, and varargs under the System V i386 calling
+// convention: every argument on the stack in 4-byte-aligned slots, integers
+// returned in eax (edx:eax for 64-bit values), floating point in st(0), and
+// aggregates through a hidden pointer the callee pops
+use ir::{Function as IrFunction, Operand, VarId};
+use model::Type;
+use super::{at, frame};
+use super::function::{align_to, is_aggregate, is_float_type, is_unsigned, FunctionGenerator, TEMP_VALUE};
+
+/// What a call jumps to.
+#[derive(Clone, Copy)]
+enum Callee<'a> {
+    Named(&'a str),
+    Pointer(&'a Operand),
+}
+
+impl FunctionGenerator<'_> {
+    /// Offset from ebp of each incoming parameter, above the return address
+    /// and the hidden pointer of an aggregate return, and of the end of the
+    /// named parameters.
+    pub(super) fn param_offsets(&self, func: &IrFunction) -> (Vec<i32>, i32) {
+        let mut offset = if is_aggregate(&func.return_type) { 12 } else { 8 };
+        let offsets = func.params.iter()
+            .map(|(ty, _)| {
+                let param = offset;
+                offset += align_to(self.size_of(ty) as i32, 4);
+                param
+            })
+            .collect();
+        (offsets, offset)
+    }
+
+    /// Give the incoming parameters their homes. Parameters in memory stay
+    /// in the caller's argument slots, which the callee owns.
+    pub(super) fn gen_params(&mut self, func: &IrFunction) {
+        self.returns_aggregate = is_aggregate(&func.return_type);
+        let (offsets, end) = self.param_offsets(func);
+        self.va_first = end;
+        for ((ty, var), &offset) in func.params.iter().zip(&offsets) {
+            if is_aggregate(ty) {
+                // Laid out before register allocation
+                continue;
+            }
+            if is_float_type(ty) || self.is_wide(ty) {
+                self.slots.insert(*var, offset);
+                continue;
+            }
+            // The caller need not extend char and short arguments
+            let extend = if is_unsigned(ty) { "movzx" } else { "movsx" };
+            let load = match self.int_size(ty) {
+                1 => Some(format!("{} {{}}, BYTE PTR {}", extend, frame(offset))),
+                2 => Some(format!("{} {{}}, WORD PTR {}", extend, frame(offset))),
+                _ => None,
+            };
+            if let Some(reg) = self.regs.get(var).copied() {
+                match load {
+                    Some(load) => self.emit(load.replace("{}", reg)),
+                    None => self.emit(format!("mov {}, DWORD PTR {}", reg, frame(offset))),
+                }
+                continue;
+            }
+            self.slots.insert(*var, offset);
+            if let Some(load) = load {
+                self.emit(load.replace("{}", "eax"));
+                self.emit(format!("mov DWORD PTR {}, eax", frame(offset)));
+            }
+        }
+    }
+
+    pub(super) fn gen_call(&mut self, dest: Option<VarId>, name: &str, args: &[Operand], vararg_types: &[Type]) {
+        if self.gen_builtin(dest, name, args) {
+            return;
+        }
+        let signature = self.cg.signatures.get(name).cloned();
+        let params = signature.as_ref().map(|s| s.params.clone());
+        let return_type = match (&signature, dest) {
+            (Some(s), _) => s.return_type.clone(),
+            (None, Some(dest)) => self.var_type(dest),
+            (None, None) => Type::Void,
+        };
+        self.emit_call(dest, Callee::Named(name), args, params.as_deref(), vararg_types, &return_type);
+    }
+
+    pub(super) fn gen_indirect_call(
+        &mut self,
+        dest: Option<VarId>,
+        func_ptr: &Operand,
+        args: &[Operand],
+        vararg_types: &[Type],
+    ) {
+        // A pointer constant-folded to the function itself has its prototype
+        if let Operand::Global(name) = func_ptr
+            && let Some(signature) = self.cg.signatures.get(name).cloned()
+        {
+            let callee = Callee::Named(name);
+            self.emit_call(dest, callee, args, Some(&signature.params), vararg_types, &signature.return_type);
+            return;
+        }
+        let (params, return_type) = match self.operand_type(func_ptr) {
+            Type::FunctionPointer { return_type, param_types, .. } => (Some(param_types), *return_type),
+            Type::Pointer(inner, _) => match *inner {
+                Type::FunctionPointer { return_type, param_types, .. } => (Some(param_types), *return_type),
+                _ => (None, dest.map_or(Type::Void, |d| self.var_type(d))),
+            },
+            _ => (None, dest.map_or(Type::Void, |d| self.var_type(d))),
+        };
+        self.emit_call(dest, Callee::Pointer(func_ptr), args, params.as_deref(), vararg_types, &return_type);
+    }
+
+    /// Store the arguments at esp, call `callee`, and put the result in
+    /// `dest`. `params` are the callee's parameter types when known; the
+    /// trailing arguments past them are passed as their `vararg_types`.
+    fn emit_call(
+        &mut self,
+        dest: Option<VarId>,
+        callee: Callee,
+        args: &[Operand],
+        params: Option<&[Type]>,
+        vararg_types: &[Type],
+        return_type: &Type,
+    ) {
+        let returns_aggregate = is_aggregate(return_type);
+        let mut offset = if returns_aggregate { 4 } else { 0 };
+        let first_vararg = args.len().saturating_sub(vararg_types.len());
+        for (i, arg) in args.iter().enumerate() {
+            let ty = match params.and_then(|p| p.get(i)) {
+                Some(ty) => ty.clone(),
+                None if i >= first_vararg => vararg_types[i - first_vararg].clone(),
+                // Default argument promotion, for calls built without types
+                None => match self.operand_type(arg) {
+                    Type::Float => Type::Double,
+                    ty => ty,
+                },
+            };
+            self.outgoing_arg(arg, &ty, offset);
+            offset += align_to(self.size_of(&ty) as i32, 4);
+        }
+        self.outgoing_size = self.outgoing_size.max(offset);
+
+        let result_buffer = returns_aggregate.then(|| self.alloc_aggregate(return_type));
+        if let Some(buffer) = result_buffer {
+            self.emit(format!("lea eax, {}", frame(buffer)));
+            self.emit("mov DWORD PTR [esp], eax");
+        }
+        match callee {
+            Callee::Pointer(func_ptr) => {
+                self.load_int("eax", func_ptr);
+                self.emit("call eax");
+            }
+            Callee::Named(name) => self.emit(format!("call {}", name)),
+        }
+        if returns_aggregate {
+            // The callee popped the hidden pointer
+            self.emit("sub esp, 4");
+        }
+
+        let Some(dest) = dest else {
+            if is_float_type(return_type) {
+                self.emit("fstp st(0)");
+            }
+            return;
+        };
+        if let Some(buffer) = result_buffer {
+            self.emit(format!("lea eax, {}", frame(buffer)));
+            self.write_dest(dest, "eax");
+        } else if is_float_type(return_type) {
+            // fstp rounds to the width it stores
+            let offset = self.slot(dest);
+            let width = if self.var_type(dest) == Type::Float { "DWORD" } else { "QWORD" };
+            self.emit(format!("fstp {} PTR {}", width, frame(offset)));
+        } else if self.is_wide(return_type) && self.is_wide(&self.var_type(dest)) {
+            self.write_wide(dest, "eax", "edx");
+        } else {
+            self.narrow("eax", return_type);
+            self.write_dest(dest, "eax");
+        }
+    }
+
+    /// Store argument `arg` of type `ty` at `[esp+offset]`.
+    fn outgoing_arg(&mut self, arg: &Operand, ty: &Type, offset: i32) {
+        let mem = at("esp", offset);
+        if is_aggregate(ty) {
+            self.load_int("ecx", arg);
+            self.emit(format!("lea edx, {}", mem));
+            self.copy_memory(self.size_of(ty));
+        } else if is_float_type(ty) {
+            let double = *ty == Type::Double;
+            self.load_float("xmm0", arg, double);
+            if double {
+                self.emit(format!("movsd QWORD PTR {}, xmm0", mem));
+            } else {
+                self.emit(format!("movss DWORD PTR {}, xmm0", mem));
+            }
+        } else if self.is_wide(ty) {
+            self.load_wide(arg);
+            self.emit(format!("mov DWORD PTR {}, eax", mem));
+            self.emit(format!("mov DWORD PTR {}, edx", at("esp", offset + 4)));
+        } else {
+            let value = self.int_source(arg, "eax");
+            if value.contains("PTR") || self.int_size(ty) < 4 {
+                if value != "eax" {
+                    self.emit(format!("mov eax, {}", value));
+                }
+                self.narrow("eax", ty);
+                self.emit(format!("mov DWORD PTR {}, eax", mem));
+            } else {
+                self.emit(format!("mov DWORD PTR {}, {}", mem, value));
+            }
+        }
+    }
+
+    pub(super) fn gen_return(&mut self, value: Option<&Operand>, return_type: &Type) {
+        let Some(value) = value else { return };
+        if *return_type == Type::Void {
+            return;
+        }
+        if is_aggregate(return_type) {
+            self.load_int("ecx", value);
+            self.emit(format!("mov edx, DWORD PTR {}", frame(8)));
+            self.copy_memory(self.size_of(return_type));
+            self.emit(format!("mov eax, DWORD PTR {}", frame(8)));
+        } else if is_float_type(return_type) {
+            let double = *return_type == Type::Double;
+            self.load_float("xmm0", value, double);
+            let temp = frame(self.scratch() + TEMP_VALUE);
+            if double {
+                self.emit(format!("movsd QWORD PTR {}, xmm0", temp));
+                self.emit(format!("fld QWORD PTR {}", temp));
+            } else {
+                self.emit(format!("movss DWORD PTR {}, xmm0", temp));
+                self.emit(format!("fld DWORD PTR {}", temp));
+            }
+        } else if self.is_wide(return_type) {
+            self.load_wide(value);
+        } else {
+            self.load_int("eax", value);
+            self.narrow("eax", return_type);
+        }
+    }
+
+    // ─── Varargs ─────────────────────────────────────────────────
+    //
+    // A `va_list` is a pointer to the next variadic argument, which the
+    // caller stored after the named ones. Each takes a multiple of 4 bytes,
+    // and `float`s are passed as `double`s.
+
+    pub(super) fn gen_va_start(&mut self, list: &Operand) {
+        self.emit(format!("lea eax, {}", frame(self.va_first)));
+        let (base, offset) = self.address(list);
+        self.emit(format!("mov DWORD PTR {}, eax", at(&base, offset)));
+    }
+
+    pub(super) fn gen_va_copy(&mut self, dest: &Operand, src: &Operand) {
+        let (base, offset) = self.address(src);
+        self.emit(format!("mov eax, DWORD PTR {}", at(&base, offset)));
+        let (base, offset) = self.address(dest);
+        self.emit(format!("mov DWORD PTR {}, eax", at(&base, offset)));
+    }
+
+    pub(super) fn gen_va_arg(&mut self, dest: VarId, list: &Operand, ty: &Type) {
+        let passed = if *ty == Type::Float { Type::Double } else { ty.clone() };
+        let step = align_to(self.size_of(&passed) as i32, 4);
+        let (base, offset) = self.address(list);
+        let list = at(&base, offset);
+        self.emit(format!("mov ecx, DWORD PTR {}", list));
+        self.emit(format!("lea eax, [ecx+{}]", step));
+        self.emit(format!("mov DWORD PTR {}, eax", list));
+        if is_aggregate(ty) {
+            let buffer = self.alloc_aggregate(ty);
+            self.emit(format!("lea edx, {}", frame(buffer)));
+            self.copy_memory(self.size_of(ty));
+            self.emit(format!("lea eax, {}", frame(buffer)));
+            self.write_dest(dest, "eax");
+        } else if is_float_type(ty) {
+            self.emit("movsd xmm0, QWORD PTR [ecx]");
+            if self.var_type(dest) == Type::Float {
+                self.emit("cvtsd2ss xmm0, xmm0");
+            }
+            self.write_float(dest, "xmm0");
+        } else if self.is_wide(ty) {
+            self.emit("mov eax, DWORD PTR [ecx]");
+            self.emit("mov edx, DWORD PTR [ecx+4]");
+            self.write_wide(dest, "eax", "edx");
+        } else {
+            self.emit("mov eax, DWORD PTR [ecx]");
+            self.narrow("eax", ty);
+            self.write_dest(dest, "eax");
+        }
+    }
+}
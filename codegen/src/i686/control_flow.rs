@@ -0,0 +1,187 @@
+// Terminators and phi resolution
+use ir::{BlockId, Function as IrFunction, Instruction as IrInstruction, Operand, Terminator as IrTerminator, VarId};
+use model::Type;
+use super::frame;
+use super::function::{is_float_type, FunctionGenerator, Home, TEMP_VALUE};
+
+/// The source of a phi copy: a variable's home, or for an `alloca`
+/// variable the address of its object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Home(Home),
+    Object(i32),
+}
+
+/// One copy of a parallel phi assignment, of a 4- or 8-byte value.
+struct PhiCopy {
+    dest: Home,
+    src: Source,
+    size: i32,
+}
+
+impl FunctionGenerator<'_> {
+    pub(super) fn gen_terminator(&mut self, term: &IrTerminator, block: BlockId, func: &IrFunction) {
+        let target_label = |id: BlockId| format!("{}_{}", func.name, id.0);
+        match term {
+            IrTerminator::Br(target) => {
+                self.gen_phi_copies(func, block, *target);
+                self.emit(format!("jmp {}", target_label(*target)));
+            }
+            IrTerminator::CondBr { cond, then_block, else_block, .. } => {
+                self.test_condition(cond);
+                let else_edge = self.new_label();
+                self.emit(format!("je {}", else_edge));
+                self.gen_phi_copies(func, block, *then_block);
+                self.emit(format!("jmp {}", target_label(*then_block)));
+                self.label(&else_edge);
+                self.gen_phi_copies(func, block, *else_block);
+                self.emit(format!("jmp {}", target_label(*else_block)));
+            }
+            IrTerminator::Switch { value, cases, default } => {
+                // A chain of compares, each case jumping to the copies of its
+                // own edge; the copies may clobber the value
+                let targets = IrTerminator::switch_targets(cases, *default);
+                let edges: Vec<String> = targets.iter().map(|_| self.new_label()).collect();
+                let edge = |id: BlockId| edges[targets.iter().position(|&t| t == id).unwrap()].clone();
+                if self.is_wide_operand(value) {
+                    self.load_wide(value);
+                    for &(case, target) in cases {
+                        let next = self.new_label();
+                        self.emit(format!("cmp eax, {}", case as i32));
+                        self.emit(format!("jne {}", next));
+                        self.emit(format!("cmp edx, {}", (case >> 32) as i32));
+                        self.emit(format!("je {}", edge(target)));
+                        self.label(&next);
+                    }
+                } else {
+                    self.load_int("eax", value);
+                    for &(case, target) in cases {
+                        self.emit(format!("cmp eax, {}", case as i32));
+                        self.emit(format!("je {}", edge(target)));
+                    }
+                }
+                self.emit(format!("jmp {}", edge(*default)));
+                for (&target, label) in targets.iter().zip(&edges) {
+                    self.label(label);
+                    self.gen_phi_copies(func, block, target);
+                    self.emit(format!("jmp {}", target_label(target)));
+                }
+            }
+            IrTerminator::Ret(value) => {
+                self.gen_return(value.as_ref(), &func.return_type);
+                self.emit(format!("jmp .L{}_ret", func.name));
+            }
+            IrTerminator::Unreachable => {
+                self.emit(format!("jmp .L{}_ret", func.name));
+            }
+            IrTerminator::IndirectBr { target } => {
+                self.load_int("eax", target);
+                self.emit("jmp eax");
+            }
+        }
+    }
+
+    /// Set ZF when branch condition `cond` is false.
+    fn test_condition(&mut self, cond: &Operand) {
+        let ty = match cond {
+            Operand::FloatConstant(_) => Type::Double,
+            _ => self.operand_type(cond),
+        };
+        if is_float_type(&ty) {
+            let double = ty == Type::Double;
+            self.load_float("xmm0", cond, double);
+            self.float_truth("xmm0", double, "al", "cl");
+            self.emit("test al, al");
+        } else if self.is_wide_operand(cond) {
+            self.load_wide(cond);
+            self.emit("or eax, edx");
+        } else {
+            let value = self.int_rm(cond, "eax");
+            if value.contains("PTR") {
+                self.emit(format!("cmp {}, 0", value));
+            } else {
+                self.emit(format!("test {}, {}", value, value));
+            }
+        }
+    }
+
+    /// Assign the phis of `to` their values for the edge from `from`, all at
+    /// once: a copy waits until no other copy still reads its destination,
+    /// and a cycle of copies is broken through the scratch area.
+    fn gen_phi_copies(&mut self, func: &IrFunction, from: BlockId, to: BlockId) {
+        let Some(block) = func.blocks.iter().find(|b| b.id == to) else { return };
+        let phis: Vec<(VarId, VarId)> = block.instructions.iter()
+            .filter_map(|inst| match inst {
+                IrInstruction::Phi { dest, preds } => preds.iter()
+                    .find(|(pred, _)| *pred == from)
+                    .map(|(_, src)| (*dest, *src)),
+                _ => None,
+            })
+            .collect();
+        let mut pending: Vec<PhiCopy> = Vec::new();
+        for (dest, src) in phis {
+            let ty = self.var_type(dest);
+            let size = if self.is_wide(&ty) || ty == Type::Double { 8 } else { 4 };
+            let src = match self.objects.get(&src) {
+                Some(offset) => Source::Object(*offset),
+                None => Source::Home(self.home(src)),
+            };
+            let dest = self.home(dest);
+            if src != Source::Home(dest) {
+                pending.push(PhiCopy { dest, src, size });
+            }
+        }
+
+        while !pending.is_empty() {
+            let ready = pending.iter()
+                .position(|copy| !pending.iter().any(|other| other.src == Source::Home(copy.dest)));
+            if let Some(i) = ready {
+                let copy = pending.remove(i);
+                self.copy_value(copy.dest, copy.src, copy.size);
+                continue;
+            }
+            // Every destination is still read: save one to a temporary
+            let copy = &pending[0];
+            let (blocked, size) = (copy.dest, copy.size);
+            let temp = Home::Slot(self.scratch() + TEMP_VALUE);
+            self.copy_value(temp, Source::Home(blocked), size);
+            for copy in &mut pending {
+                if copy.src == Source::Home(blocked) {
+                    copy.src = Source::Home(temp);
+                }
+            }
+        }
+    }
+
+    /// `dest = src` between registers and stack slots, through eax (and edx
+    /// for the high half of 8-byte values, which only live in slots).
+    fn copy_value(&mut self, dest: Home, src: Source, size: i32) {
+        let value = match src {
+            Source::Object(offset) => {
+                self.emit(format!("lea eax, {}", frame(offset)));
+                "eax".to_string()
+            }
+            Source::Home(Home::Reg(reg)) => reg.to_string(),
+            Source::Home(Home::Slot(offset)) => {
+                if size == 8 {
+                    self.emit(format!("mov edx, DWORD PTR {}", frame(offset + 4)));
+                }
+                self.emit(format!("mov eax, DWORD PTR {}", frame(offset)));
+                "eax".to_string()
+            }
+        };
+        match dest {
+            Home::Reg(reg) => {
+                if reg != value {
+                    self.emit(format!("mov {}, {}", reg, value));
+                }
+            }
+            Home::Slot(offset) => {
+                self.emit(format!("mov DWORD PTR {}, {}", frame(offset), value));
+                if size == 8 {
+                    self.emit(format!("mov DWORD PTR {}, edx", frame(offset + 4)));
+                }
+            }
+        }
+    }
+}
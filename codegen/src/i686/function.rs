@@ -0,0 +1,671 @@
+// Per-function i686 code generation: frame layout, operands, and the
+// instruction dispatch
+use std::collections::{HashMap, HashSet};
+use ir::{Function as IrFunction, Instruction as IrInstruction, Operand, VarId};
+use model::Type;
+use crate::Codegen;
+use crate::regalloc::{allocate_registers_where, PhysicalReg};
+use super::{byte_reg, frame, word_reg, CALLEE_SAVED};
+
+/// Where a variable's value lives: a register, or a stack slot at an offset
+/// from ebp. Slots are 8 bytes for `long long` and `double` values and 4
+/// bytes for everything else; aggregate-typed variables hold the address
+/// of their object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Home {
+    Reg(&'static str),
+    Slot(i32),
+}
+
+/// Offsets into the scratch area of a function: a value being converted or
+/// passed through the x87 unit, the right operand of a 64-bit operation,
+/// and the saved and truncating x87 control words.
+pub(super) const TEMP_VALUE: i32 = 0;
+pub(super) const TEMP_OPERAND: i32 = 8;
+pub(super) const TEMP_CONTROL_WORD: i32 = 16;
+
+/// Generates the code for one function. The frame, from ebp down:
+///
+/// - the caller's ebp (the return address and arguments are above it)
+/// - stack slots, `alloca` objects, aggregate temporaries, the scratch
+///   area, and the callee-saved registers the function uses
+/// - the outgoing stack arguments, at esp
+///
+/// ebp is 8 bytes off a 16-byte boundary, and esp stays 16-byte aligned
+/// between the prologue and the epilogue, so calls need no adjustment.
+pub(crate) struct FunctionGenerator<'a> {
+    pub(super) cg: &'a mut Codegen,
+    /// Assembly lines of the body, emitted before the prologue is known
+    pub(super) out: Vec<String>,
+    pub(super) func_name: String,
+    pub(super) var_types: HashMap<VarId, Type>,
+    pub(super) regs: HashMap<VarId, &'static str>,
+    pub(super) slots: HashMap<VarId, i32>,
+    /// Offset from ebp of the object each `Alloca` variable points to, and
+    /// of each aggregate parameter
+    pub(super) objects: HashMap<VarId, i32>,
+    /// Type of each `Alloca` object; inline asm outputs are written to them
+    pub(super) object_types: HashMap<VarId, Type>,
+    /// Bytes of the frame below ebp laid out so far
+    frame_bytes: i32,
+    /// Largest stack argument area of any call
+    pub(super) outgoing_size: i32,
+    /// Callee-saved registers inline assembly writes
+    pub(super) asm_regs: HashSet<&'static str>,
+    /// Offset from ebp of the first variadic argument, for `va_start`
+    pub(super) va_first: i32,
+    /// Whether the function returns an aggregate through a hidden pointer,
+    /// which it pops on return
+    pub(super) returns_aggregate: bool,
+    scratch: Option<i32>,
+    next_label: usize,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    pub fn new(cg: &'a mut Codegen) -> Self {
+        Self {
+            cg,
+            out: Vec::new(),
+            func_name: String::new(),
+            var_types: HashMap::new(),
+            regs: HashMap::new(),
+            slots: HashMap::new(),
+            objects: HashMap::new(),
+            object_types: HashMap::new(),
+            frame_bytes: 0,
+            outgoing_size: 0,
+            asm_regs: HashSet::new(),
+            va_first: 0,
+            returns_aggregate: false,
+            scratch: None,
+            next_label: 0,
+        }
+    }
+
+    pub fn gen_function(mut self, func: &IrFunction) -> String {
+        self.func_name = func.name.clone();
+        self.var_types = func.var_types.clone();
+        for (ty, var) in &func.params {
+            self.var_types.insert(*var, ty.clone());
+        }
+        let instructions = || func.blocks.iter().flat_map(|b| &b.instructions);
+
+        for inst in instructions() {
+            if let IrInstruction::Alloca { dest, r#type } = inst {
+                let size = self.size_of(r#type).max(1);
+                let align = self.cg.type_alignment(r#type).max(4);
+                let offset = self.alloc_frame(size, align);
+                self.objects.insert(*dest, offset);
+                self.object_types.insert(*dest, r#type.clone());
+            }
+        }
+
+        // Aggregate parameters are objects in the caller's argument area
+        let (offsets, _) = self.param_offsets(func);
+        for ((ty, var), offset) in func.params.iter().zip(offsets) {
+            if is_aggregate(ty) {
+                self.objects.insert(*var, offset);
+                self.object_types.insert(*var, ty.clone());
+            }
+        }
+
+        // Inline assembly stages its operands in any register, so functions
+        // with it keep their variables in memory
+        let has_asm = instructions().any(|inst| matches!(inst, IrInstruction::InlineAsm { .. }));
+        if self.cg.enable_regalloc && !has_asm {
+            let target = self.cg.target.clone();
+            let allocation = allocate_registers_where(func, &target, |var| self.fits_register(var));
+            self.regs = allocation.into_iter().map(|(var, reg)| (var, reg_name(reg))).collect();
+        }
+
+        self.gen_params(func);
+        if func.blocks.first().is_some_and(|b| b.id != func.entry_block) {
+            self.emit(format!("jmp {}_{}", func.name, func.entry_block.0));
+        }
+        let branch_targets: HashSet<ir::BlockId> =
+            func.compute_successors().into_values().flatten().collect();
+        for block in &func.blocks {
+            // Blocks CFG simplification left unreachable, as in the x86-64 backend
+            if block.instructions.is_empty()
+                && matches!(block.terminator, ir::Terminator::Unreachable)
+                && !branch_targets.contains(&block.id)
+                && block.id != func.entry_block
+            {
+                continue;
+            }
+            self.label(&format!("{}_{}", func.name, block.id.0));
+            self.count_block(block.id);
+            for inst in &block.instructions {
+                self.gen_instr(inst);
+            }
+            self.gen_terminator(&block.terminator, block.id, func);
+        }
+        self.finish()
+    }
+
+    /// Whether variable `var` may live in a register: only 32-bit values
+    /// other than `float` do, and never the objects of `alloca`s or
+    /// aggregate parameters.
+    fn fits_register(&self, var: VarId) -> bool {
+        let ty = self.var_type(var);
+        !self.objects.contains_key(&var) && !is_float_type(&ty) && !self.is_wide(&ty)
+    }
+
+    /// Under `-fprofile-generate`, count the executions of `block`.
+    fn count_block(&mut self, block: ir::BlockId) {
+        if !self.cg.profile_generate {
+            return;
+        }
+        let counter = format!("__profc_{}_{}", self.func_name, block.0);
+        if !self.cg.profile_counters.contains(&counter) {
+            self.cg.profile_counters.push(counter.clone());
+        }
+        // The counters are 64-bit
+        self.emit(format!("add DWORD PTR [{}], 1", counter));
+        self.emit(format!("adc DWORD PTR [{}+4], 0", counter));
+    }
+
+    /// The function's label, prologue, body, and epilogue.
+    fn finish(mut self) -> String {
+        // Callee-saved registers the function writes get frame slots too
+        let used: Vec<&'static str> = CALLEE_SAVED.iter()
+            .filter(|reg| self.regs.values().any(|r| r == *reg) || self.asm_regs.contains(*reg))
+            .copied()
+            .collect();
+        let saved: Vec<(&'static str, i32)> = used.into_iter().map(|reg| (reg, self.alloc_frame(4, 4))).collect();
+        // Keep esp 16-byte aligned: ebp is 8 bytes off a boundary
+        let size = align_to(self.frame_bytes + self.outgoing_size + 8, 16) - 8;
+        let mut lines = vec![
+            format!("{}:", self.func_name),
+            ".cfi_startproc".to_string(),
+            "    push ebp".to_string(),
+            ".cfi_def_cfa_offset 8".to_string(),
+            ".cfi_offset ebp, -8".to_string(),
+            "    mov ebp, esp".to_string(),
+            ".cfi_def_cfa_register ebp".to_string(),
+        ];
+        if size > 0 {
+            lines.push(format!("    sub esp, {}", size));
+        }
+        for (reg, offset) in &saved {
+            lines.push(format!("    mov DWORD PTR {}, {}", frame(*offset), reg));
+            lines.push(format!(".cfi_offset {}, {}", reg, offset - 8));
+        }
+        lines.extend(self.out);
+        lines.push(format!(".L{}_ret:", self.func_name));
+        for (reg, offset) in &saved {
+            lines.push(format!("    mov {}, DWORD PTR {}", reg, frame(*offset)));
+            lines.push(format!(".cfi_restore {}", reg));
+        }
+        lines.push("    leave".to_string());
+        lines.push(".cfi_def_cfa esp, 4".to_string());
+        // The callee pops the hidden pointer of an aggregate return
+        lines.push(if self.returns_aggregate { "    ret 4" } else { "    ret" }.to_string());
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn gen_instr(&mut self, inst: &IrInstruction) {
+        match inst {
+            IrInstruction::Binary { dest, op, left, right } => self.gen_binary(*dest, op, left, right),
+            IrInstruction::FloatBinary { dest, op, left, right } => self.gen_float_binary(*dest, op, left, right),
+            IrInstruction::Unary { dest, op, src } => self.gen_unary(*dest, op, src),
+            IrInstruction::FloatUnary { dest, op, src } => self.gen_float_unary(*dest, op, src),
+            // Phis are resolved by copies at the end of each predecessor
+            IrInstruction::Phi { .. } => {}
+            IrInstruction::Copy { dest, src } => self.gen_copy(*dest, src),
+            IrInstruction::Cast { dest, src, src_type, r#type } => self.gen_cast(*dest, src, src_type, r#type),
+            // Frame objects are laid out before the body
+            IrInstruction::Alloca { .. } => {}
+            IrInstruction::Load { dest, addr, value_type, .. } => self.gen_load(*dest, addr, value_type),
+            IrInstruction::Store { addr, src, value_type, .. } => self.gen_store(addr, src, value_type),
+            IrInstruction::GetElementPtr { dest, base, index, element_type } => self.gen_gep(*dest, base, index, element_type),
+            IrInstruction::Call { dest, name, args, vararg_types } => self.gen_call(*dest, name, args, vararg_types),
+            IrInstruction::IndirectCall { dest, func_ptr, args, vararg_types, .. } => {
+                self.gen_indirect_call(*dest, func_ptr, args, vararg_types)
+            }
+            IrInstruction::VaStart { list, .. } => self.gen_va_start(list),
+            IrInstruction::VaEnd { .. } => {}
+            IrInstruction::VaCopy { dest, src } => self.gen_va_copy(dest, src),
+            IrInstruction::VaArg { dest, list, r#type } => self.gen_va_arg(*dest, list, r#type),
+            IrInstruction::InlineAsm { template, outputs, inputs, output_constraints, input_constraints, clobbers, .. } => {
+                self.gen_inline_asm(template, outputs, inputs, output_constraints, input_constraints, clobbers)
+            }
+            IrInstruction::Memcpy { dest, src, size } => {
+                self.load_int("ecx", src);
+                self.load_int("edx", dest);
+                self.copy_memory(*size);
+            }
+            IrInstruction::Memset { dest, value, size } => self.gen_memset(dest, value, *size),
+            // Vectorization is off for i686 (`TargetConfig::i686` has no SIMD level)
+            IrInstruction::Simd { .. } => unreachable!("SIMD instructions are not generated for i686"),
+        }
+    }
+
+    // ─── Output ──────────────────────────────────────────────────
+
+    pub(super) fn emit(&mut self, line: impl AsRef<str>) {
+        self.out.push(format!("    {}", line.as_ref()));
+    }
+
+    pub(super) fn label(&mut self, name: &str) {
+        self.out.push(format!("{}:", name));
+    }
+
+    /// A fresh local label.
+    pub(super) fn new_label(&mut self) -> String {
+        self.next_label += 1;
+        format!(".L{}_{}", self.func_name, self.next_label)
+    }
+
+    // ─── Types ───────────────────────────────────────────────────
+
+    pub(super) fn var_type(&self, var: VarId) -> Type {
+        self.var_types.get(&var).cloned().unwrap_or(Type::Long)
+    }
+
+    pub(super) fn operand_type(&self, op: &Operand) -> Type {
+        match op {
+            Operand::Var(var) => self.var_type(*var),
+            // IR constants are 64-bit values
+            Operand::Constant(c) if i32::try_from(*c).is_err() => Type::LongLong,
+            Operand::Constant(_) => Type::Int,
+            Operand::FloatConstant(_) => Type::Double,
+            Operand::Global(_) => Type::Pointer(Box::new(Type::Void), Default::default()),
+        }
+    }
+
+    pub(super) fn size_of(&self, ty: &Type) -> usize {
+        self.cg.type_size(ty)
+    }
+
+    /// Size of the integer value of type `ty`: pointers, aggregate
+    /// addresses, and anything else not an integer take 4 bytes.
+    pub(super) fn int_size(&self, ty: &Type) -> usize {
+        match ty {
+            Type::Char | Type::UnsignedChar | Type::Bool | Type::Short | Type::UnsignedShort
+            | Type::Int | Type::UnsignedInt | Type::Long | Type::UnsignedLong
+            | Type::LongLong | Type::UnsignedLongLong | Type::Enum(_) | Type::Typedef(_) => {
+                self.size_of(ty).min(8)
+            }
+            _ => 4,
+        }
+    }
+
+    /// Whether values of `ty` are 64-bit integers, held in register pairs.
+    pub(super) fn is_wide(&self, ty: &Type) -> bool {
+        self.int_size(ty) == 8
+    }
+
+    pub(super) fn is_wide_operand(&self, op: &Operand) -> bool {
+        match op {
+            Operand::Var(var) if self.objects.contains_key(var) => false,
+            Operand::FloatConstant(_) => false,
+            _ => self.is_wide(&self.operand_type(op)),
+        }
+    }
+
+    // ─── Frame ───────────────────────────────────────────────────
+
+    /// Reserve `size` bytes of frame aligned to `align` (at most 16);
+    /// returns their offset from ebp.
+    pub(super) fn alloc_frame(&mut self, size: usize, align: usize) -> i32 {
+        let align = align.clamp(1, 16) as i32;
+        // ebp is 8 mod 16, so 16-byte objects sit 8 mod 16 below it
+        let bias = if align == 16 { 8 } else { 0 };
+        self.frame_bytes = align_to(self.frame_bytes + size as i32 - bias, align) + bias;
+        -self.frame_bytes
+    }
+
+    /// A frame object for an aggregate temporary of type `ty`, rounded up to
+    /// whole 4-byte words, since aggregates are copied and passed by words.
+    pub(super) fn alloc_aggregate(&mut self, ty: &Type) -> i32 {
+        let size = align_to(self.size_of(ty) as i32, 4).max(4) as usize;
+        let align = self.cg.type_alignment(ty).max(4);
+        self.alloc_frame(size, align)
+    }
+
+    /// Offset from ebp of the function's scratch area (see `TEMP_VALUE`).
+    pub(super) fn scratch(&mut self) -> i32 {
+        if let Some(offset) = self.scratch {
+            return offset;
+        }
+        let offset = self.alloc_frame(20, 8);
+        self.scratch = Some(offset);
+        offset
+    }
+
+    pub(super) fn home(&mut self, var: VarId) -> Home {
+        if let Some(reg) = self.regs.get(&var) {
+            return Home::Reg(reg);
+        }
+        if let Some(offset) = self.slots.get(&var) {
+            return Home::Slot(*offset);
+        }
+        let ty = self.var_type(var);
+        let size = if self.is_wide(&ty) || ty == Type::Double { 8 } else { 4 };
+        let offset = self.alloc_frame(size, size);
+        self.slots.insert(var, offset);
+        Home::Slot(offset)
+    }
+
+    /// The offset from ebp of variable `var`'s slot; it must not live in a
+    /// register.
+    pub(super) fn slot(&mut self, var: VarId) -> i32 {
+        match self.home(var) {
+            Home::Slot(offset) => offset,
+            Home::Reg(reg) => unreachable!("{} lives in memory, not {}", var.0, reg),
+        }
+    }
+
+    // ─── Integer operands ────────────────────────────────────────
+
+    /// Integer (or pointer, or aggregate address) operand `op` as a source
+    /// operand: an immediate, a register, or a `DWORD PTR` memory operand
+    /// (the low half of a 64-bit value). The address of an `alloca` object
+    /// is computed into `scratch`.
+    pub(super) fn int_source(&mut self, op: &Operand, scratch: &str) -> String {
+        match op {
+            Operand::Constant(c) => (*c as i32).to_string(),
+            Operand::FloatConstant(f) => (*f as i64 as i32).to_string(),
+            Operand::Global(name) => format!("OFFSET {}", name),
+            Operand::Var(var) => {
+                if let Some(&offset) = self.objects.get(var) {
+                    self.emit(format!("lea {}, {}", scratch, frame(offset)));
+                    return scratch.to_string();
+                }
+                match self.home(*var) {
+                    Home::Reg(reg) => reg.to_string(),
+                    Home::Slot(offset) => format!("DWORD PTR {}", frame(offset)),
+                }
+            }
+        }
+    }
+
+    /// `op` as a register or memory operand, for instructions that take no
+    /// immediate; immediates are moved into `scratch`.
+    pub(super) fn int_rm(&mut self, op: &Operand, scratch: &str) -> String {
+        let source = self.int_source(op, scratch);
+        if is_immediate(&source) {
+            self.emit(format!("mov {}, {}", scratch, source));
+            return scratch.to_string();
+        }
+        source
+    }
+
+    /// `reg = op`, the low 32 bits of an integer operand.
+    pub(super) fn load_int(&mut self, reg: &str, op: &Operand) {
+        let source = self.int_source(op, reg);
+        if source != reg {
+            self.emit(format!("mov {}, {}", reg, source));
+        }
+    }
+
+    /// The low and high halves of integer operand `op` as 64-bit source
+    /// operands. A 32-bit value is extended according to its own type, the
+    /// high half computed into `hi_scratch` if signed; `lo_scratch` takes
+    /// the address of an object.
+    pub(super) fn wide_source(&mut self, op: &Operand, lo_scratch: &str, hi_scratch: &str) -> (String, String) {
+        let constant = match op {
+            Operand::Constant(c) => Some(*c),
+            Operand::FloatConstant(f) => Some(*f as i64),
+            _ => None,
+        };
+        if let Some(c) = constant {
+            return ((c as i32).to_string(), ((c >> 32) as i32).to_string());
+        }
+        let ty = self.operand_type(op);
+        if let Operand::Var(var) = op
+            && !self.objects.contains_key(var)
+            && (self.is_wide(&ty) || ty == Type::Double)
+        {
+            let offset = self.slot(*var);
+            return (format!("DWORD PTR {}", frame(offset)), format!("DWORD PTR {}", frame(offset + 4)));
+        }
+        let lo = self.int_source(op, lo_scratch);
+        // Pointers and addresses are zero-extended
+        let signed = matches!(op, Operand::Var(v) if !self.objects.contains_key(v)) && is_integer(&ty) && !is_unsigned(&ty);
+        if !signed {
+            return (lo, "0".to_string());
+        }
+        self.emit(format!("mov {}, {}", hi_scratch, lo));
+        self.emit(format!("sar {}, 31", hi_scratch));
+        (lo, hi_scratch.to_string())
+    }
+
+    /// edx:eax = integer operand `op`, extended to 64 bits.
+    pub(super) fn load_wide(&mut self, op: &Operand) {
+        let (lo, hi) = self.wide_source(op, "eax", "edx");
+        if hi != "edx" {
+            self.emit(format!("mov edx, {}", hi));
+        }
+        if lo != "eax" {
+            self.emit(format!("mov eax, {}", lo));
+        }
+    }
+
+    /// Store integer operand `op`, extended to 64 bits, at `[ebp+offset]`.
+    pub(super) fn store_wide_temp(&mut self, op: &Operand, offset: i32) {
+        self.load_wide(op);
+        self.emit(format!("mov DWORD PTR {}, eax", frame(offset)));
+        self.emit(format!("mov DWORD PTR {}, edx", frame(offset + 4)));
+    }
+
+    /// Put `value` (a register, an immediate, or a memory operand), the
+    /// 32-bit value of `dest`, in `dest`'s home.
+    pub(super) fn write_dest(&mut self, dest: VarId, value: &str) {
+        let home = self.home(dest);
+        let value = match home {
+            // x86 has no memory-to-memory move
+            Home::Slot(_) if value.contains("PTR") => {
+                self.emit(format!("mov eax, {}", value));
+                "eax"
+            }
+            _ => value,
+        };
+        match home {
+            Home::Reg(reg) => {
+                if reg != value {
+                    self.emit(format!("mov {}, {}", reg, value));
+                }
+            }
+            Home::Slot(offset) => {
+                let ty = self.var_type(dest);
+                self.emit(format!("mov DWORD PTR {}, {}", frame(offset), value));
+                // A 32-bit value copied into a 64-bit variable
+                if self.is_wide(&ty) || ty == Type::Double {
+                    self.emit(format!("mov DWORD PTR {}, 0", frame(offset + 4)));
+                }
+            }
+        }
+    }
+
+    /// Put edx:eax-style halves `lo` and `hi` (registers or immediates) in
+    /// the home of 64-bit variable `dest`.
+    pub(super) fn write_wide(&mut self, dest: VarId, lo: &str, hi: &str) {
+        match self.home(dest) {
+            Home::Reg(reg) => self.emit(format!("mov {}, {}", reg, lo)),
+            Home::Slot(offset) => {
+                self.emit(format!("mov DWORD PTR {}, {}", frame(offset), lo));
+                if self.is_wide(&self.var_type(dest)) || self.var_type(dest) == Type::Double {
+                    self.emit(format!("mov DWORD PTR {}, {}", frame(offset + 4), hi));
+                }
+            }
+        }
+    }
+
+    /// Reduce scratch register `reg` (eax, ecx, or edx) to the values of
+    /// integer type `ty`, as the IR interpreter does: registers hold every
+    /// integer sign- or zero-extended to 32 bits according to its own type.
+    pub(super) fn narrow(&mut self, reg: &str, ty: &Type) {
+        if *ty == Type::Bool {
+            self.emit(format!("test {}, {}", reg, reg));
+            self.emit(format!("setne {}", byte_reg(reg)));
+            self.emit(format!("movzx {}, {}", reg, byte_reg(reg)));
+            return;
+        }
+        if !is_integer(ty) {
+            return;
+        }
+        let extend = if is_unsigned(ty) { "movzx" } else { "movsx" };
+        match self.int_size(ty) {
+            1 => self.emit(format!("{} {}, {}", extend, reg, byte_reg(reg))),
+            2 => self.emit(format!("{} {}, {}", extend, reg, word_reg(reg))),
+            _ => {}
+        }
+    }
+
+    // ─── Floating-point operands ─────────────────────────────────
+
+    /// `xmm` = operand `op` as a `double`, or a `float` unless `double`.
+    pub(super) fn load_float(&mut self, xmm: &str, op: &Operand, double: bool) {
+        match op {
+            Operand::FloatConstant(f) => self.float_constant(xmm, *f, double),
+            Operand::Constant(c) => self.float_constant(xmm, *c as f64, double),
+            Operand::Var(var) if !self.objects.contains_key(var) && is_float_type(&self.var_type(*var)) => {
+                let is_double = self.var_type(*var) == Type::Double;
+                let mem = frame(self.slot(*var));
+                match (is_double, double) {
+                    (true, true) => self.emit(format!("movsd {}, QWORD PTR {}", xmm, mem)),
+                    (false, false) => self.emit(format!("movss {}, DWORD PTR {}", xmm, mem)),
+                    (true, false) => self.emit(format!("cvtsd2ss {}, QWORD PTR {}", xmm, mem)),
+                    (false, true) => self.emit(format!("cvtss2sd {}, DWORD PTR {}", xmm, mem)),
+                }
+            }
+            _ => {
+                let ty = self.operand_type(op);
+                self.int_to_float(xmm, op, &ty, double);
+            }
+        }
+    }
+
+    /// Load a floating-point constant: +0.0 by zeroing, anything else from
+    /// the constant pool.
+    fn float_constant(&mut self, xmm: &str, value: f64, double: bool) {
+        if value.to_bits() == 0 {
+            self.emit(format!("xorps {}, {}", xmm, xmm));
+            return;
+        }
+        let label = self.constant_label(value, double);
+        if double {
+            self.emit(format!("movsd {}, QWORD PTR [{}]", xmm, label));
+        } else {
+            self.emit(format!("movss {}, DWORD PTR [{}]", xmm, label));
+        }
+    }
+
+    /// The label of `value` in the constant pool, as a `double` or a `float`.
+    pub(super) fn constant_label(&mut self, value: f64, double: bool) -> String {
+        let bits = value.to_bits();
+        let label = self.cg.float_constants.iter()
+            .find(|(_, (v, d))| v.to_bits() == bits && *d == double)
+            .map(|(label, _)| label.clone());
+        label.unwrap_or_else(|| {
+            let label = format!(".LC{}", self.cg.next_float_const);
+            self.cg.next_float_const += 1;
+            self.cg.float_constants.insert(label.clone(), (value, double));
+            label
+        })
+    }
+
+    /// `xmm` = integer operand `op` of type `ty`, converted. Signed 32-bit
+    /// values convert with SSE2; unsigned and 64-bit ones through the x87
+    /// unit, which loads 64-bit integers exactly.
+    pub(super) fn int_to_float(&mut self, xmm: &str, op: &Operand, ty: &Type, double: bool) {
+        let (p, size) = if double { ("sd", "QWORD") } else { ("ss", "DWORD") };
+        let wide = self.is_wide(ty) || self.is_wide_operand(op);
+        let via_x87 = wide || (is_unsigned(ty) && self.int_size(ty) == 4);
+        if !via_x87 {
+            let source = self.int_rm(op, "eax");
+            self.emit(format!("cvtsi2{} {}, {}", p, xmm, source));
+            return;
+        }
+        let temp = self.scratch() + TEMP_VALUE;
+        self.store_wide_temp(op, temp);
+        self.emit(format!("fild QWORD PTR {}", frame(temp)));
+        if wide && is_unsigned(ty) {
+            // Values with the top bit set loaded as negative: add 2^64
+            let done = self.new_label();
+            let two_64 = self.constant_label(18446744073709551616.0, false);
+            self.emit(format!("cmp DWORD PTR {}, 0", frame(temp + 4)));
+            self.emit(format!("jge {}", done));
+            self.emit(format!("fadd DWORD PTR [{}]", two_64));
+            self.label(&done);
+        }
+        self.emit(format!("fstp {} PTR {}", size, frame(temp)));
+        self.emit(format!("mov{} {}, {} PTR {}", p, xmm, size, frame(temp)));
+    }
+
+    /// Put `xmm`, holding the value of floating-point variable `dest` in
+    /// its own precision, in its slot.
+    pub(super) fn write_float(&mut self, dest: VarId, xmm: &str) {
+        let offset = self.slot(dest);
+        if self.var_type(dest) == Type::Float {
+            self.emit(format!("movss DWORD PTR {}, {}", frame(offset), xmm));
+        } else {
+            self.emit(format!("movsd QWORD PTR {}, {}", frame(offset), xmm));
+        }
+    }
+
+    /// Set byte register `truth` to whether `xmm` is nonzero (NaN counts as
+    /// nonzero), using byte register `temp` and xmm2.
+    pub(super) fn float_truth(&mut self, xmm: &str, double: bool, truth: &str, temp: &str) {
+        let p = if double { "d" } else { "s" };
+        self.emit("xorps xmm2, xmm2");
+        self.emit(format!("ucomis{} {}, xmm2", p, xmm));
+        self.emit(format!("setne {}", truth));
+        self.emit(format!("setp {}", temp));
+        self.emit(format!("or {}, {}", truth, temp));
+    }
+}
+
+/// The 32-bit name of an allocatable register.
+fn reg_name(reg: PhysicalReg) -> &'static str {
+    match reg {
+        PhysicalReg::Rbx => "ebx",
+        PhysicalReg::Rsi => "esi",
+        PhysicalReg::Rdi => "edi",
+        other => unreachable!("{:?} is not allocatable on i686", other),
+    }
+}
+
+/// Whether source operand `operand` is an immediate.
+pub(super) fn is_immediate(operand: &str) -> bool {
+    operand.starts_with("OFFSET") || operand.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+}
+
+/// Round `value` up to a multiple of `align`.
+pub(super) fn align_to(value: i32, align: i32) -> i32 {
+    (value + align - 1) / align * align
+}
+
+pub(super) fn is_float_type(ty: &Type) -> bool {
+    matches!(ty, Type::Float | Type::Double)
+}
+
+/// Whether values of `ty` are aggregates, which the backend handles by address.
+pub(super) fn is_aggregate(ty: &Type) -> bool {
+    matches!(ty, Type::Struct(_) | Type::Union(_) | Type::Complex(_))
+}
+
+/// Whether `ty` is an integer type, rather than a pointer or an aggregate.
+pub(super) fn is_integer(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::Char | Type::UnsignedChar | Type::Bool | Type::Short | Type::UnsignedShort
+            | Type::Int | Type::UnsignedInt | Type::Long | Type::UnsignedLong
+            | Type::LongLong | Type::UnsignedLongLong | Type::Enum(_) | Type::Typedef(_)
+    )
+}
+
+/// Whether integer type `ty` is unsigned, by the IR interpreter's rules.
+pub(super) fn is_unsigned(ty: &Type) -> bool {
+    match ty {
+        Type::UnsignedChar | Type::UnsignedShort | Type::UnsignedInt | Type::UnsignedLong
+        | Type::UnsignedLongLong | Type::Bool => true,
+        Type::Typedef(name) => name.starts_with('u') || name == "size_t",
+        _ => false,
+    }
+}
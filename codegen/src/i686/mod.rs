@@ -0,0 +1,94 @@
+// i686 backend: 32-bit x86 assembly with the System V i386 calling
+// convention, in Intel syntax.
+//
+// The same IR and register allocator as the x86-64 backend, colored with
+// the three callee-saved registers ebx, esi, and edi; eax, ecx, and edx are
+// scratch. `long long` and floating-point values live in memory: 64-bit
+// arithmetic runs on edx:eax pairs, and floating point on SSE2 scalar
+// instructions, with the x87 unit only where the ABI or a conversion needs
+// it. Instructions are emitted straight as text, like the RISC-V backend,
+// since the peephole pass over `X86Instr` assumes 64-bit registers.
+mod function;
+mod ops;
+mod call_ops;
+mod builtins;
+mod control_flow;
+
+pub(crate) use function::FunctionGenerator;
+
+/// Registers the callee preserves that the backend may write, in the order
+/// their frame slots are laid out.
+pub(crate) const CALLEE_SAVED: [&str; 3] = ["ebx", "esi", "edi"];
+
+/// The memory operand at `offset` from register or symbol `base`.
+pub(crate) fn at(base: &str, offset: i32) -> String {
+    match offset {
+        0 => format!("[{}]", base),
+        offset if offset < 0 => format!("[{}-{}]", base, -offset),
+        offset => format!("[{}+{}]", base, offset),
+    }
+}
+
+/// The memory operand at `offset` from ebp.
+pub(crate) fn frame(offset: i32) -> String {
+    at("ebp", offset)
+}
+
+/// The low byte of eax, ecx, edx, or ebx.
+pub(crate) fn byte_reg(reg: &str) -> &'static str {
+    match reg {
+        "eax" => "al",
+        "ecx" => "cl",
+        "edx" => "dl",
+        "ebx" => "bl",
+        _ => unreachable!("{} has no byte register", reg),
+    }
+}
+
+/// The low 16 bits of a general-purpose register.
+pub(crate) fn word_reg(reg: &str) -> &'static str {
+    match reg {
+        "eax" => "ax",
+        "ecx" => "cx",
+        "edx" => "dx",
+        "ebx" => "bx",
+        "esi" => "si",
+        "edi" => "di",
+        _ => unreachable!("{} has no word register", reg),
+    }
+}
+
+/// The 32-bit register an inline asm clobber or register constraint names.
+pub(crate) fn reg_from_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "eax" | "ax" | "al" | "ah" | "a" => "eax",
+        "ebx" | "bx" | "bl" | "bh" | "b" => "ebx",
+        "ecx" | "cx" | "cl" | "ch" | "c" => "ecx",
+        "edx" | "dx" | "dl" | "dh" | "d" => "edx",
+        "esi" | "si" | "S" => "esi",
+        "edi" | "di" | "D" => "edi",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_operands() {
+        assert_eq!(frame(-12), "[ebp-12]");
+        assert_eq!(frame(8), "[ebp+8]");
+        assert_eq!(at("ecx", 0), "[ecx]");
+        assert_eq!(at("g", 4), "[g+4]");
+    }
+
+    #[test]
+    fn register_names() {
+        assert_eq!(byte_reg("edx"), "dl");
+        assert_eq!(word_reg("esi"), "si");
+        assert_eq!(reg_from_name("bl"), Some("ebx"));
+        assert_eq!(reg_from_name("S"), Some("esi"));
+        assert_eq!(reg_from_name("memory"), None);
+    }
+}
@@ -0,0 +1,948 @@
+// Arithmetic, conversions, memory access, and inline assembly
+use ir::{Operand, VarId};
+use model::{BinaryOp, Type, UnaryOp};
+use super::{at, byte_reg, frame, reg_from_name, word_reg, CALLEE_SAVED};
+use super::function::{
+    is_aggregate, is_float_type, is_immediate, is_unsigned, FunctionGenerator, Home, TEMP_CONTROL_WORD,
+    TEMP_OPERAND, TEMP_VALUE,
+};
+
+fn is_comparison(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::EqualEqual | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+            | BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::LogicalAnd | BinaryOp::LogicalOr
+    )
+}
+
+/// The `set` condition of an integer comparison.
+fn condition(op: &BinaryOp, unsigned: bool) -> &'static str {
+    match (op, unsigned) {
+        (BinaryOp::EqualEqual, _) => "e",
+        (BinaryOp::NotEqual, _) => "ne",
+        (BinaryOp::Less, false) => "l",
+        (BinaryOp::Less, true) => "b",
+        (BinaryOp::LessEqual, false) => "le",
+        (BinaryOp::LessEqual, true) => "be",
+        (BinaryOp::Greater, false) => "g",
+        (BinaryOp::Greater, true) => "a",
+        (BinaryOp::GreaterEqual, false) => "ge",
+        _ => "ae",
+    }
+}
+
+impl FunctionGenerator<'_> {
+    // ─── Integer arithmetic ──────────────────────────────────────
+    //
+    // 32-bit operations run in eax and the result is narrowed to the
+    // destination type; an operation with a 64-bit operand or result runs
+    // on 64-bit values in edx:eax. Comparisons and division are unsigned
+    // when either operand is, and a right shift when its left operand is.
+
+    pub(super) fn gen_binary(&mut self, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand) {
+        let dest_ty = self.var_type(dest);
+        let left_unsigned = is_unsigned(&self.operand_type(left));
+        let unsigned = left_unsigned || is_unsigned(&self.operand_type(right));
+        // The low half of these depends only on the low halves of the operands
+        let low_half = matches!(
+            op,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr
+                | BinaryOp::BitwiseXor | BinaryOp::ShiftLeft
+        );
+        let wide_operand = self.is_wide_operand(left) || self.is_wide_operand(right);
+        if self.is_wide(&dest_ty) || (wide_operand && !low_half) {
+            self.gen_wide_binary(dest, op, left, right, left_unsigned, unsigned);
+            return;
+        }
+        self.load_int("eax", left);
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor => {
+                let mnemonic = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::BitwiseAnd => "and",
+                    BinaryOp::BitwiseOr => "or",
+                    _ => "xor",
+                };
+                let r = self.int_source(right, "ecx");
+                self.emit(format!("{} eax, {}", mnemonic, r));
+            }
+            BinaryOp::Mul => {
+                let r = self.int_source(right, "ecx");
+                if is_immediate(&r) {
+                    self.emit(format!("imul eax, eax, {}", r));
+                } else {
+                    self.emit(format!("imul eax, {}", r));
+                }
+            }
+            BinaryOp::Div | BinaryOp::Mod => {
+                let r = self.int_rm(right, "ecx");
+                if unsigned {
+                    self.emit("xor edx, edx");
+                    self.emit(format!("div {}", r));
+                } else {
+                    self.emit("cdq");
+                    self.emit(format!("idiv {}", r));
+                }
+                if matches!(op, BinaryOp::Mod) {
+                    self.emit("mov eax, edx");
+                }
+            }
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                let mnemonic = match op {
+                    BinaryOp::ShiftLeft => "shl",
+                    _ if left_unsigned => "shr",
+                    _ => "sar",
+                };
+                if let Operand::Constant(c) = right {
+                    self.emit(format!("{} eax, {}", mnemonic, c & 31));
+                } else {
+                    self.load_int("ecx", right);
+                    self.emit(format!("{} eax, cl", mnemonic));
+                }
+            }
+            BinaryOp::LogicalAnd => {
+                self.emit("test eax, eax");
+                self.emit("setne dl");
+                let r = self.int_rm(right, "ecx");
+                self.emit(format!("cmp {}, 0", r));
+                self.emit("setne al");
+                self.emit("and al, dl");
+                self.emit("movzx eax, al");
+            }
+            BinaryOp::LogicalOr => {
+                let r = self.int_source(right, "ecx");
+                self.emit(format!("or eax, {}", r));
+                self.emit("setne al");
+                self.emit("movzx eax, al");
+            }
+            op if is_comparison(op) => {
+                let r = self.int_source(right, "ecx");
+                self.emit(format!("cmp eax, {}", r));
+                self.emit(format!("set{} al", condition(op, unsigned)));
+                self.emit("movzx eax, al");
+            }
+            // Compound assignments are lowered to the plain operators
+            _ => {}
+        }
+        if !is_comparison(op) {
+            self.narrow("eax", &dest_ty);
+        }
+        self.write_dest(dest, "eax");
+    }
+
+    /// A binary operation on 64-bit values: the left operand in edx:eax,
+    /// the right one in the scratch area. Multiplication takes three 32-bit
+    /// multiplies, and division calls the libgcc routines as GCC does.
+    fn gen_wide_binary(&mut self, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand, left_unsigned: bool, unsigned: bool) {
+        let dest_ty = self.var_type(dest);
+        let scratch = self.scratch();
+        let r = scratch + TEMP_OPERAND;
+        let (r_lo, r_hi) = (format!("DWORD PTR {}", frame(r)), format!("DWORD PTR {}", frame(r + 4)));
+        match op {
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                self.load_wide(left);
+                let kind = match op {
+                    BinaryOp::ShiftLeft => "shl",
+                    _ if left_unsigned => "shr",
+                    _ => "sar",
+                };
+                match right {
+                    Operand::Constant(c) => self.wide_shift_constant(kind, (c & 63) as u32),
+                    _ => {
+                        self.load_int("ecx", right);
+                        self.wide_shift_cl(kind);
+                    }
+                }
+            }
+            BinaryOp::Div | BinaryOp::Mod => {
+                self.outgoing_size = self.outgoing_size.max(16);
+                self.load_wide(left);
+                self.emit("mov DWORD PTR [esp], eax");
+                self.emit("mov DWORD PTR [esp+4], edx");
+                self.load_wide(right);
+                self.emit("mov DWORD PTR [esp+8], eax");
+                self.emit("mov DWORD PTR [esp+12], edx");
+                let routine = match (op, unsigned) {
+                    (BinaryOp::Div, false) => "__divdi3",
+                    (BinaryOp::Div, true) => "__udivdi3",
+                    (_, false) => "__moddi3",
+                    (_, true) => "__umoddi3",
+                };
+                self.emit(format!("call {}", routine));
+            }
+            BinaryOp::Mul => {
+                let l = scratch + TEMP_VALUE;
+                self.store_wide_temp(left, l);
+                self.store_wide_temp(right, r);
+                // The cross products only reach the high half
+                self.emit(format!("mov ecx, DWORD PTR {}", frame(l + 4)));
+                self.emit(format!("imul ecx, {}", r_lo));
+                self.emit(format!("mov eax, {}", r_hi));
+                self.emit(format!("imul eax, DWORD PTR {}", frame(l)));
+                self.emit("add ecx, eax");
+                self.emit(format!("mov eax, DWORD PTR {}", frame(l)));
+                self.emit(format!("mul {}", r_lo));
+                self.emit("add edx, ecx");
+            }
+            // l > r is r < l, and l <= r is !(r < l)
+            BinaryOp::Greater | BinaryOp::LessEqual => {
+                self.store_wide_temp(left, r);
+                self.load_wide(right);
+                self.emit(format!("cmp eax, {}", r_lo));
+                self.emit(format!("sbb edx, {}", r_hi));
+                let less = if matches!(op, BinaryOp::Greater) { "l" } else { "ge" };
+                self.set_flag(less, unsigned);
+            }
+            _ => {
+                self.store_wide_temp(right, r);
+                self.load_wide(left);
+                match op {
+                    BinaryOp::Add => {
+                        self.emit(format!("add eax, {}", r_lo));
+                        self.emit(format!("adc edx, {}", r_hi));
+                    }
+                    BinaryOp::Sub => {
+                        self.emit(format!("sub eax, {}", r_lo));
+                        self.emit(format!("sbb edx, {}", r_hi));
+                    }
+                    BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor => {
+                        let mnemonic = match op {
+                            BinaryOp::BitwiseAnd => "and",
+                            BinaryOp::BitwiseOr => "or",
+                            _ => "xor",
+                        };
+                        self.emit(format!("{} eax, {}", mnemonic, r_lo));
+                        self.emit(format!("{} edx, {}", mnemonic, r_hi));
+                    }
+                    BinaryOp::EqualEqual | BinaryOp::NotEqual => {
+                        self.emit(format!("xor eax, {}", r_lo));
+                        self.emit(format!("xor edx, {}", r_hi));
+                        self.emit("or eax, edx");
+                        self.emit(format!("set{} al", condition(op, false)));
+                        self.emit("movzx eax, al");
+                    }
+                    BinaryOp::Less | BinaryOp::GreaterEqual => {
+                        self.emit(format!("cmp eax, {}", r_lo));
+                        self.emit(format!("sbb edx, {}", r_hi));
+                        let less = if matches!(op, BinaryOp::Less) { "l" } else { "ge" };
+                        self.set_flag(less, unsigned);
+                    }
+                    BinaryOp::LogicalAnd => {
+                        self.emit("or eax, edx");
+                        self.emit("setne cl");
+                        self.emit(format!("mov eax, {}", r_lo));
+                        self.emit(format!("or eax, {}", r_hi));
+                        self.emit("setne al");
+                        self.emit("and al, cl");
+                        self.emit("movzx eax, al");
+                    }
+                    BinaryOp::LogicalOr => {
+                        self.emit("or eax, edx");
+                        self.emit(format!("or eax, {}", r_lo));
+                        self.emit(format!("or eax, {}", r_hi));
+                        self.emit("setne al");
+                        self.emit("movzx eax, al");
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if is_comparison(op) {
+            self.write_dest(dest, "eax");
+        } else if self.is_wide(&dest_ty) {
+            self.write_wide(dest, "eax", "edx");
+        } else {
+            self.narrow("eax", &dest_ty);
+            self.write_dest(dest, "eax");
+        }
+    }
+
+    /// eax = the flag of signed condition `cond` ("l" or "ge"), or its
+    /// unsigned counterpart if `unsigned`, after a 64-bit `cmp`/`sbb`.
+    fn set_flag(&mut self, cond: &str, unsigned: bool) {
+        let cond = match (cond, unsigned) {
+            ("l", true) => "b",
+            ("ge", true) => "ae",
+            (cond, _) => cond,
+        };
+        self.emit(format!("set{} al", cond));
+        self.emit("movzx eax, al");
+    }
+
+    /// Shift edx:eax by `count` (below 64): `kind` is shl, shr, or sar.
+    fn wide_shift_constant(&mut self, kind: &str, count: u32) {
+        match (kind, count) {
+            (_, 0) => {}
+            ("shl", 1..=31) => {
+                self.emit(format!("shld edx, eax, {}", count));
+                self.emit(format!("shl eax, {}", count));
+            }
+            ("shl", _) => {
+                self.emit("mov edx, eax");
+                self.emit(format!("shl edx, {}", count - 32));
+                self.emit("xor eax, eax");
+            }
+            (_, 1..=31) => {
+                self.emit(format!("shrd eax, edx, {}", count));
+                self.emit(format!("{} edx, {}", kind, count));
+            }
+            _ => {
+                self.emit("mov eax, edx");
+                self.emit(format!("{} eax, {}", kind, count - 32));
+                if kind == "sar" {
+                    self.emit("sar edx, 31");
+                } else {
+                    self.emit("xor edx, edx");
+                }
+            }
+        }
+    }
+
+    /// Shift edx:eax by cl: the double shifts take the count mod 32, so
+    /// counts of 32 and over move the halves across.
+    fn wide_shift_cl(&mut self, kind: &str) {
+        let done = self.new_label();
+        if kind == "shl" {
+            self.emit("shld edx, eax, cl");
+            self.emit("shl eax, cl");
+            self.emit("test cl, 32");
+            self.emit(format!("je {}", done));
+            self.emit("mov edx, eax");
+            self.emit("xor eax, eax");
+        } else {
+            self.emit("shrd eax, edx, cl");
+            self.emit(format!("{} edx, cl", kind));
+            self.emit("test cl, 32");
+            self.emit(format!("je {}", done));
+            self.emit("mov eax, edx");
+            if kind == "sar" {
+                self.emit("sar edx, 31");
+            } else {
+                self.emit("xor edx, edx");
+            }
+        }
+        self.label(&done);
+    }
+
+    pub(super) fn gen_unary(&mut self, dest: VarId, op: &UnaryOp, src: &Operand) {
+        let dest_ty = self.var_type(dest);
+        let logical_not = matches!(op, UnaryOp::LogicalNot);
+        if self.is_wide(&dest_ty) || self.is_wide_operand(src) {
+            self.load_wide(src);
+            match op {
+                UnaryOp::Minus => {
+                    self.emit("neg eax");
+                    self.emit("adc edx, 0");
+                    self.emit("neg edx");
+                }
+                UnaryOp::BitwiseNot => {
+                    self.emit("not eax");
+                    self.emit("not edx");
+                }
+                UnaryOp::LogicalNot => {
+                    self.emit("or eax, edx");
+                    self.emit("sete al");
+                    self.emit("movzx eax, al");
+                }
+                _ => {}
+            }
+            if !logical_not && self.is_wide(&dest_ty) {
+                self.write_wide(dest, "eax", "edx");
+                return;
+            }
+        } else {
+            self.load_int("eax", src);
+            match op {
+                UnaryOp::Minus => self.emit("neg eax"),
+                UnaryOp::BitwiseNot => self.emit("not eax"),
+                UnaryOp::LogicalNot => {
+                    self.emit("test eax, eax");
+                    self.emit("sete al");
+                    self.emit("movzx eax, al");
+                }
+                _ => {}
+            }
+        }
+        if !logical_not {
+            self.narrow("eax", &dest_ty);
+        }
+        self.write_dest(dest, "eax");
+    }
+
+    // ─── Floating point ──────────────────────────────────────────
+
+    pub(super) fn gen_float_binary(&mut self, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand) {
+        let is_float_var = |op: &Operand| matches!(op, Operand::Var(v) if self.var_type(*v) == Type::Float);
+        let arithmetic = matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div);
+        // Arithmetic rounds to the destination's precision; comparisons are
+        // exact in double unless both operands are `float`
+        let double = if arithmetic {
+            self.var_type(dest) != Type::Float
+        } else {
+            !(is_float_var(left) && is_float_var(right))
+        };
+        let p = if double { "d" } else { "s" };
+        self.load_float("xmm0", left, double);
+        self.load_float("xmm1", right, double);
+        if arithmetic {
+            let mnemonic = match op {
+                BinaryOp::Add => "add",
+                BinaryOp::Sub => "sub",
+                BinaryOp::Mul => "mul",
+                _ => "div",
+            };
+            self.emit(format!("{}s{} xmm0, xmm1", mnemonic, p));
+            self.write_float(dest, "xmm0");
+            return;
+        }
+        // ucomis sets CF for below and ZF for equal, and all of ZF, PF, and
+        // CF when unordered; "above" conditions are false for NaN
+        match op {
+            BinaryOp::EqualEqual => {
+                self.emit(format!("ucomis{} xmm0, xmm1", p));
+                self.emit("sete al");
+                self.emit("setnp cl");
+                self.emit("and al, cl");
+            }
+            BinaryOp::NotEqual => {
+                self.emit(format!("ucomis{} xmm0, xmm1", p));
+                self.emit("setne al");
+                self.emit("setp cl");
+                self.emit("or al, cl");
+            }
+            BinaryOp::Less | BinaryOp::LessEqual => {
+                self.emit(format!("ucomis{} xmm1, xmm0", p));
+                self.emit(if matches!(op, BinaryOp::Less) { "seta al" } else { "setae al" });
+            }
+            BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                self.emit(format!("ucomis{} xmm0, xmm1", p));
+                self.emit(if matches!(op, BinaryOp::Greater) { "seta al" } else { "setae al" });
+            }
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+                self.float_truth("xmm0", double, "al", "cl");
+                self.float_truth("xmm1", double, "dl", "cl");
+                let combine = if matches!(op, BinaryOp::LogicalAnd) { "and" } else { "or" };
+                self.emit(format!("{} al, dl", combine));
+            }
+            _ => {}
+        }
+        self.emit("movzx eax, al");
+        self.write_dest(dest, "eax");
+    }
+
+    pub(super) fn gen_float_unary(&mut self, dest: VarId, op: &UnaryOp, src: &Operand) {
+        if let UnaryOp::LogicalNot = op {
+            let double = match src {
+                Operand::Var(v) => self.var_type(*v) != Type::Float,
+                _ => true,
+            };
+            self.load_float("xmm0", src, double);
+            self.float_truth("xmm0", double, "al", "cl");
+            self.emit("xor al, 1");
+            self.emit("movzx eax, al");
+            self.write_dest(dest, "eax");
+            return;
+        }
+        let double = self.var_type(dest) != Type::Float;
+        self.load_float("xmm0", src, double);
+        self.write_float(dest, "xmm0");
+        if let UnaryOp::Minus = op {
+            // Flip the sign bit, in the top byte of the value
+            let sign = self.slot(dest) + if double { 7 } else { 3 };
+            self.emit(format!("xor BYTE PTR {}, 0x80", frame(sign)));
+        }
+    }
+
+    // ─── Copies and conversions ──────────────────────────────────
+
+    pub(super) fn gen_copy(&mut self, dest: VarId, src: &Operand) {
+        let ty = self.var_type(dest);
+        if is_float_type(&ty) {
+            self.load_float("xmm0", src, ty == Type::Double);
+            self.write_float(dest, "xmm0");
+        } else if self.is_wide(&ty) {
+            self.load_wide(src);
+            self.write_wide(dest, "eax", "edx");
+        } else {
+            let value = self.int_source(src, "eax");
+            self.write_dest(dest, &value);
+        }
+    }
+
+    pub(super) fn gen_cast(&mut self, dest: VarId, src: &Operand, src_type: &Type, to: &Type) {
+        let from_float = is_float_type(src_type) || matches!(src, Operand::FloatConstant(_));
+        match (from_float, is_float_type(to)) {
+            (false, false) => {
+                if self.is_wide(to) {
+                    self.load_wide(src);
+                    self.write_wide(dest, "eax", "edx");
+                } else {
+                    self.load_int("eax", src);
+                    self.narrow("eax", to);
+                    self.write_dest(dest, "eax");
+                }
+            }
+            (false, true) => {
+                self.int_to_float("xmm0", src, src_type, *to == Type::Double);
+                self.write_float(dest, "xmm0");
+            }
+            (true, false) => {
+                let double = *src_type != Type::Float;
+                self.load_float("xmm0", src, double);
+                self.float_to_int(double, to);
+                if self.is_wide(to) {
+                    self.write_wide(dest, "eax", "edx");
+                } else {
+                    self.write_dest(dest, "eax");
+                }
+            }
+            (true, true) => {
+                self.load_float("xmm0", src, *to == Type::Double);
+                self.write_float(dest, "xmm0");
+            }
+        }
+    }
+
+    /// eax (edx:eax for 64-bit types) = xmm0 converted to integer type `to`,
+    /// truncating. Conversions SSE2 lacks go through the x87 unit, or
+    /// libgcc for unsigned 64-bit results.
+    pub(super) fn float_to_int(&mut self, double: bool, to: &Type) {
+        if *to == Type::Bool {
+            self.float_truth("xmm0", double, "al", "cl");
+            self.emit("movzx eax, al");
+            return;
+        }
+        let p = if double { "sd" } else { "ss" };
+        let wide = self.is_wide(to);
+        let via_x87 = wide || (is_unsigned(to) && self.int_size(to) == 4);
+        if !via_x87 {
+            self.emit(format!("cvtt{}2si eax, xmm0", p));
+            self.narrow("eax", to);
+            return;
+        }
+        if wide && is_unsigned(to) {
+            if !double {
+                self.emit("cvtss2sd xmm0, xmm0");
+            }
+            self.outgoing_size = self.outgoing_size.max(8);
+            self.emit("movsd QWORD PTR [esp], xmm0");
+            self.emit("call __fixunsdfdi");
+            return;
+        }
+        let scratch = self.scratch();
+        let (value, control) = (scratch + TEMP_VALUE, scratch + TEMP_CONTROL_WORD);
+        let size = if double { "QWORD" } else { "DWORD" };
+        self.emit(format!("movs{} {} PTR {}, xmm0", &p[1..], size, frame(value)));
+        self.emit(format!("fld {} PTR {}", size, frame(value)));
+        // fistp rounds by the control word: switch it to truncation
+        self.emit(format!("fnstcw WORD PTR {}", frame(control)));
+        self.emit(format!("movzx eax, WORD PTR {}", frame(control)));
+        self.emit("or eax, 0xc00");
+        self.emit(format!("mov WORD PTR {}, ax", frame(control + 2)));
+        self.emit(format!("fldcw WORD PTR {}", frame(control + 2)));
+        self.emit(format!("fistp QWORD PTR {}", frame(value)));
+        self.emit(format!("fldcw WORD PTR {}", frame(control)));
+        self.emit(format!("mov eax, DWORD PTR {}", frame(value)));
+        if wide {
+            self.emit(format!("mov edx, DWORD PTR {}", frame(value + 4)));
+        }
+    }
+
+    // ─── Memory ──────────────────────────────────────────────────
+
+    /// The base of the object at `addr` and the offset from it: an `alloca`
+    /// object, a global, or a pointer value (loaded into ecx unless it is
+    /// in a register).
+    pub(super) fn address(&mut self, addr: &Operand) -> (String, i32) {
+        match addr {
+            Operand::Var(var) if self.objects.contains_key(var) => ("ebp".to_string(), self.objects[var]),
+            Operand::Global(name) => (name.clone(), 0),
+            _ => {
+                let base = self.int_source(addr, "ecx");
+                if base.contains("PTR") || is_immediate(&base) {
+                    self.emit(format!("mov ecx, {}", base));
+                    return ("ecx".to_string(), 0);
+                }
+                (base, 0)
+            }
+        }
+    }
+
+    pub(super) fn gen_load(&mut self, dest: VarId, addr: &Operand, ty: &Type) {
+        if is_aggregate(ty) {
+            // The value is a copy of the object, in a frame temporary
+            let buffer = self.alloc_aggregate(ty);
+            self.load_int("ecx", addr);
+            self.emit(format!("lea edx, {}", frame(buffer)));
+            self.copy_memory(self.size_of(ty));
+            self.emit(format!("lea eax, {}", frame(buffer)));
+            self.write_dest(dest, "eax");
+            return;
+        }
+        let (base, offset) = self.address(addr);
+        let mem = at(&base, offset);
+        if is_float_type(ty) {
+            if *ty == Type::Double {
+                self.emit(format!("movsd xmm0, QWORD PTR {}", mem));
+            } else {
+                self.emit(format!("movss xmm0, DWORD PTR {}", mem));
+            }
+            match (*ty == Type::Double, self.var_type(dest)) {
+                (true, Type::Float) => self.emit("cvtsd2ss xmm0, xmm0"),
+                (false, Type::Double) => self.emit("cvtss2sd xmm0, xmm0"),
+                _ => {}
+            }
+            self.write_float(dest, "xmm0");
+        } else if self.is_wide(ty) {
+            self.emit(format!("mov eax, DWORD PTR {}", mem));
+            self.emit(format!("mov edx, DWORD PTR {}", at(&base, offset + 4)));
+            self.write_wide(dest, "eax", "edx");
+        } else {
+            let reg = match self.home(dest) {
+                Home::Reg(reg) => reg,
+                Home::Slot(_) => "eax",
+            };
+            let extend = if is_unsigned(ty) { "movzx" } else { "movsx" };
+            match self.int_size(ty) {
+                1 => self.emit(format!("{} {}, BYTE PTR {}", extend, reg, mem)),
+                2 => self.emit(format!("{} {}, WORD PTR {}", extend, reg, mem)),
+                _ => self.emit(format!("mov {}, DWORD PTR {}", reg, mem)),
+            }
+            self.write_dest(dest, reg);
+        }
+    }
+
+    pub(super) fn gen_store(&mut self, addr: &Operand, src: &Operand, ty: &Type) {
+        if is_aggregate(ty) {
+            self.load_int("ecx", src);
+            self.load_int("edx", addr);
+            self.copy_memory(self.size_of(ty));
+            return;
+        }
+        if is_float_type(ty) {
+            let double = *ty == Type::Double;
+            self.load_float("xmm0", src, double);
+            let (base, offset) = self.address(addr);
+            if double {
+                self.emit(format!("movsd QWORD PTR {}, xmm0", at(&base, offset)));
+            } else {
+                self.emit(format!("movss DWORD PTR {}, xmm0", at(&base, offset)));
+            }
+            return;
+        }
+        if self.is_wide(ty) {
+            let (mut lo, mut hi) = self.wide_source(src, "eax", "edx");
+            if lo.contains("PTR") {
+                self.emit(format!("mov eax, {}", lo));
+                lo = "eax".to_string();
+            }
+            if hi.contains("PTR") {
+                self.emit(format!("mov edx, {}", hi));
+                hi = "edx".to_string();
+            }
+            let (base, offset) = self.address(addr);
+            self.emit(format!("mov DWORD PTR {}, {}", at(&base, offset), lo));
+            self.emit(format!("mov DWORD PTR {}, {}", at(&base, offset + 4), hi));
+            return;
+        }
+        let size = self.int_size(ty);
+        let value = match src {
+            Operand::Constant(c) if *ty == Type::Bool => i64::from(*c != 0).to_string(),
+            Operand::Constant(c) => match size {
+                1 => (*c as i8).to_string(),
+                2 => (*c as i16).to_string(),
+                _ => (*c as i32).to_string(),
+            },
+            _ => {
+                self.load_int("eax", src);
+                if *ty == Type::Bool {
+                    self.narrow("eax", ty);
+                }
+                match size {
+                    1 => "al".to_string(),
+                    2 => "ax".to_string(),
+                    _ => "eax".to_string(),
+                }
+            }
+        };
+        let (base, offset) = self.address(addr);
+        let width = match size {
+            1 => "BYTE",
+            2 => "WORD",
+            _ => "DWORD",
+        };
+        self.emit(format!("mov {} PTR {}, {}", width, at(&base, offset), value));
+    }
+
+    pub(super) fn gen_gep(&mut self, dest: VarId, base: &Operand, index: &Operand, element_type: &Type) {
+        let size = self.size_of(element_type) as i64;
+        self.load_int("eax", base);
+        if let Operand::Constant(c) = index {
+            let offset = c.wrapping_mul(size) as i32;
+            if offset != 0 {
+                self.emit(format!("add eax, {}", offset));
+            }
+        } else {
+            self.load_int("ecx", index);
+            match size {
+                1 | 2 | 4 | 8 => self.emit(format!("lea eax, [eax+ecx*{}]", size)),
+                size => {
+                    self.emit(format!("imul ecx, ecx, {}", size));
+                    self.emit("add eax, ecx");
+                }
+            }
+        }
+        self.write_dest(dest, "eax");
+    }
+
+    /// Copy `size` bytes from the address in ecx to the address in edx, in
+    /// 4/2/1-byte pieces, or with `rep movsb` for large copies. Clobbers
+    /// eax, ecx, and edx.
+    pub(super) fn copy_memory(&mut self, size: usize) {
+        if size > 64 {
+            self.emit("push esi");
+            self.emit("push edi");
+            self.emit("mov esi, ecx");
+            self.emit("mov edi, edx");
+            self.emit(format!("mov ecx, {}", size));
+            self.emit("rep movsb");
+            self.emit("pop edi");
+            self.emit("pop esi");
+            return;
+        }
+        let mut pos = 0;
+        for chunk in memory_chunks(size) {
+            let (width, reg) = chunk_width(chunk);
+            self.emit(format!("mov {}, {} PTR {}", reg, width, at("ecx", pos as i32)));
+            self.emit(format!("mov {} PTR {}, {}", width, at("edx", pos as i32), reg));
+            pos += chunk;
+        }
+    }
+
+    pub(super) fn gen_memset(&mut self, dest: &Operand, value: &Operand, size: usize) {
+        self.load_int("edx", dest);
+        // The byte repeated across a dword
+        match value {
+            Operand::Constant(c) => {
+                let pattern = u32::from(*c as u8).wrapping_mul(0x0101_0101);
+                self.emit(format!("mov eax, {}", pattern as i32));
+            }
+            _ => {
+                self.load_int("eax", value);
+                self.emit("movzx eax, al");
+                self.emit("imul eax, eax, 0x01010101");
+            }
+        }
+        if size > 64 {
+            self.emit("push edi");
+            self.emit("mov edi, edx");
+            self.emit(format!("mov ecx, {}", size));
+            self.emit("rep stosb");
+            self.emit("pop edi");
+            return;
+        }
+        let mut pos = 0;
+        for chunk in memory_chunks(size) {
+            let (width, reg) = chunk_width(chunk);
+            self.emit(format!("mov {} PTR {}, {}", width, at("edx", pos as i32), reg));
+            pos += chunk;
+        }
+    }
+
+    // ─── Inline assembly ─────────────────────────────────────────
+
+    /// Substitute the operands into `template`: `%N` names output N, or
+    /// input N minus the number of outputs, `%bN`, `%wN`, and `%kN` its 8-,
+    /// 16-, and 32-bit register, and `%%` is a percent sign. Register
+    /// operands ("r", "q", "+r", matching digits) are staged in free
+    /// registers, and 'a', 'b', 'c', 'd', 'S', and 'D' in the ones they
+    /// name; "m" operands in a stack slot name it, and "i"/"n" constants
+    /// appear as numbers. Functions with inline assembly keep every
+    /// variable in memory, so any register is free to stage in.
+    pub(super) fn gen_inline_asm(
+        &mut self,
+        template: &str,
+        outputs: &[VarId],
+        inputs: &[Operand],
+        output_constraints: &[String],
+        input_constraints: &[String],
+        clobbers: &[String],
+    ) {
+        let constraints = || output_constraints.iter().chain(input_constraints);
+        // Registers named by a constraint or clobbered are not staged in
+        let named = |constraint: &str| {
+            constraint.chars().find_map(|c| match c {
+                'a' | 'b' | 'c' | 'd' | 'S' | 'D' => reg_from_name(&c.to_string()),
+                _ => None,
+            })
+        };
+        let reserved: Vec<&'static str> = constraints().filter_map(|c| named(c))
+            .chain(clobbers.iter().filter_map(|name| reg_from_name(name)))
+            .collect();
+        let mut free = ["eax", "ecx", "edx", "ebx", "esi", "edi"].into_iter().filter(|r| !reserved.contains(r));
+        let mut stage = |constraint: &str| -> &'static str {
+            named(constraint).or_else(|| free.next()).expect("a register for every inline asm operand")
+        };
+
+        let mut rendered: Vec<String> = Vec::new();
+        let mut written: Vec<(VarId, &'static str)> = Vec::new();
+        // Outputs are the `alloca` objects of the lvalues written
+        for (i, var) in outputs.iter().enumerate() {
+            let constraint = output_constraints.get(i).map_or("", String::as_str);
+            if constraint.contains('m') && let Some(&offset) = self.objects.get(var) {
+                let width = self.object_width(*var);
+                rendered.push(format!("{} PTR {}", width, frame(offset)));
+                continue;
+            }
+            let reg = stage(constraint);
+            if constraint.contains('+') {
+                self.load_output(*var, reg);
+            }
+            rendered.push(reg.to_string());
+            written.push((*var, reg));
+        }
+        for (i, input) in inputs.iter().enumerate() {
+            let constraint = input_constraints.get(i).map_or("", String::as_str);
+            if let Ok(n) = constraint.parse::<usize>()
+                && let Some(&(_, reg)) = written.iter().find(|(v, _)| Some(v) == outputs.get(n))
+            {
+                self.load_int(reg, input);
+                rendered.push(reg.to_string());
+                continue;
+            }
+            if (constraint.contains('i') || constraint.contains('n')) && let Operand::Constant(c) = input {
+                rendered.push(c.to_string());
+                continue;
+            }
+            if constraint.contains('m') && let Operand::Var(var) = input {
+                if let Some(&offset) = self.objects.get(var) {
+                    let width = self.object_width(*var);
+                    rendered.push(format!("{} PTR {}", width, frame(offset)));
+                    continue;
+                }
+                if let Home::Slot(offset) = self.home(*var) {
+                    rendered.push(format!("DWORD PTR {}", frame(offset)));
+                    continue;
+                }
+            }
+            let reg = stage(constraint);
+            self.load_int(reg, input);
+            rendered.push(reg.to_string());
+        }
+        // The callee-saved registers staged in or clobbered are saved in the
+        // prologue; nothing lives in the others across instructions
+        let used = rendered.iter().map(String::as_str).chain(reserved.iter().copied());
+        for reg in CALLEE_SAVED.iter().filter(|reg| used.clone().any(|r| r == **reg)) {
+            self.asm_regs.insert(reg);
+        }
+
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                text.push('%');
+                continue;
+            }
+            let modifier = chars.peek().copied().filter(|m| matches!(m, 'b' | 'w' | 'k'));
+            if modifier.is_some() {
+                chars.next();
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+            match digits.parse::<usize>().ok().and_then(|n| rendered.get(n)) {
+                Some(operand) => {
+                    let is_reg = reg_from_name(operand).is_some();
+                    match modifier {
+                        Some('b') if is_reg => text.push_str(byte_reg(operand)),
+                        Some('w') if is_reg => text.push_str(word_reg(operand)),
+                        _ => text.push_str(operand),
+                    }
+                }
+                None => {
+                    text.push('%');
+                    text.extend(modifier);
+                    text.push_str(&digits);
+                }
+            }
+        }
+        for line in text.lines() {
+            let line = line.trim();
+            if !line.is_empty() {
+                self.emit(line);
+            }
+        }
+
+        for (var, reg) in written {
+            self.store_output(var, reg);
+        }
+    }
+
+    /// The operand size keyword of `alloca` object `var`.
+    fn object_width(&self, var: VarId) -> &'static str {
+        match self.object_types.get(&var).map(|ty| self.int_size(ty)) {
+            Some(1) => "BYTE",
+            Some(2) => "WORD",
+            Some(8) => "QWORD",
+            _ => "DWORD",
+        }
+    }
+
+    /// `reg` = the current value of inline asm output `var`.
+    fn load_output(&mut self, var: VarId, reg: &str) {
+        let Some(&offset) = self.objects.get(&var) else {
+            self.load_int(reg, &Operand::Var(var));
+            return;
+        };
+        let ty = self.object_types[&var].clone();
+        let extend = if is_unsigned(&ty) { "movzx" } else { "movsx" };
+        match self.int_size(&ty) {
+            1 => self.emit(format!("{} {}, BYTE PTR {}", extend, reg, frame(offset))),
+            2 => self.emit(format!("{} {}, WORD PTR {}", extend, reg, frame(offset))),
+            _ => self.emit(format!("mov {}, DWORD PTR {}", reg, frame(offset))),
+        }
+    }
+
+    /// Write `reg`, the value inline asm left for output `var`, to `var`.
+    fn store_output(&mut self, var: VarId, reg: &str) {
+        let Some(&offset) = self.objects.get(&var) else {
+            self.write_dest(var, reg);
+            return;
+        };
+        let ty = self.object_types[&var].clone();
+        match self.int_size(&ty) {
+            1 => self.emit(format!("mov BYTE PTR {}, {}", frame(offset), byte_reg(reg))),
+            2 => self.emit(format!("mov WORD PTR {}, {}", frame(offset), word_reg(reg))),
+            _ => self.emit(format!("mov DWORD PTR {}, {}", frame(offset), reg)),
+        }
+    }
+}
+
+/// Piece sizes for copying `size` bytes: 4-byte pieces, then 2 and 1.
+fn memory_chunks(mut size: usize) -> Vec<usize> {
+    let mut chunks = Vec::new();
+    for chunk in [4, 2, 1] {
+        while size >= chunk {
+            chunks.push(chunk);
+            size -= chunk;
+        }
+    }
+    chunks
+}
+
+/// The operand size keyword of a copy piece and the part of eax it moves through.
+fn chunk_width(size: usize) -> (&'static str, &'static str) {
+    match size {
+        1 => ("BYTE", "al"),
+        2 => ("WORD", "ax"),
+        _ => ("DWORD", "eax"),
+    }
+}
@@ -66,6 +66,8 @@ fn render_operand(op: &X86Operand, constraint: &str, is_output: bool) -> String
         }
         X86Operand::Label(name) | X86Operand::RipRelLabel(name) => format!("[rip+{}]", name),
         X86Operand::GlobalMem(name) | X86Operand::GlobalQwordMem(name) => format!("QWORD PTR [rip+{}]", name),
+        X86Operand::GlobalWordMem(name) => format!("WORD PTR [rip+{}]", name),
+        X86Operand::GlobalByteMem(name) => format!("BYTE PTR [rip+{}]", name),
         X86Operand::WordMem(reg, offset) => format_mem_operand(reg, *offset, false),
         X86Operand::ByteMem(reg, offset) => format_mem_operand(reg, *offset, false),
         X86Operand::XmmwordMem(reg, offset) | X86Operand::YmmwordMem(reg, offset) => format_mem_operand(reg, *offset, false),
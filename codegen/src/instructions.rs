@@ -60,8 +60,8 @@ fn emit_div_by_const_64(
     if d > 0 && (d as u64).is_power_of_two() {
         let shift = d.trailing_zeros();
         if shift == 0 {
-            // Division by 1: just move
-            asm.push(X86Instr::Mov(d_op, l_op));
+            // Division by 1: the quotient is x and the remainder 0
+            asm.push(X86Instr::Mov(d_op, if want_remainder { X86Operand::Imm(0) } else { l_op }));
             return true;
         }
         // Signed division by power of 2:
@@ -145,6 +145,86 @@ fn emit_div_by_const_64(
     false
 }
 
+/// Magic number for unsigned 64-bit division by a d of at most 2^63 that
+/// is not a power of two (Granlund and Montgomery). Returns (magic, shift,
+/// add): when `add` is false, x / d = MULHI(x, magic) >> shift; otherwise
+/// the true multiplier is 2^64 + magic and, with t = MULHI(x, magic),
+/// x / d = (t + ((x - t) >> 1)) >> (shift - 1).
+fn unsigned_div_magic_64(d: u64) -> (u64, u32, bool) {
+    let ceil_log2 = 64 - (d - 1).leading_zeros();
+    for shift in 0..ceil_log2 {
+        let power = 1u128 << (64 + shift);
+        let magic = power.div_ceil(u128::from(d));
+        if magic < 1u128 << 64 && magic * u128::from(d) - power <= 1u128 << shift {
+            return (magic as u64, shift, false);
+        }
+    }
+    let magic = (1u128 << (64 + ceil_log2)) / u128::from(d) + 1;
+    // Drop the 2^64 bit; the fix-up adds x back in
+    (magic as u64, ceil_log2, true)
+}
+
+/// Emit optimized unsigned division by constant for 64-bit values.
+/// Returns true if optimization was applied.
+fn emit_udiv_by_const_64(
+    asm: &mut Vec<X86Instr>,
+    l_op: X86Operand,
+    d: i64,
+    d_op: X86Operand,
+    want_remainder: bool,
+) -> bool {
+    // A divisor of 2^63 or more (negative here) leaves a quotient of 0 or 1
+    if d <= 0 { return false; }
+    let rax = X86Operand::Reg(X86Reg::Rax);
+    let rdx = X86Operand::Reg(X86Reg::Rdx);
+    let r11 = X86Operand::Reg(X86Reg::R11);
+
+    // Power of 2: shift, or mask for the remainder
+    if (d as u64).is_power_of_two() {
+        asm.push(X86Instr::Mov(rax.clone(), l_op));
+        if want_remainder {
+            asm.push(X86Instr::Mov(rdx.clone(), X86Operand::Imm(d - 1)));
+            asm.push(X86Instr::And(rax.clone(), rdx));
+        } else if d > 1 {
+            asm.push(X86Instr::Shr(rax.clone(), X86Operand::Imm(i64::from(d.trailing_zeros()))));
+        }
+        asm.push(X86Instr::Mov(d_op, rax));
+        return true;
+    }
+
+    let (magic, shift, add) = unsigned_div_magic_64(d as u64);
+    // mul r11: rdx = high 64 bits of dividend * magic
+    asm.push(X86Instr::Mov(r11.clone(), l_op));
+    asm.push(X86Instr::Raw(format!("mov rax, {}", magic as i64)));
+    asm.push(X86Instr::Raw("mul r11".to_string()));
+    if add {
+        asm.push(X86Instr::Mov(rax.clone(), r11.clone()));
+        asm.push(X86Instr::Sub(rax.clone(), rdx.clone()));
+        asm.push(X86Instr::Shr(rax.clone(), X86Operand::Imm(1)));
+        asm.push(X86Instr::Add(rax.clone(), rdx.clone()));
+        if shift > 1 {
+            asm.push(X86Instr::Shr(rax.clone(), X86Operand::Imm(i64::from(shift) - 1)));
+        }
+    } else {
+        asm.push(X86Instr::Mov(rax.clone(), rdx.clone()));
+        if shift > 0 {
+            asm.push(X86Instr::Shr(rax.clone(), X86Operand::Imm(i64::from(shift))));
+        }
+    }
+    // rax = quotient
+
+    if want_remainder {
+        // remainder = dividend - quotient * d
+        asm.push(X86Instr::Mov(rdx.clone(), X86Operand::Imm(d)));
+        asm.push(X86Instr::Imul(rax.clone(), rdx));
+        asm.push(X86Instr::Sub(r11.clone(), rax));
+        asm.push(X86Instr::Mov(d_op, r11));
+    } else {
+        asm.push(X86Instr::Mov(d_op, rax));
+    }
+    true
+}
+
 /// Emit optimized multiply by constant using LEA/shift sequences.
 /// Returns true if optimization was applied.
 fn emit_mul_by_const(asm: &mut Vec<X86Instr>, src: &X86Reg, dest: &X86Reg, c: i64) -> bool {
@@ -351,14 +431,21 @@ impl InstructionGenerator {
             }
             BinaryOp::Div => {
                 // Try strength reduction for constant divisor
-                if let X86Operand::Imm(d) = &r_op {
-                    if !op_is_32bit && emit_div_by_const_64(asm, l_op.clone(), *d, d_op.clone(), false) {
-                        return;
+                if let X86Operand::Imm(d) = &r_op
+                    && !op_is_32bit
+                    && if is_signed {
+                        emit_div_by_const_64(asm, l_op.clone(), *d, d_op.clone(), false)
+                    } else {
+                        emit_udiv_by_const_64(asm, l_op.clone(), *d, d_op.clone(), false)
                     }
+                {
+                    return;
                 }
-                // Fallback to idiv
+                // Fallback to idiv, or div of the zero-extended dividend
                 asm.push(X86Instr::Mov(ax_op.clone(), l_op));
-                if op_is_32bit { asm.push(X86Instr::Cdq); } else { asm.push(X86Instr::Cqto); }
+                if !is_signed {
+                    asm.push(X86Instr::Xor(X86Operand::Reg(dx.clone()), X86Operand::Reg(dx.clone())));
+                } else if op_is_32bit { asm.push(X86Instr::Cdq); } else { asm.push(X86Instr::Cqto); }
                 
                 let div_op = if let X86Operand::Imm(_) = r_op {
                     asm.push(X86Instr::Mov(X86Operand::Reg(cx.clone()), r_op));
@@ -366,19 +453,26 @@ impl InstructionGenerator {
                 } else {
                     r_op
                 };
-                asm.push(X86Instr::Idiv(div_op));
+                asm.push(if is_signed { X86Instr::Idiv(div_op) } else { X86Instr::Div(div_op) });
                 asm.push(X86Instr::Mov(d_op, ax_op));
             }
             BinaryOp::Mod => {
                 // Try strength reduction for constant divisor
-                if let X86Operand::Imm(d) = &r_op {
-                    if !op_is_32bit && emit_div_by_const_64(asm, l_op.clone(), *d, d_op.clone(), true) {
-                        return;
+                if let X86Operand::Imm(d) = &r_op
+                    && !op_is_32bit
+                    && if is_signed {
+                        emit_div_by_const_64(asm, l_op.clone(), *d, d_op.clone(), true)
+                    } else {
+                        emit_udiv_by_const_64(asm, l_op.clone(), *d, d_op.clone(), true)
                     }
+                {
+                    return;
                 }
-                // Fallback to idiv
+                // Fallback to idiv, or div of the zero-extended dividend
                 asm.push(X86Instr::Mov(ax_op.clone(), l_op));
-                if op_is_32bit { asm.push(X86Instr::Cdq); } else { asm.push(X86Instr::Cqto); }
+                if !is_signed {
+                    asm.push(X86Instr::Xor(X86Operand::Reg(dx.clone()), X86Operand::Reg(dx.clone())));
+                } else if op_is_32bit { asm.push(X86Instr::Cdq); } else { asm.push(X86Instr::Cqto); }
                 
                 let div_op = if let X86Operand::Imm(_) = r_op {
                     asm.push(X86Instr::Mov(X86Operand::Reg(cx.clone()), r_op));
@@ -386,7 +480,7 @@ impl InstructionGenerator {
                 } else {
                     r_op
                 };
-                asm.push(X86Instr::Idiv(div_op));
+                asm.push(if is_signed { X86Instr::Idiv(div_op) } else { X86Instr::Div(div_op) });
                 asm.push(X86Instr::Mov(d_op, X86Operand::Reg(dx)));
             }
             BinaryOp::EqualEqual | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
@@ -405,10 +499,14 @@ impl InstructionGenerator {
                 let cond = match op {
                     BinaryOp::EqualEqual => "e",
                     BinaryOp::NotEqual => "ne",
-                    BinaryOp::Less => "l",
-                    BinaryOp::LessEqual => "le",
-                    BinaryOp::Greater => "g",
-                    BinaryOp::GreaterEqual => "ge",
+                    BinaryOp::Less if is_signed => "l",
+                    BinaryOp::LessEqual if is_signed => "le",
+                    BinaryOp::Greater if is_signed => "g",
+                    BinaryOp::GreaterEqual if is_signed => "ge",
+                    BinaryOp::Less => "b",
+                    BinaryOp::LessEqual => "be",
+                    BinaryOp::Greater => "a",
+                    BinaryOp::GreaterEqual => "ae",
                     _ => unreachable!(),
                 };
                 asm.push(X86Instr::Set(cond.to_string(), X86Operand::Reg(X86Reg::Al)));
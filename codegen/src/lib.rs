@@ -11,7 +11,15 @@ mod calling_convention;
 mod control_flow;
 mod inline_asm;
 mod liveness;
+mod loops;
 mod globals;
+mod att;
+mod encoder;
+mod assembler;
+mod coff;
+mod debug_info;
+mod riscv;
+mod i686;
 
 use model::Type;
 use ir::IRProgram;
@@ -23,6 +31,21 @@ use peephole::apply_peephole;
 use function::FunctionGenerator;
 pub use model::TargetConfig;
 
+/// Assemble Intel-syntax output of `gen_program` into an x86-64 COFF
+/// object, without an external assembler.
+pub fn assemble_coff(asm: &str) -> Result<Vec<u8>, String> {
+    coff::write(&assembler::assemble(asm)?)
+}
+
+/// The directive that switches to code section `name`, as named by a
+/// function's `section` attribute.
+pub(crate) fn code_section_directive(platform: model::Platform, name: &str) -> String {
+    match platform {
+        model::Platform::Linux => format!(".section {}, \"ax\", @progbits", name),
+        model::Platform::Windows => format!(".section {}, \"xr\"", name),
+    }
+}
+
 pub struct Codegen {
     // Shared state
     structs: HashMap<String, model::StructDef>,
@@ -30,10 +53,16 @@ pub struct Codegen {
     float_constants: HashMap<String, (f64, bool)>,
     next_float_const: usize,
     func_return_types: HashMap<String, Type>,
+    func_param_types: HashMap<String, Vec<Type>>,
+    /// Parameter and return types of every function defined or declared,
+    /// for calls under the RISC-V calling convention
+    signatures: HashMap<String, ir::Declaration>,
+    enum_constants: HashMap<String, i64>,
     enable_regalloc: bool,
     target: TargetConfig,
     profile_generate: bool,
     profile_counters: Vec<String>,
+    debug_info: Option<debug_info::DebugInfo>,
 }
 
 impl Codegen {
@@ -44,10 +73,14 @@ impl Codegen {
             float_constants: HashMap::new(),
             next_float_const: 0,
             func_return_types: HashMap::new(),
+            func_param_types: HashMap::new(),
+            signatures: HashMap::new(),
+            enum_constants: HashMap::new(),
             enable_regalloc: true,
             target: TargetConfig::host(),
             profile_generate: false,
             profile_counters: Vec::new(),
+            debug_info: None,
         }
     }
 
@@ -58,10 +91,14 @@ impl Codegen {
             float_constants: HashMap::new(),
             next_float_const: 0,
             func_return_types: HashMap::new(),
+            func_param_types: HashMap::new(),
+            signatures: HashMap::new(),
+            enum_constants: HashMap::new(),
             enable_regalloc: true,
             target,
             profile_generate: false,
             profile_counters: Vec::new(),
+            debug_info: None,
         }
     }
 
@@ -72,6 +109,13 @@ impl Codegen {
         }
     }
 
+    /// Emit DWARF line tables and variable locations (`-g`). `sources`
+    /// resolves the spans of the IR, and `comp_dir` is the directory the
+    /// compilation ran in. Only x86-64 ELF output carries debug info.
+    pub fn set_debug_info(&mut self, sources: model::SourceMap, comp_dir: String) {
+        self.debug_info = Some(debug_info::DebugInfo::new(sources, comp_dir));
+    }
+
     /// Test helper: insert a struct definition for unit tests.
     #[cfg(test)]
     pub(crate) fn add_struct(&mut self, s_def: model::StructDef) {
@@ -95,13 +139,32 @@ impl Codegen {
         for u_def in &prog.unions {
             self.unions.insert(u_def.name.clone(), u_def.clone());
         }
+        self.enum_constants = prog.enums.iter().flat_map(|e| e.constants.iter().cloned()).collect();
         self.float_constants.clear();
         self.next_float_const = 0;
         
         // Build function signature map for return type inference in calls
         self.func_return_types.clear();
+        self.func_param_types.clear();
         for func in &prog.functions {
             self.func_return_types.insert(func.name.clone(), func.return_type.clone());
+            self.func_param_types.insert(func.name.clone(), func.params.iter().map(|(ty, _)| ty.clone()).collect());
+        }
+        // Prototypes only name parameter types; `func_return_types` keeps to
+        // functions defined here, which never read the vector count in AL
+        for decl in &prog.declarations {
+            self.func_param_types.insert(decl.name.clone(), decl.params.clone());
+        }
+        self.signatures = prog.declarations.iter().map(|decl| (decl.name.clone(), decl.clone())).collect();
+        for func in &prog.functions {
+            let is_variadic = self.signatures.get(&func.name).is_some_and(|decl| decl.is_variadic)
+                || func.blocks.iter().flat_map(|b| &b.instructions).any(|i| matches!(i, ir::Instruction::VaStart { .. }));
+            self.signatures.insert(func.name.clone(), ir::Declaration {
+                name: func.name.clone(),
+                return_type: func.return_type.clone(),
+                params: func.params.iter().map(|(ty, _)| ty.clone()).collect(),
+                is_variadic,
+            });
         }
         
         // ── Pre-classify globals into sections ──────────────────
@@ -136,23 +199,39 @@ impl Codegen {
             }
         }
 
+        let riscv = self.target.arch == model::Arch::Riscv64;
+        let i686 = self.target.arch == model::Arch::I686;
         let mut output = String::new();
-        output.push_str(".intel_syntax noprefix\n");
+        if riscv {
+            if self.target.pic_mode != model::PicMode::None {
+                output.push_str(".option pic\n");
+            }
+        } else if i686 || self.target.asm_syntax == model::AsmSyntax::Intel {
+            // The i686 backend writes Intel syntax only
+            output.push_str(".intel_syntax noprefix\n");
+        }
+        if riscv || i686 || !matches!(self.target.platform, model::Platform::Linux) {
+            self.debug_info = None;
+        }
+        if let Some(debug) = &mut self.debug_info {
+            output.push_str(&debug.file_directives(&prog.functions));
+        }
         
         // ── .rodata section ─────────────────────────────────────
         if !prog.global_strings.is_empty() || !rodata_globals.is_empty() {
-            output.push_str(".section .rodata\n");
+            output.push_str(&format!(".section {}\n", self.rodata_section()));
             
-            // String constants
-            for (label, content) in &prog.global_strings {
-                let escaped = content
-                    .replace("\\", "\\\\")
-                    .replace("\n", "\\n")
-                    .replace("\r", "\\r")
-                    .replace("\t", "\\t")
-                    .replace("\"", "\\\"")
-                    .replace("\0", "\\0");
-                output.push_str(&format!("{}: .asciz \"{}\"\n", label, escaped));
+            // String constants; one that ends another is a label into it
+            let merged = globals::merge_string_suffixes(&prog.global_strings);
+            for ((label, content), host) in prog.global_strings.iter().zip(&merged) {
+                if host.is_none() {
+                    output.push_str(&format!("{}: .asciz \"{}\"\n", label, globals::escape_asciz(content)));
+                }
+            }
+            for ((label, _), host) in prog.global_strings.iter().zip(&merged) {
+                if let Some((host, offset)) = host {
+                    output.push_str(&format!(".set {}, {} + {}\n", label, host, offset));
+                }
             }
             
             for g in &rodata_globals {
@@ -181,13 +260,8 @@ impl Codegen {
                     output.push_str(&format!(".type {}, @object\n", g.name));
                 }
                 
-                let mut alignment = 4;
-                for attr in &g.attributes {
-                    if let model::Attribute::Aligned(n) = attr {
-                        alignment = *n;
-                    }
-                }
-                output.push_str(&format!(".align {}\n", alignment));
+                let alignment = self.global_alignment(g);
+                output.push_str(&self.align_directive(alignment));
                 
                 let size = self.global_var_size(g);
                 output.push_str(&format!("{}:\n", g.name));
@@ -205,7 +279,7 @@ impl Codegen {
                     output.push_str(&format!(".section {}, \"aw\", @progbits\n", section_name));
                 }
                 model::Platform::Windows => {
-                    output.push_str(&format!(".section {}\n", section_name));
+                    output.push_str(&format!(".section {}, \"dw\"\n", section_name));
                 }
             }
             self.emit_global_var(&mut output, g);
@@ -233,29 +307,41 @@ impl Codegen {
             let mut func_in_custom_section = false;
             for attr in &func.attributes {
                 if let model::Attribute::Section(section_name) = attr {
-                    output.push_str(&format!(".section {}, \"ax\", @progbits\n", section_name));
+                    output.push_str(&code_section_directive(self.target.platform, section_name));
+                    output.push('\n');
                     func_in_custom_section = true;
                 }
             }
             
-            // Label address constants for computed goto (&&label) — emit in rodata before function body
-        for label in &func.label_addrs {
-            if let Some(block_id) = func.labels.get(label) {
-                let sym = format!("__label_addr_{}", label);
-                output.push_str(&format!(".globl {}\n", sym));
-                output.push_str(&format!(".type {}, @object\n", sym));
-                output.push_str(".section .rodata\n");
-                output.push_str(&format!("{}:\n", sym));
-                output.push_str(&format!("    .quad {}_{}\n", func.name, block_id.0));
-                output.push_str(&format!(".size {}, 8\n", sym));
-                output.push_str(".text\n");
+            // Label addresses for computed goto (&&label) name the label's block
+            let resolved;
+            let func = if func.label_addrs.is_empty() {
+                func
+            } else {
+                resolved = control_flow::resolve_label_addresses(func);
+                &resolved
+            };
+
+            if riscv || i686 {
+                let asm = if riscv {
+                    riscv::FunctionGenerator::new(self).gen_function(func)
+                } else {
+                    i686::FunctionGenerator::new(self).gen_function(func)
+                };
+                output.push_str(&asm);
+                output.push_str(".cfi_endproc\n");
+                output.push_str(&format!(".size {}, .-{}\n", func.name, func.name));
+                if func_in_custom_section {
+                    output.push_str(".text\n");
+                }
+                continue;
             }
-        }
 
-        let func_gen = FunctionGenerator::new(
+            let func_gen = FunctionGenerator::new(
                 &self.structs,
                 &self.unions,
                 &self.func_return_types,
+                &self.func_param_types,
                 &mut self.float_constants,
                 &mut self.next_float_const,
                 self.enable_regalloc,
@@ -266,6 +352,7 @@ impl Codegen {
                 } else {
                     None
                 },
+                self.debug_info.as_mut(),
             );
             
             let mut func_asm = func_gen.gen_function(func);
@@ -273,7 +360,13 @@ impl Codegen {
             // Apply peephole optimizations
             apply_peephole(&mut func_asm);
             
-            output.push_str(&emit_asm(&func_asm));
+            match self.target.asm_syntax {
+                model::AsmSyntax::Intel => output.push_str(&emit_asm(&func_asm)),
+                model::AsmSyntax::Att => output.push_str(&att::to_att(&emit_asm(&func_asm))),
+            }
+            if self.debug_info.is_some() {
+                output.push_str(&format!("{}:\n", debug_info::function_end_label(&func.name)));
+            }
             
             // Emit .cfi_endproc for DWARF unwinding
             if matches!(self.target.platform, model::Platform::Linux) {
@@ -293,11 +386,15 @@ impl Codegen {
         
         // Emit float constants in .rodata section
         if !self.float_constants.is_empty() {
-            output.push_str("\n.section .rodata\n");
-            output.push_str(".align 16\n");
+            output.push_str(&format!("\n.section {}\n", self.rodata_section()));
+            output.push_str(&self.align_directive(16));
             let mut sorted_consts: Vec<_> = self.float_constants.iter().collect();
             sorted_consts.sort_by_key(|(label, _)| label.as_str());
             for (label, (value, is_double)) in sorted_consts {
+                // RISC-V loads of misaligned doubles may trap
+                if riscv && *is_double {
+                    output.push_str(&self.align_directive(8));
+                }
                 if *is_double {
                     let bits = value.to_bits();
                     output.push_str(&format!("{}: .quad 0x{:016x}\n", label, bits));
@@ -309,17 +406,22 @@ impl Codegen {
             }
         }
         
-        // Emit .init_array / .fini_array entries for constructor/destructor functions
+        // Emit .init_array / .fini_array entries for constructor/destructor
+        // functions; MinGW's startup code runs .ctors and .dtors instead
+        let (init_section, fini_section) = match self.target.platform {
+            model::Platform::Linux => (".init_array,\"aw\",@init_array", ".fini_array,\"aw\",@fini_array"),
+            model::Platform::Windows => (".ctors,\"dw\"", ".dtors,\"dw\""),
+        };
         for func in &prog.functions {
             if func.attributes.iter().any(|a| matches!(a, model::Attribute::Constructor)) {
-                output.push_str(&format!("\n.section .init_array,\"aw\",@init_array\n"));
-                output.push_str(".align 8\n");
-                output.push_str(&format!(".quad {}\n", func.name));
+                output.push_str(&format!("\n.section {}\n", init_section));
+                output.push_str(&self.align_directive(self.target.data_layout.pointer_size));
+                output.push_str(&format!("{} {}\n", self.pointer_directive(), func.name));
             }
             if func.attributes.iter().any(|a| matches!(a, model::Attribute::Destructor)) {
-                output.push_str(&format!("\n.section .fini_array,\"aw\",@fini_array\n"));
-                output.push_str(".align 8\n");
-                output.push_str(&format!(".quad {}\n", func.name));
+                output.push_str(&format!("\n.section {}\n", fini_section));
+                output.push_str(&self.align_directive(self.target.data_layout.pointer_size));
+                output.push_str(&format!("{} {}\n", self.pointer_directive(), func.name));
             }
         }
         
@@ -328,13 +430,20 @@ impl Codegen {
             output.push_str("\n.section .note.GNU-stack,\"\",@progbits\n");
         }
 
+        if let Some(debug) = &self.debug_info {
+            let layout = model::TypeLayout::new(&self.structs, &self.unions).with_data_layout(self.target.data_layout);
+            output.push_str(&debug.sections(&layout));
+        }
+
         // PGO counter storage
         if !self.profile_counters.is_empty() {
             output.push_str("\n.section .bss\n");
             for counter in &self.profile_counters {
                 output.push_str(&format!(".globl {}\n", counter));
-                output.push_str(&format!(".type {}, @object\n", counter));
-                output.push_str(".align 8\n");
+                if matches!(self.target.platform, model::Platform::Linux) {
+                    output.push_str(&format!(".type {}, @object\n", counter));
+                }
+                output.push_str(&self.align_directive(8));
                 output.push_str(&format!("{}:\n", counter));
                 output.push_str("    .quad 0\n");
                 output.push_str(&format!(".size {}, 8\n", counter));
@@ -344,6 +453,36 @@ impl Codegen {
         output
     }
     
+    /// Read-only data: `.rodata` in ELF, `.rdata` in COFF.
+    fn rodata_section(&self) -> &'static str {
+        match self.target.platform {
+            model::Platform::Linux => ".rodata",
+            model::Platform::Windows => ".rdata,\"dr\"",
+        }
+    }
+
+    /// An alignment directive for `bytes`; RISC-V assemblers read `.align`
+    /// as a power of two, so they get `.balign`.
+    fn align_directive(&self, bytes: usize) -> String {
+        match self.target.arch {
+            model::Arch::X86_64 | model::Arch::I686 => format!(".align {}\n", bytes),
+            model::Arch::Riscv64 => format!(".balign {}\n", bytes),
+        }
+    }
+
+    /// Alignment of global `g`: its `aligned` attribute, else 4 bytes on
+    /// x86 and the natural alignment of its type on RISC-V.
+    fn global_alignment(&self, g: &model::GlobalVar) -> usize {
+        let aligned = g.attributes.iter().rev().find_map(|attr| match attr {
+            model::Attribute::Aligned(n) => Some(*n),
+            _ => None,
+        });
+        aligned.unwrap_or_else(|| match self.target.arch {
+            model::Arch::X86_64 | model::Arch::I686 => 4,
+            model::Arch::Riscv64 => self.type_alignment(&g.r#type),
+        })
+    }
+
     /// Emit a single global variable (label + data directives).
     /// Used by .rodata, .data, and custom section emission.
     fn emit_global_var(&self, output: &mut String, g: &model::GlobalVar) {
@@ -356,13 +495,7 @@ impl Codegen {
             output.push_str(&format!(".type {}, @object\n", g.name));
         }
         
-        let mut alignment = 4;
-        for attr in &g.attributes {
-            if let model::Attribute::Aligned(n) = attr {
-                alignment = *n;
-            }
-        }
-        output.push_str(&format!(".align {}\n", alignment));
+        output.push_str(&self.align_directive(self.global_alignment(g)));
         
         if let Some(init) = &g.init {
             match init {
@@ -371,26 +504,22 @@ impl Codegen {
                     self.emit_init_list_data(output, &g.r#type, items);
                 }
                 model::Expr::StringLiteral(s) => {
-                    let escaped = s
-                        .replace("\\", "\\\\")
-                        .replace("\n", "\\n")
-                        .replace("\r", "\\r")
-                        .replace("\t", "\\t")
-                        .replace("\"", "\\\"")
-                        .replace("\0", "\\0");
-                    output.push_str(&format!("{}: .asciz \"{}\"\n", g.name, escaped));
+                    output.push_str(&format!("{}: .asciz \"{}\"\n", g.name, globals::escape_asciz(s)));
+                    // A longer array is zero-filled after the terminator
+                    if let Type::Array(_, len) = &g.r#type {
+                        let used = s.chars().count() + 1;
+                        if *len > used {
+                            output.push_str(&format!("    .zero {}\n", len - used));
+                        }
+                    }
+                }
+                model::Expr::WideStringLiteral(s, encoding) => {
+                    output.push_str(&format!("{}:\n", g.name));
+                    self.emit_wide_string_data(output, &g.r#type, &encoding.encode(s));
                 }
                 _ => {
-                    let init_str = match init {
-                        model::Expr::Constant(c) => c.to_string(),
-                        model::Expr::FloatConstant(f) => format!("{:.15}", f),
-                        _ => "0".to_string(),
-                    };
-                    match &g.r#type {
-                        Type::Char | Type::UnsignedChar => output.push_str(&format!("{}: .byte {}\n", g.name, init_str)),
-                        Type::Int | Type::UnsignedInt | Type::Enum(_) => output.push_str(&format!("{}: .long {}\n", g.name, init_str)),
-                        _ => output.push_str(&format!("{}: .quad {}\n", g.name, init_str)),
-                    }
+                    output.push_str(&format!("{}:\n", g.name));
+                    self.emit_init_value(output, &g.r#type, init);
                 }
             }
         } else {
@@ -408,7 +537,7 @@ impl Codegen {
     /// Check if an initializer expression is all-zeros.
     fn is_zero_init(init: &model::Expr) -> bool {
         match init {
-            model::Expr::Constant(0) => true,
+            model::Expr::Constant(0) | model::Expr::TypedConstant(0, _) => true,
             model::Expr::FloatConstant(f) => f.to_bits() == 0,
             model::Expr::InitList(items) => {
                 items.iter().all(|item| Self::is_zero_init(&item.value))
@@ -81,6 +81,32 @@ pub fn compute_live_intervals(func: &IrFunction) -> Vec<LiveInterval> {
                     block_use[bi].insert(*v);
                 }
             }
+            IrTerminator::IndirectBr { target } => {
+                if let Operand::Var(v) = target {
+                    if !alloca_vars.contains(v) && !block_def[bi].contains(v) {
+                        block_use[bi].insert(*v);
+                    }
+                }
+                for label_block in func.indirect_targets() {
+                    if let Some(&ti) = block_index.get(&label_block) {
+                        successors[bi].push(ti);
+                        predecessors[ti].push(bi);
+                    }
+                }
+            }
+            IrTerminator::Switch { value, cases, default } => {
+                if let Operand::Var(v) = value
+                    && !alloca_vars.contains(v) && !block_def[bi].contains(v)
+                {
+                    block_use[bi].insert(*v);
+                }
+                for target in IrTerminator::switch_targets(cases, *default) {
+                    if let Some(&ti) = block_index.get(&target) {
+                        successors[bi].push(ti);
+                        predecessors[ti].push(bi);
+                    }
+                }
+            }
             _ => {}
         }
     }
@@ -162,7 +188,7 @@ pub fn compute_live_intervals(func: &IrFunction) -> Vec<LiveInterval> {
         
         // Handle terminator operands
         match &block.terminator {
-            IrTerminator::CondBr { cond, .. } => {
+            IrTerminator::CondBr { cond, .. } | IrTerminator::IndirectBr { target: cond } | IrTerminator::Switch { value: cond, .. } => {
                 if let Operand::Var(v) = cond {
                     if !alloca_vars.contains(v) {
                         let entry = intervals.entry(*v).or_insert((position, position));
@@ -186,6 +212,7 @@ pub fn compute_live_intervals(func: &IrFunction) -> Vec<LiveInterval> {
     // Second: extend intervals for variables that are live-in or live-out of blocks
     // If a variable is live-in to a block, it must be live from the start of that block
     // If a variable is live-out of a block, it must be live through the end of that block
+    // (from its definition, if the block defines it, so a call before that is not crossed)
     for bi in 0..num_blocks {
         let bstart = block_start_pos[bi];
         let bend = block_end_pos[bi];
@@ -198,7 +225,6 @@ pub fn compute_live_intervals(func: &IrFunction) -> Vec<LiveInterval> {
         
         for v in &live_out[bi] {
             let entry = intervals.entry(*v).or_insert((bstart, bstart));
-            if bstart < entry.0 { entry.0 = bstart; }
             if bend > entry.1 { entry.1 = bend; }
         }
     }
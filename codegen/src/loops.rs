@@ -0,0 +1,178 @@
+// Loop structure for register allocation
+//
+// Finds the loops of a function from its retreating edges (found by a
+// depth-first walk from the entry block), merging those that share a header.
+// The allocator weighs references by loop depth and splits live ranges at
+// loop boundaries.
+
+use ir::{BlockId, Cfg, Function as IrFunction};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// A loop: its header and every block of its body, the header included.
+#[derive(Debug, Clone)]
+pub(crate) struct Loop {
+    pub header: BlockId,
+    pub body: BTreeSet<BlockId>,
+}
+
+impl Loop {
+    /// The one block outside the loop that branches to the header, when the
+    /// header is the only way in. A value copied at the end of it is the
+    /// value on entry to every iteration.
+    pub fn preheader(&self, func: &IrFunction, cfg: &Cfg) -> Option<BlockId> {
+        if self.header == func.entry_block {
+            return None;
+        }
+        let entries_elsewhere = self.body.iter()
+            .filter(|&&b| b != self.header)
+            .any(|&b| cfg.preds(b).iter().any(|p| !self.body.contains(p)));
+        if entries_elsewhere {
+            return None;
+        }
+        let mut outside = cfg.preds(self.header).iter().filter(|p| !self.body.contains(p));
+        match (outside.next(), outside.next()) {
+            (Some(&pre), None) => Some(pre),
+            _ => None,
+        }
+    }
+}
+
+/// The loops of `func`, outermost (largest) first.
+pub(crate) fn find_loops(func: &IrFunction, cfg: &Cfg) -> Vec<Loop> {
+    // Retreating edges: those into a block still on the depth-first stack
+    let mut back_edges: Vec<(BlockId, BlockId)> = Vec::new();
+    let mut visited: HashSet<BlockId> = HashSet::new();
+    let mut on_stack: HashSet<BlockId> = HashSet::new();
+    let mut stack: Vec<(BlockId, usize)> = vec![(func.entry_block, 0)];
+    visited.insert(func.entry_block);
+    on_stack.insert(func.entry_block);
+    while let Some((block, next)) = stack.last_mut() {
+        let block = *block;
+        match cfg.succs(block).get(*next) {
+            Some(&succ) => {
+                *next += 1;
+                if on_stack.contains(&succ) {
+                    back_edges.push((block, succ));
+                } else if visited.insert(succ) {
+                    on_stack.insert(succ);
+                    stack.push((succ, 0));
+                }
+            }
+            None => {
+                on_stack.remove(&block);
+                stack.pop();
+            }
+        }
+    }
+
+    // The body of a back edge: the blocks that reach its source without
+    // passing through the header
+    let mut bodies: HashMap<BlockId, BTreeSet<BlockId>> = HashMap::new();
+    for (latch, header) in back_edges {
+        let body = bodies.entry(header).or_insert_with(|| BTreeSet::from([header]));
+        let mut worklist = vec![latch];
+        while let Some(block) = worklist.pop() {
+            if body.insert(block) {
+                worklist.extend(cfg.preds(block).iter().filter(|p| visited.contains(p)));
+            }
+        }
+    }
+
+    let mut loops: Vec<Loop> = bodies.into_iter()
+        .map(|(header, body)| Loop { header, body })
+        .collect();
+    loops.sort_by(|a, b| b.body.len().cmp(&a.body.len()).then(a.header.cmp(&b.header)));
+    loops
+}
+
+/// How many loops each block is nested in; blocks outside every loop are absent.
+pub(crate) fn loop_depths(loops: &[Loop]) -> HashMap<BlockId, u32> {
+    let mut depths = HashMap::new();
+    for l in loops {
+        for &block in &l.body {
+            *depths.entry(block).or_insert(0) += 1;
+        }
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::{BasicBlock, Operand, Terminator};
+    use model::Type;
+
+    fn block(id: usize, terminator: Terminator) -> BasicBlock {
+        BasicBlock {
+            id: BlockId(id),
+            instructions: vec![],
+            terminator,
+            is_label_target: false,
+            spans: vec![],
+            terminator_span: Default::default(),
+        }
+    }
+
+    fn function(blocks: Vec<BasicBlock>) -> IrFunction {
+        let (label_addrs, labels) = IrFunction::default_meta();
+        IrFunction {
+            name: "f".into(),
+            return_type: Type::Void,
+            params: vec![],
+            blocks,
+            entry_block: BlockId(0),
+            var_types: HashMap::new(),
+            attributes: vec![],
+            is_static: false,
+            label_addrs,
+            labels,
+            local_names: HashMap::new(),
+            span: Default::default(),
+        }
+    }
+
+    fn cond(then_block: usize, else_block: usize) -> Terminator {
+        Terminator::cond_br(Operand::Constant(1), BlockId(then_block), BlockId(else_block))
+    }
+
+    #[test]
+    fn nested_loops_have_depths_and_preheaders() {
+        // 0 → 1 (outer header) → 2 (inner header) ⇄ 3, 2 → 4 → 1, 1 → 5
+        let func = function(vec![
+            block(0, Terminator::Br(BlockId(1))),
+            block(1, cond(2, 5)),
+            block(2, cond(3, 4)),
+            block(3, Terminator::Br(BlockId(2))),
+            block(4, Terminator::Br(BlockId(1))),
+            block(5, Terminator::Ret(None)),
+        ]);
+        let cfg = Cfg::new(&func);
+        let loops = find_loops(&func, &cfg);
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0].header, BlockId(1));
+        assert_eq!(loops[0].body, BTreeSet::from([BlockId(1), BlockId(2), BlockId(3), BlockId(4)]));
+        assert_eq!(loops[1].body, BTreeSet::from([BlockId(2), BlockId(3)]));
+        assert_eq!(loops[0].preheader(&func, &cfg), Some(BlockId(0)));
+        assert_eq!(loops[1].preheader(&func, &cfg), Some(BlockId(1)));
+
+        let depths = loop_depths(&loops);
+        assert_eq!(depths.get(&BlockId(0)), None);
+        assert_eq!(depths[&BlockId(1)], 1);
+        assert_eq!(depths[&BlockId(3)], 2);
+    }
+
+    #[test]
+    fn a_loop_entered_in_the_middle_has_no_preheader() {
+        // 0 branches to both 1 and 2, which form a cycle
+        let func = function(vec![
+            block(0, cond(1, 2)),
+            block(1, Terminator::Br(BlockId(2))),
+            block(2, cond(1, 3)),
+            block(3, Terminator::Ret(None)),
+        ]);
+        let cfg = Cfg::new(&func);
+        let loops = find_loops(&func, &cfg);
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].preheader(&func, &cfg), None);
+    }
+}
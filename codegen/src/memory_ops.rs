@@ -13,16 +13,8 @@ fn type_load_info(value_type: &Type) -> (bool, bool, bool, bool, bool, bool) {
         Type::UnsignedChar => (false, false, true, false, false, true),
         Type::Short  => (false, false, false, true, false, false),
         Type::UnsignedShort => (false, false, false, true, false, true),
+        Type::Bool => (false, false, true, false, false, true),
         Type::Int | Type::UnsignedInt | Type::Enum(_) => (false, false, false, false, true, matches!(value_type, Type::UnsignedInt)),
-        Type::Typedef(name) => match name.as_str() {
-            "int8_t"  | "int8"  => (false, false, true, false, false, false),
-            "uint8_t" | "uint8" => (false, false, true, false, false, true),
-            "int16_t" | "int16" => (false, false, false, true, false, false),
-            "uint16_t"| "uint16"=> (false, false, false, true, false, true),
-            "int32_t" | "int32" => (false, false, false, false, true, false),
-            "uint32_t"| "uint32"=> (false, false, false, false, true, true),
-            _ => (false, false, false, false, false, false),
-        },
         _ => (false, false, false, false, false, false),
     }
 }
@@ -49,6 +41,17 @@ fn emit_fp_store(generator: &mut FunctionGenerator, is_double: bool, base: X86Re
 
 pub fn gen_load(generator: &mut FunctionGenerator, dest: VarId, addr: &Operand, value_type: &Type) {
     generator.var_types.insert(dest, value_type.clone());
+    if crate::call_ops::is_aggregate(value_type) {
+        // A struct or union value is the address of a copy of it
+        let size = generator.get_type_size(value_type);
+        let buffer = generator.alloc_aggregate(value_type);
+        generator.load_address_into(addr, X86Reg::Rdx);
+        generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, buffer)));
+        copy_memory(generator, size);
+        let d_op = generator.var_to_op(dest);
+        generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
+        return;
+    }
     let d_op = generator.var_to_op(dest);
     let (is_float, is_double, use_byte, use_word, use_dword, is_unsigned) = type_load_info(value_type);
 
@@ -56,27 +59,27 @@ pub fn gen_load(generator: &mut FunctionGenerator, dest: VarId, addr: &Operand,
     if let Operand::Var(var) = addr {
          if let Some(buffer_offset) = generator.alloca_buffers.get(var) {
              if is_float {
-                 emit_fp_load(generator, d_op, is_double, X86Reg::Rbp, *buffer_offset);
+                 emit_fp_load(generator, d_op, is_double, X86Reg::Frame, *buffer_offset);
              } else if use_byte {
                  if is_unsigned {
-                     generator.asm.push(X86Instr::Movzx(X86Operand::Reg(X86Reg::Rax), X86Operand::ByteMem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Movzx(X86Operand::Reg(X86Reg::Rax), X86Operand::ByteMem(X86Reg::Frame, *buffer_offset)));
                  } else {
-                     generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::ByteMem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::ByteMem(X86Reg::Frame, *buffer_offset)));
                  }
                  generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
              } else if use_word {
                  if is_unsigned {
-                     generator.asm.push(X86Instr::Movzx(X86Operand::Reg(X86Reg::Rax), X86Operand::WordMem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Movzx(X86Operand::Reg(X86Reg::Rax), X86Operand::WordMem(X86Reg::Frame, *buffer_offset)));
                  } else {
-                     generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::WordMem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::WordMem(X86Reg::Frame, *buffer_offset)));
                  }
                  generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
              } else {
                  if use_dword {
-                     generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::DwordMem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::DwordMem(X86Reg::Frame, *buffer_offset)));
                      generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Eax)));
                  } else {
-                     generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Rbp, *buffer_offset)));
+                     generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, *buffer_offset)));
                  }
                  generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
              }
@@ -95,7 +98,14 @@ pub fn gen_load(generator: &mut FunctionGenerator, dest: VarId, addr: &Operand,
                  generator.asm.push(X86Instr::Movss(d_op, X86Operand::Reg(X86Reg::Xmm0)));
              }
          } else {
-             if use_dword {
+             if use_byte || use_word {
+                 let src = if use_byte { X86Operand::GlobalByteMem(name.clone()) } else { X86Operand::GlobalWordMem(name.clone()) };
+                 if is_unsigned {
+                     generator.asm.push(X86Instr::Movzx(X86Operand::Reg(X86Reg::Rax), src));
+                 } else {
+                     generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), src));
+                 }
+             } else if use_dword {
                  generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Eax), X86Operand::GlobalMem(name.clone())));
                  generator.asm.push(X86Instr::Movsx(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Eax)));
              } else {
@@ -150,11 +160,20 @@ pub fn gen_load(generator: &mut FunctionGenerator, dest: VarId, addr: &Operand,
 }
 
 pub fn gen_store(generator: &mut FunctionGenerator, addr: &Operand, src: &Operand, value_type: &Type) {
+    if crate::call_ops::is_aggregate(value_type) {
+        let size = generator.get_type_size(value_type);
+        gen_memcpy(generator, addr, src, size);
+        return;
+    }
     let (is_float, is_double, use_byte, use_word, use_dword, _is_unsigned) = type_load_info(value_type);
 
     // Load src into register
     if is_float {
-        let s_op = generator.operand_to_op(src);
+        let s_op = match src {
+            // Materialize the constant at the stored width, not as a float
+            Operand::FloatConstant(f) => X86Operand::RipRelLabel(generator.get_or_create_float_const(*f, is_double)),
+            _ => generator.operand_to_op(src),
+        };
          match s_op {
              X86Operand::Reg(X86Reg::Xmm0) => {},
              _ => {
@@ -171,7 +190,7 @@ pub fn gen_store(generator: &mut FunctionGenerator, addr: &Operand, src: &Operan
              generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rcx), X86Operand::RipRelLabel(name.clone())));
          } else if let Operand::Var(v) = src {
              if let Some(off) = generator.alloca_buffers.get(v) {
-                 generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rcx), X86Operand::Mem(X86Reg::Rbp, *off)));
+                 generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rcx), X86Operand::Mem(X86Reg::Frame, *off)));
              } else {
                  generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), s_op));
              }
@@ -212,7 +231,11 @@ pub fn gen_gep(generator: &mut FunctionGenerator, dest: VarId, base: &Operand, i
             if let Some(&alloca_offset) = generator.alloca_buffers.get(base_var) {
                 // base is a stack alloca at [rbp + alloca_offset]
                 generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), i_op));
-                if elem_size == 1 {
+                if !generator.frame_pointer {
+                    // The RSP offset is only known once the frame is laid out
+                    generator.asm.push(X86Instr::Lea(X86Operand::Reg(X86Reg::Rax), X86Operand::Mem(X86Reg::Frame, alloca_offset)));
+                    generator.asm.push(X86Instr::Raw(format!("lea rax, [rax + rcx*{}]", elem_size)));
+                } else if elem_size == 1 {
                     generator.asm.push(X86Instr::Raw(
                         format!("lea rax, [rbp + rcx{:+}]", alloca_offset)
                     ));
@@ -237,3 +260,97 @@ pub fn gen_gep(generator: &mut FunctionGenerator, dest: VarId, base: &Operand, i
     generator.asm.push(X86Instr::Add(X86Operand::Reg(X86Reg::Rax), X86Operand::Reg(X86Reg::Rcx)));
     generator.asm.push(X86Instr::Mov(d_op, X86Operand::Reg(X86Reg::Rax)));
 }
+
+/// Longest copy or fill emitted as straight-line moves; longer ones use
+/// `rep movsb` / `rep stosb`.
+const INLINE_MEMORY_LIMIT: usize = 64;
+
+/// The (offset, width) moves that cover `size` bytes: 8-byte words, then a
+/// 4, 2, and 1-byte tail.
+fn memory_chunks(size: usize) -> Vec<(i32, usize)> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    for width in [8, 4, 2, 1] {
+        while size - offset >= width {
+            chunks.push((offset as i32, width));
+            offset += width;
+        }
+    }
+    chunks
+}
+
+fn sized_mem(base: X86Reg, offset: i32, width: usize) -> X86Operand {
+    match width {
+        8 => X86Operand::Mem(base, offset),
+        4 => X86Operand::DwordMem(base, offset),
+        2 => X86Operand::WordMem(base, offset),
+        _ => X86Operand::ByteMem(base, offset),
+    }
+}
+
+fn sized_rcx(width: usize) -> X86Operand {
+    X86Operand::Reg(match width {
+        8 => X86Reg::Rcx,
+        4 => X86Reg::Ecx,
+        2 => X86Reg::Cx,
+        _ => X86Reg::Cl,
+    })
+}
+
+/// Run a `rep` string instruction with the destination in RDI. RDI and RSI
+/// may hold allocated variables, so they are parked in the R10/R11 scratch
+/// registers around it.
+fn emit_rep(generator: &mut FunctionGenerator, instruction: &str, dest: X86Reg, src: Option<X86Reg>, size: usize) {
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::R10), X86Operand::Reg(X86Reg::Rdi)));
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::R11), X86Operand::Reg(X86Reg::Rsi)));
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rdi), X86Operand::Reg(dest)));
+    if let Some(src) = src {
+        generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rsi), X86Operand::Reg(src)));
+    }
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), X86Operand::Imm(size as i64)));
+    generator.asm.push(X86Instr::Raw(format!("rep {}", instruction)));
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rdi), X86Operand::Reg(X86Reg::R10)));
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rsi), X86Operand::Reg(X86Reg::R11)));
+}
+
+pub fn gen_memcpy(generator: &mut FunctionGenerator, dest: &Operand, src: &Operand, size: usize) {
+    if size == 0 {
+        return;
+    }
+    generator.load_address_into(src, X86Reg::Rdx);
+    generator.load_address_into(dest, X86Reg::Rax);
+    copy_memory(generator, size);
+}
+
+/// Copy `size` bytes from the address in RDX to the address in RAX, leaving
+/// both in place. RCX is clobbered, and R10 and R11 too for a long copy.
+pub(crate) fn copy_memory(generator: &mut FunctionGenerator, size: usize) {
+    if size > INLINE_MEMORY_LIMIT {
+        emit_rep(generator, "movsb", X86Reg::Rax, Some(X86Reg::Rdx), size);
+        return;
+    }
+    for (offset, width) in memory_chunks(size) {
+        generator.asm.push(X86Instr::Mov(sized_rcx(width), sized_mem(X86Reg::Rdx, offset, width)));
+        generator.asm.push(X86Instr::Mov(sized_mem(X86Reg::Rax, offset, width), sized_rcx(width)));
+    }
+}
+
+pub fn gen_memset(generator: &mut FunctionGenerator, dest: &Operand, value: &Operand, size: usize) {
+    if size == 0 {
+        return;
+    }
+    if let (Operand::Constant(byte), true) = (value, size <= INLINE_MEMORY_LIMIT) {
+        // Store the byte repeated across each word
+        let pattern = i64::from_ne_bytes([*byte as u8; 8]);
+        generator.load_address_into(dest, X86Reg::Rax);
+        generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), X86Operand::Imm(pattern)));
+        for (offset, width) in memory_chunks(size) {
+            generator.asm.push(X86Instr::Mov(sized_mem(X86Reg::Rax, offset, width), sized_rcx(width)));
+        }
+        return;
+    }
+    generator.load_address_into(dest, X86Reg::Rdx);
+    let value = generator.operand_to_op(value);
+    generator.asm.push(X86Instr::Mov(X86Operand::Reg(X86Reg::Rax), value));
+    emit_rep(generator, "stosb", X86Reg::Rdx, None, size);
+}
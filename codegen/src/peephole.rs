@@ -5,6 +5,7 @@
 // fixpoint.  Adding a new pattern only requires writing its function and
 // appending one entry to `default_rules()` — no existing code needs editing.
 
+use crate::calling_convention::{CallingConvention, SystemVConvention, WindowsX64Convention};
 use crate::x86::{X86Instr, X86Operand, X86Reg};
 use std::collections::{HashMap, HashSet};
 
@@ -160,9 +161,10 @@ fn eliminate_jump_chains(instructions: &mut Vec<X86Instr>) {
     // Resolve transitive jumps: if A -> B and B -> C, then A -> C
     for _ in 0..10 {  // Max 10 iterations to prevent infinite loops
         let mut changed = false;
-        let entries: Vec<(String, String)> = jump_targets.iter()
+        let mut entries: Vec<(String, String)> = jump_targets.iter()
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
+        entries.sort(); // label order, not hash order
         
         for (label, target) in entries {
             if let Some(new_target) = jump_targets.get(&target).cloned() {
@@ -190,6 +192,13 @@ fn eliminate_jump_chains(instructions: &mut Vec<X86Instr>) {
                     *target = new_target.clone();
                 }
             }
+            X86Instr::JumpTable(_, targets) => {
+                for target in targets {
+                    if let Some(new_target) = jump_targets.get(target) {
+                        *target = new_target.clone();
+                    }
+                }
+            }
             _ => {}
         }
     }
@@ -209,6 +218,9 @@ fn eliminate_jump_chains(instructions: &mut Vec<X86Instr>) {
                 if idx == i { return false; }  // Don't count self
                 match inst {
                     X86Instr::Jmp(t) | X86Instr::Jcc(_, t) => t == &label_name,
+                    // A label whose address is taken for a computed goto
+                    X86Instr::Lea(_, X86Operand::RipRelLabel(t)) => t == &label_name,
+                    X86Instr::JumpTable(_, targets) => targets.contains(&label_name),
                     _ => false,
                 }
             });
@@ -251,7 +263,8 @@ fn is_mem_operand(op: &X86Operand) -> bool {
     matches!(op,
         X86Operand::Mem(..) | X86Operand::DwordMem(..) | X86Operand::WordMem(..) |
         X86Operand::ByteMem(..) | X86Operand::FloatMem(..) | X86Operand::DoubleMem(..) |
-        X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..)
+        X86Operand::GlobalMem(..) | X86Operand::GlobalQwordMem(..) |
+        X86Operand::GlobalWordMem(..) | X86Operand::GlobalByteMem(..)
     )
 }
 
@@ -300,6 +313,9 @@ fn rule_cmp_set_branch_fusion(instructions: &mut Vec<X86Instr>, i: usize) -> boo
     }
 
     let test_idx = match found_test_jcc { Some(idx) => idx, None => return false };
+    // The flag value must not be needed past the branch, as when another
+    // block branches on the same comparison
+    if is_reg_used_after(instructions, test_idx + 1, &test_reg) { return false; }
     let set_cond   = if let X86Instr::Set(c, _)  = &instructions[i+2]       { c.clone() } else { unreachable!() };
     let (branch_cond, branch_label) = if let X86Instr::Jcc(c, l) = &instructions[test_idx+1] { (c.clone(), l.clone()) } else { unreachable!() };
 
@@ -367,6 +383,18 @@ fn rule_mov_cmp_fusion(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
     false
 }
 
+/// Whether `mov dest, src` exists: not memory to memory, and a 64-bit
+/// immediate only into a register.
+fn is_encodable_mov(dest: &X86Operand, src: &X86Operand) -> bool {
+    if !is_mem_operand(dest) {
+        return true;
+    }
+    match src {
+        X86Operand::Imm(c) => i32::try_from(*c).is_ok(),
+        src => !is_mem_operand(src),
+    }
+}
+
 /// mov reg, X; mov Y, reg → mov Y, X (if reg dead and no mem-to-mem)
 fn rule_adjacent_copy_forward(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
     if i + 1 >= instructions.len() { return false; }
@@ -377,7 +405,7 @@ fn rule_adjacent_copy_forward(instructions: &mut Vec<X86Instr>, i: usize) -> boo
         if std::mem::discriminant(temp_reg) == std::mem::discriminant(temp_reg2)
             && !is_reg_used_after(instructions, i + 2, temp_reg)
         {
-            if !is_mem_operand(src) || !is_mem_operand(dest) {
+            if is_encodable_mov(dest, src) {
                 instructions[i] = X86Instr::Mov(dest.clone(), src.clone());
                 instructions.remove(i + 1);
                 return true;
@@ -398,7 +426,7 @@ fn rule_non_adjacent_copy_forward(instructions: &mut Vec<X86Instr>, i: usize) ->
                 if let X86Instr::Mov(dest, X86Operand::Reg(temp2)) = &instructions[j] {
                     if std::mem::discriminant(temp_reg) == std::mem::discriminant(temp2)
                         && !is_reg_used_after(instructions, j + 1, temp_reg)
-                        && (!is_mem_operand(src) || !is_mem_operand(dest))
+                        && is_encodable_mov(dest, src)
                     {
                         instructions[j] = X86Instr::Mov(dest.clone(), src.clone());
                         instructions.remove(i);
@@ -421,13 +449,13 @@ fn rule_immediate_forward(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
     if let X86Instr::Mov(X86Operand::Reg(load_reg), X86Operand::Imm(imm_val)) = &instructions[i] {
         let imm_val = *imm_val;
         let load_reg = load_reg.clone();
+        // Only a move into a register can take a 64-bit immediate.
+        let fits_imm32 = i32::try_from(imm_val).is_ok();
         let can_forward = match &instructions[i + 1] {
             X86Instr::Mov(dest, X86Operand::Reg(use_reg)) if load_reg.same_physical(use_reg) => {
-                let is_dest_mem = is_mem_operand(dest);
-                if is_dest_mem && matches!(dest, X86Operand::DwordMem(..)) {
-                    imm_val >= i32::MIN as i64 && imm_val <= i32::MAX as i64
-                } else { true }
+                is_encodable_mov(dest, &X86Operand::Imm(imm_val))
             }
+            _ if !fits_imm32 => false,
             X86Instr::Add(_, X86Operand::Reg(r)) if load_reg.same_physical(r) => true,
             X86Instr::Sub(_, X86Operand::Reg(r)) if load_reg.same_physical(r) => true,
             X86Instr::Cmp(_, X86Operand::Reg(r)) if load_reg.same_physical(r) => true,
@@ -780,7 +808,8 @@ fn rule_mov_add_mov_to_lea(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
 }
 
 /// Pattern: mov eax, DWORD PTR [addr]; movsx rax, eax → movsxd rax, DWORD PTR [addr]
-/// Eliminates the redundant register-to-register sign extension.
+/// Eliminates the redundant register-to-register sign extension. The result
+/// stays a `Movsx`, so later rules still see which registers it touches.
 fn rule_movsx_elim(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
     if i + 1 >= instructions.len() { return false; }
     if let (
@@ -790,9 +819,7 @@ fn rule_movsx_elim(instructions: &mut Vec<X86Instr>, i: usize) -> bool {
         // Check eax1 and eax2 are the same 32-bit register
         if eax1.same_physical(eax2) && eax1.same_physical(rax1) {
             // Replace with: movsxd rax, DWORD PTR [addr]
-            instructions[i] = X86Instr::Raw(
-                format!("movsxd {}, {}", rax1.to_str(), src)
-            );
+            instructions[i] = X86Instr::Movsx(X86Operand::Reg(rax1.clone()), src.clone());
             instructions.remove(i + 1);
             return true;
         }
@@ -850,9 +877,8 @@ fn is_reg_used_before_redefined(instructions: &[X86Instr], start: usize, reg: &X
             X86Instr::Label(_) => return true, // conservative: block boundary — assume used
             X86Instr::Jmp(_) | X86Instr::Jcc(..) => return true,
             X86Instr::Ret => {
-                // ret only reads rax (return value) and rsp (stack pointer)
-                let pid = reg.physical_id();
-                return pid == 0 || pid == 4; // rax=0, rsp=4
+                // ret only reads the return registers and rsp (stack pointer)
+                return is_return_reg(reg) || reg.physical_id() == 4; // rsp=4
             }
             instr => {
                 let reads = instr_reads_reg(instr, reg);
@@ -880,8 +906,9 @@ fn is_reg_used_before_redefined(instructions: &[X86Instr], start: usize, reg: &X
 /// Check if an instruction reads from a register.
 fn instr_reads_reg(instr: &X86Instr, reg: &X86Reg) -> bool {
     match instr {
-        X86Instr::Mov(_, src) | X86Instr::Movsx(_, src) |
-        X86Instr::Movzx(_, src) => src.references_reg(reg),
+        // A store through `[reg + off]` reads the base register too.
+        X86Instr::Mov(dst, src) => src.references_reg(reg) || dst.has_base_reg(reg),
+        X86Instr::Movsx(_, src) | X86Instr::Movzx(_, src) => src.references_reg(reg),
         X86Instr::Add(dst, src) | X86Instr::Sub(dst, src) |
         X86Instr::Imul(dst, src) | X86Instr::And(dst, src) |
         X86Instr::Or(dst, src) | X86Instr::Xor(dst, src) |
@@ -898,6 +925,8 @@ fn instr_reads_reg(instr: &X86Instr, reg: &X86Reg) -> bool {
             // Conservatively say they read all registers.
             true
         }
+        // Raw text (e.g. `rep movsb`) may read registers implicitly
+        X86Instr::Raw(_) => true,
         _ => false,
     }
 }
@@ -1029,6 +1058,12 @@ fn is_reg_used_after(instructions: &[X86Instr], start: usize, reg: &X86Reg) -> b
     is_reg_live_from(instructions, start, reg, &mut visited, 8)
 }
 
+/// Whether `reg` may hold part of the return value at a `ret`: RAX, or the
+/// RDX, XMM0 and XMM1 a struct or union is also returned in.
+fn is_return_reg(reg: &X86Reg) -> bool {
+    matches!(reg.physical_id(), 0 | 2 | 16 | 17)
+}
+
 /// Find the position of a label in the instruction stream.
 fn find_label_pos(instrs: &[X86Instr], label: &str) -> Option<usize> {
     instrs.iter().position(|inst| matches!(inst, X86Instr::Label(name) if name == label))
@@ -1046,7 +1081,7 @@ fn is_reg_live_from(
         return true; // conservative at depth limit
     }
     
-    let is_rax = reg.physical_id() == 0;
+    let is_return = is_return_reg(reg);
     
     for idx in start..instrs.len() {
         if !visited.insert(idx) {
@@ -1055,7 +1090,7 @@ fn is_reg_live_from(
         
         match &instrs[idx] {
             X86Instr::Label(_) => continue,
-            X86Instr::Ret => return is_rax,
+            X86Instr::Ret => return is_return,
             X86Instr::Jmp(target) => {
                 return if let Some(pos) = find_label_pos(instrs, target) {
                     is_reg_live_from(instrs, pos, reg, visited, depth - 1)
@@ -1073,26 +1108,24 @@ fn is_reg_live_from(
                 }
             }
             X86Instr::Call(_) | X86Instr::CallIndirect(_) => {
-                // Calls clobber all caller-saved registers (rax, rcx, rdx, rsi, rdi, r8-r11).
-                // If reg is caller-saved, the call redefines it, so check liveness AFTER the call.
-                // If reg is callee-saved, conservatively assume it's used.
+                // A call reads its target and the argument registers, and
+                // clobbers the caller-saved ones, so a register it only
+                // clobbers is live if it is read after the call. The pass
+                // does not know the target's convention, so that holds only
+                // for the registers both conventions clobber and neither
+                // passes arguments in: AL carries the System V vector count.
+                if instrs[idx].reads_phys_reg(reg) {
+                    return true;
+                }
+                let conventions: [&dyn CallingConvention; 2] = [&WindowsX64Convention, &SystemVConvention];
                 let pid = reg.physical_id();
-                let is_caller_saved = matches!(pid, 0 | 1 | 2 | 4 | 5 | 6 | 7 | 8 | 9);
-                if is_caller_saved {
-                    // Call clobbers this register; continue scanning after the call
-                    // to see if it's actually live after.
-                    // But first: argument registers (rdi, rsi, rdx, rcx, r8, r9) are READ by the call.
-                    // Physical IDs: rdi=5, rsi=4, rdx=2, rcx=1, r8=6, r9=7
-                    let is_arg_reg = matches!(pid, 1 | 2 | 4 | 5 | 6 | 7);
-                    if is_arg_reg {
-                        return true; // conservatively assume it's a needed argument
-                    }
-                    // Non-argument caller-saved (rax=0, r10=8, r11=9): call clobbers them
-                    // Continue scanning past the call
-                    continue;
-                } else {
-                    return true; // callee-saved: conservatively assume used
+                let is_arg_reg = pid == X86Reg::Rax.physical_id()
+                    || conventions.iter().any(|c| c.param_regs().iter().any(|r| r.physical_id() == pid));
+                let clobbered = conventions.iter().all(|c| c.caller_saved_regs().iter().any(|r| r.physical_id() == pid));
+                if is_arg_reg || !clobbered {
+                    return true;
                 }
+                continue;
             }
             instr => {
                 if instr.reads_phys_reg(reg) { return true; }
@@ -1257,6 +1290,27 @@ mod tests {
         assert!(!X86Reg::Xmm0.same_physical(&X86Reg::Xmm1));
     }
 
+    // ─── Register liveness across calls ─────────────────────────
+
+    #[test]
+    fn call_reads_argument_registers_and_al() {
+        let instrs = vec![
+            X86Instr::Call("f".to_string()),
+            X86Instr::Mov(reg(X86Reg::Rax), imm(0)),
+            X86Instr::Mov(reg(X86Reg::R11), imm(0)),
+            X86Instr::Ret,
+        ];
+        for live in [X86Reg::Rax, X86Reg::Rdi, X86Reg::Rsi, X86Reg::R9, X86Reg::Rbx, X86Reg::R12] {
+            assert!(is_reg_used_after(&instrs, 0, &live), "{:?}", live);
+        }
+        // Clobbered by the call in both conventions, and not an argument
+        assert!(!is_reg_used_after(&instrs, 0, &X86Reg::R10));
+        assert!(!is_reg_used_after(&instrs, 0, &X86Reg::R11));
+        // ...unless it holds the target of an indirect call
+        let indirect = vec![X86Instr::CallIndirect(reg(X86Reg::R10)), X86Instr::Ret];
+        assert!(is_reg_used_after(&indirect, 0, &X86Reg::R10));
+    }
+
     // ─── Pattern 1: redundant mov removal (mov reg, reg) ────────
 
     #[test]
@@ -1301,6 +1355,21 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn store_base_keeps_copy_alive() {
+        // lea r8, [rbp + rcx*4-16]; mov rax, r8; mov eax, [rax]; mov [r8], eax; ret
+        // r8 is still the store address, so the lea must not be retargeted to rax.
+        let mut instrs = vec![
+            X86Instr::Raw("lea r8, [rbp + rcx*4-16]".to_string()),
+            X86Instr::Mov(reg(X86Reg::Rax), reg(X86Reg::R8)),
+            X86Instr::Mov(reg(X86Reg::Eax), X86Operand::DwordMem(X86Reg::Rax, 0)),
+            X86Instr::Mov(X86Operand::DwordMem(X86Reg::R8, 0), reg(X86Reg::Eax)),
+            X86Instr::Ret,
+        ];
+        apply_peephole(&mut instrs);
+        assert!(matches!(&instrs[0], X86Instr::Raw(s) if s.starts_with("lea r8,")));
+    }
+
     // ─── Pattern 3: add/sub with 0 → remove ────────────────────
 
     #[test]
@@ -1478,6 +1547,21 @@ mod tests {
         ))));
     }
 
+    #[test]
+    fn immediate_forwarding_keeps_64bit_constants_in_registers() {
+        // cmp and mov-to-memory only encode a 32-bit immediate
+        let mut instrs = vec![
+            X86Instr::Mov(reg(X86Reg::R11), imm(1 << 32)),
+            X86Instr::Cmp(reg(X86Reg::Rdi), reg(X86Reg::R11)),
+            X86Instr::Mov(reg(X86Reg::Rcx), imm(-(1 << 40))),
+            X86Instr::Mov(mem(X86Reg::Rbp, -8), reg(X86Reg::Rcx)),
+            X86Instr::Ret,
+        ];
+        apply_peephole(&mut instrs);
+        assert!(instrs.iter().any(|i| matches!(i, X86Instr::Cmp(_, X86Operand::Reg(X86Reg::R11)))));
+        assert!(instrs.iter().any(|i| matches!(i, X86Instr::Mov(X86Operand::Mem(..), X86Operand::Reg(X86Reg::Rcx)))));
+    }
+
     // ─── Pattern 3b: lea + add constant folding ─────────────────
 
     #[test]
@@ -1531,6 +1615,8 @@ mod tests {
             X86Instr::Test(reg(X86Reg::Rcx), reg(X86Reg::Rcx)),
             X86Instr::Jcc("ne".to_string(), "target".to_string()),
             X86Instr::Ret,
+            X86Instr::Label("target".to_string()),
+            X86Instr::Ret,
         ];
         apply_peephole(&mut instrs);
         // Should simplify to: cmp rbx, 0; je target; ret (3 instructions)
@@ -1541,6 +1627,43 @@ mod tests {
         assert!(!instrs.iter().any(|i| matches!(i, X86Instr::Test(..))));
     }
 
+    #[test]
+    fn cmp_set_branch_keeps_flag_used_by_a_later_branch() {
+        // Another block branches on rcx again, so it must still be set
+        let mut instrs = vec![
+            X86Instr::Cmp(reg(X86Reg::Rbx), imm(0)),
+            X86Instr::Mov(reg(X86Reg::Rax), imm(0)),
+            X86Instr::Set("e".to_string(), reg(X86Reg::Al)),
+            X86Instr::Mov(reg(X86Reg::Rcx), reg(X86Reg::Rax)),
+            X86Instr::Test(reg(X86Reg::Rcx), reg(X86Reg::Rcx)),
+            X86Instr::Jcc("ne".to_string(), "target".to_string()),
+            X86Instr::Label("target".to_string()),
+            X86Instr::Test(reg(X86Reg::Rcx), reg(X86Reg::Rcx)),
+            X86Instr::Jcc("e".to_string(), "other".to_string()),
+            X86Instr::Ret,
+            X86Instr::Label("other".to_string()),
+            X86Instr::Ret,
+        ];
+        apply_peephole(&mut instrs);
+        assert!(instrs.iter().any(|i| matches!(i, X86Instr::Set(..))));
+        assert!(instrs.iter().any(|i| matches!(i, X86Instr::Mov(X86Operand::Reg(X86Reg::Rcx), _))));
+    }
+
+    #[test]
+    fn movsx_elim_keeps_a_structured_movsx() {
+        let mut instrs = vec![
+            X86Instr::Mov(reg(X86Reg::Eax), X86Operand::DwordMem(X86Reg::Rbp, -4)),
+            X86Instr::Movsx(reg(X86Reg::Rax), reg(X86Reg::Eax)),
+            X86Instr::Ret,
+        ];
+        apply_peephole(&mut instrs);
+        assert!(matches!(&instrs[0], X86Instr::Movsx(
+            X86Operand::Reg(X86Reg::Rax),
+            X86Operand::DwordMem(X86Reg::Rbp, -4)
+        )));
+        assert!(crate::x86::emit_asm(&instrs[..1]).contains("movsxd rax, DWORD PTR [rbp-4]"));
+    }
+
     // ─── is_reg_used_after cross-block ──────────────────────────
 
     #[test]
@@ -1580,6 +1703,18 @@ mod tests {
         assert!(!is_reg_used_after(&instrs, 1, &X86Reg::Rcx));
     }
 
+    #[test]
+    fn raw_string_instruction_reads_implicit_regs() {
+        let instrs = vec![
+            X86Instr::Mov(reg(X86Reg::Rcx), imm(16)),
+            X86Instr::Raw("rep stosb".to_string()),
+            X86Instr::Mov(reg(X86Reg::Rcx), imm(0)),
+            X86Instr::Ret,
+        ];
+        assert!(is_reg_used_before_redefined(&instrs, 1, &X86Reg::Rcx));
+        assert!(is_reg_used_after(&instrs, 1, &X86Reg::Rcx));
+    }
+
     // ─── LEA forwarding (Pattern 3c) ────────────────────────────
 
     #[test]
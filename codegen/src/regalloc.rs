@@ -1,11 +1,23 @@
 // Register allocation with graph coloring
-use ir::{VarId, Function as IrFunction, Instruction as IrInstruction, Terminator as IrTerminator, Operand};
+use ir::{VarId, BlockId, Cfg, Function as IrFunction, Instruction as IrInstruction, Terminator as IrTerminator, Operand};
 use std::collections::{HashMap, HashSet};
 use crate::liveness::compute_live_intervals;
+use crate::loops::{find_loops, loop_depths};
+use crate::calling_convention::{get_convention, ArgAllocator, ArgLocation};
+use model::Type;
+
+/// Loop depth beyond which references weigh no more (10^4 per reference)
+const MAX_WEIGHTED_DEPTH: u32 = 4;
+/// Times `allocate_registers_split` retries without the splits that did not pay off
+const MAX_SPLIT_ROUNDS: usize = 3;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PhysicalReg {
     Rax, Rcx, Rdx, Rbx, Rsi, Rdi, R8, R9, R10, R11, R12, R13, R14, R15,
+    // Only allocated under `-fomit-frame-pointer`
+    Rbp,
+    // XMM registers the allocator gives `float` and `double` variables
+    Xmm4, Xmm5, Xmm8, Xmm9, Xmm10, Xmm11, Xmm12, Xmm13, Xmm14, Xmm15,
 }
 
 impl PhysicalReg {
@@ -26,34 +38,70 @@ impl PhysicalReg {
             Self::R13 => X86Reg::R13,
             Self::R14 => X86Reg::R14,
             Self::R15 => X86Reg::R15,
+            Self::Rbp => X86Reg::Rbp,
+            Self::Xmm4 => X86Reg::Xmm4,
+            Self::Xmm5 => X86Reg::Xmm5,
+            Self::Xmm8 => X86Reg::Xmm8,
+            Self::Xmm9 => X86Reg::Xmm9,
+            Self::Xmm10 => X86Reg::Xmm10,
+            Self::Xmm11 => X86Reg::Xmm11,
+            Self::Xmm12 => X86Reg::Xmm12,
+            Self::Xmm13 => X86Reg::Xmm13,
+            Self::Xmm14 => X86Reg::Xmm14,
+            Self::Xmm15 => X86Reg::Xmm15,
         }
     }
+
+    /// Whether this is an XMM register, holding a `float` or `double`
+    pub fn is_xmm(&self) -> bool {
+        self.to_x86().is_xmm()
+    }
+    
+    /// The allocatable register a 64- or 32-bit GPR names, if any
+    pub fn from_x86(reg: &crate::x86::X86Reg) -> Option<PhysicalReg> {
+        use crate::x86::X86Reg;
+        Some(match reg {
+            X86Reg::Rax | X86Reg::Eax => Self::Rax,
+            X86Reg::Rcx | X86Reg::Ecx => Self::Rcx,
+            X86Reg::Rdx | X86Reg::Edx => Self::Rdx,
+            X86Reg::Rbx | X86Reg::Ebx => Self::Rbx,
+            X86Reg::Rsi | X86Reg::Esi => Self::Rsi,
+            X86Reg::Rdi | X86Reg::Edi => Self::Rdi,
+            X86Reg::R8 => Self::R8,
+            X86Reg::R9 => Self::R9,
+            X86Reg::R10 => Self::R10,
+            X86Reg::R11 => Self::R11,
+            X86Reg::R12 => Self::R12,
+            X86Reg::R13 => Self::R13,
+            X86Reg::R14 => Self::R14,
+            X86Reg::R15 => Self::R15,
+            X86Reg::Rbp | X86Reg::Ebp => Self::Rbp,
+            X86Reg::Xmm4 => Self::Xmm4,
+            X86Reg::Xmm5 => Self::Xmm5,
+            X86Reg::Xmm8 => Self::Xmm8,
+            X86Reg::Xmm9 => Self::Xmm9,
+            X86Reg::Xmm10 => Self::Xmm10,
+            X86Reg::Xmm11 => Self::Xmm11,
+            X86Reg::Xmm12 => Self::Xmm12,
+            X86Reg::Xmm13 => Self::Xmm13,
+            X86Reg::Xmm14 => Self::Xmm14,
+            X86Reg::Xmm15 => Self::Xmm15,
+            _ => return None,
+        })
+    }
+
+    fn from_x86_list(regs: &[crate::x86::X86Reg]) -> Vec<PhysicalReg> {
+        regs.iter().filter_map(Self::from_x86).collect()
+    }
     
-    // Caller-saved registers (volatile) - platform-specific
+    // Caller-saved registers (volatile), as the target's calling convention defines them
     pub fn caller_saved(target: &model::TargetConfig) -> Vec<PhysicalReg> {
-        match target.calling_convention {
-            model::CallingConvention::WindowsX64 => {
-                vec![Self::Rax, Self::Rcx, Self::Rdx, Self::R8, Self::R9, Self::R10, Self::R11]
-            }
-            model::CallingConvention::SystemV => {
-                // System V: Rax, Rcx, Rdx, Rsi, Rdi, R8-R11 are caller-saved
-                vec![Self::Rax, Self::Rcx, Self::Rdx, Self::Rsi, Self::Rdi, Self::R8, Self::R9, Self::R10, Self::R11]
-            }
-        }
+        Self::from_x86_list(get_convention(target.calling_convention).caller_saved_regs())
     }
     
-    // Callee-saved registers (non-volatile) - platform-specific
+    // Callee-saved registers (non-volatile), as the target's calling convention defines them
     pub fn callee_saved(target: &model::TargetConfig) -> Vec<PhysicalReg> {
-        match target.calling_convention {
-            model::CallingConvention::WindowsX64 => {
-                // Windows x64: Rbx, Rsi, Rdi, Rbp, R12-R15 are callee-saved
-                vec![Self::Rbx, Self::Rsi, Self::Rdi, Self::R12, Self::R13, Self::R14, Self::R15]
-            }
-            model::CallingConvention::SystemV => {
-                // System V: Rbx, Rbp, R12-R15 are callee-saved (Rsi, Rdi are caller-saved!)
-                vec![Self::Rbx, Self::R12, Self::R13, Self::R14, Self::R15]
-            }
-        }
+        Self::from_x86_list(get_convention(target.calling_convention).callee_saved_regs())
     }
     
     // All allocatable registers - platform-specific
@@ -76,6 +124,34 @@ impl PhysicalReg {
                     Self::R12, Self::R13, Self::R14, Self::R15,
                 ]
             }
+            // i386 has no R8-R15; EAX, ECX, and EDX stay scratch
+            model::CallingConvention::Cdecl => vec![Self::Rbx, Self::Rsi, Self::Rdi],
+        }
+    }
+
+    // RBP joins the allocatable registers of a function without a frame pointer
+    pub fn allocatable_for(func: &IrFunction, target: &model::TargetConfig) -> Vec<PhysicalReg> {
+        let mut regs = Self::allocatable(target);
+        if omits_frame_pointer(func, target) {
+            regs.push(Self::Rbp);
+        }
+        regs
+    }
+
+    // Allocatable XMM registers - platform-specific
+    // Only caller-saved ones, since the prologue saves no XMM registers, and
+    // none that pass arguments (codegen uses XMM0, XMM1, and XMM7 as scratch)
+    pub fn allocatable_xmm(target: &model::TargetConfig) -> Vec<PhysicalReg> {
+        match target.calling_convention {
+            // Windows x64: XMM6-XMM15 are callee-saved
+            model::CallingConvention::WindowsX64 => vec![Self::Xmm4, Self::Xmm5],
+            model::CallingConvention::SystemV => vec![
+                Self::Xmm8, Self::Xmm9, Self::Xmm10, Self::Xmm11,
+                Self::Xmm12, Self::Xmm13, Self::Xmm14, Self::Xmm15,
+            ],
+            // Every XMM register is caller-saved on i386, and the i686
+            // backend keeps floating-point values in memory
+            model::CallingConvention::Cdecl => Vec::new(),
         }
     }
 }
@@ -90,16 +166,45 @@ pub struct LiveInterval {
     pub spill_slot: Option<i32>,
 }
 
-/// allocate_registers performs graph-coloring register allocation with copy coalescing
+/// Whether `func` is generated without a frame pointer: under
+/// `-fomit-frame-pointer`, unless it has inline assembly, whose pushes move
+/// RSP and whose operands may name RBP.
+pub fn omits_frame_pointer(func: &IrFunction, target: &model::TargetConfig) -> bool {
+    target.omit_frame_pointer
+        && !func.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .any(|inst| matches!(inst, IrInstruction::InlineAsm { .. }))
+}
+
+/// allocate_registers performs graph-coloring register allocation with copy coalescing.
+/// `float` and `double` variables form a second register class, colored with
+/// XMM registers over the same interference graph.
 pub fn allocate_registers(func: &IrFunction, target: &model::TargetConfig) -> HashMap<VarId, PhysicalReg> {
+    allocate_registers_where(func, target, |_| true)
+}
+
+/// Allocate registers to the variables of `func` that `allocatable` admits;
+/// the others stay in memory without taking part in the coloring. The i686
+/// backend keeps 8-byte values out of its 32-bit registers this way.
+pub(crate) fn allocate_registers_where(
+    func: &IrFunction,
+    target: &model::TargetConfig,
+    allocatable: impl Fn(VarId) -> bool,
+) -> HashMap<VarId, PhysicalReg> {
     // 1. Compute live intervals for each variable
     let mut intervals = compute_live_intervals(func);
+    intervals.retain(|i| allocatable(i.var));
+    let float_vars = float_variables(func);
+    let (mut xmm_intervals, mut intervals): (Vec<_>, Vec<_>) =
+        intervals.into_iter().partition(|i| float_vars.contains(&i.var));
     
     // Sort intervals by var ID to be deterministic
     intervals.sort_by_key(|i| i.var);
+    xmm_intervals.sort_by_key(|i| i.var);
     
     // 2. Build interference graph
     let interference = build_interference_graph(&intervals);
+    let xmm_interference = build_interference_graph(&xmm_intervals);
     
     // 3. Collect copy hints for coalescing (maps dest -> src for Copy instructions)
     let copy_hints = collect_copy_hints(func);
@@ -114,16 +219,19 @@ pub fn allocate_registers(func: &IrFunction, target: &model::TargetConfig) -> Ha
     // 6. Identify variables that are live across function calls
     // These variables cannot use caller-saved registers
     let live_across_call = compute_live_across_call(&intervals, func);
+    let xmm_live_across_call = compute_live_across_call(&xmm_intervals, func);
     
-    // 7. Compute use counts for spill-cost heuristic
-    let use_counts = compute_use_counts(func);
+    // 7. Weigh references by loop depth for the spill-cost heuristic
+    let spill_weights = compute_spill_weights(func);
     
     // 8. Graph coloring with copy coalescing and parameter hints
-    color_graph(&mut intervals, &interference, &copy_hints, &param_hints, use_callee_saved, &live_across_call, &use_counts, target);
+    let available_regs = PhysicalReg::allocatable_for(func, target);
+    color_graph(&mut intervals, &interference, &copy_hints, &param_hints, use_callee_saved, &live_across_call, &spill_weights, &available_regs, target);
+    color_xmm(&mut xmm_intervals, &xmm_interference, &copy_hints, &xmm_live_across_call, &spill_weights, target);
     
     // 9. Build result map
     let mut reg_alloc = HashMap::new();
-    for interval in &intervals {
+    for interval in intervals.iter().chain(&xmm_intervals) {
         if let Some(reg) = interval.reg {
             reg_alloc.insert(interval.var, reg);
         }
@@ -132,7 +240,187 @@ pub fn allocate_registers(func: &IrFunction, target: &model::TargetConfig) -> Ha
     reg_alloc
 }
 
-fn should_use_callee_saved(func: &IrFunction, target: &model::TargetConfig) -> bool {
+/// Allocate registers to `func` with `allocate_registers`, then split the live
+/// ranges of values left in memory around the loops that read them and
+/// allocate again. A copy that still gets no register only adds a move, so
+/// its split is dropped and the rest retried. Returns the function to emit,
+/// when splitting rewrote it, and its allocation.
+pub(crate) fn allocate_registers_split(func: &IrFunction, target: &model::TargetConfig) -> (Option<IrFunction>, HashMap<VarId, PhysicalReg>) {
+    let reg_alloc = allocate_registers(func, target);
+    let mut skip: HashSet<(BlockId, VarId)> = HashSet::new();
+    for _ in 0..MAX_SPLIT_ROUNDS {
+        let Some((split, splits)) = split_live_ranges(func, &reg_alloc, &skip) else { break };
+        let split_alloc = allocate_registers(&split, target);
+        let spilled: Vec<(BlockId, VarId)> = splits.iter()
+            .filter(|(_, _, copy)| !split_alloc.contains_key(copy))
+            .map(|&(header, var, _)| (header, var))
+            .collect();
+        if spilled.is_empty() {
+            return (Some(split), split_alloc);
+        }
+        skip.extend(spilled);
+    }
+    (None, reg_alloc)
+}
+
+/// Split the live ranges of values that `reg_alloc` left in memory around the
+/// loops that use them, except the (loop header, value) pairs in `skip`.
+/// Inside a call-free loop that reads such a value but never writes it, the
+/// reads go to a copy made at the end of the preheader, which is free to take
+/// a caller-saved register for the length of the loop while the original
+/// stays in its stack slot across calls. Returns the rewritten function and
+/// each split as (loop header, value, copy), or `None` when nothing was split.
+fn split_live_ranges(
+    func: &IrFunction,
+    reg_alloc: &HashMap<VarId, PhysicalReg>,
+    skip: &HashSet<(BlockId, VarId)>,
+) -> Option<(IrFunction, Vec<(BlockId, VarId, VarId)>)> {
+    let instructions = || func.blocks.iter().flat_map(|b| &b.instructions);
+    // SIMD and inline asm pick registers of their own, and phis have no
+    // single place to put a copy
+    if instructions().any(|inst| matches!(inst, IrInstruction::Simd { .. } | IrInstruction::InlineAsm { .. } | IrInstruction::Phi { .. })) {
+        return None;
+    }
+    let cfg = Cfg::new(func);
+    let loops = find_loops(func, &cfg);
+    if loops.is_empty() {
+        return None;
+    }
+
+    let alloca_vars: HashSet<VarId> = instructions()
+        .filter_map(|inst| match inst {
+            IrInstruction::Alloca { dest, .. } => Some(*dest),
+            _ => None,
+        })
+        .collect();
+    let mut types: HashMap<VarId, Type> = func.var_types.clone();
+    for (ty, var) in &func.params {
+        types.insert(*var, ty.clone());
+    }
+    let mut next_var = types.keys().copied()
+        .chain(instructions().flat_map(|inst| inst.dests()))
+        .map(|v| v.0 + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut split = func.clone();
+    let mut copies: HashSet<VarId> = HashSet::new();
+    let mut splits: Vec<(BlockId, VarId, VarId)> = Vec::new();
+    for l in &loops {
+        let Some(preheader) = l.preheader(func, &cfg) else { continue };
+        let body: Vec<usize> = split.blocks.iter().enumerate()
+            .filter(|(_, b)| l.body.contains(&b.id))
+            .map(|(i, _)| i)
+            .collect();
+        let body_instructions = || body.iter().flat_map(|&i| &split.blocks[i].instructions);
+        if body_instructions().any(|inst| matches!(inst, IrInstruction::Call { .. } | IrInstruction::IndirectCall { .. })) {
+            continue;
+        }
+        let defined: HashSet<VarId> = body_instructions().flat_map(|inst| inst.dests()).collect();
+        let mut used: Vec<VarId> = Vec::new();
+        for &i in &body {
+            let block = &split.blocks[i];
+            for inst in &block.instructions {
+                inst.for_each_use(|v| used.push(v));
+            }
+            if let Some(Operand::Var(v)) = terminator_operand(&block.terminator) {
+                used.push(*v);
+            }
+        }
+        used.sort();
+        used.dedup();
+        used.retain(|v| {
+            !defined.contains(v) && !alloca_vars.contains(v) && !copies.contains(v) && !reg_alloc.contains_key(v)
+                && !skip.contains(&(l.header, *v))
+                && types.get(v).is_some_and(|ty| matches!(ty,
+                    Type::Int | Type::UnsignedInt | Type::Char | Type::UnsignedChar | Type::Short | Type::UnsignedShort
+                    | Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong | Type::Bool
+                    | Type::Enum(_) | Type::Pointer(..) | Type::Float | Type::Double))
+        });
+        if used.is_empty() {
+            continue;
+        }
+
+        let mut renames: HashMap<VarId, VarId> = HashMap::new();
+        let Some(pre) = split.blocks.iter_mut().find(|b| b.id == preheader) else { continue };
+        pre.spans.resize(pre.instructions.len(), pre.terminator_span);
+        for v in used {
+            let copy = VarId(next_var);
+            next_var += 1;
+            pre.instructions.push(IrInstruction::Copy { dest: copy, src: Operand::Var(v) });
+            pre.spans.push(pre.terminator_span);
+            split.var_types.insert(copy, types[&v].clone());
+            copies.insert(copy);
+            splits.push((l.header, v, copy));
+            renames.insert(v, copy);
+        }
+        let rename = |op: &mut Operand| {
+            if let Operand::Var(v) = op && let Some(copy) = renames.get(v) {
+                *v = *copy;
+            }
+        };
+        for &i in &body {
+            let block = &mut split.blocks[i];
+            for inst in &mut block.instructions {
+                inst.for_each_operand_mut(rename);
+            }
+            if let Some(op) = terminator_operand_mut(&mut block.terminator) {
+                rename(op);
+            }
+        }
+    }
+
+    (!splits.is_empty()).then_some((split, splits))
+}
+
+/// The operand a terminator reads, if any.
+fn terminator_operand(term: &IrTerminator) -> Option<&Operand> {
+    match term {
+        IrTerminator::CondBr { cond, .. } | IrTerminator::IndirectBr { target: cond } | IrTerminator::Switch { value: cond, .. } => Some(cond),
+        IrTerminator::Ret(value) => value.as_ref(),
+        IrTerminator::Br(_) | IrTerminator::Unreachable => None,
+    }
+}
+
+fn terminator_operand_mut(term: &mut IrTerminator) -> Option<&mut Operand> {
+    match term {
+        IrTerminator::CondBr { cond, .. } | IrTerminator::IndirectBr { target: cond } | IrTerminator::Switch { value: cond, .. } => Some(cond),
+        IrTerminator::Ret(value) => value.as_mut(),
+        IrTerminator::Br(_) | IrTerminator::Unreachable => None,
+    }
+}
+
+/// The `float` and `double` variables of `func`, which only XMM registers
+/// can hold. Those that codegen moves through a GPR or a condition test get
+/// no register at all, and neither does any variable of a function with SIMD
+/// code or inline assembly, which pick XMM registers of their own.
+fn float_variables(func: &IrFunction) -> HashSet<VarId> {
+    let mut vars: HashSet<VarId> = func.var_types.iter()
+        .map(|(var, ty)| (ty, var))
+        .chain(func.params.iter().map(|(ty, var)| (ty, var)))
+        .filter(|(ty, _)| matches!(ty, Type::Float | Type::Double))
+        .map(|(_, var)| *var)
+        .collect();
+    let mut excluded = HashSet::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            match inst {
+                IrInstruction::Simd { .. } | IrInstruction::InlineAsm { .. } => return HashSet::new(),
+                IrInstruction::VaArg { dest, .. } => {
+                    excluded.insert(*dest);
+                }
+                _ => {}
+            }
+        }
+        if let IrTerminator::CondBr { cond: Operand::Var(v), .. } = &block.terminator {
+            excluded.insert(*v);
+        }
+    }
+    vars.retain(|v| !excluded.contains(v));
+    vars
+}
+
+pub(crate) fn should_use_callee_saved(func: &IrFunction, target: &model::TargetConfig) -> bool {
     // Heuristic: for small functions with few blocks and instructions,
     // prefer to spill to stack rather than use callee-saved registers
     // This avoids the push/pop overhead which can be significant
@@ -148,7 +436,7 @@ fn should_use_callee_saved(func: &IrFunction, target: &model::TargetConfig) -> b
     num_blocks > 5 || num_instructions > 30
 }
 
-fn collect_copy_hints(func: &IrFunction) -> HashMap<VarId, VarId> {
+pub(crate) fn collect_copy_hints(func: &IrFunction) -> HashMap<VarId, VarId> {
     let mut hints = HashMap::new();
     
     // Collect copy instructions where src is a Var (dest = src)
@@ -166,61 +454,57 @@ fn collect_copy_hints(func: &IrFunction) -> HashMap<VarId, VarId> {
     hints
 }
 
-/// Count how many times each variable is referenced (used or defined) in the IR.
-/// Variables with more references have higher spill cost and should be prioritized
-/// for register allocation.
-fn compute_use_counts(func: &IrFunction) -> HashMap<VarId, usize> {
+/// Weigh how costly each variable would be to keep in memory: every
+/// reference (use or def) counts 10^depth, where depth is the number of loops
+/// around it, so one use in an inner loop outweighs many in straight-line code.
+/// Variables with more weight are prioritized for register allocation.
+pub(crate) fn compute_spill_weights(func: &IrFunction) -> HashMap<VarId, usize> {
     use crate::liveness::visit_operands;
-    let mut counts: HashMap<VarId, usize> = HashMap::new();
+    let depths = loop_depths(&find_loops(func, &Cfg::new(func)));
+    let mut weights: HashMap<VarId, usize> = HashMap::new();
     
     for block in &func.blocks {
+        // Cap the depth so deep nests cannot overflow the cost arithmetic
+        let weight = 10usize.pow(depths.get(&block.id).copied().unwrap_or(0).min(MAX_WEIGHTED_DEPTH));
         for inst in &block.instructions {
             // Count uses (via accessor)
             visit_operands(inst, |var| {
-                *counts.entry(var).or_insert(0) += 1;
+                *weights.entry(var).or_insert(0) += weight;
             });
             
             // Count defs (via accessor)
             if let Some(var) = inst.dest() {
-                *counts.entry(var).or_insert(0) += 1;
+                *weights.entry(var).or_insert(0) += weight;
             }
         }
         
         // Count terminator uses
         match &block.terminator {
-            IrTerminator::CondBr { cond, .. } => {
+            IrTerminator::CondBr { cond, .. } | IrTerminator::IndirectBr { target: cond } | IrTerminator::Switch { value: cond, .. } => {
                 if let Operand::Var(v) = cond {
-                    *counts.entry(*v).or_insert(0) += 1;
+                    *weights.entry(*v).or_insert(0) += weight;
                 }
             }
             IrTerminator::Ret(Some(Operand::Var(v))) => {
-                *counts.entry(*v).or_insert(0) += 1;
+                *weights.entry(*v).or_insert(0) += weight;
             }
             _ => {}
         }
     }
     
-    counts
+    weights
 }
 
 /// Build hints for parameter variables to prefer their incoming registers
 fn build_param_hints(func: &IrFunction, target: &model::TargetConfig) -> HashMap<VarId, PhysicalReg> {
     let mut hints = HashMap::new();
+    let convention = get_convention(target.calling_convention);
+    let param_physical_regs = PhysicalReg::from_x86_list(convention.param_regs());
     
-    // Map System V parameter registers to PhysicalReg
-    let param_physical_regs: Vec<PhysicalReg> = match target.calling_convention {
-        model::CallingConvention::WindowsX64 => {
-            vec![PhysicalReg::Rcx, PhysicalReg::Rdx, PhysicalReg::R8, PhysicalReg::R9]
-        }
-        model::CallingConvention::SystemV => {
-            vec![PhysicalReg::Rdi, PhysicalReg::Rsi, PhysicalReg::Rdx, 
-                 PhysicalReg::Rcx, PhysicalReg::R8, PhysicalReg::R9]
-        }
-    };
-    
-    // Hint each parameter to prefer its incoming register
-    for (i, (_, var_id)) in func.params.iter().enumerate() {
-        if i < param_physical_regs.len() {
+    // Hint each integer parameter to prefer its incoming register
+    let mut args = ArgAllocator::new(convention.as_ref());
+    for (ty, var_id) in &func.params {
+        if let ArgLocation::Reg(i) = args.next(matches!(ty, Type::Float | Type::Double)) {
             hints.insert(*var_id, param_physical_regs[i]);
         }
     }
@@ -228,7 +512,7 @@ fn build_param_hints(func: &IrFunction, target: &model::TargetConfig) -> HashMap
     hints
 }
 
-fn build_interference_graph(intervals: &[LiveInterval]) -> HashMap<VarId, HashSet<VarId>> {
+pub(crate) fn build_interference_graph(intervals: &[LiveInterval]) -> HashMap<VarId, HashSet<VarId>> {
     let mut graph: HashMap<VarId, HashSet<VarId>> = HashMap::new();
     
     // Two variables interfere if their live intervals overlap
@@ -250,7 +534,7 @@ fn build_interference_graph(intervals: &[LiveInterval]) -> HashMap<VarId, HashSe
 
 /// Compute which variables are live across function calls
 /// These variables cannot be allocated to caller-saved registers
-fn compute_live_across_call(intervals: &[LiveInterval], func: &IrFunction) -> HashSet<VarId> {
+pub(crate) fn compute_live_across_call(intervals: &[LiveInterval], func: &IrFunction) -> HashSet<VarId> {
     let mut live_across_call = HashSet::new();
     let mut position = 0;
     
@@ -260,18 +544,20 @@ fn compute_live_across_call(intervals: &[LiveInterval], func: &IrFunction) -> Ha
     let mut call_positions = Vec::new();
     for block in &func.blocks {
         for inst in &block.instructions {
-            if matches!(inst, IrInstruction::Call { .. } | IrInstruction::IndirectCall { .. }) {
-                call_positions.push(position);
+            if let IrInstruction::Call { dest, .. } | IrInstruction::IndirectCall { dest, .. } = inst {
+                call_positions.push((position, *dest));
             }
             position += 1;
         }
         position += 1; // account for terminator (matching compute_live_intervals)
     }
     
-    // Mark variables whose live ranges span any call position
+    // Mark variables whose live ranges span any call position. A range that
+    // starts at the call belongs to its result, unless the value was already
+    // live on entry to the block the call opens.
     for interval in intervals {
-        for &call_pos in &call_positions {
-            if interval.start < call_pos && call_pos < interval.end {
+        for &(call_pos, dest) in &call_positions {
+            if interval.start <= call_pos && call_pos < interval.end && dest != Some(interval.var) {
                 live_across_call.insert(interval.var);
                 break;
             }
@@ -281,24 +567,27 @@ fn compute_live_across_call(intervals: &[LiveInterval], func: &IrFunction) -> Ha
     live_across_call
 }
 
-fn color_graph(intervals: &mut [LiveInterval], interference: &HashMap<VarId, HashSet<VarId>>, copy_hints: &HashMap<VarId, VarId>, param_hints: &HashMap<VarId, PhysicalReg>, use_callee_saved: bool, live_across_call: &HashSet<VarId>, use_counts: &HashMap<VarId, usize>, target: &model::TargetConfig) {
+/// Sort intervals so the most expensive to spill come first.
+pub(crate) fn sort_by_spill_cost(intervals: &mut [LiveInterval], spill_weights: &HashMap<VarId, usize>) {
     // Sort by spill cost: HIGH spill cost first → gets a register first.
-    // Spill cost = use_count * 1000 / interval_length.
-    // Variables with many uses relative to their interval length are expensive to spill
-    // (they'd require frequent loads/stores). Long-lived loop counters with many uses
-    // get priority over short-lived temporaries used once.
+    // Spill cost = spill_weight * 1000 / interval_length.
+    // Variables referenced often, and in deep loops, relative to their interval
+    // length are expensive to spill (they'd require frequent loads/stores). Loop
+    // counters get priority over long-lived values only touched outside loops.
     intervals.sort_by(|a, b| {
         let len_a = (a.end - a.start).max(1) as u64;
         let len_b = (b.end - b.start).max(1) as u64;
-        let uses_a = *use_counts.get(&a.var).unwrap_or(&1) as u64;
-        let uses_b = *use_counts.get(&b.var).unwrap_or(&1) as u64;
-        let cost_a = uses_a * 1000 / len_a;
-        let cost_b = uses_b * 1000 / len_b;
+        let weight_a = *spill_weights.get(&a.var).unwrap_or(&1) as u64;
+        let weight_b = *spill_weights.get(&b.var).unwrap_or(&1) as u64;
+        let cost_a = weight_a * 1000 / len_a;
+        let cost_b = weight_b * 1000 / len_b;
         // Descending: highest spill cost first (most important to keep in register)
         cost_b.cmp(&cost_a).then_with(|| b.end.cmp(&a.end))
     });
-    
-    let available_regs = PhysicalReg::allocatable(target);
+}
+
+fn color_graph(intervals: &mut [LiveInterval], interference: &HashMap<VarId, HashSet<VarId>>, copy_hints: &HashMap<VarId, VarId>, param_hints: &HashMap<VarId, PhysicalReg>, use_callee_saved: bool, live_across_call: &HashSet<VarId>, spill_weights: &HashMap<VarId, usize>, available_regs: &[PhysicalReg], target: &model::TargetConfig) {
+    sort_by_spill_cost(intervals, spill_weights);
     
     // Build a map of var -> register for already colored intervals
     let mut var_colors: HashMap<VarId, PhysicalReg> = HashMap::new();
@@ -384,6 +673,33 @@ fn color_graph(intervals: &mut [LiveInterval], interference: &HashMap<VarId, Has
     }
 }
 
+/// Color `float`/`double` intervals with XMM registers: copy hint first, then
+/// the first free register. Every allocatable XMM register is caller-saved,
+/// so a variable live across a call stays in its stack slot.
+fn color_xmm(intervals: &mut [LiveInterval], interference: &HashMap<VarId, HashSet<VarId>>, copy_hints: &HashMap<VarId, VarId>, live_across_call: &HashSet<VarId>, spill_weights: &HashMap<VarId, usize>, target: &model::TargetConfig) {
+    sort_by_spill_cost(intervals, spill_weights);
+    let available_regs = PhysicalReg::allocatable_xmm(target);
+    let mut var_colors: HashMap<VarId, PhysicalReg> = HashMap::new();
+
+    for interval in intervals.iter_mut() {
+        let var = interval.var;
+        if live_across_call.contains(&var) {
+            continue;
+        }
+        let used_colors: HashSet<PhysicalReg> = interference.get(&var)
+            .map(|neighbors| neighbors.iter().filter_map(|n| var_colors.get(n).copied()).collect())
+            .unwrap_or_default();
+        let hint = copy_hints.get(&var)
+            .and_then(|src| var_colors.get(src))
+            .filter(|reg| !used_colors.contains(reg));
+        interval.reg = hint.copied()
+            .or_else(|| available_regs.iter().find(|reg| !used_colors.contains(reg)).copied());
+        if let Some(reg) = interval.reg {
+            var_colors.insert(var, reg);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -445,6 +761,168 @@ mod tests {
         assert!(regs.contains(&PhysicalReg::R8));
     }
 
+    #[test]
+    fn allocatable_xmm_are_caller_saved_and_not_arguments() {
+        for calling_convention in [model::CallingConvention::SystemV, model::CallingConvention::WindowsX64] {
+            let target = model::TargetConfig { calling_convention, ..model::TargetConfig::host() };
+            let convention = get_convention(target.calling_convention);
+            for reg in PhysicalReg::allocatable_xmm(&target) {
+                assert!(reg.is_xmm());
+                assert!(!convention.float_param_regs().contains(&reg.to_x86()));
+                assert!(!matches!(reg.to_x86(), crate::x86::X86Reg::Xmm0 | crate::x86::X86Reg::Xmm1 | crate::x86::X86Reg::Xmm7));
+            }
+        }
+    }
+
+    // ─── XMM register class ─────────────────────────────────────
+
+    fn float_function(instructions: Vec<IrInstruction>, ret: Operand) -> IrFunction {
+        let (label_addrs, labels) = IrFunction::default_meta();
+        IrFunction {
+            name: "f".to_string(),
+            return_type: Type::Double,
+            params: vec![(Type::Double, vid(0)), (Type::Int, vid(1))],
+            blocks: vec![ir::BasicBlock {
+                id: ir::BlockId(0),
+                instructions,
+                terminator: IrTerminator::Ret(Some(ret)),
+                is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: model::Span::default(),
+            }],
+            entry_block: ir::BlockId(0),
+            var_types: HashMap::from([(vid(2), Type::Double), (vid(3), Type::Int), (vid(4), Type::Double)]),
+            attributes: Vec::new(),
+            is_static: false,
+            label_addrs,
+            labels,
+            local_names: HashMap::new(),
+            span: model::Span::default(),
+        }
+    }
+
+    fn system_v() -> model::TargetConfig {
+        model::TargetConfig { calling_convention: model::CallingConvention::SystemV, ..model::TargetConfig::host() }
+    }
+
+    #[test]
+    fn float_variables_take_xmm_registers() {
+        let func = float_function(vec![
+            IrInstruction::FloatBinary { dest: vid(2), op: model::BinaryOp::Add, left: Operand::Var(vid(0)), right: Operand::Var(vid(0)) },
+            IrInstruction::Binary { dest: vid(3), op: model::BinaryOp::Add, left: Operand::Var(vid(1)), right: Operand::Constant(1) },
+            IrInstruction::Call { dest: None, name: "g".to_string(), args: vec![Operand::Var(vid(3))], vararg_types: vec![] },
+            IrInstruction::FloatBinary { dest: vid(4), op: model::BinaryOp::Mul, left: Operand::Var(vid(0)), right: Operand::Var(vid(0)) },
+            IrInstruction::FloatBinary { dest: vid(2), op: model::BinaryOp::Add, left: Operand::Var(vid(4)), right: Operand::Var(vid(4)) },
+        ], Operand::Var(vid(2)));
+        let alloc = allocate_registers(&func, &system_v());
+        assert!(alloc[&vid(4)].is_xmm());
+        assert!(!alloc[&vid(1)].is_xmm());
+        // The parameter lives across the call, which clobbers every XMM register
+        assert!(!alloc.contains_key(&vid(0)));
+    }
+
+    #[test]
+    fn float_variables_stay_in_memory_beside_simd() {
+        let func = float_function(vec![
+            IrInstruction::FloatBinary { dest: vid(2), op: model::BinaryOp::Add, left: Operand::Var(vid(0)), right: Operand::Var(vid(0)) },
+            IrInstruction::InlineAsm {
+                template: String::new(), outputs: Vec::new(), inputs: Vec::new(),
+                output_constraints: Vec::new(), input_constraints: Vec::new(), clobbers: Vec::new(), is_volatile: true,
+            },
+        ], Operand::Var(vid(2)));
+        let alloc = allocate_registers(&func, &system_v());
+        assert!(alloc.values().all(|reg| !reg.is_xmm()));
+    }
+
+    #[test]
+    fn rbp_is_allocatable_only_without_a_frame_pointer() {
+        let plain = float_function(Vec::new(), Operand::Var(vid(0)));
+        let with_asm = float_function(vec![IrInstruction::InlineAsm {
+            template: String::new(), outputs: Vec::new(), inputs: Vec::new(),
+            output_constraints: Vec::new(), input_constraints: Vec::new(), clobbers: Vec::new(), is_volatile: true,
+        }], Operand::Var(vid(0)));
+        let omit = model::TargetConfig { omit_frame_pointer: true, ..system_v() };
+        assert!(!PhysicalReg::allocatable_for(&plain, &system_v()).contains(&PhysicalReg::Rbp));
+        assert!(PhysicalReg::allocatable_for(&plain, &omit).contains(&PhysicalReg::Rbp));
+        // Inline assembly keeps the frame pointer
+        assert!(!PhysicalReg::allocatable_for(&with_asm, &omit).contains(&PhysicalReg::Rbp));
+        assert!(PhysicalReg::callee_saved(&omit).contains(&PhysicalReg::Rbp));
+    }
+
+    #[test]
+    fn a_call_opening_a_block_clobbers_values_live_into_it() {
+        let func = float_function(vec![
+            IrInstruction::Call { dest: None, name: "g".to_string(), args: vec![], vararg_types: vec![] },
+            IrInstruction::Binary { dest: vid(3), op: model::BinaryOp::Add, left: Operand::Var(vid(1)), right: Operand::Constant(1) },
+        ], Operand::Var(vid(0)));
+        let alloc = allocate_registers(&func, &system_v());
+        assert!(alloc.get(&vid(1)).is_none_or(|reg| PhysicalReg::callee_saved(&system_v()).contains(reg)));
+    }
+
+    // ─── Loops: spill weights and live range splitting ──────────
+
+    /// `s` (v0) lives across a call, then `for (i = 0; i < n; i++) acc += s;`
+    fn call_then_loop() -> IrFunction {
+        let block = |id: usize, instructions: Vec<IrInstruction>, terminator: IrTerminator| ir::BasicBlock {
+            id: ir::BlockId(id),
+            instructions,
+            terminator,
+            is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: model::Span::default(),
+        };
+        let mut func = float_function(Vec::new(), Operand::Var(vid(4)));
+        func.blocks = vec![
+            block(0, vec![
+                IrInstruction::Call { dest: None, name: "g".to_string(), args: vec![], vararg_types: vec![] },
+                IrInstruction::Copy { dest: vid(3), src: Operand::Constant(0) },
+                IrInstruction::Copy { dest: vid(4), src: Operand::Var(vid(0)) },
+            ], IrTerminator::Br(ir::BlockId(1))),
+            block(1, vec![
+                IrInstruction::Binary { dest: vid(5), op: model::BinaryOp::Less, left: Operand::Var(vid(3)), right: Operand::Var(vid(1)) },
+            ], IrTerminator::cond_br(Operand::Var(vid(5)), ir::BlockId(2), ir::BlockId(3))),
+            block(2, vec![
+                IrInstruction::FloatBinary { dest: vid(4), op: model::BinaryOp::Add, left: Operand::Var(vid(4)), right: Operand::Var(vid(0)) },
+                IrInstruction::Binary { dest: vid(3), op: model::BinaryOp::Add, left: Operand::Var(vid(3)), right: Operand::Constant(1) },
+            ], IrTerminator::Br(ir::BlockId(1))),
+            block(3, vec![
+                IrInstruction::Call { dest: None, name: "g".to_string(), args: vec![], vararg_types: vec![] },
+            ], IrTerminator::Ret(Some(Operand::Var(vid(4))))),
+        ];
+        func.var_types.insert(vid(5), Type::Int);
+        func
+    }
+
+    #[test]
+    fn references_in_loops_weigh_more() {
+        let weights = compute_spill_weights(&call_then_loop());
+        // The counter: defined once outside the loop, then read and written in it
+        assert_eq!(weights[&vid(3)], 1 + 3 * 10);
+        // The condition, defined in the header and read by its branch
+        assert_eq!(weights[&vid(5)], 2 * 10);
+    }
+
+    #[test]
+    fn values_spilled_across_calls_get_a_register_in_call_free_loops() {
+        let func = call_then_loop();
+        // XMM registers do not survive calls, so the parameter stays in memory
+        assert!(!allocate_registers(&func, &system_v()).contains_key(&vid(0)));
+
+        let (split, alloc) = allocate_registers_split(&func, &system_v());
+        let split = split.expect("the loop reads the spilled parameter");
+        let Some(IrInstruction::Copy { dest: copy, src: Operand::Var(src) }) = split.blocks[0].instructions.last() else {
+            panic!("expected a copy at the end of the preheader");
+        };
+        assert_eq!(*src, vid(0));
+        assert!(alloc[copy].is_xmm());
+        assert_eq!(split.var_types[copy], Type::Double);
+        let IrInstruction::FloatBinary { right, .. } = &split.blocks[2].instructions[0] else { unreachable!() };
+        assert_eq!(*right, Operand::Var(*copy));
+        // Outside the loop the value is still read from its stack slot
+        assert!(!alloc.contains_key(&vid(0)));
+        assert!(matches!(&split.blocks[0].instructions[2], IrInstruction::Copy { src: Operand::Var(v), .. } if *v == vid(0)));
+    }
+
     // ─── Interference graph ─────────────────────────────────────
 
     #[test]
@@ -0,0 +1,275 @@
+// LP64D argument and return value classification
+//
+// Scalars take the next integer register a0–a7, or for named float and
+// double arguments the next float register fa0–fa7, falling back to an
+// integer register and then the stack. Variadic arguments always use the
+// integer convention. An aggregate of up to 16 bytes made of one or two
+// floating-point fields (or one float and one integer field) is passed as
+// if those fields were separate arguments, when registers remain; other
+// aggregates of up to 16 bytes go in one or two integer registers, possibly
+// split between a7 and the stack, and larger ones by reference.
+use model::{Type, TypeLayout};
+
+/// Where an argument, or one piece of it, is passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Loc {
+    /// Integer argument register `a{n}`
+    Int(u8),
+    /// Float argument register `fa{n}`
+    Float(u8),
+    /// Byte offset into the stack argument area
+    Stack(i32),
+}
+
+/// One register-sized piece of an aggregate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Part {
+    /// Byte offset of the piece within the aggregate
+    pub offset: usize,
+    /// Bytes of the piece; integer-convention words are always 8
+    pub size: usize,
+    pub is_float: bool,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Passing {
+    Scalar(Loc),
+    /// An aggregate of up to 16 bytes, in pieces
+    Parts(Vec<Part>),
+    /// An aggregate over 16 bytes: the address of a copy, at `Loc`
+    ByRef(Loc),
+}
+
+/// Whether values of `ty` are aggregates, which the backend handles by address.
+pub(crate) fn is_aggregate(ty: &Type) -> bool {
+    matches!(ty, Type::Struct(_) | Type::Union(_) | Type::Complex(_))
+}
+
+/// Assigns argument locations in order, like `calling_convention::ArgAllocator`.
+pub(crate) struct ArgAllocator {
+    next_int: u8,
+    next_float: u8,
+    stack: i32,
+}
+
+impl ArgAllocator {
+    pub fn new() -> Self {
+        Self { next_int: 0, next_float: 0, stack: 0 }
+    }
+
+    /// Skip a0, which holds the address a large aggregate is returned to.
+    pub fn with_return_pointer() -> Self {
+        Self { next_int: 1, ..Self::new() }
+    }
+
+    /// Bytes of stack arguments assigned so far.
+    pub fn stack_size(&self) -> i32 {
+        self.stack
+    }
+
+    /// Integer registers assigned so far.
+    pub fn int_regs_used(&self) -> u8 {
+        self.next_int
+    }
+
+    fn int_loc(&mut self) -> Loc {
+        if self.next_int < 8 {
+            self.next_int += 1;
+            Loc::Int(self.next_int - 1)
+        } else {
+            self.stack += 8;
+            Loc::Stack(self.stack - 8)
+        }
+    }
+
+    fn float_loc(&mut self) -> Option<Loc> {
+        (self.next_float < 8).then(|| {
+            self.next_float += 1;
+            Loc::Float(self.next_float - 1)
+        })
+    }
+
+    /// Classify the next argument, of type `ty`. `variadic` is set for the
+    /// arguments a `...` receives.
+    pub fn classify(&mut self, layout: &TypeLayout, ty: &Type, variadic: bool) -> Passing {
+        if !is_aggregate(ty) {
+            let is_float = matches!(ty, Type::Float | Type::Double);
+            let loc = if is_float && !variadic { self.float_loc() } else { None };
+            return Passing::Scalar(loc.unwrap_or_else(|| self.int_loc()));
+        }
+        let size = layout.size_of(ty);
+        if size > 16 {
+            return Passing::ByRef(self.int_loc());
+        }
+        if !variadic && let Some(parts) = self.float_parts(layout, ty) {
+            return Passing::Parts(parts);
+        }
+        Passing::Parts((0..size.div_ceil(8)).map(|word| Part {
+            offset: word * 8,
+            size: 8,
+            is_float: false,
+            loc: self.int_loc(),
+        }).collect())
+    }
+
+    /// The pieces of an aggregate passed under the floating-point calling
+    /// convention, if it qualifies and enough registers are free.
+    fn float_parts(&mut self, layout: &TypeLayout, ty: &Type) -> Option<Vec<Part>> {
+        let mut fields = Vec::new();
+        if !flatten(layout, ty, 0, &mut fields) {
+            return None;
+        }
+        let floats = fields.iter().filter(|(_, ty)| is_float(ty)).count();
+        let ints = fields.len() - floats;
+        let fits = match (floats, ints) {
+            (1, 0) => self.next_float < 8,
+            (2, 0) => self.next_float < 7,
+            (1, 1) => self.next_float < 8 && self.next_int < 8,
+            _ => false,
+        };
+        if !fits {
+            return None;
+        }
+        Some(fields.iter().map(|(offset, ty)| Part {
+            offset: *offset,
+            size: layout.size_of(ty),
+            is_float: is_float(ty),
+            loc: if is_float(ty) { Loc::Float(self.take_float()) } else { self.int_loc() },
+        }).collect())
+    }
+
+    fn take_float(&mut self) -> u8 {
+        self.next_float += 1;
+        self.next_float - 1
+    }
+}
+
+/// How a function returns a value of type `ty`: in a0/a1 and fa0/fa1, or
+/// for aggregates over 16 bytes through the address the caller passes in a0.
+pub(crate) fn classify_return(layout: &TypeLayout, ty: &Type) -> Passing {
+    match ArgAllocator::new().classify(layout, ty, false) {
+        Passing::ByRef(_) => Passing::ByRef(Loc::Int(0)),
+        passing => passing,
+    }
+}
+
+fn is_float(ty: &Type) -> bool {
+    matches!(ty, Type::Float | Type::Double)
+}
+
+/// Collect the scalar fields of `ty` at `offset` into `fields`. Returns false
+/// if `ty` cannot go in float registers: a union, a bit-field, an array of
+/// more than two elements, or more than two scalars in all.
+fn flatten(layout: &TypeLayout, ty: &Type, offset: usize, fields: &mut Vec<(usize, Type)>) -> bool {
+    match ty {
+        Type::Struct(name) => {
+            let Some(s_def) = layout.structs.get(name) else { return false };
+            let placed = layout.struct_layout(s_def);
+            s_def.fields.iter().zip(&placed.fields).all(|(field, place)| {
+                place.bitfield.is_none()
+                    && flatten(layout, &field.field_type, offset + place.offset, fields)
+            })
+        }
+        Type::Complex(elem) => {
+            let size = layout.size_of(elem);
+            flatten(layout, elem, offset, fields) && flatten(layout, elem, offset + size, fields)
+        }
+        Type::Array(elem, len) => {
+            let size = layout.size_of(elem);
+            *len <= 2 && (0..*len).all(|i| flatten(layout, elem, offset + i * size, fields))
+        }
+        Type::Union(_) => false,
+        ty => {
+            fields.push((offset, ty.clone()));
+            fields.len() <= 2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn field(name: &str, field_type: Type) -> model::StructField {
+        model::StructField { field_type, name: name.to_string(), bit_width: None }
+    }
+
+    fn structs() -> HashMap<String, model::StructDef> {
+        let defs = [
+            ("FF", vec![field("x", Type::Float), field("y", Type::Float)]),
+            ("DI", vec![field("d", Type::Double), field("i", Type::Int)]),
+            ("II", vec![field("a", Type::Long), field("b", Type::Int)]),
+            ("Big", vec![field("a", Type::Array(Box::new(Type::Long), 3))]),
+            ("FFF", vec![field("v", Type::Array(Box::new(Type::Float), 3))]),
+        ];
+        defs.into_iter()
+            .map(|(name, fields)| (name.to_string(), model::StructDef { name: name.to_string(), fields, attributes: vec![] }))
+            .collect()
+    }
+
+    #[test]
+    fn scalars_fill_registers_then_stack() {
+        let (structs, unions) = (structs(), HashMap::new());
+        let layout = TypeLayout::new(&structs, &unions);
+        let mut args = ArgAllocator::new();
+        for n in 0..8 {
+            assert_eq!(args.classify(&layout, &Type::Int, false), Passing::Scalar(Loc::Int(n)));
+        }
+        assert_eq!(args.classify(&layout, &Type::Long, false), Passing::Scalar(Loc::Stack(0)));
+        assert_eq!(args.classify(&layout, &Type::Double, false), Passing::Scalar(Loc::Float(0)));
+        assert_eq!(args.classify(&layout, &Type::Double, true), Passing::Scalar(Loc::Stack(8)));
+        assert_eq!(args.stack_size(), 16);
+    }
+
+    #[test]
+    fn floats_spill_to_integer_registers() {
+        let (structs, unions) = (structs(), HashMap::new());
+        let layout = TypeLayout::new(&structs, &unions);
+        let mut args = ArgAllocator::new();
+        for n in 0..8 {
+            assert_eq!(args.classify(&layout, &Type::Double, false), Passing::Scalar(Loc::Float(n)));
+        }
+        assert_eq!(args.classify(&layout, &Type::Float, false), Passing::Scalar(Loc::Int(0)));
+    }
+
+    #[test]
+    fn aggregates() {
+        let (structs, unions) = (structs(), HashMap::new());
+        let layout = TypeLayout::new(&structs, &unions);
+        let mut args = ArgAllocator::new();
+        let part = |offset, size, is_float, loc| Part { offset, size, is_float, loc };
+        assert_eq!(
+            args.classify(&layout, &Type::Struct("FF".into()), false),
+            Passing::Parts(vec![part(0, 4, true, Loc::Float(0)), part(4, 4, true, Loc::Float(1))])
+        );
+        assert_eq!(
+            args.classify(&layout, &Type::Struct("DI".into()), false),
+            Passing::Parts(vec![part(0, 8, true, Loc::Float(2)), part(8, 4, false, Loc::Int(0))])
+        );
+        assert_eq!(
+            args.classify(&layout, &Type::Struct("II".into()), false),
+            Passing::Parts(vec![part(0, 8, false, Loc::Int(1)), part(8, 8, false, Loc::Int(2))])
+        );
+        assert_eq!(
+            args.classify(&layout, &Type::Struct("FFF".into()), false),
+            Passing::Parts(vec![part(0, 8, false, Loc::Int(3)), part(8, 8, false, Loc::Int(4))])
+        );
+        assert_eq!(args.classify(&layout, &Type::Struct("Big".into()), false), Passing::ByRef(Loc::Int(5)));
+        // Variadic aggregates use the integer convention
+        assert_eq!(
+            args.classify(&layout, &Type::Struct("FF".into()), true),
+            Passing::Parts(vec![part(0, 8, false, Loc::Int(6))])
+        );
+    }
+
+    #[test]
+    fn returns() {
+        let (structs, unions) = (structs(), HashMap::new());
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(classify_return(&layout, &Type::Double), Passing::Scalar(Loc::Float(0)));
+        assert_eq!(classify_return(&layout, &Type::Int), Passing::Scalar(Loc::Int(0)));
+        assert_eq!(classify_return(&layout, &Type::Struct("Big".into())), Passing::ByRef(Loc::Int(0)));
+    }
+}
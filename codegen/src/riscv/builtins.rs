@@ -0,0 +1,148 @@
+// Compiler builtins the x86-64 backend expands inline: bit counting and byte
+// swaps through libgcc, and the __sync/__atomic operations on 64-bit values
+// with the A extension
+use ir::{Operand, VarId};
+use super::Reg;
+use super::function::FunctionGenerator;
+
+impl FunctionGenerator<'_> {
+    /// Generate the call to `name` if it is a builtin; returns whether it was.
+    pub(super) fn gen_builtin(&mut self, dest: Option<VarId>, name: &str, args: &[Operand]) -> bool {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Operand::Constant(0));
+        match name {
+            // RV64GC has no bit-manipulation instructions; like GCC, call the
+            // 64-bit libgcc routines on the zero-extended operand
+            "__builtin_clz" | "__builtin_ctz" | "__builtin_popcount"
+            | "__builtin_clzl" | "__builtin_ctzl" | "__builtin_popcountl"
+            | "__builtin_clzll" | "__builtin_ctzll" | "__builtin_popcountll" => {
+                let wide = name.ends_with('l');
+                let base = name.trim_start_matches("__builtin_").trim_end_matches('l');
+                let value = self.int_operand(&arg(0), Reg::a(0));
+                self.move_reg(Reg::a(0), value, true);
+                if !wide {
+                    self.emit("slli a0, a0, 32");
+                    self.emit("srli a0, a0, 32");
+                }
+                self.emit(format!("call __{}di2", base));
+                // clz counted the 32 zero-extension bits too
+                if !wide && base == "clz" {
+                    self.emit("addiw a0, a0, -32");
+                }
+                self.builtin_result(dest);
+            }
+            "__builtin_bswap16" => {
+                let value = self.int_operand(&arg(0), Reg::T0);
+                self.emit(format!("srli t1, {}, 8", value));
+                self.emit("andi t1, t1, 255");
+                self.emit(format!("andi a0, {}, 255", value));
+                self.emit("slli a0, a0, 8");
+                self.emit("or a0, a0, t1");
+                self.builtin_result(dest);
+            }
+            "__builtin_bswap32" | "__builtin_bswap64" => {
+                let value = self.int_operand(&arg(0), Reg::a(0));
+                self.move_reg(Reg::a(0), value, true);
+                let helper = if name.ends_with("32") { "__bswapsi2" } else { "__bswapdi2" };
+                self.emit(format!("call {}", helper));
+                self.builtin_result(dest);
+            }
+            "__sync_synchronize" => self.emit("fence rw, rw"),
+            "__sync_val_compare_and_swap" | "__atomic_compare_exchange_n" => {
+                let reports_success = name == "__atomic_compare_exchange_n";
+                self.atomic_address(&arg(0));
+                let expected = self.int_operand(&arg(1), Reg::T1);
+                self.move_reg(Reg::T1, expected, true);
+                if reports_success {
+                    // `expected` points to the expected value
+                    self.emit("mv a1, t1");
+                    self.emit("ld t1, 0(a1)");
+                }
+                let desired = self.int_operand(&arg(2), Reg::T2);
+                self.move_reg(Reg::T2, desired, true);
+                let retry = self.new_label();
+                let done = self.new_label();
+                self.label(&retry);
+                self.emit("lr.d.aqrl a0, (t0)");
+                self.emit(format!("bne a0, t1, {}", done));
+                self.emit("sc.d.rl t3, t2, (t0)");
+                self.emit(format!("bnez t3, {}", retry));
+                self.label(&done);
+                if reports_success {
+                    // The value found goes back to *expected; the result is
+                    // whether it matched
+                    self.emit("sd a0, 0(a1)");
+                    self.emit("xor a0, a0, t1");
+                    self.emit("seqz a0, a0");
+                }
+                self.builtin_result(dest);
+            }
+            "__sync_lock_test_and_set" | "__atomic_exchange_n" => {
+                self.atomic_rmw("amoswap.d.aqrl", &arg(0), &arg(1), false);
+                self.builtin_result(dest);
+            }
+            "__sync_lock_release" => {
+                self.atomic_address(&arg(0));
+                self.emit("fence rw, w");
+                self.emit("sd zero, 0(t0)");
+            }
+            "__sync_fetch_and_add" | "__sync_fetch_and_sub" | "__sync_fetch_and_and"
+            | "__sync_fetch_and_or" | "__sync_fetch_and_xor"
+            | "__atomic_fetch_add" | "__atomic_fetch_sub" | "__atomic_fetch_and"
+            | "__atomic_fetch_or" | "__atomic_fetch_xor" => {
+                let op = name.rsplit('_').next().unwrap_or_default();
+                let amo = match op {
+                    "add" | "sub" => "amoadd.d.aqrl",
+                    "and" => "amoand.d.aqrl",
+                    "or" => "amoor.d.aqrl",
+                    _ => "amoxor.d.aqrl",
+                };
+                self.atomic_rmw(amo, &arg(0), &arg(1), op == "sub");
+                self.builtin_result(dest);
+            }
+            "__atomic_load_n" => {
+                self.atomic_address(&arg(0));
+                self.emit("fence rw, rw");
+                self.emit("ld a0, 0(t0)");
+                self.emit("fence r, rw");
+                self.builtin_result(dest);
+            }
+            "__atomic_store_n" => {
+                self.atomic_address(&arg(0));
+                let value = self.int_operand(&arg(1), Reg::a(0));
+                self.move_reg(Reg::a(0), value, true);
+                self.emit("fence rw, w");
+                self.emit("sd a0, 0(t0)");
+                self.builtin_result(dest);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// t0 = the address an atomic builtin operates on.
+    fn atomic_address(&mut self, ptr: &Operand) {
+        let reg = self.int_operand(ptr, Reg::T0);
+        self.move_reg(Reg::T0, reg, true);
+    }
+
+    /// a0 = the old value of `*ptr` after atomic memory operation `amo` with
+    /// `value`, negated first if `negate`.
+    fn atomic_rmw(&mut self, amo: &str, ptr: &Operand, value: &Operand, negate: bool) {
+        self.atomic_address(ptr);
+        let value = self.int_operand(value, Reg::T1);
+        if negate {
+            self.emit(format!("neg t1, {}", value));
+        } else {
+            self.move_reg(Reg::T1, value, true);
+        }
+        self.emit(format!("{} a0, t1, (t0)", amo));
+    }
+
+    fn builtin_result(&mut self, dest: Option<VarId>) {
+        if let Some(dest) = dest {
+            let ty = self.var_type(dest);
+            self.narrow(Reg::a(0), &ty);
+            self.write_dest(dest, Reg::a(0));
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// Calls, parameters, returns, and varargs under the LP64D calling convention
+use ir::{Function as IrFunction, Operand, VarId};
+use model::Type;
+use super::Reg;
+use super::abi::{self, ArgAllocator, Loc, Part, Passing};
+use super::function::{align_to, int_kind, int_load, int_store, float_load, float_store, is_float_type, FunctionGenerator, VA_SAVE_SIZE};
+
+/// The register an argument location names, if it is one.
+fn loc_reg(loc: Loc) -> Option<Reg> {
+    match loc {
+        Loc::Int(n) => Some(Reg::a(n)),
+        Loc::Float(n) => Some(Reg::fa(n)),
+        Loc::Stack(_) => None,
+    }
+}
+
+impl FunctionGenerator<'_> {
+    /// Move the incoming parameters from their argument registers and stack
+    /// slots to their homes.
+    pub(super) fn gen_params(&mut self, func: &IrFunction) {
+        let layout = self.cg.type_layout();
+        let returns_by_ref = matches!(abi::classify_return(&layout, &func.return_type), Passing::ByRef(_));
+        let mut args = if returns_by_ref { ArgAllocator::with_return_pointer() } else { ArgAllocator::new() };
+        let passing: Vec<Passing> = func.params.iter()
+            .map(|(ty, _)| args.classify(&layout, ty, false))
+            .collect();
+        let (int_regs, stack_size) = (args.int_regs_used(), args.stack_size());
+
+        if returns_by_ref {
+            let slot = self.alloc_frame(8, 8);
+            self.emit(format!("sd a0, {}(s0)", slot));
+            self.return_pointer = Some(slot);
+        }
+        // Variadic arguments start after the named ones: in the a0–a7 save
+        // area, which ends where the stack arguments begin
+        self.va_first = if int_regs < 8 { -VA_SAVE_SIZE + 8 * i32::from(int_regs) } else { stack_size };
+
+        for ((ty, var), passing) in func.params.iter().zip(passing) {
+            match passing {
+                Passing::Scalar(loc) => {
+                    let reg = self.incoming_scalar(loc, ty);
+                    self.write_dest(*var, reg);
+                }
+                Passing::ByRef(loc) => {
+                    let reg = self.incoming_scalar(loc, &Type::Long);
+                    self.write_dest(*var, reg);
+                }
+                Passing::Parts(parts) => {
+                    let buffer = self.alloc_aggregate(ty);
+                    for part in &parts {
+                        let reg = match loc_reg(part.loc) {
+                            Some(reg) => reg,
+                            None => {
+                                let Loc::Stack(offset) = part.loc else { unreachable!() };
+                                let mem = self.incoming_stack(offset);
+                                self.emit(format!("ld t0, {}", mem));
+                                Reg::T0
+                            }
+                        };
+                        let mem = self.mem(Reg::S0, i64::from(buffer) + part.offset as i64);
+                        self.emit(format!("{} {}, {}", part_store(part), reg, mem));
+                    }
+                    let reg = self.dest_reg(*var, Reg::T0);
+                    self.add_offset(reg, Reg::S0, buffer.into());
+                    self.write_dest(*var, reg);
+                }
+            }
+        }
+    }
+
+    /// The memory operand of the incoming stack argument at `offset`, which
+    /// starts at the caller's sp, that is s0.
+    fn incoming_stack(&mut self, offset: i32) -> String {
+        self.mem(Reg::S0, offset.into())
+    }
+
+    /// A register holding the incoming scalar parameter of type `ty` at `loc`.
+    fn incoming_scalar(&mut self, loc: Loc, ty: &Type) -> Reg {
+        let double = *ty != Type::Float;
+        match loc {
+            Loc::Int(n) if is_float_type(ty) => {
+                self.move_reg(Reg::FT0, Reg::a(n), double);
+                Reg::FT0
+            }
+            Loc::Int(n) => {
+                // The caller sign-extends 32-bit values, even unsigned ones
+                if int_kind(ty) == (4, true) {
+                    self.narrow(Reg::a(n), ty);
+                }
+                Reg::a(n)
+            }
+            Loc::Float(n) => Reg::fa(n),
+            Loc::Stack(offset) => {
+                let mem = self.incoming_stack(offset);
+                if is_float_type(ty) {
+                    self.emit(format!("{} ft0, {}", float_load(double), mem));
+                    Reg::FT0
+                } else {
+                    self.emit(format!("{} t0, {}", int_load(ty), mem));
+                    Reg::T0
+                }
+            }
+        }
+    }
+
+    pub(super) fn gen_call(&mut self, dest: Option<VarId>, name: &str, args: &[Operand]) {
+        if self.gen_builtin(dest, name, args) {
+            return;
+        }
+        let signature = self.cg.signatures.get(name).cloned();
+        let params = signature.as_ref().map(|s| s.params.clone());
+        let variadic = signature.as_ref().is_some_and(|s| s.is_variadic);
+        let return_type = match (&signature, dest) {
+            (Some(s), _) => s.return_type.clone(),
+            (None, Some(dest)) => self.var_type(dest),
+            (None, None) => Type::Void,
+        };
+        self.emit_call(dest, None, args, params.as_deref(), variadic, &return_type, name);
+    }
+
+    pub(super) fn gen_indirect_call(&mut self, dest: Option<VarId>, func_ptr: &Operand, args: &[Operand], is_variadic: bool) {
+        // A pointer constant-folded to the function itself has its prototype
+        if let Operand::Global(name) = func_ptr
+            && let Some(signature) = self.cg.signatures.get(name).cloned()
+        {
+            let variadic = signature.is_variadic;
+            self.emit_call(dest, Some(func_ptr), args, Some(&signature.params), variadic, &signature.return_type, "");
+            return;
+        }
+        let (params, return_type) = match self.operand_type(func_ptr) {
+            Type::FunctionPointer { return_type, param_types, .. } => (Some(param_types), *return_type),
+            Type::Pointer(inner, _) => match *inner {
+                Type::FunctionPointer { return_type, param_types, .. } => (Some(param_types), *return_type),
+                _ => (None, dest.map_or(Type::Void, |d| self.var_type(d))),
+            },
+            _ => (None, dest.map_or(Type::Void, |d| self.var_type(d))),
+        };
+        self.emit_call(dest, Some(func_ptr), args, params.as_deref(), is_variadic, &return_type, "");
+    }
+
+    /// Place the arguments, call `name` or `func_ptr`, and store the result
+    /// in `dest`. `params` are the callee's parameter types when known;
+    /// arguments past them are variadic if `variadic`.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_call(
+        &mut self,
+        dest: Option<VarId>,
+        func_ptr: Option<&Operand>,
+        args: &[Operand],
+        params: Option<&[Type]>,
+        variadic: bool,
+        return_type: &Type,
+        name: &str,
+    ) {
+        let layout = self.cg.type_layout();
+        let returns = (*return_type != Type::Void).then(|| abi::classify_return(&layout, return_type));
+        let returns_by_ref = matches!(returns, Some(Passing::ByRef(_)));
+        let mut allocator = if returns_by_ref { ArgAllocator::with_return_pointer() } else { ArgAllocator::new() };
+        let named = params.map_or(args.len(), <[Type]>::len);
+        let classified: Vec<(Type, Passing)> = args.iter().enumerate().map(|(i, arg)| {
+            let is_variadic = variadic && i >= named;
+            let mut ty = match params.and_then(|p| p.get(i)) {
+                Some(ty) if !is_variadic => ty.clone(),
+                _ => self.operand_type(arg),
+            };
+            // Default argument promotion
+            if is_variadic && ty == Type::Float {
+                ty = Type::Double;
+            }
+            let passing = allocator.classify(&layout, &ty, is_variadic);
+            (ty, passing)
+        }).collect();
+        self.outgoing_size = self.outgoing_size.max(allocator.stack_size());
+
+        // Result buffer for an aggregate returned in memory
+        let result_buffer = match &returns {
+            Some(Passing::ByRef(_)) | Some(Passing::Parts(_)) => Some(self.alloc_aggregate(return_type)),
+            _ => None,
+        };
+
+        // Aggregates over 16 bytes are copied first, since the copies use the
+        // scratch registers and would not disturb argument registers anyway
+        let mut copies = Vec::new();
+        for (arg, (ty, passing)) in args.iter().zip(&classified) {
+            if let Passing::ByRef(_) = passing {
+                let buffer = self.alloc_aggregate(ty);
+                let src = self.int_operand(arg, Reg::T1);
+                self.move_reg(Reg::T1, src, true);
+                self.add_offset(Reg::T2, Reg::S0, buffer.into());
+                self.copy_memory(self.size_of(ty));
+                copies.push(buffer);
+            }
+        }
+        let mut copies = copies.into_iter();
+
+        for (arg, (ty, passing)) in args.iter().zip(classified) {
+            match passing {
+                Passing::Scalar(loc) => self.outgoing_scalar(arg, &ty, loc),
+                Passing::ByRef(loc) => {
+                    let buffer = copies.next().expect("a copy per aggregate passed by reference");
+                    let reg = loc_reg(loc).unwrap_or(Reg::T0);
+                    self.add_offset(reg, Reg::S0, buffer.into());
+                    if let Loc::Stack(offset) = loc {
+                        self.emit(format!("sd t0, {}(sp)", offset));
+                    }
+                }
+                Passing::Parts(parts) => {
+                    let base = self.int_operand(arg, Reg::T1);
+                    for part in &parts {
+                        let reg = loc_reg(part.loc).unwrap_or(Reg::T0);
+                        self.emit(format!("{} {}, {}({})", part_load(part), reg, part.offset, base));
+                        if let Loc::Stack(offset) = part.loc {
+                            self.emit(format!("sd t0, {}(sp)", offset));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let (Some(Passing::ByRef(_)), Some(buffer)) = (&returns, result_buffer) {
+            self.add_offset(Reg::a(0), Reg::S0, buffer.into());
+        }
+        match func_ptr {
+            Some(func_ptr) => {
+                let target = self.int_operand(func_ptr, Reg::T2);
+                self.emit(format!("jalr {}", target));
+            }
+            None => self.emit(format!("call {}", name)),
+        }
+
+        let Some(dest) = dest else { return };
+        match returns {
+            None => {}
+            Some(Passing::Scalar(Loc::Int(_))) => {
+                if int_kind(return_type) == (4, true) {
+                    self.narrow(Reg::a(0), return_type);
+                }
+                self.write_dest(dest, Reg::a(0));
+            }
+            Some(Passing::Scalar(_)) => self.write_dest(dest, Reg::fa(0)),
+            Some(Passing::Parts(parts)) => {
+                let buffer = result_buffer.expect("a buffer for the returned aggregate");
+                for part in &parts {
+                    let reg = loc_reg(part.loc).expect("returned in registers");
+                    let mem = self.mem(Reg::S0, i64::from(buffer) + part.offset as i64);
+                    self.emit(format!("{} {}, {}", part_store(part), reg, mem));
+                }
+                let reg = self.dest_reg(dest, Reg::T0);
+                self.add_offset(reg, Reg::S0, buffer.into());
+                self.write_dest(dest, reg);
+            }
+            Some(Passing::ByRef(_)) => {
+                let reg = self.dest_reg(dest, Reg::T0);
+                self.add_offset(reg, Reg::S0, result_buffer.expect("a buffer for the returned aggregate").into());
+                self.write_dest(dest, reg);
+            }
+        }
+    }
+
+    /// Put scalar argument `arg` of type `ty` at `loc`.
+    fn outgoing_scalar(&mut self, arg: &Operand, ty: &Type, loc: Loc) {
+        if is_float_type(ty) {
+            let double = *ty == Type::Double;
+            let target = match loc {
+                Loc::Float(n) => Reg::fa(n),
+                _ => Reg::FT0,
+            };
+            let reg = self.float_operand(arg, target, double);
+            match loc {
+                Loc::Float(_) => self.move_reg(target, reg, double),
+                Loc::Int(n) => self.move_reg(Reg::a(n), reg, double),
+                Loc::Stack(offset) => self.emit(format!("{} {}, {}(sp)", float_store(double), reg, offset)),
+            }
+            return;
+        }
+        let target = loc_reg(loc).unwrap_or(Reg::T0);
+        let reg = self.int_operand(arg, target);
+        self.move_reg(target, reg, true);
+        // Narrow arguments are extended to 64 bits per their type, and
+        // 32-bit ones always sign-extended
+        if int_kind(ty) == (4, true) {
+            self.emit(format!("sext.w {}, {}", target, target));
+        } else {
+            self.narrow(target, ty);
+        }
+        if let Loc::Stack(offset) = loc {
+            self.emit(format!("sd t0, {}(sp)", offset));
+        }
+    }
+
+    pub(super) fn gen_return(&mut self, value: Option<&Operand>, return_type: &Type) {
+        let Some(value) = value else { return };
+        if *return_type == Type::Void {
+            return;
+        }
+        let layout = self.cg.type_layout();
+        let passing = abi::classify_return(&layout, return_type);
+        match passing {
+            Passing::Scalar(Loc::Int(_)) => {
+                let reg = self.int_operand(value, Reg::a(0));
+                self.move_reg(Reg::a(0), reg, true);
+                if int_kind(return_type) == (4, true) {
+                    self.emit("sext.w a0, a0");
+                } else {
+                    self.narrow(Reg::a(0), return_type);
+                }
+            }
+            Passing::Scalar(_) => {
+                let double = *return_type == Type::Double;
+                let reg = self.float_operand(value, Reg::fa(0), double);
+                self.move_reg(Reg::fa(0), reg, double);
+            }
+            Passing::Parts(parts) => {
+                let base = self.int_operand(value, Reg::T1);
+                for part in &parts {
+                    let reg = loc_reg(part.loc).expect("returned in registers");
+                    self.emit(format!("{} {}, {}({})", part_load(part), reg, part.offset, base));
+                }
+            }
+            Passing::ByRef(_) => {
+                let slot = self.return_pointer.expect("a return pointer for an aggregate returned in memory");
+                let src = self.int_operand(value, Reg::T1);
+                self.move_reg(Reg::T1, src, true);
+                self.emit(format!("ld t2, {}(s0)", slot));
+                self.copy_memory(self.size_of(return_type));
+                self.emit(format!("ld a0, {}(s0)", slot));
+            }
+        }
+    }
+
+    // ─── Varargs ─────────────────────────────────────────────────
+    //
+    // A `va_list` is a pointer to the next variadic argument. Every variadic
+    // argument takes 8 bytes, in the register save area or on the stack.
+
+    pub(super) fn gen_va_start(&mut self, list: &Operand) {
+        self.add_offset(Reg::T0, Reg::S0, self.va_first.into());
+        let mem = self.address(list);
+        self.emit(format!("sd t0, {}", mem));
+    }
+
+    pub(super) fn gen_va_copy(&mut self, dest: &Operand, src: &Operand) {
+        let value = self.int_operand(src, Reg::T0);
+        let mem = self.address(dest);
+        self.emit(format!("sd {}, {}", value, mem));
+    }
+
+    pub(super) fn gen_va_arg(&mut self, dest: VarId, list: &Operand, ty: &Type) {
+        let size = if abi::is_aggregate(ty) { self.size_of(ty) } else { 8 };
+        let step = if size > 16 { 8 } else { align_to(size as i32, 8) };
+        let mem = self.address(list);
+        self.emit(format!("ld t2, {}", mem));
+        self.emit(format!("addi t0, t2, {}", step));
+        self.emit(format!("sd t0, {}", mem));
+        if abi::is_aggregate(ty) {
+            if size > 16 {
+                // The argument slot holds the address of a copy
+                self.emit("ld t2, 0(t2)");
+                self.write_dest(dest, Reg::T2);
+                return;
+            }
+            let buffer = self.alloc_aggregate(ty);
+            self.emit("mv t1, t2");
+            self.add_offset(Reg::T2, Reg::S0, buffer.into());
+            self.copy_memory(size);
+            let reg = self.dest_reg(dest, Reg::T0);
+            self.add_offset(reg, Reg::S0, buffer.into());
+            self.write_dest(dest, reg);
+        } else if is_float_type(ty) {
+            // Variadic floats are passed as doubles
+            self.emit("fld ft0, 0(t2)");
+            if *ty == Type::Float {
+                self.emit("fcvt.s.d ft0, ft0");
+            }
+            self.write_dest(dest, Reg::FT0);
+        } else {
+            let reg = self.dest_reg(dest, Reg::T0);
+            self.emit(format!("{} {}, 0(t2)", int_load(ty), reg));
+            self.write_dest(dest, reg);
+        }
+    }
+}
+
+fn part_load(part: &Part) -> &'static str {
+    match (part.is_float, part.size) {
+        (true, 4) => "flw",
+        (true, _) => "fld",
+        (false, 1) => "lb",
+        (false, 2) => "lh",
+        (false, 4) => "lw",
+        (false, _) => "ld",
+    }
+}
+
+fn part_store(part: &Part) -> &'static str {
+    match (part.is_float, part.size) {
+        (true, 4) => "fsw",
+        (true, _) => "fsd",
+        (false, size) => int_store(size),
+    }
+}
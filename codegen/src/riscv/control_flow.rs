@@ -0,0 +1,175 @@
+// Terminators and phi resolution
+use ir::{BlockId, Function as IrFunction, Instruction as IrInstruction, Operand, Terminator as IrTerminator, VarId};
+use model::Type;
+use super::Reg;
+use super::function::{float_load, float_store, is_float_type, FunctionGenerator, Home};
+
+/// The source of a phi copy: a variable's home, or for an `alloca`
+/// variable the address of its object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Home(Home),
+    Object(i32),
+}
+
+/// One copy of a parallel phi assignment. `float` is `Some(is_double)` for
+/// floating-point values.
+struct PhiCopy {
+    dest: Home,
+    src: Source,
+    float: Option<bool>,
+}
+
+impl FunctionGenerator<'_> {
+    pub(super) fn gen_terminator(&mut self, term: &IrTerminator, block: BlockId, func: &IrFunction) {
+        let target_label = |id: BlockId| format!("{}_{}", func.name, id.0);
+        match term {
+            IrTerminator::Br(target) => {
+                self.gen_phi_copies(func, block, *target);
+                self.emit(format!("j {}", target_label(*target)));
+            }
+            IrTerminator::CondBr { cond, then_block, else_block, .. } => {
+                let cond = self.condition(cond);
+                // Branch over the then-edge copies; conditional branches reach
+                // only ±4 KiB, while `j` reaches ±1 MiB
+                let else_edge = self.new_label();
+                self.emit(format!("beqz {}, {}", cond, else_edge));
+                self.gen_phi_copies(func, block, *then_block);
+                self.emit(format!("j {}", target_label(*then_block)));
+                self.label(&else_edge);
+                self.gen_phi_copies(func, block, *else_block);
+                self.emit(format!("j {}", target_label(*else_block)));
+            }
+            IrTerminator::Switch { value, cases, default } => {
+                // A chain of compares against the value, held extended to 64
+                // bits as the case values are; each case jumps to the copies
+                // of its own edge, which may clobber the value
+                let targets = IrTerminator::switch_targets(cases, *default);
+                let edges: Vec<String> = targets.iter().map(|_| self.new_label()).collect();
+                let edge = |id: BlockId| edges[targets.iter().position(|&t| t == id).unwrap()].clone();
+                let value = self.int_operand(value, Reg::T0);
+                for &(case, target) in cases {
+                    let next = self.new_label();
+                    self.emit(format!("li {}, {}", Reg::T1, case));
+                    self.emit(format!("bne {}, {}, {}", value, Reg::T1, next));
+                    self.emit(format!("j {}", edge(target)));
+                    self.label(&next);
+                }
+                self.emit(format!("j {}", edge(*default)));
+                for (&target, label) in targets.iter().zip(&edges) {
+                    self.label(label);
+                    self.gen_phi_copies(func, block, target);
+                    self.emit(format!("j {}", target_label(target)));
+                }
+            }
+            IrTerminator::Ret(value) => {
+                self.gen_return(value.as_ref(), &func.return_type);
+                self.emit(format!("j .L{}_ret", func.name));
+            }
+            IrTerminator::Unreachable => {
+                self.emit(format!("j .L{}_ret", func.name));
+            }
+            IrTerminator::IndirectBr { target } => {
+                let target = self.int_operand(target, Reg::T0);
+                self.emit(format!("jr {}", target));
+            }
+        }
+    }
+
+    /// A register that is nonzero when branch condition `cond` is true.
+    fn condition(&mut self, cond: &Operand) -> Reg {
+        let ty = match cond {
+            Operand::FloatConstant(_) => Type::Double,
+            _ => self.operand_type(cond),
+        };
+        if !is_float_type(&ty) {
+            return self.int_operand(cond, Reg::T0);
+        }
+        let double = ty == Type::Double;
+        let value = self.float_operand(cond, Reg::FT0, double);
+        let s = if double { "d" } else { "s" };
+        self.emit(format!("fmv.{}.x ft1, zero", if double { "d" } else { "w" }));
+        self.emit(format!("feq.{} t0, {}, ft1", s, value));
+        self.emit("xori t0, t0, 1");
+        Reg::T0
+    }
+
+    /// Assign the phis of `to` their values for the edge from `from`, all at
+    /// once: a copy waits until no other copy still reads its destination,
+    /// and a cycle of copies is broken through t2 or ft2.
+    fn gen_phi_copies(&mut self, func: &IrFunction, from: BlockId, to: BlockId) {
+        let Some(block) = func.blocks.iter().find(|b| b.id == to) else { return };
+        let phis: Vec<(VarId, VarId)> = block.instructions.iter()
+            .filter_map(|inst| match inst {
+                IrInstruction::Phi { dest, preds } => preds.iter()
+                    .find(|(pred, _)| *pred == from)
+                    .map(|(_, src)| (*dest, *src)),
+                _ => None,
+            })
+            .collect();
+        let mut pending: Vec<PhiCopy> = Vec::new();
+        for (dest, src) in phis {
+            let ty = self.var_type(dest);
+            let float = is_float_type(&ty).then_some(ty == Type::Double);
+            let src = match self.objects.get(&src) {
+                Some(offset) => Source::Object(*offset),
+                None => Source::Home(self.home(src)),
+            };
+            let dest = self.home(dest);
+            if src != Source::Home(dest) {
+                pending.push(PhiCopy { dest, src, float });
+            }
+        }
+
+        while !pending.is_empty() {
+            let ready = pending.iter()
+                .position(|copy| !pending.iter().any(|other| other.src == Source::Home(copy.dest)));
+            if let Some(i) = ready {
+                let copy = pending.remove(i);
+                self.copy_value(copy.dest, copy.src, copy.float);
+                continue;
+            }
+            // Every destination is still read: save one to a temporary
+            let copy = &pending[0];
+            let (blocked, float) = (copy.dest, copy.float);
+            let temp = Home::Reg(if float.is_some() { Reg::FT2 } else { Reg::T2 });
+            self.copy_value(temp, Source::Home(blocked), float);
+            for copy in &mut pending {
+                if copy.src == Source::Home(blocked) {
+                    copy.src = Source::Home(temp);
+                }
+            }
+        }
+    }
+
+    /// `dest = src` between registers and stack slots.
+    fn copy_value(&mut self, dest: Home, src: Source, float: Option<bool>) {
+        let (load, store, scratch) = match float {
+            Some(double) => (float_load(double), float_store(double), Reg::FT0),
+            None => ("ld", "sd", Reg::T0),
+        };
+        let dest_reg = match dest {
+            Home::Reg(reg) => reg,
+            Home::Slot(_) => scratch,
+        };
+        match src {
+            Source::Object(offset) => self.add_offset(dest_reg, Reg::S0, offset.into()),
+            Source::Home(Home::Reg(reg)) => {
+                if let Home::Slot(offset) = dest {
+                    let mem = self.mem(Reg::S0, offset.into());
+                    self.emit(format!("{} {}, {}", store, reg, mem));
+                    return;
+                }
+                self.move_reg(dest_reg, reg, float.unwrap_or(true));
+            }
+            Source::Home(Home::Slot(offset)) => {
+                let mem = self.mem(Reg::S0, offset.into());
+                self.emit(format!("{} {}, {}", load, dest_reg, mem));
+            }
+        }
+        if let Home::Slot(offset) = dest {
+            let mem = self.mem(Reg::S0, offset.into());
+            self.emit(format!("{} {}, {}", store, dest_reg, mem));
+        }
+    }
+}
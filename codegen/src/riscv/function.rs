@@ -0,0 +1,584 @@
+// Per-function RISC-V code generation: frame layout, operands, and the
+// instruction dispatch
+use std::collections::HashMap;
+use ir::{Function as IrFunction, Instruction as IrInstruction, Operand, VarId};
+use model::Type;
+use crate::Codegen;
+use super::{fits_imm12, Reg};
+use super::regalloc::allocate_registers;
+
+/// Where a variable's value lives: a register, or an 8-byte stack slot at
+/// an offset from s0. Aggregate-typed variables hold the address of their
+/// object, so they live in integer registers or slots too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Home {
+    Reg(Reg),
+    Slot(i32),
+}
+
+/// Bytes of the register save area a variadic function keeps a0–a7 in,
+/// just below the incoming stack arguments.
+pub(super) const VA_SAVE_SIZE: i32 = 64;
+
+/// Generates the code for one function. The frame, from s0 (the stack
+/// pointer at entry) down:
+///
+/// - the a0–a7 save area of variadic functions, so that `va_arg` can walk
+///   the register arguments and then the stack arguments above them
+/// - ra, the caller's s0, and the callee-saved registers the function uses
+/// - stack slots, `alloca` objects, and aggregate temporaries
+/// - the outgoing stack arguments, at sp
+pub(crate) struct FunctionGenerator<'a> {
+    pub(super) cg: &'a mut Codegen,
+    /// Assembly lines of the body, emitted before the prologue is known
+    pub(super) out: Vec<String>,
+    pub(super) func_name: String,
+    pub(super) var_types: HashMap<VarId, Type>,
+    pub(super) regs: HashMap<VarId, Reg>,
+    slots: HashMap<VarId, i32>,
+    /// Offset from s0 of the object each `Alloca` variable points to
+    pub(super) objects: HashMap<VarId, i32>,
+    /// Type of each `Alloca` object; inline asm outputs are written to them
+    pub(super) object_types: HashMap<VarId, Type>,
+    /// Bytes of the frame below s0 laid out so far
+    frame_bytes: i32,
+    /// Bytes of the save area at the top of the frame
+    save_size: i32,
+    /// Largest stack argument area of any call
+    pub(super) outgoing_size: i32,
+    saved_regs: Vec<Reg>,
+    pub(super) is_variadic: bool,
+    /// Offset from s0 of the first variadic argument, for `va_start`
+    pub(super) va_first: i32,
+    /// Slot holding the address a returned aggregate over 16 bytes goes to
+    pub(super) return_pointer: Option<i32>,
+    next_label: usize,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    pub fn new(cg: &'a mut Codegen) -> Self {
+        Self {
+            cg,
+            out: Vec::new(),
+            func_name: String::new(),
+            var_types: HashMap::new(),
+            regs: HashMap::new(),
+            slots: HashMap::new(),
+            objects: HashMap::new(),
+            object_types: HashMap::new(),
+            frame_bytes: 0,
+            save_size: 0,
+            outgoing_size: 0,
+            saved_regs: Vec::new(),
+            is_variadic: false,
+            va_first: 0,
+            return_pointer: None,
+            next_label: 0,
+        }
+    }
+
+    pub fn gen_function(mut self, func: &IrFunction) -> String {
+        self.func_name = func.name.clone();
+        self.var_types = func.var_types.clone();
+        for (ty, var) in &func.params {
+            self.var_types.insert(*var, ty.clone());
+        }
+        let instructions = || func.blocks.iter().flat_map(|b| &b.instructions);
+        self.is_variadic = instructions().any(|inst| matches!(inst, IrInstruction::VaStart { .. }));
+
+        if self.cg.enable_regalloc {
+            self.regs = allocate_registers(func, &self.var_types, &self.cg.target);
+        }
+        // Callee-saved registers the function writes: those holding variables
+        // and those inline asm clobbers
+        let callee_saved = [Reg::callee_saved(), Reg::callee_saved_float()].concat();
+        let clobbered = instructions()
+            .filter_map(|inst| match inst {
+                IrInstruction::InlineAsm { clobbers, .. } => Some(clobbers),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|name| Reg::from_name(name));
+        let mut saved: Vec<Reg> = self.regs.values().copied().chain(clobbered)
+            .filter(|reg| callee_saved.contains(reg))
+            .collect();
+        saved.sort_by_key(|reg| callee_saved.iter().position(|r| r == reg));
+        saved.dedup();
+        self.saved_regs = saved;
+
+        let va_size = if self.is_variadic { VA_SAVE_SIZE } else { 0 };
+        self.save_size = align_to(va_size + 16 + 8 * self.saved_regs.len() as i32, 16);
+        self.frame_bytes = self.save_size;
+
+        for inst in instructions() {
+            if let IrInstruction::Alloca { dest, r#type } = inst {
+                let size = self.size_of(r#type).max(1);
+                let align = self.cg.type_alignment(r#type).max(8);
+                let offset = self.alloc_frame(size, align);
+                self.objects.insert(*dest, offset);
+                self.object_types.insert(*dest, r#type.clone());
+            }
+        }
+
+        self.gen_params(func);
+        if func.blocks.first().is_some_and(|b| b.id != func.entry_block) {
+            self.emit(format!("j {}_{}", func.name, func.entry_block.0));
+        }
+        let branch_targets: std::collections::HashSet<ir::BlockId> =
+            func.compute_successors().into_values().flatten().collect();
+        for block in &func.blocks {
+            // Blocks CFG simplification left unreachable, as in the x86-64 backend
+            if block.instructions.is_empty()
+                && matches!(block.terminator, ir::Terminator::Unreachable)
+                && !branch_targets.contains(&block.id)
+                && block.id != func.entry_block
+            {
+                continue;
+            }
+            self.label(&format!("{}_{}", func.name, block.id.0));
+            self.count_block(block.id);
+            for inst in &block.instructions {
+                self.gen_instr(inst);
+            }
+            self.gen_terminator(&block.terminator, block.id, func);
+        }
+        self.finish()
+    }
+
+    /// Under `-fprofile-generate`, count the executions of `block`.
+    fn count_block(&mut self, block: ir::BlockId) {
+        if !self.cg.profile_generate {
+            return;
+        }
+        let counter = format!("__profc_{}_{}", self.func_name, block.0);
+        if !self.cg.profile_counters.contains(&counter) {
+            self.cg.profile_counters.push(counter.clone());
+        }
+        self.emit(format!("lla t3, {}", counter));
+        self.emit("ld t0, 0(t3)");
+        self.emit("addi t0, t0, 1");
+        self.emit("sd t0, 0(t3)");
+    }
+
+    /// The function's label, prologue, body, and epilogue.
+    fn finish(self) -> String {
+        let va_size = if self.is_variadic { VA_SAVE_SIZE } else { 0 };
+        let save = self.save_size;
+        let locals = align_to(self.frame_bytes - save + self.outgoing_size, 16);
+        let mut lines = vec![
+            format!("{}:", self.func_name),
+            ".cfi_startproc".to_string(),
+            format!("    addi sp, sp, -{}", save),
+            format!(".cfi_def_cfa_offset {}", save),
+            format!("    sd ra, {}(sp)", save - va_size - 8),
+            format!("    sd s0, {}(sp)", save - va_size - 16),
+            format!(".cfi_offset ra, -{}", va_size + 8),
+            format!(".cfi_offset s0, -{}", va_size + 16),
+            format!("    addi s0, sp, {}", save),
+            ".cfi_def_cfa s0, 0".to_string(),
+        ];
+        let saved_offset = |i: usize| va_size + 24 + 8 * i as i32;
+        for (i, reg) in self.saved_regs.iter().enumerate() {
+            let op = if reg.is_float() { "fsd" } else { "sd" };
+            lines.push(format!("    {} {}, -{}(s0)", op, reg, saved_offset(i)));
+            lines.push(format!(".cfi_offset {}, -{}", reg, saved_offset(i)));
+        }
+        if self.is_variadic {
+            for n in 0..8 {
+                lines.push(format!("    sd {}, {}(s0)", Reg::a(n), -VA_SAVE_SIZE + 8 * i32::from(n)));
+            }
+        }
+        if locals > 0 {
+            if fits_imm12(-i64::from(locals)) {
+                lines.push(format!("    addi sp, sp, -{}", locals));
+            } else {
+                lines.push(format!("    li t0, {}", locals));
+                lines.push("    sub sp, sp, t0".to_string());
+            }
+        }
+        lines.extend(self.out);
+
+        lines.push(format!(".L{}_ret:", self.func_name));
+        lines.push(format!("    addi sp, s0, -{}", save));
+        lines.push(format!(".cfi_def_cfa sp, {}", save));
+        for (i, reg) in self.saved_regs.iter().enumerate() {
+            let op = if reg.is_float() { "fld" } else { "ld" };
+            lines.push(format!("    {} {}, -{}(s0)", op, reg, saved_offset(i)));
+            lines.push(format!(".cfi_restore {}", reg));
+        }
+        lines.push(format!("    ld ra, {}(sp)", save - va_size - 8));
+        lines.push(format!("    ld s0, {}(sp)", save - va_size - 16));
+        lines.push(".cfi_restore ra".to_string());
+        lines.push(".cfi_restore s0".to_string());
+        lines.push(format!("    addi sp, sp, {}", save));
+        lines.push(".cfi_def_cfa_offset 0".to_string());
+        lines.push("    ret".to_string());
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn gen_instr(&mut self, inst: &IrInstruction) {
+        match inst {
+            IrInstruction::Binary { dest, op, left, right } => self.gen_binary(*dest, op, left, right),
+            IrInstruction::FloatBinary { dest, op, left, right } => self.gen_float_binary(*dest, op, left, right),
+            IrInstruction::Unary { dest, op, src } => self.gen_unary(*dest, op, src),
+            IrInstruction::FloatUnary { dest, op, src } => self.gen_float_unary(*dest, op, src),
+            // Phis are resolved by copies at the end of each predecessor
+            IrInstruction::Phi { .. } => {}
+            IrInstruction::Copy { dest, src } => self.gen_copy(*dest, src),
+            IrInstruction::Cast { dest, src, src_type, r#type } => self.gen_cast(*dest, src, src_type, r#type),
+            // Frame objects are laid out before the body
+            IrInstruction::Alloca { .. } => {}
+            IrInstruction::Load { dest, addr, value_type, .. } => self.gen_load(*dest, addr, value_type),
+            IrInstruction::Store { addr, src, value_type, .. } => self.gen_store(addr, src, value_type),
+            IrInstruction::GetElementPtr { dest, base, index, element_type } => self.gen_gep(*dest, base, index, element_type),
+            IrInstruction::Call { dest, name, args, .. } => self.gen_call(*dest, name, args),
+            IrInstruction::IndirectCall { dest, func_ptr, args, is_variadic, .. } => {
+                self.gen_indirect_call(*dest, func_ptr, args, *is_variadic)
+            }
+            IrInstruction::VaStart { list, .. } => self.gen_va_start(list),
+            IrInstruction::VaEnd { .. } => {}
+            IrInstruction::VaCopy { dest, src } => self.gen_va_copy(dest, src),
+            IrInstruction::VaArg { dest, list, r#type } => self.gen_va_arg(*dest, list, r#type),
+            IrInstruction::InlineAsm { template, outputs, inputs, output_constraints, input_constraints, .. } => {
+                self.gen_inline_asm(template, outputs, inputs, output_constraints, input_constraints)
+            }
+            IrInstruction::Memcpy { dest, src, size } => {
+                self.address_into(Reg::T1, src);
+                self.address_into(Reg::T2, dest);
+                self.copy_memory(*size);
+            }
+            IrInstruction::Memset { dest, value, size } => self.gen_memset(dest, value, *size),
+            // Vectorization is off for RISC-V (`TargetConfig::riscv64` has no SIMD level)
+            IrInstruction::Simd { .. } => unreachable!("SIMD instructions are not generated for RISC-V"),
+        }
+    }
+
+    // ─── Output ──────────────────────────────────────────────────
+
+    pub(super) fn emit(&mut self, line: impl AsRef<str>) {
+        self.out.push(format!("    {}", line.as_ref()));
+    }
+
+    pub(super) fn label(&mut self, name: &str) {
+        self.out.push(format!("{}:", name));
+    }
+
+    /// A fresh local label.
+    pub(super) fn new_label(&mut self) -> String {
+        self.next_label += 1;
+        format!(".L{}_{}", self.func_name, self.next_label)
+    }
+
+    // ─── Types ───────────────────────────────────────────────────
+
+    pub(super) fn var_type(&self, var: VarId) -> Type {
+        self.var_types.get(&var).cloned().unwrap_or(Type::Long)
+    }
+
+    pub(super) fn operand_type(&self, op: &Operand) -> Type {
+        match op {
+            Operand::Var(var) => self.var_type(*var),
+            Operand::Constant(_) => Type::Long,
+            Operand::FloatConstant(_) => Type::Double,
+            Operand::Global(_) => Type::Pointer(Box::new(Type::Void), Default::default()),
+        }
+    }
+
+    pub(super) fn size_of(&self, ty: &Type) -> usize {
+        self.cg.type_size(ty)
+    }
+
+    // ─── Frame ───────────────────────────────────────────────────
+
+    /// Reserve `size` bytes of frame aligned to `align` (at most 16, the
+    /// alignment of s0); returns their offset from s0.
+    pub(super) fn alloc_frame(&mut self, size: usize, align: usize) -> i32 {
+        let align = align.clamp(1, 16) as i32;
+        self.frame_bytes = align_to(self.frame_bytes + size as i32, align);
+        -self.frame_bytes
+    }
+
+    /// A frame object for an aggregate temporary of type `ty`, rounded up to
+    /// whole 8-byte words, since aggregates are copied and passed by words.
+    pub(super) fn alloc_aggregate(&mut self, ty: &Type) -> i32 {
+        let size = align_to(self.size_of(ty) as i32, 8).max(8) as usize;
+        self.alloc_frame(size, 8)
+    }
+
+    pub(super) fn home(&mut self, var: VarId) -> Home {
+        if let Some(reg) = self.regs.get(&var) {
+            return Home::Reg(*reg);
+        }
+        if let Some(offset) = self.slots.get(&var) {
+            return Home::Slot(*offset);
+        }
+        let offset = self.alloc_frame(8, 8);
+        self.slots.insert(var, offset);
+        Home::Slot(offset)
+    }
+
+    /// The memory operand `offset(base)`, through t3 when the offset does not
+    /// fit an immediate. `base` must not be t3.
+    pub(super) fn mem(&mut self, base: Reg, offset: i64) -> String {
+        if fits_imm12(offset) {
+            format!("{}({})", offset, base)
+        } else {
+            self.emit(format!("li t3, {}", offset));
+            self.emit(format!("add t3, {}, t3", base));
+            "0(t3)".to_string()
+        }
+    }
+
+    /// `rd = base + offset`.
+    pub(super) fn add_offset(&mut self, rd: Reg, base: Reg, offset: i64) {
+        if offset == 0 {
+            if rd != base {
+                self.emit(format!("mv {}, {}", rd, base));
+            }
+        } else if fits_imm12(offset) {
+            self.emit(format!("addi {}, {}, {}", rd, base, offset));
+        } else {
+            self.emit(format!("li t3, {}", offset));
+            self.emit(format!("add {}, {}, t3", rd, base));
+        }
+    }
+
+    // ─── Operands ────────────────────────────────────────────────
+
+    /// The register holding integer (or pointer, or aggregate address)
+    /// operand `op`; constants and values in memory are loaded into `scratch`.
+    pub(super) fn int_operand(&mut self, op: &Operand, scratch: Reg) -> Reg {
+        match op {
+            Operand::Constant(0) => Reg::ZERO,
+            Operand::Constant(c) => {
+                self.emit(format!("li {}, {}", scratch, c));
+                scratch
+            }
+            Operand::FloatConstant(f) => {
+                self.emit(format!("li {}, {}", scratch, *f as i64));
+                scratch
+            }
+            Operand::Global(name) => {
+                self.emit(format!("la {}, {}", scratch, name));
+                scratch
+            }
+            Operand::Var(var) => {
+                if let Some(&offset) = self.objects.get(var) {
+                    self.add_offset(scratch, Reg::S0, offset.into());
+                    return scratch;
+                }
+                let ty = self.var_type(*var);
+                if is_float_type(&ty) {
+                    // The bit pattern, as moved through an integer register
+                    let freg = self.float_operand(op, Reg::FT2, ty == Type::Double);
+                    self.move_reg(scratch, freg, ty == Type::Double);
+                    return scratch;
+                }
+                match self.home(*var) {
+                    Home::Reg(reg) => reg,
+                    Home::Slot(offset) => {
+                        let mem = self.mem(Reg::S0, offset.into());
+                        self.emit(format!("ld {}, {}", scratch, mem));
+                        scratch
+                    }
+                }
+            }
+        }
+    }
+
+    /// The float register holding operand `op` as a `double`, or a `float`
+    /// unless `double`; values are converted and loaded into `scratch`.
+    pub(super) fn float_operand(&mut self, op: &Operand, scratch: Reg, double: bool) -> Reg {
+        let suffix = if double { "d" } else { "s" };
+        match op {
+            Operand::FloatConstant(f) => self.float_constant(*f, scratch, double),
+            Operand::Constant(c) => self.float_constant(*c as f64, scratch, double),
+            Operand::Global(_) => {
+                let reg = self.int_operand(op, Reg::T3);
+                self.emit(format!("fcvt.{}.l {}, {}", suffix, scratch, reg));
+                scratch
+            }
+            Operand::Var(var) => {
+                let ty = self.var_type(*var);
+                if !is_float_type(&ty) {
+                    let reg = self.int_operand(op, Reg::T3);
+                    let from = if is_unsigned(&ty) { "lu" } else { "l" };
+                    self.emit(format!("fcvt.{}.{} {}, {}", suffix, from, scratch, reg));
+                    return scratch;
+                }
+                let is_double = ty == Type::Double;
+                let reg = match self.home(*var) {
+                    Home::Reg(reg) => reg,
+                    Home::Slot(offset) => {
+                        let mem = self.mem(Reg::S0, offset.into());
+                        self.emit(format!("{} {}, {}", float_load(is_double), scratch, mem));
+                        scratch
+                    }
+                };
+                match (is_double, double) {
+                    (true, false) => self.emit(format!("fcvt.s.d {}, {}", scratch, reg)),
+                    (false, true) => self.emit(format!("fcvt.d.s {}, {}", scratch, reg)),
+                    _ => return reg,
+                }
+                scratch
+            }
+        }
+    }
+
+    /// Load a floating-point constant: +0.0 from the zero register, anything
+    /// else from the constant pool.
+    fn float_constant(&mut self, value: f64, rd: Reg, double: bool) -> Reg {
+        if value.to_bits() == 0 {
+            let op = if double { "fmv.d.x" } else { "fmv.w.x" };
+            self.emit(format!("{} {}, zero", op, rd));
+            return rd;
+        }
+        let bits = value.to_bits();
+        let label = self.cg.float_constants.iter()
+            .find(|(_, (v, d))| v.to_bits() == bits && *d == double)
+            .map(|(label, _)| label.clone());
+        let label = label.unwrap_or_else(|| {
+            let label = format!(".LC{}", self.cg.next_float_const);
+            self.cg.next_float_const += 1;
+            self.cg.float_constants.insert(label.clone(), (value, double));
+            label
+        });
+        self.emit(format!("lla t3, {}", label));
+        self.emit(format!("{} {}, 0(t3)", float_load(double), rd));
+        rd
+    }
+
+    /// The register to compute `dest` in: its own if it has one of
+    /// `scratch`'s class, else `scratch`. `write_dest` then stores it.
+    pub(super) fn dest_reg(&self, dest: VarId, scratch: Reg) -> Reg {
+        match self.regs.get(&dest) {
+            Some(reg) if reg.is_float() == scratch.is_float() => *reg,
+            _ => scratch,
+        }
+    }
+
+    /// Put `reg`, holding the value of `dest`, in `dest`'s home.
+    pub(super) fn write_dest(&mut self, dest: VarId, reg: Reg) {
+        let double = self.var_type(dest) != Type::Float;
+        match self.home(dest) {
+            Home::Reg(home) => self.move_reg(home, reg, double),
+            Home::Slot(offset) => {
+                let mem = self.mem(Reg::S0, offset.into());
+                let op = match (reg.is_float(), double) {
+                    (false, _) => "sd",
+                    (true, true) => "fsd",
+                    (true, false) => "fsw",
+                };
+                self.emit(format!("{} {}, {}", op, reg, mem));
+            }
+        }
+    }
+
+    /// `rd = rs`, between registers of either class; `double` gives the
+    /// width of a bit pattern moved between classes.
+    pub(super) fn move_reg(&mut self, rd: Reg, rs: Reg, double: bool) {
+        if rd == rs {
+            return;
+        }
+        let w = if double { "d" } else { "w" };
+        match (rd.is_float(), rs.is_float()) {
+            (false, false) => self.emit(format!("mv {}, {}", rd, rs)),
+            (true, true) => self.emit(format!("fmv.d {}, {}", rd, rs)),
+            (false, true) => self.emit(format!("fmv.x.{} {}, {}", w, rd, rs)),
+            (true, false) => self.emit(format!("fmv.{}.x {}, {}", w, rd, rs)),
+        }
+    }
+
+    /// Reduce `reg` to the values of integer type `ty`, as the IR
+    /// interpreter does: registers hold every integer sign- or zero-extended
+    /// to 64 bits according to its own type.
+    pub(super) fn narrow(&mut self, reg: Reg, ty: &Type) {
+        if *ty == Type::Bool {
+            self.emit(format!("snez {}, {}", reg, reg));
+            return;
+        }
+        let (size, unsigned) = int_kind(ty);
+        match (size, unsigned) {
+            (1, true) => self.emit(format!("andi {}, {}, 255", reg, reg)),
+            (4, false) => self.emit(format!("sext.w {}, {}", reg, reg)),
+            (1 | 2 | 4, _) => {
+                let shift = 64 - 8 * size;
+                let right = if unsigned { "srli" } else { "srai" };
+                self.emit(format!("slli {}, {}, {}", reg, reg, shift));
+                self.emit(format!("{} {}, {}, {}", right, reg, reg, shift));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Round `value` up to a multiple of `align`.
+pub(super) fn align_to(value: i32, align: i32) -> i32 {
+    (value + align - 1) / align * align
+}
+
+pub(super) fn is_float_type(ty: &Type) -> bool {
+    matches!(ty, Type::Float | Type::Double)
+}
+
+/// Whether integer type `ty` is unsigned, by the IR interpreter's rules.
+pub(super) fn is_unsigned(ty: &Type) -> bool {
+    match ty {
+        Type::UnsignedChar | Type::UnsignedShort | Type::UnsignedInt | Type::UnsignedLong
+        | Type::UnsignedLongLong | Type::Bool => true,
+        Type::Typedef(name) => name.starts_with('u') || name == "size_t",
+        _ => false,
+    }
+}
+
+/// Size in bytes and signedness of a scalar integer type; pointers and
+/// everything else are 8-byte values.
+pub(super) fn int_kind(ty: &Type) -> (usize, bool) {
+    let size = match ty {
+        Type::Char | Type::UnsignedChar | Type::Bool => 1,
+        Type::Short | Type::UnsignedShort => 2,
+        Type::Int | Type::UnsignedInt | Type::Enum(_) | Type::Float => 4,
+        Type::Typedef(name) => match name.as_str() {
+            "int8_t" | "uint8_t" | "int8" | "uint8" => 1,
+            "int16_t" | "uint16_t" | "int16" | "uint16" => 2,
+            "int32_t" | "uint32_t" | "int32" | "uint32" => 4,
+            _ => 8,
+        },
+        _ => 8,
+    };
+    (size, is_unsigned(ty))
+}
+
+/// The load instruction reading a value of type `ty` into an integer register.
+pub(super) fn int_load(ty: &Type) -> &'static str {
+    match int_kind(ty) {
+        (1, true) => "lbu",
+        (1, false) => "lb",
+        (2, true) => "lhu",
+        (2, false) => "lh",
+        (4, true) => "lwu",
+        (4, false) => "lw",
+        _ => "ld",
+    }
+}
+
+/// The store instruction writing the low `size` bytes of an integer register.
+pub(super) fn int_store(size: usize) -> &'static str {
+    match size {
+        1 => "sb",
+        2 => "sh",
+        4 => "sw",
+        _ => "sd",
+    }
+}
+
+pub(super) fn float_load(double: bool) -> &'static str {
+    if double { "fld" } else { "flw" }
+}
+
+pub(super) fn float_store(double: bool) -> &'static str {
+    if double { "fsd" } else { "fsw" }
+}
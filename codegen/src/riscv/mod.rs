@@ -0,0 +1,153 @@
+// RISC-V backend: RV64GC assembly with the LP64D calling convention.
+//
+// The same IR and register allocation framework as the x86-64 backend, but
+// instructions are emitted straight as GNU assembler text: RISC-V code has
+// no second syntax and nothing for a peephole pass over `X86Instr` to do.
+mod abi;
+mod regalloc;
+mod function;
+mod ops;
+mod call_ops;
+mod builtins;
+mod control_flow;
+
+pub(crate) use function::FunctionGenerator;
+
+use std::fmt;
+
+/// A RISC-V register: `X(n)` is integer register xn, `F(n)` float register fn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Reg {
+    X(u8),
+    F(u8),
+}
+
+const X_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+const F_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+impl Reg {
+    pub const ZERO: Reg = Reg::X(0);
+    /// Frame pointer: the canonical frame address, where `sp` was on entry
+    pub const S0: Reg = Reg::X(8);
+    // Integer scratch registers
+    pub const T0: Reg = Reg::X(5);
+    pub const T1: Reg = Reg::X(6);
+    pub const T2: Reg = Reg::X(7);
+    /// Scratch for addresses: globals, far frame offsets, constant pool
+    pub const T3: Reg = Reg::X(28);
+    // Float scratch registers
+    pub const FT0: Reg = Reg::F(0);
+    pub const FT1: Reg = Reg::F(1);
+    pub const FT2: Reg = Reg::F(2);
+
+    /// Integer argument register `a{n}`
+    pub fn a(n: u8) -> Reg {
+        Reg::X(10 + n)
+    }
+
+    /// Float argument register `fa{n}`
+    pub fn fa(n: u8) -> Reg {
+        Reg::F(10 + n)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Reg::F(_))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Reg::X(n) => X_NAMES[n as usize],
+            Reg::F(n) => F_NAMES[n as usize],
+        }
+    }
+
+    /// Allocatable registers a call may clobber: t4–t6.
+    pub fn caller_saved() -> Vec<Reg> {
+        (29..=31).map(Reg::X).collect()
+    }
+
+    /// Allocatable registers the callee preserves: s1–s11 (s0 is the frame pointer).
+    pub fn callee_saved() -> Vec<Reg> {
+        std::iter::once(9).chain(18..=27).map(Reg::X).collect()
+    }
+
+    /// Allocatable float registers a call may clobber: ft3–ft11.
+    pub fn caller_saved_float() -> Vec<Reg> {
+        (3..=7).chain(28..=31).map(Reg::F).collect()
+    }
+
+    /// Allocatable float registers the callee preserves: fs0–fs11.
+    pub fn callee_saved_float() -> Vec<Reg> {
+        (8..=9).chain(18..=27).map(Reg::F).collect()
+    }
+
+    /// The register an inline asm clobber or operand names, if any.
+    pub fn from_name(name: &str) -> Option<Reg> {
+        let name = match name {
+            "fp" => "s0",
+            name => name,
+        };
+        if let Some(n) = X_NAMES.iter().position(|r| *r == name) {
+            return Some(Reg::X(n as u8));
+        }
+        if let Some(n) = F_NAMES.iter().position(|r| *r == name) {
+            return Some(Reg::F(n as u8));
+        }
+        // Architectural names: x0–x31, f0–f31
+        let (class, num) = name.split_at(1.min(name.len()));
+        let n: u8 = num.parse().ok().filter(|n| *n < 32)?;
+        match class {
+            "x" => Some(Reg::X(n)),
+            "f" => Some(Reg::F(n)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Whether `imm` fits the signed 12-bit immediate of I- and S-type instructions.
+pub(crate) fn fits_imm12(imm: i64) -> bool {
+    (-2048..2048).contains(&imm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_names() {
+        assert_eq!(Reg::a(0).name(), "a0");
+        assert_eq!(Reg::fa(7).name(), "fa7");
+        assert_eq!(Reg::S0.name(), "s0");
+        assert_eq!(Reg::T3.name(), "t3");
+        assert_eq!(Reg::callee_saved().len(), 11);
+        assert_eq!(Reg::callee_saved_float().len(), 12);
+    }
+
+    #[test]
+    fn register_from_name() {
+        assert_eq!(Reg::from_name("a5"), Some(Reg::a(5)));
+        assert_eq!(Reg::from_name("x10"), Some(Reg::a(0)));
+        assert_eq!(Reg::from_name("fs1"), Some(Reg::F(9)));
+        assert_eq!(Reg::from_name("f31"), Some(Reg::F(31)));
+        assert_eq!(Reg::from_name("fp"), Some(Reg::S0));
+        assert_eq!(Reg::from_name("memory"), None);
+        assert_eq!(Reg::from_name("x32"), None);
+    }
+}
@@ -0,0 +1,571 @@
+// Arithmetic, conversions, memory access, and inline assembly
+use ir::{Operand, VarId};
+use model::{BinaryOp, Type, UnaryOp};
+use super::{fits_imm12, Reg};
+use super::abi::is_aggregate;
+use super::function::{
+    float_load, float_store, int_kind, int_load, int_store, is_float_type, is_unsigned, FunctionGenerator, Home,
+};
+
+impl FunctionGenerator<'_> {
+    // ─── Integer arithmetic ──────────────────────────────────────
+    //
+    // Operations run on 64-bit values and the result is narrowed to the
+    // destination type. Comparisons and division are unsigned when either
+    // operand is, and a right shift when its left operand is.
+
+    pub(super) fn gen_binary(&mut self, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand) {
+        let dest_ty = self.var_type(dest);
+        let left_unsigned = is_unsigned(&self.operand_type(left));
+        let unsigned = left_unsigned || is_unsigned(&self.operand_type(right));
+        // `int` results of add, subtract, multiply, and left shift come out
+        // of the 32-bit instructions already sign-extended
+        let word = matches!(dest_ty, Type::Int | Type::Enum(_))
+            && matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::ShiftLeft);
+        let w = if word { "w" } else { "" };
+        let u = if unsigned { "u" } else { "" };
+
+        let l = self.int_operand(left, Reg::T0);
+        let d = self.dest_reg(dest, Reg::T0);
+        let immediate = match (op, right) {
+            (BinaryOp::Add, Operand::Constant(c)) if fits_imm12(*c) => Some(format!("addi{} {}, {}, {}", w, d, l, c)),
+            (BinaryOp::Sub, Operand::Constant(c)) if fits_imm12(-*c) => Some(format!("addi{} {}, {}, {}", w, d, l, -c)),
+            (BinaryOp::BitwiseAnd, Operand::Constant(c)) if fits_imm12(*c) => Some(format!("andi {}, {}, {}", d, l, c)),
+            (BinaryOp::BitwiseOr, Operand::Constant(c)) if fits_imm12(*c) => Some(format!("ori {}, {}, {}", d, l, c)),
+            (BinaryOp::BitwiseXor, Operand::Constant(c)) if fits_imm12(*c) => Some(format!("xori {}, {}, {}", d, l, c)),
+            (BinaryOp::ShiftLeft, Operand::Constant(c)) if word && (0..32).contains(c) => Some(format!("slliw {}, {}, {}", d, l, c)),
+            (BinaryOp::ShiftLeft, Operand::Constant(c)) if !word => Some(format!("slli {}, {}, {}", d, l, c & 63)),
+            (BinaryOp::ShiftRight, Operand::Constant(c)) => {
+                let op = if left_unsigned { "srli" } else { "srai" };
+                Some(format!("{} {}, {}, {}", op, d, l, c & 63))
+            }
+            (BinaryOp::Less, Operand::Constant(c)) if fits_imm12(*c) => Some(format!("slti{} {}, {}, {}", u, d, l, c)),
+            _ => None,
+        };
+        if let Some(instr) = immediate {
+            self.emit(instr);
+        } else {
+            let r = self.int_operand(right, Reg::T1);
+            let lines = match op {
+                BinaryOp::Add => vec![format!("add{} {}, {}, {}", w, d, l, r)],
+                BinaryOp::Sub => vec![format!("sub{} {}, {}, {}", w, d, l, r)],
+                BinaryOp::Mul => vec![format!("mul{} {}, {}, {}", w, d, l, r)],
+                BinaryOp::Div => vec![format!("div{} {}, {}, {}", u, d, l, r)],
+                BinaryOp::Mod => vec![format!("rem{} {}, {}, {}", u, d, l, r)],
+                BinaryOp::BitwiseAnd => vec![format!("and {}, {}, {}", d, l, r)],
+                BinaryOp::BitwiseOr => vec![format!("or {}, {}, {}", d, l, r)],
+                BinaryOp::BitwiseXor => vec![format!("xor {}, {}, {}", d, l, r)],
+                BinaryOp::ShiftLeft => vec![format!("sll{} {}, {}, {}", w, d, l, r)],
+                BinaryOp::ShiftRight => {
+                    let op = if left_unsigned { "srl" } else { "sra" };
+                    vec![format!("{} {}, {}, {}", op, d, l, r)]
+                }
+                BinaryOp::EqualEqual => vec![format!("xor {}, {}, {}", d, l, r), format!("seqz {}, {}", d, d)],
+                BinaryOp::NotEqual => vec![format!("xor {}, {}, {}", d, l, r), format!("snez {}, {}", d, d)],
+                BinaryOp::Less => vec![format!("slt{} {}, {}, {}", u, d, l, r)],
+                BinaryOp::Greater => vec![format!("slt{} {}, {}, {}", u, d, r, l)],
+                BinaryOp::LessEqual => vec![format!("slt{} {}, {}, {}", u, d, r, l), format!("xori {}, {}, 1", d, d)],
+                BinaryOp::GreaterEqual => vec![format!("slt{} {}, {}, {}", u, d, l, r), format!("xori {}, {}, 1", d, d)],
+                BinaryOp::LogicalAnd => vec![
+                    format!("snez t2, {}", l),
+                    format!("snez {}, {}", d, r),
+                    format!("and {}, {}, t2", d, d),
+                ],
+                BinaryOp::LogicalOr => vec![format!("or {}, {}, {}", d, l, r), format!("snez {}, {}", d, d)],
+                // Compound assignments are lowered to the plain operators
+                _ => Vec::new(),
+            };
+            for line in lines {
+                self.emit(line);
+            }
+        }
+        let is_comparison = matches!(
+            op,
+            BinaryOp::EqualEqual | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+                | BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::LogicalAnd | BinaryOp::LogicalOr
+        );
+        if !word && !is_comparison {
+            self.narrow(d, &dest_ty);
+        }
+        self.write_dest(dest, d);
+    }
+
+    pub(super) fn gen_unary(&mut self, dest: VarId, op: &UnaryOp, src: &Operand) {
+        let dest_ty = self.var_type(dest);
+        let s = self.int_operand(src, Reg::T0);
+        let d = self.dest_reg(dest, Reg::T0);
+        match op {
+            UnaryOp::Minus => self.emit(format!("neg {}, {}", d, s)),
+            UnaryOp::BitwiseNot => self.emit(format!("not {}, {}", d, s)),
+            UnaryOp::LogicalNot => self.emit(format!("seqz {}, {}", d, s)),
+            _ => self.move_reg(d, s, true),
+        }
+        if !matches!(op, UnaryOp::LogicalNot) {
+            self.narrow(d, &dest_ty);
+        }
+        self.write_dest(dest, d);
+    }
+
+    // ─── Floating point ──────────────────────────────────────────
+
+    pub(super) fn gen_float_binary(&mut self, dest: VarId, op: &BinaryOp, left: &Operand, right: &Operand) {
+        let is_float_var = |op: &Operand| matches!(op, Operand::Var(v) if self.var_type(*v) == Type::Float);
+        let arithmetic = matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div);
+        // Arithmetic rounds to the destination's precision; comparisons are
+        // exact in double unless both operands are `float`
+        let double = if arithmetic {
+            self.var_type(dest) != Type::Float
+        } else {
+            !(is_float_var(left) && is_float_var(right))
+        };
+        let p = if double { "d" } else { "s" };
+        let l = self.float_operand(left, Reg::FT0, double);
+        let r = self.float_operand(right, Reg::FT1, double);
+        if arithmetic {
+            let d = self.dest_reg(dest, Reg::FT0);
+            let mnemonic = match op {
+                BinaryOp::Add => "fadd",
+                BinaryOp::Sub => "fsub",
+                BinaryOp::Mul => "fmul",
+                _ => "fdiv",
+            };
+            self.emit(format!("{}.{} {}, {}, {}", mnemonic, p, d, l, r));
+            self.write_dest(dest, d);
+            return;
+        }
+        let d = self.dest_reg(dest, Reg::T0);
+        match op {
+            BinaryOp::EqualEqual => self.emit(format!("feq.{} {}, {}, {}", p, d, l, r)),
+            BinaryOp::NotEqual => {
+                self.emit(format!("feq.{} {}, {}, {}", p, d, l, r));
+                self.emit(format!("xori {}, {}, 1", d, d));
+            }
+            BinaryOp::Less => self.emit(format!("flt.{} {}, {}, {}", p, d, l, r)),
+            BinaryOp::LessEqual => self.emit(format!("fle.{} {}, {}, {}", p, d, l, r)),
+            BinaryOp::Greater => self.emit(format!("flt.{} {}, {}, {}", p, d, r, l)),
+            BinaryOp::GreaterEqual => self.emit(format!("fle.{} {}, {}, {}", p, d, r, l)),
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+                // feq against zero gives 1 for a false operand
+                self.emit(format!("fmv.{}.x ft2, zero", if double { "d" } else { "w" }));
+                self.emit(format!("feq.{} t1, {}, ft2", p, l));
+                self.emit(format!("feq.{} t2, {}, ft2", p, r));
+                let combine = if matches!(op, BinaryOp::LogicalAnd) { "or" } else { "and" };
+                self.emit(format!("{} {}, t1, t2", combine, d));
+                self.emit(format!("seqz {}, {}", d, d));
+            }
+            _ => {}
+        }
+        self.write_dest(dest, d);
+    }
+
+    pub(super) fn gen_float_unary(&mut self, dest: VarId, op: &UnaryOp, src: &Operand) {
+        let src_double = match src {
+            Operand::Var(v) => self.var_type(*v) != Type::Float,
+            _ => true,
+        };
+        let dest_double = self.var_type(dest) != Type::Float;
+        if let UnaryOp::LogicalNot = op {
+            let p = if src_double { "d" } else { "s" };
+            let s = self.float_operand(src, Reg::FT0, src_double);
+            self.emit(format!("fmv.{}.x ft1, zero", if src_double { "d" } else { "w" }));
+            let d = self.dest_reg(dest, Reg::T0);
+            self.emit(format!("feq.{} {}, {}, ft1", p, d, s));
+            self.write_dest(dest, d);
+            return;
+        }
+        let s = self.float_operand(src, Reg::FT0, dest_double);
+        let d = self.dest_reg(dest, Reg::FT0);
+        match op {
+            UnaryOp::Minus => self.emit(format!("fneg.{} {}, {}", if dest_double { "d" } else { "s" }, d, s)),
+            _ => self.move_reg(d, s, dest_double),
+        }
+        self.write_dest(dest, d);
+    }
+
+    // ─── Copies and conversions ──────────────────────────────────
+
+    pub(super) fn gen_copy(&mut self, dest: VarId, src: &Operand) {
+        let ty = self.var_type(dest);
+        let reg = if is_float_type(&ty) {
+            let scratch = self.dest_reg(dest, Reg::FT0);
+            self.float_operand(src, scratch, ty == Type::Double)
+        } else {
+            let scratch = self.dest_reg(dest, Reg::T0);
+            self.int_operand(src, scratch)
+        };
+        self.write_dest(dest, reg);
+    }
+
+    pub(super) fn gen_cast(&mut self, dest: VarId, src: &Operand, src_type: &Type, to: &Type) {
+        let from_float = is_float_type(src_type) || matches!(src, Operand::FloatConstant(_));
+        match (from_float, is_float_type(to)) {
+            (false, false) => {
+                let d = self.dest_reg(dest, Reg::T0);
+                let s = self.int_operand(src, d);
+                self.move_reg(d, s, true);
+                self.narrow(d, to);
+                self.write_dest(dest, d);
+            }
+            (false, true) => {
+                let s = self.int_operand(src, Reg::T0);
+                let d = self.dest_reg(dest, Reg::FT0);
+                let from = if is_unsigned(src_type) { "lu" } else { "l" };
+                let p = if *to == Type::Double { "d" } else { "s" };
+                self.emit(format!("fcvt.{}.{} {}, {}", p, from, d, s));
+                self.write_dest(dest, d);
+            }
+            (true, false) => {
+                let double = *src_type != Type::Float;
+                let p = if double { "d" } else { "s" };
+                let s = self.float_operand(src, Reg::FT0, double);
+                let d = self.dest_reg(dest, Reg::T0);
+                if *to == Type::Bool {
+                    self.emit(format!("fmv.{}.x ft1, zero", if double { "d" } else { "w" }));
+                    self.emit(format!("feq.{} {}, {}, ft1", p, d, s));
+                    self.emit(format!("xori {}, {}, 1", d, d));
+                } else {
+                    let into = if is_unsigned(to) { "lu" } else { "l" };
+                    self.emit(format!("fcvt.{}.{} {}, {}, rtz", into, p, d, s));
+                    self.narrow(d, to);
+                }
+                self.write_dest(dest, d);
+            }
+            (true, true) => {
+                let d = self.dest_reg(dest, Reg::FT0);
+                let s = self.float_operand(src, d, *to == Type::Double);
+                self.write_dest(dest, s);
+            }
+        }
+    }
+
+    // ─── Memory ──────────────────────────────────────────────────
+
+    /// The memory operand for the object at `addr`: an `alloca` object,
+    /// a global, or a pointer value (loaded into t1 if in memory).
+    pub(super) fn address(&mut self, addr: &Operand) -> String {
+        match addr {
+            Operand::Var(var) if self.objects.contains_key(var) => {
+                let offset = self.objects[var];
+                self.mem(Reg::S0, offset.into())
+            }
+            Operand::Global(name) => {
+                self.emit(format!("la t3, {}", name));
+                "0(t3)".to_string()
+            }
+            _ => {
+                let base = self.int_operand(addr, Reg::T1);
+                format!("0({})", base)
+            }
+        }
+    }
+
+    /// `rd = addr`, the address an operand names.
+    pub(super) fn address_into(&mut self, rd: Reg, addr: &Operand) {
+        let reg = self.int_operand(addr, rd);
+        self.move_reg(rd, reg, true);
+    }
+
+    pub(super) fn gen_load(&mut self, dest: VarId, addr: &Operand, ty: &Type) {
+        if is_aggregate(ty) {
+            // The value is a copy of the object, in a frame temporary
+            let buffer = self.alloc_aggregate(ty);
+            self.address_into(Reg::T1, addr);
+            self.add_offset(Reg::T2, Reg::S0, buffer.into());
+            self.copy_memory(self.size_of(ty));
+            let d = self.dest_reg(dest, Reg::T0);
+            self.add_offset(d, Reg::S0, buffer.into());
+            self.write_dest(dest, d);
+            return;
+        }
+        let mem = self.address(addr);
+        let d = if is_float_type(ty) {
+            let d = self.dest_reg(dest, Reg::FT0);
+            self.emit(format!("{} {}, {}", float_load(*ty == Type::Double), d, mem));
+            d
+        } else {
+            let d = self.dest_reg(dest, Reg::T0);
+            self.emit(format!("{} {}, {}", int_load(ty), d, mem));
+            d
+        };
+        self.write_dest(dest, d);
+    }
+
+    pub(super) fn gen_store(&mut self, addr: &Operand, src: &Operand, ty: &Type) {
+        if is_aggregate(ty) {
+            self.address_into(Reg::T1, src);
+            self.address_into(Reg::T2, addr);
+            self.copy_memory(self.size_of(ty));
+            return;
+        }
+        if is_float_type(ty) {
+            let double = *ty == Type::Double;
+            let value = self.float_operand(src, Reg::FT0, double);
+            let mem = self.address(addr);
+            self.emit(format!("{} {}, {}", float_store(double), value, mem));
+            return;
+        }
+        let mut value = self.int_operand(src, Reg::T0);
+        if *ty == Type::Bool {
+            self.emit(format!("snez t0, {}", value));
+            value = Reg::T0;
+        }
+        let mem = self.address(addr);
+        self.emit(format!("{} {}, {}", int_store(int_kind(ty).0), value, mem));
+    }
+
+    pub(super) fn gen_gep(&mut self, dest: VarId, base: &Operand, index: &Operand, element_type: &Type) {
+        let size = self.size_of(element_type) as i64;
+        let b = self.int_operand(base, Reg::T0);
+        let d = self.dest_reg(dest, Reg::T0);
+        if let Operand::Constant(c) = index {
+            self.add_offset(d, b, c.wrapping_mul(size));
+        } else {
+            let i = self.int_operand(index, Reg::T1);
+            let scaled = match size {
+                1 => i,
+                size if size > 0 && size & (size - 1) == 0 => {
+                    self.emit(format!("slli t1, {}, {}", i, size.trailing_zeros()));
+                    Reg::T1
+                }
+                size => {
+                    self.emit(format!("li t2, {}", size));
+                    self.emit(format!("mul t1, {}, t2", i));
+                    Reg::T1
+                }
+            };
+            self.emit(format!("add {}, {}, {}", d, b, scaled));
+        }
+        self.write_dest(dest, d);
+    }
+
+    /// Copy `size` bytes from the address in t1 to the address in t2, in
+    /// 8/4/2/1-byte pieces, looping over the 8-byte words of large copies.
+    /// Clobbers t0–t3.
+    pub(super) fn copy_memory(&mut self, size: usize) {
+        let mut offset = 0;
+        if size > 64 {
+            let words = size / 8;
+            let top = self.new_label();
+            self.emit(format!("li t3, {}", words));
+            self.label(&top);
+            self.emit("ld t0, 0(t1)");
+            self.emit("sd t0, 0(t2)");
+            self.emit("addi t1, t1, 8");
+            self.emit("addi t2, t2, 8");
+            self.emit("addi t3, t3, -1");
+            self.emit(format!("bnez t3, {}", top));
+            offset = words * 8;
+        }
+        let mut pos = 0;
+        for chunk in memory_chunks(size - offset) {
+            self.emit(format!("{} t0, {}(t1)", int_load(&chunk_type(chunk)), pos));
+            self.emit(format!("{} t0, {}(t2)", int_store(chunk), pos));
+            pos += chunk;
+        }
+    }
+
+    pub(super) fn gen_memset(&mut self, dest: &Operand, value: &Operand, size: usize) {
+        self.address_into(Reg::T2, dest);
+        // The byte repeated across a word
+        match value {
+            Operand::Constant(c) => {
+                let pattern = u64::from(*c as u8).wrapping_mul(0x0101_0101_0101_0101);
+                self.emit(format!("li t0, {}", pattern as i64));
+            }
+            _ => {
+                let v = self.int_operand(value, Reg::T0);
+                self.emit(format!("andi t0, {}, 255", v));
+                self.emit(format!("li t1, {}", 0x0101_0101_0101_0101_i64));
+                self.emit("mul t0, t0, t1");
+            }
+        }
+        let mut offset = 0;
+        if size > 64 {
+            let words = size / 8;
+            let top = self.new_label();
+            self.emit(format!("li t3, {}", words));
+            self.label(&top);
+            self.emit("sd t0, 0(t2)");
+            self.emit("addi t2, t2, 8");
+            self.emit("addi t3, t3, -1");
+            self.emit(format!("bnez t3, {}", top));
+            offset = words * 8;
+        }
+        let mut pos = 0;
+        for chunk in memory_chunks(size - offset) {
+            self.emit(format!("{} t0, {}(t2)", int_store(chunk), pos));
+            pos += chunk;
+        }
+    }
+
+    // ─── Inline assembly ─────────────────────────────────────────
+
+    /// Substitute the operands into `template`: `%N` names output N, or
+    /// input N minus the number of outputs, and `%%` is a percent sign.
+    /// Register operands ("r", "f", "+r", matching digits) are staged in
+    /// a0–a7 and fa0–fa7, "m" operands in a stack slot name it, and
+    /// "i"/"n" constants appear as numbers.
+    pub(super) fn gen_inline_asm(
+        &mut self,
+        template: &str,
+        outputs: &[VarId],
+        inputs: &[Operand],
+        output_constraints: &[String],
+        input_constraints: &[String],
+    ) {
+        let (mut next_int, mut next_float) = (0u8, 0u8);
+        let mut stage = |float: bool| {
+            if float {
+                next_float += 1;
+                Reg::fa(next_float - 1)
+            } else {
+                next_int += 1;
+                Reg::a(next_int - 1)
+            }
+        };
+        let mut rendered: Vec<String> = Vec::new();
+        let mut written: Vec<(VarId, Reg)> = Vec::new();
+        // Outputs are the `alloca` objects of the lvalues written
+        for (i, var) in outputs.iter().enumerate() {
+            let constraint = output_constraints.get(i).map_or("", String::as_str);
+            let float = constraint.contains('f');
+            let object = self.objects.get(var).copied();
+            if constraint.contains('m') && let Some(offset) = object {
+                rendered.push(self.mem(Reg::S0, offset.into()));
+                continue;
+            }
+            let reg = stage(float);
+            if constraint.contains('+') {
+                self.load_output(*var, reg);
+            }
+            rendered.push(reg.to_string());
+            written.push((*var, reg));
+        }
+        for (i, input) in inputs.iter().enumerate() {
+            let constraint = input_constraints.get(i).map_or("", String::as_str);
+            if let Ok(n) = constraint.parse::<usize>()
+                && let Some(&(_, reg)) = written.iter().find(|(v, _)| Some(v) == outputs.get(n))
+            {
+                let value = self.int_operand(input, reg);
+                self.move_reg(reg, value, true);
+                rendered.push(reg.to_string());
+                continue;
+            }
+            if (constraint.contains('i') || constraint.contains('n')) && let Operand::Constant(c) = input {
+                rendered.push(c.to_string());
+                continue;
+            }
+            if constraint.contains('m') {
+                match input {
+                    Operand::Var(var) if self.objects.contains_key(var) => {
+                        rendered.push(format!("{}(s0)", self.objects[var]));
+                        continue;
+                    }
+                    Operand::Var(var) if !self.regs.contains_key(var) => {
+                        let Home::Slot(offset) = self.home(*var) else { unreachable!() };
+                        rendered.push(format!("{}(s0)", offset));
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            let float = constraint.contains('f');
+            let reg = stage(float);
+            let value = if float {
+                let double = self.operand_type(input) != Type::Float;
+                self.float_operand(input, reg, double)
+            } else {
+                self.int_operand(input, reg)
+            };
+            self.move_reg(reg, value, true);
+            rendered.push(reg.to_string());
+        }
+
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                text.push('%');
+                continue;
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(*d);
+                chars.next();
+            }
+            match digits.parse::<usize>().ok().and_then(|n| rendered.get(n)) {
+                Some(operand) => text.push_str(operand),
+                None => {
+                    text.push('%');
+                    text.push_str(&digits);
+                }
+            }
+        }
+        for line in text.lines() {
+            let line = line.trim();
+            if !line.is_empty() {
+                self.emit(line);
+            }
+        }
+
+        for (var, reg) in written {
+            self.store_output(var, reg);
+        }
+    }
+
+    /// `reg` = the current value of inline asm output `var`.
+    fn load_output(&mut self, var: VarId, reg: Reg) {
+        let Some(&offset) = self.objects.get(&var) else {
+            let value = if reg.is_float() {
+                self.float_operand(&Operand::Var(var), reg, self.var_type(var) == Type::Double)
+            } else {
+                self.int_operand(&Operand::Var(var), reg)
+            };
+            self.move_reg(reg, value, true);
+            return;
+        };
+        let ty = self.object_types[&var].clone();
+        let mem = self.mem(Reg::S0, offset.into());
+        let load = if reg.is_float() { float_load(ty == Type::Double) } else { int_load(&ty) };
+        self.emit(format!("{} {}, {}", load, reg, mem));
+    }
+
+    /// Write `reg`, the value inline asm left for output `var`, to `var`.
+    fn store_output(&mut self, var: VarId, reg: Reg) {
+        let Some(&offset) = self.objects.get(&var) else {
+            self.write_dest(var, reg);
+            return;
+        };
+        let ty = self.object_types[&var].clone();
+        let mem = self.mem(Reg::S0, offset.into());
+        let store = if reg.is_float() { float_store(ty == Type::Double) } else { int_store(int_kind(&ty).0) };
+        self.emit(format!("{} {}, {}", store, reg, mem));
+    }
+}
+
+/// Piece sizes for copying `size` bytes: 8-byte pieces, then 4, 2, and 1.
+fn memory_chunks(mut size: usize) -> Vec<usize> {
+    let mut chunks = Vec::new();
+    for chunk in [8, 4, 2, 1] {
+        while size >= chunk {
+            chunks.push(chunk);
+            size -= chunk;
+        }
+    }
+    chunks
+}
+
+/// An unsigned integer type of `size` bytes, to load copy pieces with.
+fn chunk_type(size: usize) -> Type {
+    match size {
+        1 => Type::UnsignedChar,
+        2 => Type::UnsignedShort,
+        4 => Type::UnsignedInt,
+        _ => Type::UnsignedLong,
+    }
+}
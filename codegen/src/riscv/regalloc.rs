@@ -0,0 +1,160 @@
+// Register allocation for the RISC-V backend
+//
+// Uses the live intervals, interference graph, and heuristics of the x86-64
+// allocator, colored with RISC-V registers: integer variables (including
+// pointers and the addresses that stand for aggregates) take t4–t6 and
+// s1–s11, and `float`/`double` variables ft3–ft11 and fs0–fs11.
+use std::collections::{HashMap, HashSet};
+use ir::{Function as IrFunction, Instruction as IrInstruction, VarId};
+use model::Type;
+use crate::liveness::compute_live_intervals;
+use crate::regalloc::{
+    build_interference_graph, collect_copy_hints, compute_live_across_call, compute_spill_weights,
+    should_use_callee_saved, sort_by_spill_cost, LiveInterval,
+};
+use super::Reg;
+
+/// Assign registers to the variables of `func`, whose types `var_types` gives.
+/// Variables left out of the map live in stack slots.
+pub(super) fn allocate_registers(
+    func: &IrFunction,
+    var_types: &HashMap<VarId, Type>,
+    target: &model::TargetConfig,
+) -> HashMap<VarId, Reg> {
+    let (mut float_intervals, mut intervals): (Vec<_>, Vec<_>) = compute_live_intervals(func)
+        .into_iter()
+        .partition(|i| matches!(var_types.get(&i.var), Some(Type::Float | Type::Double)));
+    intervals.sort_by_key(|i| i.var);
+    float_intervals.sort_by_key(|i| i.var);
+
+    // Registers an inline asm statement clobbers hold no variables at all
+    let clobbered: HashSet<Reg> = func.blocks.iter()
+        .flat_map(|b| &b.instructions)
+        .filter_map(|inst| match inst {
+            IrInstruction::InlineAsm { clobbers, .. } => Some(clobbers),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|name| Reg::from_name(name))
+        .collect();
+    let usable = |regs: Vec<Reg>| -> Vec<Reg> {
+        regs.into_iter().filter(|r| !clobbered.contains(r)).collect()
+    };
+
+    let copy_hints = collect_copy_hints(func);
+    let spill_weights = compute_spill_weights(func);
+    let use_callee_saved = should_use_callee_saved(func, target);
+
+    let mut alloc = HashMap::new();
+    let classes = [
+        (&mut intervals, usable(Reg::caller_saved()), usable(Reg::callee_saved())),
+        (&mut float_intervals, usable(Reg::caller_saved_float()), usable(Reg::callee_saved_float())),
+    ];
+    for (intervals, caller_saved, callee_saved) in classes {
+        let interference = build_interference_graph(intervals);
+        let live_across_call = compute_live_across_call(intervals, func);
+        sort_by_spill_cost(intervals, &spill_weights);
+        color(intervals, &interference, &copy_hints, &live_across_call, use_callee_saved, &caller_saved, &callee_saved, &mut alloc);
+    }
+    alloc
+}
+
+/// Greedy coloring in spill-cost order: the copy source's register if it is
+/// free, then a caller-saved register unless the variable lives across a
+/// call, then a callee-saved one, which larger functions and variables live
+/// across calls may use.
+#[allow(clippy::too_many_arguments)]
+fn color(
+    intervals: &[LiveInterval],
+    interference: &HashMap<VarId, HashSet<VarId>>,
+    copy_hints: &HashMap<VarId, VarId>,
+    live_across_call: &HashSet<VarId>,
+    use_callee_saved: bool,
+    caller_saved: &[Reg],
+    callee_saved: &[Reg],
+    alloc: &mut HashMap<VarId, Reg>,
+) {
+    for interval in intervals {
+        let var = interval.var;
+        let across_call = live_across_call.contains(&var);
+        let used: HashSet<Reg> = interference.get(&var)
+            .map(|neighbors| neighbors.iter().filter_map(|n| alloc.get(n).copied()).collect())
+            .unwrap_or_default();
+        let allowed = |reg: &Reg| {
+            !used.contains(reg)
+                && (callee_saved.contains(reg) || (!across_call && caller_saved.contains(reg)))
+        };
+        let hint = copy_hints.get(&var)
+            .filter(|src| !interference.get(&var).is_some_and(|n| n.contains(src)))
+            .and_then(|src| alloc.get(src).copied())
+            .filter(|reg| allowed(reg));
+        let caller = (!across_call).then(|| caller_saved.iter().copied().find(|r| allowed(r))).flatten();
+        let callee = (use_callee_saved || across_call)
+            .then(|| callee_saved.iter().copied().find(|r| allowed(r)))
+            .flatten();
+        if let Some(reg) = hint.or(caller).or(callee) {
+            alloc.insert(var, reg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::{BasicBlock, BlockId, Operand, Terminator};
+    use model::BinaryOp;
+
+    fn function(instructions: Vec<IrInstruction>, ret: VarId) -> IrFunction {
+        let (label_addrs, labels) = IrFunction::default_meta();
+        IrFunction {
+            name: "f".to_string(),
+            return_type: Type::Int,
+            params: vec![(Type::Int, VarId(0))],
+            blocks: vec![BasicBlock {
+                id: BlockId(0),
+                instructions,
+                terminator: Terminator::Ret(Some(Operand::Var(ret))),
+                is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: model::Span::default(),
+            }],
+            entry_block: BlockId(0),
+            var_types: HashMap::new(),
+            attributes: Vec::new(),
+            is_static: false,
+            label_addrs,
+            labels,
+            local_names: HashMap::new(),
+            span: model::Span::default(),
+        }
+    }
+
+    #[test]
+    fn value_live_across_call_gets_callee_saved_register() {
+        let func = function(vec![
+            IrInstruction::Binary { dest: VarId(1), op: BinaryOp::Add, left: Operand::Var(VarId(0)), right: Operand::Constant(1) },
+            IrInstruction::Call { dest: None, name: "g".to_string(), args: vec![], vararg_types: vec![] },
+            IrInstruction::Binary { dest: VarId(2), op: BinaryOp::Add, left: Operand::Var(VarId(1)), right: Operand::Constant(1) },
+        ], VarId(2));
+        let types = HashMap::from([(VarId(0), Type::Int), (VarId(1), Type::Int), (VarId(2), Type::Int)]);
+        let alloc = allocate_registers(&func, &types, &model::TargetConfig::riscv64());
+        assert!(Reg::callee_saved().contains(&alloc[&VarId(1)]));
+        assert!(Reg::caller_saved().contains(&alloc[&VarId(2)]));
+    }
+
+    #[test]
+    fn clobbered_registers_are_not_allocated() {
+        let clobbers: Vec<String> = ["t4", "t5", "t6"].map(String::from).to_vec();
+        let func = function(vec![
+            IrInstruction::Binary { dest: VarId(1), op: BinaryOp::Add, left: Operand::Var(VarId(0)), right: Operand::Constant(1) },
+            IrInstruction::InlineAsm {
+                template: "nop".to_string(), outputs: vec![], inputs: vec![],
+                output_constraints: vec![], input_constraints: vec![], clobbers, is_volatile: true,
+            },
+            IrInstruction::Binary { dest: VarId(2), op: BinaryOp::Add, left: Operand::Var(VarId(1)), right: Operand::Constant(1) },
+        ], VarId(2));
+        let types = HashMap::from([(VarId(0), Type::Int), (VarId(1), Type::Int), (VarId(2), Type::Int)]);
+        let alloc = allocate_registers(&func, &types, &model::TargetConfig::riscv64());
+        assert!(alloc.values().all(|reg| !Reg::caller_saved().contains(reg)));
+    }
+}
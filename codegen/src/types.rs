@@ -6,6 +6,7 @@ use std::collections::HashMap;
 pub struct TypeCalculator<'a> {
     pub structs: &'a HashMap<String, model::StructDef>,
     pub unions: &'a HashMap<String, model::UnionDef>,
+    pub data_layout: model::DataLayout,
 }
 
 impl<'a> TypeCalculator<'a> {
@@ -13,11 +14,16 @@ impl<'a> TypeCalculator<'a> {
         structs: &'a HashMap<String, model::StructDef>,
         unions: &'a HashMap<String, model::UnionDef>,
     ) -> Self {
-        Self { structs, unions }
+        Self { structs, unions, data_layout: model::DataLayout::default() }
+    }
+
+    pub fn with_data_layout(mut self, data_layout: model::DataLayout) -> Self {
+        self.data_layout = data_layout;
+        self
     }
 
     fn layout(&self) -> model::TypeLayout<'_> {
-        model::TypeLayout::new(self.structs, self.unions)
+        model::TypeLayout::new(self.structs, self.unions).with_data_layout(self.data_layout)
     }
 
     pub fn get_alignment(&self, r#type: &Type) -> usize {
@@ -116,6 +122,7 @@ mod tests {
         let fp = Type::FunctionPointer {
             return_type: Box::new(Type::Int),
             param_types: vec![Type::Int],
+            is_variadic: false,
         };
         assert_eq!(calc.get_type_size(&fp), 8);
     }
@@ -13,6 +13,10 @@ pub enum X86Reg {
     Xmm8, Xmm9, Xmm10, Xmm11, Xmm12, Xmm13, Xmm14, Xmm15, // SSE extended
     Ymm0, Ymm1, Ymm2, Ymm3, Ymm4, Ymm5, Ymm6, Ymm7, // AVX 256-bit registers
     Ymm8, Ymm9, Ymm10, Ymm11, Ymm12, Ymm13, Ymm14, Ymm15, // AVX extended
+    /// Base of the stack frame while a function is generated: RBP, or RSP
+    /// plus the frame size under `-fomit-frame-pointer`. Resolved before
+    /// peephole optimization, once the frame size is known.
+    Frame,
 }
 
 impl X86Reg {
@@ -36,6 +40,7 @@ impl X86Reg {
             Self::Ymm4 => "ymm4", Self::Ymm5 => "ymm5", Self::Ymm6 => "ymm6", Self::Ymm7 => "ymm7",
             Self::Ymm8 => "ymm8", Self::Ymm9 => "ymm9", Self::Ymm10 => "ymm10", Self::Ymm11 => "ymm11",
             Self::Ymm12 => "ymm12", Self::Ymm13 => "ymm13", Self::Ymm14 => "ymm14", Self::Ymm15 => "ymm15",
+            Self::Frame => "rbp",
         }
     }
 
@@ -71,7 +76,7 @@ impl X86Reg {
             Self::Rdx | Self::Edx => 2,
             Self::Rbx | Self::Ebx => 3,
             Self::Rsp | Self::Esp => 4,
-            Self::Rbp | Self::Ebp => 5,
+            Self::Rbp | Self::Ebp | Self::Frame => 5,
             Self::Rsi | Self::Esi => 6,
             Self::Rdi | Self::Edi => 7,
             Self::R8 | Self::R8d => 8,
@@ -105,6 +110,11 @@ impl X86Reg {
     pub fn same_physical(&self, other: &Self) -> bool {
         self.physical_id() == other.physical_id()
     }
+
+    /// Returns true for the SSE/AVX registers (xmm0-15 and their ymm aliases).
+    pub fn is_xmm(&self) -> bool {
+        self.physical_id() >= 16
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -121,6 +131,8 @@ pub enum X86Operand {
     FloatMem(X86Reg, i32), // [reg + offset] for float ops - DWORD PTR (32-bit single)
     DoubleMem(X86Reg, i32), // [reg + offset] for double ops - QWORD PTR (64-bit double)
     GlobalQwordMem(String), // RIP-relative global: QWORD PTR label[rip]
+    GlobalWordMem(String), // RIP-relative global: WORD PTR label[rip]
+    GlobalByteMem(String), // RIP-relative global: BYTE PTR label[rip]
     XmmwordMem(X86Reg, i32), // [reg + offset] - 128-bit (XMMWORD PTR)
     YmmwordMem(X86Reg, i32), // [reg + offset] - 256-bit (YMMWORD PTR)
 }
@@ -140,6 +152,8 @@ impl fmt::Display for X86Operand {
             Self::FloatMem(r, offset) => fmt_mem(f, "DWORD PTR", r, *offset),
             Self::DoubleMem(r, offset) => fmt_mem(f, "QWORD PTR", r, *offset),
             Self::GlobalQwordMem(name) => write!(f, "QWORD PTR {}[rip]", name),
+            Self::GlobalWordMem(name) => write!(f, "WORD PTR {}[rip]", name),
+            Self::GlobalByteMem(name) => write!(f, "BYTE PTR {}[rip]", name),
             Self::XmmwordMem(r, offset) => fmt_mem(f, "XMMWORD PTR", r, *offset),
             Self::YmmwordMem(r, offset) => fmt_mem(f, "YMMWORD PTR", r, *offset),
         }
@@ -156,6 +170,20 @@ fn fmt_mem(f: &mut fmt::Formatter<'_>, size: &str, reg: &X86Reg, offset: i32) ->
 }
 
 impl X86Operand {
+    /// Rebase a memory operand on the frame base onto `base`, adding `delta`
+    /// to its offset.
+    pub fn resolve_frame(&mut self, base: &X86Reg, delta: i32) {
+        match self {
+            Self::Mem(r, offset) | Self::DwordMem(r, offset) | Self::WordMem(r, offset) |
+            Self::ByteMem(r, offset) | Self::FloatMem(r, offset) | Self::DoubleMem(r, offset) |
+            Self::XmmwordMem(r, offset) | Self::YmmwordMem(r, offset) if *r == X86Reg::Frame => {
+                *r = base.clone();
+                *offset += delta;
+            }
+            _ => {}
+        }
+    }
+
     /// Returns true if this operand references the given register,
     /// either as a direct register or as a memory base.
     pub fn references_reg(&self, reg: &X86Reg) -> bool {
@@ -191,6 +219,8 @@ pub enum X86Instr {
     Sub(X86Operand, X86Operand),
     Imul(X86Operand, X86Operand),
     Idiv(X86Operand),
+    /// Unsigned divide of RDX:RAX (EDX:EAX for a 32-bit operand)
+    Div(X86Operand),
     Cmp(X86Operand, X86Operand),
     Test(X86Operand, X86Operand),
     Set(String, X86Operand),
@@ -290,9 +320,55 @@ pub enum X86Instr {
     /// scatter value to [R10 + index*4] with mask (R10 set by caller).
     Vpscatterdd(X86Operand, X86Operand, X86Operand),
     Raw(String), // Raw assembly string (for inline asm)
+    /// `.loc file line column`: source position of the instructions that
+    /// follow, under `-g`.
+    Loc(u32, usize, usize),
+    /// A switch's jump table: the label, then for each entry the offset of
+    /// its target label from the table.
+    JumpTable(String, Vec<String>),
 }
 
 impl X86Instr {
+    /// The operands of this instruction, in order.
+    pub fn operands_mut(&mut self) -> Vec<&mut X86Operand> {
+        match self {
+            X86Instr::Mov(a, b) | X86Instr::Add(a, b) | X86Instr::Sub(a, b) |
+            X86Instr::Imul(a, b) | X86Instr::Cmp(a, b) | X86Instr::Test(a, b) |
+            X86Instr::Xor(a, b) | X86Instr::Lea(a, b) | X86Instr::And(a, b) |
+            X86Instr::Or(a, b) | X86Instr::Shl(a, b) | X86Instr::Shr(a, b) |
+            X86Instr::Sar(a, b) | X86Instr::Movsx(a, b) | X86Instr::Movzx(a, b) |
+            X86Instr::Movss(a, b) | X86Instr::Addss(a, b) | X86Instr::Subss(a, b) |
+            X86Instr::Mulss(a, b) | X86Instr::Divss(a, b) | X86Instr::Ucomiss(a, b) |
+            X86Instr::Cvtsi2ss(a, b) | X86Instr::Cvttss2si(a, b) | X86Instr::Xorps(a, b) |
+            X86Instr::Movsd(a, b) | X86Instr::Addsd(a, b) | X86Instr::Subsd(a, b) |
+            X86Instr::Mulsd(a, b) | X86Instr::Divsd(a, b) | X86Instr::Ucomisd(a, b) |
+            X86Instr::Cvtsi2sd(a, b) | X86Instr::Cvttsd2si(a, b) | X86Instr::Xorpd(a, b) |
+            X86Instr::Cvtss2sd(a, b) | X86Instr::Cvtsd2ss(a, b) |
+            X86Instr::Movaps(a, b) | X86Instr::Movups(a, b) | X86Instr::Addps(a, b) |
+            X86Instr::Subps(a, b) | X86Instr::Mulps(a, b) | X86Instr::Divps(a, b) |
+            X86Instr::Movdqa(a, b) | X86Instr::Movdqu(a, b) | X86Instr::Paddd(a, b) |
+            X86Instr::Psubd(a, b) | X86Instr::Pmulld(a, b) | X86Instr::Pand(a, b) |
+            X86Instr::Pandn(a, b) | X86Instr::Por(a, b) | X86Instr::Pcmpgtd(a, b) |
+            X86Instr::Vmovaps(a, b) | X86Instr::Vmovups(a, b) |
+            X86Instr::Vmovdqa(a, b) | X86Instr::Vmovdqu(a, b) |
+            X86Instr::Pshufd(a, b, _) | X86Instr::Movd(a, b) | X86Instr::Pxor(a, b) |
+            X86Instr::Vextracti128(a, b, _) | X86Instr::Vpbroadcastd(a, b) => vec![a, b],
+            X86Instr::Vaddps(a, b, c) | X86Instr::Vsubps(a, b, c) | X86Instr::Vmulps(a, b, c) |
+            X86Instr::Vdivps(a, b, c) | X86Instr::Vpaddd(a, b, c) | X86Instr::Vpsubd(a, b, c) |
+            X86Instr::Vpmulld(a, b, c) | X86Instr::Vpandd(a, b, c) | X86Instr::Vpandnd(a, b, c) |
+            X86Instr::Vpord(a, b, c) | X86Instr::Vpcmpgtd(a, b, c) | X86Instr::Vxorps(a, b, c) |
+            X86Instr::Vandps(a, b, c) | X86Instr::Vandnps(a, b, c) | X86Instr::Vorps(a, b, c) |
+            X86Instr::Vpxor(a, b, c) | X86Instr::Vpgatherdd(a, b, c) |
+            X86Instr::Vpscatterdd(a, b, c) => vec![a, b, c],
+            X86Instr::Idiv(a) | X86Instr::Div(a) | X86Instr::Set(_, a) | X86Instr::CallIndirect(a) |
+            X86Instr::Not(a) | X86Instr::Neg(a) => vec![a],
+            X86Instr::Jmp(_) | X86Instr::Jcc(..) | X86Instr::Push(_) | X86Instr::Pop(_) |
+            X86Instr::Call(_) | X86Instr::Ret | X86Instr::Leave | X86Instr::Label(_) |
+            X86Instr::Cqto | X86Instr::Cdq | X86Instr::Vzeroupper | X86Instr::Raw(_) |
+            X86Instr::Loc(..) | X86Instr::JumpTable(..) => Vec::new(),
+        }
+    }
+
     /// Returns true if this instruction reads the given physical register.
     /// Conservatively matches the existing peephole liveness semantics.
     pub fn reads_phys_reg(&self, reg: &X86Reg) -> bool {
@@ -347,8 +423,8 @@ impl X86Instr {
             }
             // Single-operand read-modify-write
             X86Instr::Neg(op) | X86Instr::Not(op) => op.references_reg(reg),
-            // Idiv: reads operand + implicit rax, rdx
-            X86Instr::Idiv(op) => {
+            // Idiv/Div: reads operand + implicit rax, rdx
+            X86Instr::Idiv(op) | X86Instr::Div(op) => {
                 op.references_reg(reg) || reg.physical_id() == 0 || reg.physical_id() == 2
             }
             // Set: partial byte write, no read
@@ -365,7 +441,8 @@ impl X86Instr {
             X86Instr::Leave => reg.physical_id() == 5,
             // Control flow and zero-operand
             X86Instr::Label(_) | X86Instr::Jmp(_) | X86Instr::Jcc(_, _) |
-            X86Instr::Call(_) | X86Instr::Ret | X86Instr::Vzeroupper => false,
+            X86Instr::Call(_) | X86Instr::Ret | X86Instr::Vzeroupper | X86Instr::Loc(..) |
+            X86Instr::JumpTable(..) => false,
             // Raw: conservative
             X86Instr::Raw(_) => true,
         }
@@ -389,8 +466,8 @@ impl X86Instr {
             X86Instr::Vpaddd(dest, _, _) | X86Instr::Vpsubd(dest, _, _) |
             X86Instr::Vpmulld(dest, _, _) | X86Instr::Vxorps(dest, _, _) |
             X86Instr::Vpxor(dest, _, _) | X86Instr::Vpgatherdd(dest, _, _) => dest.is_direct_reg(reg),
-            // Idiv: kills rax (quotient) and rdx (remainder)
-            X86Instr::Idiv(_) => reg.physical_id() == 0 || reg.physical_id() == 2,
+            // Idiv/Div: kills rax (quotient) and rdx (remainder)
+            X86Instr::Idiv(_) | X86Instr::Div(_) => reg.physical_id() == 0 || reg.physical_id() == 2,
             // Set: partial write — handled by partially_writes_phys_reg.
             X86Instr::Set(_, _) => false,
             // Pop overwrites register
@@ -457,6 +534,7 @@ pub fn emit_asm(instructions: &[X86Instr]) -> String {
             X86Instr::Neg(d) => { let _ = write!(s, "  neg {}\n", d); }
             X86Instr::Imul(d, src) => { let _ = write!(s, "  imul {}, {}\n", d, src); }
             X86Instr::Idiv(src) => { let _ = write!(s, "  idiv {}\n", src); }
+            X86Instr::Div(src) => { let _ = write!(s, "  div {}\n", src); }
             X86Instr::Cmp(l, r) => { let _ = write!(s, "  cmp {}, {}\n", l, r); }
             X86Instr::Test(l, r) => { let _ = write!(s, "  test {}, {}\n", l, r); }
             X86Instr::Set(c, d) => { let _ = write!(s, "  set{} {}\n", c, d); }
@@ -478,6 +556,8 @@ pub fn emit_asm(instructions: &[X86Instr]) -> String {
             X86Instr::Shl(d, c) => { let _ = write!(s, "  shl {}, {}\n", d, c); }
             X86Instr::Shr(d, c) => { let _ = write!(s, "  shr {}, {}\n", d, c); }
             X86Instr::Sar(d, c) => { let _ = write!(s, "  sar {}, {}\n", d, c); }
+            // A 32-bit memory source needs the `movsxd` mnemonic
+            X86Instr::Movsx(d, src @ X86Operand::DwordMem(..)) => { let _ = write!(s, "  movsxd {}, {}\n", d, src); }
             X86Instr::Movsx(d, src) => { let _ = write!(s, "  movsx {}, {}\n", d, src); }
             X86Instr::Movzx(d, src) => { let _ = write!(s, "  movzx {}, {}\n", d, src); }
             // Float instructions
@@ -555,6 +635,13 @@ pub fn emit_asm(instructions: &[X86Instr]) -> String {
                 let _ = write!(s, "  vpscatterdd DWORD PTR [r10 + {}*4], {}, {}\n", idx, val, mask);
             }
             X86Instr::Raw(asm_str) => { let _ = write!(s, "  {}\n", asm_str); }
+            X86Instr::Loc(file, line, column) => { let _ = write!(s, "  .loc {} {} {}\n", file, line, column); }
+            X86Instr::JumpTable(label, targets) => {
+                let _ = writeln!(s, "  .p2align 2\n{}:", label);
+                for target in targets {
+                    let _ = writeln!(s, "  .long {}-{}", target, label);
+                }
+            }
         }
     }
     s
@@ -641,6 +728,12 @@ mod tests {
         assert_eq!(op.to_string(), "DWORD PTR my_global[rip]");
     }
 
+    #[test]
+    fn operand_narrow_global_mem() {
+        assert_eq!(X86Operand::GlobalWordMem("g".to_string()).to_string(), "WORD PTR g[rip]");
+        assert_eq!(X86Operand::GlobalByteMem("g".to_string()).to_string(), "BYTE PTR g[rip]");
+    }
+
     #[test]
     fn operand_rip_rel() {
         let op = X86Operand::RipRelLabel("str_0".to_string());
@@ -0,0 +1,633 @@
+// Compiler facade: drives the full pipeline in-process.
+//
+// Fuzzers, tests, and other Rust programs can compile C source without
+// spawning the driver binary. Every intermediate product (tokens, AST, IR,
+// assembly) is returned in `Artifacts` so callers can inspect any stage.
+//
+// Preprocessing, assembling, and linking are external-tool steps and stay
+// in the driver; `compile_source` expects already-preprocessed source. The
+// one exception is `assemble_coff`, the built-in assembler for Windows
+// objects.
+// `compile_ir` runs the back half of the pipeline on IR from elsewhere,
+// such as several translation units merged by `ir::link_programs`.
+
+use std::fmt;
+
+use ir::IRProgram;
+use model::{CompileError, FileId, Program, Severity, SourceMap, Span, TargetConfig, Token};
+use optimizer::{BlockProfile, PassSelection};
+
+pub use codegen::assemble_coff;
+
+/// Pipeline stages, in execution order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Semantic,
+    Lower,
+    Optimize,
+    Codegen,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Lex => "lexing",
+            Stage::Parse => "parsing",
+            Stage::Semantic => "semantic analysis",
+            Stage::Lower => "IR lowering",
+            Stage::Optimize => "optimization",
+            Stage::Codegen => "code generation",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Options controlling a single compilation.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Target platform, calling convention, SIMD level, and machine flags.
+    pub target: TargetConfig,
+    /// Optimization passes to run: an `-O` level's pipeline, or a
+    /// `--passes` list.
+    pub passes: PassSelection,
+    /// PGO profile used for block layout (`-fprofile-use`).
+    pub profile: Option<BlockProfile>,
+    /// Emit profile counter instrumentation (`-fprofile-generate`).
+    pub profile_generate: bool,
+    /// Last stage to run. Later artifacts are left as `None`.
+    pub stop_after: Stage,
+    /// Record the IR diff of every optimization pass that changes a function
+    /// (`--print-changed`).
+    pub print_changed: bool,
+    /// Insert runtime checks for integer division by zero, out-of-range
+    /// shifts, and signed overflow (`-fsanitize=undefined`).
+    pub sanitize_undefined: bool,
+    /// Pad local arrays with guard slots and check indices against their
+    /// bounds (`-fsanitize=bounds`).
+    pub sanitize_bounds: bool,
+    /// Deepest expression nesting the parser accepts before reporting an
+    /// error (`--fbracket-depth`).
+    pub max_expr_depth: usize,
+    /// Compile the `static inline` / `extern inline` definitions of the
+    /// translation unit; those from system headers are always skipped. Off,
+    /// every such definition is skipped (`--skip-inline-functions`).
+    pub inline_functions: bool,
+    /// Emit DWARF line tables and variable locations (`-g`). They map back
+    /// to the source through its line markers, so `compile_ir` leaves them out.
+    pub debug_info: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            target: TargetConfig::host(),
+            passes: PassSelection::default(),
+            profile: None,
+            profile_generate: false,
+            stop_after: Stage::Codegen,
+            print_changed: false,
+            sanitize_undefined: false,
+            sanitize_bounds: false,
+            max_expr_depth: parser::DEFAULT_MAX_EXPR_DEPTH,
+            inline_functions: true,
+            debug_info: false,
+        }
+    }
+}
+
+/// Everything produced by a compilation, up to `Options::stop_after`.
+#[derive(Debug, Clone, Default)]
+pub struct Artifacts {
+    pub tokens: Vec<Token>,
+    /// Source span of each token, parallel to `tokens`.
+    pub spans: Vec<Span>,
+    pub ast: Option<Program>,
+    /// Optimized IR.
+    pub ir: Option<IRProgram>,
+    /// Assembly text for `Options::target`: x86 in Intel syntax, or AT&T
+    /// when its `asm_syntax` asks for it; RISC-V in its standard syntax.
+    pub asm: Option<String>,
+    /// Non-fatal problems (`Severity::Warning`) found by semantic analysis.
+    pub warnings: Vec<Diagnostic>,
+    /// Optimization passes that changed a function, with their IR diffs.
+    /// Empty unless `Options::print_changed` is set.
+    pub pass_changes: Vec<optimizer::PassChange>,
+}
+
+/// A single error or warning reported by one pipeline stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub stage: Stage,
+    /// Code, severity, message, span (when the stage knows it), and notes.
+    pub error: CompileError,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.error.severity
+    }
+
+    fn headline(&self) -> String {
+        match self.error.severity {
+            Severity::Error => format!("error[{}]: {} failed: {}", self.error.code, self.stage, self.error.message),
+            Severity::Warning => format!("warning[{}]: {}", self.error.code, self.error.message),
+        }
+    }
+
+    /// Format with a `file:line:col` prefix and a caret snippet when the
+    /// diagnostic has a span that `sources` can resolve.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = match self.error.span {
+            Some(span) => sources.render(span, &self.headline()),
+            None => self.headline(),
+        };
+        for note in &self.error.notes {
+            out.push_str(&format!("\n  note: {}", note));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.headline())?;
+        for note in &self.error.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors that stopped a compilation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diagnostics {
+    pub items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn single(stage: Stage, error: CompileError) -> Self {
+        Self { items: vec![Diagnostic { stage, error }] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.items.iter()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, d) in self.items.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Where the most recent `compile_source` call on this thread got to.
+///
+/// If a stage panics, the context is left describing that stage, which is
+/// what the driver's crash report needs.
+#[derive(Debug, Clone, Default)]
+pub struct CrashContext {
+    /// Stage that was running.
+    pub stage: Option<Stage>,
+    /// Optimizer `(pass, function)` that was running, if in optimization.
+    pub pass: Option<(String, String)>,
+    /// IR as it entered the running stage (lowered IR during optimization,
+    /// optimized IR during codegen).
+    pub ir: Option<IRProgram>,
+}
+
+thread_local! {
+    static CRASH_CONTEXT: std::cell::RefCell<CrashContext> = std::cell::RefCell::new(CrashContext::default());
+}
+
+fn enter_stage(stage: Stage, ir: Option<&IRProgram>) {
+    CRASH_CONTEXT.with(|c| {
+        let mut c = c.borrow_mut();
+        c.stage = Some(stage);
+        c.ir = ir.cloned();
+    });
+}
+
+/// Take the crash context left by the last `compile_source` on this thread.
+pub fn take_crash_context() -> CrashContext {
+    let mut context = CRASH_CONTEXT.with(|c| std::mem::take(&mut *c.borrow_mut()));
+    if context.stage == Some(Stage::Optimize) {
+        context.pass = optimizer::current_pass();
+    }
+    context
+}
+
+/// File id used for the spans of `compile_source`. Register the source first
+/// in a `SourceMap` (which hands out ids from 0) to render them.
+pub const SOURCE_FILE_ID: FileId = FileId(0);
+
+/// Compile preprocessed C source through every stage up to `options.stop_after`.
+pub fn compile_source(src: &str, options: &Options) -> Result<Artifacts, Diagnostics> {
+    let result = run_stages(src, options);
+    // Only reached without a panic; a panicking stage leaves its context behind.
+    CRASH_CONTEXT.with(|c| *c.borrow_mut() = CrashContext::default());
+    result
+}
+
+/// Optimize lowered IR and generate code for it, stopping after
+/// `options.stop_after` (which must be `Optimize` or `Codegen`).
+/// Unlike source, IR has no errors left to report.
+pub fn compile_ir(ir: IRProgram, options: &Options) -> Artifacts {
+    let mut artifacts = Artifacts::default();
+    optimize_and_generate(ir, options, None, &mut artifacts);
+    CRASH_CONTEXT.with(|c| *c.borrow_mut() = CrashContext::default());
+    artifacts
+}
+
+fn run_stages(src: &str, options: &Options) -> Result<Artifacts, Diagnostics> {
+    enter_stage(Stage::Lex, None);
+    let (tokens, spans) = lexer::lex_spanned(src, SOURCE_FILE_ID)
+        .map_err(|e| Diagnostics::single(Stage::Lex, e))?
+        .into_iter()
+        .map(|t| (t.node, t.span))
+        .unzip();
+    let mut artifacts = Artifacts { tokens, spans, ..Artifacts::default() };
+    if options.stop_after == Stage::Lex {
+        return Ok(artifacts);
+    }
+
+    enter_stage(Stage::Parse, None);
+    let parse_options = parser::ParseOptions {
+        max_expr_depth: options.max_expr_depth,
+        inline_functions: options.inline_functions,
+        system_headers: if options.inline_functions { lexer::system_header_ranges(src) } else { Vec::new() },
+    };
+    let mut program = parser::parse_tokens_with_options(&artifacts.tokens, &artifacts.spans, &parse_options)
+        .map_err(|e| Diagnostics::single(Stage::Parse, e))?;
+    // Deduplicate global variables (common with extern declarations)
+    {
+        let mut seen = std::collections::HashSet::new();
+        program.globals.retain(|g| seen.insert(g.name.clone()));
+    }
+    artifacts.ast = Some(program);
+    let program = artifacts.ast.as_ref().unwrap();
+    if options.stop_after == Stage::Parse {
+        return Ok(artifacts);
+    }
+
+    enter_stage(Stage::Semantic, None);
+    let mut analyzer = semantic::SemanticAnalyzer::new();
+    analyzer
+        .analyze(program)
+        .map_err(|e| Diagnostics::single(Stage::Semantic, e))?;
+    artifacts.warnings = analyzer
+        .warnings()
+        .iter()
+        .map(|w| Diagnostic { stage: Stage::Semantic, error: w.clone() })
+        .collect();
+    if options.stop_after == Stage::Semantic {
+        return Ok(artifacts);
+    }
+
+    enter_stage(Stage::Lower, None);
+    let mut lowerer = ir::Lowerer::with_data_layout(options.target.data_layout);
+    lowerer.set_sanitize_undefined(options.sanitize_undefined);
+    lowerer.set_sanitize_bounds(options.sanitize_bounds);
+    let ir_prog = lowerer
+        .lower_program(program)
+        .map_err(|e| Diagnostics::single(Stage::Lower, e))?;
+    if options.stop_after == Stage::Lower {
+        artifacts.ir = Some(ir_prog);
+        return Ok(artifacts);
+    }
+
+    let sources = options.debug_info.then(|| debug_sources(src));
+    optimize_and_generate(ir_prog, options, sources, &mut artifacts);
+    Ok(artifacts)
+}
+
+/// The source for debug info, named after the file its first line marker
+/// names: the one given to the preprocessor.
+fn debug_sources(src: &str) -> SourceMap {
+    let markers = lexer::line_markers(src);
+    let name = markers.iter().find_map(|m| m.file.clone()).unwrap_or_else(|| "<stdin>".to_string());
+    let mut sources = SourceMap::new();
+    let id = sources.add_file(name, src);
+    sources.add_line_markers(id, markers);
+    sources
+}
+
+fn optimize_and_generate(ir_prog: IRProgram, options: &Options, sources: Option<SourceMap>, artifacts: &mut Artifacts) {
+    enter_stage(Stage::Optimize, Some(&ir_prog));
+    let ir_prog = if options.print_changed {
+        let (ir_prog, changes) =
+            optimizer::optimize_recording_changes(ir_prog, &options.passes, options.target.simd_level, options.profile.clone());
+        artifacts.pass_changes = changes;
+        ir_prog
+    } else {
+        optimizer::optimize_with_options(ir_prog, &options.passes, options.target.simd_level, options.profile.clone())
+    };
+    if options.stop_after == Stage::Optimize {
+        artifacts.ir = Some(ir_prog);
+        return;
+    }
+
+    enter_stage(Stage::Codegen, Some(&ir_prog));
+    let mut codegen = codegen::Codegen::with_target(options.target.clone());
+    if options.profile_generate {
+        codegen.set_profile_generate(true);
+    }
+    if let Some(sources) = sources {
+        let comp_dir = std::env::current_dir().map(|d| d.display().to_string()).unwrap_or_default();
+        codegen.set_debug_info(sources, comp_dir);
+    }
+    artifacts.asm = Some(codegen.gen_program(&ir_prog));
+    artifacts.ir = Some(ir_prog);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_produces_every_artifact() {
+        let artifacts = compile_source("int main() { return 42; }", &Options::default()).unwrap();
+        assert!(!artifacts.tokens.is_empty());
+        assert_eq!(artifacts.ast.as_ref().unwrap().functions[0].name, "main");
+        assert_eq!(artifacts.ir.as_ref().unwrap().functions[0].name, "main");
+        assert!(artifacts.asm.unwrap().contains("main:"));
+    }
+
+    #[test]
+    fn print_changed_records_only_changing_passes() {
+        let options = Options { print_changed: true, stop_after: Stage::Optimize, ..Options::default() };
+        let artifacts = compile_source("int main() { int x = 3; return x * 4; }", &options).unwrap();
+        assert!(!artifacts.pass_changes.is_empty());
+        for change in &artifacts.pass_changes {
+            assert_eq!(change.function, "main");
+            assert!(change.diff.lines().any(|l| l.starts_with('+') || l.starts_with('-')), "{}", change);
+        }
+        assert!(artifacts.pass_changes.iter().any(|c| c.pass == "mem2reg"));
+        assert!(compile_source("int main() { return 0; }", &Options::default()).unwrap().pass_changes.is_empty());
+    }
+
+    #[test]
+    fn output_is_reproducible() {
+        // Struct fields split by SROA, several variables merged at loop
+        // headers, a loop with two exits, and address-taken labels
+        let src = "struct P { int x; int y; int z; };\n\
+                   int f(int n) {\n\
+                   struct P p; p.x = n; p.y = n + 1; p.z = n + 2;\n\
+                   int a = 0, b = 1, c = 2, d = 3;\n\
+                   for (int i = 0; i < n; i++) { if (a > 100) break; a += b; b += c; c += d; d += i; }\n\
+                   return p.x + p.y * a + p.z * b + c + d;\n}\n\
+                   int g(int n) {\n\
+                   void *t[] = { &&one, &&two, &&three };\n\
+                   goto *t[n % 3];\n\
+                   one: return 1;\n\
+                   two: return 2;\n\
+                   three: return 3;\n}";
+        let options = Options { print_changed: true, ..Options::default() };
+        let run = || {
+            let artifacts = compile_source(src, &options).unwrap();
+            let changes: Vec<String> = artifacts.pass_changes.iter().map(|c| c.to_string()).collect();
+            (changes, artifacts.asm.unwrap())
+        };
+        let first = run();
+        for _ in 0..8 {
+            // Every run hashes with fresh random keys; compare the IR after
+            // each pass as well as the assembly
+            assert_eq!(run(), first);
+        }
+    }
+
+    #[test]
+    fn debug_info_maps_code_to_source_lines() {
+        let src = "# 1 \"sum.c\"\nint sum(int n) {\n    int total = 0;\n    for (int i = 0; i < n; i++) {\n        total += i;\n    }\n    return total;\n}\n";
+        let options = Options { debug_info: true, passes: PassSelection::Level(optimizer::OptLevel::O0), ..Options::default() };
+        let asm = compile_source(src, &options).unwrap().asm.unwrap();
+        assert!(asm.contains(".file 1 \"sum.c\"\n"), "{}", asm);
+        for line in [1, 2, 3, 4, 6] {
+            assert!(asm.contains(&format!(".loc 1 {} ", line)), "no .loc for line {}:\n{}", line, asm);
+        }
+        for name in ["sum", "n", "total", "i"] {
+            assert!(asm.contains(&format!(".asciz \"{}\"", name)), "{} not described:\n{}", name, asm);
+        }
+        assert!(asm.contains(".section .debug_info") && asm.contains(".Lfunc_end_sum:"));
+
+        // Off by default, and the code is otherwise unchanged
+        let plain = compile_source(src, &Options { debug_info: false, ..options }).unwrap().asm.unwrap();
+        assert!(!plain.contains(".loc") && !plain.contains(".debug_info"));
+    }
+
+    #[test]
+    fn omit_frame_pointer_frees_rbp() {
+        // Eight values live across calls: more than the other callee-saved registers
+        let src = "int g(int x);\n\
+                   int f(int n) {\n\
+                   int a = g(1), b = g(2), c = g(3), d = g(4), e = g(5), h = g(6), i = g(7);\n\
+                   int v[4] = { a, b, c, d };\n\
+                   return v[n & 3] * a + b * c + d * e + h * i + n;\n}\n\
+                   int k(int x) { __asm__ volatile (\"nop\"); return x; }";
+        let target = model::TargetConfig { omit_frame_pointer: true, ..model::TargetConfig::for_platform(model::Platform::Linux) };
+        let asm = compile_source(src, &Options { target, ..Options::default() }).unwrap().asm.unwrap();
+        let f = &asm[asm.find("\nf:").unwrap()..asm.find("\nk:").unwrap()];
+        assert!(!f.contains("mov rbp, rsp") && !f.contains("leave"), "{}", f);
+        assert!(f.contains("push rbp") && f.contains("pop rbp"), "{}", f);
+        assert!(f.contains("[rsp+") && f.contains(".cfi_def_cfa_offset"), "{}", f);
+        // Inline assembly keeps the frame pointer
+        assert!(asm[asm.find("\nk:").unwrap()..].contains("mov rbp, rsp"), "{}", asm);
+    }
+
+    #[test]
+    fn dense_switches_jump_through_a_table() {
+        let src = "int dense(int x) { switch (x) { case 1: return 4; case 2: return 7; case 3: x++; case 5: return x; default: return 0; } }\n\
+                   int sparse(int x) { switch (x) { case 1: return 4; case 100: return 7; case 10000: return 2; case -9: return 3; } return 0; }";
+        let target = model::TargetConfig::for_platform(model::Platform::Linux);
+        let asm = compile_source(src, &Options { target, ..Options::default() }).unwrap().asm.unwrap();
+        let dense = &asm[asm.find("\ndense:").unwrap()..asm.find("\nsparse:").unwrap()];
+        assert!(dense.contains("sub eax, 1") && dense.contains("cmp eax, 4") && dense.contains("jmp rax"), "{}", dense);
+        // Offsets from the table in .rodata; 4 is not a case and goes to the default
+        let table = &dense[dense.find(".section .rodata").unwrap()..dense.find("\n  .text").unwrap()];
+        let entries: Vec<&str> = table.lines().filter(|l| l.contains(".long")).collect();
+        assert_eq!(entries.len(), 5, "{}", table);
+        assert!(entries.iter().all(|e| e.ends_with("-.Ldense_switch_0")), "{}", table);
+        let sparse = &asm[asm.find("\nsparse:").unwrap()..];
+        assert!(!sparse.contains("jmp rax") && sparse.contains("cmp eax, 10000"), "{}", sparse);
+    }
+
+    #[test]
+    fn riscv_target_follows_lp64d() {
+        let src = "struct V { float x, y; };\n\
+                   double scale(double x, long n) { return x * n; }\n\
+                   float sum(struct V v) { return v.x + v.y; }\n\
+                   float take(struct V v, double d);\n\
+                   long g;\n\
+                   int main() { struct V v = { 1, 2 }; return (int)(scale(1.5, 2) + sum(v) + take(v, 0.5)) + g; }";
+        let options = Options { target: model::TargetConfig::riscv64(), debug_info: true, ..Options::default() };
+        let asm = compile_source(src, &options).unwrap().asm.unwrap();
+        assert!(!asm.contains(".intel_syntax") && !asm.contains(".loc"), "{}", asm);
+        assert!(asm.contains(".balign 8\ng:"), "{}", asm);
+        let scale = &asm[asm.find("\nscale:").unwrap()..asm.find("\nsum:").unwrap()];
+        assert!(scale.contains("fcvt.d.l") && scale.contains("fmul.d"), "{}", scale);
+        assert!(scale.contains(".cfi_startproc") && scale.contains("ret"), "{}", scale);
+        // A struct of two floats is passed in fa0 and fa1
+        let sum = &asm[asm.find("\nsum:").unwrap()..asm.find("\nmain:").unwrap()];
+        assert!(sum.contains("fsw fa0") && sum.contains("fsw fa1") && sum.contains("fadd.s"), "{}", sum);
+        let main = &asm[asm.find("\nmain:").unwrap()..];
+        assert!(main.contains("call take") && main.contains("flw fa1"), "{}", main);
+    }
+
+    #[test]
+    fn i686_target_uses_cdecl() {
+        let src = "struct Q { int x; char c; };\n\
+                   struct Q mk(int x) { struct Q q; q.x = x; q.c = 'q'; return q; }\n\
+                   long long mul(long long a, long long b) { return a * b; }\n\
+                   int g;\n\
+                   int *p = &g;\n\
+                   int f(const char *, ...);\n\
+                   int main(void) { struct Q q = mk(1); return f(\"\", 2LL, 1.5f) + q.x + (int)mul(3, 4); }";
+        let options = Options { target: model::TargetConfig::i686(), ..Options::default() };
+        let asm = compile_source(src, &options).unwrap().asm.unwrap();
+        assert!(asm.contains(".align 4\np:\n    .long g"), "{}", asm);
+        // The callee pops the hidden pointer of an aggregate return
+        let mk = &asm[asm.find("\nmk:").unwrap()..asm.find("\nmul:").unwrap()];
+        assert!(mk.contains("DWORD PTR [ebp+8]") && mk.contains("ret 4"), "{}", mk);
+        let mul = &asm[asm.find("\nmul:").unwrap()..asm.find("\nmain:").unwrap()];
+        assert!(mul.contains("mov edx, DWORD PTR [ebp+12]") && mul.contains("mul DWORD PTR"), "{}", mul);
+        // Variadic arguments take their promoted size: 8 bytes for the
+        // `long long` constant, and the `float` as a `double`
+        let main = &asm[asm.find("\nmain:").unwrap()..];
+        assert!(main.contains("[esp+8], edx") && main.contains("movsd QWORD PTR [esp+12]"), "{}", main);
+        assert!(main.contains("call f"), "{}", main);
+    }
+
+    #[test]
+    fn narrow_globals_are_accessed_at_their_width() {
+        let src = "signed char gch = -3;\n\
+                   unsigned short gus = 65535;\n\
+                   _Bool gb;\n\
+                   int main(void) { gb = gus; return gch + gus + gb; }\n\
+                   int rb(void) { return gb; }";
+        let asm = compile_source(src, &Options::default()).unwrap().asm.unwrap();
+        assert!(asm.contains("BYTE PTR gch[rip]") && asm.contains("movsx"), "{}", asm);
+        assert!(asm.contains("movzx rax, WORD PTR gus[rip]"), "{}", asm);
+        assert!(asm.contains("movzx rax, BYTE PTR gb[rip]"), "{}", asm);
+        // A `_Bool` store writes one byte, not its neighbours
+        let main = &asm[asm.find("\nmain:").unwrap()..asm.find("\nrb:").unwrap()];
+        assert!(main.contains("mov BYTE PTR [rax]") && !main.contains("QWORD PTR [rax]"), "{}", main);
+    }
+
+    #[test]
+    fn unsigned_operands_compare_and_divide_unsigned() {
+        let src = "int above(unsigned *a, unsigned *b) { return *a > *b; }\n\
+                   unsigned long rem(unsigned long *a, unsigned long *b) { return *a % *b; }\n\
+                   int less(int *a, int *b) { return *a < *b; }";
+        let asm = compile_source(src, &Options::default()).unwrap().asm.unwrap();
+        assert!(asm.contains("seta al") && asm.contains("setl al"), "{}", asm);
+        assert!(asm.contains("xor rdx, rdx") && asm.contains("  div ") && !asm.contains("idiv"), "{}", asm);
+    }
+
+    #[test]
+    fn typedefs_are_accessed_at_the_width_of_their_type() {
+        let src = "typedef unsigned short u16t; typedef signed char s8t;\n\
+                   int rd(u16t *p, s8t *q) { return p[1] + q[1]; }\n\
+                   void wr(u16t *p) { p[1] = 0xffff; }";
+        let asm = compile_source(src, &Options::default()).unwrap().asm.unwrap();
+        let rd = &asm[asm.find("\nrd:").unwrap()..asm.find("\nwr:").unwrap()];
+        assert!(rd.contains(", WORD PTR [rax]") && rd.contains(", BYTE PTR [rax]"), "{}", rd);
+        // The sum is an `int`, not a `u16t`
+        assert!(!rd.contains("movzx rax, ax"), "{}", rd);
+        let wr = &asm[asm.find("\nwr:").unwrap()..];
+        assert!(wr.contains("add rax, 2") && wr.contains("mov WORD PTR [rax], 65535"), "{}", wr);
+    }
+
+    #[test]
+    fn stop_after_parse_skips_later_stages() {
+        let options = Options { stop_after: Stage::Parse, ..Options::default() };
+        let artifacts = compile_source("int main() { return 0; }", &options).unwrap();
+        assert!(artifacts.ast.is_some());
+        assert!(artifacts.ir.is_none());
+        assert!(artifacts.asm.is_none());
+    }
+
+    #[test]
+    fn linked_units_are_optimized_together() {
+        let lower = |src: &str| {
+            let options = Options { stop_after: Stage::Lower, ..Options::default() };
+            compile_source(src, &options).unwrap().ir.unwrap()
+        };
+        let main = lower("int square(int x);\nint main() { return square(7); }");
+        let lib = lower("int square(int x) { return x * x; }");
+        let linked = ir::link_programs(vec![main, lib]).unwrap();
+        let artifacts = compile_ir(linked, &Options::default());
+        // Inlined across the units, then folded
+        let main_ir = artifacts.ir.unwrap().functions.into_iter().find(|f| f.name == "main").unwrap();
+        assert!(ir::function_to_text(&main_ir).contains("ret 49"), "{}", ir::function_to_text(&main_ir));
+        assert!(artifacts.asm.unwrap().contains("square:"));
+    }
+
+    #[test]
+    fn crash_context_is_cleared_after_a_normal_compile() {
+        compile_source("int main() { return 0; }", &Options::default()).unwrap();
+        let context = take_crash_context();
+        assert!(context.stage.is_none());
+        assert!(context.ir.is_none());
+    }
+
+    #[test]
+    fn variadic_function_pointer_calls_set_al() {
+        let src = "int any(const char *fmt, ...) { return 1; }\n\
+                   int one(const char *fmt, double d) { return 2; }\n\
+                   int call(double d) {\n\
+                   int (*variadic)(const char *, ...) = any;\n\
+                   int (*fixed)(const char *, double) = one;\n\
+                   return variadic(\"%f\", d) + fixed(\"%f\", d);\n}";
+        let options = Options { target: model::TargetConfig::for_platform(model::Platform::Linux), ..Options::default() };
+        let asm = compile_source(src, &options).unwrap().asm.unwrap();
+        let calls: Vec<_> = asm.lines().enumerate().filter(|(_, l)| l.trim() == "call r10").map(|(i, _)| i).collect();
+        let lines: Vec<_> = asm.lines().collect();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(lines[calls[0] - 1].trim(), "mov eax, 1");
+        assert_ne!(lines[calls[1] - 1].trim(), "mov eax, 1");
+    }
+
+    #[test]
+    fn errors_carry_the_failing_stage() {
+        let err = compile_source("int main() { return `; }", &Options::default()).unwrap_err();
+        assert_eq!(err.items.len(), 1);
+        assert_eq!(err.items[0].stage, Stage::Lex);
+    }
+
+    #[test]
+    fn lex_errors_render_with_location() {
+        let src = "int main() {\n    return `;\n}";
+        let err = compile_source(src, &Options::default()).unwrap_err();
+        let mut sources = SourceMap::new();
+        assert_eq!(sources.add_file("t.c", src), SOURCE_FILE_ID);
+        let rendered = err.items[0].render(&sources);
+        assert!(rendered.starts_with("t.c:2:12: error[E0101]: lexing failed"), "{}", rendered);
+        assert!(rendered.ends_with("  |            ^"), "{}", rendered);
+    }
+
+    #[test]
+    fn warnings_are_located_diagnostics() {
+        let src = "enum E { A, B };\nint f(enum E e) { switch (e) { case A: return 1; } return 0; }\nint main() { return f(B); }";
+        let artifacts = compile_source(src, &Options::default()).unwrap();
+        assert_eq!(artifacts.warnings.len(), 1);
+        let warning = &artifacts.warnings[0];
+        assert_eq!((warning.stage, warning.severity()), (Stage::Semantic, Severity::Warning));
+        let mut sources = SourceMap::new();
+        sources.add_file("t.c", src);
+        let rendered = warning.render(&sources);
+        assert!(rendered.starts_with("t.c:2:1: warning[E0312]: enumeration value 'B' not handled in switch"), "{}", rendered);
+    }
+}
@@ -0,0 +1,332 @@
+//! Compilation database (`compile_commands.json`) support.
+//!
+//! `--compile-commands FILE` records every input of the current invocation in
+//! FILE so clangd and similar tools can index the project. An existing entry
+//! for the same directory and file is replaced, so rebuilding does not grow
+//! the database. `--from-compile-commands FILE` does the reverse: it replays
+//! every entry through this driver, one process per entry.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use lexer::quote_json;
+
+use crate::error::{DriverError, DriverResult};
+
+/// One entry of a compilation database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileCommand {
+    pub directory: String,
+    pub arguments: Vec<String>,
+    pub file: String,
+    pub output: Option<String>,
+}
+
+/// Add `entries` to the database at `db_path`, creating it if needed.
+pub fn record(db_path: &str, entries: Vec<CompileCommand>) -> DriverResult<()> {
+    let mut db = if Path::new(db_path).exists() { load(db_path)? } else { Vec::new() };
+    db.retain(|old| !entries.iter().any(|new| new.directory == old.directory && new.file == old.file));
+    db.extend(entries);
+    fs::write(db_path, to_json(&db)).map_err(|e| DriverError::io(format!("failed to write '{}'", db_path), e))
+}
+
+/// Recompile every entry of the database at `db_path` with this driver.
+/// Returns 0 if every entry compiled, 1 otherwise.
+pub fn run_batch(db_path: &str) -> DriverResult<i32> {
+    let db = load(db_path)?;
+    let self_exe = std::env::current_exe().map_err(|e| DriverError::io("cannot locate driver executable", e))?;
+
+    let mut failed = 0;
+    for entry in &db {
+        println!("[{}] {}", entry.directory, entry.file);
+        let status = Command::new(&self_exe)
+            .args(entry.arguments.iter().skip(1))
+            .current_dir(&entry.directory)
+            .status()
+            .map_err(|e| DriverError::io(format!("failed to run driver for '{}'", entry.file), e))?;
+        if !status.success() {
+            failed += 1;
+        }
+    }
+
+    println!("{} entries: {} succeeded, {} failed", db.len(), db.len() - failed, failed);
+    Ok(if failed == 0 { 0 } else { 1 })
+}
+
+/// The driver's own command line with `--compile-commands` removed, so
+/// replaying an entry does not rewrite the database.
+pub fn invocation_arguments() -> Vec<String> {
+    let mut argv: Vec<String> = std::env::args().collect();
+    if let Ok(exe) = std::env::current_exe() {
+        argv[0] = exe.to_string_lossy().into_owned();
+    }
+
+    let mut out = Vec::with_capacity(argv.len());
+    let mut iter = argv.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--compile-commands" {
+            iter.next();
+        } else if !arg.starts_with("--compile-commands=") {
+            out.push(arg);
+        }
+    }
+    out
+}
+
+fn load(db_path: &str) -> DriverResult<Vec<CompileCommand>> {
+    let text = fs::read_to_string(db_path).map_err(|e| DriverError::io(format!("failed to read '{}'", db_path), e))?;
+    parse(&text).map_err(|message| DriverError::CompileDb { path: db_path.to_string(), message })
+}
+
+// ─── JSON ───────────────────────────────────────────────────
+
+fn to_json(db: &[CompileCommand]) -> String {
+    let mut out = String::from("[\n");
+    for (i, entry) in db.iter().enumerate() {
+        out.push_str("  {\n");
+        out.push_str(&format!("    \"directory\": {},\n", quote_json(&entry.directory)));
+        let args: Vec<String> = entry.arguments.iter().map(|a| quote_json(a)).collect();
+        out.push_str(&format!("    \"arguments\": [{}],\n", args.join(", ")));
+        out.push_str(&format!("    \"file\": {}", quote_json(&entry.file)));
+        if let Some(ref output) = entry.output {
+            out.push_str(&format!(",\n    \"output\": {}", quote_json(output)));
+        }
+        out.push_str(if i + 1 < db.len() { "\n  },\n" } else { "\n  }\n" });
+    }
+    out.push_str("]\n");
+    out
+}
+
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool,
+    Number,
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn parse(text: &str) -> Result<Vec<CompileCommand>, String> {
+    let mut reader = JsonReader { chars: text.chars().collect(), pos: 0 };
+    let value = reader.value()?;
+    reader.skip_ws();
+    if reader.pos != reader.chars.len() {
+        return Err(format!("trailing data at offset {}", reader.pos));
+    }
+
+    let Json::Array(items) = value else {
+        return Err("expected a top-level array".to_string());
+    };
+    items.into_iter().map(entry_from_json).collect()
+}
+
+fn entry_from_json(value: Json) -> Result<CompileCommand, String> {
+    let Json::Object(fields) = value else {
+        return Err("expected an object for each entry".to_string());
+    };
+
+    let mut directory = None;
+    let mut arguments = None;
+    let mut command = None;
+    let mut file = None;
+    let mut output = None;
+    for (key, value) in fields {
+        match (key.as_str(), value) {
+            ("directory", Json::Str(s)) => directory = Some(s),
+            ("file", Json::Str(s)) => file = Some(s),
+            ("output", Json::Str(s)) => output = Some(s),
+            ("command", Json::Str(s)) => command = Some(s),
+            ("arguments", Json::Array(items)) => {
+                let args = items
+                    .into_iter()
+                    .map(|a| match a {
+                        Json::Str(s) => Ok(s),
+                        _ => Err("\"arguments\" must contain only strings".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                arguments = Some(args);
+            }
+            (key, _) if ["directory", "file", "output", "command", "arguments"].contains(&key) => {
+                return Err(format!("\"{}\" has the wrong type", key));
+            }
+            _ => {}
+        }
+    }
+
+    // "command" is a shell string; split on whitespace, which covers the
+    // commands this driver and most build tools emit.
+    let arguments = arguments
+        .or_else(|| command.map(|c| c.split_whitespace().map(str::to_string).collect()))
+        .ok_or("entry has neither \"arguments\" nor \"command\"")?;
+    Ok(CompileCommand {
+        directory: directory.ok_or("entry is missing \"directory\"")?,
+        arguments,
+        file: file.ok_or("entry is missing \"file\"")?,
+        output,
+    })
+}
+
+struct JsonReader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonReader {
+    fn skip_ws(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at offset {}", c, self.pos))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(format!("invalid literal at offset {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_ws();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(format!("expected ',' or ']' at offset {}", self.pos)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_ws();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_ws();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(fields));
+                        }
+                        _ => return Err(format!("expected ',' or '}}' at offset {}", self.pos)),
+                    }
+                }
+            }
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('t') => self.literal("true", Json::Bool),
+            Some('f') => self.literal("false", Json::Bool),
+            Some('n') => self.literal("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                while matches!(self.peek(), Some(c) if c.is_ascii_digit() || "+-.eE".contains(c)) {
+                    self.pos += 1;
+                }
+                Ok(Json::Number)
+            }
+            _ => Err(format!("unexpected character at offset {}", self.pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return Err(format!("expected string at offset {}", self.pos));
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let esc = self.peek().ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match esc {
+                        '"' | '\\' | '/' => out.push(esc),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => {
+                            let hex: String = self.chars.get(self.pos..self.pos + 4).ok_or("short \\u escape")?.iter().collect();
+                            self.pos += 4;
+                            let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("bad \\u escape '{}'", hex))?;
+                            out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        _ => return Err(format!("bad escape '\\{}'", esc)),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let db = vec![CompileCommand {
+            directory: "/src/proj".to_string(),
+            arguments: vec!["driver".to_string(), "-DMSG=\"hi there\"".to_string(), "a.c".to_string()],
+            file: "a.c".to_string(),
+            output: Some("a".to_string()),
+        }];
+        assert_eq!(parse(&to_json(&db)).unwrap(), db);
+    }
+
+    #[test]
+    fn accepts_command_strings_and_unknown_fields() {
+        let text = r#"[{"directory": "/p", "command": "cc -c  b.c", "file": "b.c", "extra": [1, true, null]}]"#;
+        let db = parse(text).unwrap();
+        assert_eq!(db[0].arguments, vec!["cc", "-c", "b.c"]);
+        assert_eq!(db[0].output, None);
+    }
+
+    #[test]
+    fn rejects_entries_without_a_file() {
+        assert!(parse(r#"[{"directory": "/p", "arguments": ["cc"]}]"#).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Internal compiler error (ICE) reporting.
+//!
+//! When the compiler panics, `main` calls `report()` instead of letting the
+//! default hook print a bare backtrace. The report names the stage, pass, and
+//! function that were running and writes a reproduction bundle (the
+//! preprocessed source, the IR if lowering was reached, and a summary) to a
+//! directory under the system temp dir.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    /// Input being compiled and its preprocessed source.
+    static CURRENT_INPUT: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+    /// Message and location of the last panic on this thread.
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Replace the default panic hook with one that records the message for
+/// `report()`. With `RUST_BACKTRACE` set, the default output is kept too.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let message = match info.location() {
+            Some(loc) => format!("{} ({}:{})", message, loc.file(), loc.line()),
+            None => message,
+        };
+        PANIC_MESSAGE.with(|m| *m.borrow_mut() = Some(message));
+
+        if std::env::var_os("RUST_BACKTRACE").is_some() {
+            default_hook(info);
+        }
+    }));
+}
+
+/// Record the input about to be compiled so a crash can be reproduced.
+pub fn note_input(input: &str, preprocessed: &str) {
+    CURRENT_INPUT.with(|c| *c.borrow_mut() = Some((input.to_string(), preprocessed.to_string())));
+}
+
+/// Print the ICE message and write the reproduction bundle, if an input was
+/// being compiled.
+pub fn report() {
+    let message = PANIC_MESSAGE.with(|m| m.borrow_mut().take()).unwrap_or_else(|| "unknown panic".to_string());
+    let context = compiler::take_crash_context();
+    let input = CURRENT_INPUT.with(|c| c.borrow_mut().take());
+
+    eprintln!("internal compiler error: {}", message);
+    if let Some((ref name, _)) = input {
+        match (&context.stage, &context.pass) {
+            (_, Some((pass, function))) => {
+                eprintln!("note: while running pass '{}' on function '{}' for {}", pass, function, name)
+            }
+            (Some(stage), None) => eprintln!("note: during {} of {}", stage, name),
+            (None, None) => eprintln!("note: while compiling {}", name),
+        }
+    }
+
+    if let Some((name, source)) = input {
+        let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("ccrash-{}-{}", stem, std::process::id()));
+        match write_bundle(&dir, &name, &source, &message, &context) {
+            Ok(()) => eprintln!("note: reproduction files written to {}", dir.display()),
+            Err(e) => eprintln!("note: could not write reproduction files to {}: {}", dir.display(), e),
+        }
+    }
+    eprintln!("note: this is a bug in the compiler; please report it with the reproduction files attached");
+}
+
+/// Write `report.txt`, `<stem>.i`, and (if available) `ir.txt` into `dir`.
+fn write_bundle(
+    dir: &Path,
+    input: &str,
+    source: &str,
+    message: &str,
+    context: &compiler::CrashContext,
+) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut report = String::new();
+    report.push_str(&format!("driver version: {}\n", env!("CARGO_PKG_VERSION")));
+    report.push_str(&format!("command line: {}\n", std::env::args().collect::<Vec<_>>().join(" ")));
+    report.push_str(&format!("input: {}\n", input));
+    if let Some(stage) = context.stage {
+        report.push_str(&format!("stage: {}\n", stage));
+    }
+    if let Some((pass, function)) = &context.pass {
+        report.push_str(&format!("pass: {}\nfunction: {}\n", pass, function));
+    }
+    report.push_str(&format!("panic: {}\n", message));
+    fs::write(dir.join("report.txt"), report)?;
+
+    let source_name: PathBuf = Path::new(input).with_extension("i").file_name().map(PathBuf::from).unwrap_or_else(|| "input.i".into());
+    fs::write(dir.join(source_name), source)?;
+
+    if let Some(ref ir) = context.ir {
+        fs::write(dir.join("ir.txt"), format!("{:#?}\n", ir))?;
+    }
+    Ok(())
+}
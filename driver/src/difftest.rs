@@ -0,0 +1,176 @@
+//! Differential testing against a reference C compiler.
+//!
+//! `--difftest DIR` compiles every `.c` file in `DIR` with this compiler and
+//! with a reference compiler (gcc by default), runs both executables, and
+//! reports any program whose exit code or stdout differs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{DriverError, DriverResult};
+
+/// Observable behaviour of one program run.
+#[derive(Debug, PartialEq)]
+struct RunOutcome {
+    exit_code: Option<i32>,
+    stdout: String,
+}
+
+enum CaseResult {
+    Match,
+    Diverge { ours: RunOutcome, reference: RunOutcome },
+    OurCompileFailed(String),
+    ReferenceCompileFailed,
+}
+
+/// Run the differential suite over `dir`. `cpp_flags` (-D/-U/-I) are forwarded
+/// to both compilers. Returns the process exit code: 0 if every program
+/// matched, 1 otherwise.
+pub fn run_difftest(dir: &Path, reference_cc: &str, cpp_flags: &[String]) -> DriverResult<i32> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut sources: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("c"))
+            .collect(),
+        Err(e) => return Err(DriverError::io(format!("cannot read directory '{}'", dir.display()), e)),
+    };
+    sources.sort();
+
+    let self_exe = std::env::current_exe()
+        .map_err(|e| DriverError::io("cannot locate driver executable", e))?;
+    let work_dir = std::env::temp_dir().join(format!("difftest-{}", std::process::id()));
+    fs::create_dir_all(&work_dir)
+        .map_err(|e| DriverError::io(format!("cannot create '{}'", work_dir.display()), e))?;
+
+    let mut matched = 0;
+    let mut skipped = 0;
+    let mut failures = Vec::new();
+
+    for source in &sources {
+        let name = source.file_name().unwrap().to_string_lossy().into_owned();
+        match run_case(source, &self_exe, reference_cc, cpp_flags, &work_dir) {
+            CaseResult::Match => {
+                matched += 1;
+                println!("ok       {}", name);
+            }
+            CaseResult::ReferenceCompileFailed => {
+                skipped += 1;
+                println!("skip     {} ({} rejected it)", name, reference_cc);
+            }
+            CaseResult::OurCompileFailed(stderr) => {
+                println!("FAIL     {}: compilation failed", name);
+                for line in stderr.lines().take(5) {
+                    println!("           {}", line);
+                }
+                failures.push(name);
+            }
+            CaseResult::Diverge { ours, reference } => {
+                println!("DIVERGE  {}", name);
+                if ours.exit_code != reference.exit_code {
+                    println!(
+                        "           exit code: ours {:?}, {} {:?}",
+                        ours.exit_code, reference_cc, reference.exit_code
+                    );
+                }
+                if ours.stdout != reference.stdout {
+                    println!("           stdout differs:");
+                    println!("             ours: {:?}", truncate(&ours.stdout));
+                    println!("             {}: {:?}", reference_cc, truncate(&reference.stdout));
+                }
+                failures.push(name);
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&work_dir);
+
+    println!(
+        "\n{} programs: {} matched, {} diverged/failed, {} skipped",
+        sources.len(),
+        matched,
+        failures.len(),
+        skipped
+    );
+    Ok(if failures.is_empty() { 0 } else { 1 })
+}
+
+fn run_case(
+    source: &Path,
+    self_exe: &Path,
+    reference_cc: &str,
+    cpp_flags: &[String],
+    work_dir: &Path,
+) -> CaseResult {
+    let stem = source.file_stem().unwrap().to_string_lossy().into_owned();
+    let ours_exe = work_dir.join(format!("{}.ours", stem));
+    let ref_exe = work_dir.join(format!("{}.ref", stem));
+
+    // Reference first: if it rejects the program there is nothing to compare.
+    let ref_status = Command::new(reference_cc)
+        .arg("-w")
+        .args(cpp_flags)
+        .arg(source)
+        .arg("-o")
+        .arg(&ref_exe)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if !matches!(ref_status, Ok(s) if s.success()) {
+        return CaseResult::ReferenceCompileFailed;
+    }
+
+    // Compile with this driver in the scratch directory so its .i/.s
+    // intermediates never land next to the sources.
+    let ours = Command::new(self_exe)
+        .arg(source)
+        .args(cpp_flags)
+        .arg("-o")
+        .arg(&ours_exe)
+        .current_dir(work_dir)
+        .output();
+    match ours {
+        Ok(out) if out.status.success() => {}
+        Ok(out) => return CaseResult::OurCompileFailed(String::from_utf8_lossy(&out.stderr).into_owned()),
+        Err(e) => return CaseResult::OurCompileFailed(e.to_string()),
+    }
+
+    let ours = run_program(&ours_exe);
+    let reference = run_program(&ref_exe);
+    let _ = fs::remove_file(&ours_exe);
+    let _ = fs::remove_file(&ref_exe);
+
+    if ours == reference {
+        CaseResult::Match
+    } else {
+        CaseResult::Diverge { ours, reference }
+    }
+}
+
+fn run_program(exe: &Path) -> RunOutcome {
+    match Command::new(exe).stdin(Stdio::null()).stderr(Stdio::null()).output() {
+        Ok(out) => RunOutcome {
+            exit_code: out.status.code(),
+            stdout: String::from_utf8_lossy(&out.stdout).into_owned(),
+        },
+        Err(e) => RunOutcome {
+            exit_code: None,
+            stdout: format!("<failed to execute: {}>", e),
+        },
+    }
+}
+
+fn truncate(s: &str) -> String {
+    const LIMIT: usize = 200;
+    if s.len() <= LIMIT {
+        s.to_string()
+    } else {
+        let mut end = LIMIT;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...", &s[..end])
+    }
+}
@@ -0,0 +1,105 @@
+//! Driver-level error type and process exit codes.
+
+use std::fmt;
+use std::process::ExitStatus;
+
+/// Exit code for ordinary failures (bad input, compile errors, tool failures).
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code for internal compiler errors (panics), matching GCC's ICE code.
+pub const EXIT_ICE: i32 = 4;
+
+/// Everything that can make a driver invocation fail.
+#[derive(Debug)]
+pub enum DriverError {
+    /// Invalid command-line usage.
+    Usage(String),
+    /// An input file does not exist.
+    MissingInput(String),
+    /// A path could not be turned into an output file name.
+    InvalidPath(String),
+    /// Reading or writing a file failed.
+    Io { context: String, source: std::io::Error },
+    /// An external tool (gcc) could not be started.
+    ToolNotFound { tool: String, source: std::io::Error },
+    /// An external tool ran but failed.
+    ToolFailed { tool: String, action: &'static str, status: ExitStatus },
+    /// Lexing, parsing, semantic analysis, or lowering rejected the input.
+    /// `sources` holds the preprocessed input and its line markers, so
+    /// diagnostics with a span can name the original file and line.
+    Compile { input: String, diagnostics: compiler::Diagnostics, sources: Option<model::SourceMap> },
+    /// A PGO profile could not be loaded.
+    Profile { path: String, message: String },
+    /// A compile_commands.json file could not be parsed.
+    CompileDb { path: String, message: String },
+    /// The built-in assembler rejected a line of `input`, an assembly file.
+    Assemble { input: String, message: String },
+    /// `--interpret` hit something the IR interpreter cannot run.
+    Interpret { input: String, message: String },
+    /// A `.bir` file could not be written or read (`input` names it), or
+    /// the IR of the inputs could not be linked (`input` is `None`).
+    Lto { input: Option<String>, error: model::CompileError },
+}
+
+impl DriverError {
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        DriverError::Io { context: context.into(), source }
+    }
+
+    /// Process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        EXIT_FAILURE
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::Usage(msg) => write!(f, "error: {}", msg),
+            DriverError::MissingInput(path) => write!(f, "error: input file '{}' not found", path),
+            DriverError::InvalidPath(path) => write!(f, "error: cannot derive an output name from '{}'", path),
+            DriverError::Io { context, source } => write!(f, "error: {}: {}", context, source),
+            DriverError::ToolNotFound { tool, source } => {
+                write!(f, "error: could not run '{}' ({}); please install GCC and make sure it is in PATH", tool, source)
+            }
+            DriverError::ToolFailed { tool, action, status } => match status.code() {
+                Some(code) => write!(f, "error: {} {} failed with exit code {}", tool, action, code),
+                None => write!(f, "error: {} {} was terminated by a signal", tool, action),
+            },
+            DriverError::Compile { input, diagnostics, sources } => {
+                for (i, d) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    match (sources, d.error.span) {
+                        (Some(sources), Some(span)) if sources.location(span).is_some() => f.write_str(&d.render(sources))?,
+                        _ => write!(f, "{}: {}", input, d)?,
+                    }
+                }
+                Ok(())
+            }
+            DriverError::Profile { path, message } => {
+                write!(f, "error: failed to load profile '{}': {}", path, message)
+            }
+            DriverError::CompileDb { path, message } => {
+                write!(f, "error: malformed compilation database '{}': {}", path, message)
+            }
+            DriverError::Assemble { input, message } => write!(f, "{}: error: {}", input, message),
+            DriverError::Interpret { input, message } => write!(f, "{}: error: {}", input, message),
+            DriverError::Lto { input: Some(input), error } => write!(f, "{}: {}", input, error),
+            DriverError::Lto { input: None, error } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DriverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DriverError::Io { source, .. } | DriverError::ToolNotFound { source, .. } => Some(source),
+            DriverError::Compile { diagnostics, .. } => Some(diagnostics),
+            DriverError::Lto { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+pub type DriverResult<T> = Result<T, DriverError>;
@@ -1,9 +1,19 @@
-use clap::Parser; // clap crate for CLI argument parsing
+mod compdb;
+mod crash;
+mod difftest;
+mod error;
+
+use clap::{CommandFactory, FromArgMatches, Parser}; // clap crate for CLI argument parsing
 use std::{path::Path, process::Command};
 use std::sync::OnceLock;
 
+use error::{DriverError, DriverResult, EXIT_FAILURE, EXIT_ICE};
+
 static DEBUG_ENABLED: OnceLock<bool> = OnceLock::new();
 
+/// Stack of the thread that runs the driver, reserved but only touched as used.
+const COMPILER_STACK_SIZE: usize = 64 << 20;
+
 macro_rules! log {
     ($($arg:tt)*) => {{
         if *DEBUG_ENABLED.get().unwrap_or(&false) {
@@ -28,7 +38,7 @@ struct Args {
     output: Option<String>,
 
     /// Run lexer only
-    #[arg(short, long)]
+    #[arg(long)]
     lex: bool,
 
     /// Run lexer and parser only
@@ -39,6 +49,10 @@ struct Args {
     #[arg(long)]
     codegen: bool,
 
+    /// Check syntax and semantics only; produce no output files
+    #[arg(long = "fsyntax-only")]
+    fsyntax_only: bool,
+
     /// Emit assembly but do not assemble or link
     #[arg(short = 'S', long)]
     emit_asm: bool,
@@ -47,6 +61,58 @@ struct Args {
     #[arg(short = 'c')]
     compile_only: bool,
 
+    /// Link-time optimization: with -c, write each input's unoptimized IR
+    /// as <stem>.bir instead of an object; otherwise merge the IR of every
+    /// input (.c sources and .bir files) and optimize it as one program.
+    /// Any .bir input turns this on
+    #[arg(long = "flto")]
+    flto: bool,
+
+    /// Optimization level: 0 (none), 1 (scalar cleanup), 2 (adds inlining,
+    /// LICM, and block layout), or 3 (adds loop transforms and
+    /// vectorization; the default)
+    #[arg(short = 'O', value_name = "LEVEL", default_value = "3", value_parser = ["0", "1", "2", "3"])]
+    opt_level: String,
+
+    /// Emit DWARF debug info: line tables, and the locations of variables
+    /// kept in stack slots (Linux targets; not with --flto)
+    #[arg(short = 'g')]
+    debug_info: bool,
+
+    /// Run exactly these optimization passes, in order, instead of the -O
+    /// level's pipeline (for debugging a pass)
+    #[arg(long = "passes", value_name = "PASS,...", value_delimiter = ',')]
+    passes: Option<Vec<String>>,
+
+    /// Write the optimized CFG of each function, with its instructions, as
+    /// <stem>.<function>.cfg.dot (or .cfg.mmd with --cfg-format=mermaid)
+    #[arg(long = "dump-cfg")]
+    dump_cfg: bool,
+
+    /// Syntax of --dump-cfg output
+    #[arg(long = "cfg-format", value_name = "FORMAT", default_value = "dot", value_parser = ["dot", "mermaid"])]
+    cfg_format: String,
+
+    /// Write the program's call graph as <stem>.callgraph.dot
+    #[arg(long = "dump-callgraph")]
+    dump_callgraph: bool,
+
+    /// Print the IR diff of every optimization pass that changes a function
+    #[arg(long = "print-changed")]
+    print_changed: bool,
+
+    /// Write the optimized IR of the whole program as <stem>.ir
+    #[arg(long = "emit-ir")]
+    emit_ir: bool,
+
+    /// Write every symbol definition and reference as <stem>.symbols.json
+    #[arg(long = "emit-symbols")]
+    emit_symbols: bool,
+
+    /// Write the token stream, with source positions, as <stem>.tokens.json
+    #[arg(long = "emit-tokens")]
+    emit_tokens: bool,
+
     /// Keep intermediate files (.i, .s)
     #[arg(long, default_value_t = false)]
     keep_intermediates: bool,
@@ -71,6 +137,35 @@ struct Args {
     #[arg(long = "include", value_name = "FILE")]
     force_includes: Vec<String>,
 
+    /// Link against a library (-lNAME)
+    #[arg(short = 'l', value_name = "NAME")]
+    libraries: Vec<String>,
+
+    /// Add a directory to the library search path (-LDIR)
+    #[arg(short = 'L', value_name = "DIR")]
+    library_paths: Vec<String>,
+
+    /// Pass comma-separated options to the linker (-Wl,OPT[,OPT...]), or
+    /// turn warnings into errors (-Werror)
+    #[arg(short = 'W', value_name = "l,OPTS", allow_hyphen_values = true)]
+    wl_options: Vec<String>,
+
+    /// Suppress all warnings (-w)
+    #[arg(short = 'w')]
+    no_warnings: bool,
+
+    /// Pass a single argument to the linker
+    #[arg(long = "Xlinker", value_name = "ARG", allow_hyphen_values = true)]
+    xlinker: Vec<String>,
+
+    /// Write a Make dependency fragment for each input while preprocessing
+    #[arg(long = "MD")]
+    dep_file: bool,
+
+    /// Dependency file path (implies --MD; single input only)
+    #[arg(long = "MF", value_name = "FILE")]
+    dep_file_path: Option<String>,
+
     /// Build without standard library
     #[arg(long)]
     nostdlib: bool,
@@ -99,8 +194,12 @@ struct Args {
     #[arg(long = "fno-stack-protector")]
     fno_stack_protector: bool,
 
+    /// Address locals from RSP and free RBP for the register allocator
+    #[arg(long = "fomit-frame-pointer", overrides_with = "fno_omit_frame_pointer")]
+    fomit_frame_pointer: bool,
+
     /// Do not omit frame pointer
-    #[arg(long = "fno-omit-frame-pointer")]
+    #[arg(long = "fno-omit-frame-pointer", overrides_with = "fomit_frame_pointer")]
     fno_omit_frame_pointer: bool,
 
     /// Generate position-independent code (shared libraries / modules)
@@ -111,44 +210,242 @@ struct Args {
     #[arg(long = "fPIE", alias = "fpie")]
     fpie: bool,
 
+    /// Assembly syntax of the output: `intel` (`.intel_syntax noprefix`) or
+    /// `att`, for assemblers without the Intel directive. Inline asm
+    /// templates are written in Intel syntax either way
+    #[arg(long = "asm-syntax", value_name = "SYNTAX", default_value = "intel", value_parser = ["intel", "att"])]
+    asm_syntax: String,
+
+    /// Target to compile for: `x86_64-linux` (System V, ELF),
+    /// `x86_64-windows` (Windows x64, COFF), `i686-linux` or `i686` (32-bit
+    /// x86, System V i386 ABI), or `riscv64-linux` (RV64GC, LP64D). Defaults
+    /// to the host. i686 builds go through `gcc -m32` on an x86-64 Linux
+    /// host; other targets stop at -S or -c, since linking needs that
+    /// target's libraries; -c for RISC-V assembles with
+    /// `riscv64-linux-gnu-gcc`
+    #[arg(long, value_name = "TARGET", value_parser = ["x86_64-linux", "x86_64-windows", "i686-linux", "i686", "riscv64-linux"])]
+    target: Option<String>,
+
+    /// Assemble with `gcc` even where the built-in COFF writer would be
+    /// used (Windows targets)
+    #[arg(long = "fno-integrated-as")]
+    fno_integrated_as: bool,
+
     /// Instrument code and emit profile counters (-fprofile-generate)
     #[arg(long = "fprofile-generate")]
     fprofile_generate: bool,
 
+    /// Insert runtime checks (-fsanitize=undefined: division by zero,
+    /// out-of-range shifts, signed overflow; -fsanitize=bounds: local array
+    /// indices and guard slots)
+    #[arg(long = "fsanitize", value_name = "CHECKS", value_delimiter = ',', value_parser = ["undefined", "bounds"])]
+    fsanitize: Vec<String>,
+
+    /// Deepest expression nesting accepted before a "too deeply nested" error
+    #[arg(long = "fbracket-depth", value_name = "N", default_value_t = parser::DEFAULT_MAX_EXPR_DEPTH)]
+    fbracket_depth: usize,
+
+    /// Skip every `static inline` / `extern inline` function definition, not
+    /// just those from system headers
+    #[arg(long = "skip-inline-functions")]
+    skip_inline_functions: bool,
+
     /// Use profile data to guide optimization (-fprofile-use=FILE)
     #[arg(long = "fprofile-use", value_name = "FILE")]
     fprofile_use: Option<String>,
+
+    /// Compile, link to a temporary executable, run it, and exit with its exit code
+    #[arg(long)]
+    run: bool,
+
+    /// Run the program with the IR interpreter instead of compiling it (no
+    /// assembler or linker), and exit with its exit code
+    #[arg(long)]
+    interpret: bool,
+
+    /// Differential test: compile and run every .c file in DIR with this compiler
+    /// and the reference compiler, reporting exit code / stdout divergences
+    #[arg(long, value_name = "DIR")]
+    difftest: Option<String>,
+
+    /// Reference compiler used by --difftest
+    #[arg(long, value_name = "CC", default_value = "gcc")]
+    reference_cc: String,
+
+    /// Record this invocation in a compile_commands.json file
+    #[arg(long = "compile-commands", value_name = "FILE")]
+    compile_commands: Option<String>,
+
+    /// Recompile every entry of a compile_commands.json file
+    #[arg(long = "from-compile-commands", value_name = "FILE")]
+    from_compile_commands: Option<String>,
+
+    /// Arguments passed to the program under --run or --interpret (after `--`)
+    #[arg(last = true, value_name = "ARGS")]
+    run_args: Vec<String>,
+
+    /// -D/-U flags in command-line order (filled in after parsing)
+    #[arg(skip)]
+    macro_flags: Vec<String>,
+}
+
+impl Args {
+    /// Parse the command line, recording the relative order of -D and -U.
+    /// The preprocessor applies them left to right, so `-UX -DX=2` and
+    /// `-DX=2 -UX` mean different things.
+    fn parse_with_macro_order() -> Self {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let mut ordered: Vec<(usize, String)> = Vec::new();
+        for (id, flag) in [("defines", "-D"), ("undefines", "-U")] {
+            if let (Some(indices), Some(values)) =
+                (matches.indices_of(id), matches.get_many::<String>(id))
+            {
+                ordered.extend(indices.zip(values).map(|(i, v)| (i, format!("{}{}", flag, v))));
+            }
+        }
+        ordered.sort_by_key(|(i, _)| *i);
+        args.macro_flags = ordered.into_iter().map(|(_, f)| f).collect();
+        args
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = Args::parse_with_macro_order();
     DEBUG_ENABLED.set(args.debug).ok();
-    
+
     log!("DEBUG: Driver started");
     log!("DEBUG: Args parsed");
 
+    crash::install_panic_hook();
+    // The recursive-descent stages need more than the main thread's stack
+    // for expressions near `--fbracket-depth` in unoptimized builds. Crash
+    // context is thread-local, so the ICE report runs on the same thread.
+    let code = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(COMPILER_STACK_SIZE)
+            .spawn_scoped(scope, || match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(&args))) {
+                Ok(Ok(code)) => code,
+                Ok(Err(e)) => {
+                    eprintln!("{}", e);
+                    e.exit_code()
+                }
+                Err(_) => {
+                    crash::report();
+                    EXIT_ICE
+                }
+            })
+            .map(|handle| handle.join().unwrap_or(EXIT_ICE))
+            .unwrap_or_else(|e| {
+                eprintln!("error: failed to start compiler thread: {}", e);
+                EXIT_FAILURE
+            })
+    });
+    std::process::exit(code);
+}
+
+/// Run one driver invocation, returning the process exit code on success.
+fn run(args: &Args) -> DriverResult<i32> {
+    if let Some(ref dir) = args.difftest {
+        let mut cpp_flags = args.macro_flags.clone();
+        cpp_flags.extend(args.include_paths.iter().map(|i| format!("-I{}", i)));
+        return difftest::run_difftest(Path::new(dir), &args.reference_cc, &cpp_flags);
+    }
+
+    if let Some(ref db) = args.from_compile_commands {
+        return compdb::run_batch(db);
+    }
+
     if args.input_paths.is_empty() {
-        eprintln!("Error: No input files provided.");
-        std::process::exit(1);
+        return Err(DriverError::Usage("no input files provided".to_string()));
+    }
+
+    if args.run && (args.emit_asm || args.compile_only || args.codegen || args.parse || args.lex || args.fsyntax_only) {
+        return Err(DriverError::Usage(
+            "--run cannot be combined with -S, -c, --codegen, --parse, --lex, or --fsyntax-only".to_string(),
+        ));
+    }
+
+    let links = !(args.emit_asm || args.compile_only || args.codegen || args.parse || args.lex || args.fsyntax_only
+        || args.interpret);
+    let host_links = matches!(target_arch(args), model::Arch::X86_64 | model::Arch::I686);
+    if links && (target_platform(args) != model::Platform::host() || !host_links) {
+        return Err(DriverError::Usage(format!(
+            "cannot link for --target {} on this host; stop at -S or -c",
+            args.target.as_deref().unwrap_or_default()
+        )));
+    }
+
+    if args.interpret {
+        if args.run || args.emit_asm || args.compile_only || args.codegen || args.parse || args.lex || args.fsyntax_only {
+            return Err(DriverError::Usage(
+                "--interpret cannot be combined with --run, -S, -c, --codegen, --parse, --lex, or --fsyntax-only".to_string(),
+            ));
+        }
+        if args.input_paths.len() > 1 {
+            return Err(DriverError::Usage("--interpret takes a single input file".to_string()));
+        }
+    }
+
+    let lto = args.flto || args.input_paths.iter().any(|path| is_ir_object(path));
+    if lto && (args.interpret || args.codegen || args.parse || args.lex || args.fsyntax_only) {
+        return Err(DriverError::Usage(
+            "--flto and .bir inputs cannot be combined with --interpret, --codegen, --parse, --lex, or --fsyntax-only"
+                .to_string(),
+        ));
+    }
+
+    if (args.emit_ir || args.dump_cfg || args.dump_callgraph || args.print_changed)
+        && (args.parse || args.lex || args.fsyntax_only)
+    {
+        return Err(DriverError::Usage(
+            "--emit-ir, --dump-cfg, --dump-callgraph, and --print-changed need IR and cannot be combined with --parse, --lex, or --fsyntax-only"
+                .to_string(),
+        ));
+    }
+
+    if target_arch(args) == model::Arch::I686 && (args.fpic || args.fpie || args.asm_syntax == "att") {
+        return Err(DriverError::Usage(
+            "--target i686 generates position-dependent Intel-syntax code; -fPIC, -fPIE, and --asm-syntax=att are not supported"
+                .to_string(),
+        ));
     }
 
     let stop_after_emit_asm = args.emit_asm;
     let stop_after_codegen = args.codegen;
     let stop_after_parse = args.parse;
     let stop_after_lex = args.lex;
+    let syntax_only = args.fsyntax_only;
     let compile_only = args.compile_only;
     let nostdlib = args.nostdlib;
     let ffreestanding = args.ffreestanding;
     let keep_intermediates = args.keep_intermediates || stop_after_emit_asm;
 
-    // Build extra preprocessor flags from -D, -U, -I, -include
-    let mut cpp_extra_args = Vec::new();
-    for d in &args.defines {
-        cpp_extra_args.push(format!("-D{}", d));
+    if args.dep_file_path.is_some() && args.input_paths.len() > 1 {
+        return Err(DriverError::Usage("--MF cannot be used with multiple input files".to_string()));
     }
-    for u in &args.undefines {
-        cpp_extra_args.push(format!("-U{}", u));
+
+    if let Some(ref db) = args.compile_commands {
+        let directory = std::env::current_dir()
+            .map_err(|e| DriverError::io("cannot determine working directory", e))?
+            .to_string_lossy()
+            .into_owned();
+        let arguments = compdb::invocation_arguments();
+        let mut entries = Vec::new();
+        for input_path in &args.input_paths {
+            entries.push(compdb::CompileCommand {
+                directory: directory.clone(),
+                arguments: arguments.clone(),
+                file: input_path.clone(),
+                output: Some(output_for(args, &file_stem(input_path)?)?),
+            });
+        }
+        compdb::record(db, entries)?;
     }
+
+    // Build extra preprocessor flags from -D, -U (in command-line order), -I, -include
+    let mut cpp_extra_args = args.macro_flags.clone();
     for i in &args.include_paths {
         cpp_extra_args.push(format!("-I{}", i));
     }
@@ -159,12 +456,15 @@ fn main() {
     if ffreestanding {
         cpp_extra_args.push("-ffreestanding".to_string());
     }
+    if target_arch(args) == model::Arch::I686 {
+        // The 32-bit headers and predefined macros
+        cpp_extra_args.push("-m32".to_string());
+    }
 
     log!("DEBUG: Checking gcc...");
     // Check for gcc
-    if Command::new("gcc").arg("--version").output().is_err() {
-        eprintln!("Error: 'gcc' not found in PATH. Please install GCC.");
-        std::process::exit(1);
+    if let Err(e) = Command::new("gcc").arg("--version").output() {
+        return Err(DriverError::ToolNotFound { tool: "gcc".to_string(), source: e });
     }
     log!("DEBUG: GCC check passed");
 
@@ -174,115 +474,235 @@ fn main() {
         }
     };
 
+    let stop_after = if stop_after_lex {
+        compiler::Stage::Lex
+    } else if stop_after_parse {
+        compiler::Stage::Parse
+    } else if syntax_only {
+        compiler::Stage::Semantic
+    } else if stop_after_codegen {
+        compiler::Stage::Optimize
+    } else if args.interpret {
+        // The interpreter runs the unoptimized IR, which has no SIMD instructions
+        compiler::Stage::Lower
+    } else if lto {
+        // Optimized once all the inputs are linked
+        compiler::Stage::Lower
+    } else {
+        compiler::Stage::Codegen
+    };
+
+    let warnings_are_errors = args.wl_options.iter().any(|opt| opt == "error");
+
+    let profile = match args.fprofile_use {
+        Some(ref path) => Some(optimizer::load_profile(Path::new(path)).map_err(|message| {
+            DriverError::Profile { path: path.clone(), message }
+        })?),
+        None => None,
+    };
+
+    let mut target = match target_arch(args) {
+        model::Arch::X86_64 => model::TargetConfig::for_platform(target_platform(args)),
+        model::Arch::I686 => model::TargetConfig::i686(),
+        model::Arch::Riscv64 => model::TargetConfig::riscv64(),
+    };
+    target.no_red_zone = args.mno_red_zone;
+    target.no_sse = args.mno_sse || args.mno_80387;
+    target.omit_frame_pointer = args.fomit_frame_pointer;
+    if args.fpie {
+        target.pic_mode = model::PicMode::Pie;
+    } else if args.fpic {
+        target.pic_mode = model::PicMode::Pic;
+    }
+    if args.asm_syntax == "att" {
+        target.asm_syntax = model::AsmSyntax::Att;
+    }
+
+    let passes = match args.passes {
+        Some(ref names) => {
+            optimizer::named_pipeline(names, target.simd_level).map_err(DriverError::Usage)?;
+            optimizer::PassSelection::Named(names.clone())
+        }
+        None => optimizer::PassSelection::Level(match args.opt_level.as_str() {
+            "0" => optimizer::OptLevel::O0,
+            "1" => optimizer::OptLevel::O1,
+            "2" => optimizer::OptLevel::O2,
+            _ => optimizer::OptLevel::O3,
+        }),
+    };
+
+    let options = compiler::Options {
+        target: target.clone(),
+        passes,
+        profile: profile.clone(),
+        profile_generate: args.fprofile_generate,
+        stop_after,
+        print_changed: args.print_changed,
+        sanitize_undefined: args.fsanitize.iter().any(|c| c == "undefined"),
+        sanitize_bounds: args.fsanitize.iter().any(|c| c == "bounds"),
+        max_expr_depth: args.fbracket_depth,
+        inline_functions: !args.skip_inline_functions,
+        debug_info: args.debug_info,
+    };
+
     let mut asm_paths = Vec::new();
     let mut preprocessed_paths = Vec::new();
+    // Lowered IR of every input, under --flto
+    let mut ir_modules = Vec::new();
 
     // Process each input file
     for input_path in &args.input_paths {
         let input_file = Path::new(&input_path);
         if !input_file.exists() {
-             eprintln!("Error: Input file '{}' not found.", input_path);
-             std::process::exit(1);
+            return Err(DriverError::MissingInput(input_path.clone()));
+        }
+        let stem = file_stem(input_path)?;
+        if is_ir_object(input_path) {
+            ir_modules.push(read_ir_object(input_path)?);
+            continue;
         }
 
         log!("Processing file: {}", input_path);
         log!("Step 1: Preprocessing...");
-        let preprocessed_path = preprocess(&input_path, input_file, &cpp_extra_args);
+        let mut cpp_args = cpp_extra_args.clone();
+        if let Some((dep_path, dep_target)) = dependency_output(args, &stem)? {
+            cpp_args.extend(["-MD".to_string(), "-MF".to_string(), dep_path, "-MT".to_string(), dep_target]);
+        }
+        let preprocessed_path = preprocess(input_path, &stem, &cpp_args)?;
         log!("Step 1: Done");
 
-        let src = std::fs::read_to_string(&preprocessed_path).expect("failed to read preprocessed file");
+        let src = std::fs::read_to_string(&preprocessed_path)
+            .map_err(|e| DriverError::io(format!("failed to read '{}'", preprocessed_path), e))?;
+        crash::note_input(input_path, &src);
+
+        log!("Steps 2-7: Lexing, parsing, semantic analysis, lowering, optimization, codegen...");
+        let artifacts = match compiler::compile_source(&src, &options) {
+            Ok(artifacts) => artifacts,
+            Err(diagnostics) => {
+                cleanup(&preprocessed_path);
+                let sources = Some(source_map(input_path, &src));
+                return Err(DriverError::Compile { input: input_path.clone(), diagnostics, sources });
+            }
+        };
+        log!("Steps 2-7: Done");
+        if !args.no_warnings && !artifacts.warnings.is_empty() {
+            let sources = source_map(input_path, &src);
+            if warnings_are_errors {
+                cleanup(&preprocessed_path);
+                let diagnostics = compiler::Diagnostics { items: artifacts.warnings.into_iter().map(promote_warning).collect() };
+                return Err(DriverError::Compile { input: input_path.clone(), diagnostics, sources: Some(sources) });
+            }
+            for w in &artifacts.warnings {
+                match w.error.span {
+                    Some(span) if sources.location(span).is_some() => eprintln!("{}", w.render(&sources)),
+                    _ => eprintln!("{}: {}", input_path, w),
+                }
+            }
+        }
+        for change in &artifacts.pass_changes {
+            eprint!("{}", change);
+        }
+
+        if let Some(ref ir) = artifacts.ir && (!lto || compile_only) {
+            dump_graphs(args, &stem, ir)?;
+        }
+        if args.emit_symbols {
+            emit_symbols(input_path, &stem, &src, &artifacts)?;
+        }
+        if args.emit_tokens {
+            emit_tokens(input_path, &stem, &src, &artifacts)?;
+        }
 
-        log!("Step 2: Lexing...");
-        let tokens = lexer::lex(&src).expect("Lexing failed");
-        log!("Step 2: Done");
-        
         if stop_after_lex {
-            println!("Tokens for {}: {:?}", input_path, tokens);
+            println!("Tokens for {}: {:?}", input_path, artifacts.tokens);
             preprocessed_paths.push(preprocessed_path);
             continue;
         }
-
-        log!("Step 3: Parsing...");
-        let mut program = parser::parse_tokens(&tokens).expect("Parsing failed");
-        log!("Step 3: Done");
-        
-        // Deduplicate global variables (common with extern declarations)
-        {
-            let mut seen = std::collections::HashSet::new();
-            program.globals.retain(|g| seen.insert(g.name.clone()));
-        }
-        
         if stop_after_parse {
-            println!("AST for {}: {:?}", input_path, program);
+            println!("AST for {}: {:?}", input_path, artifacts.ast.unwrap());
+            preprocessed_paths.push(preprocessed_path);
+            continue;
+        }
+        if syntax_only {
             preprocessed_paths.push(preprocessed_path);
             continue;
         }
-
-        log!("Step 4: Semantic Analysis...");
-        let mut analyzer = semantic::SemanticAnalyzer::new();
-        analyzer.analyze(&program).expect("Semantic analysis failed");
-        log!("Step 4: Done");
-
-        log!("Step 5: IR Lowering...");
-        let mut lowerer = ir::Lowerer::new();
-        let ir_prog = lowerer.lower_program(&program).expect("IR lowering failed");
-        log!("Step 5: Done");
-        
-        log!("Step 6: Optimization...");
-        let profile = if let Some(ref path) = args.fprofile_use {
-            Some(optimizer::load_profile(std::path::Path::new(path)).expect("Failed to load profile"))
-        } else {
-            None
-        };
-        let ir_prog = optimizer::optimize_with_options(ir_prog, model::SimdLevel::detect(), profile);
-        log!("Step 6: Done");
-
         if stop_after_codegen {
-            println!("IR for {}: {:?}", input_path, ir_prog);
+            println!("IR for {}: {:?}", input_path, artifacts.ir.unwrap());
             preprocessed_paths.push(preprocessed_path);
             continue;
         }
-
-        log!("Step 7: Code Generation...");
-        let mut target = model::TargetConfig::host();
-        target.no_red_zone = args.mno_red_zone;
-        target.no_sse = args.mno_sse || args.mno_80387;
-        if args.fpie {
-            target.pic_mode = model::PicMode::Pie;
-        } else if args.fpic {
-            target.pic_mode = model::PicMode::Pic;
+        if args.interpret {
+            cleanup(&preprocessed_path);
+            log!("Step 8: Interpreting...");
+            return interpret(input_path, &artifacts.ir.unwrap(), &args.run_args);
         }
-        let mut codegen = codegen::Codegen::with_target(target);
-        if args.fprofile_generate {
-            codegen.set_profile_generate(true);
+        if lto {
+            let ir = artifacts.ir.unwrap();
+            if compile_only {
+                write_ir_object(&output_for(args, &stem)?, &ir)?;
+            } else {
+                ir_modules.push(ir);
+            }
+            preprocessed_paths.push(preprocessed_path);
+            continue;
         }
-        let asm = codegen.gen_program(&ir_prog);
-        log!("Step 7: Done");
+        let asm = artifacts.asm.unwrap();
 
-        let mut asm_path = input_file.file_stem().unwrap().to_string_lossy().into_owned();
-        asm_path.push_str(".s");
-        std::fs::write(&asm_path, asm).expect("failed to write assembly file");
+        let asm_path = format!("{}.s", stem);
+        std::fs::write(&asm_path, asm)
+            .map_err(|e| DriverError::io(format!("failed to write '{}'", asm_path), e))?;
 
         asm_paths.push(asm_path);
         preprocessed_paths.push(preprocessed_path);
     }
 
-    if stop_after_lex || stop_after_parse || stop_after_codegen {
+    if lto {
+        if compile_only {
+            for path in preprocessed_paths {
+                cleanup(&path);
+            }
+            return Ok(0);
+        }
+        log!("Linking the IR of {} inputs...", ir_modules.len());
+        let program = ir::link_programs(ir_modules).map_err(|error| DriverError::Lto { input: None, error })?;
+        let options = compiler::Options { stop_after: compiler::Stage::Codegen, ..options };
+        let artifacts = compiler::compile_ir(program, &options);
+        for change in &artifacts.pass_changes {
+            eprint!("{}", change);
+        }
+        let stem = file_stem(&args.input_paths[0])?;
+        if let Some(ref ir) = artifacts.ir {
+            dump_graphs(args, &stem, ir)?;
+        }
+        let asm_path = format!("{}.s", stem);
+        std::fs::write(&asm_path, artifacts.asm.unwrap())
+            .map_err(|e| DriverError::io(format!("failed to write '{}'", asm_path), e))?;
+        asm_paths.push(asm_path);
+    }
+
+    if stop_after_lex || stop_after_parse || syntax_only || stop_after_codegen {
         for path in preprocessed_paths {
             cleanup(&path);
         }
-        return;
+        return Ok(0);
     }
 
     if stop_after_emit_asm {
         for path in preprocessed_paths {
             cleanup(&path);
         }
-        return;
+        return Ok(0);
     }
 
     // Collect machine flags to forward to GCC assembler and linker
     let mut machine_flags = Vec::new();
+    match target_arch(args) {
+        model::Arch::Riscv64 => machine_flags.extend(["-march=rv64gc".to_string(), "-mabi=lp64d".to_string()]),
+        // The i686 backend addresses globals absolutely
+        model::Arch::I686 => machine_flags.extend(["-m32".to_string(), "-no-pie".to_string()]),
+        model::Arch::X86_64 => {}
+    }
     if args.mno_red_zone { machine_flags.push("-mno-red-zone".to_string()); }
     if args.mno_sse { machine_flags.push("-mno-sse".to_string()); }
     if args.mno_mmx { machine_flags.push("-mno-mmx".to_string()); }
@@ -293,8 +713,44 @@ fn main() {
     if args.fpie { machine_flags.push("-fPIE".to_string()); }
     if args.fpie { machine_flags.push("-pie".to_string()); }
 
+    // Library and raw linker arguments; these go after the inputs so the
+    // linker sees the objects that reference a library before the library.
+    let mut link_flags = Vec::new();
+    for dir in &args.library_paths {
+        link_flags.push(format!("-L{}", dir));
+    }
+    for lib in &args.libraries {
+        link_flags.push(format!("-l{}", lib));
+    }
+    for opt in args.wl_options.iter().filter(|opt| *opt != "error") {
+        if !opt.starts_with("l,") {
+            return Err(DriverError::Usage(format!(
+                "unsupported option '-W{}' (only -Wl,<options> and -Werror are accepted)",
+                opt
+            )));
+        }
+        link_flags.push(format!("-W{}", opt));
+    }
+    for arg in &args.xlinker {
+        link_flags.push("-Xlinker".to_string());
+        link_flags.push(arg.clone());
+    }
+
+    let cleanup_all = |preprocessed_paths: &[String], asm_paths: &[String]| {
+        for path in preprocessed_paths.iter().chain(asm_paths) {
+            cleanup(path);
+        }
+    };
+
+    // Windows objects come from the built-in COFF writer, which reads only
+    // Intel syntax
+    let integrated_as = target_platform(args) == model::Platform::Windows
+        && !args.fno_integrated_as
+        && args.asm_syntax == "intel";
+
     // -c: assemble each .s to .o, skip linking
     if compile_only {
+        let mut result = Ok(0);
         for asm_path in &asm_paths {
             let obj_path = if let Some(ref out) = args.output {
                 // -o overrides output name (only valid for single file)
@@ -302,94 +758,285 @@ fn main() {
             } else {
                 asm_path.replace(".s", ".o")
             };
-            assemble(asm_path, &obj_path, &machine_flags);
+            let assembled = if integrated_as {
+                assemble_builtin(asm_path, &obj_path)
+            } else {
+                let tool = match target_arch(args) {
+                    model::Arch::X86_64 | model::Arch::I686 => "gcc",
+                    model::Arch::Riscv64 => "riscv64-linux-gnu-gcc",
+                };
+                assemble(asm_path, &obj_path, &machine_flags, tool)
+            };
+            if let Err(e) = assembled {
+                result = Err(e);
+                break;
+            }
         }
-        for path in preprocessed_paths {
-            cleanup(&path);
+        cleanup_all(&preprocessed_paths, &asm_paths);
+        return result;
+    }
+
+    // The linker takes the .s files, or the objects the built-in assembler
+    // wrote from them
+    let mut link_inputs = asm_paths.clone();
+    if integrated_as {
+        for input in &mut link_inputs {
+            let obj_path = format!("{}.o", input.trim_end_matches(".s"));
+            if let Err(e) = assemble_builtin(input, &obj_path) {
+                cleanup_all(&preprocessed_paths, &asm_paths);
+                return Err(e);
+            }
+            *input = obj_path;
         }
-        for path in asm_paths {
-            cleanup(&path);
+    }
+    let remove_objects = || {
+        if integrated_as {
+            for path in &link_inputs {
+                let _ = std::fs::remove_file(path);
+            }
         }
-        return;
+    };
+
+    // --run: link to a temporary executable, run it, and forward its exit code
+    if args.run {
+        let exe_path = temp_executable_path(&args.input_paths[0])?;
+        log!("Step 8: Linking...");
+        let linked = run_linker(&link_inputs, &exe_path, nostdlib, ffreestanding, &machine_flags, &link_flags);
+        cleanup_all(&preprocessed_paths, &asm_paths);
+        remove_objects();
+        linked?;
+        log!("Step 8: Done");
+        log!("Step 9: Running {}...", exe_path);
+        let code = run_executable(&exe_path, &args.run_args);
+        let _ = std::fs::remove_file(&exe_path);
+        return code;
     }
 
-    // Determine output executable name
-    let output_name = if let Some(name) = args.output {
-        name
-    } else {
-        // Default: use first input file's stem
-        let first_input = Path::new(&args.input_paths[0]);
-        let platform = model::Platform::host();
-        let mut name = first_input.file_stem().unwrap().to_string_lossy().into_owned();
-        name.push_str(platform.executable_extension());
-        name
-    };
+    let output_name = executable_name(args)?;
 
     log!("Step 8: Linking...");
-    run_linker(&asm_paths, &output_name, nostdlib, ffreestanding, &machine_flags);
+    let linked = run_linker(&link_inputs, &output_name, nostdlib, ffreestanding, &machine_flags, &link_flags);
+    cleanup_all(&preprocessed_paths, &asm_paths);
+    remove_objects();
+    linked?;
     log!("Step 8: Done");
     println!("Compilation successful. Generated executable: {}", output_name);
+    Ok(0)
+}
 
-    // Cleanup
-    for path in preprocessed_paths {
-        cleanup(&path);
+/// Write the files requested by --emit-ir / --dump-cfg / --dump-callgraph.
+fn dump_graphs(args: &Args, stem: &str, ir: &ir::IRProgram) -> DriverResult<()> {
+    let write = |path: String, contents: String| {
+        std::fs::write(&path, contents).map_err(|e| DriverError::io(format!("failed to write '{}'", path), e))
+    };
+    if args.emit_ir {
+        write(format!("{}.ir", stem), ir::program_to_text(ir))?;
     }
-    for path in asm_paths {
-        cleanup(&path);
+    if args.dump_cfg {
+        let (format, extension) = match args.cfg_format.as_str() {
+            "mermaid" => (ir::CfgFormat::Mermaid, "mmd"),
+            _ => (ir::CfgFormat::Dot, "dot"),
+        };
+        for func in &ir.functions {
+            write(format!("{}.{}.cfg.{}", stem, func.name, extension), ir::render_cfg(func, format))?;
+        }
+    }
+    if args.dump_callgraph {
+        write(format!("{}.callgraph.dot", stem), ir::callgraph_to_dot(ir))?;
     }
+    Ok(())
+}
+
+/// The preprocessed source of `input_path` with its line markers, so
+/// diagnostics point at the original file and line rather than the `.i` file.
+fn source_map(input_path: &str, src: &str) -> model::SourceMap {
+    let mut sources = model::SourceMap::new();
+    let id = sources.add_file(input_path, src);
+    sources.add_line_markers(id, lexer::line_markers(src));
+    sources
 }
 
-fn preprocess(input_path: &str, input_file: &Path, extra_args: &[String]) -> String {
-    let mut preprocessed_path = input_file.file_stem().unwrap().to_string_lossy().to_string();
-    preprocessed_path.push_str(".i");
+/// A warning reported as an error under -Werror, with a note saying why.
+fn promote_warning(mut warning: compiler::Diagnostic) -> compiler::Diagnostic {
+    warning.error.severity = model::Severity::Error;
+    warning.error.notes.push("warnings are treated as errors (-Werror)".to_string());
+    warning
+}
 
-    let mut cmd = Command::new("gcc");
-    cmd.args(["-E", "-P", "-Iinclude"]);
-    
-    // Forward extra preprocessor flags (-D, -U, -I, -include)
-    for arg in extra_args {
-        cmd.arg(arg);
+/// Write <stem>.symbols.json for --emit-symbols. Positions refer to the
+/// preprocessed source.
+fn emit_symbols(input_path: &str, stem: &str, src: &str, artifacts: &compiler::Artifacts) -> DriverResult<()> {
+    let index = parser::index_symbols(&artifacts.tokens, &artifacts.spans).map_err(|error| DriverError::Compile {
+        input: input_path.to_string(),
+        diagnostics: compiler::Diagnostics { items: vec![compiler::Diagnostic { stage: compiler::Stage::Parse, error }] },
+        sources: Some(source_map(input_path, src)),
+    })?;
+    let mut sources = model::SourceMap::new();
+    sources.add_file(input_path, src);
+    let path = format!("{}.symbols.json", stem);
+    std::fs::write(&path, index.to_json(&sources)).map_err(|e| DriverError::io(format!("failed to write '{}'", path), e))
+}
+
+/// Write <stem>.tokens.json for --emit-tokens. Positions refer to the
+/// preprocessed source.
+fn emit_tokens(input_path: &str, stem: &str, src: &str, artifacts: &compiler::Artifacts) -> DriverResult<()> {
+    let mut sources = model::SourceMap::new();
+    sources.add_file(input_path, src);
+    let path = format!("{}.tokens.json", stem);
+    let json = lexer::tokens_to_json(&artifacts.tokens, &artifacts.spans, &sources);
+    std::fs::write(&path, json).map_err(|e| DriverError::io(format!("failed to write '{}'", path), e))
+}
+
+/// Platform named by --target, or the host's.
+fn target_platform(args: &Args) -> model::Platform {
+    match args.target.as_deref() {
+        Some("x86_64-windows") => model::Platform::Windows,
+        Some(_) => model::Platform::Linux,
+        None => model::Platform::host(),
     }
-    
-    cmd.args([input_path, "-o", &preprocessed_path]);
+}
 
-    let exit_code = cmd
-        .status()
-        .expect("failed to execute process");
+/// Architecture named by --target; the host is x86-64.
+fn target_arch(args: &Args) -> model::Arch {
+    match args.target.as_deref() {
+        Some("riscv64-linux") => model::Arch::Riscv64,
+        Some("i686-linux" | "i686") => model::Arch::I686,
+        _ => model::Arch::X86_64,
+    }
+}
 
-    if !exit_code.success() {
-        if let Some(code) = exit_code.code() {
-            panic!("gcc preprocess command failed with exit code {}", code);
-        }
-        panic!("gcc preprocess command was terminated by a signal");
+/// Output executable name: `-o` if given, else the first input's stem.
+fn executable_name(args: &Args) -> DriverResult<String> {
+    if let Some(ref name) = args.output {
+        return Ok(name.clone());
     }
-    preprocessed_path
+    let mut name = file_stem(&args.input_paths[0])?;
+    name.push_str(model::Platform::host().executable_extension());
+    Ok(name)
 }
 
-fn assemble(asm_path: &str, obj_path: &str, extra_flags: &[String]) {
-    let mut cmd = Command::new("gcc");
-    cmd.args(["-c", asm_path, "-o", obj_path]);
-    for flag in extra_flags { cmd.arg(flag); }
-    let exit_code = cmd
-        .status()
-        .expect("failed to run gcc assembler");
+/// What this invocation produces for the input with `stem`: the `.o` under
+/// -c (`.bir` under -c --flto), the `.s` under -S, otherwise the executable.
+fn output_for(args: &Args, stem: &str) -> DriverResult<String> {
+    Ok(match args.output {
+        Some(ref out) if args.compile_only || args.emit_asm => out.clone(),
+        _ if args.compile_only && args.flto => format!("{}.bir", stem),
+        _ if args.compile_only => format!("{}.o", stem),
+        _ if args.emit_asm => format!("{}.s", stem),
+        _ => executable_name(args)?,
+    })
+}
+
+/// Dependency file path and Make target for one input, if --MD/--MF was given.
+///
+/// The target is the input's output (see `output_for`). Without --MF the
+/// fragment goes next to a -c/-S output, or to `<stem>.d` in the working directory.
+fn dependency_output(args: &Args, stem: &str) -> DriverResult<Option<(String, String)>> {
+    if !args.dep_file && args.dep_file_path.is_none() {
+        return Ok(None);
+    }
 
-    if !exit_code.success() {
-        if let Some(code) = exit_code.code() {
-            panic!("gcc assembly failed with exit code {}", code);
+    let target = output_for(args, stem)?;
+    let dep_path = match args.dep_file_path {
+        Some(ref path) => path.clone(),
+        None if (args.compile_only || args.emit_asm) && args.output.is_some() => {
+            Path::new(&target).with_extension("d").to_string_lossy().into_owned()
         }
-        panic!("gcc assembly was terminated by a signal");
+        None => format!("{}.d", stem),
+    };
+    Ok(Some((dep_path, target)))
+}
+
+/// Whether `path` is IR written by `-c --flto` rather than C source.
+fn is_ir_object(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "bir")
+}
+
+fn write_ir_object(path: &str, ir: &ir::IRProgram) -> DriverResult<()> {
+    let bytes = ir::program_to_bytes(ir).map_err(|error| DriverError::Lto { input: Some(path.to_string()), error })?;
+    std::fs::write(path, bytes).map_err(|e| DriverError::io(format!("failed to write '{}'", path), e))
+}
+
+fn read_ir_object(path: &str) -> DriverResult<ir::IRProgram> {
+    let bytes = std::fs::read(path).map_err(|e| DriverError::io(format!("failed to read '{}'", path), e))?;
+    ir::program_from_bytes(&bytes).map_err(|error| DriverError::Lto { input: Some(path.to_string()), error })
+}
+
+/// File name of `path` without its extension, used to name intermediates and outputs.
+fn file_stem(path: &str) -> DriverResult<String> {
+    Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .ok_or_else(|| DriverError::InvalidPath(path.to_string()))
+}
+
+/// Spawn `gcc` with `args`, mapping spawn failures and non-zero exits to errors.
+fn run_gcc(args: &[String], action: &'static str) -> DriverResult<()> {
+    run_tool("gcc", args, action)
+}
+
+/// Spawn `tool` with `args`, as `run_gcc` does.
+fn run_tool(tool: &str, args: &[String], action: &'static str) -> DriverResult<()> {
+    let status = Command::new(tool)
+        .args(args)
+        .status()
+        .map_err(|e| DriverError::ToolNotFound { tool: tool.to_string(), source: e })?;
+
+    if !status.success() {
+        return Err(DriverError::ToolFailed { tool: tool.to_string(), action, status });
     }
+    Ok(())
+}
+
+fn preprocess(input_path: &str, stem: &str, extra_args: &[String]) -> DriverResult<String> {
+    let preprocessed_path = format!("{}.i", stem);
+
+    // Without -P, gcc writes `# N "file"` line markers, which the lexer uses to
+    // map diagnostics back into the original sources
+    let mut args: Vec<String> = ["-E", "-Iinclude"].iter().map(|s| s.to_string()).collect();
+
+    // Forward extra preprocessor flags (-D, -U, -I, -include, -MD -MF -MT)
+    args.extend(extra_args.iter().cloned());
+
+    args.push(input_path.to_string());
+    args.push("-o".to_string());
+    args.push(preprocessed_path.clone());
+
+    run_gcc(&args, "preprocessing")?;
+    Ok(preprocessed_path)
+}
+
+/// Assemble with `tool`: `gcc`, or the cross compiler of a RISC-V target.
+fn assemble(asm_path: &str, obj_path: &str, extra_flags: &[String], tool: &str) -> DriverResult<()> {
+    let mut args = vec!["-c".to_string(), asm_path.to_string(), "-o".to_string(), obj_path.to_string()];
+    args.extend(extra_flags.iter().cloned());
+    run_tool(tool, &args, "assembly")
+}
+
+/// Assemble with the built-in COFF writer instead of `gcc`.
+fn assemble_builtin(asm_path: &str, obj_path: &str) -> DriverResult<()> {
+    let asm = std::fs::read_to_string(asm_path)
+        .map_err(|e| DriverError::io(format!("failed to read '{}'", asm_path), e))?;
+    let object = compiler::assemble_coff(&asm)
+        .map_err(|message| DriverError::Assemble { input: asm_path.to_string(), message })?;
+    std::fs::write(obj_path, object).map_err(|e| DriverError::io(format!("failed to write '{}'", obj_path), e))
 }
 
-fn run_linker(asm_paths: &[String], output_file: &str, nostdlib: bool, ffreestanding: bool, extra_flags: &[String]) {
+fn run_linker(
+    inputs: &[String],
+    output_file: &str,
+    nostdlib: bool,
+    ffreestanding: bool,
+    extra_flags: &[String],
+    link_flags: &[String],
+) -> DriverResult<()> {
     let platform = model::Platform::host();
 
     let mut args = Vec::new();
     
-    // Add all assembly files
-    for asm_path in asm_paths {
-        args.push(asm_path.clone());
+    // Add all assembly files or objects
+    for input in inputs {
+        args.push(input.clone());
     }
     
     args.push("-o".to_string());
@@ -413,15 +1060,51 @@ fn run_linker(asm_paths: &[String], output_file: &str, nostdlib: bool, ffreestan
         args.push(flag.clone());
     }
 
-    let exit_code = Command::new("gcc")
-        .args(&args)
+    // Library search paths, libraries, and raw linker options
+    args.extend(link_flags.iter().cloned());
+
+    run_gcc(&args, "linking")
+}
+
+/// Run `main` of the lowered program in the IR interpreter. A trap (e.g. a
+/// null dereference) is reported and exits with `128 + signal`, like `--run`.
+fn interpret(input_path: &str, ir: &ir::IRProgram, program_args: &[String]) -> DriverResult<i32> {
+    match ir::interpret(ir, input_path, program_args) {
+        Ok(code) => Ok(code),
+        Err(e) => match e.exit_code() {
+            Some(code) => {
+                eprintln!("{}: program terminated: {}", input_path, e);
+                Ok(code)
+            }
+            None => Err(DriverError::Interpret { input: input_path.to_string(), message: e.to_string() }),
+        },
+    }
+}
+
+/// Pick a unique path in the system temp directory for a `--run` executable.
+fn temp_executable_path(first_input: &str) -> DriverResult<String> {
+    let mut name = format!("{}-run-{}", file_stem(first_input)?, std::process::id());
+    name.push_str(model::Platform::host().executable_extension());
+    Ok(std::env::temp_dir().join(name).to_string_lossy().into_owned())
+}
+
+/// Run a freshly linked executable with inherited stdio and return the exit
+/// code to propagate. Signal deaths map to the shell convention `128 + signo`.
+fn run_executable(exe_path: &str, program_args: &[String]) -> DriverResult<i32> {
+    let status = Command::new(exe_path)
+        .args(program_args)
         .status()
-        .expect("executable generated sucessfully");
+        .map_err(|e| DriverError::io(format!("failed to run '{}'", exe_path), e))?;
 
-    if !exit_code.success() {
-        if let Some(code) = exit_code.code() {
-            panic!("gcc compilation failed with exit code {}", code);
+    if let Some(code) = status.code() {
+        return Ok(code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Ok(128 + signal);
         }
-        panic!("gcc compilation was terminated by a signal");
     }
+    Ok(EXIT_FAILURE)
 }
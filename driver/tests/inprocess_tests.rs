@@ -13,26 +13,9 @@ use std::process::Command;
 /// Run the full compiler pipeline in-process on a C source string,
 /// returning the generated assembly.
 fn compile_source(src: &str) -> Result<String, String> {
-    let tokens = lexer::lex(src).map_err(|e| format!("Lex error: {:?}", e))?;
-    let mut program = parser::parse_tokens(&tokens).map_err(|e| format!("Parse error: {:?}", e))?;
-
-    // Deduplicate globals (same as driver/src/main.rs)
-    {
-        let mut seen = std::collections::HashSet::new();
-        program.globals.retain(|g| seen.insert(g.name.clone()));
-    }
-
-    let mut analyzer = semantic::SemanticAnalyzer::new();
-    analyzer.analyze(&program).map_err(|e| format!("Semantic error: {:?}", e))?;
-
-    let mut lowerer = ir::Lowerer::new();
-    let ir_prog = lowerer.lower_program(&program).map_err(|e| format!("IR error: {:?}", e))?;
-
-    let ir_prog = optimizer::optimize(ir_prog);
-
-    let mut cg = codegen::Codegen::new();
-    let asm = cg.gen_program(&ir_prog);
-    Ok(asm)
+    let artifacts = compiler::compile_source(src, &compiler::Options::default())
+        .map_err(|e| e.to_string())?;
+    Ok(artifacts.asm.expect("full pipeline produces assembly"))
 }
 
 /// Compile C source to an executable, run it, and return exit code.
@@ -82,10 +65,11 @@ fn parse_expected(src: &str) -> Option<i32> {
     None
 }
 
-/// Preprocess a C file with gcc -E, returning the preprocessed source
+/// Preprocess a C file with gcc -E, returning the preprocessed source. Line
+/// markers are kept so inline definitions from system headers are skipped.
 fn preprocess(path: &Path) -> Result<String, String> {
     let output = Command::new("gcc")
-        .args(&["-E", "-P", path.to_str().unwrap()])
+        .args(&["-E", path.to_str().unwrap()])
         .output()
         .map_err(|e| format!("gcc -E failed: {}", e))?;
 
@@ -101,6 +85,8 @@ fn preprocess(path: &Path) -> Result<String, String> {
 
 /// Files that require preprocessing (#include) — these go through gcc -E
 const NEEDS_PREPROCESS: &[&str] = &[
+    "test_computed_goto.c",
+    "test_digraphs.c",
     "test_escape_sequences.c",
     "test_function_pointer_debug.c",
     "test_include.c",
@@ -108,6 +94,7 @@ const NEEDS_PREPROCESS: &[&str] = &[
     "test_interrupt_handler.c",
     "test_malloc.c",
     "test_signal.c",
+    "test_static_inline.c",
 ];
 
 /// Files to skip entirely (need features not supported in-process)
@@ -115,17 +102,25 @@ const SKIP_FILES: &[&str] = &[
     "test_variadic_intrinsics.c", // va_list/va_start from <stdarg.h> not fully supported after gcc -E
 ];
 
-#[test]
-fn run_all_c_tests_in_process() {
+/// A corpus program ready to compile: file name, source (preprocessed if it
+/// needs to be), and expected exit code.
+struct CorpusProgram {
+    file_name: String,
+    src: String,
+    expected_code: i32,
+}
+
+/// Every program in `testing/` with an `// EXPECT` annotation, in file-name
+/// order, and the number of files skipped.
+fn corpus_programs() -> (Vec<CorpusProgram>, usize) {
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
     let workspace_root = Path::new(&manifest_dir)
         .parent()
         .expect("Failed to get workspace root");
     let testing_dir = workspace_root.join("testing");
 
-    let mut passed = 0;
+    let mut programs = Vec::new();
     let mut skipped = 0;
-    let mut failed = Vec::new();
 
     let mut entries: Vec<_> = fs::read_dir(&testing_dir)
         .expect("Failed to read testing dir")
@@ -172,18 +167,31 @@ fn run_all_c_tests_in_process() {
                 }
             }
         } else {
-            raw_src.clone()
+            raw_src
         };
 
-        let test_name = path.file_stem().unwrap().to_str().unwrap();
-        match compile_and_run(&src, test_name) {
-            Ok(exit_code) if exit_code == expected_code => {
+        programs.push(CorpusProgram { file_name: file_name.to_string(), src, expected_code });
+    }
+    (programs, skipped)
+}
+
+#[test]
+fn run_all_c_tests_in_process() {
+    let (programs, skipped) = corpus_programs();
+    let mut passed = 0;
+    let mut failed = Vec::new();
+
+    for program in &programs {
+        let file_name = &program.file_name;
+        let test_name = file_name.trim_end_matches(".c");
+        match compile_and_run(&program.src, test_name) {
+            Ok(exit_code) if exit_code == program.expected_code => {
                 passed += 1;
             }
             Ok(exit_code) => {
                 eprintln!(
                     "  FAIL {}: expected exit {}, got {}",
-                    file_name, expected_code, exit_code
+                    file_name, program.expected_code, exit_code
                 );
                 failed.push(file_name.to_string());
             }
@@ -209,3 +217,64 @@ fn run_all_c_tests_in_process() {
         );
     }
 }
+
+/// Interpret `src` after lowering (`Stage::Lower`) or after optimization
+/// (`Stage::Optimize`). Returns `None` when the program uses something the
+/// interpreter cannot run.
+fn interpret_source(src: &str, stop_after: compiler::Stage) -> Result<Option<i32>, String> {
+    let options = compiler::Options { stop_after, ..compiler::Options::default() };
+    let artifacts = compiler::compile_source(src, &options).map_err(|e| e.to_string())?;
+    let program = artifacts.ir.expect("lowering produces IR");
+    let mut out = Vec::new();
+    let status = ir::Interpreter::new(&program, &mut out).and_then(|mut interp| interp.run_main("prog", &[]));
+    match status {
+        Ok(code) => Ok(Some(code)),
+        Err(e) => Ok(e.exit_code()),
+    }
+}
+
+/// Differential test: the IR interpreter must agree with the compiled
+/// program on every corpus file it can run, both on the IR as lowered and
+/// on the IR the optimizer hands to codegen.
+#[test]
+fn interpret_all_c_tests_in_process() {
+    let (programs, _) = corpus_programs();
+    let mut passed = 0;
+    let mut unsupported = 0;
+    let mut failed = Vec::new();
+
+    for program in &programs {
+        for stage in [compiler::Stage::Lower, compiler::Stage::Optimize] {
+            match interpret_source(&program.src, stage) {
+                Ok(Some(code)) if code == program.expected_code => passed += 1,
+                Ok(Some(code)) => {
+                    eprintln!(
+                        "  FAIL {} ({}): expected exit {}, interpreter gave {}",
+                        program.file_name, stage, program.expected_code, code
+                    );
+                    failed.push(format!("{} ({})", program.file_name, stage));
+                }
+                Ok(None) => unsupported += 1,
+                Err(e) => {
+                    eprintln!("  FAIL {} ({}): {}", program.file_name, stage, e);
+                    failed.push(format!("{} ({})", program.file_name, stage));
+                }
+            }
+        }
+    }
+
+    println!(
+        "\nInterpreter: {} runs agreed, {} not interpretable, {} failed",
+        passed,
+        unsupported,
+        failed.len()
+    );
+
+    if !failed.is_empty() {
+        panic!(
+            "{} interpreter runs disagreed with the expected exit code:\n  {}",
+            failed.len(),
+            failed.join("\n  ")
+        );
+    }
+}
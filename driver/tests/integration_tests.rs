@@ -98,3 +98,952 @@ fn parse_expected_code(source: &str) -> Option<i32> {
     }
     None
 }
+
+/// Write `src` to a fresh scratch directory and return (dir, source path).
+fn scratch_source(test_name: &str, src: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("driver-{}-{}", test_name, std::process::id()));
+    fs::create_dir_all(&dir).expect("Failed to create scratch dir");
+    let path = dir.join(format!("{}.c", test_name));
+    fs::write(&path, src).expect("Failed to write scratch source");
+    (dir, path)
+}
+
+#[test]
+fn run_mode_forwards_exit_code_and_stdout() {
+    let src = "int puts(const char *s);\nint main(int argc, char **argv) { puts(argv[1]); return 40 + argc; }\n";
+    let (dir, path) = scratch_source("run_mode", src);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .arg("--run")
+        .arg("--")
+        .arg("hello")
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+
+    assert_eq!(output.status.code(), Some(42));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+    assert!(!dir.join("run_mode.s").exists(), "--run should not leave intermediates behind");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn interpret_mode_runs_without_assembling() {
+    let src = "int printf(const char *fmt, ...);\nint main(int argc, char **argv) { printf(\"%s %d\\n\", argv[1], argc); return 40 + argc; }\n";
+    let (dir, path) = scratch_source("interpret_mode", src);
+
+    let interpret = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .arg("--interpret")
+            .args(extra)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    let output = interpret(&["--", "hello"]);
+    assert_eq!(output.status.code(), Some(42));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello 2\n");
+    assert!(!dir.join("interpret_mode.s").exists(), "--interpret should not write assembly");
+
+    let output = interpret(&["-S"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--interpret cannot be combined"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn interpret_mode_reports_traps_like_signals() {
+    let (dir, path) = scratch_source("interpret_trap", "int main() { int *p = 0; return *p; }\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .arg("--interpret")
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+
+    assert_eq!(output.status.code(), Some(128 + 11));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid memory access"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn macro_definitions_reach_the_preprocessor_in_order() {
+    let src = "#ifndef VAL\n#define VAL 1\n#endif\nint main() {\n#ifdef FLAG\n    return VAL + 100;\n#else\n    return VAL;\n#endif\n}\n";
+    let (dir, path) = scratch_source("macro_order", src);
+
+    let run = |flags: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args(flags)
+            .arg("--run")
+            .current_dir(&dir)
+            .status()
+            .expect("Failed to run driver")
+            .code()
+    };
+
+    assert_eq!(run(&["-DVAL=5", "-DFLAG"]), Some(105));
+    assert_eq!(run(&["-D", "VAL=(2+3)"]), Some(5));
+    assert_eq!(run(&["-DVAL=5", "-UVAL"]), Some(1));
+    assert_eq!(run(&["-UVAL", "-DVAL=9"]), Some(9));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dependency_file_lists_included_headers() {
+    let (dir, path) = scratch_source("deps", "#include \"answer.h\"\nint main() { return ANSWER; }\n");
+    fs::write(dir.join("answer.h"), "#define ANSWER 42\n").expect("Failed to write header");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["-c", "--MD"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+
+    let deps = fs::read_to_string(dir.join("deps.d")).expect("--MD should write deps.d");
+    assert!(deps.starts_with("deps.o:"), "unexpected target in {:?}", deps);
+    assert!(deps.contains("answer.h"), "header missing from {:?}", deps);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn compile_commands_are_recorded_and_replayed() {
+    let (dir, path) = scratch_source("compdb", "int main() { return 0; }\n");
+    let driver = env!("CARGO_BIN_EXE_driver");
+
+    for _ in 0..2 {
+        let status = Command::new(driver)
+            .arg(&path)
+            .args(["-c", "--compile-commands", "compile_commands.json"])
+            .current_dir(&dir)
+            .status()
+            .expect("Failed to run driver");
+        assert!(status.success());
+    }
+    let db = fs::read_to_string(dir.join("compile_commands.json")).expect("database should be written");
+    assert_eq!(db.matches("\"file\"").count(), 1, "rebuilding should replace the entry: {}", db);
+    assert!(db.contains("\"output\": \"compdb.o\""), "unexpected database: {}", db);
+    assert!(!db.contains("--compile-commands"), "recorded arguments should not rewrite the database");
+
+    fs::remove_file(dir.join("compdb.o")).expect("-c should have produced compdb.o");
+    let output = Command::new(driver)
+        .args(["--from-compile-commands", "compile_commands.json"])
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(dir.join("compdb.o").exists(), "batch mode should recompile the entry");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn att_syntax_matches_intel() {
+    // Indexed addressing, sign extension, doubles, a computed goto, and
+    // Intel inline asm, which is rewritten like the generated code
+    let src = "int printf(const char *fmt, ...);\n\
+               struct P { int x; long y; };\n\
+               long sum(int *a, int n) { long s = 0; for (int i = 0; i < n; i++) s += a[i] * 5; return s; }\n\
+               double avg(struct P *p, int n) { double s = 0; for (int i = 0; i < n; i++) s += p[i].x + p[i].y; return s / n; }\n\
+               int pick(int i) { static void *where[] = { &&one, &&two }; goto *where[i]; one: return 10; two: return 20; }\n\
+               int main() {\n\
+                   int a[6] = { 1, -2, 3, -4, 5, 6 };\n\
+                   struct P ps[2] = { { 1, 2 }, { 3, 4 } };\n\
+                   int r; __asm volatile(\"mov %0, 42\" : \"=r\"(r));\n\
+                   printf(\"%ld %.2f %d %d\\n\", sum(a, 6), avg(ps, 2), pick(1), r);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("att", src);
+    for level in ["-O0", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--asm-syntax", "att", "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "45 5.00 20 42\n", "{}", level);
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["-S", "--asm-syntax=att"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+    let asm = fs::read_to_string(dir.join("att.s")).expect("-S should write the assembly");
+    assert!(!asm.contains(".intel_syntax"));
+    assert!(asm.contains("%rbp") && !asm.contains("PTR"), "{}", asm);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dump_flags_write_dot_files() {
+    let src = "int helper(int x) { if (x) { return 1; } return 2; }\nint main() { return helper(0); }\n";
+    let (dir, path) = scratch_source("dot", src);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["-S", "--dump-cfg", "--dump-callgraph"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+
+    let cfg = fs::read_to_string(dir.join("dot.main.cfg.dot")).expect("--dump-cfg should write one file per function");
+    assert!(cfg.starts_with("digraph \"main\""));
+    let calls = fs::read_to_string(dir.join("dot.callgraph.dot")).expect("--dump-callgraph should write the call graph");
+    assert!(calls.starts_with("digraph callgraph"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["-S", "--dump-cfg", "--cfg-format", "mermaid"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+    let cfg = fs::read_to_string(dir.join("dot.helper.cfg.mmd")).expect("--cfg-format=mermaid should write .mmd files");
+    assert!(cfg.starts_with("flowchart TD"));
+    assert!(cfg.contains("-->|\"T\"|"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn emit_ir_writes_program_text() {
+    let src = "int g = 3;\nint main() { return g + 1; }\n";
+    let (dir, path) = scratch_source("emitir", src);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["-S", "--emit-ir"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+
+    let text = fs::read_to_string(dir.join("emitir.ir")).expect("--emit-ir should write <stem>.ir");
+    assert!(text.starts_with("global int @g = 3\n"), "{}", text);
+    assert!(text.contains("function main() -> int {"), "{}", text);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn emit_tokens_writes_json() {
+    let src = "int main() { return 42; }\n";
+    let (dir, path) = scratch_source("tokens", src);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .args(["--fsyntax-only", "--emit-tokens"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run driver");
+    assert!(status.success());
+
+    let json = fs::read_to_string(dir.join("tokens.tokens.json")).expect("--emit-tokens should write <stem>.tokens.json");
+    assert!(json.starts_with("{\n  \"tokens\": ["));
+    assert!(json.contains("{\"kind\": \"return\", \"text\": \"return\""));
+    assert!(json.contains("{\"kind\": \"constant\", \"text\": \"42\""));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sanitize_undefined_aborts_on_undefined_arithmetic() {
+    let src = "int div(int a, int b) { return a / b; }\n\
+               int add(int a, int b) { return a + b; }\n\
+               int main(int argc, char **argv) {\n\
+                   if (argc == 1) return div(84, 2);\n\
+                   if (argv[1][0] == 'd') return div(1, 0);\n\
+                   return add(2147483647, argc);\n\
+               }\n";
+    let (dir, path) = scratch_source("ubsan", src);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args(["--fsanitize", "undefined", "--run", "--"])
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    assert_eq!(run(&[]).status.code(), Some(42));
+    let output = run(&["d"]);
+    assert_eq!(output.status.code(), Some(128 + 6), "division by zero should abort");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("runtime error: integer division by zero"));
+    let output = run(&["o"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("runtime error: signed integer overflow"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sanitize_bounds_traps_on_stack_array_overflow() {
+    let src = "int get(int i) { int a[4] = {1, 2, 3, 4}; return a[i]; }\n\
+               void fill(int n) { char buf[5]; char *p = buf; for (int i = 0; i < n; i++) p[i] = 'x'; }\n\
+               int main(int argc, char **argv) {\n\
+                   if (argc == 1) { fill(5); return get(3) + 38; }\n\
+                   if (argv[1][0] == 'i') return get(argc + 2);\n\
+                   fill(7);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("bounds", src);
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args(["--fsanitize=undefined,bounds", "--run", "--"])
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    assert_eq!(run(&[]).status.code(), Some(42));
+    let output = run(&["i"]);
+    assert_eq!(output.status.code(), Some(128 + 6), "a[4] should abort");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("runtime error: index out of bounds for stack array"));
+    let output = run(&["w"]);
+    assert_eq!(output.status.code(), Some(128 + 6), "writing past buf should abort at return");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("runtime error: stack array guard overwritten"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn syntax_only_reports_errors_without_writing_outputs() {
+    let (dir, good) = scratch_source("syntax_only", "int main() { return 0; }\n");
+    let bad = dir.join("syntax_only_bad.c");
+    fs::write(&bad, "int main() { return missing; }\n").expect("Failed to write scratch source");
+
+    let check = |path: &std::path::Path| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(path)
+            .arg("--fsyntax-only")
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    assert!(check(&good).status.success());
+    let output = check(&bad);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undeclared variable missing"));
+
+    let leftovers: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().and_then(|x| x.to_str()) != Some("c"))
+        .collect();
+    assert!(leftovers.is_empty(), "--fsyntax-only should leave no files behind: {:?}", leftovers);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn internal_errors_write_a_reproduction_bundle() {
+    // The optimizer panics in the named pass when asked to
+    let src = "int main() { int x = 6; return x * 7; }\n";
+    let (dir, path) = scratch_source("ice", src);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .arg("-S")
+        .env("CCOMPILER_PANIC_IN_PASS", "folding-dce")
+        .env_remove("RUST_BACKTRACE")
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    assert_eq!(output.status.code(), Some(4), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("internal compiler error: "), "unexpected stderr: {}", stderr);
+    assert!(!stderr.contains("stack backtrace"), "backtrace should be suppressed: {}", stderr);
+    assert!(stderr.contains("pass 'folding-dce' on function 'main'"), "unexpected stderr: {}", stderr);
+
+    let bundle = stderr
+        .lines()
+        .find_map(|l| l.strip_prefix("note: reproduction files written to "))
+        .map(std::path::PathBuf::from)
+        .expect("bundle path missing from stderr");
+    let report = fs::read_to_string(bundle.join("report.txt")).expect("report.txt should be written");
+    assert!(report.contains("stage: optimization"));
+    assert!(fs::read_to_string(bundle.join("ice.i")).unwrap().contains("x * 7"));
+    assert!(bundle.join("ir.txt").exists());
+    let _ = fs::remove_dir_all(&bundle);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn difftest_reports_matching_programs() {
+    let (dir, _) = scratch_source("difftest_a", "int main() { return 7; }\n");
+    fs::write(dir.join("difftest_b.c"), "int printf(const char *f, ...);\nint main() { printf(\"%d\\n\", 6 * 7); return 0; }\n")
+        .expect("Failed to write scratch source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("--difftest")
+        .arg(&dir)
+        .output()
+        .expect("Failed to run driver");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "difftest reported divergence:\n{}", stdout);
+    assert!(stdout.contains("2 matched"), "unexpected summary:\n{}", stdout);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn errors_exit_with_code_one_instead_of_panicking() {
+    let (dir, path) = scratch_source("bad_token", "int main() { return `; }\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "stderr:\n{}", stderr);
+    assert!(stderr.contains("error[E0101]: lexing failed"), "stderr:\n{}", stderr);
+    assert!(!stderr.contains("panicked"), "stderr:\n{}", stderr);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(dir.join("does_not_exist.c"))
+        .output()
+        .expect("Failed to run driver");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not found"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn warning_flags_filter_by_severity() {
+    let src = "enum E { A, B };\nint f(enum E e) { switch (e) { case A: return 1; } return 0; }\nint main(void) { return f(B); }\n";
+    let (dir, path) = scratch_source("warning_flags", src);
+    let compile = |flags: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args(["-S", "-o", "warning_flags.s"])
+            .args(flags)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    let output = compile(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(0), "stderr:\n{}", stderr);
+    assert!(stderr.contains("warning_flags.c:2:1: warning[E0312]: enumeration value 'B' not handled"), "stderr:\n{}", stderr);
+
+    let output = compile(&["-w"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty(), "stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = compile(&["-Werror"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "stderr:\n{}", stderr);
+    assert!(stderr.contains("error[E0312]"), "stderr:\n{}", stderr);
+    assert!(stderr.contains("note: warnings are treated as errors (-Werror)"), "stderr:\n{}", stderr);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn links_against_libraries_from_library_paths() {
+    let (dir, path) = scratch_source("link_lib", "int twice(int x);\nint main() { return twice(21); }\n");
+    fs::write(dir.join("twice.c"), "int twice(int x) { return x * 2; }\n").expect("Failed to write library source");
+    let status = Command::new("gcc")
+        .args(["-c", "twice.c", "-o", "twice.o"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run gcc");
+    assert!(status.success());
+    let status = Command::new("ar")
+        .args(["rcs", "libtwice.a", "twice.o"])
+        .current_dir(&dir)
+        .status()
+        .expect("Failed to run ar");
+    assert!(status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg(&path)
+        .arg(format!("-L{}", dir.display()))
+        .arg("-ltwice")
+        .arg("-Wl,--as-needed")
+        .arg("--run")
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    assert_eq!(output.status.code(), Some(42), "stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn flto_links_ir_objects_across_units() {
+    let src = "static int scale(int x) { return x + 1; }\nint twice(int x);\nint main() { return twice(scale(20)); }\n";
+    let (dir, path) = scratch_source("flto", src);
+    fs::write(dir.join("twice.c"), "static int scale(int x) { return x * 2; }\nint twice(int x) { return scale(x); }\n")
+        .expect("Failed to write second unit");
+    for input in [path.to_str().unwrap(), "twice.c"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .args([input, "-c", "--flto"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert!(output.status.success(), "stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+    assert!(dir.join("flto.bir").exists() && dir.join("twice.bir").exists());
+    assert!(!dir.join("flto.o").exists(), "-c --flto should write IR instead of an object");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .args(["flto.bir", "twice.bir", "--run"])
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    assert_eq!(output.status.code(), Some(42), "stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+
+    fs::write(dir.join("broken.bir"), "not IR").expect("Failed to write broken IR");
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .args(["flto.bir", "broken.bir"])
+        .current_dir(&dir)
+        .output()
+        .expect("Failed to run driver");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "stderr:\n{}", stderr);
+    assert!(stderr.contains("broken.bir: error[E0201]"), "stderr:\n{}", stderr);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn optimization_levels_and_pass_lists() {
+    // Unoptimized code keeps `% 1`, a `while (0)` branch on a constant, and
+    // float operands of double arithmetic for codegen to handle
+    let src = "int main(int argc, char **argv) {\n    int r = argc % 1;\n    do { r += 40; } while (0);\n    float f = 1.5f;\n    double d = 0.5;\n    return r + (int)(f + d);\n}\n";
+    let (dir, path) = scratch_source("opt_levels", src);
+    let driver = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver")
+    };
+
+    for level in ["-O0", "-O1", "-O2", "-O3"] {
+        let output = driver(&[level, "--run"]);
+        assert_eq!(output.status.code(), Some(42), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+    }
+
+    let ir_text = |args: &[&str]| {
+        let output = driver(&[args, &["-S", "--emit-ir"]].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        fs::read_to_string(dir.join("opt_levels.ir")).unwrap()
+    };
+    assert!(ir_text(&["-O0"]).contains("alloca"));
+    assert!(!ir_text(&["-O1"]).contains("alloca"));
+    let promoted = ir_text(&["--passes=sroa,mem2reg"]);
+    assert!(!promoted.contains("alloca") && promoted.contains("mod"), "{}", promoted);
+
+    let output = driver(&["--passes=mem2reg,hoist", "-S"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown pass 'hoist'"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn division_by_constants_runs_natively() {
+    // The dividends depend on argc, so the divisions happen at run time.
+    // -O0 keeps `idiv`, which is signed even for `unsigned` operands
+    let src = "int sdiv7(int x) { return x / 7; }\n\
+               int smod7(int x) { return x % 7; }\n\
+               int sdivn10(int x) { return x / -10; }\n\
+               int smodn10(int x) { return x % -10; }\n\
+               int sdiv8(int x) { return x / 8; }\n\
+               int smod8(int x) { return x % 8; }\n\
+               unsigned udiv7(unsigned x) { return x / 7u; }\n\
+               unsigned umod7(unsigned x) { return x % 7u; }\n\
+               unsigned udiv10(unsigned x) { return x / 10u; }\n\
+               unsigned umod16(unsigned x) { return x % 16u; }\n\
+               int main(int argc, char **argv) {\n\
+                   int n = argc - 1;\n\
+                   int bad = (sdiv7(-100 - n) != -14) + (smod7(-100 - n) != -2);\n\
+                   bad += (sdivn10(-2147483647 - 1 + n) != 214748364) + (smodn10(-2147483647 - 1 + n) != -8);\n\
+                   bad += (sdiv8(-15 + n) != -1) + (smod8(-15 + n) != -7);\n\
+                   bad += (udiv7(4000000000u + n) != 571428571u) + (umod7(4000000000u + n) != 3u);\n\
+                   bad += (udiv10(4294967295u - n) != 429496729u) + (umod16(4294967295u - n) != 15u);\n\
+                   return bad;\n\
+               }\n";
+    let (dir, path) = scratch_source("const_div", src);
+    for level in ["-O1", "-O2", "-O3"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn mixed_int_and_double_arguments_run_natively() {
+    // Doubles come from globals, and each callee takes more arguments of
+    // one class than fit in registers. printf reads AL for the number of
+    // vector registers used; sum walks its register save area with va_arg
+    let src = "#include <stdarg.h>\n\
+               int printf(const char *fmt, ...);\n\
+               double half = 0.5, two = 2.0, four = 4.0;\n\
+               double mix(int a, double b, int c, double d, int e, int f, int g, int h, double i) {\n\
+                   return b * 10.0 + d * 1000.0 + i + (double)(a + c * 100 + e + f + g + h * 2);\n\
+               }\n\
+               double many(double a, double b, double c, double d, double e, double f, double g, double h, double i, long j) {\n\
+                   return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8 + i * 9 + (double)j;\n\
+               }\n\
+               int sum(int n, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, n);\n\
+                   int s = 0;\n\
+                   for (int i = 0; i < n; i++) s = s * 10 + va_arg(ap, int);\n\
+                   va_end(ap);\n\
+                   return s;\n\
+               }\n\
+               int main() {\n\
+                   printf(\"%d %f %d %f\\n\", 1, two, 3, four);\n\
+                   printf(\"%f\\n\", mix(1, two, 3, four, 5, 6, 7, 8, half));\n\
+                   printf(\"%f\\n\", many(half, half, half, half, half, half, half, half, two, 100));\n\
+                   printf(\"%d\\n\", sum(3, 1, 2, 3));\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("mixed_args", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1 2.000000 3 4.000000\n4355.500000\n136.000000\n123\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn va_arg_reads_doubles_and_stack_arguments_natively() {
+    // Doubles come from the XMM part of the register save area, and the
+    // arguments past the registers from the stack; copies advance on their
+    // own, and vsnprintf takes the va_list as it is
+    let src = "#include <stdarg.h>\n\
+               int printf(const char *fmt, ...);\n\
+               int vsnprintf(char *s, unsigned long n, const char *fmt, va_list ap);\n\
+               __attribute__((noinline)) double first(int n, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, n);\n\
+                   double d = va_arg(ap, double);\n\
+                   va_end(ap);\n\
+                   return d;\n\
+               }\n\
+               __attribute__((noinline)) double avg(int n, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, n);\n\
+                   double acc = 0;\n\
+                   for (int i = 0; i < n; i++) acc += va_arg(ap, double);\n\
+                   va_end(ap);\n\
+                   return acc / n;\n\
+               }\n\
+               __attribute__((noinline)) long total(int n, ...) {\n\
+                   va_list ap, again;\n\
+                   va_start(ap, n);\n\
+                   va_copy(again, ap);\n\
+                   long t = 0;\n\
+                   for (int i = 0; i < n; i++) t = t * 10 + va_arg(ap, int);\n\
+                   for (int i = 0; i < n; i++) t += va_arg(again, int);\n\
+                   va_end(again);\n\
+                   va_end(ap);\n\
+                   return t;\n\
+               }\n\
+               __attribute__((noinline)) double mixed(int n, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, n);\n\
+                   double t = 0;\n\
+                   for (int i = 0; i < n; i++) { t += va_arg(ap, int); t += va_arg(ap, double); }\n\
+                   va_end(ap);\n\
+                   return t;\n\
+               }\n\
+               __attribute__((noinline)) void format(char *buf, const char *fmt, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, fmt);\n\
+                   vsnprintf(buf, 64, fmt, ap);\n\
+                   va_end(ap);\n\
+               }\n\
+               int main(void) {\n\
+                   char buf[64];\n\
+                   printf(\"%g %g\\n\", first(1, 3.5), avg(4, 1.0, 2.0, 3.0, 4.0));\n\
+                   printf(\"%g\\n\", avg(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0));\n\
+                   printf(\"%ld\\n\", total(8, 1, 2, 3, 4, 5, 6, 7, 8));\n\
+                   printf(\"%g\\n\", mixed(9, 1, 0.5, 2, 0.5, 3, 0.5, 4, 0.5, 5, 0.5, 6, 0.5, 7, 0.5, 8, 0.5, 9, 0.5));\n\
+                   format(buf, \"%d %.1f %s\", 7, 2.5, \"ok\");\n\
+                   printf(\"%s\\n\", buf);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("va_arg_sysv", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3.5 2.5\n5.5\n12345714\n49.5\n7 2.5 ok\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn structs_pass_and_return_by_value_natively() {
+    // Compound literals, array elements, dereferences, `?:`, and call
+    // results as arguments and return values of functions kept out of line
+    let src = "int printf(const char *fmt, ...);\n\
+               struct B { long a, b, c; };\n\
+               struct D { double x, y; };\n\
+               struct S { int a; char b; };\n\
+               __attribute__((noinline)) struct B bump(struct B b) { b.a += 1; return b; }\n\
+               __attribute__((noinline)) struct D swapd(struct D d) { return (struct D){ d.y, d.x }; }\n\
+               __attribute__((noinline)) int small(struct S s) { return s.a * 10 + s.b; }\n\
+               __attribute__((noinline)) struct S pick(int k) { return k ? (struct S){ k, 1 } : (struct S){ 0, 2 }; }\n\
+               int main() {\n\
+                   struct B arr[2] = { { 1, 2, 3 }, { 4, 5, 6 } };\n\
+                   struct B *p = arr;\n\
+                   int i = 1;\n\
+                   struct D d = swapd((struct D){ 1.5, 2.5 });\n\
+                   printf(\"%ld %ld %ld\\n\", bump((struct B){ 4, 5, 6 }).a, bump(arr[i]).b, bump(*p).c);\n\
+                   printf(\"%f %f %d\\n\", d.x, d.y, small((struct S){ 3, 4 }));\n\
+                   printf(\"%d %d %ld\\n\", small(pick(5)), pick(0).b, bump(bump(i ? arr[0] : arr[1])).a);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("struct_values", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "5 5 3\n2.500000 1.500000 34\n51 2 3\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sizeof_string_literals_counts_encoded_units() {
+    let src = "int printf(const char *fmt, ...);\n\
+               static const unsigned long g = sizeof(U\"abc\") + sizeof(u\"\\U0001F600\");\n\
+               int main(void) {\n\
+                   unsigned long w = sizeof L\"ab\", h = sizeof(u\"ab\"), e = sizeof(\"\u{e9}\");\n\
+                   switch (w) { case sizeof(U\"ab\"): break; default: return 1; }\n\
+                   printf(\"%lu %lu %lu %lu\\n\", w, h, e, g);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("sizeof_strings", src);
+    for level in ["-O0", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "12 6 3 22\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn signed_bit_fields_read_sign_extended() {
+    let src = "int printf(const char *fmt, ...);\n\
+               struct S { signed b:5; unsigned u:5; int c:7; char d:3; long long e:40; };\n\
+               int main(void) {\n\
+                   struct S s = {0}; struct S *p = &s;\n\
+                   s.b = -3; s.u = 29; p->c = -64; s.d = -2; s.e = -5000000000LL;\n\
+                   printf(\"%d %u %d %d %lld\\n\", s.b, s.u, p->c, s.d, s.e);\n\
+                   long x = s.b;\n\
+                   printf(\"%ld\\n\", x - 100);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("signed_bit_fields", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "-3 29 -64 -2 -5000000000\n-103\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn variadic_arguments_get_the_default_promotions() {
+    let src = "int printf(const char *fmt, ...);\n\
+               int main(void) {\n\
+                   float f = 1.5f; char c = -3; unsigned short us = 65535; short s = -7;\n\
+                   printf(\"%f %d %d %d\\n\", f, c, us, s);\n\
+                   printf(\"%f %f\\n\", f * 2, (float)3);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("vararg_promotion", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1.500000 -3 65535 -7\n3.000000 3.000000\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn mixed_integer_operands_convert_to_their_common_type() {
+    let src = "int printf(const char *fmt, ...);\n\
+               unsigned g = 4000000000u;\n\
+               __attribute__((noinline)) unsigned long add(unsigned long a, unsigned b) { return a + b; }\n\
+               __attribute__((noinline)) long addp(long a, unsigned *p) { return a + *p; }\n\
+               int main(void) {\n\
+                   int m = -1; unsigned one = 1; unsigned char uc = 200;\n\
+                   printf(\"%d %d %d\\n\", -1 < 1u, m < one, m < 1L);\n\
+                   printf(\"%lu %ld\\n\", add(1, 4000000000u), addp(1, &g));\n\
+                   long l = 10; l += g; unsigned long ul = 5; ul += m; uc += 100;\n\
+                   int k = -8; k /= 2u;\n\
+                   printf(\"%ld %lu %d %d %u\\n\", l, ul, uc, k, (unsigned)m >> 28);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("common_type", src);
+    let expected = "0 0 1\n4000000001 4000000001\n4000000010 4 44 2147483644 15\n";
+    for level in ["-O0", "-O1", "-O2", "-O3"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn unsigned_long_division_by_constants_runs_natively() {
+    // x / 7 needs a 65-bit multiplier; 2^64 - 2 would go negative in imul
+    let src = "int printf(const char *fmt, ...);\n\
+               unsigned long xs[] = { 0xFFFFFFFFFFFFFFFEul, 0x8000000000000001ul, 99 };\n\
+               int main(void) {\n\
+                   for (int i = 0; i < 3; i++) {\n\
+                       unsigned long x = xs[i];\n\
+                       printf(\"%lu %lu %lu %lu %lu\\n\", x / 7, x % 7, x / 10, x % 8, x / 1000000007ul);\n\
+                   }\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("udiv_const", src);
+    let expected = "2635249153387078802 0 1844674407370955161 6 18446743944\n\
+                    1317624576693539401 2 922337203685477580 1 9223371972\n\
+                    14 1 9 3 0\n";
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn float_loops_run_natively() {
+    // fib and scale keep their loop values in XMM registers, and fib's
+    // phis swap them; around's x lives across a call, which clobbers them
+    let src = "int printf(const char *fmt, ...);\n\
+               double one = 1.0, two = 2.0;\n\
+               float quarter = 0.25f;\n\
+               double fib(double a, double b, int n) {\n\
+                   for (int i = 0; i < n; i++) { double t = a + b; a = b; b = t; }\n\
+                   return b;\n\
+               }\n\
+               float scale(float x, int n) {\n\
+                   float s = 0.0f;\n\
+                   for (int i = 0; i < n; i++) s = s + x * (float)i;\n\
+                   return s;\n\
+               }\n\
+               double around(double x) {\n\
+                   double y = x * 3.0;\n\
+                   printf(\"%f\\n\", y);\n\
+                   return y - x;\n\
+               }\n\
+               int main() {\n\
+                   printf(\"%f\\n\", fib(one, two, 10));\n\
+                   printf(\"%f\\n\", (double)scale(quarter, 8));\n\
+                   printf(\"%f\\n\", around(two));\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("float_loops", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "233.000000\n7.000000\n6.000000\n4.000000\n", "{}", level);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn doubles_keep_full_precision_natively() {
+    // sqrtf is known only by its prototype, so once its argument folds to a
+    // constant, codegen must still pass it as a float
+    let src = "int printf(const char *fmt, ...);\n\
+               double sqrt(double x);\n\
+               float sqrtf(float x);\n\
+               double third(int n) { return n / 3.0; }\n\
+               float half(float x) { return x * 0.5f; }\n\
+               double mix(float f, double d, int i) { return f + d * i; }\n\
+               int main() {\n\
+                   double t = third(1);\n\
+                   float f = 3;\n\
+                   f -= 0.25;\n\
+                   printf(\"%.15f\\n\", t);\n\
+                   printf(\"%.15f %.7f\\n\", sqrt(2.0), (double)sqrtf(2.0f));\n\
+                   printf(\"%.3f %.3f\\n\", (double)half(5.0f), mix(1.5f, 0.1, 3));\n\
+                   printf(\"%.2f %.17g\\n\", (double)f, 1e300 * 10 / 1e290);\n\
+                   return 0;\n\
+               }\n";
+    let (dir, path) = scratch_source("doubles", src);
+    for level in ["-O0", "-O1", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .arg(&path)
+            .args([level, "-l", "m", "--run"])
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to run driver");
+        assert_eq!(output.status.code(), Some(0), "{}: {}", level, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0.333333333333333\n1.414213562373095 1.4142135\n2.500 1.800\n2.75 100000000000\n",
+            "{}",
+            level
+        );
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,1222 @@
+// Binary IR
+//
+// A compact serialized form of a whole `IRProgram`, written per translation
+// unit by `driver -c --flto` (a `.bir` file) and read back to link the
+// units (link.rs), optimize across them, and generate code once.
+//
+// Unlike the text of printer.rs, the encoding is lossless: variable types,
+// spans, labels, local variable names, and initializer expressions all
+// round-trip. Layout: the magic `CIR`, a version byte, a table of every
+// string the program uses, then the program, starting with the target's
+// data layout, which names strings by their index in the table. Integers are LEB128 (signed ones
+// zigzag-encoded first), floats their raw bits, and enums a one-byte tag.
+
+use std::collections::HashMap;
+
+use model::{
+    Attribute, BinaryOp, CompileError, DataLayout, Designator, EnumDef, ErrorCode, Expr, FileId, GlobalVar, InitItem, Span,
+    StringEncoding, StructDef, StructField, Type, TypeQualifiers, UnaryOp, UnionDef,
+};
+
+use crate::types::{
+    BasicBlock, BlockId, BranchHint, Declaration, Function, IRProgram, Instruction, Operand, SimdOp, Terminator, VarId,
+};
+
+const MAGIC: &[u8; 3] = b"CIR";
+/// Bumped whenever the encoding changes; older files are rejected.
+const VERSION: u8 = 6;
+
+const SCALAR_TYPES: [Type; 14] = [
+    Type::Int,
+    Type::UnsignedInt,
+    Type::Char,
+    Type::UnsignedChar,
+    Type::Short,
+    Type::UnsignedShort,
+    Type::Long,
+    Type::UnsignedLong,
+    Type::LongLong,
+    Type::UnsignedLongLong,
+    Type::Void,
+    Type::Float,
+    Type::Double,
+    Type::Bool,
+];
+
+const BINARY_OPS: [BinaryOp; 29] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::Mod,
+    BinaryOp::EqualEqual,
+    BinaryOp::NotEqual,
+    BinaryOp::Less,
+    BinaryOp::LessEqual,
+    BinaryOp::Greater,
+    BinaryOp::GreaterEqual,
+    BinaryOp::LogicalAnd,
+    BinaryOp::LogicalOr,
+    BinaryOp::BitwiseAnd,
+    BinaryOp::BitwiseOr,
+    BinaryOp::BitwiseXor,
+    BinaryOp::ShiftLeft,
+    BinaryOp::ShiftRight,
+    BinaryOp::Assign,
+    BinaryOp::AddAssign,
+    BinaryOp::SubAssign,
+    BinaryOp::MulAssign,
+    BinaryOp::DivAssign,
+    BinaryOp::ModAssign,
+    BinaryOp::BitwiseAndAssign,
+    BinaryOp::BitwiseOrAssign,
+    BinaryOp::BitwiseXorAssign,
+    BinaryOp::ShiftLeftAssign,
+    BinaryOp::ShiftRightAssign,
+];
+
+const UNARY_OPS: [UnaryOp; 6] =
+    [UnaryOp::Plus, UnaryOp::Minus, UnaryOp::LogicalNot, UnaryOp::BitwiseNot, UnaryOp::AddrOf, UnaryOp::Deref];
+
+const SIMD_OPS: [SimdOp; 15] = [
+    SimdOp::Load,
+    SimdOp::Store,
+    SimdOp::Add,
+    SimdOp::Sub,
+    SimdOp::Mul,
+    SimdOp::And,
+    SimdOp::Or,
+    SimdOp::Xor,
+    SimdOp::HorizontalAdd,
+    SimdOp::Splat,
+    SimdOp::LaneMask,
+    SimdOp::Blend,
+    SimdOp::IndexSeq,
+    SimdOp::Gather,
+    SimdOp::Scatter,
+];
+
+const ENCODINGS: [StringEncoding; 5] = [
+    StringEncoding::Plain,
+    StringEncoding::Utf8,
+    StringEncoding::Wide,
+    StringEncoding::Utf16,
+    StringEncoding::Utf32,
+];
+
+const HINTS: [BranchHint; 3] = [BranchHint::None, BranchHint::LikelyThen, BranchHint::LikelyElse];
+
+/// Serialize `program`. Fails only for a global initializer holding a
+/// statement expression, which cannot be a constant and is not encoded.
+pub fn program_to_bytes(program: &IRProgram) -> Result<Vec<u8>, CompileError> {
+    let mut w = Writer::default();
+    w.data_layout(&program.data_layout);
+    w.list(&program.structs, Writer::struct_def);
+    w.list(&program.unions, Writer::union_def);
+    w.list(&program.enums, Writer::enum_def);
+    w.list(&program.global_strings, |w, (label, content)| {
+        w.str(label);
+        w.str(content);
+    });
+    w.list(&program.globals, Writer::global);
+    w.list(&program.declarations, Writer::declaration);
+    w.list(&program.functions, Writer::function);
+    if let Some(error) = w.error {
+        return Err(error);
+    }
+
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    let body = std::mem::take(&mut w.out);
+    w.uint(w.strings.len() as u64);
+    for s in std::mem::take(&mut w.strings) {
+        w.uint(s.len() as u64);
+        w.out.extend_from_slice(s.as_bytes());
+    }
+    out.append(&mut w.out);
+    out.extend(body);
+    Ok(out)
+}
+
+/// Read a program written by `program_to_bytes`.
+pub fn program_from_bytes(bytes: &[u8]) -> Result<IRProgram, CompileError> {
+    let mut r = Reader { data: bytes, pos: 0, strings: Vec::new() };
+    if !bytes.starts_with(MAGIC) {
+        return Err(r.error("not a binary IR file"));
+    }
+    r.pos = MAGIC.len();
+    let version = r.byte()?;
+    if version != VERSION {
+        return Err(r.error(format!("unsupported binary IR version {} (expected {})", version, VERSION)));
+    }
+    r.strings = r.list(|r| {
+        let len = r.len()?;
+        let end = r.pos.checked_add(len).filter(|&end| end <= r.data.len()).ok_or_else(|| r.error("truncated string"))?;
+        let s = std::str::from_utf8(&r.data[r.pos..end]).map_err(|_| r.error("string is not UTF-8"))?.to_string();
+        r.pos = end;
+        Ok(s)
+    })?;
+
+    let data_layout = r.data_layout()?;
+    let structs = r.list(Reader::struct_def)?;
+    let unions = r.list(Reader::union_def)?;
+    let enums = r.list(Reader::enum_def)?;
+    let global_strings = r.list(|r| Ok((r.str()?, r.str()?)))?;
+    let globals = r.list(Reader::global)?;
+    let declarations = r.list(Reader::declaration)?;
+    let functions = r.list(Reader::function)?;
+    if r.pos != bytes.len() {
+        return Err(r.error("trailing bytes after the program"));
+    }
+    Ok(IRProgram { functions, global_strings, globals, structs, unions, enums, declarations, data_layout })
+}
+
+fn index_of<T: PartialEq>(table: &[T], value: &T) -> u8 {
+    table.iter().position(|v| v == value).expect("every variant is in its table") as u8
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    strings: Vec<String>,
+    string_ids: HashMap<String, usize>,
+    /// First value that cannot be encoded; reported by `program_to_bytes`.
+    error: Option<CompileError>,
+}
+
+impl Writer {
+    fn byte(&mut self, b: u8) {
+        self.out.push(b);
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.byte(u8::from(b));
+    }
+
+    fn uint(&mut self, mut v: u64) {
+        loop {
+            let low = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                self.byte(low);
+                return;
+            }
+            self.byte(low | 0x80);
+        }
+    }
+
+    fn int(&mut self, v: i64) {
+        self.uint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn len(&mut self, v: usize) {
+        self.uint(v as u64);
+    }
+
+    fn float(&mut self, f: f64) {
+        self.out.extend_from_slice(&f.to_bits().to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        let id = match self.string_ids.get(s) {
+            Some(&id) => id,
+            None => {
+                self.strings.push(s.to_string());
+                self.string_ids.insert(s.to_string(), self.strings.len() - 1);
+                self.strings.len() - 1
+            }
+        };
+        self.len(id);
+    }
+
+    fn list<T>(&mut self, items: &[T], mut each: impl FnMut(&mut Self, &T)) {
+        self.len(items.len());
+        for item in items {
+            each(self, item);
+        }
+    }
+
+    fn option<T>(&mut self, value: Option<&T>, each: impl FnOnce(&mut Self, &T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            each(self, value);
+        }
+    }
+
+    fn span(&mut self, span: &Span) {
+        self.uint(u64::from(span.file_id.0));
+        self.len(span.start);
+        self.len(span.end);
+    }
+
+    fn qualifiers(&mut self, q: &TypeQualifiers) {
+        self.byte(u8::from(q.is_const) | u8::from(q.is_volatile) << 1 | u8::from(q.is_restrict) << 2);
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        if let Some(index) = SCALAR_TYPES.iter().position(|t| t == ty) {
+            self.byte(index as u8);
+            return;
+        }
+        let tag = SCALAR_TYPES.len() as u8;
+        match ty {
+            Type::Array(elem, len) => {
+                self.byte(tag);
+                self.ty(elem);
+                self.len(*len);
+            }
+            Type::Pointer(inner, q) => {
+                self.byte(tag + 1);
+                self.ty(inner);
+                self.qualifiers(q);
+            }
+            Type::Struct(name) => {
+                self.byte(tag + 2);
+                self.str(name);
+            }
+            Type::Union(name) => {
+                self.byte(tag + 3);
+                self.str(name);
+            }
+            Type::Typedef(name) => {
+                self.byte(tag + 4);
+                self.str(name);
+            }
+            Type::FunctionPointer { return_type, param_types, is_variadic } => {
+                self.byte(tag + 5);
+                self.ty(return_type);
+                self.list(param_types, Self::ty);
+                self.bool(*is_variadic);
+            }
+            Type::Enum(name) => {
+                self.byte(tag + 6);
+                self.str(name);
+            }
+            Type::TypeofExpr(expr) => {
+                self.byte(tag + 7);
+                self.expr(expr);
+            }
+            Type::Complex(elem) => {
+                self.byte(tag + 8);
+                self.ty(elem);
+            }
+            _ => unreachable!("scalar types are in SCALAR_TYPES"),
+        }
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        match attribute {
+            Attribute::Packed => self.byte(0),
+            Attribute::Pack(n) => {
+                self.byte(1);
+                self.len(*n);
+            }
+            Attribute::Aligned(n) => {
+                self.byte(2);
+                self.len(*n);
+            }
+            Attribute::Section(name) => {
+                self.byte(3);
+                self.str(name);
+            }
+            Attribute::NoReturn => self.byte(4),
+            Attribute::AlwaysInline => self.byte(5),
+            Attribute::Weak => self.byte(6),
+            Attribute::Unused => self.byte(7),
+            Attribute::Constructor => self.byte(8),
+            Attribute::Destructor => self.byte(9),
+        }
+    }
+
+    fn field(&mut self, field: &StructField) {
+        self.ty(&field.field_type);
+        self.str(&field.name);
+        self.option(field.bit_width.as_ref(), |w, n| w.len(*n));
+    }
+
+    fn struct_def(&mut self, def: &StructDef) {
+        self.str(&def.name);
+        self.list(&def.fields, Self::field);
+        self.list(&def.attributes, Self::attribute);
+    }
+
+    fn union_def(&mut self, def: &UnionDef) {
+        self.str(&def.name);
+        self.list(&def.fields, Self::field);
+    }
+
+    fn data_layout(&mut self, data: &DataLayout) {
+        for size in [
+            data.pointer_size,
+            data.pointer_align,
+            data.short_size,
+            data.int_size,
+            data.long_size,
+            data.long_long_size,
+            data.long_long_align,
+            data.float_size,
+            data.double_size,
+            data.double_align,
+        ] {
+            self.len(size);
+        }
+    }
+
+    fn enum_def(&mut self, def: &EnumDef) {
+        self.str(&def.name);
+        self.list(&def.constants, |w, (name, value)| {
+            w.str(name);
+            w.int(*value);
+        });
+        self.ty(&def.underlying);
+    }
+
+    fn declaration(&mut self, decl: &Declaration) {
+        self.str(&decl.name);
+        self.ty(&decl.return_type);
+        self.list(&decl.params, Writer::ty);
+        self.bool(decl.is_variadic);
+    }
+
+    fn global(&mut self, global: &GlobalVar) {
+        self.ty(&global.r#type);
+        self.qualifiers(&global.qualifiers);
+        self.str(&global.name);
+        self.option(global.init.as_ref(), Self::expr);
+        self.list(&global.attributes, Self::attribute);
+        self.bool(global.is_extern);
+        self.bool(global.is_static);
+        self.span(&global.span);
+    }
+
+    fn init_item(&mut self, item: &InitItem) {
+        match &item.designator {
+            None => self.byte(0),
+            Some(Designator::Field(name)) => {
+                self.byte(1);
+                self.str(name);
+            }
+            Some(Designator::Index(i)) => {
+                self.byte(2);
+                self.int(*i);
+            }
+            Some(Designator::Range { start, end }) => {
+                self.byte(3);
+                self.int(*start);
+                self.int(*end);
+            }
+        }
+        self.expr(&item.value);
+    }
+
+    /// A global initializer: a constant expression, possibly inside
+    /// `sizeof` or `typeof`, so every kind of expression but a statement
+    /// expression can occur.
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Binary { left, op, right } => {
+                self.byte(0);
+                self.expr(left);
+                self.byte(index_of(&BINARY_OPS, op));
+                self.expr(right);
+            }
+            Expr::Unary { op, expr } => {
+                self.byte(1);
+                self.byte(index_of(&UNARY_OPS, op));
+                self.expr(expr);
+            }
+            Expr::PostfixIncrement(e) => {
+                self.byte(2);
+                self.expr(e);
+            }
+            Expr::PostfixDecrement(e) => {
+                self.byte(3);
+                self.expr(e);
+            }
+            Expr::PrefixIncrement(e) => {
+                self.byte(4);
+                self.expr(e);
+            }
+            Expr::PrefixDecrement(e) => {
+                self.byte(5);
+                self.expr(e);
+            }
+            Expr::Variable(name) => {
+                self.byte(6);
+                self.str(name);
+            }
+            Expr::Constant(v) => {
+                self.byte(7);
+                self.int(*v);
+            }
+            Expr::TypedConstant(v, ty) => {
+                self.byte(8);
+                self.int(*v);
+                self.ty(ty);
+            }
+            Expr::FloatConstant(f) => {
+                self.byte(9);
+                self.float(*f);
+            }
+            Expr::ImaginaryConstant(f) => {
+                self.byte(10);
+                self.float(*f);
+            }
+            Expr::StringLiteral(s) => {
+                self.byte(11);
+                self.str(s);
+            }
+            Expr::WideStringLiteral(s, encoding) => {
+                self.byte(12);
+                self.str(s);
+                self.byte(index_of(&ENCODINGS, encoding));
+            }
+            Expr::Index { array, index } => {
+                self.byte(13);
+                self.expr(array);
+                self.expr(index);
+            }
+            Expr::Call { func, args } => {
+                self.byte(14);
+                self.expr(func);
+                self.list(args, Self::expr);
+            }
+            Expr::SizeOf(ty) => {
+                self.byte(15);
+                self.ty(ty);
+            }
+            Expr::SizeOfExpr(e) => {
+                self.byte(16);
+                self.expr(e);
+            }
+            Expr::AlignOf(ty) => {
+                self.byte(17);
+                self.ty(ty);
+            }
+            Expr::Cast(ty, e) => {
+                self.byte(18);
+                self.ty(ty);
+                self.expr(e);
+            }
+            Expr::Member { expr, member } => {
+                self.byte(19);
+                self.expr(expr);
+                self.str(member);
+            }
+            Expr::PtrMember { expr, member } => {
+                self.byte(20);
+                self.expr(expr);
+                self.str(member);
+            }
+            Expr::Conditional { condition, then_expr, else_expr } => {
+                self.byte(21);
+                self.expr(condition);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+            Expr::Comma(exprs) => {
+                self.byte(22);
+                self.list(exprs, Self::expr);
+            }
+            Expr::CompoundLiteral { r#type, init } => {
+                self.byte(23);
+                self.ty(r#type);
+                self.list(init, Self::init_item);
+            }
+            Expr::InitList(items) => {
+                self.byte(24);
+                self.list(items, Self::init_item);
+            }
+            Expr::VaArg { list, r#type } => {
+                self.byte(25);
+                self.expr(list);
+                self.ty(r#type);
+            }
+            Expr::BuiltinOffsetof { r#type, member } => {
+                self.byte(26);
+                self.ty(r#type);
+                self.str(member);
+            }
+            Expr::Generic { controlling, associations } => {
+                self.byte(27);
+                self.expr(controlling);
+                self.list(associations, |w, (ty, e)| {
+                    w.option(ty.as_ref(), Self::ty);
+                    w.expr(e);
+                });
+            }
+            Expr::Expect { expr, expected } => {
+                self.byte(28);
+                self.expr(expr);
+                self.expr(expected);
+            }
+            Expr::LabelAddr(name) => {
+                self.byte(29);
+                self.str(name);
+            }
+            Expr::Real(e) => {
+                self.byte(30);
+                self.expr(e);
+            }
+            Expr::Imag(e) => {
+                self.byte(31);
+                self.expr(e);
+            }
+            Expr::StmtExpr(_) => {
+                self.error.get_or_insert_with(|| {
+                    CompileError::new(
+                        ErrorCode::Unsupported,
+                        "a statement expression in a global initializer cannot be written as binary IR",
+                    )
+                });
+                self.byte(7);
+                self.int(0);
+            }
+        }
+    }
+
+    fn operand(&mut self, op: &Operand) {
+        match op {
+            Operand::Constant(v) => {
+                self.byte(0);
+                self.int(*v);
+            }
+            Operand::FloatConstant(f) => {
+                self.byte(1);
+                self.float(*f);
+            }
+            Operand::Var(v) => {
+                self.byte(2);
+                self.len(v.0);
+            }
+            Operand::Global(name) => {
+                self.byte(3);
+                self.str(name);
+            }
+        }
+    }
+
+    fn var(&mut self, v: &VarId) {
+        self.len(v.0);
+    }
+
+    fn instruction(&mut self, inst: &Instruction) {
+        match inst {
+            Instruction::Binary { dest, op, left, right } | Instruction::FloatBinary { dest, op, left, right } => {
+                self.byte(if matches!(inst, Instruction::Binary { .. }) { 0 } else { 1 });
+                self.var(dest);
+                self.byte(index_of(&BINARY_OPS, op));
+                self.operand(left);
+                self.operand(right);
+            }
+            Instruction::Unary { dest, op, src } | Instruction::FloatUnary { dest, op, src } => {
+                self.byte(if matches!(inst, Instruction::Unary { .. }) { 2 } else { 3 });
+                self.var(dest);
+                self.byte(index_of(&UNARY_OPS, op));
+                self.operand(src);
+            }
+            Instruction::Phi { dest, preds } => {
+                self.byte(4);
+                self.var(dest);
+                self.list(preds, |w, (block, var)| {
+                    w.len(block.0);
+                    w.var(var);
+                });
+            }
+            Instruction::Copy { dest, src } => {
+                self.byte(5);
+                self.var(dest);
+                self.operand(src);
+            }
+            Instruction::Cast { dest, src, src_type, r#type } => {
+                self.byte(6);
+                self.var(dest);
+                self.operand(src);
+                self.ty(src_type);
+                self.ty(r#type);
+            }
+            Instruction::Alloca { dest, r#type } => {
+                self.byte(7);
+                self.var(dest);
+                self.ty(r#type);
+            }
+            Instruction::Load { dest, addr, value_type, volatile } => {
+                self.byte(8);
+                self.var(dest);
+                self.operand(addr);
+                self.ty(value_type);
+                self.bool(*volatile);
+            }
+            Instruction::Store { addr, src, value_type, volatile } => {
+                self.byte(9);
+                self.operand(addr);
+                self.operand(src);
+                self.ty(value_type);
+                self.bool(*volatile);
+            }
+            Instruction::GetElementPtr { dest, base, index, element_type } => {
+                self.byte(10);
+                self.var(dest);
+                self.operand(base);
+                self.operand(index);
+                self.ty(element_type);
+            }
+            Instruction::Call { dest, name, args, vararg_types } => {
+                self.byte(11);
+                self.option(dest.as_ref(), Self::var);
+                self.str(name);
+                self.list(args, Self::operand);
+                self.list(vararg_types, Self::ty);
+            }
+            Instruction::IndirectCall { dest, func_ptr, args, is_variadic, vararg_types } => {
+                self.byte(12);
+                self.option(dest.as_ref(), Self::var);
+                self.operand(func_ptr);
+                self.list(args, Self::operand);
+                self.bool(*is_variadic);
+                self.list(vararg_types, Self::ty);
+            }
+            Instruction::VaStart { list, arg_index } => {
+                self.byte(13);
+                self.operand(list);
+                self.len(*arg_index);
+            }
+            Instruction::VaEnd { list } => {
+                self.byte(14);
+                self.operand(list);
+            }
+            Instruction::VaCopy { dest, src } => {
+                self.byte(15);
+                self.operand(dest);
+                self.operand(src);
+            }
+            Instruction::VaArg { dest, list, r#type } => {
+                self.byte(16);
+                self.var(dest);
+                self.operand(list);
+                self.ty(r#type);
+            }
+            Instruction::InlineAsm {
+                template,
+                outputs,
+                inputs,
+                output_constraints,
+                input_constraints,
+                clobbers,
+                is_volatile,
+            } => {
+                self.byte(17);
+                self.str(template);
+                self.list(outputs, Self::var);
+                self.list(inputs, Self::operand);
+                self.list(output_constraints, |w, s| w.str(s));
+                self.list(input_constraints, |w, s| w.str(s));
+                self.list(clobbers, |w, s| w.str(s));
+                self.bool(*is_volatile);
+            }
+            Instruction::Memcpy { dest, src, size } => {
+                self.byte(18);
+                self.operand(dest);
+                self.operand(src);
+                self.len(*size);
+            }
+            Instruction::Memset { dest, value, size } => {
+                self.byte(19);
+                self.operand(dest);
+                self.operand(value);
+                self.len(*size);
+            }
+            Instruction::Simd { op, dest, operands, elem_type, width } => {
+                self.byte(20);
+                self.byte(index_of(&SIMD_OPS, op));
+                self.option(dest.as_ref(), Self::var);
+                self.list(operands, Self::operand);
+                self.ty(elem_type);
+                self.len(*width);
+            }
+        }
+    }
+
+    fn terminator(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Br(target) => {
+                self.byte(0);
+                self.len(target.0);
+            }
+            Terminator::CondBr { cond, then_block, else_block, hint } => {
+                self.byte(1);
+                self.operand(cond);
+                self.len(then_block.0);
+                self.len(else_block.0);
+                self.byte(index_of(&HINTS, hint));
+            }
+            Terminator::Ret(value) => {
+                self.byte(2);
+                self.option(value.as_ref(), Self::operand);
+            }
+            Terminator::Unreachable => self.byte(3),
+            Terminator::IndirectBr { target } => {
+                self.byte(4);
+                self.operand(target);
+            }
+            Terminator::Switch { value, cases, default } => {
+                self.byte(5);
+                self.operand(value);
+                self.list(cases, |w, (case, block)| {
+                    w.int(*case);
+                    w.len(block.0);
+                });
+                self.len(default.0);
+            }
+        }
+    }
+
+    fn block(&mut self, block: &BasicBlock) {
+        self.len(block.id.0);
+        self.list(&block.instructions, Self::instruction);
+        self.terminator(&block.terminator);
+        self.bool(block.is_label_target);
+        self.list(&block.spans, Self::span);
+        self.span(&block.terminator_span);
+    }
+
+    fn function(&mut self, func: &Function) {
+        self.str(&func.name);
+        self.ty(&func.return_type);
+        self.list(&func.params, |w, (ty, var)| {
+            w.ty(ty);
+            w.var(var);
+        });
+        self.list(&func.blocks, Self::block);
+        self.len(func.entry_block.0);
+        // Sorted, so the same program always encodes to the same bytes
+        let mut var_types: Vec<_> = func.var_types.iter().collect();
+        var_types.sort_by_key(|(var, _)| **var);
+        self.list(&var_types, |w, (var, ty)| {
+            w.var(var);
+            w.ty(ty);
+        });
+        self.list(&func.attributes, Self::attribute);
+        self.bool(func.is_static);
+        self.list(&func.label_addrs, |w, s| w.str(s));
+        let mut labels: Vec<_> = func.labels.iter().collect();
+        labels.sort();
+        self.list(&labels, |w, (name, block)| {
+            w.str(name);
+            w.len(block.0);
+        });
+        let mut local_names: Vec<_> = func.local_names.iter().collect();
+        local_names.sort_by_key(|(var, _)| **var);
+        self.list(&local_names, |w, (var, name)| {
+            w.var(var);
+            w.str(name);
+        });
+        self.span(&func.span);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    strings: Vec<String>,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> CompileError {
+        CompileError::new(ErrorCode::Syntax, format!("binary IR byte {}: {}", self.pos, message.into()))
+    }
+
+    fn byte(&mut self) -> Result<u8, CompileError> {
+        let b = *self.data.get(self.pos).ok_or_else(|| self.error("unexpected end of data"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bool(&mut self) -> Result<bool, CompileError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(self.bad_tag("boolean", b)),
+        }
+    }
+
+    fn uint(&mut self) -> Result<u64, CompileError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 || (shift == 63 && b > 1) {
+                return Err(self.error("integer too large"));
+            }
+            value |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, CompileError> {
+        let v = self.uint()?;
+        Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+    }
+
+    fn len(&mut self) -> Result<usize, CompileError> {
+        let v = self.uint()?;
+        usize::try_from(v).map_err(|_| self.error("length too large"))
+    }
+
+    fn float(&mut self) -> Result<f64, CompileError> {
+        let bytes = self.data.get(self.pos..self.pos + 8).ok_or_else(|| self.error("unexpected end of data"))?;
+        self.pos += 8;
+        Ok(f64::from_bits(u64::from_le_bytes(bytes.try_into().unwrap())))
+    }
+
+    fn str(&mut self) -> Result<String, CompileError> {
+        let id = self.len()?;
+        self.strings.get(id).cloned().ok_or_else(|| self.error(format!("string {} is not in the table", id)))
+    }
+
+    fn bad_tag(&self, what: &str, tag: u8) -> CompileError {
+        CompileError::new(
+            ErrorCode::Syntax,
+            format!("binary IR byte {}: unknown {} tag {}", self.pos.saturating_sub(1), what, tag),
+        )
+    }
+
+    fn table_entry<T: Clone>(&mut self, table: &[T], what: &str) -> Result<T, CompileError> {
+        let tag = self.byte()?;
+        table.get(usize::from(tag)).cloned().ok_or_else(|| self.bad_tag(what, tag))
+    }
+
+    fn list<T>(&mut self, mut each: impl FnMut(&mut Self) -> Result<T, CompileError>) -> Result<Vec<T>, CompileError> {
+        let count = self.len()?;
+        // Every item takes at least one byte, so a corrupt count fails here
+        // rather than in a huge allocation
+        if count > self.data.len() - self.pos {
+            return Err(self.error("list longer than the data"));
+        }
+        (0..count).map(|_| each(self)).collect()
+    }
+
+    fn option<T>(&mut self, each: impl FnOnce(&mut Self) -> Result<T, CompileError>) -> Result<Option<T>, CompileError> {
+        if self.bool()? { each(self).map(Some) } else { Ok(None) }
+    }
+
+    fn span(&mut self) -> Result<Span, CompileError> {
+        let file_id = u32::try_from(self.uint()?).map_err(|_| self.error("file id too large"))?;
+        Ok(Span::new(FileId(file_id), self.len()?, self.len()?))
+    }
+
+    fn qualifiers(&mut self) -> Result<TypeQualifiers, CompileError> {
+        let bits = self.byte()?;
+        if bits > 0b111 {
+            return Err(self.bad_tag("qualifier", bits));
+        }
+        Ok(TypeQualifiers { is_const: bits & 1 != 0, is_volatile: bits & 2 != 0, is_restrict: bits & 4 != 0 })
+    }
+
+    fn ty(&mut self) -> Result<Type, CompileError> {
+        let tag = self.byte()?;
+        if let Some(ty) = SCALAR_TYPES.get(usize::from(tag)) {
+            return Ok(ty.clone());
+        }
+        Ok(match tag - SCALAR_TYPES.len() as u8 {
+            0 => Type::Array(Box::new(self.ty()?), self.len()?),
+            1 => Type::Pointer(Box::new(self.ty()?), self.qualifiers()?),
+            2 => Type::Struct(self.str()?),
+            3 => Type::Union(self.str()?),
+            4 => Type::Typedef(self.str()?),
+            5 => Type::FunctionPointer {
+                return_type: Box::new(self.ty()?),
+                param_types: self.list(Self::ty)?,
+                is_variadic: self.bool()?,
+            },
+            6 => Type::Enum(self.str()?),
+            7 => Type::TypeofExpr(Box::new(self.expr()?)),
+            8 => Type::Complex(Box::new(self.ty()?)),
+            _ => return Err(self.bad_tag("type", tag)),
+        })
+    }
+
+    fn attribute(&mut self) -> Result<Attribute, CompileError> {
+        Ok(match self.byte()? {
+            0 => Attribute::Packed,
+            1 => Attribute::Pack(self.len()?),
+            2 => Attribute::Aligned(self.len()?),
+            3 => Attribute::Section(self.str()?),
+            4 => Attribute::NoReturn,
+            5 => Attribute::AlwaysInline,
+            6 => Attribute::Weak,
+            7 => Attribute::Unused,
+            8 => Attribute::Constructor,
+            9 => Attribute::Destructor,
+            tag => return Err(self.bad_tag("attribute", tag)),
+        })
+    }
+
+    fn field(&mut self) -> Result<StructField, CompileError> {
+        Ok(StructField { field_type: self.ty()?, name: self.str()?, bit_width: self.option(Self::len)? })
+    }
+
+    fn struct_def(&mut self) -> Result<StructDef, CompileError> {
+        Ok(StructDef { name: self.str()?, fields: self.list(Self::field)?, attributes: self.list(Self::attribute)? })
+    }
+
+    fn union_def(&mut self) -> Result<UnionDef, CompileError> {
+        Ok(UnionDef { name: self.str()?, fields: self.list(Self::field)? })
+    }
+
+    fn data_layout(&mut self) -> Result<DataLayout, CompileError> {
+        Ok(DataLayout {
+            pointer_size: self.len()?,
+            pointer_align: self.len()?,
+            short_size: self.len()?,
+            int_size: self.len()?,
+            long_size: self.len()?,
+            long_long_size: self.len()?,
+            long_long_align: self.len()?,
+            float_size: self.len()?,
+            double_size: self.len()?,
+            double_align: self.len()?,
+        })
+    }
+
+    fn enum_def(&mut self) -> Result<EnumDef, CompileError> {
+        Ok(EnumDef {
+            name: self.str()?,
+            constants: self.list(|r| Ok((r.str()?, r.int()?)))?,
+            underlying: self.ty()?,
+        })
+    }
+
+    fn declaration(&mut self) -> Result<Declaration, CompileError> {
+        Ok(Declaration {
+            name: self.str()?,
+            return_type: self.ty()?,
+            params: self.list(Reader::ty)?,
+            is_variadic: self.bool()?,
+        })
+    }
+
+    fn global(&mut self) -> Result<GlobalVar, CompileError> {
+        Ok(GlobalVar {
+            r#type: self.ty()?,
+            qualifiers: self.qualifiers()?,
+            name: self.str()?,
+            init: self.option(Self::expr)?,
+            attributes: self.list(Self::attribute)?,
+            is_extern: self.bool()?,
+            is_static: self.bool()?,
+            span: self.span()?,
+        })
+    }
+
+    fn init_item(&mut self) -> Result<InitItem, CompileError> {
+        let designator = match self.byte()? {
+            0 => None,
+            1 => Some(Designator::Field(self.str()?)),
+            2 => Some(Designator::Index(self.int()?)),
+            3 => Some(Designator::Range { start: self.int()?, end: self.int()? }),
+            tag => return Err(self.bad_tag("designator", tag)),
+        };
+        Ok(InitItem { designator, value: self.expr()? })
+    }
+
+    fn boxed(&mut self) -> Result<Box<Expr>, CompileError> {
+        self.expr().map(Box::new)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        Ok(match self.byte()? {
+            0 => Expr::Binary { left: self.boxed()?, op: self.table_entry(&BINARY_OPS, "binary operator")?, right: self.boxed()? },
+            1 => Expr::Unary { op: self.table_entry(&UNARY_OPS, "unary operator")?, expr: self.boxed()? },
+            2 => Expr::PostfixIncrement(self.boxed()?),
+            3 => Expr::PostfixDecrement(self.boxed()?),
+            4 => Expr::PrefixIncrement(self.boxed()?),
+            5 => Expr::PrefixDecrement(self.boxed()?),
+            6 => Expr::Variable(self.str()?),
+            7 => Expr::Constant(self.int()?),
+            8 => Expr::TypedConstant(self.int()?, self.ty()?),
+            9 => Expr::FloatConstant(self.float()?),
+            10 => Expr::ImaginaryConstant(self.float()?),
+            11 => Expr::StringLiteral(self.str()?),
+            12 => Expr::WideStringLiteral(self.str()?, self.table_entry(&ENCODINGS, "string encoding")?),
+            13 => Expr::Index { array: self.boxed()?, index: self.boxed()? },
+            14 => Expr::Call { func: self.boxed()?, args: self.list(Self::expr)? },
+            15 => Expr::SizeOf(self.ty()?),
+            16 => Expr::SizeOfExpr(self.boxed()?),
+            17 => Expr::AlignOf(self.ty()?),
+            18 => Expr::Cast(self.ty()?, self.boxed()?),
+            19 => Expr::Member { expr: self.boxed()?, member: self.str()? },
+            20 => Expr::PtrMember { expr: self.boxed()?, member: self.str()? },
+            21 => Expr::Conditional { condition: self.boxed()?, then_expr: self.boxed()?, else_expr: self.boxed()? },
+            22 => Expr::Comma(self.list(Self::expr)?),
+            23 => Expr::CompoundLiteral { r#type: self.ty()?, init: self.list(Self::init_item)? },
+            24 => Expr::InitList(self.list(Self::init_item)?),
+            25 => Expr::VaArg { list: self.boxed()?, r#type: self.ty()? },
+            26 => Expr::BuiltinOffsetof { r#type: self.ty()?, member: self.str()? },
+            27 => Expr::Generic {
+                controlling: self.boxed()?,
+                associations: self.list(|r| Ok((r.option(Self::ty)?, r.expr()?)))?,
+            },
+            28 => Expr::Expect { expr: self.boxed()?, expected: self.boxed()? },
+            29 => Expr::LabelAddr(self.str()?),
+            30 => Expr::Real(self.boxed()?),
+            31 => Expr::Imag(self.boxed()?),
+            tag => return Err(self.bad_tag("expression", tag)),
+        })
+    }
+
+    fn var(&mut self) -> Result<VarId, CompileError> {
+        self.len().map(VarId)
+    }
+
+    fn block_id(&mut self) -> Result<BlockId, CompileError> {
+        self.len().map(BlockId)
+    }
+
+    fn operand(&mut self) -> Result<Operand, CompileError> {
+        Ok(match self.byte()? {
+            0 => Operand::Constant(self.int()?),
+            1 => Operand::FloatConstant(self.float()?),
+            2 => Operand::Var(self.var()?),
+            3 => Operand::Global(self.str()?),
+            tag => return Err(self.bad_tag("operand", tag)),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, CompileError> {
+        Ok(match self.byte()? {
+            0 => Instruction::Binary {
+                dest: self.var()?,
+                op: self.table_entry(&BINARY_OPS, "binary operator")?,
+                left: self.operand()?,
+                right: self.operand()?,
+            },
+            1 => Instruction::FloatBinary {
+                dest: self.var()?,
+                op: self.table_entry(&BINARY_OPS, "binary operator")?,
+                left: self.operand()?,
+                right: self.operand()?,
+            },
+            2 => Instruction::Unary {
+                dest: self.var()?,
+                op: self.table_entry(&UNARY_OPS, "unary operator")?,
+                src: self.operand()?,
+            },
+            3 => Instruction::FloatUnary {
+                dest: self.var()?,
+                op: self.table_entry(&UNARY_OPS, "unary operator")?,
+                src: self.operand()?,
+            },
+            4 => Instruction::Phi { dest: self.var()?, preds: self.list(|r| Ok((r.block_id()?, r.var()?)))? },
+            5 => Instruction::Copy { dest: self.var()?, src: self.operand()? },
+            6 => Instruction::Cast { dest: self.var()?, src: self.operand()?, src_type: self.ty()?, r#type: self.ty()? },
+            7 => Instruction::Alloca { dest: self.var()?, r#type: self.ty()? },
+            8 => Instruction::Load {
+                dest: self.var()?,
+                addr: self.operand()?,
+                value_type: self.ty()?,
+                volatile: self.bool()?,
+            },
+            9 => Instruction::Store {
+                addr: self.operand()?,
+                src: self.operand()?,
+                value_type: self.ty()?,
+                volatile: self.bool()?,
+            },
+            10 => Instruction::GetElementPtr {
+                dest: self.var()?,
+                base: self.operand()?,
+                index: self.operand()?,
+                element_type: self.ty()?,
+            },
+            11 => Instruction::Call {
+                dest: self.option(Self::var)?,
+                name: self.str()?,
+                args: self.list(Self::operand)?,
+                vararg_types: self.list(Self::ty)?,
+            },
+            12 => Instruction::IndirectCall {
+                dest: self.option(Self::var)?,
+                func_ptr: self.operand()?,
+                args: self.list(Self::operand)?,
+                is_variadic: self.bool()?,
+                vararg_types: self.list(Self::ty)?,
+            },
+            13 => Instruction::VaStart { list: self.operand()?, arg_index: self.len()? },
+            14 => Instruction::VaEnd { list: self.operand()? },
+            15 => Instruction::VaCopy { dest: self.operand()?, src: self.operand()? },
+            16 => Instruction::VaArg { dest: self.var()?, list: self.operand()?, r#type: self.ty()? },
+            17 => Instruction::InlineAsm {
+                template: self.str()?,
+                outputs: self.list(Self::var)?,
+                inputs: self.list(Self::operand)?,
+                output_constraints: self.list(Self::str)?,
+                input_constraints: self.list(Self::str)?,
+                clobbers: self.list(Self::str)?,
+                is_volatile: self.bool()?,
+            },
+            18 => Instruction::Memcpy { dest: self.operand()?, src: self.operand()?, size: self.len()? },
+            19 => Instruction::Memset { dest: self.operand()?, value: self.operand()?, size: self.len()? },
+            20 => Instruction::Simd {
+                op: self.table_entry(&SIMD_OPS, "SIMD operation")?,
+                dest: self.option(Self::var)?,
+                operands: self.list(Self::operand)?,
+                elem_type: self.ty()?,
+                width: self.len()?,
+            },
+            tag => return Err(self.bad_tag("instruction", tag)),
+        })
+    }
+
+    fn terminator(&mut self) -> Result<Terminator, CompileError> {
+        Ok(match self.byte()? {
+            0 => Terminator::Br(self.block_id()?),
+            1 => Terminator::CondBr {
+                cond: self.operand()?,
+                then_block: self.block_id()?,
+                else_block: self.block_id()?,
+                hint: self.table_entry(&HINTS, "branch hint")?,
+            },
+            2 => Terminator::Ret(self.option(Self::operand)?),
+            3 => Terminator::Unreachable,
+            4 => Terminator::IndirectBr { target: self.operand()? },
+            5 => Terminator::Switch {
+                value: self.operand()?,
+                cases: self.list(|r| Ok((r.int()?, r.block_id()?)))?,
+                default: self.block_id()?,
+            },
+            tag => return Err(self.bad_tag("terminator", tag)),
+        })
+    }
+
+    fn block(&mut self) -> Result<BasicBlock, CompileError> {
+        Ok(BasicBlock {
+            id: self.block_id()?,
+            instructions: self.list(Self::instruction)?,
+            terminator: self.terminator()?,
+            is_label_target: self.bool()?,
+            spans: self.list(Self::span)?,
+            terminator_span: self.span()?,
+        })
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        Ok(Function {
+            name: self.str()?,
+            return_type: self.ty()?,
+            params: self.list(|r| Ok((r.ty()?, r.var()?)))?,
+            blocks: self.list(Self::block)?,
+            entry_block: self.block_id()?,
+            var_types: self.list(|r| Ok((r.var()?, r.ty()?)))?.into_iter().collect(),
+            attributes: self.list(Self::attribute)?,
+            is_static: self.bool()?,
+            label_addrs: self.list(Self::str)?,
+            labels: self.list(|r| Ok((r.str()?, r.block_id()?)))?.into_iter().collect(),
+            local_names: self.list(|r| Ok((r.var()?, r.str()?)))?.into_iter().collect(),
+            span: self.span()?,
+        })
+    }
+}
@@ -0,0 +1,251 @@
+// Whole-program call graph over the IR.
+//
+// `build` gives every function its callees. A `Call`, or an `IndirectCall`
+// through a function's own address (what inlining leaves of `f(g)` with
+// `g` a known function), is a direct edge. Any other call through a pointer
+// may reach every function whose address is taken, so it gets an indirect
+// edge to each of them. Functions the program calls but does not define
+// (library functions) appear only as callees.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use model::Expr;
+
+use crate::types::{IRProgram, Instruction, Operand, Terminator};
+
+/// How a call edge was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CallKind {
+    /// The call names its callee.
+    Direct,
+    /// A call through a pointer, which may reach this address-taken function.
+    Indirect,
+}
+
+/// A caller → callee edge. Several call sites of the same kind share one edge.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CallEdge {
+    pub caller: String,
+    pub callee: String,
+    pub kind: CallKind,
+}
+
+/// Callers and callees of every function in a program; see `build`.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    edges: BTreeSet<CallEdge>,
+    defined: BTreeSet<String>,
+    address_taken: BTreeSet<String>,
+    // Functions with a call through a pointer of unknown target
+    indirect_callers: BTreeSet<String>,
+    callees: BTreeMap<String, BTreeSet<String>>,
+    callers: BTreeMap<String, BTreeSet<String>>,
+    // Members of a cycle, self-calls included
+    recursive: BTreeSet<String>,
+}
+
+/// Build the call graph of `program`. A function's address is taken when
+/// an instruction, a terminator, or a global initializer uses it as a value.
+pub fn build(program: &IRProgram) -> CallGraph {
+    let defined: BTreeSet<String> = program.functions.iter().map(|f| f.name.clone()).collect();
+    let mut referenced = BTreeSet::new();
+    let mut direct = Vec::new();
+    let mut indirect_callers = BTreeSet::new();
+
+    for func in &program.functions {
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            match inst {
+                Instruction::Call { name, args, .. } => {
+                    direct.push((func.name.clone(), name.clone()));
+                    args.iter().for_each(|a| note_global(a, &mut referenced));
+                }
+                Instruction::IndirectCall { func_ptr: Operand::Global(name), args, .. } => {
+                    direct.push((func.name.clone(), name.clone()));
+                    args.iter().for_each(|a| note_global(a, &mut referenced));
+                }
+                Instruction::IndirectCall { args, .. } => {
+                    indirect_callers.insert(func.name.clone());
+                    args.iter().for_each(|a| note_global(a, &mut referenced));
+                }
+                _ => inst.for_each_operand(|op| note_global(op, &mut referenced)),
+            }
+        }
+        for block in &func.blocks {
+            match &block.terminator {
+                Terminator::Ret(Some(op)) | Terminator::CondBr { cond: op, .. } | Terminator::IndirectBr { target: op }
+                | Terminator::Switch { value: op, .. } => {
+                    note_global(op, &mut referenced)
+                }
+                _ => {}
+            }
+        }
+    }
+    for init in program.globals.iter().filter_map(|g| g.init.as_ref()) {
+        note_names(init, &mut referenced);
+    }
+    let address_taken: BTreeSet<String> = referenced.intersection(&defined).cloned().collect();
+
+    let mut edges: BTreeSet<CallEdge> = direct
+        .into_iter()
+        .map(|(caller, callee)| CallEdge { caller, callee, kind: CallKind::Direct })
+        .collect();
+    for caller in &indirect_callers {
+        for callee in &address_taken {
+            edges.insert(CallEdge { caller: caller.clone(), callee: callee.clone(), kind: CallKind::Indirect });
+        }
+    }
+
+    let mut callees: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut callers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for edge in &edges {
+        callees.entry(edge.caller.clone()).or_default().insert(edge.callee.clone());
+        callers.entry(edge.callee.clone()).or_default().insert(edge.caller.clone());
+    }
+    let recursive = cycle_members(&callees);
+    CallGraph { edges, defined, address_taken, indirect_callers, callees, callers, recursive }
+}
+
+fn note_global(op: &Operand, referenced: &mut BTreeSet<String>) {
+    if let Operand::Global(name) = op {
+        referenced.insert(name.clone());
+    }
+}
+
+/// Names a global initializer refers to, such as `f` in `{ f, &g[1] }`.
+pub(crate) fn note_names(expr: &Expr, referenced: &mut BTreeSet<String>) {
+    match expr {
+        Expr::Variable(name) => {
+            referenced.insert(name.clone());
+        }
+        Expr::Unary { expr, .. } | Expr::Cast(_, expr) | Expr::Member { expr, .. } => note_names(expr, referenced),
+        Expr::Binary { left, right, .. } | Expr::Index { array: left, index: right } => {
+            note_names(left, referenced);
+            note_names(right, referenced);
+        }
+        Expr::Conditional { condition, then_expr, else_expr } => {
+            for e in [condition, then_expr, else_expr] {
+                note_names(e, referenced);
+            }
+        }
+        Expr::Comma(exprs) => exprs.iter().for_each(|e| note_names(e, referenced)),
+        Expr::InitList(items) | Expr::CompoundLiteral { init: items, .. } => {
+            items.iter().for_each(|item| note_names(&item.value, referenced))
+        }
+        _ => {}
+    }
+}
+
+/// Functions on a call cycle, found as the strongly connected components
+/// (Tarjan's algorithm) that have more than one member or call themselves.
+fn cycle_members(callees: &BTreeMap<String, BTreeSet<String>>) -> BTreeSet<String> {
+    struct Tarjan<'a> {
+        callees: &'a BTreeMap<String, BTreeSet<String>>,
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: BTreeSet<&'a str>,
+        members: BTreeSet<String>,
+    }
+
+    impl<'a> Tarjan<'a> {
+        fn visit(&mut self, name: &'a str) {
+            let index = self.index.len();
+            self.index.insert(name, index);
+            self.low.insert(name, index);
+            self.stack.push(name);
+            self.on_stack.insert(name);
+            for callee in self.callees.get(name).into_iter().flatten() {
+                let callee = callee.as_str();
+                if !self.index.contains_key(callee) {
+                    self.visit(callee);
+                    let low = self.low[name].min(self.low[callee]);
+                    self.low.insert(name, low);
+                } else if self.on_stack.contains(callee) {
+                    let low = self.low[name].min(self.index[callee]);
+                    self.low.insert(name, low);
+                }
+            }
+            if self.low[name] == index {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member);
+                    if member == name {
+                        break;
+                    }
+                }
+                let calls_itself = self.callees.get(name).is_some_and(|c| c.contains(name));
+                if component.len() > 1 || calls_itself {
+                    self.members.extend(component.into_iter().map(str::to_string));
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        callees,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        members: BTreeSet::new(),
+    };
+    for name in callees.keys() {
+        if !tarjan.index.contains_key(name.as_str()) {
+            tarjan.visit(name);
+        }
+    }
+    tarjan.members
+}
+
+impl CallGraph {
+    /// Every edge, ordered by caller, callee, and kind.
+    pub fn edges(&self) -> impl Iterator<Item = &CallEdge> {
+        self.edges.iter()
+    }
+
+    /// The functions `name` may call, by name.
+    pub fn callees(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.callees.get(name).into_iter().flatten().map(String::as_str)
+    }
+
+    /// The functions that may call `name`.
+    pub fn callers(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.callers.get(name).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Whether `name` is defined in the program, rather than only called.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defined.contains(name)
+    }
+
+    /// Whether the address of the defined function `name` is taken, so code
+    /// outside the graph (a callback, a function pointer table) may call it.
+    pub fn is_address_taken(&self, name: &str) -> bool {
+        self.address_taken.contains(name)
+    }
+
+    /// Whether `name` calls through a pointer whose target is unknown.
+    pub fn calls_indirectly(&self, name: &str) -> bool {
+        self.indirect_callers.contains(name)
+    }
+
+    /// Whether `name` can reach a call to itself, directly or through other
+    /// functions.
+    pub fn is_recursive(&self, name: &str) -> bool {
+        self.recursive.contains(name)
+    }
+
+    /// The functions reachable from `roots` through any chain of calls,
+    /// roots included.
+    pub fn reachable_from<'a>(&self, roots: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        let mut reached = BTreeSet::new();
+        let mut worklist: Vec<&str> = roots.into_iter().collect();
+        while let Some(name) = worklist.pop() {
+            if reached.insert(name.to_string()) {
+                worklist.extend(self.callees(name));
+            }
+        }
+        reached
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use crate::types::{BlockId, Function, Terminator};
+
+/// Predecessor and successor lists for every block of a function.
+///
+/// Build one with `Cfg::new` at the start of a pass, or keep one current
+/// while blocks are created by calling `set_successors` whenever a
+/// terminator changes. Either way a query is a map lookup instead of a scan
+/// over every block. Predecessors are kept in block-id order, with a block
+/// listed twice when both arms of its `CondBr` lead to the same place.
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    preds: HashMap<BlockId, Vec<BlockId>>,
+    succs: HashMap<BlockId, Vec<BlockId>>,
+}
+
+impl Cfg {
+    /// The control-flow graph of `func` as it is now. A computed goto may
+    /// reach any label whose address is taken.
+    pub fn new(func: &Function) -> Self {
+        let indirect = func.indirect_targets();
+        let mut cfg = Cfg::default();
+        for block in &func.blocks {
+            cfg.preds.entry(block.id).or_default();
+        }
+        for block in &func.blocks {
+            let targets = match &block.terminator {
+                Terminator::IndirectBr { .. } => indirect.clone(),
+                term => Self::direct_targets(term),
+            };
+            cfg.set_successors(block.id, targets);
+        }
+        cfg
+    }
+
+    /// The blocks a terminator names as branch targets. A computed goto
+    /// names none; its targets depend on the function's labels.
+    pub fn direct_targets(term: &Terminator) -> Vec<BlockId> {
+        match term {
+            Terminator::Br(target) => vec![*target],
+            Terminator::CondBr { then_block, else_block, .. } => vec![*then_block, *else_block],
+            Terminator::Switch { cases, default, .. } => Terminator::switch_targets(cases, *default),
+            Terminator::IndirectBr { .. } | Terminator::Ret(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// Blocks that branch to `block`.
+    pub fn preds(&self, block: BlockId) -> &[BlockId] {
+        self.preds.get(&block).map_or(&[], |p| p.as_slice())
+    }
+
+    /// Blocks `block` branches to, in terminator order.
+    pub fn succs(&self, block: BlockId) -> &[BlockId] {
+        self.succs.get(&block).map_or(&[], |s| s.as_slice())
+    }
+
+    /// Replace the outgoing edges of `block`, e.g. after giving it a new
+    /// terminator, and update the predecessor lists of the old and new
+    /// targets to match.
+    pub fn set_successors(&mut self, block: BlockId, targets: Vec<BlockId>) {
+        for old in self.succs.remove(&block).unwrap_or_default() {
+            if let Some(preds) = self.preds.get_mut(&old) {
+                if let Some(i) = preds.iter().position(|&p| p == block) {
+                    preds.remove(i);
+                }
+            }
+        }
+        for &target in &targets {
+            let preds = self.preds.entry(target).or_default();
+            let at = preds.partition_point(|&p| p.0 <= block.0);
+            preds.insert(at, block);
+        }
+        self.succs.insert(block, targets);
+    }
+
+    /// Forget every edge.
+    pub fn clear(&mut self) {
+        self.preds.clear();
+        self.succs.clear();
+    }
+}
@@ -0,0 +1,251 @@
+// Lowering of `_Complex float` / `_Complex double` values.
+//
+// A complex value never lives in a single IR variable. Expressions of complex
+// type are lowered to a (real, imaginary) pair of element-typed operands, and
+// complex objects are two adjacent elements in memory, real part first.
+
+use model::{BinaryOp, CompileError, ErrorCode, UnaryOp, Type, Expr as AstExpr};
+use crate::types::{VarId, Operand, Instruction};
+use crate::lowerer::Lowerer;
+
+/// Real and imaginary parts of a lowered complex expression.
+pub(crate) type ComplexParts = (Operand, Operand);
+
+impl Lowerer {
+    /// Whether `expr` has complex type. Only expression forms that can yield
+    /// a complex value are typed, so `lower_expr` can call this on every node.
+    pub(crate) fn is_complex_expr(&self, expr: &AstExpr) -> bool {
+        match expr {
+            AstExpr::ImaginaryConstant(_) => true,
+            AstExpr::Variable(_) | AstExpr::Member { .. } | AstExpr::PtrMember { .. }
+            | AstExpr::Index { .. } | AstExpr::Unary { .. } | AstExpr::Binary { .. }
+            | AstExpr::Cast(..) | AstExpr::Comma(_) | AstExpr::Conditional { .. }
+            | AstExpr::Call { .. } | AstExpr::CompoundLiteral { .. } => {
+                matches!(self.get_expr_type(expr), Type::Complex(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// Lower `expr` to its real and imaginary parts. Real operands get a zero
+    /// imaginary part.
+    pub(crate) fn lower_complex(&mut self, expr: &AstExpr) -> Result<ComplexParts, CompileError> {
+        let ty = self.get_expr_type(expr);
+        let Type::Complex(elem) = &ty else {
+            let elem = if ty == Type::Float { Type::Float } else { Type::Double };
+            let val = self.lower_expr(expr)?;
+            return Ok((self.convert_to_element(val, &ty, &elem), Operand::FloatConstant(0.0)));
+        };
+        let elem = elem.as_ref().clone();
+        match expr {
+            AstExpr::ImaginaryConstant(v) => Ok((Operand::FloatConstant(0.0), Operand::FloatConstant(*v))),
+            AstExpr::Variable(_) | AstExpr::Member { .. } | AstExpr::PtrMember { .. }
+            | AstExpr::Index { .. } | AstExpr::Unary { op: UnaryOp::Deref, .. } => {
+                let addr = self.lower_to_addr(expr)?;
+                let volatile = self.is_volatile_access(expr);
+                Ok(self.load_complex(addr, &elem, volatile))
+            }
+            AstExpr::Binary { left, op: BinaryOp::Assign, right } => {
+                let parts = self.lower_complex_as(right, &elem)?;
+                let addr = self.lower_to_addr(left)?;
+                let volatile = self.is_volatile_access(left);
+                self.store_complex(addr, &elem, parts.clone(), volatile);
+                Ok(parts)
+            }
+            AstExpr::Binary { left, op, right } => {
+                let compound = match op {
+                    BinaryOp::AddAssign => Some(BinaryOp::Add),
+                    BinaryOp::SubAssign => Some(BinaryOp::Sub),
+                    BinaryOp::MulAssign => Some(BinaryOp::Mul),
+                    BinaryOp::DivAssign => Some(BinaryOp::Div),
+                    _ => None,
+                };
+                if let Some(arith) = compound {
+                    let rhs = self.lower_complex_as(right, &elem)?;
+                    let addr = self.lower_to_addr(left)?;
+                    let volatile = self.is_volatile_access(left);
+                    let lhs = self.load_complex(addr, &elem, volatile);
+                    let result = self.complex_arith(&arith, lhs, rhs, &elem)?;
+                    self.store_complex(addr, &elem, result.clone(), volatile);
+                    return Ok(result);
+                }
+                let lhs = self.lower_complex_as(left, &elem)?;
+                let rhs = self.lower_complex_as(right, &elem)?;
+                self.complex_arith(op, lhs, rhs, &elem)
+            }
+            AstExpr::Unary { op: UnaryOp::Plus, expr: inner } => self.lower_complex_as(inner, &elem),
+            AstExpr::Unary { op: UnaryOp::Minus, expr: inner } => {
+                let (re, im) = self.lower_complex_as(inner, &elem)?;
+                Ok((self.float_negate(re, &elem), self.float_negate(im, &elem)))
+            }
+            AstExpr::Unary { op: UnaryOp::BitwiseNot, expr: inner } => {
+                // GCC extension: `~z` is the complex conjugate
+                let (re, im) = self.lower_complex_as(inner, &elem)?;
+                Ok((re, self.float_negate(im, &elem)))
+            }
+            AstExpr::Cast(_, inner) => self.lower_complex_as(inner, &elem),
+            AstExpr::Comma(exprs) if !exprs.is_empty() => {
+                for e in &exprs[..exprs.len() - 1] {
+                    self.lower_expr(e)?;
+                }
+                self.lower_complex(exprs.last().unwrap())
+            }
+            _ => Err(CompileError::new(
+                ErrorCode::Unsupported,
+                format!("this expression of type '{}' is not supported yet", ty),
+            )),
+        }
+    }
+
+    /// Lower `expr` as a complex value with element type `elem`.
+    pub(crate) fn lower_complex_as(&mut self, expr: &AstExpr, elem: &Type) -> Result<ComplexParts, CompileError> {
+        let src_elem = model::TypeEnv::real_type(&self.get_expr_type(expr));
+        let (re, im) = self.lower_complex(expr)?;
+        let src_elem = if src_elem == Type::Float { Type::Float } else { Type::Double };
+        Ok((self.convert_to_element(re, &src_elem, elem), self.convert_to_element(im, &src_elem, elem)))
+    }
+
+    /// `==` and `!=` compare both parts.
+    pub(crate) fn lower_complex_equality(&mut self, left: &AstExpr, op: &BinaryOp, right: &AstExpr) -> Result<Operand, CompileError> {
+        let lt = self.get_expr_type(left);
+        let rt = self.get_expr_type(right);
+        let elem = match model::TypeEnv::usual_arithmetic_conversions(&lt, &rt) {
+            Type::Complex(elem) => *elem,
+            _ => Type::Double,
+        };
+        let (lre, lim) = self.lower_complex_as(left, &elem)?;
+        let (rre, rim) = self.lower_complex_as(right, &elem)?;
+        let re = self.float_binary(op, lre, rre, &elem);
+        let im = self.float_binary(op, lim, rim, &elem);
+        let combine = if *op == BinaryOp::EqualEqual { BinaryOp::BitwiseAnd } else { BinaryOp::BitwiseOr };
+        if let (Operand::Constant(a), Operand::Constant(b)) = (&re, &im) {
+            return Ok(Operand::Constant(if combine == BinaryOp::BitwiseAnd { a & b } else { a | b }));
+        }
+        let dest = self.new_var();
+        self.var_types.insert(dest, Type::Int);
+        self.add_instruction(Instruction::Binary { dest, op: combine, left: re, right: im });
+        Ok(Operand::Var(dest))
+    }
+
+    /// Load the parts of the complex object at `addr`.
+    pub(crate) fn load_complex(&mut self, addr: VarId, elem: &Type, volatile: bool) -> ComplexParts {
+        let re = self.load_part(addr, 0, elem, volatile);
+        let im = self.load_part(addr, 1, elem, volatile);
+        (re, im)
+    }
+
+    /// Store `parts` into the complex object at `addr`.
+    pub(crate) fn store_complex(&mut self, addr: VarId, elem: &Type, parts: ComplexParts, volatile: bool) {
+        let (re, im) = parts;
+        for (index, src) in [(0, re), (1, im)] {
+            let part_addr = self.part_addr(addr, index, elem);
+            self.add_instruction(Instruction::Store {
+                addr: Operand::Var(part_addr),
+                src,
+                value_type: elem.clone(),
+                volatile,
+            });
+        }
+    }
+
+    fn part_addr(&mut self, addr: VarId, index: i64, elem: &Type) -> VarId {
+        let dest = self.new_var();
+        self.add_instruction(Instruction::GetElementPtr {
+            dest,
+            base: Operand::Var(addr),
+            index: Operand::Constant(index),
+            element_type: elem.clone(),
+        });
+        dest
+    }
+
+    fn load_part(&mut self, addr: VarId, index: i64, elem: &Type, volatile: bool) -> Operand {
+        let part_addr = self.part_addr(addr, index, elem);
+        let dest = self.new_var();
+        self.var_types.insert(dest, elem.clone());
+        self.add_instruction(Instruction::Load {
+            dest,
+            addr: Operand::Var(part_addr),
+            value_type: elem.clone(),
+            volatile,
+        });
+        Operand::Var(dest)
+    }
+
+    /// Convert a real value of type `from` to the floating type `elem`.
+    fn convert_to_element(&mut self, val: Operand, from: &Type, elem: &Type) -> Operand {
+        self.convert_arithmetic(val, from, elem)
+    }
+
+    /// Emit one floating-point operation, folding it when both operands are
+    /// constants so double-precision constants are never rounded through float.
+    fn float_binary(&mut self, op: &BinaryOp, left: Operand, right: Operand, elem: &Type) -> Operand {
+        if let (Operand::FloatConstant(l), Operand::FloatConstant(r)) = (&left, &right) {
+            let (l, r) = (*l, *r);
+            match op {
+                BinaryOp::Add => return Operand::FloatConstant(l + r),
+                BinaryOp::Sub => return Operand::FloatConstant(l - r),
+                BinaryOp::Mul => return Operand::FloatConstant(l * r),
+                BinaryOp::Div => return Operand::FloatConstant(l / r),
+                BinaryOp::EqualEqual => return Operand::Constant(i64::from(l == r)),
+                BinaryOp::NotEqual => return Operand::Constant(i64::from(l != r)),
+                _ => {}
+            }
+        }
+        let dest = self.new_var();
+        let result_type = if matches!(op, BinaryOp::EqualEqual | BinaryOp::NotEqual) { Type::Int } else { elem.clone() };
+        self.var_types.insert(dest, result_type);
+        self.add_instruction(Instruction::FloatBinary { dest, op: op.clone(), left, right });
+        Operand::Var(dest)
+    }
+
+    fn float_negate(&mut self, val: Operand, elem: &Type) -> Operand {
+        if let Operand::FloatConstant(f) = val {
+            return Operand::FloatConstant(-f);
+        }
+        let dest = self.new_var();
+        self.var_types.insert(dest, elem.clone());
+        self.add_instruction(Instruction::FloatUnary { dest, op: UnaryOp::Minus, src: val });
+        Operand::Var(dest)
+    }
+
+    /// `(a + bi) op (c + di)`. Division uses the textbook formula without
+    /// scaling, like GCC's `-fcx-limited-range`.
+    fn complex_arith(&mut self, op: &BinaryOp, lhs: ComplexParts, rhs: ComplexParts, elem: &Type) -> Result<ComplexParts, CompileError> {
+        let ((a, b), (c, d)) = (lhs, rhs);
+        match op {
+            BinaryOp::Add | BinaryOp::Sub => {
+                let re = self.float_binary(op, a, c, elem);
+                let im = self.float_binary(op, b, d, elem);
+                Ok((re, im))
+            }
+            BinaryOp::Mul => {
+                let ac = self.float_binary(&BinaryOp::Mul, a.clone(), c.clone(), elem);
+                let bd = self.float_binary(&BinaryOp::Mul, b.clone(), d.clone(), elem);
+                let ad = self.float_binary(&BinaryOp::Mul, a, d, elem);
+                let bc = self.float_binary(&BinaryOp::Mul, b, c, elem);
+                let re = self.float_binary(&BinaryOp::Sub, ac, bd, elem);
+                let im = self.float_binary(&BinaryOp::Add, bc, ad, elem);
+                Ok((re, im))
+            }
+            BinaryOp::Div => {
+                let cc = self.float_binary(&BinaryOp::Mul, c.clone(), c.clone(), elem);
+                let dd = self.float_binary(&BinaryOp::Mul, d.clone(), d.clone(), elem);
+                let denom = self.float_binary(&BinaryOp::Add, cc, dd, elem);
+                let ac = self.float_binary(&BinaryOp::Mul, a.clone(), c.clone(), elem);
+                let bd = self.float_binary(&BinaryOp::Mul, b.clone(), d.clone(), elem);
+                let bc = self.float_binary(&BinaryOp::Mul, b, c, elem);
+                let ad = self.float_binary(&BinaryOp::Mul, a, d, elem);
+                let re_num = self.float_binary(&BinaryOp::Add, ac, bd, elem);
+                let im_num = self.float_binary(&BinaryOp::Sub, bc, ad, elem);
+                let re = self.float_binary(&BinaryOp::Div, re_num, denom.clone(), elem);
+                let im = self.float_binary(&BinaryOp::Div, im_num, denom, elem);
+                Ok((re, im))
+            }
+            _ => Err(CompileError::new(
+                ErrorCode::InvalidOperand,
+                format!("invalid operator {:?} on complex operands", op),
+            )),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// Graphviz (DOT) and Mermaid rendering of IR control-flow and call graphs.
+//
+// Used by the driver's --dump-cfg / --dump-callgraph flags. `cfg_to_dot` and
+// `callgraph_to_dot` are meant for `dot -Tsvg` on large programs, so they
+// favour short labels; `render_cfg` lists every instruction instead.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::printer::{instruction_text, terminator_text};
+use crate::callgraph::{self, CallKind};
+use crate::types::{BasicBlock, BlockId, Function, IRProgram, Instruction, Terminator};
+
+/// Output syntax for `render_cfg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfgFormat {
+    /// Graphviz, for `dot -Tsvg`.
+    Dot,
+    /// Mermaid `flowchart`, which renders in Markdown viewers.
+    Mermaid,
+}
+
+/// Render one function's CFG: a node per basic block, an edge per branch.
+/// Conditional branches label their edges `T` / `F`.
+pub fn cfg_to_dot(func: &Function) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph \"{}\" {{", escape(&func.name)).unwrap();
+    writeln!(out, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+
+    for block in &func.blocks {
+        let phis = block.instructions.iter().filter(|i| matches!(i, Instruction::Phi { .. })).count();
+        let mut label = format!("bb{}", block.id.0);
+        if block.id == func.entry_block {
+            label.push_str(" (entry)");
+        }
+        label.push_str(&format!("\\n{} instrs", block.instructions.len()));
+        if phis > 0 {
+            label.push_str(&format!(", {} phis", phis));
+        }
+        label.push_str(&format!("\\n{}", escape(&terminator_text(&block.terminator))));
+        let style = if block.id == func.entry_block { ", style=bold" } else { "" };
+        writeln!(out, "  bb{} [label=\"{}\"{}];", block.id.0, label, style).unwrap();
+    }
+
+    for block in &func.blocks {
+        match &block.terminator {
+            Terminator::Br(target) => {
+                writeln!(out, "  bb{} -> bb{};", block.id.0, target.0).unwrap();
+            }
+            Terminator::CondBr { then_block, else_block, .. } => {
+                writeln!(out, "  bb{} -> bb{} [label=\"T\"];", block.id.0, then_block.0).unwrap();
+                writeln!(out, "  bb{} -> bb{} [label=\"F\"];", block.id.0, else_block.0).unwrap();
+            }
+            Terminator::IndirectBr { .. } => {
+                // Any address-taken label may be the target.
+                for name in &func.label_addrs {
+                    if let Some(target) = func.labels.get(name) {
+                        writeln!(out, "  bb{} -> bb{} [style=dashed];", block.id.0, target.0).unwrap();
+                    }
+                }
+            }
+            Terminator::Switch { cases, default, .. } => {
+                for (value, target) in cases {
+                    writeln!(out, "  bb{} -> bb{} [label=\"{}\"];", block.id.0, target.0, value).unwrap();
+                }
+                writeln!(out, "  bb{} -> bb{} [label=\"default\"];", block.id.0, default.0).unwrap();
+            }
+            Terminator::Ret(_) | Terminator::Unreachable => {}
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Render one function's CFG with every instruction of each block. Phis are
+/// listed with their incoming blocks, and each edge is annotated with the phi
+/// copies it carries (`%dest <- %src`).
+pub fn render_cfg(func: &Function, format: CfgFormat) -> String {
+    let mut edges = Vec::new();
+    for block in &func.blocks {
+        match &block.terminator {
+            Terminator::Br(target) => edges.push((block.id, *target, String::new(), false)),
+            Terminator::CondBr { then_block, else_block, .. } => {
+                edges.push((block.id, *then_block, "T".to_string(), false));
+                edges.push((block.id, *else_block, "F".to_string(), false));
+            }
+            Terminator::IndirectBr { .. } => {
+                for name in &func.label_addrs {
+                    if let Some(target) = func.labels.get(name) {
+                        edges.push((block.id, *target, String::new(), true));
+                    }
+                }
+            }
+            // One edge per target, since the phi copies are per edge
+            Terminator::Switch { cases, default, .. } => {
+                for target in Terminator::switch_targets(cases, *default) {
+                    let mut labels: Vec<String> = cases.iter()
+                        .filter(|(_, block)| *block == target)
+                        .map(|(value, _)| value.to_string())
+                        .collect();
+                    if target == *default {
+                        labels.push("default".to_string());
+                    }
+                    edges.push((block.id, target, labels.join(","), false));
+                }
+            }
+            Terminator::Ret(_) | Terminator::Unreachable => {}
+        }
+    }
+    let edges: Vec<_> = edges
+        .into_iter()
+        .map(|(from, to, label, dashed)| {
+            let mut parts: Vec<String> = Some(label).filter(|l| !l.is_empty()).into_iter().collect();
+            parts.extend(phi_copies(func, from, to));
+            (from, to, parts.join("; "), dashed)
+        })
+        .collect();
+
+    let mut out = String::new();
+    match format {
+        CfgFormat::Dot => {
+            writeln!(out, "digraph \"{}\" {{", escape(&func.name)).unwrap();
+            writeln!(out, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+            for block in &func.blocks {
+                // `\l` ends a left-justified line
+                let label: String = block_lines(func, block).iter().map(|l| format!("{}\\l", escape(l))).collect();
+                let style = if block.id == func.entry_block { ", style=bold" } else { "" };
+                writeln!(out, "  bb{} [label=\"{}\"{}];", block.id.0, label, style).unwrap();
+            }
+            for (from, to, label, dashed) in &edges {
+                let mut attrs = Vec::new();
+                if !label.is_empty() {
+                    attrs.push(format!("label=\"{}\"", escape(label)));
+                }
+                if *dashed {
+                    attrs.push("style=dashed".to_string());
+                }
+                let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+                writeln!(out, "  bb{} -> bb{}{};", from.0, to.0, attrs).unwrap();
+            }
+            out.push_str("}\n");
+        }
+        CfgFormat::Mermaid => {
+            out.push_str("flowchart TD\n");
+            for block in &func.blocks {
+                let label: Vec<String> = block_lines(func, block).iter().map(|l| mermaid_escape(l)).collect();
+                writeln!(out, "  bb{}[\"{}\"]", block.id.0, label.join("<br/>")).unwrap();
+            }
+            for (from, to, label, dashed) in &edges {
+                let arrow = if *dashed { "-.->" } else { "-->" };
+                if label.is_empty() {
+                    writeln!(out, "  bb{} {} bb{}", from.0, arrow, to.0).unwrap();
+                } else {
+                    writeln!(out, "  bb{} {}|\"{}\"| bb{}", from.0, arrow, mermaid_escape(label), to.0).unwrap();
+                }
+            }
+            writeln!(out, "  style bb{} stroke-width:3px", func.entry_block.0).unwrap();
+        }
+    }
+    out
+}
+
+/// Header, instructions, and terminator of a block, one per line.
+fn block_lines(func: &Function, block: &BasicBlock) -> Vec<String> {
+    let id = block.id;
+    let mut lines = vec![if id == func.entry_block { format!("bb{} (entry):", id.0) } else { format!("bb{}:", id.0) }];
+    lines.extend(block.instructions.iter().map(|inst| format!("  {}", instruction_text(inst))));
+    lines.push(format!("  {}", terminator_text(&block.terminator)));
+    lines
+}
+
+/// `%dest <- %src` for each phi in `to` that takes a value from `from`.
+fn phi_copies(func: &Function, from: BlockId, to: BlockId) -> Vec<String> {
+    let Some(block) = func.blocks.iter().find(|b| b.id == to) else {
+        return Vec::new();
+    };
+    block
+        .instructions
+        .iter()
+        .filter_map(|inst| match inst {
+            Instruction::Phi { dest, preds } => {
+                preds.iter().find(|(pred, _)| *pred == from).map(|(_, src)| format!("%{} <- %{}", dest.0, src.0))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render the program's call graph. Functions defined in the program are
+/// boxes; external callees are ellipses. Indirect calls point at a single
+/// `<indirect>` node rather than at every function they might reach.
+pub fn callgraph_to_dot(program: &IRProgram) -> String {
+    let graph = callgraph::build(program);
+    let mut edges: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut externals = BTreeSet::new();
+    for edge in graph.edges().filter(|e| e.kind == CallKind::Direct) {
+        if !graph.is_defined(&edge.callee) {
+            externals.insert(edge.callee.as_str());
+        }
+        edges.entry(&edge.caller).or_default().insert(&edge.callee);
+    }
+    let mut has_indirect = false;
+    for func in program.functions.iter().filter(|f| graph.calls_indirectly(&f.name)) {
+        has_indirect = true;
+        edges.entry(&func.name).or_default().insert("<indirect>");
+    }
+
+    let mut out = String::new();
+    out.push_str("digraph callgraph {\n");
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    for func in &program.functions {
+        writeln!(out, "  \"{}\";", escape(&func.name)).unwrap();
+    }
+    for name in &externals {
+        writeln!(out, "  \"{}\" [shape=ellipse, style=dashed];", escape(name)).unwrap();
+    }
+    if has_indirect {
+        out.push_str("  \"<indirect>\" [shape=diamond];\n");
+    }
+    for (caller, callees) in &edges {
+        for callee in callees {
+            writeln!(out, "  \"{}\" -> \"{}\";", escape(caller), escape(callee)).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('&', "#amp;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+        .replace('|', "#124;")
+        .replace('\n', " ")
+}
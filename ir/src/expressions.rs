@@ -1,6 +1,7 @@
-use model::{BinaryOp, UnaryOp, Type, Expr as AstExpr};
-use crate::types::{BranchHint, Operand, Instruction, Terminator};
-use crate::lowerer::Lowerer;
+use model::{BinaryOp, CompileError, ErrorCode, UnaryOp, Type, Expr as AstExpr};
+use crate::types::{is_comparison, BranchHint, Operand, Instruction, Terminator, VarId};
+use crate::lowerer::{bitfield_mask, Lowerer};
+use crate::type_utils::ScalarKind;
 
 /// Expression lowering implementation
 impl Lowerer {
@@ -8,7 +9,7 @@ impl Lowerer {
     pub(crate) fn lower_branch_condition(
         &mut self,
         expr: &AstExpr,
-    ) -> Result<(Operand, BranchHint), String> {
+    ) -> Result<(Operand, BranchHint), CompileError> {
         match expr {
             AstExpr::Expect { expr: inner, expected } => {
                 let hint = match expected.as_ref() {
@@ -22,72 +23,201 @@ impl Lowerer {
         }
     }
 
-    /// Lower an AST expression to an IR operand
-    pub(crate) fn lower_expr(&mut self, expr: &AstExpr) -> Result<Operand, String> {
+    /// Convert a scalar to `_Bool`: zero stays 0, anything else becomes 1.
+    pub(crate) fn lower_to_bool(&mut self, val: Operand, src_type: &Type) -> Operand {
+        match val {
+            Operand::Constant(c) => return Operand::Constant(i64::from(c != 0)),
+            Operand::FloatConstant(f) => return Operand::Constant(i64::from(f != 0.0)),
+            _ => {}
+        }
+        let dest = self.new_var();
+        self.var_types.insert(dest, Type::Int);
+        if self.is_float_type(src_type) {
+            self.add_instruction(Instruction::FloatBinary {
+                dest,
+                op: BinaryOp::NotEqual,
+                left: val,
+                right: Operand::FloatConstant(0.0),
+            });
+        } else {
+            self.add_instruction(Instruction::Binary {
+                dest,
+                op: BinaryOp::NotEqual,
+                left: val,
+                right: Operand::Constant(0),
+            });
+        }
+        Operand::Var(dest)
+    }
+
+    /// Convert the result of an in-place update (`x += y`, `x++`), computed
+    /// in the object's own type, for storage back into an object of type
+    /// `target`. Only `_Bool`, and `char` and `short`, whose arithmetic is
+    /// done in `int`, need work here; `convert_arithmetic` handles values of
+    /// another type. Narrowing here rather than leaving it to the store keeps
+    /// the value right once mem2reg or load forwarding removes the store.
+    pub(crate) fn convert_for_store(&mut self, val: Operand, src_type: &Type, target: &Type) -> Operand {
+        if matches!(target, Type::Bool) && !matches!(src_type, Type::Bool) {
+            self.lower_to_bool(val, src_type)
+        } else if !matches!(self.scalar_type(target), Type::Bool)
+            && matches!(self.scalar_kind(target), Some(ScalarKind::Int { size: 1 | 2, .. }))
+        {
+            self.convert_arithmetic(val, src_type, target)
+        } else {
+            val
+        }
+    }
+
+    /// Convert `val` (of `src_type`) to the scalar type `target`, as a cast
+    /// or an assignment does: to `_Bool` by comparison with zero, and with a
+    /// `Cast` wherever the representation changes (integer width or
+    /// signedness, integer ↔ floating point, `float` ↔ `double`). Constants
+    /// are converted in place; non-scalar types are left alone.
+    pub(crate) fn convert_arithmetic(&mut self, val: Operand, src_type: &Type, target: &Type) -> Operand {
+        let (from, to) = (self.scalar_type(src_type), self.scalar_type(target));
+        if matches!(to, Type::Bool) {
+            return self.convert_for_store(val, &from, &to);
+        }
+        let (Some(from_kind), Some(to_kind)) = (self.scalar_kind(&from), self.scalar_kind(&to)) else {
+            return val;
+        };
+        if from_kind.same_representation(to_kind) {
+            return val;
+        }
+        let from_u64 = from_kind == ScalarKind::Int { size: 8, signed: false };
+        match (val, to_kind) {
+            (Operand::Constant(c), ScalarKind::Float { double }) => {
+                let f = if from_u64 { c as u64 as f64 } else { c as f64 };
+                Operand::FloatConstant(if double { f } else { f64::from(f as f32) })
+            }
+            (Operand::Constant(c), _) => Operand::Constant(to_kind.wrap(c)),
+            (Operand::FloatConstant(f), ScalarKind::Float { double }) => {
+                Operand::FloatConstant(if double { f } else { f64::from(f as f32) })
+            }
+            (Operand::FloatConstant(f), ScalarKind::Int { size: 8, signed: false }) => Operand::Constant(f as u64 as i64),
+            (Operand::FloatConstant(f), _) => Operand::Constant(to_kind.wrap(f as i64)),
+            (val @ Operand::Global(_), _) => val,
+            (val, _) => {
+                let dest = self.new_var();
+                self.var_types.insert(dest, to.clone());
+                self.add_instruction(Instruction::Cast { dest, src: val, src_type: from, r#type: to });
+                Operand::Var(dest)
+            }
+        }
+    }
+
+    /// The type `arg` is passed as where no prototype converts it: after the
+    /// default argument promotions (C11 §6.5.2.2p6), with arrays decayed.
+    fn promoted_arg_type(&self, arg: &AstExpr) -> Type {
+        match self.resolve_type(&self.get_expr_type(arg)) {
+            Type::Float => Type::Double,
+            Type::Bool | Type::Char | Type::UnsignedChar | Type::Short | Type::UnsignedShort => Type::Int,
+            Type::Array(inner, _) => Type::ptr(*inner),
+            ty => ty,
+        }
+    }
+
+    /// Emit a `FloatBinary` on `left` and `right` converted to their common
+    /// type (C11 §6.3.1.8), so an integer operand is converted and a `float`
+    /// one widened beside a `double`. Returns the result and its type, which
+    /// is `int` for a comparison.
+    fn lower_float_binary(&mut self, op: BinaryOp, left: Operand, l_ty: &Type, right: Operand, r_ty: &Type) -> (VarId, Type) {
+        let common = model::TypeEnv::usual_arithmetic_conversions(&self.scalar_type(l_ty), &self.scalar_type(r_ty));
+        let left = self.convert_arithmetic(left, l_ty, &common);
+        let right = self.convert_arithmetic(right, r_ty, &common);
+        let result_type = if is_comparison(&op) { Type::Int } else { common };
+        let dest = self.new_var();
+        self.var_types.insert(dest, result_type.clone());
+        self.add_instruction(Instruction::FloatBinary { dest, op, left, right });
+        (dest, result_type)
+    }
+
+    /// Emit a `Binary` on integer operands converted to their common type
+    /// (C11 §6.3.1.8), or, for a shift, with the left operand promoted, so a
+    /// narrower or signed operand is extended the way the result needs.
+    /// Other operands (pointers) are used as they are. Returns the result
+    /// and its type, which is `int` for a comparison.
+    fn lower_int_binary(&mut self, op: BinaryOp, left: Operand, l_ty: &Type, right: Operand, r_ty: &Type) -> (VarId, Type) {
+        let (l_scalar, r_scalar) = (self.scalar_type(l_ty), self.scalar_type(r_ty));
+        let (left, right, common) = if !model::TypeEnv::is_integer_type(&l_scalar) {
+            (left, right, l_scalar)
+        } else if matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) {
+            let promoted = model::TypeEnv::integer_promotion(&l_scalar);
+            (self.convert_arithmetic(left, l_ty, &promoted), right, promoted)
+        } else if model::TypeEnv::is_integer_type(&r_scalar) {
+            let common = model::TypeEnv::usual_arithmetic_conversions(&l_scalar, &r_scalar);
+            let left = self.convert_arithmetic(left, l_ty, &common);
+            let right = self.convert_arithmetic(right, r_ty, &common);
+            (left, right, common)
+        } else {
+            (left, right, r_scalar)
+        };
+        self.sanitize_binary(&op, &left, &right, l_ty, r_ty);
+        let result_type = if is_comparison(&op) { Type::Int } else { common };
+        let dest = self.new_var();
+        self.var_types.insert(dest, result_type.clone());
+        self.add_instruction(Instruction::Binary { dest, op, left, right });
+        (dest, result_type)
+    }
+
+    /// Lower an AST expression to an IR operand. A variable holding the
+    /// result is given the expression's type in `var_types` unless it
+    /// already has one.
+    pub(crate) fn lower_expr(&mut self, expr: &AstExpr) -> Result<Operand, CompileError> {
+        let val = self.lower_expr_value(expr)?;
+        if let Operand::Var(v) = val {
+            if !self.var_types.contains_key(&v) {
+                let ty = self.resolve_type(&self.get_expr_type(expr));
+                let ty = model::TypeEnv::decay_array(&model::TypeEnv::real_type(&ty));
+                self.var_types.insert(v, ty);
+            }
+        }
+        Ok(val)
+    }
+
+    fn lower_expr_value(&mut self, expr: &AstExpr) -> Result<Operand, CompileError> {
+        // A complex value used as a scalar converts to its real part
+        if self.is_complex_expr(expr) {
+            return Ok(self.lower_complex(expr)?.0);
+        }
         match expr {
-            AstExpr::Constant(c) => Ok(Operand::Constant(*c)),
+            AstExpr::Constant(c) | AstExpr::TypedConstant(c, _) => Ok(Operand::Constant(*c)),
             AstExpr::FloatConstant(f) => Ok(Operand::FloatConstant(*f)),
+            AstExpr::ImaginaryConstant(_) => unreachable!("imaginary constants are complex"),
+            AstExpr::Real(inner) if self.is_complex_expr(inner) => Ok(self.lower_complex(inner)?.0),
+            AstExpr::Imag(inner) if self.is_complex_expr(inner) => Ok(self.lower_complex(inner)?.1),
+            AstExpr::Real(inner) => self.lower_expr(inner),
+            AstExpr::Imag(inner) => {
+                // The imaginary part of a real value is zero; evaluate it for side effects
+                let inner_type = self.get_expr_type(inner);
+                self.lower_expr(inner)?;
+                Ok(if self.is_float_type(&inner_type) { Operand::FloatConstant(0.0) } else { Operand::Constant(0) })
+            }
+            AstExpr::Binary { left, op: op @ (BinaryOp::EqualEqual | BinaryOp::NotEqual), right }
+                if self.is_complex_expr(left) || self.is_complex_expr(right) =>
+            {
+                self.lower_complex_equality(left, op, right)
+            }
             AstExpr::Binary { left, op, right } => {
                 if *op == BinaryOp::Assign {
+                    let value_type = self.resolve_type(&self.get_expr_type(left));
+                    if matches!(value_type, Type::Struct(_) | Type::Union(_)) {
+                        // Struct assignment copies the whole object and
+                        // evaluates to the address of the left side
+                        let dest = self.lower_to_addr(left)?;
+                        self.lower_aggregate_copy(Operand::Var(dest), right, &value_type)?;
+                        return Ok(Operand::Var(dest));
+                    }
                     let val = self.lower_expr(right)?;
                     let addr = self.lower_to_addr(left)?;
+                    let volatile = self.is_volatile_access(left);
                     let value_type = self.get_expr_type(left);
+                    let rhs_type = self.get_expr_type(right);
+                    let val = self.convert_arithmetic(val, &rhs_type, &value_type);
 
                     // Check if this is a bitfield write → read-modify-write
                     if let Some(bf_info) = self.get_bitfield_info(left) {
-                        let mask = ((1u64 << bf_info.bit_width) - 1) as i64;
-                        // Load the current storage unit
-                        let old_val = self.new_var();
-                        self.add_instruction(Instruction::Load {
-                            dest: old_val,
-                            addr: Operand::Var(addr),
-                            value_type: value_type.clone(),
-                            volatile: false,
-                        });
-                        // Clear the bitfield bits: old & ~(mask << bit_offset)
-                        let clear_mask = !(mask << bf_info.bit_offset);
-                        let cleared = self.new_var();
-                        self.add_instruction(Instruction::Binary {
-                            dest: cleared,
-                            op: BinaryOp::BitwiseAnd,
-                            left: Operand::Var(old_val),
-                            right: Operand::Constant(clear_mask),
-                        });
-                        // Mask the new value and shift into position: (val & mask) << bit_offset
-                        let masked_val = self.new_var();
-                        self.add_instruction(Instruction::Binary {
-                            dest: masked_val,
-                            op: BinaryOp::BitwiseAnd,
-                            left: val.clone(),
-                            right: Operand::Constant(mask),
-                        });
-                        let shifted_val = if bf_info.bit_offset > 0 {
-                            let sv = self.new_var();
-                            self.add_instruction(Instruction::Binary {
-                                dest: sv,
-                                op: BinaryOp::ShiftLeft,
-                                left: Operand::Var(masked_val),
-                                right: Operand::Constant(bf_info.bit_offset as i64),
-                            });
-                            sv
-                        } else {
-                            masked_val
-                        };
-                        // Combine: cleared | shifted_val
-                        let combined = self.new_var();
-                        self.add_instruction(Instruction::Binary {
-                            dest: combined,
-                            op: BinaryOp::BitwiseOr,
-                            left: Operand::Var(cleared),
-                            right: Operand::Var(shifted_val),
-                        });
-                        // Store back
-                        self.add_instruction(Instruction::Store {
-                            addr: Operand::Var(addr),
-                            src: Operand::Var(combined),
-                            value_type,
-                            volatile: false,
-                        });
+                        self.store_bitfield(addr, val.clone(), value_type, &bf_info);
                         return Ok(val);
                     }
 
@@ -95,7 +225,7 @@ impl Lowerer {
                         addr: Operand::Var(addr),
                         src: val.clone(),
                         value_type,
-                        volatile: false,
+                        volatile,
                     });
                     return Ok(val);
                 }
@@ -104,42 +234,43 @@ impl Lowerer {
                 // If a == 0, result = 0; else result = b (with short-circuit)
                 if *op == BinaryOp::LogicalAnd {
                     let lhs_val = self.lower_expr(left)?;
-                    let entry_bid = self.current_block.ok_or("LogicalAnd outside block")?;
+                    let entry_bid = self.require_block("LogicalAnd outside block")?;
 
                     let rhs_id   = self.new_block();
                     let false_id = self.new_block();
                     let merge_id = self.new_block();
 
-                    self.blocks[entry_bid.0].terminator =
-                        Terminator::cond_br(lhs_val, rhs_id, false_id);
+                    self.set_terminator(entry_bid, Terminator::cond_br(lhs_val, rhs_id, false_id));
 
                     // false_id: lhs was 0, emit 0
                     self.sealed_blocks.insert(false_id);
                     self.current_block = Some(false_id);
                     let false_var = self.new_var();
-                    self.blocks[false_id.0].instructions.push(Instruction::Copy {
+                    self.push_instruction(false_id, Instruction::Copy {
                         dest: false_var,
                         src: Operand::Constant(0),
                     });
-                    self.blocks[false_id.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(false_id, Terminator::Br(merge_id));
 
                     // rhs_id: lhs was truthy, evaluate rhs
                     self.sealed_blocks.insert(rhs_id);
                     self.current_block = Some(rhs_id);
                     let rhs_val = self.lower_expr(right)?;
+                    let rhs_type = self.get_expr_type(right);
+                    let rhs_val = self.lower_to_bool(rhs_val, &rhs_type);
                     let rhs_var = self.new_var();
-                    let rhs_bid = self.current_block.ok_or("LogicalAnd rhs outside block")?;
-                    self.blocks[rhs_bid.0].instructions.push(Instruction::Copy {
+                    let rhs_bid = self.require_block("LogicalAnd rhs outside block")?;
+                    self.push_instruction(rhs_bid, Instruction::Copy {
                         dest: rhs_var,
                         src: rhs_val,
                     });
-                    self.blocks[rhs_bid.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(rhs_bid, Terminator::Br(merge_id));
 
                     // merge_id: phi result
                     self.sealed_blocks.insert(merge_id);
                     self.current_block = Some(merge_id);
                     let result = self.new_var();
-                    self.blocks[merge_id.0].instructions.push(Instruction::Phi {
+                    self.push_instruction(merge_id, Instruction::Phi {
                         dest: result,
                         preds: vec![(false_id, false_var), (rhs_bid, rhs_var)],
                     });
@@ -150,42 +281,43 @@ impl Lowerer {
                 // If a != 0, result = 1; else result = b
                 if *op == BinaryOp::LogicalOr {
                     let lhs_val = self.lower_expr(left)?;
-                    let entry_bid = self.current_block.ok_or("LogicalOr outside block")?;
+                    let entry_bid = self.require_block("LogicalOr outside block")?;
 
                     let rhs_id  = self.new_block();
                     let true_id = self.new_block();
                     let merge_id = self.new_block();
 
-                    self.blocks[entry_bid.0].terminator =
-                        Terminator::cond_br(lhs_val, true_id, rhs_id);
+                    self.set_terminator(entry_bid, Terminator::cond_br(lhs_val, true_id, rhs_id));
 
                     // true_id: lhs was truthy, emit 1
                     self.sealed_blocks.insert(true_id);
                     self.current_block = Some(true_id);
                     let true_var = self.new_var();
-                    self.blocks[true_id.0].instructions.push(Instruction::Copy {
+                    self.push_instruction(true_id, Instruction::Copy {
                         dest: true_var,
                         src: Operand::Constant(1),
                     });
-                    self.blocks[true_id.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(true_id, Terminator::Br(merge_id));
 
                     // rhs_id: lhs was 0, evaluate rhs
                     self.sealed_blocks.insert(rhs_id);
                     self.current_block = Some(rhs_id);
                     let rhs_val = self.lower_expr(right)?;
+                    let rhs_type = self.get_expr_type(right);
+                    let rhs_val = self.lower_to_bool(rhs_val, &rhs_type);
                     let rhs_var = self.new_var();
-                    let rhs_bid = self.current_block.ok_or("LogicalOr rhs outside block")?;
-                    self.blocks[rhs_bid.0].instructions.push(Instruction::Copy {
+                    let rhs_bid = self.require_block("LogicalOr rhs outside block")?;
+                    self.push_instruction(rhs_bid, Instruction::Copy {
                         dest: rhs_var,
                         src: rhs_val,
                     });
-                    self.blocks[rhs_bid.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(rhs_bid, Terminator::Br(merge_id));
 
                     // merge_id: phi result
                     self.sealed_blocks.insert(merge_id);
                     self.current_block = Some(merge_id);
                     let result = self.new_var();
-                    self.blocks[merge_id.0].instructions.push(Instruction::Phi {
+                    self.push_instruction(merge_id, Instruction::Phi {
                         dest: result,
                         preds: vec![(true_id, true_var), (rhs_bid, rhs_var)],
                     });
@@ -201,6 +333,7 @@ impl Lowerer {
                 {
                     // 1. Get address of LHS
                     let addr = self.lower_to_addr(left)?;
+                    let volatile = self.is_volatile_access(left);
                     
                     // 2. Load current value of LHS
                     let lhs_type = self.get_expr_type(left);
@@ -210,10 +343,11 @@ impl Lowerer {
                         dest: curr_val_var,
                         addr: Operand::Var(addr),
                         value_type: lhs_type.clone(),
-                        volatile: false,
+                        volatile,
                     });
                     
                     // 3. Evaluate RHS
+                    let rhs_type = self.get_expr_type(right);
                     let rhs_val = self.lower_expr(right)?;
                     
                     // 4. Perform operation
@@ -265,35 +399,30 @@ impl Lowerer {
                             right: scaled_rhs,
                         });
                         res
-                    } else if self.is_float_type(&lhs_type) {
-                        let res = self.new_var();
-                        self.add_instruction(Instruction::FloatBinary {
-                            dest: res,
-                            op: binary_op,
-                            left: Operand::Var(curr_val_var),
-                            right: rhs_val,
-                        });
-                        res
+                    } else if self.is_float_type(&lhs_type) || self.is_float_type(&rhs_type) {
+                        let (res, res_type) = self.lower_float_binary(binary_op, Operand::Var(curr_val_var), &lhs_type, rhs_val, &rhs_type);
+                        match self.convert_arithmetic(Operand::Var(res), &res_type, &lhs_type) {
+                            Operand::Var(converted) => converted,
+                            _ => res,
+                        }
                     } else {
-                        let res = self.new_var();
-                        self.add_instruction(Instruction::Binary {
-                            dest: res,
-                            op: binary_op,
-                            left: Operand::Var(curr_val_var),
-                            right: rhs_val,
-                        });
-                        res
+                        let (res, res_type) = self.lower_int_binary(binary_op, Operand::Var(curr_val_var), &lhs_type, rhs_val, &rhs_type);
+                        match self.convert_arithmetic(Operand::Var(res), &res_type, &lhs_type) {
+                            Operand::Var(converted) => converted,
+                            _ => res,
+                        }
                     };
                     
                     // 5. Store result back to LHS
+                    let result = Operand::Var(result_var);
                     self.add_instruction(Instruction::Store {
                         addr: Operand::Var(addr),
-                        src: Operand::Var(result_var),
+                        src: result.clone(),
                         value_type: lhs_type,
-                        volatile: false,
+                        volatile,
                     });
                     
-                    return Ok(Operand::Var(result_var));
+                    return Ok(result);
                 }
 
                 let l_ty = self.get_expr_type(left);
@@ -375,27 +504,20 @@ impl Lowerer {
                     }
                 }
 
-                let dest = self.new_var();
                 // Check if this is a floating-point operation
                 if self.is_float_type(&l_ty) || self.is_float_type(&r_ty) {
-                    self.add_instruction(Instruction::FloatBinary {
-                        dest,
-                        op: op.clone(),
-                        left: l_val,
-                        right: r_val,
-                    });
-                } else {
-                    self.add_instruction(Instruction::Binary {
-                        dest,
-                        op: op.clone(),
-                        left: l_val,
-                        right: r_val,
-                    });
+                    let (dest, _) = self.lower_float_binary(op.clone(), l_val, &l_ty, r_val, &r_ty);
+                    return Ok(Operand::Var(dest));
                 }
+                let (dest, _) = self.lower_int_binary(op.clone(), l_val, &l_ty, r_val, &r_ty);
                 Ok(Operand::Var(dest))
             }
             AstExpr::Unary { op, expr: inner } if *op == UnaryOp::AddrOf => {
-                let addr = self.lower_to_addr(inner)?;
+                let addr = match inner.as_ref() {
+                    // `&a[len]` is a valid one-past-the-end pointer
+                    AstExpr::Index { array, index } => self.lower_index_to_addr(array, index, true)?,
+                    _ => self.lower_to_addr(inner)?,
+                };
                 Ok(Operand::Var(addr))
             }
             AstExpr::Variable(name) if self.enum_constants.contains_key(name) => {
@@ -404,7 +526,7 @@ impl Lowerer {
                 Ok(Operand::Constant(value))
             }
             AstExpr::Variable(name) if self.is_local(name) && !self.variable_allocas.contains_key(name) => {
-                let bid = self.current_block.ok_or("Variable access outside block")?;
+                let bid = self.require_block("Variable access outside block")?;
                 Ok(Operand::Var(self.read_variable(name, bid)))
             }
             AstExpr::Variable(name) if self.is_function(name) => {
@@ -485,17 +607,45 @@ impl Lowerer {
                 // Check for bitfield read
                 let bf_info = self.get_bitfield_info(expr);
                 let addr = self.lower_to_addr(expr)?;
-                let dest = self.new_var();
                 let value_type = self.get_expr_type(expr);
+                // An array element or member (`m[1]`, `s.name`) decays to its address
+                if matches!(self.resolve_type(&value_type), Type::Array(..)) {
+                    return Ok(Operand::Var(addr));
+                }
+                let volatile = self.is_volatile_access(expr);
+                let dest = self.new_var();
                 self.var_types.insert(dest, value_type.clone());
                 self.add_instruction(Instruction::Load {
                     dest,
                     addr: Operand::Var(addr),
                     value_type,
-                    volatile: false,
+                    volatile,
                 });
-                // If bitfield, extract the field: (loaded >> bit_offset) & mask
+                // If bitfield, extract the field: (loaded >> bit_offset) & mask,
+                // or for a signed field, shift its top bit to bit 63 and
+                // arithmetic-shift it back down, in a `long` so every backend
+                // shifts the same width, then convert to the promoted type
                 if let Some(bf) = bf_info {
+                    let field_type = self.scalar_type(&self.get_expr_type(expr));
+                    if !matches!(field_type, Type::Bool) && !model::TypeEnv::is_unsigned_integer(&field_type) {
+                        let mut val = dest;
+                        for (op, amount) in [
+                            (BinaryOp::ShiftLeft, 64 - bf.bit_offset - bf.bit_width),
+                            (BinaryOp::ShiftRight, 64 - bf.bit_width),
+                        ] {
+                            let shifted = self.new_var();
+                            self.var_types.insert(shifted, Type::Long);
+                            self.add_instruction(Instruction::Binary {
+                                dest: shifted,
+                                op,
+                                left: Operand::Var(val),
+                                right: Operand::Constant(amount as i64),
+                            });
+                            val = shifted;
+                        }
+                        let promoted = model::TypeEnv::integer_promotion(&field_type);
+                        return Ok(self.convert_arithmetic(Operand::Var(val), &Type::Long, &promoted));
+                    }
                     let shifted = if bf.bit_offset > 0 {
                         let sv = self.new_var();
                         self.add_instruction(Instruction::Binary {
@@ -508,7 +658,7 @@ impl Lowerer {
                     } else {
                         dest
                     };
-                    let mask = ((1u64 << bf.bit_width) - 1) as i64;
+                    let mask = bitfield_mask(bf.bit_width);
                     let masked = self.new_var();
                     self.add_instruction(Instruction::Binary {
                         dest: masked,
@@ -538,6 +688,7 @@ impl Lowerer {
                 
                 // 2. Get the address
                 let addr = self.lower_to_addr(expr)?;
+                let volatile = self.is_volatile_access(expr);
                 // 3. Load old value
                 let old_val_var = self.new_var();
                 self.var_types.insert(old_val_var, expr_type.clone());
@@ -545,7 +696,7 @@ impl Lowerer {
                     dest: old_val_var,
                     addr: Operand::Var(addr),
                     value_type: expr_type.clone(),
-                    volatile: false,
+                    volatile,
                 });
                 // 4. Compute new value (old + 1)
                 let new_val_var = self.new_var();
@@ -565,11 +716,12 @@ impl Lowerer {
                     });
                 }
                 // 5. Store new value back
+                let new_val = self.convert_for_store(Operand::Var(new_val_var), &Type::Int, &expr_type);
                 self.add_instruction(Instruction::Store {
                     addr: Operand::Var(addr),
-                    src: Operand::Var(new_val_var),
+                    src: new_val.clone(),
                     value_type: expr_type,
-                    volatile: false,
+                    volatile,
                 });
                 // 6. Return old value
                 Ok(Operand::Var(old_val_var))
@@ -592,6 +744,7 @@ impl Lowerer {
                 
                 // 2. Get the address
                 let addr = self.lower_to_addr(expr)?;
+                let volatile = self.is_volatile_access(expr);
                 // 3. Load old value
                 let old_val_var = self.new_var();
                 self.var_types.insert(old_val_var, expr_type.clone());
@@ -599,7 +752,7 @@ impl Lowerer {
                     dest: old_val_var,
                     addr: Operand::Var(addr),
                     value_type: expr_type.clone(),
-                    volatile: false,
+                    volatile,
                 });
                 // 4. Compute new value (old - 1)
                 let new_val_var = self.new_var();
@@ -619,11 +772,12 @@ impl Lowerer {
                     });
                 }
                 // 5. Store new value back
+                let new_val = self.convert_for_store(Operand::Var(new_val_var), &Type::Int, &expr_type);
                 self.add_instruction(Instruction::Store {
                     addr: Operand::Var(addr),
-                    src: Operand::Var(new_val_var),
+                    src: new_val.clone(),
                     value_type: expr_type,
-                    volatile: false,
+                    volatile,
                 });
                 // 6. Return old value
                 Ok(Operand::Var(old_val_var))
@@ -646,6 +800,7 @@ impl Lowerer {
                 
                 // 2. Get the address
                 let addr = self.lower_to_addr(expr)?;
+                let volatile = self.is_volatile_access(expr);
                 // 3. Load old value
                 let old_val_var = self.new_var();
                 self.var_types.insert(old_val_var, expr_type.clone());
@@ -653,7 +808,7 @@ impl Lowerer {
                     dest: old_val_var,
                     addr: Operand::Var(addr),
                     value_type: expr_type.clone(),
-                    volatile: false,
+                    volatile,
                 });
                 // 4. Compute new value (old + 1)
                 let new_val_var = self.new_var();
@@ -673,14 +828,15 @@ impl Lowerer {
                     });
                 }
                 // 5. Store new value back
+                let new_val = self.convert_for_store(Operand::Var(new_val_var), &Type::Int, &expr_type);
                 self.add_instruction(Instruction::Store {
                     addr: Operand::Var(addr),
-                    src: Operand::Var(new_val_var),
+                    src: new_val.clone(),
                     value_type: expr_type,
-                    volatile: false,
+                    volatile,
                 });
                 // 6. Return new value
-                Ok(Operand::Var(new_val_var))
+                Ok(new_val)
             }
             AstExpr::PrefixDecrement(expr) => {
                 // For prefix: return new value after modification
@@ -700,6 +856,7 @@ impl Lowerer {
                 
                 // 2. Get the address
                 let addr = self.lower_to_addr(expr)?;
+                let volatile = self.is_volatile_access(expr);
                 // 3. Load old value
                 let old_val_var = self.new_var();
                 self.var_types.insert(old_val_var, expr_type.clone());
@@ -707,7 +864,7 @@ impl Lowerer {
                     dest: old_val_var,
                     addr: Operand::Var(addr),
                     value_type: expr_type.clone(),
-                    volatile: false,
+                    volatile,
                 });
                 // 4. Compute new value (old - 1)
                 let new_val_var = self.new_var();
@@ -727,14 +884,15 @@ impl Lowerer {
                     });
                 }
                 // 5. Store new value back
+                let new_val = self.convert_for_store(Operand::Var(new_val_var), &Type::Int, &expr_type);
                 self.add_instruction(Instruction::Store {
                     addr: Operand::Var(addr),
-                    src: Operand::Var(new_val_var),
+                    src: new_val.clone(),
                     value_type: expr_type,
-                    volatile: false,
+                    volatile,
                 });
                 // 6. Return new value
-                Ok(Operand::Var(new_val_var))
+                Ok(new_val)
             }            AstExpr::Unary { op, expr } => {
                 let val = self.lower_expr(expr)?;
                 let dest = self.new_var();
@@ -754,9 +912,23 @@ impl Lowerer {
                 }
                 Ok(Operand::Var(dest))
             }
-            AstExpr::StringLiteral(s) => {
-                let label = format!("str_{}", self.global_strings.len());
-                self.global_strings.push((label.clone(), s.clone()));
+            AstExpr::StringLiteral(s) => Ok(Operand::Global(self.string_label(s))),
+            AstExpr::WideStringLiteral(s, encoding) => {
+                if let Some(g) = self.wide_strings.iter().find(|g| g.init.as_ref() == Some(expr)) {
+                    return Ok(Operand::Global(g.name.clone()));
+                }
+                let label = format!("wstr_{}", self.wide_strings.len());
+                let len = encoding.encode(s).len() + 1;
+                self.wide_strings.push(model::GlobalVar {
+                    r#type: Type::Array(Box::new(encoding.unit_type()), len),
+                    qualifiers: model::TypeQualifiers { is_const: true, ..Default::default() },
+                    name: label.clone(),
+                    init: Some(expr.clone()),
+                    attributes: Vec::new(),
+                    is_extern: false,
+                    is_static: true,
+                    span: model::Span::default(),
+                });
                 Ok(Operand::Global(label))
             }
             AstExpr::Call { func, args } => {
@@ -768,13 +940,13 @@ impl Lowerer {
                             
                             // Find index of second argument (last named parameter)
                             let arg_index = if let AstExpr::Variable(name) = &args[1] {
-                                *self.param_indices.get(name).ok_or(format!("__builtin_va_start argument '{}' must be a parameter name", name))?
+                                *self.param_indices.get(name).ok_or_else(|| CompileError::new(ErrorCode::InvalidOperand, format!("__builtin_va_start argument '{}' must be a parameter name", name)))?
                             } else {
-                                return Err("__builtin_va_start second argument must be a variable name".to_string());
+                                return Err(CompileError::new(ErrorCode::InvalidOperand, "__builtin_va_start second argument must be a variable name"));
                             };
                             
-                            let bid = self.current_block.ok_or("VaStart outside block")?;
-                            self.blocks[bid.0].instructions.push(Instruction::VaStart {
+                            let bid = self.require_block("VaStart outside block")?;
+                            self.push_instruction(bid, Instruction::VaStart {
                                 list: Operand::Var(list_addr),
                                 arg_index,
                             });
@@ -783,8 +955,8 @@ impl Lowerer {
                     } else if name == "__builtin_va_end" {
                         if !args.is_empty() {
                             let list_addr = self.lower_to_addr(&args[0])?;
-                            let bid = self.current_block.ok_or("VaEnd outside block")?;
-                            self.blocks[bid.0].instructions.push(Instruction::VaEnd {
+                            let bid = self.require_block("VaEnd outside block")?;
+                            self.push_instruction(bid, Instruction::VaEnd {
                                 list: Operand::Var(list_addr),
                             });
                             return Ok(Operand::Constant(0));
@@ -793,8 +965,8 @@ impl Lowerer {
                         if args.len() >= 2 {
                             let dest_addr = self.lower_to_addr(&args[0])?;
                             let src_val = self.lower_expr(&args[1])?;
-                            let bid = self.current_block.ok_or("VaCopy outside block")?;
-                            self.blocks[bid.0].instructions.push(Instruction::VaCopy {
+                            let bid = self.require_block("VaCopy outside block")?;
+                            self.push_instruction(bid, Instruction::VaCopy {
                                 dest: Operand::Var(dest_addr),
                                 src: src_val,
                             });
@@ -802,14 +974,14 @@ impl Lowerer {
                         }
                     } else if name == "__builtin_unreachable" {
                         // Mark this point as unreachable — emit an Unreachable terminator
-                        let bid = self.current_block.ok_or("Unreachable outside block")?;
-                        self.blocks[bid.0].terminator = Terminator::Unreachable;
+                        let bid = self.require_block("Unreachable outside block")?;
+                        self.set_terminator(bid, Terminator::Unreachable);
                         self.current_block = None;
                         return Ok(Operand::Constant(0));
                     } else if name == "__builtin_trap" {
                         // __builtin_trap() — abort execution; treat as unreachable
-                        let bid = self.current_block.ok_or("Trap outside block")?;
-                        self.blocks[bid.0].terminator = Terminator::Unreachable;
+                        let bid = self.require_block("Trap outside block")?;
+                        self.set_terminator(bid, Terminator::Unreachable);
                         self.current_block = None;
                         return Ok(Operand::Constant(0));
                     } else if matches!(
@@ -831,25 +1003,25 @@ impl Lowerer {
                                     let u = v as u64;
                                     match name.as_str() {
                                         "__builtin_clzl" | "__builtin_clzll" => {
-                                            if u == 0 { 64 } else { u.leading_zeros() as i64 }
+                                            if u == 0 { 64 } else { i64::from(u.leading_zeros()) }
                                         }
                                         "__builtin_ctzl" | "__builtin_ctzll" => {
-                                            if u == 0 { 64 } else { u.trailing_zeros() as i64 }
+                                            if u == 0 { 64 } else { i64::from(u.trailing_zeros()) }
                                         }
                                         "__builtin_popcountl" | "__builtin_popcountll" => {
-                                            u.count_ones() as i64
+                                            i64::from(u.count_ones())
                                         }
                                         _ => unreachable!(),
                                     }
                                 } else {
                                     match name.as_str() {
                                         "__builtin_clz" => {
-                                            if v == 0 { 32 } else { (v as u32).leading_zeros() as i64 }
+                                            if v == 0 { 32 } else { i64::from((v as u32).leading_zeros()) }
                                         }
                                         "__builtin_ctz" => {
-                                            if v == 0 { 32 } else { (v as u32).trailing_zeros() as i64 }
+                                            if v == 0 { 32 } else { i64::from((v as u32).trailing_zeros()) }
                                         }
-                                        "__builtin_popcount" => (v as u32).count_ones() as i64,
+                                        "__builtin_popcount" => i64::from((v as u32).count_ones()),
                                         _ => unreachable!(),
                                     }
                                 };
@@ -857,23 +1029,23 @@ impl Lowerer {
                             }
                             // For non-constant __builtin_abs, generate: (x ^ (x>>31)) - (x>>31)
                             if name == "__builtin_abs" {
-                                let bid = self.current_block.ok_or("abs outside block")?;
+                                let bid = self.require_block("abs outside block")?;
                                 let shift = self.new_var();
-                                self.blocks[bid.0].instructions.push(Instruction::Binary {
+                                self.push_instruction(bid, Instruction::Binary {
                                     dest: shift,
                                     op: BinaryOp::ShiftRight,
                                     left: val.clone(),
                                     right: Operand::Constant(31),
                                 });
                                 let xored = self.new_var();
-                                self.blocks[bid.0].instructions.push(Instruction::Binary {
+                                self.push_instruction(bid, Instruction::Binary {
                                     dest: xored,
                                     op: BinaryOp::BitwiseXor, 
                                     left: val,
                                     right: Operand::Var(shift),
                                 });
                                 let result = self.new_var();
-                                self.blocks[bid.0].instructions.push(Instruction::Binary {
+                                self.push_instruction(bid, Instruction::Binary {
                                     dest: result,
                                     op: BinaryOp::Sub,
                                     left: Operand::Var(xored),
@@ -888,12 +1060,13 @@ impl Lowerer {
                                     | "__builtin_clzl" | "__builtin_ctzl" | "__builtin_popcountl"
                                     | "__builtin_clzll" | "__builtin_ctzll" | "__builtin_popcountll"
                             ) {
-                                let bid = self.current_block.ok_or("builtin outside block")?;
+                                let bid = self.require_block("builtin outside block")?;
                                 let result = self.new_var();
-                                self.blocks[bid.0].instructions.push(Instruction::Call {
+                                self.push_instruction(bid, Instruction::Call {
                                     dest: Some(result),
                                     name: name.clone(),
                                     args: vec![val],
+                                    vararg_types: Vec::new(),
                                 });
                                 return Ok(Operand::Var(result));
                             }
@@ -904,8 +1077,8 @@ impl Lowerer {
                             let val = self.lower_expr(&args[0])?;
                             if let Operand::Constant(v) = val {
                                 let result = match name.as_str() {
-                                    "__builtin_bswap16" => (v as u16).swap_bytes() as i64,
-                                    "__builtin_bswap32" => (v as u32).swap_bytes() as i64,
+                                    "__builtin_bswap16" => i64::from((v as u16).swap_bytes()),
+                                    "__builtin_bswap32" => i64::from((v as u32).swap_bytes()),
                                     "__builtin_bswap64" => (v as u64).swap_bytes() as i64,
                                     _ => unreachable!(),
                                 };
@@ -919,12 +1092,13 @@ impl Lowerer {
                             let dest_arg = self.lower_expr(&args[0])?;
                             let src_arg = self.lower_expr(&args[1])?;
                             let size_arg = self.lower_expr(&args[2])?;
-                            let bid = self.current_block.ok_or("memcpy outside block")?;
+                            let bid = self.require_block("memcpy outside block")?;
                             let result = self.new_var();
-                            self.blocks[bid.0].instructions.push(Instruction::Call {
+                            self.push_instruction(bid, Instruction::Call {
                                 dest: Some(result),
                                 name: "memcpy".to_string(),
                                 args: vec![dest_arg, src_arg, size_arg],
+                                vararg_types: Vec::new(),
                             });
                             return Ok(Operand::Var(result));
                         }
@@ -934,22 +1108,24 @@ impl Lowerer {
                             let dest_arg = self.lower_expr(&args[0])?;
                             let c_arg = self.lower_expr(&args[1])?;
                             let size_arg = self.lower_expr(&args[2])?;
-                            let bid = self.current_block.ok_or("memset outside block")?;
+                            let bid = self.require_block("memset outside block")?;
                             let result = self.new_var();
-                            self.blocks[bid.0].instructions.push(Instruction::Call {
+                            self.push_instruction(bid, Instruction::Call {
                                 dest: Some(result),
                                 name: "memset".to_string(),
                                 args: vec![dest_arg, c_arg, size_arg],
+                                vararg_types: Vec::new(),
                             });
                             return Ok(Operand::Var(result));
                         }
                     } else if name == "__sync_synchronize" {
                         // Memory fence — emit as call for codegen
-                        let bid = self.current_block.ok_or("sync outside block")?;
-                        self.blocks[bid.0].instructions.push(Instruction::Call {
+                        let bid = self.require_block("sync outside block")?;
+                        self.push_instruction(bid, Instruction::Call {
                             dest: None,
                             name: "__sync_synchronize".to_string(),
                             args: vec![],
+                            vararg_types: Vec::new(),
                         });
                         return Ok(Operand::Constant(0));
                     } else if name.starts_with("__sync_") || name.starts_with("__atomic_") {
@@ -958,25 +1134,52 @@ impl Lowerer {
                         for arg in args {
                             ir_args.push(self.lower_expr(arg)?);
                         }
-                        let bid = self.current_block.ok_or("atomic outside block")?;
+                        let bid = self.require_block("atomic outside block")?;
                         let result = self.new_var();
-                        self.blocks[bid.0].instructions.push(Instruction::Call {
+                        self.push_instruction(bid, Instruction::Call {
                             dest: Some(result),
                             name: name.clone(),
                             args: ir_args,
+                            vararg_types: Vec::new(),
                         });
                         return Ok(Operand::Var(result));
                     }
                 }
 
+                // Parameter types of a known callee or function pointer, for
+                // argument conversions
+                let callee_type = match func.as_ref() {
+                    AstExpr::Variable(name) if self.function_types.contains_key(name) => self.function_types[name].clone(),
+                    _ => self.expand_typedefs(&self.get_expr_type(func)),
+                };
+                let param_types = match callee_type {
+                    Type::FunctionPointer { param_types, .. } => param_types,
+                    Type::Pointer(inner, ..) => match *inner {
+                        Type::FunctionPointer { param_types, .. } => param_types,
+                        _ => Vec::new(),
+                    },
+                    _ => Vec::new(),
+                };
                 let mut ir_args = Vec::new();
-                for arg in args {
-                    ir_args.push(self.lower_expr(arg)?);
+                let vararg_types: Vec<Type> = args.iter().skip(param_types.len())
+                    .map(|arg| self.promoted_arg_type(arg))
+                    .collect();
+                for (i, arg) in args.iter().enumerate() {
+                    if self.is_complex_expr(arg) || matches!(param_types.get(i), Some(Type::Complex(_))) {
+                        return Err(CompileError::new(ErrorCode::Unsupported, "passing _Complex arguments is not supported yet"));
+                    }
+                    let val = self.lower_value(arg)?;
+                    // An argument with no parameter to convert it to gets the
+                    // default argument promotions, the same for every backend
+                    let arg_type = self.get_expr_type(arg);
+                    let target = param_types.get(i).unwrap_or_else(|| &vararg_types[i - param_types.len()]);
+                    let val = self.convert_arithmetic(val, &arg_type, target);
+                    ir_args.push(val);
                 }
                 
                 // Re-read current_block AFTER lowering args, since ternary expressions
                 // in arguments can create new basic blocks and change current_block
-                let bid = self.current_block.ok_or("Call outside block")?;
+                let bid = self.require_block("Call outside block")?;
                 let dest = self.new_var();
                 
                 // Check if it's a direct call (function name) or indirect call (function pointer variable)
@@ -990,20 +1193,28 @@ impl Lowerer {
                 if is_direct_call {
                     // Direct call to a function
                     if let AstExpr::Variable(name) = func.as_ref() {
-                        self.blocks[bid.0].instructions.push(Instruction::Call {
+                        self.push_instruction(bid, Instruction::Call {
                             dest: Some(dest),
                             name: name.clone(),
                             args: ir_args,
+                            vararg_types,
                         });
                     }
                 } else {
                     // Indirect call through function pointer
+                    let is_variadic = match self.get_expr_type(func) {
+                        Type::FunctionPointer { is_variadic, .. } => is_variadic,
+                        Type::Pointer(inner, ..) => matches!(*inner, Type::FunctionPointer { is_variadic: true, .. }),
+                        _ => false,
+                    };
                     let func_ptr = self.lower_expr(func)?;
-                    let bid = self.current_block.ok_or("IndirectCall outside block")?;
-                    self.blocks[bid.0].instructions.push(Instruction::IndirectCall {
+                    let bid = self.require_block("IndirectCall outside block")?;
+                    self.push_instruction(bid, Instruction::IndirectCall {
                         dest: Some(dest),
                         func_ptr,
                         args: ir_args,
+                        is_variadic,
+                        vararg_types,
                     });
                 }
                 Ok(Operand::Var(dest))
@@ -1012,7 +1223,8 @@ impl Lowerer {
                 Ok(Operand::Constant(self.get_type_size(ty)))
             }
             AstExpr::SizeOfExpr(expr) => {
-                let expr_type = self.get_expr_type(expr);
+                // A string literal is an array here, not a pointer to its first char
+                let expr_type = model::TypeEnv::string_literal_type(expr).unwrap_or_else(|| self.get_expr_type(expr));
                 Ok(Operand::Constant(self.get_type_size(&expr_type)))
             }
             AstExpr::AlignOf(ty) => {
@@ -1020,153 +1232,95 @@ impl Lowerer {
             }
             AstExpr::Cast(ty, expr) => {
                 let src_val = self.lower_expr(expr)?;
-                // Check if this is a type conversion (not just a pointer cast)
-                let src_type = self.get_operand_type(&src_val)?;
-                
-                // If types are the same, no conversion needed
-                if &src_type == ty {
-                    return Ok(src_val);
-                }
-                
-                // Check if this requires a float<->int conversion
-                let src_is_float = matches!(src_type, Type::Float | Type::Double);
-                let dest_is_float = matches!(ty, Type::Float | Type::Double);
-                
-                if src_is_float != dest_is_float {
-                    // This is a float<->int conversion, generate a Copy instruction
-                    // The codegen layer will handle this specially  
-                    let dest = self.new_var();
-                    // Record the destination type
-                    self.var_types.insert(dest, ty.clone());
-                    
-                    let bid = self.current_block.ok_or("Cast outside block")?;
-                    self.blocks[bid.0].instructions.push(Instruction::Cast {
-                        dest,
-                        src: src_val,
-                        r#type: ty.clone(),
-                    });
-                    return Ok(Operand::Var(dest));
-                }
-                
-                // For other casts (int-to-int with different signedness or width),
-                // emit a Cast so the optimizer can fold with correct truncation/masking.
-                let is_int_type = |t: &Type| matches!(t,
-                    Type::Char | Type::UnsignedChar |
-                    Type::Short | Type::UnsignedShort |
-                    Type::Int | Type::UnsignedInt |
-                    Type::Long | Type::UnsignedLong |
-                    Type::LongLong | Type::UnsignedLongLong |
-                    Type::Enum(_)
-                );
-                if is_int_type(&src_type) && is_int_type(ty) && src_type != *ty {
-                    let dest = self.new_var();
-                    self.var_types.insert(dest, ty.clone());
-                    let bid = self.current_block.ok_or("Cast outside block")?;
-                    self.blocks[bid.0].instructions.push(Instruction::Cast {
-                        dest,
-                        src: src_val,
-                        r#type: ty.clone(),
-                    });
-                    return Ok(Operand::Var(dest));
-                }
-
-                // For pointer casts etc, just return the source value
-                Ok(src_val)
+                let src_type = self.get_expr_type(expr);
+                Ok(self.convert_arithmetic(src_val, &src_type, ty))
             }
             AstExpr::Conditional { condition, then_expr, else_expr } => {
                 // Evaluate condition in the current block.
                 let cond_val = self.lower_expr(condition)?;
-                let entry_bid = self.current_block.ok_or("Ternary outside block")?;
+                let entry_bid = self.require_block("Ternary outside block")?;
+                let result_type = self.get_expr_type(expr);
 
                 let then_id  = self.new_block();
                 let else_id  = self.new_block();
                 let merge_id = self.new_block();
 
-                self.blocks[entry_bid.0].terminator =
-                    Terminator::cond_br(cond_val, then_id, else_id);
+                self.set_terminator(entry_bid, Terminator::cond_br(cond_val, then_id, else_id));
+
+                // `c ? f() : (void)0` has no value to merge; the arms only
+                // run for their side effects.
+                if matches!(self.resolve_type(&result_type), Type::Void) {
+                    for (arm_id, arm) in [(then_id, then_expr), (else_id, else_expr)] {
+                        self.sealed_blocks.insert(arm_id);
+                        self.current_block = Some(arm_id);
+                        self.lower_expr(arm)?;
+                        let arm_bid = self.require_block("Ternary arm outside block")?;
+                        self.set_terminator(arm_bid, Terminator::Br(merge_id));
+                    }
+                    self.sealed_blocks.insert(merge_id);
+                    self.current_block = Some(merge_id);
+                    return Ok(Operand::Constant(0));
+                }
 
                 // Then branch – evaluate then_expr and materialise it into a var.
                 self.sealed_blocks.insert(then_id);
                 self.current_block = Some(then_id);
-                let then_operand = self.lower_expr(then_expr)?;
+                let then_operand = self.lower_value(then_expr)?;
+                let then_type = self.get_expr_type(then_expr);
+                let then_operand = self.convert_arithmetic(then_operand, &then_type, &result_type);
                 let then_var = self.new_var();
-                let then_bid = self.current_block.ok_or("Ternary then outside block")?;
-                self.blocks[then_bid.0].instructions.push(Instruction::Copy {
+                let then_bid = self.require_block("Ternary then outside block")?;
+                self.push_instruction(then_bid, Instruction::Copy {
                     dest: then_var,
                     src: then_operand,
                 });
-                self.blocks[then_bid.0].terminator = Terminator::Br(merge_id);
+                self.set_terminator(then_bid, Terminator::Br(merge_id));
 
                 // Else branch – evaluate else_expr and materialise it into a var.
                 self.sealed_blocks.insert(else_id);
                 self.current_block = Some(else_id);
-                let else_operand = self.lower_expr(else_expr)?;
+                let else_operand = self.lower_value(else_expr)?;
+                let else_type = self.get_expr_type(else_expr);
+                let else_operand = self.convert_arithmetic(else_operand, &else_type, &result_type);
                 let else_var = self.new_var();
-                let else_bid = self.current_block.ok_or("Ternary else outside block")?;
-                self.blocks[else_bid.0].instructions.push(Instruction::Copy {
+                let else_bid = self.require_block("Ternary else outside block")?;
+                self.push_instruction(else_bid, Instruction::Copy {
                     dest: else_var,
                     src: else_operand,
                 });
-                self.blocks[else_bid.0].terminator = Terminator::Br(merge_id);
+                self.set_terminator(else_bid, Terminator::Br(merge_id));
 
                 // Merge block – Phi to select the result.
                 self.sealed_blocks.insert(merge_id);
                 self.current_block = Some(merge_id);
                 let result = self.new_var();
+                if self.is_float_type(&result_type) {
+                    for var in [then_var, else_var, result] {
+                        self.var_types.insert(var, result_type.clone());
+                    }
+                }
                 let merge_bid = merge_id; // already known
-                self.blocks[merge_bid.0].instructions.push(Instruction::Phi {
+                self.push_instruction(merge_bid, Instruction::Phi {
                     dest: result,
                     preds: vec![(then_bid, then_var), (else_bid, else_var)],
                 });
                 Ok(Operand::Var(result))
             }
             AstExpr::CompoundLiteral { r#type, init } => {
-                // Compound literal: allocate anonymous local, initialize it,
-                // and return either a pointer (for aggregates) or the value.
-                let bid = self.current_block.ok_or("CompoundLiteral outside block")?;
-                let alloca = self.new_var();
-                let ty = r#type.clone();
-                self.blocks[bid.0].instructions.push(Instruction::Alloca {
-                    dest: alloca,
-                    r#type: ty.clone(),
-                });
-
-                // Dispatch to the correct init-list helper based on type.
-                match &ty {
-                    Type::Array(inner, _) => {
-                        let elem_size = self.get_type_size(inner);
-                        self.lower_init_list_to_stores(alloca, init, inner, elem_size, bid)?;
-                    }
-                    Type::Struct(_) | Type::Union(_) => {
-                        self.lower_struct_init_list(alloca, &ty, init, bid)?;
-                    }
-                    _ => {
-                        // Scalar compound literal, e.g. (int){42}
-                        if let Some(item) = init.first() {
-                            let val = self.lower_expr(&item.value)?;
-                            self.blocks[bid.0].instructions.push(Instruction::Store {
-                                addr: Operand::Var(alloca),
-                                src: val,
-                                value_type: ty.clone(),
-                                volatile: false,
-                            });
-                        }
-                    }
-                }
-
-                // For aggregates, the compound literal evaluates to the
-                // address of the temporary (like an array name).  For scalars,
-                // load the value back out.
-                match &ty {
-                    Type::Array(..) | Type::Struct(_) | Type::Union(_) => {
+                // An array compound literal evaluates to the address of the
+                // temporary (like an array name). Otherwise load the value
+                // back out.
+                let alloca = self.lower_compound_literal(r#type, init)?;
+                match self.expand_typedefs(r#type) {
+                    Type::Array(..) => {
                         Ok(Operand::Var(alloca))
                     }
                     _ => {
                         let result = self.new_var();
-                        self.blocks[bid.0].instructions.push(Instruction::Load {
+                        self.add_instruction(Instruction::Load {
                             dest: result,
                             addr: Operand::Var(alloca),
-                            value_type: ty,
+                            value_type: r#type.clone(),
                             volatile: false,
                         });
                         Ok(Operand::Var(result))
@@ -1177,7 +1331,7 @@ impl Lowerer {
                 // Comma operator: evaluate each sub-expression left to right,
                 // discarding all results except the last one.
                 if exprs.is_empty() {
-                    return Err("Empty comma expression".to_string());
+                    return Err(CompileError::new(ErrorCode::Unsupported, "Empty comma expression"));
                 }
                 let mut result = Operand::Constant(0);
                 for e in exprs {
@@ -1208,15 +1362,15 @@ impl Lowerer {
             AstExpr::InitList(_) => {
                 // InitList is handled specially during declaration lowering,
                 // not as a standalone expression.
-                Err("InitList expression cannot be lowered standalone; it must appear as a declaration initializer".to_string())
+                Err(CompileError::new(ErrorCode::InvalidInitializer, "InitList expression cannot be lowered standalone; it must appear as a declaration initializer"))
             }
             AstExpr::VaArg { list, r#type } => {
                 // __builtin_va_arg(ap, type) → IR VaArg instruction
                 let list_addr = self.lower_to_addr(list)?;
-                let bid = self.current_block.ok_or("VaArg outside block")?;
+                let bid = self.require_block("VaArg outside block")?;
                 let dest = self.new_var();
                 self.var_types.insert(dest, r#type.clone());
-                self.blocks[bid.0].instructions.push(Instruction::VaArg {
+                self.push_instruction(bid, Instruction::VaArg {
                     dest,
                     list: Operand::Var(list_addr),
                     r#type: r#type.clone(),
@@ -1228,7 +1382,7 @@ impl Lowerer {
                 let struct_name = match r#type {
                     Type::Struct(name) => name.clone(),
                     Type::Union(name) => name.clone(),
-                    _ => return Err(format!("__builtin_offsetof requires struct/union type, got {:?}", r#type)),
+                    _ => return Err(CompileError::new(ErrorCode::InvalidOperand, format!("__builtin_offsetof requires struct/union type, got '{}'", r#type))),
                 };
                 let (offset, _field_type, _) = self.get_member_offset(&struct_name, member);
                 Ok(Operand::Constant(offset))
@@ -1256,7 +1410,7 @@ impl Lowerer {
                 }
                 
                 let selected = matched_expr.or(default_expr)
-                    .ok_or("_Generic: no matching type and no default")?;
+                    .ok_or_else(|| CompileError::new(ErrorCode::IncompatibleTypes, "_Generic: no matching type and no default"))?;
                 self.lower_expr(selected)
             }
         }
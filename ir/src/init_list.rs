@@ -1,19 +1,127 @@
-use model::{BinaryOp, Type, Expr as AstExpr};
-use crate::types::{VarId, BlockId, Operand, Instruction};
+use model::{BinaryOp, CompileError, ErrorCode, Type, Expr as AstExpr};
+use crate::types::{VarId, Operand, Instruction};
 use crate::lowerer::Lowerer;
 
 /// Initializer list lowering implementation
 impl Lowerer {
+    /// Lower an initializer list for the array, struct, or union at `base_var`.
+    /// A list that leaves part of the object implicitly zero clears the whole
+    /// object with one memset first; explicit zeros are then not stored again.
+    pub(crate) fn lower_aggregate_init(
+        &mut self,
+        base_var: VarId,
+        ty: &Type,
+        items: &[model::InitItem],
+    ) -> Result<(), CompileError> {
+        let zeroed = !self.init_list_is_complete(ty, items);
+        if zeroed {
+            let size = self.get_type_size(ty) as usize;
+            self.add_instruction(Instruction::Memset {
+                dest: Operand::Var(base_var),
+                value: Operand::Constant(0),
+                size,
+            });
+        }
+        match ty {
+            Type::Array(inner, _) => {
+                let elem_size = self.get_type_size(inner);
+                self.lower_init_list_to_stores(base_var, items, inner, elem_size, zeroed)
+            }
+            _ => self.lower_struct_init_list(base_var, ty, items, zeroed),
+        }
+    }
+
+    /// Whether `items` gives every element or member of `ty` an explicit
+    /// value, in order. Designators, brace elision, bit-fields, and unions
+    /// are treated as incomplete.
+    fn init_list_is_complete(&self, ty: &Type, items: &[model::InitItem]) -> bool {
+        if items.iter().any(|item| item.designator.is_some()) {
+            return false;
+        }
+        let members: Vec<Type> = match ty {
+            Type::Array(inner, len) => vec![inner.as_ref().clone(); *len],
+            Type::Struct(name) => match self.struct_defs.get(name) {
+                Some(def) if def.fields.iter().all(|f| f.bit_width.is_none()) => {
+                    def.fields.iter().map(|f| f.field_type.clone()).collect()
+                }
+                _ => return false,
+            },
+            _ => return false,
+        };
+        members.len() == items.len()
+            && members.iter().zip(items).all(|(member, item)| match (&item.value, self.resolve_type(member)) {
+                (AstExpr::InitList(nested), member @ (Type::Array(..) | Type::Struct(_) | Type::Union(_))) => {
+                    self.init_list_is_complete(&member, nested)
+                }
+                (value, member) if self.is_whole_aggregate(value, &member) => true,
+                (_, Type::Array(..) | Type::Struct(_) | Type::Union(_)) => false,
+                _ => true,
+            })
+    }
+
+    /// Whether `expr` initializes a struct or union element as a whole
+    /// (`struct W w = {1, s};`), rather than its first scalar by brace
+    /// elision.
+    fn is_whole_aggregate(&self, expr: &AstExpr, member: &Type) -> bool {
+        matches!(member, Type::Struct(_) | Type::Union(_))
+            && self.resolve_type(&self.get_expr_type(expr)) == self.resolve_type(member)
+    }
+
+    /// Initialize the character array `ty` at `base_var` from a string
+    /// literal: its code units, then the terminator if it fits, then zeros
+    /// for the rest of the array. `zeroed` is as for init lists.
+    pub(crate) fn lower_string_init(&mut self, base_var: VarId, ty: &Type, units: Vec<u32>, zeroed: bool) {
+        let Type::Array(elem_type, len) = ty else { return };
+        let elem_size = self.get_type_size(elem_type);
+        let short = units.len() + 1 < *len;
+        if short && !zeroed {
+            self.add_instruction(Instruction::Memset {
+                dest: Operand::Var(base_var),
+                value: Operand::Constant(0),
+                size: (*len as i64 * elem_size) as usize,
+            });
+        }
+        let zeroed = zeroed || short;
+        for (i, unit) in units.into_iter().chain(std::iter::once(0)).take(*len).enumerate() {
+            if zeroed && unit == 0 {
+                continue;
+            }
+            let dest_var = self.init_offset_addr(base_var, i as i64 * elem_size);
+            self.add_instruction(Instruction::Store {
+                addr: Operand::Var(dest_var),
+                src: Operand::Constant(i64::from(unit)),
+                value_type: elem_type.as_ref().clone(),
+                volatile: false,
+            });
+        }
+    }
+
+    /// Address `offset` bytes past `base_var`.
+    fn init_offset_addr(&mut self, base_var: VarId, offset: i64) -> VarId {
+        if offset == 0 {
+            return base_var;
+        }
+        let offset_var = self.new_var();
+        self.add_instruction(Instruction::Binary {
+            dest: offset_var,
+            op: BinaryOp::Add,
+            left: Operand::Var(base_var),
+            right: Operand::Constant(offset),
+        });
+        offset_var
+    }
+
     /// Lower an array initializer list to a sequence of GEP+Store instructions.
-    /// `base_var` is the alloca'd array address.
-    pub(crate) fn lower_init_list_to_stores(
+    /// `base_var` is the alloca'd array address; `zeroed` means it was already
+    /// cleared, so zero elements need no store.
+    fn lower_init_list_to_stores(
         &mut self,
         base_var: VarId,
         items: &[model::InitItem],
         elem_type: &Type,
         elem_size: i64,
-        bid: BlockId,
-    ) -> Result<(), String> {
+        zeroed: bool,
+    ) -> Result<(), CompileError> {
         let mut positional = 0usize;
         for item in items {
             let indices: Vec<usize> = match &item.designator {
@@ -22,7 +130,7 @@ impl Lowerer {
                     (*start as usize..=*end as usize).collect()
                 }
                 Some(model::Designator::Field(_)) => {
-                    return Err("Field designator not valid in array initializer".to_string());
+                    return Err(CompileError::new(ErrorCode::InvalidInitializer, "Field designator not valid in array initializer"));
                 }
                 None => {
                     let idx = positional;
@@ -33,37 +141,39 @@ impl Lowerer {
 
             for index in indices {
             let byte_offset = (index as i64) * elem_size;
-            let dest_var = if byte_offset == 0 {
-                base_var
-            } else {
-                let offset_var = self.new_var();
-                self.blocks[bid.0].instructions.push(Instruction::Binary {
-                    dest: offset_var,
-                    op: BinaryOp::Add,
-                    left: Operand::Var(base_var),
-                    right: Operand::Constant(byte_offset),
-                });
-                offset_var
-            };
 
             // Handle nested init lists (e.g., 2D arrays or array of structs)
             match &item.value {
                 AstExpr::InitList(nested_items) => {
+                    let dest_var = self.init_offset_addr(base_var, byte_offset);
                     // For nested array: inner element type and size
                     match elem_type {
                         Type::Array(inner, _) => {
                             let inner_size = self.get_type_size(inner);
-                            self.lower_init_list_to_stores(dest_var, nested_items, inner, inner_size, bid)?;
+                            self.lower_init_list_to_stores(dest_var, nested_items, inner, inner_size, zeroed)?;
                         }
                         Type::Struct(_) | Type::Union(_) => {
-                            self.lower_struct_init_list(dest_var, elem_type, nested_items, bid)?;
+                            self.lower_struct_init_list(dest_var, elem_type, nested_items, zeroed)?;
                         }
-                        _ => return Err(format!("Nested init list for non-compound type {:?}", elem_type)),
+                        _ => return Err(CompileError::new(ErrorCode::InvalidInitializer, format!("Nested init list for non-compound type '{}'", elem_type))),
                     }
                 }
+                value if string_init_units(value, elem_type).is_some() => {
+                    let dest_var = self.init_offset_addr(base_var, byte_offset);
+                    let units = string_init_units(value, elem_type).unwrap_or_default();
+                    self.lower_string_init(dest_var, elem_type, units, zeroed);
+                }
+                value if self.is_whole_aggregate(value, elem_type) => {
+                    let dest_var = self.init_offset_addr(base_var, byte_offset);
+                    self.lower_aggregate_copy(Operand::Var(dest_var), value, elem_type)?;
+                }
                 _ => {
-                    let val = self.lower_expr(&item.value)?;
-                    self.blocks[bid.0].instructions.push(Instruction::Store {
+                    let val = self.lower_init_scalar(&item.value, elem_type)?;
+                    if zeroed && is_zero(&val) {
+                        continue;
+                    }
+                    let dest_var = self.init_offset_addr(base_var, byte_offset);
+                    self.add_instruction(Instruction::Store {
                         addr: Operand::Var(dest_var),
                         src: val,
                         value_type: elem_type.clone(),
@@ -76,19 +186,27 @@ impl Lowerer {
         Ok(())
     }
 
+    /// Lower one scalar initializer and convert it to the type of the element
+    /// or member it initializes, as an assignment would.
+    fn lower_init_scalar(&mut self, expr: &AstExpr, target: &Type) -> Result<Operand, CompileError> {
+        let val = self.lower_expr(expr)?;
+        let src_type = self.get_expr_type(expr);
+        Ok(self.convert_arithmetic(val, &src_type, target))
+    }
+
     /// Lower a struct/union initializer list to a sequence of GEP+Store instructions.
-    /// `base_var` is the alloca'd struct address.
-    pub(crate) fn lower_struct_init_list(
+    /// `base_var` is the alloca'd struct address; `zeroed` is as for arrays.
+    fn lower_struct_init_list(
         &mut self,
         base_var: VarId,
         struct_type: &Type,
         items: &[model::InitItem],
-        bid: BlockId,
-    ) -> Result<(), String> {
+        zeroed: bool,
+    ) -> Result<(), CompileError> {
         let type_name = match struct_type {
             Type::Struct(name) => name.clone(),
             Type::Union(name) => name.clone(),
-            _ => return Err(format!("Expected struct/union type, got {:?}", struct_type)),
+            _ => return Err(CompileError::new(ErrorCode::InvalidInitializer, format!("Expected struct/union type, got '{}'", struct_type))),
         };
 
         let is_union = matches!(struct_type, Type::Union(_));
@@ -97,7 +215,7 @@ impl Lowerer {
         } else if let Some(u_def) = self.union_defs.get(&type_name).cloned() {
             u_def.fields.clone()
         } else {
-            return Err(format!("Unknown struct/union type '{}'", type_name));
+            return Err(CompileError::new(ErrorCode::IncompleteType, format!("Unknown struct/union type '{}'", type_name)));
         };
 
         let mut field_idx = 0usize;
@@ -106,16 +224,23 @@ impl Lowerer {
             let target_idx = match &item.designator {
                 Some(model::Designator::Field(name)) => {
                     fields.iter().position(|f| &f.name == name)
-                        .ok_or_else(|| format!("No field '{}' in struct '{}'", name, type_name))?
+                        .ok_or_else(|| CompileError::new(ErrorCode::InvalidInitializer, format!("No field '{}' in struct '{}'", name, type_name)))?
                 }
                 Some(model::Designator::Index(_)) => {
-                    return Err("Index designator not valid in struct initializer".to_string());
+                    return Err(CompileError::new(ErrorCode::InvalidInitializer, "Index designator not valid in struct initializer"));
                 }
                 Some(model::Designator::Range { .. }) => {
-                    return Err("Range designator not valid in struct initializer".to_string());
+                    return Err(CompileError::new(ErrorCode::InvalidInitializer, "Range designator not valid in struct initializer"));
                 }
                 None => {
-                    let idx = field_idx;
+                    // Positional initializers skip unnamed bit-fields
+                    let mut idx = field_idx;
+                    while fields.get(idx).is_some_and(|f| f.name.is_empty() && f.bit_width.is_some()) {
+                        idx += 1;
+                    }
+                    if idx >= fields.len() {
+                        break;
+                    }
                     idx
                 }
             };
@@ -124,42 +249,49 @@ impl Lowerer {
             let field = &fields[target_idx];
 
             // For unions, all fields start at offset 0
-            let (offset, field_type) = if is_union {
-                (0i64, field.field_type.clone())
-            } else {
-                let (off, ty, _bf) = self.get_member_offset(&type_name, &field.name);
-                (off, ty)
-            };
-
-            let dest_var = if offset == 0 {
-                base_var
+            let (offset, field_type, bitfield) = if is_union {
+                (0i64, field.field_type.clone(), None)
             } else {
-                let offset_var = self.new_var();
-                self.blocks[bid.0].instructions.push(Instruction::Binary {
-                    dest: offset_var,
-                    op: BinaryOp::Add,
-                    left: Operand::Var(base_var),
-                    right: Operand::Constant(offset),
-                });
-                offset_var
+                self.get_member_offset(&type_name, &field.name)
             };
 
             match &item.value {
                 AstExpr::InitList(nested_items) => {
+                    let dest_var = self.init_offset_addr(base_var, offset);
                     match &field_type {
                         Type::Array(inner, _) => {
                             let inner_size = self.get_type_size(inner);
-                            self.lower_init_list_to_stores(dest_var, nested_items, inner, inner_size, bid)?;
+                            self.lower_init_list_to_stores(dest_var, nested_items, inner, inner_size, zeroed)?;
                         }
                         Type::Struct(_) | Type::Union(_) => {
-                            self.lower_struct_init_list(dest_var, &field_type, nested_items, bid)?;
+                            self.lower_struct_init_list(dest_var, &field_type, nested_items, zeroed)?;
                         }
-                        _ => return Err(format!("Nested init list for non-compound field type {:?}", field_type)),
+                        _ => return Err(CompileError::new(ErrorCode::InvalidInitializer, format!("Nested init list for non-compound field type '{}'", field_type))),
                     }
                 }
+                value if string_init_units(value, &field_type).is_some() => {
+                    let dest_var = self.init_offset_addr(base_var, offset);
+                    let units = string_init_units(value, &field_type).unwrap_or_default();
+                    self.lower_string_init(dest_var, &field_type, units, zeroed);
+                }
+                value if self.is_whole_aggregate(value, &field_type) => {
+                    let dest_var = self.init_offset_addr(base_var, offset);
+                    self.lower_aggregate_copy(Operand::Var(dest_var), value, &field_type)?;
+                }
                 _ => {
-                    let val = self.lower_expr(&item.value)?;
-                    self.blocks[bid.0].instructions.push(Instruction::Store {
+                    let val = self.lower_init_scalar(&item.value, &field_type)?;
+                    if zeroed && is_zero(&val) {
+                        if is_union {
+                            break;
+                        }
+                        continue;
+                    }
+                    let dest_var = self.init_offset_addr(base_var, offset);
+                    if let Some(bf) = bitfield {
+                        self.store_bitfield(dest_var, val, field_type.clone(), &bf);
+                        continue;
+                    }
+                    self.add_instruction(Instruction::Store {
                         addr: Operand::Var(dest_var),
                         src: val,
                         value_type: field_type.clone(),
@@ -176,3 +308,25 @@ impl Lowerer {
         Ok(())
     }
 }
+
+/// The code units of `expr` when it is a string literal initializing the
+/// character array `ty`.
+pub(crate) fn string_init_units(expr: &AstExpr, ty: &Type) -> Option<Vec<u32>> {
+    if !matches!(ty, Type::Array(..)) {
+        return None;
+    }
+    match expr {
+        AstExpr::StringLiteral(s) => Some(model::StringEncoding::Plain.encode(s)),
+        AstExpr::WideStringLiteral(s, encoding) => Some(encoding.encode(s)),
+        _ => None,
+    }
+}
+
+/// A constant whose bytes are all zero.
+fn is_zero(val: &Operand) -> bool {
+    match val {
+        Operand::Constant(c) => *c == 0,
+        Operand::FloatConstant(f) => f.to_bits() == 0,
+        _ => false,
+    }
+}
@@ -0,0 +1,1647 @@
+// IR interpreter
+//
+// Runs an `IRProgram` directly, so a program can be executed without an
+// assembler or linker. Memory is one flat byte array: an unmapped page at
+// address 0, then globals and string literals, then the stack, then the heap.
+// Every value is an `i64` or an `f64`; pointers are plain addresses into that
+// array. Function pointers and `&&label` addresses live in ranges above the
+// array, so loading through one traps like a native segfault.
+//
+// Calls to functions the program does not define go to a small libc shim
+// (`printf`, `puts`, `malloc`, `memcpy`, `strlen`, `exit`, ...). Anything the
+// interpreter cannot run (inline assembly, SIMD instructions, unknown
+// external functions) stops it with `InterpError::Unsupported`.
+//
+// Integer results are truncated to the width of their destination's type in
+// `var_types`, and comparisons, division, and right shifts are unsigned when
+// an operand has an unsigned type, following C rather than the codegen.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+use model::consteval::{ConstContext, ConstValue};
+use model::{BinaryOp, Designator, EnumDef, Expr, InitItem, StringEncoding, StructDef, Type, TypeLayout, UnaryOp, UnionDef};
+
+use crate::types::{BlockId, Function, IRProgram, Instruction, Operand, Terminator, VarId};
+
+/// First valid address; lower addresses trap as null dereferences.
+const NULL_PAGE: i64 = 0x1000;
+const STACK_SIZE: i64 = 8 << 20;
+const HEAP_LIMIT: i64 = 256 << 20;
+/// `FUNC_BASE + 16 * n` is the address of the n-th callable (defined or external).
+const FUNC_BASE: i64 = 0x7000_0000;
+/// `LABEL_BASE + block` is the address of a label taken with `&&label`.
+const LABEL_BASE: i64 = 0x7800_0000;
+/// `FILE_BASE + fd` is the `FILE *` for `stdin`, `stdout`, and `stderr`.
+const FILE_BASE: i64 = 0x7f00_0000;
+const MAX_CALL_DEPTH: usize = 100_000;
+
+const SIGILL: i32 = 4;
+const SIGABRT: i32 = 6;
+const SIGFPE: i32 = 8;
+const SIGSEGV: i32 = 11;
+
+/// Why the interpreter stopped before the program exited.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpError {
+    /// The program did something that kills a native process with `signal`,
+    /// such as dividing by zero or reading unmapped memory.
+    Trap { signal: i32, message: String },
+    /// The program uses something the interpreter cannot run.
+    Unsupported(String),
+}
+
+impl InterpError {
+    fn trap(signal: i32, message: impl Into<String>) -> Self {
+        InterpError::Trap { signal, message: message.into() }
+    }
+
+    /// The shell exit code of a native run that failed the same way
+    /// (`128 + signal`), or `None` if the program could not be run.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            InterpError::Trap { signal, .. } => Some(128 + signal),
+            InterpError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpError::Trap { signal, message } => write!(f, "{} (signal {})", message, signal),
+            InterpError::Unsupported(what) => write!(f, "cannot interpret: {}", what),
+        }
+    }
+}
+
+/// Run `main` of `program` as `program_name` with `args` as `argv[1..]`,
+/// writing the program's standard output to stdout. Returns the exit status,
+/// as a shell sees it.
+pub fn interpret(program: &IRProgram, program_name: &str, args: &[String]) -> Result<i32, InterpError> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let status = Interpreter::new(program, &mut out)?.run_main(program_name, args);
+    let _ = out.flush();
+    status
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    fn as_int(self) -> i64 {
+        match self {
+            Value::Int(v) => v,
+            Value::Float(f) => f as i64,
+        }
+    }
+
+    fn as_float(self) -> f64 {
+        match self {
+            Value::Int(v) => v as f64,
+            Value::Float(f) => f,
+        }
+    }
+
+    fn is_true(self) -> bool {
+        match self {
+            Value::Int(v) => v != 0,
+            Value::Float(f) => f != 0.0,
+        }
+    }
+}
+
+/// How execution stopped: `exit()` or an error.
+enum Stop {
+    Exit(i32),
+    Error(InterpError),
+}
+
+impl From<InterpError> for Stop {
+    fn from(e: InterpError) -> Self {
+        Stop::Error(e)
+    }
+}
+
+type Exec<T> = Result<T, Stop>;
+
+/// What the caller loop does after an instruction.
+enum Flow {
+    Next,
+    Call { func: usize, args: Vec<Value>, dest: Option<VarId> },
+}
+
+/// Per-function data computed once: block positions and the number of vars.
+struct FuncInfo {
+    block_index: HashMap<BlockId, usize>,
+    num_vars: usize,
+}
+
+struct Frame<'p> {
+    func: &'p Function,
+    info: usize,
+    vars: Vec<Value>,
+    /// Stack slots of the function's allocas and struct or union values,
+    /// reused each time one runs.
+    allocas: HashMap<VarId, i64>,
+    args: Vec<Value>,
+    block: usize,
+    ip: usize,
+    prev: Option<BlockId>,
+    stack_mark: i64,
+    /// The caller's variable receiving the return value.
+    ret_dest: Option<VarId>,
+}
+
+/// Source of `printf` arguments: call operands or a `va_list` cursor.
+enum VarArgs<'a> {
+    Values(std::slice::Iter<'a, Value>),
+    List(i64),
+}
+
+/// Interpreter state for one run of a program.
+pub struct Interpreter<'p, 'o> {
+    program: &'p IRProgram,
+    out: &'o mut dyn Write,
+    functions: HashMap<&'p str, usize>,
+    infos: Vec<FuncInfo>,
+    structs: HashMap<String, StructDef>,
+    unions: HashMap<String, UnionDef>,
+    enums: HashMap<String, EnumDef>,
+    enum_constants: HashMap<String, i64>,
+    mem: Vec<u8>,
+    /// Addresses of globals and string literals.
+    symbols: HashMap<String, i64>,
+    /// Names of the functions whose address was taken, indexed by callable id.
+    callables: Vec<String>,
+    callable_ids: HashMap<String, usize>,
+    stack_end: i64,
+    sp: i64,
+    heap_end: i64,
+    heap_blocks: HashMap<i64, i64>,
+}
+
+impl<'p, 'o> Interpreter<'p, 'o> {
+    /// Lay out and initialize the program's globals. Fails if an initializer
+    /// is not a constant the interpreter can evaluate.
+    pub fn new(program: &'p IRProgram, out: &'o mut dyn Write) -> Result<Self, InterpError> {
+        let functions = program.functions.iter().enumerate().map(|(i, f)| (f.name.as_str(), i)).collect();
+        let infos = program.functions.iter().map(func_info).collect();
+        let enum_constants = program
+            .enums
+            .iter()
+            .flat_map(|e| e.constants.iter().cloned())
+            .collect();
+        let mut interp = Interpreter {
+            program,
+            out,
+            functions,
+            infos,
+            structs: program.structs.iter().map(|s| (s.name.clone(), s.clone())).collect(),
+            unions: program.unions.iter().map(|u| (u.name.clone(), u.clone())).collect(),
+            enums: program.enums.iter().map(|e| (e.name.clone(), e.clone())).collect(),
+            enum_constants,
+            mem: vec![0; NULL_PAGE as usize],
+            symbols: HashMap::new(),
+            callables: Vec::new(),
+            callable_ids: HashMap::new(),
+            stack_end: 0,
+            sp: 0,
+            heap_end: 0,
+            heap_blocks: HashMap::new(),
+        };
+        interp.init_globals()?;
+
+        let stack_base = align_up(interp.mem.len() as i64, 16);
+        interp.sp = stack_base;
+        interp.stack_end = stack_base + STACK_SIZE;
+        interp.heap_end = interp.stack_end;
+        interp.mem.resize(interp.stack_end as usize, 0);
+        Ok(interp)
+    }
+
+    /// Call `main(argc, argv)` and return its exit status (`exit()`'s argument
+    /// or `main`'s return value, truncated to 8 bits as by a shell).
+    pub fn run_main(&mut self, program_name: &str, args: &[String]) -> Result<i32, InterpError> {
+        let main = *self
+            .functions
+            .get("main")
+            .ok_or_else(|| InterpError::Unsupported("program has no 'main' function".to_string()))?;
+
+        // argv strings and the argv array go on the heap
+        let mut argv = Vec::new();
+        for arg in std::iter::once(program_name).chain(args.iter().map(String::as_str)) {
+            let mut bytes = arg.as_bytes().to_vec();
+            bytes.push(0);
+            let addr = self.malloc(bytes.len() as i64);
+            self.write_bytes(addr, &bytes)?;
+            argv.push(addr);
+        }
+        let argv_addr = self.malloc(8 * (argv.len() as i64 + 1));
+        for (i, addr) in argv.iter().enumerate() {
+            self.write_int(argv_addr + 8 * i as i64, 8, *addr)?;
+        }
+
+        let program = self.program;
+        let with_attribute = |attr: model::Attribute| {
+            program.functions.iter().enumerate().filter(move |(_, f)| f.attributes.contains(&attr)).map(|(i, _)| i)
+        };
+        let mut result = with_attribute(model::Attribute::Constructor)
+            .try_for_each(|ctor| self.call(ctor, Vec::new()).map(drop))
+            .and_then(|()| self.call(main, vec![Value::Int(argv.len() as i64), Value::Int(argv_addr)]));
+        // Destructors run after main returns or calls exit()
+        if matches!(result, Ok(_) | Err(Stop::Exit(_))) {
+            if let Err(stop) = with_attribute(model::Attribute::Destructor).try_for_each(|dtor| self.call(dtor, Vec::new()).map(drop)) {
+                result = Err(stop);
+            }
+        }
+        let _ = self.out.flush();
+        let status = match result {
+            Ok(value) => value.as_int() as i32,
+            Err(Stop::Exit(code)) => code,
+            Err(Stop::Error(e)) => return Err(e),
+        };
+        Ok(status & 0xff)
+    }
+
+    // ─── Globals ────────────────────────────────────────────────
+
+    fn init_globals(&mut self) -> Result<(), InterpError> {
+        let program = self.program;
+        for (label, content) in &program.global_strings {
+            let addr = self.alloc_string(content, StringEncoding::Plain);
+            self.symbols.insert(label.clone(), addr);
+        }
+        for g in &program.globals {
+            if self.symbols.contains_key(&g.name) {
+                continue;
+            }
+            let layout = self.layout();
+            let size = layout.size_of(&g.r#type).max(1);
+            let mut align = layout.align_of(&g.r#type).max(1);
+            for attr in &g.attributes {
+                if let model::Attribute::Aligned(n) = attr {
+                    align = align.max(*n);
+                }
+            }
+            let addr = self.alloc_static(size, align);
+            self.symbols.insert(g.name.clone(), addr);
+        }
+        // The C library's standard streams, unless the program defines its own
+        for (fd, name) in ["stdin", "stdout", "stderr"].iter().enumerate() {
+            if !self.symbols.contains_key(*name) {
+                let addr = self.alloc_static(8, 8);
+                self.write_int(addr, 8, FILE_BASE + fd as i64)?;
+                self.symbols.insert(name.to_string(), addr);
+            }
+        }
+        for g in &program.globals {
+            if let Some(init) = &g.init {
+                let addr = self.symbols[&g.name];
+                self.write_init(addr, &g.r#type, init, &g.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn layout(&self) -> TypeLayout<'_> {
+        TypeLayout::new(&self.structs, &self.unions).with_enums(&self.enums)
+    }
+
+    fn size_of(&self, ty: &Type) -> i64 {
+        self.layout().size_of(ty) as i64
+    }
+
+    /// Reserve zeroed static storage (before the stack is laid out).
+    fn alloc_static(&mut self, size: usize, align: usize) -> i64 {
+        let addr = align_up(self.mem.len() as i64, align as i64);
+        self.mem.resize(addr as usize + size, 0);
+        addr
+    }
+
+    /// Lay out a string literal and its terminator in static storage.
+    fn alloc_string(&mut self, s: &str, encoding: StringEncoding) -> i64 {
+        let unit = self.size_of(&encoding.unit_type());
+        let units = encoding.encode(s);
+        let addr = self.alloc_static((units.len() + 1) * unit as usize, unit as usize);
+        for (i, code) in units.into_iter().enumerate() {
+            let bytes = i64::from(code).to_le_bytes();
+            let start = addr as usize + i * unit as usize;
+            self.mem[start..start + unit as usize].copy_from_slice(&bytes[..unit as usize]);
+        }
+        addr
+    }
+
+    /// Store the constant initializer `init` of an object of type `ty` at `addr`.
+    fn write_init(&mut self, addr: i64, ty: &Type, init: &Expr, global: &str) -> Result<(), InterpError> {
+        match (ty, init) {
+            (Type::Array(elem, len), Expr::StringLiteral(_) | Expr::WideStringLiteral(..)) => {
+                let units = match init {
+                    Expr::WideStringLiteral(s, encoding) => encoding.encode(s),
+                    Expr::StringLiteral(s) => StringEncoding::Plain.encode(s),
+                    _ => unreachable!(),
+                };
+                let elem_size = self.size_of(elem);
+                for (i, code) in units.into_iter().chain([0]).take(*len).enumerate() {
+                    self.write_int(addr + i as i64 * elem_size, elem_size, i64::from(code))?;
+                }
+                Ok(())
+            }
+            (Type::Array(elem, len), Expr::InitList(items)) => {
+                let elem_size = self.size_of(elem);
+                let mut pos = 0i64;
+                for item in items {
+                    let (first, last) = match &item.designator {
+                        None => (pos, pos),
+                        Some(Designator::Index(i)) => (*i, *i),
+                        Some(Designator::Range { start, end }) => (*start, *end),
+                        Some(Designator::Field(_)) => {
+                            return Err(unsupported_init(global));
+                        }
+                    };
+                    for i in first..=last.min(*len as i64 - 1) {
+                        self.write_init(addr + i * elem_size, elem, &item.value, global)?;
+                    }
+                    pos = last + 1;
+                }
+                Ok(())
+            }
+            (Type::Struct(name), Expr::InitList(items)) => {
+                let Some(s_def) = self.structs.get(name).cloned() else {
+                    return Err(unsupported_init(global));
+                };
+                let placements = self.layout().struct_layout(&s_def).fields;
+                let mut next = 0usize;
+                for item in items {
+                    let idx = match &item.designator {
+                        Some(Designator::Field(f)) => s_def.fields.iter().position(|field| &field.name == f),
+                        _ => (next..s_def.fields.len()).find(|&i| !(s_def.fields[i].name.is_empty() && s_def.fields[i].bit_width.is_some())),
+                    };
+                    let Some(idx) = idx else { break };
+                    next = idx + 1;
+                    let field_addr = addr + placements[idx].offset as i64;
+                    match &placements[idx].bitfield {
+                        Some(bf) => {
+                            let value = self.eval_const(&item.value, global)?.as_int();
+                            self.write_bits(field_addr, bf.bit_offset, bf.bit_width, value)?;
+                        }
+                        None => self.write_init(field_addr, &s_def.fields[idx].field_type, &item.value, global)?,
+                    }
+                }
+                Ok(())
+            }
+            (Type::Union(name), Expr::InitList(items)) => {
+                let Some(u_def) = self.unions.get(name).cloned() else {
+                    return Err(unsupported_init(global));
+                };
+                let Some(item) = items.first() else { return Ok(()) };
+                let field = match &item.designator {
+                    Some(Designator::Field(f)) => u_def.fields.iter().find(|field| &field.name == f),
+                    _ => u_def.fields.first(),
+                };
+                match field {
+                    Some(field) => self.write_init(addr, &field.field_type, &item.value, global),
+                    None => Ok(()),
+                }
+            }
+            // A scalar in braces, or the first element of an aggregate
+            (_, Expr::InitList(items)) => match items.first() {
+                Some(InitItem { value, .. }) => self.write_init(addr, ty, value, global),
+                None => Ok(()),
+            },
+            // Without braces, a scalar initializes the first member
+            (Type::Array(..) | Type::Struct(_) | Type::Union(_), _) => {
+                let first = match ty {
+                    Type::Array(elem, _) => Some(elem.as_ref().clone()),
+                    Type::Struct(name) => self.structs.get(name).and_then(|s| s.fields.first()).map(|f| f.field_type.clone()),
+                    Type::Union(name) => self.unions.get(name).and_then(|u| u.fields.first()).map(|f| f.field_type.clone()),
+                    _ => None,
+                };
+                match first {
+                    Some(first) => self.write_init(addr, &first, init, global),
+                    None => Err(unsupported_init(global)),
+                }
+            }
+            _ => {
+                let value = self.eval_const(init, global)?;
+                self.store(addr, ty, value)
+            }
+        }
+    }
+
+    /// Evaluate a constant expression of a global initializer. Arithmetic
+    /// goes through `model::consteval`, as in codegen, so the value is
+    /// converted to its C type at each step; addresses and strings are
+    /// handled here.
+    fn eval_const(&mut self, expr: &Expr, global: &str) -> Result<Value, InterpError> {
+        if let Ok(value) = model::consteval::eval_constant(expr, &*self) {
+            return Ok(match value {
+                ConstValue::Int(c, _) => Value::Int(c),
+                ConstValue::Float(f) => Value::Float(f),
+            });
+        }
+        Ok(match expr {
+            Expr::Constant(c) | Expr::TypedConstant(c, _) => Value::Int(*c),
+            Expr::FloatConstant(f) => Value::Float(*f),
+            Expr::StringLiteral(s) => Value::Int(self.alloc_string(s, StringEncoding::Plain)),
+            Expr::WideStringLiteral(s, encoding) => Value::Int(self.alloc_string(s, *encoding)),
+            Expr::SizeOf(ty) => Value::Int(self.size_of(ty)),
+            Expr::AlignOf(ty) => Value::Int(self.layout().align_of(ty) as i64),
+            Expr::Variable(name) => match self.enum_constants.get(name) {
+                Some(v) => Value::Int(*v),
+                None => self.address_of(name, global)?,
+            },
+            Expr::Unary { op: UnaryOp::AddrOf, expr } => match expr.as_ref() {
+                Expr::Variable(name) => self.address_of(name, global)?,
+                _ => return Err(unsupported_init(global)),
+            },
+            Expr::Unary { op, expr } => match (op, self.eval_const(expr, global)?) {
+                (UnaryOp::Plus, v) => v,
+                (UnaryOp::Minus, Value::Float(f)) => Value::Float(-f),
+                (UnaryOp::Minus, Value::Int(v)) => Value::Int(v.wrapping_neg()),
+                (UnaryOp::BitwiseNot, v) => Value::Int(!v.as_int()),
+                (UnaryOp::LogicalNot, v) => Value::Int(i64::from(!v.is_true())),
+                _ => return Err(unsupported_init(global)),
+            },
+            Expr::Binary { left, op, right } => {
+                let l = self.eval_const(left, global)?;
+                let r = self.eval_const(right, global)?;
+                match (l, r) {
+                    (Value::Int(l), Value::Int(r)) => Value::Int(int_binary(op, l, r, false).map_err(|_| unsupported_init(global))?),
+                    _ => float_binary(op, l.as_float(), r.as_float()).map_err(|_| unsupported_init(global))?,
+                }
+            }
+            Expr::Cast(ty, expr) => {
+                let value = self.eval_const(expr, global)?;
+                convert(value, ty, false)
+            }
+            Expr::Conditional { condition, then_expr, else_expr } => {
+                if self.eval_const(condition, global)?.is_true() {
+                    self.eval_const(then_expr, global)?
+                } else {
+                    self.eval_const(else_expr, global)?
+                }
+            }
+            _ => return Err(unsupported_init(global)),
+        })
+    }
+
+    /// Address of a global or function named in an initializer.
+    fn address_of(&mut self, name: &str, global: &str) -> Result<Value, InterpError> {
+        if let Some(addr) = self.symbols.get(name) {
+            Ok(Value::Int(*addr))
+        } else if self.functions.contains_key(name) {
+            Ok(Value::Int(self.callable_address(name)))
+        } else {
+            Err(unsupported_init(global))
+        }
+    }
+
+    fn callable_address(&mut self, name: &str) -> i64 {
+        let id = match self.callable_ids.get(name) {
+            Some(id) => *id,
+            None => {
+                self.callables.push(name.to_string());
+                self.callable_ids.insert(name.to_string(), self.callables.len() - 1);
+                self.callables.len() - 1
+            }
+        };
+        FUNC_BASE + 16 * id as i64
+    }
+
+    // ─── Memory ─────────────────────────────────────────────────
+
+    fn check(&self, addr: i64, len: i64) -> Result<usize, InterpError> {
+        if addr < NULL_PAGE || len < 0 || addr.checked_add(len).is_none_or(|end| end > self.mem.len() as i64) {
+            return Err(InterpError::trap(SIGSEGV, format!("invalid memory access of {} bytes at {:#x}", len, addr)));
+        }
+        Ok(addr as usize)
+    }
+
+    fn read_bytes(&self, addr: i64, len: i64) -> Result<&[u8], InterpError> {
+        let start = self.check(addr, len)?;
+        Ok(&self.mem[start..start + len as usize])
+    }
+
+    fn write_bytes(&mut self, addr: i64, bytes: &[u8]) -> Result<(), InterpError> {
+        let start = self.check(addr, bytes.len() as i64)?;
+        self.mem[start..start + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_int(&self, addr: i64, size: i64, signed: bool) -> Result<i64, InterpError> {
+        let bytes = self.read_bytes(addr, size)?;
+        let mut buf = [0u8; 8];
+        buf[..size as usize].copy_from_slice(bytes);
+        let raw = i64::from_le_bytes(buf);
+        Ok(match (size, signed) {
+            (1, true) => i64::from(raw as i8),
+            (2, true) => i64::from(raw as i16),
+            (4, true) => i64::from(raw as i32),
+            _ => raw,
+        })
+    }
+
+    fn write_int(&mut self, addr: i64, size: i64, value: i64) -> Result<(), InterpError> {
+        let bytes = value.to_le_bytes();
+        self.write_bytes(addr, &bytes[..size as usize])
+    }
+
+    fn write_bits(&mut self, addr: i64, bit_offset: usize, width: usize, value: i64) -> Result<(), InterpError> {
+        for bit in 0..width {
+            let pos = bit_offset + bit;
+            let byte_addr = addr + (pos / 8) as i64;
+            let mut byte = self.read_bytes(byte_addr, 1)?[0];
+            if (value >> bit) & 1 != 0 {
+                byte |= 1 << (pos % 8);
+            } else {
+                byte &= !(1 << (pos % 8));
+            }
+            self.write_bytes(byte_addr, &[byte])?;
+        }
+        Ok(())
+    }
+
+    fn read_cstr(&self, addr: i64) -> Result<Vec<u8>, InterpError> {
+        let start = self.check(addr, 1)?;
+        match self.mem[start..].iter().position(|&b| b == 0) {
+            Some(len) => Ok(self.mem[start..start + len].to_vec()),
+            None => Err(InterpError::trap(SIGSEGV, format!("unterminated string at {:#x}", addr))),
+        }
+    }
+
+    /// Load a value of type `ty`, extending it to 64 bits.
+    fn load(&self, addr: i64, ty: &Type) -> Result<Value, InterpError> {
+        Ok(match ty {
+            Type::Float => Value::Float(f64::from(f32::from_le_bytes(self.read_bytes(addr, 4)?.try_into().unwrap()))),
+            Type::Double => Value::Float(f64::from_le_bytes(self.read_bytes(addr, 8)?.try_into().unwrap())),
+            _ => Value::Int(self.read_int(addr, scalar_size(ty), !is_unsigned(ty))?),
+        })
+    }
+
+    fn store(&mut self, addr: i64, ty: &Type, value: Value) -> Result<(), InterpError> {
+        match ty {
+            Type::Float => self.write_bytes(addr, &(value.as_float() as f32).to_le_bytes()),
+            Type::Double => self.write_bytes(addr, &value.as_float().to_le_bytes()),
+            Type::Bool => self.write_int(addr, 1, i64::from(value.is_true())),
+            _ => self.write_int(addr, scalar_size(ty), value.as_int()),
+        }
+    }
+
+    /// The stack slot of `frame` for an object of type `ty` that `var`
+    /// addresses, reserved the first time and reused after.
+    fn frame_slot(&mut self, frame: &mut Frame<'p>, var: VarId, ty: &Type) -> Result<i64, InterpError> {
+        if let Some(&addr) = frame.allocas.get(&var) {
+            return Ok(addr);
+        }
+        let layout = self.layout();
+        let size = (layout.size_of(ty) as i64).max(1);
+        let align = (layout.align_of(ty) as i64).max(1);
+        let addr = align_up(self.sp, align);
+        if addr + size > self.stack_end {
+            return Err(InterpError::trap(SIGSEGV, "stack overflow"));
+        }
+        self.sp = addr + size;
+        frame.allocas.insert(var, addr);
+        Ok(addr)
+    }
+
+    fn copy_bytes(&mut self, dest: i64, src: i64, size: i64) -> Result<(), InterpError> {
+        let bytes = self.read_bytes(src, size)?.to_vec();
+        self.write_bytes(dest, &bytes)
+    }
+
+    /// Bump-allocate `size` bytes of heap, or return 0 when the heap is full.
+    fn malloc(&mut self, size: i64) -> i64 {
+        let addr = align_up(self.heap_end, 16);
+        let size = size.max(1);
+        if size > HEAP_LIMIT || addr + size - self.stack_end > HEAP_LIMIT {
+            return 0;
+        }
+        self.heap_end = addr + size;
+        self.mem.resize(self.heap_end as usize, 0);
+        self.heap_blocks.insert(addr, size);
+        addr
+    }
+
+    // ─── Execution ──────────────────────────────────────────────
+
+    fn new_frame(&mut self, func: usize, args: Vec<Value>, ret_dest: Option<VarId>) -> Frame<'p> {
+        let f = &self.program.functions[func];
+        let mut vars = vec![Value::Int(0); self.infos[func].num_vars];
+        for ((_, var), arg) in f.params.iter().zip(&args) {
+            vars[var.0] = *arg;
+        }
+        Frame {
+            func: f,
+            info: func,
+            vars,
+            allocas: HashMap::new(),
+            args,
+            block: self.infos[func].block_index.get(&f.entry_block).copied().unwrap_or(0),
+            ip: 0,
+            prev: None,
+            stack_mark: self.sp,
+            ret_dest,
+        }
+    }
+
+    /// Run function `func` to completion and return its result.
+    fn call(&mut self, func: usize, args: Vec<Value>) -> Exec<Value> {
+        let first = self.new_frame(func, args, None);
+        let mut frames = vec![first];
+        loop {
+            let frame = frames.last_mut().unwrap();
+            let block = &frame.func.blocks[frame.block];
+            if let Some(inst) = block.instructions.get(frame.ip) {
+                frame.ip += 1;
+                if let Flow::Call { func, args, dest } = self.step(frame, inst)? {
+                    if frames.len() >= MAX_CALL_DEPTH {
+                        return Err(InterpError::trap(SIGSEGV, "stack overflow").into());
+                    }
+                    let callee = self.new_frame(func, args, dest);
+                    frames.push(callee);
+                }
+                continue;
+            }
+            match &block.terminator {
+                Terminator::Br(target) => {
+                    let target = *target;
+                    self.enter_block(frame, target)?;
+                }
+                Terminator::CondBr { cond, then_block, else_block, .. } => {
+                    let target = if self.operand(frame, cond)?.is_true() { *then_block } else { *else_block };
+                    self.enter_block(frame, target)?;
+                }
+                Terminator::Switch { value, cases, default } => {
+                    let value = self.operand(frame, value)?.as_int();
+                    let target = cases.iter().find(|(case, _)| *case == value).map_or(*default, |&(_, block)| block);
+                    self.enter_block(frame, target)?;
+                }
+                Terminator::IndirectBr { target } => {
+                    let addr = self.operand(frame, target)?.as_int();
+                    self.enter_block(frame, BlockId(addr.wrapping_sub(LABEL_BASE) as usize))?;
+                }
+                Terminator::Unreachable => {
+                    return Err(InterpError::trap(SIGILL, format!("reached unreachable code in '{}'", frame.func.name)).into());
+                }
+                Terminator::Ret(value) => {
+                    let return_type = &frame.func.return_type;
+                    let value = match value {
+                        Some(op) => return_value(return_type, self.operand(frame, op)?),
+                        None => Value::Int(0),
+                    };
+                    // A struct or union outlives the callee's frame as a copy in the caller's
+                    let aggregate = match value {
+                        Value::Int(addr) if is_aggregate(return_type) => Some(self.read_bytes(addr, self.size_of(return_type))?.to_vec()),
+                        _ => None,
+                    };
+                    self.sp = frame.stack_mark;
+                    let done = frames.pop().unwrap();
+                    match frames.last_mut() {
+                        Some(caller) => {
+                            if let Some(dest) = done.ret_dest {
+                                caller.vars[dest.0] = match aggregate {
+                                    Some(bytes) => {
+                                        let copy = self.frame_slot(caller, dest, &done.func.return_type)?;
+                                        self.write_bytes(copy, &bytes)?;
+                                        Value::Int(copy)
+                                    }
+                                    None => value,
+                                };
+                            }
+                        }
+                        None => return Ok(value),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Branch to `target`, assigning its phis from the current block.
+    fn enter_block(&mut self, frame: &mut Frame<'p>, target: BlockId) -> Result<(), InterpError> {
+        let from = frame.func.blocks[frame.block].id;
+        let Some(&index) = self.infos[frame.info].block_index.get(&target) else {
+            return Err(InterpError::trap(SIGSEGV, format!("jump to unknown block bb{} in '{}'", target.0, frame.func.name)));
+        };
+        let mut incoming = Vec::new();
+        for inst in &frame.func.blocks[index].instructions {
+            let Instruction::Phi { dest, preds } = inst else { break };
+            let value = phi_value(frame, preds, from)?;
+            incoming.push((*dest, value));
+        }
+        frame.ip = incoming.len();
+        for (dest, value) in incoming {
+            frame.vars[dest.0] = value;
+        }
+        frame.block = index;
+        frame.prev = Some(from);
+        Ok(())
+    }
+
+    fn operand(&mut self, frame: &Frame<'p>, op: &Operand) -> Result<Value, InterpError> {
+        Ok(match op {
+            Operand::Constant(c) => Value::Int(*c),
+            Operand::FloatConstant(f) => Value::Float(*f),
+            Operand::Var(v) => frame.vars[v.0],
+            Operand::Global(name) => {
+                if let Some(addr) = self.symbols.get(name) {
+                    Value::Int(*addr)
+                } else if let Some(label) = name.strip_prefix("__label_addr_") {
+                    match frame.func.labels.get(label) {
+                        Some(block) => Value::Int(LABEL_BASE + block.0 as i64),
+                        None => return Err(InterpError::Unsupported(format!("address of unknown label '{}'", label))),
+                    }
+                } else {
+                    Value::Int(self.callable_address(name))
+                }
+            }
+        })
+    }
+
+    fn var_type(frame: &Frame<'p>, op: &Operand) -> Option<&'p Type> {
+        match op {
+            Operand::Var(v) => frame.func.var_types.get(v),
+            _ => None,
+        }
+    }
+
+    fn step(&mut self, frame: &mut Frame<'p>, inst: &'p Instruction) -> Exec<Flow> {
+        match inst {
+            Instruction::Binary { dest, op, left, right } => {
+                let l = self.operand(frame, left)?;
+                let r = self.operand(frame, right)?;
+                let unsigned = if *op == BinaryOp::ShiftRight {
+                    Self::var_type(frame, left).is_some_and(is_unsigned)
+                } else {
+                    Self::var_type(frame, left).is_some_and(is_unsigned) || Self::var_type(frame, right).is_some_and(is_unsigned)
+                };
+                let value = int_binary(op, l.as_int(), r.as_int(), unsigned)?;
+                frame.vars[dest.0] = Value::Int(narrow(frame.func.var_types.get(dest), value));
+            }
+            Instruction::FloatBinary { dest, op, left, right } => {
+                let l = self.operand(frame, left)?.as_float();
+                let r = self.operand(frame, right)?.as_float();
+                let value = float_binary(op, l, r)?;
+                frame.vars[dest.0] = match (value, frame.func.var_types.get(dest)) {
+                    (Value::Float(f), Some(Type::Float)) => Value::Float(f64::from(f as f32)),
+                    (value, _) => value,
+                };
+            }
+            Instruction::Unary { dest, op, src } => {
+                let v = self.operand(frame, src)?.as_int();
+                let value = match op {
+                    UnaryOp::Plus => v,
+                    UnaryOp::Minus => v.wrapping_neg(),
+                    UnaryOp::BitwiseNot => !v,
+                    UnaryOp::LogicalNot => i64::from(v == 0),
+                    UnaryOp::AddrOf | UnaryOp::Deref => {
+                        return Err(InterpError::Unsupported(format!("unary {:?} instruction", op)).into());
+                    }
+                };
+                frame.vars[dest.0] = Value::Int(narrow(frame.func.var_types.get(dest), value));
+            }
+            Instruction::FloatUnary { dest, op, src } => {
+                let v = self.operand(frame, src)?.as_float();
+                frame.vars[dest.0] = match op {
+                    UnaryOp::Minus => Value::Float(-v),
+                    UnaryOp::LogicalNot => Value::Int(i64::from(v == 0.0)),
+                    _ => Value::Float(v),
+                };
+            }
+            Instruction::Phi { dest, preds } => {
+                let from = frame.prev.unwrap_or(frame.func.entry_block);
+                frame.vars[dest.0] = phi_value(frame, preds, from)?;
+            }
+            Instruction::Copy { dest, src } => {
+                frame.vars[dest.0] = self.operand(frame, src)?;
+            }
+            Instruction::Cast { dest, src, src_type, r#type } => {
+                let value = self.operand(frame, src)?;
+                frame.vars[dest.0] = convert(value, r#type, is_unsigned(src_type));
+            }
+            Instruction::Alloca { dest, r#type } => {
+                let addr = self.frame_slot(frame, *dest, r#type)?;
+                let size = self.size_of(r#type).max(1);
+                self.mem[addr as usize..(addr + size) as usize].fill(0);
+                frame.vars[dest.0] = Value::Int(addr);
+            }
+            Instruction::Load { dest, addr, value_type, .. } => {
+                let addr = self.operand(frame, addr)?.as_int();
+                frame.vars[dest.0] = if is_aggregate(value_type) {
+                    // A struct or union value is the address of a copy of it
+                    let copy = self.frame_slot(frame, *dest, value_type)?;
+                    self.copy_bytes(copy, addr, self.size_of(value_type))?;
+                    Value::Int(copy)
+                } else {
+                    self.load(addr, value_type)?
+                };
+            }
+            Instruction::Store { addr, src, value_type, .. } => {
+                let addr = self.operand(frame, addr)?.as_int();
+                let value = self.operand(frame, src)?;
+                if is_aggregate(value_type) {
+                    self.copy_bytes(addr, value.as_int(), self.size_of(value_type))?;
+                } else {
+                    self.store(addr, value_type, value)?;
+                }
+            }
+            Instruction::Memcpy { dest, src, size } => {
+                let dest = self.operand(frame, dest)?.as_int();
+                let src = self.operand(frame, src)?.as_int();
+                self.copy_bytes(dest, src, *size as i64)?;
+            }
+            Instruction::Memset { dest, value, size } => {
+                let dest = self.operand(frame, dest)?.as_int();
+                let value = self.operand(frame, value)?.as_int() as u8;
+                self.write_bytes(dest, &vec![value; *size])?;
+            }
+            Instruction::GetElementPtr { dest, base, index, element_type } => {
+                let base = self.operand(frame, base)?.as_int();
+                let index = self.operand(frame, index)?.as_int();
+                let size = self.size_of(element_type);
+                frame.vars[dest.0] = Value::Int(base.wrapping_add(index.wrapping_mul(size)));
+            }
+            Instruction::Call { dest, name, args, .. } => {
+                let args = args.iter().map(|a| self.operand(frame, a)).collect::<Result<Vec<_>, _>>()?;
+                return self.call_named(frame, name, args, *dest);
+            }
+            Instruction::IndirectCall { dest, func_ptr, args, .. } => {
+                let target = self.operand(frame, func_ptr)?.as_int();
+                let id = target.wrapping_sub(FUNC_BASE);
+                let name = match self.callables.get((id / 16) as usize) {
+                    Some(name) if id >= 0 && id % 16 == 0 => name.clone(),
+                    _ => return Err(InterpError::trap(SIGSEGV, format!("call through invalid function pointer {:#x}", target)).into()),
+                };
+                let args = args.iter().map(|a| self.operand(frame, a)).collect::<Result<Vec<_>, _>>()?;
+                return self.call_named(frame, &name, args, *dest);
+            }
+            Instruction::VaStart { list, arg_index } => {
+                // Spill the variadic arguments into 8-byte slots; the va_list
+                // points at the next one, as in the i686 and RISC-V codegen.
+                let extra: Vec<Value> = frame.args.iter().skip(arg_index + 1).copied().collect();
+                let area = align_up(self.sp, 8);
+                if area + 8 * extra.len() as i64 > self.stack_end {
+                    return Err(InterpError::trap(SIGSEGV, "stack overflow").into());
+                }
+                self.sp = area + 8 * extra.len() as i64;
+                for (i, value) in extra.iter().enumerate() {
+                    let bits = match value {
+                        Value::Int(v) => *v,
+                        Value::Float(f) => f.to_bits() as i64,
+                    };
+                    self.write_int(area + 8 * i as i64, 8, bits)?;
+                }
+                let list = self.operand(frame, list)?.as_int();
+                self.write_int(list, 8, area)?;
+            }
+            Instruction::VaEnd { .. } => {}
+            Instruction::VaCopy { dest, src } => {
+                let dest = self.operand(frame, dest)?.as_int();
+                let cursor = self.operand(frame, src)?.as_int();
+                self.write_int(dest, 8, cursor)?;
+            }
+            Instruction::VaArg { dest, list, r#type } => {
+                let list = self.operand(frame, list)?.as_int();
+                let cursor = self.read_int(list, 8, false)?;
+                let bits = self.read_int(cursor, 8, false)?;
+                self.write_int(list, 8, cursor + 8)?;
+                frame.vars[dest.0] = match r#type {
+                    Type::Float | Type::Double => convert(Value::Float(f64::from_bits(bits as u64)), r#type, false),
+                    _ => convert(Value::Int(bits), r#type, false),
+                };
+            }
+            Instruction::InlineAsm { .. } => {
+                return Err(InterpError::Unsupported(format!("inline assembly in '{}'", frame.func.name)).into());
+            }
+            Instruction::Simd { .. } => {
+                return Err(InterpError::Unsupported(format!("SIMD instructions in '{}'", frame.func.name)).into());
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Call a defined function by pushing a frame, or an external one through the shim.
+    fn call_named(&mut self, frame: &mut Frame<'p>, name: &str, args: Vec<Value>, dest: Option<VarId>) -> Exec<Flow> {
+        if let Some(&func) = self.functions.get(name) {
+            return Ok(Flow::Call { func, args, dest });
+        }
+        let value = self.call_external(name, &args)?;
+        if let Some(dest) = dest {
+            frame.vars[dest.0] = value;
+        }
+        Ok(Flow::Next)
+    }
+
+    // ─── C library shim ─────────────────────────────────────────
+
+    fn call_external(&mut self, name: &str, args: &[Value]) -> Exec<Value> {
+        let int = |i: usize| args.get(i).map_or(0, |v| v.as_int());
+        let float = |i: usize| args.get(i).map_or(0.0, |v| v.as_float());
+        let value = match name {
+            "putchar" => {
+                self.emit(1, &[int(0) as u8])?;
+                Value::Int(int(0) & 0xff)
+            }
+            "puts" => {
+                let mut s = self.read_cstr(int(0))?;
+                s.push(b'\n');
+                self.emit(1, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "fputs" => {
+                let s = self.read_cstr(int(0))?;
+                self.emit(file_fd(int(1))?, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "fputc" | "putc" => {
+                self.emit(file_fd(int(1))?, &[int(0) as u8])?;
+                Value::Int(int(0) & 0xff)
+            }
+            "printf" => {
+                let s = self.format(int(0), VarArgs::Values(args[1..].iter()))?;
+                self.emit(1, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "fprintf" => {
+                let s = self.format(int(1), VarArgs::Values(args[2..].iter()))?;
+                self.emit(file_fd(int(0))?, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "vprintf" => {
+                let s = self.format(int(0), VarArgs::List(int(1)))?;
+                self.emit(1, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "vfprintf" => {
+                let s = self.format(int(1), VarArgs::List(int(2)))?;
+                self.emit(file_fd(int(0))?, &s)?;
+                Value::Int(s.len() as i64)
+            }
+            "sprintf" | "vsprintf" => {
+                let rest = if name == "sprintf" { VarArgs::Values(args[2..].iter()) } else { VarArgs::List(int(2)) };
+                let mut s = self.format(int(1), rest)?;
+                let len = s.len() as i64;
+                s.push(0);
+                self.write_bytes(int(0), &s)?;
+                Value::Int(len)
+            }
+            "snprintf" | "vsnprintf" => {
+                let rest = if name == "snprintf" { VarArgs::Values(args[3..].iter()) } else { VarArgs::List(int(3)) };
+                let mut s = self.format(int(2), rest)?;
+                let len = s.len() as i64;
+                let cap = int(1);
+                if cap > 0 {
+                    s.truncate(cap as usize - 1);
+                    s.push(0);
+                    self.write_bytes(int(0), &s)?;
+                }
+                Value::Int(len)
+            }
+            "fwrite" => {
+                let bytes = self.read_bytes(int(0), int(1) * int(2))?.to_vec();
+                self.emit(file_fd(int(3))?, &bytes)?;
+                Value::Int(int(2))
+            }
+            "write" => {
+                let bytes = self.read_bytes(int(1), int(2))?.to_vec();
+                self.emit(int(0), &bytes)?;
+                Value::Int(int(2))
+            }
+            "fflush" => Value::Int(0),
+            "getchar" => {
+                let _ = self.out.flush();
+                let mut byte = [0u8];
+                match std::io::stdin().read(&mut byte) {
+                    Ok(1) => Value::Int(i64::from(byte[0])),
+                    _ => Value::Int(-1),
+                }
+            }
+            "malloc" => Value::Int(self.malloc(int(0))),
+            "calloc" => Value::Int(self.malloc(int(0).wrapping_mul(int(1)))),
+            "realloc" => {
+                let old = int(0);
+                let new = self.malloc(int(1));
+                if old != 0 && new != 0 {
+                    let old_size = self.heap_blocks.get(&old).copied().unwrap_or(0).min(int(1));
+                    let bytes = self.read_bytes(old, old_size)?.to_vec();
+                    self.write_bytes(new, &bytes)?;
+                    self.heap_blocks.remove(&old);
+                }
+                Value::Int(new)
+            }
+            "free" => {
+                let addr = int(0);
+                if addr != 0 && self.heap_blocks.remove(&addr).is_none() {
+                    return Err(InterpError::trap(SIGABRT, format!("free(): invalid pointer {:#x}", addr)).into());
+                }
+                Value::Int(0)
+            }
+            "memset" => {
+                let len = int(2);
+                self.check(int(0), len)?;
+                let start = int(0) as usize;
+                self.mem[start..start + len as usize].fill(int(1) as u8);
+                Value::Int(int(0))
+            }
+            "memcpy" | "memmove" => {
+                let bytes = self.read_bytes(int(1), int(2))?.to_vec();
+                self.write_bytes(int(0), &bytes)?;
+                Value::Int(int(0))
+            }
+            "memcmp" => {
+                let a = self.read_bytes(int(0), int(2))?;
+                let b = self.read_bytes(int(1), int(2))?;
+                Value::Int(compare_bytes(a, b))
+            }
+            "strlen" => Value::Int(self.read_cstr(int(0))?.len() as i64),
+            "strcmp" | "strncmp" => {
+                let mut a = self.read_cstr(int(0))?;
+                let mut b = self.read_cstr(int(1))?;
+                if name == "strncmp" {
+                    a.truncate(int(2) as usize);
+                    b.truncate(int(2) as usize);
+                }
+                Value::Int(compare_bytes(&a, &b))
+            }
+            "strcpy" => {
+                let mut s = self.read_cstr(int(1))?;
+                s.push(0);
+                self.write_bytes(int(0), &s)?;
+                Value::Int(int(0))
+            }
+            "strcat" => {
+                let end = int(0) + self.read_cstr(int(0))?.len() as i64;
+                let mut s = self.read_cstr(int(1))?;
+                s.push(0);
+                self.write_bytes(end, &s)?;
+                Value::Int(int(0))
+            }
+            "strchr" => {
+                let s = self.read_cstr(int(0))?;
+                let c = int(1) as u8;
+                match s.iter().position(|&b| b == c) {
+                    Some(i) => Value::Int(int(0) + i as i64),
+                    None if c == 0 => Value::Int(int(0) + s.len() as i64),
+                    None => Value::Int(0),
+                }
+            }
+            "atoi" | "atol" => {
+                let s = String::from_utf8_lossy(&self.read_cstr(int(0))?).into_owned();
+                let s = s.trim_start();
+                let digits = s.len() - s.trim_start_matches(['+', '-']).len().min(s.len());
+                let end = s[digits.min(1)..].find(|c: char| !c.is_ascii_digit()).map_or(s.len(), |i| i + digits.min(1));
+                let v = s[..end].parse::<i64>().unwrap_or(0);
+                Value::Int(if name == "atoi" { i64::from(v as i32) } else { v })
+            }
+            "abs" => Value::Int(i64::from((int(0) as i32).wrapping_abs())),
+            "labs" | "llabs" => Value::Int(int(0).wrapping_abs()),
+            "exit" | "_exit" | "_Exit" => {
+                let _ = self.out.flush();
+                return Err(Stop::Exit(int(0) as i32));
+            }
+            "abort" => {
+                return Err(InterpError::trap(SIGABRT, "abort() called").into());
+            }
+            "sqrt" | "sin" | "cos" | "tan" | "atan" | "exp" | "log" | "fabs" | "floor" | "ceil" | "round" => {
+                let x = float(0);
+                Value::Float(match name {
+                    "sqrt" => x.sqrt(),
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "atan" => x.atan(),
+                    "exp" => x.exp(),
+                    "log" => x.ln(),
+                    "fabs" => x.abs(),
+                    "floor" => x.floor(),
+                    "ceil" => x.ceil(),
+                    _ => x.round(),
+                })
+            }
+            "pow" => Value::Float(float(0).powf(float(1))),
+            "fmod" => Value::Float(float(0) % float(1)),
+            "atan2" => Value::Float(float(0).atan2(float(1))),
+            "__builtin_clz" => Value::Int(i64::from((int(0) as u32).leading_zeros())),
+            "__builtin_ctz" => Value::Int(i64::from((int(0) as u32).trailing_zeros())),
+            "__builtin_popcount" => Value::Int(i64::from((int(0) as u32).count_ones())),
+            "__builtin_clzl" | "__builtin_clzll" => Value::Int(i64::from((int(0) as u64).leading_zeros())),
+            "__builtin_ctzl" | "__builtin_ctzll" => Value::Int(i64::from((int(0) as u64).trailing_zeros())),
+            "__builtin_popcountl" | "__builtin_popcountll" => Value::Int(i64::from((int(0) as u64).count_ones())),
+            "__builtin_bswap16" => Value::Int(i64::from((int(0) as u16).swap_bytes())),
+            "__builtin_bswap32" => Value::Int(i64::from((int(0) as u32).swap_bytes())),
+            "__builtin_bswap64" => Value::Int((int(0) as u64).swap_bytes() as i64),
+            "__sync_synchronize" => Value::Int(0),
+            // Atomics operate on 8-byte objects, as in the codegen
+            "__sync_fetch_and_add" | "__sync_fetch_and_sub" | "__sync_fetch_and_and" | "__sync_fetch_and_or"
+            | "__sync_fetch_and_xor" | "__sync_lock_test_and_set" => {
+                let old = self.read_int(int(0), 8, true)?;
+                let new = match name {
+                    "__sync_fetch_and_add" => old.wrapping_add(int(1)),
+                    "__sync_fetch_and_sub" => old.wrapping_sub(int(1)),
+                    "__sync_fetch_and_and" => old & int(1),
+                    "__sync_fetch_and_or" => old | int(1),
+                    "__sync_fetch_and_xor" => old ^ int(1),
+                    _ => int(1),
+                };
+                self.write_int(int(0), 8, new)?;
+                Value::Int(old)
+            }
+            "__sync_val_compare_and_swap" => {
+                let old = self.read_int(int(0), 8, true)?;
+                if old == int(1) {
+                    self.write_int(int(0), 8, int(2))?;
+                }
+                Value::Int(old)
+            }
+            "__sync_lock_release" => {
+                self.write_int(int(0), 8, 0)?;
+                Value::Int(0)
+            }
+            "__atomic_load_n" => Value::Int(self.read_int(int(0), 8, true)?),
+            "__atomic_store_n" => {
+                self.write_int(int(0), 8, int(1))?;
+                Value::Int(int(1))
+            }
+            _ => return Err(InterpError::Unsupported(format!("call to external function '{}'", name)).into()),
+        };
+        Ok(value)
+    }
+
+    /// Write program output to file descriptor `fd`.
+    fn emit(&mut self, fd: i64, bytes: &[u8]) -> Result<(), InterpError> {
+        let result = match fd {
+            1 => self.out.write_all(bytes),
+            2 => {
+                let _ = self.out.flush();
+                std::io::stderr().write_all(bytes)
+            }
+            _ => return Err(InterpError::Unsupported(format!("write to file descriptor {}", fd))),
+        };
+        result.map_err(|e| InterpError::Unsupported(format!("cannot write program output: {}", e)))
+    }
+
+    /// Next `printf` argument. A `va_list` cursor lives in the caller's
+    /// `va_list` object, so it advances there as with the real `vprintf`.
+    fn next_arg(&mut self, args: &mut VarArgs<'_>) -> Result<Value, InterpError> {
+        match args {
+            VarArgs::Values(iter) => Ok(iter.next().copied().unwrap_or(Value::Int(0))),
+            VarArgs::List(list) => {
+                let cursor = self.read_int(*list, 8, false)?;
+                let bits = self.read_int(cursor, 8, false)?;
+                self.write_int(*list, 8, cursor + 8)?;
+                Ok(Value::Int(bits))
+            }
+        }
+    }
+
+    /// Expand a `printf` format string.
+    fn format(&mut self, fmt_addr: i64, mut args: VarArgs<'_>) -> Result<Vec<u8>, InterpError> {
+        let fmt = self.read_cstr(fmt_addr)?;
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < fmt.len() {
+            if fmt[i] != b'%' {
+                out.push(fmt[i]);
+                i += 1;
+                continue;
+            }
+            i += 1;
+            let mut spec = Spec::default();
+            while let Some(&c) = fmt.get(i) {
+                match c {
+                    b'-' => spec.left = true,
+                    b'+' => spec.plus = true,
+                    b' ' => spec.space = true,
+                    b'#' => spec.alt = true,
+                    b'0' => spec.zero = true,
+                    _ => break,
+                }
+                i += 1;
+            }
+            if fmt.get(i) == Some(&b'*') {
+                let w = self.take_int(&mut args)? as i32;
+                if w < 0 {
+                    spec.left = true;
+                }
+                spec.width = w.unsigned_abs() as usize;
+                i += 1;
+            } else {
+                while let Some(d) = fmt.get(i).filter(|c| c.is_ascii_digit()) {
+                    spec.width = spec.width * 10 + (d - b'0') as usize;
+                    i += 1;
+                }
+            }
+            if fmt.get(i) == Some(&b'.') {
+                i += 1;
+                if fmt.get(i) == Some(&b'*') {
+                    let p = self.take_int(&mut args)? as i32;
+                    spec.precision = (p >= 0).then_some(p as usize);
+                    i += 1;
+                } else {
+                    let mut p = 0;
+                    while let Some(d) = fmt.get(i).filter(|c| c.is_ascii_digit()) {
+                        p = p * 10 + (d - b'0') as usize;
+                        i += 1;
+                    }
+                    spec.precision = Some(p);
+                }
+            }
+            let mut length = 0u8;
+            while let Some(&c) = fmt.get(i).filter(|c| b"hlzjtL".contains(c)) {
+                length = match (length, c) {
+                    (b'h', b'h') => b'H',
+                    (b'l', b'l') => b'q',
+                    (_, b'h') => b'h',
+                    _ => b'l',
+                };
+                i += 1;
+            }
+            let Some(&conv) = fmt.get(i) else { break };
+            i += 1;
+            match conv {
+                b'%' => out.push(b'%'),
+                b'd' | b'i' => {
+                    let v = self.take_int(&mut args)?;
+                    let v = match length {
+                        b'H' => i64::from(v as i8),
+                        b'h' => i64::from(v as i16),
+                        0 => i64::from(v as i32),
+                        _ => v,
+                    };
+                    let sign = if v < 0 { "-" } else if spec.plus { "+" } else if spec.space { " " } else { "" };
+                    let digits = spec.int_digits(v.unsigned_abs().to_string());
+                    spec.pad_number(&mut out, sign, &digits);
+                }
+                b'u' | b'x' | b'X' | b'o' => {
+                    let v = self.take_int(&mut args)?;
+                    let v = match length {
+                        b'H' => u64::from(v as u8),
+                        b'h' => u64::from(v as u16),
+                        0 => u64::from(v as u32),
+                        _ => v as u64,
+                    };
+                    let digits = match conv {
+                        b'u' => v.to_string(),
+                        b'x' => format!("{:x}", v),
+                        b'X' => format!("{:X}", v),
+                        _ => format!("{:o}", v),
+                    };
+                    let mut digits = spec.int_digits(digits);
+                    if spec.alt && conv == b'o' && !digits.starts_with('0') {
+                        digits.insert(0, '0');
+                    }
+                    let prefix = match conv {
+                        b'x' if spec.alt && v != 0 => "0x",
+                        b'X' if spec.alt && v != 0 => "0X",
+                        _ => "",
+                    };
+                    spec.pad_number(&mut out, prefix, &digits);
+                }
+                b'c' => {
+                    let c = self.take_int(&mut args)? as u8;
+                    spec.pad(&mut out, &[c]);
+                }
+                b's' => {
+                    let addr = self.take_int(&mut args)?;
+                    let mut s = if addr == 0 { b"(null)".to_vec() } else { self.read_cstr(addr)? };
+                    if let Some(p) = spec.precision {
+                        s.truncate(p);
+                    }
+                    spec.pad(&mut out, &s);
+                }
+                b'p' => {
+                    let v = self.take_int(&mut args)?;
+                    let s = if v == 0 { "(nil)".to_string() } else { format!("0x{:x}", v) };
+                    spec.pad(&mut out, s.as_bytes());
+                }
+                b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                    let v = match self.next_arg(&mut args)? {
+                        Value::Float(f) => f,
+                        Value::Int(bits) => f64::from_bits(bits as u64),
+                    };
+                    let sign = if v.is_sign_negative() && !v.is_nan() { "-" } else if spec.plus { "+" } else if spec.space { " " } else { "" };
+                    let body = format_float(v.abs(), conv, spec.precision.unwrap_or(6), spec.alt);
+                    if v.is_finite() {
+                        spec.pad_number(&mut out, sign, &body);
+                    } else {
+                        spec.zero = false;
+                        spec.pad(&mut out, format!("{}{}", sign, body).as_bytes());
+                    }
+                }
+                b'n' => {
+                    self.take_int(&mut args)?;
+                }
+                other => {
+                    return Err(InterpError::Unsupported(format!("printf conversion '%{}'", other as char)));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Next integer argument; advances a `va_list` cursor in memory.
+    fn take_int(&mut self, args: &mut VarArgs<'_>) -> Result<i64, InterpError> {
+        Ok(self.next_arg(args)?.as_int())
+    }
+}
+
+impl ConstContext for Interpreter<'_, '_> {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.enum_constants.get(name).copied()
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.layout().size_of(ty))
+    }
+
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.layout().align_of(ty))
+    }
+}
+
+/// Conversion flags of one `%` directive.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Apply an integer precision (minimum digit count; `.0` prints nothing for 0).
+    fn int_digits(&mut self, digits: String) -> String {
+        match self.precision {
+            Some(p) => {
+                self.zero = false;
+                if p == 0 && digits == "0" {
+                    String::new()
+                } else {
+                    format!("{:0>width$}", digits, width = p)
+                }
+            }
+            None => digits,
+        }
+    }
+
+    /// Pad `prefix` + `digits` to the field width, zero-filling after the prefix.
+    fn pad_number(&self, out: &mut Vec<u8>, prefix: &str, digits: &str) {
+        if self.zero && !self.left {
+            let fill = self.width.saturating_sub(prefix.len() + digits.len());
+            out.extend_from_slice(prefix.as_bytes());
+            out.extend(std::iter::repeat_n(b'0', fill));
+            out.extend_from_slice(digits.as_bytes());
+        } else {
+            self.pad(out, format!("{}{}", prefix, digits).as_bytes());
+        }
+    }
+
+    fn pad(&self, out: &mut Vec<u8>, s: &[u8]) {
+        let fill = self.width.saturating_sub(s.len());
+        if !self.left {
+            out.extend(std::iter::repeat_n(b' ', fill));
+        }
+        out.extend_from_slice(s);
+        if self.left {
+            out.extend(std::iter::repeat_n(b' ', fill));
+        }
+    }
+}
+
+/// Format a non-negative float for `%f`, `%e`, or `%g`.
+fn format_float(v: f64, conv: u8, precision: usize, alt: bool) -> String {
+    let upper = conv.is_ascii_uppercase();
+    let s = if v.is_nan() {
+        "nan".to_string()
+    } else if v.is_infinite() {
+        "inf".to_string()
+    } else {
+        match conv.to_ascii_lowercase() {
+            b'f' => format!("{:.*}", precision, v),
+            b'e' => format_exp(v, precision),
+            _ => {
+                let p = precision.max(1);
+                let exp = if v == 0.0 { 0 } else { exponent_of(&format!("{:.*e}", p - 1, v)) };
+                let mut s = if exp < -4 || exp >= p as i32 {
+                    format_exp(v, p - 1)
+                } else {
+                    format!("{:.*}", (p as i32 - 1 - exp) as usize, v)
+                };
+                if !alt && s.contains('.') {
+                    let (mantissa, exp_part) = match s.find('e') {
+                        Some(i) => s.split_at(i),
+                        None => (s.as_str(), ""),
+                    };
+                    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+                    s = format!("{}{}", mantissa, exp_part);
+                }
+                s
+            }
+        }
+    };
+    if upper { s.to_ascii_uppercase() } else { s }
+}
+
+/// `%e` formatting: `d.ddde+XX`.
+fn format_exp(v: f64, precision: usize) -> String {
+    let s = format!("{:.*e}", precision, v);
+    let exp = exponent_of(&s);
+    let mantissa = &s[..s.find('e').unwrap()];
+    format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+fn exponent_of(rust_exp: &str) -> i32 {
+    rust_exp[rust_exp.find('e').unwrap() + 1..].parse().unwrap_or(0)
+}
+
+fn func_info(func: &Function) -> FuncInfo {
+    let block_index = func.blocks.iter().enumerate().map(|(i, b)| (b.id, i)).collect();
+    let mut max_var = func.params.iter().map(|(_, v)| v.0).max().unwrap_or(0);
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            for dest in inst.dests() {
+                max_var = max_var.max(dest.0);
+            }
+            inst.for_each_use(|v| max_var = max_var.max(v.0));
+        }
+        if let Terminator::CondBr { cond: Operand::Var(v), .. }
+        | Terminator::Ret(Some(Operand::Var(v)))
+        | Terminator::IndirectBr { target: Operand::Var(v) }
+        | Terminator::Switch { value: Operand::Var(v), .. } = &block.terminator
+        {
+            max_var = max_var.max(v.0);
+        }
+    }
+    FuncInfo { block_index, num_vars: max_var + 1 }
+}
+
+fn phi_value(frame: &Frame<'_>, preds: &[(BlockId, VarId)], from: BlockId) -> Result<Value, InterpError> {
+    match preds.iter().find(|(block, _)| *block == from) {
+        Some((_, var)) => Ok(frame.vars[var.0]),
+        None => Err(InterpError::Unsupported(format!("phi in '{}' has no value for bb{}", frame.func.name, from.0))),
+    }
+}
+
+fn align_up(value: i64, align: i64) -> i64 {
+    (value + align - 1) / align * align
+}
+
+fn unsupported_init(global: &str) -> InterpError {
+    InterpError::Unsupported(format!("initializer of global '{}'", global))
+}
+
+/// Bytes a load or store of scalar type `ty` touches.
+fn scalar_size(ty: &Type) -> i64 {
+    match ty {
+        Type::Char | Type::UnsignedChar | Type::Bool => 1,
+        Type::Short | Type::UnsignedShort => 2,
+        Type::Int | Type::UnsignedInt | Type::Enum(_) | Type::Float => 4,
+        Type::Typedef(name) => match name.as_str() {
+            "int8_t" | "uint8_t" | "int8" | "uint8" => 1,
+            "int16_t" | "uint16_t" | "int16" | "uint16" => 2,
+            "int32_t" | "uint32_t" | "int32" | "uint32" => 4,
+            _ => 8,
+        },
+        _ => 8,
+    }
+}
+
+/// Whether values of `ty` are structs or unions, which are the addresses of
+/// their objects.
+fn is_aggregate(ty: &Type) -> bool {
+    matches!(ty, Type::Struct(_) | Type::Union(_))
+}
+
+fn is_unsigned(ty: &Type) -> bool {
+    match ty {
+        Type::UnsignedChar | Type::UnsignedShort | Type::UnsignedInt | Type::UnsignedLong | Type::UnsignedLongLong | Type::Bool => true,
+        Type::Typedef(name) => name.starts_with('u') || name == "size_t",
+        _ => false,
+    }
+}
+
+/// Truncate an integer result to the width of its type, if it has a narrow integer type.
+fn narrow(ty: Option<&Type>, value: i64) -> i64 {
+    match ty {
+        Some(Type::Bool) => i64::from(value != 0),
+        Some(Type::Char) => i64::from(value as i8),
+        Some(Type::UnsignedChar) => i64::from(value as u8),
+        Some(Type::Short) => i64::from(value as i16),
+        Some(Type::UnsignedShort) => i64::from(value as u16),
+        Some(Type::Int | Type::Enum(_)) => i64::from(value as i32),
+        Some(Type::UnsignedInt) => i64::from(value as u32),
+        Some(ty @ Type::Typedef(_)) => match scalar_size(ty) {
+            1 if is_unsigned(ty) => i64::from(value as u8),
+            1 => i64::from(value as i8),
+            2 if is_unsigned(ty) => i64::from(value as u16),
+            2 => i64::from(value as i16),
+            4 if is_unsigned(ty) => i64::from(value as u32),
+            4 => i64::from(value as i32),
+            _ => value,
+        },
+        _ => value,
+    }
+}
+
+/// Convert a value to `ty` as a C cast does.
+fn convert(value: Value, ty: &Type, from_unsigned: bool) -> Value {
+    match (ty, value) {
+        (Type::Float, v) => Value::Float(match v {
+            Value::Int(i) if from_unsigned => f64::from(i as u64 as f32),
+            v => f64::from(v.as_float() as f32),
+        }),
+        (Type::Double, Value::Int(i)) if from_unsigned => Value::Float(i as u64 as f64),
+        (Type::Double, v) => Value::Float(v.as_float()),
+        (Type::Bool, v) => Value::Int(i64::from(v.is_true())),
+        (_, Value::Float(f)) if is_unsigned(ty) => Value::Int(narrow(Some(ty), f as u64 as i64)),
+        (_, v) => Value::Int(narrow(Some(ty), v.as_int())),
+    }
+}
+
+/// A returned value converted to the function's return type.
+fn return_value(ty: &Type, value: Value) -> Value {
+    match (ty, value) {
+        (Type::Float | Type::Double, v) => convert(v, ty, false),
+        (_, Value::Int(v)) => Value::Int(narrow(Some(ty), v)),
+        (_, v) => v,
+    }
+}
+
+fn int_binary(op: &BinaryOp, l: i64, r: i64, unsigned: bool) -> Result<i64, InterpError> {
+    let cmp = |ord: std::cmp::Ordering, accept: &[std::cmp::Ordering]| i64::from(accept.contains(&ord));
+    let ord = if unsigned { (l as u64).cmp(&(r as u64)) } else { l.cmp(&r) };
+    use std::cmp::Ordering::{Equal, Greater, Less};
+    Ok(match op {
+        BinaryOp::Add => l.wrapping_add(r),
+        BinaryOp::Sub => l.wrapping_sub(r),
+        BinaryOp::Mul => l.wrapping_mul(r),
+        BinaryOp::Div | BinaryOp::Mod => {
+            if r == 0 || (!unsigned && l == i64::MIN && r == -1) {
+                return Err(InterpError::trap(SIGFPE, "integer division by zero or overflow"));
+            }
+            match (op, unsigned) {
+                (BinaryOp::Div, true) => ((l as u64) / (r as u64)) as i64,
+                (BinaryOp::Div, false) => l / r,
+                (_, true) => ((l as u64) % (r as u64)) as i64,
+                (_, false) => l % r,
+            }
+        }
+        BinaryOp::EqualEqual => i64::from(l == r),
+        BinaryOp::NotEqual => i64::from(l != r),
+        BinaryOp::Less => cmp(ord, &[Less]),
+        BinaryOp::LessEqual => cmp(ord, &[Less, Equal]),
+        BinaryOp::Greater => cmp(ord, &[Greater]),
+        BinaryOp::GreaterEqual => cmp(ord, &[Greater, Equal]),
+        BinaryOp::LogicalAnd => i64::from(l != 0 && r != 0),
+        BinaryOp::LogicalOr => i64::from(l != 0 || r != 0),
+        BinaryOp::BitwiseAnd => l & r,
+        BinaryOp::BitwiseOr => l | r,
+        BinaryOp::BitwiseXor => l ^ r,
+        BinaryOp::ShiftLeft => l.wrapping_shl((r & 63) as u32),
+        BinaryOp::ShiftRight if unsigned => ((l as u64) >> (r & 63)) as i64,
+        BinaryOp::ShiftRight => l >> (r & 63),
+        other => return Err(InterpError::Unsupported(format!("integer {:?} instruction", other))),
+    })
+}
+
+fn float_binary(op: &BinaryOp, l: f64, r: f64) -> Result<Value, InterpError> {
+    Ok(match op {
+        BinaryOp::Add => Value::Float(l + r),
+        BinaryOp::Sub => Value::Float(l - r),
+        BinaryOp::Mul => Value::Float(l * r),
+        BinaryOp::Div => Value::Float(l / r),
+        BinaryOp::EqualEqual => Value::Int(i64::from(l == r)),
+        BinaryOp::NotEqual => Value::Int(i64::from(l != r)),
+        BinaryOp::Less => Value::Int(i64::from(l < r)),
+        BinaryOp::LessEqual => Value::Int(i64::from(l <= r)),
+        BinaryOp::Greater => Value::Int(i64::from(l > r)),
+        BinaryOp::GreaterEqual => Value::Int(i64::from(l >= r)),
+        BinaryOp::LogicalAnd => Value::Int(i64::from(l != 0.0 && r != 0.0)),
+        BinaryOp::LogicalOr => Value::Int(i64::from(l != 0.0 || r != 0.0)),
+        other => return Err(InterpError::Unsupported(format!("floating-point {:?} instruction", other))),
+    })
+}
+
+fn compare_bytes(a: &[u8], b: &[u8]) -> i64 {
+    match a.iter().zip(b).find(|(x, y)| x != y) {
+        Some((x, y)) => i64::from(*x) - i64::from(*y),
+        None => a.len() as i64 - b.len() as i64,
+    }
+}
+
+/// File descriptor of a `stdout`/`stderr` `FILE *`.
+fn file_fd(file: i64) -> Result<i64, InterpError> {
+    match file - FILE_BASE {
+        fd @ (1 | 2) => Ok(fd),
+        _ => Err(InterpError::Unsupported(format!("output to FILE {:#x}", file))),
+    }
+}
@@ -2,25 +2,43 @@
 // Lowers AST to SSA-form IR with basic blocks
 
 mod types;
+mod cfg;
 mod lowerer;
 mod type_utils;
 mod ssa;
 mod expressions;
+mod complex;
+mod sanitize;
 mod lvalue;
 mod statements;
 mod init_list;
 mod mem2reg;
 mod ssa_utils;
+mod dot;
+pub mod callgraph;
+mod printer;
+mod reader;
+mod binary;
+mod link;
+mod interp;
 
 // Public exports
 pub use types::{
     BranchHint, VarId, BlockId, Operand, Instruction, SimdOp, Terminator, BasicBlock, Function,
-    IRProgram,
+    Declaration, IRProgram,
 };
+pub use cfg::Cfg;
 pub use lowerer::Lowerer;
 pub use mem2reg::mem2reg;
 pub use ssa_utils::remove_phis;
 pub use ssa_utils::verify_ssa;
+pub use ssa_utils::verify_function;
+pub use dot::{callgraph_to_dot, cfg_to_dot, render_cfg, CfgFormat};
+pub use printer::{function_to_text, program_to_text};
+pub use reader::{function_from_text, program_from_text};
+pub use binary::{program_from_bytes, program_to_bytes};
+pub use link::link_programs;
+pub use interp::{interpret, InterpError, Interpreter};
 
 #[cfg(test)]
 mod tests {
@@ -46,6 +64,20 @@ mod tests {
         f.blocks.iter().flat_map(|b| b.instructions.iter()).collect()
     }
 
+    #[test]
+    fn test_lower_asm_named_operands() {
+        let ir = lower(r#"int f(int a) { int r; asm("lea %[r], [%[a] + %%rip + %k[a]]" : [r] "=r"(r) : [a] "r"(a)); return r; }"#);
+        let asm = all_instructions(first_fn(&ir)).into_iter().find_map(|i| match i {
+            Instruction::InlineAsm { template, .. } => Some(template.clone()),
+            _ => None,
+        });
+        assert_eq!(asm.as_deref(), Some("lea %0, [%1 + %%rip + %k1]"));
+
+        let ast = parse_tokens(&lex(r#"int f() { int r; asm("mov %[x], 1" : [r] "=r"(r)); return r; }"#).unwrap()).unwrap();
+        let err = Lowerer::new().lower_program(&ast).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::InvalidOperand);
+    }
+
     // ─── Basic lowering ─────────────────────────────────────────
     #[test]
     fn test_lower_simple_arithmetic() {
@@ -117,6 +149,55 @@ mod tests {
         assert!(has_cond, "do-while should produce CondBr");
     }
 
+    #[test]
+    fn test_lower_computed_goto() {
+        let ir = lower("int main() { void *p = &&b; goto *p; a: return 1; b: return 2; }");
+        let f = first_fn(&ir);
+        assert!(f.has_indirect_branch());
+        let targets = f.indirect_targets();
+        assert_eq!(targets, vec![f.labels["b"]]);
+        let from = f.blocks.iter().find(|b| matches!(b.terminator, Terminator::IndirectBr { .. })).unwrap().id;
+        assert_eq!(f.compute_successors()[&from], targets);
+        assert!(f.compute_predecessors()[&targets[0]].contains(&from));
+    }
+
+    #[test]
+    fn test_cfg_matches_terminators() {
+        let ir = lower("int main(int n) { int s = 0; for (int i = 0; i < n; i++) { if (i & 1) continue; s += i; }\n\
+                        switch (s) { case 1: s = 2; case 2: break; default: s = 0; } while (s > 9) s--; return s; }");
+        let f = first_fn(&ir);
+        let cfg = Cfg::new(f);
+        let preds = f.compute_predecessors();
+        let succs = f.compute_successors();
+        for b in &f.blocks {
+            let mut expected = preds[&b.id].clone();
+            expected.sort_by_key(|p| p.0);
+            assert_eq!(cfg.preds(b.id), expected.as_slice(), "preds of {:?}", b.id);
+            assert_eq!(cfg.succs(b.id), succs[&b.id].as_slice(), "succs of {:?}", b.id);
+        }
+    }
+
+    #[test]
+    fn test_lowerer_keeps_cfg_current() {
+        let src = "int main(int n) { int s = 0; while (n) { if (n > 3) { s++; goto out; } n--; } out: return s; }";
+        let ast = parse_tokens(&lex(src).unwrap()).unwrap();
+        let mut lowerer = Lowerer::new();
+        let ir = lowerer.lower_program(&ast).unwrap();
+        let rebuilt = Cfg::new(first_fn(&ir));
+        for b in &first_fn(&ir).blocks {
+            assert_eq!(lowerer.cfg.preds(b.id), rebuilt.preds(b.id), "preds of {:?}", b.id);
+            assert_eq!(lowerer.cfg.succs(b.id), rebuilt.succs(b.id), "succs of {:?}", b.id);
+        }
+
+        let mut cfg = rebuilt;
+        let (a, b, c) = (BlockId(0), BlockId(1), BlockId(2));
+        cfg.set_successors(a, vec![c, c]);
+        assert!(!cfg.preds(b).contains(&a));
+        assert_eq!(cfg.preds(c).iter().filter(|&&p| p == a).count(), 2, "both arms of a CondBr are edges");
+        cfg.set_successors(a, Vec::new());
+        assert!(!cfg.preds(c).contains(&a));
+    }
+
     // ─── Expressions ────────────────────────────────────────────
     #[test]
     fn test_lower_binary_operations() {
@@ -179,6 +260,31 @@ mod tests {
         assert!(!ir.global_strings.is_empty(), "String literal should produce global_strings");
     }
 
+    #[test]
+    fn test_equal_string_literals_share_a_label() {
+        let ir = lower(r#"int puts(const char *s); int main() { puts("hi"); puts("yo"); puts("hi"); return 0; }"#);
+        assert_eq!(ir.global_strings, [("str_0".to_string(), "hi".to_string()), ("str_1".to_string(), "yo".to_string())]);
+        let main = ir.functions.iter().find(|f| f.name == "main").unwrap();
+        let args: Vec<Operand> = all_instructions(main)
+            .into_iter()
+            .filter_map(|i| match i {
+                Instruction::Call { args, .. } => Some(args[0].clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(args, ["str_0", "str_1", "str_0"].map(|l| Operand::Global(l.to_string())));
+    }
+
+    #[test]
+    fn test_lower_wide_string_literal() {
+        let ir = lower(r#"int main() { const unsigned short *s = u"a😀"; return s[0]; }"#);
+        let g = ir.globals.iter().find(|g| g.name == "wstr_0").expect("wide literal becomes a global");
+        // Surrogate pair plus terminator
+        assert_eq!(g.r#type, model::Type::Array(Box::new(model::Type::UnsignedShort), 4));
+        assert!(g.is_static && g.qualifiers.is_const);
+        assert!(ir.global_strings.is_empty());
+    }
+
     // ─── Structs ────────────────────────────────────────────────
     #[test]
     fn test_lower_struct_definition() {
@@ -199,9 +305,26 @@ mod tests {
     fn test_lower_switch() {
         let ir = lower("int main() { int x = 2; switch (x) { case 1: return 1; case 2: return 2; default: return 0; } }");
         let f = first_fn(&ir);
-        // Switch turns into a chain of CondBr comparisons
-        let cond_count = f.blocks.iter().filter(|b| matches!(b.terminator, Terminator::CondBr { .. })).count();
-        assert!(cond_count >= 2, "Switch with 2 cases should produce at least 2 CondBr");
+        // One Switch terminator dispatches on the value
+        let switch = f.blocks.iter().find_map(|b| match &b.terminator {
+            Terminator::Switch { cases, .. } => Some(cases.iter().map(|&(v, _)| v).collect::<Vec<_>>()),
+            _ => None,
+        });
+        assert_eq!(switch, Some(vec![1, 2]));
+        assert!(!f.blocks.iter().any(|b| matches!(b.terminator, Terminator::CondBr { .. })));
+    }
+
+    #[test]
+    fn test_lower_switch_break_leaves_only_the_switch() {
+        let ir = lower("int f(int n) { int r = 0; for (int i = 0; i < n; i++) { switch (i) { case 1: break; default: r++; } r += 10; } return r; }");
+        let f = first_fn(&ir);
+        let cfg = Cfg::new(f);
+        let ret = f.blocks.iter().find(|b| matches!(b.terminator, Terminator::Ret(_))).unwrap().id;
+        assert_eq!(cfg.preds(ret).len(), 1, "only the loop condition reaches the return");
+
+        let ast = parse_tokens(&lex("int main() { case 1: return 0; }").unwrap()).unwrap();
+        let err = Lowerer::new().lower_program(&ast).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::MisplacedStatement);
     }
 
     // ─── Pointers and arrays ────────────────────────────────────
@@ -223,6 +346,91 @@ mod tests {
         assert!(has_gep, "Array indexing should produce GetElementPtr");
     }
 
+    #[test]
+    fn test_init_list_converts_elements() {
+        let ir = lower("struct S { int k; double d; }; int main() { double a[2] = {1, 2.5}; struct S s = {7.9, 3}; return s.k; }");
+        let stores: Vec<_> = all_instructions(first_fn(&ir)).into_iter().filter_map(|i| match i {
+            Instruction::Store { src, value_type, .. } => Some((src.clone(), value_type.clone())),
+            _ => None,
+        }).collect();
+        assert!(stores.contains(&(Operand::FloatConstant(1.0), model::Type::Double)));
+        assert!(stores.contains(&(Operand::Constant(7), model::Type::Int)));
+        assert!(stores.contains(&(Operand::FloatConstant(3.0), model::Type::Double)));
+    }
+
+    #[test]
+    fn test_aggregate_copies_and_fills_lower_to_intrinsics() {
+        let ir = lower("struct S { long a, b, c; }; int main() { struct S s = {0}; struct S t = {1, 2, 3}; int a[4] = {0, 5};\n\
+                        s = t; return a[1] + (int)s.b; }");
+        let instrs = all_instructions(first_fn(&ir));
+        let memsets: Vec<usize> = instrs.iter().filter_map(|i| match i {
+            Instruction::Memset { value: Operand::Constant(0), size, .. } => Some(*size),
+            _ => None,
+        }).collect();
+        assert_eq!(memsets, vec![24, 16], "only the incomplete initializers clear their object");
+        assert!(instrs.iter().any(|i| matches!(i, Instruction::Memcpy { size: 24, .. })));
+        let stored: Vec<&Operand> = instrs.iter().filter_map(|i| match i {
+            Instruction::Store { src, .. } => Some(src),
+            _ => None,
+        }).collect();
+        assert!(!stored.contains(&&Operand::Constant(0)), "zeros already set by memset are not stored again");
+        assert_eq!(stored.len(), 4);
+    }
+
+    #[test]
+    fn test_struct_values_copy_whole_objects() {
+        let ir = lower("struct S { long a, b, c; }; struct W { int tag; struct S in; };\n\
+                        int main(int n) { struct S a = {1, 2, 3}; struct S b = a; struct S c, d; c = d = b;\n\
+                        struct S e = n ? a : (d, c); struct W w = {1, e}; return (int)w.in.c; }");
+        let instrs = all_instructions(first_fn(&ir));
+        let copies = instrs.iter().filter(|i| matches!(i, Instruction::Memcpy { size: 24, .. })).count();
+        assert_eq!(copies, 5, "init, two chained assignments, ?: init, and the init-list member");
+        assert!(!instrs.iter().any(|i| matches!(i, Instruction::Store { value_type: model::Type::Struct(_), .. })));
+        assert!(!instrs.iter().any(|i| matches!(i, Instruction::Memset { .. })), "a struct member given whole completes the list");
+
+        let ir = lower("struct P { long x, y; }; struct P mk(void);\n\
+                        int main() { struct P p = mk(); return (int)p.y + (int)mk().x; }");
+        let instrs = all_instructions(first_fn(&ir));
+        let stores = instrs.iter().filter(|i| matches!(i, Instruction::Store { value_type: model::Type::Struct(_), .. })).count();
+        assert_eq!(stores, 2, "a call's 16-byte result is stored whole, to its variable or a temporary for the member");
+    }
+
+    #[test]
+    fn test_lower_void_conditional() {
+        let ir = lower("void f(void); void g(void); int main(int n) { n ? f() : g(); n > 1 ? f() : (void)0; return n; }");
+        let instrs = all_instructions(first_fn(&ir));
+        assert!(!instrs.iter().any(|i| matches!(i, Instruction::Phi { .. })), "void arms have nothing to merge");
+        assert_eq!(instrs.iter().filter(|i| matches!(i, Instruction::Call { .. })).count(), 3);
+    }
+
+    #[test]
+    fn test_every_variable_has_a_type() {
+        let ir = lower("double atof(const char *s); float half(float x) { return x / 2; }\n\
+                        int main() { float (*f)(float) = half; unsigned u = 3; long n = u + 1;\n\
+                        return (int)(atof(\"2.5\") * 4) + (int)f(2.0f) + (int)n; }");
+        for func in &ir.functions {
+            for instr in func.blocks.iter().flat_map(|b| &b.instructions) {
+                if let Some(d) = instr.dest() {
+                    assert!(func.var_types.contains_key(&d), "{} has no type for {:?}", func.name, instr);
+                }
+            }
+        }
+        let main = ir.functions.iter().find(|f| f.name == "main").unwrap();
+        let type_of_call = |name: &str| main.blocks.iter().flat_map(|b| &b.instructions).find_map(|i| match i {
+            Instruction::Call { dest: Some(d), name: n, .. } if n == name => main.var_types.get(d).cloned(),
+            _ => None,
+        });
+        assert_eq!(type_of_call("atof"), Some(model::Type::Double), "a prototype gives the call its type");
+        let indirect = main.blocks.iter().flat_map(|b| &b.instructions).find_map(|i| match i {
+            Instruction::IndirectCall { dest: Some(d), .. } => main.var_types.get(d).cloned(),
+            _ => None,
+        });
+        assert_eq!(indirect, Some(model::Type::Float));
+        assert!(main.blocks.iter().flat_map(|b| &b.instructions).any(|i| matches!(i,
+            Instruction::Binary { dest, op: model::BinaryOp::Add, .. } if main.var_types.get(dest) == Some(&model::Type::UnsignedInt))),
+            "unsigned + int is unsigned");
+    }
+
     // ─── mem2reg ────────────────────────────────────────────────
     #[test]
     fn test_mem2reg_eliminates_alloca() {
@@ -266,6 +474,239 @@ mod tests {
         assert!(matches!(f.blocks[0].terminator, Terminator::Ret(Some(Operand::Constant(1)))));
     }
 
+    #[test]
+    fn test_enum_defs_carry_underlying_type() {
+        let ir = lower("enum Big { SMALL, HUGE = 0x100000000 }; int main() { return sizeof(enum Big); }");
+        assert_eq!(ir.enums.len(), 1);
+        assert_eq!(ir.enums[0].underlying, model::Type::UnsignedLongLong);
+        assert!(matches!(first_fn(&ir).blocks[0].terminator, Terminator::Ret(Some(Operand::Constant(8)))));
+    }
+
+    // ─── Textual IR ─────────────────────────────────────────────
+    #[test]
+    fn test_program_to_text() {
+        let ir = lower(
+            "struct P { int x; long y : 3; };\n\
+             int table[3] = { 1, [2] = 4 };\n\
+             int *end = &table[2];\n\
+             static int twice(int v) { return v * 2; }\n\
+             int main(void) { return twice(table[2]); }",
+        );
+        let expected = "\
+struct P { int x; long y : 3; }
+
+global int @table[3] = { 1, [2] = 4 }
+global int *@end = &@table[2]
+
+static function twice(int %0) -> int {
+bb0: (entry)
+  %1 = alloca int
+  store int %0, %1
+  %2 = load int, %1
+  %3 = mul %2, 2
+  ret %3
+}
+
+function main() -> int {
+bb0: (entry)
+  %0 = @table
+  %1 = gep %0, 2 x int
+  %2 = load int, %1
+  %3 = call twice(%2)
+  ret %3
+}
+";
+        assert_eq!(program_to_text(&ir), expected);
+    }
+
+    // ─── Reading textual IR ─────────────────────────────────────
+    /// Lower `src`, print it, read the text back, and print that again.
+    fn assert_round_trip(src: &str, promote: bool) {
+        let mut ir = lower(src);
+        if promote {
+            ir.functions.iter_mut().for_each(mem2reg);
+        }
+        let text = program_to_text(&ir);
+        let read = program_from_text(&text).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        assert_eq!(program_to_text(&read), text);
+    }
+
+    #[test]
+    fn test_read_program_text_round_trips() {
+        assert_round_trip(
+            "struct P { int x; long y : 3; char *name; } __attribute__((packed));\n\
+             union U { int i; float f; };\n\
+             enum E { A, B = 5 };\n\
+             const char *const greeting = \"hi\\n\\t\\\"there\\\"\";\n\
+             int (*handlers[2])(int, ...);\n\
+             int table[3] = { 1, [2] = 4 };\n\
+             int *end = &table[2];\n\
+             double scale = 1.0;\n\
+             struct P origin = { .x = -1 };\n\
+             extern int errno_value;\n\
+             int printf(const char *fmt, ...);\n\
+             float scalef(float x, struct P *p);\n\
+             static int twice(int v) { return v * 2; }\n\
+             int main(void) { return twice(table[2]); }",
+            false,
+        );
+        assert_round_trip(
+            "double mix(double a, float b, int n) { double s = 0; for (int i = 0; i < n; i++) s += a * b - i; return -s; }\n\
+             int pick(int c, int x) { int r = c ? x : -x; if (r > 10) r = 10; return r; }\n\
+             int jump(int i) { static void *where[] = { &&one, &&two }; goto *where[i]; one: return 1; two: return 2; }\n\
+             int count(int n) { int k = 0; again: k++; if (k < n) goto again; return k; }\n\
+             int sel(unsigned u) { switch (u) { case -1: return 3; case 7: u++; case 8: return u; default: return 0; } }\n\
+             int rd(int a) { int r; asm volatile(\"lea %0, [%1 + 1]\" : \"=r\"(r) : \"r\"(a) : \"memory\"); return r; }\n\
+             int sum(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); int v = __builtin_va_arg(ap, int); __builtin_va_end(ap); return v; }\n\
+             struct Q { long a, b; }; long fill(struct Q *q) { struct Q z = {0}; *q = z; return q->b; }",
+            true,
+        );
+    }
+
+    #[test]
+    fn test_binary_ir_round_trips() {
+        let mut ir = lower(
+            "struct P { int x; long y : 3; char *name; } __attribute__((packed));\n\
+             union U { int i; float f; };\n\
+             enum E { A, B = 5 };\n\
+             int table[3] = { 1, [2] = B };\n\
+             int *end = &table[2];\n\
+             long size = sizeof(struct P) * 2;\n\
+             unsigned wide = 4000000000u;\n\
+             struct P origin = { .x = -1 };\n\
+             __attribute__((weak)) double scale = 1.5;\n\
+             float scalef(float x, ...);\n\
+             static int twice(int v) { return v * 2; }\n\
+             int jump(int i) { static void *where[] = { &&one, &&two }; goto *where[i]; one: return 1; two: return 2; }\n\
+             int rd(int a) { int r; asm volatile(\"lea %0, [%1 + 1]\" : \"=r\"(r) : \"r\"(a) : \"memory\"); return r; }\n\
+             double mix(double a, int n) { double s = 0; for (int i = 0; i < n; i++) s += a - i; return -s; }\n\
+             int sel(long v) { switch (v) { case -5: return 1; case 1L << 40: return 2; } return 0; }\n\
+             int main(void) { struct P p = origin; return twice(table[2]) + p.x + (int)mix(scale, 3); }",
+        );
+        ir.functions.iter_mut().for_each(mem2reg);
+        ir.functions[0].blocks[0].spans[0] = model::Span::new(model::FileId(2), 40, 52);
+        ir.data_layout = model::DataLayout { pointer_size: 4, pointer_align: 4, long_size: 4, ..model::DataLayout::lp64() };
+
+        let bytes = program_to_bytes(&ir).unwrap();
+        let read = program_from_bytes(&bytes).unwrap();
+        assert_eq!(program_to_text(&read), program_to_text(&ir));
+        for (before, after) in ir.functions.iter().zip(&read.functions) {
+            assert_eq!(before.var_types, after.var_types);
+            assert_eq!(before.labels, after.labels);
+            assert_eq!((&before.local_names, before.span), (&after.local_names, after.span));
+            for (b, a) in before.blocks.iter().zip(&after.blocks) {
+                assert_eq!((&b.spans, b.terminator_span), (&a.spans, a.terminator_span));
+            }
+        }
+        assert_eq!(read.globals, ir.globals);
+        assert_eq!(read.declarations, ir.declarations);
+        assert_eq!(read.data_layout, ir.data_layout);
+        assert_eq!(program_to_bytes(&read).unwrap(), bytes);
+
+        for corrupt in [&bytes[..bytes.len() - 1], &bytes[1..], b"CIR\x63"] {
+            assert_eq!(program_from_bytes(corrupt).unwrap_err().code, model::ErrorCode::Syntax);
+        }
+    }
+
+    #[test]
+    fn test_link_programs_resolves_symbols_across_units() {
+        let a = lower(
+            "int puts(const char *s);
+             struct Wrap { struct { char c; long l; } in; };
+             struct Wrap pair;
+             static int helper(int v) { return v * 2; }
+             int shared;
+             int twice(int v);
+             int main() { puts(\"a\"); return helper(1) + twice(shared) + pair.in.c; }",
+        );
+        let b = lower(
+            "int puts(const char *s);
+             struct Box { struct { int a[4]; } in; };
+             struct Box quad;
+             static int helper(int v) { return v * 3; }
+             int shared = 4;
+             int twice(int v) { puts(\"b\"); puts(\"a\"); return helper(v) + quad.in.a[3]; }",
+        );
+        let linked = link_programs(vec![a.clone(), b.clone()]).unwrap();
+
+        let names: Vec<&str> = linked.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["helper.0", "main", "helper.1", "twice"]);
+        let calls = |name: &str| -> Vec<(String, Vec<Operand>)> {
+            let f = linked.functions.iter().find(|f| f.name == name).unwrap();
+            all_instructions(f)
+                .into_iter()
+                .filter_map(|i| match i {
+                    Instruction::Call { name, args, .. } => Some((name.clone(), args.clone())),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert!(calls("main").iter().any(|(callee, _)| callee == "helper.0"));
+        assert!(calls("twice").contains(&("puts".to_string(), vec![Operand::Global("str_1".to_string())])));
+        // "a" is already linked from the first unit
+        assert!(calls("twice").contains(&("puts".to_string(), vec![Operand::Global("str_0".to_string())])));
+        assert!(calls("twice").iter().any(|(callee, _)| callee == "helper.1"));
+        assert_eq!(linked.global_strings, [("str_0".to_string(), "a".to_string()), ("str_1".to_string(), "b".to_string())]);
+        // `twice` is only a prototype in the first unit
+        let declared: Vec<&str> = linked.declarations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(declared, ["puts"]);
+
+        // The tentative definition yields to the initialized one
+        let shared: Vec<_> = linked.globals.iter().filter(|g| g.name == "shared").collect();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].init, Some(model::Expr::Constant(4)));
+
+        // Both units number their anonymous member struct 1
+        let tags: Vec<&str> = linked.structs.iter().map(|s| s.name.as_str()).collect();
+        assert!(tags.contains(&"<anonymous 1>") && tags.contains(&"<anonymous 1>.1"));
+        let boxed = linked.structs.iter().find(|s| s.name == "Box").unwrap();
+        assert_eq!(boxed.fields[0].field_type, model::Type::Struct("<anonymous 1>.1".to_string()));
+
+        let mut narrow = lower("int other;");
+        narrow.data_layout.long_size = 4;
+        let err = link_programs(vec![a.clone(), narrow]).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::IncompatibleTypes);
+
+        let strong = lower("int twice(int v) { return v; }");
+        let err = link_programs(vec![b.clone(), strong]).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::Redeclaration);
+        let weak = lower("__attribute__((weak)) int twice(int v) { return 0; }");
+        let linked = link_programs(vec![weak, b]).unwrap();
+        let twice: Vec<_> = linked.functions.iter().filter(|f| f.name == "twice").collect();
+        assert_eq!(twice.len(), 1);
+        assert!(twice[0].attributes.is_empty());
+    }
+
+    #[test]
+    fn test_read_function_text() {
+        let f = function_from_text(
+            "function clamp(int %0, double %1) -> int {\n\
+             bb0:\n\
+               %2 = greater %0, 10\n\
+               br %2 ? bb1 : bb2 (likely else)\n\
+             bb1:\n\
+               ret 10\n\
+             bb2: (label done)\n\
+               %3 = fmul %1, 2.0\n\
+               %4 = cast double %3 to int\n\
+               %5 = add %0, %4\n\
+               ret %5\n\
+             }\n",
+        )
+        .unwrap();
+        assert_eq!(f.entry_block, BlockId(0));
+        assert_eq!(f.labels.get("done"), Some(&BlockId(2)));
+        assert!(f.blocks[2].is_label_target);
+        assert!(matches!(f.blocks[0].terminator, Terminator::CondBr { hint: BranchHint::LikelyElse, .. }));
+        assert_eq!(f.var_types.get(&VarId(3)), Some(&model::Type::Double));
+        assert_eq!(f.var_types.get(&VarId(4)), Some(&model::Type::Int));
+
+        let err = function_from_text("function f() -> int {\nbb0:\n  %1 = frobnicate %0\n  ret %1\n}\n").unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::Syntax);
+        assert!(err.message.contains("line 3"), "{}", err.message);
+        assert!(function_from_text("function f() -> int {\nbb0:\n  %1 = add %0, 1\n}\n").is_err());
+    }
+
     // ─── Cast instruction ───────────────────────────────────────
     #[test]
     fn test_cast_expression() {
@@ -275,4 +716,389 @@ mod tests {
         let has_cast = instrs.iter().any(|i| matches!(i, Instruction::Cast { .. }));
         assert!(has_cast, "Cast expression should produce Cast instruction");
     }
+
+    #[test]
+    fn test_cast_records_source_type() {
+        let ir = lower("long f(int x, unsigned u, long l) { unsigned char b = l; unsigned v = -x; return (long)x + (long)u + b + v + (short)3.5; }");
+        let casts: Vec<(model::Type, model::Type)> = all_instructions(first_fn(&ir))
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Cast { src_type, r#type, .. } => Some((src_type.clone(), r#type.clone())),
+                _ => None,
+            })
+            .collect();
+        use model::Type::*;
+        assert!(casts.contains(&(Long, UnsignedChar)), "{:?}", casts);
+        assert!(casts.contains(&(Int, UnsignedInt)), "{:?}", casts);
+        assert!(casts.contains(&(Int, Long)), "{:?}", casts);
+        assert!(casts.contains(&(UnsignedInt, Long)), "{:?}", casts);
+        // Constants are converted in place
+        assert!(!casts.iter().any(|(from, _)| *from == Double), "{:?}", casts);
+    }
+
+    #[test]
+    fn test_narrow_updates_are_converted_before_the_store() {
+        let ir = lower("int f(unsigned short w, signed char c) { w++; c += 100; return w + c; }");
+        let casts: Vec<(model::Type, model::Type)> = all_instructions(first_fn(&ir))
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Cast { src_type, r#type, .. } => Some((src_type.clone(), r#type.clone())),
+                _ => None,
+            })
+            .collect();
+        use model::Type::*;
+        assert!(casts.contains(&(Int, UnsignedShort)), "{:?}", casts);
+        assert!(casts.contains(&(Int, Char)), "{:?}", casts);
+    }
+
+    #[test]
+    fn test_instructions_carry_statement_spans() {
+        let src = "int f(int x) {\n    int a = x * 3;\n    while (a > 10) {\n        a = a - 2;\n    }\n    return a;\n}\n";
+        let (tokens, spans): (Vec<_>, Vec<_>) =
+            lexer::lex_spanned(src, model::FileId(0)).unwrap().into_iter().map(|t| (t.node, t.span)).unzip();
+        let ast = parser::parse_tokens_with_spans(&tokens, &spans).unwrap();
+        let ir = Lowerer::new().lower_program(&ast).unwrap();
+        let mut map = model::SourceMap::new();
+        let file = map.add_file("t.c", src);
+        let line = |span: model::Span| map.line_col(file, span.start).unwrap().line;
+
+        let f = first_fn(&ir);
+        let mut lines = std::collections::HashMap::new();
+        for block in &f.blocks {
+            assert_eq!(block.spans.len(), block.instructions.len());
+            for (inst, span) in block.instructions.iter().zip(&block.spans) {
+                if let Instruction::Binary { op, .. } = inst {
+                    lines.insert(format!("{:?}", op), line(*span));
+                }
+            }
+        }
+        assert_eq!(lines["Mul"], 2);
+        assert_eq!(lines["Greater"], 3);
+        assert_eq!(lines["Sub"], 4);
+        let ret = f.blocks.iter().find(|b| matches!(b.terminator, Terminator::Ret(_))).unwrap();
+        assert_eq!(line(ret.terminator_span), 6);
+    }
+
+    #[test]
+    fn test_conditional_converts_branches_to_common_type() {
+        let ir = lower("double f(int c, int x) { return c ? x : 0.5; }");
+        let instrs = all_instructions(first_fn(&ir));
+        let has_cast = instrs
+            .iter()
+            .any(|i| matches!(i, Instruction::Cast { r#type: model::Type::Double, .. }));
+        assert!(has_cast, "the int branch should be converted to double");
+        let ir = lower("double f(int c) { return c ? 1 : 0.5; }");
+        let instrs = all_instructions(first_fn(&ir));
+        let copies_float = instrs
+            .iter()
+            .any(|i| matches!(i, Instruction::Copy { src: Operand::FloatConstant(f), .. } if *f == 1.0));
+        assert!(copies_float, "the constant 1 should become 1.0");
+    }
+
+    // ─── -fsanitize=undefined ───────────────────────────────────
+    #[test]
+    fn test_sanitize_checks_division_and_adds_helper() {
+        let tokens = lex("int f(int a, int b) { return a / b + (a << 3) + (unsigned)a * 2u; }").unwrap();
+        let ast = parse_tokens(&tokens).unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.set_sanitize_undefined(true);
+        let ir = lowerer.lower_program(&ast).unwrap();
+
+        let f = first_fn(&ir);
+        let aborts = all_instructions(f)
+            .into_iter()
+            .filter(|i| matches!(i, Instruction::Call { name, .. } if name == "__ubsan_abort"))
+            .count();
+        // Zero divisor, INT_MIN / -1, and the signed add; the constant shift
+        // and the unsigned multiply need no check
+        assert_eq!(aborts, 3);
+        let helper = ir.functions.iter().find(|f| f.name == "__ubsan_abort").expect("helper should be added");
+        assert!(helper.is_static);
+        assert_eq!(ir.global_strings.len(), 2, "one message per kind of check");
+
+        let plain = lower("int f(int a, int b) { return a / b; }");
+        assert!(plain.functions.iter().all(|f| f.name != "__ubsan_abort"));
+    }
+
+    #[test]
+    fn test_sanitize_bounds_pads_arrays_and_checks_indices() {
+        let src = "int f(int i) { int a[4]; a[1] = 2; a[i] = 3; int *end = &a[4]; return a[0] + (end != 0); }";
+        let ast = parse_tokens(&lex(src).unwrap()).unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.set_sanitize_bounds(true);
+        let ir = lowerer.lower_program(&ast).unwrap();
+
+        let f = first_fn(&ir);
+        let insts = all_instructions(f);
+        // Two guard slots round up to two more ints
+        assert!(insts.iter().any(|i| matches!(i, Instruction::Alloca { r#type: model::Type::Array(_, 6), .. })));
+        let aborts = insts
+            .iter()
+            .filter(|i| matches!(i, Instruction::Call { name, .. } if name == "__ubsan_abort"))
+            .count();
+        // `a[i]` and the guard check at the return; constant indices, `&a[4]`
+        // included, need no check
+        assert_eq!(aborts, 2);
+
+        let plain = lower(src);
+        assert!(all_instructions(first_fn(&plain)).iter().any(|i| matches!(i, Instruction::Alloca { r#type: model::Type::Array(_, 4), .. })));
+    }
+
+    // ─── DOT dumps ──────────────────────────────────────────────
+    #[test]
+    fn test_cfg_dot_has_branch_edges() {
+        let ir = lower("int main(int c) { if (c) { return 1; } return 2; }");
+        let dot = cfg_to_dot(first_fn(&ir));
+        assert!(dot.starts_with("digraph \"main\""));
+        assert!(dot.contains("[label=\"T\"]"));
+        assert!(dot.contains("[label=\"F\"]"));
+    }
+
+    #[test]
+    fn test_render_cfg_lists_instructions_and_phi_edges() {
+        let mut ir = lower("int main(int c) { int x = 1; if (c) { x = 2; } return x; }");
+        mem2reg(&mut ir.functions[0]);
+        let f = first_fn(&ir);
+        let phi = all_instructions(f)
+            .into_iter()
+            .find_map(|i| match i {
+                Instruction::Phi { dest, preds } => Some((dest, preds)),
+                _ => None,
+            })
+            .expect("mem2reg should merge x with a phi");
+        let (dest, preds) = phi;
+        let (pred, src) = preds[0];
+
+        let dot = render_cfg(f, CfgFormat::Dot);
+        assert!(dot.starts_with("digraph \"main\""));
+        assert!(dot.contains(&format!("%{} = phi [bb{}: %{}]", dest.0, pred.0, src.0)));
+        assert!(dot.contains(&format!("%{} <- %{}", dest.0, src.0)), "edge should carry the phi copy:\n{}", dot);
+
+        let mermaid = render_cfg(f, CfgFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart TD"));
+        assert!(mermaid.contains(&format!("%{} #lt;- %{}", dest.0, src.0)));
+        assert!(mermaid.contains(" -->|\"T"));
+    }
+
+    #[test]
+    fn test_callgraph_dot_marks_external_callees() {
+        let ir = lower("int puts(const char *s); int f() { return puts(\"x\"); } int main() { return f(); }");
+        let dot = callgraph_to_dot(&ir);
+        assert!(dot.contains("\"main\" -> \"f\";"));
+        assert!(dot.contains("\"f\" -> \"puts\";"));
+        assert!(dot.contains("\"puts\" [shape=ellipse, style=dashed];"));
+    }
+
+    #[test]
+    fn test_callgraph_edges_recursion_and_reachability() {
+        use callgraph::CallKind;
+        let ir = lower(
+            "int puts(const char *s);
+             typedef int (*unop)(int);
+             int sq(int x) { return x * x; }
+             int neg(int x) { return -x; }
+             unop table[] = { neg };
+             int apply(int (*f)(int), int v) { return f(v); }
+             int is_odd(int n);
+             int is_even(int n) { return n == 0 ? 1 : is_odd(n - 1); }
+             int is_odd(int n) { return n == 0 ? 0 : is_even(n - 1); }
+             static int unused(void) { return puts(\"x\"); }
+             int main() { return apply(sq, 3) + is_even(4); }",
+        );
+        let graph = callgraph::build(&ir);
+        let edges: Vec<(&str, &str, CallKind)> =
+            graph.edges().map(|e| (e.caller.as_str(), e.callee.as_str(), e.kind)).collect();
+        assert!(edges.contains(&("main", "apply", CallKind::Direct)));
+        assert!(edges.contains(&("unused", "puts", CallKind::Direct)));
+        // The call through `f` may reach either function whose address is taken
+        assert!(edges.contains(&("apply", "sq", CallKind::Indirect)));
+        assert!(edges.contains(&("apply", "neg", CallKind::Indirect)));
+        assert!(!graph.callees("apply").any(|c| c == "main" || c == "is_even"));
+        assert!(graph.calls_indirectly("apply") && !graph.calls_indirectly("main"));
+        assert!(graph.is_address_taken("neg") && !graph.is_address_taken("apply"));
+        assert!(!graph.is_defined("puts"));
+        assert_eq!(graph.callers("is_odd").collect::<Vec<_>>(), ["is_even"]);
+
+        assert!(graph.is_recursive("is_even") && graph.is_recursive("is_odd"));
+        assert!(!graph.is_recursive("main") && !graph.is_recursive("apply"));
+
+        let live = graph.reachable_from(["main"]);
+        assert!(live.contains("neg") && live.contains("is_odd"));
+        assert!(!live.contains("unused") && !live.contains("puts"));
+    }
+
+    // ─── _Bool conversions ──────────────────────────────────────
+    #[test]
+    fn test_bool_store_normalizes() {
+        let ir = lower("int main(int x) { _Bool b = x; return b; }");
+        let f = first_fn(&ir);
+        let has_ne = all_instructions(f).iter().any(|i| matches!(
+            i,
+            Instruction::Binary { op: model::BinaryOp::NotEqual, right: Operand::Constant(0), .. }
+        ));
+        assert!(has_ne, "storing an int into _Bool should compare against 0");
+    }
+
+    #[test]
+    fn test_bool_constant_cast_folds() {
+        let ir = lower("int main() { return (_Bool)512; }");
+        assert!(matches!(first_fn(&ir).blocks[0].terminator, Terminator::Ret(Some(Operand::Constant(1)))));
+    }
+
+    // ─── Data layout ────────────────────────────────────────────
+    #[test]
+    fn test_sizeof_follows_data_layout() {
+        let src = "int main() { return sizeof(char *) + sizeof(long); }";
+        let ast = parse_tokens(&lex(src).unwrap()).unwrap();
+        let ilp32 = model::DataLayout { pointer_size: 4, pointer_align: 4, long_size: 4, ..model::DataLayout::lp64() };
+        let ir = Lowerer::with_data_layout(ilp32).lower_program(&ast).unwrap();
+        assert!(matches!(
+            first_fn(&ir).blocks[0].instructions[0],
+            Instruction::Binary { left: Operand::Constant(4), right: Operand::Constant(4), .. }
+        ));
+    }
+
+    #[test]
+    fn test_sizeof_suffixed_constant() {
+        let ir = lower("int main() { return sizeof(1u) + sizeof(1LL) + sizeof(3000000000); }");
+        assert!(matches!(
+            first_fn(&ir).blocks[0].instructions[0],
+            Instruction::Binary { left: Operand::Constant(4), right: Operand::Constant(8), .. }
+        ));
+        let insts = all_instructions(first_fn(&ir));
+        assert!(matches!(insts[1], Instruction::Binary { right: Operand::Constant(8), .. }));
+    }
+
+    // ─── _Complex ───────────────────────────────────────────────
+    #[test]
+    fn test_complex_stored_as_part_pair() {
+        let ir = lower("int main() { float _Complex z = 1.0f + 2.0if; return __imag__ z; }");
+        let insts = all_instructions(first_fn(&ir));
+        let part_stores: Vec<_> = insts.iter().filter_map(|i| match i {
+            Instruction::Store { src: Operand::FloatConstant(f), value_type: model::Type::Float, .. } => Some(*f),
+            _ => None,
+        }).collect();
+        assert_eq!(part_stores, vec![1.0, 2.0], "constant parts are folded and stored real first");
+    }
+
+    // ─── Qualifiers ─────────────────────────────────────────────
+    #[test]
+    fn test_volatile_pointee_accesses() {
+        let ir = lower("int f(volatile int *p, int *q) { *p = 1; *q = 2; return *p + *q; }");
+        let insts = all_instructions(first_fn(&ir));
+        let volatile_stores = insts.iter().filter(|i| matches!(i, Instruction::Store { volatile: true, .. })).count();
+        let volatile_loads = insts.iter().filter(|i| matches!(i, Instruction::Load { volatile: true, .. })).count();
+        assert_eq!(volatile_stores, 1, "only the store through p is volatile");
+        assert_eq!(volatile_loads, 1, "only the load through p is volatile");
+    }
+
+    // ─── Interpreter ────────────────────────────────────────────
+    /// Helper: interpret `main` and return (exit status, stdout)
+    fn run(src: &str, args: &[&str]) -> (Result<i32, InterpError>, String) {
+        let ir = lower(src);
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut out = Vec::new();
+        let status = Interpreter::new(&ir, &mut out).and_then(|mut interp| interp.run_main("prog", &args));
+        (status, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_interpret_program_output() {
+        let src = "int printf(const char *fmt, ...);\n\
+                   int fib(int n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }\n\
+                   int sum(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); int t = 0;\n\
+                     for (int i = 0; i < n; i++) t += __builtin_va_arg(ap, int); __builtin_va_end(ap); return t; }\n\
+                   const char *names[] = { \"zero\", \"one\" };\n\
+                   unsigned int big = 4000000000u;\n\
+                   int main(int argc, char **argv) {\n\
+                     char c = 127; c++;\n\
+                     printf(\"%d %s %s %u %d %5.2f|%-3x|\\n\", fib(10), names[1], argv[1], big / 3, c, 2.5, 255);\n\
+                     return sum(3, 1, 2, argc);\n\
+                   }";
+        let (status, out) = run(src, &["arg"]);
+        assert_eq!(status, Ok(5));
+        assert_eq!(out, "55 one arg 1333333333 -128  2.50|ff |\n");
+    }
+
+    #[test]
+    fn test_interpret_exit_and_traps() {
+        let (status, _) = run("void exit(int code); int main() { exit(300); return 1; }", &[]);
+        assert_eq!(status, Ok(300 & 0xff), "exit status is truncated to 8 bits");
+
+        let (status, _) = run("int main() { int *p = 0; return *p; }", &[]);
+        assert!(matches!(status, Err(InterpError::Trap { signal: 11, .. })), "{:?}", status);
+        assert_eq!(status.unwrap_err().exit_code(), Some(139));
+
+        let (status, _) = run("int main(int argc, char **argv) { return 10 / (argc - 1); }", &[]);
+        assert!(matches!(status, Err(InterpError::Trap { signal: 8, .. })), "{:?}", status);
+
+        let (status, _) = run("int nothing(int x); int main() { return nothing(1); }", &[]);
+        assert_eq!(status, Err(InterpError::Unsupported("call to external function 'nothing'".to_string())));
+    }
+
+    #[test]
+    fn test_interpret_struct_copy_and_partial_init() {
+        let src = "struct P { long x, y, z; };\n\
+                   int main() { struct P a = {1, 2, 3}; struct P b = {.y = 4}; int v[5] = {[3] = 6};\n\
+                     b = a; a.x = 10; return (int)(b.x + b.y + b.z) + v[0] + v[3] + v[4]; }";
+        assert_eq!(run(src, &[]).0, Ok(12));
+    }
+
+    #[test]
+    fn test_interpret_struct_assignment_forms() {
+        let src = "struct S { long a, b, c; }; struct W { int tag; struct S in; };\n\
+                   int main(int argc, char **argv) { struct S a = {1, 2, 3}, b = {4, 5, 6}, c, d, arr[2] = {b, a};\n\
+                     c = d = argc > 1 ? b : a; struct W w = {7, d}; struct S *p = &arr[0]; *p = w.in; b.a = 40;\n\
+                     return (int)(c.c + d.b + w.in.a + arr[0].c + arr[1].a + b.a); }";
+        assert_eq!(run(src, &[]).0, Ok(3 + 2 + 1 + 3 + 1 + 40));
+        assert_eq!(run(src, &["x"]).0, Ok(6 + 5 + 4 + 6 + 1 + 40));
+    }
+
+    #[test]
+    fn test_interpret_array_initializers_zero_fill() {
+        let src = "struct S { int n; char name[8]; };\n\
+                   int sum(const char *p, int n) { int s = 0; for (int i = 0; i < n; i++) s += p[i]; return s; }\n\
+                   int f(void) { char buf[16] = \"hi\"; char m[2][4] = {\"ab\", \"c\"}; struct S s = {1, \"xy\"};\n\
+                     int b[2][3] = {{1}, {2, 3}};\n\
+                     return (sum(buf, 16) == 'h' + 'i') + (sum(m[0], 8) == 'a' + 'b' + 'c') * 2\n\
+                       + (sum(s.name, 8) == 'x' + 'y') * 4 + b[0][2] * 10 + b[1][1] * 8; }\n\
+                   void dirty(void) { char junk[64]; for (int i = 0; i < 64; i++) junk[i] = 7; }\n\
+                   int main() { dirty(); return f(); }";
+        assert_eq!(run(src, &[]).0, Ok(1 + 2 + 4 + 24));
+    }
+
+    #[test]
+    fn test_interpret_conditional_forms() {
+        let src = "int calls; void hit(void) { calls++; }\n\
+                   int main(int argc, char **argv) { int arr[3] = {argc ? 4 : 5, argc > 1 ? 6 : 8, (int){argc ? 1 : 2}};\n\
+                     double d = argc > 1 ? 1 : 2.5; long l = argc > 1 ? 1 : -1; int g = argc ?: 9;\n\
+                     argc > 1 ? hit() : (void)0; return arr[0] + arr[1] + arr[2] + (int)(d * 2) + (int)l + g + calls; }";
+        assert_eq!(run(src, &[]).0, Ok(4 + 8 + 1 + 5 - 1 + 1));
+        assert_eq!(run(src, &["x"]).0, Ok(4 + 6 + 1 + 2 + 1 + 2 + 1));
+    }
+
+    #[test]
+    fn test_interpret_switch_fallthrough_and_nesting() {
+        let src = "int f(int x, unsigned u) { int r = 0;\n\
+                   for (int i = 0; i < 3; i++) { switch (i + x) { case 0: r += 1; case 1: r += 2; break;\n\
+                     default: switch (i) { case 0: r += 100; break; case 1: continue; } r += 1000; } r += 10; }\n\
+                   switch (u) { case -1: r += 5; } return r; }\n\
+                   int main(int argc, char **argv) { return f(argc - 1, -argc) - f(argc, 0); }";
+        // f(0, -1): 1 + 2 + 2 + 1000 + 30 + 5; f(1, 0): 2 + 1000 + 20
+        assert_eq!(run(src, &[]).0, Ok(1040 - 1022));
+    }
+
+    #[test]
+    fn test_interpret_promotes_narrow_operands() {
+        let src = "int main() { _Bool b = 1; unsigned char c = 200; short s = -3;\n\
+                   long d = c * 2 - (&c + 1 - &c); return b + 41 + (c + c > 255) + (s >> 1 < 0) + (int)(d - 399); }";
+        assert_eq!(run(src, &[]).0, Ok(44));
+    }
+
+    #[test]
+    fn test_interpret_global_initializers_use_c_types() {
+        let src = "long a = (-1 < 0u) + (unsigned char)300; unsigned b = -1 / 2u; char *s = \"hi\";\n\
+                   int main() { return (int)a + (b > 0x7ffffffe) + s[1] - 'i'; }";
+        assert_eq!(run(src, &[]).0, Ok(45));
+    }
 }
@@ -0,0 +1,402 @@
+// Linking IR modules
+//
+// `link_programs` merges the IR of several translation units into one
+// program, so the optimizer sees every function at once (link-time
+// optimization). It does the symbol resolution a linker would:
+//
+// - A `static` function or global whose name another unit also uses is
+//   renamed `name.N`, N being its unit's index.
+// - String literals, which every unit labels from `str_0`, are renumbered,
+//   and a literal equal to one from an earlier unit shares its label.
+// - Two definitions of one external function or initialized global are
+//   an error unless one is weak, in which case the strong one wins. A
+//   tentative definition (`int x;`) or `extern` declaration yields to the
+//   definition. So does a prototype in another unit's declarations.
+// - Struct, union, and enum definitions with the same tag and the same
+//   members are merged. A tag defined differently in two units (anonymous
+//   tags `<anonymous N>` are numbered per unit) is renamed like a static.
+//
+// Spans keep the file ids of their own unit's source map.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use model::{Attribute, CompileError, EnumDef, ErrorCode, Expr, GlobalVar, StructDef, Type, UnionDef};
+
+use crate::callgraph::note_names;
+use crate::types::{Function, IRProgram, Instruction, Operand, Terminator};
+
+/// Merge `modules` into one program; see the module comment.
+pub fn link_programs(modules: Vec<IRProgram>) -> Result<IRProgram, CompileError> {
+    let used: Vec<HashSet<String>> = modules.iter().map(used_names).collect();
+    let data_layout = modules.first().map(|m| m.data_layout).unwrap_or_default();
+    if modules.iter().any(|m| m.data_layout != data_layout) {
+        return Err(CompileError::new(
+            ErrorCode::IncompatibleTypes,
+            "cannot link units compiled for targets with different data layouts",
+        ));
+    }
+    let mut linked = IRProgram {
+        functions: Vec::new(),
+        global_strings: Vec::new(),
+        globals: Vec::new(),
+        structs: Vec::new(),
+        unions: Vec::new(),
+        enums: Vec::new(),
+        declarations: Vec::new(),
+        data_layout,
+    };
+
+    for (index, mut module) in modules.into_iter().enumerate() {
+        let used_elsewhere = |name: &str| used.iter().enumerate().any(|(i, names)| i != index && names.contains(name));
+        let mut renamer = type_renames(&module, &linked, index);
+        let mut new_strings = Vec::new();
+        for (label, content) in std::mem::take(&mut module.global_strings) {
+            let shared = linked.global_strings.iter().chain(&new_strings).find(|(_, c)| *c == content);
+            let linked_label = match shared {
+                Some((shared_label, _)) => shared_label.clone(),
+                None => {
+                    let linked_label = format!("str_{}", linked.global_strings.len() + new_strings.len());
+                    new_strings.push((linked_label.clone(), content));
+                    linked_label
+                }
+            };
+            renamer.symbols.insert(label, linked_label);
+        }
+        let statics = module.functions.iter().filter(|f| f.is_static).map(|f| &f.name);
+        let static_globals = module.globals.iter().filter(|g| g.is_static && !g.is_extern).map(|g| &g.name);
+        for name in statics.chain(static_globals) {
+            if used_elsewhere(name) {
+                renamer.symbols.insert(name.clone(), format!("{}.{}", name, index));
+            }
+        }
+        renamer.program(&mut module);
+
+        for def in module.structs {
+            if !linked.structs.iter().any(|s| s.name == def.name) {
+                linked.structs.push(def);
+            }
+        }
+        for def in module.unions {
+            if !linked.unions.iter().any(|u| u.name == def.name) {
+                linked.unions.push(def);
+            }
+        }
+        for def in module.enums {
+            if !linked.enums.iter().any(|e| e.name == def.name) {
+                linked.enums.push(def);
+            }
+        }
+        linked.global_strings.extend(new_strings);
+        for global in module.globals {
+            merge_global(&mut linked.globals, global)?;
+        }
+        for func in module.functions {
+            merge_function(&mut linked.functions, func)?;
+        }
+        for decl in module.declarations {
+            if !linked.declarations.iter().any(|d| d.name == decl.name) {
+                linked.declarations.push(decl);
+            }
+        }
+    }
+    // A prototype in one unit is a definition in another
+    let defined: HashSet<String> = linked.functions.iter().map(|f| f.name.clone()).collect();
+    linked.declarations.retain(|d| !defined.contains(&d.name));
+    Ok(linked)
+}
+
+/// Every name a module defines or refers to.
+fn used_names(program: &IRProgram) -> HashSet<String> {
+    let mut names: HashSet<String> = program.functions.iter().map(|f| f.name.clone()).collect();
+    names.extend(program.globals.iter().map(|g| g.name.clone()));
+    for func in &program.functions {
+        for block in &func.blocks {
+            for inst in &block.instructions {
+                if let Instruction::Call { name, .. } = inst {
+                    names.insert(name.clone());
+                }
+                inst.for_each_operand(|op| {
+                    if let Operand::Global(name) = op {
+                        names.insert(name.clone());
+                    }
+                });
+            }
+            if let Some(Operand::Global(name)) = terminator_operand(&block.terminator) {
+                names.insert(name.clone());
+            }
+        }
+    }
+    let mut referenced = BTreeSet::new();
+    for init in program.globals.iter().filter_map(|g| g.init.as_ref()) {
+        note_names(init, &mut referenced);
+    }
+    names.extend(referenced);
+    names
+}
+
+fn terminator_operand(term: &Terminator) -> Option<&Operand> {
+    match term {
+        Terminator::CondBr { cond: op, .. } | Terminator::Ret(Some(op)) | Terminator::IndirectBr { target: op }
+        | Terminator::Switch { value: op, .. } => Some(op),
+        _ => None,
+    }
+}
+
+/// Renames for the tags of `module` that `linked` already defines
+/// differently. Renaming one tag changes the members of the tags that
+/// contain it, so this repeats until nothing new clashes.
+fn type_renames(module: &IRProgram, linked: &IRProgram, index: usize) -> Renamer {
+    let mut renamer = Renamer::default();
+    loop {
+        let mut clashes = Vec::new();
+        for def in &module.structs {
+            let mut renamed = def.clone();
+            renamer.struct_def(&mut renamed);
+            if linked.structs.iter().any(|s| s.name == renamed.name && *s != renamed) {
+                clashes.push((Tag::Struct, def.name.clone()));
+            }
+        }
+        for def in &module.unions {
+            let mut renamed = def.clone();
+            renamer.union_def(&mut renamed);
+            if linked.unions.iter().any(|u| u.name == renamed.name && *u != renamed) {
+                clashes.push((Tag::Union, def.name.clone()));
+            }
+        }
+        for def in &module.enums {
+            let mut renamed = def.clone();
+            renamer.enum_def(&mut renamed);
+            if linked.enums.iter().any(|e| e.name == renamed.name && *e != renamed) {
+                clashes.push((Tag::Enum, def.name.clone()));
+            }
+        }
+        let mut changed = false;
+        for (tag, name) in clashes {
+            let map = match tag {
+                Tag::Struct => &mut renamer.structs,
+                Tag::Union => &mut renamer.unions,
+                Tag::Enum => &mut renamer.enums,
+            };
+            if !map.contains_key(&name) {
+                map.insert(name.clone(), format!("{}.{}", name, index));
+                changed = true;
+            }
+        }
+        if !changed {
+            return renamer;
+        }
+    }
+}
+
+enum Tag {
+    Struct,
+    Union,
+    Enum,
+}
+
+fn is_weak(attributes: &[Attribute]) -> bool {
+    attributes.contains(&Attribute::Weak)
+}
+
+fn multiple_definition(name: &str) -> CompileError {
+    CompileError::new(ErrorCode::Redeclaration, format!("multiple definition of '{}'", name))
+}
+
+fn merge_function(functions: &mut Vec<Function>, func: Function) -> Result<(), CompileError> {
+    let existing = if func.is_static { None } else { functions.iter_mut().find(|f| !f.is_static && f.name == func.name) };
+    match existing {
+        None => functions.push(func),
+        Some(existing) => match (is_weak(&existing.attributes), is_weak(&func.attributes)) {
+            (_, true) => {}
+            (true, false) => *existing = func,
+            (false, false) => return Err(multiple_definition(&func.name)),
+        },
+    }
+    Ok(())
+}
+
+fn merge_global(globals: &mut Vec<GlobalVar>, global: GlobalVar) -> Result<(), CompileError> {
+    let external = |g: &GlobalVar| !g.is_static || g.is_extern;
+    let existing = if external(&global) { globals.iter_mut().find(|g| external(g) && g.name == global.name) } else { None };
+    let Some(existing) = existing else {
+        globals.push(global);
+        return Ok(());
+    };
+    // Definitions with an initializer outrank tentative ones, which outrank
+    // `extern` declarations
+    let rank = |g: &GlobalVar| if g.is_extern { 0 } else if g.init.is_none() { 1 } else { 2 };
+    match (rank(existing), rank(&global)) {
+        (2, 2) => match (is_weak(&existing.attributes), is_weak(&global.attributes)) {
+            (_, true) => {}
+            (true, false) => *existing = global,
+            (false, false) => return Err(multiple_definition(&global.name)),
+        },
+        (old, new) if new > old => *existing = global,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Symbol and tag renames applied to a whole module.
+#[derive(Default)]
+struct Renamer {
+    symbols: HashMap<String, String>,
+    structs: HashMap<String, String>,
+    unions: HashMap<String, String>,
+    enums: HashMap<String, String>,
+}
+
+impl Renamer {
+    fn rename(map: &HashMap<String, String>, name: &mut String) {
+        if let Some(new) = map.get(name) {
+            *name = new.clone();
+        }
+    }
+
+    fn program(&mut self, program: &mut IRProgram) {
+        program.structs.iter_mut().for_each(|def| self.struct_def(def));
+        program.unions.iter_mut().for_each(|def| self.union_def(def));
+        program.enums.iter_mut().for_each(|def| self.enum_def(def));
+        for (label, _) in &mut program.global_strings {
+            Self::rename(&self.symbols, label);
+        }
+        for global in &mut program.globals {
+            Self::rename(&self.symbols, &mut global.name);
+            self.ty(&mut global.r#type);
+            if let Some(init) = &mut global.init {
+                self.expr(init);
+            }
+        }
+        for decl in &mut program.declarations {
+            self.ty(&mut decl.return_type);
+            decl.params.iter_mut().for_each(|param| self.ty(param));
+        }
+        for func in &mut program.functions {
+            self.function(func);
+        }
+    }
+
+    fn struct_def(&mut self, def: &mut StructDef) {
+        Self::rename(&self.structs, &mut def.name);
+        def.fields.iter_mut().for_each(|field| self.ty(&mut field.field_type));
+    }
+
+    fn union_def(&mut self, def: &mut UnionDef) {
+        Self::rename(&self.unions, &mut def.name);
+        def.fields.iter_mut().for_each(|field| self.ty(&mut field.field_type));
+    }
+
+    fn enum_def(&mut self, def: &mut EnumDef) {
+        Self::rename(&self.enums, &mut def.name);
+    }
+
+    fn function(&mut self, func: &mut Function) {
+        Self::rename(&self.symbols, &mut func.name);
+        self.ty(&mut func.return_type);
+        func.params.iter_mut().for_each(|(ty, _)| self.ty(ty));
+        func.var_types.values_mut().for_each(|ty| self.ty(ty));
+        for block in &mut func.blocks {
+            for inst in &mut block.instructions {
+                self.instruction(inst);
+            }
+            match &mut block.terminator {
+                Terminator::CondBr { cond: op, .. } | Terminator::Ret(Some(op)) | Terminator::IndirectBr { target: op }
+                | Terminator::Switch { value: op, .. } => {
+                    self.operand(op)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn operand(&self, op: &mut Operand) {
+        if let Operand::Global(name) = op {
+            Self::rename(&self.symbols, name);
+        }
+    }
+
+    fn instruction(&mut self, inst: &mut Instruction) {
+        if let Instruction::Call { name, .. } = inst {
+            Self::rename(&self.symbols, name);
+        }
+        inst.for_each_type_mut(|ty| self.ty(ty));
+        inst.for_each_operand_mut(|op| self.operand(op));
+    }
+
+    fn ty(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Struct(name) => Self::rename(&self.structs, name),
+            Type::Union(name) => Self::rename(&self.unions, name),
+            Type::Enum(name) => Self::rename(&self.enums, name),
+            Type::Array(inner, _) | Type::Pointer(inner, _) | Type::Complex(inner) => self.ty(inner),
+            Type::FunctionPointer { return_type, param_types, .. } => {
+                self.ty(return_type);
+                param_types.iter_mut().for_each(|ty| self.ty(ty));
+            }
+            Type::TypeofExpr(expr) => self.expr(expr),
+            _ => {}
+        }
+    }
+
+    /// A global initializer, renaming the globals it names and the tags of
+    /// the types it mentions.
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Variable(name) => Self::rename(&self.symbols, name),
+            Expr::TypedConstant(_, ty) | Expr::SizeOf(ty) | Expr::AlignOf(ty) | Expr::BuiltinOffsetof { r#type: ty, .. } => {
+                self.ty(ty)
+            }
+            Expr::Cast(ty, inner) | Expr::VaArg { list: inner, r#type: ty } => {
+                self.ty(ty);
+                self.expr(inner);
+            }
+            Expr::CompoundLiteral { r#type, init: items } => {
+                self.ty(r#type);
+                items.iter_mut().for_each(|item| self.expr(&mut item.value));
+            }
+            Expr::InitList(items) => items.iter_mut().for_each(|item| self.expr(&mut item.value)),
+            Expr::Binary { left, right, .. }
+            | Expr::Index { array: left, index: right }
+            | Expr::Expect { expr: left, expected: right } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { expr: inner, .. }
+            | Expr::PostfixIncrement(inner)
+            | Expr::PostfixDecrement(inner)
+            | Expr::PrefixIncrement(inner)
+            | Expr::PrefixDecrement(inner)
+            | Expr::SizeOfExpr(inner)
+            | Expr::Member { expr: inner, .. }
+            | Expr::PtrMember { expr: inner, .. }
+            | Expr::Real(inner)
+            | Expr::Imag(inner) => self.expr(inner),
+            Expr::Call { func, args } => {
+                self.expr(func);
+                args.iter_mut().for_each(|arg| self.expr(arg));
+            }
+            Expr::Conditional { condition, then_expr, else_expr } => {
+                self.expr(condition);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+            Expr::Comma(exprs) => exprs.iter_mut().for_each(|e| self.expr(e)),
+            Expr::Generic { controlling, associations } => {
+                self.expr(controlling);
+                for (ty, e) in associations {
+                    if let Some(ty) = ty {
+                        self.ty(ty);
+                    }
+                    self.expr(e);
+                }
+            }
+            Expr::Constant(_)
+            | Expr::FloatConstant(_)
+            | Expr::ImaginaryConstant(_)
+            | Expr::StringLiteral(_)
+            | Expr::WideStringLiteral(..)
+            | Expr::StmtExpr(_)
+            | Expr::LabelAddr(_) => {}
+        }
+    }
+}
@@ -1,43 +1,51 @@
-use model::{Type, Program as AstProgram, Function as AstFunction, Expr as AstExpr};
-use std::collections::{HashMap, HashSet};
-use crate::types::{VarId, BlockId, BasicBlock, Function, IRProgram, Instruction, Terminator, Operand};
+use model::{CompileError, ErrorCode, Type, Program as AstProgram, Function as AstFunction, Expr as AstExpr, Span};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use crate::types::{VarId, BlockId, BasicBlock, Declaration, Function, IRProgram, Instruction, Terminator, Operand};
+use model::BinaryOp;
+use crate::sanitize::UbCheck;
+use crate::cfg::Cfg;
 
 /// Control-flow bookkeeping for loops, switches, and gotos.
 /// Extracted from Lowerer so that each concern has its own struct.
 pub(crate) struct ControlFlowContext {
-    pub loop_context: Vec<(BlockId, BlockId)>,       // (continue_target, break_target)
-    pub break_targets: Vec<BlockId>,                  // for switch statements
-    pub current_switch_cases: Vec<(i64, BlockId)>,    // (value, block)
-    pub current_default: Option<BlockId>,
+    pub continue_targets: Vec<BlockId>,               // innermost loop last
+    pub break_targets: Vec<BlockId>,                  // innermost loop or switch last
+    pub switches: Vec<SwitchContext>,                 // innermost switch last
     pub labels: HashMap<String, BlockId>,             // label name => block
     pub pending_gotos: Vec<(String, BlockId)>,        // (label, goto_block) for forward gotos
-    pub label_addrs: HashSet<String>,                 // labels with address taken (&&label)
+    pub label_addrs: BTreeSet<String>,                // labels with address taken (&&label), in name order
 }
 
 impl ControlFlowContext {
     pub fn new() -> Self {
         Self {
-            loop_context: Vec::new(),
+            continue_targets: Vec::new(),
             break_targets: Vec::new(),
-            current_switch_cases: Vec::new(),
-            current_default: None,
+            switches: Vec::new(),
             labels: HashMap::new(),
             pending_gotos: Vec::new(),
-            label_addrs: HashSet::new(),
+            label_addrs: BTreeSet::new(),
         }
     }
 
     pub fn reset(&mut self) {
-        self.loop_context.clear();
+        self.continue_targets.clear();
         self.break_targets.clear();
-        self.current_switch_cases.clear();
-        self.current_default = None;
+        self.switches.clear();
         self.labels.clear();
         self.pending_gotos.clear();
         self.label_addrs.clear();
     }
 }
 
+/// The `case` and `default` labels found so far in the body of one switch.
+/// Their blocks stay unsealed until the dispatch branches to them.
+pub(crate) struct SwitchContext {
+    pub value_type: Type,                             // promoted type of the controlling expression
+    pub cases: Vec<(i64, BlockId)>,                   // (value, block), in source order
+    pub default: Option<BlockId>,
+}
+
 /// Main AST to IR lowering engine with SSA construction
 pub struct Lowerer {
     pub(crate) next_var: usize,
@@ -47,10 +55,18 @@ pub struct Lowerer {
     pub(crate) variable_defs: HashMap<String, HashMap<BlockId, VarId>>,
     pub(crate) blocks: Vec<BasicBlock>,
     pub(crate) current_block: Option<BlockId>,
+    // Span of the statement being lowered, given to every instruction it emits
+    pub(crate) current_span: Span,
     pub(crate) incomplete_phis: HashMap<BlockId, HashMap<String, VarId>>,
     pub(crate) sealed_blocks: HashSet<BlockId>,
     pub(crate) global_strings: Vec<(String, String)>,
+    // Label of each distinct string literal, so equal literals share one
+    pub(crate) string_labels: HashMap<String, String>,
+    /// `L"..."`, `u"..."`, and `U"..."` literals, as static const arrays.
+    pub(crate) wide_strings: Vec<model::GlobalVar>,
     pub(crate) variable_allocas: HashMap<String, VarId>,
+    // Source name of each local's alloca, kept across scopes for debug info
+    pub(crate) local_names: HashMap<VarId, String>,
     pub(crate) global_vars: HashSet<String>,
     pub(crate) global_types: HashMap<String, Type>,
     pub(crate) function_names: HashSet<String>,
@@ -60,21 +76,36 @@ pub struct Lowerer {
     pub(crate) struct_defs: HashMap<String, model::StructDef>,
     pub(crate) union_defs: HashMap<String, model::UnionDef>,
     pub(crate) enum_constants: HashMap<String, i64>, // enum constant name => value
+    pub(crate) enum_defs: HashMap<String, model::EnumDef>,
     pub(crate) typedefs: HashMap<String, Type>,
     pub(crate) current_return_type: Option<Type>,
     // Variable types for IR variables (used for float/int conversions)
     pub(crate) var_types: HashMap<VarId, Type>,
     pub(crate) param_indices: HashMap<String, usize>,
-    // Cache for predecessor lookups
-    pub(crate) pred_cache: HashMap<BlockId, Vec<BlockId>>,
-    pub(crate) pred_cache_valid: bool,
+    // Edges between the blocks built so far, kept current by `set_terminator`
+    pub(crate) cfg: Cfg,
     // Cache for type sizes (using string representation as key since Type doesn't implement Hash)
     pub(crate) type_size_cache: HashMap<String, i64>,
+    // Scalar sizes of the target, shared with codegen through TargetConfig
+    pub(crate) data_layout: model::DataLayout,
+    // -fsanitize=undefined: emit runtime checks (see sanitize.rs)
+    pub(crate) sanitize_undefined: bool,
+    // String label of each check's failure message
+    pub(crate) ubsan_labels: HashMap<UbCheck, String>,
+    // -fsanitize=bounds: guard and check local arrays (see sanitize.rs)
+    pub(crate) sanitize_bounds: bool,
+    // Guarded arrays of the current function: alloca and guard byte offset
+    pub(crate) stack_guards: Vec<(VarId, i64)>,
 }
 
 impl Lowerer {
     /// Create a new Lowerer instance
     pub fn new() -> Self {
+        Self::with_data_layout(model::DataLayout::default())
+    }
+
+    /// Create a Lowerer that sizes types with the target's data layout
+    pub fn with_data_layout(data_layout: model::DataLayout) -> Self {
         Self {
             next_var: 0,
             next_block: 0,
@@ -83,10 +114,14 @@ impl Lowerer {
             variable_defs: HashMap::new(),
             blocks: Vec::new(),
             current_block: None,
+            current_span: Span::default(),
             incomplete_phis: HashMap::new(),
             sealed_blocks: HashSet::new(),
             global_strings: Vec::new(),
+            string_labels: HashMap::new(),
+            wide_strings: Vec::new(),
             variable_allocas: HashMap::new(),
+            local_names: HashMap::new(),
             global_vars: HashSet::new(),
             global_types: HashMap::new(),
             function_names: HashSet::new(),
@@ -95,13 +130,18 @@ impl Lowerer {
             struct_defs: HashMap::new(),
             union_defs: HashMap::new(),
             enum_constants: HashMap::new(),
+            enum_defs: HashMap::new(),
             typedefs: HashMap::new(),
             current_return_type: None,
             var_types: HashMap::new(),
             param_indices: HashMap::new(),
-            pred_cache: HashMap::new(),
-            pred_cache_valid: false,
+            cfg: Cfg::default(),
             type_size_cache: HashMap::new(),
+            data_layout,
+            sanitize_undefined: false,
+            ubsan_labels: HashMap::new(),
+            sanitize_bounds: false,
+            stack_guards: Vec::new(),
         }
     }
 
@@ -121,24 +161,64 @@ impl Lowerer {
             instructions: Vec::new(),
             terminator: Terminator::Unreachable,
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: self.current_span,
         };
         self.blocks.push(block);
         BlockId(id)
     }
 
+    /// End `block` with `term` and record its outgoing edges. A computed
+    /// goto gets no edges here; its targets are only known once every label
+    /// has been seen.
+    /// The `str_N` label of the literal `s`, shared by every equal literal.
+    pub(crate) fn string_label(&mut self, s: &str) -> String {
+        if let Some(label) = self.string_labels.get(s) {
+            return label.clone();
+        }
+        let label = format!("str_{}", self.global_strings.len());
+        self.global_strings.push((label.clone(), s.to_string()));
+        self.string_labels.insert(s.to_string(), label.clone());
+        label
+    }
+
+    pub(crate) fn set_terminator(&mut self, block: BlockId, term: Terminator) {
+        self.cfg.set_successors(block, Cfg::direct_targets(&term));
+        self.blocks[block.0].terminator = term;
+        self.blocks[block.0].terminator_span = self.current_span;
+    }
+
     /// Add an instruction to the current block
     pub(crate) fn add_instruction(&mut self, instr: Instruction) {
         if let Some(bid) = self.current_block {
-            self.blocks[bid.0].instructions.push(instr);
+            self.push_instruction(bid, instr);
         }
     }
 
+    /// Append an instruction to `block`, at the span of the current statement
+    pub(crate) fn push_instruction(&mut self, block: BlockId, instr: Instruction) {
+        self.blocks[block.0].instructions.push(instr);
+        self.blocks[block.0].spans.push(self.current_span);
+    }
+
     /// Resolve a type that may contain `TypeofExpr` to a concrete type.
+    /// Enums too wide for `int` become their underlying type, since codegen
+    /// moves every `Type::Enum` as a 32-bit value.
     pub(crate) fn resolve_type(&self, ty: &Type) -> Type {
         match ty {
             Type::TypeofExpr(expr) => self.get_expr_type(expr),
-            Type::Pointer(inner, ..) => Type::ptr(self.resolve_type(inner)),
+            Type::Enum(name) => match self.enum_defs.get(name) {
+                Some(def) if matches!(def.underlying, Type::LongLong | Type::UnsignedLongLong) => def.underlying.clone(),
+                _ => ty.clone(),
+            },
+            Type::Pointer(inner, q) => Type::qualified_ptr(self.resolve_type(inner), q.clone()),
             Type::Array(inner, size) => Type::Array(Box::new(self.resolve_type(inner)), *size),
+            // A typedef of a struct or union, or of a pointer, is the type it
+            // names, so members can be found; scalar typedefs stay by name
+            Type::Typedef(name) => match self.typedefs.get(name).map(|aliased| self.resolve_type(aliased)) {
+                Some(aliased @ (Type::Struct(_) | Type::Union(_) | Type::Pointer(..))) => aliased,
+                _ => ty.clone(),
+            },
             other => other.clone(),
         }
     }
@@ -147,23 +227,50 @@ impl Lowerer {
     pub(crate) fn get_expr_type(&self, expr: &AstExpr) -> Type {
         match expr {
             AstExpr::Constant(_) => Type::Int,
+            // The parser types literals for LP64; on ILP32 those past 32 bits
+            // are `long long`
+            AstExpr::TypedConstant(v, Type::Long) if self.data_layout.long_size == 4 && i32::try_from(*v).is_err() => {
+                Type::LongLong
+            }
+            AstExpr::TypedConstant(v, Type::UnsignedLong) if self.data_layout.long_size == 4 && u32::try_from(*v).is_err() => {
+                Type::UnsignedLongLong
+            }
+            AstExpr::TypedConstant(_, ty) => ty.clone(),
             AstExpr::FloatConstant(_) => Type::Double,  // Default float literals to double
+            AstExpr::ImaginaryConstant(_) => Type::Complex(Box::new(Type::Double)),
+            AstExpr::Real(expr) | AstExpr::Imag(expr) => model::TypeEnv::real_type(&self.get_expr_type(expr)),
             AstExpr::Variable(name) => {
                 if let Some(ty) = self.symbol_table.get(name) {
                     ty.clone()
                 } else if let Some(ty) = self.global_types.get(name) {
                     ty.clone()
+                } else if let Some(ty) = self.function_types.get(name) {
+                    ty.clone() // A function designator decays to a pointer
                 } else {
                     Type::Int // Default to int for undeclared, should be caught by semantic
                 }
             }
-            AstExpr::Binary { left, op, .. } => {
+            AstExpr::Binary { left, op, right } => {
                 if matches!(op, model::BinaryOp::Assign) {
                     self.get_expr_type(left)
                 } else if matches!(op, model::BinaryOp::Less | model::BinaryOp::LessEqual | model::BinaryOp::Greater | model::BinaryOp::GreaterEqual | model::BinaryOp::EqualEqual | model::BinaryOp::NotEqual | model::BinaryOp::LogicalAnd | model::BinaryOp::LogicalOr) {
                     Type::Int
-                } else {
+                } else if model::TypeEnv::compound_assign_op(op).is_some() {
                     self.get_expr_type(left)
+                } else {
+                    let left_type = self.scalar_type(&self.get_expr_type(left));
+                    let right_type = self.scalar_type(&self.get_expr_type(right));
+                    let is_pointer = |t: &Type| matches!(t, Type::Pointer(..) | Type::FunctionPointer { .. });
+                    match op {
+                        model::BinaryOp::Sub if is_pointer(&left_type) && is_pointer(&right_type) => Type::Long,
+                        model::BinaryOp::Add | model::BinaryOp::Sub if is_pointer(&left_type) => left_type,
+                        model::BinaryOp::Add if is_pointer(&right_type) => right_type,
+                        model::BinaryOp::ShiftLeft | model::BinaryOp::ShiftRight => model::TypeEnv::integer_promotion(&left_type),
+                        _ if [&left_type, &right_type].iter().all(|t| model::TypeEnv::is_scalar_type(t) || model::TypeEnv::is_complex_type(t)) => {
+                            model::TypeEnv::usual_arithmetic_conversions(&left_type, &right_type)
+                        }
+                        _ => left_type,
+                    }
                 }
             }
             AstExpr::Unary { op, expr } => {
@@ -179,7 +286,11 @@ impl Lowerer {
                             Type::Int
                         }
                     }
-                    _ => self.get_expr_type(expr),
+                    model::UnaryOp::LogicalNot => Type::Int,
+                    _ => match self.scalar_type(&self.get_expr_type(expr)) {
+                        ty if model::TypeEnv::is_integer_type(&ty) => model::TypeEnv::integer_promotion(&ty),
+                        ty => ty,
+                    },
                 }
             }
             AstExpr::PostfixIncrement(expr) | AstExpr::PostfixDecrement(expr) 
@@ -252,13 +363,38 @@ impl Lowerer {
                     _ => Type::Int,
                 }
             }
-            AstExpr::Call { func: _, args:_ } => Type::Int, // Assume int return
+            AstExpr::Call { func, .. } => {
+                let callee = match func.as_ref() {
+                    AstExpr::Variable(name) if !self.is_local(name) => self.function_types.get(name).cloned(),
+                    _ => None,
+                };
+                let callee = callee.unwrap_or_else(|| self.resolve_type(&self.get_expr_type(func)));
+                match callee {
+                    Type::FunctionPointer { return_type, .. } => self.resolve_type(&return_type),
+                    Type::Pointer(inner, ..) => match *inner {
+                        Type::FunctionPointer { return_type, .. } => self.resolve_type(&return_type),
+                        _ => Type::Int,
+                    },
+                    _ => Type::Int,
+                }
+            }
             AstExpr::SizeOf(_) | AstExpr::SizeOfExpr(_) | AstExpr::AlignOf(_) => Type::Int,
             AstExpr::StringLiteral(_) => Type::ptr(Type::Char),
-            AstExpr::Conditional { then_expr, .. } => {
-                // Ternary operator type is the type of the then/else branches
-                // (In C, both branches should have compatible types)
-                self.get_expr_type(then_expr)
+            AstExpr::WideStringLiteral(_, encoding) => Type::ptr(encoding.unit_type()),
+            AstExpr::Conditional { then_expr, else_expr, .. } => {
+                // Arithmetic branches meet in their common type; semantic
+                // analysis has checked that other branches agree, so a pointer
+                // wins over the integer of a null pointer constant
+                let then_type = self.get_expr_type(then_expr);
+                let else_type = self.get_expr_type(else_expr);
+                let arithmetic = |t: &Type| model::TypeEnv::is_integer_type(t) || model::TypeEnv::is_floating_type(t);
+                if arithmetic(&then_type) && arithmetic(&else_type) {
+                    model::TypeEnv::usual_arithmetic_conversions(&then_type, &else_type)
+                } else if model::TypeEnv::is_integer_type(&then_type) {
+                    else_type
+                } else {
+                    then_type
+                }
             }
             AstExpr::CompoundLiteral { r#type, .. } => r#type.clone(),
             AstExpr::StmtExpr(stmts) => {
@@ -316,13 +452,15 @@ impl Lowerer {
     /// (Implementation in type_utils.rs)
 
     /// Lower an entire AST program to IR
-    pub fn lower_program(&mut self, ast: &AstProgram) -> Result<IRProgram, String> {
+    pub fn lower_program(&mut self, ast: &AstProgram) -> Result<IRProgram, CompileError> {
         self.global_vars.clear();
         self.function_names.clear();
         self.function_types.clear();
         self.struct_defs.clear();
         self.union_defs.clear();
         self.enum_constants.clear();
+        self.enum_defs.clear();
+        self.typedefs = ast.typedefs.clone();
         
         for s_def in &ast.structs {
             self.struct_defs.insert(s_def.name.clone(), s_def.clone());
@@ -337,12 +475,14 @@ impl Lowerer {
             for (const_name, const_value) in &enum_def.constants {
                 self.enum_constants.insert(const_name.clone(), *const_value);
             }
+            self.enum_defs.insert(enum_def.name.clone(), enum_def.clone());
         }
         
         self.global_types.clear();
         for g in &ast.globals {
             self.global_vars.insert(g.name.clone());
-            self.global_types.insert(g.name.clone(), g.r#type.clone());
+            let ty = self.resolve_type(&g.r#type);
+            self.global_types.insert(g.name.clone(), ty);
         }
         // Add function names as globals (they can be used as function pointers)
         for f in &ast.functions {
@@ -352,6 +492,16 @@ impl Lowerer {
             self.function_types.insert(f.name.clone(), Type::FunctionPointer {
                 return_type: Box::new(f.return_type.clone()),
                 param_types: f.params.iter().map(|p| p.0.clone()).collect(),
+                is_variadic: f.is_variadic,
+            });
+        }
+
+        // Prototypes of functions defined elsewhere, for argument conversions
+        for proto in &ast.prototypes {
+            self.function_types.entry(proto.name.clone()).or_insert_with(|| Type::FunctionPointer {
+                return_type: Box::new(proto.return_type.clone()),
+                param_types: proto.params.iter().map(|p| p.0.clone()).collect(),
+                is_variadic: proto.is_variadic,
             });
         }
 
@@ -359,53 +509,91 @@ impl Lowerer {
         for f in &ast.functions {
             functions.push(self.lower_function(f)?);
         }
+        functions.extend(self.ubsan_helper());
+        let mut declarations: Vec<Declaration> = Vec::new();
+        for proto in &ast.prototypes {
+            let defined = functions.iter().any(|f| f.name == proto.name);
+            if !defined && !declarations.iter().any(|d| d.name == proto.name) {
+                declarations.push(Declaration {
+                    name: proto.name.clone(),
+                    return_type: self.expand_typedefs(&proto.return_type),
+                    params: proto.params.iter().map(|p| self.expand_typedefs(&p.0)).collect(),
+                    is_variadic: proto.is_variadic,
+                });
+            }
+        }
         Ok(IRProgram {
             functions,
             global_strings: self.global_strings.clone(),
-            globals: ast.globals.clone(),
-            structs: ast.structs.clone(),
-            unions: ast.unions.clone(),
+            globals: ast.globals.iter().map(|g| {
+                let mut g = g.clone();
+                g.r#type = self.expand_typedefs(&g.r#type);
+                g
+            }).chain(self.wide_strings.iter().cloned()).collect(),
+            structs: ast.structs.iter().cloned().map(|mut def| {
+                def.fields.iter_mut().for_each(|field| field.field_type = self.expand_typedefs(&field.field_type));
+                def
+            }).collect(),
+            unions: ast.unions.iter().cloned().map(|mut def| {
+                def.fields.iter_mut().for_each(|field| field.field_type = self.expand_typedefs(&field.field_type));
+                def
+            }).collect(),
+            enums: ast.enums.clone(),
+            declarations,
+            data_layout: self.data_layout,
         })
     }
 
     /// Lower a single function to IR
-    pub(crate) fn lower_function(&mut self, f: &AstFunction) -> Result<Function, String> {
+    pub(crate) fn lower_function(&mut self, f: &AstFunction) -> Result<Function, CompileError> {
         self.current_def.clear();
         self.symbol_table.clear();
         self.variable_defs.clear();
         self.blocks.clear();
+        self.var_types.clear();
         self.next_var = 0;
         self.next_block = 0;
         self.incomplete_phis.clear();
         self.sealed_blocks.clear();
         self.variable_allocas.clear();
+        self.local_names.clear();
+        self.stack_guards.clear();
         self.cf.reset();
         self.current_return_type = Some(f.return_type.clone());
         self.param_indices.clear();
-        self.pred_cache.clear();
-        self.pred_cache_valid = false;
+        self.cfg.clear();
+        self.current_span = f.span;
 
         let entry_id = self.new_block();
         self.current_block = Some(entry_id);
         self.sealed_blocks.insert(entry_id);
 
+        if matches!(f.return_type, Type::Complex(_)) || f.params.iter().any(|(t, _)| matches!(t, Type::Complex(_))) {
+            return Err(CompileError::new(
+                ErrorCode::Unsupported,
+                format!("function '{}': passing or returning _Complex values is not supported yet", f.name),
+            ));
+        }
+
         let mut params = Vec::new();
         for (i, (t, name)) in f.params.iter().enumerate() {
+            let t = &self.resolve_type(t);
             let var = self.new_var();
             // Map parameter name to index
             self.param_indices.insert(name.clone(), i);
 
             // Create stack slot for parameter (to support address-of and mem2reg will optimize if not needed)
             let stack_slot = self.new_var();
-            self.blocks[entry_id.0].instructions.push(Instruction::Alloca {
+            self.push_instruction(entry_id, Instruction::Alloca {
                 dest: stack_slot,
                 r#type: t.clone(),
             });
             self.variable_allocas.insert(name.clone(), stack_slot);
+            self.local_names.insert(stack_slot, name.clone());
             self.var_types.insert(stack_slot, Type::ptr(t.clone()));
             
             // Store initial value
-            self.blocks[entry_id.0].instructions.push(Instruction::Store {
+            self.push_instruction(entry_id, Instruction::Store {
                 addr: Operand::Var(stack_slot),
                 src: Operand::Var(var),
                 value_type: t.clone(),
@@ -421,23 +609,37 @@ impl Lowerer {
         // Check for unresolved gotos
         if !self.cf.pending_gotos.is_empty() {
             let labels: Vec<String> = self.cf.pending_gotos.iter().map(|(l, _)| l.clone()).collect();
-            return Err(format!("Undefined labels: {:?}", labels));
+            return Err(CompileError::new(ErrorCode::UndefinedLabel, format!("Undefined labels: {:?}", labels)));
         }
         
-        // Ensure the last block has a return if it's void or just hanging
+        // Ensure the last block has a return if it's void or just hanging.
+        // An implicit return belongs to the closing brace.
+        self.current_span = Span::new(f.span.file_id, f.span.end.saturating_sub(1), f.span.end);
         if let Some(bid) = self.current_block {
              if matches!(self.blocks[bid.0].terminator, Terminator::Unreachable) {
                 if f.return_type == Type::Void {
-                    self.blocks[bid.0].terminator = Terminator::Ret(None);
+                    self.set_terminator(bid, Terminator::Ret(None));
                 } else {
                     // Non-void function fell off the end — insert implicit return 0
                     // (matches GCC/Clang behavior for missing return in non-void functions)
-                    self.blocks[bid.0].terminator = Terminator::Ret(Some(Operand::Constant(0)));
+                    self.set_terminator(bid, Terminator::Ret(Some(Operand::Constant(0))));
                 }
              }
         }
+        self.check_stack_guards();
+        debug_assert!(
+            self.blocks.iter().all(|b| b.spans.len() == b.instructions.len()),
+            "instruction pushed without a span in '{}'",
+            f.name
+        );
 
-        Ok(Function {
+        let returns: HashMap<String, Type> = self.function_types.iter()
+            .filter_map(|(name, ty)| match ty {
+                Type::FunctionPointer { return_type, .. } => Some((name.clone(), self.resolve_type(return_type))),
+                _ => None,
+            })
+            .collect();
+        let mut function = Function {
             name: f.name.clone(),
             return_type: f.return_type.clone(),
             params,
@@ -448,7 +650,13 @@ impl Lowerer {
             is_static: f.is_static,
             label_addrs: self.cf.label_addrs.iter().cloned().collect(),
             labels: self.cf.labels.clone(),
-        })
+            local_names: self.local_names.clone(),
+            span: f.span,
+        };
+        // Temporaries the lowerer made without a C type get one from their instruction
+        function.infer_var_types(&returns);
+        self.expand_function_typedefs(&mut function);
+        Ok(function)
     }
 
     /// Check if a name refers to a local variable
@@ -456,13 +664,18 @@ impl Lowerer {
         self.variable_defs.contains_key(name) || self.variable_allocas.contains_key(name)
     }
 
+    /// The block being filled, or an internal error naming `context`.
+    pub(crate) fn require_block(&self, context: &str) -> Result<BlockId, CompileError> {
+        self.current_block.ok_or_else(|| CompileError::new(ErrorCode::Internal, context))
+    }
+
     /// Check if a name refers to a function
     pub(crate) fn is_function(&self, name: &str) -> bool {
         self.function_names.contains(name)
     }
 
     /// Get the type of an operand
-    pub(crate) fn get_operand_type(&self, op: &crate::types::Operand) -> Result<Type, String> {
+    pub(crate) fn get_operand_type(&self, op: &crate::types::Operand) -> Result<Type, CompileError> {
         match op {
             crate::types::Operand::Constant(_) => Ok(Type::Int),
             crate::types::Operand::FloatConstant(_) => Ok(Type::Float),
@@ -480,12 +693,25 @@ impl Lowerer {
                 if let Some(ty) = self.symbol_table.get(name) {
                     Ok(Type::ptr(ty.clone()))
                 } else {
-                    Err(format!("Unknown global: {}", name))
+                    Err(CompileError::new(ErrorCode::Undeclared, format!("Unknown global: {}", name)))
                 }
             }
         }
     }
 
+    /// Whether an lvalue is accessed through a pointer to `volatile`, in which
+    /// case its loads and stores must not be merged or removed.
+    pub(crate) fn is_volatile_access(&mut self, expr: &AstExpr) -> bool {
+        let base = match expr {
+            AstExpr::Unary { op: model::UnaryOp::Deref, expr } => expr,
+            AstExpr::Index { array, .. } => array,
+            AstExpr::PtrMember { expr, .. } => expr,
+            AstExpr::Member { expr, .. } => return self.is_volatile_access(expr),
+            _ => return false,
+        };
+        matches!(self.get_expr_type(base), Type::Pointer(_, q) if q.is_volatile)
+    }
+
     /// Check if an expression is a bitfield member access, and if so return the bitfield info.
     pub(crate) fn get_bitfield_info(&mut self, expr: &AstExpr) -> Option<model::BitfieldInfo> {
         match expr {
@@ -515,4 +741,73 @@ impl Lowerer {
             _ => None,
         }
     }
+
+    /// Store `val` into a bit-field whose storage unit is at `addr`:
+    /// load the unit, clear the field's bits, OR in the shifted value, store it back.
+    pub(crate) fn store_bitfield(&mut self, addr: VarId, val: Operand, value_type: Type, bf: &model::BitfieldInfo) {
+        let mask = bitfield_mask(bf.bit_width);
+        let old_val = self.new_var();
+        self.add_instruction(Instruction::Load {
+            dest: old_val,
+            addr: Operand::Var(addr),
+            value_type: value_type.clone(),
+            volatile: false,
+        });
+        let cleared = self.new_var();
+        self.add_instruction(Instruction::Binary {
+            dest: cleared,
+            op: BinaryOp::BitwiseAnd,
+            left: Operand::Var(old_val),
+            right: Operand::Constant(truncate_to_unit(!(mask << bf.bit_offset), bf.storage_size)),
+        });
+        // Place the value: (val & mask) << bit_offset
+        let placed = if let Operand::Constant(c) = val {
+            Operand::Constant(truncate_to_unit((c & mask) << bf.bit_offset, bf.storage_size))
+        } else {
+            let masked_val = self.new_var();
+            self.add_instruction(Instruction::Binary {
+                dest: masked_val,
+                op: BinaryOp::BitwiseAnd,
+                left: val,
+                right: Operand::Constant(mask),
+            });
+            if bf.bit_offset > 0 {
+                let sv = self.new_var();
+                self.add_instruction(Instruction::Binary {
+                    dest: sv,
+                    op: BinaryOp::ShiftLeft,
+                    left: Operand::Var(masked_val),
+                    right: Operand::Constant(bf.bit_offset as i64),
+                });
+                Operand::Var(sv)
+            } else {
+                Operand::Var(masked_val)
+            }
+        };
+        let combined = self.new_var();
+        self.add_instruction(Instruction::Binary {
+            dest: combined,
+            op: BinaryOp::BitwiseOr,
+            left: Operand::Var(cleared),
+            right: placed,
+        });
+        self.add_instruction(Instruction::Store {
+            addr: Operand::Var(addr),
+            src: Operand::Var(combined),
+            value_type,
+            volatile: false,
+        });
+    }
+}
+
+/// Sign-extend the low `storage_size` bytes of `value`. Bits above the unit
+/// are never stored, and keeping them out keeps the constant a valid immediate.
+fn truncate_to_unit(value: i64, storage_size: usize) -> i64 {
+    let bits = (storage_size * 8).clamp(8, 64) as u32;
+    (value << (64 - bits)) >> (64 - bits)
+}
+
+/// Mask covering the low `width` bits.
+pub(crate) fn bitfield_mask(width: usize) -> i64 {
+    (u64::MAX >> (64 - width.clamp(1, 64))) as i64
 }
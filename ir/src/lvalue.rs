@@ -1,88 +1,57 @@
-use model::{UnaryOp, Type, Expr as AstExpr};
-use crate::types::{VarId, Operand, Instruction};
+use model::{BinaryOp, CompileError, ErrorCode, InitItem, UnaryOp, Type, Expr as AstExpr};
+use crate::types::{VarId, Operand, Instruction, Terminator};
 use crate::lowerer::Lowerer;
 
 /// L-value (address) lowering implementation
 impl Lowerer {
     /// Lower an expression to its address (for l-values)
-    pub(crate) fn lower_to_addr(&mut self, expr: &AstExpr) -> Result<VarId, String> {
-        let bid = self.current_block.ok_or("Address calculation outside block")?;
+    pub(crate) fn lower_to_addr(&mut self, expr: &AstExpr) -> Result<VarId, CompileError> {
+        let bid = self.require_block("Address calculation outside block")?;
         match expr {
             AstExpr::Variable(name) => {
                 if let Some(addr) = self.variable_allocas.get(name) {
                     Ok(*addr)
                 } else if self.global_vars.contains(name) {
                     let dest = self.new_var();
-                    self.blocks[bid.0].instructions.push(Instruction::Copy {
+                    self.push_instruction(bid, Instruction::Copy {
                         dest,
                         src: Operand::Global(name.clone()),
                     });
                     Ok(dest)
                 } else {
-                    Err(format!("Undefined variable {}", name))
+                    Err(CompileError::new(ErrorCode::Undeclared, format!("Undefined variable {}", name)))
                 }
             }
-            AstExpr::Index { array, index } => {
-                let array_type = self.get_expr_type(array);
-                let base_addr = match &array_type {
-                    Type::Pointer(_, ..) => {
-                        // For pointer indexing, we need the pointer's value, not its address.
-                        // The base may be a Var or a Global (e.g. string literal "..."[i]).
-                        let operand = self.lower_expr(array)?;
-                        match operand {
-                            Operand::Var(v) => v,
-                            // String literals and globals: materialise into a tmp var first.
-                            other => {
-                                let tmp = self.new_var();
-                                let bid = self.current_block.ok_or("Index outside of block")?;
-                                self.blocks[bid.0].instructions.push(Instruction::Copy {
-                                    dest: tmp,
-                                    src: other,
-                                });
-                                tmp
-                            }
-                        }
-                    }
-                    _ => {
-                        // For array indexing, we need the array's address
-                        self.lower_to_addr(array)?
-                    }
-                };
-                let index_val = self.lower_expr(index)?;
-                let dest = self.new_var();
-                let element_type = match array_type {
-                    Type::Array(inner, _) => *inner,
-                    Type::Pointer(inner, ..) => *inner,
-                    _ => Type::Int, // fallback
-                };
-                let bid = self.current_block.ok_or("Index outside of block")?;
-                self.blocks[bid.0].instructions.push(Instruction::GetElementPtr {
-                    dest,
-                    base: Operand::Var(base_addr),
-                    index: index_val,
-                    element_type,
-                });
-                Ok(dest)
-            }
+            AstExpr::Index { array, index } => self.lower_index_to_addr(array, index, false),
             AstExpr::Unary { op: UnaryOp::Deref, expr } => {
                 let addr_op = self.lower_expr(expr)?;
                 match addr_op {
                     Operand::Var(v) => Ok(v),
-                    _ => Err("Dereference operand must be in a variable".to_string()),
+                    _ => Err(CompileError::new(ErrorCode::Unsupported, "Dereference operand must be in a variable")),
                 }
             }
             AstExpr::Member { expr, member } => {
-                let base_addr = self.lower_to_addr(expr)?;
                 // Get the struct/union type from the expression
                 let expr_type = self.get_expr_type(expr);
+                // A struct or union that is not an object, like a call's
+                // result, is copied to a temporary one
+                let base_addr = if self.has_address(expr) {
+                    self.lower_to_addr(expr)?
+                } else {
+                    let temp = self.new_var();
+                    self.add_instruction(Instruction::Alloca { dest: temp, r#type: expr_type.clone() });
+                    self.lower_aggregate_copy(Operand::Var(temp), expr, &expr_type)?;
+                    temp
+                };
+                let bid = self.require_block("Address calculation outside block")?;
                 let type_name = match &expr_type {
                     Type::Struct(name) => name.clone(),
                     Type::Union(name) => name.clone(),
-                    _ => return Err(format!("Member access on non-struct/union type {:?}", expr_type)),
+                    _ => return Err(CompileError::new(ErrorCode::InvalidOperand, format!("Member access on non-struct/union type '{}'", expr_type))),
                 };
                 let (offset, _, _) = self.get_member_offset(&type_name, member); 
                 let dest = self.new_var();
-                self.blocks[bid.0].instructions.push(Instruction::GetElementPtr {
+                self.push_instruction(bid, Instruction::GetElementPtr {
                     dest,
                     base: Operand::Var(base_addr),
                     index: Operand::Constant(offset),
@@ -94,7 +63,7 @@ impl Lowerer {
                 let addr_op = self.lower_expr(expr)?;
                 let base_addr = match addr_op {
                     Operand::Var(v) => v,
-                    _ => return Err("-> operand must be in a variable".to_string()),
+                    _ => return Err(CompileError::new(ErrorCode::Unsupported, "-> operand must be in a variable")),
                 };
                 // Get the struct/union type from the pointer
                 let expr_type = self.get_expr_type(expr);
@@ -103,14 +72,14 @@ impl Lowerer {
                         match &**inner {
                             Type::Struct(name) => name.clone(),
                             Type::Union(name) => name.clone(),
-                            _ => return Err(format!("Pointer member access on non-struct/union pointer {:?}", expr_type)),
+                            _ => return Err(CompileError::new(ErrorCode::InvalidOperand, format!("Pointer member access on non-struct/union pointer '{}'", expr_type))),
                         }
                     }
-                    _ => return Err(format!("-> operator on non-pointer type {:?}", expr_type)),
+                    _ => return Err(CompileError::new(ErrorCode::InvalidOperand, format!("-> operator on non-pointer type '{}'", expr_type))),
                 };
                 let (offset, _, _) = self.get_member_offset(&type_name, member);
                 let dest = self.new_var();
-                self.blocks[bid.0].instructions.push(Instruction::GetElementPtr {
+                self.push_instruction(bid, Instruction::GetElementPtr {
                     dest,
                     base: Operand::Var(base_addr),
                     index: Operand::Constant(offset),
@@ -118,7 +87,183 @@ impl Lowerer {
                 });
                 Ok(dest)
             }
-            _ => Err("Expression is not an l-value".to_string()),
+            AstExpr::CompoundLiteral { r#type, init } => self.lower_compound_literal(r#type, init),
+            _ => Err(CompileError::new(ErrorCode::NotAnLvalue, "Expression is not an l-value")),
         }
     }
+
+    /// Whether `lower_to_addr` can take the address of `expr`.
+    pub(crate) fn has_address(&self, expr: &AstExpr) -> bool {
+        match expr {
+            AstExpr::Variable(name) => self.variable_allocas.contains_key(name) || self.global_vars.contains(name),
+            AstExpr::Member { expr, .. } => self.has_address(expr),
+            AstExpr::Index { .. } | AstExpr::Unary { op: UnaryOp::Deref, .. }
+            | AstExpr::PtrMember { .. } | AstExpr::CompoundLiteral { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the struct or union value of `expr` lives in an object a copy
+    /// can read: an l-value or compound literal, an assignment (its left
+    /// side), the last operand of a comma, or a `?:` whose arms both are.
+    pub(crate) fn is_aggregate_object(&self, expr: &AstExpr) -> bool {
+        match expr {
+            AstExpr::Binary { op: BinaryOp::Assign, left, .. } => self.has_address(left),
+            AstExpr::Comma(exprs) => exprs.last().is_some_and(|e| self.is_aggregate_object(e)),
+            AstExpr::Conditional { then_expr, else_expr, .. } => {
+                self.is_aggregate_object(then_expr) && self.is_aggregate_object(else_expr)
+            }
+            _ => self.has_address(expr),
+        }
+    }
+
+    /// Address of the object holding the value of `expr`, which must satisfy
+    /// `is_aggregate_object`. A `?:` yields a phi of its arms' addresses.
+    fn lower_aggregate_addr(&mut self, expr: &AstExpr) -> Result<Operand, CompileError> {
+        match expr {
+            // Struct assignment evaluates to the address of its left side
+            AstExpr::Binary { op: BinaryOp::Assign, .. } => self.lower_expr(expr),
+            AstExpr::Comma(exprs) => {
+                let (last, rest) = exprs.split_last().expect("comma has operands");
+                for e in rest {
+                    self.lower_expr(e)?;
+                }
+                self.lower_aggregate_addr(last)
+            }
+            AstExpr::Conditional { condition, then_expr, else_expr } => {
+                let cond = self.lower_expr(condition)?;
+                let entry_bid = self.require_block("Ternary outside block")?;
+                let then_id = self.new_block();
+                let else_id = self.new_block();
+                let merge_id = self.new_block();
+                self.set_terminator(entry_bid, Terminator::cond_br(cond, then_id, else_id));
+
+                let mut arms = Vec::new();
+                for (block, arm) in [(then_id, then_expr), (else_id, else_expr)] {
+                    self.sealed_blocks.insert(block);
+                    self.current_block = Some(block);
+                    let addr = self.lower_aggregate_addr(arm)?;
+                    let var = self.new_var();
+                    self.add_instruction(Instruction::Copy { dest: var, src: addr });
+                    let end = self.require_block("Ternary arm outside block")?;
+                    self.set_terminator(end, Terminator::Br(merge_id));
+                    arms.push((end, var));
+                }
+
+                self.sealed_blocks.insert(merge_id);
+                self.current_block = Some(merge_id);
+                let result = self.new_var();
+                self.add_instruction(Instruction::Phi { dest: result, preds: arms });
+                Ok(Operand::Var(result))
+            }
+            _ => Ok(Operand::Var(self.lower_to_addr(expr)?)),
+        }
+    }
+
+    /// Copy the struct or union value of `src` to the object at `dest`: a
+    /// memcpy when the value lives in an object, otherwise a store of the
+    /// value itself (e.g. a call's result).
+    pub(crate) fn lower_aggregate_copy(&mut self, dest: Operand, src: &AstExpr, ty: &Type) -> Result<(), CompileError> {
+        let size = self.get_type_size(ty) as usize;
+        if self.is_aggregate_object(src) {
+            let src = self.lower_aggregate_addr(src)?;
+            self.add_instruction(Instruction::Memcpy { dest, src, size });
+        } else {
+            let val = self.lower_expr(src)?;
+            self.add_instruction(Instruction::Store { addr: dest, src: val, value_type: ty.clone(), volatile: false });
+        }
+        Ok(())
+    }
+
+    /// The value of `expr`. A struct or union one that lives in an object is
+    /// loaded from it, so call arguments, return values, and `?:` arms get a
+    /// value however the object is named.
+    pub(crate) fn lower_value(&mut self, expr: &AstExpr) -> Result<Operand, CompileError> {
+        let ty = self.resolve_type(&self.get_expr_type(expr));
+        if !matches!(ty, Type::Struct(_) | Type::Union(_)) || !self.is_aggregate_object(expr) {
+            return self.lower_expr(expr);
+        }
+        let addr = self.lower_aggregate_addr(expr)?;
+        let dest = self.new_var();
+        self.var_types.insert(dest, ty.clone());
+        self.add_instruction(Instruction::Load { dest, addr, value_type: ty, volatile: false });
+        Ok(Operand::Var(dest))
+    }
+
+    /// Allocate the anonymous object of a compound literal `(type){init}`,
+    /// initialize it, and return its address.
+    pub(crate) fn lower_compound_literal(&mut self, ty: &Type, init: &[InitItem]) -> Result<VarId, CompileError> {
+        let bid = self.require_block("CompoundLiteral outside block")?;
+        let alloca = self.new_var();
+        self.push_instruction(bid, Instruction::Alloca {
+            dest: alloca,
+            r#type: ty.clone(),
+        });
+
+        // Dispatch to the correct init-list helper based on type.
+        match ty {
+            Type::Array(..) | Type::Struct(_) | Type::Union(_) => {
+                self.lower_aggregate_init(alloca, ty, init)?;
+            }
+            _ => {
+                // Scalar compound literal, e.g. (int){42}
+                if let Some(item) = init.first() {
+                    let val = self.lower_expr(&item.value)?;
+                    self.add_instruction(Instruction::Store {
+                        addr: Operand::Var(alloca),
+                        src: val,
+                        value_type: ty.clone(),
+                        volatile: false,
+                    });
+                }
+            }
+        }
+        Ok(alloca)
+    }
+
+    /// Address of `array[index]`. With bounds checking, `one_past_end`
+    /// allows the index equal to the array length, as in `&a[len]`.
+    pub(crate) fn lower_index_to_addr(&mut self, array: &AstExpr, index: &AstExpr, one_past_end: bool) -> Result<VarId, CompileError> {
+        let array_type = self.get_expr_type(array);
+        let base_addr = match &array_type {
+            Type::Pointer(_, ..) => {
+                // For pointer indexing, we need the pointer's value, not its address.
+                // The base may be a Var or a Global (e.g. string literal "..."[i]).
+                let operand = self.lower_expr(array)?;
+                match operand {
+                    Operand::Var(v) => v,
+                    // String literals and globals: materialise into a tmp var first.
+                    other => {
+                        let tmp = self.new_var();
+                        let bid = self.require_block("Index outside of block")?;
+                        self.push_instruction(bid, Instruction::Copy {
+                            dest: tmp,
+                            src: other,
+                        });
+                        tmp
+                    }
+                }
+            }
+            _ => {
+                // For array indexing, we need the array's address
+                self.lower_to_addr(array)?
+            }
+        };
+        let index_val = self.lower_expr(index)?;
+        self.check_index(array, &array_type, &index_val, one_past_end);
+        let dest = self.new_var();
+        let element_type = match array_type {
+            Type::Array(inner, _) => *inner,
+            Type::Pointer(inner, ..) => *inner,
+            _ => Type::Int, // fallback
+        };
+        let bid = self.require_block("Index outside of block")?;
+        self.push_instruction(bid, Instruction::GetElementPtr {
+            dest,
+            base: Operand::Var(base_addr),
+            index: index_val,
+            element_type,
+        });
+        Ok(dest)
+    }
 }
@@ -1,11 +1,13 @@
 use std::collections::{HashMap, HashSet};
 use crate::types::{VarId, BlockId, Instruction, Function, Operand, Terminator};
 use model::Type;
+use crate::cfg::Cfg;
 
 /// Mem2reg optimization pass: promotes memory allocations to SSA registers
 pub fn mem2reg(func: &mut Function) {
-    let mut pass = Mem2RegPass::new(func);
-    pass.run();
+    let spans = func.spans_by_dest();
+    Mem2RegPass::new(func).run();
+    func.realign_spans(&spans);
     // Verify SSA invariants after promotion.  Catches undefined-VarId bugs
     // (like the transitive-simplified-phi issue) before they become runtime
     // segfaults downstream in codegen/regalloc.
@@ -15,7 +17,7 @@ pub fn mem2reg(func: &mut Function) {
 
 struct Mem2RegPass<'a> {
     func: &'a mut Function,
-    preds: HashMap<BlockId, Vec<BlockId>>,
+    cfg: Cfg,
     promotable: HashSet<VarId>,
     // Last definition of a var in a block
     block_defs: HashMap<VarId, HashMap<BlockId, VarId>>,
@@ -41,7 +43,7 @@ impl<'a> Mem2RegPass<'a> {
         let next_var_id = Self::find_max_var_id(func) + 1;
         Self {
             func,
-            preds: HashMap::new(),
+            cfg: Cfg::default(),
             promotable: HashSet::new(),
             block_defs: HashMap::new(),
             incoming_cache: HashMap::new(),
@@ -57,7 +59,7 @@ impl<'a> Mem2RegPass<'a> {
 
     fn run(&mut self) {
         // println!("Running mem2reg on function {}", self.func.name);
-        self.compute_preds();
+        self.cfg = Cfg::new(self.func);
         self.identify_promotable_allocas();
         
         if self.promotable.is_empty() { return; }
@@ -78,10 +80,6 @@ impl<'a> Mem2RegPass<'a> {
         self.fixup_simplified_phi_sources();
     }
     
-    fn compute_preds(&mut self) {
-        self.preds = self.func.compute_predecessors();
-    }
-
     fn identify_promotable_allocas(&mut self) {
         let mut alloca_types = HashMap::new();
         for block in &self.func.blocks {
@@ -118,22 +116,24 @@ impl<'a> Mem2RegPass<'a> {
         let next_var_id = &mut self.next_var_id;
         let promotable = &self.promotable;
         let block_defs = &mut self.block_defs;
+        let var_types = &mut self.func.var_types;
 
         for block in blocks {
             let mut i = 0;
             while i < block.instructions.len() {
                 // If Store(alloca, Constant), convert to Copy + Store(alloca, Var)
                 let mut inject_copy = None;
-                if let Instruction::Store { addr: Operand::Var(alloca_id), src, .. } = &block.instructions[i] {
+                if let Instruction::Store { addr: Operand::Var(alloca_id), src, value_type, .. } = &block.instructions[i] {
                     if promotable.contains(alloca_id) && !matches!(src, Operand::Var(_)) {
-                        let c = src.clone();
-                        inject_copy = Some(c);
+                        inject_copy = Some((src.clone(), value_type.clone()));
                     }
                 }
                 
-                if let Some(c) = inject_copy {
+                if let Some((c, value_type)) = inject_copy {
                     let new_v = VarId(*next_var_id);
                     *next_var_id += 1;
+                    // Keep the slot's type, so a `double` constant stays a double
+                    var_types.insert(new_v, value_type);
                     
                     // Insert Copy
                     block.instructions.insert(i, Instruction::Copy { dest: new_v, src: c });
@@ -195,7 +195,7 @@ impl<'a> Mem2RegPass<'a> {
             return *val;
         }
 
-        let preds = self.preds.get(&block_id).cloned().unwrap_or_default();
+        let preds = self.cfg.preds(block_id).to_vec();
         
         if preds.is_empty() {
             // Uninitialized / Entry
@@ -321,7 +321,7 @@ impl<'a> Mem2RegPass<'a> {
             }
             // Also fix terminators
             match &mut block.terminator {
-                Terminator::CondBr { cond, .. } => {
+                Terminator::CondBr { cond, .. } | Terminator::Switch { value: cond, .. } => {
                     resolve_operand(cond, &resolved_map);
                 }
                 Terminator::Ret(Some(val)) => {
@@ -0,0 +1,278 @@
+// Textual IR
+//
+// One line per instruction, in an LLVM-like notation: `%N` is a variable,
+// `@name` a global, `bbN` a block. Used for diffs between optimization passes,
+// inside the CFG renderings of dot.rs, and for whole programs by --emit-ir.
+// reader.rs parses the same notation back.
+
+use std::fmt::Write;
+
+use model::{Attribute, Designator, Expr, StringEncoding, StructField, UnaryOp};
+
+use crate::types::{BranchHint, Function, IRProgram, Instruction, Operand, Terminator, VarId};
+
+/// Render a whole program: type definitions, string literals, and globals,
+/// then every function, with a blank line between sections.
+pub fn program_to_text(program: &IRProgram) -> String {
+    let mut sections = Vec::new();
+    let mut types = String::new();
+    for def in &program.structs {
+        writeln!(types, "struct {}{} {}", def.name, attributes_text(&def.attributes), fields_text(&def.fields)).unwrap();
+    }
+    for def in &program.unions {
+        writeln!(types, "union {} {}", def.name, fields_text(&def.fields)).unwrap();
+    }
+    for def in &program.enums {
+        let constants: Vec<String> = def.constants.iter().map(|(name, v)| format!("{} = {}", name, v)).collect();
+        writeln!(types, "enum {} : {} {{ {} }}", def.name, def.underlying, constants.join(", ")).unwrap();
+    }
+    sections.push(types);
+
+    let mut data = String::new();
+    for (label, content) in &program.global_strings {
+        writeln!(data, "@{} = string \"{}\"", label, content.escape_default()).unwrap();
+    }
+    for global in &program.globals {
+        let linkage = match (global.is_extern, global.is_static) {
+            (true, _) => "extern ",
+            (_, true) => "static ",
+            _ => "",
+        };
+        let declaration = global.r#type.qualified_declaration(&global.qualifiers, &format!("@{}", global.name));
+        write!(data, "{}global {}{}", linkage, declaration, attributes_text(&global.attributes)).unwrap();
+        if let Some(init) = &global.init {
+            write!(data, " = {}", initializer_text(init)).unwrap();
+        }
+        data.push('\n');
+    }
+    sections.push(data);
+
+    let mut declarations = String::new();
+    for decl in &program.declarations {
+        let mut params: Vec<String> = decl.params.iter().map(|ty| ty.to_string()).collect();
+        if decl.is_variadic {
+            params.push("...".to_string());
+        }
+        writeln!(declarations, "declare {}({}) -> {}", decl.name, params.join(", "), decl.return_type).unwrap();
+    }
+    sections.push(declarations);
+
+    sections.extend(program.functions.iter().map(function_to_text));
+    sections.retain(|section| !section.is_empty());
+    sections.join("\n")
+}
+
+/// ` __attribute__((...))` for a non-empty attribute list, in GCC spelling.
+fn attributes_text(attributes: &[Attribute]) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = attributes
+        .iter()
+        .map(|attribute| match attribute {
+            Attribute::Packed => "packed".to_string(),
+            Attribute::Pack(n) => format!("pack({})", n),
+            Attribute::Aligned(n) => format!("aligned({})", n),
+            Attribute::Section(name) => format!("section(\"{}\")", name.escape_default()),
+            Attribute::NoReturn => "noreturn".to_string(),
+            Attribute::AlwaysInline => "always_inline".to_string(),
+            Attribute::Weak => "weak".to_string(),
+            Attribute::Unused => "unused".to_string(),
+            Attribute::Constructor => "constructor".to_string(),
+            Attribute::Destructor => "destructor".to_string(),
+        })
+        .collect();
+    format!(" __attribute__(({}))", names.join(", "))
+}
+
+fn fields_text(fields: &[StructField]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| match f.bit_width {
+            Some(width) => format!("{} : {};", f.field_type.declaration(&f.name), width),
+            None => format!("{};", f.field_type.declaration(&f.name)),
+        })
+        .collect();
+    format!("{{ {} }}", fields.join(" "))
+}
+
+/// A static initializer: constants, string literals, address constants
+/// such as `&@table[2]`, and brace lists with their designators.
+fn initializer_text(expr: &Expr) -> String {
+    match expr {
+        Expr::Constant(v) | Expr::TypedConstant(v, _) => v.to_string(),
+        Expr::FloatConstant(f) => format!("{:?}", f),
+        Expr::StringLiteral(s) => format!("\"{}\"", s.escape_default()),
+        Expr::WideStringLiteral(s, encoding) => format!("{}\"{}\"", encoding_prefix(*encoding), s.escape_default()),
+        Expr::Variable(name) => format!("@{}", name),
+        Expr::Unary { op: UnaryOp::AddrOf, expr } => format!("&{}", initializer_text(expr)),
+        Expr::Unary { op: UnaryOp::Minus, expr } => format!("-{}", initializer_text(expr)),
+        Expr::Cast(ty, inner) => format!("({}) {}", ty, initializer_text(inner)),
+        Expr::Index { array, index } => format!("{}[{}]", initializer_text(array), initializer_text(index)),
+        Expr::Member { expr, member } => format!("{}.{}", initializer_text(expr), member),
+        Expr::Binary { left, op, right } => {
+            format!("({} {} {})", initializer_text(left), op, initializer_text(right))
+        }
+        Expr::InitList(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| {
+                    let value = initializer_text(&item.value);
+                    match &item.designator {
+                        Some(Designator::Field(name)) => format!(".{} = {}", name, value),
+                        Some(Designator::Index(i)) => format!("[{}] = {}", i, value),
+                        Some(Designator::Range { start, end }) => format!("[{} ... {}] = {}", start, end, value),
+                        None => value,
+                    }
+                })
+                .collect();
+            format!("{{ {} }}", items.join(", "))
+        }
+        other => format!("{:?}", other),
+    }
+}
+
+/// The C prefix of a string literal with this encoding.
+pub(crate) fn encoding_prefix(encoding: StringEncoding) -> &'static str {
+    match encoding {
+        StringEncoding::Plain => "",
+        StringEncoding::Utf8 => "u8",
+        StringEncoding::Wide => "L",
+        StringEncoding::Utf16 => "u",
+        StringEncoding::Utf32 => "U",
+    }
+}
+
+/// Render a function's signature, then each block with its instructions and
+/// terminator.
+pub fn function_to_text(func: &Function) -> String {
+    let mut out = String::new();
+    let params: Vec<String> = func.params.iter().map(|(ty, v)| format!("{} %{}", ty, v.0)).collect();
+    let linkage = if func.is_static { "static " } else { "" };
+    let attributes = attributes_text(&func.attributes);
+    writeln!(out, "{}function {}({}) -> {}{} {{", linkage, func.name, params.join(", "), func.return_type, attributes)
+        .unwrap();
+    for block in &func.blocks {
+        // The entry block, then the labels on the block; `&&` marks those
+        // whose address is taken
+        let mut notes: Vec<String> = Vec::new();
+        if block.id == func.entry_block {
+            notes.push("entry".to_string());
+        }
+        let mut labels: Vec<&String> =
+            func.labels.iter().filter(|(_, id)| **id == block.id).map(|(label, _)| label).collect();
+        labels.sort();
+        for label in labels {
+            let address = if func.label_addrs.contains(label) { "&&" } else { "" };
+            notes.push(format!("label {}{}", address, label));
+        }
+        let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+        writeln!(out, "bb{}:{}", block.id.0, notes).unwrap();
+        for inst in &block.instructions {
+            writeln!(out, "  {}", instruction_text(inst)).unwrap();
+        }
+        writeln!(out, "  {}", terminator_text(&block.terminator)).unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub(crate) fn terminator_text(term: &Terminator) -> String {
+    match term {
+        Terminator::Br(target) => format!("br bb{}", target.0),
+        Terminator::CondBr { cond, then_block, else_block, hint } => {
+            let hint = match hint {
+                BranchHint::None => "",
+                BranchHint::LikelyThen => " (likely then)",
+                BranchHint::LikelyElse => " (likely else)",
+            };
+            format!("br {} ? bb{} : bb{}{}", operand(cond), then_block.0, else_block.0, hint)
+        }
+        Terminator::Ret(Some(value)) => format!("ret {}", operand(value)),
+        Terminator::Ret(None) => "ret".to_string(),
+        Terminator::Unreachable => "unreachable".to_string(),
+        Terminator::IndirectBr { target } => format!("goto *{}", operand(target)),
+        Terminator::Switch { value, cases, default } => {
+            let cases: Vec<String> = cases.iter().map(|(v, block)| format!("{}: bb{}", v, block.0)).collect();
+            format!("switch {} [{}] default bb{}", operand(value), cases.join(", "), default.0)
+        }
+    }
+}
+
+pub(crate) fn instruction_text(inst: &Instruction) -> String {
+    let list = |ops: &[Operand]| ops.iter().map(operand).collect::<Vec<_>>().join(", ");
+    let call = |dest: &Option<VarId>, callee: String, args: &[Operand]| match dest {
+        Some(d) => format!("%{} = call {}({})", d.0, callee, list(args)),
+        None => format!("call {}({})", callee, list(args)),
+    };
+    match inst {
+        Instruction::Binary { dest, op, left, right } => {
+            format!("%{} = {} {}, {}", dest.0, op_name(op), operand(left), operand(right))
+        }
+        Instruction::FloatBinary { dest, op, left, right } => {
+            format!("%{} = f{} {}, {}", dest.0, op_name(op), operand(left), operand(right))
+        }
+        Instruction::Unary { dest, op, src } => format!("%{} = {} {}", dest.0, op_name(op), operand(src)),
+        Instruction::FloatUnary { dest, op, src } => format!("%{} = f{} {}", dest.0, op_name(op), operand(src)),
+        Instruction::Phi { dest, preds } => {
+            let preds: Vec<String> = preds.iter().map(|(b, v)| format!("[bb{}: %{}]", b.0, v.0)).collect();
+            format!("%{} = phi {}", dest.0, preds.join(" "))
+        }
+        Instruction::Copy { dest, src } => format!("%{} = {}", dest.0, operand(src)),
+        Instruction::Cast { dest, src, src_type, r#type } => {
+            format!("%{} = cast {} {} to {}", dest.0, src_type, operand(src), r#type)
+        }
+        Instruction::Alloca { dest, r#type } => format!("%{} = alloca {}", dest.0, r#type),
+        Instruction::Load { dest, addr, value_type, volatile } => {
+            let v = if *volatile { "volatile " } else { "" };
+            format!("%{} = load {}{}, {}", dest.0, v, value_type, operand(addr))
+        }
+        Instruction::Store { addr, src, value_type, volatile } => {
+            let v = if *volatile { "volatile " } else { "" };
+            format!("store {}{} {}, {}", v, value_type, operand(src), operand(addr))
+        }
+        Instruction::GetElementPtr { dest, base, index, element_type } => {
+            format!("%{} = gep {}, {} x {}", dest.0, operand(base), operand(index), element_type)
+        }
+        Instruction::Call { dest, name, args, .. } => call(dest, name.clone(), args),
+        Instruction::IndirectCall { dest, func_ptr, args, .. } => call(dest, format!("*{}", operand(func_ptr)), args),
+        Instruction::VaStart { list: l, arg_index } => format!("va_start {}, {}", operand(l), arg_index),
+        Instruction::VaEnd { list: l } => format!("va_end {}", operand(l)),
+        Instruction::VaCopy { dest, src } => format!("va_copy {}, {}", operand(dest), operand(src)),
+        Instruction::VaArg { dest, list: l, r#type } => format!("%{} = va_arg {}, {}", dest.0, operand(l), r#type),
+        Instruction::InlineAsm { template, outputs, inputs, output_constraints, input_constraints, clobbers, is_volatile } => {
+            let quoted = |s: &String| format!("\"{}\"", s.escape_default());
+            let outputs: Vec<String> =
+                output_constraints.iter().zip(outputs).map(|(c, v)| format!("{} %{}", quoted(c), v.0)).collect();
+            let inputs: Vec<String> =
+                input_constraints.iter().zip(inputs).map(|(c, op)| format!("{} {}", quoted(c), operand(op))).collect();
+            let volatile = if *is_volatile { "volatile " } else { "" };
+            let mut text = format!("asm {}{} ({}) <- ({})", volatile, quoted(template), outputs.join(", "), inputs.join(", "));
+            if !clobbers.is_empty() {
+                let clobbers: Vec<String> = clobbers.iter().map(quoted).collect();
+                write!(text, " clobber({})", clobbers.join(", ")).unwrap();
+            }
+            text
+        }
+        Instruction::Memcpy { dest, src, size } => format!("memcpy {}, {}, {}", operand(dest), operand(src), size),
+        Instruction::Memset { dest, value, size } => format!("memset {}, {}, {}", operand(dest), operand(value), size),
+        Instruction::Simd { op, dest, operands, elem_type, width } => {
+            let dest = dest.map(|d| format!("%{} = ", d.0)).unwrap_or_default();
+            format!("{}simd.{}<{} x {}> {}", dest, op_name(op), width, elem_type, list(operands))
+        }
+    }
+}
+
+pub(crate) fn op_name(op: &impl std::fmt::Debug) -> String {
+    format!("{:?}", op).to_lowercase()
+}
+
+fn operand(op: &Operand) -> String {
+    match op {
+        Operand::Constant(v) => v.to_string(),
+        // `{:?}` keeps the decimal point, so `1.0` does not read back as an integer
+        Operand::FloatConstant(v) => format!("{:?}", v),
+        Operand::Var(v) => format!("%{}", v.0),
+        Operand::Global(name) => format!("@{}", name),
+    }
+}
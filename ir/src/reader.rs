@@ -0,0 +1,1208 @@
+// Reading textual IR
+//
+// The inverse of printer.rs: `program_from_text` parses what
+// `program_to_text` writes, and `function_from_text` what `function_to_text`
+// writes, so passes can be tested on hand-written IR without going through
+// the C front end. Types are written in C syntax (`char *`, `int (*)(int)`).
+//
+// The text does not carry every variable's type. `var_types` is rebuilt from
+// what the instructions state (parameters, loads, casts, `va_arg`, calls of
+// functions in the program) and carried through copies, phis, and float
+// arithmetic; a float operation on untyped operands is taken as `double`.
+
+use std::collections::HashMap;
+
+use model::{
+    Attribute, BinaryOp, CompileError, Designator, EnumDef, ErrorCode, Expr, GlobalVar, InitItem, Span,
+    StringEncoding, StructDef, StructField, Type, TypeQualifiers, UnaryOp, UnionDef,
+};
+
+use crate::printer::{encoding_prefix, op_name};
+use crate::types::{
+    BasicBlock, BlockId, BranchHint, Declaration, Function, IRProgram, Instruction, Operand, SimdOp, Terminator, VarId,
+};
+
+/// Parse a whole program in the notation of `program_to_text`.
+pub fn program_from_text(text: &str) -> Result<IRProgram, CompileError> {
+    let mut program = IRProgram {
+        functions: Vec::new(),
+        global_strings: Vec::new(),
+        globals: Vec::new(),
+        structs: Vec::new(),
+        unions: Vec::new(),
+        enums: Vec::new(),
+        declarations: Vec::new(),
+        data_layout: model::DataLayout::default(),
+    };
+    let lines: Vec<(usize, &str)> = numbered_lines(text).collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let (number, line) = lines[i];
+        let mut cursor = Cursor::new(line, number)?;
+        match cursor.peek_ident() {
+            Some("struct") | Some("union") => {
+                let is_union = cursor.peek_ident() == Some("union");
+                cursor.next();
+                let name = cursor.expect_name()?;
+                let attributes = cursor.attributes()?;
+                let fields = cursor.fields()?;
+                if is_union {
+                    program.unions.push(UnionDef { name, fields });
+                } else {
+                    program.structs.push(StructDef { name, fields, attributes });
+                }
+            }
+            Some("enum") => program.enums.push(cursor.enum_def()?),
+            Some("declare") => program.declarations.push(cursor.prototype()?),
+            Some("function") | Some("static") if line_declares_function(line) => {
+                let end = lines[i..]
+                    .iter()
+                    .position(|(_, l)| *l == "}")
+                    .map(|offset| i + offset)
+                    .ok_or_else(|| error(number, "function has no closing '}'"))?;
+                program.functions.push(read_function(&lines[i..=end])?);
+                i = end + 1;
+                continue;
+            }
+            Some("global") | Some("static") | Some("extern") => program.globals.push(cursor.global()?),
+            _ if matches!(cursor.peek(), Some(Token::Global(_))) => {
+                let Some(Token::Global(label)) = cursor.next() else { unreachable!() };
+                cursor.expect_punct('=')?;
+                cursor.expect_keyword("string")?;
+                let content = cursor.expect_string()?;
+                program.global_strings.push((label, content));
+            }
+            _ => return Err(error(number, format!("unexpected line '{}'", line))),
+        }
+        cursor.expect_end()?;
+        i += 1;
+    }
+    let defined = program.functions.iter().map(|f| (f.name.clone(), f.return_type.clone()));
+    let declared = program.declarations.iter().map(|d| (d.name.clone(), d.return_type.clone()));
+    let returns: HashMap<String, Type> = declared.chain(defined).collect();
+    for function in &mut program.functions {
+        function.infer_var_types(&returns);
+    }
+    Ok(program)
+}
+
+/// Parse one function in the notation of `function_to_text`.
+pub fn function_from_text(text: &str) -> Result<Function, CompileError> {
+    let lines: Vec<(usize, &str)> = numbered_lines(text).collect();
+    let mut function = read_function(&lines)?;
+    function.infer_var_types(&HashMap::new());
+    Ok(function)
+}
+
+/// Non-blank lines, trimmed, with 1-based line numbers.
+fn numbered_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())).filter(|(_, line)| !line.is_empty())
+}
+
+fn line_declares_function(line: &str) -> bool {
+    line.starts_with("function ") || line.starts_with("static function ")
+}
+
+fn error(line: usize, message: impl Into<String>) -> CompileError {
+    CompileError::new(ErrorCode::Syntax, format!("line {}: {}", line, message.into()))
+}
+
+fn read_function(lines: &[(usize, &str)]) -> Result<Function, CompileError> {
+    let Some(&(number, header)) = lines.first() else {
+        return Err(error(0, "expected a function"));
+    };
+    if !line_declares_function(header) {
+        return Err(error(number, "expected 'function'"));
+    }
+    let mut cursor = Cursor::new(header, number)?;
+    let is_static = cursor.eat_keyword("static");
+    cursor.expect_keyword("function")?;
+    let name = cursor.expect_name()?;
+    cursor.expect_punct('(')?;
+    let mut params = Vec::new();
+    if !cursor.eat_punct(')') {
+        loop {
+            let ty = cursor.parse_type()?;
+            params.push((ty, cursor.expect_var()?));
+            if cursor.eat_punct(')') {
+                break;
+            }
+            cursor.expect_punct(',')?;
+        }
+    }
+    cursor.expect_punct('-')?;
+    cursor.expect_punct('>')?;
+    let return_type = cursor.parse_type()?;
+    let attributes = cursor.attributes()?;
+    cursor.expect_punct('{')?;
+    cursor.expect_end()?;
+
+    let mut function = Function {
+        name,
+        return_type,
+        params,
+        blocks: Vec::new(),
+        entry_block: BlockId(0),
+        var_types: HashMap::new(),
+        attributes,
+        is_static,
+        label_addrs: Vec::new(),
+        labels: HashMap::new(),
+        local_names: HashMap::new(),
+        span: model::Span::default(),
+    };
+    let mut entry = None;
+    let mut block: Option<BasicBlock> = None;
+    for &(number, line) in &lines[1..] {
+        if line == "}" {
+            break;
+        }
+        let mut cursor = Cursor::new(line, number)?;
+        if let Some(id) = cursor.block_header()? {
+            if let Some(open) = block.take() {
+                return Err(error(number, format!("block bb{} has no terminator", open.id.0)));
+            }
+            let mut new_block =
+                BasicBlock { id, instructions: Vec::new(), terminator: Terminator::Unreachable, is_label_target: false, spans: Vec::new(), terminator_span: Span::default() };
+            if cursor.eat_punct('(') {
+                loop {
+                    if cursor.eat_keyword("entry") {
+                        entry = Some(id);
+                    } else {
+                        cursor.expect_keyword("label")?;
+                        let address_taken = cursor.eat_punct('&');
+                        if address_taken {
+                            cursor.expect_punct('&')?;
+                        }
+                        let label = cursor.expect_name()?;
+                        if address_taken {
+                            function.label_addrs.push(label.clone());
+                        }
+                        function.labels.insert(label, id);
+                        new_block.is_label_target = true;
+                    }
+                    if cursor.eat_punct(')') {
+                        break;
+                    }
+                    cursor.expect_punct(',')?;
+                }
+            }
+            cursor.expect_end()?;
+            block = Some(new_block);
+            continue;
+        }
+        let Some(open) = block.as_mut() else {
+            return Err(error(number, "instruction outside a block"));
+        };
+        if let Some(terminator) = cursor.terminator()? {
+            open.terminator = terminator;
+            cursor.expect_end()?;
+            function.blocks.push(block.take().expect("block is open"));
+        } else {
+            open.instructions.push(cursor.instruction()?);
+            open.spans.push(Span::default());
+            cursor.expect_end()?;
+        }
+    }
+    if let Some(open) = block {
+        return Err(error(number, format!("block bb{} has no terminator", open.id.0)));
+    }
+    function.entry_block = entry.or_else(|| function.blocks.first().map(|b| b.id)).unwrap_or(BlockId(0));
+    Ok(function)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Var(usize),
+    Global(String),
+    Ellipsis,
+    Punct(char),
+}
+
+/// The tokens of one line.
+struct Cursor {
+    tokens: Vec<Token>,
+    /// Whether whitespace came before each token, parallel to `tokens`.
+    spaced: Vec<bool>,
+    pos: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn new(text: &str, line: usize) -> Result<Self, CompileError> {
+        let (tokens, spaced) = tokenize(text, line)?.into_iter().unzip();
+        Ok(Cursor { tokens, spaced, pos: 0, line })
+    }
+
+    /// Whether the next token directly follows the previous one.
+    fn next_is_joined(&self) -> bool {
+        self.pos < self.tokens.len() && !self.spaced[self.pos]
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Ident(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> CompileError {
+        let found = match self.peek() {
+            Some(token) => format!("{:?}", token),
+            None => "end of line".to_string(),
+        };
+        error(self.line, format!("{}, found {}", message.into(), found))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(c));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), CompileError> {
+        if self.eat_punct(c) { Ok(()) } else { Err(self.error(format!("expected '{}'", c))) }
+    }
+
+    fn eat_keyword(&mut self, word: &str) -> bool {
+        let found = self.peek_ident() == Some(word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, word: &str) -> Result<(), CompileError> {
+        if self.eat_keyword(word) { Ok(()) } else { Err(self.error(format!("expected '{}'", word))) }
+    }
+
+    fn expect_name(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn expect_var(&mut self) -> Result<VarId, CompileError> {
+        match self.peek() {
+            Some(&Token::Var(v)) => {
+                self.pos += 1;
+                Ok(VarId(v))
+            }
+            _ => Err(self.error("expected a variable")),
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<i64, CompileError> {
+        match self.peek() {
+            Some(&Token::Int(v)) => {
+                self.pos += 1;
+                Ok(v)
+            }
+            _ => Err(self.error("expected an integer")),
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), CompileError> {
+        if self.pos >= self.tokens.len() { Ok(()) } else { Err(self.error("expected end of line")) }
+    }
+
+    fn expect_block(&mut self) -> Result<BlockId, CompileError> {
+        match self.peek_ident().and_then(block_number) {
+            Some(id) => {
+                self.pos += 1;
+                Ok(BlockId(id))
+            }
+            None => Err(self.error("expected a block")),
+        }
+    }
+
+    // ─── Types ──────────────────────────────────────────────────
+
+    /// An abstract type name, as `Type`'s `Display` writes it.
+    fn parse_type(&mut self) -> Result<Type, CompileError> {
+        Ok(self.declaration(false)?.0)
+    }
+
+    /// A type with its declarator, as `Type::qualified_declaration` writes
+    /// it; `named` allows a field name or `@global` inside it. Also returns
+    /// the qualifiers of the declared object itself.
+    fn declaration(&mut self, named: bool) -> Result<(Type, TypeQualifiers, Option<String>), CompileError> {
+        let (base, quals) = self.base_type()?;
+        let (derivations, name) = self.declarator(named)?;
+        let (ty, quals) = apply_derivations(base, quals, derivations);
+        Ok((ty, quals, name))
+    }
+
+    fn qualifiers(&mut self) -> TypeQualifiers {
+        let mut quals = TypeQualifiers::default();
+        loop {
+            if self.eat_keyword("const") {
+                quals.is_const = true;
+            } else if self.eat_keyword("volatile") {
+                quals.is_volatile = true;
+            } else if self.eat_keyword("restrict") {
+                quals.is_restrict = true;
+            } else {
+                return quals;
+            }
+        }
+    }
+
+    fn base_type(&mut self) -> Result<(Type, TypeQualifiers), CompileError> {
+        let quals = self.qualifiers();
+        let word = self.expect_name().map_err(|_| self.error("expected a type"))?;
+        let ty = match word.as_str() {
+            "int" => Type::Int,
+            "char" => Type::Char,
+            "short" => Type::Short,
+            "long" if self.eat_keyword("long") => Type::LongLong,
+            "long" => Type::Long,
+            "unsigned" => match self.expect_name()?.as_str() {
+                "int" => Type::UnsignedInt,
+                "char" => Type::UnsignedChar,
+                "short" => Type::UnsignedShort,
+                "long" if self.eat_keyword("long") => Type::UnsignedLongLong,
+                "long" => Type::UnsignedLong,
+                other => return Err(error(self.line, format!("unknown type 'unsigned {}'", other))),
+            },
+            "void" => Type::Void,
+            "float" => Type::Float,
+            "double" => Type::Double,
+            "_Bool" => Type::Bool,
+            "_Complex" => Type::Complex(Box::new(self.base_type()?.0)),
+            "struct" => Type::Struct(self.expect_name()?),
+            "union" => Type::Union(self.expect_name()?),
+            "enum" => Type::Enum(self.expect_name()?),
+            _ => Type::Typedef(word),
+        };
+        Ok((ty, quals))
+    }
+
+    /// The pointers, array and function suffixes, and (when `named`) the
+    /// name of a declarator, as derivations applied from the base type out.
+    fn declarator(&mut self, named: bool) -> Result<(Vec<Derivation>, Option<String>), CompileError> {
+        let mut derivations = Vec::new();
+        while self.eat_punct('*') {
+            derivations.push(Derivation::Pointer(self.qualifiers()));
+        }
+        let nested = self.peek() == Some(&Token::Punct('(')) && self.peek_at(1) == Some(&Token::Punct('*'));
+        let (inner, name) = if nested {
+            self.pos += 1;
+            let inner = self.declarator(named)?;
+            self.expect_punct(')')?;
+            inner
+        } else {
+            let name = match self.peek() {
+                Some(Token::Ident(n) | Token::Global(n)) if named => Some(n.clone()),
+                _ => None,
+            };
+            if name.is_some() {
+                self.pos += 1;
+            }
+            (Vec::new(), name)
+        };
+        let mut suffixes = Vec::new();
+        loop {
+            if self.eat_punct('[') {
+                let size = if self.eat_punct(']') {
+                    0
+                } else {
+                    let size = self.expect_int()?;
+                    self.expect_punct(']')?;
+                    size as usize
+                };
+                suffixes.push(Derivation::Array(size));
+            } else if self.eat_punct('(') {
+                let mut params = Vec::new();
+                let mut variadic = false;
+                if !(self.peek_ident() == Some("void") && self.peek_at(1) == Some(&Token::Punct(')'))) {
+                    loop {
+                        if self.peek() == Some(&Token::Ellipsis) {
+                            self.pos += 1;
+                            variadic = true;
+                            break;
+                        }
+                        params.push(self.parse_type()?);
+                        if !self.eat_punct(',') {
+                            break;
+                        }
+                    }
+                } else {
+                    self.pos += 1;
+                }
+                self.expect_punct(')')?;
+                suffixes.push(Derivation::Function(params, variadic));
+            } else {
+                break;
+            }
+        }
+        derivations.extend(suffixes.into_iter().rev());
+        derivations.extend(inner);
+        Ok((derivations, name))
+    }
+
+    fn attributes(&mut self) -> Result<Vec<Attribute>, CompileError> {
+        let mut attributes = Vec::new();
+        if !self.eat_keyword("__attribute__") {
+            return Ok(attributes);
+        }
+        self.expect_punct('(')?;
+        self.expect_punct('(')?;
+        loop {
+            let name = self.expect_name()?;
+            attributes.push(match name.as_str() {
+                "packed" => Attribute::Packed,
+                "pack" => Attribute::Pack(self.attribute_argument()? as usize),
+                "aligned" => Attribute::Aligned(self.attribute_argument()? as usize),
+                "section" => {
+                    self.expect_punct('(')?;
+                    let section = self.expect_string()?;
+                    self.expect_punct(')')?;
+                    Attribute::Section(section)
+                }
+                "noreturn" => Attribute::NoReturn,
+                "always_inline" => Attribute::AlwaysInline,
+                "weak" => Attribute::Weak,
+                "unused" => Attribute::Unused,
+                "constructor" => Attribute::Constructor,
+                "destructor" => Attribute::Destructor,
+                other => return Err(error(self.line, format!("unknown attribute '{}'", other))),
+            });
+            if !self.eat_punct(',') {
+                break;
+            }
+        }
+        self.expect_punct(')')?;
+        self.expect_punct(')')?;
+        Ok(attributes)
+    }
+
+    /// The `(N)` of `pack(N)` and `aligned(N)`.
+    fn attribute_argument(&mut self) -> Result<i64, CompileError> {
+        self.expect_punct('(')?;
+        let value = self.expect_int()?;
+        self.expect_punct(')')?;
+        Ok(value)
+    }
+
+    // ─── Definitions ────────────────────────────────────────────
+
+    fn fields(&mut self) -> Result<Vec<StructField>, CompileError> {
+        self.expect_punct('{')?;
+        let mut fields = Vec::new();
+        while !self.eat_punct('}') {
+            let (field_type, _, name) = self.declaration(true)?;
+            let bit_width = if self.eat_punct(':') { Some(self.expect_int()? as usize) } else { None };
+            self.expect_punct(';')?;
+            fields.push(StructField { field_type, name: name.unwrap_or_default(), bit_width });
+        }
+        Ok(fields)
+    }
+
+    fn enum_def(&mut self) -> Result<EnumDef, CompileError> {
+        self.expect_keyword("enum")?;
+        // Anonymous enums have an empty name
+        let name = if self.peek() == Some(&Token::Punct(':')) { String::new() } else { self.expect_name()? };
+        self.expect_punct(':')?;
+        let underlying = self.parse_type()?;
+        self.expect_punct('{')?;
+        let mut constants = Vec::new();
+        while !self.eat_punct('}') {
+            let constant = self.expect_name()?;
+            self.expect_punct('=')?;
+            constants.push((constant, self.expect_int()?));
+            if !self.eat_punct(',') {
+                self.expect_punct('}')?;
+                break;
+            }
+        }
+        Ok(EnumDef { name, constants, underlying })
+    }
+
+    /// `declare name(type, ..., ...) -> type`
+    fn prototype(&mut self) -> Result<Declaration, CompileError> {
+        self.expect_keyword("declare")?;
+        let name = self.expect_name()?;
+        self.expect_punct('(')?;
+        let mut params = Vec::new();
+        let mut is_variadic = false;
+        if !self.eat_punct(')') {
+            loop {
+                if self.peek() == Some(&Token::Ellipsis) {
+                    self.pos += 1;
+                    is_variadic = true;
+                } else {
+                    params.push(self.parse_type()?);
+                }
+                if self.eat_punct(')') {
+                    break;
+                }
+                self.expect_punct(',')?;
+            }
+        }
+        self.expect_punct('-')?;
+        self.expect_punct('>')?;
+        let return_type = self.parse_type()?;
+        Ok(Declaration { name, return_type, params, is_variadic })
+    }
+
+    fn global(&mut self) -> Result<GlobalVar, CompileError> {
+        let is_extern = self.eat_keyword("extern");
+        let is_static = self.eat_keyword("static");
+        self.expect_keyword("global")?;
+        let (r#type, qualifiers, name) = self.declaration(true)?;
+        let Some(name) = name else { return Err(self.error("expected a global name")) };
+        let attributes = self.attributes()?;
+        let init = if self.eat_punct('=') { Some(self.initializer()?) } else { None };
+        Ok(GlobalVar { r#type, qualifiers, name, init, attributes, is_extern, is_static, span: Span::default() })
+    }
+
+    /// An initializer as `printer::initializer_text` writes it.
+    fn initializer(&mut self) -> Result<Expr, CompileError> {
+        let mut expr = match self.next() {
+            Some(Token::Int(v)) => Expr::Constant(v),
+            Some(Token::Float(f)) => Expr::FloatConstant(f),
+            Some(Token::Str(s)) => Expr::StringLiteral(s),
+            Some(Token::Ident(prefix)) if self.next_is_joined() && matches!(self.peek(), Some(Token::Str(_))) => {
+                let encoding = ENCODINGS
+                    .into_iter()
+                    .find(|e| encoding_prefix(*e) == prefix)
+                    .ok_or_else(|| error(self.line, format!("unknown string prefix '{}'", prefix)))?;
+                Expr::WideStringLiteral(self.expect_string()?, encoding)
+            }
+            Some(Token::Global(name)) => Expr::Variable(name),
+            Some(Token::Punct('&')) => Expr::Unary { op: UnaryOp::AddrOf, expr: Box::new(self.initializer()?) },
+            Some(Token::Punct('-')) => Expr::Unary { op: UnaryOp::Minus, expr: Box::new(self.initializer()?) },
+            Some(Token::Punct('(')) if self.starts_type() => {
+                let ty = self.parse_type()?;
+                self.expect_punct(')')?;
+                Expr::Cast(ty, Box::new(self.initializer()?))
+            }
+            Some(Token::Punct('(')) => {
+                let left = self.initializer()?;
+                let op = self.binary_operator()?;
+                let right = self.initializer()?;
+                self.expect_punct(')')?;
+                Expr::Binary { left: Box::new(left), op, right: Box::new(right) }
+            }
+            Some(Token::Punct('{')) => {
+                let mut items = Vec::new();
+                while !self.eat_punct('}') {
+                    let designator = if self.eat_punct('.') {
+                        let field = self.expect_name()?;
+                        self.expect_punct('=')?;
+                        Some(Designator::Field(field))
+                    } else if self.eat_punct('[') {
+                        let start = self.expect_int()?;
+                        let designator = if self.peek() == Some(&Token::Ellipsis) {
+                            self.pos += 1;
+                            Designator::Range { start, end: self.expect_int()? }
+                        } else {
+                            Designator::Index(start)
+                        };
+                        self.expect_punct(']')?;
+                        self.expect_punct('=')?;
+                        Some(designator)
+                    } else {
+                        None
+                    };
+                    items.push(InitItem { designator, value: self.initializer()? });
+                    if !self.eat_punct(',') {
+                        self.expect_punct('}')?;
+                        break;
+                    }
+                }
+                Expr::InitList(items)
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected an initializer"));
+            }
+        };
+        loop {
+            if self.eat_punct('[') {
+                let index = self.initializer()?;
+                self.expect_punct(']')?;
+                expr = Expr::Index { array: Box::new(expr), index: Box::new(index) };
+            } else if self.eat_punct('.') {
+                expr = Expr::Member { expr: Box::new(expr), member: self.expect_name()? };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn starts_type(&self) -> bool {
+        matches!(
+            self.peek_ident(),
+            Some(
+                "int" | "char" | "short" | "long" | "unsigned" | "void" | "float" | "double" | "_Bool" | "_Complex"
+                    | "struct" | "union" | "enum" | "const" | "volatile"
+            )
+        )
+    }
+
+    /// A C binary operator token, as `BinaryOp`'s `Display` writes it.
+    fn binary_operator(&mut self) -> Result<BinaryOp, CompileError> {
+        let mut text = String::new();
+        while let Some(&Token::Punct(c)) = self.peek() {
+            if !text.is_empty() && !self.next_is_joined() || !"+-*/%=!<>&|^".contains(c) {
+                break;
+            }
+            text.push(c);
+            self.pos += 1;
+        }
+        BINARY_OPS
+            .iter()
+            .find(|op| op.to_string() == text)
+            .cloned()
+            .ok_or_else(|| error(self.line, format!("unknown operator '{}'", text)))
+    }
+
+    // ─── Function bodies ────────────────────────────────────────
+
+    /// `bbN:` starting a block, or `None` when the line is not one.
+    fn block_header(&mut self) -> Result<Option<BlockId>, CompileError> {
+        if self.peek_at(1) != Some(&Token::Punct(':')) {
+            return Ok(None);
+        }
+        let id = self.expect_block()?;
+        self.expect_punct(':')?;
+        Ok(Some(id))
+    }
+
+    fn operand(&mut self) -> Result<Operand, CompileError> {
+        let operand = match self.peek() {
+            Some(&Token::Int(v)) => Operand::Constant(v),
+            Some(&Token::Float(f)) => Operand::FloatConstant(f),
+            Some(&Token::Var(v)) => Operand::Var(VarId(v)),
+            Some(Token::Global(name)) => Operand::Global(name.clone()),
+            Some(Token::Ident(word)) if word == "inf" => Operand::FloatConstant(f64::INFINITY),
+            Some(Token::Ident(word)) if word == "NaN" => Operand::FloatConstant(f64::NAN),
+            Some(Token::Punct('-')) if self.peek_at(1) == Some(&Token::Ident("inf".to_string())) => {
+                self.pos += 1;
+                Operand::FloatConstant(f64::NEG_INFINITY)
+            }
+            _ => return Err(self.error("expected an operand")),
+        };
+        self.pos += 1;
+        Ok(operand)
+    }
+
+    /// Operands separated by commas up to the closing `)`.
+    fn operand_list(&mut self) -> Result<Vec<Operand>, CompileError> {
+        let mut operands = Vec::new();
+        if self.eat_punct(')') {
+            return Ok(operands);
+        }
+        loop {
+            operands.push(self.operand()?);
+            if self.eat_punct(')') {
+                return Ok(operands);
+            }
+            self.expect_punct(',')?;
+        }
+    }
+
+    fn terminator(&mut self) -> Result<Option<Terminator>, CompileError> {
+        let terminator = match self.peek_ident() {
+            Some("br") => {
+                self.pos += 1;
+                if self.peek_ident().and_then(block_number).is_some() {
+                    Terminator::Br(self.expect_block()?)
+                } else {
+                    let cond = self.operand()?;
+                    self.expect_punct('?')?;
+                    let then_block = self.expect_block()?;
+                    self.expect_punct(':')?;
+                    let else_block = self.expect_block()?;
+                    let hint = if self.eat_punct('(') {
+                        self.expect_keyword("likely")?;
+                        let hint = if self.eat_keyword("then") {
+                            BranchHint::LikelyThen
+                        } else {
+                            self.expect_keyword("else")?;
+                            BranchHint::LikelyElse
+                        };
+                        self.expect_punct(')')?;
+                        hint
+                    } else {
+                        BranchHint::None
+                    };
+                    Terminator::CondBr { cond, then_block, else_block, hint }
+                }
+            }
+            Some("ret") => {
+                self.pos += 1;
+                Terminator::Ret(if self.peek().is_some() { Some(self.operand()?) } else { None })
+            }
+            Some("unreachable") => {
+                self.pos += 1;
+                Terminator::Unreachable
+            }
+            Some("goto") => {
+                self.pos += 1;
+                self.expect_punct('*')?;
+                Terminator::IndirectBr { target: self.operand()? }
+            }
+            Some("switch") => {
+                self.pos += 1;
+                let value = self.operand()?;
+                self.expect_punct('[')?;
+                let mut cases = Vec::new();
+                while !self.eat_punct(']') {
+                    if !cases.is_empty() {
+                        self.expect_punct(',')?;
+                    }
+                    let case = self.expect_int()?;
+                    self.expect_punct(':')?;
+                    cases.push((case, self.expect_block()?));
+                }
+                self.expect_keyword("default")?;
+                Terminator::Switch { value, cases, default: self.expect_block()? }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(terminator))
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, CompileError> {
+        let dest = if matches!(self.peek(), Some(Token::Var(_))) && self.peek_at(1) == Some(&Token::Punct('=')) {
+            let dest = self.expect_var()?;
+            self.pos += 1;
+            Some(dest)
+        } else {
+            None
+        };
+        let need_dest = |cursor: &Cursor| dest.ok_or_else(|| cursor.error("expected '%N =' before the instruction"));
+        let Some(word) = self.peek_ident().map(str::to_string) else {
+            // `%N = operand`
+            let dest = need_dest(self)?;
+            return Ok(Instruction::Copy { dest, src: self.operand()? });
+        };
+        if matches!(word.as_str(), "inf" | "NaN") {
+            let dest = need_dest(self)?;
+            return Ok(Instruction::Copy { dest, src: self.operand()? });
+        }
+        self.pos += 1;
+        Ok(match word.as_str() {
+            "phi" => {
+                let mut preds = Vec::new();
+                while self.eat_punct('[') {
+                    let block = self.expect_block()?;
+                    self.expect_punct(':')?;
+                    preds.push((block, self.expect_var()?));
+                    self.expect_punct(']')?;
+                }
+                Instruction::Phi { dest: need_dest(self)?, preds }
+            }
+            "cast" => {
+                let src_type = self.parse_type()?;
+                let src = self.operand()?;
+                self.expect_keyword("to")?;
+                Instruction::Cast { dest: need_dest(self)?, src, src_type, r#type: self.parse_type()? }
+            }
+            "alloca" => Instruction::Alloca { dest: need_dest(self)?, r#type: self.parse_type()? },
+            "load" => {
+                let volatile = self.eat_keyword("volatile");
+                let value_type = self.parse_type()?;
+                self.expect_punct(',')?;
+                Instruction::Load { dest: need_dest(self)?, addr: self.operand()?, value_type, volatile }
+            }
+            "store" => {
+                let volatile = self.eat_keyword("volatile");
+                let value_type = self.parse_type()?;
+                let src = self.operand()?;
+                self.expect_punct(',')?;
+                Instruction::Store { addr: self.operand()?, src, value_type, volatile }
+            }
+            "gep" => {
+                let base = self.operand()?;
+                self.expect_punct(',')?;
+                let index = self.operand()?;
+                self.expect_keyword("x")?;
+                Instruction::GetElementPtr { dest: need_dest(self)?, base, index, element_type: self.parse_type()? }
+            }
+            "call" => {
+                if self.eat_punct('*') {
+                    let func_ptr = self.operand()?;
+                    self.expect_punct('(')?;
+                    let args = self.operand_list()?;
+                    Instruction::IndirectCall { dest, func_ptr, args, is_variadic: false, vararg_types: Vec::new() }
+                } else {
+                    let name = self.expect_name()?;
+                    self.expect_punct('(')?;
+                    Instruction::Call { dest, name, args: self.operand_list()?, vararg_types: Vec::new() }
+                }
+            }
+            "va_start" => {
+                let list = self.operand()?;
+                self.expect_punct(',')?;
+                Instruction::VaStart { list, arg_index: self.expect_int()? as usize }
+            }
+            "va_end" => Instruction::VaEnd { list: self.operand()? },
+            "va_copy" => {
+                let dest = self.operand()?;
+                self.expect_punct(',')?;
+                Instruction::VaCopy { dest, src: self.operand()? }
+            }
+            "va_arg" => {
+                let list = self.operand()?;
+                self.expect_punct(',')?;
+                Instruction::VaArg { dest: need_dest(self)?, list, r#type: self.parse_type()? }
+            }
+            "memcpy" | "memset" => {
+                let dest = self.operand()?;
+                self.expect_punct(',')?;
+                let second = self.operand()?;
+                self.expect_punct(',')?;
+                let size = self.expect_int()? as usize;
+                if word == "memcpy" {
+                    Instruction::Memcpy { dest, src: second, size }
+                } else {
+                    Instruction::Memset { dest, value: second, size }
+                }
+            }
+            "asm" => self.inline_asm()?,
+            "simd" => {
+                self.expect_punct('.')?;
+                let name = self.expect_name()?;
+                let op = SIMD_OPS
+                    .iter()
+                    .find(|op| op_name(*op) == name)
+                    .cloned()
+                    .ok_or_else(|| error(self.line, format!("unknown SIMD operation '{}'", name)))?;
+                self.expect_punct('<')?;
+                let width = self.expect_int()? as usize;
+                self.expect_keyword("x")?;
+                let elem_type = self.parse_type()?;
+                self.expect_punct('>')?;
+                let mut operands = Vec::new();
+                while self.peek().is_some() {
+                    operands.push(self.operand()?);
+                    if !self.eat_punct(',') {
+                        break;
+                    }
+                }
+                Instruction::Simd { op, dest, operands, elem_type, width }
+            }
+            _ => {
+                let dest = need_dest(self)?;
+                let (float, name) = match word.strip_prefix('f') {
+                    Some(rest) if !is_op_name(&word) => (true, rest),
+                    _ => (false, word.as_str()),
+                };
+                let src = self.operand()?;
+                if self.eat_punct(',') {
+                    let op = BINARY_OPS
+                        .iter()
+                        .find(|op| op_name(*op) == name)
+                        .cloned()
+                        .ok_or_else(|| error(self.line, format!("unknown operation '{}'", word)))?;
+                    let right = self.operand()?;
+                    if float {
+                        Instruction::FloatBinary { dest, op, left: src, right }
+                    } else {
+                        Instruction::Binary { dest, op, left: src, right }
+                    }
+                } else {
+                    let op = UNARY_OPS
+                        .iter()
+                        .find(|op| op_name(*op) == name)
+                        .cloned()
+                        .ok_or_else(|| error(self.line, format!("unknown operation '{}'", word)))?;
+                    if float {
+                        Instruction::FloatUnary { dest, op, src }
+                    } else {
+                        Instruction::Unary { dest, op, src }
+                    }
+                }
+            }
+        })
+    }
+
+    /// `asm [volatile] "template" ("=r" %1, ...) <- ("r" %0, ...) [clobber("cc", ...)]`
+    fn inline_asm(&mut self) -> Result<Instruction, CompileError> {
+        let is_volatile = self.eat_keyword("volatile");
+        let template = self.expect_string()?;
+        let mut outputs = Vec::new();
+        let mut output_constraints = Vec::new();
+        self.expect_punct('(')?;
+        while !self.eat_punct(')') {
+            output_constraints.push(self.expect_string()?);
+            outputs.push(self.expect_var()?);
+            if !self.eat_punct(',') {
+                self.expect_punct(')')?;
+                break;
+            }
+        }
+        self.expect_punct('<')?;
+        self.expect_punct('-')?;
+        let mut inputs = Vec::new();
+        let mut input_constraints = Vec::new();
+        self.expect_punct('(')?;
+        while !self.eat_punct(')') {
+            input_constraints.push(self.expect_string()?);
+            inputs.push(self.operand()?);
+            if !self.eat_punct(',') {
+                self.expect_punct(')')?;
+                break;
+            }
+        }
+        let mut clobbers = Vec::new();
+        if self.eat_keyword("clobber") {
+            self.expect_punct('(')?;
+            loop {
+                clobbers.push(self.expect_string()?);
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(')')?;
+        }
+        Ok(Instruction::InlineAsm { template, outputs, inputs, output_constraints, input_constraints, clobbers, is_volatile })
+    }
+}
+
+/// One step of a declarator, applied to the type built so far.
+enum Derivation {
+    Pointer(TypeQualifiers),
+    Array(usize),
+    Function(Vec<Type>, bool),
+}
+
+/// Build the declared type from its base. `quals` qualify the type built so
+/// far and move into the pointer that points to it; what is left qualifies
+/// the declared object. A `Type::FunctionPointer` is already a pointer, so the
+/// `*` of `(*)(int)` is not applied again.
+fn apply_derivations(base: Type, mut quals: TypeQualifiers, derivations: Vec<Derivation>) -> (Type, TypeQualifiers) {
+    let mut ty = base;
+    let mut bare_function = false;
+    for derivation in derivations {
+        match derivation {
+            Derivation::Pointer(pointer_quals) if bare_function => {
+                bare_function = false;
+                quals = pointer_quals;
+            }
+            Derivation::Pointer(pointer_quals) => {
+                ty = Type::Pointer(Box::new(ty), quals);
+                quals = pointer_quals;
+            }
+            Derivation::Array(size) => ty = Type::Array(Box::new(ty), size),
+            Derivation::Function(param_types, is_variadic) => {
+                ty = Type::FunctionPointer { return_type: Box::new(ty), param_types, is_variadic };
+                quals = TypeQualifiers::default();
+                bare_function = true;
+            }
+        }
+    }
+    (ty, quals)
+}
+
+fn block_number(word: &str) -> Option<usize> {
+    word.strip_prefix("bb")?.parse().ok()
+}
+
+fn is_op_name(word: &str) -> bool {
+    BINARY_OPS.iter().any(|op| op_name(op) == word) || UNARY_OPS.iter().any(|op| op_name(op) == word)
+}
+
+const BINARY_OPS: [BinaryOp; 29] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::Mod,
+    BinaryOp::EqualEqual,
+    BinaryOp::NotEqual,
+    BinaryOp::Less,
+    BinaryOp::LessEqual,
+    BinaryOp::Greater,
+    BinaryOp::GreaterEqual,
+    BinaryOp::LogicalAnd,
+    BinaryOp::LogicalOr,
+    BinaryOp::BitwiseAnd,
+    BinaryOp::BitwiseOr,
+    BinaryOp::BitwiseXor,
+    BinaryOp::ShiftLeft,
+    BinaryOp::ShiftRight,
+    BinaryOp::Assign,
+    BinaryOp::AddAssign,
+    BinaryOp::SubAssign,
+    BinaryOp::MulAssign,
+    BinaryOp::DivAssign,
+    BinaryOp::ModAssign,
+    BinaryOp::BitwiseAndAssign,
+    BinaryOp::BitwiseOrAssign,
+    BinaryOp::BitwiseXorAssign,
+    BinaryOp::ShiftLeftAssign,
+    BinaryOp::ShiftRightAssign,
+];
+
+const UNARY_OPS: [UnaryOp; 6] =
+    [UnaryOp::Plus, UnaryOp::Minus, UnaryOp::LogicalNot, UnaryOp::BitwiseNot, UnaryOp::AddrOf, UnaryOp::Deref];
+
+const ENCODINGS: [StringEncoding; 5] =
+    [StringEncoding::Plain, StringEncoding::Utf8, StringEncoding::Wide, StringEncoding::Utf16, StringEncoding::Utf32];
+
+const SIMD_OPS: [SimdOp; 15] = [
+    SimdOp::Load,
+    SimdOp::Store,
+    SimdOp::Add,
+    SimdOp::Sub,
+    SimdOp::Mul,
+    SimdOp::And,
+    SimdOp::Or,
+    SimdOp::Xor,
+    SimdOp::HorizontalAdd,
+    SimdOp::Splat,
+    SimdOp::LaneMask,
+    SimdOp::Blend,
+    SimdOp::IndexSeq,
+    SimdOp::Gather,
+    SimdOp::Scatter,
+];
+
+/// Each token with whether whitespace came before it.
+fn tokenize(text: &str, line: usize) -> Result<Vec<(Token, bool)>, CompileError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut spaced = Vec::new();
+    let mut i = 0;
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let take_while = |i: &mut usize, f: &dyn Fn(char) -> bool| {
+        let start = *i;
+        while *i < chars.len() && f(chars[*i]) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        spaced.push(i > 0 && chars[i - 1].is_whitespace());
+        if c == '%' && next.is_some_and(|n| n.is_ascii_digit()) {
+            i += 1;
+            let digits = take_while(&mut i, &|c| c.is_ascii_digit());
+            tokens.push(Token::Var(digits.parse().map_err(|_| error(line, "variable number too large"))?));
+        } else if c == '@' {
+            i += 1;
+            tokens.push(Token::Global(take_while(&mut i, &is_name_char)));
+        } else if c == '"' {
+            i += 1;
+            tokens.push(Token::Str(read_string(&chars, &mut i, line)?));
+        } else if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                let d = chars[i];
+                let exponent_sign = (d == '-' || d == '+') && matches!(chars[i - 1], 'e' | 'E');
+                if d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            let token = if number.contains(['.', 'e', 'E']) {
+                number.parse().map(Token::Float).ok()
+            } else {
+                number.parse().map(Token::Int).ok()
+            };
+            tokens.push(token.ok_or_else(|| error(line, format!("invalid number '{}'", number)))?);
+        } else if c == '<' && text[chars[..i].iter().map(|c| c.len_utf8()).sum::<usize>()..].starts_with("<anonymous ") {
+            // Tags of anonymous structs and unions, like `<anonymous 1>`
+            let start = i;
+            while i < chars.len() && chars[i] != '>' {
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Ident(chars[start..i.min(chars.len())].iter().collect()));
+        } else if c == '.' && next == Some('.') && chars.get(i + 2) == Some(&'.') {
+            i += 3;
+            tokens.push(Token::Ellipsis);
+        } else if is_name_char(c) {
+            tokens.push(Token::Ident(take_while(&mut i, &is_name_char)));
+        } else {
+            i += 1;
+            tokens.push(Token::Punct(c));
+        }
+    }
+    Ok(tokens.into_iter().zip(spaced).collect())
+}
+
+/// The rest of a string written with `escape_default`, after its opening quote.
+fn read_string(chars: &[char], i: &mut usize, line: usize) -> Result<String, CompileError> {
+    let mut s = String::new();
+    loop {
+        let Some(&c) = chars.get(*i) else { return Err(error(line, "unterminated string")) };
+        *i += 1;
+        match c {
+            '"' => return Ok(s),
+            '\\' => {
+                let Some(&e) = chars.get(*i) else { return Err(error(line, "unterminated string")) };
+                *i += 1;
+                s.push(match e {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    'u' => {
+                        let close = chars[*i..].iter().position(|&c| c == '}').map(|p| *i + p);
+                        let code = close
+                            .filter(|_| chars.get(*i) == Some(&'{'))
+                            .and_then(|close| {
+                                let hex: String = chars[*i + 1..close].iter().collect();
+                                *i = close + 1;
+                                u32::from_str_radix(&hex, 16).ok()
+                            })
+                            .and_then(char::from_u32);
+                        code.ok_or_else(|| error(line, "invalid unicode escape"))?
+                    }
+                    other => other,
+                });
+            }
+            c => s.push(c),
+        }
+    }
+}
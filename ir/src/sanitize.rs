@@ -0,0 +1,374 @@
+// Runtime checks for `-fsanitize=undefined` and `-fsanitize=bounds`.
+//
+// With `Lowerer::set_sanitize_undefined(true)`, integer division and
+// remainder, shifts, and signed `+ - *` are preceded by a check that branches
+// to a failure block when the operation would be undefined. The failure block
+// calls `__ubsan_abort(message, length)`, a small helper the lowerer adds to
+// the program, which writes the message to stderr and calls `abort()`.
+//
+// Checks run on the operands after the usual arithmetic conversions, so a
+// check on `int` computes in `long` and tests the range, while a check on
+// `long` uses wrapping arithmetic and sign tests. Operands that are
+// constants in the safe range are not checked.
+//
+// With `Lowerer::set_sanitize_bounds(true)`, every local array is allocated
+// with guard slots after its last element, filled with `STACK_GUARD`. Indexing
+// such an array checks the index against the array bound, and each return
+// checks that the guards are intact, which catches writes through pointers
+// that escaped the index check. Local arrays are allocated in the entry block
+// in this mode, so their guards are set on every path to a return.
+
+use model::{BinaryOp, Expr as AstExpr, Span, Type, TypeEnv};
+use crate::lowerer::Lowerer;
+use crate::types::{BasicBlock, BlockId, Function, Instruction, Operand, Terminator, VarId};
+
+/// Name of the runtime helper called when a check fails.
+pub(crate) const UBSAN_ABORT: &str = "__ubsan_abort";
+
+/// Undefined behaviour detected at run time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum UbCheck {
+    DivisionByZero,
+    ShiftOutOfRange,
+    SignedOverflow,
+    IndexOutOfBounds,
+    StackGuardOverwritten,
+}
+
+impl UbCheck {
+    fn message(self) -> &'static str {
+        match self {
+            UbCheck::DivisionByZero => "runtime error: integer division by zero\n",
+            UbCheck::ShiftOutOfRange => "runtime error: shift exponent out of range\n",
+            UbCheck::SignedOverflow => "runtime error: signed integer overflow\n",
+            UbCheck::IndexOutOfBounds => "runtime error: index out of bounds for stack array\n",
+            UbCheck::StackGuardOverwritten => "runtime error: stack array guard overwritten\n",
+        }
+    }
+}
+
+/// Value stored in the guard slots after each stack array.
+const STACK_GUARD: i64 = 0x5AC0_FFEE;
+/// Bytes of guard after each stack array, rounded up to whole elements.
+const GUARD_BYTES: i64 = 8;
+
+impl Lowerer {
+    /// Enable or disable `-fsanitize=undefined` checks.
+    pub fn set_sanitize_undefined(&mut self, enable: bool) {
+        self.sanitize_undefined = enable;
+    }
+
+    /// Enable or disable `-fsanitize=bounds` checks on local arrays.
+    pub fn set_sanitize_bounds(&mut self, enable: bool) {
+        self.sanitize_bounds = enable;
+    }
+
+    /// Allocate the local array `ty` in `bid`. With bounds checking, the
+    /// array gets trailing guard slots and goes in the entry block instead.
+    pub(crate) fn alloca_stack_array(&mut self, ty: &Type, bid: BlockId) -> VarId {
+        let var = self.new_var();
+        let (elem, len) = match ty {
+            Type::Array(elem, len) if self.sanitize_bounds && *len > 0 => (elem.as_ref().clone(), *len as i64),
+            _ => {
+                self.push_instruction(bid, Instruction::Alloca { dest: var, r#type: ty.clone() });
+                return var;
+            }
+        };
+        let elem_size = self.get_type_size(&elem).max(1);
+        let pad = (GUARD_BYTES + elem_size - 1) / elem_size;
+        let guard_offset = len * elem_size;
+        // The entry block (always block 0) dominates every return, so the
+        // guards are set whichever path reaches the check
+        let padded = Type::Array(Box::new(elem), (len + pad) as usize);
+        self.push_instruction(BlockId(0), Instruction::Alloca { dest: var, r#type: padded });
+        for word in 0..GUARD_BYTES / 4 {
+            let addr = self.new_var();
+            self.var_types.insert(addr, Type::ptr(Type::Int));
+            self.push_instruction(BlockId(0), Instruction::GetElementPtr {
+                dest: addr,
+                base: Operand::Var(var),
+                index: Operand::Constant(guard_offset + word * 4),
+                element_type: Type::Char,
+            });
+            self.push_instruction(BlockId(0), Instruction::Store {
+                addr: Operand::Var(addr),
+                src: Operand::Constant(STACK_GUARD),
+                value_type: Type::Int,
+                volatile: true,
+            });
+        }
+        self.stack_guards.push((var, guard_offset));
+        var
+    }
+
+    /// Check `index` against the bound of `array`, when it indexes a local
+    /// array. `&a[len]` is allowed when `one_past_end` is set.
+    pub(crate) fn check_index(&mut self, array: &AstExpr, array_type: &Type, index: &Operand, one_past_end: bool) {
+        let Type::Array(_, len) = array_type else {
+            return;
+        };
+        if !self.sanitize_bounds || *len == 0 || !self.is_stack_array(array) {
+            return;
+        }
+        let limit = *len as i64 + i64::from(one_past_end);
+        if matches!(index, Operand::Constant(c) if (0..limit).contains(c)) {
+            return;
+        }
+        let index_type = self.get_operand_type(index).unwrap_or(Type::Long);
+        let index = self.ub_cast_long(index, &index_type);
+        let negative = self.ub_op(BinaryOp::Less, index.clone(), Operand::Constant(0));
+        let too_big = self.ub_op(BinaryOp::GreaterEqual, index, Operand::Constant(limit));
+        let cond = self.ub_op(BinaryOp::BitwiseOr, negative, too_big);
+        self.ub_check(cond, UbCheck::IndexOutOfBounds);
+    }
+
+    /// Whether `expr` names a guarded local array. Rows of multi-dimensional
+    /// arrays are left to the guard check, since only the whole array's extent
+    /// is known for certain.
+    fn is_stack_array(&self, expr: &AstExpr) -> bool {
+        let AstExpr::Variable(name) = expr else {
+            return false;
+        };
+        self.variable_allocas
+            .get(name)
+            .is_some_and(|var| self.stack_guards.iter().any(|(guarded, _)| guarded == var))
+    }
+
+    /// Check the guards of every local array before each return of the
+    /// function being lowered.
+    pub(crate) fn check_stack_guards(&mut self) {
+        if self.stack_guards.is_empty() {
+            return;
+        }
+        let returns: Vec<BlockId> = self
+            .blocks
+            .iter()
+            .filter(|b| matches!(b.terminator, Terminator::Ret(_)))
+            .map(|b| b.id)
+            .collect();
+        for bid in returns {
+            let ret = std::mem::replace(&mut self.blocks[bid.0].terminator, Terminator::Unreachable);
+            self.current_block = Some(bid);
+            let mut cond = Operand::Constant(0);
+            for (var, offset) in self.stack_guards.clone() {
+                for word in 0..GUARD_BYTES / 4 {
+                    let addr = self.new_var();
+                    self.var_types.insert(addr, Type::ptr(Type::Int));
+                    self.add_instruction(Instruction::GetElementPtr {
+                        dest: addr,
+                        base: Operand::Var(var),
+                        index: Operand::Constant(offset + word * 4),
+                        element_type: Type::Char,
+                    });
+                    let value = self.new_var();
+                    self.var_types.insert(value, Type::Int);
+                    self.add_instruction(Instruction::Load {
+                        dest: value,
+                        addr: Operand::Var(addr),
+                        value_type: Type::Int,
+                        volatile: true,
+                    });
+                    let changed = self.ub_op(BinaryOp::NotEqual, Operand::Var(value), Operand::Constant(STACK_GUARD));
+                    cond = self.ub_op(BinaryOp::BitwiseOr, cond, changed);
+                }
+            }
+            self.ub_check(cond, UbCheck::StackGuardOverwritten);
+            if let Some(cont) = self.current_block {
+                self.set_terminator(cont, ret);
+            }
+        }
+    }
+
+    /// Emit the checks for `left op right`, with operand types `l_ty` and
+    /// `r_ty`, before the operation itself. Leaves the lowerer in the block
+    /// where the operation is safe.
+    pub(crate) fn sanitize_binary(&mut self, op: &BinaryOp, left: &Operand, right: &Operand, l_ty: &Type, r_ty: &Type) {
+        let (l_ty, r_ty) = (self.resolve_type(l_ty), self.resolve_type(r_ty));
+        if !self.sanitize_undefined || !TypeEnv::is_integer_type(&l_ty) || !TypeEnv::is_integer_type(&r_ty) {
+            return;
+        }
+        // A shift has the type of its promoted left operand
+        let ty = match op {
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => TypeEnv::integer_promotion(&l_ty),
+            _ => TypeEnv::usual_arithmetic_conversions(&l_ty, &r_ty),
+        };
+        let signed = !TypeEnv::is_unsigned_integer(&ty);
+        let wide = self.get_type_size(&ty) == 8;
+        match op {
+            BinaryOp::Div | BinaryOp::Mod => {
+                if !matches!(right, Operand::Constant(c) if *c != 0) {
+                    let cond = self.ub_op(BinaryOp::EqualEqual, right.clone(), Operand::Constant(0));
+                    self.ub_check(cond, UbCheck::DivisionByZero);
+                }
+                // MIN / -1 overflows
+                if signed && !matches!(right, Operand::Constant(c) if *c != -1) {
+                    let is_min = if wide {
+                        self.is_long_min(left)
+                    } else {
+                        self.ub_op(BinaryOp::EqualEqual, left.clone(), Operand::Constant(i64::from(i32::MIN)))
+                    };
+                    let minus_one = self.ub_op(BinaryOp::EqualEqual, right.clone(), Operand::Constant(-1));
+                    let cond = self.ub_op(BinaryOp::BitwiseAnd, is_min, minus_one);
+                    self.ub_check(cond, UbCheck::SignedOverflow);
+                }
+            }
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                let bits = if wide { 64 } else { 32 };
+                if !matches!(right, Operand::Constant(c) if (0..bits).contains(c)) {
+                    let negative = self.ub_op(BinaryOp::Less, right.clone(), Operand::Constant(0));
+                    let too_big = self.ub_op(BinaryOp::GreaterEqual, right.clone(), Operand::Constant(bits));
+                    let cond = self.ub_op(BinaryOp::BitwiseOr, negative, too_big);
+                    self.ub_check(cond, UbCheck::ShiftOutOfRange);
+                }
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul if signed => {
+                let cond = if wide {
+                    self.long_overflows(op, left, right)
+                } else {
+                    // The exact result fits in a long; test that it fits in an int
+                    let l = self.ub_cast_long(left, &ty);
+                    let r = self.ub_cast_long(right, &ty);
+                    let exact = self.ub_op(op.clone(), l, r);
+                    let low = self.ub_op(BinaryOp::Less, exact.clone(), Operand::Constant(i64::from(i32::MIN)));
+                    let high = self.ub_op(BinaryOp::Greater, exact, Operand::Constant(i64::from(i32::MAX)));
+                    self.ub_op(BinaryOp::BitwiseOr, low, high)
+                };
+                self.ub_check(cond, UbCheck::SignedOverflow);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `left op right` overflows a signed 64-bit value.
+    fn long_overflows(&mut self, op: &BinaryOp, left: &Operand, right: &Operand) -> Operand {
+        let result = self.ub_op(op.clone(), left.clone(), right.clone());
+        match op {
+            // Overflow iff both operands differ in sign from the result
+            BinaryOp::Add => {
+                let a = self.ub_op(BinaryOp::BitwiseXor, left.clone(), result.clone());
+                let b = self.ub_op(BinaryOp::BitwiseXor, right.clone(), result);
+                let both = self.ub_op(BinaryOp::BitwiseAnd, a, b);
+                self.ub_op(BinaryOp::Less, both, Operand::Constant(0))
+            }
+            // Overflow iff the operands differ in sign and the result differs from left
+            BinaryOp::Sub => {
+                let a = self.ub_op(BinaryOp::BitwiseXor, left.clone(), right.clone());
+                let b = self.ub_op(BinaryOp::BitwiseXor, left.clone(), result);
+                let both = self.ub_op(BinaryOp::BitwiseAnd, a, b);
+                self.ub_op(BinaryOp::Less, both, Operand::Constant(0))
+            }
+            // Divide the wrapped product back, with the divisor replaced by 1
+            // when it is 0 or -1; -1 * MIN is the only overflow in that case
+            _ => {
+                let zero = self.ub_op(BinaryOp::EqualEqual, left.clone(), Operand::Constant(0));
+                let minus_one = self.ub_op(BinaryOp::EqualEqual, left.clone(), Operand::Constant(-1));
+                let special = self.ub_op(BinaryOp::BitwiseOr, zero, minus_one.clone());
+                let fixup = self.ub_op(BinaryOp::Sub, Operand::Constant(1), left.clone());
+                let fixup = self.ub_op(BinaryOp::Mul, special.clone(), fixup);
+                let divisor = self.ub_op(BinaryOp::Add, left.clone(), fixup);
+                let quotient = self.ub_op(BinaryOp::Div, result, divisor);
+                let mismatch = self.ub_op(BinaryOp::NotEqual, quotient, right.clone());
+                let ordinary = self.ub_op(BinaryOp::EqualEqual, special, Operand::Constant(0));
+                let ordinary = self.ub_op(BinaryOp::BitwiseAnd, ordinary, mismatch);
+                let right_min = self.is_long_min(right);
+                let negated_min = self.ub_op(BinaryOp::BitwiseAnd, minus_one, right_min);
+                self.ub_op(BinaryOp::BitwiseOr, ordinary, negated_min)
+            }
+        }
+    }
+
+    /// Whether `value` is `LONG_MIN`, the only nonzero value equal to its
+    /// own negation. Avoids a 64-bit immediate.
+    fn is_long_min(&mut self, value: &Operand) -> Operand {
+        let negated = self.ub_op(BinaryOp::Sub, Operand::Constant(0), value.clone());
+        let same = self.ub_op(BinaryOp::EqualEqual, negated, value.clone());
+        let nonzero = self.ub_op(BinaryOp::NotEqual, value.clone(), Operand::Constant(0));
+        self.ub_op(BinaryOp::BitwiseAnd, same, nonzero)
+    }
+
+    /// Emit `left op right` as a `long` into the current block.
+    fn ub_op(&mut self, op: BinaryOp, left: Operand, right: Operand) -> Operand {
+        let dest = self.new_var();
+        self.var_types.insert(dest, Type::Long);
+        self.add_instruction(Instruction::Binary { dest, op, left, right });
+        Operand::Var(dest)
+    }
+
+    fn ub_cast_long(&mut self, value: &Operand, ty: &Type) -> Operand {
+        if let Operand::Constant(_) = value {
+            return value.clone();
+        }
+        let dest = self.new_var();
+        self.var_types.insert(dest, Type::Long);
+        self.add_instruction(Instruction::Cast { dest, src: value.clone(), src_type: ty.clone(), r#type: Type::Long });
+        Operand::Var(dest)
+    }
+
+    /// Branch to a block that reports `check` when `cond` is nonzero, and
+    /// continue lowering in a fresh block otherwise.
+    fn ub_check(&mut self, cond: Operand, check: UbCheck) {
+        let Some(bid) = self.current_block else {
+            return;
+        };
+        let fail_id = self.new_block();
+        let cont_id = self.new_block();
+        self.set_terminator(bid, Terminator::cond_br(cond, fail_id, cont_id));
+
+        let label = match self.ubsan_labels.get(&check) {
+            Some(label) => label.clone(),
+            None => {
+                let label = self.string_label(check.message());
+                self.ubsan_labels.insert(check, label.clone());
+                label
+            }
+        };
+        self.sealed_blocks.insert(fail_id);
+        self.push_instruction(fail_id, Instruction::Call {
+            dest: None,
+            name: UBSAN_ABORT.to_string(),
+            args: vec![Operand::Global(label), Operand::Constant(check.message().len() as i64)],
+            vararg_types: Vec::new(),
+        });
+
+        self.sealed_blocks.insert(cont_id);
+        self.current_block = Some(cont_id);
+    }
+
+    /// The `__ubsan_abort` helper, if any check was emitted.
+    pub(crate) fn ubsan_helper(&self) -> Option<Function> {
+        if self.ubsan_labels.is_empty() {
+            return None;
+        }
+        let (msg, len) = (VarId(0), VarId(1));
+        let (label_addrs, labels) = Function::default_meta();
+        Some(Function {
+            name: UBSAN_ABORT.to_string(),
+            return_type: Type::Void,
+            params: vec![(Type::ptr(Type::Char), msg), (Type::Long, len)],
+            blocks: vec![BasicBlock {
+                id: BlockId(0),
+                instructions: vec![
+                    Instruction::Call {
+                        dest: None,
+                        name: "write".to_string(),
+                        args: vec![Operand::Constant(2), Operand::Var(msg), Operand::Var(len)],
+                        vararg_types: Vec::new(),
+                    },
+                    Instruction::Call { dest: None, name: "abort".to_string(), args: Vec::new(), vararg_types: Vec::new() },
+                ],
+                terminator: Terminator::Ret(None),
+                is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
+            }],
+            entry_block: BlockId(0),
+            var_types: [(msg, Type::ptr(Type::Char)), (len, Type::Long)].into_iter().collect(),
+            attributes: vec![model::Attribute::NoReturn],
+            is_static: true,
+            label_addrs,
+            labels,
+            local_names: std::collections::HashMap::new(),
+            span: Span::default(),
+        })
+    }
+}
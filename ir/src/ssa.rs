@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use crate::types::{VarId, BlockId, Instruction, Terminator};
+use crate::types::{VarId, BlockId, Instruction};
 use crate::lowerer::Lowerer;
 
 /// SSA construction trait for the Lowerer
@@ -9,7 +9,10 @@ impl Lowerer {
         if self.sealed_blocks.contains(&block) { 
             return; 
         }
-        let phis = self.incomplete_phis.remove(&block).unwrap_or_default();
+        // Complete the phis in creation order, so the phis this creates are
+        // numbered the same on every run
+        let mut phis: Vec<_> = self.incomplete_phis.remove(&block).unwrap_or_default().into_iter().collect();
+        phis.sort_by_key(|(_, phi_var)| *phi_var);
         for (name, phi_var) in phis {
             self.add_phi_operands(&name, block, phi_var);
         }
@@ -69,71 +72,13 @@ impl Lowerer {
             dest: phi_var,
             preds: phi_preds,
         });
+        self.blocks[block.0].spans.insert(0, self.current_span);
         // Trivial phi elimination could go here
         phi_var
     }
 
-    /// Get all predecessor blocks for a given block (with caching)
-    pub(crate) fn get_predecessors(&mut self, block: BlockId) -> Vec<BlockId> {
-        // Check cache first
-        if self.pred_cache_valid {
-            if let Some(preds) = self.pred_cache.get(&block) {
-                return preds.clone();
-            }
-        }
-        
-        // Compute predecessors
-        let mut preds = Vec::new();
-        for b in &self.blocks {
-            match &b.terminator {
-                Terminator::Br(id) if *id == block => preds.push(b.id),
-                Terminator::CondBr { then_block, else_block, .. } => {
-                    if *then_block == block { preds.push(b.id); }
-                    if *else_block == block { preds.push(b.id); }
-                }
-                _ => {}
-            }
-        }
-        
-        // Update cache if it's valid
-        if self.pred_cache_valid {
-            self.pred_cache.insert(block, preds.clone());
-        }
-        
-        preds
-    }
-    
-    /// Invalidate the predecessor cache (call when CFG changes)
-    #[allow(dead_code)]
-    pub(crate) fn invalidate_pred_cache(&mut self) {
-        self.pred_cache_valid = false;
-        self.pred_cache.clear();
-    }
-    
-    /// Rebuild the predecessor cache for all blocks
-    #[allow(dead_code)]
-    pub(crate) fn rebuild_pred_cache(&mut self) {
-        self.pred_cache.clear();
-        
-        // Initialize empty predecessor lists for all blocks
-        for block in &self.blocks {
-            self.pred_cache.insert(block.id, Vec::new());
-        }
-        
-        // Populate predecessor lists
-        for block in &self.blocks {
-            match &block.terminator {
-                Terminator::Br(target) => {
-                    self.pred_cache.entry(*target).or_default().push(block.id);
-                }
-                Terminator::CondBr { then_block, else_block, .. } => {
-                    self.pred_cache.entry(*then_block).or_default().push(block.id);
-                    self.pred_cache.entry(*else_block).or_default().push(block.id);
-                }
-                _ => {}
-            }
-        }
-        
-        self.pred_cache_valid = true;
+    /// Get all predecessor blocks for a given block
+    pub(crate) fn get_predecessors(&self, block: BlockId) -> Vec<BlockId> {
+        self.cfg.preds(block).to_vec()
     }
 }
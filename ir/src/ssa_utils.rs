@@ -1,5 +1,6 @@
 // SSA utility functions
-// Extracted from mem2reg.rs: verify_ssa, remove_phis
+// Extracted from mem2reg.rs: verify_ssa, remove_phis; and verify_function,
+// the structural check run between optimization passes
 
 use std::collections::{HashMap, HashSet};
 use crate::types::{VarId, BlockId, Instruction, Function, Operand, Terminator};
@@ -49,7 +50,7 @@ pub fn verify_ssa(func: &Function) -> Result<(), String> {
         }
         // Check terminator operands
         match &block.terminator {
-            Terminator::CondBr { cond, .. } => {
+            Terminator::CondBr { cond, .. } | Terminator::Switch { value: cond, .. } => {
                 if let Operand::Var(v) = cond {
                     if !defs.contains(v) {
                         return Err(format!("VarId({}) used but never defined (terminator of block {:?})", v.0, block.id));
@@ -69,6 +70,49 @@ pub fn verify_ssa(func: &Function) -> Result<(), String> {
     Ok(())
 }
 
+/// Check the structure every optimization pass must preserve: block ids are
+/// unique, the entry block and every branch target exist, phis come first
+/// in their block and name existing blocks, and every variable used is
+/// defined (`verify_ssa`). A variable may be defined more than once, as it
+/// is after `remove_phis`.
+pub fn verify_function(func: &Function) -> Result<(), String> {
+    let mut ids: HashSet<BlockId> = HashSet::new();
+    for block in &func.blocks {
+        if !ids.insert(block.id) {
+            return Err(format!("block {:?} appears twice", block.id));
+        }
+    }
+    if !ids.contains(&func.entry_block) {
+        return Err(format!("entry block {:?} does not exist", func.entry_block));
+    }
+    for block in &func.blocks {
+        let targets = match &block.terminator {
+            Terminator::Br(target) => vec![*target],
+            Terminator::CondBr { then_block, else_block, .. } => vec![*then_block, *else_block],
+            Terminator::Switch { cases, default, .. } => Terminator::switch_targets(cases, *default),
+            _ => Vec::new(),
+        };
+        if let Some(target) = targets.iter().find(|t| !ids.contains(t)) {
+            return Err(format!("block {:?} branches to missing block {:?}", block.id, target));
+        }
+        let mut past_phis = false;
+        for instr in &block.instructions {
+            match instr {
+                Instruction::Phi { dest, preds } => {
+                    if past_phis {
+                        return Err(format!("phi for VarId({}) follows other instructions in block {:?}", dest.0, block.id));
+                    }
+                    if let Some((pred, _)) = preds.iter().find(|(pred, _)| !ids.contains(pred)) {
+                        return Err(format!("phi for VarId({}) in block {:?} names missing block {:?}", dest.0, block.id, pred));
+                    }
+                }
+                _ => past_phis = true,
+            }
+        }
+    }
+    verify_ssa(func)
+}
+
 /// Remove Phi nodes by inserting Copy instructions in predecessor blocks
 pub fn remove_phis(func: &mut Function) {
     let mut insertions: HashMap<BlockId, Vec<Instruction>> = HashMap::new();
@@ -93,10 +137,19 @@ pub fn remove_phis(func: &mut Function) {
             continue;
         }
         
+        // The copies stand in for the jump, so they take its span
+        block.spans.resize(block.instructions.len(), block.terminator_span);
         if let Some(copies) = insertions.remove(&block.id) {
+            block.spans.extend(copies.iter().map(|_| block.terminator_span));
             block.instructions.extend(copies);
         }
         // Remove Phis
-        block.instructions.retain(|i| !matches!(i, Instruction::Phi{..}));
+        let (instructions, spans) = std::mem::take(&mut block.instructions)
+            .into_iter()
+            .zip(std::mem::take(&mut block.spans))
+            .filter(|(i, _)| !matches!(i, Instruction::Phi { .. }))
+            .unzip();
+        block.instructions = instructions;
+        block.spans = spans;
     }
 }
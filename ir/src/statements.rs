@@ -1,20 +1,39 @@
 use std::collections::HashMap;
-use model::{Type, Stmt as AstStmt, Block as AstBlock, Expr as AstExpr, BinaryOp};
-use crate::types::{VarId, Operand, Instruction, Terminator};
-use crate::lowerer::Lowerer;
+use model::{CompileError, ErrorCode, Type, Stmt as AstStmt, Block as AstBlock, Expr as AstExpr, AsmOperand};
+use crate::types::{VarId, BlockId, Operand, Instruction, Terminator};
+use crate::lowerer::{Lowerer, SwitchContext};
+use crate::init_list::string_init_units;
 
 /// Statement lowering implementation
 impl Lowerer {
     /// Lower an AST block to IR
-    pub(crate) fn lower_block(&mut self, block: &AstBlock) -> Result<(), String> {
-        for stmt in &block.statements {
+    pub(crate) fn lower_block(&mut self, block: &AstBlock) -> Result<(), CompileError> {
+        // Code emitted after the block (a loop's step, say) belongs to the
+        // enclosing statement again
+        let outer_span = self.current_span;
+        for (i, stmt) in block.statements.iter().enumerate() {
+            if let Some(&span) = block.spans.get(i) {
+                self.current_span = span;
+            }
             self.lower_stmt(stmt)?;
         }
+        self.current_span = outer_span;
         Ok(())
     }
 
+    /// Lower a loop body with `continue` and `break` bound to the given
+    /// blocks.
+    fn lower_loop_body(&mut self, body: &AstStmt, continue_target: BlockId, break_target: BlockId) -> Result<(), CompileError> {
+        self.cf.continue_targets.push(continue_target);
+        self.cf.break_targets.push(break_target);
+        let lowered = self.lower_stmt(body);
+        self.cf.break_targets.pop();
+        self.cf.continue_targets.pop();
+        lowered
+    }
+
     /// Lower an AST statement to IR
-    pub(crate) fn lower_stmt(&mut self, stmt: &AstStmt) -> Result<(), String> {
+    pub(crate) fn lower_stmt(&mut self, stmt: &AstStmt) -> Result<(), CompileError> {
         // If we don't have a current block, create an unreachable one for dead code
         // This happens after goto, return, break, continue, etc.
         if self.current_block.is_none() && !matches!(stmt, AstStmt::Label(_) | AstStmt::Case(_) | AstStmt::Default) {
@@ -27,107 +46,45 @@ impl Lowerer {
         match stmt {
             AstStmt::Return(expr) => {
                 let val = if let Some(e) = expr {
-                    let mut v = self.lower_expr(e)?;
-                    
-                    // Handle implicit cast for return value
-                    // Clone return type to avoid borrowing self while mutating self
+                    let mut v = self.lower_value(e)?;
+                    // The value is converted to the return type as if by assignment
                     if let Some(ret_type) = self.current_return_type.clone() {
-                        let expr_type = self.get_operand_type(&v)?;
-                        
-                        let src_is_float = matches!(expr_type, Type::Float | Type::Double);
-                        let dest_is_float = matches!(ret_type, Type::Float | Type::Double);
-                        
-                        if src_is_float != dest_is_float {
-                             let dest = self.new_var();
-                             self.var_types.insert(dest, ret_type.clone());
-                             let bid = self.current_block.ok_or("Return cast outside block")?;
-                             
-                             self.blocks[bid.0].instructions.push(Instruction::Cast {
-                                 dest,
-                                 src: v,
-                                 r#type: ret_type.clone(),
-                             });
-                             v = Operand::Var(dest);
-                        }
+                        let expr_type = self.get_expr_type(e);
+                        v = self.convert_arithmetic(v, &expr_type, &ret_type);
                     }
                     Some(v)
                 } else {
                     None
                 };
-                let bid = self.current_block.ok_or("Return outside of block")?;
-                self.blocks[bid.0].terminator = Terminator::Ret(val);
+                let bid = self.require_block("Return outside of block")?;
+                self.set_terminator(bid, Terminator::Ret(val));
                 self.current_block = None; // Dead code after return
             }
-            AstStmt::Declaration { r#type, qualifiers: _, name, init } => {
+            AstStmt::Declaration { r#type, name, init, .. } => {
                 // Resolve typeof expressions to concrete types
                 let r#type = &self.resolve_type(r#type);
                 self.symbol_table.insert(name.clone(), r#type.clone());
-                let bid = self.current_block.ok_or("Declaration outside of block")?;
+                let bid = self.require_block("Declaration outside of block")?;
                 
                 if matches!(r#type, Type::Array(..)) {
-                    let var = self.new_var();
-                    self.blocks[bid.0].instructions.push(Instruction::Alloca {
-                        dest: var,
-                        r#type: r#type.clone(),
-                    });
+                    let var = self.alloca_stack_array(r#type, bid);
                     self.write_variable(name, bid, var);
                     self.variable_allocas.insert(name.clone(), var);
+                    self.local_names.insert(var, name.clone());
                     
                     // Handle array initialization (e.g., char arr[] = "string")
                     if let Some(init_expr) = init {
                         match init_expr {
-                            AstExpr::StringLiteral(s) => {
-                                // Initialize each character in the array
-                                for (i, ch) in s.chars().chain(std::iter::once('\0')).enumerate() {
-                                    // Calculate offset: base + i * element_size
-                                    let elem_type = if let Type::Array(inner, _) = r#type {
-                                        inner.as_ref().clone()
-                                    } else {
-                                        unreachable!()
-                                    };
-                                    
-                                    let offset_var = if i == 0 {
-                                        var
-                                    } else {
-                                        let idx_var = self.new_var();
-                                        self.blocks[bid.0].instructions.push(Instruction::Binary {
-                                            dest: idx_var,
-                                            op: BinaryOp::Add,
-                                            left: Operand::Var(var),
-                                            right: Operand::Constant(i as i64),
-                                        });
-                                        idx_var
-                                    };
-                                    
-                                    self.blocks[bid.0].instructions.push(Instruction::Store {
-                                        addr: Operand::Var(offset_var),
-                                        src: Operand::Constant(ch as i64),
-                                        value_type: elem_type.clone(),
-                                        volatile: false,
-                                    });
-                                }
+                            AstExpr::StringLiteral(_) | AstExpr::WideStringLiteral(..) => {
+                                let units = string_init_units(init_expr, r#type).unwrap_or_default();
+                                self.lower_string_init(var, r#type, units, false);
                             }
                             AstExpr::InitList(items) => {
-                                let elem_type = if let Type::Array(inner, _) = r#type {
-                                    inner.as_ref().clone()
-                                } else {
-                                    unreachable!()
-                                };
-                                let elem_size = self.get_type_size(&elem_type);
-                                self.lower_init_list_to_stores(var, items, &elem_type, elem_size, bid)?;
+                                self.lower_aggregate_init(var, r#type, items)?;
                             }
-                            AstExpr::CompoundLiteral { init, r#type: cl_type } => {
+                            AstExpr::CompoundLiteral { init, .. } => {
                                 // Compound literal used as array initializer
-                                let elem_type = match cl_type {
-                                    Type::Array(inner, _) => inner.as_ref().clone(),
-                                    _ => if let Type::Array(inner, _) = r#type {
-                                        inner.as_ref().clone()
-                                    } else {
-                                        unreachable!()
-                                    },
-                                };
-                                let elem_size = self.get_type_size(&elem_type);
-                                self.lower_init_list_to_stores(var, init, &elem_type, elem_size, bid)?;
+                                self.lower_aggregate_init(var, r#type, init)?;
                             }
                             _ => {
                                 // Other init expressions for arrays not supported
@@ -137,63 +94,50 @@ impl Lowerer {
                 } else if matches!(r#type, Type::Struct(..) | Type::Union(..)) {
                     // Struct/Union declaration
                     let alloca_var = self.new_var();
-                    self.blocks[bid.0].instructions.push(Instruction::Alloca {
+                    self.push_instruction(bid, Instruction::Alloca {
                         dest: alloca_var,
                         r#type: r#type.clone(),
                     });
                     self.write_variable(name, bid, alloca_var);
                     self.variable_allocas.insert(name.clone(), alloca_var);
+                    self.local_names.insert(alloca_var, name.clone());
 
                     if let Some(init_expr) = init {
                         match init_expr {
                             AstExpr::InitList(items) => {
-                                self.lower_struct_init_list(alloca_var, r#type, items, bid)?;
+                                self.lower_aggregate_init(alloca_var, r#type, items)?;
                             }
                             AstExpr::CompoundLiteral { init, .. } => {
                                 // Compound literal with struct type: treat as init list
-                                self.lower_struct_init_list(alloca_var, r#type, init, bid)?;
+                                self.lower_aggregate_init(alloca_var, r#type, init)?;
                             }
                             _ => {
-                                // Scalar init for struct (e.g., copy from another struct)
-                                let val = self.lower_expr(init_expr)?;
-                                // Re-read current_block after lowering (ternary may change it)
-                                let cur_bid = self.current_block.ok_or("Declaration init outside of block")?;
-                                self.blocks[cur_bid.0].instructions.push(Instruction::Store {
-                                    addr: Operand::Var(alloca_var),
-                                    src: val.clone(),
-                                    value_type: r#type.clone(),
-                                    volatile: false,
-                                });
-                                let var = match val {
-                                    Operand::Var(v) => v,
-                                    _ => {
-                                        let v = self.new_var();
-                                        self.blocks[cur_bid.0].instructions.push(Instruction::Copy {
-                                            dest: v,
-                                            src: val,
-                                        });
-                                        v
-                                    }
-                                };
-                                self.write_variable(name, cur_bid, var);
+                                // Copy from another struct or union value
+                                self.lower_aggregate_copy(Operand::Var(alloca_var), init_expr, r#type)?;
                             }
                         }
                     }
                 } else {
                     // Alloca for all scalars too to support & operator
                     let alloca_var = self.new_var();
-                    self.blocks[bid.0].instructions.push(Instruction::Alloca {
+                    self.push_instruction(bid, Instruction::Alloca {
                         dest: alloca_var,
                         r#type: r#type.clone(),
                     });
                     self.variable_allocas.insert(name.clone(), alloca_var);
+                    self.local_names.insert(alloca_var, name.clone());
 
-                    if let Some(e) = init {
+                    if let (Some(e), Type::Complex(elem)) = (init, r#type) {
+                        let parts = self.lower_complex_as(e, elem)?;
+                        self.store_complex(alloca_var, elem, parts, false);
+                    } else if let Some(e) = init {
                         let val = self.lower_expr(e)?;
+                        let init_type = self.get_expr_type(e);
+                        let val = self.convert_arithmetic(val, &init_type, r#type);
                         // Re-read current_block AFTER lowering init expr, since ternary
                         // expressions create new blocks and change current_block.
-                        let cur_bid = self.current_block.ok_or("Declaration init outside of block")?;
-                        self.blocks[cur_bid.0].instructions.push(Instruction::Store {
+                        let cur_bid = self.require_block("Declaration init outside of block")?;
+                        self.push_instruction(cur_bid, Instruction::Store {
                             addr: Operand::Var(alloca_var),
                             src: val.clone(),
                             value_type: r#type.clone(),
@@ -204,10 +148,11 @@ impl Lowerer {
                             Operand::Var(v) => v,
                             Operand::Constant(_) | Operand::FloatConstant(_) | Operand::Global(_) => {
                                 let v = self.new_var();
-                                self.blocks[cur_bid.0].instructions.push(Instruction::Copy {
+                                self.push_instruction(cur_bid, Instruction::Copy {
                                     dest: v,
                                     src: val,
                                 });
+                                self.var_types.insert(v, r#type.clone());
                                 v
                             }
                         };
@@ -259,25 +204,25 @@ impl Lowerer {
                 }
             }            AstStmt::If { cond, then_branch, else_branch } => {
                 let (cond_val, hint) = self.lower_branch_condition(cond)?;
-                let bid = self.current_block.ok_or("If outside of block")?;
+                let bid = self.require_block("If outside of block")?;
 
                 let then_id = self.new_block();
                 let else_id = self.new_block();
                 let merge_id = self.new_block();
 
-                self.blocks[bid.0].terminator = Terminator::cond_br_hint(
+                self.set_terminator(bid, Terminator::cond_br_hint(
                     cond_val,
                     then_id,
                     else_id,
                     hint,
-                );
+                ));
 
                 // Lower Then
                 self.sealed_blocks.insert(then_id);
                 self.current_block = Some(then_id);
                 self.lower_stmt(then_branch)?;
                 if let Some(curr) = self.current_block {
-                    self.blocks[curr.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(curr, Terminator::Br(merge_id));
                 }
                 let then_end = self.current_block;
 
@@ -288,7 +233,7 @@ impl Lowerer {
                     self.lower_stmt(eb)?;
                 }
                 if let Some(curr) = self.current_block {
-                    self.blocks[curr.0].terminator = Terminator::Br(merge_id);
+                    self.set_terminator(curr, Terminator::Br(merge_id));
                 }
                 let else_end = self.current_block;
 
@@ -306,29 +251,27 @@ impl Lowerer {
                 let body_id = self.new_block();
                 let exit_id = self.new_block();
 
-                let bid = self.current_block.ok_or("While outside of block")?;
-                self.blocks[bid.0].terminator = Terminator::Br(header_id);
+                let bid = self.require_block("While outside of block")?;
+                self.set_terminator(bid, Terminator::Br(header_id));
 
                 self.current_block = Some(header_id);
                 let (cond_val, hint) = self.lower_branch_condition(cond)?;
                 // Use current_block (not header_id) because LogicalAnd/Or
                 // may have created short-circuit blocks, moving current_block
                 // to a merge block.
-                let cond_block = self.current_block.ok_or("While cond ended outside block")?;
-                self.blocks[cond_block.0].terminator = Terminator::cond_br_hint(
+                let cond_block = self.require_block("While cond ended outside block")?;
+                self.set_terminator(cond_block, Terminator::cond_br_hint(
                     cond_val,
                     body_id,
                     exit_id,
                     hint,
-                );
+                ));
 
                 self.sealed_blocks.insert(body_id);
                 self.current_block = Some(body_id);
-                self.cf.loop_context.push((header_id, exit_id));
-                self.lower_stmt(body)?;
-                self.cf.loop_context.pop();
+                self.lower_loop_body(body, header_id, exit_id)?;
                 if let Some(curr) = self.current_block {
-                    self.blocks[curr.0].terminator = Terminator::Br(header_id);
+                    self.set_terminator(curr, Terminator::Br(header_id));
                 }
 
                 self.seal_block(header_id);
@@ -341,15 +284,13 @@ impl Lowerer {
                 let latch_id = self.new_block();
                 let exit_id = self.new_block();
 
-                let bid = self.current_block.ok_or("Do-while outside of block")?;
-                self.blocks[bid.0].terminator = Terminator::Br(body_id);
+                let bid = self.require_block("Do-while outside of block")?;
+                self.set_terminator(bid, Terminator::Br(body_id));
 
                 self.current_block = Some(body_id);
-                self.cf.loop_context.push((latch_id, exit_id));
-                self.lower_stmt(body)?;
-                self.cf.loop_context.pop();
+                self.lower_loop_body(body, latch_id, exit_id)?;
                 if let Some(curr) = self.current_block {
-                    self.blocks[curr.0].terminator = Terminator::Br(latch_id);
+                    self.set_terminator(curr, Terminator::Br(latch_id));
                 }
 
                 self.sealed_blocks.insert(latch_id);
@@ -357,13 +298,13 @@ impl Lowerer {
                 let (cond_val, hint) = self.lower_branch_condition(cond)?;
                 // Use current_block (not latch_id) because LogicalAnd/Or
                 // may have created short-circuit blocks.
-                let cond_block = self.current_block.ok_or("DoWhile cond ended outside block")?;
-                self.blocks[cond_block.0].terminator = Terminator::cond_br_hint(
+                let cond_block = self.require_block("DoWhile cond ended outside block")?;
+                self.set_terminator(cond_block, Terminator::cond_br_hint(
                     cond_val,
                     body_id,
                     exit_id,
                     hint,
-                );
+                ));
 
                 self.seal_block(body_id);
                 self.seal_block(exit_id);
@@ -382,7 +323,7 @@ impl Lowerer {
 
                 // Branch from current block (after init) to header
                 if let Some(bid) = self.current_block {
-                    self.blocks[bid.0].terminator = Terminator::Br(header_id);
+                    self.set_terminator(bid, Terminator::Br(header_id));
                 }
 
                 self.current_block = Some(header_id);
@@ -390,24 +331,22 @@ impl Lowerer {
                     let (cond_val, hint) = self.lower_branch_condition(c)?;
                     // Use current_block (not header_id) because LogicalAnd/Or
                     // may have created short-circuit blocks.
-                    let cond_block = self.current_block.ok_or("For cond ended outside block")?;
-                    self.blocks[cond_block.0].terminator = Terminator::cond_br_hint(
+                    let cond_block = self.require_block("For cond ended outside block")?;
+                    self.set_terminator(cond_block, Terminator::cond_br_hint(
                         cond_val,
                         body_id,
                         exit_id,
                         hint,
-                    );
+                    ));
                 } else {
-                    self.blocks[header_id.0].terminator = Terminator::Br(body_id);
+                    self.set_terminator(header_id, Terminator::Br(body_id));
                 }
 
                 self.sealed_blocks.insert(body_id);
                 self.current_block = Some(body_id);
-                self.cf.loop_context.push((post_id, exit_id));
-                self.lower_stmt(body)?;
-                self.cf.loop_context.pop();
+                self.lower_loop_body(body, post_id, exit_id)?;
                 if let Some(curr) = self.current_block {
-                    self.blocks[curr.0].terminator = Terminator::Br(post_id);
+                    self.set_terminator(curr, Terminator::Br(post_id));
                 }
 
                 self.sealed_blocks.insert(post_id);
@@ -415,7 +354,7 @@ impl Lowerer {
                 if let Some(p) = post {
                     self.lower_expr(p)?;
                 }
-                self.blocks[post_id.0].terminator = Terminator::Br(header_id);
+                self.set_terminator(post_id, Terminator::Br(header_id));
 
                 self.seal_block(header_id);
                 self.seal_block(exit_id);
@@ -424,125 +363,98 @@ impl Lowerer {
             }
 
             AstStmt::Continue => {
-                if let Some((continue_target, _)) = self.cf.loop_context.last() {
-                    let bid = self.current_block.ok_or("Continue outside of block")?;
-                    self.blocks[bid.0].terminator = Terminator::Br(*continue_target);
-                    self.current_block = None;
-                } else {
-                    return Err("Continue outside of loop".to_string());
-                }
+                let Some(&continue_target) = self.cf.continue_targets.last() else {
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "Continue outside of loop"));
+                };
+                let bid = self.require_block("Continue outside of block")?;
+                self.set_terminator(bid, Terminator::Br(continue_target));
+                self.current_block = None;
             }
             AstStmt::Break => {
-                if let Some((_, break_target)) = self.cf.loop_context.last() {
-                     let bid = self.current_block.ok_or("Break outside of block")?;
-                     self.blocks[bid.0].terminator = Terminator::Br(*break_target);
-                     self.current_block = None;
-                } else if let Some(break_target) = self.cf.break_targets.last() {
-                     let bid = self.current_block.ok_or("Break outside of block")?;
-                     self.blocks[bid.0].terminator = Terminator::Br(*break_target);
-                     self.current_block = None;
-                } else {
-                    return Err("Break not in loop or switch".to_string());
-                }
+                // The innermost loop or switch, whichever is closer
+                let Some(&break_target) = self.cf.break_targets.last() else {
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "Break not in loop or switch"));
+                };
+                let bid = self.require_block("Break outside of block")?;
+                self.set_terminator(bid, Terminator::Br(break_target));
+                self.current_block = None;
             }
             AstStmt::Switch { cond, body } => {
+                // The body is lowered first, in source order, so each case
+                // falls through to the next along an ordinary edge. The
+                // `Switch` that dispatches on the value ends `head`, built
+                // afterwards, and only then are the case blocks sealed, once
+                // all their predecessors are known.
+                let cond_type = self.resolve_type(&self.get_expr_type(cond));
+                let value_type = model::TypeEnv::integer_promotion(&cond_type);
                 let cond_val = self.lower_expr(cond)?;
+                let cond_val = self.convert_arithmetic(cond_val, &cond_type, &value_type);
                 let head = self.new_block();
                 let end = self.new_block();
-                
-                let bid = self.current_block.ok_or("Switch outside block")?;
-                self.blocks[bid.0].terminator = Terminator::Br(head);
+
+                let bid = self.require_block("Switch outside block")?;
+                self.set_terminator(bid, Terminator::Br(head));
                 self.seal_block(head);
-                
-                self.cf.break_targets.push(end);
-                let old_cases = std::mem::take(&mut self.cf.current_switch_cases);
-                let old_default = self.cf.current_default.take();
-                
-                // Lower body - this will register cases in self.current_switch_cases
-                self.current_block = Some(self.new_block()); // Start of body
-                let body_start = self.current_block.unwrap();
+
+                // Statements before the first label are unreachable.
+                let body_start = self.new_block();
                 self.seal_block(body_start);
-                self.lower_stmt(body)?;
-                
-                let cases = std::mem::take(&mut self.cf.current_switch_cases);
-                let default = self.cf.current_default.take();
+                self.current_block = Some(body_start);
+                self.cf.break_targets.push(end);
+                self.cf.switches.push(SwitchContext { value_type, cases: Vec::new(), default: None });
+                let lowered = self.lower_stmt(body);
+                let switch = self.cf.switches.pop().expect("switch context pushed above");
                 self.cf.break_targets.pop();
+                lowered?;
 
-                // Finish the body if it's still open
+                // The last case falls out of the switch
                 if let Some(bid) = self.current_block {
-                    self.blocks[bid.0].terminator = Terminator::Br(end);
+                    self.set_terminator(bid, Terminator::Br(end));
                 }
 
-                // Now fill the head with comparisons
-                self.current_block = Some(head);
-                let mut current_head = head;
-                for (val, block) in cases {
-                    let next_head = self.new_block();
-                    let cond_var = self.new_var();
-                    self.add_instruction(Instruction::Binary {
-                        dest: cond_var,
-                        op: model::BinaryOp::EqualEqual,
-                        left: cond_val.clone(),
-                        right: Operand::Constant(val),
-                    });
-                    self.blocks[current_head.0].terminator = Terminator::cond_br(
-                        Operand::Var(cond_var),
-                        block,
-                        next_head,
-                    );
-                    self.seal_block(next_head);
-                    current_head = next_head;
-                    self.current_block = Some(next_head);
+                self.set_terminator(head, Terminator::Switch {
+                    value: cond_val,
+                    cases: switch.cases.clone(),
+                    default: switch.default.unwrap_or(end),
+                });
+
+                for block in switch.cases.iter().map(|&(_, block)| block).chain(switch.default) {
+                    self.seal_block(block);
                 }
-                
-                let default_target = default.unwrap_or(end);
-                self.blocks[current_head.0].terminator = Terminator::Br(default_target);
-                
-                self.current_block = Some(end);
                 self.seal_block(end);
-                
-                // Restore old context
-                self.cf.current_switch_cases = old_cases;
-                self.cf.current_default = old_default;
+                self.current_block = Some(end);
             }
             AstStmt::Case(expr) => {
-                // Resolve the case value: must be a compile-time constant
-                let val = match expr {
-                    AstExpr::Constant(v) => *v,
-                    AstExpr::Variable(name) => {
-                        // Check if it's an enum constant
-                        if let Some(&v) = self.enum_constants.get(name) {
-                            v
-                        } else {
-                            return Err(format!("Case label '{}' is not a constant", name));
-                        }
-                    }
-                    AstExpr::Unary { op: model::UnaryOp::Minus, expr } => {
-                        if let AstExpr::Constant(v) = expr.as_ref() {
-                            -v
-                        } else {
-                            return Err("Case label must be a constant".to_string());
-                        }
-                    }
-                    _ => return Err("Case label must be a constant".to_string()),
+                // The value is converted to the promoted type of the
+                // controlling expression (C11 6.8.4.2p5), so `case -1` in a
+                // switch on an `unsigned` matches 0xffffffff.
+                let Some(value_type) = self.cf.switches.last().map(|s| s.value_type.clone()) else {
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "'case' label not within a switch statement"));
                 };
+                let converted = AstExpr::Cast(value_type, Box::new(expr.clone()));
+                let val = model::consteval::eval_integer_constant(&converted, self)?;
                 let case_block = self.new_block();
                 if let Some(bid) = self.current_block {
-                    self.blocks[bid.0].terminator = Terminator::Br(case_block);
+                    self.set_terminator(bid, Terminator::Br(case_block));
+                }
+                if let Some(switch) = self.cf.switches.last_mut() {
+                    switch.cases.push((val, case_block));
                 }
-                self.cf.current_switch_cases.push((val, case_block));
-                self.seal_block(case_block);
                 self.current_block = Some(case_block);
             }
             AstStmt::Default => {
                 let default_block = self.new_block();
+                match self.cf.switches.last_mut() {
+                    Some(switch) => switch.default = Some(default_block),
+                    None => return Err(CompileError::new(ErrorCode::MisplacedStatement, "'default' label not within a switch statement")),
+                }
                 if let Some(bid) = self.current_block {
-                    self.blocks[bid.0].terminator = Terminator::Br(default_block);
+                    self.set_terminator(bid, Terminator::Br(default_block));
                 }
-                self.cf.current_default = Some(default_block);
-                self.seal_block(default_block);
                 self.current_block = Some(default_block);
             }
+            // Checked by semantic analysis
+            AstStmt::StaticAssert(_) => {}
             AstStmt::Label(name) => {
                 // Create a new block for the label
                 let label_block = self.new_block();
@@ -554,7 +466,7 @@ impl Lowerer {
                 if let Some(bid) = self.current_block {
                     // Only add branch if the current block doesn't already have a terminator
                     if matches!(self.blocks[bid.0].terminator, Terminator::Unreachable) {
-                        self.blocks[bid.0].terminator = Terminator::Br(label_block);
+                        self.set_terminator(bid, Terminator::Br(label_block));
                     }
                 }
                 
@@ -568,7 +480,7 @@ impl Lowerer {
                 while i < self.cf.pending_gotos.len() {
                     if self.cf.pending_gotos[i].0 == *name {
                         let goto_block = self.cf.pending_gotos[i].1;
-                        self.blocks[goto_block.0].terminator = Terminator::Br(label_block);
+                        self.set_terminator(goto_block, Terminator::Br(label_block));
                         self.cf.pending_gotos.remove(i);
                     } else {
                         i += 1;
@@ -576,28 +488,32 @@ impl Lowerer {
                 }
             }
             AstStmt::Goto(label) => {
-                let bid = self.current_block.ok_or("Goto outside of block")?;
+                let bid = self.require_block("Goto outside of block")?;
                 
                 // Check if label already exists (backward goto)
                 if let Some(&label_block) = self.cf.labels.get(label) {
-                    self.blocks[bid.0].terminator = Terminator::Br(label_block);
+                    self.set_terminator(bid, Terminator::Br(label_block));
                 } else {
                     // Forward goto - store for later resolution
                     self.cf.pending_gotos.push((label.clone(), bid));
                     // Temporary terminator, will be fixed when label is found
-                    self.blocks[bid.0].terminator = Terminator::Unreachable;
+                    self.set_terminator(bid, Terminator::Unreachable);
                 }
                 self.current_block = None;  // Dead code after goto
             }
             AstStmt::ComputedGoto(expr) => {
-                let bid = self.current_block.ok_or("Computed goto outside of block")?;
+                let bid = self.require_block("Computed goto outside of block")?;
                 let target = self.lower_expr(expr)?;
-                self.blocks[bid.0].terminator = Terminator::IndirectBr { target };
+                self.set_terminator(bid, Terminator::IndirectBr { target });
                 self.current_block = None;
             }
-            AstStmt::InlineAsm { template, outputs, inputs, clobbers, is_volatile } => {
+            AstStmt::InlineAsm { template, outputs, inputs, clobbers, is_volatile, goto_labels } => {
                 // Lower inline assembly to IR
-                let bid = self.current_block.ok_or("Inline assembly outside of block")?;
+                let bid = self.require_block("Inline assembly outside of block")?;
+                if !goto_labels.is_empty() {
+                    return Err(CompileError::new(ErrorCode::Unsupported, "asm goto is not supported"));
+                }
+                let template = resolve_asm_operand_names(template, outputs, inputs)?;
                 
                 // Map output expressions to VarIds
                 let mut output_vars = Vec::new();
@@ -607,10 +523,10 @@ impl Lowerer {
                         if let Some(&alloca_var) = self.variable_allocas.get(name) {
                             output_vars.push(alloca_var);
                         } else {
-                            return Err(format!("Output variable {} not found for inline asm", name));
+                            return Err(CompileError::new(ErrorCode::Undeclared, format!("Output variable {} not found for inline asm", name)));
                         }
                     } else {
-                        return Err("Inline assembly output must be a variable".to_string());
+                        return Err(CompileError::new(ErrorCode::NotAnLvalue, "Inline assembly output must be a variable"));
                     }
                 }
                 
@@ -620,8 +536,8 @@ impl Lowerer {
                     input_ops.push(self.lower_expr(&input.expr)?);
                 }
                 
-                self.blocks[bid.0].instructions.push(Instruction::InlineAsm {
-                    template: template.clone(),
+                self.push_instruction(bid, Instruction::InlineAsm {
+                    template,
                     outputs: output_vars,
                     output_constraints: outputs.iter().map(|o| o.constraint.clone()).collect(),
                     input_constraints: inputs.iter().map(|i| i.constraint.clone()).collect(),
@@ -634,3 +550,37 @@ impl Lowerer {
         Ok(())
     }
 }
+
+/// Rewrite the symbolic operand references `%[name]` of an asm template to
+/// their positional form `%N`, counting outputs first, then inputs.
+fn resolve_asm_operand_names(template: &str, outputs: &[AsmOperand], inputs: &[AsmOperand]) -> Result<String, CompileError> {
+    let mut resolved = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(percent) = rest.find('%') {
+        resolved.push_str(&rest[..percent]);
+        rest = &rest[percent + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            resolved.push_str("%%");
+            rest = after;
+            continue;
+        }
+        resolved.push('%');
+        // An operand modifier letter may precede the name: `%k[name]`
+        let modifier = rest.chars().next().filter(|c| c.is_ascii_alphabetic() && rest[1..].starts_with('['));
+        let name_start = modifier.map_or(0, |c| c.len_utf8());
+        let Some(close) = rest[name_start..].strip_prefix('[').and_then(|r| r.find(']')) else { continue };
+        let name = &rest[name_start + 1..name_start + 1 + close];
+        let index = outputs
+            .iter()
+            .chain(inputs)
+            .position(|op| op.name.as_deref() == Some(name))
+            .ok_or_else(|| CompileError::new(ErrorCode::InvalidOperand, format!("undefined named operand '{}' in asm", name)))?;
+        if let Some(c) = modifier {
+            resolved.push(c);
+        }
+        resolved.push_str(&index.to_string());
+        rest = &rest[name_start + close + 2..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
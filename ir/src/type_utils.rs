@@ -2,32 +2,139 @@
 // Delegates core layout computation to model::TypeLayout, keeping a name-based
 // cache for complex types (struct/union/typedef) whose size is queried repeatedly.
 
-use model::{Type, TypeLayout};
+use model::{ConstContext, Expr as AstExpr, Type, TypeLayout};
 use crate::lowerer::Lowerer;
+use crate::types::Function;
+
+/// Constant expressions in function bodies (`case` labels) see the whole
+/// program's enums and layouts.
+impl ConstContext for Lowerer {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.enum_constants.get(name).copied()
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.type_layout().size_of(ty))
+    }
+
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        Some(self.get_alignment(ty) as usize)
+    }
+
+    fn resolve_type(&self, ty: &Type) -> Type {
+        Lowerer::resolve_type(self, ty)
+    }
+
+    fn type_of(&self, expr: &AstExpr) -> Option<Type> {
+        Some(model::TypeEnv::string_literal_type(expr).unwrap_or_else(|| self.get_expr_type(expr)))
+    }
+}
+
+/// How a scalar value is represented, for deciding whether a conversion
+/// changes it. A pointer is a 64-bit integer that no signedness applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ScalarKind {
+    Int { size: i64, signed: bool },
+    Pointer,
+    Float { double: bool },
+}
+
+impl ScalarKind {
+    /// Whether a value of this kind is already a value of `other`. Integers
+    /// keep all 64 bits when converted to a pointer, so the result of a call
+    /// to an undeclared function (implicitly `int`) is still a whole address.
+    pub(crate) fn same_representation(self, other: ScalarKind) -> bool {
+        match (self, other) {
+            (ScalarKind::Pointer, ScalarKind::Int { size: 8, .. }) | (ScalarKind::Int { .. }, ScalarKind::Pointer) => true,
+            _ => self == other,
+        }
+    }
+
+    /// An integer constant converted to this integer kind.
+    pub(crate) fn wrap(self, value: i64) -> i64 {
+        match self {
+            ScalarKind::Int { size: 1, signed: true } => i64::from(value as i8),
+            ScalarKind::Int { size: 1, signed: false } => i64::from(value as u8),
+            ScalarKind::Int { size: 2, signed: true } => i64::from(value as i16),
+            ScalarKind::Int { size: 2, signed: false } => i64::from(value as u16),
+            ScalarKind::Int { size: 4, signed: true } => i64::from(value as i32),
+            ScalarKind::Int { size: 4, signed: false } => i64::from(value as u32),
+            _ => value,
+        }
+    }
+}
 
 impl Lowerer {
+    /// `ty` with typedefs and `typeof` resolved and arrays decayed, as the
+    /// type of a scalar value.
+    pub(crate) fn scalar_type(&self, ty: &Type) -> Type {
+        match self.resolve_type(ty) {
+            Type::Typedef(name) => match self.typedefs.get(&name) {
+                Some(aliased) => self.scalar_type(&aliased.clone()),
+                None => Type::Typedef(name),
+            },
+            ty => model::TypeEnv::decay_array(&ty),
+        }
+    }
+
+    /// `ty` with every typedef in it replaced by its definition, for the
+    /// program handed to later stages, which know no typedefs.
+    pub(crate) fn expand_typedefs(&self, ty: &Type) -> Type {
+        match self.resolve_type(ty) {
+            Type::Typedef(name) => match self.typedefs.get(&name) {
+                Some(aliased) => self.expand_typedefs(aliased),
+                None => Type::Typedef(name),
+            },
+            Type::Pointer(inner, q) => Type::Pointer(Box::new(self.expand_typedefs(&inner)), q),
+            Type::Array(inner, size) => Type::Array(Box::new(self.expand_typedefs(&inner)), size),
+            Type::Complex(inner) => Type::Complex(Box::new(self.expand_typedefs(&inner))),
+            Type::FunctionPointer { return_type, param_types, is_variadic } => Type::FunctionPointer {
+                return_type: Box::new(self.expand_typedefs(&return_type)),
+                param_types: param_types.iter().map(|ty| self.expand_typedefs(ty)).collect(),
+                is_variadic,
+            },
+            ty => ty,
+        }
+    }
+
+    /// Expand the typedefs in every type `func` mentions.
+    pub(crate) fn expand_function_typedefs(&self, func: &mut Function) {
+        func.return_type = self.expand_typedefs(&func.return_type);
+        for (ty, _) in &mut func.params {
+            *ty = self.expand_typedefs(ty);
+        }
+        for ty in func.var_types.values_mut() {
+            *ty = self.expand_typedefs(ty);
+        }
+        for inst in func.blocks.iter_mut().flat_map(|block| &mut block.instructions) {
+            inst.for_each_type_mut(|ty| *ty = self.expand_typedefs(ty));
+        }
+    }
+
+    /// The representation of a value of `ty`, or `None` if it is not a scalar.
+    pub(crate) fn scalar_kind(&mut self, ty: &Type) -> Option<ScalarKind> {
+        let ty = self.scalar_type(ty);
+        match ty {
+            Type::Float => Some(ScalarKind::Float { double: false }),
+            Type::Double => Some(ScalarKind::Float { double: true }),
+            Type::Pointer(..) | Type::FunctionPointer { .. } => Some(ScalarKind::Pointer),
+            _ if model::TypeEnv::is_integer_type(&ty) => Some(ScalarKind::Int {
+                size: self.get_type_size(&ty),
+                signed: !model::TypeEnv::is_unsigned_integer(&ty),
+            }),
+            _ => None,
+        }
+    }
+
     /// Build a TypeLayout that borrows from the lowerer's definition maps.
     fn type_layout(&self) -> TypeLayout<'_> {
         TypeLayout::with_typedefs(&self.struct_defs, &self.union_defs, &self.typedefs)
+            .with_enums(&self.enum_defs)
+            .with_data_layout(self.data_layout)
     }
 
     /// Calculate the size of a type in bytes.
     pub(crate) fn get_type_size(&mut self, ty: &Type) -> i64 {
-        // Fast path – leaf types never need the cache
-        match ty {
-            Type::Int | Type::UnsignedInt | Type::Enum(_) => return 4,
-            Type::Bool => return 1,
-            Type::Char | Type::UnsignedChar => return 1,
-            Type::Short | Type::UnsignedShort => return 2,
-            Type::Long | Type::UnsignedLong => return 8,
-            Type::LongLong | Type::UnsignedLongLong => return 8,
-            Type::Float => return 4,
-            Type::Double => return 8,
-            Type::Void => return 0,
-            Type::Pointer(_, ..) | Type::FunctionPointer { .. } => return 8,
-            _ => {}
-        }
-
         // Name-based cache for struct/union/typedef
         let cache_key = match ty {
             Type::Struct(name) | Type::Union(name) | Type::Typedef(name) => Some(name.clone()),
@@ -98,7 +205,10 @@ impl Lowerer {
             (Type::UnsignedShort, Type::UnsignedShort) => true,
             (Type::UnsignedInt, Type::UnsignedInt) => true,
             (Type::UnsignedLong, Type::UnsignedLong) => true,
-            (Type::Pointer(a_inner, ..), Type::Pointer(b_inner, ..)) => self.types_compatible(a_inner, b_inner),
+            // `const char *` and `char *` are different types
+            (Type::Pointer(a_inner, a_quals), Type::Pointer(b_inner, b_quals)) => {
+                a_quals == b_quals && self.types_compatible(a_inner, b_inner)
+            }
             (Type::Array(a_inner, _), Type::Array(b_inner, _)) => self.types_compatible(a_inner, b_inner),
             (Type::Struct(a_name), Type::Struct(b_name)) => a_name == b_name,
             (Type::Union(a_name), Type::Union(b_name)) => a_name == b_name,
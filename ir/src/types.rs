@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use model::{BinaryOp, UnaryOp, Type, GlobalVar as AstGlobalVar};
+use model::{BinaryOp, UnaryOp, Type, GlobalVar as AstGlobalVar, Span};
 
 /// Variable identifier in IR
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -67,9 +67,12 @@ pub enum Instruction {
         dest: VarId,
         src: Operand,
     },
+    /// Convert `src` from `src_type` to `r#type`: integer truncation and
+    /// sign or zero extension, int ↔ float, and float ↔ double.
     Cast {
         dest: VarId,
         src: Operand,
+        src_type: Type,
         r#type: Type,
     },
     Alloca {
@@ -98,11 +101,21 @@ pub enum Instruction {
         dest: Option<VarId>,
         name: String,
         args: Vec<Operand>,
+        /// Types of the trailing arguments no prototype converts (past the
+        /// named parameters of a variadic callee, or all of them without a
+        /// prototype), after default promotion, which the arguments have
+        /// already been converted by. Constants carry no type, and targets
+        /// that pass arguments in slots of their size need it
+        vararg_types: Vec<Type>,
     },
     IndirectCall {
         dest: Option<VarId>,
         func_ptr: Operand,
         args: Vec<Operand>,
+        /// The pointer's type ends in `...`
+        is_variadic: bool,
+        /// As for `Call`
+        vararg_types: Vec<Type>,
     },
     // Variadic intrinsics
     VaStart {
@@ -130,6 +143,20 @@ pub enum Instruction {
         clobbers: Vec<String>,   // Clobbered registers
         is_volatile: bool,
     },
+    /// Copy `size` bytes from `src` to `dest`. The ranges are either the
+    /// same or disjoint. Struct assignment lowers to this.
+    Memcpy {
+        dest: Operand,
+        src: Operand,
+        size: usize,
+    },
+    /// Set `size` bytes at `dest` to the low byte of `value`. Aggregate
+    /// initializers that leave elements zero lower to this.
+    Memset {
+        dest: Operand,
+        value: Operand,
+        size: usize,
+    },
     /// SIMD vector instruction (generated by auto-vectorizer)
     Simd {
         op: SimdOp,
@@ -162,7 +189,9 @@ impl Instruction {
             Instruction::Store { .. }
             | Instruction::VaStart { .. }
             | Instruction::VaEnd { .. }
-            | Instruction::VaCopy { .. } => None,
+            | Instruction::VaCopy { .. }
+            | Instruction::Memcpy { .. }
+            | Instruction::Memset { .. } => None,
         }
     }
 
@@ -177,55 +206,69 @@ impl Instruction {
 
     /// Visit all VarIds used (read) by this instruction.
     pub fn for_each_use<F: FnMut(VarId)>(&self, mut f: F) {
-        let visit_op = |op: &Operand, f: &mut F| {
+        if let Instruction::Phi { preds, .. } = self {
+            for (_, v) in preds { f(*v); }
+            return;
+        }
+        self.for_each_operand(|op| {
             if let Operand::Var(v) = op { f(*v); }
-        };
+        });
+    }
+
+    /// Visit all operands read by this instruction. Phi sources are VarIds,
+    /// not Operands, and are skipped; `for_each_use` includes them.
+    pub fn for_each_operand<F: FnMut(&Operand)>(&self, mut f: F) {
         match self {
             Instruction::Binary { left, right, .. }
             | Instruction::FloatBinary { left, right, .. } => {
-                visit_op(left, &mut f);
-                visit_op(right, &mut f);
+                f(left);
+                f(right);
             }
             Instruction::Unary { src, .. }
             | Instruction::FloatUnary { src, .. }
             | Instruction::Copy { src, .. }
             | Instruction::Cast { src, .. } => {
-                visit_op(src, &mut f);
+                f(src);
             }
             Instruction::Load { addr, .. } => {
-                visit_op(addr, &mut f);
+                f(addr);
             }
             Instruction::Store { addr, src, .. } => {
-                visit_op(addr, &mut f);
-                visit_op(src, &mut f);
+                f(addr);
+                f(src);
             }
             Instruction::GetElementPtr { base, index, .. } => {
-                visit_op(base, &mut f);
-                visit_op(index, &mut f);
+                f(base);
+                f(index);
             }
             Instruction::Call { args, .. } => {
-                for arg in args { visit_op(arg, &mut f); }
+                for arg in args { f(arg); }
             }
             Instruction::IndirectCall { func_ptr, args, .. } => {
-                visit_op(func_ptr, &mut f);
-                for arg in args { visit_op(arg, &mut f); }
-            }
-            Instruction::Phi { preds, .. } => {
-                for (_, v) in preds { f(*v); }
+                f(func_ptr);
+                for arg in args { f(arg); }
             }
-            Instruction::VaStart { list, .. } => { visit_op(list, &mut f); }
-            Instruction::VaEnd { list } => { visit_op(list, &mut f); }
+            Instruction::Phi { .. } | Instruction::Alloca { .. } => {}
+            Instruction::VaStart { list, .. } => { f(list); }
+            Instruction::VaEnd { list } => { f(list); }
             Instruction::VaCopy { dest, src } => {
-                visit_op(dest, &mut f);
-                visit_op(src, &mut f);
+                f(dest);
+                f(src);
             }
-            Instruction::VaArg { list, .. } => { visit_op(list, &mut f); }
+            Instruction::VaArg { list, .. } => { f(list); }
             Instruction::InlineAsm { inputs, .. } => {
-                for input in inputs { visit_op(input, &mut f); }
+                for input in inputs { f(input); }
+            }
+            Instruction::Memcpy { dest, src, .. } => {
+                f(dest);
+                f(src);
+            }
+            Instruction::Memset { dest, value, .. } => {
+                f(dest);
+                f(value);
             }
-            Instruction::Alloca { .. } => {}
             Instruction::Simd { operands, .. } => {
-                for op in operands { visit_op(op, &mut f); }
+                for op in operands { f(op); }
             }
         }
     }
@@ -277,6 +320,14 @@ impl Instruction {
             Instruction::InlineAsm { inputs, .. } => {
                 for input in inputs { f(input); }
             }
+            Instruction::Memcpy { dest, src, .. } => {
+                f(dest);
+                f(src);
+            }
+            Instruction::Memset { dest, value, .. } => {
+                f(dest);
+                f(value);
+            }
             Instruction::Alloca { .. } => {}
             Instruction::Simd { operands, .. } => {
                 for op in operands { f(op); }
@@ -284,6 +335,26 @@ impl Instruction {
         }
     }
 
+    /// Visit every type the instruction carries mutably.
+    pub fn for_each_type_mut<F: FnMut(&mut Type)>(&mut self, mut f: F) {
+        match self {
+            Instruction::Cast { src_type, r#type, .. } => {
+                f(src_type);
+                f(r#type);
+            }
+            Instruction::Alloca { r#type, .. }
+            | Instruction::VaArg { r#type, .. }
+            | Instruction::Load { value_type: r#type, .. }
+            | Instruction::Store { value_type: r#type, .. }
+            | Instruction::GetElementPtr { element_type: r#type, .. }
+            | Instruction::Simd { elem_type: r#type, .. } => f(r#type),
+            Instruction::Call { vararg_types, .. } | Instruction::IndirectCall { vararg_types, .. } => {
+                vararg_types.iter_mut().for_each(f)
+            }
+            _ => {}
+        }
+    }
+
     /// Returns true if this instruction has side effects (stores, calls, etc.)
     /// and should not be removed even if its result is unused.
     pub fn has_side_effects(&self) -> bool {
@@ -299,6 +370,8 @@ impl Instruction {
                 | Instruction::VaEnd { .. }
                 | Instruction::VaCopy { .. }
                 | Instruction::VaArg { .. }
+                | Instruction::Memcpy { .. }
+                | Instruction::Memset { .. }
                 | Instruction::Simd { .. }
             )
         }
@@ -365,6 +438,14 @@ pub enum Terminator {
     Unreachable,
     /// Computed goto: `goto *ptr`
     IndirectBr { target: Operand },
+    /// `switch`: jump to the block of the case equal to `value`, or to
+    /// `default` if none is. `value` has the promoted type of the
+    /// controlling expression, and each case value was converted to it.
+    Switch {
+        value: Operand,
+        cases: Vec<(i64, BlockId)>,
+        default: BlockId,
+    },
 }
 
 impl Terminator {
@@ -390,6 +471,18 @@ impl Terminator {
             hint,
         }
     }
+
+    /// The distinct blocks a `Switch` jumps to: those of its cases in order,
+    /// then the default.
+    pub fn switch_targets(cases: &[(i64, BlockId)], default: BlockId) -> Vec<BlockId> {
+        let mut targets: Vec<BlockId> = Vec::new();
+        for block in cases.iter().map(|&(_, block)| block).chain(std::iter::once(default)) {
+            if !targets.contains(&block) {
+                targets.push(block);
+            }
+        }
+        targets
+    }
 }
 
 /// Basic block with instructions and terminator
@@ -400,6 +493,19 @@ pub struct BasicBlock {
     pub terminator: Terminator,
     /// True if this block is a target of a goto/label statement (should not be merged)
     pub is_label_target: bool,
+    /// Source span of each instruction, parallel to `instructions`. Passes
+    /// may leave this out of step; `Function::realign_spans` repairs it.
+    pub spans: Vec<Span>,
+    /// Source span of the statement that produced the terminator.
+    pub terminator_span: Span,
+}
+
+impl BasicBlock {
+    /// Source span of instruction `index`, or the terminator's span when
+    /// none was recorded for it.
+    pub fn span_of(&self, index: usize) -> Span {
+        self.spans.get(index).copied().unwrap_or(self.terminator_span)
+    }
 }
 
 /// Function in IR form
@@ -410,7 +516,7 @@ pub struct Function {
     pub params: Vec<(Type, VarId)>,
     pub blocks: Vec<BasicBlock>,
     pub entry_block: BlockId,
-    /// Type annotations for IR variables (used to distinguish float/int in codegen)
+    /// The type of every IR variable, filled by the lowerer and `infer_var_types`
     pub var_types: HashMap<VarId, Type>,
     /// Function attributes (weak, section, noreturn, etc.)
     pub attributes: Vec<model::Attribute>,
@@ -420,6 +526,11 @@ pub struct Function {
     pub label_addrs: Vec<String>,
     /// All labels defined in this function (name → block id).
     pub labels: HashMap<String, BlockId>,
+    /// Source name of each alloca that holds a named local or parameter,
+    /// for debug info.
+    pub local_names: HashMap<VarId, String>,
+    /// Source span of the definition.
+    pub span: Span,
 }
 
 impl Function {
@@ -428,6 +539,49 @@ impl Function {
         (Vec::new(), HashMap::new())
     }
 
+    /// The span of every instruction that defines a variable, by that variable.
+    pub fn spans_by_dest(&self) -> HashMap<VarId, Span> {
+        self.blocks
+            .iter()
+            .flat_map(|b| b.instructions.iter().enumerate().map(move |(i, inst)| (inst, b.span_of(i))))
+            .filter_map(|(inst, span)| inst.dest().map(|d| (d, span)))
+            .collect()
+    }
+
+    /// Bring every block's `spans` back in step with its instructions after
+    /// a pass rewrote them. An instruction takes the span its destination
+    /// had in `by_dest` (taken before the pass), so moved and rewritten
+    /// values keep their source line; one without a known span keeps its
+    /// old span if the block did not change length, and otherwise inherits
+    /// the span of the instruction before it.
+    pub fn realign_spans(&mut self, by_dest: &HashMap<VarId, Span>) {
+        for block in &mut self.blocks {
+            let in_step = block.spans.len() == block.instructions.len();
+            let known: Vec<Option<Span>> = block
+                .instructions
+                .iter()
+                .enumerate()
+                .map(|(i, inst)| {
+                    inst.dest()
+                        .and_then(|d| by_dest.get(&d).copied())
+                        .or_else(|| in_step.then(|| block.spans[i]))
+                })
+                .collect();
+            let first = known.iter().flatten().next().copied().unwrap_or(block.terminator_span);
+            let mut last = first;
+            block.spans = known
+                .into_iter()
+                .map(|span| {
+                    last = span.unwrap_or(last);
+                    last
+                })
+                .collect();
+            if block.terminator_span == Span::default() {
+                block.terminator_span = last;
+            }
+        }
+    }
+
     /// Compute a predecessor map for all blocks in this function.
     ///
     /// Returns a mapping from each `BlockId` to the list of `BlockId`s whose
@@ -446,6 +600,16 @@ impl Function {
                     preds.entry(*then_block).or_default().push(block.id);
                     preds.entry(*else_block).or_default().push(block.id);
                 }
+                Terminator::IndirectBr { .. } => {
+                    for target in self.indirect_targets() {
+                        preds.entry(target).or_default().push(block.id);
+                    }
+                }
+                Terminator::Switch { cases, default, .. } => {
+                    for target in Terminator::switch_targets(cases, *default) {
+                        preds.entry(target).or_default().push(block.id);
+                    }
+                }
                 _ => {}
             }
         }
@@ -463,12 +627,134 @@ impl Function {
                     s.push(*then_block);
                     s.push(*else_block);
                 }
+                Terminator::IndirectBr { .. } => s.extend(self.indirect_targets()),
+                Terminator::Switch { cases, default, .. } => s.extend(Terminator::switch_targets(cases, *default)),
                 _ => {}
             }
             succs.insert(block.id, s);
         }
         succs
     }
+
+    /// Blocks a computed goto (`IndirectBr`) may reach: the labels whose
+    /// address is taken, in `label_addrs` order.
+    pub fn indirect_targets(&self) -> Vec<BlockId> {
+        self.label_addrs.iter().filter_map(|label| self.labels.get(label).copied()).collect()
+    }
+
+    /// Whether any block ends in a computed goto.
+    pub fn has_indirect_branch(&self) -> bool {
+        self.blocks.iter().any(|b| matches!(b.terminator, Terminator::IndirectBr { .. }))
+    }
+
+    /// Give every variable without an entry in `var_types` the type of the
+    /// value its defining instruction produces, where that can be told.
+    /// `returns` maps directly called functions to their return types.
+    /// Existing entries are kept, so the lowerer's C types take precedence.
+    pub fn infer_var_types(&mut self, returns: &HashMap<String, Type>) {
+        let types = &mut self.var_types;
+        for (ty, var) in &self.params {
+            types.entry(*var).or_insert_with(|| ty.clone());
+        }
+        // Copies and phis may name variables defined further down, so repeat
+        // until nothing changes
+        loop {
+            let mut changed = false;
+            for inst in self.blocks.iter().flat_map(|b| &b.instructions) {
+                let Some(dest) = inst.dest() else { continue };
+                if types.contains_key(&dest) {
+                    continue;
+                }
+                let operand_type = |op: &Operand| match op {
+                    Operand::Var(v) => types.get(v).cloned(),
+                    Operand::Constant(_) => Some(Type::Int),
+                    _ => None,
+                };
+                let ty = match inst {
+                    Instruction::Load { value_type, .. } => Some(value_type.clone()),
+                    Instruction::Cast { r#type, .. } | Instruction::VaArg { r#type, .. } => Some(r#type.clone()),
+                    Instruction::Alloca { r#type, .. } => Some(Type::ptr(r#type.clone())),
+                    Instruction::GetElementPtr { element_type, .. } => Some(Type::ptr(element_type.clone())),
+                    Instruction::Call { name, .. } => returns.get(name).cloned(),
+                    Instruction::IndirectCall { func_ptr, .. } => match operand_type(func_ptr) {
+                        Some(Type::FunctionPointer { return_type, .. }) => Some(*return_type),
+                        Some(Type::Pointer(inner, _)) => match *inner {
+                            Type::FunctionPointer { return_type, .. } => Some(*return_type),
+                            _ => None,
+                        },
+                        _ => None,
+                    },
+                    Instruction::Copy { src: Operand::Var(v), .. } => types.get(v).cloned(),
+                    Instruction::Phi { preds, .. } => preds.iter().find_map(|(_, v)| types.get(v).cloned()),
+                    Instruction::Binary { op, left, right, .. } => {
+                        binary_result_type(op, operand_type(left), operand_type(right))
+                    }
+                    Instruction::FloatBinary { op, .. } if is_comparison(op) => Some(Type::Int),
+                    Instruction::FloatBinary { left, right, .. } => {
+                        let float_type = |op: &Operand| match op {
+                            Operand::Var(v) => types.get(v).cloned(),
+                            _ => None,
+                        };
+                        Some(float_type(left).or_else(|| float_type(right)).unwrap_or(Type::Double))
+                    }
+                    Instruction::Unary { op: UnaryOp::LogicalNot, .. } => Some(Type::Int),
+                    Instruction::Unary { src, .. } => operand_type(src).map(|t| model::TypeEnv::integer_promotion(&t)),
+                    Instruction::FloatUnary { src: Operand::Var(v), .. } => {
+                        Some(types.get(v).cloned().unwrap_or(Type::Double))
+                    }
+                    Instruction::FloatUnary { .. } => Some(Type::Double),
+                    _ => None,
+                };
+                if let Some(ty) = ty {
+                    types.insert(dest, ty);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+pub(crate) fn is_comparison(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::EqualEqual | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+            | BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::LogicalAnd | BinaryOp::LogicalOr
+    )
+}
+
+/// The type of an integer `Binary` result, following C's conversions:
+/// comparisons give `int`, pointer arithmetic keeps the pointer, and a
+/// pointer difference is a `long`.
+fn binary_result_type(op: &BinaryOp, left: Option<Type>, right: Option<Type>) -> Option<Type> {
+    use model::TypeEnv;
+    if is_comparison(op) {
+        return Some(Type::Int);
+    }
+    if matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) {
+        return left.filter(TypeEnv::is_integer_type).map(|t| TypeEnv::integer_promotion(&t));
+    }
+    match (left, right) {
+        (Some(Type::Pointer(..)), Some(Type::Pointer(..))) if *op == BinaryOp::Sub => Some(Type::Long),
+        (Some(ptr @ Type::Pointer(..)), _) | (_, Some(ptr @ Type::Pointer(..))) => Some(ptr),
+        (Some(l), Some(r)) if TypeEnv::is_integer_type(&l) && TypeEnv::is_integer_type(&r) => {
+            Some(TypeEnv::usual_arithmetic_conversions(&l, &r))
+        }
+        _ => None,
+    }
+}
+
+/// A function declared by a prototype but defined in another unit or a
+/// library. Codegen needs its parameter types to pass constant arguments in
+/// the right precision once optimization has folded away their casts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub return_type: Type,
+    pub params: Vec<Type>,
+    pub is_variadic: bool,
 }
 
 /// Complete IR program
@@ -479,4 +765,11 @@ pub struct IRProgram {
     pub globals: Vec<AstGlobalVar>,
     pub structs: Vec<model::StructDef>,
     pub unions: Vec<model::UnionDef>,
+    /// Enum definitions with their enumerators and underlying types.
+    pub enums: Vec<model::EnumDef>,
+    /// Prototypes of the functions the program calls but does not define.
+    pub declarations: Vec<Declaration>,
+    /// Scalar sizes of the target the program was lowered for, which the
+    /// optimizer needs to lay out types as codegen will.
+    pub data_layout: model::DataLayout,
 }
@@ -0,0 +1,85 @@
+use model::{SourceMap, Span, Token};
+
+/// Render a token stream as JSON, in the spirit of `clang -dump-tokens`.
+/// Each token records its kind (the `Token` variant in snake case), its
+/// spelling in the source, and its position; lines and columns are 1-based.
+pub fn tokens_to_json(tokens: &[Token], spans: &[Span], sources: &SourceMap) -> String {
+    let mut out = String::from("{\n  \"tokens\": [");
+    for (i, (token, span)) in tokens.iter().zip(spans).enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        let file = sources.name(span.file_id).unwrap_or("");
+        let pos = sources.line_col(span.file_id, span.start);
+        out.push_str(&format!(
+            "    {{\"kind\": \"{}\", \"text\": {}, \"file\": {}, \"line\": {}, \"col\": {}, \"start\": {}, \"end\": {}}}",
+            token_kind(token),
+            quote_json(sources.snippet(*span).unwrap_or("")),
+            quote_json(file),
+            pos.map_or(0, |p| p.line),
+            pos.map_or(0, |p| p.col),
+            span.start,
+            span.end
+        ));
+    }
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+/// The variant name of `token` in snake case: `OpenParenthesis` → `open_parenthesis`.
+fn token_kind(token: &Token) -> String {
+    let debug = format!("{:?}", token);
+    let name = debug.split([' ', '{', '(']).next().unwrap_or_default();
+    let mut kind = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                kind.push('_');
+            }
+            kind.push(c.to_ascii_lowercase());
+        } else {
+            kind.push(c);
+        }
+    }
+    kind
+}
+
+/// `s` as a JSON string literal, quotes included.
+pub fn quote_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::Spanned;
+
+    #[test]
+    fn json_lists_kind_spelling_and_position() {
+        let src = "int x;\nchar *s = \"a\\\"b\";";
+        let mut sources = SourceMap::new();
+        let file = sources.add_file("t.c", src);
+        let (tokens, spans): (Vec<Token>, Vec<Span>) = crate::lex_spanned(src, file)
+            .unwrap()
+            .into_iter()
+            .map(|Spanned { node, span }| (node, span))
+            .unzip();
+        let json = tokens_to_json(&tokens, &spans, &sources);
+        assert!(json.contains("{\"kind\": \"int\", \"text\": \"int\", \"file\": \"t.c\", \"line\": 1, \"col\": 1, \"start\": 0, \"end\": 3}"));
+        assert!(json.contains("{\"kind\": \"identifier\", \"text\": \"x\", \"file\": \"t.c\", \"line\": 1, \"col\": 5"));
+        assert!(json.contains("{\"kind\": \"string_literal\", \"text\": \"\\\"a\\\\\\\"b\\\"\", \"file\": \"t.c\", \"line\": 2, \"col\": 11"));
+        assert_eq!(token_kind(&Token::OpenParenthesis), "open_parenthesis");
+    }
+}
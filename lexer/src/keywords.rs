@@ -41,6 +41,7 @@ pub fn keyword_or_identifier(value: &str) -> Token {
         "__asm" => Token::Asm,
         "__asm__" => Token::Asm,
         "__attribute__" => Token::Attribute,
+        "__declspec" => Token::Attribute,
         "__extension__" => Token::Extension,
         "__inline" => Token::Inline,
         "__inline__" => Token::Inline,
@@ -72,11 +73,24 @@ pub fn keyword_or_identifier(value: &str) -> Token {
         "__alignof" => Token::AlignOf,
         "__alignof__" => Token::AlignOf,
         "alignof" => Token::AlignOf,
+        "_Alignas" => Token::Alignas,
+        "alignas" => Token::Alignas,
+        "_Atomic" => Token::Atomic,
+        "_Thread_local" => Token::ThreadLocal,
+        "thread_local" => Token::ThreadLocal,
+        "__thread" => Token::ThreadLocal,
         "register" => Token::Register,
         "_Generic" => Token::Generic,
-        "_Noreturn" => Token::Register,  // Treat _Noreturn like a skippable qualifier
-        "__noreturn__" => Token::Register,
-        "noreturn" => Token::Register,   // C23 noreturn keyword
+        "_Complex" => Token::Complex,
+        "__complex" => Token::Complex,
+        "__complex__" => Token::Complex,
+        "__real" => Token::RealPart,
+        "__real__" => Token::RealPart,
+        "__imag" => Token::ImagPart,
+        "__imag__" => Token::ImagPart,
+        "_Noreturn" => Token::Noreturn,
+        "__noreturn__" => Token::Noreturn,
+        "noreturn" => Token::Noreturn,   // C23 noreturn keyword
         "__label__" => Token::Extension,  // GCC local label declaration
         "__auto_type" => Token::Extension, // GCC auto type deduction
         _ => Token::Identifier { value: value.to_string() },
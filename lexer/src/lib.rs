@@ -1,22 +1,68 @@
+mod json;
 mod keywords;
 mod literals;
 mod state_machine;
+mod trivia;
 #[cfg(test)]
 mod repro_bug;
 
-use model::Token;
+use model::{CompileError, FileId, LineMarker, Spanned, Token};
+use std::ops::Range;
 use state_machine::StateMachineLexer;
 
+pub use json::{quote_json, tokens_to_json};
+pub use trivia::{lex_with_comments, Comment, CommentKind, CommentedTokens, TriviaToken};
+
 /// Main lexer entry point using efficient state machine
-pub fn lex(input: &str) -> Result<Vec<Token>, String> {
+pub fn lex(input: &str) -> Result<Vec<Token>, CompileError> {
     let mut lexer = StateMachineLexer::new(input);
     lexer.tokenize()
 }
 
+/// Lex `input` as file `file_id`, attaching a source span to every token.
+/// On failure, the error carries the span of the text that could not be lexed.
+pub fn lex_spanned(input: &str, file_id: FileId) -> Result<Vec<Spanned<Token>>, CompileError> {
+    let mut lexer = StateMachineLexer::new(input);
+    lexer.tokenize_spanned(file_id)
+}
+
+/// Line markers (`# 12 "file.h"` as written by `gcc -E`, or `#line 12`) in
+/// preprocessed `input`, in source order, for `SourceMap::add_line_markers`.
+/// Lexing stops at the first error, so only the markers before it are returned.
+pub fn line_markers(input: &str) -> Vec<LineMarker> {
+    let mut lexer = StateMachineLexer::new(input);
+    let _ = lexer.tokenize();
+    lexer.line_markers
+}
+
+/// Byte ranges of preprocessed `input` that come from system headers,
+/// according to the flag-3 line markers `gcc -E` writes.
+pub fn system_header_ranges(input: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for marker in line_markers(input) {
+        if marker.file.is_none() {
+            continue;
+        }
+        match (start, marker.system_header) {
+            (None, true) => start = Some(marker.offset),
+            (Some(from), false) => {
+                ranges.push(from..marker.offset);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        ranges.push(from..input.len());
+    }
+    ranges
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use model::IntegerSuffix;
+    use model::{IntegerSuffix, StringEncoding};
 
     #[test]
     fn lex_simple_identifier_and_constant() {
@@ -89,13 +135,63 @@ mod tests {
     #[test]
     fn lex_string_literal() {
         let tokens = lex(r#""hello world""#).unwrap();
-        assert_eq!(tokens, vec![Token::StringLiteral { value: "hello world".to_string() }]);
+        assert_eq!(tokens, vec![Token::StringLiteral { value: "hello world".to_string(), encoding: StringEncoding::Plain }]);
     }
 
     #[test]
     fn lex_empty_string() {
         let tokens = lex(r#""""#).unwrap();
-        assert_eq!(tokens, vec![Token::StringLiteral { value: "".to_string() }]);
+        assert_eq!(tokens, vec![Token::StringLiteral { value: "".to_string(), encoding: StringEncoding::Plain }]);
+    }
+
+    #[test]
+    fn lex_prefixed_string_literals() {
+        let tokens = lex(r#"L"é\x263a" u8"é" u"😀" U"a" L u8"#).unwrap();
+        assert_eq!(tokens, vec![
+            Token::StringLiteral { value: "é\u{263a}".to_string(), encoding: StringEncoding::Wide },
+            // Narrow literals keep the source bytes, one per char
+            Token::StringLiteral { value: "\u{c3}\u{a9}".to_string(), encoding: StringEncoding::Utf8 },
+            Token::StringLiteral { value: "😀".to_string(), encoding: StringEncoding::Utf16 },
+            Token::StringLiteral { value: "a".to_string(), encoding: StringEncoding::Utf32 },
+            Token::Identifier { value: "L".to_string() },
+            Token::Identifier { value: "u8".to_string() },
+        ]);
+        assert!(lex(r#""\x263a""#).is_ok(), "narrow hex escapes stop after two digits");
+        assert!(lex(r#"L"\xd800""#).is_err(), "surrogates are not code points");
+    }
+
+    #[test]
+    fn lex_prefixed_char_literals() {
+        let tokens = lex("L'é' u'\\x263a' U'😀'").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Constant { value: 0xe9, suffix: IntegerSuffix::None },
+            Token::Constant { value: 0x263a, suffix: IntegerSuffix::None },
+            Token::Constant { value: 0x1f600, suffix: IntegerSuffix::U },
+        ]);
+    }
+
+    #[test]
+    fn lex_universal_character_names() {
+        let tokens = lex(r#"caf\u00e9 \U0001F600x "\u00e9" L"\U0001F600" '\u00e9' L'\u20ac'"#).unwrap();
+        assert_eq!(tokens, vec![
+            Token::Identifier { value: "café".to_string() },
+            Token::Identifier { value: "😀x".to_string() },
+            // UTF-8 bytes in narrow data, one per char
+            Token::StringLiteral { value: "\u{c3}\u{a9}".to_string(), encoding: StringEncoding::Plain },
+            Token::StringLiteral { value: "😀".to_string(), encoding: StringEncoding::Wide },
+            Token::Constant { value: 0xc3a9, suffix: IntegerSuffix::None },
+            Token::Constant { value: 0x20ac, suffix: IntegerSuffix::None },
+        ]);
+        // Basic characters, surrogates, and short UCNs are rejected
+        assert!(lex(r#""\u0041""#).is_err());
+        assert!(lex(r#"L"\ud800""#).is_err());
+        assert!(lex(r#"x\u00e"#).is_err());
+        assert!(lex(r#""\u0024""#).is_ok(), "$ may be named by a UCN");
+        // Extended characters may also be written directly
+        assert_eq!(lex("über caf\\u00e9é").unwrap(), vec![
+            Token::Identifier { value: "über".to_string() },
+            Token::Identifier { value: "caféé".to_string() },
+        ]);
     }
 
     // ─── Character literal tests ────────────────────────────────
@@ -293,11 +389,22 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn lex_c11_keywords() {
+        let tokens = lex("_Alignas _Alignof _Atomic _Noreturn _Static_assert _Thread_local _Bool _Complex").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Alignas, Token::AlignOf, Token::Atomic, Token::Noreturn,
+            Token::StaticAssert, Token::ThreadLocal, Token::Bool, Token::Complex,
+        ]);
+        let tokens = lex("alignas thread_local __thread noreturn").unwrap();
+        assert_eq!(tokens, vec![Token::Alignas, Token::ThreadLocal, Token::ThreadLocal, Token::Noreturn]);
+    }
+
     #[test]
     fn lex_gcc_extensions() {
-        let tokens = lex("__attribute__ __extension__ __typeof__ __alignof__").unwrap();
+        let tokens = lex("__attribute__ __extension__ __typeof__ __alignof__ __declspec").unwrap();
         assert_eq!(tokens, vec![
-            Token::Attribute, Token::Extension, Token::Typeof, Token::AlignOf,
+            Token::Attribute, Token::Extension, Token::Typeof, Token::AlignOf, Token::Attribute,
         ]);
     }
 
@@ -366,6 +473,66 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn lex_records_line_markers() {
+        let input = "# 0 \"t.c\"\n# 1 \"<built-in>\"\n# 1 \"dir\\\\inc.h\" 1 3 4\nint x; // note\n#line 20\n#pragma once\nint y;";
+        let tokens = lex(input).unwrap();
+        assert_eq!(tokens.len(), 6, "{:?}", tokens);
+        let after = |needle: &str| input.find(needle).unwrap() + needle.len() + 1;
+        assert_eq!(line_markers(input), vec![
+            LineMarker { offset: after("\"t.c\""), line: 0, file: Some("t.c".to_string()), system_header: false },
+            LineMarker { offset: after("\"<built-in>\""), line: 1, file: Some("<built-in>".to_string()), system_header: false },
+            LineMarker { offset: after("1 3 4"), line: 1, file: Some("dir\\inc.h".to_string()), system_header: true },
+            LineMarker { offset: after("#line 20"), line: 20, file: None, system_header: false },
+        ]);
+    }
+
+    #[test]
+    fn lex_system_header_ranges() {
+        let input = "# 1 \"t.c\"\n# 1 \"/usr/include/a.h\" 1 3 4\nint a;\n#line 9\nint b;\n# 3 \"t.c\" 2\nint c;\n# 1 \"/usr/include/b.h\" 1 3\nint d;";
+        let at = |needle: &str| input.find(needle).unwrap();
+        assert_eq!(system_header_ranges(input), vec![at("int a")..at("int c"), at("int d")..input.len()]);
+    }
+
+    #[test]
+    fn lex_pragma_pack() {
+        use model::PackPragma;
+        let input = "#pragma pack(push, 1)\n#pragma pack(push, r, 2)\n# pragma pack ( pop )\n#pragma pack(8)\n#pragma pack()\n#pragma pack(3)\n#pragma once\nint x;";
+        let tokens = lex(input).unwrap();
+        assert_eq!(tokens[..5], [
+            Token::PragmaPack(PackPragma::Push(Some(1))),
+            Token::PragmaPack(PackPragma::Push(Some(2))),
+            Token::PragmaPack(PackPragma::Pop),
+            Token::PragmaPack(PackPragma::Set(Some(8))),
+            Token::PragmaPack(PackPragma::Set(None)),
+        ]);
+        // pack(3) is not a power of two and is ignored, like other pragmas
+        assert_eq!(tokens[5], Token::Int);
+    }
+
+    #[test]
+    fn lex_digraphs() {
+        let tokens = lex("int a<:2:> = <%1, 2%>; %:\n%:line 9\nx").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Int,
+            Token::Identifier { value: "a".to_string() },
+            Token::OpenBracket,
+            Token::Constant { value: 2, suffix: IntegerSuffix::None },
+            Token::CloseBracket,
+            Token::Equal,
+            Token::OpenBrace,
+            Token::Constant { value: 1, suffix: IntegerSuffix::None },
+            Token::Comma,
+            Token::Constant { value: 2, suffix: IntegerSuffix::None },
+            Token::CloseBrace,
+            Token::Semicolon,
+            Token::Hash,
+            Token::Identifier { value: "x".to_string() },
+        ]);
+        // `%:` starting a line is a directive, like `#`
+        assert_eq!(line_markers("%:line 9\nx")[0].line, 9);
+    }
+
     #[test]
     fn lex_complex_expression() {
         let tokens = lex("a->b.c[0]").unwrap();
@@ -380,4 +547,27 @@ mod tests {
             Token::CloseBracket,
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lex_large_preprocessed_input() {
+        // Shaped like a big `.i` file: line markers, declarations, comments
+        let src: String = (0..50_000)
+            .map(|i| format!("# {i} \"big.c\"\nint v{i} = {i}; /* c */\n"))
+            .collect();
+        let tokens = lex(&src).unwrap();
+        assert_eq!(tokens.len(), 50_000 * 5);
+        assert_eq!(tokens[49_999 * 5 + 1], Token::Identifier { value: "v49999".to_string() });
+    }
+
+    #[test]
+    fn lex_spanned_records_byte_ranges() {
+        let tokens = lex_spanned("int  foo=42;", FileId(3)).unwrap();
+        let ranges: Vec<_> = tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+        assert_eq!(ranges, vec![(0, 3), (5, 8), (8, 9), (9, 11), (11, 12)]);
+        assert!(tokens.iter().all(|t| t.span.file_id == FileId(3)));
+
+        let err = lex_spanned("int x = 1 @ 2;", FileId(0)).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::UnexpectedCharacter);
+        assert_eq!(err.span.map(|s| (s.start, s.end)), Some((10, 11)));
+    }
+}
@@ -1,5 +1,7 @@
+use model::{CompileError, ErrorCode};
+
 /// Parse a character literal to its integer value
-pub fn parse_char_literal(content: &str) -> Result<i64, String> {
+pub fn parse_char_literal(content: &str) -> Result<i64, CompileError> {
     if content.starts_with('\\') {
         // Escape sequence
         match content.chars().nth(1) {
@@ -18,47 +20,63 @@ pub fn parse_char_literal(content: &str) -> Result<i64, String> {
                 // Hexadecimal escape like '\x1F'
                 let hex = &content[2..];
                 i64::from_str_radix(hex, 16)
-                    .map_err(|_| format!("Invalid hex escape sequence: {}", content))
+                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, format!("Invalid hex escape sequence: {}", content)))
             }
+            Some('u') | Some('U') => ucn_char(&content[2..]).map(|c| i64::from(u32::from(c))),
             Some(c) if c.is_ascii_digit() => {
                 // Octal escape sequence like '\077'
                 let octal = content[1..].chars()
                     .take_while(|ch| ch.is_ascii_digit())
                     .collect::<String>();
                 i64::from_str_radix(&octal, 8)
-                    .map_err(|_| format!("Invalid octal escape sequence: {}", content))
+                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, format!("Invalid octal escape sequence: {}", content)))
             }
-            _ => Err(format!("Unknown escape sequence in character literal: '{}'", content)),
+            _ => Err(CompileError::new(ErrorCode::InvalidEscape, format!("Unknown escape sequence in character literal: '{}'", content))),
         }
     } else {
         // Regular character
         content.chars().next()
             .map(|c| c as i64)
-            .ok_or_else(|| "Empty character literal".to_string())
+            .ok_or_else(|| CompileError::new(ErrorCode::InvalidEscape, "Empty character literal"))
+    }
+}
+
+/// Decode the hex digits of a universal character name (`\u00e9`,
+/// `\U0001F600`). C11 6.4.3 rules out surrogates and characters below
+/// U+00A0 other than `$`, `@`, and `` ` ``.
+pub fn ucn_char(digits: &str) -> Result<char, CompileError> {
+    let invalid = || CompileError::new(ErrorCode::InvalidEscape, format!("Invalid universal character name: {}", digits));
+    if !matches!(digits.len(), 4 | 8) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let code = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+    if code < 0xA0 && !matches!(code, 0x24 | 0x40 | 0x60) {
+        return Err(invalid());
     }
+    char::from_u32(code).ok_or_else(invalid)
 }
 
 /// Parse an integer constant (decimal or hexadecimal)
 /// Returns the value as i64 (bit-reinterpreted for values > i64::MAX)
-pub fn parse_int_constant(text: &str) -> Result<i64, String> {
+pub fn parse_int_constant(text: &str) -> Result<i64, CompileError> {
     if text.starts_with("0x") || text.starts_with("0X") {
         // Use u64 to handle full unsigned range, then reinterpret as i64
         u64::from_str_radix(&text[2..], 16)
             .map(|v| v as i64)
-            .map_err(|_| format!("Failed to parse hex constant: {}", text))
+            .map_err(|_| CompileError::new(ErrorCode::InvalidNumber, format!("Failed to parse hex constant: {}", text)))
     } else {
         // Try i64 first, then u64 for large unsigned values
         text.parse::<i64>()
             .or_else(|_| text.parse::<u64>().map(|v| v as i64))
-            .map_err(|_| format!("Failed to parse constant: {}", text))
+            .map_err(|_| CompileError::new(ErrorCode::InvalidNumber, format!("Failed to parse constant: {}", text)))
     }
 }
 
 /// Parse a float literal, removing optional 'f' or 'F' suffix
-pub fn parse_float_literal(text: &str) -> Result<f64, String> {
+pub fn parse_float_literal(text: &str) -> Result<f64, CompileError> {
     let float_str = text.trim_end_matches(|c| c == 'f' || c == 'F');
     float_str.parse::<f64>()
-        .map_err(|_| format!("Failed to parse float literal: {}", text))
+        .map_err(|_| CompileError::new(ErrorCode::InvalidNumber, format!("Failed to parse float literal: {}", text)))
 }
 
 #[cfg(test)]
@@ -27,7 +27,7 @@ fn test_escape_sequences() {
     let tokens = lex(input).expect("lexing should succeed");
     assert_eq!(tokens.len(), 1);
     match &tokens[0] {
-        Token::StringLiteral { value } => {
+        Token::StringLiteral { value, .. } => {
             assert_eq!(value.chars().nth(0), Some('\x07')); // \a
             assert_eq!(value.chars().nth(1), Some('\x08')); // \b
             assert_eq!(value.chars().nth(2), Some('\x0C')); // \f
@@ -1,6 +1,7 @@
-use model::{Token, IntegerSuffix};
+use model::{CompileError, ErrorCode, FileId, IntegerSuffix, LineMarker, PackPragma, Span, Spanned, StringEncoding, Token};
 use crate::keywords::keyword_or_identifier;
-use crate::literals::{parse_char_literal, parse_int_constant, parse_float_literal};
+use crate::literals::{parse_char_literal, parse_int_constant, parse_float_literal, ucn_char};
+use crate::trivia::CommentKind;
 
 /// Parse integer suffix characters (U, L, UL, LL, ULL, etc.) from the current position.
 /// Returns the parsed IntegerSuffix.
@@ -33,11 +34,95 @@ fn parse_integer_suffix(input: &[u8], pos: &mut usize) -> IntegerSuffix {
     }
 }
 
+/// Length of a GNU imaginary suffix (`i` or `j`, optionally mixed with the
+/// `f`/`l` floating suffixes, as in `1.0iF`) starting at `pos`, if there is one.
+fn imaginary_suffix_len(input: &[u8], pos: usize) -> Option<usize> {
+    let run = input[pos..].iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+        .count();
+    let suffix = &input[pos..pos + run];
+    let imaginary = suffix.iter().filter(|b| matches!(b, b'i' | b'I' | b'j' | b'J')).count();
+    let valid = suffix.iter().all(|b| matches!(b, b'i' | b'I' | b'j' | b'J' | b'f' | b'F' | b'l' | b'L'));
+    (imaginary == 1 && valid).then_some(run)
+}
+
+/// Append the code unit `code` of a string literal to `value`. Narrow
+/// literals hold bytes, one per `char`; wide ones hold code points.
+fn push_unit(value: &mut String, code: u32, encoding: StringEncoding) -> Result<(), CompileError> {
+    let ch = if encoding.is_narrow() { u8::try_from(code).ok().map(char::from) } else { char::from_u32(code) };
+    let ch = ch.ok_or_else(|| CompileError::new(ErrorCode::InvalidEscape, format!("Escape sequence out of range: {:#x}", code)))?;
+    value.push(ch);
+    Ok(())
+}
+
+/// Line number, optional file name, and system-header flag of a line marker,
+/// given the directive text after its `#`: `12 "file.h" 1 3` as `gcc -E`
+/// writes it, or `line 12 "file.h"`. Other directives give `None`.
+fn parse_line_marker(directive: &[u8]) -> Option<(usize, Option<String>, bool)> {
+    let text = std::str::from_utf8(directive).ok()?.trim_start();
+    let text = match text.strip_prefix("line") {
+        Some(rest) if rest.starts_with([' ', '\t']) => rest.trim_start(),
+        Some(_) => return None,
+        None => text,
+    };
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    let line = text[..digits].parse().ok()?;
+    let rest = text[digits..].trim_start();
+    let Some(quoted) = rest.strip_prefix('"') else {
+        return Some((line, None, false));
+    };
+    let mut file = Vec::new();
+    let mut bytes = quoted.bytes().enumerate();
+    let flags = loop {
+        match bytes.next()? {
+            (i, b'"') => break &quoted[i + 1..],
+            (_, b'\\') => file.push(bytes.next()?.1),
+            (_, b) => file.push(b),
+        }
+    };
+    // Flag 3: the text that follows comes from a system header
+    let system_header = flags.split_whitespace().any(|flag| flag == "3");
+    Some((line, Some(String::from_utf8_lossy(&file).into_owned()), system_header))
+}
+
+/// The action of a `pragma pack(...)` directive, given the text after its
+/// `#`. Accepts `pack()`, `pack(N)`, `pack(push[, name][, N])`, and
+/// `pack(pop[, name])`; other directives, and malformed ones, give `None`.
+fn parse_pack_pragma(directive: &[u8]) -> Option<PackPragma> {
+    let text = std::str::from_utf8(directive).ok()?.trim();
+    let rest = text.strip_prefix("pragma")?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let args = rest.trim_start().strip_prefix("pack")?.trim_start().strip_prefix('(')?;
+    let args = args.strip_suffix(')')?.trim();
+    let alignment = |arg: &str| arg.parse::<usize>().ok().filter(|n| n.is_power_of_two());
+    let mut parts = args.split(',').map(str::trim);
+    match parts.next()? {
+        "" => Some(PackPragma::Set(None)),
+        "push" => {
+            let mut value = None;
+            for part in parts {
+                if part.starts_with(|c: char| c.is_ascii_digit()) {
+                    value = Some(alignment(part)?);
+                }
+            }
+            Some(PackPragma::Push(value))
+        }
+        "pop" => Some(PackPragma::Pop),
+        n => Some(PackPragma::Set(Some(alignment(n)?))),
+    }
+}
+
 pub struct StateMachineLexer<'a> {
     input: &'a [u8],
     pos: usize,
     token_start: usize,
     at_line_start: bool,
+    /// `# N "file"` / `#line N` markers seen so far, in source order.
+    pub line_markers: Vec<LineMarker>,
+    /// Byte ranges of the comments skipped so far, when `keep_comments` was called.
+    pub comments: Option<Vec<(CommentKind, usize, usize)>>,
 }
 
 impl<'a> StateMachineLexer<'a> {
@@ -47,10 +132,18 @@ impl<'a> StateMachineLexer<'a> {
             pos: 0,
             token_start: 0,
             at_line_start: true,
+            line_markers: Vec::new(),
+            comments: None,
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+    /// Record the range of every comment in `comments` instead of only skipping it.
+    pub fn keep_comments(mut self) -> Self {
+        self.comments = Some(Vec::new());
+        self
+    }
+
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, CompileError> {
         let mut tokens = Vec::new();
 
         while self.pos < self.input.len() {
@@ -63,7 +156,28 @@ impl<'a> StateMachineLexer<'a> {
         Ok(tokens)
     }
 
-    fn lex_next_token(&mut self) -> Result<Option<Token>, String> {
+    /// Like `tokenize`, but records the byte range of every token and of the
+    /// offending text on error.
+    pub fn tokenize_spanned(&mut self, file_id: FileId) -> Result<Vec<Spanned<Token>>, CompileError> {
+        let mut tokens = Vec::new();
+
+        while self.pos < self.input.len() {
+            match self.lex_next_token() {
+                Ok(Some(token)) => {
+                    tokens.push(Spanned::new(token, Span::new(file_id, self.token_start, self.pos)));
+                }
+                Ok(None) => continue,
+                Err(error) => {
+                    let end = self.pos.max(self.token_start + 1).min(self.input.len());
+                    return Err(error.with_span(Span::new(file_id, self.token_start, end)));
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    fn lex_next_token(&mut self) -> Result<Option<Token>, CompileError> {
         self.skip_whitespace();
         
         if self.pos >= self.input.len() {
@@ -77,28 +191,39 @@ impl<'a> StateMachineLexer<'a> {
             // Comments
             '/' if self.peek(1) == Some('/') => {
                 self.skip_line_comment();
+                self.note_comment(CommentKind::Line);
                 Ok(None)
             }
             '/' if self.peek(1) == Some('*') => {
                 self.skip_block_comment()?;
+                self.note_comment(CommentKind::Block);
                 self.at_line_start = false;
                 Ok(None)
             }
-            // Preprocessor directives - skip entire line
-            '#' if self.is_start_of_line() => {
-                self.skip_preprocessor_line();
-                self.at_line_start = false;
-                Ok(None)
-            }
+            // Preprocessor directives - skip entire line, noting line markers
+            // and keeping `#pragma pack` for the parser
+            '#' if self.is_start_of_line() => Ok(self.skip_preprocessor_line(1)),
+            '%' if self.is_start_of_line() && self.peek(1) == Some(':') => Ok(self.skip_preprocessor_line(2)),
             // String literals
             '"' => {
                 self.at_line_start = false;
-                self.lex_string()
+                self.lex_string(StringEncoding::Plain)
             }
             // Character literals
             '\'' => {
                 self.at_line_start = false;
-                self.lex_char()
+                self.lex_char(StringEncoding::Plain)
+            }
+            // Prefixed literals: L"...", u8"...", u'...', U'...'
+            'L' | 'u' | 'U' if self.literal_prefix().is_some() => {
+                self.at_line_start = false;
+                let (len, encoding) = self.literal_prefix().expect("checked by the guard");
+                self.pos += len;
+                if self.current_char() == '"' {
+                    self.lex_string(encoding)
+                } else {
+                    self.lex_char(encoding)
+                }
             }
             // Numbers
             '0'..='9' => {
@@ -109,11 +234,20 @@ impl<'a> StateMachineLexer<'a> {
                 self.at_line_start = false;
                 self.lex_number()
             }
-            // Identifiers and keywords
+            // Identifiers and keywords (which may start with a UCN)
             'a'..='z' | 'A'..='Z' | '_' => {
                 self.at_line_start = false;
                 self.lex_identifier()
             }
+            '\\' if matches!(self.peek(1), Some('u' | 'U')) => {
+                self.at_line_start = false;
+                self.lex_identifier()
+            }
+            // Extended characters written directly in UTF-8 (`café`)
+            _ if self.at_extended_char() => {
+                self.at_line_start = false;
+                self.lex_identifier()
+            }
             // Operators and punctuation
             _ => {
                 self.at_line_start = false;
@@ -158,10 +292,20 @@ impl<'a> StateMachineLexer<'a> {
         }
         if self.pos < self.input.len() {
             self.pos += 1; // Skip the newline
+            self.at_line_start = true;
         }
     }
 
-    fn skip_block_comment(&mut self) -> Result<(), String> {
+    /// Record the comment that was just skipped, without a line comment's newline.
+    fn note_comment(&mut self, kind: CommentKind) {
+        if let Some(comments) = &mut self.comments {
+            let text = &self.input[self.token_start..self.pos];
+            let end = self.pos - (text.len() - text.trim_ascii_end().len());
+            comments.push((kind, self.token_start, end));
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), CompileError> {
         self.pos += 2; // Skip the initial /*
         
         while self.pos < self.input.len() {
@@ -172,60 +316,96 @@ impl<'a> StateMachineLexer<'a> {
             self.pos += 1;
         }
         
-        Err("Unterminated block comment".to_string())
+        Err(CompileError::new(ErrorCode::Unterminated, "Unterminated block comment"))
     }
 
-    fn skip_preprocessor_line(&mut self) {
+    /// Skip a directive line whose `#` (or `%:`) is `hash_len` bytes long.
+    /// A `#pragma pack` line becomes a `Token::PragmaPack`.
+    fn skip_preprocessor_line(&mut self, hash_len: usize) -> Option<Token> {
+        let start = self.pos + hash_len;
         while self.pos < self.input.len() && self.current_char() != '\n' {
             self.pos += 1;
         }
+        let directive = &self.input[start..self.pos];
         if self.pos < self.input.len() {
             self.pos += 1; // Skip the newline
         }
+        if let Some((line, file, system_header)) = parse_line_marker(directive) {
+            self.line_markers.push(LineMarker { offset: self.pos, line, file, system_header });
+        }
+        parse_pack_pragma(directive).map(Token::PragmaPack)
+    }
+
+    /// Length of a string or character literal prefix (`L`, `u`, `U`, `u8`)
+    /// at the cursor, and the encoding it selects, if one starts a literal.
+    fn literal_prefix(&self) -> Option<(usize, StringEncoding)> {
+        let (len, encoding) = match (self.current_char(), self.peek(1)) {
+            ('u', Some('8')) => (2, StringEncoding::Utf8),
+            ('L', _) => (1, StringEncoding::Wide),
+            ('u', _) => (1, StringEncoding::Utf16),
+            ('U', _) => (1, StringEncoding::Utf32),
+            _ => return None,
+        };
+        match self.peek(len) {
+            Some('"') => Some((len, encoding)),
+            // C11 has no u8 character constants
+            Some('\'') if encoding != StringEncoding::Utf8 => Some((len, encoding)),
+            _ => None,
+        }
     }
 
-    fn lex_string(&mut self) -> Result<Option<Token>, String> {
+    fn lex_string(&mut self, encoding: StringEncoding) -> Result<Option<Token>, CompileError> {
         self.pos += 1; // Skip opening quote
         let mut value = String::new();
+        // Escapes in wide literals may name units up to 32 bits
+        let max_hex_digits = if encoding.is_narrow() { 2 } else { 8 };
         
         while self.pos < self.input.len() {
             match self.current_char() {
                 '"' => {
                     self.pos += 1;
-                    return Ok(Some(Token::StringLiteral { value }));
+                    return Ok(Some(Token::StringLiteral { value, encoding }));
                 }
                 '\\' => {
                     self.pos += 1;
                     if let Some(ch) = self.peek(0) {
-                        match ch {
-                            '"' => { self.pos += 1; value.push('"'); }
-                            '\\' => { self.pos += 1; value.push('\\'); }
-                            'n' => { self.pos += 1; value.push('\n'); }
-                            't' => { self.pos += 1; value.push('\t'); }
-                            'r' => { self.pos += 1; value.push('\r'); }
-                            '0' => { self.pos += 1; value.push('\0'); }
-                            'a' => { self.pos += 1; value.push('\x07'); }
-                            'b' => { self.pos += 1; value.push('\x08'); }
-                            'f' => { self.pos += 1; value.push('\x0C'); }
-                            'v' => { self.pos += 1; value.push('\x0B'); }
+                        let code = match ch {
+                            'n' => { self.pos += 1; u32::from('\n') }
+                            't' => { self.pos += 1; u32::from('\t') }
+                            'r' => { self.pos += 1; u32::from('\r') }
+                            'a' => { self.pos += 1; 0x07 }
+                            'b' => { self.pos += 1; 0x08 }
+                            'f' => { self.pos += 1; 0x0C }
+                            'v' => { self.pos += 1; 0x0B }
+                            'u' | 'U' => {
+                                // Universal character name: UTF-8 bytes in
+                                // narrow strings, one code point in wide ones
+                                let ch = self.lex_ucn()?;
+                                if encoding.is_narrow() {
+                                    value.extend(ch.encode_utf8(&mut [0; 4]).bytes().map(char::from));
+                                } else {
+                                    value.push(ch);
+                                }
+                                continue;
+                            }
                             'x' => {
                                 // Hexadecimal escape \xHH
                                 self.pos += 1;
                                 let hex_start = self.pos;
-                                while self.pos < self.input.len() && self.pos - hex_start < 2 {
+                                while self.pos < self.input.len() && self.pos - hex_start < max_hex_digits {
                                     if self.current_char().is_ascii_hexdigit() {
                                         self.pos += 1;
                                     } else {
                                         break;
                                     }
                                 }
-                                if self.pos > hex_start {
-                                    let hex_str = std::str::from_utf8(&self.input[hex_start..self.pos])
-                                        .map_err(|_| "Invalid UTF-8 in hex escape")?;
-                                    let code = u8::from_str_radix(hex_str, 16)
-                                        .map_err(|_| format!("Invalid hex escape: \\x{}", hex_str))?;
-                                    value.push(code as char);
+                                if self.pos == hex_start {
+                                    continue;
                                 }
+                                let hex_str = std::str::from_utf8(&self.input[hex_start..self.pos])
+                                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, "Invalid UTF-8 in hex escape"))?;
+                                u32::from_str_radix(hex_str, 16)
+                                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, format!("Invalid hex escape: \\x{}", hex_str)))?
                             }
                             '0'..='7' => {
                                 // Octal escape \ooo
@@ -238,33 +418,78 @@ impl<'a> StateMachineLexer<'a> {
                                     }
                                 }
                                 let octal_str = std::str::from_utf8(&self.input[octal_start..self.pos])
-                                    .map_err(|_| "Invalid UTF-8 in octal escape")?;
-                                let code = u8::from_str_radix(octal_str, 8)
-                                    .map_err(|_| format!("Invalid octal escape: \\{}", octal_str))?;
-                                value.push(code as char);
+                                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, "Invalid UTF-8 in octal escape"))?;
+                                let code = u32::from_str_radix(octal_str, 8)
+                                    .map_err(|_| CompileError::new(ErrorCode::InvalidEscape, format!("Invalid octal escape: \\{}", octal_str)))?;
+                                if encoding.is_narrow() && code > 0xFF {
+                                    return Err(CompileError::new(ErrorCode::InvalidEscape, format!("Invalid octal escape: \\{}", octal_str)));
+                                }
+                                code
                             }
                             _ => {
+                                // '"', '\\', '\'', '?', and unknown escapes stand for themselves
                                 self.pos += 1;
-                                value.push(ch);
+                                u32::from(ch)
                             }
-                        }
+                        };
+                        push_unit(&mut value, code, encoding)?;
                     }
                 }
-                ch => {
+                _ if encoding.is_narrow() => {
+                    // Narrow strings keep the source bytes, one per char
+                    value.push(self.current_char());
                     self.pos += 1;
+                }
+                _ => {
+                    let ch = self.current_utf8_char();
+                    self.pos += ch.len_utf8();
                     value.push(ch);
                 }
             }
         }
         
-        Err("Unterminated string literal".to_string())
+        Err(CompileError::new(ErrorCode::Unterminated, "Unterminated string literal"))
     }
 
-    fn lex_char(&mut self) -> Result<Option<Token>, String> {
+    /// Read the `u`/`U` and hex digits of a universal character name; the
+    /// backslash has been consumed.
+    fn lex_ucn(&mut self) -> Result<char, CompileError> {
+        let len = if self.current_char() == 'U' { 8 } else { 4 };
+        self.pos += 1;
+        let digits_end = (self.pos + len).min(self.input.len());
+        let digits = std::str::from_utf8(&self.input[self.pos..digits_end]).unwrap_or("");
+        let ch = ucn_char(digits)?;
+        self.pos = digits_end;
+        Ok(ch)
+    }
+
+    /// Whether the cursor is on a non-ASCII character that may appear in an
+    /// identifier, as GCC and Clang accept in UTF-8 source.
+    fn at_extended_char(&self) -> bool {
+        let c = self.current_utf8_char();
+        !c.is_ascii() && !c.is_whitespace()
+    }
+
+    /// The whole UTF-8 character at the cursor (the input came from a `&str`).
+    fn current_utf8_char(&self) -> char {
+        let len = match self.input[self.pos] {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        let end = (self.pos + len).min(self.input.len());
+        std::str::from_utf8(&self.input[self.pos..end])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    fn lex_char(&mut self, encoding: StringEncoding) -> Result<Option<Token>, CompileError> {
         self.pos += 1; // Skip opening quote
         
         if self.pos >= self.input.len() {
-            return Err("Unterminated character literal".to_string());
+            return Err(CompileError::new(ErrorCode::Unterminated, "Unterminated character literal"));
         }
 
         let content_start = self.pos;
@@ -300,14 +525,23 @@ impl<'a> StateMachineLexer<'a> {
         }
         
         if self.pos >= self.input.len() || self.current_char() != '\'' {
-            return Err("Unterminated character literal".to_string());
+            return Err(CompileError::new(ErrorCode::Unterminated, "Unterminated character literal"));
         }
         
         let content = std::str::from_utf8(&self.input[content_start..self.pos])
             .expect("Invalid UTF-8 in char literal");
         
-        // Check if this is a multi-character constant
-        let value = if content.len() > 1 && !content.starts_with('\\') {
+        let is_ucn = content.starts_with("\\u") || content.starts_with("\\U");
+        let value = if encoding.is_narrow() && is_ucn {
+            // A UCN names a character, whose UTF-8 bytes pack like 'ab'
+            let code = parse_char_literal(content)?;
+            let ch = char::from_u32(code as u32).expect("validated by ucn_char");
+            ch.encode_utf8(&mut [0; 4]).bytes().fold(0, |acc, b| (acc << 8) | i64::from(b))
+        } else if !encoding.is_narrow() && !content.starts_with('\\') {
+            // Wide constant: the code point of the character (the last one
+            // of several, as GCC does)
+            content.chars().last().map_or(0, |c| i64::from(u32::from(c)))
+        } else if content.len() > 1 && !content.starts_with('\\') {
             // Multi-character constant: pack bytes big-endian (GCC-compatible)
             let mut result: i64 = 0;
             for byte in content.bytes() {
@@ -319,10 +553,12 @@ impl<'a> StateMachineLexer<'a> {
         };
         
         self.pos += 1; // Skip closing quote
-        Ok(Some(Token::Constant { value, suffix: IntegerSuffix::None }))
+        // char32_t constants are unsigned int; the others fit in int
+        let suffix = if encoding == StringEncoding::Utf32 { IntegerSuffix::U } else { IntegerSuffix::None };
+        Ok(Some(Token::Constant { value, suffix }))
     }
 
-    fn lex_number(&mut self) -> Result<Option<Token>, String> {
+    fn lex_number(&mut self) -> Result<Option<Token>, CompileError> {
         // Check for hexadecimal
         if self.current_char() == '0' && matches!(self.peek(1), Some('x') | Some('X')) {
             return self.lex_hex_number();
@@ -380,6 +616,16 @@ impl<'a> StateMachineLexer<'a> {
         let text = std::str::from_utf8(&self.input[start..self.pos])
             .expect("Invalid UTF-8 in number");
 
+        if let Some(len) = imaginary_suffix_len(self.input, self.pos) {
+            self.pos += len;
+            let value = if has_dot || has_exp {
+                parse_float_literal(text)?
+            } else {
+                parse_int_constant(text)? as f64
+            };
+            return Ok(Some(Token::ImaginaryLiteral { value }));
+        }
+
         if has_dot || has_exp {
             let value = parse_float_literal(text)?;
             Ok(Some(Token::FloatLiteral { value }))
@@ -390,7 +636,7 @@ impl<'a> StateMachineLexer<'a> {
         }
     }
 
-    fn lex_octal_number(&mut self) -> Result<Option<Token>, String> {
+    fn lex_octal_number(&mut self) -> Result<Option<Token>, CompileError> {
         self.pos += 1; // Skip leading '0'
         let start = self.pos;
 
@@ -401,13 +647,13 @@ impl<'a> StateMachineLexer<'a> {
         let octal_str = std::str::from_utf8(&self.input[start..self.pos])
             .expect("Invalid UTF-8 in octal number");
         let value = i64::from_str_radix(octal_str, 8)
-            .map_err(|_| format!("Invalid octal number: 0{}", octal_str))?;
+            .map_err(|_| CompileError::new(ErrorCode::InvalidNumber, format!("Invalid octal number: 0{}", octal_str)))?;
 
         let suffix = parse_integer_suffix(self.input, &mut self.pos);
         Ok(Some(Token::Constant { value, suffix }))
     }
 
-    fn lex_binary_number(&mut self) -> Result<Option<Token>, String> {
+    fn lex_binary_number(&mut self) -> Result<Option<Token>, CompileError> {
         self.pos += 2; // Skip '0b' or '0B'
         let start = self.pos;
 
@@ -416,19 +662,19 @@ impl<'a> StateMachineLexer<'a> {
         }
 
         if self.pos == start {
-            return Err("Invalid binary number: no digits after 0b".to_string());
+            return Err(CompileError::new(ErrorCode::InvalidNumber, "Invalid binary number: no digits after 0b"));
         }
 
         let bin_str = std::str::from_utf8(&self.input[start..self.pos])
             .expect("Invalid UTF-8 in binary number");
         let value = i64::from_str_radix(bin_str, 2)
-            .map_err(|_| format!("Invalid binary number: 0b{}", bin_str))?;
+            .map_err(|_| CompileError::new(ErrorCode::InvalidNumber, format!("Invalid binary number: 0b{}", bin_str)))?;
 
         let suffix = parse_integer_suffix(self.input, &mut self.pos);
         Ok(Some(Token::Constant { value, suffix }))
     }
 
-    fn lex_hex_number(&mut self) -> Result<Option<Token>, String> {
+    fn lex_hex_number(&mut self) -> Result<Option<Token>, CompileError> {
         self.pos += 2; // Skip 0x
         let start = self.pos;
 
@@ -440,7 +686,7 @@ impl<'a> StateMachineLexer<'a> {
         }
 
         if self.pos == start {
-            return Err("Invalid hexadecimal number: no digits after 0x".to_string());
+            return Err(CompileError::new(ErrorCode::InvalidNumber, "Invalid hexadecimal number: no digits after 0x"));
         }
 
         let text = std::str::from_utf8(&self.input[self.token_start..self.pos])
@@ -450,19 +696,36 @@ impl<'a> StateMachineLexer<'a> {
         Ok(Some(Token::Constant { value, suffix }))
     }
 
-    fn lex_identifier(&mut self) -> Result<Option<Token>, String> {
+    fn lex_identifier(&mut self) -> Result<Option<Token>, CompileError> {
+        // Spelling with UCNs decoded, built only once one is seen
+        let mut decoded: Option<String> = None;
+        let mut chunk_start = self.pos;
         while self.pos < self.input.len() {
             match self.current_char() {
                 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => self.pos += 1,
+                _ if self.at_extended_char() => self.pos += self.current_utf8_char().len_utf8(),
+                '\\' if matches!(self.peek(1), Some('u' | 'U')) => {
+                    let name = decoded.get_or_insert_with(String::new);
+                    name.push_str(std::str::from_utf8(&self.input[chunk_start..self.pos]).expect("identifier is UTF-8"));
+                    self.pos += 1;
+                    let ch = self.lex_ucn()?;
+                    name.push(ch);
+                    chunk_start = self.pos;
+                }
                 _ => break,
             }
         }
 
-        let text = self.current_slice();
-        Ok(Some(keyword_or_identifier(text)))
+        match decoded {
+            Some(mut name) => {
+                name.push_str(std::str::from_utf8(&self.input[chunk_start..self.pos]).expect("identifier is UTF-8"));
+                Ok(Some(keyword_or_identifier(&name)))
+            }
+            None => Ok(Some(keyword_or_identifier(self.current_slice()))),
+        }
     }
 
-    fn lex_operator_or_punctuation(&mut self) -> Result<Option<Token>, String> {
+    fn lex_operator_or_punctuation(&mut self) -> Result<Option<Token>, CompileError> {
         let ch = self.current_char();
         let next = self.peek(1);
 
@@ -494,6 +757,12 @@ impl<'a> StateMachineLexer<'a> {
             ('&', Some('=')) => Some(Token::AndEqual),
             ('|', Some('=')) => Some(Token::OrEqual),
             ('^', Some('=')) => Some(Token::XorEqual),
+            // Digraphs
+            ('<', Some('%')) => Some(Token::OpenBrace),
+            ('%', Some('>')) => Some(Token::CloseBrace),
+            ('<', Some(':')) => Some(Token::OpenBracket),
+            (':', Some('>')) => Some(Token::CloseBracket),
+            ('%', Some(':')) => Some(Token::Hash),
             _ => None,
         };
 
@@ -539,7 +808,7 @@ impl<'a> StateMachineLexer<'a> {
             '!' => Token::Bang,
             '|' => Token::Pipe,
             '^' => Token::Caret,
-            _ => return Err(format!("Unexpected character: '{}'", ch)),
+            _ => return Err(CompileError::new(ErrorCode::UnexpectedCharacter, format!("Unexpected character: '{}'", ch))),
         };
 
         Ok(Some(token))
@@ -573,6 +842,18 @@ mod tests {
         assert!(matches!(tokens[3], Token::FloatLiteral { .. }));
     }
 
+    #[test]
+    fn test_state_machine_imaginary() {
+        let input = "z = 1.0iF + 2i; _Complex double w; __real__ w;";
+        let mut lexer = StateMachineLexer::new(input);
+        let tokens = lexer.tokenize().expect("Should tokenize");
+
+        assert_eq!(tokens[2], Token::ImaginaryLiteral { value: 1.0 });
+        assert_eq!(tokens[4], Token::ImaginaryLiteral { value: 2.0 });
+        assert_eq!(tokens[6], Token::Complex);
+        assert_eq!(tokens[10], Token::RealPart);
+    }
+
     #[test]
     fn test_state_machine_hex() {
         let input = "int x = 0xFF;";
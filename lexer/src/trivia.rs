@@ -0,0 +1,109 @@
+use model::{CompileError, FileId, Span, Spanned, Token};
+use crate::state_machine::StateMachineLexer;
+
+/// `// ...` or `/* ... */`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,
+    Block,
+}
+
+/// A comment kept by `lex_with_comments`. `text` includes the delimiters
+/// but not the newline ending a line comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub kind: CommentKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// A token with the comments attached to it. A comment that starts on the
+/// line where a token ends is trailing trivia of that token; any other
+/// comment leads the next token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriviaToken {
+    pub token: Spanned<Token>,
+    pub leading: Vec<Comment>,
+    pub trailing: Vec<Comment>,
+}
+
+/// Output of `lex_with_comments`: the tokens, and the comments after the
+/// last token that have no following token to lead.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommentedTokens {
+    pub tokens: Vec<TriviaToken>,
+    pub end_comments: Vec<Comment>,
+}
+
+impl CommentedTokens {
+    /// The bare token stream, as `lex_spanned` returns it.
+    pub fn spanned_tokens(&self) -> Vec<Spanned<Token>> {
+        self.tokens.iter().map(|t| t.token.clone()).collect()
+    }
+}
+
+/// Lex `input` as file `file_id` like `lex_spanned`, keeping comments as
+/// trivia on the neighboring tokens for formatters and doc tools.
+pub fn lex_with_comments(input: &str, file_id: FileId) -> Result<CommentedTokens, CompileError> {
+    let mut lexer = StateMachineLexer::new(input).keep_comments();
+    let tokens = lexer.tokenize_spanned(file_id)?;
+    let comments = lexer.comments.take().unwrap_or_default();
+
+    let mut out = CommentedTokens {
+        tokens: tokens.into_iter().map(|token| TriviaToken { token, leading: Vec::new(), trailing: Vec::new() }).collect(),
+        end_comments: Vec::new(),
+    };
+    // Index of the first token starting after the current comment
+    let mut next = 0;
+    for (kind, start, end) in comments {
+        while next < out.tokens.len() && out.tokens[next].token.span.start < start {
+            next += 1;
+        }
+        let comment = Comment { kind, text: input[start..end].to_string(), span: Span::new(file_id, start, end) };
+        let same_line = next > 0 && {
+            let previous_end = out.tokens[next - 1].token.span.end;
+            !input[previous_end..start].contains('\n')
+        };
+        if same_line {
+            out.tokens[next - 1].trailing.push(comment);
+        } else if let Some(token) = out.tokens.get_mut(next) {
+            token.leading.push(comment);
+        } else {
+            out.end_comments.push(comment);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(comments: &[Comment]) -> Vec<&str> {
+        comments.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn comments_attach_to_neighbors() {
+        let src = "/* header */\n// doc\nint x; // trailing\n/* a */ int /* b */ y;\n// end\n";
+        let lexed = lex_with_comments(src, FileId(0)).unwrap();
+        let tokens = &lexed.tokens;
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(texts(&tokens[0].leading), vec!["/* header */", "// doc"]);
+        assert_eq!(tokens[0].leading[1].kind, CommentKind::Line);
+        assert_eq!(texts(&tokens[2].trailing), vec!["// trailing"]);
+        // `/* a */` is on a new line, so it leads `int`; `/* b */` follows `int` on its line
+        assert_eq!(texts(&tokens[3].leading), vec!["/* a */"]);
+        assert_eq!(texts(&tokens[3].trailing), vec!["/* b */"]);
+        assert!(tokens[4].leading.is_empty());
+        assert_eq!(texts(&lexed.end_comments), vec!["// end"]);
+        assert_eq!(lexed.end_comments[0].span, Span::new(FileId(0), src.len() - 7, src.len() - 1));
+    }
+
+    #[test]
+    fn token_stream_matches_plain_lexing() {
+        let src = "int /* c */ main() { return 0; } // done";
+        let lexed = lex_with_comments(src, FileId(0)).unwrap();
+        assert_eq!(lexed.spanned_tokens(), crate::lex_spanned(src, FileId(0)).unwrap());
+    }
+}
@@ -0,0 +1,567 @@
+//! Constant-expression evaluation (C11 §6.6).
+//!
+//! One evaluator serves every place C requires a constant: array sizes,
+//! bit-field widths, enumerator values, `case` labels, `_Static_assert`, and
+//! static initializers. What names and types mean comes from a
+//! `ConstContext`, so the parser can evaluate with what it has seen so far
+//! while semantic analysis and lowering use the full struct layouts.
+//!
+//! Integer results are converted to their C type as they are computed:
+//! `(unsigned char)300` is 44 and `-1 < 0u` is 0. Signed overflow and shifts
+//! past the width of the type still produce the truncated value, but
+//! `eval_constant_with_warnings` also reports them.
+
+use crate::{BinaryOp, CompileError, ErrorCode, Expr, Type, TypeEnv, UnaryOp};
+use std::collections::HashMap;
+
+/// What the evaluator needs to know about names and types.
+pub trait ConstContext {
+    /// Value of the enumeration constant `name`, if it is one.
+    fn enum_constant(&self, name: &str) -> Option<i64>;
+
+    /// `sizeof(ty)`, or `None` when the size is not known.
+    fn size_of(&self, ty: &Type) -> Option<usize>;
+
+    /// `_Alignof(ty)`, or `None` when the alignment is not known.
+    fn align_of(&self, ty: &Type) -> Option<usize>;
+
+    /// The type `ty` names, with typedefs expanded.
+    fn resolve_type(&self, ty: &Type) -> Type {
+        ty.clone()
+    }
+
+    /// Type of the operand of `sizeof expr`; `None` when unknown. Only a
+    /// string literal's is known without the program's declarations.
+    fn type_of(&self, expr: &Expr) -> Option<Type> {
+        TypeEnv::string_literal_type(expr)
+    }
+
+    /// Byte offset of `member` in struct `ty`, for `offsetof`.
+    fn offset_of(&self, _ty: &Type, _member: &str) -> Option<usize> {
+        None
+    }
+}
+
+/// The value of a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    /// An integer, already converted to its type.
+    Int(i64, Type),
+    Float(f64),
+}
+
+impl ConstValue {
+    pub fn is_zero(&self) -> bool {
+        match self {
+            ConstValue::Int(v, _) => *v == 0,
+            ConstValue::Float(f) => *f == 0.0,
+        }
+    }
+
+    /// The value converted to an integer, truncating a float.
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            ConstValue::Int(v, _) => *v,
+            ConstValue::Float(f) => *f as i64,
+        }
+    }
+}
+
+/// Evaluate an integer constant expression: array sizes, widths, enumerator
+/// values, `case` labels, and `_Static_assert` conditions. Floating
+/// constants may only appear as operands of casts to integer types.
+pub fn eval_integer_constant(expr: &Expr, ctx: &dyn ConstContext) -> Result<i64, CompileError> {
+    match eval_constant(expr, ctx)? {
+        ConstValue::Int(v, _) => Ok(v),
+        ConstValue::Float(_) => Err(not_constant("expression is not an integer constant expression")),
+    }
+}
+
+/// Evaluate an arithmetic constant expression, as allowed in the
+/// initializer of a static object.
+pub fn eval_constant(expr: &Expr, ctx: &dyn ConstContext) -> Result<ConstValue, CompileError> {
+    eval(expr, ctx, &mut Vec::new())
+}
+
+/// Like `eval_constant`, also returning a warning for each signed overflow
+/// or over-wide shift met along the way.
+pub fn eval_constant_with_warnings(
+    expr: &Expr,
+    ctx: &dyn ConstContext,
+) -> Result<(ConstValue, Vec<CompileError>), CompileError> {
+    let mut warnings = Vec::new();
+    let value = eval(expr, ctx, &mut warnings)?;
+    Ok((value, warnings))
+}
+
+/// The warning for converting the integer constant `value` to integer type
+/// `to`, when the conversion changes it: `char c = 300;` becomes 44. Negative
+/// values converted to an unsigned type of the same width, as in
+/// `unsigned x = -1;`, are a common idiom and not reported.
+pub fn conversion_warning(value: &ConstValue, to: &Type, ctx: &dyn ConstContext) -> Option<CompileError> {
+    let ConstValue::Int(v, from) = value else { return None };
+    let to = ctx.resolve_type(to);
+    // The width of an enum depends on its enumerators
+    if !TypeEnv::is_integer_type(&to) || matches!(to, Type::Bool | Type::Enum(_)) {
+        return None;
+    }
+    let converted = wide_value(int_value(*v, &to, ctx).as_i64(), &to);
+    let original = wide_value(*v, from);
+    if converted == original {
+        return None;
+    }
+    let bits = int_bits(&to, ctx);
+    if TypeEnv::is_unsigned_integer(&to) && fits_signed(original, bits) {
+        return None;
+    }
+    let kind = if TypeEnv::is_unsigned_integer(&to) { "unsigned conversion" } else { "overflow in conversion" };
+    Some(overflow_warning(format!(
+        "{} from '{}' to '{}' changes value from '{}' to '{}'",
+        kind, from, to, original, converted
+    )))
+}
+
+fn eval(expr: &Expr, ctx: &dyn ConstContext, warnings: &mut Vec<CompileError>) -> Result<ConstValue, CompileError> {
+    Ok(match expr {
+        Expr::Constant(v) => {
+            let ty = if i32::try_from(*v).is_ok() { Type::Int } else { Type::Long };
+            ConstValue::Int(*v, ty)
+        }
+        Expr::TypedConstant(v, ty) => int_value(*v, ty, ctx),
+        Expr::FloatConstant(f) => ConstValue::Float(*f),
+        Expr::Variable(name) => match ctx.enum_constant(name) {
+            Some(v) => ConstValue::Int(v, Type::Int),
+            None => return Err(not_constant(format!("'{}' is not a constant", name))),
+        },
+        Expr::SizeOf(ty) => size_value("sizeof", ctx.size_of(&ctx.resolve_type(ty)), ty, ctx)?,
+        Expr::SizeOfExpr(inner) => match ctx.type_of(inner) {
+            Some(ty) => size_value("sizeof", ctx.size_of(&ty), &ty, ctx)?,
+            None => return Err(not_constant("operand of 'sizeof' has unknown type")),
+        },
+        Expr::AlignOf(ty) => size_value("_Alignof", ctx.align_of(&ctx.resolve_type(ty)), ty, ctx)?,
+        Expr::BuiltinOffsetof { r#type, member } => match ctx.offset_of(&ctx.resolve_type(r#type), member) {
+            Some(offset) => ConstValue::Int(offset as i64, Type::UnsignedLong),
+            None => return Err(not_constant(format!("offset of '{}' in '{}' is not known", member, r#type))),
+        },
+        Expr::Cast(ty, inner) => convert(eval(inner, ctx, warnings)?, &ctx.resolve_type(ty), ctx)?,
+        Expr::Expect { expr, .. } => eval(expr, ctx, warnings)?,
+        Expr::Unary { op, expr } => unary(op, eval(expr, ctx, warnings)?, ctx, warnings)?,
+        Expr::Binary { left, op: op @ (BinaryOp::LogicalAnd | BinaryOp::LogicalOr), right } => {
+            // The right operand is only evaluated when it decides the result
+            let l = !eval(left, ctx, warnings)?.is_zero();
+            let value = match (op, l) {
+                (BinaryOp::LogicalAnd, false) => false,
+                (BinaryOp::LogicalOr, true) => true,
+                _ => !eval(right, ctx, warnings)?.is_zero(),
+            };
+            ConstValue::Int(i64::from(value), Type::Int)
+        }
+        Expr::Binary { left, op, right } => binary(op, eval(left, ctx, warnings)?, eval(right, ctx, warnings)?, ctx, warnings)?,
+        Expr::Conditional { condition, then_expr, else_expr } => {
+            if eval(condition, ctx, warnings)?.is_zero() {
+                eval(else_expr, ctx, warnings)?
+            } else {
+                eval(then_expr, ctx, warnings)?
+            }
+        }
+        Expr::Call { .. } => return Err(not_constant("function call in constant expression")),
+        Expr::Comma(_) => return Err(not_constant("comma operator in constant expression")),
+        Expr::PostfixIncrement(_) | Expr::PostfixDecrement(_) | Expr::PrefixIncrement(_) | Expr::PrefixDecrement(_) => {
+            return Err(not_constant("increment or decrement in constant expression"))
+        }
+        _ => return Err(not_constant("expression is not constant")),
+    })
+}
+
+fn not_constant(message: impl Into<String>) -> CompileError {
+    CompileError::new(ErrorCode::NotConstant, message)
+}
+
+fn overflow_warning(message: impl Into<String>) -> CompileError {
+    CompileError::warning(ErrorCode::ConstantOverflow, message)
+}
+
+/// `v` as a mathematical integer, reading it as unsigned for unsigned `ty`.
+fn wide_value(v: i64, ty: &Type) -> i128 {
+    if TypeEnv::is_unsigned_integer(ty) { i128::from(v as u64) } else { i128::from(v) }
+}
+
+/// Whether `v` is representable in a signed integer of `bits` bits.
+fn fits_signed(v: i128, bits: u32) -> bool {
+    let max = (1i128 << (bits - 1)) - 1;
+    (-max - 1..=max).contains(&v)
+}
+
+/// `v` truncated to signed type `ty`, with a warning when that changed it.
+fn signed_result(v: i128, ty: &Type, ctx: &dyn ConstContext, warnings: &mut Vec<CompileError>) -> ConstValue {
+    let value = int_value(v as i64, ty, ctx);
+    if i128::from(value.as_i64()) != v {
+        warnings.push(overflow_warning(format!(
+            "integer overflow in expression of type '{}' results in '{}'",
+            ty,
+            value.as_i64()
+        )));
+    }
+    value
+}
+
+fn size_value(op: &str, size: Option<usize>, ty: &Type, ctx: &dyn ConstContext) -> Result<ConstValue, CompileError> {
+    match size {
+        Some(size) => Ok(ConstValue::Int(size as i64, Type::UnsignedLong)),
+        None if matches!(ctx.resolve_type(ty), Type::Void | Type::Struct(_) | Type::Union(_)) => {
+            Err(invalid_size_operand(op, ty))
+        }
+        None => Err(not_constant(format!("size of '{}' is not known", ty))),
+    }
+}
+
+/// The error for `sizeof` or `_Alignof` (`op`) applied to `ty` when it has
+/// no size: `void` or a struct or union that is only declared.
+pub fn invalid_size_operand(op: &str, ty: &Type) -> CompileError {
+    let message = match ty {
+        Type::Void => format!("invalid application of '{}' to a void type", op),
+        _ => format!("invalid application of '{}' to incomplete type '{}'", op, ty),
+    };
+    CompileError::new(ErrorCode::IncompleteType, message)
+}
+
+/// Width in bits of integer type `ty`.
+fn int_bits(ty: &Type, ctx: &dyn ConstContext) -> u32 {
+    (ctx.size_of(ty).unwrap_or(8).clamp(1, 8) * 8) as u32
+}
+
+/// `v` converted to integer type `ty` (C11 §6.3.1.3, wrapping modulo 2^N).
+fn int_value(v: i64, ty: &Type, ctx: &dyn ConstContext) -> ConstValue {
+    let ty = match ty {
+        Type::Enum(_) => Type::Int,
+        other => other.clone(),
+    };
+    let bits = int_bits(&ty, ctx);
+    let v = if ty == Type::Bool {
+        i64::from(v != 0)
+    } else if bits >= 64 {
+        v
+    } else if TypeEnv::is_unsigned_integer(&ty) {
+        v & ((1i64 << bits) - 1)
+    } else {
+        (v << (64 - bits)) >> (64 - bits)
+    };
+    ConstValue::Int(v, ty)
+}
+
+fn convert(value: ConstValue, ty: &Type, ctx: &dyn ConstContext) -> Result<ConstValue, CompileError> {
+    Ok(match (value, ty) {
+        (ConstValue::Int(v, from), Type::Float | Type::Double) => {
+            let f = if TypeEnv::is_unsigned_integer(&from) { v as u64 as f64 } else { v as f64 };
+            ConstValue::Float(if *ty == Type::Float { f64::from(f as f32) } else { f })
+        }
+        (ConstValue::Float(f), Type::Float) => ConstValue::Float(f64::from(f as f32)),
+        (value @ ConstValue::Float(_), Type::Double) => value,
+        (ConstValue::Float(f), Type::Bool) => ConstValue::Int(i64::from(f != 0.0), Type::Bool),
+        (ConstValue::Float(f), ty) if TypeEnv::is_integer_type(ty) => {
+            let v = if TypeEnv::is_unsigned_integer(ty) { f as u64 as i64 } else { f as i64 };
+            int_value(v, ty, ctx)
+        }
+        (ConstValue::Int(v, _), ty) if TypeEnv::is_integer_type(ty) => int_value(v, ty, ctx),
+        // An integer cast to a pointer, as in `(void *)0`
+        (ConstValue::Int(v, _), Type::Pointer(..)) => ConstValue::Int(v, Type::UnsignedLong),
+        (_, ty) => return Err(not_constant(format!("cast to '{}' in constant expression", ty))),
+    })
+}
+
+fn unary(
+    op: &UnaryOp,
+    value: ConstValue,
+    ctx: &dyn ConstContext,
+    warnings: &mut Vec<CompileError>,
+) -> Result<ConstValue, CompileError> {
+    Ok(match (op, value) {
+        (UnaryOp::LogicalNot, value) => ConstValue::Int(i64::from(value.is_zero()), Type::Int),
+        (UnaryOp::Plus, ConstValue::Int(v, ty)) => int_value(v, &TypeEnv::integer_promotion(&ty), ctx),
+        (UnaryOp::Minus, ConstValue::Int(v, ty)) => {
+            let ty = TypeEnv::integer_promotion(&ty);
+            if TypeEnv::is_unsigned_integer(&ty) {
+                int_value(v.wrapping_neg(), &ty, ctx)
+            } else {
+                signed_result(-i128::from(v), &ty, ctx, warnings)
+            }
+        }
+        (UnaryOp::BitwiseNot, ConstValue::Int(v, ty)) => int_value(!v, &TypeEnv::integer_promotion(&ty), ctx),
+        (UnaryOp::Plus, value @ ConstValue::Float(_)) => value,
+        (UnaryOp::Minus, ConstValue::Float(f)) => ConstValue::Float(-f),
+        (op, _) => return Err(not_constant(format!("unary '{}' in constant expression", op))),
+    })
+}
+
+fn binary(
+    op: &BinaryOp,
+    l: ConstValue,
+    r: ConstValue,
+    ctx: &dyn ConstContext,
+    warnings: &mut Vec<CompileError>,
+) -> Result<ConstValue, CompileError> {
+    let (l, lt, r, rt) = match (l, r) {
+        (ConstValue::Int(l, lt), ConstValue::Int(r, rt)) => (l, lt, r, rt),
+        (l, r) => return float_binary(op, float_of(l), float_of(r)),
+    };
+    let bool_value = |b: bool| ConstValue::Int(i64::from(b), Type::Int);
+    if matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) {
+        // The result has the promoted type of the left operand
+        let ty = TypeEnv::integer_promotion(&lt);
+        let bits = int_bits(&ty, ctx);
+        let unsigned = TypeEnv::is_unsigned_integer(&ty);
+        let count = match u32::try_from(r) {
+            Ok(count) if TypeEnv::is_unsigned_integer(&rt) || r >= 0 => count,
+            _ => return Err(not_constant(format!("shift count {} is negative", r))),
+        };
+        if count >= bits {
+            // Every bit is shifted out: the truncated result is 0, or -1 when
+            // shifting a negative signed value right
+            let v = if *op == BinaryOp::ShiftRight && !unsigned && l < 0 { -1 } else { 0 };
+            let direction = if *op == BinaryOp::ShiftLeft { "left" } else { "right" };
+            warnings.push(overflow_warning(format!(
+                "{} shift count {} >= width of type '{}' results in '{}'",
+                direction, count, ty, v
+            )));
+            return Ok(int_value(v, &ty, ctx));
+        }
+        let v = match op {
+            // Shifting a one into the sign bit is allowed; shifting past it is not
+            BinaryOp::ShiftLeft if !unsigned && !fits_signed(i128::from(l) << count, bits + 1) => {
+                return Ok(signed_result(i128::from(l) << count, &ty, ctx, warnings));
+            }
+            BinaryOp::ShiftLeft => l.wrapping_shl(count),
+            _ if unsigned => ((l as u64) >> count) as i64,
+            _ => l >> count,
+        };
+        return Ok(int_value(v, &ty, ctx));
+    }
+
+    let ty = TypeEnv::usual_arithmetic_conversions(&lt, &rt);
+    let l = int_value(l, &ty, ctx).as_i64();
+    let r = int_value(r, &ty, ctx).as_i64();
+    let unsigned = TypeEnv::is_unsigned_integer(&ty);
+    let exact = match op {
+        BinaryOp::Add => Some(i128::from(l) + i128::from(r)),
+        BinaryOp::Sub => Some(i128::from(l) - i128::from(r)),
+        BinaryOp::Mul => Some(i128::from(l) * i128::from(r)),
+        BinaryOp::Div if r != 0 => Some(i128::from(l) / i128::from(r)),
+        _ => None,
+    };
+    if let (Some(exact), false) = (exact, unsigned) {
+        return Ok(signed_result(exact, &ty, ctx, warnings));
+    }
+    let v = match op {
+        BinaryOp::Add => l.wrapping_add(r),
+        BinaryOp::Sub => l.wrapping_sub(r),
+        BinaryOp::Mul => l.wrapping_mul(r),
+        BinaryOp::Div | BinaryOp::Mod if r == 0 => return Err(not_constant("division by zero in constant expression")),
+        BinaryOp::Div if unsigned => ((l as u64) / (r as u64)) as i64,
+        BinaryOp::Mod if unsigned => ((l as u64) % (r as u64)) as i64,
+        BinaryOp::Div => l.wrapping_div(r),
+        BinaryOp::Mod => l.wrapping_rem(r),
+        BinaryOp::BitwiseAnd => l & r,
+        BinaryOp::BitwiseOr => l | r,
+        BinaryOp::BitwiseXor => l ^ r,
+        BinaryOp::EqualEqual => return Ok(bool_value(l == r)),
+        BinaryOp::NotEqual => return Ok(bool_value(l != r)),
+        BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+            let ordering = if unsigned { (l as u64).cmp(&(r as u64)) } else { l.cmp(&r) };
+            return Ok(bool_value(match op {
+                BinaryOp::Less => ordering.is_lt(),
+                BinaryOp::LessEqual => ordering.is_le(),
+                BinaryOp::Greater => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }));
+        }
+        _ => return Err(not_constant(format!("binary '{}' in constant expression", op))),
+    };
+    Ok(int_value(v, &ty, ctx))
+}
+
+fn float_of(value: ConstValue) -> f64 {
+    match value {
+        ConstValue::Int(v, ty) if TypeEnv::is_unsigned_integer(&ty) => v as u64 as f64,
+        ConstValue::Int(v, _) => v as f64,
+        ConstValue::Float(f) => f,
+    }
+}
+
+fn float_binary(op: &BinaryOp, l: f64, r: f64) -> Result<ConstValue, CompileError> {
+    let bool_value = |b: bool| ConstValue::Int(i64::from(b), Type::Int);
+    Ok(match op {
+        BinaryOp::Add => ConstValue::Float(l + r),
+        BinaryOp::Sub => ConstValue::Float(l - r),
+        BinaryOp::Mul => ConstValue::Float(l * r),
+        BinaryOp::Div => ConstValue::Float(l / r),
+        BinaryOp::EqualEqual => bool_value(l == r),
+        BinaryOp::NotEqual => bool_value(l != r),
+        BinaryOp::Less => bool_value(l < r),
+        BinaryOp::LessEqual => bool_value(l <= r),
+        BinaryOp::Greater => bool_value(l > r),
+        BinaryOp::GreaterEqual => bool_value(l >= r),
+        _ => return Err(not_constant(format!("invalid operands to binary {} in constant expression", op))),
+    })
+}
+
+impl TypeEnv {
+    /// Type of the operand of `sizeof expr`: like `expr_type`, but a string
+    /// literal is still an array.
+    pub fn sizeof_operand_type(&self, expr: &Expr, locals: &HashMap<String, Type>) -> Type {
+        TypeEnv::string_literal_type(expr).unwrap_or_else(|| self.expr_type(expr, locals))
+    }
+}
+
+/// Full knowledge of the program: struct layouts, typedefs, globals.
+impl ConstContext for TypeEnv {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.enum_constant_value(name)
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        let ty = TypeEnv::resolve_type(self, ty);
+        match ty {
+            _ if !self.is_complete_type(&ty) => None,
+            _ => Some(self.layout().size_of(&ty)),
+        }
+    }
+
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        let ty = TypeEnv::resolve_type(self, ty);
+        self.is_complete_type(&ty).then(|| self.layout().align_of(&ty))
+    }
+
+    fn resolve_type(&self, ty: &Type) -> Type {
+        TypeEnv::resolve_type(self, ty)
+    }
+
+    fn type_of(&self, expr: &Expr) -> Option<Type> {
+        Some(self.sizeof_operand_type(expr, &HashMap::new()))
+    }
+
+    fn offset_of(&self, ty: &Type, member: &str) -> Option<usize> {
+        match ty {
+            Type::Struct(name) => {
+                let def = self.structs.get(name)?;
+                let index = def.fields.iter().position(|f| f.name == member)?;
+                Some(self.layout().struct_layout(def).fields[index].offset)
+            }
+            Type::Union(name) => self.unions.get(name)?.fields.iter().any(|f| f.name == member).then_some(0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Program, StructDef, StructField};
+
+    fn env() -> TypeEnv {
+        let mut program = Program::default();
+        program.structs.push(StructDef {
+            name: "S".to_string(),
+            fields: vec![
+                StructField { field_type: Type::Char, name: "c".to_string(), bit_width: None },
+                StructField { field_type: Type::Long, name: "l".to_string(), bit_width: None },
+            ],
+            attributes: Vec::new(),
+        });
+        TypeEnv::from_program(&program)
+    }
+
+    fn int(v: i64) -> Expr {
+        Expr::Constant(v)
+    }
+
+    fn bin(l: Expr, op: BinaryOp, r: Expr) -> Expr {
+        Expr::Binary { left: Box::new(l), op, right: Box::new(r) }
+    }
+
+    #[test]
+    fn integer_arithmetic_follows_c_types() {
+        let env = env();
+        let eval = |e: &Expr| eval_integer_constant(e, &env).unwrap();
+        assert_eq!(eval(&bin(int(7), BinaryOp::Div, int(2))), 3);
+        assert_eq!(eval(&Expr::Cast(Type::UnsignedChar, Box::new(int(300)))), 44);
+        assert_eq!(eval(&Expr::Cast(Type::Char, Box::new(int(200)))), -56);
+        // -1 converts to UINT_MAX when compared with an unsigned operand
+        assert_eq!(eval(&bin(int(-1), BinaryOp::Less, Expr::TypedConstant(0, Type::UnsignedInt))), 0);
+        assert_eq!(eval(&bin(Expr::TypedConstant(-1, Type::UnsignedInt), BinaryOp::ShiftRight, int(28))), 15);
+        assert_eq!(eval(&Expr::Cast(Type::Int, Box::new(Expr::FloatConstant(2.9)))), 2);
+        let cond = Expr::Conditional {
+            condition: Box::new(bin(int(0), BinaryOp::LogicalAnd, bin(int(1), BinaryOp::Div, int(0)))),
+            then_expr: Box::new(int(1)),
+            else_expr: Box::new(int(2)),
+        };
+        assert_eq!(eval(&cond), 2);
+    }
+
+    #[test]
+    fn sizes_and_offsets_use_layouts() {
+        let env = env();
+        let eval = |e: &Expr| eval_integer_constant(e, &env).unwrap();
+        assert_eq!(eval(&Expr::SizeOf(Type::Struct("S".to_string()))), 16);
+        assert_eq!(eval(&Expr::AlignOf(Type::Array(Box::new(Type::Long), 3))), 8);
+        let offset = Expr::BuiltinOffsetof { r#type: Type::Struct("S".to_string()), member: "l".to_string() };
+        assert_eq!(eval(&offset), 8);
+    }
+
+    #[test]
+    fn non_constants_are_rejected() {
+        let env = env();
+        for expr in [
+            Expr::Variable("x".to_string()),
+            bin(int(1), BinaryOp::Mod, int(0)),
+            bin(int(1), BinaryOp::ShiftLeft, int(-1)),
+            Expr::Call { func: Box::new(Expr::Variable("f".to_string())), args: Vec::new() },
+            Expr::FloatConstant(1.5),
+        ] {
+            let err = eval_integer_constant(&expr, &env).unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotConstant, "{:?}", expr);
+        }
+        let value = eval_constant(&bin(Expr::FloatConstant(1.5), BinaryOp::Mul, int(2)), &env).unwrap();
+        assert_eq!(value, ConstValue::Float(3.0));
+    }
+
+    #[test]
+    fn overflow_is_truncated_with_a_warning() {
+        let env = env();
+        let eval = |e: &Expr| {
+            let (value, warnings) = eval_constant_with_warnings(e, &env).unwrap();
+            let messages: Vec<String> = warnings.into_iter().map(|w| w.message).collect();
+            (value.as_i64(), messages)
+        };
+        let (v, warnings) = eval(&bin(int(2147483647), BinaryOp::Add, int(1)));
+        assert_eq!(v, -2147483648);
+        assert_eq!(warnings, ["integer overflow in expression of type 'int' results in '-2147483648'"]);
+        assert_eq!(eval(&bin(int(65536), BinaryOp::Mul, int(65536))).0, 0);
+        assert_eq!(eval(&bin(int(1), BinaryOp::ShiftLeft, int(40))).1, [
+            "left shift count 40 >= width of type 'int' results in '0'"
+        ]);
+        assert_eq!(eval(&bin(int(3), BinaryOp::ShiftLeft, int(31))).1.len(), 1);
+        // No warning: unsigned arithmetic wraps, and 1 << 31 only reaches the sign bit
+        let uint_max = Expr::TypedConstant(-1, Type::UnsignedInt);
+        assert_eq!(eval(&bin(uint_max, BinaryOp::Add, int(1))), (0, Vec::new()));
+        assert_eq!(eval(&bin(int(1), BinaryOp::ShiftLeft, int(31))), (-2147483648, Vec::new()));
+        assert!(eval(&bin(int(1), BinaryOp::Add, int(1))).1.is_empty());
+    }
+
+    #[test]
+    fn conversions_that_change_constants_warn() {
+        let env = env();
+        let warning = |v: i64, to: Type| conversion_warning(&ConstValue::Int(v, Type::Int), &to, &env).map(|w| w.message);
+        assert_eq!(
+            warning(300, Type::Char).unwrap(),
+            "overflow in conversion from 'int' to 'char' changes value from '300' to '44'"
+        );
+        assert_eq!(
+            warning(300, Type::UnsignedChar).unwrap(),
+            "unsigned conversion from 'int' to 'unsigned char' changes value from '300' to '44'"
+        );
+        assert_eq!(warning(-1, Type::UnsignedInt), None);
+        assert_eq!(warning(127, Type::Char), None);
+        assert_eq!(warning(300, Type::Long), None);
+        assert_eq!(warning(2, Type::Bool), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! C-syntax rendering of types and operators for diagnostics.
+//!
+//! `Type` implements `Display` as an abstract declarator (`int (*)(char *, int)`),
+//! and `Type::declaration` wraps a name in the declarator (`int (*fp)(char *, int)`).
+//! `BinaryOp` and `UnaryOp` display as their C tokens.
+
+use crate::{BinaryOp, Type, TypeQualifiers, UnaryOp};
+use std::fmt;
+
+impl fmt::Display for TypeQualifiers {
+    /// Space-separated qualifier keywords, e.g. `const volatile`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+        ];
+        let mut first = true;
+        for (_, word) in words.iter().filter(|(set, _)| *set) {
+            if !first {
+                f.write_str(" ")?;
+            }
+            f.write_str(word)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.declaration(""))
+    }
+}
+
+impl Type {
+    /// Render a declaration of `name` with this type, e.g. `char *argv[]`.
+    /// An empty name gives the abstract type name used by `Display`.
+    pub fn declaration(&self, name: &str) -> String {
+        render(self, &TypeQualifiers::default(), name.to_string())
+    }
+
+    /// Like `declaration`, with `quals` on the declared object itself:
+    /// `const int x`, or `char *const p` for a pointer.
+    pub fn qualified_declaration(&self, quals: &TypeQualifiers, name: &str) -> String {
+        render(self, quals, name.to_string())
+    }
+}
+
+fn is_qualified(quals: &TypeQualifiers) -> bool {
+    quals.is_const || quals.is_volatile || quals.is_restrict
+}
+
+/// Render `ty` (qualified by `quals`) around the declarator built so far.
+/// Derived types are peeled from the outside in, growing the declarator the
+/// way C reads it: pointers prepend `*`, arrays and functions append a suffix,
+/// and a pointer under a suffix gets parenthesized.
+fn render(ty: &Type, quals: &TypeQualifiers, declarator: String) -> String {
+    match ty {
+        Type::Pointer(pointee, pointee_quals) => {
+            let star = if !is_qualified(quals) {
+                format!("*{}", declarator)
+            } else if declarator.is_empty() {
+                format!("*{}", quals)
+            } else {
+                format!("*{} {}", quals, declarator)
+            };
+            render(pointee, pointee_quals, star)
+        }
+        Type::Array(elem, size) => {
+            let size = if *size == 0 { String::new() } else { size.to_string() };
+            render(elem, quals, format!("{}[{}]", parenthesize(declarator), size))
+        }
+        Type::FunctionPointer { return_type, param_types, is_variadic } => {
+            let pointer = match (is_qualified(quals), declarator.is_empty()) {
+                (false, _) => format!("(*{})", declarator),
+                (true, true) => format!("(*{})", quals),
+                (true, false) => format!("(*{} {})", quals, declarator),
+            };
+            let mut params: Vec<String> = param_types.iter().map(|p| p.to_string()).collect();
+            if *is_variadic {
+                params.push("...".to_string());
+            } else if params.is_empty() {
+                params.push("void".to_string());
+            }
+            render(return_type, &TypeQualifiers::default(), format!("{}({})", pointer, params.join(", ")))
+        }
+        _ => {
+            let base = base_name(ty);
+            let base = if is_qualified(quals) { format!("{} {}", quals, base) } else { base };
+            if declarator.is_empty() { base } else { format!("{} {}", base, declarator) }
+        }
+    }
+}
+
+/// `*p` must bind tighter than a following `[]` or `()`.
+fn parenthesize(declarator: String) -> String {
+    if declarator.starts_with('*') {
+        format!("({})", declarator)
+    } else {
+        declarator
+    }
+}
+
+fn base_name(ty: &Type) -> String {
+    match ty {
+        Type::Int => "int".to_string(),
+        Type::UnsignedInt => "unsigned int".to_string(),
+        Type::Char => "char".to_string(),
+        Type::UnsignedChar => "unsigned char".to_string(),
+        Type::Short => "short".to_string(),
+        Type::UnsignedShort => "unsigned short".to_string(),
+        Type::Long => "long".to_string(),
+        Type::UnsignedLong => "unsigned long".to_string(),
+        Type::LongLong => "long long".to_string(),
+        Type::UnsignedLongLong => "unsigned long long".to_string(),
+        Type::Void => "void".to_string(),
+        Type::Float => "float".to_string(),
+        Type::Double => "double".to_string(),
+        Type::Bool => "_Bool".to_string(),
+        Type::Struct(name) => format!("struct {}", name),
+        Type::Union(name) => format!("union {}", name),
+        Type::Enum(name) => format!("enum {}", name),
+        Type::Typedef(name) => name.clone(),
+        Type::TypeofExpr(_) => "typeof(...)".to_string(),
+        Type::Complex(elem) => format!("_Complex {}", base_name(elem)),
+        Type::Pointer(..) | Type::Array(..) | Type::FunctionPointer { .. } => unreachable!("derived types are rendered by render()"),
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::EqualEqual => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::LogicalOr => "||",
+            BinaryOp::BitwiseAnd => "&",
+            BinaryOp::BitwiseOr => "|",
+            BinaryOp::BitwiseXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::Assign => "=",
+            BinaryOp::AddAssign => "+=",
+            BinaryOp::SubAssign => "-=",
+            BinaryOp::MulAssign => "*=",
+            BinaryOp::DivAssign => "/=",
+            BinaryOp::ModAssign => "%=",
+            BinaryOp::BitwiseAndAssign => "&=",
+            BinaryOp::BitwiseOrAssign => "|=",
+            BinaryOp::BitwiseXorAssign => "^=",
+            BinaryOp::ShiftLeftAssign => "<<=",
+            BinaryOp::ShiftRightAssign => ">>=",
+        })
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Plus => "+",
+            UnaryOp::Minus => "-",
+            UnaryOp::LogicalNot => "!",
+            UnaryOp::BitwiseNot => "~",
+            UnaryOp::AddrOf => "&",
+            UnaryOp::Deref => "*",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn konst() -> TypeQualifiers {
+        TypeQualifiers { is_const: true, ..Default::default() }
+    }
+
+    #[test]
+    fn renders_pointers_and_arrays() {
+        assert_eq!(Type::ptr(Type::Char).to_string(), "char *");
+        assert_eq!(Type::qualified_ptr(Type::Char, konst()).to_string(), "const char *");
+        assert_eq!(Type::Array(Box::new(Type::ptr(Type::Int)), 3).to_string(), "int *[3]");
+        assert_eq!(Type::ptr(Type::Array(Box::new(Type::Int), 3)).to_string(), "int (*)[3]");
+        let cpc = Type::ptr(Type::qualified_ptr(Type::Char, konst()));
+        assert_eq!(cpc.to_string(), "const char **");
+        let pcp = Type::qualified_ptr(Type::ptr(Type::Char), konst());
+        assert_eq!(pcp.to_string(), "char *const *");
+        assert_eq!(pcp.declaration("p"), "char *const *p");
+        assert_eq!(Type::Array(Box::new(Type::Int), 0).declaration("xs"), "int xs[]");
+        assert_eq!(Type::Complex(Box::new(Type::Double)).declaration("z"), "_Complex double z");
+        assert_eq!(Type::Int.qualified_declaration(&konst(), "n"), "const int n");
+        assert_eq!(Type::qualified_ptr(Type::Char, konst()).qualified_declaration(&konst(), "s"), "const char *const s");
+    }
+
+    #[test]
+    fn renders_function_pointers() {
+        let fp = Type::FunctionPointer {
+            return_type: Box::new(Type::Int),
+            param_types: vec![Type::ptr(Type::Char), Type::Int],
+            is_variadic: false,
+        };
+        assert_eq!(fp.to_string(), "int (*)(char *, int)");
+        assert_eq!(fp.declaration("cb"), "int (*cb)(char *, int)");
+        let printf_like = Type::FunctionPointer {
+            return_type: Box::new(Type::Void),
+            param_types: vec![Type::qualified_ptr(Type::Char, konst())],
+            is_variadic: true,
+        };
+        assert_eq!(printf_like.to_string(), "void (*)(const char *, ...)");
+        let table = Type::Array(Box::new(fp), 4);
+        assert_eq!(table.declaration("ops"), "int (*ops[4])(char *, int)");
+    }
+}
@@ -0,0 +1,200 @@
+//! Errors reported by the lexer, parser, semantic analysis, and IR lowering.
+//!
+//! Every error carries a stable `ErrorCode` so tests and tools can match on
+//! the kind of failure instead of on message text.
+
+use crate::Span;
+use std::fmt;
+
+/// Kind of compile error. Codes are grouped by the stage that usually
+/// reports them: `E01xx` lexing, `E02xx` parsing, `E03xx` semantic analysis,
+/// `E04xx` IR lowering, and `E0999` for internal errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// A character that cannot start any token.
+    UnexpectedCharacter,
+    /// A string, character literal, or block comment without its terminator.
+    Unterminated,
+    /// A malformed integer or floating-point constant.
+    InvalidNumber,
+    /// An unknown escape sequence in a character literal.
+    InvalidEscape,
+    /// The token stream does not match the grammar.
+    Syntax,
+    /// Incompatible type specifiers such as `unsigned float`.
+    InvalidTypeSpecifier,
+    /// A `_Static_assert` whose condition is zero.
+    StaticAssertFailed,
+    /// An expression nested deeper than the parser's limit.
+    NestingTooDeep,
+    /// A name declared twice in the same scope.
+    Redeclaration,
+    /// A use of a name that was never declared.
+    Undeclared,
+    /// Operand or initializer types that cannot be converted.
+    IncompatibleTypes,
+    /// A call with the wrong number of arguments.
+    ArgumentCount,
+    /// A write to a const object.
+    ConstViolation,
+    /// An operation that needs an lvalue was given something else.
+    NotAnLvalue,
+    /// `break`, `continue`, `case`, or `default` outside a construct that allows it.
+    MisplacedStatement,
+    /// Two `case` labels with the same value in one switch.
+    DuplicateCase,
+    /// A variable of incomplete type, such as an undefined struct.
+    IncompleteType,
+    /// A bit-field with a bad width or type.
+    InvalidBitfield,
+    /// A qualifier applied where it is not allowed (`restrict` on a non-pointer).
+    InvalidQualifier,
+    /// An enumerator with no `case` in a switch on its enum (reported as a warning).
+    UnhandledEnumerator,
+    /// A local that may be read before it is assigned (reported as a warning).
+    MaybeUninitialized,
+    /// A pointer to const converted to a pointer to a non-const type (reported
+    /// as a warning).
+    DiscardedQualifiers,
+    /// `.` or `->` naming a member the struct or union does not have.
+    UnknownMember,
+    /// A constant expression that overflows its type, or a constant that
+    /// changes value when converted (reported as a warning).
+    ConstantOverflow,
+    /// An expression that must be constant is not.
+    NotConstant,
+    /// A brace initializer or designator that does not fit the object.
+    InvalidInitializer,
+    /// An operand of the wrong kind, such as `.` on a non-struct.
+    InvalidOperand,
+    /// A `goto` to a label that is never defined.
+    UndefinedLabel,
+    /// A construct the compiler does not support yet.
+    Unsupported,
+    /// An inconsistency inside the compiler itself.
+    Internal,
+}
+
+impl ErrorCode {
+    /// The printed form, e.g. `E0201`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "E0101",
+            ErrorCode::Unterminated => "E0102",
+            ErrorCode::InvalidNumber => "E0103",
+            ErrorCode::InvalidEscape => "E0104",
+            ErrorCode::Syntax => "E0201",
+            ErrorCode::InvalidTypeSpecifier => "E0202",
+            ErrorCode::StaticAssertFailed => "E0203",
+            ErrorCode::NestingTooDeep => "E0204",
+            ErrorCode::Redeclaration => "E0301",
+            ErrorCode::Undeclared => "E0302",
+            ErrorCode::IncompatibleTypes => "E0303",
+            ErrorCode::ArgumentCount => "E0304",
+            ErrorCode::ConstViolation => "E0305",
+            ErrorCode::NotAnLvalue => "E0306",
+            ErrorCode::MisplacedStatement => "E0307",
+            ErrorCode::DuplicateCase => "E0308",
+            ErrorCode::IncompleteType => "E0309",
+            ErrorCode::InvalidBitfield => "E0310",
+            ErrorCode::InvalidQualifier => "E0311",
+            ErrorCode::UnhandledEnumerator => "E0312",
+            ErrorCode::MaybeUninitialized => "E0313",
+            ErrorCode::DiscardedQualifiers => "E0314",
+            ErrorCode::UnknownMember => "E0315",
+            ErrorCode::ConstantOverflow => "E0316",
+            ErrorCode::NotConstant => "E0401",
+            ErrorCode::InvalidInitializer => "E0402",
+            ErrorCode::InvalidOperand => "E0403",
+            ErrorCode::UndefinedLabel => "E0404",
+            ErrorCode::Unsupported => "E0405",
+            ErrorCode::Internal => "E0999",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A likely mistake; compilation continues.
+    Warning,
+    /// Compilation cannot continue.
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A diagnostic shared by every stage: an error that stops compilation, or
+/// a warning that does not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub code: ErrorCode,
+    pub severity: Severity,
+    pub message: String,
+    /// Where the error was found, when the reporting stage knows.
+    pub span: Option<Span>,
+    /// Extra context printed after the message.
+    pub notes: Vec<String>,
+}
+
+impl CompileError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        CompileError { code, severity: Severity::Error, message: message.into(), span: None, notes: Vec::new() }
+    }
+
+    /// A diagnostic with `Severity::Warning`.
+    pub fn warning(code: ErrorCode, message: impl Into<String>) -> Self {
+        CompileError { severity: Severity::Warning, ..Self::new(code, message) }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Attach `span` unless a more precise one is already set.
+    pub fn or_span(mut self, span: Option<Span>) -> Self {
+        self.span = self.span.or(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileError {}
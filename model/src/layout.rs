@@ -5,8 +5,29 @@
 // Having a single implementation eliminates the previous triple duplication
 // across ir/type_utils.rs, codegen/types.rs, and codegen/globals.rs.
 
-use crate::{Type, StructDef, UnionDef, Attribute, BitfieldInfo};
-use std::collections::HashMap;
+use crate::{Type, StructDef, StructField, UnionDef, EnumDef, Attribute, BitfieldInfo, DataLayout};
+use std::collections::{HashMap, HashSet};
+
+/// Placement of one struct field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLayout {
+    /// Byte offset of the field, or of the storage unit holding a bit-field.
+    pub offset: usize,
+    /// Position within the storage unit, for bit-fields.
+    pub bitfield: Option<BitfieldInfo>,
+}
+
+/// Layout of a whole struct: one `FieldLayout` per field, in declaration order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    pub fields: Vec<FieldLayout>,
+    pub size: usize,
+    pub align: usize,
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
 
 /// Provides type size and alignment computation for a given set of struct/union definitions.
 pub struct TypeLayout<'a> {
@@ -14,6 +35,10 @@ pub struct TypeLayout<'a> {
     pub unions: &'a HashMap<String, UnionDef>,
     /// Optional typedef resolution map (typedef name -> resolved type)
     pub typedefs: Option<&'a HashMap<String, Type>>,
+    /// Optional enum definitions, for the size of their underlying types
+    pub enums: Option<&'a HashMap<String, EnumDef>>,
+    /// Scalar sizes and alignments of the target.
+    pub data: DataLayout,
 }
 
 impl<'a> TypeLayout<'a> {
@@ -21,7 +46,7 @@ impl<'a> TypeLayout<'a> {
         structs: &'a HashMap<String, StructDef>,
         unions: &'a HashMap<String, UnionDef>,
     ) -> Self {
-        Self { structs, unions, typedefs: None }
+        Self { structs, unions, typedefs: None, enums: None, data: DataLayout::default() }
     }
 
     pub fn with_typedefs(
@@ -29,7 +54,19 @@ impl<'a> TypeLayout<'a> {
         unions: &'a HashMap<String, UnionDef>,
         typedefs: &'a HashMap<String, Type>,
     ) -> Self {
-        Self { structs, unions, typedefs: Some(typedefs) }
+        Self { structs, unions, typedefs: Some(typedefs), enums: None, data: DataLayout::default() }
+    }
+
+    /// Size enums by their underlying type. Unknown enums are `int`-sized.
+    pub fn with_enums(mut self, enums: &'a HashMap<String, EnumDef>) -> Self {
+        self.enums = Some(enums);
+        self
+    }
+
+    /// Use `data` instead of the default LP64 scalar layout.
+    pub fn with_data_layout(mut self, data: DataLayout) -> Self {
+        self.data = data;
+        self
     }
 
     /// Calculate the size of a type in bytes.
@@ -37,14 +74,18 @@ impl<'a> TypeLayout<'a> {
         match ty {
             Type::Bool => 1,
             Type::Char | Type::UnsignedChar => 1,
-            Type::Short | Type::UnsignedShort => 2,
-            Type::Int | Type::UnsignedInt | Type::Enum(_) => 4,
-            Type::Long | Type::UnsignedLong => 8,
-            Type::LongLong | Type::UnsignedLongLong => 8,
-            Type::Float => 4,
-            Type::Double => 8,
+            Type::Short | Type::UnsignedShort => self.data.short_size,
+            Type::Int | Type::UnsignedInt => self.data.int_size,
+            Type::Enum(name) => match self.enum_underlying(name) {
+                Some(underlying) => self.size_of(underlying),
+                None => self.data.int_size,
+            },
+            Type::Long | Type::UnsignedLong => self.data.long_size,
+            Type::LongLong | Type::UnsignedLongLong => self.data.long_long_size,
+            Type::Float => self.data.float_size,
+            Type::Double => self.data.double_size,
             Type::Void => 0,
-            Type::Pointer(_, ..) | Type::FunctionPointer { .. } => 8,
+            Type::Pointer(_, ..) | Type::FunctionPointer { .. } => self.data.pointer_size,
             Type::Array(inner, count) => self.size_of(inner) * count,
             Type::Struct(name) => {
                 if let Some(s_def) = self.structs.get(name) {
@@ -57,10 +98,7 @@ impl<'a> TypeLayout<'a> {
             }
             Type::Union(name) => {
                 if let Some(u_def) = self.unions.get(name) {
-                    u_def.fields.iter()
-                        .map(|f| self.size_of(&f.field_type))
-                        .max()
-                        .unwrap_or(0)
+                    self.union_size(u_def)
                 } else {
                     4 // fallback for unknown union
                 }
@@ -73,16 +111,10 @@ impl<'a> TypeLayout<'a> {
                     }
                 }
                 // Fallback: well-known typedefs
-                match name.as_str() {
-                    "int8_t" | "uint8_t" | "int8" | "uint8" => 1,
-                    "int16_t" | "uint16_t" | "int16" | "uint16" => 2,
-                    "int32_t" | "uint32_t" | "int32" | "uint32" => 4,
-                    "int64_t" | "uint64_t" | "int64" | "uint64"
-                    | "size_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => 8,
-                    _ => 4,
-                }
+                self.well_known_typedef_size(name)
             }
-            Type::TypeofExpr(_) => 8, // Should be resolved before layout computation
+            Type::TypeofExpr(_) => self.data.pointer_size, // Should be resolved before layout computation
+            Type::Complex(elem) => 2 * self.size_of(elem),
         }
     }
 
@@ -91,25 +123,21 @@ impl<'a> TypeLayout<'a> {
         match ty {
             Type::Bool => 1,
             Type::Char | Type::UnsignedChar => 1,
-            Type::Short | Type::UnsignedShort => 2,
-            Type::Int | Type::UnsignedInt | Type::Enum(_) => 4,
-            Type::Long | Type::UnsignedLong => 8,
-            Type::LongLong | Type::UnsignedLongLong => 8,
-            Type::Float => 4,
-            Type::Double => 8,
-            Type::Pointer(_, ..) | Type::FunctionPointer { .. } => 8,
+            Type::Short | Type::UnsignedShort => self.data.short_size,
+            Type::Int | Type::UnsignedInt => self.data.int_size,
+            Type::Enum(name) => match self.enum_underlying(name) {
+                Some(underlying) => self.align_of(underlying),
+                None => self.data.int_size,
+            },
+            Type::Long | Type::UnsignedLong => self.data.long_size,
+            Type::LongLong | Type::UnsignedLongLong => self.data.long_long_align,
+            Type::Float => self.data.float_size,
+            Type::Double => self.data.double_align,
+            Type::Pointer(_, ..) | Type::FunctionPointer { .. } => self.data.pointer_align,
             Type::Array(inner, _) => self.align_of(inner),
             Type::Struct(name) => {
                 if let Some(s_def) = self.structs.get(name) {
-                    let is_packed = s_def.attributes.iter()
-                        .any(|attr| matches!(attr, Attribute::Packed));
-                    if is_packed {
-                        return 1;
-                    }
-                    s_def.fields.iter()
-                        .map(|f| self.align_of(&f.field_type))
-                        .max()
-                        .unwrap_or(4)
+                    self.struct_layout(s_def).align
                 } else {
                     4
                 }
@@ -130,164 +158,219 @@ impl<'a> TypeLayout<'a> {
                         return self.align_of(real_ty);
                     }
                 }
-                match name.as_str() {
-                    "int8_t" | "uint8_t" | "int8" | "uint8" => 1,
-                    "int16_t" | "uint16_t" | "int16" | "uint16" => 2,
-                    "int32_t" | "uint32_t" | "int32" | "uint32" => 4,
-                    "int64_t" | "uint64_t" | "int64" | "uint64"
-                    | "size_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => 8,
-                    _ => 4,
-                }
+                self.well_known_typedef_size(name)
             }
             Type::Void => 1,
-            Type::TypeofExpr(_) => 8,
+            Type::TypeofExpr(_) => self.data.pointer_align,
+            Type::Complex(elem) => self.align_of(elem),
+        }
+    }
+
+    fn enum_underlying(&self, name: &str) -> Option<&Type> {
+        self.enums.and_then(|enums| enums.get(name)).map(|e| &e.underlying)
+    }
+
+    /// Size (and alignment) of fixed-width and pointer-sized typedefs that
+    /// were not declared in the program.
+    fn well_known_typedef_size(&self, name: &str) -> usize {
+        match name {
+            "int8_t" | "uint8_t" | "int8" | "uint8" => 1,
+            "int16_t" | "uint16_t" | "int16" | "uint16" => 2,
+            "int32_t" | "uint32_t" | "int32" | "uint32" => 4,
+            "int64_t" | "uint64_t" | "int64" | "uint64" => 8,
+            "size_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => self.data.pointer_size,
+            // A pointer to the next variadic argument slot
+            "__builtin_va_list" => self.data.pointer_size,
+            _ => self.data.int_size,
         }
     }
 
     /// Compute the total size of a struct including field alignment padding and bitfield packing.
+    /// Size of a union: its largest member, rounded up to the alignment of
+    /// its most aligned member so that arrays of it stay aligned.
+    pub fn union_size(&self, u_def: &UnionDef) -> usize {
+        let size = u_def.fields.iter().map(|f| self.size_of(&f.field_type)).max().unwrap_or(0);
+        let align = u_def.fields.iter().map(|f| self.align_of(&f.field_type)).max().unwrap_or(1);
+        size.next_multiple_of(align)
+    }
+
     pub fn struct_size(&self, s_def: &StructDef, is_packed: bool) -> usize {
-        let mut size: usize = 0;
-        let mut bit_offset: usize = 0; // bits used within current storage unit
-        let mut in_bitfield = false;
-        let mut bf_storage_size: usize = 0;
+        self.layout_fields(s_def, is_packed).size
+    }
+
+    /// Compute the placement of every field of a struct.
+    pub fn struct_layout(&self, s_def: &StructDef) -> StructLayout {
+        let is_packed = s_def.attributes.iter()
+            .any(|attr| matches!(attr, Attribute::Packed));
+        self.layout_fields(s_def, is_packed)
+    }
+
+    /// System V layout. Fields are placed at a running bit position:
+    /// - a regular field starts at the next byte aligned for its type;
+    /// - a bit-field takes the next free bits, unless that would straddle a
+    ///   boundary of its type's size, in which case it starts a new unit;
+    /// - a zero-width bit-field rounds the position up to its type's boundary;
+    /// - unnamed bit-fields do not raise the struct's alignment.
+    ///
+    /// Packed structs drop all padding, so bit-fields may straddle units.
+    /// Under `#pragma pack(N)` no field is aligned to more than `N` bytes.
+    fn layout_fields(&self, s_def: &StructDef, is_packed: bool) -> StructLayout {
+        let mut fields = Vec::with_capacity(s_def.fields.len());
+        let mut bit_pos: usize = 0;
+        let mut struct_align: usize = 1;
+        let max_align = s_def.attributes.iter().fold(usize::MAX, |max, attr| match attr {
+            Attribute::Pack(n) => max.min(*n),
+            _ => max,
+        });
 
         for field in &s_def.fields {
-            if let Some(bw) = field.bit_width {
-                let storage = self.size_of(&field.field_type);
-                let storage_bits = storage * 8;
-                if bw == 0 {
-                    // Zero-width bitfield = force alignment to next storage unit boundary
-                    if in_bitfield {
-                        size += bf_storage_size;
-                        bit_offset = 0;
-                        in_bitfield = false;
-                    }
-                    continue;
+            let size = self.size_of(&field.field_type);
+            let align = if is_packed { 1 } else { self.align_of(&field.field_type).min(max_align) };
+            match field.bit_width {
+                None => {
+                    let offset = round_up(bit_pos.div_ceil(8), align);
+                    fields.push(FieldLayout { offset, bitfield: None });
+                    bit_pos = (offset + size) * 8;
+                    struct_align = struct_align.max(align);
                 }
-                if in_bitfield && bf_storage_size == storage && bit_offset + bw <= storage_bits {
-                    // Fits in current storage unit
-                    bit_offset += bw;
-                } else {
-                    // Finish previous storage unit if any
-                    if in_bitfield {
-                        size += bf_storage_size;
-                    } else if !is_packed {
-                        let alignment = self.align_of(&field.field_type);
-                        size = (size + alignment - 1) / alignment * alignment;
+                Some(width) => {
+                    let unit_bits = (size * 8).max(8);
+                    if width == 0 {
+                        if !is_packed {
+                            bit_pos = round_up(bit_pos, unit_bits);
+                        }
+                    } else if !is_packed && bit_pos % unit_bits + width > unit_bits {
+                        bit_pos = round_up(bit_pos, unit_bits);
+                    }
+                    // Packed bit-fields are addressed from the byte holding
+                    // their first bit; otherwise from their aligned unit.
+                    let offset = if is_packed { bit_pos / 8 } else { bit_pos / unit_bits * size };
+                    fields.push(FieldLayout {
+                        offset,
+                        bitfield: Some(BitfieldInfo {
+                            bit_offset: bit_pos - offset * 8,
+                            bit_width: width,
+                            storage_size: size,
+                        }),
+                    });
+                    bit_pos += width;
+                    if !field.name.is_empty() {
+                        struct_align = struct_align.max(align);
                     }
-                    bf_storage_size = storage;
-                    bit_offset = bw;
-                    in_bitfield = true;
-                }
-            } else {
-                // Regular field — finish any pending bitfield storage
-                if in_bitfield {
-                    size += bf_storage_size;
-                    bit_offset = 0;
-                    in_bitfield = false;
-                }
-                let field_size = self.size_of(&field.field_type);
-                if !is_packed {
-                    let alignment = self.align_of(&field.field_type);
-                    size = (size + alignment - 1) / alignment * alignment;
                 }
-                size += field_size;
             }
         }
-        // Finish any trailing bitfield
-        if in_bitfield {
-            size += bf_storage_size;
-        }
-        // Add trailing padding
-        if !is_packed {
-            let struct_align = s_def.fields.iter()
-                .map(|f| self.align_of(&f.field_type))
-                .max()
-                .unwrap_or(1);
-            size = (size + struct_align - 1) / struct_align * struct_align;
-        }
-        size
+
+        let size = round_up(bit_pos.div_ceil(8), struct_align);
+        StructLayout { fields, size, align: struct_align }
     }
 
     /// Get the byte offset and type of a struct/union member, plus optional bitfield info.
     pub fn member_offset(&self, struct_or_union_name: &str, member_name: &str) -> (usize, Type, Option<BitfieldInfo>) {
-        // Check structs
+        self.find_member(struct_or_union_name, member_name).unwrap_or((0, Type::Int, None))
+    }
+
+    /// Like `member_offset`, but `None` when the struct or union has no such
+    /// member. Members of an anonymous struct or union member are found
+    /// as if they belonged to the enclosing record.
+    pub fn find_member(&self, struct_or_union_name: &str, member_name: &str) -> Option<(usize, Type, Option<BitfieldInfo>)> {
         if let Some(s_def) = self.structs.get(struct_or_union_name) {
-            let is_packed = s_def.attributes.iter()
-                .any(|attr| matches!(attr, Attribute::Packed));
-            let mut offset: usize = 0;
-            let mut bit_offset: usize = 0;
-            let mut in_bitfield = false;
-            let mut bf_storage_size: usize = 0;
-
-            for field in &s_def.fields {
-                if let Some(bw) = field.bit_width {
-                    let storage = self.size_of(&field.field_type);
-                    let storage_bits = storage * 8;
-                    if bw == 0 {
-                        if in_bitfield {
-                            offset += bf_storage_size;
-                            bit_offset = 0;
-                            in_bitfield = false;
-                        }
-                        continue;
-                    }
-                    if in_bitfield && bf_storage_size == storage && bit_offset + bw <= storage_bits {
-                        // Fits in current storage unit
-                        if field.name == member_name {
-                            return (offset, field.field_type.clone(), Some(BitfieldInfo {
-                                bit_offset,
-                                bit_width: bw,
-                                storage_size: storage,
-                            }));
-                        }
-                        bit_offset += bw;
-                    } else {
-                        // New storage unit
-                        if in_bitfield {
-                            offset += bf_storage_size;
-                        } else if !is_packed {
-                            let alignment = self.align_of(&field.field_type);
-                            offset = (offset + alignment - 1) / alignment * alignment;
-                        }
-                        bf_storage_size = storage;
-                        if field.name == member_name {
-                            return (offset, field.field_type.clone(), Some(BitfieldInfo {
-                                bit_offset: 0,
-                                bit_width: bw,
-                                storage_size: storage,
-                            }));
-                        }
-                        bit_offset = bw;
-                        in_bitfield = true;
-                    }
-                } else {
-                    // Regular field
-                    if in_bitfield {
-                        offset += bf_storage_size;
-                        bit_offset = 0;
-                        in_bitfield = false;
-                    }
-                    if !is_packed {
-                        let alignment = self.align_of(&field.field_type);
-                        offset = (offset + alignment - 1) / alignment * alignment;
-                    }
-                    if field.name == member_name {
-                        return (offset, field.field_type.clone(), None);
-                    }
-                    offset += self.size_of(&field.field_type);
+            let layout = self.struct_layout(s_def);
+            for (field, field_layout) in s_def.fields.iter().zip(layout.fields) {
+                if field.name == member_name {
+                    return Some((field_layout.offset, field.field_type.clone(), field_layout.bitfield));
+                }
+                if let Some((offset, ty, bitfield)) = self.find_anonymous_member(field, member_name) {
+                    return Some((field_layout.offset + offset, ty, bitfield));
                 }
             }
+            return None;
         }
-        // Check unions (all fields at offset 0)
-        if let Some(u_def) = self.unions.get(struct_or_union_name) {
-            for field in &u_def.fields {
-                if field.name == member_name {
-                    return (0, field.field_type.clone(), None);
+        // Union members all start at offset 0
+        let u_def = self.unions.get(struct_or_union_name)?;
+        for field in &u_def.fields {
+            if field.name == member_name {
+                let bitfield = field.bit_width.map(|bit_width| BitfieldInfo {
+                    bit_offset: 0,
+                    bit_width,
+                    storage_size: self.size_of(&field.field_type),
+                });
+                return Some((0, field.field_type.clone(), bitfield));
+            }
+            if let Some(found) = self.find_anonymous_member(field, member_name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn find_anonymous_member(&self, field: &StructField, member_name: &str) -> Option<(usize, Type, Option<BitfieldInfo>)> {
+        match &field.field_type {
+            Type::Struct(tag) | Type::Union(tag) if field.name.is_empty() && field.bit_width.is_none() => {
+                self.find_member(tag, member_name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Names of the members of a struct or union, including those reached
+    /// through anonymous members; `None` if it is not defined.
+    pub fn member_names(&self, struct_or_union_name: &str) -> Option<Vec<&str>> {
+        let fields = match self.structs.get(struct_or_union_name) {
+            Some(s_def) => &s_def.fields,
+            None => &self.unions.get(struct_or_union_name)?.fields,
+        };
+        let mut names = Vec::new();
+        for field in fields {
+            match &field.field_type {
+                Type::Struct(tag) | Type::Union(tag) if field.name.is_empty() && field.bit_width.is_none() => {
+                    names.extend(self.member_names(tag).unwrap_or_default());
                 }
+                _ if !field.name.is_empty() => names.push(field.name.as_str()),
+                _ => {}
+            }
+        }
+        Some(names)
+    }
+
+    /// The field through which struct or union `tag` contains itself by
+    /// value, directly or inside other members and arrays. Such a type has
+    /// no size; `None` when `tag` does not contain itself.
+    pub fn self_containing_field(&self, tag: &str) -> Option<&'a str> {
+        let fields = self.record_fields(tag)?;
+        let mut visited = HashSet::new();
+        fields
+            .iter()
+            .find(|f| self.contains_record(&f.field_type, tag, &mut visited))
+            .map(|f| f.name.as_str())
+    }
+
+    fn record_fields(&self, tag: &str) -> Option<&'a [StructField]> {
+        let (structs, unions) = (self.structs, self.unions);
+        match structs.get(tag) {
+            Some(s_def) => Some(&s_def.fields),
+            None => unions.get(tag).map(|u_def| u_def.fields.as_slice()),
+        }
+    }
+
+    /// Whether a value of type `ty` holds a `target` struct or union by value.
+    /// `visited` keeps records already searched, so other cycles end.
+    fn contains_record(&self, ty: &Type, target: &str, visited: &mut HashSet<String>) -> bool {
+        match ty {
+            Type::Array(elem, _) => self.contains_record(elem, target, visited),
+            Type::Typedef(name) => self
+                .typedefs
+                .and_then(|typedefs| typedefs.get(name))
+                .is_some_and(|real| self.contains_record(real, target, visited)),
+            Type::Struct(tag) | Type::Union(tag) => {
+                tag == target
+                    || (visited.insert(tag.clone())
+                        && self
+                            .record_fields(tag)
+                            .is_some_and(|fields| fields.iter().any(|f| self.contains_record(&f.field_type, target, visited))))
             }
+            _ => false,
         }
-        (0, Type::Int, None) // fallback
     }
 
     /// Check if a type is a floating-point type.
@@ -320,6 +403,40 @@ mod tests {
         assert_eq!(layout.size_of(&Type::ptr(Type::Int)), 8);
     }
 
+    #[test]
+    fn test_custom_data_layout() {
+        let ilp32 = DataLayout {
+            pointer_size: 4,
+            pointer_align: 4,
+            long_size: 4,
+            long_long_align: 4,
+            double_align: 4,
+            ..DataLayout::lp64()
+        };
+        let layout = empty_layout().with_data_layout(ilp32);
+        assert_eq!(layout.size_of(&Type::ptr(Type::Char)), 4);
+        assert_eq!(layout.size_of(&Type::Long), 4);
+        assert_eq!(layout.size_of(&Type::Typedef("size_t".to_string())), 4);
+        assert_eq!(layout.align_of(&Type::Double), 4);
+        assert_eq!(layout.size_of(&Type::Array(Box::new(Type::ptr(Type::Int)), 3)), 12);
+    }
+
+    #[test]
+    fn test_enum_underlying_size() {
+        let mut enums = HashMap::new();
+        let small = EnumDef::new("Small".to_string(), vec![("A".to_string(), -1), ("B".to_string(), 1)]);
+        let wide = EnumDef::new("Wide".to_string(), vec![("BIG".to_string(), 1 << 40)]);
+        assert_eq!(small.underlying, Type::Int);
+        assert_eq!(wide.underlying, Type::UnsignedLongLong);
+        enums.insert(small.name.clone(), small);
+        enums.insert(wide.name.clone(), wide);
+        let layout = empty_layout().with_enums(&enums);
+        assert_eq!(layout.size_of(&Type::Enum("Small".to_string())), 4);
+        assert_eq!(layout.size_of(&Type::Enum("Wide".to_string())), 8);
+        assert_eq!(layout.align_of(&Type::Enum("Wide".to_string())), 8);
+        assert_eq!(layout.size_of(&Type::Enum("Unknown".to_string())), 4);
+    }
+
     #[test]
     fn test_array_size() {
         let layout = empty_layout();
@@ -386,6 +503,136 @@ mod tests {
         assert_eq!(layout.align_of(&Type::Struct("Packed".to_string())), 1);
     }
 
+    #[test]
+    fn test_pragma_pack_caps_alignment() {
+        let mut structs = HashMap::new();
+        structs.insert("P".to_string(), StructDef {
+            name: "P".to_string(),
+            fields: vec![
+                StructField { field_type: Type::Char, name: "c".to_string(), bit_width: None },
+                StructField { field_type: Type::Long, name: "l".to_string(), bit_width: None },
+                StructField { field_type: Type::Int, name: "i".to_string(), bit_width: None },
+            ],
+            attributes: vec![Attribute::Pack(2)],
+        });
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+        // Each field aligned to min(natural, 2): c@0, l@2, i@10
+        assert_eq!(layout.member_offset("P", "l").0, 2);
+        assert_eq!(layout.member_offset("P", "i").0, 10);
+        assert_eq!(layout.size_of(&Type::Struct("P".to_string())), 14);
+        assert_eq!(layout.align_of(&Type::Struct("P".to_string())), 2);
+    }
+
+    #[test]
+    fn test_anonymous_members_are_transparent() {
+        // struct S { char c; union { int i; struct { short lo, hi; }; }; };
+        let field = |ty: Type, name: &str| StructField { field_type: ty, name: name.to_string(), bit_width: None };
+        let mut structs = HashMap::new();
+        structs.insert("S".to_string(), StructDef {
+            name: "S".to_string(),
+            fields: vec![field(Type::Char, "c"), field(Type::Union("U".to_string()), "")],
+            attributes: vec![],
+        });
+        structs.insert("Halves".to_string(), StructDef {
+            name: "Halves".to_string(),
+            fields: vec![field(Type::Short, "lo"), field(Type::Short, "hi")],
+            attributes: vec![],
+        });
+        let mut unions = HashMap::new();
+        unions.insert("U".to_string(), UnionDef {
+            name: "U".to_string(),
+            fields: vec![field(Type::Int, "i"), field(Type::Struct("Halves".to_string()), "")],
+        });
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.find_member("S", "i").map(|m| m.0), Some(4));
+        assert_eq!(layout.find_member("S", "hi").map(|m| (m.0, m.1)), Some((6, Type::Short)));
+        assert_eq!(layout.find_member("S", "x"), None);
+        assert_eq!(layout.member_names("S").unwrap(), ["c", "i", "lo", "hi"]);
+    }
+
+    #[test]
+    fn test_self_containing_field() {
+        let field = |ty: Type, name: &str| StructField { field_type: ty, name: name.to_string(), bit_width: None };
+        let record = |name: &str, fields| StructDef { name: name.to_string(), fields, attributes: vec![] };
+        let node = Type::Struct("Node".to_string());
+        let mut structs = HashMap::new();
+        structs.insert("Node".to_string(), record("Node", vec![field(Type::Int, "v"), field(Type::ptr(node.clone()), "next")]));
+        structs.insert("Bad".to_string(), record("Bad", vec![field(Type::Int, "v"), field(Type::Typedef("Outer".to_string()), "o")]));
+        structs.insert("Outer".to_string(), record("Outer", vec![
+            field(node, "n"),
+            field(Type::Array(Box::new(Type::Struct("Bad".to_string())), 2), "bad"),
+        ]));
+        let unions = HashMap::new();
+        let typedefs = HashMap::from([("Outer".to_string(), Type::Struct("Outer".to_string()))]);
+        let layout = TypeLayout::with_typedefs(&structs, &unions, &typedefs);
+        assert_eq!(layout.self_containing_field("Node"), None);
+        assert_eq!(layout.self_containing_field("Bad"), Some("o"));
+        assert_eq!(layout.self_containing_field("Outer"), Some("bad"));
+    }
+
+    fn bitfield_struct(fields: &[(Type, &str, Option<usize>)]) -> HashMap<String, StructDef> {
+        let mut structs = HashMap::new();
+        structs.insert("S".to_string(), StructDef {
+            name: "S".to_string(),
+            fields: fields.iter()
+                .map(|(ty, name, bw)| StructField { field_type: ty.clone(), name: name.to_string(), bit_width: *bw })
+                .collect(),
+            attributes: vec![],
+        });
+        structs
+    }
+
+    #[test]
+    fn test_bitfield_shares_unit_with_following_field() {
+        // struct { int a:4; char c; } — c goes in the byte after a's bits
+        let structs = bitfield_struct(&[(Type::Int, "a", Some(4)), (Type::Char, "c", None)]);
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.size_of(&Type::Struct("S".to_string())), 4);
+        assert_eq!(layout.member_offset("S", "c").0, 1);
+    }
+
+    #[test]
+    fn test_bitfields_of_different_types_pack() {
+        // struct { char a:4; int b:4; } — b fits in the int unit at offset 0
+        let structs = bitfield_struct(&[(Type::Char, "a", Some(4)), (Type::Int, "b", Some(4))]);
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.size_of(&Type::Struct("S".to_string())), 4);
+        let (offset, _, bf) = layout.member_offset("S", "b");
+        assert_eq!(offset, 0);
+        assert_eq!(bf.unwrap().bit_offset, 4);
+    }
+
+    #[test]
+    fn test_bitfield_does_not_straddle() {
+        // struct { int a:30; int b:4; } — b moves to the next int
+        let structs = bitfield_struct(&[(Type::Int, "a", Some(30)), (Type::Int, "b", Some(4))]);
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.size_of(&Type::Struct("S".to_string())), 8);
+        let (offset, _, bf) = layout.member_offset("S", "b");
+        assert_eq!(offset, 4);
+        assert_eq!(bf.unwrap().bit_offset, 0);
+    }
+
+    #[test]
+    fn test_zero_width_and_unnamed_bitfields() {
+        // struct { char a:3; int :0; char b:3; } — b starts at the next int boundary,
+        // and the unnamed int does not raise the alignment
+        let structs = bitfield_struct(&[
+            (Type::Char, "a", Some(3)),
+            (Type::Int, "", Some(0)),
+            (Type::Char, "b", Some(3)),
+        ]);
+        let unions = HashMap::new();
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.size_of(&Type::Struct("S".to_string())), 5);
+        assert_eq!(layout.align_of(&Type::Struct("S".to_string())), 1);
+        assert_eq!(layout.member_offset("S", "b").0, 4);
+    }
+
     #[test]
     fn test_union_size() {
         let structs = HashMap::new();
@@ -401,6 +648,30 @@ mod tests {
         assert_eq!(layout.size_of(&Type::Union("Data".to_string())), 8);
     }
 
+    #[test]
+    fn test_union_size_rounds_up_to_alignment() {
+        let structs = HashMap::new();
+        let mut unions = HashMap::new();
+        unions.insert("U".to_string(), UnionDef {
+            name: "U".to_string(),
+            fields: vec![
+                StructField { field_type: Type::Array(Box::new(Type::Int), 3), name: "a".to_string(), bit_width: None },
+                StructField { field_type: Type::Long, name: "l".to_string(), bit_width: None },
+            ],
+        });
+        unions.insert("B".to_string(), UnionDef {
+            name: "B".to_string(),
+            fields: vec![
+                StructField { field_type: Type::Array(Box::new(Type::Char), 5), name: "b".to_string(), bit_width: None },
+                StructField { field_type: Type::Int, name: "i".to_string(), bit_width: None },
+            ],
+        });
+        let layout = TypeLayout::new(&structs, &unions);
+        assert_eq!(layout.size_of(&Type::Union("U".to_string())), 16);
+        assert_eq!(layout.size_of(&Type::Union("B".to_string())), 8);
+        assert_eq!(layout.size_of(&Type::Array(Box::new(Type::Union("B".to_string())), 3)), 24);
+    }
+
     #[test]
     fn test_alignments() {
         let layout = empty_layout();
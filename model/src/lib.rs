@@ -1,14 +1,25 @@
 use std::collections::HashMap;
 pub mod target;
-pub use target::{Platform, CallingConvention, TargetConfig, SimdLevel, PicMode};
+pub use target::{Arch, AsmSyntax, Platform, CallingConvention, DataLayout, TargetConfig, SimdLevel, PicMode};
 
 // Centralized type layout computation
 pub mod layout;
-pub use layout::TypeLayout;
+pub use layout::{FieldLayout, StructLayout, TypeLayout};
 
 pub mod typing;
 pub use typing::{FunctionSig, TypeEnv};
 
+pub mod consteval;
+pub use consteval::{ConstContext, ConstValue};
+
+pub mod display;
+
+pub mod span;
+pub use span::{FileId, LineCol, LineMarker, SourceMap, Span, Spanned};
+
+pub mod error;
+pub use error::{CompileError, ErrorCode, Severity};
+
 /// Suffix on an integer constant, controlling its type.
 #[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub enum IntegerSuffix {
@@ -27,12 +38,83 @@ pub enum IntegerSuffix {
     ULL,
 }
 
+/// Encoding prefix on a string or character literal.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum StringEncoding {
+    /// No prefix — `char` data, the source bytes as written.
+    #[default]
+    Plain,
+    /// `u8` — `char` data in UTF-8.
+    Utf8,
+    /// `L` — `wchar_t` (a 32-bit `int`) code points.
+    Wide,
+    /// `u` — `char16_t` UTF-16 code units.
+    Utf16,
+    /// `U` — `char32_t` code points.
+    Utf32,
+}
+
+impl StringEncoding {
+    /// Whether the literal holds `char` data rather than wider units.
+    pub fn is_narrow(self) -> bool {
+        matches!(self, StringEncoding::Plain | StringEncoding::Utf8)
+    }
+
+    /// The element type of a string literal with this prefix.
+    pub fn unit_type(self) -> Type {
+        match self {
+            StringEncoding::Plain | StringEncoding::Utf8 => Type::Char,
+            StringEncoding::Wide => Type::Int,
+            StringEncoding::Utf16 => Type::UnsignedShort,
+            StringEncoding::Utf32 => Type::UnsignedInt,
+        }
+    }
+
+    /// The type of a literal holding `value`: an array of its code units
+    /// and the terminator.
+    pub fn array_type(self, value: &str) -> Type {
+        Type::Array(Box::new(self.unit_type()), self.encode(value).len() + 1)
+    }
+
+    /// The code units of `value` in this encoding, without the terminator.
+    /// Narrow values hold one byte per `char`; wide ones hold code points.
+    pub fn encode(self, value: &str) -> Vec<u32> {
+        match self {
+            StringEncoding::Plain | StringEncoding::Utf8 => value.chars().map(|c| u32::from(c as u8)).collect(),
+            StringEncoding::Wide | StringEncoding::Utf32 => value.chars().map(u32::from).collect(),
+            StringEncoding::Utf16 => value.encode_utf16().map(u32::from).collect(),
+        }
+    }
+}
+
+impl IntegerSuffix {
+    /// The type of a constant with this suffix and value, per C11 6.4.4.1:
+    /// the first of the suffix's candidate types that can hold the value.
+    /// Tokens do not record their radix, so unsuffixed constants follow the
+    /// decimal list (`int`, `long`). The lexer wraps values above `i64::MAX`
+    /// to negatives, and those only fit the unsigned 64-bit types.
+    pub fn constant_type(self, value: i64) -> Type {
+        let fits_int = (0..=i64::from(i32::MAX)).contains(&value);
+        let fits_unsigned_int = (0..=i64::from(u32::MAX)).contains(&value);
+        match self {
+            IntegerSuffix::None if fits_int => Type::Int,
+            IntegerSuffix::None | IntegerSuffix::L if value >= 0 => Type::Long,
+            IntegerSuffix::U if fits_unsigned_int => Type::UnsignedInt,
+            IntegerSuffix::None | IntegerSuffix::L | IntegerSuffix::U | IntegerSuffix::UL => Type::UnsignedLong,
+            IntegerSuffix::LL if value >= 0 => Type::LongLong,
+            IntegerSuffix::LL | IntegerSuffix::ULL => Type::UnsignedLongLong,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Token {
     Identifier { value: String },
     Constant { value: i64, suffix: IntegerSuffix },
     FloatLiteral { value: f64 },
-    StringLiteral { value: String },
+    /// Imaginary constant such as `2.0i` (GCC extension, used by `<complex.h>`'s `I`).
+    ImaginaryLiteral { value: f64 },
+    StringLiteral { value: String, encoding: StringEncoding },
     OpenParenthesis,
     CloseParenthesis,
     OpenBrace,
@@ -89,8 +171,15 @@ pub enum Token {
     StaticAssert, // _Static_assert
     Bool, // _Bool
     AlignOf, // _Alignof / __alignof__
+    Alignas, // _Alignas / alignas
+    Atomic, // _Atomic
+    ThreadLocal, // _Thread_local / __thread
+    Noreturn, // _Noreturn
     Register, // register
     Generic, // _Generic
+    Complex, // _Complex / __complex__
+    RealPart, // __real__
+    ImagPart, // __imag__
     // Operators
     Plus,
     Minus,
@@ -126,6 +215,20 @@ pub enum Token {
     XorEqual,
     LessLessEqual,
     GreaterGreaterEqual,
+    /// A `#pragma pack` directive line. The parser removes these from the
+    /// token stream and applies them to the struct definitions that follow.
+    PragmaPack(PackPragma),
+}
+
+/// The action of a `#pragma pack` directive.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PackPragma {
+    /// `pack(N)`, or `pack()` (`None`) to restore natural alignment.
+    Set(Option<usize>),
+    /// `pack(push)` or `pack(push, N)`: save the current packing, then set `N` if given.
+    Push(Option<usize>),
+    /// `pack(pop)`: restore the packing saved by the matching `push`.
+    Pop,
 }
 
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -138,6 +241,9 @@ pub struct TypeQualifiers {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Attribute {
     Packed,
+    /// `#pragma pack(N)` in effect at the definition: members are aligned to
+    /// at most `N` bytes.
+    Pack(usize),
     Aligned(usize),
     Section(String),
     NoReturn,
@@ -164,9 +270,10 @@ pub enum Type {
     Float,
     Double,
     Array(Box<Type>, usize),
-    /// Pointer type with optional qualifiers on the pointer itself.
-    /// E.g. `const int *` → `Pointer(Int, {is_const: true, ..})`, 
-    /// `int *const` → outer declaration qualifier, not on the pointer type.
+    /// Pointer type carrying the qualifiers of the pointed-to object.
+    /// E.g. `const int *` → `Pointer(Int, {is_const: true, ..})`, and
+    /// `const char *volatile *` → `Pointer(Pointer(Char, const), volatile)`.
+    /// Qualifiers after the last `*` (`int *const`) belong to the declaration.
     Pointer(Box<Type>, TypeQualifiers),
     Struct(String),
     Union(String),
@@ -174,6 +281,9 @@ pub enum Type {
     FunctionPointer {
         return_type: Box<Type>,
         param_types: Vec<Type>,
+        /// Declared with a trailing `...`; calls pass the vector-register
+        /// count in AL under System V.
+        is_variadic: bool,
     },
     Bool,
     /// Enum type: behaves like `int` but carries the tag for type-checking.
@@ -181,6 +291,9 @@ pub enum Type {
     /// `typeof(expr)` — resolved to the concrete type of the expression
     /// during IR lowering.
     TypeofExpr(Box<Expr>),
+    /// `_Complex float` / `_Complex double`: a pair of the element type,
+    /// real part first.
+    Complex(Box<Type>),
 }
 
 impl Type {
@@ -195,7 +308,7 @@ impl Type {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct Program {
     pub functions: Vec<Function>,
     pub globals: Vec<GlobalVar>,
@@ -206,6 +319,17 @@ pub struct Program {
     pub forward_structs: Vec<String>,
     /// Typedef name → underlying type (for semantic resolution and layout).
     pub typedefs: HashMap<String, Type>,
+    /// File-scope `_Static_assert`s; block-scope ones are statements.
+    pub static_asserts: Vec<StaticAssert>,
+}
+
+/// A `_Static_assert`, evaluated during semantic analysis once struct
+/// layouts are known.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StaticAssert {
+    pub condition: Expr,
+    pub message: Option<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -251,6 +375,36 @@ pub struct UnionDef {
 pub struct EnumDef {
     pub name: String,
     pub constants: Vec<(String, i64)>, // name => value
+    /// Integer type that objects of this enum are stored as.
+    pub underlying: Type,
+}
+
+impl EnumDef {
+    pub fn new(name: String, constants: Vec<(String, i64)>) -> Self {
+        let underlying = Self::underlying_type_for(&constants);
+        Self { name, constants, underlying }
+    }
+
+    /// GCC's choice of compatible type: `unsigned int` when no enumerator is
+    /// negative, `int` otherwise, widening to `long long` of the same
+    /// signedness when a value does not fit in 32 bits, as `long` is 32-bit
+    /// on ILP32 targets.
+    pub fn underlying_type_for(constants: &[(String, i64)]) -> Type {
+        let min = constants.iter().map(|(_, v)| *v).min().unwrap_or(0);
+        let max = constants.iter().map(|(_, v)| *v).max().unwrap_or(0);
+        if min < 0 {
+            if min >= i64::from(i32::MIN) && max <= i64::from(i32::MAX) { Type::Int } else { Type::LongLong }
+        } else if max <= i64::from(u32::MAX) {
+            Type::UnsignedInt
+        } else {
+            Type::UnsignedLongLong
+        }
+    }
+
+    /// Value of the enumerator `name`, if this enum declares it.
+    pub fn value_of(&self, name: &str) -> Option<i64> {
+        self.constants.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -262,6 +416,8 @@ pub struct GlobalVar {
     pub attributes: Vec<Attribute>,
     pub is_extern: bool,
     pub is_static: bool,
+    /// Span of the declarator name.
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -274,11 +430,16 @@ pub struct Function {
     pub is_static: bool,
     pub is_variadic: bool,
     pub attributes: Vec<Attribute>,
+    /// Span of the whole definition, from its first specifier to the closing brace.
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block {
     pub statements: Vec<Stmt>,
+    /// Span of each statement, parallel to `statements`. Default spans when
+    /// the parser was run without token spans.
+    pub spans: Vec<Span>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -310,6 +471,9 @@ pub enum Stmt {
         qualifiers: TypeQualifiers,
         name: String,
         init: Option<Expr>,
+        /// Declared `static`, `extern`, or `_Thread_local`: the object does
+        /// not live in the stack frame and needs no initializer to be read.
+        has_static_storage: bool,
     },
     Break,
     Continue,
@@ -332,12 +496,18 @@ pub enum Stmt {
         inputs: Vec<AsmOperand>,
         clobbers: Vec<String>,
         is_volatile: bool,
+        /// Labels of an `asm goto`, referenced as `%l[name]` or `%lN`.
+        goto_labels: Vec<String>,
     },
+    /// Block-scope `_Static_assert`; it generates no code.
+    StaticAssert(StaticAssert),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AsmOperand {
-    pub constraint: String,  // "=r", "r", "m", etc.
+    /// Symbolic name from `[name] "r"(x)`, referenced as `%[name]`.
+    pub name: Option<String>,
+    pub constraint: String,  // "=r", "+&r", "r", "m", "i", etc.
     pub expr: Expr,          // variable or expression
 }
 
@@ -358,8 +528,16 @@ pub enum Expr {
     PrefixDecrement(Box<Expr>),
     Variable(String),
     Constant(i64),
+    /// Integer constant whose type is not `int`: a suffixed literal (`10u`,
+    /// `5LL`) or one too large for `int`. Plain `int` literals stay `Constant`.
+    TypedConstant(i64, Type),
     FloatConstant(f64),
+    /// Imaginary constant `2.0i`, of type `_Complex double`.
+    ImaginaryConstant(f64),
     StringLiteral(String),
+    /// `L"..."`, `u"..."`, or `U"..."`: an array of `wchar_t`, `char16_t`, or
+    /// `char32_t`. The string holds code points; `u8` literals stay `StringLiteral`.
+    WideStringLiteral(String, StringEncoding),
     Index {
         array: Box<Expr>,
         index: Box<Expr>,
@@ -422,6 +600,10 @@ pub enum Expr {
     },
     /// GCC extension: address of a label (`&&label`).
     LabelAddr(String),
+    /// `__real__ expr` or `creal(expr)`: real part of a complex value.
+    Real(Box<Expr>),
+    /// `__imag__ expr` or `cimag(expr)`: imaginary part of a complex value.
+    Imag(Box<Expr>),
 }
 
 /// A single item inside a brace-enclosed initializer list.
@@ -444,7 +626,7 @@ pub enum Designator {
     Range { start: i64, end: i64 },
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -479,7 +661,7 @@ pub enum BinaryOp {
     ShiftRightAssign,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum UnaryOp {
     Plus,
     Minus,
@@ -0,0 +1,268 @@
+//! Source locations shared by tokens, AST nodes, and diagnostics.
+//!
+//! A `Span` is a byte range inside one file registered in a `SourceMap`.
+//! Spans are cheap to copy and carry no text; the `SourceMap` turns them
+//! into `file:line:col` positions and caret snippets when a diagnostic is
+//! printed.
+
+use std::fmt::Write;
+
+/// Identifies a file registered in a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct FileId(pub u32);
+
+/// Half-open byte range `start..end` in one source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file_id: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file_id: FileId, start: usize, end: usize) -> Self {
+        Span { file_id, start, end }
+    }
+
+    /// Smallest span covering both `self` and `other` (same file assumed).
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file_id: self.file_id,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// A value paired with the span it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+/// 1-based line and column (in bytes) of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// A preprocessor line marker (`# 12 "file.h"` or `#line 12 "file.h"`):
+/// the line starting at byte `offset` is line `line` of `file`. Without a
+/// file name the marker renumbers lines of the current file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMarker {
+    pub offset: usize,
+    pub line: usize,
+    pub file: Option<String>,
+    /// GCC flag 3: the text after a marker naming a file comes from a
+    /// system header. A marker without a file name keeps the current state.
+    pub system_header: bool,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    text: String,
+    /// Byte offset where each line starts; always begins with 0.
+    line_starts: Vec<usize>,
+    /// Line markers by offset, each with the file name it resolves to.
+    markers: Vec<(LineMarker, String)>,
+}
+
+/// Owns the text of every compiled file so spans can be resolved and rendered.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a file and return its id. Ids are handed out in order from 0.
+    pub fn add_file(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.push(SourceFile { name: name.into(), text, line_starts, markers: Vec::new() });
+        FileId((self.files.len() - 1) as u32)
+    }
+
+    fn file(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+
+    pub fn name(&self, id: FileId) -> Option<&str> {
+        self.file(id).map(|f| f.name.as_str())
+    }
+
+    pub fn source(&self, id: FileId) -> Option<&str> {
+        self.file(id).map(|f| f.text.as_str())
+    }
+
+    /// Text covered by `span`, if it lies inside its file.
+    pub fn snippet(&self, span: Span) -> Option<&str> {
+        self.file(span.file_id)?.text.get(span.start..span.end)
+    }
+
+    /// Line and column of a byte offset. Offsets past the end clamp to the end.
+    pub fn line_col(&self, id: FileId, offset: usize) -> Option<LineCol> {
+        let file = self.file(id)?;
+        let offset = offset.min(file.text.len());
+        let line = file.line_starts.partition_point(|&s| s <= offset) - 1;
+        Some(LineCol { line: line + 1, col: offset - file.line_starts[line] + 1 })
+    }
+
+    /// Record the line markers of a preprocessed file, so diagnostics report
+    /// the original file and line (`presumed`) instead of the position in
+    /// the preprocessor output. `markers` must be in source order.
+    pub fn add_line_markers(&mut self, id: FileId, markers: Vec<LineMarker>) {
+        let Some(file) = self.files.get_mut(id.0 as usize) else {
+            return;
+        };
+        for marker in markers {
+            let name = match (&marker.file, file.markers.last()) {
+                (Some(name), _) => name.clone(),
+                (None, Some((_, current))) => current.clone(),
+                (None, None) => file.name.clone(),
+            };
+            file.markers.push((marker, name));
+        }
+    }
+
+    /// File name and line/column of a byte offset as the line markers
+    /// describe it. Without markers this is the file's own name and `line_col`.
+    pub fn presumed(&self, id: FileId, offset: usize) -> Option<(&str, LineCol)> {
+        let file = self.file(id)?;
+        let pos = self.line_col(id, offset)?;
+        let index = file.markers.partition_point(|(m, _)| m.offset <= offset);
+        let Some((marker, name)) = index.checked_sub(1).map(|i| &file.markers[i]) else {
+            return Some((file.name.as_str(), pos));
+        };
+        let marker_line = self.line_col(id, marker.offset)?.line;
+        Some((name.as_str(), LineCol { line: marker.line + (pos.line - marker_line), col: pos.col }))
+    }
+
+    /// Text of a 1-based line, without its newline.
+    pub fn line_text(&self, id: FileId, line: usize) -> Option<&str> {
+        let file = self.file(id)?;
+        let start = *file.line_starts.get(line.checked_sub(1)?)?;
+        let end = file.line_starts.get(line).map_or(file.text.len(), |&e| e - 1);
+        Some(file.text[start..end].trim_end_matches('\r'))
+    }
+
+    /// `name:line:col` for the start of `span`, following any line markers.
+    pub fn location(&self, span: Span) -> Option<String> {
+        let (name, pos) = self.presumed(span.file_id, span.start)?;
+        Some(format!("{}:{}:{}", name, pos.line, pos.col))
+    }
+
+    /// Render a diagnostic in the familiar compiler layout:
+    ///
+    /// ```text
+    /// file.c:2:12: error: something went wrong
+    /// 2 |     return x +;
+    ///   |            ^^
+    /// ```
+    ///
+    /// Spans crossing a line break are underlined to the end of their first line.
+    /// Falls back to the bare message if the span's file is unknown. Line
+    /// numbers are the presumed ones when the file has line markers.
+    pub fn render(&self, span: Span, message: &str) -> String {
+        let (Some(location), Some(pos), Some((_, presumed))) = (
+            self.location(span),
+            self.line_col(span.file_id, span.start),
+            self.presumed(span.file_id, span.start),
+        ) else {
+            return message.to_string();
+        };
+        let line = self.line_text(span.file_id, pos.line).unwrap_or("");
+        let underline_len = span.len().min(line.len().saturating_sub(pos.col - 1)).max(1);
+        let gutter = presumed.line.to_string().len();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}: {}", location, message);
+        let _ = writeln!(out, "{:>w$} | {}", presumed.line, line, w = gutter);
+        let _ = write!(out, "{:>w$} | {}{}", "", " ".repeat(pos.col - 1), "^".repeat(underline_len), w = gutter);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> (SourceMap, FileId) {
+        let mut sm = SourceMap::new();
+        let id = sm.add_file("t.c", "int main() {\n    return x +;\n}\n");
+        (sm, id)
+    }
+
+    #[test]
+    fn resolves_line_and_column() {
+        let (sm, id) = map();
+        assert_eq!(sm.line_col(id, 0), Some(LineCol { line: 1, col: 1 }));
+        assert_eq!(sm.line_col(id, 17), Some(LineCol { line: 2, col: 5 }));
+        assert_eq!(sm.line_text(id, 2), Some("    return x +;"));
+        assert_eq!(sm.snippet(Span::new(id, 17, 23)), Some("return"));
+    }
+
+    #[test]
+    fn renders_caret_under_span() {
+        let (sm, id) = map();
+        let rendered = sm.render(Span::new(id, 24, 26), "error: undeclared identifier");
+        assert_eq!(
+            rendered,
+            "t.c:2:12: error: undeclared identifier\n2 |     return x +;\n  |            ^^"
+        );
+    }
+
+    #[test]
+    fn line_markers_give_presumed_locations() {
+        let mut sm = SourceMap::new();
+        let text = "# 1 \"main.c\"\nint a;\n# 7 \"inc.h\" 1\nint b;\n\nint c +;\n#line 40\nint d;\n";
+        let id = sm.add_file("main.i", text);
+        let at = |needle: &str| text.find(needle).unwrap();
+        sm.add_line_markers(id, vec![
+            LineMarker { offset: at("int a"), line: 1, file: Some("main.c".to_string()), system_header: false },
+            LineMarker { offset: at("int b"), line: 7, file: Some("inc.h".to_string()), system_header: false },
+            LineMarker { offset: at("int d"), line: 40, file: None, system_header: false },
+        ]);
+        assert_eq!(sm.presumed(id, 0), Some(("main.i", LineCol { line: 1, col: 1 })));
+        assert_eq!(sm.location(Span::new(id, at("a;"), at("a;") + 1)), Some("main.c:1:5".to_string()));
+        assert_eq!(sm.location(Span::new(id, at("+"), at("+") + 1)), Some("inc.h:9:7".to_string()));
+        assert_eq!(sm.location(Span::new(id, at("d;"), at("d;") + 1)), Some("inc.h:40:5".to_string()));
+        assert_eq!(
+            sm.render(Span::new(id, at("+"), at("+") + 1), "error: bad"),
+            "inc.h:9:7: error: bad\n9 | int c +;\n  |       ^"
+        );
+        // Tooling positions stay in the preprocessed text
+        assert_eq!(sm.line_col(id, at("+")), Some(LineCol { line: 6, col: 7 }));
+    }
+
+    #[test]
+    fn merges_spans() {
+        let a = Span::new(FileId(0), 4, 6);
+        let b = Span::new(FileId(0), 10, 12);
+        assert_eq!(a.to(b), Span::new(FileId(0), 4, 12));
+        assert_eq!(b.to(a).len(), 8);
+    }
+}
@@ -33,10 +33,22 @@ impl Platform {
     }
 }
 
+/// Instruction set the code generator emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arch {
+    #[default]
+    X86_64,
+    /// RV64GC with the LP64D calling convention.
+    Riscv64,
+    /// 32-bit x86 (i686) with the System V i386 calling convention.
+    I686,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CallingConvention {
     WindowsX64,  // Microsoft x64 calling convention
     SystemV,     // System V AMD64 ABI (Linux, BSD, macOS)
+    Cdecl,       // System V i386 ABI: every argument on the stack
 }
 
 impl CallingConvention {
@@ -52,7 +64,7 @@ impl CallingConvention {
     pub fn shadow_space_size(&self) -> usize {
         match self {
             CallingConvention::WindowsX64 => 32, // 4 registers × 8 bytes
-            CallingConvention::SystemV => 0,      // No shadow space
+            CallingConvention::SystemV | CallingConvention::Cdecl => 0, // No shadow space
         }
     }
 }
@@ -119,18 +131,98 @@ pub enum PicMode {
     Pie,
 }
 
+/// Assembly dialect of the emitted code and of inline asm templates
+/// (--asm-syntax, like GCC's -masm).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsmSyntax {
+    /// `.intel_syntax noprefix`: `mov rax, QWORD PTR [rbp-8]`.
+    #[default]
+    Intel,
+    /// GNU as's default: `movq -8(%rbp), %rax`.
+    Att,
+}
+
+/// Sizes and alignments of the scalar C types, in bytes.
+///
+/// `TypeLayout` builds struct, union, and array layouts on top of these, so
+/// every stage that asks for a size sees the same numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLayout {
+    pub pointer_size: usize,
+    pub pointer_align: usize,
+    pub short_size: usize,
+    pub int_size: usize,
+    pub long_size: usize,
+    pub long_long_size: usize,
+    pub long_long_align: usize,
+    pub float_size: usize,
+    pub double_size: usize,
+    pub double_align: usize,
+}
+
+impl DataLayout {
+    /// x86-64 LP64: 64-bit pointers and `long`.
+    pub const fn lp64() -> Self {
+        Self {
+            pointer_size: 8,
+            pointer_align: 8,
+            short_size: 2,
+            int_size: 4,
+            long_size: 8,
+            long_long_size: 8,
+            long_long_align: 8,
+            float_size: 4,
+            double_size: 8,
+            double_align: 8,
+        }
+    }
+
+    /// i386 ILP32: 32-bit pointers and `long`; `long long` and `double`
+    /// are only 4-byte aligned.
+    pub const fn ilp32() -> Self {
+        Self {
+            pointer_size: 4,
+            pointer_align: 4,
+            long_size: 4,
+            long_long_align: 4,
+            double_align: 4,
+            ..Self::lp64()
+        }
+    }
+
+    /// Layout for a platform. Windows is LLP64, but codegen still moves
+    /// `long` as a quadword, so both platforms use LP64 for now.
+    pub fn for_platform(_platform: Platform) -> Self {
+        Self::lp64()
+    }
+}
+
+impl Default for DataLayout {
+    fn default() -> Self {
+        Self::lp64()
+    }
+}
+
 /// Complete target configuration
 #[derive(Debug, Clone)]
 pub struct TargetConfig {
     pub platform: Platform,
+    pub arch: Arch,
+    /// Calling convention of the x86 backends; RISC-V always uses LP64D.
     pub calling_convention: CallingConvention,
     pub simd_level: SimdLevel,
     /// When true, do not use the 128-byte red zone below RSP (kernel code).
     pub no_red_zone: bool,
     /// When true, do not emit SSE/FPU instructions (kernel code).
     pub no_sse: bool,
+    /// When true, address locals from RSP and let RBP hold variables.
+    pub omit_frame_pointer: bool,
     /// PIC/PIE code generation for shared objects and PIE executables.
     pub pic_mode: PicMode,
+    /// Dialect of the assembly output.
+    pub asm_syntax: AsmSyntax,
+    /// Scalar sizes and alignments used for all type layout.
+    pub data_layout: DataLayout,
 }
 
 impl TargetConfig {
@@ -139,11 +231,15 @@ impl TargetConfig {
         let platform = Platform::host();
         Self {
             platform,
+            arch: Arch::X86_64,
             calling_convention: CallingConvention::for_platform(platform),
             simd_level: SimdLevel::detect(),
             no_red_zone: false,
             no_sse: false,
+            omit_frame_pointer: false,
             pic_mode: PicMode::None,
+            asm_syntax: AsmSyntax::Intel,
+            data_layout: DataLayout::for_platform(platform),
         }
     }
 
@@ -151,11 +247,38 @@ impl TargetConfig {
     pub fn for_platform(platform: Platform) -> Self {
         Self {
             platform,
+            arch: Arch::X86_64,
             calling_convention: CallingConvention::for_platform(platform),
             simd_level: SimdLevel::detect(),
             no_red_zone: false,
             no_sse: false,
+            omit_frame_pointer: false,
             pic_mode: PicMode::None,
+            asm_syntax: AsmSyntax::Intel,
+            data_layout: DataLayout::for_platform(platform),
+        }
+    }
+
+    /// Configuration for 64-bit RISC-V Linux (RV64GC, LP64D). There is no
+    /// vector extension in RV64GC, so SIMD is off.
+    pub fn riscv64() -> Self {
+        Self {
+            arch: Arch::Riscv64,
+            simd_level: SimdLevel::None,
+            ..Self::for_platform(Platform::Linux)
+        }
+    }
+
+    /// Configuration for 32-bit x86 Linux: ILP32 types and cdecl calls.
+    /// Floating point uses SSE2 scalar instructions, but nothing is
+    /// vectorized.
+    pub fn i686() -> Self {
+        Self {
+            arch: Arch::I686,
+            calling_convention: CallingConvention::Cdecl,
+            simd_level: SimdLevel::None,
+            data_layout: DataLayout::ilp32(),
+            ..Self::for_platform(Platform::Linux)
         }
     }
 
@@ -195,6 +318,13 @@ mod tests {
         assert_eq!(linux_cc.shadow_space_size(), 0);
     }
 
+    #[test]
+    fn test_data_layout() {
+        let dl = TargetConfig::for_platform(Platform::Linux).data_layout;
+        assert_eq!(dl, DataLayout::lp64());
+        assert_eq!(dl.pointer_size, 8);
+    }
+
     #[test]
     fn test_target_config() {
         let config = TargetConfig::host();
@@ -202,5 +332,26 @@ mod tests {
             config.calling_convention,
             CallingConvention::for_platform(config.platform)
         );
+        assert_eq!(config.arch, Arch::X86_64);
+    }
+
+    #[test]
+    fn test_riscv64_config() {
+        let config = TargetConfig::riscv64();
+        assert_eq!(config.arch, Arch::Riscv64);
+        assert_eq!(config.platform, Platform::Linux);
+        assert_eq!(config.simd_level, SimdLevel::None);
+        assert_eq!(config.data_layout, DataLayout::lp64());
+    }
+
+    #[test]
+    fn test_i686_config() {
+        let config = TargetConfig::i686();
+        assert_eq!(config.arch, Arch::I686);
+        assert_eq!(config.calling_convention, CallingConvention::Cdecl);
+        assert_eq!(config.calling_convention.shadow_space_size(), 0);
+        let dl = config.data_layout;
+        assert_eq!((dl.pointer_size, dl.long_size, dl.long_long_size), (4, 4, 8));
+        assert_eq!((dl.long_long_align, dl.double_align), (4, 4));
     }
 }
@@ -3,8 +3,8 @@
 //! Used by the semantic analyzer for validation; mirrors rules applied during IR lowering.
 
 use crate::{
-    BinaryOp, Expr, FunctionPrototype, Program, StructDef, StructField, Type, TypeQualifiers, UnaryOp,
-    UnionDef,
+    BinaryOp, CompileError, EnumDef, ErrorCode, Expr, FunctionPrototype, Program, StringEncoding, StructDef, StructField, Type,
+    TypeLayout, TypeQualifiers, UnaryOp, UnionDef,
 };
 use std::collections::{HashMap, HashSet};
 
@@ -26,6 +26,8 @@ pub struct TypeEnv {
     pub unions: HashMap<String, UnionDef>,
     pub forward_structs: HashSet<String>,
     pub enum_constants: HashSet<String>,
+    /// Enum tag → definition, including the underlying type.
+    pub enums: HashMap<String, EnumDef>,
 }
 
 impl TypeEnv {
@@ -46,6 +48,11 @@ impl TypeEnv {
                 .collect(),
             forward_structs: program.forward_structs.iter().cloned().collect(),
             enum_constants: HashSet::new(),
+            enums: program
+                .enums
+                .iter()
+                .map(|e| (e.name.clone(), e.clone()))
+                .collect(),
         };
 
         for e in &program.enums {
@@ -80,6 +87,7 @@ impl TypeEnv {
                         .iter()
                         .map(|(t, _)| env.resolve_type(t))
                         .collect(),
+                    is_variadic: f.is_variadic,
                 },
             );
         }
@@ -108,10 +116,33 @@ impl TypeEnv {
             Type::FunctionPointer {
                 return_type: Box::new(sig.return_type.clone()),
                 param_types: sig.param_types.clone(),
+                is_variadic: sig.is_variadic,
             },
         );
     }
 
+    /// Layout calculator for the program's aggregates.
+    pub fn layout(&self) -> TypeLayout<'_> {
+        TypeLayout::with_typedefs(&self.structs, &self.unions, &self.typedefs).with_enums(&self.enums)
+    }
+
+    /// Value of an enumeration constant.
+    pub fn enum_constant_value(&self, name: &str) -> Option<i64> {
+        self.enums.values().find_map(|e| e.value_of(name))
+    }
+
+    /// Enumerators of `enum tag` whose values are not in `handled`, in
+    /// declaration order. Empty for unknown tags.
+    pub fn unhandled_enumerators(&self, tag: &str, handled: &HashSet<i64>) -> Vec<String> {
+        self.enums.get(tag).map_or_else(Vec::new, |e| {
+            e.constants
+                .iter()
+                .filter(|(_, v)| !handled.contains(v))
+                .map(|(n, _)| n.clone())
+                .collect()
+        })
+    }
+
     /// Resolve typedef and typeof wrappers to a concrete type.
     pub fn resolve_type(&self, ty: &Type) -> Type {
         match ty {
@@ -139,8 +170,9 @@ impl TypeEnv {
     pub fn is_complete_type(&self, ty: &Type) -> bool {
         let ty = self.resolve_type(ty);
         match ty {
-            Type::Struct(name) => self.structs.contains_key(&name),
-            Type::Union(name) => self.unions.contains_key(&name),
+            // A record that contains itself by value never becomes complete
+            Type::Struct(name) => self.structs.contains_key(&name) && self.layout().self_containing_field(&name).is_none(),
+            Type::Union(name) => self.unions.contains_key(&name) && self.layout().self_containing_field(&name).is_none(),
             Type::Array(inner, 0) => self.is_complete_type(&inner),
             Type::Void => false,
             Type::TypeofExpr(_) => false,
@@ -170,6 +202,18 @@ impl TypeEnv {
         matches!(ty, Type::Float | Type::Double)
     }
 
+    pub fn is_complex_type(ty: &Type) -> bool {
+        matches!(ty, Type::Complex(_))
+    }
+
+    /// Element type of a complex type; other types are returned unchanged.
+    pub fn real_type(ty: &Type) -> Type {
+        match ty {
+            Type::Complex(elem) => elem.as_ref().clone(),
+            other => other.clone(),
+        }
+    }
+
     pub fn is_scalar_type(ty: &Type) -> bool {
         Self::is_integer_type(ty) || Self::is_floating_type(ty) || matches!(ty, Type::Pointer(_, ..))
     }
@@ -210,6 +254,11 @@ impl TypeEnv {
 
     /// C11 §6.3.1.8 usual arithmetic conversions (binary ops).
     pub fn usual_arithmetic_conversions(a: &Type, b: &Type) -> Type {
+        if Self::is_complex_type(a) || Self::is_complex_type(b) {
+            let elem = Self::usual_arithmetic_conversions(&Self::real_type(a), &Self::real_type(b));
+            let elem = if Self::is_floating_type(&elem) { elem } else { Type::Double };
+            return Type::Complex(Box::new(elem));
+        }
         if Self::is_floating_type(a) || Self::is_floating_type(b) {
             if matches!(a, Type::Double) || matches!(b, Type::Double) {
                 return Type::Double;
@@ -237,6 +286,16 @@ impl TypeEnv {
         ta
     }
 
+    /// The array type of a string literal, whose size counts its encoded
+    /// code units and the terminator; `None` for any other expression.
+    pub fn string_literal_type(expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::StringLiteral(s) => Some(StringEncoding::Plain.array_type(s)),
+            Expr::WideStringLiteral(s, encoding) => Some(encoding.array_type(s)),
+            _ => None,
+        }
+    }
+
     pub fn decay_array(ty: &Type) -> Type {
         match ty {
             Type::Array(inner, _) => Type::ptr((**inner).clone()),
@@ -244,11 +303,10 @@ impl TypeEnv {
         }
     }
 
+    /// Whether the object a pointer points to is `const`. Only the first
+    /// level counts: `*pp = q` is fine for `const char **pp`.
     pub fn pointee_is_const(ty: &Type) -> bool {
-        match ty {
-            Type::Pointer(inner, q) => q.is_const || Self::pointee_is_const(inner),
-            _ => false,
-        }
+        matches!(ty, Type::Pointer(_, q) if q.is_const)
     }
 
     pub fn types_compatible(&self, a: &Type, b: &Type) -> bool {
@@ -265,12 +323,17 @@ impl TypeEnv {
             (Type::LongLong, Type::LongLong) | (Type::UnsignedLongLong, Type::UnsignedLongLong) => true,
             (Type::Float, Type::Float) | (Type::Double, Type::Double) => true,
             (Type::Bool, Type::Bool) => true,
+            (Type::Complex(a_e), Type::Complex(b_e)) => a_e == b_e,
             (Type::Pointer(a_i, _), Type::Pointer(b_i, _)) => self.types_compatible(a_i, b_i),
             (Type::Array(a_i, _), Type::Array(b_i, _)) => self.types_compatible(a_i, b_i),
             (Type::Struct(a), Type::Struct(b)) => a == b,
             (Type::Union(a), Type::Union(b)) => a == b,
-            (Type::FunctionPointer { return_type: ar, param_types: ap }, Type::FunctionPointer { return_type: br, param_types: bp }) => {
-                ap.len() == bp.len()
+            (
+                Type::FunctionPointer { return_type: ar, param_types: ap, is_variadic: av },
+                Type::FunctionPointer { return_type: br, param_types: bp, is_variadic: bv },
+            ) => {
+                av == bv
+                    && ap.len() == bp.len()
                     && self.types_compatible(ar, br)
                     && ap.iter().zip(bp.iter()).all(|(x, y)| self.types_compatible(x, y))
             }
@@ -290,7 +353,7 @@ impl TypeEnv {
         let lhs = self.resolve_type(lhs);
         let mut rhs = self.resolve_type(rhs);
         rhs = Self::decay_array(&rhs);
-        if self.types_compatible(&lhs, &rhs) {
+        if self.types_compatible(&lhs, &rhs) || Self::is_opaque(&lhs) || Self::is_opaque(&rhs) {
             return true;
         }
         // Pointer assignment: null (0), void*, compatible pointees
@@ -312,6 +375,14 @@ impl TypeEnv {
                 return true;
             }
         }
+        // Any scalar converts to _Bool (zero → 0, anything else → 1)
+        if matches!(lhs, Type::Bool)
+            && (Self::is_integer_type(&rhs)
+                || Self::is_floating_type(&rhs)
+                || matches!(rhs, Type::Pointer(..) | Type::FunctionPointer { .. }))
+        {
+            return true;
+        }
         if Self::is_floating_type(&lhs) && Self::is_integer_type(&rhs) {
             return true;
         }
@@ -321,14 +392,25 @@ impl TypeEnv {
         if Self::is_floating_type(&lhs) && Self::is_floating_type(&rhs) {
             return true;
         }
+        // Real values become complex with a zero imaginary part; complex
+        // values assigned to a real object keep only their real part.
+        if Self::is_arithmetic(&lhs) && Self::is_arithmetic(&rhs)
+            && (Self::is_complex_type(&lhs) || Self::is_complex_type(&rhs))
+        {
+            return true;
+        }
         false
     }
 
     pub fn expr_type(&self, expr: &Expr, locals: &HashMap<String, Type>) -> Type {
         match expr {
             Expr::Constant(_) => Type::Int,
+            Expr::TypedConstant(_, ty) => ty.clone(),
             Expr::FloatConstant(_) => Type::Double,
+            Expr::ImaginaryConstant(_) => Type::Complex(Box::new(Type::Double)),
+            Expr::Real(inner) | Expr::Imag(inner) => Self::real_type(&self.expr_type(inner, locals)),
             Expr::StringLiteral(_) => Type::ptr(Type::Char),
+            Expr::WideStringLiteral(_, encoding) => Type::ptr(encoding.unit_type()),
             Expr::Variable(name) => {
                 if let Some(t) = locals.get(name) {
                     return t.clone();
@@ -360,17 +442,9 @@ impl TypeEnv {
             }
             Expr::Call { func, .. } => self.call_return_type(func, locals),
             Expr::SizeOf(_) | Expr::SizeOfExpr(_) | Expr::AlignOf(_) => Type::Long,
-            Expr::Conditional { then_expr, else_expr, .. } => {
-                let t = self.expr_type(then_expr, locals);
-                let e = self.expr_type(else_expr, locals);
-                if Self::is_arithmetic(&t) && Self::is_arithmetic(&e) {
-                    Self::usual_arithmetic_conversions(&t, &e)
-                } else if self.types_compatible(&t, &e) {
-                    t
-                } else {
-                    t
-                }
-            }
+            Expr::Conditional { then_expr, else_expr, .. } => self
+                .conditional_type(then_expr, else_expr, locals)
+                .unwrap_or_else(|_| self.expr_type(then_expr, locals)),
             Expr::CompoundLiteral { r#type, .. } => self.resolve_type(r#type),
             Expr::Comma(exprs) => exprs
                 .last()
@@ -409,7 +483,7 @@ impl TypeEnv {
     }
 
     fn is_arithmetic(ty: &Type) -> bool {
-        Self::is_integer_type(ty) || Self::is_floating_type(ty)
+        Self::is_integer_type(ty) || Self::is_floating_type(ty) || Self::is_complex_type(ty)
     }
 
     fn binary_type(
@@ -448,8 +522,12 @@ impl TypeEnv {
         ) {
             return Type::Int;
         }
-        let lt = self.expr_type(left, locals);
-        let rt = self.expr_type(right, locals);
+        let lt = Self::decay_array(&self.expr_type(left, locals));
+        let rt = Self::decay_array(&self.expr_type(right, locals));
+        // A shift has the promoted type of its left operand
+        if matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) && Self::is_integer_type(&lt) {
+            return Self::integer_promotion(&lt);
+        }
         if matches!(op, BinaryOp::Add | BinaryOp::Sub) {
             if let Type::Pointer(inner, ..) = &lt {
                 if Self::is_integer_type(&rt) {
@@ -469,7 +547,9 @@ impl TypeEnv {
                 }
             }
         }
-        if Self::is_floating_type(&lt) || Self::is_floating_type(&rt) {
+        if Self::is_floating_type(&lt) || Self::is_floating_type(&rt)
+            || Self::is_complex_type(&lt) || Self::is_complex_type(&rt)
+        {
             return Self::usual_arithmetic_conversions(&lt, &rt);
         }
         if Self::is_integer_type(&lt) && Self::is_integer_type(&rt) {
@@ -509,37 +589,27 @@ impl TypeEnv {
             }
         }
         match ty {
-            Type::Struct(name) => self
-                .structs
-                .get(&name)
-                .and_then(|s| s.fields.iter().find(|f| f.name == member))
-                .map(|f| f.field_type.clone())
-                .unwrap_or(Type::Int),
-            Type::Union(name) => self
-                .unions
-                .get(&name)
-                .and_then(|u| u.fields.iter().find(|f| f.name == member))
-                .map(|f| f.field_type.clone())
-                .unwrap_or(Type::Int),
+            Type::Struct(name) | Type::Union(name) => self.layout().member_offset(&name, member).1,
             _ => Type::Int,
         }
     }
 
     fn call_return_type(&self, func: &Expr, locals: &HashMap<String, Type>) -> Type {
-        match func {
-            Expr::Variable(name) => self
-                .functions
-                .get(name)
-                .map(|s| s.return_type.clone())
-                .unwrap_or(Type::Int),
-            _ => {
-                let ft = self.expr_type(func, locals);
-                if let Type::FunctionPointer { return_type, .. } = ft {
-                    *return_type
-                } else {
-                    Type::Int
-                }
-            }
+        // A function, unless a local function pointer shadows it
+        if let Expr::Variable(name) = func
+            && !locals.contains_key(name)
+            && let Some(sig) = self.functions.get(name)
+        {
+            return sig.return_type.clone();
+        }
+        let ft = self.resolve_type(&self.expr_type(func, locals));
+        match ft {
+            Type::FunctionPointer { return_type, .. } => *return_type,
+            Type::Pointer(inner, _) => match self.resolve_type(&inner) {
+                Type::FunctionPointer { return_type, .. } => *return_type,
+                _ => Type::Int,
+            },
+            _ => Type::Int,
         }
     }
 
@@ -548,7 +618,7 @@ impl TypeEnv {
         func: &Expr,
         args: &[Expr],
         locals: &HashMap<String, Type>,
-    ) -> Result<(), String> {
+    ) -> Result<(), CompileError> {
         let (sig, name) = match func {
             Expr::Variable(name) => (
                 self.functions.get(name).cloned(),
@@ -561,39 +631,259 @@ impl TypeEnv {
         };
         let required = sig.param_types.len();
         if args.len() < required || (!sig.is_variadic && args.len() > required) {
-            return Err(format!(
+            return Err(CompileError::new(ErrorCode::ArgumentCount, format!(
                 "Call to '{}' expects {} argument(s){}, got {}",
                 name.unwrap_or_default(),
                 required,
                 if sig.is_variadic { " (variadic)" } else { "" },
                 args.len()
-            ));
+            )));
         }
         for (i, arg) in args.iter().enumerate().take(required) {
             let expected = &sig.param_types[i];
             let got = self.expr_type(arg, locals);
             if !self.is_assign_compatible(expected, &got) {
-                return Err(format!(
-                    "Argument {} to '{}': expected {:?}, got {:?}",
+                return Err(CompileError::new(ErrorCode::IncompatibleTypes, format!(
+                    "Argument {} to '{}': expected '{}', got '{}'",
                     i + 1,
                     name.unwrap_or_default(),
                     expected,
                     got
-                ));
+                )));
             }
         }
         Ok(())
     }
 
+    /// The operator behind a compound assignment (`+=` → `+`).
+    pub fn compound_assign_op(op: &BinaryOp) -> Option<BinaryOp> {
+        Some(match op {
+            BinaryOp::AddAssign => BinaryOp::Add,
+            BinaryOp::SubAssign => BinaryOp::Sub,
+            BinaryOp::MulAssign => BinaryOp::Mul,
+            BinaryOp::DivAssign => BinaryOp::Div,
+            BinaryOp::ModAssign => BinaryOp::Mod,
+            BinaryOp::BitwiseAndAssign => BinaryOp::BitwiseAnd,
+            BinaryOp::BitwiseOrAssign => BinaryOp::BitwiseOr,
+            BinaryOp::BitwiseXorAssign => BinaryOp::BitwiseXor,
+            BinaryOp::ShiftLeftAssign => BinaryOp::ShiftLeft,
+            BinaryOp::ShiftRightAssign => BinaryOp::ShiftRight,
+            _ => return None,
+        })
+    }
+
+    fn is_pointer_like(ty: &Type) -> bool {
+        matches!(ty, Type::Pointer(..) | Type::FunctionPointer { .. })
+    }
+
+    /// Types the checker cannot see through yet (`typeof`, unknown typedef
+    /// names); operators applied to them are not diagnosed.
+    fn is_opaque(ty: &Type) -> bool {
+        matches!(ty, Type::TypeofExpr(_) | Type::Typedef(_))
+    }
+
+    /// Check that a binary operator (C11 §6.5.5–6.5.14) accepts operands of
+    /// types `lt` and `rt`; arrays are taken after decay. Assignment is
+    /// checked by `is_assign_compatible`.
+    pub fn check_binary_operands(&self, op: &BinaryOp, lt: &Type, rt: &Type) -> Result<(), CompileError> {
+        let l = Self::decay_array(&self.resolve_type(lt));
+        let r = Self::decay_array(&self.resolve_type(rt));
+        if Self::is_opaque(&l) || Self::is_opaque(&r) {
+            return Ok(());
+        }
+        let arith = Self::is_arithmetic(&l) && Self::is_arithmetic(&r);
+        let integer = Self::is_integer_type(&l) && Self::is_integer_type(&r);
+        let real = (Self::is_integer_type(&l) || Self::is_floating_type(&l))
+            && (Self::is_integer_type(&r) || Self::is_floating_type(&r));
+        let both_pointers = Self::is_pointer_like(&l) && Self::is_pointer_like(&r);
+        // Comparing a pointer with an integer is only a warning in C compilers
+        let pointer_and_integer = (Self::is_pointer_like(&l) && Self::is_integer_type(&r))
+            || (Self::is_integer_type(&l) && Self::is_pointer_like(&r));
+        let ok = match op {
+            BinaryOp::Add => arith || pointer_and_integer,
+            BinaryOp::Sub => {
+                arith
+                    || (Self::is_pointer_like(&l) && Self::is_integer_type(&r))
+                    || match (&l, &r) {
+                        // `types_compatible` lets integer types convert; pointees must match exactly
+                        (Type::Pointer(a, _), Type::Pointer(b, _)) => {
+                            self.resolve_type(a) == self.resolve_type(b)
+                                || matches!(**a, Type::Void)
+                                || matches!(**b, Type::Void)
+                        }
+                        _ => false,
+                    }
+            }
+            BinaryOp::Mul | BinaryOp::Div => arith,
+            BinaryOp::Mod
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor
+            | BinaryOp::ShiftLeft
+            | BinaryOp::ShiftRight => integer,
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                real || both_pointers || pointer_and_integer
+            }
+            BinaryOp::EqualEqual | BinaryOp::NotEqual => arith || both_pointers || pointer_and_integer,
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => Self::is_scalar_like(&l) && Self::is_scalar_like(&r),
+            _ => match Self::compound_assign_op(op) {
+                Some(inner) => return self.check_binary_operands(&inner, &l, &r),
+                None => true,
+            },
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(CompileError::new(
+                ErrorCode::InvalidOperand,
+                format!("invalid operands to binary {} ('{}' and '{}')", op, l, r),
+            ))
+        }
+    }
+
+    /// Check that a unary operator accepts an operand of type `ty`.
+    pub fn check_unary_operand(&self, op: &UnaryOp, ty: &Type) -> Result<(), CompileError> {
+        let ty = Self::decay_array(&self.resolve_type(ty));
+        if Self::is_opaque(&ty) {
+            return Ok(());
+        }
+        let ok = match op {
+            UnaryOp::Plus | UnaryOp::Minus => Self::is_arithmetic(&ty),
+            // `~` on a complex value is GCC's conjugate
+            UnaryOp::BitwiseNot => Self::is_integer_type(&ty) || Self::is_complex_type(&ty),
+            UnaryOp::LogicalNot => Self::is_scalar_like(&ty),
+            // Expressions of unknown type are typed `int`, so only reject
+            // operands that certainly are not pointers
+            UnaryOp::Deref => Self::is_pointer_like(&ty) || Self::is_integer_type(&ty),
+            UnaryOp::AddrOf => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(CompileError::new(ErrorCode::InvalidOperand, format!("invalid operand to unary {} ('{}')", op, ty)))
+        }
+    }
+
+    /// Check that `ty` can be tested against zero: a condition, or an operand
+    /// of `!`, `&&`, `||`, or `?:`.
+    pub fn check_scalar(&self, ty: &Type) -> Result<(), CompileError> {
+        let ty = Self::decay_array(&self.resolve_type(ty));
+        if Self::is_opaque(&ty) || Self::is_scalar_like(&ty) {
+            return Ok(());
+        }
+        Err(CompileError::new(ErrorCode::InvalidOperand, format!("'{}' used where a scalar is required", ty)))
+    }
+
+    /// Check the operands of `array[index]`: one must be a pointer and the
+    /// other an integer.
+    pub fn check_subscript(&self, array: &Type, index: &Type) -> Result<(), CompileError> {
+        let a = Self::decay_array(&self.resolve_type(array));
+        let i = Self::decay_array(&self.resolve_type(index));
+        if Self::is_opaque(&a) || Self::is_opaque(&i) {
+            return Ok(());
+        }
+        // Unknown expressions type as `int`, so `int[int]` is let through
+        let ok = (Self::is_pointer_like(&a) && Self::is_integer_type(&i))
+            || (Self::is_integer_type(&a) && (Self::is_pointer_like(&i) || Self::is_integer_type(&i)));
+        if ok {
+            Ok(())
+        } else {
+            Err(CompileError::new(
+                ErrorCode::InvalidOperand,
+                format!("invalid operands to subscript ('{}' and '{}')", a, i),
+            ))
+        }
+    }
+
+    /// The type of `c ? then_expr : else_expr` (C11 §6.5.15): the usual
+    /// arithmetic conversions of two arithmetic operands, the common struct,
+    /// union, or `void` type, or a pointer whose target carries the
+    /// qualifiers of both sides. A null pointer constant takes the type of
+    /// the other pointer, and `void *` absorbs any object pointer.
+    pub fn conditional_type(
+        &self,
+        then_expr: &Expr,
+        else_expr: &Expr,
+        locals: &HashMap<String, Type>,
+    ) -> Result<Type, CompileError> {
+        let t = Self::decay_array(&self.resolve_type(&self.expr_type(then_expr, locals)));
+        let e = Self::decay_array(&self.resolve_type(&self.expr_type(else_expr, locals)));
+        if Self::is_opaque(&t) || Self::is_opaque(&e) {
+            return Ok(t);
+        }
+        if Self::is_arithmetic(&t) && Self::is_arithmetic(&e) {
+            return Ok(Self::usual_arithmetic_conversions(&t, &e));
+        }
+        let composite = match (&t, &e) {
+            (Type::Void, Type::Void) => Some(t.clone()),
+            (Type::Struct(a), Type::Struct(b)) | (Type::Union(a), Type::Union(b)) => (a == b).then(|| t.clone()),
+            (Type::Pointer(a, aq), Type::Pointer(b, bq)) => {
+                let qualifiers = TypeQualifiers {
+                    is_const: aq.is_const || bq.is_const,
+                    is_volatile: aq.is_volatile || bq.is_volatile,
+                    is_restrict: aq.is_restrict && bq.is_restrict,
+                };
+                let target = if self.is_null_pointer_constant(then_expr) {
+                    Some(b)
+                } else if self.is_null_pointer_constant(else_expr) {
+                    Some(a)
+                } else if matches!(self.resolve_type(a), Type::Void) {
+                    Some(a)
+                } else if matches!(self.resolve_type(b), Type::Void) {
+                    Some(b)
+                } else {
+                    // `types_compatible` lets integer types convert; integer pointees must match exactly
+                    let (ra, rb) = (self.resolve_type(a), self.resolve_type(b));
+                    (ra == rb || (!Self::is_integer_type(&ra) && self.types_compatible(&ra, &rb))).then_some(a)
+                };
+                target.map(|target| Type::Pointer(target.clone(), qualifiers))
+            }
+            (Type::FunctionPointer { .. }, Type::FunctionPointer { .. }) => self.types_compatible(&t, &e).then(|| t.clone()),
+            // Unknown expressions type as `int`, so a pointer and any integer
+            // are let through with the pointer's type
+            _ if Self::is_pointer_like(&t) && Self::is_integer_type(&e) => Some(t.clone()),
+            _ if Self::is_integer_type(&t) && Self::is_pointer_like(&e) => Some(e.clone()),
+            _ => None,
+        };
+        composite.ok_or_else(|| {
+            CompileError::new(
+                ErrorCode::IncompatibleTypes,
+                format!("type mismatch in conditional expression ('{}' and '{}')", t, e),
+            )
+        })
+    }
+
+    /// Whether `expr` is a null pointer constant: an integer constant
+    /// expression with value 0, possibly cast to `void *`.
+    pub fn is_null_pointer_constant(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Cast(Type::Pointer(inner, _), value) if matches!(self.resolve_type(inner), Type::Void) => {
+                self.is_null_pointer_constant(value)
+            }
+            Expr::Cast(Type::Pointer(..), _) => false,
+            _ => matches!(crate::consteval::eval_constant(expr, self), Ok(crate::consteval::ConstValue::Int(0, _))),
+        }
+    }
+
+    /// Arithmetic, pointer, or function pointer.
+    fn is_scalar_like(ty: &Type) -> bool {
+        Self::is_arithmetic(ty) || Self::is_pointer_like(ty)
+    }
+
+    /// Whether `expr` designates an object (C11 §6.3.2.1). `s.m` is an
+    /// lvalue only when `s` is, so `f().m` is not.
     pub fn is_lvalue(expr: &Expr) -> bool {
-        matches!(
-            expr,
-            Expr::Variable(_)
-                | Expr::Index { .. }
-                | Expr::Member { .. }
-                | Expr::PtrMember { .. }
-                | Expr::Unary { op: UnaryOp::Deref, .. }
-        )
+        match expr {
+            Expr::Member { expr, .. } => Self::is_lvalue(expr),
+            _ => matches!(
+                expr,
+                Expr::Variable(_)
+                    | Expr::Index { .. }
+                    | Expr::PtrMember { .. }
+                    | Expr::Unary { op: UnaryOp::Deref, .. }
+                    | Expr::CompoundLiteral { .. }
+            ),
+        }
     }
 
     pub fn max_bitfield_width(field_type: &Type) -> usize {
@@ -606,20 +896,27 @@ impl TypeEnv {
         }
     }
 
-    pub fn validate_bitfield(field: &StructField) -> Result<(), String> {
+    pub fn validate_bitfield(field: &StructField) -> Result<(), CompileError> {
         if let Some(w) = field.bit_width {
             let max = Self::max_bitfield_width(&field.field_type);
             if max == 0 {
-                return Err(format!(
+                return Err(CompileError::new(ErrorCode::InvalidBitfield, format!(
                     "Bit-field '{}' has non-integral type",
                     field.name
-                ));
+                )));
             }
-            if w == 0 || w > max {
-                return Err(format!(
+            if w == 0 {
+                // Only an unnamed bit-field may have zero width (C11 6.7.2.1p4)
+                if field.name.is_empty() {
+                    return Ok(());
+                }
+                return Err(CompileError::new(ErrorCode::InvalidBitfield, format!("Named bit-field '{}' has zero width", field.name)));
+            }
+            if w > max {
+                return Err(CompileError::new(ErrorCode::InvalidBitfield, format!(
                     "Bit-field '{}' width {} exceeds type width {}",
                     field.name, w, max
-                ));
+                )));
             }
         }
         Ok(())
@@ -641,6 +938,20 @@ mod tests {
         assert_eq!(t, Type::UnsignedInt);
     }
 
+    #[test]
+    fn shift_has_promoted_left_type() {
+        let env = TypeEnv::from_program(&Program::default());
+        let mut locals = HashMap::new();
+        locals.insert("c".to_string(), Type::Char);
+        let shift = Expr::Binary {
+            left: Box::new(Expr::Variable("c".to_string())),
+            op: BinaryOp::ShiftLeft,
+            right: Box::new(Expr::TypedConstant(1, Type::Long)),
+        };
+        assert_eq!(env.expr_type(&shift, &locals), Type::Int);
+        assert!(env.check_binary_operands(&BinaryOp::ShiftLeft, &Type::Double, &Type::Int).is_err());
+    }
+
     #[test]
     fn array_decays_to_pointer() {
         let t = TypeEnv::decay_array(&Type::Array(Box::new(Type::Int), 10));
@@ -10,8 +10,7 @@ use model::{BinaryOp, UnaryOp};
 /// - x ^ 0 = x, x << 0 = x, x >> 0 = x
 pub fn algebraic_simplification(func: &mut Function) {
     // First pass: build a map from VarId to its defining instruction
-    // so we can detect double-negation patterns like ~~x, -(-x),
-    // and chain add/sub constants like (x+a)+b → x+(a+b).
+    // so we can detect double-negation patterns like ~~x, -(-x).
     let mut var_def: std::collections::HashMap<ir::VarId, Instruction> = std::collections::HashMap::new();
     for block in &func.blocks {
         for inst in &block.instructions {
@@ -36,7 +35,7 @@ pub fn algebraic_simplification(func: &mut Function) {
                     left,
                     right,
                 } => {
-                    if let Some(simplified) = try_simplify_binary(&op, &left, &right, dest, &var_def) {
+                    if let Some(simplified) = try_simplify_binary(&op, &left, &right, dest) {
                         new_instructions.push(simplified);
                     } else {
                         new_instructions.push(Instruction::Binary {
@@ -67,10 +66,8 @@ fn try_simplify_binary(
     left: &Operand,
     right: &Operand,
     dest: ir::VarId,
-    var_def: &std::collections::HashMap<ir::VarId, Instruction>,
 ) -> Option<Instruction> {
-    // First try basic simplifications
-    let basic = match op {
+    match op {
         BinaryOp::Mul => simplify_mul(left, right, dest),
         BinaryOp::Div => simplify_div(left, right, dest),
         BinaryOp::Mod => simplify_mod(left, right, dest),
@@ -84,49 +81,7 @@ fn try_simplify_binary(
         | BinaryOp::Less | BinaryOp::LessEqual
         | BinaryOp::Greater | BinaryOp::GreaterEqual => simplify_comparison(op, left, right, dest),
         _ => None,
-    };
-    if basic.is_some() {
-        return basic;
     }
-    
-    // Chain add/sub constants: (x + a) + b → x + (a + b)
-    if let Operand::Constant(c2) = right {
-        if matches!(op, BinaryOp::Add | BinaryOp::Sub) {
-            if let Operand::Var(src_var) = left {
-                if let Some(Instruction::Binary {
-                    op: prev_op,
-                    left: orig_left,
-                    right: Operand::Constant(c1),
-                    ..
-                }) = var_def.get(src_var) {
-                    if matches!(prev_op, BinaryOp::Add | BinaryOp::Sub) {
-                        // Combine: (x +/- a) +/- b
-                        let effective_c1 = if *prev_op == BinaryOp::Sub { -c1 } else { *c1 };
-                        let effective_c2 = if *op == BinaryOp::Sub { -c2 } else { *c2 };
-                        let combined = effective_c1 + effective_c2;
-                        
-                        if combined >= 0 {
-                            return Some(Instruction::Binary {
-                                dest,
-                                op: BinaryOp::Add,
-                                left: orig_left.clone(),
-                                right: Operand::Constant(combined),
-                            });
-                        } else {
-                            return Some(Instruction::Binary {
-                                dest,
-                                op: BinaryOp::Sub,
-                                left: orig_left.clone(),
-                                right: Operand::Constant(-combined),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    None
 }
 
 fn simplify_mul(left: &Operand, right: &Operand, dest: ir::VarId) -> Option<Instruction> {
@@ -1,159 +1,247 @@
-// Block Layout Optimization for I-cache Locality
+// Block Layout Optimization
 //
-// Reorders basic blocks to improve instruction cache performance by:
-// 1. Placing loop headers followed immediately by loop bodies (keeps hot loops tight)
-// 2. Placing fall-through targets immediately after conditional branches
-// 3. Keeping the loop exit block after the loop (cold path deferred)
+// Orders basic blocks so each one is followed by the successor it most
+// likely branches to, letting codegen's peephole drop that jump:
+// 1. Cold blocks go last: those that call a function that never returns
+//    (`abort`, `exit`, ...) or end in `unreachable`, a `__builtin_expect`
+//    unlikely side that has no other predecessor, and blocks that only
+//    lead to or are only reached from cold ones.
+// 2. Starting at the entry, each block is followed by its preferred
+//    successor: the hinted side of a branch, else the side that is not
+//    cold, else the side in more loops (the loop body over the exit), else
+//    `then`. A successor is placed only once all of its forward
+//    predecessors are, so an if/else join comes after both arms. When no
+//    successor can follow, the first ready block in creation order does.
+// 3. Loops are rotated: a loop laid out as header, body, latch, where the
+//    header tests and the latch jumps back, has its header moved after the
+//    latch, so an iteration runs one conditional branch instead of a
+//    conditional branch and a jump. Cold blocks of the loop do not count.
 //
-// This is a standard compiler optimization that reduces I-cache misses by
-// ensuring the most likely execution path is laid out sequentially in memory.
+// Blocks unreachable from the entry keep their relative order at the end.
 
-use ir::{Function, Terminator, BlockId};
-use std::collections::{HashSet, VecDeque};
+use ir::{BlockId, BranchHint, Function, Instruction, Terminator};
+use std::collections::{HashMap, HashSet};
+use crate::dominators::DominatorTree;
 use crate::loop_analysis;
 
-/// Optimize block layout for better I-cache locality
+/// Library functions that never return; a block calling one is cold.
+const NORETURN_LIBRARY: &[&str] = &[
+    "abort",
+    "exit",
+    "_exit",
+    "_Exit",
+    "quick_exit",
+    "longjmp",
+    "siglongjmp",
+    "__assert_fail",
+    "__stack_chk_fail",
+    "__builtin_trap",
+    "__builtin_unreachable",
+];
+
+/// Optimize block layout for better I-cache locality and fewer jumps
 pub fn optimize_block_layout(func: &mut Function) {
     if func.blocks.len() <= 2 {
         return; // Nothing to optimize
     }
+    let cfg = Cfg::new(func);
+    let mut layout = cfg.chain_layout(func);
+    cfg.rotate_loops(func, &mut layout);
+
+    let mut blocks: HashMap<BlockId, ir::BasicBlock> = func.blocks.drain(..).map(|b| (b.id, b)).collect();
+    func.blocks = layout.iter().filter_map(|id| blocks.remove(id)).collect();
+}
 
-    // Detect loops to identify hot blocks
-    let loops = loop_analysis::find_loops(func);
-    let mut loop_blocks: HashSet<BlockId> = HashSet::new();
-    let mut loop_headers: HashSet<BlockId> = HashSet::new();
+struct Cfg {
+    succs: HashMap<BlockId, Vec<BlockId>>,
+    /// Predecessors along forward (not loop back) edges
+    forward_preds: HashMap<BlockId, Vec<BlockId>>,
+    reachable: HashSet<BlockId>,
+    cold: HashSet<BlockId>,
+    /// Number of loops each block is in
+    depth: HashMap<BlockId, usize>,
+    /// Every loop's blocks, by header, with a header's back edges merged
+    loops: Vec<(BlockId, HashSet<BlockId>)>,
+}
 
-    for lp in &loops {
-        loop_headers.insert(lp.header);
-        for &b in &lp.body {
-            loop_blocks.insert(b);
+impl Cfg {
+    fn new(func: &Function) -> Self {
+        let succs = func.compute_successors();
+        let dom = DominatorTree::new(func);
+        let reachable: HashSet<BlockId> = dom.order().iter().copied().collect();
+        let mut forward_preds: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for &b in dom.order() {
+            for &s in succs.get(&b).map(Vec::as_slice).unwrap_or(&[]) {
+                if !dom.dominates(s, b) {
+                    forward_preds.entry(s).or_default().push(b);
+                }
+            }
         }
-    }
 
-    // Build successor map
-    let succ_map: std::collections::HashMap<BlockId, Vec<BlockId>> = func.blocks.iter()
-        .map(|b| {
-            let succs = match &b.terminator {
-                Terminator::Br(t) => vec![*t],
-                Terminator::CondBr { then_block, else_block, .. } => vec![*then_block, *else_block],
-                _ => vec![],
-            };
-            (b.id, succs)
-        })
-        .collect();
-
-    // Layout blocks using a modified DFS that prioritizes:
-    // 1. Fall-through targets (then_block for CondBr, target for Br)
-    // 2. Loop body blocks before exit blocks
-    // 3. Hot (loop) blocks before cold blocks
-    let mut ordered: Vec<BlockId> = Vec::with_capacity(func.blocks.len());
-    let mut visited: HashSet<BlockId> = HashSet::new();
-    let mut worklist: VecDeque<BlockId> = VecDeque::new();
-
-    // Start with entry block
-    worklist.push_back(func.entry_block);
-
-    while let Some(block_id) = worklist.pop_front() {
-        if visited.contains(&block_id) {
-            continue;
+        let mut by_header: HashMap<BlockId, HashSet<BlockId>> = HashMap::new();
+        for lp in loop_analysis::find_loops(func) {
+            by_header.entry(lp.header).or_default().extend(lp.body);
         }
-        visited.insert(block_id);
-        ordered.push(block_id);
-
-        // Get successors in priority order
-        if let Some(succs) = succ_map.get(&block_id) {
-            let block = func.blocks.iter().find(|b| b.id == block_id);
-            match block.map(|b| &b.terminator) {
-                Some(Terminator::Br(target)) => {
-                    // Unconditional: place target next
-                    if !visited.contains(target) {
-                        worklist.push_front(*target);
-                    }
+        let mut loops: Vec<(BlockId, HashSet<BlockId>)> = by_header.into_iter().collect();
+        // Inner loops first, so rotating one keeps its outer loop contiguous
+        loops.sort_by_key(|(header, body)| (body.len(), header.0));
+        let mut depth: HashMap<BlockId, usize> = HashMap::new();
+        for (_, body) in &loops {
+            for &b in body {
+                *depth.entry(b).or_default() += 1;
+            }
+        }
+
+        let mut cfg = Cfg { succs, forward_preds, reachable, cold: HashSet::new(), depth, loops };
+        cfg.cold = cfg.cold_blocks(func, &dom);
+        cfg
+    }
+
+    fn successors(&self, block: BlockId) -> &[BlockId] {
+        self.succs.get(&block).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn cold_blocks(&self, func: &Function, dom: &DominatorTree) -> HashSet<BlockId> {
+        let mut cold = HashSet::new();
+        for block in func.blocks.iter().filter(|b| self.reachable.contains(&b.id)) {
+            let never_returns = block.instructions.iter().any(|inst| {
+                matches!(inst, Instruction::Call { name, .. } if NORETURN_LIBRARY.contains(&name.as_str()))
+            });
+            if never_returns || matches!(block.terminator, Terminator::Unreachable) {
+                cold.insert(block.id);
+            }
+            if let Terminator::CondBr { then_block, else_block, hint, .. } = &block.terminator {
+                let unlikely = match hint {
+                    BranchHint::LikelyThen => Some(*else_block),
+                    BranchHint::LikelyElse => Some(*then_block),
+                    _ => None,
+                };
+                if let Some(unlikely) = unlikely.filter(|u| self.forward_preds.get(u).is_some_and(|p| p.len() == 1)) {
+                    cold.insert(unlikely);
                 }
-                Some(Terminator::CondBr {
-                    then_block,
-                    else_block,
-                    hint,
-                    ..
-                }) => {
-                    use ir::BranchHint;
-                    if *hint == BranchHint::LikelyThen {
-                        if !visited.contains(then_block) {
-                            worklist.push_front(*then_block);
-                        }
-                        if !visited.contains(else_block) {
-                            worklist.push_back(*else_block);
-                        }
-                    } else if *hint == BranchHint::LikelyElse {
-                        if !visited.contains(else_block) {
-                            worklist.push_front(*else_block);
-                        }
-                        if !visited.contains(then_block) {
-                            worklist.push_back(*then_block);
-                        }
-                    } else {
-                    // For conditional branches in loop headers:
-                    // Place the loop body block next (fall-through),
-                    // and the exit block later.
-                    let then_is_loop = loop_blocks.contains(then_block);
-                    let else_is_loop = loop_blocks.contains(else_block);
-
-                    if then_is_loop && !else_is_loop {
-                        // then is loop body, else is exit — place then first
-                        if !visited.contains(then_block) {
-                            worklist.push_front(*then_block);
-                        }
-                        if !visited.contains(else_block) {
-                            worklist.push_back(*else_block);
-                        }
-                    } else if else_is_loop && !then_is_loop {
-                        // else is loop body, then is exit — place else first
-                        if !visited.contains(else_block) {
-                            worklist.push_front(*else_block);
-                        }
-                        if !visited.contains(then_block) {
-                            worklist.push_back(*then_block);
-                        }
-                    } else {
-                        // Both or neither in loop — default: then first
-                        if !visited.contains(then_block) {
-                            worklist.push_front(*then_block);
-                        }
-                        if !visited.contains(else_block) {
-                            // Push exit/cold path to back
-                            worklist.push_back(*else_block);
-                        }
-                    }
-                    }
+            }
+        }
+        // Spread to blocks only reached from cold ones, and to blocks that
+        // only lead to cold ones
+        loop {
+            let mut changed = false;
+            for &b in dom.order().iter().skip(1) {
+                if cold.contains(&b) {
+                    continue;
                 }
-                _ => {
-                    // Ret/Unreachable: no successors
-                    for s in succs {
-                        if !visited.contains(s) {
-                            worklist.push_back(*s);
-                        }
-                    }
+                let preds = self.forward_preds.get(&b).map(Vec::as_slice).unwrap_or(&[]);
+                let succs = self.successors(b);
+                let from_cold = !preds.is_empty() && preds.iter().all(|p| cold.contains(p));
+                let to_cold = !succs.is_empty() && succs.iter().all(|s| cold.contains(s));
+                if from_cold || to_cold {
+                    cold.insert(b);
+                    changed = true;
                 }
             }
+            if !changed {
+                break;
+            }
         }
+        cold.remove(&func.entry_block);
+        cold
     }
 
-    // Add any unreachable blocks that weren't visited
-    for block in &func.blocks {
-        if !visited.contains(&block.id) {
-            ordered.push(block.id);
+    /// The successors of `block`, most likely first.
+    fn preferred_successors(&self, func: &Function, block: BlockId) -> Vec<BlockId> {
+        let Some(b) = func.blocks.iter().find(|b| b.id == block) else { return Vec::new() };
+        match &b.terminator {
+            Terminator::Br(target) => vec![*target],
+            Terminator::CondBr { then_block, else_block, hint, .. } => {
+                let (t, e) = (*then_block, *else_block);
+                let depth = |b: BlockId| self.depth.get(&b).copied().unwrap_or(0);
+                let else_first = match hint {
+                    BranchHint::LikelyThen => false,
+                    BranchHint::LikelyElse => true,
+                    _ if self.cold.contains(&t) != self.cold.contains(&e) => self.cold.contains(&t),
+                    _ => depth(e) > depth(t),
+                };
+                if else_first { vec![e, t] } else { vec![t, e] }
+            }
+            Terminator::Switch { cases, default, .. } => Terminator::switch_targets(cases, *default),
+            _ => Vec::new(),
         }
     }
 
-    // Reorder blocks according to the layout
-    if ordered.len() == func.blocks.len() {
-        let mut new_blocks = Vec::with_capacity(func.blocks.len());
-        for &block_id in &ordered {
-            if let Some(idx) = func.blocks.iter().position(|b| b.id == block_id) {
-                new_blocks.push(func.blocks[idx].clone());
+    /// Chains of likely successors from the entry, then cold blocks, then
+    /// unreachable ones.
+    fn chain_layout(&self, func: &Function) -> Vec<BlockId> {
+        let mut layout = Vec::with_capacity(func.blocks.len());
+        let mut placed: HashSet<BlockId> = HashSet::new();
+        // Cold predecessors are placed last, so they are not waited for
+        let ready = |b: BlockId, placed: &HashSet<BlockId>| {
+            self.forward_preds
+                .get(&b)
+                .is_none_or(|preds| preds.iter().all(|p| placed.contains(p) || self.cold.contains(p)))
+        };
+        let mut next = Some(func.entry_block);
+        while let Some(block) = next {
+            placed.insert(block);
+            layout.push(block);
+            let block_is_cold = self.cold.contains(&block);
+            let candidate = |b: &BlockId| {
+                !placed.contains(b) && self.reachable.contains(b) && (block_is_cold || !self.cold.contains(b))
+            };
+            next = self
+                .preferred_successors(func, block)
+                .into_iter()
+                .find(|s| candidate(s) && ready(*s, &placed))
+                .or_else(|| {
+                    // Hot blocks before cold ones, ready ones first, in
+                    // creation order
+                    let unplaced: Vec<BlockId> = func
+                        .blocks
+                        .iter()
+                        .map(|b| b.id)
+                        .filter(|b| !placed.contains(b) && self.reachable.contains(b))
+                        .collect();
+                    let hot = |b: &&BlockId| !self.cold.contains(*b);
+                    unplaced
+                        .iter()
+                        .filter(hot)
+                        .find(|b| ready(**b, &placed))
+                        .or_else(|| unplaced.iter().find(hot))
+                        .or_else(|| unplaced.iter().find(|b| ready(**b, &placed)))
+                        .or_else(|| unplaced.first())
+                        .copied()
+                });
+        }
+        layout.extend(func.blocks.iter().map(|b| b.id).filter(|b| !placed.contains(b)));
+        layout
+    }
+
+    /// Move each loop header that tests at the top after its latch, when
+    /// the loop's hot blocks are laid out contiguously from the header and
+    /// end with the jump back.
+    fn rotate_loops(&self, func: &Function, layout: &mut [BlockId]) {
+        let terminator = |id: BlockId| func.blocks.iter().find(|b| b.id == id).map(|b| &b.terminator);
+        for (header, body) in &self.loops {
+            if *header == func.entry_block {
+                continue;
+            }
+            let Some(Terminator::CondBr { then_block, else_block, .. }) = terminator(*header) else { continue };
+            let inside = match (body.contains(then_block), body.contains(else_block)) {
+                (true, false) => *then_block,
+                (false, true) => *else_block,
+                _ => continue,
+            };
+            let Some(start) = layout.iter().position(|b| b == header) else { continue };
+            let end = start + body.iter().filter(|b| !self.cold.contains(b)).count();
+            if end > layout.len()
+                || layout.get(start + 1) != Some(&inside)
+                || !layout[start..end].iter().all(|b| body.contains(b) && !self.cold.contains(b))
+            {
+                continue;
             }
+            if !matches!(terminator(layout[end - 1]), Some(Terminator::Br(target)) if target == header) {
+                continue;
+            }
+            layout[start..end].rotate_left(1);
         }
-        func.blocks = new_blocks;
     }
 }
 
@@ -211,4 +299,61 @@ mod tests {
         // Entry block should still be first
         assert_eq!(prog.functions[0].blocks[0].id, prog.functions[0].entry_block);
     }
+
+    #[test]
+    fn test_block_layout_moves_noreturn_block_last() {
+        let src = r#"
+            void abort(void);
+            int check(int x) {
+                if (x < 0) {
+                    abort();
+                }
+                return x + 1;
+            }
+            int main() {
+                return check(4);
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            optimize_block_layout(func);
+        }
+        let func = prog.functions.iter().find(|f| f.name == "check").unwrap();
+        let last = func.blocks.last().unwrap();
+        assert!(last.instructions.iter().any(|i| matches!(i, Instruction::Call { name, .. } if name == "abort")));
+        assert_eq!(ir::interpret(&prog, "test", &[]).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_block_layout_rotates_loop() {
+        let src = r#"
+            int sum(int n) {
+                int s = 0;
+                int i;
+                for (i = 0; i < n; i = i + 1) {
+                    s = s + i;
+                }
+                return s;
+            }
+            int main() {
+                return sum(5);
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            optimize_block_layout(func);
+        }
+        let func = prog.functions.iter().find(|f| f.name == "sum").unwrap();
+        // The latch falls through into the header, which branches back to the body
+        let pos = |id: BlockId| func.blocks.iter().position(|b| b.id == id).unwrap();
+        let rotated = func.blocks.iter().enumerate().any(|(i, b)| {
+            matches!(b.terminator, Terminator::Br(target) if pos(target) == i + 1
+                && matches!(&func.blocks[i + 1].terminator,
+                    Terminator::CondBr { then_block, .. } if pos(*then_block) < i + 1))
+        });
+        assert!(rotated);
+        assert_eq!(ir::interpret(&prog, "test", &[]).unwrap(), 10);
+    }
 }
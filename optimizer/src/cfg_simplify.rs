@@ -40,6 +40,10 @@ fn fold_constant_branches(func: &mut Function) -> bool {
                 block.terminator = Terminator::Br(target);
                 changed = true;
             }
+        } else if let Terminator::Switch { value: Operand::Constant(val), cases, default } = &block.terminator {
+            let target = cases.iter().find(|&&(v, _)| v == *val).map_or(*default, |&(_, b)| b);
+            block.terminator = Terminator::Br(target);
+            changed = true;
         }
     }
     changed
@@ -67,15 +71,20 @@ fn eliminate_dead_blocks(func: &mut Function) -> bool {
                     worklist.push(*then_block);
                     worklist.push(*else_block);
                 }
+                Terminator::Switch { cases, default, .. } => {
+                    worklist.extend(Terminator::switch_targets(cases, *default));
+                }
                 _ => {}
             }
         }
     }
     
-    // Mark unreachable blocks as dead
+    // Mark unreachable blocks as dead; a block with no instructions may still
+    // use a value in its terminator (`ret %v`)
     let mut changed = false;
     for block in &mut func.blocks {
-        if !reachable.contains(&block.id) && !block.instructions.is_empty() {
+        let dead = block.instructions.is_empty() && matches!(block.terminator, Terminator::Unreachable);
+        if !reachable.contains(&block.id) && !dead {
             block.instructions.clear();
             block.terminator = Terminator::Unreachable;
             changed = true;
@@ -181,6 +190,13 @@ fn merge_blocks(func: &mut Function) -> bool {
                         *else_block = BlockId(pred_idx);
                     }
                 }
+                Terminator::Switch { cases, default, .. } => {
+                    for target in cases.iter_mut().map(|(_, b)| b).chain(std::iter::once(default)) {
+                        if target.0 == succ_idx {
+                            *target = BlockId(pred_idx);
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -263,6 +279,23 @@ fn remove_empty_blocks(func: &mut Function) -> bool {
                         changed = true;
                     }
                 }
+                Terminator::Switch { cases, default, .. } => {
+                    for target in cases.iter_mut().map(|(_, b)| b).chain(std::iter::once(default)) {
+                        let mut final_target = *target;
+                        let mut visited = HashSet::new();
+                        while let Some(&next) = redirect_map.get(&final_target) {
+                            if visited.contains(&next) {
+                                break;
+                            }
+                            visited.insert(final_target);
+                            final_target = next;
+                        }
+                        if final_target != *target {
+                            *target = final_target;
+                            changed = true;
+                        }
+                    }
+                }
                 _ => {}
             }
             block.terminator = new_terminator;
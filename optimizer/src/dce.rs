@@ -1,70 +1,107 @@
+use crate::purity::PureFunctions;
 use ir::{Function, Instruction, Operand, VarId};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Dead code elimination: remove instructions that compute unused values
 ///
 /// Identifies variables that are never used and removes instructions that
-/// define them (as long as those instructions have no side effects).
-pub fn dce_function(func: &mut Function) -> bool {
-    let mut changed = false;
-    let used_vars = collect_used_vars(func);
-
-    // Remove instructions that define unused variables (without side effects)
-    for block in &mut func.blocks {
-        let initial_count = block.instructions.len();
-        block.instructions.retain(|inst| should_retain(inst, &used_vars));
-
-        if block.instructions.len() < initial_count {
-            changed = true;
+/// define them (as long as those instructions have no side effects). A call
+/// to a pure function that always returns counts as having none. Removing
+/// an instruction drops a use of each of its operands, and the definitions
+/// of an operand left with no uses are looked at again, so whole chains of
+/// dead computations go in one call.
+pub fn dce_function(func: &mut Function, pure: &PureFunctions) -> bool {
+    let mut use_counts = count_uses(func);
+    let mut definitions: HashMap<VarId, Vec<(usize, usize)>> = HashMap::new();
+    let mut worklist = Vec::new();
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            for dest in inst.dests() {
+                definitions.entry(dest).or_default().push((b, i));
+            }
+            worklist.push((b, i));
         }
     }
 
-    changed
-}
-
-fn collect_used_vars(func: &Function) -> HashSet<VarId> {
-    let mut used_vars = HashSet::new();
-
-    for block in &func.blocks {
-        for inst in &block.instructions {
-            collect_uses_from_instruction(inst, &mut used_vars);
+    let mut removed: HashSet<(usize, usize)> = HashSet::new();
+    while let Some((b, i)) = worklist.pop() {
+        let inst = &func.blocks[b].instructions[i];
+        if removed.contains(&(b, i)) || should_retain(inst, &use_counts, pure) {
+            continue;
         }
-
-        collect_uses_from_terminator(&block.terminator, &mut used_vars);
+        removed.insert((b, i));
+        inst.for_each_use(|v| {
+            let count = use_counts.entry(v).or_default();
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                worklist.extend(definitions.get(&v).into_iter().flatten().copied());
+            }
+        });
     }
 
-    used_vars
-}
-
-fn collect_uses_from_instruction(inst: &Instruction, used_vars: &mut HashSet<VarId>) {
-    inst.for_each_use(|v| { used_vars.insert(v); });
+    for (b, block) in func.blocks.iter_mut().enumerate() {
+        let mut i = 0;
+        block.instructions.retain(|_| {
+            i += 1;
+            !removed.contains(&(b, i - 1))
+        });
+    }
+    !removed.is_empty()
 }
 
-fn collect_uses_from_terminator(terminator: &ir::Terminator, used_vars: &mut HashSet<VarId>) {
-    match terminator {
-        ir::Terminator::CondBr { cond, .. } => {
-            add_operand_var(cond, used_vars);
+/// How many times each variable is used by an instruction or terminator.
+fn count_uses(func: &Function) -> HashMap<VarId, usize> {
+    let mut use_counts = HashMap::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            inst.for_each_use(|v| *use_counts.entry(v).or_default() += 1);
         }
-        ir::Terminator::Ret(Some(op)) => {
-            add_operand_var(op, used_vars);
+        match &block.terminator {
+            ir::Terminator::CondBr { cond: Operand::Var(v), .. }
+            | ir::Terminator::Ret(Some(Operand::Var(v)))
+            | ir::Terminator::IndirectBr { target: Operand::Var(v) }
+            | ir::Terminator::Switch { value: Operand::Var(v), .. } => {
+                *use_counts.entry(*v).or_default() += 1;
+            }
+            _ => {}
         }
-        _ => {}
     }
+    use_counts
 }
 
-fn add_operand_var(op: &Operand, used_vars: &mut HashSet<VarId>) {
-    if let Operand::Var(v) = op {
-        used_vars.insert(*v);
+fn should_retain(inst: &Instruction, use_counts: &HashMap<VarId, usize>, pure: &PureFunctions) -> bool {
+    let used = |dest: &VarId| use_counts.get(dest).is_some_and(|&count| count > 0);
+    if let Instruction::Call { dest, name, .. } = inst {
+        if pure.is_removable(name) {
+            return dest.as_ref().is_some_and(used);
+        }
     }
-}
-
-fn should_retain(inst: &Instruction, used_vars: &HashSet<VarId>) -> bool {
     if inst.has_side_effects() {
         return true;
     }
     // Pure computations - only keep if result is used
     match inst.dest() {
-        Some(dest) => used_vars.contains(&dest),
+        Some(dest) => used(&dest),
         None => true, // No dest and no side-effects shouldn't happen, keep to be safe
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dce_removes_dead_chains_in_one_call() {
+        let tokens = lexer::lex("int f(int a) { int b = a + 1; int c = b * 2; int d = c - a; return a; }").unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut prog = ir::Lowerer::new().lower_program(&ast).unwrap();
+        let func = &mut prog.functions[0];
+        ir::mem2reg(func);
+        assert!(dce_function(func, &PureFunctions::default()));
+        assert!(!dce_function(func, &PureFunctions::default()));
+        let binaries = func.blocks.iter().flat_map(|b| &b.instructions)
+            .filter(|inst| matches!(inst, Instruction::Binary { .. }))
+            .count();
+        assert_eq!(binaries, 0);
+    }
+}
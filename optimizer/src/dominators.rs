@@ -0,0 +1,174 @@
+// Dominator tree of a function's CFG
+//
+// Built with Cooper, Harvey, and Kennedy's iterative algorithm ("A Simple,
+// Fast Dominance Algorithm") over the reverse postorder of the blocks
+// reachable from the entry. Each block also gets the interval of preorder
+// numbers its subtree covers, so a dominance query is two comparisons.
+
+use ir::{BlockId, Function};
+use std::collections::{HashMap, HashSet};
+
+pub struct DominatorTree {
+    order: Vec<BlockId>,
+    children: HashMap<BlockId, Vec<BlockId>>,
+    // First and one-past-last preorder number of each block's subtree
+    interval: HashMap<BlockId, (usize, usize)>,
+}
+
+impl DominatorTree {
+    pub fn new(func: &Function) -> Self {
+        let successors = func.compute_successors();
+        let predecessors = func.compute_predecessors();
+
+        // Reverse postorder from an iterative depth-first search
+        let mut postorder = Vec::new();
+        let mut visited = HashSet::from([func.entry_block]);
+        let mut stack = vec![(func.entry_block, 0)];
+        while let Some((block, next)) = stack.last_mut() {
+            let succs = successors.get(block).map(Vec::as_slice).unwrap_or(&[]);
+            if let Some(&succ) = succs.get(*next) {
+                *next += 1;
+                if visited.insert(succ) {
+                    stack.push((succ, 0));
+                }
+            } else {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+        let order: Vec<BlockId> = postorder.into_iter().rev().collect();
+        let rank: HashMap<BlockId, usize> = order.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+
+        let mut idom: HashMap<BlockId, BlockId> = HashMap::from([(func.entry_block, func.entry_block)]);
+        let intersect = |idom: &HashMap<BlockId, BlockId>, mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while rank[&a] > rank[&b] {
+                    a = idom[&a];
+                }
+                while rank[&b] > rank[&a] {
+                    b = idom[&b];
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().skip(1) {
+                let mut new_idom = None;
+                for pred in predecessors.get(block).into_iter().flatten() {
+                    if !idom.contains_key(pred) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *pred,
+                        Some(current) => intersect(&idom, *pred, current),
+                    });
+                }
+                if let Some(new_idom) = new_idom {
+                    if idom.get(block) != Some(&new_idom) {
+                        idom.insert(*block, new_idom);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for block in order.iter().skip(1) {
+            children.entry(idom[block]).or_default().push(*block);
+        }
+
+        let mut interval = HashMap::new();
+        let mut counter = 0;
+        let mut stack = vec![(func.entry_block, false)];
+        while let Some((block, done)) = stack.pop() {
+            if done {
+                interval.entry(block).and_modify(|(_, end)| *end = counter);
+                continue;
+            }
+            interval.insert(block, (counter, counter));
+            counter += 1;
+            stack.push((block, true));
+            for child in children.get(&block).into_iter().flatten() {
+                stack.push((*child, false));
+            }
+        }
+        DominatorTree { order, children, interval }
+    }
+
+    /// The blocks reachable from the entry in reverse postorder, entry first.
+    pub fn order(&self) -> &[BlockId] {
+        &self.order
+    }
+
+    /// The blocks `block` immediately dominates, in reverse postorder.
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        self.children.get(&block).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether every path from the entry to `b` passes through `a`. A block
+    /// dominates itself; an unreachable block neither dominates nor is
+    /// dominated.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        match (self.interval.get(&a), self.interval.get(&b)) {
+            (Some(&(start, end)), Some(&(position, _))) => start <= position && position < end,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    #[test]
+    fn test_dominator_tree_of_a_diamond_and_loop() {
+        let src = r#"
+            int f(int n) {
+                int s = 0;
+                for (int i = 0; i < n; i++) {
+                    if (i & 1) s += i; else s -= 1;
+                }
+                return s;
+            }
+        "#;
+        let prog = compile_to_ir(src);
+        let func = &prog.functions[0];
+        let tree = DominatorTree::new(func);
+        let order = tree.order();
+        assert_eq!(order[0], func.entry_block);
+        // Every reachable block but the entry has exactly one parent, which
+        // comes before it in reverse postorder
+        let mut parents = HashMap::new();
+        for parent in order {
+            for kid in tree.children(*parent) {
+                assert!(parents.insert(*kid, *parent).is_none());
+                let rank = |b| order.iter().position(|o| *o == b).unwrap();
+                assert!(rank(*parent) < rank(*kid));
+            }
+        }
+        assert_eq!(parents.len(), order.len() - 1);
+
+        // Dominance agrees with the tree: a block dominates exactly itself
+        // and the blocks below it
+        for a in order {
+            for b in order {
+                let mut up = Some(*b);
+                let mut above = false;
+                while let Some(block) = up {
+                    above |= block == *a;
+                    up = parents.get(&block).copied();
+                }
+                assert_eq!(tree.dominates(*a, *b), above, "{:?} {:?}", a, b);
+            }
+        }
+    }
+}
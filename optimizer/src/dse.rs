@@ -0,0 +1,446 @@
+// Dead Store Elimination (DSE)
+//
+// Removes stores whose value no later instruction can read.
+//
+// Algorithm:
+// 1. Resolve every address derived from an alloca (through GEPs and copies)
+//    to its alloca and, when the indices are constant, its byte offset
+// 2. Track only allocas whose address never escapes: every use is the
+//    address of a load, store, memcpy, or memset, or the base of a GEP or
+//    copy. A call, inline asm, or store through another pointer cannot
+//    reach such an alloca, so calls need no special treatment
+// 3. Solve backwards liveness of the bytes of each tracked alloca over the
+//    CFG. A load makes the bytes it reads live (all of the alloca when its
+//    offset is unknown), a store or memset of a known range kills it, and
+//    nothing is live when the function returns
+// 4. Remove each non-volatile store, memset, or memcpy into a tracked alloca
+//    that writes no live byte
+// 5. For any other address, remove a store that a later store to the same
+//    address in the same block overwrites, at least as wide, with no memory
+//    read, call, or asm between them
+
+use crate::utils::scalar_size;
+use ir::{BlockId, Function, Instruction, Operand, Terminator, VarId};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Memset and memcpy ranges longer than this are treated as unknown, to
+/// keep the live byte sets small.
+const MAX_RANGE: usize = 256;
+
+/// An address within an alloca: the alloca and the byte offset into it,
+/// `None` when an index is not constant.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    alloca: VarId,
+    offset: Option<i64>,
+}
+
+/// The live bytes of one alloca.
+#[derive(Debug, Clone, PartialEq)]
+enum Live {
+    /// Only these bytes are live
+    Bytes(BTreeSet<i64>),
+    /// Every byte is live except these
+    AllExcept(BTreeSet<i64>),
+}
+
+impl Live {
+    fn any_live(&self, range: std::ops::Range<i64>) -> bool {
+        match self {
+            Live::Bytes(live) => live.range(range).next().is_some(),
+            Live::AllExcept(dead) => range.into_iter().any(|b| !dead.contains(&b)),
+        }
+    }
+
+    fn read(&mut self, range: std::ops::Range<i64>) {
+        match self {
+            Live::Bytes(live) => live.extend(range),
+            Live::AllExcept(dead) => range.for_each(|b| {
+                dead.remove(&b);
+            }),
+        }
+    }
+
+    fn write(&mut self, range: std::ops::Range<i64>) {
+        match self {
+            Live::Bytes(live) => {
+                let mut from_start = live.split_off(&range.start);
+                live.append(&mut from_start.split_off(&range.end));
+            }
+            Live::AllExcept(dead) => dead.extend(range),
+        }
+    }
+
+    fn union(&mut self, other: &Live) {
+        *self = match (&*self, other) {
+            (Live::Bytes(a), Live::Bytes(b)) => Live::Bytes(a | b),
+            (Live::Bytes(live), Live::AllExcept(dead)) | (Live::AllExcept(dead), Live::Bytes(live)) => {
+                Live::AllExcept(dead - live)
+            }
+            (Live::AllExcept(a), Live::AllExcept(b)) => Live::AllExcept(a & b),
+        };
+    }
+}
+
+/// Live bytes per tracked alloca; an alloca missing from the map has none.
+type LiveSet = BTreeMap<VarId, Live>;
+
+/// Run dead store elimination on a single function.
+pub fn dead_store_elimination(func: &mut Function) {
+    let locations = tracked_locations(func);
+    if !locations.is_empty() {
+        remove_dead_local_stores(func, &locations);
+    }
+    remove_overwritten_stores(func, &locations);
+}
+
+/// Steps 1 and 2: the location of every address into an alloca whose
+/// address never escapes.
+fn tracked_locations(func: &Function) -> HashMap<VarId, Location> {
+    let mut locations: HashMap<VarId, Location> = HashMap::new();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        if let Instruction::Alloca { dest, .. } = inst {
+            locations.insert(*dest, Location { alloca: *dest, offset: Some(0) });
+        }
+    }
+    if locations.is_empty() {
+        return locations;
+    }
+
+    // Blocks are not in dominance order, so derive until nothing changes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            let (dest, location) = match inst {
+                Instruction::GetElementPtr { dest, base: Operand::Var(base), index, element_type } => {
+                    let Some(base) = locations.get(base) else { continue };
+                    let offset = match (index, scalar_size(element_type)) {
+                        (Operand::Constant(0), _) => base.offset,
+                        (Operand::Constant(i), Some(stride)) => base.offset.map(|o| o + i * stride as i64),
+                        _ => None,
+                    };
+                    (dest, Location { alloca: base.alloca, offset })
+                }
+                Instruction::Copy { dest, src: Operand::Var(src) } => match locations.get(src) {
+                    Some(location) => (dest, *location),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if !locations.contains_key(dest) {
+                locations.insert(*dest, location);
+                changed = true;
+            }
+        }
+    }
+
+    let mut escaped: HashSet<VarId> = HashSet::new();
+    let mut escape = |op: &Operand| {
+        if let Some(location) = var_of(op).and_then(|v| locations.get(&v)) {
+            escaped.insert(location.alloca);
+        }
+    };
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            match inst {
+                Instruction::Load { .. } | Instruction::Alloca { .. } | Instruction::Memcpy { .. } => {}
+                Instruction::Store { src, .. } => escape(src),
+                Instruction::Memset { value, .. } => escape(value),
+                Instruction::GetElementPtr { index, .. } => escape(index),
+                Instruction::Copy { .. } => {}
+                Instruction::Phi { preds, .. } => {
+                    preds.iter().for_each(|(_, v)| escape(&Operand::Var(*v)));
+                }
+                _ => inst.for_each_operand(&mut escape),
+            }
+        }
+        match &block.terminator {
+            Terminator::Ret(Some(op)) | Terminator::CondBr { cond: op, .. } | Terminator::IndirectBr { target: op }
+            | Terminator::Switch { value: op, .. } => {
+                escape(op)
+            }
+            _ => {}
+        }
+    }
+    locations.retain(|_, location| !escaped.contains(&location.alloca));
+    locations
+}
+
+fn var_of(op: &Operand) -> Option<VarId> {
+    match op {
+        Operand::Var(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// Steps 3 and 4: solve liveness over the CFG, then remove the writes to
+/// tracked allocas that nothing reads.
+fn remove_dead_local_stores(func: &mut Function, locations: &HashMap<VarId, Location>) {
+    let successors = func.compute_successors();
+    let mut live_in: HashMap<BlockId, LiveSet> = HashMap::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in func.blocks.iter().rev() {
+            let mut live = live_out(block.id, &successors, &live_in);
+            scan_block(&block.instructions, &mut live, locations, None);
+            if live_in.get(&block.id) != Some(&live) {
+                live_in.insert(block.id, live);
+                changed = true;
+            }
+        }
+    }
+
+    for block in &mut func.blocks {
+        let mut live = live_out(block.id, &successors, &live_in);
+        let mut dead = Vec::new();
+        scan_block(&block.instructions, &mut live, locations, Some(&mut dead));
+        remove_indices(&mut block.instructions, &dead);
+    }
+}
+
+fn live_out(block: BlockId, successors: &HashMap<BlockId, Vec<BlockId>>, live_in: &HashMap<BlockId, LiveSet>) -> LiveSet {
+    let mut live = LiveSet::new();
+    for succ in successors.get(&block).into_iter().flatten() {
+        for (alloca, bytes) in live_in.get(succ).into_iter().flatten() {
+            live.entry(*alloca).or_insert_with(|| Live::Bytes(BTreeSet::new())).union(bytes);
+        }
+    }
+    live
+}
+
+/// Walk `instructions` backwards from the bytes live after them, leaving
+/// `live` as the bytes live before them. With `dead`, also collect the
+/// indices of writes to tracked allocas that are not live.
+fn scan_block(
+    instructions: &[Instruction],
+    live: &mut LiveSet,
+    locations: &HashMap<VarId, Location>,
+    mut dead: Option<&mut Vec<usize>>,
+) {
+    let location = |op: &Operand| var_of(op).and_then(|v| locations.get(&v)).copied();
+    for (i, inst) in instructions.iter().enumerate().rev() {
+        let (written, read) = match inst {
+            Instruction::Store { addr, value_type, volatile, .. } => {
+                let Some(at) = location(addr) else { continue };
+                let written = (!volatile).then_some((at, scalar_size(value_type)));
+                (written, None)
+            }
+            Instruction::Memset { dest, size, .. } => match location(dest) {
+                Some(at) => (Some((at, Some(*size).filter(|&s| s <= MAX_RANGE))), None),
+                None => continue,
+            },
+            Instruction::Memcpy { dest, src, size } => {
+                let size = Some(*size).filter(|&s| s <= MAX_RANGE);
+                (location(dest).map(|at| (at, size)), location(src).map(|at| (at, size)))
+            }
+            Instruction::Load { addr, value_type, .. } => match location(addr) {
+                Some(at) => (None, Some((at, scalar_size(value_type)))),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        if let Some((at, size)) = written {
+            let bytes = live.entry(at.alloca).or_insert_with(|| Live::Bytes(BTreeSet::new()));
+            match (at.offset, size) {
+                (Some(offset), Some(size)) => {
+                    let range = offset..offset + size as i64;
+                    if bytes.any_live(range.clone()) {
+                        bytes.write(range);
+                    } else if let Some(dead) = dead.as_deref_mut() {
+                        dead.push(i);
+                        continue;
+                    }
+                }
+                // An unknown range writes nothing for certain, and is dead
+                // only when no byte of the alloca is live
+                _ => {
+                    if let (Live::Bytes(live), Some(dead)) = (&*bytes, dead.as_deref_mut()) {
+                        if live.is_empty() {
+                            dead.push(i);
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((at, size)) = read {
+            let bytes = live.entry(at.alloca).or_insert_with(|| Live::Bytes(BTreeSet::new()));
+            match (at.offset, size) {
+                (Some(offset), Some(size)) => bytes.read(offset..offset + size as i64),
+                _ => *bytes = Live::AllExcept(BTreeSet::new()),
+            }
+        }
+    }
+}
+
+/// Step 5: remove stores to untracked addresses that a later store in the
+/// same block overwrites before anything can read them.
+fn remove_overwritten_stores(func: &mut Function, locations: &HashMap<VarId, Location>) {
+    for block in &mut func.blocks {
+        // Address → widest store to it below the current instruction
+        let mut overwritten: HashMap<Operand, usize> = HashMap::new();
+        let mut dead = Vec::new();
+
+        for (i, inst) in block.instructions.iter().enumerate().rev() {
+            match inst {
+                Instruction::Store { addr, value_type, volatile: false, .. }
+                    if var_of(addr).is_none_or(|v| !locations.contains_key(&v)) =>
+                {
+                    let Some(size) = scalar_size(value_type) else {
+                        overwritten.clear();
+                        continue;
+                    };
+                    if overwritten.get(addr).is_some_and(|&later| later >= size) {
+                        dead.push(i);
+                    } else {
+                        let widest = overwritten.entry(addr.clone()).or_insert(0);
+                        *widest = (*widest).max(size);
+                    }
+                }
+                // Stores to tracked allocas cannot alias these addresses
+                Instruction::Store { volatile: false, .. } => {}
+                // Anything else that touches memory may read what was stored
+                Instruction::Store { .. }
+                | Instruction::Load { .. }
+                | Instruction::Call { .. }
+                | Instruction::IndirectCall { .. }
+                | Instruction::InlineAsm { .. }
+                | Instruction::Memcpy { .. }
+                | Instruction::Memset { .. }
+                | Instruction::VaStart { .. }
+                | Instruction::VaEnd { .. }
+                | Instruction::VaCopy { .. }
+                | Instruction::VaArg { .. }
+                | Instruction::Simd { .. } => overwritten.clear(),
+                _ => {}
+            }
+        }
+
+        remove_indices(&mut block.instructions, &dead);
+    }
+}
+
+/// Remove the instructions at `indices`, which are in any order.
+fn remove_indices(instructions: &mut Vec<Instruction>, indices: &[usize]) {
+    if indices.is_empty() {
+        return;
+    }
+    let dead: HashSet<usize> = indices.iter().copied().collect();
+    let mut index = 0;
+    instructions.retain(|_| {
+        let keep = !dead.contains(&index);
+        index += 1;
+        keep
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    /// Run mem2reg, copy propagation, and DSE on every function, returning
+    /// the stored constants left in the function named `name`.
+    fn stored_constants(prog: &mut ir::IRProgram, name: &str) -> Vec<i64> {
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            crate::propagation::copy_propagation(func);
+            dead_store_elimination(func);
+        }
+        let func = prog.functions.iter().find(|f| f.name == name).unwrap();
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Store { src: Operand::Constant(c), .. } => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dse_removes_stores_overwritten_on_every_path() {
+        // a[0] = 1 is overwritten on both arms before the read, and a[2] = 9
+        // is never read; a[1] = 2 may be read through a[i & 1]
+        let src = r#"
+            int pick(int c, int i) {
+                int a[4];
+                a[0] = 1;
+                a[1] = 2;
+                if (c) a[0] = 5; else a[0] = 6;
+                int r = a[i & 1];
+                a[2] = 9;
+                return r;
+            }
+            int main() { return pick(1, 0) * 10 + pick(0, 1); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let mut stored = stored_constants(&mut prog, "pick");
+        stored.sort();
+        assert_eq!(stored, [2, 5, 6]);
+        assert_eq!(ir::interpret(&prog, "dse", &[]).unwrap(), 52);
+    }
+
+    #[test]
+    fn test_dse_keeps_stores_a_loop_reads() {
+        let src = r#"
+            int main() {
+                int a[2];
+                int sum = 0;
+                a[0] = 3;
+                for (int i = 0; i < 4; i++) {
+                    sum += a[0];
+                    a[0] = i;
+                }
+                return sum;
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let stored = stored_constants(&mut prog, "main");
+        assert!(stored.contains(&3));
+        assert_eq!(ir::interpret(&prog, "dse", &[]).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_dse_keeps_escaped_and_volatile_stores() {
+        let src = r#"
+            void use(int *p);
+            int main() {
+                int a[2];
+                a[0] = 1;
+                use(a);
+                a[0] = 2;
+                return 0;
+            }
+            void poke(volatile int *port) { *port = 3; *port = 4; }
+        "#;
+        let mut prog = compile_to_ir(src);
+        assert_eq!(stored_constants(&mut prog, "main"), [1, 2]);
+        assert_eq!(stored_constants(&mut prog, "poke"), [3, 4]);
+    }
+
+    #[test]
+    fn test_dse_through_pointers_needs_a_covering_store() {
+        let src = r#"
+            int g;
+            void set(int *p, int *q) { *p = 1; *p = 2; g = 3; *q = 4; g = 5; }
+            void reread(int *p, int *q) { *p = 6; int x = *q; *p = x; }
+            void widen(long *p) { *p = 7; *(char *)p = 8; }
+        "#;
+        let mut prog = compile_to_ir(src);
+        assert_eq!(stored_constants(&mut prog, "set"), [2, 4, 5]);
+        assert_eq!(stored_constants(&mut prog, "reread"), [6]);
+        assert_eq!(stored_constants(&mut prog, "widen"), [7, 8]);
+    }
+}
@@ -1,296 +1,362 @@
-use ir::{Function, Instruction, Operand, VarId};
+use crate::purity::PureFunctions;
+use ir::{BasicBlock, BlockId, Function, Instruction, Operand, Terminator, VarId};
 use model::{BinaryOp, UnaryOp, Type};
 use std::collections::HashMap;
 
+/// Where an instruction or terminator is: the index of its block, and its
+/// index in the block, or `None` for the terminator
+type Site = (usize, Option<usize>);
+
 /// Constant folding and propagation
 ///
-/// Performs repeated passes of constant folding until no more changes occur.
-/// Evaluates expressions with constant operands at compile time and propagates
-/// the results through the function.
-pub fn optimize_function(func: &mut Function) {
-    let mut changed = true;
-    let mut iterations = 0;
-    const MAX_ITERATIONS: usize = 10;
+/// Evaluates expressions with constant operands at compile time, replaces
+/// uses of the variables they define with the constant, and folds branches
+/// on constants, then removes what became dead. A worklist drives the
+/// propagation: every instruction is visited once, and again only when one
+/// of its operands becomes a constant, so a constant reaches all of its uses
+/// whatever the block order, without sweeping the whole function again.
+pub fn optimize_function(func: &mut Function, pure: &PureFunctions) {
+    let mut users: HashMap<VarId, Vec<Site>> = HashMap::new();
+    let mut definitions: HashMap<VarId, usize> = HashMap::new();
+    let mut worklist: Vec<Site> = Vec::new();
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            inst.for_each_use(|v| users.entry(v).or_default().push((b, Some(i))));
+            for dest in inst.dests() {
+                *definitions.entry(dest).or_default() += 1;
+            }
+            worklist.push((b, Some(i)));
+        }
+        if let Terminator::CondBr { cond: Operand::Var(v), .. }
+        | Terminator::Switch { value: Operand::Var(v), .. }
+        | Terminator::Ret(Some(Operand::Var(v))) = &block.terminator
+        {
+            users.entry(*v).or_default().push((b, None));
+        }
+        worklist.push((b, None));
+    }
+    // Visit in block order first
+    worklist.reverse();
 
-    while changed && iterations < MAX_ITERATIONS {
-        changed = false;
-        iterations += 1;
+    let mut constants: HashMap<VarId, i64> = HashMap::new();
+    let mut float_constants: HashMap<VarId, f64> = HashMap::new();
+    // (predecessor, successor) edges removed by folding a branch
+    let mut dropped_edges = Vec::new();
+    while let Some((b, index)) = worklist.pop() {
+        let block = &mut func.blocks[b];
+        let Some(i) = index else {
+            fold_terminator(block, &constants, &mut dropped_edges);
+            continue;
+        };
+        let dest = block.instructions[i].dest();
+        let known = |v: &VarId| constants.contains_key(v) || float_constants.contains_key(v);
+        let was_known = dest.as_ref().is_some_and(known);
+        block.instructions[i] = fold_instruction(block.instructions[i].clone(), &mut constants, &mut float_constants);
+        let Some(dest) = dest else { continue };
+        if was_known || !(constants.contains_key(&dest) || float_constants.contains_key(&dest)) {
+            continue;
+        }
+        // A variable assigned more than once has no single value to propagate
+        if definitions.get(&dest) != Some(&1) {
+            constants.remove(&dest);
+            float_constants.remove(&dest);
+            continue;
+        }
+        worklist.extend(users.get(&dest).into_iter().flatten().copied());
+    }
 
-        let mut constants: HashMap<VarId, i64> = HashMap::new();
-        let mut float_constants: HashMap<VarId, f64> = HashMap::new();
+    // A phi must not keep an incoming value for an edge that no longer
+    // exists, or remove_phis would copy it into the old predecessor.
+    for (pred, succ) in dropped_edges {
+        if let Some(block) = func.blocks.iter_mut().find(|b| b.id == succ) {
+            for inst in &mut block.instructions {
+                if let Instruction::Phi { preds, .. } = inst {
+                    preds.retain(|(b, _)| *b != pred);
+                }
+            }
+        }
+    }
 
-        for block in &mut func.blocks {
-            let mut new_instructions = Vec::new();
+    crate::dce::dce_function(func, pure);
+}
 
-            for inst in block.instructions.drain(..) {
-                match inst {
-                    Instruction::Binary {
-                        dest,
-                        op,
-                        left,
-                        right,
-                    } => {
-                        let l = resolve_operand(&left, &constants);
-                        let r = resolve_operand(&right, &constants);
+/// Fold `inst` into a copy of a constant when its operands are constants,
+/// recording the constant it defines, or else replace its operands that
+/// are known constants.
+fn fold_instruction(
+    inst: Instruction,
+    constants: &mut HashMap<VarId, i64>,
+    float_constants: &mut HashMap<VarId, f64>,
+) -> Instruction {
+    match inst {
+        Instruction::Binary {
+            dest,
+            op,
+            left,
+            right,
+        } => {
+            let l = resolve_operand(&left, constants);
+            let r = resolve_operand(&right, constants);
 
-                        if let (Operand::Constant(lc), Operand::Constant(rc)) = (&l, &r) {
-                            if let Some(val) = fold_binary(op.clone(), *lc, *rc) {
-                                constants.insert(dest, val);
-                                new_instructions.push(Instruction::Copy {
-                                    dest,
-                                    src: Operand::Constant(val),
-                                });
-                                changed = true;
-                                continue;
-                            }
-                        }
-                        new_instructions.push(Instruction::Binary {
-                            dest,
-                            op,
-                            left: l,
-                            right: r,
-                        });
-                    }
-                    Instruction::FloatBinary {
+            if let (Operand::Constant(lc), Operand::Constant(rc)) = (&l, &r) {
+                if let Some(val) = fold_binary(op.clone(), *lc, *rc) {
+                    constants.insert(dest, val);
+                    return Instruction::Copy {
                         dest,
-                        op,
-                        left,
-                        right,
-                    } => {
-                        let l = resolve_float_operand(&left, &constants, &float_constants);
-                        let r = resolve_float_operand(&right, &constants, &float_constants);
+                        src: Operand::Constant(val),
+                    };
+                }
+            }
+            Instruction::Binary {
+                dest,
+                op,
+                left: l,
+                right: r,
+            }
+        }
+        Instruction::FloatBinary {
+            dest,
+            op,
+            left,
+            right,
+        } => {
+            let l = resolve_float_operand(&left, constants, float_constants);
+            let r = resolve_float_operand(&right, constants, float_constants);
 
-                        if let (Operand::FloatConstant(lf), Operand::FloatConstant(rf)) = (&l, &r) {
-                            if let Some(val) = fold_float_binary(&op, *lf, *rf) {
-                                match val {
-                                    FloatFoldResult::Float(f) => {
-                                        float_constants.insert(dest, f);
-                                        new_instructions.push(Instruction::Copy {
-                                            dest,
-                                            src: Operand::FloatConstant(f),
-                                        });
-                                    }
-                                    FloatFoldResult::Int(i) => {
-                                        constants.insert(dest, i);
-                                        new_instructions.push(Instruction::Copy {
-                                            dest,
-                                            src: Operand::Constant(i),
-                                        });
-                                    }
-                                }
-                                changed = true;
-                                continue;
+            if let (Operand::FloatConstant(lf), Operand::FloatConstant(rf)) = (&l, &r) {
+                if let Some(val) = fold_float_binary(&op, *lf, *rf) {
+                    return match val {
+                        FloatFoldResult::Float(f) => {
+                            float_constants.insert(dest, f);
+                            Instruction::Copy {
+                                dest,
+                                src: Operand::FloatConstant(f),
                             }
                         }
-                        new_instructions.push(Instruction::FloatBinary {
-                            dest,
-                            op,
-                            left: l,
-                            right: r,
-                        });
-                    }
-                    Instruction::Unary { dest, op, src } => {
-                        let s = resolve_operand(&src, &constants);
-
-                        if let Operand::Constant(sc) = s {
-                            if let Some(val) = fold_unary(op.clone(), sc) {
-                                constants.insert(dest, val);
-                                new_instructions.push(Instruction::Copy {
-                                    dest,
-                                    src: Operand::Constant(val),
-                                });
-                                changed = true;
-                                continue;
+                        FloatFoldResult::Int(i) => {
+                            constants.insert(dest, i);
+                            Instruction::Copy {
+                                dest,
+                                src: Operand::Constant(i),
                             }
                         }
-                        new_instructions.push(Instruction::Unary { dest, op, src: s });
-                    }
-                    Instruction::FloatUnary { dest, op, src } => {
-                        let s = resolve_float_operand(&src, &constants, &float_constants);
+                    };
+                }
+            }
+            Instruction::FloatBinary {
+                dest,
+                op,
+                left: l,
+                right: r,
+            }
+        }
+        Instruction::Unary { dest, op, src } => {
+            let s = resolve_operand(&src, constants);
 
-                        if let Operand::FloatConstant(sf) = s {
-                            if let Some(val) = fold_float_unary(&op, sf) {
-                                match val {
-                                    FloatFoldResult::Float(f) => {
-                                        float_constants.insert(dest, f);
-                                        new_instructions.push(Instruction::Copy {
-                                            dest,
-                                            src: Operand::FloatConstant(f),
-                                        });
-                                    }
-                                    FloatFoldResult::Int(i) => {
-                                        constants.insert(dest, i);
-                                        new_instructions.push(Instruction::Copy {
-                                            dest,
-                                            src: Operand::Constant(i),
-                                        });
-                                    }
-                                }
-                                changed = true;
-                                continue;
-                            }
-                        }
-                        new_instructions.push(Instruction::FloatUnary { dest, op, src: s });
-                    }
-                    Instruction::Copy { dest, src } => {
-                        let s = resolve_operand(&src, &constants);
-                        if let Operand::Constant(sc) = &s {
-                            constants.insert(dest, *sc);
-                        } else if let Operand::FloatConstant(fc) = &s {
-                            float_constants.insert(dest, *fc);
-                        }
-                        // Also check if a float var is being copied
-                        let s = resolve_float_operand(&s, &constants, &float_constants);
-                        if let Operand::FloatConstant(fc) = &s {
-                            float_constants.insert(dest, *fc);
-                        }
-                        new_instructions.push(Instruction::Copy { dest, src: s });
-                    }
-                    Instruction::Cast { dest, src, r#type } => {
-                        let s = resolve_float_operand(&src, &constants, &float_constants);
-                        // Int constant → float type
-                        if let Operand::Constant(val) = &s {
-                            if r#type == Type::Float || r#type == Type::Double {
-                                let f = *val as f64;
-                                float_constants.insert(dest, f);
-                                new_instructions.push(Instruction::Copy {
-                                    dest,
-                                    src: Operand::FloatConstant(f),
-                                });
-                                changed = true;
-                                continue;
-                            }
-                            if let Some(folded) = fold_cast(*val, &r#type) {
-                                constants.insert(dest, folded);
-                                new_instructions.push(Instruction::Copy {
-                                    dest,
-                                    src: Operand::Constant(folded),
-                                });
-                                changed = true;
-                                continue;
+            if let Operand::Constant(sc) = s {
+                if let Some(val) = fold_unary(op.clone(), sc) {
+                    constants.insert(dest, val);
+                    return Instruction::Copy {
+                        dest,
+                        src: Operand::Constant(val),
+                    };
+                }
+            }
+            Instruction::Unary { dest, op, src: s }
+        }
+        Instruction::FloatUnary { dest, op, src } => {
+            let s = resolve_float_operand(&src, constants, float_constants);
+
+            if let Operand::FloatConstant(sf) = s {
+                if let Some(val) = fold_float_unary(&op, sf) {
+                    return match val {
+                        FloatFoldResult::Float(f) => {
+                            float_constants.insert(dest, f);
+                            Instruction::Copy {
+                                dest,
+                                src: Operand::FloatConstant(f),
                             }
                         }
-                        // Float constant → int type
-                        if let Operand::FloatConstant(f) = &s {
-                            match &r#type {
-                                Type::Float | Type::Double => {
-                                    // float → float cast (e.g., double → float)
-                                    let f_val = if r#type == Type::Float { (*f as f32) as f64 } else { *f };
-                                    float_constants.insert(dest, f_val);
-                                    new_instructions.push(Instruction::Copy {
-                                        dest,
-                                        src: Operand::FloatConstant(f_val),
-                                    });
-                                    changed = true;
-                                    continue;
-                                }
-                                _ => {
-                                    // float → int
-                                    let i = *f as i64;
-                                    if let Some(folded) = fold_cast(i, &r#type) {
-                                        constants.insert(dest, folded);
-                                        new_instructions.push(Instruction::Copy {
-                                            dest,
-                                            src: Operand::Constant(folded),
-                                        });
-                                        changed = true;
-                                        continue;
-                                    }
-                                }
+                        FloatFoldResult::Int(i) => {
+                            constants.insert(dest, i);
+                            Instruction::Copy {
+                                dest,
+                                src: Operand::Constant(i),
                             }
                         }
-                        new_instructions.push(Instruction::Cast { dest, src: s, r#type });
-                    }
-                    Instruction::Call { dest, name, args } => {
-                        let resolved_args: Vec<_> =
-                            args.iter().map(|arg| resolve_operand(arg, &constants)).collect();
-                        new_instructions.push(Instruction::Call {
-                            dest,
-                            name,
-                            args: resolved_args,
-                        });
-                    }
-                    Instruction::IndirectCall {
+                    };
+                }
+            }
+            Instruction::FloatUnary { dest, op, src: s }
+        }
+        Instruction::Copy { dest, src } => {
+            let s = resolve_operand(&src, constants);
+            if let Operand::Constant(sc) = &s {
+                constants.insert(dest, *sc);
+            } else if let Operand::FloatConstant(fc) = &s {
+                float_constants.insert(dest, *fc);
+            }
+            // Also check if a float var is being copied
+            let s = resolve_float_operand(&s, constants, float_constants);
+            if let Operand::FloatConstant(fc) = &s {
+                float_constants.insert(dest, *fc);
+            }
+            Instruction::Copy { dest, src: s }
+        }
+        Instruction::Cast { dest, src, src_type, r#type } => {
+            let s = resolve_float_operand(&src, constants, float_constants);
+            // Int constant → float type
+            if let Operand::Constant(val) = &s {
+                if r#type == Type::Float || r#type == Type::Double {
+                    let f = match src_type {
+                        Type::UnsignedLong | Type::UnsignedLongLong => *val as u64 as f64,
+                        _ => *val as f64,
+                    };
+                    let f = if r#type == Type::Float { f as f32 as f64 } else { f };
+                    float_constants.insert(dest, f);
+                    return Instruction::Copy {
                         dest,
-                        func_ptr,
-                        args,
-                    } => {
-                        let resolved_func_ptr = resolve_operand(&func_ptr, &constants);
-                        let resolved_args: Vec<_> =
-                            args.iter().map(|arg| resolve_operand(arg, &constants)).collect();
-                        new_instructions.push(Instruction::IndirectCall {
-                            dest,
-                            func_ptr: resolved_func_ptr,
-                            args: resolved_args,
-                        });
-                    }
-                    Instruction::Load { dest, addr, value_type, volatile } => {
-                        new_instructions.push(Instruction::Load {
-                            dest,
-                            addr: resolve_operand(&addr, &constants),
-                            value_type,
-                            volatile,
-                        });
-                    }
-                    Instruction::Store { addr, src, value_type, volatile } => {
-                        new_instructions.push(Instruction::Store {
-                            addr: resolve_operand(&addr, &constants),
-                            src: resolve_operand(&src, &constants),
-                            value_type,
-                            volatile,
-                        });
-                    }
-                    Instruction::GetElementPtr {
+                        src: Operand::FloatConstant(f),
+                    };
+                }
+                if let Some(folded) = fold_cast(*val, &r#type) {
+                    constants.insert(dest, folded);
+                    return Instruction::Copy {
                         dest,
-                        base,
-                        index,
-                        element_type,
-                    } => {
-                        new_instructions.push(Instruction::GetElementPtr {
+                        src: Operand::Constant(folded),
+                    };
+                }
+            }
+            // Float constant → int type
+            if let Operand::FloatConstant(f) = &s {
+                match &r#type {
+                    Type::Float | Type::Double => {
+                        // float → float cast (e.g., double → float)
+                        let f_val = if r#type == Type::Float { (*f as f32) as f64 } else { *f };
+                        float_constants.insert(dest, f_val);
+                        return Instruction::Copy {
                             dest,
-                            base: resolve_operand(&base, &constants),
-                            index: resolve_operand(&index, &constants),
-                            element_type,
-                        });
+                            src: Operand::FloatConstant(f_val),
+                        };
+                    }
+                    _ => {
+                        // float → int
+                        let i = match r#type {
+                            Type::UnsignedLong | Type::UnsignedLongLong => *f as u64 as i64,
+                            _ => *f as i64,
+                        };
+                        if let Some(folded) = fold_cast(i, &r#type) {
+                            constants.insert(dest, folded);
+                            return Instruction::Copy {
+                                dest,
+                                src: Operand::Constant(folded),
+                            };
+                        }
                     }
-                    other => new_instructions.push(other),
                 }
             }
-            block.instructions = new_instructions;
+            Instruction::Cast { dest, src: s, src_type, r#type }
+        }
+        Instruction::Call { dest, name, args, vararg_types } => {
+            let resolved_args: Vec<_> =
+                args.iter().map(|arg| resolve_operand(arg, constants)).collect();
+            Instruction::Call {
+                dest,
+                name,
+                args: resolved_args,
+                vararg_types,
+            }
+        }
+        Instruction::IndirectCall {
+            dest,
+            func_ptr,
+            args,
+            is_variadic,
+            vararg_types,
+        } => {
+            let resolved_func_ptr = resolve_operand(&func_ptr, constants);
+            let resolved_args: Vec<_> =
+                args.iter().map(|arg| resolve_operand(arg, constants)).collect();
+            Instruction::IndirectCall {
+                dest,
+                func_ptr: resolved_func_ptr,
+                args: resolved_args,
+                is_variadic,
+                vararg_types,
+            }
+        }
+        Instruction::Load { dest, addr, value_type, volatile } => {
+            Instruction::Load {
+                dest,
+                addr: resolve_operand(&addr, constants),
+                value_type,
+                volatile,
+            }
+        }
+        Instruction::Store { addr, src, value_type, volatile } => {
+            Instruction::Store {
+                addr: resolve_operand(&addr, constants),
+                src: resolve_operand(&src, constants),
+                value_type,
+                volatile,
+            }
+        }
+        Instruction::GetElementPtr {
+            dest,
+            base,
+            index,
+            element_type,
+        } => {
+            Instruction::GetElementPtr {
+                dest,
+                base: resolve_operand(&base, constants),
+                index: resolve_operand(&index, constants),
+                element_type,
+            }
+        }
+        other => other,
+    }
+}
 
-            // Fold terminator conditions
-            match &mut block.terminator {
-                ir::Terminator::CondBr {
-                    cond,
-                    then_block,
-                    else_block,
-                    ..
-                } => {
-                    let c = resolve_operand(cond, &constants);
-                    if let Operand::Constant(val) = c {
-                        let target = if val != 0 { *then_block } else { *else_block };
-                        block.terminator = ir::Terminator::Br(target);
-                        changed = true;
-                    } else {
-                        *cond = c;
-                    }
+/// Fold a branch on a constant into a jump, recording the edge it drops, and
+/// replace a returned variable that is a known constant.
+fn fold_terminator(block: &mut BasicBlock, constants: &HashMap<VarId, i64>, dropped_edges: &mut Vec<(BlockId, BlockId)>) {
+    match &mut block.terminator {
+        Terminator::CondBr {
+            cond,
+            then_block,
+            else_block,
+            ..
+        } => {
+            let c = resolve_operand(cond, constants);
+            if let Operand::Constant(val) = c {
+                let (target, dropped) = if val != 0 { (*then_block, *else_block) } else { (*else_block, *then_block) };
+                if dropped != target {
+                    dropped_edges.push((block.id, dropped));
                 }
-                ir::Terminator::Ret(Some(op)) => {
-                    *op = resolve_operand(op, &constants);
+                block.terminator = Terminator::Br(target);
+            } else {
+                *cond = c;
+            }
+        }
+        Terminator::Switch { value, cases, default } => {
+            let v = resolve_operand(value, constants);
+            if let Operand::Constant(val) = v {
+                let target = cases.iter().find(|(case, _)| *case == val).map_or(*default, |&(_, block)| block);
+                for dropped in Terminator::switch_targets(cases, *default) {
+                    if dropped != target {
+                        dropped_edges.push((block.id, dropped));
+                    }
                 }
-                _ => {}
+                block.terminator = Terminator::Br(target);
+            } else {
+                *value = v;
             }
         }
-
-        // Run DCE after each folding pass
-        changed |= crate::dce::dce_function(func);
-    }
-
-    if iterations >= MAX_ITERATIONS {
-        // eprintln!(
-        //     "Warning: Optimizer reached max iterations ({}) for function {}",
-        //     MAX_ITERATIONS, func.name
-        // );
+        Terminator::Ret(Some(op)) => {
+            *op = resolve_operand(op, constants);
+        }
+        _ => {}
     }
 }
 
@@ -353,25 +419,16 @@ fn fold_float_unary(op: &UnaryOp, s: f64) -> Option<FloatFoldResult> {
     }
 }
 
+/// Fold an integer operation the way the generated code computes it: sums
+/// and products wrap, and a division that would trap (by zero, or of
+/// `i64::MIN` by -1) is left to run.
 pub fn fold_binary(op: BinaryOp, l: i64, r: i64) -> Option<i64> {
     match op {
-        BinaryOp::Add => Some(l + r),
-        BinaryOp::Sub => Some(l - r),
-        BinaryOp::Mul => Some(l * r),
-        BinaryOp::Div => {
-            if r != 0 {
-                Some(l / r)
-            } else {
-                None
-            }
-        }
-        BinaryOp::Mod => {
-            if r != 0 {
-                Some(l % r)
-            } else {
-                None
-            }
-        }
+        BinaryOp::Add => Some(l.wrapping_add(r)),
+        BinaryOp::Sub => Some(l.wrapping_sub(r)),
+        BinaryOp::Mul => Some(l.wrapping_mul(r)),
+        BinaryOp::Div => l.checked_div(r),
+        BinaryOp::Mod => l.checked_rem(r),
         BinaryOp::EqualEqual => Some((l == r) as i64),
         BinaryOp::NotEqual => Some((l != r) as i64),
         BinaryOp::Less => Some((l < r) as i64),
@@ -390,7 +447,7 @@ pub fn fold_binary(op: BinaryOp, l: i64, r: i64) -> Option<i64> {
 
 pub fn fold_unary(op: UnaryOp, s: i64) -> Option<i64> {
     match op {
-        UnaryOp::Minus => Some(-s),
+        UnaryOp::Minus => Some(s.wrapping_neg()),
         UnaryOp::Plus => Some(s),
         UnaryOp::LogicalNot => Some((s == 0) as i64),
         UnaryOp::BitwiseNot => Some(!s),
@@ -413,3 +470,76 @@ fn fold_cast(val: i64, target: &Type) -> Option<i64> {
         _ => None, // Pointer, float, struct, etc. — don't fold
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    #[test]
+    fn test_folding_propagates_against_block_order() {
+        // Each doubling is in its own block; with the blocks reversed, every
+        // constant is defined after the block that uses it
+        let steps: String = (1..=12).map(|i| format!("l{i}: a = a * 2; goto l{};", i + 1)).collect();
+        let src = format!("int f() {{ int a = 1; goto l1; {steps} l13: return a; }}");
+        let mut prog = compile_to_ir(&src);
+        let func = &mut prog.functions[0];
+        ir::mem2reg(func);
+        func.blocks.reverse();
+        optimize_function(func, &PureFunctions::default());
+        let returns: Vec<&Terminator> = func.blocks.iter()
+            .map(|b| &b.terminator)
+            .filter(|t| matches!(t, Terminator::Ret(_)))
+            .collect();
+        assert!(matches!(returns[..], [Terminator::Ret(Some(Operand::Constant(4096)))]), "{:?}", returns);
+        assert!(func.blocks.iter().all(|b| b.instructions.is_empty()));
+    }
+
+    #[test]
+    fn test_folding_keeps_variables_assigned_twice() {
+        // Once phis are removed, the conditional's result is assigned 1 on
+        // one path and `y` on the other
+        let src = r#"
+            int f(int c, int y) {
+                return c ? 1 : y;
+            }
+            int main() { return f(0, 5); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            ir::remove_phis(func);
+            optimize_function(func, &PureFunctions::default());
+        }
+        assert_eq!(ir::interpret(&prog, "fold", &[]).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_folding_wraps_and_leaves_trapping_division() {
+        // 2^63 + 2^63 wraps to 0; i64::MIN / -1 traps at run time, so it is
+        // not folded
+        let src = r#"
+            long f(void) {
+                unsigned long x = 0x8000000000000000UL;
+                long m = -9223372036854775807L - 1;
+                long d = -1;
+                return (long)(x + x) + -m + m / d;
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let func = &mut prog.functions[0];
+        ir::mem2reg(func);
+        optimize_function(func, &PureFunctions::default());
+        let divisions: Vec<&Instruction> = func.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .filter(|i| matches!(i, Instruction::Binary { op: BinaryOp::Div, .. }))
+            .collect();
+        assert!(matches!(divisions[..], [Instruction::Binary { left: Operand::Constant(i64::MIN), right: Operand::Constant(-1), .. }]), "{:?}", divisions);
+    }
+}
@@ -0,0 +1,169 @@
+// GEP combining
+//
+// Member-of-array-element accesses lower to a chain of `GetElementPtr`s
+// with constant indices (`s[2].p[3].y` is four of them), which codegen
+// would otherwise turn into one add per step. Each GEP whose index is
+// constant, and whose base is another constant-index GEP, is rewritten to
+// step from that GEP's own base, so every chain collapses into a single
+// offset from the first address that is not a constant GEP:
+//
+//   %3 = gep %0, 2 x struct S         %3 = gep %0, 2 x struct S
+//   %4 = gep %3, 4 x char      →      %4 = gep %0, 100 x char
+//   %5 = gep %4, 3 x struct P         %5 = gep %0, 124 x char
+//
+// The steps above become dead and are left for DCE. A chain whose steps all
+// have one element type keeps it; otherwise the offset is counted in bytes,
+// using the program's type layout. A zero offset becomes a copy of the
+// base. Offsets that overflow an `int` are left alone. The pass needs SSA
+// form, so that the base a rewritten GEP reaches back to still holds the
+// same address.
+
+use crate::utils::is_ssa;
+use ir::{Function, Instruction, Operand, VarId};
+use model::{Type, TypeLayout};
+use std::collections::HashMap;
+
+/// Combine chains of constant-index GEPs in `func`.
+pub fn combine_geps(func: &mut Function, layout: &TypeLayout) {
+    if !is_ssa(func) {
+        return;
+    }
+    // Each constant-index GEP, as its base and the steps taken from it.
+    // Rewritten GEPs are recorded in their combined form, so a later one
+    // in the chain reaches the root in a single lookup.
+    let mut chains: HashMap<VarId, (Operand, Vec<(i64, Type)>)> = HashMap::new();
+    for block_id in dominator_order(func) {
+        let Some(block) = func.blocks.iter_mut().find(|b| b.id == block_id) else { continue };
+        for inst in &mut block.instructions {
+            let Instruction::GetElementPtr { dest, base, index: Operand::Constant(index), element_type } = inst else {
+                continue;
+            };
+            let step = (*index, element_type.clone());
+            let (root, steps) = match base {
+                Operand::Var(v) if chains.contains_key(v) => {
+                    let (root, steps) = &chains[v];
+                    (root.clone(), steps.iter().cloned().chain(std::iter::once(step)).collect::<Vec<_>>())
+                }
+                _ => {
+                    chains.insert(*dest, (base.clone(), vec![step]));
+                    continue;
+                }
+            };
+            let dest = *dest;
+            let Some(combined) = combine(&steps, layout) else {
+                chains.insert(dest, (base.clone(), vec![(*index, element_type.clone())]));
+                continue;
+            };
+            *inst = match combined {
+                (0, _) => Instruction::Copy { dest, src: root.clone() },
+                (index, element_type) => {
+                    Instruction::GetElementPtr { dest, base: root.clone(), index: Operand::Constant(index), element_type }
+                }
+            };
+            chains.insert(dest, (root, steps));
+        }
+    }
+}
+
+/// The single step equivalent to `steps`: the summed index when they share
+/// an element type, else a byte offset. `None` when the offset does not
+/// fit an `int`.
+fn combine(steps: &[(i64, Type)], layout: &TypeLayout) -> Option<(i64, Type)> {
+    let (_, first_type) = &steps[0];
+    let combined = if steps.iter().all(|(_, ty)| ty == first_type) {
+        let total = steps.iter().try_fold(0i64, |acc, (index, _)| acc.checked_add(*index))?;
+        (total, first_type.clone())
+    } else {
+        let total = steps
+            .iter()
+            .try_fold(0i64, |acc, (index, ty)| acc.checked_add(index.checked_mul(layout.size_of(ty) as i64)?))?;
+        (total, Type::Char)
+    };
+    let bytes = combined.0.checked_mul(layout.size_of(&combined.1) as i64)?;
+    i32::try_from(bytes).ok()?;
+    Some(combined)
+}
+
+/// The reachable blocks in an order where every block comes after its
+/// dominators, so each GEP is visited after the definition of its base.
+fn dominator_order(func: &Function) -> Vec<ir::BlockId> {
+    crate::dominators::DominatorTree::new(func).order().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    /// Promote, combine, and clean up every function, returning the GEPs
+    /// left in the function named `name`.
+    fn geps(prog: &mut ir::IRProgram, name: &str) -> Vec<(Operand, i64, Type)> {
+        let structs = prog.structs.iter().map(|s| (s.name.clone(), s.clone())).collect();
+        let unions = prog.unions.iter().map(|u| (u.name.clone(), u.clone())).collect();
+        let layout = TypeLayout::new(&structs, &unions).with_data_layout(prog.data_layout);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            crate::propagation::copy_propagation(func);
+            combine_geps(func, &layout);
+            while crate::dce::dce_function(func, &crate::purity::PureFunctions::default()) {}
+            assert!(ir::verify_ssa(func).is_ok(), "{}", ir::verify_ssa(func).unwrap_err());
+        }
+        let func = prog.functions.iter().find(|f| f.name == name).unwrap();
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::GetElementPtr { base, index: Operand::Constant(index), element_type, .. } => {
+                    Some((base.clone(), *index, element_type.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_member_of_element_chains_become_one_offset() {
+        let src = r#"
+            struct P { int x; int y; };
+            struct S { int a; struct P p[4]; long z; };
+            struct S g[8];
+            int f(struct S *s) { return s[2].p[3].y + g[1].p[2].x + s->p[1].y; }
+            int main() {
+                g[2].p[3].y = 30; g[1].p[2].x = 10; g[0].p[1].y = 2;
+                return f(g);
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let geps = geps(&mut prog, "f");
+        // sizeof(struct S) is 48 and p starts at 4
+        let s = Operand::Var(VarId(0));
+        assert_eq!(
+            geps,
+            [
+                (s.clone(), 128, Type::Char),
+                (Operand::Global("g".to_string()), 68, Type::Char),
+                (s, 16, Type::Char),
+            ]
+        );
+        assert_eq!(ir::interpret(&prog, "test", &[]).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_same_type_steps_keep_their_element_type() {
+        let src = r#"
+            int f(int *a) { int *b = &a[1]; int *c = &b[1]; return c[1] + c[0]; }
+            int main() { int a[4] = {0, 0, 5, 37}; return f(a); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let geps = geps(&mut prog, "f");
+        let a = Operand::Var(VarId(0));
+        assert_eq!(geps, [(a.clone(), 3, Type::Int), (a, 2, Type::Int)]);
+        assert_eq!(ir::interpret(&prog, "test", &[]).unwrap(), 42);
+    }
+}
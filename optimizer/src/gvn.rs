@@ -0,0 +1,369 @@
+// Global Value Numbering (GVN)
+//
+// Replaces a pure computation with an earlier one that computes the same
+// value and dominates it, in the same block or in any block above it.
+//
+// Algorithm (dominator-based value numbering):
+// 1. Build the dominator tree with Cooper, Harvey, and Kennedy's iterative
+//    algorithm over the reverse postorder of the reachable blocks
+// 2. Give each variable a leader: the earliest variable (or constant) known
+//    to hold its value. A copy's leader is its source's leader
+// 3. Walk the tree in preorder. Rewrite each instruction's operands to
+//    their leaders, then look up its expression (opcode, operand leaders,
+//    and the types it computes with) in a hash table of the expressions
+//    available in the dominating blocks. A hit turns the instruction into a
+//    copy of the earlier result, which becomes its leader
+// 4. Leaving a block removes its expressions from the table, so a value
+//    computed on one arm of a branch is never reused on the other
+//
+// Only pure instructions are numbered: integer and float arithmetic, casts,
+// GEPs, and calls to pure functions (see `purity.rs`). A call to one that
+// reads memory is only reused within its block, and only while nothing in
+// between may write memory. Loads, other calls, and anything else that
+// touches memory are not numbered.
+// The pass needs SSA form, so a function with a variable defined twice is
+// left alone.
+
+use crate::dominators::DominatorTree;
+use crate::purity::PureFunctions;
+use crate::utils::is_ssa;
+use ir::{BlockId, Function, Instruction, Operand, Terminator, VarId};
+use model::{BinaryOp, Type, UnaryOp};
+use std::collections::{HashMap, HashSet};
+
+/// A pure computation. Types are numbered by `TypeTable`, since `Type`
+/// itself cannot be hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expr {
+    Binary { op: BinaryOp, left: Operand, right: Operand, ty: usize },
+    FloatBinary { op: BinaryOp, left: Operand, right: Operand, ty: usize },
+    Unary { op: UnaryOp, src: Operand, ty: usize },
+    FloatUnary { op: UnaryOp, src: Operand, ty: usize },
+    Cast { src: Operand, from: usize, to: usize },
+    Gep { base: Operand, index: Operand, element: usize, ty: usize },
+    /// `memory` is the block and the number of memory writes before the
+    /// call in it, for a function that reads memory
+    Call { name: String, args: Vec<Operand>, ty: usize, memory: Option<(BlockId, usize)> },
+}
+
+/// Distinct types seen by the pass, numbered in order of appearance.
+#[derive(Default)]
+struct TypeTable(Vec<Option<Type>>);
+
+impl TypeTable {
+    fn number(&mut self, ty: Option<&Type>) -> usize {
+        match self.0.iter().position(|known| known.as_ref() == ty) {
+            Some(index) => index,
+            None => {
+                self.0.push(ty.cloned());
+                self.0.len() - 1
+            }
+        }
+    }
+}
+
+/// Run GVN on a single function.
+pub fn global_value_numbering(func: &mut Function, pure: &PureFunctions) {
+    if !is_ssa(func) {
+        return;
+    }
+    let tree = DominatorTree::new(func);
+    let Some(&entry) = tree.order().first() else { return };
+    let index: HashMap<BlockId, usize> = func.blocks.iter().enumerate().map(|(i, b)| (b.id, i)).collect();
+
+    let mut leaders: HashMap<VarId, Operand> = HashMap::new();
+    let mut available: HashMap<Expr, VarId> = HashMap::new();
+    // Expressions added by the blocks on the current path of the walk
+    let mut scope: Vec<Expr> = Vec::new();
+    let mut types = TypeTable::default();
+
+    enum Visit {
+        Enter(BlockId),
+        Leave(usize),
+    }
+    let mut stack = vec![Visit::Enter(entry)];
+    while let Some(visit) = stack.pop() {
+        let block_id = match visit {
+            Visit::Enter(block_id) => block_id,
+            Visit::Leave(depth) => {
+                for expr in scope.drain(depth..) {
+                    available.remove(&expr);
+                }
+                continue;
+            }
+        };
+        stack.push(Visit::Leave(scope.len()));
+        for child in tree.children(block_id).iter().rev() {
+            stack.push(Visit::Enter(*child));
+        }
+
+        let block = &mut func.blocks[index[&block_id]];
+        let mut writes = 0;
+        for inst in &mut block.instructions {
+            inst.for_each_operand_mut(|op| resolve(op, &leaders));
+            if let Instruction::Copy { dest, src } = inst {
+                leaders.insert(*dest, src.clone());
+                continue;
+            }
+            if pure.may_write_memory(inst) {
+                writes += 1;
+            }
+            let memory = (block_id, writes);
+            let Some((dest, expr)) = expression(inst, &func.var_types, &mut types, pure, memory) else { continue };
+            match available.get(&expr) {
+                Some(&earlier) => {
+                    *inst = Instruction::Copy { dest, src: Operand::Var(earlier) };
+                    leaders.insert(dest, Operand::Var(earlier));
+                }
+                None => {
+                    available.insert(expr.clone(), dest);
+                    scope.push(expr);
+                }
+            }
+        }
+        match &mut block.terminator {
+            Terminator::Ret(Some(op)) | Terminator::CondBr { cond: op, .. } | Terminator::IndirectBr { target: op }
+            | Terminator::Switch { value: op, .. } => {
+                resolve(op, &leaders)
+            }
+            _ => {}
+        }
+    }
+
+    // Phi sources are used at the end of their predecessor, which the walk
+    // may reach after the phi; they can only name a variable. A block's phis
+    // are resolved by copies made one after another, so a source never
+    // becomes a sibling phi, whose copy may already have overwritten it
+    for block in &mut func.blocks {
+        let phis: HashSet<VarId> = block.instructions.iter().filter_map(|inst| match inst {
+            Instruction::Phi { dest, .. } => Some(*dest),
+            _ => None,
+        }).collect();
+        for inst in &mut block.instructions {
+            if let Instruction::Phi { preds, .. } = inst {
+                for (_, var) in preds {
+                    if let Some(Operand::Var(leader)) = leaders.get(var) {
+                        if !phis.contains(leader) {
+                            *var = *leader;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn resolve(op: &mut Operand, leaders: &HashMap<VarId, Operand>) {
+    if let Operand::Var(v) = op {
+        if let Some(leader) = leaders.get(v) {
+            *op = leader.clone();
+        }
+    }
+}
+
+/// The expression a pure instruction computes, with commutative and
+/// mirrored operands in a canonical order. `memory` is the block and the
+/// number of memory writes so far in it.
+fn expression(
+    inst: &Instruction,
+    var_types: &HashMap<VarId, Type>,
+    types: &mut TypeTable,
+    pure: &PureFunctions,
+    memory: (BlockId, usize),
+) -> Option<(VarId, Expr)> {
+    let ty = types.number(var_types.get(&inst.dest()?));
+    let expr = match inst {
+        Instruction::Binary { dest, op, left, right } if !is_assignment(op) => {
+            let (op, left, right) = canonical(op, left, right);
+            (*dest, Expr::Binary { op, left, right, ty })
+        }
+        Instruction::FloatBinary { dest, op, left, right } if !is_assignment(op) => {
+            let (op, left, right) = canonical(op, left, right);
+            (*dest, Expr::FloatBinary { op, left, right, ty })
+        }
+        Instruction::Unary { dest, op, src } if !matches!(op, UnaryOp::AddrOf | UnaryOp::Deref) => {
+            (*dest, Expr::Unary { op: op.clone(), src: src.clone(), ty })
+        }
+        Instruction::FloatUnary { dest, op, src } if !matches!(op, UnaryOp::AddrOf | UnaryOp::Deref) => {
+            (*dest, Expr::FloatUnary { op: op.clone(), src: src.clone(), ty })
+        }
+        Instruction::Cast { dest, src, src_type, r#type } => {
+            let (from, to) = (types.number(Some(src_type)), types.number(Some(r#type)));
+            (*dest, Expr::Cast { src: src.clone(), from, to })
+        }
+        Instruction::GetElementPtr { dest, base, index, element_type } => {
+            let element = types.number(Some(element_type));
+            (*dest, Expr::Gep { base: base.clone(), index: index.clone(), element, ty })
+        }
+        Instruction::Call { dest: Some(dest), name, args, .. } => {
+            let function = pure.get(name)?;
+            let memory = function.reads_memory.then_some(memory);
+            (*dest, Expr::Call { name: name.clone(), args: args.clone(), ty, memory })
+        }
+        _ => return None,
+    };
+    Some(expr)
+}
+
+fn is_assignment(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Assign
+            | BinaryOp::AddAssign
+            | BinaryOp::SubAssign
+            | BinaryOp::MulAssign
+            | BinaryOp::DivAssign
+            | BinaryOp::ModAssign
+            | BinaryOp::BitwiseAndAssign
+            | BinaryOp::BitwiseOrAssign
+            | BinaryOp::BitwiseXorAssign
+            | BinaryOp::ShiftLeftAssign
+            | BinaryOp::ShiftRightAssign
+    )
+}
+
+/// Order the operands of a commutative operation, or of a comparison that
+/// has a mirror image (`a < b` is `b > a`), so that both spellings match.
+fn canonical(op: &BinaryOp, left: &Operand, right: &Operand) -> (BinaryOp, Operand, Operand) {
+    let swapped = match op {
+        BinaryOp::Add
+        | BinaryOp::Mul
+        | BinaryOp::BitwiseAnd
+        | BinaryOp::BitwiseOr
+        | BinaryOp::BitwiseXor
+        | BinaryOp::EqualEqual
+        | BinaryOp::NotEqual
+        | BinaryOp::LogicalAnd
+        | BinaryOp::LogicalOr => op.clone(),
+        BinaryOp::Less => BinaryOp::Greater,
+        BinaryOp::Greater => BinaryOp::Less,
+        BinaryOp::LessEqual => BinaryOp::GreaterEqual,
+        BinaryOp::GreaterEqual => BinaryOp::LessEqual,
+        _ => return (op.clone(), left.clone(), right.clone()),
+    };
+    if operand_rank(right) < operand_rank(left) {
+        (swapped, right.clone(), left.clone())
+    } else {
+        (op.clone(), left.clone(), right.clone())
+    }
+}
+
+/// A total order on operands: constants, then globals, then variables.
+fn operand_rank(op: &Operand) -> (u8, i64, &str) {
+    match op {
+        Operand::Constant(c) => (0, *c, ""),
+        Operand::FloatConstant(f) => (1, f.to_bits() as i64, ""),
+        Operand::Global(name) => (2, 0, name),
+        Operand::Var(v) => (3, v.0 as i64, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    /// Run mem2reg and GVN on every function, returning the binary
+    /// operations left in the function named `name`.
+    fn binary_ops(prog: &mut ir::IRProgram, name: &str) -> Vec<BinaryOp> {
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            global_value_numbering(func, &PureFunctions::default());
+            assert!(ir::verify_ssa(func).is_ok(), "{}", ir::verify_ssa(func).unwrap_err());
+        }
+        let func = prog.functions.iter().find(|f| f.name == name).unwrap();
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Binary { op, .. } => Some(op.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gvn_reuses_values_from_dominating_blocks() {
+        // a * b in the entry block dominates both arms and the join, and
+        // b * a and a > b (as b < a) are the same values
+        let src = r#"
+            int f(int a, int b) {
+                int x = a * b;
+                int r;
+                if (a > b) r = b * a + 1;
+                else r = a * b - 1;
+                if (b < a) r += 2;
+                return r + a * b + x;
+            }
+            int main() { return f(3, 2) + f(2, 3); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let ops = binary_ops(&mut prog, "f");
+        assert_eq!(ops.iter().filter(|op| **op == BinaryOp::Mul).count(), 1);
+        assert_eq!(ops.iter().filter(|op| matches!(op, BinaryOp::Greater | BinaryOp::Less)).count(), 1);
+        assert_eq!(ir::interpret(&prog, "gvn", &[]).unwrap(), 38);
+    }
+
+    #[test]
+    fn test_gvn_does_not_reuse_across_branch_arms() {
+        // Each arm computes a + 1, but neither arm dominates the other
+        let src = r#"
+            int f(int a, int c) {
+                int r;
+                if (c) r = a + 1;
+                else r = (a + 1) * 2;
+                return r;
+            }
+            int main() { return f(4, 1) + f(4, 0); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let ops = binary_ops(&mut prog, "f");
+        assert_eq!(ops.iter().filter(|op| **op == BinaryOp::Add).count(), 2);
+        assert_eq!(ir::interpret(&prog, "gvn", &[]).unwrap(), 15);
+    }
+
+    #[test]
+    fn test_gvn_keeps_computations_of_different_types() {
+        // The same operands divided as int and as unsigned are different values
+        let src = r#"
+            int f(int a, int b) {
+                int s = a / b;
+                unsigned u = (unsigned)a / (unsigned)b;
+                long l = (long)a;
+                long m = (long)a;
+                return s + (int)u + (int)(l - m);
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let ops = binary_ops(&mut prog, "f");
+        assert_eq!(ops.iter().filter(|op| **op == BinaryOp::Div).count(), 2);
+        let func = &prog.functions[0];
+        let casts = func.blocks.iter().flat_map(|b| &b.instructions)
+            .filter(|inst| matches!(inst, Instruction::Cast { r#type: Type::Long, .. }))
+            .count();
+        assert_eq!(casts, 1);
+    }
+
+    #[test]
+    fn test_gvn_keeps_loop_carried_phis_apart() {
+        // x's phi would take i's phi as its source, and a and b's phis each
+        // other's; phi copies are made in order, so those read updated values
+        let src = r#"
+            int last(int n) { int x = 0; for (int i = 0; i < n; i++) x = i; return x + 1; }
+            int swap(int n) { int a = 1, b = 20, t; for (int i = 0; i < n; i++) { t = a; a = b; b = t; } return a; }
+            int main() { return last(5) * 10 + swap(3); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        binary_ops(&mut prog, "main");
+        for func in &mut prog.functions {
+            ir::remove_phis(func);
+        }
+        assert_eq!(ir::interpret(&prog, "gvn", &[]).unwrap(), 70);
+    }
+}
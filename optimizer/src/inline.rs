@@ -6,7 +6,7 @@
 //
 // Strategy:
 // - Inline functions with ≤ MAX_INLINE_BLOCKS basic blocks
-// - Don't inline recursive functions (call to self)
+// - Don't inline recursive functions (on a cycle of the call graph)
 // - Don't inline variadic functions
 // - Don't inline functions with inline asm
 // - Inline at most MAX_INLINE_SITES call sites per function
@@ -36,6 +36,7 @@ fn has_loop(func: &Function) -> bool {
         let targets: Vec<BlockId> = match &block.terminator {
             Terminator::Br(t) => vec![*t],
             Terminator::CondBr { then_block, else_block, .. } => vec![*then_block, *else_block],
+            Terminator::Switch { cases, default, .. } => Terminator::switch_targets(cases, *default),
             _ => vec![],
         };
         for t in targets {
@@ -91,6 +92,12 @@ fn is_inlineable(func: &Function) -> bool {
         return false;
     }
 
+    // Label addresses (`&&label`) name blocks of this function, so a copy
+    // in the caller would jump back into the original
+    if !func.label_addrs.is_empty() {
+        return false;
+    }
+
     // Don't inline functions with inline asm or va_start
     for block in &func.blocks {
         for inst in &block.instructions {
@@ -105,27 +112,14 @@ fn is_inlineable(func: &Function) -> bool {
     true
 }
 
-/// Check if a function has recursive calls (calls itself)
-fn is_recursive(func: &Function) -> bool {
-    for block in &func.blocks {
-        for inst in &block.instructions {
-            if let Instruction::Call { name, .. } = inst {
-                if *name == func.name {
-                    return true;
-                }
-            }
-        }
-    }
-    false
-}
-
 /// Inline all eligible call sites in the program.
 /// Returns true if any inlining was performed.
 pub fn inline_functions(program: &mut IRProgram) -> bool {
     // Build a map of inlineable function definitions
+    let graph = ir::callgraph::build(program);
     let mut inline_candidates: HashMap<String, Function> = HashMap::new();
     for func in &program.functions {
-        if is_inlineable(func) && !is_recursive(func) {
+        if is_inlineable(func) && !graph.is_recursive(&func.name) {
             inline_candidates.insert(func.name.clone(), func.clone());
         }
     }
@@ -162,7 +156,7 @@ pub fn inline_functions(program: &mut IRProgram) -> bool {
                 if should_inline {
                     let (call_name, call_dest, call_args) = {
                         let inst = &program.functions[func_idx].blocks[block_idx].instructions[inst_idx];
-                        if let Instruction::Call { name, dest, args } = inst {
+                        if let Instruction::Call { name, dest, args, .. } = inst {
                             (name.clone(), dest.clone(), args.clone())
                         } else {
                             unreachable!()
@@ -227,11 +221,16 @@ fn inline_call_site(
 
     // Split the current block: everything after the call goes to merge_block
     let orig_block = &mut caller.blocks[block_idx];
+    let call_span = orig_block.span_of(inst_idx);
+    orig_block.spans.resize(orig_block.instructions.len(), orig_block.terminator_span);
     let post_call_insts: Vec<Instruction> = orig_block.instructions.split_off(inst_idx + 1);
+    let post_call_spans = orig_block.spans.split_off(inst_idx + 1);
     let orig_terminator = std::mem::replace(&mut orig_block.terminator, Terminator::Unreachable);
+    let orig_terminator_span = std::mem::replace(&mut orig_block.terminator_span, call_span);
 
     // Remove the Call instruction itself
     orig_block.instructions.pop(); // removes the Call at inst_idx
+    orig_block.spans.pop();
 
     // Add parameter copies: callee param vars ← call args
     // NOTE: call_args are in the CALLER's namespace — do NOT remap them
@@ -242,6 +241,7 @@ fn inline_call_site(
                 dest: remapped_param,
                 src: call_args[i].clone(),
             });
+            orig_block.spans.push(call_span);
         }
     }
 
@@ -256,16 +256,20 @@ fn inline_call_site(
     let mut next_temp_var = var_offset + find_max_var(callee) + 1;
 
     for callee_block in &callee.blocks {
+        // The inlined body keeps the callee's source lines
         let mut new_block = BasicBlock {
             id: BlockId(callee_block.id.0 + block_offset),
             instructions: Vec::new(),
             terminator: Terminator::Unreachable,
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: callee_block.terminator_span,
         };
 
         // Remap instructions
-        for inst in &callee_block.instructions {
+        for (i, inst) in callee_block.instructions.iter().enumerate() {
             new_block.instructions.push(remap_instruction(inst, var_offset, block_offset));
+            new_block.spans.push(callee_block.span_of(i));
         }
 
         // Remap terminator
@@ -287,6 +291,7 @@ fn inline_call_site(
                                     dest: tv,
                                     src: remapped_val,
                                 });
+                                new_block.spans.push(callee_block.terminator_span);
                                 tv
                             }
                         };
@@ -311,6 +316,11 @@ fn inline_call_site(
             Terminator::IndirectBr { target } => Terminator::IndirectBr {
                 target: remap_operand(target, var_offset),
             },
+            Terminator::Switch { value, cases, default } => Terminator::Switch {
+                value: remap_operand(value, var_offset),
+                cases: cases.iter().map(|&(case, block)| (case, BlockId(block.0 + block_offset))).collect(),
+                default: BlockId(default.0 + block_offset),
+            },
         };
 
         inlined_blocks.push(new_block);
@@ -337,13 +347,17 @@ fn inline_call_site(
     }
 
     // Append post-call instructions after the Phi/Copy
+    let mut merge_spans = vec![call_span; merge_instructions.len()];
     merge_instructions.extend(post_call_insts);
+    merge_spans.extend(post_call_spans);
 
     let merge_block = BasicBlock {
         id: merge_block_id,
         instructions: merge_instructions,
         terminator: orig_terminator,
         is_label_target: false,
+        spans: merge_spans,
+        terminator_span: orig_terminator_span,
     };
 
     // Copy callee's var_types into caller with remapped VarIds
@@ -364,6 +378,7 @@ fn inline_call_site(
     let successor_ids: Vec<BlockId> = match &caller.blocks.last().unwrap().terminator {
         Terminator::Br(t) => vec![*t],
         Terminator::CondBr { then_block, else_block, .. } => vec![*then_block, *else_block],
+        Terminator::Switch { cases, default, .. } => Terminator::switch_targets(cases, *default),
         _ => vec![],
     };
     for block in &mut caller.blocks {
@@ -420,9 +435,10 @@ fn remap_instruction(inst: &Instruction, var_offset: usize, block_offset: usize)
             dest: VarId(dest.0 + var_offset),
             src: remap_operand(src, var_offset),
         },
-        Instruction::Cast { dest, src, r#type } => Instruction::Cast {
+        Instruction::Cast { dest, src, src_type, r#type } => Instruction::Cast {
             dest: VarId(dest.0 + var_offset),
             src: remap_operand(src, var_offset),
+            src_type: src_type.clone(),
             r#type: r#type.clone(),
         },
         Instruction::Alloca { dest, r#type } => Instruction::Alloca {
@@ -447,15 +463,18 @@ fn remap_instruction(inst: &Instruction, var_offset: usize, block_offset: usize)
             index: remap_operand(index, var_offset),
             element_type: element_type.clone(),
         },
-        Instruction::Call { dest, name, args } => Instruction::Call {
+        Instruction::Call { dest, name, args, vararg_types } => Instruction::Call {
             dest: dest.map(|d| VarId(d.0 + var_offset)),
             name: name.clone(),
             args: args.iter().map(|a| remap_operand(a, var_offset)).collect(),
+            vararg_types: vararg_types.clone(),
         },
-        Instruction::IndirectCall { dest, func_ptr, args } => Instruction::IndirectCall {
+        Instruction::IndirectCall { dest, func_ptr, args, is_variadic, vararg_types } => Instruction::IndirectCall {
             dest: dest.map(|d| VarId(d.0 + var_offset)),
             func_ptr: remap_operand(func_ptr, var_offset),
             args: args.iter().map(|a| remap_operand(a, var_offset)).collect(),
+            is_variadic: *is_variadic,
+            vararg_types: vararg_types.clone(),
         },
         Instruction::VaStart { list, arg_index } => Instruction::VaStart {
             list: remap_operand(list, var_offset),
@@ -468,6 +487,16 @@ fn remap_instruction(inst: &Instruction, var_offset: usize, block_offset: usize)
             dest: remap_operand(dest, var_offset),
             src: remap_operand(src, var_offset),
         },
+        Instruction::Memcpy { dest, src, size } => Instruction::Memcpy {
+            dest: remap_operand(dest, var_offset),
+            src: remap_operand(src, var_offset),
+            size: *size,
+        },
+        Instruction::Memset { dest, value, size } => Instruction::Memset {
+            dest: remap_operand(dest, var_offset),
+            value: remap_operand(value, var_offset),
+            size: *size,
+        },
         Instruction::VaArg { dest, list, r#type } => Instruction::VaArg {
             dest: VarId(dest.0 + var_offset),
             list: remap_operand(list, var_offset),
@@ -554,6 +583,7 @@ fn find_max_var(func: &Function) -> usize {
         match &block.terminator {
             Terminator::Ret(Some(Operand::Var(v))) => { max = max.max(v.0); }
             Terminator::CondBr { cond: Operand::Var(v), .. } => { max = max.max(v.0); }
+            Terminator::Switch { value: Operand::Var(v), .. } => { max = max.max(v.0); }
             _ => {}
         }
     }
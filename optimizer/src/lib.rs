@@ -1,29 +1,38 @@
 // Optimizer module: IR optimization passes for improving code quality and performance
 //
 // Architecture: Each optimization is a `FunctionPass` registered with a
-// `PassManager`.  The pipeline is built in `default_pipeline()`.  Adding a
-// new pass only requires implementing the trait and appending one entry –
-// no existing code needs editing.
+// `PassManager`.  The pipeline for each optimization level is built in
+// `level_pipeline()`, and `--passes` lists are resolved by `named_pipeline()`.
+// Adding a new pass only requires implementing the trait, appending it to
+// the levels that run it, and naming it in `named_pipeline()`.
 //
 // Module organization:
 // - algebraic.rs: Algebraic simplification (x*0=0, x+0=x, etc.)
 // - strength.rs: Strength reduction (multiply by power of 2 → shift)
 // - propagation.rs: Copy propagation (replace uses with copy sources)
-// - cse.rs: Common subexpression elimination
+// - gvn.rs: Global value numbering over the dominator tree
+// - purity.rs: Pure function detection, for GVN and DCE to merge and drop calls
+// - dominators.rs: Dominator tree shared by GVN and copy propagation
 // - dce.rs: Dead code elimination (remove unused computations)
 // - folding.rs: Constant folding and propagation
 // - load_forwarding.rs: Eliminate redundant loads from same memory location
+// - dse.rs: Dead store elimination over the CFG
+// - print_changed.rs: Per-pass IR diffs for --print-changed
 // - utils.rs: Utility functions (is_power_of_two, etc.)
 
 mod algebraic;
+mod reassociate;
+mod gep_combine;
 mod strength;
 mod propagation;
-mod cse;
+mod gvn;
+mod dominators;
 mod dce;
 mod folding;
 mod utils;
 mod cfg_simplify;
 mod load_forwarding;
+mod dse;
 mod licm;
 mod prefetch;
 mod block_layout;
@@ -37,17 +46,22 @@ mod inline;
 mod profile;
 mod recurrence;
 mod sroa;
+mod print_changed;
+mod purity;
 
 use ir::IRProgram;
 use recurrence::eliminate_linear_recurrences;
 use sroa::scalar_replacement_of_aggregates;
 use algebraic::algebraic_simplification;
+use reassociate::reassociate;
+use gep_combine::combine_geps;
 use strength::strength_reduce_function;
 use propagation::copy_propagation;
-use cse::common_subexpression_elimination;
+use gvn::global_value_numbering;
 use folding::optimize_function;
 use cfg_simplify::simplify_cfg;
 use load_forwarding::load_forwarding;
+use dse::dead_store_elimination;
 use licm::loop_invariant_code_motion;
 use prefetch::insert_prefetches;
 use block_layout::optimize_block_layout;
@@ -61,13 +75,30 @@ use model::target::SimdLevel;
 /// A single optimization pass that operates on one IR function at a time.
 ///
 /// Implement this trait to add a new optimization.  Then register it in
-/// `default_pipeline()` via `PassManager::add_pass()`.
+/// `level_pipeline()` via `PassManager::add_pass()`, and name it in
+/// `PASS_NAMES` and `named_pipeline()`.
 pub trait FunctionPass {
     /// Human-readable name for diagnostics / debugging.
     fn name(&self) -> &str;
 
     /// Apply the pass to a single function, mutating it in place.
     fn run(&self, func: &mut ir::Function);
+
+    /// Like `run`, with facts about the whole program. Passes that need
+    /// them override this; the rest keep the default, which calls `run`.
+    fn run_with_info(&self, func: &mut ir::Function, info: &ProgramInfo) {
+        let _ = info;
+        self.run(func);
+    }
+}
+
+/// Whole-program facts the function passes may use, gathered once before
+/// the pipeline runs.
+pub struct ProgramInfo<'a> {
+    /// Type sizes of the program's structs and unions and its data layout
+    pub layout: model::TypeLayout<'a>,
+    /// Functions whose calls have no effect besides their result
+    pub pure_functions: PureFunctions,
 }
 
 /// Ordered collection of `FunctionPass` objects that runs each pass on every
@@ -86,16 +117,105 @@ impl PassManager {
         self.passes.push(pass);
     }
 
+    /// The names of the passes, in run order.
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
     /// Run every registered pass, in order, on every function in the program.
     pub fn run(&self, program: &mut IRProgram) {
+        self.run_inner(program, None);
+    }
+
+    /// Like `run`, but also return the IR diff of every pass that changed a
+    /// function.
+    pub fn run_recording_changes(&self, program: &mut IRProgram) -> Vec<PassChange> {
+        let mut changes = Vec::new();
+        self.run_inner(program, Some(&mut changes));
+        changes
+    }
+
+    fn run_inner(&self, program: &mut IRProgram, mut changes: Option<&mut Vec<PassChange>>) {
+        let returns: std::collections::HashMap<String, model::Type> =
+            program.functions.iter().map(|f| (f.name.clone(), f.return_type.clone())).collect();
+        // Types are sized as codegen sizes them, so offsets the passes
+        // compute agree with the ones codegen would have
+        let structs: std::collections::HashMap<String, model::StructDef> =
+            program.structs.iter().map(|s| (s.name.clone(), s.clone())).collect();
+        let unions: std::collections::HashMap<String, model::UnionDef> =
+            program.unions.iter().map(|u| (u.name.clone(), u.clone())).collect();
+        let info = ProgramInfo {
+            layout: model::TypeLayout::new(&structs, &unions).with_data_layout(program.data_layout),
+            pure_functions: PureFunctions::analyze(program),
+        };
+        // Names a pass that panics instead of running, so the driver's tests
+        // can provoke an internal compiler error where one would happen
+        let panic_in_pass = std::env::var("CCOMPILER_PANIC_IN_PASS").ok();
         for func in &mut program.functions {
+            // The passes only follow direct branches, so a function with a
+            // computed goto is left as the lowerer built it
+            if func.has_indirect_branch() {
+                continue;
+            }
+            // Debug builds check the IR after every pass, so a pass that
+            // breaks it is named instead of whatever later trips over it.
+            // Input the lowerer already got wrong is not the passes' fault.
+            let verify = cfg!(debug_assertions) && ir::verify_function(func).is_ok();
             for pass in &self.passes {
-                pass.run(func);
+                set_current_pass(pass.name(), &func.name);
+                if panic_in_pass.as_deref() == Some(pass.name()) {
+                    panic!("CCOMPILER_PANIC_IN_PASS is set to '{}'", pass.name());
+                }
+                let before = changes.is_some().then(|| ir::function_to_text(func));
+                // Passes edit instructions without their spans; put each
+                // value back at the source line it came from
+                let spans = func.spans_by_dest();
+                pass.run_with_info(func, &info);
+                func.realign_spans(&spans);
+                if verify {
+                    if let Err(message) = ir::verify_function(func) {
+                        panic!("invalid IR after pass '{}' in '{}': {}", pass.name(), func.name, message);
+                    }
+                }
+                if let (Some(changes), Some(before)) = (changes.as_deref_mut(), before) {
+                    record_change(changes, pass.name(), &func.name, &before, &ir::function_to_text(func));
+                }
             }
+            // Type the variables the passes introduced
+            func.infer_var_types(&returns);
         }
+        clear_current_pass();
     }
 }
 
+fn record_change(changes: &mut Vec<PassChange>, pass: &str, function: &str, before: &str, after: &str) {
+    if before != after {
+        changes.push(PassChange {
+            pass: pass.to_string(),
+            function: function.to_string(),
+            diff: print_changed::line_diff(before, after),
+        });
+    }
+}
+
+thread_local! {
+    static CURRENT_PASS: std::cell::RefCell<Option<(String, String)>> = const { std::cell::RefCell::new(None) };
+}
+
+fn set_current_pass(pass: &str, function: &str) {
+    CURRENT_PASS.with(|c| *c.borrow_mut() = Some((pass.to_string(), function.to_string())));
+}
+
+fn clear_current_pass() {
+    CURRENT_PASS.with(|c| *c.borrow_mut() = None);
+}
+
+/// The `(pass, function)` being optimized on this thread, if a pass is
+/// running. A pass that panics leaves this set, so crash reporting can name it.
+pub fn current_pass() -> Option<(String, String)> {
+    CURRENT_PASS.with(|c| c.borrow().clone())
+}
+
 // ═══════════════════════════════════════════════════════════════════
 //  Concrete pass wrappers
 // ═══════════════════════════════════════════════════════════════════
@@ -136,22 +256,48 @@ impl FunctionPass for CopyPropagation {
     fn run(&self, func: &mut ir::Function) { copy_propagation(func); }
 }
 
+struct Reassociation;
+impl FunctionPass for Reassociation {
+    fn name(&self) -> &str { "reassociate" }
+    fn run(&self, func: &mut ir::Function) { reassociate(func); }
+}
+
+struct GepCombining;
+impl FunctionPass for GepCombining {
+    fn name(&self) -> &str { "gep-combine" }
+    // Offsets need type sizes, so there is nothing to do without a layout
+    fn run(&self, _func: &mut ir::Function) {}
+    fn run_with_info(&self, func: &mut ir::Function, info: &ProgramInfo) { combine_geps(func, &info.layout); }
+}
+
 struct LoadForwarding;
 impl FunctionPass for LoadForwarding {
     fn name(&self) -> &str { "load-forwarding" }
     fn run(&self, func: &mut ir::Function) { load_forwarding(func); }
 }
 
-struct CommonSubexprElim;
-impl FunctionPass for CommonSubexprElim {
-    fn name(&self) -> &str { "cse" }
-    fn run(&self, func: &mut ir::Function) { common_subexpression_elimination(func); }
+struct DeadStoreElimination;
+impl FunctionPass for DeadStoreElimination {
+    fn name(&self) -> &str { "dse" }
+    fn run(&self, func: &mut ir::Function) { dead_store_elimination(func); }
+}
+
+struct GlobalValueNumbering;
+impl FunctionPass for GlobalValueNumbering {
+    fn name(&self) -> &str { "gvn" }
+    fn run(&self, func: &mut ir::Function) { global_value_numbering(func, &PureFunctions::default()); }
+    fn run_with_info(&self, func: &mut ir::Function, info: &ProgramInfo) {
+        global_value_numbering(func, &info.pure_functions);
+    }
 }
 
 struct FoldingAndDCE;
 impl FunctionPass for FoldingAndDCE {
     fn name(&self) -> &str { "folding-dce" }
-    fn run(&self, func: &mut ir::Function) { optimize_function(func); }
+    fn run(&self, func: &mut ir::Function) { optimize_function(func, &PureFunctions::default()); }
+    fn run_with_info(&self, func: &mut ir::Function, info: &ProgramInfo) {
+        optimize_function(func, &info.pure_functions);
+    }
 }
 
 struct LoopInterchange;
@@ -214,81 +360,267 @@ impl FunctionPass for BlockLayout {
 //  Pipeline construction
 // ═══════════════════════════════════════════════════════════════════
 
-/// Build the default optimization pipeline for the given SIMD capability.
-pub fn default_pipeline(simd_level: SimdLevel) -> PassManager {
+/// Optimization level (`-O0` to `-O3`), which picks the pass pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    /// No passes: the lowered IR goes straight to codegen.
+    O0,
+    /// Scalar cleanup: SSA promotion, simplification, copy propagation,
+    /// redundant load and store removal, GVN, folding, and CFG cleanup.
+    O1,
+    /// O1 plus inlining, LICM, recurrence elimination, a second cleanup
+    /// round, and block layout.
+    O2,
+    /// O2 plus loop interchange, prefetching, and vectorization. The
+    /// default, and what `optimize()` runs.
+    #[default]
+    O3,
+}
+
+/// The passes to run: an optimization level's pipeline, or the passes
+/// named on the command line (`--passes=a,b,c`), in order, for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassSelection {
+    Level(OptLevel),
+    Named(Vec<String>),
+}
+
+impl Default for PassSelection {
+    fn default() -> Self {
+        PassSelection::Level(OptLevel::default())
+    }
+}
+
+/// Every name `--passes` accepts. `inline` is a whole-program pass and
+/// runs before the function passes wherever it appears in the list.
+pub const PASS_NAMES: &[&str] = &[
+    "inline",
+    "sroa",
+    "mem2reg",
+    "algebraic-simplification",
+    "copy-propagation",
+    "reassociate",
+    "gep-combine",
+    "strength-reduction",
+    "load-forwarding",
+    "dse",
+    "gvn",
+    "folding-dce",
+    "loop-interchange",
+    "licm",
+    "prefetch",
+    "vectorize",
+    "slp",
+    "recurrence-elimination",
+    "remove-phis",
+    "cfg-simplify",
+    "block-layout",
+];
+
+/// Build the pipeline of an optimization level for the given SIMD capability.
+pub fn level_pipeline(level: OptLevel, simd_level: SimdLevel) -> PassManager {
     let mut pm = PassManager::new();
+    if level == OptLevel::O0 {
+        return pm;
+    }
 
     // ── Round 1: initial optimization ───────────────────────────
     pm.add_pass(Box::new(SROA));
     pm.add_pass(Box::new(Mem2Reg));
     pm.add_pass(Box::new(AlgebraicSimplification));
-    pm.add_pass(Box::new(StrengthReduction));
     pm.add_pass(Box::new(CopyPropagation));
+    pm.add_pass(Box::new(Reassociation));
+    pm.add_pass(Box::new(GepCombining));
+    pm.add_pass(Box::new(StrengthReduction));
     pm.add_pass(Box::new(LoadForwarding));
-    pm.add_pass(Box::new(CommonSubexprElim));
+    pm.add_pass(Box::new(DeadStoreElimination));
+    pm.add_pass(Box::new(GlobalValueNumbering));
     pm.add_pass(Box::new(FoldingAndDCE));
-    pm.add_pass(Box::new(LoopInterchange));
-    pm.add_pass(Box::new(LICM));
-    pm.add_pass(Box::new(Prefetch));
-    if simd_level >= SimdLevel::SSE2 {
-        let vec_level = match simd_level {
-            SimdLevel::AVX2 | SimdLevel::AVX => vectorize::SimdLevel::AVX2,
-            _ => vectorize::SimdLevel::SSE2,
-        };
-        pm.add_pass(Box::new(Vectorize { level: vec_level }));
-        pm.add_pass(Box::new(SlpVectorize { vf: vec_level.vector_width() }));
+    if level >= OptLevel::O3 {
+        pm.add_pass(Box::new(LoopInterchange));
+    }
+    if level >= OptLevel::O2 {
+        pm.add_pass(Box::new(LICM));
+    }
+    if level >= OptLevel::O3 {
+        pm.add_pass(Box::new(Prefetch));
+        if let Some(vec_level) = vector_level(simd_level) {
+            pm.add_pass(Box::new(Vectorize { level: vec_level }));
+            pm.add_pass(Box::new(SlpVectorize { vf: vec_level.vector_width() }));
+        }
     }
 
-    // ── Round 2: clean up after LICM / vectorize / etc. ────────
-    pm.add_pass(Box::new(AlgebraicSimplification));
-    pm.add_pass(Box::new(StrengthReduction));
-    pm.add_pass(Box::new(CopyPropagation));
-    pm.add_pass(Box::new(LoadForwarding));
-    pm.add_pass(Box::new(CommonSubexprElim));
-    pm.add_pass(Box::new(FoldingAndDCE));
+    if level >= OptLevel::O2 {
+        // ── Round 2: clean up after LICM / vectorize / etc. ────────
+        pm.add_pass(Box::new(AlgebraicSimplification));
+        pm.add_pass(Box::new(CopyPropagation));
+        pm.add_pass(Box::new(Reassociation));
+        pm.add_pass(Box::new(GepCombining));
+        pm.add_pass(Box::new(StrengthReduction));
+        pm.add_pass(Box::new(LoadForwarding));
+        pm.add_pass(Box::new(DeadStoreElimination));
+        pm.add_pass(Box::new(GlobalValueNumbering));
+        pm.add_pass(Box::new(FoldingAndDCE));
+
+        // Transform linear sum recurrences after other opts; re-SSA before phi removal.
+        pm.add_pass(Box::new(RecurrenceElimination));
+    }
 
     // ── Finalize ────────────────────────────────────────────────
-    // Transform linear sum recurrences after other opts; re-SSA before phi removal.
-    pm.add_pass(Box::new(RecurrenceElimination));
     pm.add_pass(Box::new(RemovePhis));
     pm.add_pass(Box::new(CfgSimplify));
-    pm.add_pass(Box::new(BlockLayout));
+    if level >= OptLevel::O2 {
+        pm.add_pass(Box::new(BlockLayout));
+    }
     pm
 }
 
+/// Build the default (`-O3`) optimization pipeline for the given SIMD capability.
+pub fn default_pipeline(simd_level: SimdLevel) -> PassManager {
+    level_pipeline(OptLevel::O3, simd_level)
+}
+
+/// Build a pipeline of the function passes in `names`, in order (`inline`
+/// is skipped; `run_optimizer` runs it). Fails on a name not in
+/// `PASS_NAMES`, or on a vectorizer when the target has no SIMD.
+pub fn named_pipeline(names: &[String], simd_level: SimdLevel) -> Result<PassManager, String> {
+    let mut pm = PassManager::new();
+    for name in names {
+        let pass: Box<dyn FunctionPass> = match name.as_str() {
+            "inline" => continue,
+            "sroa" => Box::new(SROA),
+            "mem2reg" => Box::new(Mem2Reg),
+            "algebraic-simplification" => Box::new(AlgebraicSimplification),
+            "strength-reduction" => Box::new(StrengthReduction),
+            "copy-propagation" => Box::new(CopyPropagation),
+            "reassociate" => Box::new(Reassociation),
+            "gep-combine" => Box::new(GepCombining),
+            "load-forwarding" => Box::new(LoadForwarding),
+            "dse" => Box::new(DeadStoreElimination),
+            "gvn" => Box::new(GlobalValueNumbering),
+            "folding-dce" => Box::new(FoldingAndDCE),
+            "loop-interchange" => Box::new(LoopInterchange),
+            "licm" => Box::new(LICM),
+            "prefetch" => Box::new(Prefetch),
+            "vectorize" | "slp" => {
+                let Some(level) = vector_level(simd_level) else {
+                    return Err(format!("pass '{}' needs a target with SSE2", name));
+                };
+                if name == "vectorize" {
+                    Box::new(Vectorize { level })
+                } else {
+                    Box::new(SlpVectorize { vf: level.vector_width() })
+                }
+            }
+            "recurrence-elimination" => Box::new(RecurrenceElimination),
+            "remove-phis" => Box::new(RemovePhis),
+            "cfg-simplify" => Box::new(CfgSimplify),
+            "block-layout" => Box::new(BlockLayout),
+            _ => return Err(format!("unknown pass '{}' (known passes: {})", name, PASS_NAMES.join(", "))),
+        };
+        pm.add_pass(pass);
+    }
+    Ok(pm)
+}
+
+fn vector_level(simd_level: SimdLevel) -> Option<vectorize::SimdLevel> {
+    match simd_level {
+        SimdLevel::AVX2 | SimdLevel::AVX => Some(vectorize::SimdLevel::AVX2),
+        level if level >= SimdLevel::SSE2 => Some(vectorize::SimdLevel::SSE2),
+        _ => None,
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════
 //  Public entry points
 // ═══════════════════════════════════════════════════════════════════
 
 pub use profile::{load_profile, write_profile, apply_profile_layout, BlockProfile, profile_counter_name};
+pub use print_changed::PassChange;
+pub use purity::{PureFunction, PureFunctions};
 
 /// Main optimization entry point (auto-detects SIMD level).
 pub fn optimize(program: IRProgram) -> IRProgram {
-    optimize_with_options(program, SimdLevel::detect(), None)
+    optimize_with_options(program, &PassSelection::default(), SimdLevel::detect(), None)
 }
 
 /// Optimize with explicit SIMD level control.
-pub fn optimize_with_simd(mut program: IRProgram, simd_level: SimdLevel) -> IRProgram {
-    optimize_with_options(program, simd_level, None)
+pub fn optimize_with_simd(program: IRProgram, simd_level: SimdLevel) -> IRProgram {
+    optimize_with_options(program, &PassSelection::default(), simd_level, None)
 }
 
-/// Optimize with optional PGO profile data for block layout.
+/// Run the selected passes, with optional PGO profile data for block layout.
+/// A `PassSelection::Named` list must have been accepted by `named_pipeline`.
 pub fn optimize_with_options(
+    program: IRProgram,
+    passes: &PassSelection,
+    simd_level: SimdLevel,
+    profile: Option<BlockProfile>,
+) -> IRProgram {
+    run_optimizer(program, passes, simd_level, profile, None)
+}
+
+/// Optimize like `optimize_with_options`, and also return the IR diff of
+/// every pass that changed a function (`--print-changed`), in run order.
+pub fn optimize_recording_changes(
+    program: IRProgram,
+    passes: &PassSelection,
+    simd_level: SimdLevel,
+    profile: Option<BlockProfile>,
+) -> (IRProgram, Vec<PassChange>) {
+    let mut changes = Vec::new();
+    let program = run_optimizer(program, passes, simd_level, profile, Some(&mut changes));
+    (program, changes)
+}
+
+fn run_optimizer(
     mut program: IRProgram,
+    passes: &PassSelection,
     simd_level: SimdLevel,
     profile: Option<BlockProfile>,
+    mut changes: Option<&mut Vec<PassChange>>,
 ) -> IRProgram {
-    inline::inline_functions(&mut program);
+    let (inline, pipeline) = match passes {
+        PassSelection::Level(level) => (*level >= OptLevel::O2, level_pipeline(*level, simd_level)),
+        PassSelection::Named(names) => (
+            names.iter().any(|name| name == "inline"),
+            named_pipeline(names, simd_level).unwrap_or_else(|message| panic!("{}", message)),
+        ),
+    };
+    if inline {
+        run_program_pass(&mut program, "inline", changes.as_deref_mut(), |p| {
+            inline::inline_functions(p);
+        });
+    }
 
-    let pipeline = default_pipeline(simd_level);
-    pipeline.run(&mut program);
+    pipeline.run_inner(&mut program, changes.as_deref_mut());
 
     if let Some(ref prof) = profile {
-        apply_profile_layout(&mut program, prof);
+        run_program_pass(&mut program, "profile-layout", changes, |p| apply_profile_layout(p, prof));
     }
     program
 }
 
+/// Run a whole-program pass, recording a change for each function it altered.
+fn run_program_pass(
+    program: &mut IRProgram,
+    name: &str,
+    changes: Option<&mut Vec<PassChange>>,
+    pass: impl FnOnce(&mut IRProgram),
+) {
+    set_current_pass(name, "<program>");
+    let before: Option<Vec<String>> =
+        changes.is_some().then(|| program.functions.iter().map(ir::function_to_text).collect());
+    pass(program);
+    clear_current_pass();
+    if let (Some(changes), Some(before)) = (changes, before) {
+        // Program passes rewrite functions in place without adding or removing any
+        for (func, before) in program.functions.iter().zip(&before) {
+            record_change(changes, name, &func.name, before, &ir::function_to_text(func));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -392,6 +724,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn optimized_instructions_keep_their_source_lines() {
+        let src = "int f(int x, int y) {\n    int a = x * y;\n    int b = a + 7;\n    return b;\n}\n";
+        let (tokens, spans): (Vec<_>, Vec<_>) =
+            lexer::lex_spanned(src, model::FileId(0)).unwrap().into_iter().map(|t| (t.node, t.span)).unzip();
+        let ast = parser::parse_tokens_with_spans(&tokens, &spans).unwrap();
+        let ir = optimize(ir::Lowerer::new().lower_program(&ast).unwrap());
+        let mut map = model::SourceMap::new();
+        let file = map.add_file("t.c", src);
+        let line = |span: model::Span| map.line_col(file, span.start).unwrap().line;
+
+        let f = &ir.functions[0];
+        for block in &f.blocks {
+            assert_eq!(block.spans.len(), block.instructions.len());
+            for (inst, span) in block.instructions.iter().zip(&block.spans) {
+                match inst {
+                    Instruction::Binary { op: model::BinaryOp::Mul, .. } => assert_eq!(line(*span), 2),
+                    Instruction::Binary { op: model::BinaryOp::Add, .. } => assert_eq!(line(*span), 3),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(line(f.blocks[0].terminator_span), 4);
+    }
+
+    #[test]
+    fn mutually_recursive_functions_are_not_inlined() {
+        let ir = compile_to_ir(
+            "int is_odd(int n);
+             int is_even(int n) { return n == 0 ? 1 : is_odd(n - 1); }
+             int is_odd(int n) { return n == 0 ? 0 : is_even(n - 1); }
+             int main() { return is_even(4); }",
+        );
+        let calls = |name: &str| -> Vec<String> {
+            let f = ir.functions.iter().find(|f| f.name == name).unwrap();
+            f.blocks
+                .iter()
+                .flat_map(|b| &b.instructions)
+                .filter_map(|i| match i {
+                    Instruction::Call { name, .. } => Some(name.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(calls("is_even"), ["is_odd"]);
+        assert_eq!(calls("main"), ["is_even"]);
+    }
+
     #[test]
     fn dead_code_eliminated() {
         let ir = compile_to_ir("int main() { int x = 5; int y = 10; return x; }");
@@ -419,12 +799,43 @@ mod tests {
         assert!(ir.functions[0].blocks.len() >= 3);
     }
 
+    #[test]
+    fn vectorizer_skips_narrow_element_loops() {
+        let src = "int sum(const char *p, int n) { int s = 0; for (int i = 0; i < n; i++) s += p[i]; return s; }\n\
+                   int total(const int *p, int n) { int s = 0; for (int i = 0; i < n; i++) s += p[i]; return s; }";
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let ir = optimize_with_simd(ir::Lowerer::new().lower_program(&ast).unwrap(), SimdLevel::AVX2);
+        let simd_loads = |name: &str| ir.functions.iter().find(|f| f.name == name).unwrap().blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .filter(|inst| matches!(inst, Instruction::Simd { op: ir::SimdOp::Load, .. }))
+            .count();
+        assert_eq!(simd_loads("sum"), 0);
+        assert!(simd_loads("total") > 0);
+    }
+
     #[test]
     fn optimizer_handles_if_else() {
         let ir = compile_to_ir("int f(int x) { if (x > 0) { return 1; } else { return 0; } }");
         assert!(!ir.functions.is_empty());
     }
 
+    #[test]
+    fn sroa_keeps_unions_whole() {
+        let src = "int main() { union { int i; char c[4]; } u; u.i = 0x01020304; return u.c[0]; }";
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut ir_prog = ir::Lowerer::new().lower_program(&ast).unwrap();
+        let func = &mut ir_prog.functions[0];
+        let allocas = |f: &ir::Function| {
+            f.blocks.iter().flat_map(|b| &b.instructions).filter(|i| matches!(i, Instruction::Alloca { .. })).count()
+        };
+        let before = allocas(func);
+        scalar_replacement_of_aggregates(func);
+        assert_eq!(allocas(func), before);
+        assert_eq!(ir::interpret(&ir_prog, "sroa", &[]).unwrap(), 4);
+    }
+
     #[test]
     fn nested_struct_ssa_after_sroa_and_mem2reg() {
         let src = include_str!("../../testing/test_nested_struct.c");
@@ -455,4 +866,106 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn load_forwarding_sees_stores_through_other_spellings_of_an_address() {
+        // p + 1 and &p[1] are different SSA values for the same memory, so
+        // the store through q must not be forwarded past
+        let src = r#"
+            int f(int *p) {
+                int *q = p + 1;
+                p[1] = 5;
+                *q = 7;
+                p[2] = 1;
+                return p[1];
+            }
+            int main() {
+                int a[3];
+                return f(a);
+            }
+        "#;
+        let ir_prog = compile_to_ir(src);
+        assert_eq!(ir::interpret(&ir_prog, "load_forwarding", &[]).unwrap(), 7);
+    }
+
+    #[test]
+    fn levels_add_passes_in_order() {
+        let names = |level| level_pipeline(level, SimdLevel::AVX2).pass_names().join(",");
+        assert_eq!(names(OptLevel::O0), "");
+        assert_eq!(
+            names(OptLevel::O1),
+            "sroa,mem2reg,algebraic-simplification,copy-propagation,reassociate,gep-combine,strength-reduction,load-forwarding,dse,gvn,folding-dce,remove-phis,cfg-simplify"
+        );
+        let o2 = names(OptLevel::O2);
+        assert!(o2.contains("licm") && o2.ends_with("block-layout") && !o2.contains("vectorize"), "{}", o2);
+        let o3 = names(OptLevel::O3);
+        assert!(o3.contains("loop-interchange") && o3.contains("vectorize") && o3.contains("slp"), "{}", o3);
+        assert_eq!(o3, default_pipeline(SimdLevel::AVX2).pass_names().join(","));
+        assert!(!level_pipeline(OptLevel::O3, SimdLevel::None).pass_names().contains(&"vectorize"));
+    }
+
+    #[test]
+    fn named_pipelines_accept_every_pass_name() {
+        let names: Vec<String> = PASS_NAMES.iter().map(|name| name.to_string()).collect();
+        let pm = named_pipeline(&names, SimdLevel::SSE2).unwrap();
+        // Everything but the whole-program inliner is a function pass
+        assert_eq!(pm.pass_names(), PASS_NAMES[1..].to_vec());
+
+        let err = named_pipeline(&["mem2reg".to_string(), "hoist".to_string()], SimdLevel::SSE2).err().unwrap();
+        assert!(err.contains("unknown pass 'hoist'"), "{}", err);
+        assert!(named_pipeline(&["slp".to_string()], SimdLevel::None).is_err());
+    }
+
+    #[test]
+    fn named_pipeline_runs_only_the_listed_passes() {
+        let src = "int main() { int x = 4; return x * 2; }";
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let program = ir::Lowerer::new().lower_program(&ast).unwrap();
+        let passes = PassSelection::Named(vec!["mem2reg".to_string()]);
+        let (program, changes) = optimize_recording_changes(program, &passes, SimdLevel::None, None);
+        assert_eq!(changes.iter().map(|c| c.pass.as_str()).collect::<Vec<_>>(), vec!["mem2reg"]);
+        // Promoted, but not folded
+        let text = ir::function_to_text(&program.functions[0]);
+        assert!(!text.contains("alloca") && text.contains("mul"), "{}", text);
+    }
+
+    #[test]
+    fn folded_branches_drop_their_phi_incomings() {
+        // `while (0)` folds away the back edge; the loop header's phi must
+        // forget it, or a second round reassociates across the stale copy.
+        let src = "int main(int argc, char **argv) { int r = argc % 1; do { r += 40; } while (0); return r + 2; }";
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let program = ir::Lowerer::new().lower_program(&ast).unwrap();
+        let round = "sroa,mem2reg,algebraic-simplification,copy-propagation,gvn,folding-dce";
+        let names: Vec<String> = format!("{},{},remove-phis", round, round).split(',').map(String::from).collect();
+        let program = optimize_with_options(program, &PassSelection::Named(names), SimdLevel::None, None);
+        assert_eq!(ir::interpret(&program, "test", &[]).unwrap(), 42);
+    }
+
+    #[test]
+    fn debug_builds_name_the_pass_that_breaks_the_ir() {
+        struct DropInstructions;
+        impl FunctionPass for DropInstructions {
+            fn name(&self) -> &str { "drop-instructions" }
+            fn run(&self, func: &mut ir::Function) {
+                for block in &mut func.blocks {
+                    block.instructions.clear();
+                }
+            }
+        }
+        let src = "int main(int argc, char **argv) { return argc * 3 + 1; }";
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut program = ir::Lowerer::new().lower_program(&ast).unwrap();
+        let mut pm = PassManager::new();
+        pm.add_pass(Box::new(Mem2Reg));
+        pm.add_pass(Box::new(DropInstructions));
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pm.run(&mut program))).unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("invalid IR after pass 'drop-instructions' in 'main'"), "{}", message);
+        assert_eq!(current_pass(), Some(("drop-instructions".to_string(), "main".to_string())));
+        clear_current_pass();
+    }
 }
@@ -19,7 +19,7 @@
 //   }
 
 use ir::{Function, Instruction, Operand, VarId, BlockId};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use crate::loop_analysis::{self, NaturalLoop};
 
 /// Run LICM on all loops in a function
@@ -31,7 +31,7 @@ pub fn loop_invariant_code_motion(func: &mut Function) {
 }
 
 /// Check if a variable is defined inside the loop
-fn is_defined_in_loop(var: VarId, func: &Function, loop_body: &HashSet<BlockId>) -> bool {
+fn is_defined_in_loop(var: VarId, func: &Function, loop_body: &BTreeSet<BlockId>) -> bool {
     for block in &func.blocks {
         if !loop_body.contains(&block.id) {
             continue;
@@ -56,7 +56,7 @@ fn get_def(inst: &Instruction) -> Option<VarId> {
 fn is_operand_invariant(
     op: &Operand,
     func: &Function,
-    loop_body: &HashSet<BlockId>,
+    loop_body: &BTreeSet<BlockId>,
     already_hoisted: &HashSet<VarId>,
 ) -> bool {
     match op {
@@ -76,7 +76,7 @@ fn is_operand_invariant(
 fn is_hoistable(
     inst: &Instruction,
     func: &Function,
-    loop_body: &HashSet<BlockId>,
+    loop_body: &BTreeSet<BlockId>,
     already_hoisted: &HashSet<VarId>,
 ) -> bool {
     match inst {
@@ -124,6 +124,8 @@ fn is_hoistable(
         Instruction::Phi { .. }         // SSA control-flow join
         | Instruction::Alloca { .. }    // Stack allocation
         | Instruction::Store { .. }     // Side effects
+        | Instruction::Memcpy { .. }
+        | Instruction::Memset { .. }
         | Instruction::Call { .. }      // Side effects
         | Instruction::IndirectCall { .. }
         | Instruction::VaStart { .. }
@@ -136,7 +138,7 @@ fn is_hoistable(
 }
 
 /// Check if any block in the loop body contains a Store instruction
-fn loop_has_stores(func: &Function, loop_body: &HashSet<BlockId>) -> bool {
+fn loop_has_stores(func: &Function, loop_body: &BTreeSet<BlockId>) -> bool {
     for block in &func.blocks {
         if !loop_body.contains(&block.id) {
             continue;
@@ -144,6 +146,8 @@ fn loop_has_stores(func: &Function, loop_body: &HashSet<BlockId>) -> bool {
         for inst in &block.instructions {
             match inst {
                 Instruction::Store { .. }
+                | Instruction::Memcpy { .. }
+                | Instruction::Memset { .. }
                 | Instruction::Call { .. }
                 | Instruction::IndirectCall { .. }
                 | Instruction::InlineAsm { .. }
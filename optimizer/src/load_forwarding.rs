@@ -1,5 +1,6 @@
-use ir::{Function, Instruction, Operand};
-use model::Type;
+use crate::utils::scalar_size;
+use ir::{Function, Instruction, Operand, VarId};
+use model::{BinaryOp, Type};
 use std::collections::HashMap;
 
 /// Load forwarding: eliminate redundant loads within a basic block
@@ -13,8 +14,17 @@ use std::collections::HashMap;
 /// - A store to an address records that the address holds a known value
 /// - A load from a known address is replaced by a copy from the stored value
 /// - Any call or indirect call invalidates all known addresses (may alias anything)
-/// - A store to a different address does NOT invalidate others (SSA addresses are distinct)
+/// - A store invalidates the known addresses it may overlap: two addresses
+///   are only known apart when they are in different locals or globals, or
+///   at non-overlapping constant offsets into the same one
 pub fn load_forwarding(func: &mut Function) {
+    let defs: HashMap<VarId, Instruction> = func.blocks.iter()
+        .flat_map(|b| &b.instructions)
+        .filter(|inst| matches!(inst,
+            Instruction::Alloca { .. } | Instruction::GetElementPtr { .. }
+            | Instruction::Copy { .. } | Instruction::Binary { op: BinaryOp::Add, .. }))
+        .filter_map(|inst| Some((inst.dest()?, inst.clone())))
+        .collect();
     for block in &mut func.blocks {
         // Map from address operand → (stored value operand, value_type)
         let mut known_stores: HashMap<Operand, (Operand, Type)> = HashMap::new();
@@ -27,6 +37,10 @@ pub fn load_forwarding(func: &mut Function) {
                         // Volatile store — invalidate all known stores (acts as memory barrier)
                         known_stores.clear();
                     } else {
+                        let stored = address_of(addr, &defs);
+                        known_stores.retain(|known, (_, known_type)| {
+                            known != addr && disjoint(&address_of(known, &defs), known_type, &stored, value_type)
+                        });
                         // Record that this address now holds this value
                         known_stores.insert(addr.clone(), (src.clone(), value_type.clone()));
                     }
@@ -55,8 +69,9 @@ pub fn load_forwarding(func: &mut Function) {
                 Instruction::InlineAsm { .. } => {
                     known_stores.clear();
                 }
-                // VaStart/VaEnd/VaCopy/VaArg modify va_list memory
-                Instruction::VaStart { .. } | Instruction::VaEnd { .. }
+                // Bulk copies/fills and VaStart/VaEnd/VaCopy/VaArg write memory
+                Instruction::Memcpy { .. } | Instruction::Memset { .. }
+                | Instruction::VaStart { .. } | Instruction::VaEnd { .. }
                 | Instruction::VaCopy { .. } | Instruction::VaArg { .. } => {
                     known_stores.clear();
                 }
@@ -71,58 +86,66 @@ pub fn load_forwarding(func: &mut Function) {
             block.instructions[idx] = new_inst;
         }
     }
-
-    // Dead store elimination: remove stores that are overwritten before being read
-    dead_store_elimination(func);
 }
 
-/// Dead store elimination within a basic block.
-///
-/// Scans instructions in reverse order. If we see a store to an address
-/// that is already in the "will be overwritten" set (meaning a later store
-/// to the same address exists with no intervening load/call), the earlier
-/// store is dead and can be removed.
-fn dead_store_elimination(func: &mut Function) {
-    use std::collections::HashSet;
-
-    for block in &mut func.blocks {
-        // Scan backwards to find dead stores
-        // Track addresses that are stored to without being loaded
-        let mut overwritten: HashSet<Operand> = HashSet::new();
-        let mut dead_indices: Vec<usize> = Vec::new();
+/// The object an address points into.
+#[derive(Debug, Clone, PartialEq)]
+enum Base {
+    /// A local
+    Alloca(VarId),
+    /// A global variable
+    Global(String),
+    /// A pointer of unknown origin, which may point into anything
+    Unknown(Operand),
+}
 
-        for i in (0..block.instructions.len()).rev() {
-            match &block.instructions[i] {
-                Instruction::Store { addr, volatile, .. } => {
-                    if *volatile {
-                        // Volatile store is never dead
-                        overwritten.clear();
-                    } else if overwritten.contains(addr) {
-                        // This store is dead — a later store overwrites it
-                        dead_indices.push(i);
-                    } else {
-                        overwritten.insert(addr.clone());
-                    }
-                }
-                Instruction::Load { addr, .. } => {
-                    // A load reads from this address — earlier stores are NOT dead
-                    overwritten.remove(addr);
-                }
-                // Calls/asm may read any memory — clear all
-                Instruction::Call { .. } | Instruction::IndirectCall { .. }
-                | Instruction::InlineAsm { .. }
-                | Instruction::VaStart { .. } | Instruction::VaEnd { .. }
-                | Instruction::VaCopy { .. } | Instruction::VaArg { .. } => {
-                    overwritten.clear();
-                }
-                _ => {}
+/// Split an address into its base object and, when it is constant, the
+/// byte offset into it, through GEPs, copies, and additions of a constant.
+fn address_of(addr: &Operand, defs: &HashMap<VarId, Instruction>) -> (Base, Option<i64>) {
+    let mut current = addr.clone();
+    let mut offset = Some(0i64);
+    // Chains are short; the bound only guards against cycles in non-SSA code
+    for _ in 0..16 {
+        let Operand::Var(v) = &current else { break };
+        let next = match defs.get(v) {
+            Some(Instruction::Alloca { dest, .. }) => return (Base::Alloca(*dest), offset),
+            Some(Instruction::Copy { src, .. }) => src.clone(),
+            Some(Instruction::GetElementPtr { base, index, element_type, .. }) => {
+                offset = match (index, scalar_size(element_type)) {
+                    (Operand::Constant(0), _) => offset,
+                    (Operand::Constant(i), Some(stride)) => offset.map(|o| o + i * stride as i64),
+                    _ => None,
+                };
+                base.clone()
             }
-        }
+            Some(Instruction::Binary { left, right, .. }) => match (left, right) {
+                (Operand::Constant(c), base) | (base, Operand::Constant(c)) => {
+                    offset = offset.map(|o| o + c);
+                    base.clone()
+                }
+                _ => break,
+            },
+            _ => break,
+        };
+        current = next;
+    }
+    match current {
+        Operand::Global(name) => (Base::Global(name), offset),
+        other => (Base::Unknown(other), None),
+    }
+}
 
-        // Remove dead stores (in reverse order to maintain indices)
-        dead_indices.sort();
-        for &idx in dead_indices.iter().rev() {
-            block.instructions.remove(idx);
+/// Whether accesses of types `a_type` and `b_type` at these addresses
+/// certainly do not overlap.
+fn disjoint(a: &(Base, Option<i64>), a_type: &Type, b: &(Base, Option<i64>), b_type: &Type) -> bool {
+    match (a, b) {
+        ((Base::Unknown(_), _), _) | (_, (Base::Unknown(_), _)) => false,
+        ((a_base, Some(a_off)), (b_base, Some(b_off))) if a_base == b_base => {
+            match (scalar_size(a_type), scalar_size(b_type)) {
+                (Some(a_size), Some(b_size)) => a_off + a_size as i64 <= *b_off || b_off + b_size as i64 <= *a_off,
+                _ => false,
+            }
         }
+        ((a_base, _), (b_base, _)) => a_base != b_base,
     }
 }
@@ -6,7 +6,7 @@
 
 use ir::{Function, Terminator, BlockId, Instruction, Operand, VarId};
 use model::BinaryOp;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 
 /// A natural loop in the CFG
 #[derive(Debug, Clone)]
@@ -16,7 +16,7 @@ pub struct NaturalLoop {
     /// The back-edge source (block that jumps back to header)
     pub latch: BlockId,
     /// All blocks in the loop body (including header and latch)
-    pub body: HashSet<BlockId>,
+    pub body: BTreeSet<BlockId>,
     /// The exit block (first block outside the loop)
     pub exit: Option<BlockId>,
     /// Preheader block (unique predecessor of header from outside the loop)
@@ -123,7 +123,7 @@ pub fn find_loops(func: &Function) -> Vec<NaturalLoop> {
     let mut loops = Vec::new();
     for (latch, header) in back_edges {
         // Compute loop body: all nodes that can reach latch without going through header
-        let mut body = HashSet::new();
+        let mut body = BTreeSet::new();
         body.insert(header);
         body.insert(latch);
 
@@ -184,7 +184,7 @@ fn detect_induction_var(
     func: &Function,
     header: BlockId,
     _latch: BlockId,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
 ) -> Option<InductionVar> {
     let header_block = func.blocks.iter().find(|b| b.id == header)?;
 
@@ -301,7 +301,7 @@ fn detect_induction_var(
 }
 
 /// Check if var `a` is a copy of var `b` within the loop body
-fn is_copy_of(func: &Function, a: VarId, b: VarId, body: &HashSet<BlockId>) -> bool {
+fn is_copy_of(func: &Function, a: VarId, b: VarId, body: &BTreeSet<BlockId>) -> bool {
     for &block_id in body {
         if let Some(block) = func.blocks.iter().find(|bl| bl.id == block_id) {
             for inst in &block.instructions {
@@ -317,7 +317,7 @@ fn is_copy_of(func: &Function, a: VarId, b: VarId, body: &HashSet<BlockId>) -> b
 }
 
 /// Find the initial value of the induction variable
-fn find_iv_init(func: &Function, iv_var: VarId, header: BlockId, body: &HashSet<BlockId>) -> Option<i64> {
+fn find_iv_init(func: &Function, iv_var: VarId, header: BlockId, body: &BTreeSet<BlockId>) -> Option<i64> {
     let header_block = func.blocks.iter().find(|b| b.id == header)?;
 
     // Check for phi node defining iv_var
@@ -470,6 +470,7 @@ fn compute_trip_count(iv: &InductionVar) -> Option<usize> {
 mod tests {
     use super::*;
     use ir::{BasicBlock, Terminator, Instruction, Operand, VarId, BlockId};
+    use model::Span;
 
     fn make_simple_loop_func() -> Function {
         // Create a simple loop:
@@ -487,6 +488,8 @@ mod tests {
             is_static: false,
             label_addrs: vec![],
             labels: HashMap::new(),
+            local_names: HashMap::new(),
+            span: model::Span::default(),
             blocks: vec![
                 BasicBlock {
                     id: BlockId(0),
@@ -495,6 +498,8 @@ mod tests {
                     ],
                     terminator: Terminator::Br(BlockId(1)),
                     is_label_target: false,
+                    spans: Vec::new(),
+                    terminator_span: Span::default(),
                 },
                 BasicBlock {
                     id: BlockId(1),
@@ -516,6 +521,8 @@ mod tests {
                         BlockId(3),
                     ),
                     is_label_target: false,
+                    spans: Vec::new(),
+                    terminator_span: Span::default(),
                 },
                 BasicBlock {
                     id: BlockId(2),
@@ -529,12 +536,16 @@ mod tests {
                     ],
                     terminator: Terminator::Br(BlockId(1)),
                     is_label_target: false,
+                    spans: Vec::new(),
+                    terminator_span: Span::default(),
                 },
                 BasicBlock {
                     id: BlockId(3),
                     instructions: vec![],
                     terminator: Terminator::Ret(Some(Operand::Constant(0))),
                     is_label_target: false,
+                    spans: Vec::new(),
+                    terminator_span: Span::default(),
                 },
             ],
         }
@@ -596,12 +607,16 @@ mod tests {
             is_static: false,
             label_addrs: vec![],
             labels: HashMap::new(),
+            local_names: HashMap::new(),
+            span: model::Span::default(),
             blocks: vec![
                 BasicBlock {
                     id: BlockId(0),
                     instructions: vec![],
                     terminator: Terminator::Ret(Some(Operand::Constant(42))),
                     is_label_target: false,
+                    spans: Vec::new(),
+                    terminator_span: Span::default(),
                 },
             ],
         };
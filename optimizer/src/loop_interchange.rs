@@ -24,7 +24,7 @@
 
 use ir::{Function, Instruction, Operand, VarId, BlockId};
 use model::BinaryOp;
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use crate::loop_analysis::{self, NaturalLoop, InductionVar};
 
 /// Run loop interchange analysis on all loops in a function
@@ -131,7 +131,7 @@ fn is_perfectly_nested(func: &Function, outer: &NaturalLoop, inner: &NaturalLoop
 /// it's the innermost loop IV.
 fn count_gep_stride_refs(
     func: &Function,
-    blocks: &HashSet<BlockId>,
+    blocks: &BTreeSet<BlockId>,
     var: VarId,
 ) -> usize {
     let mut count = 0;
@@ -299,7 +299,7 @@ fn swap_comparison_bound(func: &mut Function, header: BlockId, iv_var: VarId, ne
 }
 
 /// Change the init value of a phi node for an IV
-fn swap_phi_init(func: &mut Function, header: BlockId, iv_var: VarId, body: &HashSet<BlockId>, new_init: i64) {
+fn swap_phi_init(func: &mut Function, header: BlockId, iv_var: VarId, body: &BTreeSet<BlockId>, new_init: i64) {
     // First pass: find the variable and block to update
     let mut target: Option<(VarId, BlockId)> = None;
     if let Some(block) = func.blocks.iter().find(|b| b.id == header) {
@@ -339,7 +339,7 @@ fn update_constant_value(func: &mut Function, var: VarId, block_id: BlockId, new
 }
 
 /// Change the step value in a loop body's IV increment
-fn swap_step_value(func: &mut Function, body: &HashSet<BlockId>, iv_var: VarId, new_step: i64) {
+fn swap_step_value(func: &mut Function, body: &BTreeSet<BlockId>, iv_var: VarId, new_step: i64) {
     for block in &mut func.blocks {
         if !body.contains(&block.id) {
             continue;
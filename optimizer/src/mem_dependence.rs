@@ -345,6 +345,7 @@ pub fn check_memory_dependence(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use model::Span;
 
     fn load(base: VarId, scale: i64, off: i64, dest: VarId) -> MemAccess {
         MemAccess {
@@ -378,6 +379,8 @@ mod tests {
             is_static: false,
             label_addrs: vec![],
             labels: std::collections::HashMap::new(),
+            local_names: std::collections::HashMap::new(),
+            span: model::Span::default(),
         }
     }
 
@@ -462,6 +465,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(None),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         });
         let l = load(VarId(10), 1, 0, VarId(2));
         let s = store(VarId(11), 1, 0, VarId(2));
@@ -7,7 +7,7 @@
 
 use ir::{Function, Instruction, VarId, BlockId};
 use model::BinaryOp;
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use crate::loop_analysis::{self, NaturalLoop};
 
 /// Prepare function for vectorization: no IR change today, but validates nests.
@@ -163,7 +163,7 @@ fn index_uses_var(
     op: &ir::Operand,
     var: VarId,
     func: &Function,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     inner_iv: VarId,
 ) -> bool {
     match op {
@@ -197,9 +197,9 @@ fn index_uses_var(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ir::{BasicBlock, Operand, Terminator};
+    use ir::Operand;
 
-    fn make_loop(header: BlockId, body: HashSet<BlockId>, iv: VarId) -> NaturalLoop {
+    fn make_loop(header: BlockId, body: BTreeSet<BlockId>, iv: VarId) -> NaturalLoop {
         NaturalLoop {
             header,
             latch: *body.iter().next().unwrap(),
@@ -220,8 +220,8 @@ mod tests {
 
     #[test]
     fn innermost_loop_has_no_child() {
-        let outer_body: HashSet<_> = [BlockId(1), BlockId(2)].into_iter().collect();
-        let inner_body: HashSet<_> = [BlockId(2)].into_iter().collect();
+        let outer_body: BTreeSet<_> = [BlockId(1), BlockId(2)].into_iter().collect();
+        let inner_body: BTreeSet<_> = [BlockId(2)].into_iter().collect();
         let outer = make_loop(BlockId(1), outer_body, VarId(0));
         let inner = make_loop(BlockId(2), inner_body, VarId(1));
         let loops = [&outer, &inner];
@@ -231,8 +231,8 @@ mod tests {
 
     #[test]
     fn parent_loop_finds_container() {
-        let outer_body: HashSet<_> = [BlockId(1), BlockId(2)].into_iter().collect();
-        let inner_body: HashSet<_> = [BlockId(2)].into_iter().collect();
+        let outer_body: BTreeSet<_> = [BlockId(1), BlockId(2)].into_iter().collect();
+        let inner_body: BTreeSet<_> = [BlockId(2)].into_iter().collect();
         let outer = make_loop(BlockId(1), outer_body, VarId(0));
         let inner = make_loop(BlockId(2), inner_body, VarId(1));
         let loops = [&outer, &inner];
@@ -191,6 +191,7 @@ fn find_max_var_id(func: &Function) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use model::Span;
 
     fn compile_to_ir(src: &str) -> ir::IRProgram {
         let tokens = lexer::lex(src).unwrap();
@@ -258,6 +259,8 @@ mod tests {
             is_static: false,
             label_addrs: vec![],
             labels: HashMap::new(),
+            local_names: HashMap::new(),
+            span: Span::default(),
         }
     }
 
@@ -267,6 +270,8 @@ mod tests {
             instructions: vec![],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }
     }
 
@@ -293,6 +298,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 7);
     }
@@ -319,6 +326,7 @@ mod tests {
                 Instruction::Cast {
                     dest: VarId(3),
                     src: Operand::Var(VarId(1)),
+                    src_type: model::Type::Int,
                     r#type: model::Type::Long,
                 },
                 Instruction::Unary {
@@ -329,6 +337,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 10);
     }
@@ -342,6 +352,7 @@ mod tests {
                     dest: Some(VarId(15)),
                     name: "foo".to_string(),
                     args: vec![],
+                    vararg_types: vec![],
                 },
                 Instruction::InlineAsm {
                     template: "nop".to_string(),
@@ -355,6 +366,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 20);
     }
@@ -374,6 +387,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 25);
     }
@@ -392,6 +407,8 @@ mod tests {
             ],
             terminator: ir::Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         // Store has no dest → max stays 0
         assert_eq!(find_max_var_id(&func), 0);
@@ -409,6 +426,8 @@ mod tests {
                 instructions: vec![],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ir::BasicBlock {
                 id: BlockId(1),
@@ -428,6 +447,8 @@ mod tests {
                 ],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
@@ -470,6 +491,8 @@ mod tests {
                 ],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
@@ -510,6 +533,8 @@ mod tests {
                 ],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
@@ -539,6 +564,8 @@ mod tests {
                 instructions: vec![],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ir::BasicBlock {
                 id: BlockId(1),
@@ -557,6 +584,8 @@ mod tests {
                     BlockId(3),
                 ),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ir::BasicBlock {
                 id: BlockId(2),
@@ -582,6 +611,8 @@ mod tests {
                 ],
                 terminator: ir::Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ret_block(3),
         ]);
@@ -0,0 +1,94 @@
+// --print-changed support: per-pass IR diffs
+//
+// With change recording on, the pass manager snapshots each function's
+// textual IR (`ir::function_to_text`) before a pass and compares it after.
+// Only passes that changed the text are recorded, with a line diff, which is
+// the quickest way to find the pass that introduced a miscompile.
+
+/// One pass's effect on one function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassChange {
+    pub pass: String,
+    pub function: String,
+    /// Line diff of the function's IR: ` ` context, `-` removed, `+` added.
+    pub diff: String,
+}
+
+impl std::fmt::Display for PassChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "*** IR changed by {} on {} ***", self.pass, self.function)?;
+        write!(f, "{}", self.diff)
+    }
+}
+
+/// Lines of unchanged context kept around each change.
+const CONTEXT: usize = 2;
+
+/// Diff two texts line by line (longest common subsequence), keeping
+/// `CONTEXT` unchanged lines around each change. Hunks are separated by `...`.
+pub(crate) fn line_diff(before: &str, after: &str) -> String {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, (tag, _))| *tag != ' ').map(|(k, _)| k).collect();
+    let keep = |k: usize| changed.iter().any(|&c| c.abs_diff(k) <= CONTEXT);
+    let mut out = String::new();
+    let mut skipped = false;
+    for (k, (tag, line)) in lines.iter().enumerate() {
+        if !keep(k) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !out.is_empty() {
+            out.push_str("...\n");
+        }
+        skipped = false;
+        out.push(*tag);
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_keeps_context_around_changes() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let after = "a\nb\nc\nd\nE\nf\ng\nh\n";
+        assert_eq!(line_diff(before, after), " c\n d\n-e\n+E\n f\n g\n");
+    }
+
+    #[test]
+    fn diff_separates_distant_hunks() {
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let after = "0\n2\n3\n4\n5\n6\n7\n8\n";
+        assert_eq!(line_diff(before, after), "-1\n+0\n 2\n 3\n...\n 7\n 8\n-9\n");
+    }
+}
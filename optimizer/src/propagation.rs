@@ -1,66 +1,82 @@
-use ir::{Function, Instruction, Operand, VarId};
+use crate::dominators::DominatorTree;
+use ir::{BlockId, Function, Instruction, Operand, VarId};
 use std::collections::{HashMap, HashSet};
 
+/// Where an instruction sits: its block and index, with a block's
+/// terminator at index `instructions.len()`.
+type Position = (BlockId, usize);
+
 /// Copy propagation: replace uses of copies with their sources
 ///
 /// Finds all simple copy instructions (x = y) and replaces uses of x with y
-/// throughout the function. This simplifies the code and enables further optimizations.
+/// wherever the copy dominates the use. This simplifies the code and enables
+/// further optimizations.
 pub fn copy_propagation(func: &mut Function) {
     // Count definitions per variable to detect Phi-resolved copies
     // (after phi removal, a phi with N preds becomes N copies to the same dest).
     // Only propagate when there is exactly one definition — anything else means
     // the variable carries different values on different control-flow paths.
+    // The same holds for the source, which must not change between the copy
+    // and a use.
     let mut def_count: HashMap<VarId, usize> = HashMap::new();
     for block in &func.blocks {
         for inst in &block.instructions {
-            if let Instruction::Copy { dest, .. } = inst {
-                *def_count.entry(*dest).or_insert(0) += 1;
+            for dest in inst.dests() {
+                *def_count.entry(dest).or_insert(0) += 1;
             }
         }
     }
-
-    let mut copies: HashMap<VarId, Operand> = HashMap::new();
+    let stable = |op: &Operand| match op {
+        Operand::Var(v) => def_count.get(v).copied().unwrap_or(0) <= 1,
+        _ => true,
+    };
 
     // Collect copy instructions — only for singly-defined variables.
+    let mut copies: HashMap<VarId, (Operand, Position)> = HashMap::new();
     for block in &func.blocks {
-        for inst in &block.instructions {
+        for (index, inst) in block.instructions.iter().enumerate() {
             if let Instruction::Copy { dest, src } = inst {
-                if def_count.get(dest).copied().unwrap_or(0) == 1 {
-                    copies.insert(*dest, src.clone());
+                if def_count.get(dest).copied().unwrap_or(0) == 1 && stable(src) {
+                    copies.insert(*dest, (src.clone(), (block.id, index)));
                 }
             }
         }
     }
 
+    // A copy's value reaches a use when the copy comes first in the same
+    // block or its block strictly dominates the use's. Any other use is on
+    // a path that skips the copy, where substituting its source would
+    // change the value.
+    let tree = DominatorTree::new(func);
+    let reaches = |from: Position, to: Position| {
+        if from.0 == to.0 {
+            from.1 < to.1
+        } else {
+            tree.dominates(from.0, to.0)
+        }
+    };
+
     // Resolve transitive copies: if x=y and y=z, then x=z
-    // This is important for chains of copies that arise from SSA construction
-    // Resolve in-place by following chains without cloning the entire map
-    let keys: Vec<VarId> = copies.keys().copied().collect();
-    for start in keys {
-        let mut var = start;
-        // Follow the chain: var -> src -> src's src -> ...
-        loop {
-            if let Some(Operand::Var(src_var)) = copies.get(&var) {
-                let next = *src_var;
-                if next == start {
-                    break; // cycle
-                }
-                if copies.contains_key(&next) {
-                    var = next;
-                } else {
-                    break;
+    // This is important for chains of copies that arise from SSA construction.
+    // A link is followed only when y's copy reaches x's, so the chain is
+    // acyclic and z holds y's value wherever x's copy reaches.
+    let mut resolved: HashMap<VarId, (Operand, Position)> = HashMap::new();
+    for (&start, (src, position)) in &copies {
+        let mut src = src;
+        let mut at = *position;
+        let mut seen = HashSet::from([start]);
+        while let Operand::Var(next) = src {
+            match copies.get(next) {
+                Some((next_src, next_at)) if reaches(*next_at, at) && seen.insert(*next) => {
+                    src = next_src;
+                    at = *next_at;
                 }
-            } else {
-                break;
-            }
-        }
-        // If we followed a chain, update to the final target
-        if var != start {
-            if let Some(final_op) = copies.get(&var).cloned() {
-                copies.insert(start, final_op);
+                _ => break,
             }
         }
+        resolved.insert(start, (src.clone(), *position));
     }
+    let copies = resolved;
 
     // Track which variables are used after propagation
     let mut used_vars: HashSet<VarId> = HashSet::new();
@@ -76,7 +92,10 @@ pub fn copy_propagation(func: &mut Function) {
             }
         }
         
-        for inst in &mut block.instructions {
+        let block_id = block.id;
+        let end = block.instructions.len();
+        for (index, inst) in block.instructions.iter_mut().enumerate() {
+            let replace = |op: &mut Operand| replace_operand(op, &copies, (block_id, index), &reaches);
             match inst {
                 Instruction::Phi { preds, .. } => {
                     // Phi uses VarIds, not Operands — use replacement manually
@@ -88,13 +107,13 @@ pub fn copy_propagation(func: &mut Function) {
                     // Also propagate through the source of copy instructions and
                     // track the (possibly updated) source as used so that DCE
                     // doesn't remove its definition.
-                    replace_operand(src, &copies);
+                    replace(src);
                     collect_used_var(src, &mut used_vars);
                 }
                 _ => {
                     // Use accessor for all other instructions
                     inst.for_each_operand_mut(|op| {
-                        replace_operand(op, &copies);
+                        replace(op);
                         collect_used_var(op, &mut used_vars);
                     });
                 }
@@ -102,13 +121,14 @@ pub fn copy_propagation(func: &mut Function) {
         }
 
         // Also update terminators
+        let replace = |op: &mut Operand| replace_operand(op, &copies, (block_id, end), &reaches);
         match &mut block.terminator {
-            ir::Terminator::CondBr { cond, .. } => {
-                replace_operand(cond, &copies);
+            ir::Terminator::CondBr { cond, .. } | ir::Terminator::Switch { value: cond, .. } => {
+                replace(cond);
                 collect_used_var(cond, &mut used_vars);
             }
             ir::Terminator::Ret(Some(op)) => {
-                replace_operand(op, &copies);
+                replace(op);
                 collect_used_var(op, &mut used_vars);
             }
             _ => {}
@@ -128,10 +148,17 @@ pub fn copy_propagation(func: &mut Function) {
     }
 }
 
-fn replace_operand(op: &mut Operand, copies: &HashMap<VarId, Operand>) {
+fn replace_operand(
+    op: &mut Operand,
+    copies: &HashMap<VarId, (Operand, Position)>,
+    at: Position,
+    reaches: &impl Fn(Position, Position) -> bool,
+) {
     if let Operand::Var(v) = op {
-        if let Some(replacement) = copies.get(v) {
-            *op = replacement.clone();
+        if let Some((replacement, from)) = copies.get(v) {
+            if reaches(*from, at) {
+                *op = replacement.clone();
+            }
         }
     }
 }
@@ -141,3 +168,166 @@ fn collect_used_var(op: &Operand, used: &mut HashSet<VarId>) {
         used.insert(*v);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::{BasicBlock, Terminator};
+    use model::{BinaryOp, Span, Type};
+
+    fn block(id: usize, instructions: Vec<Instruction>, terminator: Terminator) -> BasicBlock {
+        BasicBlock {
+            id: BlockId(id),
+            instructions,
+            terminator,
+            is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
+        }
+    }
+
+    fn function(blocks: Vec<BasicBlock>) -> Function {
+        let (label_addrs, labels) = Function::default_meta();
+        Function {
+            name: "f".to_string(),
+            return_type: Type::Int,
+            params: vec![(Type::Int, VarId(0))],
+            blocks,
+            entry_block: BlockId(0),
+            var_types: HashMap::new(),
+            attributes: Vec::new(),
+            is_static: false,
+            label_addrs,
+            labels,
+            local_names: HashMap::new(),
+            span: model::Span::default(),
+        }
+    }
+
+    fn copy(dest: usize, src: Operand) -> Instruction {
+        Instruction::Copy { dest: VarId(dest), src }
+    }
+
+    fn add(dest: usize, left: Operand, right: Operand) -> Instruction {
+        Instruction::Binary { dest: VarId(dest), op: BinaryOp::Add, left, right }
+    }
+
+    fn var(id: usize) -> Operand {
+        Operand::Var(VarId(id))
+    }
+
+    // Instructions and terminators have no `PartialEq`
+    fn debug(value: &impl std::fmt::Debug) -> String {
+        format!("{:?}", value)
+    }
+
+    /// A diamond on the parameter v0, with `then` and `otherwise` as its
+    /// arms and `join` returning through block 3.
+    fn diamond(entry: Vec<Instruction>, then: Vec<Instruction>, otherwise: Vec<Instruction>, join: Vec<Instruction>, ret: Operand) -> Function {
+        function(vec![
+            block(0, entry, Terminator::cond_br(var(0), BlockId(1), BlockId(2))),
+            block(1, then, Terminator::Br(BlockId(3))),
+            block(2, otherwise, Terminator::Br(BlockId(3))),
+            block(3, join, Terminator::Ret(Some(ret))),
+        ])
+    }
+
+    #[test]
+    fn propagates_a_copy_into_the_blocks_it_dominates() {
+        let mut func = diamond(
+            vec![copy(1, var(0))],
+            vec![add(2, var(1), Operand::Constant(1))],
+            vec![add(3, var(1), Operand::Constant(2))],
+            vec![add(4, var(1), var(1))],
+            var(4),
+        );
+        copy_propagation(&mut func);
+        let operands: Vec<Operand> = func.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Binary { left, .. } => Some(left.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(operands, vec![var(0), var(0), var(0)]);
+        assert!(func.blocks[0].instructions.is_empty(), "dead copy should be removed");
+    }
+
+    #[test]
+    fn keeps_a_copy_on_one_arm_out_of_the_join() {
+        // v1 = 5 only on the then arm, so the join may see some other v1
+        let mut func = diamond(
+            Vec::new(),
+            vec![copy(1, Operand::Constant(5))],
+            Vec::new(),
+            vec![add(2, var(1), Operand::Constant(1))],
+            var(1),
+        );
+        copy_propagation(&mut func);
+        assert_eq!(debug(&func.blocks[1].instructions), debug(&vec![copy(1, Operand::Constant(5))]));
+        assert_eq!(debug(&func.blocks[3].instructions), debug(&vec![add(2, var(1), Operand::Constant(1))]));
+        assert_eq!(debug(&func.blocks[3].terminator), debug(&Terminator::Ret(Some(var(1)))));
+    }
+
+    #[test]
+    fn keeps_a_variable_also_defined_by_another_instruction() {
+        // One arm copies into v1, the other computes it
+        let mut func = diamond(
+            Vec::new(),
+            vec![copy(1, Operand::Constant(5))],
+            vec![add(1, var(0), Operand::Constant(1))],
+            Vec::new(),
+            var(1),
+        );
+        copy_propagation(&mut func);
+        assert_eq!(debug(&func.blocks[3].terminator), debug(&Terminator::Ret(Some(var(1)))));
+    }
+
+    #[test]
+    fn keeps_a_copy_of_a_variable_reassigned_in_a_loop() {
+        // After phi removal: i (v1) is assigned before the loop and at the
+        // end of its body, so `old = i` must not become i past the update
+        //   b0: i = 0                         → b1
+        //   b1: c = i < 10                    → b2 / b3
+        //   b2: old = i; n = i + 1; i = n; s = old + i → b1
+        //   b3: ret i
+        let mut func = function(vec![
+            block(0, vec![copy(1, Operand::Constant(0))], Terminator::Br(BlockId(1))),
+            block(
+                1,
+                vec![Instruction::Binary { dest: VarId(2), op: BinaryOp::Less, left: var(1), right: Operand::Constant(10) }],
+                Terminator::cond_br(var(2), BlockId(2), BlockId(3)),
+            ),
+            block(
+                2,
+                vec![
+                    copy(3, var(1)),
+                    add(4, var(1), Operand::Constant(1)),
+                    copy(1, var(4)),
+                    add(5, var(3), var(1)),
+                ],
+                Terminator::Br(BlockId(1)),
+            ),
+            block(3, Vec::new(), Terminator::Ret(Some(var(5)))),
+        ]);
+        copy_propagation(&mut func);
+        assert_eq!(debug(&func.blocks[2].instructions[0]), debug(&copy(3, var(1))));
+        assert_eq!(debug(&func.blocks[2].instructions[3]), debug(&add(5, var(3), var(1))));
+    }
+
+    #[test]
+    fn follows_copy_chains_only_down_the_dominator_tree() {
+        // v2 = v1 sits on an arm, v1 = v0 in the entry: the then arm may
+        // use v0 for v2, and the join keeps v2
+        let mut func = diamond(
+            vec![copy(1, var(0))],
+            vec![copy(2, var(1)), add(3, var(2), Operand::Constant(1))],
+            Vec::new(),
+            Vec::new(),
+            var(2),
+        );
+        copy_propagation(&mut func);
+        assert_eq!(debug(&func.blocks[1].instructions), debug(&vec![copy(2, var(0)), add(3, var(0), Operand::Constant(1))]));
+        assert_eq!(debug(&func.blocks[3].terminator), debug(&Terminator::Ret(Some(var(2)))));
+    }
+}
@@ -0,0 +1,304 @@
+// Pure Function Detection
+//
+// Finds the functions whose calls have no effect a caller can observe
+// besides their result, so GVN can merge two calls with the same arguments
+// and DCE can drop a call whose result is unused.
+//
+// A defined function is pure when it:
+// - stores only to its own locals: addresses derived from its allocas
+//   through GEPs, copies, casts, pointer adds, and phis
+// - makes no volatile access, inline asm, variadic access, or indirect call
+// - calls only pure functions
+// It reads memory when it loads from (or copies out of) anything other
+// than its locals, or calls a function that does; a function that does not
+// returns the same value for the same arguments wherever it is called. It
+// may loop when its CFG has a cycle, it is recursive, or it calls a
+// function that may loop. Such a call can still be merged with an earlier
+// one, which returned, but is never removed.
+//
+// Purity is found optimistically: every defined function starts pure, and
+// one calling a function that is not is demoted until nothing changes.
+// Library functions are pure only when listed below and not defined by the
+// program itself.
+
+use ir::{BlockId, Function, IRProgram, Instruction, Operand, VarId};
+use model::BinaryOp;
+use std::collections::{HashMap, HashSet};
+
+/// Library functions that read no memory and always return.
+const CONST_LIBRARY: &[&str] = &["abs", "labs", "llabs"];
+
+/// Library functions that only read memory and always return.
+const PURE_LIBRARY: &[&str] = &["strlen", "strcmp", "strncmp", "memcmp", "strchr", "strrchr"];
+
+/// What a call to a pure function may do besides computing its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PureFunction {
+    /// Reads memory the caller can write, so its result depends on the
+    /// state of memory at the call
+    pub reads_memory: bool,
+    /// May never return
+    pub may_loop: bool,
+}
+
+/// The pure functions of a program, by name; see `analyze`.
+#[derive(Debug, Clone, Default)]
+pub struct PureFunctions(HashMap<String, PureFunction>);
+
+impl PureFunctions {
+    /// Find the pure functions of `program`.
+    pub fn analyze(program: &IRProgram) -> Self {
+        let graph = ir::callgraph::build(program);
+        let mut pure: HashMap<String, PureFunction> = HashMap::new();
+        let mut calls: HashMap<&str, Vec<&str>> = HashMap::new();
+        for func in &program.functions {
+            if let Some((summary, callees)) = local_summary(func) {
+                let may_loop = summary.may_loop || graph.is_recursive(&func.name);
+                pure.insert(func.name.clone(), PureFunction { may_loop, ..summary });
+                calls.insert(&func.name, callees);
+            }
+        }
+        let library = |name: &str| {
+            if graph.is_defined(name) {
+                None
+            } else if CONST_LIBRARY.contains(&name) {
+                Some(PureFunction::default())
+            } else if PURE_LIBRARY.contains(&name) {
+                Some(PureFunction { reads_memory: true, may_loop: false })
+            } else {
+                None
+            }
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (&caller, callees) in &calls {
+                let Some(&before) = pure.get(caller) else { continue };
+                let mut after = Some(before);
+                for &callee in callees {
+                    let effect = pure.get(callee).copied().or_else(|| library(callee));
+                    after = match (after, effect) {
+                        (Some(a), Some(c)) => Some(PureFunction {
+                            reads_memory: a.reads_memory || c.reads_memory,
+                            may_loop: a.may_loop || c.may_loop,
+                        }),
+                        _ => None,
+                    };
+                }
+                if after != Some(before) {
+                    match after {
+                        Some(after) => pure.insert(caller.to_string(), after),
+                        None => pure.remove(caller),
+                    };
+                    changed = true;
+                }
+            }
+        }
+
+        for func in &program.functions {
+            for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+                if let Instruction::Call { name, .. } = inst {
+                    if let Some(effect) = library(name) {
+                        pure.insert(name.clone(), effect);
+                    }
+                }
+            }
+        }
+        PureFunctions(pure)
+    }
+
+    /// How calls to `name` behave, if it is pure.
+    pub fn get(&self, name: &str) -> Option<PureFunction> {
+        self.0.get(name).copied()
+    }
+
+    /// Whether a call to `name` whose result is unused can be removed.
+    pub fn is_removable(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|f| !f.may_loop)
+    }
+
+    /// Whether `inst` may write memory a pure function could read.
+    pub fn may_write_memory(&self, inst: &Instruction) -> bool {
+        match inst {
+            Instruction::Call { name, .. } => self.get(name).is_none(),
+            Instruction::Load { volatile, .. } => *volatile,
+            Instruction::Simd { dest, .. } => dest.is_none(),
+            _ => inst.has_side_effects() && !matches!(inst, Instruction::Alloca { .. }),
+        }
+    }
+}
+
+/// The purity of `func` judged by its own body, and the functions it calls;
+/// `None` when the body alone makes it impure.
+fn local_summary(func: &Function) -> Option<(PureFunction, Vec<&str>)> {
+    if func.has_indirect_branch() {
+        return None;
+    }
+    let locals = local_addresses(func);
+    let is_local = |op: &Operand| matches!(op, Operand::Var(v) if locals.contains(v));
+    let mut summary = PureFunction { reads_memory: false, may_loop: has_cycle(func) };
+    let mut callees = Vec::new();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        match inst {
+            Instruction::Store { addr, volatile: false, .. } | Instruction::Memset { dest: addr, .. }
+                if is_local(addr) => {}
+            Instruction::Memcpy { dest, src, .. } if is_local(dest) => summary.reads_memory |= !is_local(src),
+            Instruction::Load { addr, volatile: false, .. } => summary.reads_memory |= !is_local(addr),
+            Instruction::Call { name, .. } => callees.push(name.as_str()),
+            Instruction::Store { .. }
+            | Instruction::Memset { .. }
+            | Instruction::Memcpy { .. }
+            | Instruction::Load { .. }
+            | Instruction::IndirectCall { .. }
+            | Instruction::InlineAsm { .. }
+            | Instruction::VaStart { .. }
+            | Instruction::VaEnd { .. }
+            | Instruction::VaCopy { .. }
+            | Instruction::VaArg { .. }
+            | Instruction::Simd { .. } => return None,
+            _ => {}
+        }
+    }
+    Some((summary, callees))
+}
+
+/// Variables holding the address of one of `func`'s own allocas.
+fn local_addresses(func: &Function) -> HashSet<VarId> {
+    let mut locals = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            let is_local = |op: &Operand| matches!(op, Operand::Var(v) if locals.contains(v));
+            let local = match inst {
+                Instruction::Alloca { .. } => true,
+                Instruction::GetElementPtr { base: src, .. }
+                | Instruction::Copy { src, .. }
+                | Instruction::Cast { src, .. } => is_local(src),
+                Instruction::Binary { op: BinaryOp::Add | BinaryOp::Sub, left, right, .. } => {
+                    is_local(left) && !is_local(right) || is_local(right) && !is_local(left)
+                }
+                Instruction::Phi { preds, .. } => !preds.is_empty() && preds.iter().all(|(_, v)| locals.contains(v)),
+                _ => false,
+            };
+            if local {
+                if let Some(dest) = inst.dest() {
+                    changed |= locals.insert(dest);
+                }
+            }
+        }
+    }
+    locals
+}
+
+/// Whether the blocks reachable from the entry form a cycle.
+fn has_cycle(func: &Function) -> bool {
+    let succs = func.compute_successors();
+    // Blocks on the current DFS path, and blocks already finished
+    let mut on_path: HashSet<BlockId> = HashSet::new();
+    let mut done: HashSet<BlockId> = HashSet::new();
+    let mut stack: Vec<(BlockId, usize)> = vec![(func.entry_block, 0)];
+    on_path.insert(func.entry_block);
+    while let Some((block, next)) = stack.last_mut() {
+        let targets = succs.get(block).map(Vec::as_slice).unwrap_or(&[]);
+        match targets.get(*next) {
+            Some(&target) => {
+                *next += 1;
+                if on_path.contains(&target) {
+                    return true;
+                }
+                if !done.contains(&target) {
+                    on_path.insert(target);
+                    stack.push((target, 0));
+                }
+            }
+            None => {
+                on_path.remove(block);
+                done.insert(*block);
+                stack.pop();
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    #[test]
+    fn test_purity_classifies_functions() {
+        let src = r#"
+            int g;
+            int sq(int x) { int t[2]; t[0] = x; return t[0] * x; }
+            int sum(int *a, int n) { int s = 0; for (int i = 0; i < n; i++) s += a[i]; return s; }
+            int twice(int x) { return sq(x) + sq(x); }
+            int fact(int n) { return n <= 1 ? 1 : n * fact(n - 1); }
+            int len(char *s) { return strlen(s); }
+            int set(int x) { g = x; return x; }
+            int fill(int *a) { a[0] = 1; return 0; }
+            int indirect(int (*f)(int)) { return f(1); }
+            int calls_set(int x) { return set(x) + 1; }
+        "#;
+        let pure = PureFunctions::analyze(&compile_to_ir(src));
+        let pf = |reads_memory, may_loop| Some(PureFunction { reads_memory, may_loop });
+        assert_eq!(pure.get("sq"), pf(false, false));
+        assert_eq!(pure.get("sum"), pf(true, true));
+        assert_eq!(pure.get("twice"), pf(false, false));
+        assert_eq!(pure.get("fact"), pf(false, true));
+        assert_eq!(pure.get("len"), pf(true, false));
+        assert_eq!(pure.get("strlen"), pf(true, false));
+        for impure in ["set", "fill", "indirect", "calls_set"] {
+            assert_eq!(pure.get(impure), None, "{}", impure);
+        }
+        assert!(pure.is_removable("twice"));
+        assert!(!pure.is_removable("fact"));
+    }
+
+    #[test]
+    fn test_pure_calls_are_merged_and_removed() {
+        // sq(3) is computed once and sq(10) dropped; readg(1) is reused
+        // until g is stored; spin may loop, so its calls are merged but the
+        // first one stays
+        let src = r#"
+            int g = 5;
+            int sq(int x) { return x * x + 1; }
+            int readg(int x) { return g + x; }
+            int spin(int n) { int i = 0; while (i < n) i++; return i; }
+            int main() {
+                int a = sq(3) + sq(3);
+                int b = readg(1) + readg(1);
+                g = 7;
+                int c = readg(1);
+                sq(10);
+                spin(4);
+                int d = spin(4);
+                return a + b + c + d;
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let pure = PureFunctions::analyze(&prog);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            crate::gvn::global_value_numbering(func, &pure);
+            while crate::dce::dce_function(func, &pure) {}
+        }
+        let main = prog.functions.iter().find(|f| f.name == "main").unwrap();
+        let calls: Vec<&str> = main.blocks.iter().flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Call { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, ["sq", "readg", "readg", "spin"]);
+        assert_eq!(ir::interpret(&prog, "pure", &[]).unwrap(), 44);
+    }
+}
@@ -0,0 +1,383 @@
+// Reassociation
+//
+// Rewrites a tree of one associative, commutative integer operator into a
+// canonical left-leaning chain: the variable operands in rank order, then
+// a single constant. `(a + 1) + 2` becomes `a + 3`, `x * 2 * 4` becomes
+// `x * 8`, and `(b + 1) + a` and `(a + b) + 1` both become `(a + b) + 1`,
+// which GVN then sees as one value.
+//
+// Algorithm:
+// 1. Count the uses of every variable. An interior node of a tree is a
+//    binary operation of the same family (`+`/`-`, `*`, `&`, `|`, `^`) and
+//    type as its user, used only by it, in the same block
+// 2. For each root, collect the leaves of its tree. Subtraction is
+//    addition of a negated leaf. Constants are combined; a combination that
+//    overflows, or does not fit an `int` immediate, leaves the tree alone
+// 3. Order the variable leaves (globals, then variables by id, the order
+//    they were defined in) and rebuild the chain at the root. The interior
+//    nodes are left for DCE
+//
+// Only integer `Binary` instructions are reassociated. Float arithmetic is
+// a separate instruction and is never touched, since rounding makes it
+// non-associative; pointer arithmetic is left alone too. The pass needs
+// SSA form, so a function with a variable defined twice is skipped.
+
+use crate::utils::is_ssa;
+use ir::{Function, Instruction, Operand, Terminator, VarId};
+use model::{BinaryOp, Type, TypeEnv};
+use std::collections::{HashMap, HashSet};
+
+/// A leaf of an additive tree is added or subtracted; leaves of the other
+/// families are never negated.
+#[derive(Debug, Clone, PartialEq)]
+struct Leaf {
+    operand: Operand,
+    negated: bool,
+}
+
+/// A rebuilt tree: the first operand, then each step applied to it in turn.
+#[derive(Debug, PartialEq)]
+struct Chain {
+    first: Operand,
+    steps: Vec<(BinaryOp, Operand)>,
+}
+
+/// Run reassociation on a single function.
+pub fn reassociate(func: &mut Function) {
+    if !is_ssa(func) {
+        return;
+    }
+    let mut uses: HashMap<VarId, usize> = HashMap::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            inst.for_each_use(|v| *uses.entry(v).or_insert(0) += 1);
+        }
+        match &block.terminator {
+            Terminator::CondBr { cond: op, .. } | Terminator::Ret(Some(op)) | Terminator::IndirectBr { target: op }
+            | Terminator::Switch { value: op, .. } => {
+                if let Operand::Var(v) = op {
+                    *uses.entry(*v).or_insert(0) += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut next_var = func.blocks.iter().flat_map(|b| &b.instructions).flat_map(|i| i.dests()).map(|d| d.0 + 1).max().unwrap_or(0);
+    next_var = next_var.max(func.params.iter().map(|(_, v)| v.0 + 1).max().unwrap_or(0));
+
+    for block_index in 0..func.blocks.len() {
+        let block = &func.blocks[block_index];
+        // Integer binary operations of this block, by destination
+        let defs: HashMap<VarId, (BinaryOp, Operand, Operand)> = block
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Binary { dest, op, left, right } if family(op).is_some() => {
+                    Some((*dest, (op.clone(), left.clone(), right.clone())))
+                }
+                _ => None,
+            })
+            .collect();
+        let tree = Tree { defs: &defs, uses: &uses, var_types: &func.var_types };
+        let interior: HashSet<VarId> = defs
+            .iter()
+            .flat_map(|(dest, (op, left, right))| {
+                [left, right].into_iter().filter_map(move |operand| match operand {
+                    Operand::Var(v) if tree.joins(*v, *dest, op) => Some(*v),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        let mut rewrites: HashMap<VarId, Chain> = HashMap::new();
+        for (dest, (op, left, right)) in &defs {
+            if interior.contains(dest) {
+                continue;
+            }
+            if !func.var_types.get(dest).is_some_and(TypeEnv::is_integer_type) {
+                continue;
+            }
+            let mut leaves = Vec::new();
+            tree.collect(*dest, op, left, false, &mut leaves);
+            tree.collect(*dest, op, right, *op == BinaryOp::Sub, &mut leaves);
+            if leaves.len() < 3 {
+                // A single operation is already as simple as it gets
+                continue;
+            }
+            let Some(chain) = canonical_chain(family(op).unwrap(), leaves) else { continue };
+            if tree.current_chain(*dest).as_ref() != Some(&chain) {
+                rewrites.insert(*dest, chain);
+            }
+        }
+        if rewrites.is_empty() {
+            continue;
+        }
+
+        let ty_of = |dest: &VarId| func.var_types.get(dest).cloned();
+        let mut new_types: Vec<(VarId, Type)> = Vec::new();
+        let block = &mut func.blocks[block_index];
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+        for inst in block.instructions.drain(..) {
+            let Some(chain) = inst.dest().and_then(|dest| rewrites.remove(&dest)) else {
+                instructions.push(inst);
+                continue;
+            };
+            let dest = inst.dest().unwrap();
+            let ty = ty_of(&dest);
+            let mut acc = chain.first;
+            let count = chain.steps.len();
+            if count == 0 {
+                instructions.push(Instruction::Copy { dest, src: acc });
+                continue;
+            }
+            for (i, (op, operand)) in chain.steps.into_iter().enumerate() {
+                let step_dest = if i + 1 == count {
+                    dest
+                } else {
+                    let v = VarId(next_var);
+                    next_var += 1;
+                    if let Some(ty) = &ty {
+                        new_types.push((v, ty.clone()));
+                    }
+                    v
+                };
+                instructions.push(Instruction::Binary { dest: step_dest, op, left: acc, right: operand });
+                acc = Operand::Var(step_dest);
+            }
+        }
+        block.instructions = instructions;
+        func.var_types.extend(new_types);
+    }
+}
+
+/// The operator family `op` belongs to: the operator chains are built from.
+fn family(op: &BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::Add | BinaryOp::Sub => Some(BinaryOp::Add),
+        BinaryOp::Mul | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor => Some(op.clone()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Tree<'a> {
+    defs: &'a HashMap<VarId, (BinaryOp, Operand, Operand)>,
+    uses: &'a HashMap<VarId, usize>,
+    var_types: &'a HashMap<VarId, Type>,
+}
+
+impl Tree<'_> {
+    /// Whether `var` is an interior node of the tree that `user`, an
+    /// operation `op`, belongs to.
+    fn joins(&self, var: VarId, user: VarId, op: &BinaryOp) -> bool {
+        let Some((var_op, ..)) = self.defs.get(&var) else { return false };
+        family(var_op) == family(op)
+            && self.uses.get(&var) == Some(&1)
+            && self.var_types.get(&var).is_some()
+            && self.var_types.get(&var) == self.var_types.get(&user)
+    }
+
+    /// Append the leaves under `operand`, an operand of `user`. `negated`
+    /// is whether the operand is subtracted.
+    fn collect(&self, user: VarId, op: &BinaryOp, operand: &Operand, negated: bool, leaves: &mut Vec<Leaf>) {
+        if let Operand::Var(v) = operand {
+            if self.joins(*v, user, op) {
+                let (inner, left, right) = &self.defs[v];
+                self.collect(*v, inner, left, negated, leaves);
+                self.collect(*v, inner, right, negated != (*inner == BinaryOp::Sub), leaves);
+                return;
+            }
+        }
+        leaves.push(Leaf { operand: operand.clone(), negated });
+    }
+
+    /// The tree under `root` as a chain, if it already is one: every
+    /// interior node is the left operand of its user.
+    fn current_chain(&self, root: VarId) -> Option<Chain> {
+        let (op, left, right) = &self.defs[&root];
+        if matches!(right, Operand::Var(v) if self.joins(*v, root, op)) {
+            return None;
+        }
+        let mut chain = match left {
+            Operand::Var(v) if self.joins(*v, root, op) => self.current_chain(*v)?,
+            _ => Chain { first: left.clone(), steps: Vec::new() },
+        };
+        chain.steps.push((op.clone(), right.clone()));
+        Some(chain)
+    }
+}
+
+/// Build the canonical chain for the leaves of a tree of `family`, or
+/// `None` when its constants cannot be combined safely.
+fn canonical_chain(family: BinaryOp, leaves: Vec<Leaf>) -> Option<Chain> {
+    let mut constant: Option<i64> = None;
+    let mut vars: Vec<Leaf> = Vec::new();
+    for leaf in leaves {
+        let Operand::Constant(c) = leaf.operand else {
+            if matches!(leaf.operand, Operand::FloatConstant(_)) {
+                return None;
+            }
+            vars.push(leaf);
+            continue;
+        };
+        let c = if leaf.negated { c.checked_neg()? } else { c };
+        constant = Some(match constant {
+            None => c,
+            Some(acc) => match family {
+                BinaryOp::Add => acc.checked_add(c)?,
+                BinaryOp::Mul => acc.checked_mul(c)?,
+                BinaryOp::BitwiseAnd => acc & c,
+                BinaryOp::BitwiseOr => acc | c,
+                _ => acc ^ c,
+            },
+        });
+    }
+    if constant.is_some_and(|c| i32::try_from(c).is_err()) {
+        return None;
+    }
+    vars.sort_by(|a, b| (a.negated, rank(&a.operand)).cmp(&(b.negated, rank(&b.operand))));
+
+    let identity = match family {
+        BinaryOp::Mul => 1,
+        BinaryOp::BitwiseAnd => -1,
+        _ => 0,
+    };
+    let absorbing = match family {
+        BinaryOp::Mul | BinaryOp::BitwiseAnd => Some(0),
+        BinaryOp::BitwiseOr => Some(-1),
+        _ => None,
+    };
+    if let Some(c) = constant.filter(|c| Some(*c) == absorbing) {
+        return Some(Chain { first: Operand::Constant(c), steps: Vec::new() });
+    }
+    let constant = constant.filter(|c| *c != identity);
+
+    let mut vars = vars.into_iter();
+    let first = match vars.next() {
+        Some(leaf) if !leaf.negated => leaf.operand,
+        Some(leaf) => {
+            let first = Operand::Constant(constant.unwrap_or(0));
+            let steps = std::iter::once(leaf).chain(vars).map(|leaf| (BinaryOp::Sub, leaf.operand)).collect();
+            return Some(Chain { first, steps });
+        }
+        None => return Some(Chain { first: Operand::Constant(constant.unwrap_or(identity)), steps: Vec::new() }),
+    };
+    let mut steps: Vec<(BinaryOp, Operand)> = vars
+        .map(|leaf| {
+            let op = if leaf.negated { BinaryOp::Sub } else { family.clone() };
+            (op, leaf.operand)
+        })
+        .collect();
+    if let Some(c) = constant {
+        steps.push(match family {
+            BinaryOp::Add if c < 0 && c != i32::MIN as i64 => (BinaryOp::Sub, Operand::Constant(-c)),
+            _ => (family, Operand::Constant(c)),
+        });
+    }
+    Some(Chain { first, steps })
+}
+
+/// Leaf order: globals by name, then variables in definition order.
+fn rank(op: &Operand) -> (u8, usize, &str) {
+    match op {
+        Operand::Global(name) => (0, 0, name),
+        Operand::Var(v) => (1, v.0, ""),
+        _ => (2, 0, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    /// Promote, reassociate, and clean up every function, returning the
+    /// binary operations left in the function named `name`.
+    fn binary_ops(prog: &mut ir::IRProgram, name: &str, gvn: bool) -> Vec<(BinaryOp, Operand, Operand)> {
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            crate::propagation::copy_propagation(func);
+            reassociate(func);
+            if gvn {
+                crate::gvn::global_value_numbering(func, &crate::purity::PureFunctions::default());
+            }
+            while crate::dce::dce_function(func, &crate::purity::PureFunctions::default()) {}
+            assert!(ir::verify_ssa(func).is_ok(), "{}", ir::verify_ssa(func).unwrap_err());
+        }
+        let func = prog.functions.iter().find(|f| f.name == name).unwrap();
+        func.blocks
+            .iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Binary { op, left, right, .. } => Some((op.clone(), left.clone(), right.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_constants_in_a_chain_are_combined() {
+        let src = r#"
+            int f(int a) { return (a + 1) + 2 - 5; }
+            int g(int x) { return x * 2 * 4; }
+            int h(int a, int b) { return a - (b - 3) - 1; }
+            int main() { return f(10) + g(3) + h(9, 4); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let f = binary_ops(&mut prog, "f", false);
+        assert_eq!(f.len(), 1, "{:?}", f);
+        assert_eq!((&f[0].0, &f[0].2), (&BinaryOp::Sub, &Operand::Constant(2)));
+        let g = binary_ops(&mut prog, "g", false);
+        assert_eq!(g.len(), 1, "{:?}", g);
+        assert_eq!((&g[0].0, &g[0].2), (&BinaryOp::Mul, &Operand::Constant(8)));
+        let h = binary_ops(&mut prog, "h", false);
+        assert_eq!(h.len(), 2, "{:?}", h);
+        assert_eq!(ir::interpret(&prog, "reassociate", &[]).unwrap(), 8 + 24 + 7);
+    }
+
+    #[test]
+    fn test_reordered_sums_meet_in_gvn() {
+        // Both become (a + b) + 1, which GVN then computes once
+        let src = r#"
+            int f(int a, int b) {
+                int x = (b + 1) + a;
+                int y = (a + b) + 1;
+                return x * y;
+            }
+            int main() { return f(2, 3); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let ops = binary_ops(&mut prog, "f", true);
+        assert_eq!(ops.iter().filter(|(op, ..)| *op == BinaryOp::Add).count(), 2, "{:?}", ops);
+        assert_eq!(ir::interpret(&prog, "reassociate", &[]).unwrap(), 36);
+    }
+
+    #[test]
+    fn test_shared_overflowing_and_float_chains_are_kept() {
+        // `t` has two uses, the constants would overflow `int`, and float
+        // arithmetic is not associative
+        let src = r#"
+            int f(int a) { int t = a + 1; return t * (t + 2); }
+            int g(int a) { return (a + 2147483647) + 1; }
+            double h(double d) { return (d + 1.0) + 2.0; }
+            int main() { return f(1) + g(-2147483647) + (int)h(0.5); }
+        "#;
+        let mut prog = compile_to_ir(src);
+        let f = binary_ops(&mut prog, "f", false);
+        assert_eq!(f.iter().filter(|(op, ..)| *op == BinaryOp::Add).count(), 2, "{:?}", f);
+        let g = binary_ops(&mut prog, "g", false);
+        assert_eq!(g.len(), 2, "{:?}", g);
+        let h = &prog.functions.iter().find(|f| f.name == "h").unwrap();
+        let float_adds = h.blocks.iter().flat_map(|b| &b.instructions)
+            .filter(|inst| matches!(inst, Instruction::FloatBinary { op: BinaryOp::Add, .. }))
+            .count();
+        assert_eq!(float_adds, 2);
+        assert_eq!(ir::interpret(&prog, "reassociate", &[]).unwrap(), 8 + 1 + 3);
+    }
+}
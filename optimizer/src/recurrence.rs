@@ -10,7 +10,7 @@
 use ir::{
     BasicBlock, BlockId, BranchHint, Function, Instruction, Operand, Terminator, VarId,
 };
-use model::{BinaryOp, Type};
+use model::{BinaryOp, Span, Type};
 use std::collections::HashSet;
 
 fn max_var_id(func: &Function) -> usize {
@@ -159,6 +159,7 @@ fn self_calls_in_block<'a>(
                 name: n,
                 args,
                 dest: Some(_),
+                ..
             } = inst
             else {
                 return None;
@@ -443,18 +444,24 @@ fn rewrite_sum_recurrence(func: &mut Function, pattern: &SumRecurrence) {
                 hint: BranchHint::None,
             },
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
         BasicBlock {
             id: base,
             instructions: vec![],
             terminator: Terminator::Ret(Some(base_ret)),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
         BasicBlock {
             id: init,
             instructions: init_insts,
             terminator: Terminator::Br(loop_cond),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
         BasicBlock {
             id: loop_cond,
@@ -471,18 +478,24 @@ fn rewrite_sum_recurrence(func: &mut Function, pattern: &SumRecurrence) {
                 hint: BranchHint::None,
             },
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
         BasicBlock {
             id: loop_body,
             instructions: loop_body_insts,
             terminator: Terminator::Br(loop_cond),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
         BasicBlock {
             id: done,
             instructions: vec![],
             terminator: Terminator::Ret(Some(Operand::Var(slots[0]))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         },
     ];
     func.entry_block = entry;
@@ -26,7 +26,7 @@ fn try_slp_copy_cluster(func: &mut Function, block_id: BlockId, vf: usize) {
     let Some(block) = func.blocks.iter().find(|b| b.id == block_id) else {
         return;
     };
-    if matches!(block.terminator, ir::Terminator::CondBr { .. }) {
+    if matches!(block.terminator, ir::Terminator::CondBr { .. } | ir::Terminator::Switch { .. }) {
         return;
     }
 
@@ -174,6 +174,7 @@ fn try_slp_copy_cluster(func: &mut Function, block_id: BlockId, vf: usize) {
 mod tests {
     use super::*;
     use ir::{BasicBlock, BlockId, Terminator};
+    use model::Span;
 
     #[test]
     fn slp_replaces_four_scalar_copies() {
@@ -284,12 +285,16 @@ mod tests {
                 ],
                 terminator: Terminator::Ret(Some(Operand::Constant(0))),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             }],
             var_types: std::collections::HashMap::new(),
             attributes: vec![],
             is_static: false,
             label_addrs: vec![],
             labels: std::collections::HashMap::new(),
+            local_names: std::collections::HashMap::new(),
+            span: model::Span::default(),
         };
 
         slp_vectorize_function(&mut func, 4);
@@ -7,10 +7,13 @@
 // Algorithm:
 // 1. Find all allocas of aggregate types (Struct, Union, Array)
 // 2. Verify all uses of each alloca are GEPs with constant byte offsets
-// 3. For each unique (alloca, offset), create a new scalar alloca
-// 4. Replace all uses of GEP dest vars with the new scalar alloca vars
-// 5. Remove GEP instructions and original alloca
+// 3. Verify accesses that overlap (union members, type punning) are the
+//    same load or store type at the same offset
+// 4. For each unique (alloca, offset), create a new scalar alloca
+// 5. Replace all uses of GEP dest vars with the new scalar alloca vars
+// 6. Remove GEP instructions and original alloca
 
+use crate::utils::scalar_size;
 use ir::{Function, Instruction, Operand, VarId};
 use model::Type;
 use std::collections::{HashMap, HashSet};
@@ -54,11 +57,22 @@ pub fn scalar_replacement_of_aggregates(func: &mut Function) {
                         continue; // Already resolved
                     }
                     if let Operand::Constant(offset) = index {
-                        let byte_offset = if *element_type == Type::Char || *element_type == Type::UnsignedChar {
-                            *offset
-                        } else {
-                            let stride = type_size(element_type);
-                            *offset * stride as i64
+                        // The size of a struct or union element is unknown
+                        // here, so only its first element has a known offset
+                        let byte_offset = match (*offset, scalar_size(element_type)) {
+                            (0, _) => 0,
+                            (offset, Some(stride)) => offset * stride as i64,
+                            (_, None) => {
+                                let root = if alloca_set.contains(base_var) {
+                                    Some(*base_var)
+                                } else {
+                                    gep_to_field.get(base_var).map(|(root, _)| *root)
+                                };
+                                if let Some(root) = root {
+                                    alloca_uses_ok.insert(root, false);
+                                }
+                                continue;
+                            }
                         };
 
                         if alloca_set.contains(base_var) {
@@ -170,35 +184,37 @@ pub fn scalar_replacement_of_aggregates(func: &mut Function) {
 
     for block in &func.blocks {
         for inst in &block.instructions {
-            match inst {
-                Instruction::Load { addr: Operand::Var(addr_var), value_type, .. } => {
-                    if let Some((alloca_var, offset)) = gep_to_field.get(addr_var) {
-                        // If any Load/Store at this offset uses an aggregate type,
-                        // disqualify the alloca (we can't represent sub-struct
-                        // access after breaking into scalar allocas)
-                        if is_aggregate_type(value_type) {
-                            disqualified_allocas.insert(*alloca_var);
-                        } else {
-                            field_types.entry((*alloca_var, *offset))
-                                .or_insert_with(|| value_type.clone());
-                        }
-                    }
+            let (addr_var, value_type) = match inst {
+                Instruction::Load { addr: Operand::Var(addr_var), value_type, .. }
+                | Instruction::Store { addr: Operand::Var(addr_var), value_type, .. } => (addr_var, value_type),
+                _ => continue,
+            };
+            if let Some((alloca_var, offset)) = gep_to_field.get(addr_var) {
+                // If any Load/Store at this offset uses an aggregate type,
+                // disqualify the alloca (we can't represent sub-struct
+                // access after breaking into scalar allocas). Neither can
+                // one field be read as another type, as through a union.
+                let previous = field_types.entry((*alloca_var, *offset)).or_insert_with(|| value_type.clone());
+                if is_aggregate_type(value_type) || previous != value_type {
+                    disqualified_allocas.insert(*alloca_var);
                 }
-                Instruction::Store { addr: Operand::Var(addr_var), value_type, .. } => {
-                    if let Some((alloca_var, offset)) = gep_to_field.get(addr_var) {
-                        if is_aggregate_type(value_type) {
-                            disqualified_allocas.insert(*alloca_var);
-                        } else {
-                            field_types.entry((*alloca_var, *offset))
-                                .or_insert_with(|| value_type.clone());
-                        }
-                    }
-                }
-                _ => {}
             }
         }
     }
 
+    // Fields that overlap without starting at the same offset, such as
+    // `char b[4]` and `int i` in one union, are not separate scalars either
+    let mut sorted_fields: Vec<_> = field_types.iter().collect();
+    sorted_fields.sort_by_key(|(key, _)| **key);
+    for pair in sorted_fields.windows(2) {
+        let (((alloca, offset), ty), ((next_alloca, next_offset), _)) = (pair[0], pair[1]);
+        let size = scalar_size(ty).unwrap_or(1) as i64;
+        if alloca == next_alloca && offset + size > *next_offset {
+            disqualified_allocas.insert(*alloca);
+        }
+    }
+    field_types.retain(|(alloca, _), _| !disqualified_allocas.contains(alloca));
+
     // Remove allocas disqualified during field type collection
     if !disqualified_allocas.is_empty() {
         gep_to_field.retain(|_, (alloca_var, _)| !disqualified_allocas.contains(alloca_var));
@@ -226,7 +242,11 @@ pub fn scalar_replacement_of_aggregates(func: &mut Function) {
     let mut field_alloca: HashMap<(VarId, i64), VarId> = HashMap::new();
     let mut new_allocas: Vec<Instruction> = Vec::new();
 
-    for ((alloca_var, offset), field_type) in &field_types {
+    // Number the new allocas in (alloca, offset) order, not hash order, so
+    // the output is the same on every run
+    let mut fields: Vec<_> = field_types.iter().collect();
+    fields.sort_by_key(|(key, _)| **key);
+    for ((alloca_var, offset), field_type) in fields {
         let new_var = VarId(next_var);
         next_var += 1;
         field_alloca.insert((*alloca_var, *offset), new_var);
@@ -305,17 +325,6 @@ fn is_aggregate_type(ty: &Type) -> bool {
     matches!(ty, Type::Struct(_) | Type::Union(_) | Type::Array(..))
 }
 
-fn type_size(ty: &Type) -> usize {
-    match ty {
-        Type::Char | Type::UnsignedChar | Type::Bool => 1,
-        Type::Short | Type::UnsignedShort => 2,
-        Type::Int | Type::UnsignedInt | Type::Float | Type::Enum(_) => 4,
-        Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong
-        | Type::Double | Type::Pointer(..) | Type::FunctionPointer { .. } => 8,
-        _ => 1,
-    }
-}
-
 /// Check if an instruction directly uses an alloca var (not through GEP).
 /// If so, mark the alloca as ineligible.
 fn check_direct_use(inst: &Instruction, alloca_set: &HashSet<VarId>, ok_map: &mut HashMap<VarId, bool>) {
@@ -419,6 +428,14 @@ fn substitute_vars_in_instruction(inst: &mut Instruction, subst: &HashMap<VarId,
             substitute_vars_in_operand(dest, subst);
             substitute_vars_in_operand(src, subst);
         }
+        Instruction::Memcpy { dest, src, .. } => {
+            substitute_vars_in_operand(dest, subst);
+            substitute_vars_in_operand(src, subst);
+        }
+        Instruction::Memset { dest, value, .. } => {
+            substitute_vars_in_operand(dest, subst);
+            substitute_vars_in_operand(value, subst);
+        }
         Instruction::VaArg { dest, list, .. } => {
             if let Some(nv) = subst.get(dest) { *dest = *nv; }
             substitute_vars_in_operand(list, subst);
@@ -447,7 +464,7 @@ fn substitute_vars_in_terminator(term: &mut ir::Terminator, subst: &HashMap<VarI
         ir::Terminator::Ret(Some(op)) => {
             substitute_vars_in_operand(op, subst);
         }
-        ir::Terminator::CondBr { cond, .. } => {
+        ir::Terminator::CondBr { cond, .. } | ir::Terminator::Switch { value: cond, .. } => {
             substitute_vars_in_operand(cond, subst);
         }
         _ => {}
@@ -1,5 +1,5 @@
 use ir::{Function, Instruction, Operand, VarId};
-use model::BinaryOp;
+use model::{BinaryOp, Type};
 use crate::utils::{is_power_of_two, log2};
 use std::collections::HashMap;
 
@@ -7,9 +7,13 @@ use std::collections::HashMap;
 ///
 /// Examples:
 /// - x * (power of 2) → x << log2(power)
-/// - x / (power of 2) → x >> log2(power)
-/// - x % (power of 2) → x & (power - 1)
+/// - x / d, x % d for an `int` or `unsigned` x and constant d → a multiply
+///   by a magic number and shifts (see `expand_division`)
 pub fn strength_reduce_function(func: &mut Function) {
+    let mut next_var = func.blocks.iter().flat_map(|b| &b.instructions).flat_map(|i| i.dests()).map(|d| d.0 + 1).max().unwrap_or(0);
+    next_var = next_var.max(func.params.iter().map(|(_, v)| v.0 + 1).max().unwrap_or(0));
+    let mut new_types = Vec::new();
+
     for block in &mut func.blocks {
         let mut new_instructions = Vec::new();
 
@@ -23,6 +27,10 @@ pub fn strength_reduce_function(func: &mut Function) {
                 } => {
                     if let Some(reduced) = try_reduce_binary(&op, &left, &right, dest) {
                         new_instructions.push(reduced);
+                    } else if let Some(mut expansion) = expand_division(&op, &left, &right, dest, &func.var_types, next_var) {
+                        next_var = expansion.next_var;
+                        new_instructions.append(&mut expansion.instructions);
+                        new_types.append(&mut expansion.types);
                     } else {
                         new_instructions.push(Instruction::Binary {
                             dest,
@@ -38,6 +46,7 @@ pub fn strength_reduce_function(func: &mut Function) {
 
         block.instructions = new_instructions;
     }
+    func.var_types.extend(new_types);
 
     // Second pass: combine consecutive shifts
     combine_consecutive_shifts(func);
@@ -51,8 +60,6 @@ fn try_reduce_binary(
 ) -> Option<Instruction> {
     match op {
         BinaryOp::Mul => reduce_mul(left, right, dest),
-        BinaryOp::Div => reduce_div(left, right, dest),
-        BinaryOp::Mod => reduce_mod(left, right, dest),
         _ => None,
     }
 }
@@ -91,16 +98,196 @@ fn reduce_mul(left: &Operand, right: &Operand, dest: ir::VarId) -> Option<Instru
     None
 }
 
-fn reduce_div(_left: &Operand, _right: &Operand, _dest: ir::VarId) -> Option<Instruction> {
-    // x / (power of 2) → x >> log2(power) is WRONG for signed negative values
-    // (-7 / 2 == -3 but -7 >> 1 == -4). Disabled until we track signedness.
-    None
+/// Instructions replacing one division, with the temporaries they define.
+struct Expansion {
+    instructions: Vec<Instruction>,
+    types: Vec<(VarId, Type)>,
+    next_var: usize,
 }
 
-fn reduce_mod(_left: &Operand, _right: &Operand, _dest: ir::VarId) -> Option<Instruction> {
-    // x % (power of 2) → x & (power - 1) is WRONG for signed negative values
-    // (-7 % 4 == -3 but -7 & 3 == 1). Disabled until we track signedness.
-    None
+impl Expansion {
+    fn temp(&mut self, ty: Type) -> VarId {
+        let v = VarId(self.next_var);
+        self.next_var += 1;
+        self.types.push((v, ty));
+        v
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: Operand, right: Operand, ty: Type) -> Operand {
+        let dest = self.temp(ty);
+        self.instructions.push(Instruction::Binary { dest, op, left, right });
+        Operand::Var(dest)
+    }
+
+    fn cast(&mut self, dest: VarId, src: Operand, src_type: Type, r#type: Type) {
+        self.instructions.push(Instruction::Cast { dest, src, src_type, r#type });
+    }
+}
+
+/// Expand `x / d` or `x % d`, for an `int` or `unsigned` variable x and a
+/// constant d, into a multiply by a magic number and shifts (Granlund and
+/// Montgomery, "Division by invariant integers using multiplication").
+/// x is widened to 64 bits, where the full product fits, so no high-half
+/// multiply is needed; the quotient is truncated back. Signed quotients
+/// round toward zero by adding one when x is negative, and a remainder is
+/// `x - q * |d|`. Powers of two take a shift (with a rounding bias when
+/// signed) instead. 64-bit divisions are left to codegen, which reduces
+/// them with the high half of `imul` (signed) or `mul` (unsigned).
+fn expand_division(
+    op: &BinaryOp,
+    left: &Operand,
+    right: &Operand,
+    dest: VarId,
+    var_types: &HashMap<VarId, Type>,
+    next_var: usize,
+) -> Option<Expansion> {
+    let want_remainder = match op {
+        BinaryOp::Div => false,
+        BinaryOp::Mod => true,
+        _ => return None,
+    };
+    let (Operand::Var(x), Operand::Constant(d)) = (left, right) else {
+        return None;
+    };
+    let ty = var_types.get(x)?;
+    if var_types.get(&dest).is_some_and(|t| t != ty) {
+        return None;
+    }
+    let mut e = Expansion { instructions: Vec::new(), types: Vec::new(), next_var };
+    match ty {
+        Type::Int => {
+            // |d| must be an `int`, and x / ±1 is for algebraic simplification
+            if *d == i32::MIN as i64 || i32::try_from(*d).is_err() || d.abs() < 2 {
+                return None;
+            }
+            expand_signed(&mut e, *x, *d, dest, want_remainder);
+        }
+        Type::UnsignedInt => {
+            if !(2..=u32::MAX as i64).contains(d) {
+                return None;
+            }
+            expand_unsigned(&mut e, *x, *d as u32, dest, want_remainder);
+        }
+        _ => return None,
+    }
+    Some(e)
+}
+
+fn expand_signed(e: &mut Expansion, x: VarId, d: i64, dest: VarId, want_remainder: bool) {
+    let abs = d.abs();
+    let w = e.temp(Type::Long);
+    e.cast(w, Operand::Var(x), Type::Int, Type::Long);
+    let w = Operand::Var(w);
+    let sign_of = |e: &mut Expansion, v: Operand| e.binary(BinaryOp::ShiftRight, v, Operand::Constant(63), Type::Long);
+    let quotient = if is_power_of_two(abs) {
+        // Bias a negative x by |d| - 1 so the shift rounds toward zero
+        let sign = sign_of(e, w.clone());
+        let bias = e.binary(BinaryOp::BitwiseAnd, sign, Operand::Constant(abs - 1), Type::Long);
+        let biased = e.binary(BinaryOp::Add, w.clone(), bias, Type::Long);
+        if want_remainder {
+            let multiple = e.binary(BinaryOp::BitwiseAnd, biased, Operand::Constant(-abs), Type::Long);
+            let r = e.binary(BinaryOp::Sub, w, multiple, Type::Long);
+            e.cast(dest, r, Type::Long, Type::Int);
+            return;
+        }
+        e.binary(BinaryOp::ShiftRight, biased, Operand::Constant(log2(abs)), Type::Long)
+    } else {
+        let (magic, shift) = signed_magic(abs as u32);
+        let product = e.binary(BinaryOp::Mul, w.clone(), Operand::Constant(magic), Type::Long);
+        let floor = e.binary(BinaryOp::ShiftRight, product, Operand::Constant(shift), Type::Long);
+        let sign = sign_of(e, floor.clone());
+        e.binary(BinaryOp::Sub, floor, sign, Type::Long)
+    };
+    let result = if want_remainder {
+        let multiple = e.binary(BinaryOp::Mul, quotient, Operand::Constant(abs), Type::Long);
+        e.binary(BinaryOp::Sub, w, multiple, Type::Long)
+    } else if d < 0 {
+        e.binary(BinaryOp::Sub, Operand::Constant(0), quotient, Type::Long)
+    } else {
+        quotient
+    };
+    e.cast(dest, result, Type::Long, Type::Int);
+}
+
+fn expand_unsigned(e: &mut Expansion, x: VarId, d: u32, dest: VarId, want_remainder: bool) {
+    let d64 = d as i64;
+    if is_power_of_two(d64) && want_remainder {
+        e.instructions.push(Instruction::Binary {
+            dest,
+            op: BinaryOp::BitwiseAnd,
+            left: Operand::Var(x),
+            right: Operand::Constant(d64 - 1),
+        });
+        return;
+    }
+    let w = e.temp(Type::UnsignedLong);
+    e.cast(w, Operand::Var(x), Type::UnsignedInt, Type::UnsignedLong);
+    let w = Operand::Var(w);
+    let quotient = if is_power_of_two(d64) {
+        e.binary(BinaryOp::ShiftRight, w.clone(), Operand::Constant(log2(d64)), Type::UnsignedLong)
+    } else {
+        match unsigned_magic(d) {
+            UnsignedMagic::Multiply { magic, shift } => {
+                let product = e.binary(BinaryOp::Mul, w.clone(), Operand::Constant(magic), Type::UnsignedLong);
+                e.binary(BinaryOp::ShiftRight, product, Operand::Constant(shift), Type::UnsignedLong)
+            }
+            UnsignedMagic::MultiplyAdd { magic, shift } => {
+                // x * (2^32 + magic) >> (32 + shift), without the 65-bit product
+                let product = e.binary(BinaryOp::Mul, w.clone(), Operand::Constant(magic), Type::UnsignedLong);
+                let high = e.binary(BinaryOp::ShiftRight, product, Operand::Constant(32), Type::UnsignedLong);
+                let sum = e.binary(BinaryOp::Add, high, w.clone(), Type::UnsignedLong);
+                e.binary(BinaryOp::ShiftRight, sum, Operand::Constant(shift), Type::UnsignedLong)
+            }
+        }
+    };
+    let result = if want_remainder {
+        let multiple = e.binary(BinaryOp::Mul, quotient, Operand::Constant(d64), Type::UnsignedLong);
+        e.binary(BinaryOp::Sub, w, multiple, Type::UnsignedLong)
+    } else {
+        quotient
+    };
+    e.cast(dest, result, Type::UnsignedLong, Type::UnsignedInt);
+}
+
+/// `(m, p)` with `floor(x * m / 2^p) + (x < 0)` equal to `x / d` for every
+/// `int` x, where d > 1 is not a power of two. m is below 2^32, so the
+/// product fits an `i64`. The rounding error `m * d - 2^p` is below
+/// `2^(p - 31)`, which keeps it from reaching the next integer for any
+/// `|x| <= 2^31`.
+fn signed_magic(d: u32) -> (i64, i64) {
+    let d = d as u128;
+    (32..64)
+        .find_map(|p| {
+            let m = (1u128 << p).div_ceil(d);
+            let error = m * d - (1u128 << p);
+            (error < 1u128 << (p - 31) && m < 1u128 << 32).then_some((m as i64, p as i64))
+        })
+        .expect("a divisor below 2^31 has a 32-bit magic number")
+}
+
+enum UnsignedMagic {
+    /// `x / d == (x * magic) >> shift`
+    Multiply { magic: i64, shift: i64 },
+    /// `x / d == (((x * magic) >> 32) + x) >> shift`, for a multiplier of
+    /// `2^32 + magic` that needs 33 bits.
+    MultiplyAdd { magic: i64, shift: i64 },
+}
+
+/// The magic number for `unsigned` division by d > 1, not a power of two:
+/// the smallest `p` whose `m = ceil(2^p / d)` fits 32 bits with an error
+/// `m * d - 2^p` of at most `2^(p - 32)`. Otherwise `p = 32 + ceil(log2 d)`
+/// always works, with a 33-bit m.
+fn unsigned_magic(d: u32) -> UnsignedMagic {
+    let d = d as u128;
+    let log = 32 - (d as u32 - 1).leading_zeros() as u128;
+    for p in 32..=32 + log {
+        let m = (1u128 << p).div_ceil(d);
+        if m < 1u128 << 32 && m * d - (1u128 << p) <= 1u128 << (p - 32) {
+            return UnsignedMagic::Multiply { magic: m as i64, shift: p as i64 };
+        }
+    }
+    let m = (1u128 << (32 + log)).div_ceil(d);
+    UnsignedMagic::MultiplyAdd { magic: (m - (1u128 << 32)) as i64, shift: log as i64 }
 }
 
 /// Combine consecutive shifts in the same direction.
@@ -146,3 +333,122 @@ fn combine_consecutive_shifts(func: &mut Function) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_to_ir(src: &str) -> ir::IRProgram {
+        let tokens = lexer::lex(src).unwrap();
+        let ast = parser::parse_tokens(&tokens).unwrap();
+        let mut lowerer = ir::Lowerer::new();
+        lowerer.lower_program(&ast).unwrap()
+    }
+
+    /// Divisors worth checking: small ones, ones near powers of two, and
+    /// the largest the types allow.
+    fn divisors() -> Vec<u32> {
+        let mut ds: Vec<u32> = (2..=1000).collect();
+        for k in 10..32 {
+            ds.extend([(1u32 << k) - 1, (1u32 << k) + 1, (1u32 << k) / 3 * 3]);
+        }
+        ds.extend([641, 6700417, 1_000_000_007, i32::MAX as u32, u32::MAX - 1, u32::MAX]);
+        ds.retain(|d| !d.is_power_of_two());
+        ds
+    }
+
+    fn dividends(d: i64, min: i64, max: i64) -> Vec<i64> {
+        let mut xs = vec![min, min + 1, -d - 1, -d, -d + 1, -1, 0, 1, d - 1, d, d + 1, max - 1, max];
+        xs.extend((0..64).map(|i| max / 64 * i + 7 * i));
+        xs.extend((0..64).map(|i| min / 64 * i - 3 * i));
+        xs.retain(|x| (min..=max).contains(x));
+        xs
+    }
+
+    #[test]
+    fn signed_magic_numbers_divide_every_int() {
+        for d in divisors().into_iter().filter(|&d| d <= i32::MAX as u32) {
+            let (magic, shift) = signed_magic(d);
+            for x in dividends(d as i64, i32::MIN as i64, i32::MAX as i64) {
+                let floor = (x * magic) >> shift;
+                assert_eq!(floor - (floor >> 63), x / d as i64, "{} / {}", x, d);
+            }
+        }
+    }
+
+    #[test]
+    fn unsigned_magic_numbers_divide_every_unsigned() {
+        for d in divisors() {
+            for x in dividends(d as i64, 0, u32::MAX as i64) {
+                let x = x as u64;
+                let q = match unsigned_magic(d) {
+                    UnsignedMagic::Multiply { magic, shift } => (x * magic as u64) >> shift,
+                    UnsignedMagic::MultiplyAdd { magic, shift } => (((x * magic as u64) >> 32) + x) >> shift,
+                };
+                assert_eq!(q, x / d as u64, "{} / {}", x, d);
+            }
+        }
+    }
+
+    #[test]
+    fn constant_divisions_become_multiplies() {
+        // Each function divides by a constant, and check() by the same
+        // divisor passed at run time, which stays a division
+        let src = r#"
+            int sdiv7(int x) { return x / 7; }
+            int smod7(int x) { return x % 7; }
+            int sdivn10(int x) { return x / -10; }
+            int smodn10(int x) { return x % -10; }
+            int sdiv8(int x) { return x / 8; }
+            int smod8(int x) { return x % 8; }
+            unsigned udiv7(unsigned x) { return x / 7u; }
+            unsigned umod7(unsigned x) { return x % 7u; }
+            unsigned udiv10(unsigned x) { return x / 10u; }
+            unsigned udiv16(unsigned x) { return x / 16u; }
+            unsigned umod16(unsigned x) { return x % 16u; }
+            int sdiv(int x, int d) { return x / d; }
+            int smod(int x, int d) { return x % d; }
+            unsigned udiv(unsigned x, unsigned d) { return x / d; }
+            unsigned umod(unsigned x, unsigned d) { return x % d; }
+            int main() {
+                int xs[9] = { -2147483647 - 1, -2147483647, -100, -15, -1, 0, 13, 99999, 2147483647 };
+                unsigned us[6] = { 0u, 6u, 7u, 4000000000u, 4294967294u, 4294967295u };
+                int bad = 0;
+                for (int i = 0; i < 9; i++) {
+                    int x = xs[i];
+                    bad += sdiv7(x) != sdiv(x, 7);
+                    bad += smod7(x) != smod(x, 7);
+                    bad += sdivn10(x) != sdiv(x, -10);
+                    bad += smodn10(x) != smod(x, -10);
+                    bad += sdiv8(x) != sdiv(x, 8);
+                    bad += smod8(x) != smod(x, 8);
+                }
+                for (int i = 0; i < 6; i++) {
+                    unsigned u = us[i];
+                    bad += udiv7(u) != udiv(u, 7u);
+                    bad += umod7(u) != umod(u, 7u);
+                    bad += udiv10(u) != udiv(u, 10u);
+                    bad += udiv16(u) != udiv(u, 16u);
+                    bad += umod16(u) != umod(u, 16u);
+                }
+                return bad;
+            }
+        "#;
+        let mut prog = compile_to_ir(src);
+        for func in &mut prog.functions {
+            ir::mem2reg(func);
+            strength_reduce_function(func);
+            assert!(ir::verify_function(func).is_ok(), "{}", ir::verify_function(func).unwrap_err());
+            let divisions = func
+                .blocks
+                .iter()
+                .flat_map(|b| &b.instructions)
+                .filter(|inst| {
+                    matches!(inst, Instruction::Binary { op: BinaryOp::Div | BinaryOp::Mod, right: Operand::Constant(_), .. })
+                })
+                .count();
+            assert_eq!(divisions, 0, "division by a constant left in {}", func.name);
+        }
+        assert_eq!(ir::interpret(&prog, "test", &[]).unwrap(), 0);
+    }
+}
@@ -1,5 +1,9 @@
 /// Utility functions for optimization passes
 
+use ir::Function;
+use model::Type;
+use std::collections::HashSet;
+
 /// Check if a number is a power of 2
 #[inline]
 pub fn is_power_of_two(n: i64) -> bool {
@@ -12,6 +16,29 @@ pub fn log2(n: i64) -> i64 {
     (n as u64).trailing_zeros() as i64
 }
 
+/// Size of a scalar type; `None` for aggregates, whose layout the optimizer
+/// does not know.
+pub fn scalar_size(ty: &Type) -> Option<usize> {
+    Some(match ty {
+        Type::Char | Type::UnsignedChar | Type::Bool => 1,
+        Type::Short | Type::UnsignedShort => 2,
+        Type::Int | Type::UnsignedInt | Type::Float | Type::Enum(_) => 4,
+        Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong
+        | Type::Double | Type::Pointer(..) | Type::FunctionPointer { .. } => 8,
+        _ => return None,
+    })
+}
+
+/// Whether every variable has a single definition.
+pub fn is_ssa(func: &Function) -> bool {
+    let mut defined = HashSet::new();
+    func.blocks
+        .iter()
+        .flat_map(|b| &b.instructions)
+        .flat_map(|inst| inst.dests())
+        .all(|dest| defined.insert(dest))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
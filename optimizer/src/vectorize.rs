@@ -13,8 +13,8 @@
 // packed SSE or AVX instructions based on the target features.
 
 use ir::{Function, Instruction, Operand, VarId, BlockId, Terminator, BasicBlock, SimdOp};
-use model::{BinaryOp, Type};
-use std::collections::{HashMap, HashSet};
+use model::{BinaryOp, Span, Type};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use crate::loop_analysis::{self, NaturalLoop};
 use crate::mem_dependence::{self, check_memory_dependence};
 use crate::polyhedral;
@@ -71,7 +71,7 @@ fn resolve_index_pattern(
     op: &Operand,
     iv: VarId,
     func: &Function,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     arith_ops: &[(VarId, BinaryOp, Operand, Operand, bool)],
 ) -> Option<IndexPattern> {
     let mut visited = HashSet::new();
@@ -82,7 +82,7 @@ fn resolve_index_pattern_inner(
     op: &Operand,
     iv: VarId,
     func: &Function,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     arith_ops: &[(VarId, BinaryOp, Operand, Operand, bool)],
     visited: &mut HashSet<VarId>,
 ) -> Option<IndexPattern> {
@@ -311,7 +311,7 @@ fn analyze_loop_body(
         }
         let block = func.blocks.iter().find(|b| b.id == block_id)?;
         match &block.terminator {
-            Terminator::CondBr { .. } => {
+            Terminator::CondBr { .. } | Terminator::Switch { .. } => {
                 has_complex_control_flow = true;
             }
             _ => {}
@@ -324,7 +324,7 @@ fn analyze_loop_body(
 
     // Collect all phi nodes in the header to find reductions
     let header_block = func.blocks.iter().find(|b| b.id == lp.header)?;
-    let mut phi_vars: HashMap<VarId, Vec<(BlockId, VarId)>> = HashMap::new();
+    let mut phi_vars: BTreeMap<VarId, Vec<(BlockId, VarId)>> = BTreeMap::new();
     for inst in &header_block.instructions {
         if let Instruction::Phi { dest, preds } = inst {
             if *dest != iv.var {
@@ -338,7 +338,8 @@ fn analyze_loop_body(
         let block = func.blocks.iter().find(|b| b.id == block_id)?;
         for inst in &block.instructions {
             match inst {
-                Instruction::Call { .. } | Instruction::IndirectCall { .. } => has_calls = true,
+                Instruction::Call { .. } | Instruction::IndirectCall { .. }
+                | Instruction::Memcpy { .. } | Instruction::Memset { .. } => has_calls = true,
                 Instruction::Binary { dest, op, left, right } => {
                     arithmetic_ops.push((*dest, op.clone(), left.clone(), right.clone(), false));
                 }
@@ -439,6 +440,13 @@ fn analyze_loop_body(
         return None;
     }
 
+    // Vector lanes are 32 bits wide; a `char` or `short` access would read or
+    // write a whole lane per element.
+    let lane_sized = |m: &MemAccess| matches!(m.elem_type, Type::Int | Type::UnsignedInt | Type::Float);
+    if !loads.iter().chain(&stores).all(lane_sized) {
+        return None;
+    }
+
     // Check for stores whose data depends on the induction variable (e.g., arr[i] = i).
     // A Splat would incorrectly broadcast a single IV value to all lanes.
     // We can only vectorize stores whose data is either:
@@ -505,7 +513,7 @@ fn is_iv_or_iv_derived(
 fn find_gep_for_var(
     func: &Function,
     var: VarId,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     iv_var: VarId,
     arith_ops: &[(VarId, BinaryOp, Operand, Operand, bool)],
 ) -> Option<(VarId, IndexPattern)> {
@@ -535,7 +543,7 @@ fn find_gep_for_var(
 fn find_indexed_access(
     func: &Function,
     index_op: &Operand,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     iv_var: VarId,
     arith_ops: &[(VarId, BinaryOp, Operand, Operand, bool)],
 ) -> Option<(VarId, IndexPattern)> {
@@ -568,7 +576,7 @@ fn find_indexed_access(
 fn resolve_gep_access(
     func: &Function,
     addr_var: VarId,
-    body: &HashSet<BlockId>,
+    body: &BTreeSet<BlockId>,
     iv_var: VarId,
     arith_ops: &[(VarId, BinaryOp, Operand, Operand, bool)],
 ) -> Option<(VarId, IndexPattern, MemAccessMode)> {
@@ -1239,6 +1247,8 @@ fn apply_vectorization(
             instructions: tail_insts,
             terminator: Terminator::Br(exit_block),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         });
         Some(id)
     } else {
@@ -1272,6 +1282,8 @@ fn apply_vectorization(
             instructions: bridge_insts,
             terminator: Terminator::Br(bridge_target),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         });
         bridge_id
     } else if use_masked_tail {
@@ -1291,6 +1303,8 @@ fn apply_vectorization(
             vec_exit_target,
         ),
         is_label_target: false,
+        spans: Vec::new(),
+        terminator_span: Span::default(),
     };
 
     // --- Build the vectorized loop body ---
@@ -1299,6 +1313,8 @@ fn apply_vectorization(
         instructions: vec_body_insts,
         terminator: Terminator::Br(vec_header_id),
         is_label_target: false,
+        spans: Vec::new(),
+        terminator_span: Span::default(),
     };
 
     // --- Modify preheader to jump to vectorized header ---
@@ -1350,6 +1366,12 @@ fn apply_vectorization(
                 if *then_block == lp.header { *then_block = vec_header_id; }
                 if *else_block == lp.header { *else_block = vec_header_id; }
             }
+            Terminator::Switch { cases, default, .. } => {
+                for (_, block) in cases.iter_mut() {
+                    if *block == lp.header { *block = vec_header_id; }
+                }
+                if *default == lp.header { *default = vec_header_id; }
+            }
             _ => {}
         }
     }
@@ -1507,6 +1529,8 @@ mod tests {
             is_static: false,
             label_addrs: vec![],
             labels: HashMap::new(),
+            local_names: HashMap::new(),
+            span: Span::default(),
         }
     }
 
@@ -1516,6 +1540,8 @@ mod tests {
             instructions: vec![],
             terminator: Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }
     }
 
@@ -1623,10 +1649,12 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
-        let body: HashSet<BlockId> = vec![BlockId(1)].into_iter().collect();
+        let body: BTreeSet<BlockId> = vec![BlockId(1)].into_iter().collect();
         let result = find_gep_for_var(&func, VarId(5), &body, VarId(0), &[]);
         assert_eq!(result, Some((VarId(10), IndexPattern::direct())));
     }
@@ -1646,10 +1674,12 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
-        let body: HashSet<BlockId> = vec![BlockId(1)].into_iter().collect();
+        let body: BTreeSet<BlockId> = vec![BlockId(1)].into_iter().collect();
         let result = find_gep_for_var(&func, VarId(5), &body, VarId(0), &[]);
         assert_eq!(result, None);
     }
@@ -1669,10 +1699,12 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
-        let body: HashSet<BlockId> = vec![BlockId(2)].into_iter().collect(); // Block 1 not in body
+        let body: BTreeSet<BlockId> = vec![BlockId(2)].into_iter().collect(); // Block 1 not in body
         let result = find_gep_for_var(&func, VarId(5), &body, VarId(0), &[]);
         assert_eq!(result, None);
     }
@@ -1692,17 +1724,19 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
-        let body: HashSet<BlockId> = vec![BlockId(1)].into_iter().collect();
+        let body: BTreeSet<BlockId> = vec![BlockId(1)].into_iter().collect();
         let result = find_gep_for_var(&func, VarId(5), &body, VarId(0), &[]);
         assert_eq!(result, None);
     }
 
     #[test]
     fn test_resolve_index_pattern_iv_plus_const() {
-        let body: HashSet<BlockId> = [BlockId(1)].into_iter().collect();
+        let body: BTreeSet<BlockId> = [BlockId(1)].into_iter().collect();
         let func = make_func(vec![BasicBlock {
             id: BlockId(1),
             instructions: vec![Instruction::Binary {
@@ -1713,6 +1747,8 @@ mod tests {
             }],
             terminator: Terminator::Br(BlockId(1)),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         let arith = vec![(VarId(7), BinaryOp::Add, Operand::Var(VarId(0)), Operand::Constant(2), false)];
         let pat = resolve_index_pattern(&Operand::Var(VarId(7)), VarId(0), &func, &body, &arith);
@@ -1736,6 +1772,8 @@ mod tests {
             ],
             terminator: Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 7);
     }
@@ -1761,6 +1799,8 @@ mod tests {
             ],
             terminator: Terminator::Ret(Some(Operand::Constant(0))),
             is_label_target: false,
+            spans: Vec::new(),
+            terminator_span: Span::default(),
         }]);
         assert_eq!(find_max_var_id(&func), 42);
     }
@@ -1822,6 +1862,8 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             BasicBlock {
                 id: BlockId(1),
@@ -1830,10 +1872,13 @@ mod tests {
                         dest: None,
                         name: "printf".to_string(),
                         args: vec![],
+                        vararg_types: vec![],
                     },
                 ],
                 terminator: Terminator::Br(BlockId(0)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
         ]);
 
@@ -1869,6 +1914,8 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(1)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             BasicBlock {
                 id: BlockId(1),
@@ -1879,6 +1926,8 @@ mod tests {
                     BlockId(0),
                 ),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ret_block(2),
         ]);
@@ -1924,6 +1973,8 @@ mod tests {
                     BlockId(2),
                 ),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             BasicBlock {
                 id: BlockId(1),
@@ -1961,6 +2012,8 @@ mod tests {
                 ],
                 terminator: Terminator::Br(BlockId(0)),
                 is_label_target: false,
+                spans: Vec::new(),
+                terminator_span: Span::default(),
             },
             ret_block(2),
         ]);
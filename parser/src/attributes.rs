@@ -1,25 +1,38 @@
-use model::{Attribute, Token};
-use crate::parser::Parser;
+use model::{Attribute, CompileError, Token};
+use crate::parser::{syntax_error, Parser};
+use crate::expressions::ExpressionParser;
 
 pub(crate) trait AttributeParser {
-    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, String>;
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, CompileError>;
 }
 
 impl<'a> AttributeParser for Parser<'a> {
-    /// Parse __attribute__((...)) syntax and return a list of attributes
-    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, String> {
+    /// Parse __attribute__((...)) syntax and return a list of attributes.
+    /// The C11 specifiers `_Alignas(...)` and `_Noreturn` map to the
+    /// `aligned` and `noreturn` attributes, and MSVC's `__declspec(...)`
+    /// (lexed as `Token::Attribute`) goes through `parse_declspec()`.
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, CompileError> {
         let mut attributes = Vec::new();
 
-        while self.match_token(|t| matches!(t, Token::Attribute | Token::Extension)) {
+        loop {
+            if self.match_token(|t| matches!(t, Token::Alignas)) {
+                attributes.push(Attribute::Aligned(self.parse_alignas()?));
+                continue;
+            }
+            if self.match_token(|t| matches!(t, Token::Noreturn)) {
+                attributes.push(Attribute::NoReturn);
+                continue;
+            }
+            if !self.match_token(|t| matches!(t, Token::Attribute | Token::Extension)) {
+                break;
+            }
             // Expect (( after __attribute__
             if !self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
                 continue; // Just skip if no parentheses
             }
             if !self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
-                // Malformed attribute, skip to semicolon or close paren
-                while !self.check(|t| matches!(t, Token::Semicolon | Token::CloseParenthesis)) && !self.is_at_end() {
-                    self.advance();
-                }
+                // A single parenthesis: __declspec(...)
+                self.parse_declspec(&mut attributes)?;
                 continue;
             }
 
@@ -45,10 +58,10 @@ impl<'a> AttributeParser for Parser<'a> {
                                     attributes.push(Attribute::Aligned(*value as usize));
                                 }
                                 other => {
-                                    return Err(format!(
+                                    return Err(syntax_error(format!(
                                         "expected alignment constant, found {:?}",
                                         other
-                                    ));
+                                    )));
                                 }
                             }
                             self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
@@ -60,20 +73,20 @@ impl<'a> AttributeParser for Parser<'a> {
                         // Parse section("name")
                         if self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
                             match self.advance() {
-                                Some(Token::StringLiteral { value }) => {
+                                Some(Token::StringLiteral { value, .. }) => {
                                     attributes.push(Attribute::Section(value.clone()));
                                 }
                                 other => {
-                                    return Err(format!(
+                                    return Err(syntax_error(format!(
                                         "expected section name string, found {:?}",
                                         other
-                                    ));
+                                    )));
                                 }
                             }
                             self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
                         }
                     }
-                    Some(Token::Identifier { value }) if value == "noreturn" => {
+                    Some(Token::Noreturn) => {
                         self.advance();
                         attributes.push(Attribute::NoReturn);
                     }
@@ -138,3 +151,45 @@ impl<'a> AttributeParser for Parser<'a> {
         Ok(attributes)
     }
 }
+
+impl<'a> Parser<'a> {
+    /// Parse the specifiers of `__declspec(...)` after its opening
+    /// parenthesis. They are separated by whitespace, not commas.
+    /// `align(N)`, `noreturn` and `selectany` map to `Aligned`, `NoReturn`
+    /// and `Weak`; linkage and hint specifiers such as `dllimport`,
+    /// `dllexport`, `noinline` or `deprecated("...")` are dropped.
+    fn parse_declspec(&mut self, attributes: &mut Vec<Attribute>) -> Result<(), CompileError> {
+        while !self.check(|t| matches!(t, Token::CloseParenthesis)) && !self.is_at_end() {
+            match self.advance() {
+                Some(Token::Identifier { value }) if value == "align" => {
+                    self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                    match self.advance() {
+                        Some(Token::Constant { value, .. }) => {
+                            attributes.push(Attribute::Aligned(*value as usize));
+                        }
+                        other => {
+                            return Err(syntax_error(format!(
+                                "expected alignment constant, found {:?}",
+                                other
+                            )));
+                        }
+                    }
+                    self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                }
+                Some(Token::Noreturn) => {
+                    attributes.push(Attribute::NoReturn);
+                }
+                Some(Token::Identifier { value }) if value == "selectany" => {
+                    attributes.push(Attribute::Weak);
+                }
+                _ => {
+                    if self.check(|t| matches!(t, Token::OpenParenthesis)) {
+                        self.skip_parentheses()?;
+                    }
+                }
+            }
+        }
+        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        Ok(())
+    }
+}
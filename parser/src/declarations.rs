@@ -1,25 +1,26 @@
-use model::{Function, GlobalVar, Program, Token};
-use crate::parser::Parser;
-use crate::types::TypeParser;
+use model::{CompileError, Function, GlobalVar, Program, Token};
+use crate::parser::{syntax_error, Parser};
+use crate::types::{array_of, TypeParser};
 use crate::statements::StatementParser;
 use crate::expressions::ExpressionParser;
 use crate::attributes::AttributeParser;
 use crate::utils::ParserUtils;
+use crate::symbols::{Occurrence, SymbolKind};
 
 pub(crate) trait DeclarationParser {
-    fn parse_program(&mut self) -> Result<Program, String>;
-    fn parse_typedef(&mut self) -> Result<(), String>;
-    fn parse_function(&mut self) -> Result<Function, String>;
-    fn parse_function_prototype(&mut self) -> Result<model::FunctionPrototype, String>;
-    fn parse_forward_struct_name(&mut self) -> Result<String, String>;
-    fn parse_function_params(&mut self) -> Result<(Vec<(model::Type, String)>, bool), String>;
-    fn parse_globals(&mut self) -> Result<Vec<GlobalVar>, String>;
-    fn parse_static_assert(&mut self) -> Result<(), String>;
+    fn parse_program(&mut self) -> Result<Program, CompileError>;
+    fn parse_typedef(&mut self) -> Result<(), CompileError>;
+    fn parse_function(&mut self) -> Result<Function, CompileError>;
+    fn parse_function_prototype(&mut self) -> Result<model::FunctionPrototype, CompileError>;
+    fn parse_forward_struct_name(&mut self) -> Result<String, CompileError>;
+    fn parse_function_params(&mut self) -> Result<(Vec<(model::Type, String)>, bool), CompileError>;
+    fn parse_globals(&mut self) -> Result<Vec<GlobalVar>, CompileError>;
+    fn parse_static_assert(&mut self) -> Result<model::StaticAssert, CompileError>;
 }
 
 impl<'a> DeclarationParser for Parser<'a> {
     /// Parse the entire program (functions, globals, structs, unions, enums)
-    fn parse_program(&mut self) -> Result<Program, String> {
+    fn parse_program(&mut self) -> Result<Program, CompileError> {
         let mut functions = Vec::new();
         let mut globals = Vec::new();
         let mut structs = Vec::new();
@@ -27,11 +28,12 @@ impl<'a> DeclarationParser for Parser<'a> {
         let mut enums = Vec::new();
         let mut prototypes = Vec::new();
         let mut forward_structs = Vec::new();
+        let mut static_asserts = Vec::new();
 
-        while !self.is_at_end() {
+        while !self.is_at_end() && self.fatal.is_none() {
              if self.match_token(|t| matches!(t, Token::StaticAssert)) {
                 // _Static_assert(expr, "message") or _Static_assert(expr)
-                self.parse_static_assert()?;
+                static_asserts.push(self.parse_static_assert()?);
             } else if self.match_token(|t| matches!(t, Token::Typedef)) {
                 // Try to parse typedef, but skip if it fails (complex header typedef)
                 if self.parse_typedef().is_err() {
@@ -65,9 +67,10 @@ impl<'a> DeclarationParser for Parser<'a> {
                 }
                 continue;
             } else if self.check(|t| matches!(t, Token::Enum))
-                && self.check_at(2, &|t: &Token| matches!(t, Token::OpenBrace))
+                && (self.check_at(1, &|t: &Token| matches!(t, Token::OpenBrace))
+                    || self.check_at(2, &|t: &Token| matches!(t, Token::OpenBrace)))
             {
-                // enum definition: enum Color { ... };
+                // enum definition: enum Color { ... }; or enum { ... };
                 // Try to parse, skip if it fails
                 match self.parse_enum_definition() {
                     Ok(e) => {
@@ -78,6 +81,16 @@ impl<'a> DeclarationParser for Parser<'a> {
                         let _ = self.skip_top_level_item();
                     }
                 }
+            } else if self.is_inline_function() {
+                if self.skips_inline_at(self.pos) {
+                    // Inline wrappers from system headers (e.g., printf/scanf)
+                    let _ = self.skip_extern_inline_function();
+                } else {
+                    match self.parse_function() {
+                        Ok(f) => functions.push(f),
+                        Err(_) => { let _ = self.skip_top_level_item(); }
+                    }
+                }
             } else if self.peek() == Some(&Token::Extern) {
                 // Extern declarations: parse as globals with is_extern=true
                 // If it's a function declaration like "extern int foo(void);", 
@@ -86,9 +99,6 @@ impl<'a> DeclarationParser for Parser<'a> {
                     Ok(gvars) => globals.extend(gvars),
                     Err(_) => { let _ = self.skip_extern_declaration(); }
                 }
-            } else if self.is_inline_function() {
-                // Skip extern/static inline functions from headers (e.g., printf/scanf wrappers)
-                let _ = self.skip_extern_inline_function();
             } else if self.is_function_definition() {
                 // Try to parse function, skip if it fails
                 match self.parse_function() {
@@ -124,19 +134,31 @@ impl<'a> DeclarationParser for Parser<'a> {
                     self.skip_forward_declaration()
                 } else if self.check(|t| matches!(t, Token::Struct)) && self.is_struct_definition() {
                     // struct definition without variable: struct foo { ... };
+                    // With variables, `struct foo { ... } x;`, the type parser
+                    // records the definition as it parses the globals
+                    let start = self.pos;
                     match self.parse_struct_definition() {
-                        Ok(s) => {
+                        Ok(s) if self.match_token(|t| matches!(t, Token::Semicolon)) => {
                             structs.push(s);
-                            self.expect(|t| matches!(t, Token::Semicolon), "';'")
+                            Ok(())
+                        }
+                        Ok(_) => {
+                            self.pos = start;
+                            self.parse_globals().map(|gs| globals.extend(gs))
                         }
                         Err(e) => Err(e),
                     }
                 } else if self.check(|t| matches!(t, Token::Union)) && self.is_union_definition() {
                     // union definition without variable: union foo { ... };
+                    let start = self.pos;
                     match self.parse_union_definition() {
-                        Ok(u) => {
+                        Ok(u) if self.match_token(|t| matches!(t, Token::Semicolon)) => {
                             unions.push(u);
-                            self.expect(|t| matches!(t, Token::Semicolon), "';'")
+                            Ok(())
+                        }
+                        Ok(_) => {
+                            self.pos = start;
+                            self.parse_globals().map(|gs| globals.extend(gs))
                         }
                         Err(e) => Err(e),
                     }
@@ -154,6 +176,11 @@ impl<'a> DeclarationParser for Parser<'a> {
                 let _ = self.skip_top_level_item();
             }
         }
+        if let Some(error) = self.fatal.take() {
+            return Err(error);
+        }
+        structs.append(&mut self.nested_structs);
+        unions.append(&mut self.nested_unions);
 
         Ok(Program {
             functions,
@@ -164,10 +191,11 @@ impl<'a> DeclarationParser for Parser<'a> {
             prototypes,
             forward_structs,
             typedefs: self.typedef_defs.clone(),
+            static_asserts,
         })
     }
 
-    fn parse_typedef(&mut self) -> Result<(), String> {
+    fn parse_typedef(&mut self) -> Result<(), CompileError> {
         let base_ty = self.parse_type()?;
         let base_ty_clone = base_ty.clone();
         if self.check(|t| matches!(t, Token::OpenBrace)) {
@@ -254,11 +282,21 @@ impl<'a> DeclarationParser for Parser<'a> {
         // Parse typedef aliases (can be multiple, comma-separated)
         let mut alias_ty = base_ty_clone.clone();
         loop {
-            // Skip pointer stars and qualifiers
+            // Pointer stars; qualifiers after a `*` qualify that pointer, so
+            // they become the pointee qualifiers of the next level
+            let mut pointee_qualifiers = model::TypeQualifiers::default();
             while self.match_token(|t| matches!(t, Token::Star)) {
-                alias_ty = model::Type::ptr(alias_ty);
-                while self.match_token(|t| matches!(t, Token::Const | Token::Volatile | Token::Restrict)) {
-                    // qualifiers on pointer — consumed for parse progress
+                alias_ty = model::Type::qualified_ptr(alias_ty, pointee_qualifiers);
+                pointee_qualifiers = model::TypeQualifiers::default();
+                while let Some(token) = self.peek() {
+                    match token {
+                        Token::Const => pointee_qualifiers.is_const = true,
+                        Token::Volatile => pointee_qualifiers.is_volatile = true,
+                        Token::Restrict => pointee_qualifiers.is_restrict = true,
+                        Token::Atomic => {}
+                        _ => break,
+                    }
+                    self.advance();
                 }
             }
             
@@ -277,9 +315,15 @@ impl<'a> DeclarationParser for Parser<'a> {
                 }
             };
             self.typedefs.insert(name.clone());
-            self.typedef_defs.insert(name, alias_ty.clone());
             
+            // Function type: typedef int handler(int);
+            if self.check(|t| matches!(t, Token::OpenParenthesis)) {
+                alias_ty = self.parse_function_pointer_type(alias_ty)?;
+                self.function_typedefs.insert(name.clone());
+            }
+
             // Check for array syntax: typedef int arr[10];
+            let mut dims = Vec::new();
             while self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
                     0
@@ -300,8 +344,10 @@ impl<'a> DeclarationParser for Parser<'a> {
                     }
                     return Ok(());
                 }
-                alias_ty = model::Type::Array(Box::new(alias_ty), size);
+                dims.push(size);
             }
+            alias_ty = array_of(alias_ty, &dims);
+            self.typedef_defs.insert(name, alias_ty);
             
             if !self.match_token(|t| matches!(t, Token::Comma)) {
                 break;
@@ -319,7 +365,7 @@ impl<'a> DeclarationParser for Parser<'a> {
         Ok(())
     }
 
-    fn parse_function(&mut self) -> Result<Function, String> {
+    fn parse_function(&mut self) -> Result<Function, CompileError> {
         // Track inline, static, and attributes before parsing type
         let saved_pos = self.pos;
         let mut is_inline = false;
@@ -330,16 +376,16 @@ impl<'a> DeclarationParser for Parser<'a> {
             match self.peek() {
                 Some(Token::Inline) => {
                     is_inline = true;
-                    break;
+                    self.pos += 1;
                 }
                 Some(Token::Static) => {
                     is_static = true;
                     self.pos += 1;
                 }
-                Some(Token::Extern | Token::Const | Token::Volatile | Token::Restrict) => {
+                Some(Token::Extern | Token::Const | Token::Volatile | Token::Restrict | Token::Noreturn) => {
                     self.pos += 1;
                 }
-                Some(Token::Attribute | Token::Extension) => {
+                Some(Token::Attribute | Token::Extension | Token::Alignas) => {
                     self.pos += 1;
                     if self.check(|t| matches!(t, Token::OpenParenthesis)) {
                         let _ = self.skip_parentheses();
@@ -355,14 +401,14 @@ impl<'a> DeclarationParser for Parser<'a> {
         // Parse attributes before function
         let mut attributes = self.parse_attributes()?;
         
-        let return_type = match self.parse_type() {
+        let mut return_type = match self.parse_type() {
             Ok(ty) => ty,
             Err(_) => {
                 // Check if we have an identifier next (implicit int return type)
                 if self.check(|t| matches!(t, Token::Identifier { .. })) {
                     model::Type::Int
                 } else {
-                    return Err("Expected return type or function name".to_string());
+                    return Err(syntax_error("Expected return type or function name"));
                 }
             }
         };
@@ -370,20 +416,33 @@ impl<'a> DeclarationParser for Parser<'a> {
         // Parse attributes after return type but before function name
         let mut more_attributes = self.parse_attributes()?;
         attributes.append(&mut more_attributes);
+
+        // `ret (*name(params))(ret_params)`: a function returning a function pointer
+        let returns_fn_ptr = self.check(|t| matches!(t, Token::OpenParenthesis)) && self.check_at(1, |t| matches!(t, Token::Star));
+        if returns_fn_ptr {
+            self.advance();
+            self.advance();
+        }
         
+        let name_pos = self.pos;
         let name = match self.advance() {
             Some(Token::Identifier { value }) => value.clone(),
             other => {
-                return Err(format!(
+                return Err(syntax_error(format!(
                     "expected function name identifier, found {:?}",
                     other
-                ))
+                )))
             }
         };
+        self.note_symbol(name_pos, Occurrence::Def(SymbolKind::Function, None));
 
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         let (params, is_variadic) = self.parse_function_params()?;
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        if returns_fn_ptr {
+            self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+            return_type = self.parse_function_pointer_type(return_type)?;
+        }
         
         // Parse attributes after function declaration (e.g., void foo() __attribute__((noreturn)))
         let mut post_attributes = self.parse_attributes()?;
@@ -391,6 +450,12 @@ impl<'a> DeclarationParser for Parser<'a> {
 
         let body_block = self.parse_block()?;
 
+        // A non-static inline definition may also be emitted by other
+        // translation units, so give it weak linkage
+        if is_inline && !is_static && !attributes.contains(&model::Attribute::Weak) {
+            attributes.push(model::Attribute::Weak);
+        }
+
         Ok(Function {
             return_type,
             name,
@@ -400,12 +465,13 @@ impl<'a> DeclarationParser for Parser<'a> {
             is_static,
             is_variadic,
             attributes,
+            span: self.span_since(saved_pos),
         })
     }
 
-    fn parse_function_prototype(&mut self) -> Result<model::FunctionPrototype, String> {
+    fn parse_function_prototype(&mut self) -> Result<model::FunctionPrototype, CompileError> {
         // Skip storage class specifiers (extern, static, etc.)
-        while self.check(|t| matches!(t, Token::Extern | Token::Static | Token::Inline)) {
+        while self.check(|t| matches!(t, Token::Extern | Token::Static | Token::Inline | Token::Noreturn)) {
             self.advance();
         }
         // Skip __extension__ / __attribute__
@@ -416,7 +482,7 @@ impl<'a> DeclarationParser for Parser<'a> {
             }
         }
         
-        let return_type = self.parse_type()?;
+        let mut return_type = self.parse_type()?;
         
         // Skip post-type attributes
         while self.check(|t| matches!(t, Token::Attribute | Token::Extension)) {
@@ -425,15 +491,28 @@ impl<'a> DeclarationParser for Parser<'a> {
                 self.skip_parentheses()?;
             }
         }
+
+        let returns_fn_ptr = self.check(|t| matches!(t, Token::OpenParenthesis)) && self.check_at(1, |t| matches!(t, Token::Star));
+        if returns_fn_ptr {
+            self.advance();
+            self.advance();
+        }
         
         let name = match self.advance() {
             Some(Token::Identifier { value }) => value.clone(),
-            other => return Err(format!("expected function name, found {:?}", other)),
+            other => return Err(syntax_error(format!("expected function name, found {:?}", other))),
         };
         
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+        let params_start = self.pos;
         let (params, is_variadic) = self.parse_function_params()?;
+        // Parameter names of a declaration are not symbols anything can refer to
+        self.forget_symbols(params_start..self.pos);
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        if returns_fn_ptr {
+            self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+            return_type = self.parse_function_pointer_type(return_type)?;
+        }
         
         // Skip post-declaration attributes
         while self.check(|t| matches!(t, Token::Attribute | Token::Extension)) {
@@ -453,7 +532,7 @@ impl<'a> DeclarationParser for Parser<'a> {
         })
     }
 
-    fn parse_forward_struct_name(&mut self) -> Result<String, String> {
+    fn parse_forward_struct_name(&mut self) -> Result<String, CompileError> {
         self.advance(); // skip 'struct'
         
         // Skip attributes
@@ -466,14 +545,14 @@ impl<'a> DeclarationParser for Parser<'a> {
         
         let name = match self.advance() {
             Some(Token::Identifier { value }) => value.clone(),
-            other => return Err(format!("expected struct name, found {:?}", other)),
+            other => return Err(syntax_error(format!("expected struct name, found {:?}", other))),
         };
         
         self.expect(|t| matches!(t, Token::Semicolon), "';'")?;
         Ok(name)
     }
 
-    fn parse_function_params(&mut self) -> Result<(Vec<(model::Type, String)>, bool), String> {
+    fn parse_function_params(&mut self) -> Result<(Vec<(model::Type, String)>, bool), CompileError> {
         let mut params = Vec::new();
         let mut is_variadic = false;
 
@@ -487,7 +566,7 @@ impl<'a> DeclarationParser for Parser<'a> {
                 break;
             }
 
-            let mut p_type = self.parse_type()?;
+            let p_type = self.parse_type()?;
             
             // Handle (void)
             if matches!(p_type, model::Type::Void) && self.check(|t| matches!(t, Token::CloseParenthesis)) {
@@ -495,26 +574,14 @@ impl<'a> DeclarationParser for Parser<'a> {
             }
 
             // Parameter name is optional in prototypes
-            let p_name = if let Some(Token::Identifier { value }) = self.peek() {
-                let name = value.clone();
-                self.advance();
-                name
-            } else {
-                "".to_string()
+            let (p_type, p_name) = self.parse_param_declarator(p_type)?;
+            let p_name = match p_name {
+                Some((pos, name)) => {
+                    self.note_symbol(pos, Occurrence::Def(SymbolKind::Parameter, None));
+                    name
+                }
+                None => "".to_string(),
             };
-            
-            // Handle array syntax in function parameters: type name[] (supports multi-dimensional)
-            while self.match_token(|t| matches!(t, Token::OpenBracket)) {
-                // Check if array size is provided (empty brackets [] are common for params)
-                let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
-                    0 // Use 0 to represent unsized array
-                } else {
-                    self.parse_array_size()?
-                };
-                self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                p_type = model::Type::Array(Box::new(p_type), size);
-            }
-            
             params.push((p_type, p_name));
 
             if !self.match_token(|t| matches!(t, Token::Comma)) {
@@ -525,7 +592,7 @@ impl<'a> DeclarationParser for Parser<'a> {
         Ok((params, is_variadic))
     }
 
-    fn parse_globals(&mut self) -> Result<Vec<GlobalVar>, String> {
+    fn parse_globals(&mut self) -> Result<Vec<GlobalVar>, CompileError> {
         // Parse attributes before the type
         let mut attributes = self.parse_attributes()?;
         
@@ -538,8 +605,8 @@ impl<'a> DeclarationParser for Parser<'a> {
                 match &self.tokens[peek_pos] {
                     Token::Extern => { is_extern = true; peek_pos += 1; }
                     Token::Static => { is_static = true; peek_pos += 1; }
-                    Token::Const | Token::Volatile | Token::Restrict | Token::Inline => { peek_pos += 1; }
-                    Token::Attribute | Token::Extension => {
+                    Token::Const | Token::Volatile | Token::Restrict | Token::Inline | Token::ThreadLocal | Token::Atomic => { peek_pos += 1; }
+                    Token::Attribute | Token::Extension | Token::Alignas => {
                         peek_pos += 1;
                         // Skip attribute parens
                         if peek_pos < self.tokens.len() && matches!(&self.tokens[peek_pos], Token::OpenParenthesis) {
@@ -573,14 +640,17 @@ impl<'a> DeclarationParser for Parser<'a> {
         let mut globals = Vec::new(); // Explicit type annotation
 
         loop {
+            let name_span = self.span_at(self.pos);
+            self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Global, None));
             let name = match self.advance() {
                 Some(Token::Identifier { value }) => value.clone(),
-                other => return Err(format!("expected identifier after type, found {:?}", other)),
+                other => return Err(syntax_error(format!("expected identifier after type, found {:?}", other))),
             };
 
             let mut var_type = base_type.clone();
 
             // Check for array (supports multi-dimensional)
+            let mut dims = Vec::new();
             while self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 // Check if array size is provided (empty brackets [] are allowed for externs/params)
                 let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
@@ -589,8 +659,9 @@ impl<'a> DeclarationParser for Parser<'a> {
                     self.parse_array_size()?
                 };
                 self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                var_type = model::Type::Array(Box::new(var_type), size);
+                dims.push(size);
             }
+            var_type = array_of(var_type, &dims);
 
             let init = if self.match_token(|t| matches!(t, Token::Equal)) {
                 if self.check(|t| matches!(t, Token::OpenBrace)) {
@@ -607,7 +678,9 @@ impl<'a> DeclarationParser for Parser<'a> {
             // Infer array size from initializer
             if let model::Type::Array(inner, 0) = &var_type {
                 if let Some(model::Expr::StringLiteral(s)) = &init {
-                    var_type = model::Type::Array(inner.clone(), s.len() + 1);
+                    var_type = model::Type::Array(inner.clone(), s.chars().count() + 1);
+                } else if let Some(model::Expr::WideStringLiteral(s, encoding)) = &init {
+                    var_type = model::Type::Array(inner.clone(), encoding.encode(s).len() + 1);
                 } else if let Some(model::Expr::InitList(items)) = &init {
                     var_type = model::Type::Array(inner.clone(), items.len());
                 }
@@ -621,6 +694,7 @@ impl<'a> DeclarationParser for Parser<'a> {
                 attributes: attributes.clone(),
                 is_extern,
                 is_static,
+                span: name_span,
             });
 
             if !self.match_token(|t| matches!(t, Token::Comma)) {
@@ -633,19 +707,21 @@ impl<'a> DeclarationParser for Parser<'a> {
     }
 
     /// Parse _Static_assert(expr, "message") or _Static_assert(expr)
-    /// The _Static_assert token has already been consumed.
-    fn parse_static_assert(&mut self) -> Result<(), String> {
+    /// The _Static_assert token has already been consumed. The condition is
+    /// evaluated by semantic analysis, which knows every struct layout.
+    fn parse_static_assert(&mut self) -> Result<model::StaticAssert, CompileError> {
+        let start = self.pos - 1;
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         
         // Parse the constant expression
-        let expr = self.parse_assignment()?;
+        let condition = self.parse_assignment()?;
         
         // Check if there's a message (optional in C23)
-        let _message = if self.match_token(|t| matches!(t, Token::Comma)) {
+        let message = if self.match_token(|t| matches!(t, Token::Comma)) {
             // Consume the string literal message
             match self.advance() {
-                Some(Token::StringLiteral { value }) => Some(value.clone()),
-                _ => return Err("Expected string literal in _Static_assert".to_string()),
+                Some(Token::StringLiteral { value, .. }) => Some(value.clone()),
+                _ => return Err(syntax_error("Expected string literal in _Static_assert")),
             }
         } else {
             None
@@ -653,16 +729,7 @@ impl<'a> DeclarationParser for Parser<'a> {
         
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
         self.expect(|t| matches!(t, Token::Semicolon), "';'")?;
-        
-        // Evaluate the expression at compile time if it's a constant
-        // For non-constant expressions, we skip the check (best-effort)
-        if let model::Expr::Constant(val) = &expr {
-            if *val == 0 {
-                let msg = _message.unwrap_or_else(|| "static assertion failed".to_string());
-                return Err(format!("_Static_assert failed: {}", msg));
-            }
-        }
-        
-        Ok(())
+        let span = self.span_since(start);
+        Ok(model::StaticAssert { condition, message, span })
     }
 }
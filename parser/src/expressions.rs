@@ -1,18 +1,26 @@
-use model::{BinaryOp, Expr, Token, Type, UnaryOp};
-use crate::parser::Parser;
+use model::consteval::{eval_integer_constant, ConstContext};
+use model::{BinaryOp, CompileError, ErrorCode, Expr, Token, Type, TypeLayout, UnaryOp};
+use crate::parser::{syntax_error, Parser};
 use crate::types::TypeParser;
 use crate::statements::StatementParser;
 use crate::utils::ParserUtils;
+use crate::symbols::Occurrence;
 
 /// Expression parsing functionality using precedence climbing
 pub(crate) trait ExpressionParser {
-    fn parse_expr(&mut self) -> Result<Expr, String>;
+    fn parse_expr(&mut self) -> Result<Expr, CompileError>;
     /// Parse a constant expression and evaluate it to a usize (for array sizes)
-    fn parse_array_size(&mut self) -> Result<usize, String>;
+    fn parse_array_size(&mut self) -> Result<usize, CompileError>;
+    /// Parse an integer constant expression and evaluate it; names of enum
+    /// constants declared so far may appear in it
+    fn parse_constant_expr(&mut self) -> Result<i64, CompileError>;
+    /// Parse the parenthesized operand of `_Alignas` (a type or a constant
+    /// expression) and return the alignment in bytes
+    fn parse_alignas(&mut self) -> Result<usize, CompileError>;
 }
 
 impl<'a> ExpressionParser for Parser<'a> {
-    fn parse_expr(&mut self) -> Result<Expr, String> {
+    fn parse_expr(&mut self) -> Result<Expr, CompileError> {
         let first = self.parse_assignment()?;
         if self.check(|t| matches!(t, Token::Comma)) {
             let mut exprs = vec![first];
@@ -25,94 +33,87 @@ impl<'a> ExpressionParser for Parser<'a> {
         }
     }
     
-    fn parse_array_size(&mut self) -> Result<usize, String> {
+    fn parse_array_size(&mut self) -> Result<usize, CompileError> {
+        let size = self.parse_constant_expr()?;
+        usize::try_from(size).map_err(|_| CompileError::new(ErrorCode::NotConstant, format!("size of array is negative ({})", size)))
+    }
+
+    fn parse_constant_expr(&mut self) -> Result<i64, CompileError> {
         let expr = self.parse_conditional()?;
-        const_eval_expr(&expr)
-            .map(|v| v as usize)
-            .ok_or_else(|| format!("expected constant array size expression, got {:?}", expr))
+        eval_integer_constant(&expr, self)
+    }
+
+    fn parse_alignas(&mut self) -> Result<usize, CompileError> {
+        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+        let align = if self.check_is_type() {
+            let ty = self.parse_type()?;
+            self.layout().align_of(&ConstContext::resolve_type(self, &ty))
+        } else {
+            self.parse_array_size()?
+        };
+        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        Ok(align)
     }
 }
 
-/// Evaluate a constant expression at compile time (for array sizes, etc.)
-fn const_eval_expr(expr: &Expr) -> Option<i64> {
-    match expr {
-        Expr::Constant(v) => Some(*v),
-        Expr::SizeOf(ty) => Some(const_sizeof(ty)),
-        Expr::AlignOf(ty) => Some(const_alignof(ty)),
-        Expr::Cast(_, inner) => const_eval_expr(inner),
-        Expr::Unary { op, expr } => {
-            let v = const_eval_expr(expr)?;
-            match op {
-                UnaryOp::Minus => Some(-v),
-                UnaryOp::BitwiseNot => Some(!v),
-                UnaryOp::LogicalNot => Some(if v == 0 { 1 } else { 0 }),
-                _ => None,
-            }
-        }
-        Expr::Binary { left, op, right } => {
-            let l = const_eval_expr(left)?;
-            let r = const_eval_expr(right)?;
-            match op {
-                BinaryOp::Add => Some(l + r),
-                BinaryOp::Sub => Some(l - r),
-                BinaryOp::Mul => Some(l * r),
-                BinaryOp::Div => if r != 0 { Some(l / r) } else { None },
-                BinaryOp::Mod => if r != 0 { Some(l % r) } else { None },
-                BinaryOp::ShiftLeft => if r >= 0 && r < 64 { Some(l << r) } else { None },
-                BinaryOp::ShiftRight => if r >= 0 && r < 64 { Some(l >> r) } else { None },
-                BinaryOp::BitwiseAnd => Some(l & r),
-                BinaryOp::BitwiseOr => Some(l | r),
-                BinaryOp::BitwiseXor => Some(l ^ r),
-                BinaryOp::Less => Some(if l < r { 1 } else { 0 }),
-                BinaryOp::LessEqual => Some(if l <= r { 1 } else { 0 }),
-                BinaryOp::Greater => Some(if l > r { 1 } else { 0 }),
-                BinaryOp::GreaterEqual => Some(if l >= r { 1 } else { 0 }),
-                BinaryOp::EqualEqual => Some(if l == r { 1 } else { 0 }),
-                BinaryOp::NotEqual => Some(if l != r { 1 } else { 0 }),
-                _ => None,
-            }
-        }
-        Expr::Conditional { condition, then_expr, else_expr } => {
-            let cond = const_eval_expr(condition)?;
-            if cond != 0 {
-                const_eval_expr(then_expr)
-            } else {
-                const_eval_expr(else_expr)
-            }
+/// What the parser knows while it is still reading the file: the enum
+/// constants, typedefs, and aggregates declared so far.
+impl ConstContext for Parser<'_> {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.enum_constants.get(name).copied()
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        match ConstContext::resolve_type(self, ty) {
+            Type::Void => None,
+            Type::Struct(name) if !self.struct_defs.contains_key(&name) => None,
+            Type::Union(name) if !self.union_defs.contains_key(&name) => None,
+            Type::Struct(name) | Type::Union(name) if self.layout().self_containing_field(&name).is_some() => None,
+            ty => Some(self.layout().size_of(&ty)),
         }
-        _ => None,
     }
-}
 
-/// Compile-time sizeof for common types (used in constant expressions)
-fn const_sizeof(ty: &Type) -> i64 {
-    match ty {
-        Type::Char | Type::UnsignedChar | Type::Bool => 1,
-        Type::Short | Type::UnsignedShort => 2,
-        Type::Int | Type::UnsignedInt | Type::Float => 4,
-        Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong
-            | Type::Double | Type::Pointer(_, ..) | Type::FunctionPointer { .. } => 8,
-        Type::Array(inner, n) => const_sizeof(inner) * (*n as i64),
-        Type::Void => 1, // GCC extension
-        _ => 4, // Default fallback
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        // Only complete types have an alignment
+        self.size_of(ty)?;
+        Some(self.layout().align_of(&ConstContext::resolve_type(self, ty)))
     }
-}
 
-/// Compile-time alignof for common types
-fn const_alignof(ty: &Type) -> i64 {
-    match ty {
-        Type::Char | Type::UnsignedChar | Type::Bool => 1,
-        Type::Short | Type::UnsignedShort => 2,
-        Type::Int | Type::UnsignedInt | Type::Float => 4,
-        Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong
-            | Type::Double | Type::Pointer(_, ..) | Type::FunctionPointer { .. } => 8,
-        _ => 4,
+    fn resolve_type(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Typedef(name) => self.typedef_defs.get(name).map_or_else(|| ty.clone(), |t| ConstContext::resolve_type(self, t)),
+            other => other.clone(),
+        }
     }
 }
 
 impl<'a> Parser<'a> {
+    fn layout(&self) -> TypeLayout<'_> {
+        TypeLayout::with_typedefs(&self.struct_defs, &self.union_defs, &self.typedef_defs)
+    }
+
+    /// Run `parse` one expression nesting level deeper. Past `max_expr_depth`
+    /// levels this fails instead of recursing further, so a pathological
+    /// expression is a diagnostic rather than a stack overflow. The error is
+    /// also kept in `fatal`, since top-level recovery would otherwise skip it.
+    pub(crate) fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, CompileError>) -> Result<T, CompileError> {
+        if self.expr_depth >= self.max_expr_depth {
+            let error = CompileError::new(
+                ErrorCode::NestingTooDeep,
+                format!("expression too deeply nested (limit is {} levels)", self.max_expr_depth),
+            )
+            .or_span(self.error_span());
+            self.fatal.get_or_insert_with(|| error.clone());
+            return Err(error);
+        }
+        self.expr_depth += 1;
+        let result = parse(self);
+        self.expr_depth -= 1;
+        result
+    }
+
     // Assignment (lowest precedence)
-    pub(crate) fn parse_assignment(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_assignment(&mut self) -> Result<Expr, CompileError> {
         let left = self.parse_conditional()?;
 
         if self.check(|t| matches!(t, Token::Equal 
@@ -136,31 +137,20 @@ impl<'a> Parser<'a> {
                 _ => unreachable!(),
             };
 
-            match left {
-                Expr::Variable(_)
-                | Expr::Index { .. }
-                | Expr::Member { .. }
-                | Expr::PtrMember { .. }
-                | Expr::Unary {
-                    op: UnaryOp::Deref,
-                    ..
-                } => {
-                    let right = self.parse_assignment()?;
-                    Ok(Expr::Binary {
-                        left: Box::new(left),
-                        op,
-                        right: Box::new(right),
-                    })
-                }
-                _ => Err(format!("invalid assignment target: {:?}", left)),
-            }
+            // Whether the target is an lvalue is checked by semantic analysis
+            let right = self.nested(Self::parse_assignment)?;
+            Ok(Expr::Binary {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            })
         } else {
             Ok(left)
         }
     }
 
     // Conditional/Ternary (? :) operator
-    pub(crate) fn parse_conditional(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_conditional(&mut self) -> Result<Expr, CompileError> {
         let condition = self.parse_logical_or()?;
 
         if self.match_token(|t| matches!(t, Token::Question)) {
@@ -173,7 +163,7 @@ impl<'a> Parser<'a> {
                 self.parse_expr()?
             };
             self.expect(|t| matches!(t, Token::Colon), "':' in conditional expression")?;
-            let else_expr = self.parse_conditional()?;
+            let else_expr = self.nested(Self::parse_conditional)?;
             Ok(Expr::Conditional {
                 condition: Box::new(condition),
                 then_expr: Box::new(then_expr),
@@ -185,7 +175,7 @@ impl<'a> Parser<'a> {
     }
 
     // Logical OR
-    pub(crate) fn parse_logical_or(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_logical_or(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_logical_and()?;
         while self.match_token(|t| matches!(t, Token::OrOr)) {
             let right = self.parse_logical_and()?;
@@ -199,7 +189,7 @@ impl<'a> Parser<'a> {
     }
 
     // Logical AND
-    pub(crate) fn parse_logical_and(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_logical_and(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_bitwise_or()?;
         while self.match_token(|t| matches!(t, Token::AndAnd)) {
             let right = self.parse_bitwise_or()?;
@@ -213,7 +203,7 @@ impl<'a> Parser<'a> {
     }
 
     // Bitwise OR
-    pub(crate) fn parse_bitwise_or(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_bitwise_or(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_bitwise_xor()?;
         while self.match_token(|t| matches!(t, Token::Pipe)) {
             let right = self.parse_bitwise_xor()?;
@@ -227,7 +217,7 @@ impl<'a> Parser<'a> {
     }
 
     // Bitwise XOR
-    pub(crate) fn parse_bitwise_xor(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_bitwise_xor(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_bitwise_and()?;
         while self.match_token(|t| matches!(t, Token::Caret)) {
             let right = self.parse_bitwise_and()?;
@@ -241,7 +231,7 @@ impl<'a> Parser<'a> {
     }
 
     // Bitwise AND
-    pub(crate) fn parse_bitwise_and(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_bitwise_and(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_equality()?;
         while self.match_token(|t| matches!(t, Token::Ampersand)) {
             let right = self.parse_equality()?;
@@ -255,7 +245,7 @@ impl<'a> Parser<'a> {
     }
 
     // Equality (== !=)
-    pub(crate) fn parse_equality(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_equality(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_relational()?;
 
         while self.match_token(|t| matches!(t, Token::EqualEqual | Token::BangEqual)) {
@@ -276,7 +266,7 @@ impl<'a> Parser<'a> {
     }
 
     // Relational (< <= > >=)
-    pub(crate) fn parse_relational(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_relational(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_shift()?;
         while self.match_token(|t| {
             matches!(
@@ -302,7 +292,7 @@ impl<'a> Parser<'a> {
     }
 
     // Shift (<< >>)
-    pub(crate) fn parse_shift(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_shift(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_additive()?;
         while self.match_token(|t| matches!(t, Token::LessLess | Token::GreaterGreater)) {
             let op = match self.previous().unwrap() {
@@ -321,7 +311,7 @@ impl<'a> Parser<'a> {
     }
 
     // Additive (+ -)
-    pub(crate) fn parse_additive(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_additive(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_multiplicative()?;
 
         while self.match_token(|t| matches!(t, Token::Plus | Token::Minus)) {
@@ -342,7 +332,7 @@ impl<'a> Parser<'a> {
     }
 
     // Multiplicative (* / %)
-    pub(crate) fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_multiplicative(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_unary()?;
 
         while self.match_token(|t| matches!(t, Token::Star | Token::Slash | Token::Percent)) {
@@ -364,20 +354,22 @@ impl<'a> Parser<'a> {
     }
 
     // Unary (+ - ! ~ * & sizeof cast)
-    pub(crate) fn parse_unary(&mut self) -> Result<Expr, String> {
-        // GCC label address: &&label
-        if self.check(|t| matches!(t, Token::Ampersand))
-            && self.check_at(1, |t| matches!(t, Token::Ampersand))
-        {
-            self.advance();
-            self.advance();
+    /// Unary expressions are where parenthesized, cast, and prefix-operator
+    /// nesting recurses, so each counts as one level of `nested()`.
+    pub(crate) fn parse_unary(&mut self) -> Result<Expr, CompileError> {
+        self.nested(Self::parse_unary_inner)
+    }
+
+    fn parse_unary_inner(&mut self) -> Result<Expr, CompileError> {
+        // GCC label address: &&label (the lexer reads `&&` as one token)
+        if self.match_token(|t| matches!(t, Token::AndAnd)) {
             let label = match self.advance() {
                 Some(Token::Identifier { value }) => value.clone(),
                 other => {
-                    return Err(format!(
+                    return Err(syntax_error(format!(
                         "expected label name after '&&', found {:?}",
                         other
-                    ))
+                    )))
                 }
             };
             return Ok(Expr::LabelAddr(label));
@@ -417,6 +409,15 @@ impl<'a> Parser<'a> {
             // Prefix decrement
             let expr = self.parse_unary()?;
             Ok(Expr::PrefixDecrement(Box::new(expr)))
+        } else if self.match_token(|t| matches!(t, Token::Extension)) {
+            // `__extension__ expr` only silences pedantic warnings in GCC
+            self.parse_unary()
+        } else if self.match_token(|t| matches!(t, Token::RealPart)) {
+            let expr = self.parse_unary()?;
+            Ok(Expr::Real(Box::new(expr)))
+        } else if self.match_token(|t| matches!(t, Token::ImagPart)) {
+            let expr = self.parse_unary()?;
+            Ok(Expr::Imag(Box::new(expr)))
         } else if self.match_token(|t| matches!(t, Token::SizeOf)) {
             self.parse_sizeof()
         } else if self.match_token(|t| matches!(t, Token::AlignOf)) {
@@ -447,7 +448,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_sizeof(&mut self) -> Result<Expr, String> {
+    fn parse_sizeof(&mut self) -> Result<Expr, CompileError> {
         if self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
             if self.check_is_type() {
                 let ty = self.parse_type()?;
@@ -464,7 +465,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_alignof(&mut self) -> Result<Expr, String> {
+    fn parse_alignof(&mut self) -> Result<Expr, CompileError> {
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         let ty = self.parse_type()?;
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
@@ -472,7 +473,7 @@ impl<'a> Parser<'a> {
     }
 
     // Postfix ([] () . ->)
-    pub(crate) fn parse_postfix(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_postfix(&mut self) -> Result<Expr, CompileError> {
         let mut expr = self.parse_primary()?;
 
         loop {
@@ -503,9 +504,10 @@ impl<'a> Parser<'a> {
                 };
             } else if self.match_token(|t| matches!(t, Token::Dot)) {
                 // Struct member access
+                self.note_symbol(self.pos, Occurrence::MemberRef);
                 let member = match self.advance() {
                     Some(Token::Identifier { value }) => value.clone(),
-                    other => return Err(format!("expected member name after '.', found {:?}", other)),
+                    other => return Err(syntax_error(format!("expected member name after '.', found {:?}", other))),
                 };
                 expr = Expr::Member {
                     expr: Box::new(expr),
@@ -513,10 +515,11 @@ impl<'a> Parser<'a> {
                 };
             } else if self.match_token(|t| matches!(t, Token::Arrow)) {
                 // Pointer member access
+                self.note_symbol(self.pos, Occurrence::MemberRef);
                 let member = match self.advance() {
                     Some(Token::Identifier { value }) => value.clone(),
                     other => {
-                        return Err(format!("expected member name after '->', found {:?}", other))
+                        return Err(syntax_error(format!("expected member name after '->', found {:?}", other)))
                     }
                 };
                 expr = Expr::PtrMember {
@@ -538,93 +541,24 @@ impl<'a> Parser<'a> {
     }
 
     // Primary (literals, identifiers, parenthesized expressions)
-    pub(crate) fn parse_primary(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_primary(&mut self) -> Result<Expr, CompileError> {
         match self.advance() {
             Some(Token::Identifier { value }) => {
-                // Handle GCC builtins at parse time
-                match value.as_str() {
-                    "__builtin_expect" | "__builtin_expect_with_probability" => {
-                        // Preserve hint for IR/block layout (likely/unlikely macros).
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let expr = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let expected = self.parse_assignment()?;
-                        if self.check(|t| matches!(t, Token::Comma)) {
-                            self.advance();
-                            let _ = self.parse_assignment()?;
-                        }
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        Ok(Expr::Expect {
-                            expr: Box::new(expr),
-                            expected: Box::new(expected),
-                        })
-                    }
-                    "__builtin_constant_p" => {
-                        // __builtin_constant_p(expr) → 1 if expr is a compile-time constant, 0 otherwise
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let expr = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        let is_const = Self::is_constant_expr(&expr);
-                        Ok(Expr::Constant(if is_const { 1 } else { 0 }))
-                    }
-                    "__builtin_va_arg" => {
-                        // __builtin_va_arg(ap, type) — extract next arg from va_list
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let list_expr = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let ty = self.parse_type()?;
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        Ok(Expr::VaArg {
-                            list: Box::new(list_expr),
-                            r#type: ty,
-                        })
-                    }
-                    "__builtin_offsetof" => {
-                        // __builtin_offsetof(type, member) → constant offset
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let ty = self.parse_type()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let member = match self.advance() {
-                            Some(Token::Identifier { value }) => value.clone(),
-                            other => return Err(format!("expected member name in __builtin_offsetof, found {:?}", other)),
-                        };
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        Ok(Expr::BuiltinOffsetof { r#type: ty, member })
-                    }
-                    "__builtin_types_compatible_p" => {
-                        // __builtin_types_compatible_p(type1, type2) → 1 if compatible, 0 otherwise
-                        // Used in kernel's __same_type() macro
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let type1 = self.parse_type()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let type2 = self.parse_type()?;
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        let compatible = if type1 == type2 { 1 } else { 0 };
-                        Ok(Expr::Constant(compatible))
-                    }
-                    "__builtin_choose_expr" => {
-                        // __builtin_choose_expr(const_expr, expr1, expr2)
-                        // → expr1 if const_expr is nonzero, expr2 otherwise
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let cond = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let expr1 = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::Comma), "','")?;
-                        let expr2 = self.parse_assignment()?;
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        // Evaluate at compile time
-                        match cond {
-                            Expr::Constant(v) if v != 0 => Ok(expr1),
-                            Expr::Constant(_) => Ok(expr2),
-                            _ => Ok(expr1), // Default to first if not constant
-                        }
-                    }
-                    _ => Ok(Expr::Variable(value.clone())),
+                let name = value.clone();
+                if let Some(expr) = self.parse_builtin(&name)? {
+                    return Ok(expr);
                 }
+                self.note_symbol(self.pos - 1, Occurrence::Ref);
+                Ok(Expr::Variable(name))
             }
-            Some(Token::Constant { value, .. }) => Ok(Expr::Constant(*value)),
+            Some(Token::Constant { value, suffix }) => Ok(match suffix.constant_type(*value) {
+                Type::Int => Expr::Constant(*value),
+                ty => Expr::TypedConstant(*value, ty),
+            }),
             Some(Token::FloatLiteral { value }) => Ok(Expr::FloatConstant(*value)),
-            Some(Token::StringLiteral { value }) => Ok(Expr::StringLiteral(value.clone())),
+            Some(Token::ImaginaryLiteral { value }) => Ok(Expr::ImaginaryConstant(*value)),
+            Some(Token::StringLiteral { value, encoding }) if encoding.is_narrow() => Ok(Expr::StringLiteral(value.clone())),
+            Some(Token::StringLiteral { value, encoding }) => Ok(Expr::WideStringLiteral(value.clone(), *encoding)),
             Some(Token::OpenParenthesis) => {
                 // Check for statement expression: ({ ... })
                 if self.check(|t| matches!(t, Token::OpenBrace)) {
@@ -637,54 +571,179 @@ impl<'a> Parser<'a> {
                 self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
                 Ok(expr)
             }
-            Some(Token::Generic) => {
-                // _Generic(controlling_expr, type: expr, type: expr, ..., default: expr)
+            Some(Token::Generic) => self.parse_generic(),
+            other => Err(syntax_error(format!("expected expression, found {:?}", other))),
+        }
+    }
+
+    /// GCC builtins and `<complex.h>` accessors handled at parse time, after
+    /// their name has been consumed. `None` for any other identifier. Kept out
+    /// of `parse_primary` so its stack frame, paid at every nesting level,
+    /// stays small.
+    fn parse_builtin(&mut self, name: &str) -> Result<Option<Expr>, CompileError> {
+        let expr = match name {
+            "__builtin_expect" | "__builtin_expect_with_probability" => {
+                // Preserve hint for IR/block layout (likely/unlikely macros).
                 self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                let ctrl_expr = self.parse_assignment()?;
+                let expr = self.parse_assignment()?;
                 self.expect(|t| matches!(t, Token::Comma), "','")?;
-                
-                let mut associations: Vec<(Option<Type>, Expr)> = Vec::new();
-                
-                loop {
-                    if self.check(|t| matches!(t, Token::CloseParenthesis)) {
-                        break;
-                    }
-                    
-                    if self.check(|t| matches!(t, Token::Default)) {
-                        self.advance();
-                        self.expect(|t| matches!(t, Token::Colon), "':'")?;
-                        let expr = self.parse_assignment()?;
-                        associations.push((None, expr));
-                    } else if self.check_is_type() {
-                        let ty = self.parse_type()?;
-                        self.expect(|t| matches!(t, Token::Colon), "':'")?;
-                        let expr = self.parse_assignment()?;
-                        associations.push((Some(ty), expr));
-                    } else {
-                        self.advance();
-                    }
-                    
-                    if !self.match_token(|t| matches!(t, Token::Comma)) {
-                        break;
-                    }
+                let expected = self.parse_assignment()?;
+                if self.check(|t| matches!(t, Token::Comma)) {
+                    self.advance();
+                    let _ = self.parse_assignment()?;
+                }
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                Ok(Expr::Expect {
+                    expr: Box::new(expr),
+                    expected: Box::new(expected),
+                })
+            }
+            "__builtin_constant_p" => {
+                // __builtin_constant_p(expr) → 1 if expr is a compile-time constant, 0 otherwise
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let expr = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                let is_const = Self::is_constant_expr(&expr);
+                Ok(Expr::Constant(if is_const { 1 } else { 0 }))
+            }
+            "__builtin_va_arg" => {
+                // __builtin_va_arg(ap, type) — extract next arg from va_list
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let list_expr = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let ty = self.parse_type()?;
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                Ok(Expr::VaArg {
+                    list: Box::new(list_expr),
+                    r#type: ty,
+                })
+            }
+            "__builtin_offsetof" => {
+                // __builtin_offsetof(type, member) → constant offset
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let ty = self.parse_type()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let member = match self.advance() {
+                    Some(Token::Identifier { value }) => value.clone(),
+                    other => return Err(syntax_error(format!("expected member name in __builtin_offsetof, found {:?}", other))),
+                };
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                Ok(Expr::BuiltinOffsetof { r#type: ty, member })
+            }
+            "__builtin_types_compatible_p" => {
+                // __builtin_types_compatible_p(type1, type2) → 1 if compatible, 0 otherwise
+                // Used in kernel's __same_type() macro
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let type1 = self.parse_type()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let type2 = self.parse_type()?;
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                let compatible = if type1 == type2 { 1 } else { 0 };
+                Ok(Expr::Constant(compatible))
+            }
+            "__builtin_choose_expr" => {
+                // __builtin_choose_expr(const_expr, expr1, expr2)
+                // → expr1 if const_expr is nonzero, expr2 otherwise
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let cond = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let expr1 = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let expr2 = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                // Evaluate at compile time
+                match cond {
+                    Expr::Constant(v) | Expr::TypedConstant(v, _) if v != 0 => Ok(expr1),
+                    Expr::Constant(_) | Expr::TypedConstant(..) => Ok(expr2),
+                    _ => Ok(expr1), // Default to first if not constant
                 }
-                
+            }
+            // <complex.h> accessors are builtins, as in GCC
+            "creal" | "crealf" | "__builtin_creal" | "__builtin_crealf"
+            | "cimag" | "cimagf" | "__builtin_cimag" | "__builtin_cimagf"
+            | "conj" | "conjf" | "__builtin_conj" | "__builtin_conjf" => {
+                if !self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
+                    return Ok(None);
+                }
+                let arg = Box::new(self.parse_assignment()?);
                 self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                
-                Ok(Expr::Generic {
-                    controlling: Box::new(ctrl_expr),
-                    associations,
+                Ok(if name.contains("creal") {
+                    Expr::Real(arg)
+                } else if name.contains("cimag") {
+                    Expr::Imag(arg)
+                } else {
+                    // GCC spells complex conjugation as `~z`
+                    Expr::Unary { op: UnaryOp::BitwiseNot, expr: arg }
                 })
             }
-            other => Err(format!("expected expression, found {:?}", other)),
-        }
+            "__builtin_complex" => {
+                // __builtin_complex(re, im) → re + im * 1.0i
+                self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+                let re = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::Comma), "','")?;
+                let im = self.parse_assignment()?;
+                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                Ok(Expr::Binary {
+                    left: Box::new(re),
+                    op: BinaryOp::Add,
+                    right: Box::new(Expr::Binary {
+                        left: Box::new(im),
+                        op: BinaryOp::Mul,
+                        right: Box::new(Expr::ImaginaryConstant(1.0)),
+                    }),
+                })
+            }
+            _ => return Ok(None),
+        };
+        expr.map(Some)
     }
 
+    /// `_Generic(controlling_expr, type: expr, ..., default: expr)`, after
+    /// the `_Generic` keyword.
+    fn parse_generic(&mut self) -> Result<Expr, CompileError> {
+        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+        let ctrl_expr = self.parse_assignment()?;
+        self.expect(|t| matches!(t, Token::Comma), "','")?;
+        
+        let mut associations: Vec<(Option<Type>, Expr)> = Vec::new();
+        
+        loop {
+            if self.check(|t| matches!(t, Token::CloseParenthesis)) {
+                break;
+            }
+            
+            if self.check(|t| matches!(t, Token::Default)) {
+                self.advance();
+                self.expect(|t| matches!(t, Token::Colon), "':'")?;
+                let expr = self.parse_assignment()?;
+                associations.push((None, expr));
+            } else if self.check_is_type() {
+                let ty = self.parse_type()?;
+                self.expect(|t| matches!(t, Token::Colon), "':'")?;
+                let expr = self.parse_assignment()?;
+                associations.push((Some(ty), expr));
+            } else {
+                self.advance();
+            }
+            
+            if !self.match_token(|t| matches!(t, Token::Comma)) {
+                break;
+            }
+        }
+        
+        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        
+        Ok(Expr::Generic {
+            controlling: Box::new(ctrl_expr),
+            associations,
+        })
+        }
+
     /// Check if an expression is a compile-time constant (for __builtin_constant_p).
     fn is_constant_expr(expr: &Expr) -> bool {
         match expr {
-            Expr::Constant(_) | Expr::FloatConstant(_) => true,
-            Expr::StringLiteral(_) => true,
+            Expr::Constant(_) | Expr::TypedConstant(..) | Expr::FloatConstant(_) => true,
+            Expr::StringLiteral(_) | Expr::WideStringLiteral(..) => true,
             Expr::Unary { expr, .. } => Self::is_constant_expr(expr),
             Expr::Binary { left, right, .. } => {
                 Self::is_constant_expr(left) && Self::is_constant_expr(right)
@@ -0,0 +1,407 @@
+// Incremental reparsing for editors
+//
+// A `ParsedFile` keeps the source, tokens, and AST of one translation unit.
+// After a text edit, only the body of the function containing the edit is
+// re-lexed and re-parsed when that is safe; every other edit falls back to a
+// full reparse. Either way the result is the same as parsing the new text
+// from scratch.
+
+use std::collections::HashSet;
+
+use model::{Block, CompileError, ErrorCode, Expr, FileId, Program, Span, Stmt, Token};
+
+use crate::declarations::DeclarationParser;
+use crate::parser::Parser;
+use crate::statements::StatementParser;
+
+/// Replace the bytes `start..end` of the old source with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(start: usize, end: usize, text: impl Into<String>) -> Self {
+        TextEdit { start, end, text: text.into() }
+    }
+
+    /// Change in source length caused by the edit.
+    fn delta(&self) -> isize {
+        self.text.len() as isize - (self.end - self.start) as isize
+    }
+}
+
+/// What `ParsedFile::apply_edit` had to redo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reparse {
+    /// Only the body of `program.functions[index]` was re-parsed.
+    FunctionBody(usize),
+    /// The whole file was re-lexed and re-parsed.
+    Full,
+}
+
+/// A parsed translation unit that can be updated after text edits.
+#[derive(Debug, Clone)]
+pub struct ParsedFile {
+    pub file_id: FileId,
+    pub source: String,
+    pub tokens: Vec<Token>,
+    /// Source span of each token, parallel to `tokens`.
+    pub spans: Vec<Span>,
+    pub program: Program,
+    /// Every name the parser treated as a typedef by the end of the file.
+    typedef_names: HashSet<String>,
+    /// The typedef names that stand for function types.
+    function_typedefs: HashSet<String>,
+}
+
+impl ParsedFile {
+    /// Lex and parse `source` as file `file_id`.
+    pub fn parse(source: impl Into<String>, file_id: FileId) -> Result<Self, CompileError> {
+        let source = source.into();
+        let (tokens, spans): (Vec<Token>, Vec<Span>) = lexer::lex_spanned(&source, file_id)?
+            .into_iter()
+            .map(|t| (t.node, t.span))
+            .unzip();
+        let mut parser = Parser::with_spans(&tokens, &spans);
+        let program = parser.parse_program().map_err(|e| e.or_span(parser.error_span()))?;
+        let typedef_names = std::mem::take(&mut parser.typedefs);
+        let function_typedefs = std::mem::take(&mut parser.function_typedefs);
+        Ok(ParsedFile { file_id, source, tokens, spans, program, typedef_names, function_typedefs })
+    }
+
+    /// Apply `edit` to the source and bring the tokens and AST up to date.
+    /// On error the file is left unchanged.
+    pub fn apply_edit(&mut self, edit: &TextEdit) -> Result<Reparse, CompileError> {
+        if edit.start > edit.end
+            || edit.end > self.source.len()
+            || !self.source.is_char_boundary(edit.start)
+            || !self.source.is_char_boundary(edit.end)
+        {
+            return Err(CompileError::new(
+                ErrorCode::Internal,
+                format!("edit range {}..{} is not valid in a {}-byte source", edit.start, edit.end, self.source.len()),
+            ));
+        }
+        let mut source = self.source.clone();
+        source.replace_range(edit.start..edit.end, &edit.text);
+
+        if let Some(index) = self.try_reparse_body(&source, edit) {
+            self.source = source;
+            return Ok(Reparse::FunctionBody(index));
+        }
+        *self = ParsedFile::parse(source, self.file_id)?;
+        Ok(Reparse::Full)
+    }
+
+    /// Replace the bytes in `range` with `new_text` and reparse, as
+    /// `apply_edit` does; the entry point for an editor that reports changes
+    /// as a range and its replacement.
+    pub fn reparse(&mut self, range: std::ops::Range<usize>, new_text: &str) -> Result<Reparse, CompileError> {
+        self.apply_edit(&TextEdit::new(range.start, range.end, new_text))
+    }
+
+    /// Re-parse just the body of the function whose braces enclose `edit`.
+    /// Returns `None` when the edit cannot be handled locally.
+    fn try_reparse_body(&mut self, new_source: &str, edit: &TextEdit) -> Option<usize> {
+        let (index, open, close) = self.enclosing_body(edit)?;
+        // Typedefs inside a body change how the rest of the file parses
+        if self.tokens[open..close].iter().any(|t| matches!(t, Token::Typedef)) {
+            return None;
+        }
+        let delta = edit.delta();
+        let body_start = self.spans[open].end;
+        let body_end = self.spans[close].start.checked_add_signed(delta)?;
+        let region: Vec<(Token, Span)> = lexer::lex_spanned(new_source.get(body_start..body_end)?, self.file_id)
+            .ok()?
+            .into_iter()
+            .map(|t| (t.node, Span::new(self.file_id, t.span.start + body_start, t.span.end + body_start)))
+            .collect();
+        if region.iter().any(|(t, _)| matches!(t, Token::Typedef)) {
+            return None;
+        }
+
+        let shift = |span: Span| Span::new(span.file_id, shift_offset(span.start, delta), shift_offset(span.end, delta));
+        let mut tokens = vec![Token::OpenBrace];
+        let mut spans = vec![self.spans[open]];
+        for (token, span) in &region {
+            tokens.push(token.clone());
+            spans.push(*span);
+        }
+        tokens.push(Token::CloseBrace);
+        spans.push(shift(self.spans[close]));
+
+        let mut parser = Parser::with_spans(&tokens, &spans);
+        parser.typedefs = self.typedef_names.clone();
+        parser.typedef_defs = self.program.typedefs.clone();
+        parser.function_typedefs = self.function_typedefs.clone();
+        parser.struct_defs = self.program.structs.iter().map(|s| (s.name.clone(), s.clone())).collect();
+        parser.union_defs = self.program.unions.iter().map(|u| (u.name.clone(), u.clone())).collect();
+        let body = parser.parse_block().ok()?;
+        if !parser.is_at_end() {
+            // The edit unbalanced the braces
+            return None;
+        }
+        // Struct and union definitions in a body are the program's, so one
+        // in the old or the new body needs a full parse
+        if !parser.nested_structs.is_empty() || !parser.nested_unions.is_empty() || defines_tag(&self.tokens[open..close]) {
+            return None;
+        }
+
+        let (region_tokens, region_spans): (Vec<Token>, Vec<Span>) = region.into_iter().unzip();
+        self.tokens.splice(open + 1..close, region_tokens);
+        let tail = self.spans.split_off(close);
+        self.spans.truncate(open + 1);
+        self.spans.extend(region_spans);
+        self.spans.extend(tail.into_iter().map(shift));
+
+        let function = &mut self.program.functions[index];
+        function.body = body;
+        function.span.end = shift_offset(function.span.end, delta);
+        for f in &mut self.program.functions[index + 1..] {
+            f.span = shift(f.span);
+            shift_block(&mut f.body, &shift);
+        }
+        for g in &mut self.program.globals {
+            if g.span.start >= edit.end {
+                g.span = shift(g.span);
+            }
+        }
+        Some(index)
+    }
+
+    /// Index of the function whose body braces strictly enclose `edit`, with
+    /// the token positions of its `{` and `}`.
+    fn enclosing_body(&self, edit: &TextEdit) -> Option<(usize, usize, usize)> {
+        let (index, function) = self
+            .program
+            .functions
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.span.start <= edit.start && edit.end <= f.span.end)?;
+        let close = self.spans.iter().position(|s| s.end == function.span.end)?;
+        if !matches!(self.tokens[close], Token::CloseBrace) {
+            return None;
+        }
+        let mut depth = 0usize;
+        let mut open = None;
+        for i in (0..=close).rev() {
+            match self.tokens[i] {
+                Token::CloseBrace => depth += 1,
+                Token::OpenBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        open = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let open = open?;
+        (self.spans[open].end <= edit.start && edit.end <= self.spans[close].start).then_some((index, open, close))
+    }
+}
+
+/// Whether `tokens` hold a struct or union body, `struct S {` or `union {`.
+fn defines_tag(tokens: &[Token]) -> bool {
+    tokens.iter().enumerate().any(|(i, t)| {
+        matches!(t, Token::Struct | Token::Union)
+            && match tokens.get(i + 1) {
+                Some(Token::OpenBrace) => true,
+                Some(Token::Identifier { .. }) => matches!(tokens.get(i + 2), Some(Token::OpenBrace)),
+                _ => false,
+            }
+    })
+}
+
+fn shift_offset(offset: usize, delta: isize) -> usize {
+    offset.checked_add_signed(delta).unwrap_or(0)
+}
+
+/// Move the statement spans of a body that lies after the edit.
+fn shift_block(block: &mut Block, shift: &dyn Fn(Span) -> Span) {
+    for span in &mut block.spans {
+        *span = shift(*span);
+    }
+    for stmt in &mut block.statements {
+        shift_stmt(stmt, shift);
+    }
+}
+
+fn shift_stmt(stmt: &mut Stmt, shift: &dyn Fn(Span) -> Span) {
+    match stmt {
+        Stmt::Block(block) => shift_block(block, shift),
+        Stmt::If { cond, then_branch, else_branch } => {
+            shift_expr(cond, shift);
+            shift_stmt(then_branch, shift);
+            if let Some(else_branch) = else_branch {
+                shift_stmt(else_branch, shift);
+            }
+        }
+        Stmt::While { cond, body } | Stmt::DoWhile { body, cond } | Stmt::Switch { cond, body } => {
+            shift_expr(cond, shift);
+            shift_stmt(body, shift);
+        }
+        Stmt::For { init, cond, post, body } => {
+            if let Some(init) = init {
+                shift_stmt(init, shift);
+            }
+            for expr in cond.iter_mut().chain(post) {
+                shift_expr(expr, shift);
+            }
+            shift_stmt(body, shift);
+        }
+        Stmt::MultiDecl(stmts) => stmts.iter_mut().for_each(|s| shift_stmt(s, shift)),
+        Stmt::Return(Some(expr)) | Stmt::Expr(expr) | Stmt::Case(expr) | Stmt::Declaration { init: Some(expr), .. } => {
+            shift_expr(expr, shift)
+        }
+        Stmt::ComputedGoto(expr) => shift_expr(expr, shift),
+        Stmt::InlineAsm { outputs, inputs, .. } => {
+            outputs.iter_mut().chain(inputs).for_each(|op| shift_expr(&mut op.expr, shift))
+        }
+        _ => {}
+    }
+}
+
+/// Statement expressions are the only expressions that hold blocks.
+fn shift_expr(expr: &mut Expr, shift: &dyn Fn(Span) -> Span) {
+    match expr {
+        Expr::StmtExpr(stmts) => stmts.iter_mut().for_each(|s| shift_stmt(s, shift)),
+        Expr::Binary { left, right, .. }
+        | Expr::Index { array: left, index: right }
+        | Expr::Expect { expr: left, expected: right } => {
+            shift_expr(left, shift);
+            shift_expr(right, shift);
+        }
+        Expr::Unary { expr, .. }
+        | Expr::PostfixIncrement(expr)
+        | Expr::PostfixDecrement(expr)
+        | Expr::PrefixIncrement(expr)
+        | Expr::PrefixDecrement(expr)
+        | Expr::SizeOfExpr(expr)
+        | Expr::Cast(_, expr)
+        | Expr::Member { expr, .. }
+        | Expr::PtrMember { expr, .. }
+        | Expr::VaArg { list: expr, .. }
+        | Expr::Real(expr)
+        | Expr::Imag(expr) => shift_expr(expr, shift),
+        Expr::Call { func, args } => {
+            shift_expr(func, shift);
+            args.iter_mut().for_each(|a| shift_expr(a, shift));
+        }
+        Expr::Conditional { condition, then_expr, else_expr } => {
+            for e in [condition, then_expr, else_expr] {
+                shift_expr(e, shift);
+            }
+        }
+        Expr::Comma(exprs) => exprs.iter_mut().for_each(|e| shift_expr(e, shift)),
+        Expr::CompoundLiteral { init, .. } | Expr::InitList(init) => {
+            init.iter_mut().for_each(|item| shift_expr(&mut item.value, shift))
+        }
+        Expr::Generic { controlling, associations } => {
+            shift_expr(controlling, shift);
+            associations.iter_mut().for_each(|(_, e)| shift_expr(e, shift));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "int g = 1;\nint add(int a, int b) { return a + b; }\nint main() { return add(g, 2); }\n";
+
+    fn edit_at(src: &str, needle: &str, text: &str) -> TextEdit {
+        let start = src.find(needle).unwrap();
+        TextEdit::new(start, start + needle.len(), text)
+    }
+
+    fn assert_matches_full_parse(file: &ParsedFile) {
+        let full = ParsedFile::parse(file.source.clone(), file.file_id).unwrap();
+        assert_eq!(file.tokens, full.tokens);
+        assert_eq!(file.spans, full.spans);
+        assert_eq!(file.program, full.program);
+    }
+
+    #[test]
+    fn body_edit_reparses_one_function() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        let edit = edit_at(SRC, "a + b", "a * b - 1");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::FunctionBody(0));
+        assert!(file.source.contains("return a * b - 1;"));
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn reparse_replaces_a_range() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        let start = SRC.find("g, 2").unwrap();
+        assert_eq!(file.reparse(start..start + 1, "40").unwrap(), Reparse::FunctionBody(1));
+        assert!(file.source.contains("add(40, 2)"));
+        assert_matches_full_parse(&file);
+        assert!(file.reparse(0..file.source.len() + 1, "x").is_err());
+    }
+
+    #[test]
+    fn body_edit_shifts_statement_spans_of_later_functions() {
+        let src = "int f(void) { return 1; }\nint g(int n) { while (n) { n--; } return ({ int t = n; { t++; } t; }); }\n";
+        let mut file = ParsedFile::parse(src, FileId(0)).unwrap();
+        let edit = edit_at(src, "return 1;", "int x = 2; return x;");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::FunctionBody(0));
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn body_edit_resolves_array_and_function_typedefs() {
+        let src = "typedef int vec4[4];\ntypedef int handler(int);\nint main() { vec4 v; return 0; }\n";
+        let mut file = ParsedFile::parse(src, FileId(0)).unwrap();
+        let edit = edit_at(src, "vec4 v;", "vec4 v; handler *h;");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::FunctionBody(0));
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn tag_definition_in_body_falls_back_to_full_parse() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        let edit = edit_at(SRC, "return a + b;", "union P { int i; char c; } p; p.i = a; return p.c + b;");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::Full);
+        assert_eq!(file.program.unions.len(), 1);
+        assert_matches_full_parse(&file);
+
+        // Taking the definition out again drops it from the program
+        let source = file.source.clone();
+        let edit = edit_at(&source, "union P { int i; char c; } p; p.i = a; return p.c + b;", "return b;");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::Full);
+        assert!(file.program.unions.is_empty());
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn signature_edit_falls_back_to_full_parse() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        let edit = edit_at(SRC, "int b", "int c");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::Full);
+        assert_eq!(file.program.functions[0].params[1].1, "c");
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn unbalanced_body_edit_falls_back_to_full_parse() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        let edit = edit_at(SRC, "return a + b;", "return a; } int sub(int a, int b) { return a - b;");
+        assert_eq!(file.apply_edit(&edit).unwrap(), Reparse::Full);
+        assert_eq!(file.program.functions.len(), 3);
+        assert_matches_full_parse(&file);
+    }
+
+    #[test]
+    fn invalid_range_leaves_file_unchanged() {
+        let mut file = ParsedFile::parse(SRC, FileId(0)).unwrap();
+        assert!(file.apply_edit(&TextEdit::new(5, SRC.len() + 1, "")).is_err());
+        assert_eq!(file.source, SRC);
+    }
+}
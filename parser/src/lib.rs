@@ -5,6 +5,8 @@
 // - types.rs: Type parsing (int, void, struct, function pointers, etc.)
 // - expressions.rs: Expression parsing with precedence climbing
 // - statements.rs: Statement parsing (if, while, for, return, etc.)
+// - incremental.rs: ParsedFile, which re-parses only the edited function body
+// - symbols.rs: Symbol index of definitions and references for editor tooling
 
 mod parser;
 mod types;
@@ -13,11 +15,21 @@ mod statements;
 mod attributes;
 mod declarations;
 mod utils;
+mod incremental;
+mod symbols;
 
-use model::{Program, Token};
+pub use incremental::{ParsedFile, Reparse, TextEdit};
+pub use symbols::{index_symbols, Reference, Symbol, SymbolIndex, SymbolKind};
+
+use model::{CompileError, Program, Span, Token};
+use std::ops::Range;
 use parser::Parser;
 use declarations::DeclarationParser;
 
+/// Default limit on expression nesting (parentheses, unary operators, casts,
+/// and right-nested `=` / `?:`), like clang's `-fbracket-depth`.
+pub const DEFAULT_MAX_EXPR_DEPTH: usize = 256;
+
 /// Parse a list of tokens into a Program AST
 ///
 /// # Arguments
@@ -25,12 +37,54 @@ use declarations::DeclarationParser;
 ///
 /// # Returns
 /// * `Ok(Program)` - Successfully parsed program with functions, globals, and structs
-/// * `Err(String)` - Parse error with description
-pub fn parse_tokens(tokens: &[Token]) -> Result<Program, String> {
+/// * `Err(CompileError)` - Parse error with code and description
+pub fn parse_tokens(tokens: &[Token]) -> Result<Program, CompileError> {
     let mut parser = Parser::new(tokens);
     parser.parse_program()
 }
 
+/// Parse tokens with their source spans (parallel slices, as produced by
+/// `lexer::lex_spanned`). AST nodes get real spans, and an error carries the
+/// span of the token the parser stopped at.
+pub fn parse_tokens_with_spans(tokens: &[Token], spans: &[Span]) -> Result<Program, CompileError> {
+    parse_tokens_with_depth_limit(tokens, spans, DEFAULT_MAX_EXPR_DEPTH)
+}
+
+/// `parse_tokens_with_spans` with a custom expression nesting limit. An
+/// expression nested deeper is an `ErrorCode::NestingTooDeep` error.
+pub fn parse_tokens_with_depth_limit(tokens: &[Token], spans: &[Span], max_expr_depth: usize) -> Result<Program, CompileError> {
+    parse_tokens_with_options(tokens, spans, &ParseOptions { max_expr_depth, ..ParseOptions::default() })
+}
+
+/// Settings for `parse_tokens_with_options`.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Deepest expression nesting accepted.
+    pub max_expr_depth: usize,
+    /// Compile `static inline` / `extern inline` function definitions. When
+    /// off they are skipped like the inline wrappers of libc headers.
+    pub inline_functions: bool,
+    /// Byte ranges of the input that come from system headers (see
+    /// `lexer::system_header_ranges`). Inline definitions there are skipped
+    /// even with `inline_functions` on.
+    pub system_headers: Vec<Range<usize>>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self { max_expr_depth: DEFAULT_MAX_EXPR_DEPTH, inline_functions: true, system_headers: Vec::new() }
+    }
+}
+
+/// `parse_tokens_with_spans` with explicit `ParseOptions`.
+pub fn parse_tokens_with_options(tokens: &[Token], spans: &[Span], options: &ParseOptions) -> Result<Program, CompileError> {
+    let mut parser = Parser::with_spans(tokens, spans);
+    parser.max_expr_depth = options.max_expr_depth;
+    parser.compile_inline = options.inline_functions;
+    parser.system_headers = options.system_headers.clone();
+    parser.parse_program().map_err(|e| e.or_span(parser.error_span()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -185,6 +239,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_complex_type() {
+        let src = "int main() { double _Complex z = 1.0 + 2.0i; return __real__ z; }";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        let stmts = &program.functions[0].body.statements;
+        if let Stmt::Declaration { r#type, .. } = &stmts[0] {
+            assert_eq!(*r#type, model::Type::Complex(Box::new(model::Type::Double)));
+        } else {
+            panic!("Expected Declaration");
+        }
+        assert!(matches!(stmts[1], Stmt::Return(Some(model::Expr::Real(_)))));
+    }
+
     #[test]
     fn parse_bool_type() {
         let src = "int main() { _Bool b = 1; return b; }";
@@ -248,6 +316,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_suffixed_constants() {
+        use model::{Expr, Type};
+        let src = "int main() { f(10, 10u, 10L, 10ULL, 3000000000, 4000000000u); }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        let Stmt::Expr(Expr::Call { args, .. }) = &program.functions[0].body.statements[0] else {
+            panic!("Expected call statement");
+        };
+        assert_eq!(args, &vec![
+            Expr::Constant(10),
+            Expr::TypedConstant(10, Type::UnsignedInt),
+            Expr::TypedConstant(10, Type::Long),
+            Expr::TypedConstant(10, Type::UnsignedLongLong),
+            Expr::TypedConstant(3000000000, Type::Long),
+            Expr::TypedConstant(4000000000, Type::UnsignedInt),
+        ]);
+    }
+
+    #[test]
+    fn parse_c11_specifiers() {
+        use model::{Attribute, Type};
+        let src = "_Alignas(16) char buf[8]; _Alignas(long) int n; _Thread_local int t; _Atomic(long) a; int _Atomic *p;\n\
+                   _Noreturn void stop(void) { for (;;); }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        let globals = &program.globals;
+        assert_eq!(globals[0].attributes, vec![Attribute::Aligned(16)]);
+        assert_eq!(globals[1].attributes, vec![Attribute::Aligned(8)]);
+        assert_eq!(globals[2].r#type, Type::Int);
+        assert_eq!(globals[3].r#type, Type::Long);
+        assert!(matches!(&globals[4].r#type, Type::Pointer(inner, _) if **inner == Type::Int));
+        assert_eq!(program.functions[0].attributes, vec![Attribute::NoReturn]);
+    }
+
     #[test]
     fn parse_cast() {
         let src = "int main() { return (int)3.14; }";
@@ -329,6 +430,41 @@ mod tests {
         assert!(matches!(&stmts[1], Stmt::Label(label) if label == "end"));
     }
 
+    #[test]
+    fn parse_computed_goto() {
+        let src = "int main() { void *p = &&out; goto *p; out: return 0; }";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        let stmts = &program.functions[0].body.statements;
+        assert!(matches!(&stmts[0], Stmt::Declaration { init: Some(model::Expr::LabelAddr(label)), .. } if label == "out"));
+        assert!(matches!(&stmts[1], Stmt::ComputedGoto(target) if matches!(&**target, model::Expr::Variable(name) if name == "p")));
+    }
+
+    #[test]
+    fn parse_extended_asm() {
+        let src = r#"int f(int a) {
+            int r;
+            __asm__ volatile("mov %[out], %[in]\n\t" "add %[out], %2"
+                : [out] "=&r"(r) : [in] "r"(a), "i"(5) : "cc", "memory");
+            asm goto("jmp %l0" : : : : done);
+            done:
+            return r;
+        }"#;
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        let stmts = &program.functions[0].body.statements;
+        let Stmt::InlineAsm { template, outputs, inputs, clobbers, is_volatile, goto_labels } = &stmts[1] else {
+            panic!("expected asm, got {:?}", stmts[1]);
+        };
+        assert_eq!(template, "mov %[out], %[in]\n\tadd %[out], %2");
+        assert!(*is_volatile && goto_labels.is_empty());
+        assert_eq!((outputs[0].name.as_deref(), outputs[0].constraint.as_str()), (Some("out"), "=&r"));
+        let inputs: Vec<_> = inputs.iter().map(|op| (op.name.as_deref(), op.constraint.as_str())).collect();
+        assert_eq!(inputs, [(Some("in"), "r"), (None, "i")]);
+        assert_eq!(clobbers, &["cc", "memory"]);
+        assert!(matches!(&stmts[2], Stmt::InlineAsm { is_volatile: true, goto_labels, .. } if goto_labels == &["done"]));
+    }
+
     #[test]
     fn parse_nested_blocks() {
         let src = "void main() { { int x = 1; { int y = 2; } } }";
@@ -371,6 +507,69 @@ mod tests {
         assert_eq!(program.enums[0].constants.len(), 3);
     }
 
+    #[test]
+    fn parse_enum_constant_expressions() {
+        let src = "enum { A = 1 << 4, B = A + 1, C, D = -(B * 2) | 1, E = sizeof(long) }; int v[B]; int main() { return 0; }";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        assert_eq!(program.enums[0].name, "");
+        let values: Vec<i64> = program.enums[0].constants.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![16, 17, 18, -33, 8]);
+        assert_eq!(program.globals[0].r#type, model::Type::Array(Box::new(model::Type::Int), 17));
+    }
+
+    #[test]
+    fn expression_nesting_limit() {
+        let nested = |depth: usize| format!("int main() {{ return {}1{}; }}", "(".repeat(depth), ")".repeat(depth));
+        // Unoptimized frames are large, so give the default limit a main-thread-sized stack
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                let tokens = lex(&nested(DEFAULT_MAX_EXPR_DEPTH - 10)).unwrap();
+                assert_eq!(parse_tokens(&tokens).unwrap().functions.len(), 1);
+
+                // Far deeper is a diagnostic, not a stack overflow or a skipped function
+                let tokens = lex(&nested(100_000)).unwrap();
+                let err = parse_tokens(&tokens).unwrap_err();
+                assert_eq!(err.code, model::ErrorCode::NestingTooDeep);
+                assert!(err.message.contains("too deeply nested"));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let tokens = lex("int main() { int a; return -(-(-(a = 1))); }").unwrap();
+        let spans = vec![Span::default(); tokens.len()];
+        let err = parse_tokens_with_depth_limit(&tokens, &spans, 3).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::NestingTooDeep);
+        assert!(parse_tokens_with_depth_limit(&tokens, &spans, 8).is_ok());
+    }
+
+    #[test]
+    fn parse_inline_function_options() {
+        let src = "static inline int hdr(void) { return 1; }\n\
+                   inline static int sq(int x) { return x * x; }\n\
+                   inline int twice(int x) { return 2 * x; }\n\
+                   int main() { return sq(2); }";
+        let (tokens, spans): (Vec<Token>, Vec<Span>) =
+            lexer::lex_spanned(src, model::FileId(0)).unwrap().into_iter().map(|t| (t.node, t.span)).unzip();
+        let names = |options: &ParseOptions| -> Vec<String> {
+            let program = parse_tokens_with_options(&tokens, &spans, options).unwrap();
+            program.functions.iter().map(|f| f.name.clone()).collect()
+        };
+
+        let program = parse_tokens_with_options(&tokens, &spans, &ParseOptions::default()).unwrap();
+        let functions: Vec<_> = program.functions.iter().map(|f| (f.name.as_str(), f.is_static)).collect();
+        assert_eq!(functions, [("hdr", true), ("sq", true), ("twice", false), ("main", false)]);
+        assert!(program.functions[2].attributes.contains(&model::Attribute::Weak));
+        assert!(!program.functions[1].attributes.contains(&model::Attribute::Weak));
+
+        let header = ParseOptions { system_headers: vec![0..src.find("inline static").unwrap()], ..ParseOptions::default() };
+        assert_eq!(names(&header), ["sq", "twice", "main"]);
+        let skip = ParseOptions { inline_functions: false, ..ParseOptions::default() };
+        assert_eq!(names(&skip), ["twice", "main"]);
+    }
+
     #[test]
     fn parse_struct_definition() {
         let src = "struct Point { int x; int y; }; int main() { return 0; }";
@@ -381,6 +580,55 @@ mod tests {
         assert_eq!(program.structs[0].fields.len(), 2);
     }
 
+    #[test]
+    fn parse_nested_member_definitions() {
+        let src = "struct Outer { struct Inner { int a; } in; union { int i; float f; }; int tail; };";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        let outer = program.structs.iter().find(|s| s.name == "Outer").unwrap();
+        let names: Vec<&str> = outer.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["in", "", "tail"]);
+        assert_eq!(outer.fields[0].field_type, model::Type::Struct("Inner".to_string()));
+        assert!(program.structs.iter().any(|s| s.name == "Inner" && s.fields.len() == 1));
+        let model::Type::Union(tag) = &outer.fields[1].field_type else { panic!("expected a union member") };
+        assert_eq!(program.unions.iter().find(|u| &u.name == tag).unwrap().fields.len(), 2);
+    }
+
+    #[test]
+    fn parse_tag_definitions_inside_declarations() {
+        let src = "struct G { int x, y; } g = {3, 4}, h;\n\
+                   typedef union { float f; unsigned u; } Bits;\n\
+                   int main() { union P { int i; char c[4]; } p; struct L { long l; }; p.i = 1; return p.c[0]; }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        assert!(program.functions.iter().any(|f| f.name == "main"));
+        for name in ["G", "L"] {
+            assert!(program.structs.iter().any(|s| s.name == name), "missing struct {}", name);
+        }
+        assert!(program.unions.iter().any(|u| u.name == "P" && u.fields.len() == 2));
+        assert_eq!(program.unions.len(), 2);
+        let globals: Vec<(&str, &model::Type)> = program.globals.iter().map(|g| (g.name.as_str(), &g.r#type)).collect();
+        let g = model::Type::Struct("G".to_string());
+        assert_eq!(globals, [("g", &g), ("h", &g)]);
+    }
+
+    #[test]
+    fn parse_comma_separated_members() {
+        use model::{Type, TypeQualifiers};
+        let src = "struct P { const char *a, *b; int x, y[2], *z; unsigned f : 1, g : 2; };";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        let fields: Vec<(&str, &Type, Option<usize>)> =
+            program.structs[0].fields.iter().map(|f| (f.name.as_str(), &f.field_type, f.bit_width)).collect();
+        let const_char = Type::qualified_ptr(Type::Char, TypeQualifiers { is_const: true, ..Default::default() });
+        assert_eq!(fields, [
+            ("a", &const_char, None),
+            ("b", &const_char, None),
+            ("x", &Type::Int, None),
+            ("y", &Type::Array(Box::new(Type::Int), 2), None),
+            ("z", &Type::ptr(Type::Int), None),
+            ("f", &Type::UnsignedInt, Some(1)),
+            ("g", &Type::UnsignedInt, Some(2)),
+        ]);
+    }
+
     #[test]
     fn parse_function_pointer_local() {
         // Function pointer as local variable (parser supports this)
@@ -396,6 +644,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_function_pointer_params_and_returns() {
+        let src = "int apply(int (*op)(int, int), int x) { return op(x, x); }\n\
+                   int (*pick(int which))(int, int) { return 0; }\n\
+                   void sort(void *base, int (*)(const void *, const void *));";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        assert_eq!(program.functions.len(), 2);
+        let (op_type, op_name) = &program.functions[0].params[0];
+        assert_eq!(op_name, "op");
+        assert!(matches!(op_type, model::Type::FunctionPointer { param_types, .. } if param_types.len() == 2));
+        assert_eq!(program.functions[1].name, "pick");
+        assert!(matches!(
+            &program.functions[1].return_type,
+            model::Type::FunctionPointer { return_type, .. } if **return_type == model::Type::Int
+        ));
+        assert_eq!(program.functions[1].params[0].1, "which");
+        let sort = program.prototypes.iter().find(|p| p.name == "sort").unwrap();
+        assert!(matches!(sort.params[1].0, model::Type::FunctionPointer { .. }));
+    }
+
     #[test]
     fn parse_const_qualifier() {
         let src = "int main() { const int x = 5; return x; }";
@@ -408,6 +677,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_qualifiers_per_pointer_level() {
+        let src = "int main() { const char * const * volatile p = 0; return 0; }";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        if let Stmt::Declaration { r#type, qualifiers, .. } = &program.functions[0].body.statements[0] {
+            let const_q = model::TypeQualifiers { is_const: true, ..Default::default() };
+            let inner = model::Type::qualified_ptr(model::Type::Char, const_q.clone());
+            assert_eq!(*r#type, model::Type::qualified_ptr(inner, const_q));
+            // The qualifier after the last '*' applies to p itself
+            assert!(qualifiers.is_volatile && !qualifiers.is_const);
+        } else {
+            panic!("Expected Declaration");
+        }
+    }
+
     #[test]
     fn parse_typedef_usage() {
         let src = "typedef int my_int; int main() { my_int x = 42; return x; }";
@@ -420,7 +705,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_array_and_function_typedefs() {
+        use model::Type;
+        let src = "typedef int vec4[4]; typedef int handler(int);\n\
+                   int run(vec4 v, handler h, handler *p, int raw[8]) { vec4 w; handler *t[2]; return 0; }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        let handler = Type::FunctionPointer { return_type: Box::new(Type::Int), param_types: vec![Type::Int], is_variadic: false };
+        assert_eq!(program.typedefs["vec4"], Type::Array(Box::new(Type::Int), 4));
+        assert_eq!(program.typedefs["handler"], handler);
+
+        let params: Vec<&Type> = program.functions[0].params.iter().map(|p| &p.0).collect();
+        assert_eq!(params, vec![&Type::ptr(Type::Int), &handler, &handler, &Type::ptr(Type::Int)]);
+        let stmts = &program.functions[0].body.statements;
+        assert!(matches!(&stmts[0], Stmt::Declaration { r#type: Type::Array(elem, 4), .. } if **elem == Type::Int));
+        assert!(matches!(&stmts[1], Stmt::Declaration { r#type: Type::Array(elem, 2), .. } if **elem == handler));
+    }
+
+    #[test]
+    fn parse_multidimensional_arrays_in_source_order() {
+        use model::Type;
+        let rows = |inner: usize, outer: usize| Type::Array(Box::new(Type::Array(Box::new(Type::Int), inner)), outer);
+        let src = "typedef int grid[2][3]; int g[2][3]; struct S { int m[2][3]; };\n\
+                   int main() { int l[2][3]; return 0; }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        assert_eq!(program.typedefs["grid"], rows(3, 2));
+        assert_eq!(program.globals[0].r#type, rows(3, 2));
+        assert_eq!(program.structs[0].fields[0].field_type, rows(3, 2));
+        assert!(matches!(&program.functions[0].body.statements[0], Stmt::Declaration { r#type, .. } if *r#type == rows(3, 2)));
+    }
+
     // ─── Attribute tests ────────────────────────────────────────
+    #[test]
+    fn parse_pragma_pack() {
+        let src = "#pragma pack(push, 2)\nstruct A { char c; long l; };\n#pragma pack(pop)\nstruct B { char c; long l; };\nint main() { struct A a; a.l = 1; return a.l; }";
+        let program = parse_tokens(&lex(src).unwrap()).unwrap();
+        assert_eq!(program.structs[0].attributes, vec![model::Attribute::Pack(2)]);
+        assert!(program.structs[1].attributes.is_empty());
+        assert_eq!(program.functions.len(), 1);
+
+        // Symbol positions still line up with the tokens around the pragmas
+        let spanned = lexer::lex_spanned(src, model::FileId(0)).unwrap();
+        let (tokens, spans): (Vec<Token>, Vec<Span>) = spanned.into_iter().map(|t| (t.node, t.span)).unzip();
+        let index = index_symbols(&tokens, &spans).unwrap();
+        let main = index.symbols.iter().find(|s| s.name == "main").unwrap();
+        assert_eq!(&src[main.span.start..main.span.end], "main");
+    }
+
     #[test]
     fn parse_packed_attribute() {
         let src = "struct __attribute__((packed)) S { int x; char y; }; int main() { return 0; }";
@@ -438,6 +769,25 @@ mod tests {
         assert!(init_fn.attributes.contains(&model::Attribute::Constructor));
     }
 
+    #[test]
+    fn parse_declspec() {
+        let src = "__declspec(dllimport) int imported;\n\
+                   __declspec(noreturn) void fail() { for (;;) {} }\n\
+                   __declspec(selectany) __declspec(align(16)) int table[4];\n\
+                   struct __declspec(align(32)) V { int x; };\n\
+                   __declspec(deprecated(\"old\") noinline) int main() { return 0; }";
+        let tokens = lex(src).unwrap();
+        let program = parse_tokens(&tokens).unwrap();
+        assert_eq!(program.functions.len(), 2);
+        let fail = program.functions.iter().find(|f| f.name == "fail").unwrap();
+        assert_eq!(fail.attributes, vec![model::Attribute::NoReturn]);
+        let imported = program.globals.iter().find(|g| g.name == "imported").unwrap();
+        assert!(imported.attributes.is_empty());
+        let table = program.globals.iter().find(|g| g.name == "table").unwrap();
+        assert_eq!(table.attributes, vec![model::Attribute::Weak, model::Attribute::Aligned(16)]);
+        assert_eq!(program.structs[0].attributes, vec![model::Attribute::Aligned(32)]);
+    }
+
     // ─── Edge cases ─────────────────────────────────────────────
     #[test]
     fn parse_empty_function_body() {
@@ -513,4 +863,39 @@ mod tests {
         let program = parse_tokens(&tokens).unwrap();
         assert!(program.forward_structs.contains(&"opaque".to_string()));
     }
+
+    #[test]
+    fn parse_with_spans_locates_items_and_errors() {
+        let src = "int g;\nint main() { return 0; }";
+        let (tokens, spans): (Vec<_>, Vec<_>) = lexer::lex_spanned(src, model::FileId(0))
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.node, t.span))
+            .unzip();
+        let program = parse_tokens_with_spans(&tokens, &spans).unwrap();
+        let g = program.globals[0].span;
+        assert_eq!(&src[g.start..g.end], "g");
+        let f = program.functions[0].span;
+        assert_eq!(&src[f.start..f.end], "int main() { return 0; }");
+
+        let src = "int x;\n_Static_assert(1 2);";
+        let (tokens, spans): (Vec<_>, Vec<_>) = lexer::lex_spanned(src, model::FileId(0))
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.node, t.span))
+            .unzip();
+        let err = parse_tokens_with_spans(&tokens, &spans).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::Syntax);
+        let span = err.span.unwrap();
+        assert_eq!(&src[span.start..span.end], "2");
+    }
+
+    #[test]
+    fn errors_carry_codes() {
+        // Assertions are kept for semantic analysis to evaluate
+        let program = parse_tokens(&lex("_Static_assert(0, \"no\");").unwrap()).unwrap();
+        assert_eq!(program.static_asserts[0].message.as_deref(), Some("no"));
+        let err = parse_tokens(&lex("_Static_assert(1;").unwrap()).unwrap_err();
+        assert_eq!(err.code, model::ErrorCode::Syntax);
+    }
 }
@@ -1,24 +1,108 @@
-use model::Token;
-use std::collections::{HashMap, HashSet};
+use model::{CompileError, ErrorCode, PackPragma, Span, Token};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+use crate::symbols::Occurrence;
+
+/// A syntax error. The public entry points attach the span of the token the
+/// parser stopped at when the error does not carry one already.
+pub(crate) fn syntax_error(message: impl Into<String>) -> CompileError {
+    CompileError::new(ErrorCode::Syntax, message)
+}
 
 /// Core parser struct that maintains parsing state
 pub(crate) struct Parser<'a> {
-    pub(crate) tokens: &'a [Token],
+    /// The tokens to parse, without `#pragma pack` lines (see `packing`).
+    pub(crate) tokens: Cow<'a, [Token]>,
+    /// Source span of each token, parallel to `tokens`; empty when unknown.
+    pub(crate) spans: Cow<'a, [Span]>,
+    /// `#pragma pack` value in effect from each token position on, in
+    /// position order; `None` is natural alignment.
+    pub(crate) packing: Vec<(usize, Option<usize>)>,
     pub(crate) pos: usize,
     pub(crate) typedefs: HashSet<String>,
     pub(crate) typedef_defs: HashMap<String, model::Type>,
+    /// Typedefs of function types (`typedef int handler(int);`). Their entry
+    /// in `typedef_defs` is the `FunctionPointer` a use of the name decays to.
+    pub(crate) function_typedefs: HashSet<String>,
+    /// Compile `static inline` / `extern inline` definitions instead of
+    /// skipping them; see `skips_inline_at()`.
+    pub(crate) compile_inline: bool,
+    /// Byte ranges of the input that come from system headers.
+    pub(crate) system_headers: Vec<Range<usize>>,
+    /// Values of the enum constants declared so far, for constant expressions.
+    pub(crate) enum_constants: HashMap<String, i64>,
+    /// Struct and union definitions so far, for `sizeof` in constant expressions.
+    pub(crate) struct_defs: HashMap<String, model::StructDef>,
+    pub(crate) union_defs: HashMap<String, model::UnionDef>,
+    /// Definitions written inside a member declaration
+    /// (`struct S { union { int i; float f; } u; };`), added to the program
+    /// alongside the top-level ones.
+    pub(crate) nested_structs: Vec<model::StructDef>,
+    pub(crate) nested_unions: Vec<model::UnionDef>,
+    /// Tagless struct and union definitions so far, for naming them.
+    pub(crate) anonymous_records: usize,
+    /// Current and maximum expression nesting depth; see `nested()`.
+    pub(crate) expr_depth: usize,
+    pub(crate) max_expr_depth: usize,
+    /// An error that top-level recovery must not skip past.
+    pub(crate) fatal: Option<CompileError>,
+    /// Symbol occurrences by token position, when building a symbol index.
+    /// Keyed by position so re-parsing after backtracking overwrites.
+    pub(crate) symbols: Option<BTreeMap<usize, Occurrence>>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
+        Self::with_spans(tokens, &[])
+    }
+
+    pub fn with_spans(tokens: &'a [Token], spans: &'a [Span]) -> Self {
         let mut typedefs = HashSet::new();
         typedefs.insert("__builtin_va_list".to_string());
-        
+        let (tokens, spans, packing) = strip_pack_pragmas(tokens, spans);
+
         Parser {
             tokens,
+            spans,
+            packing,
             pos: 0,
             typedefs,
             typedef_defs: HashMap::new(),
+            function_typedefs: HashSet::new(),
+            compile_inline: true,
+            system_headers: Vec::new(),
+            enum_constants: HashMap::new(),
+            struct_defs: HashMap::new(),
+            union_defs: HashMap::new(),
+            nested_structs: Vec::new(),
+            nested_unions: Vec::new(),
+            anonymous_records: 0,
+            expr_depth: 0,
+            max_expr_depth: crate::DEFAULT_MAX_EXPR_DEPTH,
+            fatal: None,
+            symbols: None,
+        }
+    }
+
+    /// The `#pragma pack` value in effect at token `pos`.
+    pub(crate) fn pack_at(&self, pos: usize) -> Option<usize> {
+        let after = self.packing.partition_point(|&(start, _)| start <= pos);
+        after.checked_sub(1).and_then(|i| self.packing[i].1)
+    }
+
+    /// Record that the token at `pos` defines or references a symbol.
+    pub(crate) fn note_symbol(&mut self, pos: usize, occurrence: Occurrence) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.insert(pos, occurrence);
+        }
+    }
+
+    /// Drop the occurrences recorded for tokens in `range`.
+    pub(crate) fn forget_symbols(&mut self, range: std::ops::Range<usize>) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.retain(|pos, _| !range.contains(pos));
         }
     }
 
@@ -27,6 +111,41 @@ impl<'a> Parser<'a> {
         self.pos >= self.tokens.len()
     }
 
+    /// Span of the token at `pos`. Past the end, an empty span just after the
+    /// last token; without span information, the default span.
+    pub(crate) fn span_at(&self, pos: usize) -> Span {
+        match (self.spans.get(pos), self.spans.last()) {
+            (Some(span), _) => *span,
+            (None, Some(last)) => Span::new(last.file_id, last.end, last.end),
+            (None, None) => Span::default(),
+        }
+    }
+
+    /// Span of the current token for error reporting, if spans are known.
+    pub(crate) fn error_span(&self) -> Option<Span> {
+        (!self.spans.is_empty()).then(|| self.span_at(self.pos))
+    }
+
+    /// Whether the `static inline` / `extern inline` definition starting at
+    /// token `pos` is skipped: always when `compile_inline` is off, and
+    /// otherwise when it comes from a system header, whose inline wrappers
+    /// libc already provides.
+    pub(crate) fn skips_inline_at(&self, pos: usize) -> bool {
+        if !self.compile_inline {
+            return true;
+        }
+        match self.spans.get(pos) {
+            Some(span) => self.system_headers.iter().any(|range| range.contains(&span.start)),
+            None => false,
+        }
+    }
+
+    /// Span from the token at `start` through the last consumed token.
+    pub(crate) fn span_since(&self, start: usize) -> Span {
+        let end = self.pos.saturating_sub(1).max(start);
+        self.span_at(start).to(self.span_at(end))
+    }
+
     pub(crate) fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.pos)
     }
@@ -75,7 +194,7 @@ impl<'a> Parser<'a> {
         self.tokens.get(self.pos + offset).map_or(false, predicate)
     }
 
-    pub(crate) fn expect<F>(&mut self, predicate: F, expected: &str) -> Result<(), String>
+    pub(crate) fn expect<F>(&mut self, predicate: F, expected: &str) -> Result<(), CompileError>
     where
         F: Fn(&Token) -> bool,
     {
@@ -85,7 +204,42 @@ impl<'a> Parser<'a> {
         } else {
             let peeked = self.peek();
             // eprintln!("Parse error at pos {}: expected {}, found {:?}", self.pos, expected, peeked);
-            Err(format!("expected {expected}, found {:?} at position {}", peeked, self.pos))
+            Err(syntax_error(format!("expected {expected}, found {:?} at position {}", peeked, self.pos)))
+        }
+    }
+}
+
+/// Remove the `Token::PragmaPack` directives from `tokens` (and their spans),
+/// returning the packing each one sets and the position it takes effect at.
+/// Token streams without any are borrowed as they are.
+fn strip_pack_pragmas<'a>(
+    tokens: &'a [Token],
+    spans: &'a [Span],
+) -> (Cow<'a, [Token]>, Cow<'a, [Span]>, Vec<(usize, Option<usize>)>) {
+    if !tokens.iter().any(|t| matches!(t, Token::PragmaPack(_))) {
+        return (Cow::Borrowed(tokens), Cow::Borrowed(spans), Vec::new());
+    }
+    let mut kept = Vec::with_capacity(tokens.len());
+    let mut kept_spans = Vec::with_capacity(spans.len());
+    let mut packing = Vec::new();
+    let mut current = None;
+    let mut stack = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::PragmaPack(pragma) = token else {
+            kept.push(token.clone());
+            kept_spans.extend(spans.get(i).copied());
+            continue;
+        };
+        match *pragma {
+            PackPragma::Set(value) => current = value,
+            PackPragma::Push(value) => {
+                stack.push(current);
+                current = value.or(current);
+            }
+            // An unmatched pop is ignored, as in GCC
+            PackPragma::Pop => current = stack.pop().unwrap_or(current),
         }
+        packing.push((kept.len(), current));
     }
+    (Cow::Owned(kept), Cow::Owned(kept_spans), packing)
 }
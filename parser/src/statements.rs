@@ -1,31 +1,35 @@
-use model::{Block, Expr, InitItem, Designator, Stmt, Token, Type};
-use crate::parser::Parser;
-use crate::types::TypeParser;
+use model::{Block, CompileError, Expr, InitItem, Designator, Stmt, Token, Type};
+use crate::parser::{syntax_error, Parser};
+use crate::types::{array_of, TypeParser};
 use crate::expressions::ExpressionParser;
 use crate::declarations::DeclarationParser;
 use crate::utils::ParserUtils;
+use crate::symbols::{Occurrence, SymbolKind};
 
 /// Statement parsing functionality
 pub(crate) trait StatementParser {
-    fn parse_stmt(&mut self) -> Result<Stmt, String>;
-    fn parse_block(&mut self) -> Result<Block, String>;
+    fn parse_stmt(&mut self) -> Result<Stmt, CompileError>;
+    fn parse_block(&mut self) -> Result<Block, CompileError>;
 }
 
 impl<'a> StatementParser for Parser<'a> {
-    fn parse_block(&mut self) -> Result<Block, String> {
+    fn parse_block(&mut self) -> Result<Block, CompileError> {
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
         let mut statements = Vec::new();
+        let mut spans = Vec::new();
         while !self.check(|t| matches!(t, Token::CloseBrace)) && !self.is_at_end() {
+            let start = self.pos;
             statements.push(self.parse_stmt()?);
+            spans.push(self.span_since(start));
         }
         self.expect(|t| matches!(t, Token::CloseBrace), "'}'")?;
-        Ok(Block { statements })
+        Ok(Block { statements, spans })
     }
 
-    fn parse_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_stmt(&mut self) -> Result<Stmt, CompileError> {
         // Empty statement: a lone semicolon
         if self.match_token(|t| matches!(t, Token::Semicolon)) {
-            return Ok(Stmt::Block(Block { statements: vec![] }));
+            return Ok(Stmt::Block(Block { statements: vec![], spans: vec![] }));
         }
 
         // Return statement
@@ -54,7 +58,7 @@ impl<'a> StatementParser for Parser<'a> {
             }
             let label = match self.advance() {
                 Some(Token::Identifier { value }) => value.clone(),
-                other => return Err(format!("expected label name after 'goto', found {:?}", other)),
+                other => return Err(syntax_error(format!("expected label name after 'goto', found {:?}", other))),
             };
             self.expect(|t| matches!(t, Token::Semicolon), "';'")?;
             return Ok(Stmt::Goto(label));
@@ -103,8 +107,7 @@ impl<'a> StatementParser for Parser<'a> {
 
         // _Static_assert
         if self.match_token(|t| matches!(t, Token::StaticAssert)) {
-            self.parse_static_assert()?;
-            return Ok(Stmt::Block(Block { statements: vec![] })); // No-op statement
+            return Ok(Stmt::StaticAssert(self.parse_static_assert()?));
         }
 
         // Variable declaration
@@ -139,7 +142,7 @@ impl<'a> StatementParser for Parser<'a> {
 }
 
 impl<'a> Parser<'a> {
-    fn parse_return_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_return_stmt(&mut self) -> Result<Stmt, CompileError> {
         if self.match_token(|t| matches!(t, Token::Semicolon)) {
             return Ok(Stmt::Return(None));
         }
@@ -148,7 +151,7 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Return(Some(expr)))
     }
 
-    fn parse_if_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_if_stmt(&mut self) -> Result<Stmt, CompileError> {
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         let cond = self.parse_expr()?;
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
@@ -165,7 +168,7 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_while_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_while_stmt(&mut self) -> Result<Stmt, CompileError> {
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         let cond = self.parse_expr()?;
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
@@ -173,7 +176,7 @@ impl<'a> Parser<'a> {
         Ok(Stmt::While { cond, body })
     }
 
-    fn parse_do_while_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_do_while_stmt(&mut self) -> Result<Stmt, CompileError> {
         let body = Box::new(self.parse_stmt()?);
         self.expect(|t| matches!(t, Token::While), "while")?;
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
@@ -183,7 +186,7 @@ impl<'a> Parser<'a> {
         Ok(Stmt::DoWhile { body, cond })
     }
 
-    fn parse_for_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_for_stmt(&mut self) -> Result<Stmt, CompileError> {
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
 
         // Init clause
@@ -221,7 +224,7 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_switch_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_switch_stmt(&mut self) -> Result<Stmt, CompileError> {
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
         let cond = self.parse_expr()?;
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
@@ -229,14 +232,26 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Switch { cond, body })
     }
 
-    fn parse_case_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_case_stmt(&mut self) -> Result<Stmt, CompileError> {
         let expr = self.parse_expr()?;
         self.expect(|t| matches!(t, Token::Colon), "':'")?;
         Ok(Stmt::Case(expr))
     }
 
-    fn parse_declaration(&mut self) -> Result<Stmt, String> {
+    fn parse_declaration(&mut self) -> Result<Stmt, CompileError> {
+        let start = self.pos;
         let (mut r#type, qualifiers) = self.parse_type_with_qualifiers()?;
+        let has_static_storage = self.tokens[start..self.pos]
+            .iter()
+            .any(|t| matches!(t, Token::Static | Token::Extern | Token::ThreadLocal));
+
+        // A tag declaration with no declarator, `struct P { int a; };`: the
+        // type parser has already recorded the definition
+        if matches!(r#type, Type::Struct(_) | Type::Union(_) | Type::Enum(_))
+            && self.match_token(|t| matches!(t, Token::Semicolon))
+        {
+            return Ok(Stmt::Block(Block { statements: vec![], spans: vec![] }));
+        }
 
         // Check for function pointer: type (*name)(params)
         if self.check(|t| matches!(t, Token::OpenParenthesis)) {
@@ -247,11 +262,12 @@ impl<'a> Parser<'a> {
 
             if self.match_token(|t| matches!(t, Token::Star)) {
                 // It's a function pointer
+                self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Local, None));
                 let name = match self.advance() {
                     Some(Token::Identifier { value }) => value.clone(),
                     _other => {
                         // Can't parse this function pointer, bail out
-                        return Err("Cannot parse function pointer declaration".to_string());
+                        return Err(syntax_error("Cannot parse function pointer declaration"));
                     }
                 };
                 
@@ -269,55 +285,13 @@ impl<'a> Parser<'a> {
                 
                 if !self.match_token(|t| matches!(t, Token::CloseParenthesis)) {
                     // Malformed function pointer
-                    return Err("Expected ')' after function pointer name".to_string());
+                    return Err(syntax_error("Expected ')' after function pointer name"));
                 }
                 
-                if !self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
-                    // Malformed function pointer
-                    return Err("Expected '(' for function pointer parameters".to_string());
-                }
-
-                // Parse parameter types
-                let mut param_types = Vec::new();
-                if !self.check(|t| matches!(t, Token::CloseParenthesis)) {
-                    loop {
-                        let param_type = self.parse_type()?;
-                        param_types.push(param_type.clone());
-                        // Optional parameter name
-                        if self.check(|t| matches!(t, Token::Identifier { .. })) {
-                            self.advance();
-                        }
-                        // Handle array syntax: type name[] or type[] (supports multi-dimensional)
-                        while self.match_token(|t| matches!(t, Token::OpenBracket)) {
-                            // Check if array size is provided
-                            let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
-                                0 // Use 0 to represent unsized array
-                            } else {
-                                self.parse_array_size()?
-                            };
-                            self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                            // Update the last parameter type to be an array
-                            if let Some(last_param) = param_types.last_mut() {
-                                let inner = last_param.clone();
-                                *last_param = Type::Array(Box::new(inner), size);
-                            }
-                        }
-                        if !self.match_token(|t| matches!(t, Token::Comma)) {
-                            break;
-                        }
-                    }
-                }
-                self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-
-                r#type = Type::FunctionPointer {
-                    return_type: Box::new(r#type),
-                    param_types,
-                };
+                r#type = self.parse_function_pointer_type(r#type)?;
 
                 // Wrap in Array if array dimensions were found inside declarator
-                for size in array_sizes {
-                    r#type = Type::Array(Box::new(r#type), size);
-                }
+                r#type = array_of(r#type, &array_sizes);
 
                 let init = if self.match_token(|t| matches!(t, Token::Equal)) {
                     Some(self.parse_assignment()?)
@@ -331,6 +305,7 @@ impl<'a> Parser<'a> {
                     qualifiers: qualifiers.clone(),
                     name,
                     init,
+                    has_static_storage,
                 });
             } else {
                 // Not a function pointer, restore position
@@ -348,12 +323,14 @@ impl<'a> Parser<'a> {
         loop {
             let mut decl_type = base_type.clone();
 
+            self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Local, None));
             let name = match self.advance() {
                 Some(Token::Identifier { value }) => value.clone(),
-                other => return Err(format!("expected identifier after type, found {:?}", other)),
+                other => return Err(syntax_error(format!("expected identifier after type, found {:?}", other))),
             };
 
             // Check for array dimensions on this declarator (supports multi-dimensional)
+            let mut dims = Vec::new();
             while self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 // Check if array size is provided (empty brackets [] are allowed)
                 let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
@@ -362,8 +339,9 @@ impl<'a> Parser<'a> {
                     self.parse_array_size()?
                 };
                 self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                decl_type = Type::Array(Box::new(decl_type), size);
+                dims.push(size);
             }
+            decl_type = array_of(decl_type, &dims);
 
             let init = if self.match_token(|t| matches!(t, Token::Equal)) {
                 if self.check(|t| matches!(t, Token::OpenBrace)) {
@@ -380,7 +358,9 @@ impl<'a> Parser<'a> {
             // Infer array size from initializer
             if let Type::Array(inner, 0) = &decl_type {
                 if let Some(Expr::StringLiteral(s)) = &init {
-                    decl_type = Type::Array(inner.clone(), s.len() + 1);
+                    decl_type = Type::Array(inner.clone(), s.chars().count() + 1);
+                } else if let Some(Expr::WideStringLiteral(s, encoding)) = &init {
+                    decl_type = Type::Array(inner.clone(), encoding.encode(s).len() + 1);
                 } else if let Some(Expr::InitList(items)) = &init {
                     decl_type = Type::Array(inner.clone(), items.len());
                 }
@@ -391,6 +371,7 @@ impl<'a> Parser<'a> {
                 qualifiers: qualifiers.clone(),
                 name,
                 init,
+                has_static_storage,
             });
 
             if !self.match_token(|t| matches!(t, Token::Comma)) {
@@ -407,71 +388,47 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_inline_asm(&mut self) -> Result<Stmt, String> {
-        // asm [volatile] ( "assembly template" : outputs : inputs : clobbers );
-        let is_volatile = self.match_token(|t| matches!(t, Token::Volatile));
-        
+    fn parse_inline_asm(&mut self) -> Result<Stmt, CompileError> {
+        // asm [volatile] [inline] [goto] ( "template" : outputs : inputs : clobbers : labels );
+        let mut is_volatile = false;
+        let mut is_goto = false;
+        loop {
+            if self.match_token(|t| matches!(t, Token::Volatile)) {
+                is_volatile = true;
+            } else if self.match_token(|t| matches!(t, Token::Goto)) {
+                is_goto = true;
+            } else if !self.match_token(|t| matches!(t, Token::Inline)) {
+                break;
+            }
+        }
+
         self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-        
-        // Parse assembly template string
-        let template = match self.advance() {
-            Some(Token::StringLiteral { value }) => value.clone(),
-            other => return Err(format!("expected string literal for asm template, found {:?}", other)),
-        };
-        
-        // Check for operands and clobbers
+        let template = self.parse_asm_string("asm template")?;
+
         let mut outputs = Vec::new();
         let mut inputs = Vec::new();
         let mut clobbers = Vec::new();
-        
-        // Parse outputs (if present)
+        let mut goto_labels = Vec::new();
+
         if self.match_token(|t| matches!(t, Token::Colon)) {
-            if !self.check(|t| matches!(t, Token::Colon | Token::CloseParenthesis)) {
-                loop {
-                    let constraint = match self.advance() {
-                        Some(Token::StringLiteral { value }) => value.clone(),
-                        other => return Err(format!("expected constraint string, found {:?}", other)),
-                    };
-                    self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                    let expr = self.parse_expr()?;
-                    self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                    outputs.push(model::AsmOperand { constraint, expr });
-                    
-                    if !self.match_token(|t| matches!(t, Token::Comma)) {
-                        break;
-                    }
-                }
-            }
-            
-            // Parse inputs (if present)
+            outputs = self.parse_asm_operands()?;
             if self.match_token(|t| matches!(t, Token::Colon)) {
-                if !self.check(|t| matches!(t, Token::Colon | Token::CloseParenthesis)) {
-                    loop {
-                        let constraint = match self.advance() {
-                            Some(Token::StringLiteral { value }) => value.clone(),
-                            other => return Err(format!("expected constraint string, found {:?}", other)),
-                        };
-                        self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
-                        let expr = self.parse_expr()?;
-                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
-                        inputs.push(model::AsmOperand { constraint, expr });
-                        
-                        if !self.match_token(|t| matches!(t, Token::Comma)) {
-                            break;
+                inputs = self.parse_asm_operands()?;
+                if self.match_token(|t| matches!(t, Token::Colon)) {
+                    if self.check(|t| matches!(t, Token::StringLiteral { .. })) {
+                        loop {
+                            clobbers.push(self.parse_asm_string("clobber string")?);
+                            if !self.match_token(|t| matches!(t, Token::Comma)) {
+                                break;
+                            }
                         }
                     }
-                }
-                
-                // Parse clobbers (if present)
-                if self.match_token(|t| matches!(t, Token::Colon)) {
-                    if !self.check(|t| matches!(t, Token::CloseParenthesis)) {
+                    if is_goto && self.match_token(|t| matches!(t, Token::Colon)) {
                         loop {
-                            let clobber = match self.advance() {
-                                Some(Token::StringLiteral { value }) => value.clone(),
-                                other => return Err(format!("expected clobber string, found {:?}", other)),
-                            };
-                            clobbers.push(clobber);
-                            
+                            match self.advance() {
+                                Some(Token::Identifier { value }) => goto_labels.push(value.clone()),
+                                other => return Err(syntax_error(format!("expected label name in asm goto, found {:?}", other))),
+                            }
                             if !self.match_token(|t| matches!(t, Token::Comma)) {
                                 break;
                             }
@@ -480,23 +437,71 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        
+        if is_goto && goto_labels.is_empty() {
+            return Err(syntax_error("expected labels after the clobbers of asm goto"));
+        }
+
         self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
         self.expect(|t| matches!(t, Token::Semicolon), "';'")?;
-        
+
         Ok(Stmt::InlineAsm {
             template,
             outputs,
             inputs,
             clobbers,
-            is_volatile,
+            // asm goto is implicitly volatile
+            is_volatile: is_volatile || is_goto,
+            goto_labels,
         })
     }
 
+    /// One or more adjacent string literals, concatenated.
+    fn parse_asm_string(&mut self, what: &str) -> Result<String, CompileError> {
+        let mut text = match self.advance() {
+            Some(Token::StringLiteral { value, .. }) => value.clone(),
+            other => return Err(syntax_error(format!("expected {}, found {:?}", what, other))),
+        };
+        while let Some(Token::StringLiteral { value, .. }) = self.peek() {
+            text.push_str(value);
+            self.advance();
+        }
+        Ok(text)
+    }
+
+    /// A possibly empty list of `[name] "constraint" (expr)` operands.
+    fn parse_asm_operands(&mut self) -> Result<Vec<model::AsmOperand>, CompileError> {
+        let mut operands = Vec::new();
+        if !self.check(|t| matches!(t, Token::OpenBracket | Token::StringLiteral { .. })) {
+            return Ok(operands);
+        }
+        loop {
+            let name = if self.match_token(|t| matches!(t, Token::OpenBracket)) {
+                let name = match self.advance() {
+                    Some(Token::Identifier { value }) => value.clone(),
+                    other => return Err(syntax_error(format!("expected asm operand name, found {:?}", other))),
+                };
+                self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
+                Some(name)
+            } else {
+                None
+            };
+            let constraint = self.parse_asm_string("constraint string")?;
+            self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
+            let expr = self.parse_expr()?;
+            self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+            operands.push(model::AsmOperand { name, constraint, expr });
+
+            if !self.match_token(|t| matches!(t, Token::Comma)) {
+                break;
+            }
+        }
+        Ok(operands)
+    }
+
     /// Parse a brace-enclosed initializer list: `{ expr, expr, ... }`
     /// Supports designated initializers: `{ .field = expr, [idx] = expr }`
     /// and nested initializer lists: `{ {1,2}, {3,4} }`
-    pub(crate) fn parse_init_list(&mut self) -> Result<Expr, String> {
+    pub(crate) fn parse_init_list(&mut self) -> Result<Expr, CompileError> {
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
         let mut items = Vec::new();
 
@@ -506,18 +511,20 @@ impl<'a> Parser<'a> {
 
         loop {
             let designator = if self.match_token(|t| matches!(t, Token::Dot)) {
+                self.note_symbol(self.pos, Occurrence::MemberRef);
                 let field_name = match self.advance() {
                     Some(Token::Identifier { value }) => value.clone(),
-                    other => return Err(format!("expected field name after '.', found {:?}", other)),
+                    other => return Err(syntax_error(format!("expected field name after '.', found {:?}", other))),
                 };
                 if self.match_token(|t| matches!(t, Token::Dot)) {
+                    self.note_symbol(self.pos, Occurrence::MemberRef);
                     let child = match self.advance() {
                         Some(Token::Identifier { value }) => value.clone(),
                         other => {
-                            return Err(format!(
+                            return Err(syntax_error(format!(
                                 "expected nested field name, found {:?}",
                                 other
-                            ))
+                            )))
                         }
                     };
                     self.expect(|t| matches!(t, Token::Equal), "'='")?;
@@ -546,16 +553,16 @@ impl<'a> Parser<'a> {
             } else if self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 let index = match self.advance() {
                     Some(Token::Constant { value, .. }) => *value,
-                    other => return Err(format!("expected constant index in designator, found {:?}", other)),
+                    other => return Err(syntax_error(format!("expected constant index in designator, found {:?}", other))),
                 };
                 if self.match_token(|t| matches!(t, Token::Ellipsis)) {
                     let end = match self.advance() {
                         Some(Token::Constant { value, .. }) => *value,
                         other => {
-                            return Err(format!(
+                            return Err(syntax_error(format!(
                                 "expected end index in range designator, found {:?}",
                                 other
-                            ))
+                            )))
                         }
                     };
                     self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
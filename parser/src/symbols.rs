@@ -0,0 +1,284 @@
+// Symbol index for go-to-definition and find-references
+//
+// While indexing, the parser notes which tokens define or reference a name
+// (`Parser::note_symbol`). The notes are then resolved in token order with a
+// scope per brace pair, so a reference points at the innermost visible
+// definition. Member references resolve only when the member name is unique,
+// since the parser does not know the type of the object being accessed.
+
+use std::collections::HashMap;
+
+use lexer::quote_json;
+use model::{CompileError, SourceMap, Span, Token};
+
+use crate::declarations::DeclarationParser;
+use crate::parser::Parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Global,
+    Parameter,
+    Local,
+    /// Struct or union member.
+    Member,
+    Enumerator,
+}
+
+impl SymbolKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Global => "global",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Local => "local",
+            SymbolKind::Member => "member",
+            SymbolKind::Enumerator => "enumerator",
+        }
+    }
+}
+
+/// A definition of a name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Span of the name in the definition.
+    pub span: Span,
+    /// Enclosing function of a parameter or local; struct or union of a member.
+    pub container: Option<String>,
+}
+
+/// A use of a name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    /// Index into `SymbolIndex::symbols`, when the name resolves.
+    pub symbol: Option<usize>,
+}
+
+/// Every definition and reference in a translation unit, in source order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SymbolIndex {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+}
+
+/// What the token at a noted position does.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Occurrence {
+    /// Defines a symbol; members carry their struct or union name.
+    Def(SymbolKind, Option<String>),
+    /// Names a variable, function, or enumerator.
+    Ref,
+    /// Names a member after `.`, `->`, or in a designator.
+    MemberRef,
+}
+
+/// Parse `tokens` (with their parallel `spans`) and index their symbols.
+pub fn index_symbols(tokens: &[Token], spans: &[Span]) -> Result<SymbolIndex, CompileError> {
+    let mut parser = Parser::with_spans(tokens, spans);
+    parser.symbols = Some(Default::default());
+    parser.parse_program().map_err(|e| e.or_span(parser.error_span()))?;
+    let occurrences = parser.symbols.take().unwrap_or_default();
+    // Positions are into the parser's tokens, which omit `#pragma pack` lines
+    let (tokens, spans) = (&*parser.tokens, &*parser.spans);
+
+    let name_at = |pos: usize| match &tokens[pos] {
+        Token::Identifier { value } => Some(value.clone()),
+        _ => None,
+    };
+    let span_at = |pos: usize| spans.get(pos).copied().unwrap_or_default();
+
+    // Pass 1: create the symbols, and put file-scope ones where every
+    // reference can see them, wherever they are defined
+    let mut index = SymbolIndex::default();
+    let mut symbol_at = HashMap::new();
+    let mut file_scope = HashMap::new();
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut current_function = None;
+    let mut depth = 0usize;
+    for (pos, token) in tokens.iter().enumerate() {
+        match token {
+            Token::OpenBrace => depth += 1,
+            Token::CloseBrace => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let (Some(Occurrence::Def(kind, container)), Some(name)) = (occurrences.get(&pos), name_at(pos)) else {
+            continue;
+        };
+        let container = match kind {
+            SymbolKind::Function => {
+                current_function = Some(name.clone());
+                None
+            }
+            SymbolKind::Parameter | SymbolKind::Local => current_function.clone(),
+            _ => container.clone(),
+        };
+        let id = index.symbols.len();
+        match kind {
+            SymbolKind::Function | SymbolKind::Global if depth == 0 => {
+                file_scope.entry(name.clone()).or_insert(id);
+            }
+            SymbolKind::Enumerator => {
+                file_scope.entry(name.clone()).or_insert(id);
+            }
+            SymbolKind::Member => members.entry(name.clone()).or_default().push(id),
+            _ => {}
+        }
+        symbol_at.insert(pos, id);
+        index.symbols.push(Symbol { name, kind: *kind, span: span_at(pos), container });
+    }
+
+    // Pass 2: resolve references against the scopes open at each token
+    let mut scopes: Vec<HashMap<String, usize>> = vec![file_scope];
+    let mut pending_params = Vec::new();
+    for (pos, token) in tokens.iter().enumerate() {
+        match token {
+            // A function body's scope starts with the parameters
+            Token::OpenBrace => scopes.push(pending_params.drain(..).collect()),
+            Token::CloseBrace if scopes.len() > 1 => {
+                scopes.pop();
+            }
+            Token::Semicolon if scopes.len() == 1 => pending_params.clear(),
+            _ => {}
+        }
+        let (Some(occurrence), Some(name)) = (occurrences.get(&pos), name_at(pos)) else {
+            continue;
+        };
+        let symbol = match occurrence {
+            Occurrence::Def(kind, _) => {
+                let id = symbol_at[&pos];
+                match kind {
+                    SymbolKind::Parameter => pending_params.push((name, id)),
+                    SymbolKind::Local | SymbolKind::Function | SymbolKind::Global if scopes.len() > 1 => {
+                        scopes.last_mut().unwrap().insert(name, id);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Occurrence::Ref => scopes.iter().rev().find_map(|scope| scope.get(&name).copied()),
+            Occurrence::MemberRef => match members.get(&name).map(Vec::as_slice) {
+                Some([only]) => Some(*only),
+                _ => None,
+            },
+        };
+        index.references.push(Reference { name, span: span_at(pos), symbol });
+    }
+    Ok(index)
+}
+
+impl SymbolIndex {
+    /// References that resolve to `symbols[id]`.
+    pub fn references_to(&self, id: usize) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.symbol == Some(id))
+    }
+
+    /// Render as JSON, with 1-based lines and columns from `sources`.
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let location = |span: Span| {
+            let file = sources.name(span.file_id).unwrap_or("");
+            let pos = sources.line_col(span.file_id, span.start);
+            format!(
+                "\"file\": {}, \"line\": {}, \"col\": {}, \"start\": {}, \"end\": {}",
+                quote_json(file),
+                pos.map_or(0, |p| p.line),
+                pos.map_or(0, |p| p.col),
+                span.start,
+                span.end
+            )
+        };
+        let mut out = String::from("{\n  \"symbols\": [");
+        for (id, s) in self.symbols.iter().enumerate() {
+            out.push_str(if id == 0 { "\n" } else { ",\n" });
+            let container = s.container.as_deref().map_or("null".to_string(), quote_json);
+            out.push_str(&format!(
+                "    {{\"id\": {}, \"name\": {}, \"kind\": \"{}\", \"container\": {}, {}}}",
+                id,
+                quote_json(&s.name),
+                s.kind.as_str(),
+                container,
+                location(s.span)
+            ));
+        }
+        out.push_str("\n  ],\n  \"references\": [");
+        for (i, r) in self.references.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            let symbol = r.symbol.map_or("null".to_string(), |id| id.to_string());
+            out.push_str(&format!(
+                "    {{\"name\": {}, \"symbol\": {}, {}}}",
+                quote_json(&r.name),
+                symbol,
+                location(r.span)
+            ));
+        }
+        out.push_str("\n  ]\n}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::FileId;
+
+    fn index(src: &str) -> SymbolIndex {
+        let (tokens, spans): (Vec<Token>, Vec<Span>) = lexer::lex_spanned(src, FileId(0))
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.node, t.span))
+            .unzip();
+        index_symbols(&tokens, &spans).unwrap()
+    }
+
+    fn symbol(index: &SymbolIndex, name: &str, kind: SymbolKind) -> usize {
+        index.symbols.iter().position(|s| s.name == name && s.kind == kind).unwrap()
+    }
+
+    #[test]
+    fn resolves_locals_params_and_globals() {
+        let src = "int g; int add(int a, int b) { int g = a; return g + b; } int main() { return add(g, 1); }";
+        let index = index(src);
+        let global = symbol(&index, "g", SymbolKind::Global);
+        let local = symbol(&index, "g", SymbolKind::Local);
+        let add = symbol(&index, "add", SymbolKind::Function);
+        assert_eq!(index.symbols[local].container.as_deref(), Some("add"));
+        assert_eq!(index.references_to(local).count(), 1, "the local shadows the global inside add");
+        assert_eq!(index.references_to(global).count(), 1, "main sees the global");
+        assert_eq!(index.references_to(add).count(), 1);
+        assert_eq!(index.references_to(symbol(&index, "a", SymbolKind::Parameter)).count(), 1);
+        let span = index.symbols[add].span;
+        assert_eq!(&src[span.start..span.end], "add");
+    }
+
+    #[test]
+    fn resolves_members_and_enumerators() {
+        let src = "struct P { int x; int y; }; enum E { A, B }; \
+                   int main() { struct P p = { .x = A }; struct P *q = &p; return q->y + p.x + B; }";
+        let index = index(src);
+        let x = symbol(&index, "x", SymbolKind::Member);
+        assert_eq!(index.symbols[x].container.as_deref(), Some("P"));
+        assert_eq!(index.references_to(x).count(), 2);
+        assert_eq!(index.references_to(symbol(&index, "y", SymbolKind::Member)).count(), 1);
+        assert_eq!(index.references_to(symbol(&index, "B", SymbolKind::Enumerator)).count(), 1);
+    }
+
+    #[test]
+    fn prototype_params_are_not_symbols() {
+        let index = index("int f(int n); int main() { int n = 0; return f(n); }");
+        assert_eq!(index.symbols.iter().filter(|s| s.name == "n").count(), 1);
+        assert!(index.references.iter().all(|r| r.name != "f" || r.symbol.is_none()));
+    }
+
+    #[test]
+    fn json_has_positions() {
+        let src = "int main() {\n  int v = 1;\n  return v;\n}\n";
+        let mut sources = SourceMap::new();
+        sources.add_file("t.c", src);
+        let json = index(src).to_json(&sources);
+        assert!(json.contains("\"name\": \"v\", \"kind\": \"local\", \"container\": \"main\", \"file\": \"t.c\", \"line\": 2, \"col\": 7"));
+        assert!(json.contains("\"name\": \"v\", \"symbol\": 1, \"file\": \"t.c\", \"line\": 3, \"col\": 10"));
+    }
+}
@@ -1,36 +1,55 @@
-use model::{Token, Type, TypeQualifiers};
-use crate::parser::Parser;
+use model::{CompileError, ErrorCode, Token, Type, TypeQualifiers};
+use crate::parser::{syntax_error, Parser};
 use crate::attributes::AttributeParser;
 use crate::expressions::ExpressionParser;
 use crate::utils::ParserUtils;
+use crate::symbols::{Occurrence, SymbolKind};
+
+/// Start of the tag given to a struct or union defined without one, as in
+/// `<anonymous 1>`.
+const ANONYMOUS_TAG_PREFIX: &str = "<anonymous ";
+
+/// `base` with the array dimensions written after a declarator name, in
+/// source order: `int a[2][3]` is an array of 2 arrays of 3 `int`s.
+pub(crate) fn array_of(base: Type, dims: &[usize]) -> Type {
+    dims.iter().rev().fold(base, |ty, &size| Type::Array(Box::new(ty), size))
+}
 
 /// Type parsing functionality
 pub(crate) trait TypeParser {
-    fn parse_type(&mut self) -> Result<Type, String>;
-    fn parse_type_with_qualifiers(&mut self) -> Result<(Type, TypeQualifiers), String>;
-    fn parse_struct_definition(&mut self) -> Result<model::StructDef, String>;
-    fn parse_union_definition(&mut self) -> Result<model::UnionDef, String>;
-    fn parse_enum_definition(&mut self) -> Result<model::EnumDef, String>;
+    fn parse_type(&mut self) -> Result<Type, CompileError>;
+    fn parse_type_with_qualifiers(&mut self) -> Result<(Type, TypeQualifiers), CompileError>;
+    fn parse_struct_definition(&mut self) -> Result<model::StructDef, CompileError>;
+    fn parse_union_definition(&mut self) -> Result<model::UnionDef, CompileError>;
+    fn parse_enum_definition(&mut self) -> Result<model::EnumDef, CompileError>;
 }
 
 impl<'a> TypeParser for Parser<'a> {
-    fn parse_type(&mut self) -> Result<Type, String> {
+    fn parse_type(&mut self) -> Result<Type, CompileError> {
         let (ty, _qualifiers) = self.parse_type_with_qualifiers()?;
         Ok(ty)
     }
 
-    fn parse_type_with_qualifiers(&mut self) -> Result<(Type, TypeQualifiers), String> {
+    fn parse_type_with_qualifiers(&mut self) -> Result<(Type, TypeQualifiers), CompileError> {
         let mut qualifiers = TypeQualifiers::default();
 
         // Parse storage class specifiers and type qualifiers
         loop {
             let token = self.peek();
             match token {
-                Some(Token::Static | Token::Extern) => {
+                Some(Token::Static | Token::Extern | Token::ThreadLocal) => {
+                    self.advance();
+                }
+                Some(Token::Inline | Token::Noreturn) => {
+                    self.advance();
+                }
+                Some(Token::Atomic) if !self.check_at(1, &|t: &Token| matches!(t, Token::OpenParenthesis)) => {
+                    // `_Atomic` as a qualifier; accesses are not made atomic
                     self.advance();
                 }
-                Some(Token::Inline) => {
+                Some(Token::Alignas) => {
                     self.advance();
+                    self.parse_alignas()?;
                 }
                 Some(Token::Const) => {
                     qualifiers.is_const = true;
@@ -59,6 +78,8 @@ impl<'a> TypeParser for Parser<'a> {
         let mut is_signed = false;
         let mut long_count = 0; // 0 = no long, 1 = long, 2 = long long
         let mut is_short = false;
+        let mut is_complex = false;
+        let mut is_function_typedef = false;
         let mut base_type = None;
 
         // Collect type specifiers
@@ -67,87 +88,112 @@ impl<'a> TypeParser for Parser<'a> {
             match token {
                 Some(Token::Unsigned) => {
                     if is_signed {
-                        return Err("Cannot combine 'unsigned' and 'signed'".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot combine 'unsigned' and 'signed'"));
                     }
                     is_unsigned = true;
                     self.advance();
                 }
                 Some(Token::Signed) => {
                     if is_unsigned {
-                        return Err("Cannot combine 'unsigned' and 'signed'".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot combine 'unsigned' and 'signed'"));
                     }
                     is_signed = true;
                     self.advance();
                 }
                 Some(Token::Long) => {
                     if is_short {
-                        return Err("Cannot combine 'long' and 'short'".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot combine 'long' and 'short'"));
                     }
                     long_count += 1;
                     if long_count > 2 {
-                        return Err("Too many 'long' specifiers".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Too many 'long' specifiers"));
                     }
                     self.advance();
                 }
                 Some(Token::Short) => {
                     if long_count > 0 {
-                        return Err("Cannot combine 'long' and 'short'".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot combine 'long' and 'short'"));
                     }
                     is_short = true;
                     self.advance();
                 }
                 Some(Token::Int) => {
                     if base_type.is_some() {
-                        return Err("Multiple base types specified".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Multiple base types specified"));
                     }
                     base_type = Some(Type::Int);
                     self.advance();
                 }
                 Some(Token::Char) => {
                     if base_type.is_some() || long_count > 0 || is_short {
-                        return Err("Invalid type combination with 'char'".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Invalid type combination with 'char'"));
                     }
                     base_type = Some(Type::Char);
                     self.advance();
                 }
                 Some(Token::Void) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify 'void' type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify 'void' type"));
                     }
                     base_type = Some(Type::Void);
                     self.advance();
                 }
                 Some(Token::Float) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify 'float' type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify 'float' type"));
                     }
                     base_type = Some(Type::Float);
                     self.advance();
                 }
                 Some(Token::Double) => {
                     if is_unsigned || is_signed || is_short {
-                        return Err("Cannot modify 'double' with unsigned/signed/short".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify 'double' with unsigned/signed/short"));
                     }
                     if long_count > 1 {
-                        return Err("'long long double' is not valid".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "'long long double' is not valid"));
                     }
                     base_type = Some(Type::Double);
                     self.advance();
                 }
                 Some(Token::Bool) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify '_Bool' type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify '_Bool' type"));
                     }
                     base_type = Some(Type::Bool);
                     self.advance();
                 }
+                Some(Token::Complex) => {
+                    is_complex = true;
+                    self.advance();
+                }
                 Some(Token::Register) => {
                     // 'register' storage class — just skip it
                     self.advance();
                 }
+                Some(Token::Atomic) => {
+                    self.advance();
+                    // `_Atomic(T)` names T; a bare `_Atomic` here follows the specifiers
+                    if self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
+                        if base_type.is_some() || is_unsigned || is_signed || long_count > 0 || is_short {
+                            return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot combine '_Atomic(type)' with other type specifiers"));
+                        }
+                        base_type = Some(self.parse_type()?);
+                        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+                        break;
+                    }
+                }
                 Some(Token::Struct) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify struct type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify struct type"));
+                    }
+                    if self.is_struct_definition() {
+                        // A type with its own body (`struct S { ... } s;`, a
+                        // member, a typedef): the definition is recorded too,
+                        // under a made-up tag if it has none
+                        let def = self.parse_struct_definition()?;
+                        base_type = Some(Type::Struct(def.name.clone()));
+                        self.nested_structs.push(def);
+                        break;
                     }
                     self.advance();
                     let (struct_type, _) = self.parse_struct_type()?;
@@ -156,7 +202,13 @@ impl<'a> TypeParser for Parser<'a> {
                 }
                 Some(Token::Union) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify union type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify union type"));
+                    }
+                    if self.is_union_definition() {
+                        let def = self.parse_union_definition()?;
+                        base_type = Some(Type::Union(def.name.clone()));
+                        self.nested_unions.push(def);
+                        break;
                     }
                     self.advance();
                     let (union_type, _) = self.parse_union_type()?;
@@ -165,7 +217,7 @@ impl<'a> TypeParser for Parser<'a> {
                 }
                 Some(Token::Enum) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify enum type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify enum type"));
                     }
                     self.advance();
                     let (enum_type, _) = self.parse_enum_type()?;
@@ -174,7 +226,7 @@ impl<'a> TypeParser for Parser<'a> {
                 }
                 Some(Token::Typeof) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify typeof type".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify typeof type"));
                     }
                     self.advance();
                     self.expect(|t| matches!(t, Token::OpenParenthesis), "'('")?;
@@ -192,17 +244,32 @@ impl<'a> TypeParser for Parser<'a> {
                 }
                 Some(Token::Identifier { value }) if self.typedefs.contains(value) => {
                     if is_unsigned || is_signed || long_count > 0 || is_short {
-                        return Err("Cannot modify typedef".to_string());
+                        return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "Cannot modify typedef"));
                     }
                     let v = value.clone();
                     self.advance();
-                    base_type = Some(Type::Typedef(v));
+                    is_function_typedef = self.function_typedefs.contains(&v);
+                    base_type = Some(self.typedef_type(v));
                     break;
                 }
                 _ => break,
             }
         }
 
+        if is_complex {
+            // Plain `_Complex` means `_Complex double`; `long double` is double here
+            let elem = match base_type.take() {
+                None | Some(Type::Double) => Type::Double,
+                Some(Type::Float) => Type::Float,
+                Some(_) => return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "'_Complex' requires 'float' or 'double'")),
+            };
+            if is_unsigned || is_signed || is_short || (long_count > 0 && elem != Type::Double) {
+                return Err(CompileError::new(ErrorCode::InvalidTypeSpecifier, "'_Complex' requires 'float' or 'double'"));
+            }
+            long_count = 0;
+            base_type = Some(Type::Complex(Box::new(elem)));
+        }
+
         // If no base type specified, default to int for modifiers
         if base_type.is_none() && (is_unsigned || is_signed || long_count > 0 || is_short) {
             base_type = Some(Type::Int);
@@ -244,32 +311,42 @@ impl<'a> TypeParser for Parser<'a> {
             }
             Some(ty) => ty,
             None => {
-                return Err("expected type specifier".to_string());
+                return Err(syntax_error("expected type specifier"));
             }
         };
 
-        // Handle pointer types
+        // Qualifiers may also follow the specifiers (`char const *p`)
+        self.parse_qualifiers_into(&mut qualifiers);
+
+        // Handle pointer types. Each pointer records the qualifiers of what it
+        // points to, so `const char * const * p` keeps both consts in the type;
+        // qualifiers after the last `*` belong to the declared object itself.
         while self.match_token(|t| matches!(t, Token::Star)) {
-            // Skip qualifiers after * (e.g., int * restrict p)
-            while self.match_token(|t| matches!(t, Token::Const | Token::Volatile | Token::Restrict)) {
-                // Qualifiers after * apply to the pointer itself
-                // For now, we just skip them (not tracked per-pointer-level)
+            // `handler *` is the function pointer that a function typedef
+            // already stands for
+            if !std::mem::take(&mut is_function_typedef) {
+                final_type = Type::qualified_ptr(final_type, qualifiers);
             }
-            final_type = Type::ptr(final_type);
+            qualifiers = TypeQualifiers::default();
+            self.parse_qualifiers_into(&mut qualifiers);
         }
 
         Ok((final_type, qualifiers))
     }
 
-    fn parse_struct_definition(&mut self) -> Result<model::StructDef, String> {
+    fn parse_struct_definition(&mut self) -> Result<model::StructDef, CompileError> {
+        let start = self.pos;
         self.expect(|t| matches!(t, Token::Struct), "struct")?;
         
         // Parse attributes before struct name (e.g., struct __attribute__((packed)) foo)
         let mut attributes = self.parse_attributes()?;
         
-        let name = match self.advance() {
-            Some(Token::Identifier { value }) => value.clone(),
-            other => return Err(format!("expected struct name identifier, found {:?}", other)),
+        let name = match self.peek() {
+            Some(Token::OpenBrace) => self.anonymous_tag(),
+            _ => match self.advance() {
+                Some(Token::Identifier { value }) => value.clone(),
+                other => return Err(syntax_error(format!("expected struct name identifier, found {:?}", other))),
+            },
         };
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
 
@@ -293,8 +370,18 @@ impl<'a> TypeParser for Parser<'a> {
                 }
             };
             
-            let field_name = match self.advance() {
-                Some(Token::Identifier { value }) => value.clone(),
+            // Unnamed bit-fields (`int : 3;`) still take up space in the layout
+            let name_pos = self.pos;
+            let field_name = match self.peek() {
+                // Unnamed bit-field, or an anonymous struct/union member
+                Some(Token::Colon) => String::new(),
+                Some(Token::Semicolon) if Self::is_anonymous_record(&ty) => String::new(),
+                Some(Token::Identifier { value }) => {
+                    let field = value.clone();
+                    self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Member, Some(name.clone())));
+                    self.advance();
+                    field
+                }
                 _ => {
                     // Skip to next semicolon or closing brace
                     while !self.is_at_end() 
@@ -311,6 +398,7 @@ impl<'a> TypeParser for Parser<'a> {
 
             // Handle optional array in struct field (supports multi-dimensional)
             let mut final_ty = ty;
+            let mut dims = Vec::new();
             while self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 // Check if array size is provided (empty brackets [] are allowed)
                 let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
@@ -333,13 +421,14 @@ impl<'a> TypeParser for Parser<'a> {
                     }
                 };
                 self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                final_ty = Type::Array(Box::new(final_ty), size);
+                dims.push(size);
             }
+            final_ty = array_of(final_ty, &dims);
 
             // Check for bit field syntax (: width)
             let bit_width = if self.match_token(|t| matches!(t, Token::Colon)) {
-                match self.advance() {
-                    Some(Token::Constant { value, .. }) => Some(*value as usize),
+                match self.parse_constant_expr().map(usize::try_from) {
+                    Ok(Ok(width)) => Some(width),
                     _ => {
                         // Skip malformed bit field
                         while !self.is_at_end() 
@@ -362,6 +451,14 @@ impl<'a> TypeParser for Parser<'a> {
                 name: field_name,
                 bit_width,
             });
+            // `int x, *p, buf[4];` declares several members
+            let base = self.declarator_base(&fields[fields.len() - 1].field_type, name_pos);
+            while self.match_token(|t| matches!(t, Token::Comma)) {
+                match self.parse_extra_member(&base, &name, true) {
+                    Some(field) => fields.push(field),
+                    None => break,
+                }
+            }
             
             if self.expect(|t| matches!(t, Token::Semicolon), "';'").is_err() {
                 // Failed to find semicolon - skip to next one or closing brace
@@ -381,15 +478,23 @@ impl<'a> TypeParser for Parser<'a> {
         // Parse attributes after struct body (e.g., struct foo { ... } __attribute__((packed)))
         let mut more_attributes = self.parse_attributes()?;
         attributes.append(&mut more_attributes);
-        
-        Ok(model::StructDef { name, fields, attributes })
+        if let Some(pack) = self.pack_at(start) {
+            attributes.push(model::Attribute::Pack(pack));
+        }
+
+        let def = model::StructDef { name, fields, attributes };
+        self.struct_defs.insert(def.name.clone(), def.clone());
+        Ok(def)
     }
 
-    fn parse_union_definition(&mut self) -> Result<model::UnionDef, String> {
+    fn parse_union_definition(&mut self) -> Result<model::UnionDef, CompileError> {
         self.expect(|t| matches!(t, Token::Union), "union")?;
-        let name = match self.advance() {
-            Some(Token::Identifier { value }) => value.clone(),
-            other => return Err(format!("expected union name identifier, found {:?}", other)),
+        let name = match self.peek() {
+            Some(Token::OpenBrace) => self.anonymous_tag(),
+            _ => match self.advance() {
+                Some(Token::Identifier { value }) => value.clone(),
+                other => return Err(syntax_error(format!("expected union name identifier, found {:?}", other))),
+            },
         };
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
 
@@ -413,8 +518,14 @@ impl<'a> TypeParser for Parser<'a> {
                 }
             };
             
-            let field_name = match self.advance() {
-                Some(Token::Identifier { value }) => value.clone(),
+            let field_pos = self.pos;
+            let field_name = match self.peek() {
+                Some(Token::Semicolon) if Self::is_anonymous_record(&ty) => String::new(),
+                Some(Token::Identifier { value }) => {
+                    let field = value.clone();
+                    self.advance();
+                    field
+                }
                 _ => {
                     // Skip to next semicolon or closing brace
                     while !self.is_at_end() 
@@ -428,9 +539,13 @@ impl<'a> TypeParser for Parser<'a> {
                     continue; // Skip this field
                 }
             };
+            if !field_name.is_empty() {
+                self.note_symbol(field_pos, Occurrence::Def(SymbolKind::Member, Some(name.clone())));
+            }
 
             // Handle optional array in union field (supports multi-dimensional)
             let mut final_ty = ty;
+            let mut dims = Vec::new();
             while self.match_token(|t| matches!(t, Token::OpenBracket)) {
                 // Check if array size is provided (empty brackets [] are allowed)
                 let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
@@ -453,14 +568,22 @@ impl<'a> TypeParser for Parser<'a> {
                     }
                 };
                 self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
-                final_ty = Type::Array(Box::new(final_ty), size);
+                dims.push(size);
             }
+            final_ty = array_of(final_ty, &dims);
 
             fields.push(model::StructField {
                 field_type: final_ty,
                 name: field_name,
                 bit_width: None, // Unions don't support bit fields
             });
+            let base = self.declarator_base(&fields[fields.len() - 1].field_type, field_pos);
+            while self.match_token(|t| matches!(t, Token::Comma)) {
+                match self.parse_extra_member(&base, &name, false) {
+                    Some(field) => fields.push(field),
+                    None => break,
+                }
+            }
             
             if self.expect(|t| matches!(t, Token::Semicolon), "';'").is_err() {
                 // Failed to find semicolon - skip to next one or closing brace
@@ -476,14 +599,21 @@ impl<'a> TypeParser for Parser<'a> {
         }
 
         self.expect(|t| matches!(t, Token::CloseBrace), "'}'")?;
-        Ok(model::UnionDef { name, fields })
+        let def = model::UnionDef { name, fields };
+        self.union_defs.insert(def.name.clone(), def.clone());
+        Ok(def)
     }
 
-    fn parse_enum_definition(&mut self) -> Result<model::EnumDef, String> {
+    fn parse_enum_definition(&mut self) -> Result<model::EnumDef, CompileError> {
         self.expect(|t| matches!(t, Token::Enum), "enum")?;
-        let name = match self.advance() {
-            Some(Token::Identifier { value }) => value.clone(),
-            other => return Err(format!("expected enum name identifier, found {:?}", other)),
+        // Anonymous enums only declare their constants
+        let name = match self.peek() {
+            Some(Token::Identifier { value }) => {
+                let name = value.clone();
+                self.advance();
+                name
+            }
+            _ => String::new(),
         };
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
 
@@ -491,27 +621,22 @@ impl<'a> TypeParser for Parser<'a> {
         let mut next_value = 0_i64;
 
         while !self.check(|t| matches!(t, Token::CloseBrace)) && !self.is_at_end() {
+            self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Enumerator, None));
             let const_name = match self.advance() {
                 Some(Token::Identifier { value }) => value.clone(),
-                other => return Err(format!("expected enum constant name, found {:?}", other)),
+                other => return Err(syntax_error(format!("expected enum constant name, found {:?}", other))),
             };
 
             let value = if self.match_token(|t| matches!(t, Token::Equal)) {
-                // Explicit value: RED = 10 or ERROR = -1
-                let is_negative = self.match_token(|t| matches!(t, Token::Minus));
-                match self.advance() {
-                    Some(Token::Constant { value, .. }) => {
-                        let actual_value = if is_negative { -value } else { *value };
-                        next_value = actual_value;
-                        actual_value
-                    }
-                    other => return Err(format!("expected constant value, found {:?}", other)),
-                }
+                // Explicit value: RED = 10, ERROR = -1, or B = A << 1
+                next_value = self.parse_constant_expr()?;
+                next_value
             } else {
                 // Auto-increment: GREEN (implicit = 0, 1, 2, ...)
                 next_value
             };
 
+            self.enum_constants.insert(const_name.clone(), value);
             constants.push((const_name, value));
             next_value += 1;
 
@@ -522,12 +647,111 @@ impl<'a> TypeParser for Parser<'a> {
         }
 
         self.expect(|t| matches!(t, Token::CloseBrace), "'}'")?;
-        Ok(model::EnumDef { name, constants })
+        Ok(model::EnumDef::new(name, constants))
     }
 }
 
 impl<'a> Parser<'a> {
-    fn parse_struct_type(&mut self) -> Result<(Type, TypeQualifiers), String> {
+    /// The type and pointee qualifiers a member declarator started from,
+    /// given the member's full type `ty` and the position of its name: the
+    /// `*`s just before the name are peeled off, so that the next declarator
+    /// after a comma can add its own.
+    fn declarator_base(&self, ty: &Type, name_pos: usize) -> (Type, TypeQualifiers) {
+        let mut base = ty.clone();
+        let mut qualifiers = TypeQualifiers::default();
+        // Arrays come after the name, so peel them first
+        while let Type::Array(elem, _) = base {
+            base = *elem;
+        }
+        let mut pos = name_pos;
+        while pos > 0 && matches!(self.tokens[pos - 1], Token::Star | Token::Const | Token::Volatile | Token::Restrict) {
+            pos -= 1;
+            if matches!(self.tokens[pos], Token::Star) {
+                if let Type::Pointer(inner, pointee) = base {
+                    base = *inner;
+                    qualifiers = pointee;
+                }
+            }
+        }
+        (base, qualifiers)
+    }
+
+    /// One more member declarator after a comma, such as `*p`, `buf[4]`, or
+    /// (when `bit_fields`) `flag : 1`. `None` when it cannot be read; the
+    /// caller's recovery then skips to the `;`.
+    fn parse_extra_member(&mut self, base: &(Type, TypeQualifiers), record: &str, bit_fields: bool) -> Option<model::StructField> {
+        let (mut ty, mut qualifiers) = base.clone();
+        loop {
+            if self.match_token(|t| matches!(t, Token::Star)) {
+                ty = Type::qualified_ptr(ty, std::mem::take(&mut qualifiers));
+            } else if !self.match_token(|t| matches!(t, Token::Const | Token::Volatile | Token::Restrict)) {
+                break;
+            }
+        }
+        let name = match self.peek() {
+            Some(Token::Identifier { value }) => value.clone(),
+            Some(Token::Colon) if bit_fields => String::new(),
+            _ => return None,
+        };
+        if !name.is_empty() {
+            self.note_symbol(self.pos, Occurrence::Def(SymbolKind::Member, Some(record.to_string())));
+            self.advance();
+        }
+        // Dimensions wrap the same way as for the first declarator
+        let mut dims = Vec::new();
+        while self.match_token(|t| matches!(t, Token::OpenBracket)) {
+            let size = if self.check(|t| matches!(t, Token::CloseBracket)) { 0 } else { self.parse_array_size().ok()? };
+            self.expect(|t| matches!(t, Token::CloseBracket), "']'").ok()?;
+            dims.push(size);
+        }
+        ty = array_of(ty, &dims);
+        let bit_width = if bit_fields && self.match_token(|t| matches!(t, Token::Colon)) {
+            Some(usize::try_from(self.parse_constant_expr().ok()?).ok()?)
+        } else {
+            None
+        };
+        Some(model::StructField { field_type: ty, name, bit_width })
+    }
+
+    /// A tag for a struct or union definition written without one. It
+    /// cannot clash with a real tag.
+    fn anonymous_tag(&mut self) -> String {
+        self.anonymous_records += 1;
+        format!("{}{}>", ANONYMOUS_TAG_PREFIX, self.anonymous_records)
+    }
+
+    /// Whether `ty` names a struct or union defined without a tag, whose
+    /// members belong to the enclosing record when it is declared unnamed.
+    fn is_anonymous_record(ty: &Type) -> bool {
+        matches!(ty, Type::Struct(tag) | Type::Union(tag) if tag.starts_with(ANONYMOUS_TAG_PREFIX))
+    }
+
+    /// Consume any run of `const` / `volatile` / `restrict` / `_Atomic`.
+    fn parse_qualifiers_into(&mut self, qualifiers: &mut TypeQualifiers) {
+        loop {
+            match self.peek() {
+                Some(Token::Const) => qualifiers.is_const = true,
+                Some(Token::Volatile) => qualifiers.is_volatile = true,
+                Some(Token::Restrict) => qualifiers.is_restrict = true,
+                Some(Token::Atomic) => {}
+                _ => break,
+            }
+            self.advance();
+        }
+    }
+
+    /// The type a use of typedef `name` stands for. Array and function
+    /// typedefs resolve to their definition so declarations get the real
+    /// size and shape; other typedefs stay as `Type::Typedef`.
+    fn typedef_type(&self, name: String) -> Type {
+        match self.typedef_defs.get(&name) {
+            Some(ty @ Type::Array(..)) => ty.clone(),
+            Some(ty) if self.function_typedefs.contains(&name) => ty.clone(),
+            _ => Type::Typedef(name),
+        }
+    }
+
+    fn parse_struct_type(&mut self) -> Result<(Type, TypeQualifiers), CompileError> {
         // Skip attributes before struct name
         let _ = self.parse_attributes()?;
         
@@ -543,7 +767,7 @@ impl<'a> Parser<'a> {
         Ok((Type::Struct(name), TypeQualifiers::default()))
     }
 
-    fn parse_union_type(&mut self) -> Result<(Type, TypeQualifiers), String> {
+    fn parse_union_type(&mut self) -> Result<(Type, TypeQualifiers), CompileError> {
         // Skip attributes before union name
         let _ = self.parse_attributes()?;
         
@@ -559,7 +783,7 @@ impl<'a> Parser<'a> {
         Ok((Type::Union(name), TypeQualifiers::default()))
     }
 
-    fn parse_enum_type(&mut self) -> Result<(Type, TypeQualifiers), String> {
+    fn parse_enum_type(&mut self) -> Result<(Type, TypeQualifiers), CompileError> {
         // For "enum Name", return Type::Enum(name) (behaves like int but carries the tag)
         if let Some(Token::Identifier { value, .. }) = self.peek() {
             let name = value.clone();
@@ -571,7 +795,85 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub(crate) fn skip_parentheses(&mut self) -> Result<(), String> {
+    /// The declarator after a parameter's type: an optional name with `[N]`
+    /// suffixes, or a function pointer `(*name)(params)` whose name may be
+    /// omitted. Returns the parameter type and the name with its token position.
+    pub(crate) fn parse_param_declarator(&mut self, mut ty: Type) -> Result<(Type, Option<(usize, String)>), CompileError> {
+        if self.check(|t| matches!(t, Token::OpenParenthesis)) && self.check_at(1, |t| matches!(t, Token::Star)) {
+            self.advance(); // (
+            self.advance(); // *
+            let mut qualifiers = TypeQualifiers::default();
+            self.parse_qualifiers_into(&mut qualifiers);
+            let name = match self.peek() {
+                Some(Token::Identifier { value }) => {
+                    let name = (self.pos, value.clone());
+                    self.advance();
+                    Some(name)
+                }
+                _ => None,
+            };
+            self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+            let ty = self.parse_function_pointer_type(ty)?;
+            return Ok((ty, name));
+        }
+
+        let name = match self.peek() {
+            Some(Token::Identifier { value }) => {
+                let name = (self.pos, value.clone());
+                self.advance();
+                Some(name)
+            }
+            _ => None,
+        };
+        // Array syntax: type name[] or type[] (supports multi-dimensional)
+        let mut dims = Vec::new();
+        while self.match_token(|t| matches!(t, Token::OpenBracket)) {
+            let size = if self.check(|t| matches!(t, Token::CloseBracket)) {
+                0 // Use 0 to represent unsized array
+            } else {
+                self.parse_array_size()?
+            };
+            self.expect(|t| matches!(t, Token::CloseBracket), "']'")?;
+            dims.push(size);
+        }
+        ty = array_of(ty, &dims);
+        // An array parameter is a pointer to its first element
+        if let Type::Array(elem, _) = ty {
+            ty = Type::ptr(*elem);
+        }
+        Ok((ty, name))
+    }
+
+    /// The parenthesized parameter list of a function pointer declarator,
+    /// giving a pointer to a function returning `return_type`.
+    pub(crate) fn parse_function_pointer_type(&mut self, return_type: Type) -> Result<Type, CompileError> {
+        self.expect(|t| matches!(t, Token::OpenParenthesis), "'(' for function pointer parameters")?;
+        let mut param_types = Vec::new();
+        let mut is_variadic = false;
+        if !self.check(|t| matches!(t, Token::CloseParenthesis)) {
+            loop {
+                // Variadic: `...` must be the last parameter
+                if self.match_token(|t| matches!(t, Token::Ellipsis)) {
+                    is_variadic = true;
+                    break;
+                }
+                let param_type = self.parse_type()?;
+                let (param_type, _) = self.parse_param_declarator(param_type)?;
+                param_types.push(param_type);
+                if !self.match_token(|t| matches!(t, Token::Comma)) {
+                    break;
+                }
+            }
+        }
+        self.expect(|t| matches!(t, Token::CloseParenthesis), "')'")?;
+        Ok(Type::FunctionPointer {
+            return_type: Box::new(return_type),
+            param_types,
+            is_variadic,
+        })
+    }
+
+    pub(crate) fn skip_parentheses(&mut self) -> Result<(), CompileError> {
         if !self.match_token(|t| matches!(t, Token::OpenParenthesis)) {
             return Ok(());
         }
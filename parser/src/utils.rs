@@ -1,24 +1,24 @@
-use model::Token;
-use crate::parser::Parser;
+use model::{CompileError, Token};
+use crate::parser::{syntax_error, Parser};
 use crate::types::TypeParser;
 
 pub(crate) trait ParserUtils {
     fn is_function_definition(&self) -> bool;
     fn is_inline_function(&self) -> bool;
-    fn skip_extern_inline_function(&mut self) -> Result<(), String>;
+    fn skip_extern_inline_function(&mut self) -> Result<(), CompileError>;
     fn is_function_declaration(&self) -> bool;
-    fn skip_function_declaration(&mut self) -> Result<(), String>;
-    fn skip_extern_declaration(&mut self) -> Result<(), String>;
+    fn skip_function_declaration(&mut self) -> Result<(), CompileError>;
+    fn skip_extern_declaration(&mut self) -> Result<(), CompileError>;
     fn skip_parentheses_from(&self, start_pos: usize) -> usize;
     fn skip_block_from(&self, start_pos: usize) -> usize;
     fn is_struct_definition(&self) -> bool;
     fn is_union_definition(&self) -> bool;
     fn is_struct_forward_declaration(&self) -> bool;
     fn is_union_forward_declaration(&self) -> bool;
-    fn skip_forward_declaration(&mut self) -> Result<(), String>;
-    fn skip_top_level_item(&mut self) -> Result<(), String>;
-    fn skip_block_internal(&mut self) -> Result<(), String>;
-    fn skip_parentheses_content(&mut self) -> Result<(), String>;
+    fn skip_forward_declaration(&mut self) -> Result<(), CompileError>;
+    fn skip_top_level_item(&mut self) -> Result<(), CompileError>;
+    fn skip_block_internal(&mut self) -> Result<(), CompileError>;
+    fn skip_parentheses_content(&mut self) -> Result<(), CompileError>;
     fn check_is_type(&self) -> bool;
     fn check_is_type_at(&self, offset: usize) -> bool;
 }
@@ -36,6 +36,10 @@ impl<'a> ParserUtils for Parser<'a> {
                 Token::Static
                     | Token::Extern
                     | Token::Inline
+                    | Token::ThreadLocal
+                    | Token::Noreturn
+                    | Token::Alignas
+                    | Token::Atomic
                     | Token::Attribute
                     | Token::Extension
                     | Token::Const
@@ -64,7 +68,7 @@ impl<'a> ParserUtils for Parser<'a> {
             let tok = &self.tokens[temp_pos];
             if matches!(
                 tok,
-                Token::Int | Token::Void | Token::Char | Token::Float | Token::Double | Token::Long | Token::Short | Token::Unsigned | Token::Signed
+                Token::Int | Token::Void | Token::Char | Token::Bool | Token::Float | Token::Double | Token::Long | Token::Short | Token::Unsigned | Token::Signed | Token::Complex
             ) {
                 temp_pos += 1;
             } else if matches!(tok, Token::Struct | Token::Union | Token::Enum) {
@@ -91,7 +95,7 @@ impl<'a> ParserUtils for Parser<'a> {
         while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Star) {
             temp_pos += 1;
             // Skip qualifiers after *
-            while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Const | Token::Volatile | Token::Restrict) {
+            while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Const | Token::Volatile | Token::Restrict | Token::Atomic) {
                 temp_pos += 1;
             }
         }
@@ -108,6 +112,18 @@ impl<'a> ParserUtils for Parser<'a> {
             }
         }
 
+        // `(*name(`: a function returning a function pointer, whose declarator
+        // group is still open after the parameter list
+        let mut paren_depth = 0;
+        if matches!(self.tokens.get(temp_pos), Some(Token::OpenParenthesis))
+            && matches!(self.tokens.get(temp_pos + 1), Some(Token::Star))
+            && matches!(self.tokens.get(temp_pos + 2), Some(Token::Identifier { .. }))
+            && matches!(self.tokens.get(temp_pos + 3), Some(Token::OpenParenthesis))
+        {
+            temp_pos += 2;
+            paren_depth = 1;
+        }
+
         if temp_pos >= self.tokens.len() {
             return false;
         }
@@ -124,7 +140,6 @@ impl<'a> ParserUtils for Parser<'a> {
         }
 
         // Search for '{' or ';' to distinguish definition vs prototype
-        let mut paren_depth = 0;
         while temp_pos < self.tokens.len() {
             match &self.tokens[temp_pos] {
                 Token::OpenParenthesis => paren_depth += 1,
@@ -138,7 +153,7 @@ impl<'a> ParserUtils for Parser<'a> {
         false
     }
 
-    /// Check if this is an extern inline or static inline function (from headers)
+    /// Check if this is an extern inline or static inline function definition
     fn is_inline_function(&self) -> bool {
         let mut temp_pos = self.pos;
         let mut has_inline = false;
@@ -161,7 +176,7 @@ impl<'a> ParserUtils for Parser<'a> {
                     has_static = true;
                     temp_pos += 1;
                 }
-                Token::Const | Token::Volatile | Token::Restrict | Token::Extension => {
+                Token::Const | Token::Volatile | Token::Restrict | Token::Extension | Token::Noreturn => {
                     temp_pos += 1;
                 }
                 Token::Attribute => {
@@ -179,9 +194,9 @@ impl<'a> ParserUtils for Parser<'a> {
     }
 
     /// Skip an extern inline function definition
-    fn skip_extern_inline_function(&mut self) -> Result<(), String> {
+    fn skip_extern_inline_function(&mut self) -> Result<(), CompileError> {
         // Skip modifiers and type
-        while self.check(|t| matches!(t, Token::Extern | Token::Inline | Token::Static | Token::Const | Token::Volatile | Token::Restrict | Token::Extension | Token::Attribute)) {
+        while self.check(|t| matches!(t, Token::Extern | Token::Inline | Token::Static | Token::Noreturn | Token::Const | Token::Volatile | Token::Restrict | Token::Extension | Token::Attribute | Token::Alignas)) {
             self.advance();
             if self.check(|t| matches!(t, Token::OpenParenthesis)) {
                 self.skip_parentheses()?;
@@ -242,6 +257,10 @@ impl<'a> ParserUtils for Parser<'a> {
                 Token::Static
                     | Token::Extern
                     | Token::Inline
+                    | Token::ThreadLocal
+                    | Token::Noreturn
+                    | Token::Alignas
+                    | Token::Atomic
                     | Token::Attribute
                     | Token::Extension
                     | Token::Const
@@ -268,7 +287,7 @@ impl<'a> ParserUtils for Parser<'a> {
             let tok = &self.tokens[temp_pos];
             if matches!(
                 tok,
-                Token::Int | Token::Void | Token::Char | Token::Float | Token::Double | Token::Long | Token::Short | Token::Unsigned | Token::Signed
+                Token::Int | Token::Void | Token::Char | Token::Bool | Token::Float | Token::Double | Token::Long | Token::Short | Token::Unsigned | Token::Signed | Token::Complex
             ) {
                 temp_pos += 1;
             } else if matches!(tok, Token::Struct | Token::Union | Token::Enum) {
@@ -295,7 +314,7 @@ impl<'a> ParserUtils for Parser<'a> {
         while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Star) {
             temp_pos += 1;
             // Skip qualifiers after *
-            while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Const | Token::Volatile | Token::Restrict) {
+            while temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Const | Token::Volatile | Token::Restrict | Token::Atomic) {
                 temp_pos += 1;
             }
         }
@@ -312,6 +331,18 @@ impl<'a> ParserUtils for Parser<'a> {
             }
         }
 
+        // `(*name(`: a function returning a function pointer, whose declarator
+        // group is still open after the parameter list
+        let mut paren_depth = 0;
+        if matches!(self.tokens.get(temp_pos), Some(Token::OpenParenthesis))
+            && matches!(self.tokens.get(temp_pos + 1), Some(Token::Star))
+            && matches!(self.tokens.get(temp_pos + 2), Some(Token::Identifier { .. }))
+            && matches!(self.tokens.get(temp_pos + 3), Some(Token::OpenParenthesis))
+        {
+            temp_pos += 2;
+            paren_depth = 1;
+        }
+
         if temp_pos >= self.tokens.len() {
             return false;
         }
@@ -328,7 +359,6 @@ impl<'a> ParserUtils for Parser<'a> {
         }
 
         // Search for ';' (declaration) but NOT '{' (definition)
-        let mut paren_depth = 0;
         while temp_pos < self.tokens.len() {
             match &self.tokens[temp_pos] {
                 Token::OpenParenthesis => paren_depth += 1,
@@ -342,7 +372,7 @@ impl<'a> ParserUtils for Parser<'a> {
         false
     }
 
-    fn skip_function_declaration(&mut self) -> Result<(), String> {
+    fn skip_function_declaration(&mut self) -> Result<(), CompileError> {
         let mut paren_depth = 0;
         while !self.is_at_end() {
             match self.peek() {
@@ -356,17 +386,17 @@ impl<'a> ParserUtils for Parser<'a> {
             }
             self.advance();
         }
-        Err("Unexpected end of file in function declaration".to_string())
+        Err(syntax_error("Unexpected end of file in function declaration"))
     }
 
-    fn skip_extern_declaration(&mut self) -> Result<(), String> {
+    fn skip_extern_declaration(&mut self) -> Result<(), CompileError> {
         while !self.is_at_end() {
             if self.match_token(|t| matches!(t, Token::Semicolon)) {
                 return Ok(());
             }
             self.advance();
         }
-        Err("Unexpected end of file in extern declaration".to_string())
+        Err(syntax_error("Unexpected end of file in extern declaration"))
     }
 
     fn skip_parentheses_from(&self, start_pos: usize) -> usize {
@@ -485,7 +515,7 @@ impl<'a> ParserUtils for Parser<'a> {
         temp_pos < self.tokens.len() && matches!(self.tokens[temp_pos], Token::Semicolon)
     }
 
-    fn skip_forward_declaration(&mut self) -> Result<(), String> {
+    fn skip_forward_declaration(&mut self) -> Result<(), CompileError> {
         self.advance(); // skip struct/union keyword
         
         while self.check(|t| matches!(t, Token::Attribute | Token::Extension)) {
@@ -503,7 +533,7 @@ impl<'a> ParserUtils for Parser<'a> {
         Ok(())
     }
 
-    fn skip_top_level_item(&mut self) -> Result<(), String> {
+    fn skip_top_level_item(&mut self) -> Result<(), CompileError> {
         while !self.is_at_end() {
             match self.peek() {
                 Some(Token::Semicolon) => {
@@ -542,7 +572,7 @@ impl<'a> ParserUtils for Parser<'a> {
         Ok(())
     }
 
-    fn skip_block_internal(&mut self) -> Result<(), String> {
+    fn skip_block_internal(&mut self) -> Result<(), CompileError> {
         self.expect(|t| matches!(t, Token::OpenBrace), "'{'")?;
         let mut depth = 1;
         while depth > 0 && !self.is_at_end() {
@@ -555,7 +585,7 @@ impl<'a> ParserUtils for Parser<'a> {
         Ok(())
     }
 
-    fn skip_parentheses_content(&mut self) -> Result<(), String> {
+    fn skip_parentheses_content(&mut self) -> Result<(), CompileError> {
         let mut depth = 1;
         while depth > 0 && !self.is_at_end() {
             match self.peek() {
@@ -589,6 +619,7 @@ impl<'a> ParserUtils for Parser<'a> {
                 | Token::Char
                 | Token::Float
                 | Token::Double
+                | Token::Complex
                 | Token::Struct
                 | Token::Union
                 | Token::Enum
@@ -602,11 +633,16 @@ impl<'a> ParserUtils for Parser<'a> {
                 | Token::Volatile
                 | Token::Restrict
                 | Token::Attribute
-                | Token::Extension
                 | Token::Typeof
                 | Token::Bool
-                | Token::Register,
+                | Token::Register
+                | Token::Alignas
+                | Token::Atomic
+                | Token::ThreadLocal
+                | Token::Noreturn,
             ) => true,
+            // `__extension__` may prefix a type name or an expression
+            Some(Token::Extension) => self.check_is_type_at(offset + 1),
             Some(Token::Identifier { value }) => self.typedefs.contains(value),
             _ => false,
         }
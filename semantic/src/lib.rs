@@ -1,6 +1,13 @@
-use model::{Program, Function, Stmt, Expr, Type, BinaryOp, TypeEnv, TypeQualifiers};
+use model::consteval::{
+    conversion_warning, ConstValue, eval_constant, eval_constant_with_warnings, eval_integer_constant, invalid_size_operand,
+};
+use model::{Program, Function, Stmt, Expr, Type, BinaryOp, TypeEnv, TypeQualifiers, CompileError, ErrorCode};
+use model::{ConstContext, StaticAssert};
 use std::collections::{HashMap, HashSet};
 
+mod suggest;
+mod uninit;
+
 pub struct SemanticAnalyzer {
     type_env: TypeEnv,
     scopes: Vec<HashMap<String, Type>>,
@@ -10,21 +17,19 @@ pub struct SemanticAnalyzer {
     in_switch: bool,
     current_return_type: Option<Type>,
     case_values: HashSet<i64>,
+    has_default: bool,
+    /// Promoted type of the innermost switch's controlling expression;
+    /// `case` values are converted to it before they are compared.
+    switch_type: Type,
+    /// Functions of the program declared `noreturn`.
+    noreturn_functions: HashSet<String>,
+    warnings: Vec<CompileError>,
 }
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
-            type_env: TypeEnv::from_program(&Program {
-                functions: vec![],
-                globals: vec![],
-                structs: vec![],
-                unions: vec![],
-                enums: vec![],
-                prototypes: vec![],
-                forward_structs: vec![],
-                typedefs: HashMap::new(),
-            }),
+            type_env: TypeEnv::from_program(&Program::default()),
             scopes: Vec::new(),
             const_vars: HashSet::new(),
             volatile_vars: HashSet::new(),
@@ -32,36 +37,62 @@ impl SemanticAnalyzer {
             in_switch: false,
             current_return_type: None,
             case_values: HashSet::new(),
+            has_default: false,
+            switch_type: Type::Int,
+            noreturn_functions: HashSet::new(),
+            warnings: Vec::new(),
         }
     }
 
-    pub fn analyze(&mut self, program: &Program) -> Result<(), String> {
+    /// Problems found by the last `analyze` that do not stop compilation.
+    pub fn warnings(&self) -> &[CompileError] {
+        &self.warnings
+    }
+
+    pub fn analyze(&mut self, program: &Program) -> Result<(), CompileError> {
         self.type_env = TypeEnv::from_program(program);
         self.const_vars.clear();
         self.volatile_vars.clear();
         self.scopes.clear();
+        self.warnings.clear();
+        self.noreturn_functions = program
+            .functions
+            .iter()
+            .filter(|f| f.attributes.contains(&model::Attribute::NoReturn))
+            .map(|f| f.name.clone())
+            .collect();
 
         for s_def in &program.structs {
             for field in &s_def.fields {
                 TypeEnv::validate_bitfield(field)?;
             }
         }
+        // Only a pointer may refer back to the enclosing record
+        let records = program.structs.iter().map(|s| (&s.name, Type::Struct(s.name.clone())))
+            .chain(program.unions.iter().map(|u| (&u.name, Type::Union(u.name.clone()))));
+        for (tag, record) in records {
+            if let Some(field) = self.type_env.layout().self_containing_field(tag) {
+                let field = if field.is_empty() { "an anonymous member".to_string() } else { format!("field '{}'", field) };
+                return Err(CompileError::new(ErrorCode::IncompleteType, format!("'{}' contains itself through {}", record, field))
+                    .with_note(format!("a member can only refer to '{}' through a pointer", record)));
+            }
+        }
 
         for enum_def in &program.enums {
             let mut seen = HashSet::new();
             for (const_name, _) in &enum_def.constants {
                 if !seen.insert(const_name.clone()) {
-                    return Err(format!("Redeclaration of enum constant {}", const_name));
+                    return Err(CompileError::new(ErrorCode::Redeclaration, format!("Redeclaration of enum constant {}", const_name)));
                 }
             }
         }
 
         for global in &program.globals {
             if global.qualifiers.is_restrict && !matches!(global.r#type, Type::Pointer(_, ..)) {
-                return Err(format!(
+                return Err(CompileError::new(ErrorCode::InvalidQualifier, format!(
                     "'restrict' can only be applied to pointer types on '{}'",
                     global.name
-                ));
+                )));
             }
             if global.qualifiers.is_const {
                 self.const_vars.insert(global.name.clone());
@@ -71,15 +102,41 @@ impl SemanticAnalyzer {
             }
             if let Some(init) = &global.init {
                 let ty = self.type_env.resolve_type(&global.r#type);
+                let first_warning = self.warnings.len();
                 self.check_init_compatible(&ty, init)?;
+                for warning in &mut self.warnings[first_warning..] {
+                    *warning = warning.clone().or_span((!global.span.is_empty()).then_some(global.span));
+                }
+                // Address constants are left to codegen; arithmetic ones must fold
+                let arithmetic = TypeEnv::is_integer_type(&ty) || TypeEnv::is_floating_type(&ty);
+                if arithmetic && !matches!(init, Expr::InitList(_)) {
+                    eval_constant(init, &self.type_env).map_err(|e| {
+                        CompileError::new(ErrorCode::NotConstant, format!("initializer element of '{}' is not constant", global.name))
+                            .with_note(e.message)
+                            .or_span((!global.span.is_empty()).then_some(global.span))
+                    })?;
+                }
             }
         }
 
+        for assertion in &program.static_asserts {
+            check_static_assert(assertion, &self.type_env)?;
+        }
+
         for function in &program.functions {
             if self.type_env.functions.contains_key(&function.name) {
                 // Definition may follow prototype — validated at registration time.
             }
-            self.analyze_function(function)?;
+            // Expressions carry no spans yet; point at the enclosing function.
+            let locate = |e: CompileError| {
+                e.or_span((!function.span.is_empty()).then_some(function.span))
+                    .with_note(format!("in function '{}'", function.name))
+            };
+            let first_warning = self.warnings.len();
+            self.analyze_function(function).map_err(locate)?;
+            for warning in &mut self.warnings[first_warning..] {
+                *warning = locate(warning.clone());
+            }
         }
         Ok(())
     }
@@ -94,7 +151,7 @@ impl SemanticAnalyzer {
         map
     }
 
-    fn analyze_function(&mut self, function: &Function) -> Result<(), String> {
+    fn analyze_function(&mut self, function: &Function) -> Result<(), CompileError> {
         self.scopes.clear();
         self.loop_depth = 0;
         self.in_switch = false;
@@ -104,15 +161,16 @@ impl SemanticAnalyzer {
         for (t, name) in &function.params {
             let resolved = self.type_env.resolve_type(t);
             if !self.type_env.is_complete_type(&resolved) {
-                return Err(format!(
+                return Err(CompileError::new(ErrorCode::IncompleteType, format!(
                     "Parameter '{}' has incomplete type in function '{}'",
                     name, function.name
-                ));
+                )));
             }
             self.declare_local(name, resolved, TypeQualifiers::default(), false)?;
         }
         self.analyze_stmt(&Stmt::Block(function.body.clone()))?;
         self.exit_scope();
+        self.warnings.extend(uninit::check_function(function, &self.type_env, &self.noreturn_functions));
         Ok(())
     }
 
@@ -130,13 +188,13 @@ impl SemanticAnalyzer {
         ty: Type,
         qualifiers: model::TypeQualifiers,
         allow_shadow: bool,
-    ) -> Result<(), String> {
+    ) -> Result<(), CompileError> {
         if qualifiers.is_restrict && !matches!(ty, Type::Pointer(_, ..)) {
-            return Err(format!("'restrict' can only be applied to pointer types"));
+            return Err(CompileError::new(ErrorCode::InvalidQualifier, format!("'restrict' can only be applied to pointer types")));
         }
         if let Some(scope) = self.scopes.last_mut() {
             if scope.contains_key(name) && !allow_shadow {
-                return Err(format!("Redeclaration of '{}'", name));
+                return Err(CompileError::new(ErrorCode::Redeclaration, format!("Redeclaration of '{}'", name)));
             }
             scope.insert(name.to_string(), ty);
         }
@@ -158,13 +216,13 @@ impl SemanticAnalyzer {
         self.type_env.globals.get(name).cloned()
     }
 
-    fn analyze_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+    fn analyze_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
         match stmt {
-            Stmt::Declaration { r#type, qualifiers, name, init } => {
+            Stmt::Declaration { r#type, qualifiers, name, init, .. } => {
                 let locals = self.locals();
                 let resolved = self.type_env.resolve_type_in_context(r#type, &locals);
                 if !self.type_env.is_complete_type(&resolved) {
-                    return Err(format!("Variable '{}' has incomplete type", name));
+                    return Err(CompileError::new(ErrorCode::IncompleteType, format!("Variable '{}' has incomplete type", name)));
                 }
                 self.declare_local(name, resolved.clone(), qualifiers.clone(), true)?;
                 if let Some(expr) = init {
@@ -176,16 +234,18 @@ impl SemanticAnalyzer {
                 if let Some(ret_ty) = ret_ty {
                     if ret_ty == Type::Void {
                         if expr.is_some() {
-                            return Err("Return with value in void function".to_string());
+                            return Err(CompileError::new(ErrorCode::IncompatibleTypes, "Return with value in void function"));
                         }
                     } else if let Some(e) = expr {
                         let got = self.check_expr(e)?;
                         if !self.type_env.is_assign_compatible(&ret_ty, &got) {
-                            return Err(format!(
-                                "Return type mismatch: expected {:?}, got {:?}",
+                            return Err(CompileError::new(ErrorCode::IncompatibleTypes, format!(
+                                "Return type mismatch: expected '{}', got '{}'",
                                 ret_ty, got
-                            ));
+                            )));
                         }
+                        self.check_discarded_const(&ret_ty, e, "return");
+                        self.check_constant_conversion(&ret_ty, e);
                     }
                 } else if let Some(e) = expr {
                     self.check_expr(e)?;
@@ -207,14 +267,14 @@ impl SemanticAnalyzer {
                 }
             }
             Stmt::If { cond, then_branch, else_branch } => {
-                self.check_expr(cond)?;
+                self.check_condition(cond)?;
                 self.analyze_stmt(then_branch)?;
                 if let Some(else_stmt) = else_branch {
                     self.analyze_stmt(else_stmt)?;
                 }
             }
             Stmt::While { cond, body } => {
-                self.check_expr(cond)?;
+                self.check_condition(cond)?;
                 self.loop_depth += 1;
                 self.analyze_stmt(body)?;
                 self.loop_depth -= 1;
@@ -223,7 +283,7 @@ impl SemanticAnalyzer {
                 self.loop_depth += 1;
                 self.analyze_stmt(body)?;
                 self.loop_depth -= 1;
-                self.check_expr(cond)?;
+                self.check_condition(cond)?;
             }
             Stmt::For { init, cond, post, body } => {
                 self.enter_scope();
@@ -231,7 +291,7 @@ impl SemanticAnalyzer {
                     self.analyze_stmt(stmt)?;
                 }
                 if let Some(e) = cond {
-                    self.check_expr(e)?;
+                    self.check_condition(e)?;
                 }
                 if let Some(e) = post {
                     self.check_expr(e)?;
@@ -243,38 +303,72 @@ impl SemanticAnalyzer {
             }
             Stmt::Break => {
                 if self.loop_depth == 0 && !self.in_switch {
-                    return Err("'break' statement not within a loop or switch".to_string());
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "'break' statement not within a loop or switch"));
                 }
             }
             Stmt::Continue => {
                 if self.loop_depth == 0 {
-                    return Err("'continue' statement not within a loop".to_string());
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "'continue' statement not within a loop"));
                 }
             }
             Stmt::Switch { cond, body } => {
-                self.check_expr(cond)?;
+                let cond_ty = self.check_expr(cond)?;
                 let old_switch = self.in_switch;
                 let old_cases = std::mem::take(&mut self.case_values);
+                let old_default = std::mem::replace(&mut self.has_default, false);
+                let switch_type = TypeEnv::integer_promotion(&self.type_env.resolve_type(&cond_ty));
+                let old_type = std::mem::replace(&mut self.switch_type, switch_type);
                 self.in_switch = true;
                 self.analyze_stmt(body)?;
+                // -Wswitch: a switch on an enum without `default` should name every enumerator
+                if let Type::Enum(tag) = self.type_env.resolve_type(&cond_ty) {
+                    if !self.has_default {
+                        for name in self.type_env.unhandled_enumerators(&tag, &self.case_values) {
+                            self.warnings.push(CompileError::warning(
+                                ErrorCode::UnhandledEnumerator,
+                                format!("enumeration value '{}' not handled in switch", name),
+                            ));
+                        }
+                    }
+                }
                 self.in_switch = old_switch;
                 self.case_values = old_cases;
+                self.has_default = old_default;
+                self.switch_type = old_type;
             }
             Stmt::Case(expr) => {
                 if !self.in_switch {
-                    return Err("'case' label not within a switch statement".to_string());
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "'case' label not within a switch statement"));
                 }
-                if let Expr::Constant(v) = expr {
-                    if !self.case_values.insert(*v) {
-                        return Err(format!("Duplicate case value {}", v));
+                self.check_expr(expr)?;
+                let scope = FunctionScope { env: &self.type_env, locals: self.locals() };
+                let (v, warnings) = eval_constant_with_warnings(expr, &scope).map_err(|e| e.with_note("in a 'case' label"))?;
+                let v = match v {
+                    ConstValue::Int(v, _) => v,
+                    ConstValue::Float(_) => {
+                        return Err(CompileError::new(ErrorCode::NotConstant, "expression is not an integer constant expression")
+                            .with_note("in a 'case' label"))
                     }
+                };
+                self.warnings.extend(warnings);
+                // `case -1:` and `case 0xFFFFFFFF:` are the same label in a switch on `unsigned`
+                let v = if TypeEnv::is_integer_type(&self.switch_type) {
+                    eval_integer_constant(&Expr::Cast(self.switch_type.clone(), Box::new(Expr::Constant(v))), &scope)?
+                } else {
+                    v
+                };
+                if !self.case_values.insert(v) {
+                    return Err(CompileError::new(ErrorCode::DuplicateCase, format!("Duplicate case value {}", v)));
                 }
-                self.check_expr(expr)?;
             }
             Stmt::Default => {
                 if !self.in_switch {
-                    return Err("'default' label not within a switch statement".to_string());
+                    return Err(CompileError::new(ErrorCode::MisplacedStatement, "'default' label not within a switch statement"));
+                }
+                if self.has_default {
+                    return Err(CompileError::new(ErrorCode::DuplicateCase, "multiple default labels in one switch"));
                 }
+                self.has_default = true;
             }
             Stmt::Goto(_label) => {}
             Stmt::ComputedGoto(expr) => {
@@ -282,27 +376,32 @@ impl SemanticAnalyzer {
                 match ty {
                     Type::Pointer(_, ..) => {}
                     _ => {
-                        return Err(format!(
-                            "Computed goto requires pointer type, got {:?}",
+                        return Err(CompileError::new(ErrorCode::IncompatibleTypes, format!(
+                            "Computed goto requires pointer type, got '{}'",
                             ty
-                        ));
+                        )));
                     }
                 }
             }
             Stmt::Label(_name) => {}
             Stmt::InlineAsm { outputs, inputs, .. } => {
                 for operand in outputs {
+                    self.check_lvalue(&operand.expr, "asm output operand")?;
                     self.check_expr(&operand.expr)?;
                 }
                 for operand in inputs {
                     self.check_expr(&operand.expr)?;
                 }
             }
+            Stmt::StaticAssert(assertion) => {
+                let scope = FunctionScope { env: &self.type_env, locals: self.locals() };
+                check_static_assert(assertion, &scope)?;
+            }
         }
         Ok(())
     }
 
-    fn check_expr(&mut self, expr: &Expr) -> Result<Type, String> {
+    fn check_expr(&mut self, expr: &Expr) -> Result<Type, CompileError> {
         let locals = self.locals();
         let ty = self.type_env.expr_type(expr, &locals);
 
@@ -311,12 +410,15 @@ impl SemanticAnalyzer {
                 if self.lookup_symbol(name).is_none()
                     && !self.type_env.enum_constants.contains(name)
                 {
-                    return Err(format!("Undeclared variable {}", name));
+                    return Err(CompileError::new(ErrorCode::Undeclared, format!("Undeclared variable {}", name)));
                 }
             }
             Expr::Binary { left, op, right } => {
-                self.check_expr(left)?;
-                self.check_expr(right)?;
+                let lhs_ty = self.check_expr(left)?;
+                let rhs_ty = self.check_expr(right)?;
+                if *op != BinaryOp::Assign {
+                    self.type_env.check_binary_operands(op, &lhs_ty, &rhs_ty)?;
+                }
                 if matches!(
                     op,
                     BinaryOp::Assign
@@ -331,42 +433,56 @@ impl SemanticAnalyzer {
                         | BinaryOp::ShiftLeftAssign
                         | BinaryOp::ShiftRightAssign
                 ) {
-                    if !TypeEnv::is_lvalue(left) {
-                        return Err("Assignment requires an lvalue".to_string());
-                    }
+                    self.check_lvalue(left, "left operand of assignment")?;
                     self.check_const_assignment(left)?;
                     let lhs_ty = self.type_env.expr_type(left, &locals);
+                    if matches!(self.type_env.resolve_type(&lhs_ty), Type::Array(..)) {
+                        return Err(CompileError::new(ErrorCode::NotAnLvalue, "assignment to expression with array type"));
+                    }
                     let rhs_ty = self.type_env.expr_type(right, &locals);
                     if !self.type_env.is_assign_compatible(&lhs_ty, &rhs_ty) {
-                        return Err(format!(
-                            "Incompatible assignment: {:?} = {:?}",
+                        return Err(CompileError::new(ErrorCode::IncompatibleTypes, format!(
+                            "Incompatible assignment: '{}' = '{}'",
                             lhs_ty, rhs_ty
-                        ));
+                        )));
                     }
-                    if TypeEnv::pointee_is_const(&lhs_ty) {
-                        return Err("Cannot assign through pointer to const".to_string());
+                    if *op == BinaryOp::Assign {
+                        self.check_discarded_const(&lhs_ty, right, "assignment");
+                        self.check_constant_conversion(&lhs_ty, right);
                     }
                 }
             }
-            Expr::Unary { op: model::UnaryOp::Deref, expr: inner } => {
-                self.check_expr(inner)?;
-                if TypeEnv::pointee_is_const(&ty) {
-                    // read-only deref is fine; assignment checked elsewhere
-                    let _ = inner;
+            Expr::Unary { op, expr: inner } => {
+                if *op == model::UnaryOp::AddrOf {
+                    self.check_lvalue(inner, "unary '&' operand")?;
                 }
+                let operand_ty = self.check_expr(inner)?;
+                self.type_env.check_unary_operand(op, &operand_ty)?;
             }
-            Expr::Unary { expr, .. } => {
-                self.check_expr(expr)?;
+            Expr::PostfixIncrement(inner)
+            | Expr::PostfixDecrement(inner)
+            | Expr::PrefixIncrement(inner)
+            | Expr::PrefixDecrement(inner) => {
+                let operand = match expr {
+                    Expr::PostfixIncrement(_) | Expr::PrefixIncrement(_) => "increment operand",
+                    _ => "decrement operand",
+                };
+                self.check_lvalue(inner, operand)?;
+                self.check_const_assignment(inner)?;
+                let operand_ty = self.check_expr(inner)?;
+                // `x++` is `x += 1`
+                self.type_env.check_binary_operands(&BinaryOp::AddAssign, &operand_ty, &Type::Int)?;
             }
-            Expr::PostfixIncrement(expr)
-            | Expr::PostfixDecrement(expr)
-            | Expr::PrefixIncrement(expr)
-            | Expr::PrefixDecrement(expr) => {
-                if !TypeEnv::is_lvalue(expr) {
-                    return Err("Increment/decrement requires an lvalue".to_string());
-                }
-                self.check_const_assignment(expr)?;
-                self.check_expr(expr)?;
+            Expr::Index { array, index } => {
+                let array_ty = self.check_expr(array)?;
+                let index_ty = self.check_expr(index)?;
+                self.type_env.check_subscript(&array_ty, &index_ty)?;
+            }
+            Expr::Conditional { condition, then_expr, else_expr } => {
+                self.check_condition(condition)?;
+                self.check_expr(then_expr)?;
+                self.check_expr(else_expr)?;
+                self.type_env.conditional_type(then_expr, else_expr, &locals)?;
             }
             Expr::Call { func, args } => {
                 self.type_env.check_call(func, args, &locals)?;
@@ -376,11 +492,28 @@ impl SemanticAnalyzer {
                 for arg in args {
                     self.check_expr(arg)?;
                 }
+                if let Expr::Variable(name) = func.as_ref() {
+                    if let Some(sig) = self.type_env.functions.get(name).cloned() {
+                        for (i, (param, arg)) in sig.param_types.iter().zip(args).enumerate() {
+                            self.check_discarded_const(param, arg, &format!("passing argument {} of '{}'", i + 1, name));
+                        }
+                    }
+                }
             }
             Expr::Cast(cast_ty, inner) => {
                 self.check_expr(inner)?;
                 let _ = self.type_env.resolve_type(cast_ty);
             }
+            Expr::Member { expr: base, member } | Expr::PtrMember { expr: base, member } => {
+                let base_ty = self.check_expr(base)?;
+                let mut record = self.type_env.resolve_type(&base_ty);
+                if matches!(expr, Expr::PtrMember { .. }) {
+                    if let Type::Pointer(pointee, _) = record {
+                        record = self.type_env.resolve_type(&pointee);
+                    }
+                }
+                self.check_member(&record, member)?;
+            }
             Expr::LabelAddr(label) => {
                 // Label must exist in function — validated at IR lowering
                 let _ = label;
@@ -392,27 +525,41 @@ impl SemanticAnalyzer {
         Ok(ty)
     }
 
-    fn check_expr_children(&mut self, expr: &Expr) -> Result<(), String> {
+    /// `member` must name a member of `record` when it is a defined struct
+    /// or union; a near miss is suggested in a note.
+    fn check_member(&self, record: &Type, member: &str) -> Result<(), CompileError> {
+        let (Type::Struct(tag) | Type::Union(tag)) = record else {
+            return Ok(());
+        };
+        let layout = self.type_env.layout();
+        let Some(names) = layout.member_names(tag) else {
+            return Ok(());
+        };
+        if names.contains(&member) {
+            return Ok(());
+        }
+        let err = CompileError::new(ErrorCode::UnknownMember, format!("'{}' has no member named '{}'", record, member));
+        Err(match suggest::closest_match(member, names) {
+            Some(name) => err.with_note(format!("did you mean '{}'?", name)),
+            None => err,
+        })
+    }
+
+    /// A controlling expression must have scalar type.
+    fn check_condition(&mut self, cond: &Expr) -> Result<(), CompileError> {
+        let ty = self.check_expr(cond)?;
+        self.type_env.check_scalar(&ty)
+    }
+
+    fn check_expr_children(&mut self, expr: &Expr) -> Result<(), CompileError> {
         match expr {
             Expr::Binary { left, right, .. } => {
                 self.check_expr(left)?;
                 self.check_expr(right)?;
             }
-            Expr::Unary { expr, .. } => {
+            Expr::Unary { expr, .. } | Expr::Real(expr) | Expr::Imag(expr) => {
                 self.check_expr(expr)?;
             }
-            Expr::Index { array, index } => {
-                self.check_expr(array)?;
-                self.check_expr(index)?;
-            }
-            Expr::Member { expr, .. } | Expr::PtrMember { expr, .. } => {
-                self.check_expr(expr)?;
-            }
-            Expr::Conditional { condition, then_expr, else_expr } => {
-                self.check_expr(condition)?;
-                self.check_expr(then_expr)?;
-                self.check_expr(else_expr)?;
-            }
             Expr::CompoundLiteral { init, .. } => {
                 for item in init {
                     self.check_expr(&item.value)?;
@@ -448,53 +595,203 @@ impl SemanticAnalyzer {
             }
             Expr::SizeOfExpr(e) => {
                 self.check_expr(e)?;
+                self.check_sizeof_expr(e)?;
             }
+            Expr::SizeOf(ty) => self.check_size_operand("sizeof", ty)?,
+            Expr::AlignOf(ty) => self.check_size_operand("_Alignof", ty)?,
             _ => {}
         }
         Ok(())
     }
 
-    fn check_const_assignment(&self, expr: &Expr) -> Result<(), String> {
+    /// The operand of `sizeof` or `_Alignof` (`op`) must have a size: not
+    /// `void` and not a struct or union that is only declared.
+    fn check_size_operand(&self, op: &str, ty: &Type) -> Result<(), CompileError> {
+        let resolved = self.type_env.resolve_type_in_context(ty, &self.locals());
+        if matches!(resolved, Type::TypeofExpr(_)) || self.type_env.is_complete_type(&resolved) {
+            return Ok(());
+        }
+        Err(invalid_size_operand(op, &resolved))
+    }
+
+    /// `sizeof expr` additionally rejects a function designator.
+    fn check_sizeof_expr(&self, operand: &Expr) -> Result<(), CompileError> {
+        if let Expr::Variable(name) = operand {
+            let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+            if !is_local && self.type_env.functions.contains_key(name) {
+                return Err(CompileError::new(ErrorCode::InvalidOperand, "invalid application of 'sizeof' to a function type"));
+            }
+        }
+        let ty = self.type_env.sizeof_operand_type(operand, &self.locals());
+        self.check_size_operand("sizeof", &ty)
+    }
+
+    /// Whether `expr` designates an object: an lvalue per `TypeEnv::is_lvalue`
+    /// that is not an enum constant.
+    fn is_lvalue(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Variable(name) => self.lookup_symbol(name).is_some() || !self.type_env.enum_constants.contains(name),
+            Expr::Member { expr, .. } => self.is_lvalue(expr),
+            _ => TypeEnv::is_lvalue(expr),
+        }
+    }
+
+    /// `operand` names the use in the message, as in "lvalue required as
+    /// increment operand".
+    fn check_lvalue(&self, expr: &Expr, operand: &str) -> Result<(), CompileError> {
+        if self.is_lvalue(expr) {
+            Ok(())
+        } else {
+            Err(CompileError::new(ErrorCode::NotAnLvalue, format!("lvalue required as {}", operand)))
+        }
+    }
+
+    fn check_const_assignment(&self, expr: &Expr) -> Result<(), CompileError> {
         match expr {
             Expr::Variable(name) => {
                 if self.const_vars.contains(name) {
-                    return Err(format!("Cannot modify const variable '{}'", name));
+                    return Err(CompileError::new(ErrorCode::ConstViolation, format!("Cannot modify const variable '{}'", name)));
                 }
             }
-            Expr::Unary { op: model::UnaryOp::Deref, expr: inner } => {
+            Expr::Unary { op: model::UnaryOp::Deref, expr: inner }
+            | Expr::Index { array: inner, .. }
+            | Expr::PtrMember { expr: inner, .. } => {
                 let locals = self.locals();
                 let ptr_ty = self.type_env.expr_type(inner, &locals);
                 if TypeEnv::pointee_is_const(&ptr_ty) {
-                    return Err("Cannot assign through pointer to const".to_string());
+                    return Err(CompileError::new(ErrorCode::ConstViolation, "Cannot assign through pointer to const"));
+                }
+                // Elements of a const array are const
+                if matches!(expr, Expr::Index { .. }) && matches!(ptr_ty, Type::Array(..)) {
+                    self.check_const_assignment(inner)?;
                 }
             }
+            // Members of a const struct are const
+            Expr::Member { expr: inner, .. } => self.check_const_assignment(inner)?,
             _ => {}
         }
         Ok(())
     }
 
-    fn check_init_compatible(&mut self, target: &Type, init: &Expr) -> Result<(), String> {
+    /// Whether `expr` designates a const object: a const variable, an
+    /// element or member of one, or an object reached through a pointer to
+    /// const.
+    fn is_const_object(&self, expr: &Expr) -> bool {
+        self.check_const_assignment(expr).is_err()
+    }
+
+    /// Whether the pointer value of `expr` points to const, either by its
+    /// type (`const char *`) or because it is the address of a const object
+    /// (`&x`, or a const array that decays).
+    fn points_to_const(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Unary { op: model::UnaryOp::AddrOf, expr: inner } => self.is_const_object(inner),
+            Expr::Conditional { then_expr, else_expr, .. } => {
+                self.points_to_const(then_expr) || self.points_to_const(else_expr)
+            }
+            _ => {
+                let ty = self.type_env.resolve_type(&self.type_env.expr_type(expr, &self.locals()));
+                TypeEnv::pointee_is_const(&ty) || (matches!(ty, Type::Array(..)) && self.is_const_object(expr))
+            }
+        }
+    }
+
+    /// Warn when `value` points to const but `target` is a pointer to a
+    /// non-const type. `context` starts the message, as in "passing argument
+    /// 1 of 'f'".
+    fn check_discarded_const(&mut self, target: &Type, value: &Expr, context: &str) {
+        if let Type::Pointer(_, pointee) = self.type_env.resolve_type(target) {
+            if !pointee.is_const && self.points_to_const(value) {
+                self.warnings.push(CompileError::warning(
+                    ErrorCode::DiscardedQualifiers,
+                    format!("{} discards 'const' qualifier from pointer target type", context),
+                ));
+            }
+        }
+    }
+
+    /// When `value` is a constant, warn about overflow while folding it and
+    /// about a change in value when it is converted to `target`.
+    fn check_constant_conversion(&mut self, target: &Type, value: &Expr) {
+        let scope = FunctionScope { env: &self.type_env, locals: self.locals() };
+        if let Ok((v, warnings)) = eval_constant_with_warnings(value, &scope) {
+            self.warnings.extend(warnings);
+            self.warnings.extend(conversion_warning(&v, target, &scope));
+        }
+    }
+
+    fn check_init_compatible(&mut self, target: &Type, init: &Expr) -> Result<(), CompileError> {
         match init {
             Expr::InitList(_) => Ok(()),
+            // A wide string literal initializes an array of its unit type
+            Expr::WideStringLiteral(_, encoding) if matches!(
+                self.type_env.resolve_type(target),
+                Type::Array(ref elem, _) if self.type_env.types_compatible(elem, &encoding.unit_type())
+            ) => Ok(()),
             _ => {
                 let got = self.check_expr(init)?;
                 if !self.type_env.is_assign_compatible(target, &got) {
-                    return Err(format!(
-                        "Initializer incompatible: expected {:?}, got {:?}",
+                    return Err(CompileError::new(ErrorCode::IncompatibleTypes, format!(
+                        "Initializer incompatible: expected '{}', got '{}'",
                         target, got
-                    ));
+                    )));
                 }
+                self.check_discarded_const(target, init, "initialization");
+                self.check_constant_conversion(target, init);
                 Ok(())
             }
         }
     }
 }
 
+/// Constant evaluation inside a function body, where `sizeof x` may name a
+/// local.
+struct FunctionScope<'a> {
+    env: &'a TypeEnv,
+    locals: HashMap<String, Type>,
+}
+
+impl ConstContext for FunctionScope<'_> {
+    fn enum_constant(&self, name: &str) -> Option<i64> {
+        self.env.enum_constant(name)
+    }
+
+    fn size_of(&self, ty: &Type) -> Option<usize> {
+        self.env.size_of(ty)
+    }
+
+    fn align_of(&self, ty: &Type) -> Option<usize> {
+        self.env.align_of(ty)
+    }
+
+    fn resolve_type(&self, ty: &Type) -> Type {
+        self.env.resolve_type(ty)
+    }
+
+    fn type_of(&self, expr: &Expr) -> Option<Type> {
+        Some(self.env.sizeof_operand_type(expr, &self.locals))
+    }
+
+    fn offset_of(&self, ty: &Type, member: &str) -> Option<usize> {
+        self.env.offset_of(ty, member)
+    }
+}
+
+fn check_static_assert(assertion: &StaticAssert, ctx: &dyn ConstContext) -> Result<(), CompileError> {
+    let span = (!assertion.span.is_empty()).then_some(assertion.span);
+    let value = eval_integer_constant(&assertion.condition, ctx).map_err(|e| e.with_note("in a '_Static_assert'").or_span(span))?;
+    if value == 0 {
+        let msg = assertion.message.as_deref().unwrap_or("static assertion failed");
+        return Err(CompileError::new(ErrorCode::StaticAssertFailed, format!("_Static_assert failed: {}", msg)).or_span(span));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn analyze(src: &str) -> Result<(), String> {
+    fn analyze(src: &str) -> Result<(), CompileError> {
         let tokens = lexer::lex(src).unwrap();
         let program = parser::parse_tokens(&tokens).unwrap();
         let mut analyzer = SemanticAnalyzer::new();
@@ -516,6 +813,99 @@ mod tests {
         assert!(analyze("int main() { const int x = 5; x = 10; return x; }").is_err());
     }
 
+    #[test]
+    fn const_applies_per_pointer_level() {
+        // Pointer to const: the pointer may change, the pointee may not
+        assert!(analyze("int main() { const char *p = \"ab\"; p = p + 1; return *p; }").is_ok());
+        assert!(analyze("int main() { char c; const char *p = &c; *p = 1; return 0; }").is_err());
+        assert!(analyze("int main() { char c; const char *p = &c; p[0] = 1; return 0; }").is_err());
+        // Const pointer: the pointee may change, the pointer may not
+        assert!(analyze("int main() { char c; char *const p = &c; *p = 1; return c; }").is_ok());
+        assert!(analyze("int main() { char c; char *const p = &c; p = 0; return 0; }").is_err());
+        // Only the first level matters for a store through the pointer
+        assert!(analyze("int main() { const char *s; const char **pp = &s; *pp = 0; return 0; }").is_ok());
+        assert!(analyze("int main() { const int a[2] = {1, 2}; a[0] = 3; return 0; }").is_err());
+    }
+
+    fn warnings_with_code(src: &str, code: ErrorCode) -> Vec<String> {
+        let program = parser::parse_tokens(&lexer::lex(src).unwrap()).unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        analyzer
+            .warnings()
+            .iter()
+            .filter(|w| w.code == code)
+            .map(|w| w.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn warn_discarded_const_qualifier() {
+        let src = "void take(char *s); char *ret(const char *s) { return s; } \
+                   int main(void) { const char *cs = \"ab\"; const int x = 1; const int a[2] = {1, 2}; \
+                   char *p = cs; int *q = &x; int *r = a; take(cs); p = cs; return 0; }";
+        assert_eq!(
+            warnings_with_code(src, ErrorCode::DiscardedQualifiers),
+            vec![
+                "return discards 'const' qualifier from pointer target type",
+                "initialization discards 'const' qualifier from pointer target type",
+                "initialization discards 'const' qualifier from pointer target type",
+                "initialization discards 'const' qualifier from pointer target type",
+                "passing argument 1 of 'take' discards 'const' qualifier from pointer target type",
+                "assignment discards 'const' qualifier from pointer target type",
+            ]
+        );
+        // Adding const, casting it away, and null pointers are fine
+        let src = "void show(const char *s); int main(void) { char buf[4]; const char *cs = buf; \
+                   char *p = (char *)cs; const int *q = 0; int x = 0; q = &x; show(buf); return *p + *q; }";
+        assert!(warnings_with_code(src, ErrorCode::DiscardedQualifiers).is_empty());
+        let err = analyze("struct S { int a; }; int f(const struct S *sp) { sp->a = 1; return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::ConstViolation);
+    }
+
+    #[test]
+    fn conditional_operands_must_agree() {
+        let ok = "struct S { int a; }; struct S s; struct S t; int *p; const int *cp; void *v; \
+                  int main(void) { int c = 1; double d = c ? 1 : 2.5; int *q = c ? p : 0; \
+                  const int *r = c ? p : cp; void *w = c ? p : v; struct S u = c ? s : t; \
+                  c ? (void)0 : (void)1; return d > 0 && q == 0 && r && w && u.a == 0; }";
+        analyze(ok).unwrap();
+        for src in [
+            "struct S { int a; }; struct S s; int main(void) { int c = 1; return c ? s : 1; }",
+            "int *p; char *q; int main(void) { int c = 1; return c ? p : q; }",
+            "struct S { int a; }; union U { int a; }; struct S s; union U u; int main(void) { int c = 1; c ? s : u; return 0; }",
+        ] {
+            let err = analyze(src).unwrap_err();
+            assert_eq!(err.code, ErrorCode::IncompatibleTypes, "{}", src);
+            assert!(err.message.starts_with("type mismatch in conditional expression"), "{}", err.message);
+        }
+    }
+
+    #[test]
+    fn warn_constant_overflow() {
+        let src = "char g = 300; int h = 2147483647 + 1; \
+                   int main(void) { unsigned u = -1; short s = 1 << 40; s = 65536; \
+                   switch (u) { case 2147483647 * 2: break; } return 0; }";
+        assert_eq!(
+            warnings_with_code(src, ErrorCode::ConstantOverflow),
+            vec![
+                "overflow in conversion from 'int' to 'char' changes value from '300' to '44'",
+                "integer overflow in expression of type 'int' results in '-2147483648'",
+                "left shift count 40 >= width of type 'int' results in '0'",
+                "overflow in conversion from 'int' to 'short' changes value from '65536' to '0'",
+                "integer overflow in expression of type 'int' results in '-2'",
+            ]
+        );
+    }
+
+    #[test]
+    fn type_errors_use_c_syntax() {
+        let err = analyze("struct S { int a; }; int main() { struct S s; int *p = 0; s = p; return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::IncompatibleTypes);
+        assert_eq!(err.message, "Incompatible assignment: 'struct S' = 'int *'");
+        assert_eq!(err.notes, vec!["in function 'main'".to_string()]);
+    }
+
     #[test]
     fn error_wrong_call_arity() {
         assert!(analyze("int foo(int a) { return a; } int main() { return foo(1, 2); }").is_err());
@@ -541,5 +931,196 @@ mod tests {
             "int main() { int x = 1; switch (x) { case 1: break; case 1: break; } return 0; }"
         )
         .is_err());
+        let err = analyze("int f(unsigned x) { switch (x) { case -1: case 0xFFFFFFFF: return 1; } return 0; }").unwrap_err();
+        assert_eq!(err.message, "Duplicate case value 4294967295");
+        let err = analyze("int f(int x) { switch (x) { default: break; case 1: default: return 1; } return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::DuplicateCase);
+        assert_eq!(err.message, "multiple default labels in one switch");
+        assert!(analyze("int f(int x) { switch (x) { case 1: switch (x) { case 1: default: break; } default: break; } return 0; }").is_ok());
+    }
+
+    #[test]
+    fn error_not_an_lvalue() {
+        for (src, message) in [
+            ("int main() { int x = 1; 5 = x; return x; }", "lvalue required as left operand of assignment"),
+            ("int main() { int a = 1, b = 2; int *p = &(a + b); return *p; }", "lvalue required as unary '&' operand"),
+            ("int main() { int x = 1, y = 2; (x + y)++; return x; }", "lvalue required as increment operand"),
+            ("enum { RED }; int main() { RED = 1; return 0; }", "lvalue required as left operand of assignment"),
+            ("enum { RED }; int main() { --RED; return 0; }", "lvalue required as decrement operand"),
+            ("struct S { int m; }; struct S f(void); int main() { f().m = 1; return 0; }", "lvalue required as left operand of assignment"),
+            ("int main() { int a[2], b[2]; a = b; return 0; }", "assignment to expression with array type"),
+        ] {
+            let err = analyze(src).unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotAnLvalue, "{src}");
+            assert_eq!(err.message, message, "{src}");
+        }
+        let src = "struct S { int a; }; enum { RED }; int g[2]; \
+                   int f(int a[], int *b) { a = b; return a[0]; } \
+                   int main() { struct S s; struct S *q = &(struct S){1}; int *p = &q->a; int RED = 0; \
+                   s.a = 1; *p = 2; RED = 3; g[1] = 4; return f(g, p) + s.a + RED; }";
+        assert!(analyze(src).is_ok());
+    }
+
+    #[test]
+    fn error_unknown_member() {
+        let err = analyze("struct P { int width; int height; }; int main() { struct P p; p.widht = 1; return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownMember);
+        assert_eq!(err.message, "'struct P' has no member named 'widht'");
+        assert_eq!(err.notes, vec!["did you mean 'width'?".to_string(), "in function 'main'".to_string()]);
+        let err = analyze("union U { int i; float f; }; int f(union U *u) { return u->depth; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownMember);
+        assert!(err.notes.iter().all(|n| !n.starts_with("did you mean")));
+        // Members of anonymous members, and records the parser knows nothing about
+        let src = "struct S { int tag; union { int i; float f; }; }; struct Opaque; \
+                   int g(struct Opaque *o); int main() { struct S s; s.i = 1; s.tag = s.i; return s.tag; }";
+        assert!(analyze(src).is_ok());
+    }
+
+    #[test]
+    fn error_sizeof_without_size() {
+        let prelude = "struct Fwd; extern struct Fwd g; int f(void); void v(void); ";
+        for (body, code, message) in [
+            ("sizeof(void)", ErrorCode::IncompleteType, "invalid application of 'sizeof' to a void type"),
+            ("sizeof(struct Fwd)", ErrorCode::IncompleteType, "invalid application of 'sizeof' to incomplete type 'struct Fwd'"),
+            ("sizeof g", ErrorCode::IncompleteType, "invalid application of 'sizeof' to incomplete type 'struct Fwd'"),
+            ("sizeof(v())", ErrorCode::IncompleteType, "invalid application of 'sizeof' to a void type"),
+            ("_Alignof(struct Fwd)", ErrorCode::IncompleteType, "invalid application of '_Alignof' to incomplete type 'struct Fwd'"),
+            ("sizeof f", ErrorCode::InvalidOperand, "invalid application of 'sizeof' to a function type"),
+        ] {
+            let err = analyze(&format!("{}int main(void) {{ return {}; }}", prelude, body)).unwrap_err();
+            assert_eq!((err.code, err.message.as_str()), (code, message), "{}", body);
+        }
+        let ok = "struct Fwd; int f(void); int main(void) { struct Fwd *p = 0; int (*fp)(void); \
+                  return sizeof p + sizeof(struct Fwd *) + sizeof fp + sizeof(f()) + sizeof \"abc\"; }";
+        assert!(analyze(ok).is_ok());
+    }
+
+    #[test]
+    fn error_struct_contains_itself() {
+        let err = analyze("struct Bad { int v; struct Bad self; }; int main(void) { return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::IncompleteType);
+        assert_eq!(err.message, "'struct Bad' contains itself through field 'self'");
+        // Through an array of another struct, and through a union
+        let err = analyze("struct A; struct B { struct A a[2]; }; struct A { struct B b; }; int main(void) { return 0; }").unwrap_err();
+        assert_eq!(err.message, "'struct B' contains itself through field 'a'");
+        let err = analyze("union U { int i; struct W { union U u; } w; }; int main(void) { return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::IncompleteType);
+        // Pointers to the record are fine
+        let ok = "struct Node { int v; struct Node *next; struct Node *kids[2]; }; \
+                  int main(void) { struct Node n; n.next = &n; return sizeof n == 32; }";
+        assert!(analyze(ok).is_ok());
+    }
+
+    #[test]
+    fn constant_expressions_are_evaluated() {
+        let src = "struct S { char c; long l; }; enum { N = 2 }; \
+                   _Static_assert(sizeof(struct S) == 16 && N * 8 == 16, \"layout\"); \
+                   int g = N << 3; \
+                   int main() { char buf[12]; _Static_assert(sizeof buf == 12, \"local\"); \
+                   switch (g) { case N + 1: return 1; case (int)sizeof(struct S): return 2; } return 0; }";
+        assert!(analyze(src).is_ok());
+
+        let err = analyze("struct S { char c; long l; }; _Static_assert(sizeof(struct S) == 9, \"no\");").unwrap_err();
+        assert_eq!(err.code, ErrorCode::StaticAssertFailed);
+        assert_eq!(err.message, "_Static_assert failed: no");
+        let err = analyze("enum { A = 1 }; int f(int x) { switch (x) { case A + 1: case 2: return 1; } return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::DuplicateCase);
+        let err = analyze("int f(int x, int y) { switch (x) { case y: return 1; } return 0; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotConstant);
+        let err = analyze("int f(void); int g = f() + 1;").unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotConstant);
+        assert_eq!(err.message, "initializer element of 'g' is not constant");
+    }
+
+    #[test]
+    fn warn_unhandled_enumerator() {
+        let src = "enum Color { RED, GREEN, BLUE }; \
+                   int f(enum Color c) { switch (c) { case RED: return 1; case GREEN: return 2; } return 0; } \
+                   int g(enum Color c) { switch (c) { case RED: return 1; default: return 0; } } \
+                   int main() { return f(RED) + g(BLUE); }";
+        let program = parser::parse_tokens(&lexer::lex(src).unwrap()).unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        let warnings = analyzer.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].is_warning());
+        assert_eq!(warnings[0].code, ErrorCode::UnhandledEnumerator);
+        assert_eq!(warnings[0].message, "enumeration value 'BLUE' not handled in switch");
+        assert_eq!(warnings[0].notes, vec!["in function 'f'".to_string()]);
+    }
+
+    fn uninit_warnings(src: &str) -> Vec<String> {
+        let program = parser::parse_tokens(&lexer::lex(src).unwrap()).unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        analyzer
+            .warnings()
+            .iter()
+            .filter(|w| w.code == ErrorCode::MaybeUninitialized)
+            .map(|w| w.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn warn_maybe_uninitialized() {
+        let src = "int main(int argc) { int x; int y; int z; if (argc) x = 1; \
+                   while (argc--) y = 2; for (int i = 0; i < 3; i++) z = i; return x + y + z; }";
+        assert_eq!(
+            uninit_warnings(src),
+            vec!["'x' may be used uninitialized", "'y' may be used uninitialized", "'z' may be used uninitialized"]
+        );
+        assert_eq!(uninit_warnings("int main() { int x; x++; return x; }"), vec!["'x' may be used uninitialized"]);
+    }
+
+    #[test]
+    fn no_uninit_warning_when_every_path_assigns() {
+        let src = "void abort(void); int set(int *p) { *p = 1; return 0; } \
+                   int main(int argc) { int a; int b; int c; int d; int e; int f; static int s; int arr[2]; \
+                   set(&a); if (argc) b = 1; else b = 2; for (;;) { c = 3; break; } \
+                   switch (argc) { case 1: d = 1; break; default: d = 2; } \
+                   if (argc > 5) e = 1; else abort(); \
+                   f = argc ? 1 : 2; arr[0] = 1; return a + b + c + d + e + f + s + arr[0]; }";
+        assert!(uninit_warnings(src).is_empty());
+    }
+
+    #[test]
+    fn error_invalid_binary_operands() {
+        let err = analyze("int main() { int a[2]; int *p = a; int *q = a; return p + q; }").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperand);
+        assert_eq!(err.message, "invalid operands to binary + ('int *' and 'int *')");
+        let err = analyze("struct S { int a; }; int main() { struct S s; return s * 2; }").unwrap_err();
+        assert_eq!(err.message, "invalid operands to binary * ('struct S' and 'int')");
+        assert!(analyze("int main() { double d = 1.5; return d % 2; }").is_err());
+        assert!(analyze("int main() { double d = 1.5; d <<= 1; return 0; }").is_err());
+        assert!(analyze("int main() { int x; char c; int *p = &x; char *q = &c; return p - q; }").is_err());
+        assert!(analyze("struct S { int a; }; int main() { struct S s; s++; return 0; }").is_err());
+    }
+
+    #[test]
+    fn error_invalid_unary_and_scalar_operands() {
+        let err = analyze("int main() { int x; int *p = &x; p = -p; return 0; }").unwrap_err();
+        assert_eq!(err.message, "invalid operand to unary - ('int *')");
+        assert!(analyze("int main() { float f = 1; return ~f; }").is_err());
+        assert!(analyze("struct S { int a; }; int main() { struct S s; return *s; }").is_err());
+        let err = analyze("struct S { int a; }; int main() { struct S s; if (s) return 1; return 0; }").unwrap_err();
+        assert_eq!(err.message, "'struct S' used where a scalar is required");
+        assert!(analyze("struct S { int a; }; int main() { struct S s; return !s; }").is_err());
+        assert!(analyze("int main() { int a[2]; double d = 0; return a[d]; }").is_err());
+    }
+
+    #[test]
+    fn valid_operands_after_conversions() {
+        let src = "struct S { int a; }; \
+                   int f(int (*cb)(int)) { return cb ? cb(1) : 0; } \
+                   int main() { int a[4]; int *p = a + 1; char c = 'a'; unsigned long n = p - a; \
+                   double d = c * 2.5; struct S s; struct S *sp = &s; \
+                   if (p && !sp) return 1; while (p != 0 && p > a) p--; \
+                   return 2[a] + (int)(d / 2) + (c << 1L) + (sp == 0) + n; }";
+        assert!(analyze(src).is_ok());
+    }
+
+    #[test]
+    fn valid_scalar_to_bool_conversions() {
+        assert!(analyze("int main() { _Bool b = 0.5; int x; _Bool c = &x; return b + c; }").is_ok());
     }
 }
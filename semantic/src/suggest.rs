@@ -0,0 +1,65 @@
+//! "Did you mean" suggestions for misspelled names.
+
+/// Edit distance between `a` and `b` in chars, where an insertion, a
+/// deletion, a substitution, or swapping two adjacent chars each count as
+/// one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between the first i chars of a and the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j] = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The candidate closest to `name`, if it is near enough to be a likely
+/// typo: at most a third of the name's length away (and at least one edit).
+/// Ties go to the earliest candidate.
+pub(crate) fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        let distance = edit_distance(name, candidate);
+        if distance <= limit && best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_counts_single_edits() {
+        assert_eq!(edit_distance("count", "count"), 0);
+        assert_eq!(edit_distance("cout", "count"), 1);
+        assert_eq!(edit_distance("lenght", "length"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_match_rejects_distant_names() {
+        let fields = ["width", "height", "x", "y"];
+        assert_eq!(closest_match("widht", fields), Some("width"));
+        assert_eq!(closest_match("hieght", fields), Some("height"));
+        assert_eq!(closest_match("z", fields), Some("x"));
+        assert_eq!(closest_match("depth", fields), None);
+    }
+}
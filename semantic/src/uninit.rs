@@ -0,0 +1,386 @@
+//! Uninitialized-use warnings (`-Wmaybe-uninitialized`).
+//!
+//! A definite-assignment walk over a function body. Each scalar local
+//! declared without an initializer is tracked from its declaration; a read
+//! on a path where no assignment has reached it yet is reported once. Paths
+//! that end in `return`, `break`, `continue`, or `goto` drop out of the
+//! merges after `if`, `?:`, loops, and `switch`. Taking a variable's address
+//! counts as initializing it, and after a label, whose incoming `goto`s are
+//! not followed, every variable in scope counts as assigned. A call to a
+//! function that does not return (`abort`, `exit`, or one declared
+//! `noreturn`) ends its path too.
+
+use model::{BinaryOp, CompileError, ErrorCode, Expr, Function, Stmt, Type, TypeEnv, UnaryOp};
+use std::collections::{HashMap, HashSet};
+
+/// Library functions that never return.
+const NORETURN_LIBRARY: &[&str] = &[
+    "abort",
+    "exit",
+    "_exit",
+    "_Exit",
+    "quick_exit",
+    "longjmp",
+    "siglongjmp",
+    "__assert_fail",
+    "__builtin_trap",
+    "__builtin_unreachable",
+];
+
+/// Warnings for the locals of `function` that may be read uninitialized.
+/// `noreturn` names the program's own functions that never return.
+pub(crate) fn check_function(function: &Function, type_env: &TypeEnv, noreturn: &HashSet<String>) -> Vec<CompileError> {
+    let mut checker = Checker {
+        type_env,
+        noreturn,
+        names: Vec::new(),
+        scopes: vec![HashMap::new()],
+        state: State::default(),
+        breaks: Vec::new(),
+        switches: Vec::new(),
+        warned: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    for stmt in &function.body.statements {
+        checker.stmt(stmt);
+    }
+    checker.warnings
+}
+
+/// Tracked variables (by declaration index) assigned on every path reaching
+/// the current point.
+#[derive(Clone, Default)]
+struct State {
+    assigned: HashSet<usize>,
+    /// False after a jump: no path reaches here.
+    unreachable: bool,
+}
+
+impl State {
+    /// The state where the paths of `self` and `other` join.
+    fn merge(self, other: State) -> State {
+        match (self.unreachable, other.unreachable) {
+            (true, _) => other,
+            (_, true) => self,
+            _ => State { assigned: self.assigned.intersection(&other.assigned).copied().collect(), unreachable: false },
+        }
+    }
+
+    fn unreachable() -> State {
+        State { assigned: HashSet::new(), unreachable: true }
+    }
+}
+
+struct Checker<'a> {
+    type_env: &'a TypeEnv,
+    noreturn: &'a HashSet<String>,
+    /// Name of each tracked declaration.
+    names: Vec<String>,
+    /// Name → declaration index; `None` shadows an outer tracked variable
+    /// with one that is not tracked.
+    scopes: Vec<HashMap<String, Option<usize>>>,
+    state: State,
+    /// States at the `break`s of each enclosing loop or switch.
+    breaks: Vec<Vec<State>>,
+    /// State on entry to the body of each enclosing switch.
+    switches: Vec<State>,
+    warned: HashSet<usize>,
+    warnings: Vec<CompileError>,
+}
+
+impl Checker<'_> {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).copied().flatten()
+    }
+
+    /// Whether a local of type `ty` is tracked: scalars only, since arrays,
+    /// structs, and unions are usually filled in piece by piece.
+    fn is_tracked_type(&self, ty: &Type) -> bool {
+        let ty = self.type_env.resolve_type(ty);
+        TypeEnv::is_scalar_type(&ty) || matches!(ty, Type::FunctionPointer { .. })
+    }
+
+    fn declare(&mut self, name: &str, tracked: bool) -> Option<usize> {
+        let id = tracked.then(|| {
+            self.names.push(name.to_string());
+            self.names.len() - 1
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), id);
+        }
+        id
+    }
+
+    fn assign(&mut self, name: &str) {
+        if let Some(id) = self.lookup(name) {
+            self.state.assigned.insert(id);
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        let Some(id) = self.lookup(name) else { return };
+        if self.state.unreachable || self.state.assigned.contains(&id) || !self.warned.insert(id) {
+            return;
+        }
+        self.warnings.push(CompileError::warning(
+            ErrorCode::MaybeUninitialized,
+            format!("'{}' may be used uninitialized", self.names[id]),
+        ));
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    /// Run `body` as a loop or switch body and return the states of its `break`s.
+    fn breakable(&mut self, body: impl FnOnce(&mut Self)) -> Vec<State> {
+        self.breaks.push(Vec::new());
+        body(self);
+        self.breaks.pop().unwrap_or_default()
+    }
+
+    fn merge_all(states: Vec<State>) -> State {
+        states.into_iter().fold(State::unreachable(), State::merge)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Declaration { r#type, name, init, has_static_storage, .. } => {
+                let tracked = !has_static_storage && self.is_tracked_type(r#type);
+                // The name is in scope in its own initializer
+                let id = self.declare(name, tracked);
+                if let Some(init) = init {
+                    self.expr(init);
+                    if let Some(id) = id {
+                        self.state.assigned.insert(id);
+                    }
+                }
+            }
+            Stmt::MultiDecl(stmts) => {
+                for s in stmts {
+                    self.stmt(s);
+                }
+            }
+            Stmt::Block(block) => self.block(&block.statements),
+            Stmt::Expr(expr) => {
+                self.expr(expr);
+                if let Expr::Call { func, .. } = expr {
+                    if matches!(&**func, Expr::Variable(name) if self.noreturn.contains(name) || NORETURN_LIBRARY.contains(&name.as_str())) {
+                        self.state = State::unreachable();
+                    }
+                }
+            }
+            Stmt::Return(expr) => {
+                if let Some(e) = expr {
+                    self.expr(e);
+                }
+                self.state = State::unreachable();
+            }
+            Stmt::If { cond, then_branch, else_branch } => {
+                self.expr(cond);
+                let before = self.state.clone();
+                self.stmt(then_branch);
+                let after_then = std::mem::replace(&mut self.state, before);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+                let after_else = std::mem::take(&mut self.state);
+                self.state = after_then.merge(after_else);
+            }
+            Stmt::While { cond, body } => {
+                self.expr(cond);
+                let exit = self.state.clone();
+                let breaks = self.breakable(|c| c.stmt(body));
+                self.state = Self::loop_exit(cond_is_true(Some(cond)), exit, breaks);
+            }
+            Stmt::DoWhile { body, cond } => {
+                let breaks = self.breakable(|c| c.stmt(body));
+                // `continue` paths are not joined in: the condition sees the
+                // fall-through state
+                self.expr(cond);
+                let exit = self.state.clone();
+                self.state = Self::loop_exit(cond_is_true(Some(cond)), exit, breaks);
+            }
+            Stmt::For { init, cond, post, body } => {
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.stmt(init);
+                }
+                if let Some(cond) = cond {
+                    self.expr(cond);
+                }
+                let exit = self.state.clone();
+                let breaks = self.breakable(|c| {
+                    c.stmt(body);
+                    if let Some(post) = post {
+                        c.expr(post);
+                    }
+                });
+                self.state = Self::loop_exit(cond_is_true(cond.as_ref()), exit, breaks);
+                self.scopes.pop();
+            }
+            Stmt::Switch { cond, body } => {
+                self.expr(cond);
+                let entry = self.state.clone();
+                self.switches.push(entry.clone());
+                // Nothing in the body runs before the first label
+                self.state = State::unreachable();
+                let has_default = contains_default(body);
+                let breaks = self.breakable(|c| c.stmt(body));
+                self.switches.pop();
+                let mut exit = Self::merge_all(breaks).merge(std::mem::take(&mut self.state));
+                if !has_default {
+                    exit = exit.merge(entry);
+                }
+                self.state = exit;
+            }
+            Stmt::Case(_) | Stmt::Default => {
+                if let Some(entry) = self.switches.last() {
+                    self.state = std::mem::take(&mut self.state).merge(entry.clone());
+                }
+            }
+            Stmt::Break => {
+                let state = std::mem::replace(&mut self.state, State::unreachable());
+                if let Some(breaks) = self.breaks.last_mut() {
+                    breaks.push(state);
+                }
+            }
+            Stmt::Continue | Stmt::Goto(_) => self.state = State::unreachable(),
+            Stmt::ComputedGoto(target) => {
+                self.expr(target);
+                self.state = State::unreachable();
+            }
+            Stmt::Label(_) => {
+                self.state = State { assigned: (0..self.names.len()).collect(), unreachable: false };
+            }
+            Stmt::StaticAssert(_) => {}
+            Stmt::InlineAsm { outputs, inputs, .. } => {
+                for input in inputs {
+                    self.expr(&input.expr);
+                }
+                for output in outputs {
+                    if output.constraint.contains('+') {
+                        self.expr(&output.expr);
+                    }
+                    self.store(&output.expr);
+                }
+            }
+        }
+    }
+
+    /// The state after a loop: the paths where the condition was false,
+    /// unless it is always true, joined with the `break`s.
+    fn loop_exit(always_true: bool, exit: State, breaks: Vec<State>) -> State {
+        let exit = if always_true { State::unreachable() } else { exit };
+        exit.merge(Self::merge_all(breaks))
+    }
+
+    /// An assignment target: a plain variable is written, anything else is
+    /// evaluated for the reads in it.
+    fn store(&mut self, target: &Expr) {
+        match target {
+            Expr::Variable(name) => self.assign(name),
+            other => self.expr(other),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable(name) => self.read(name),
+            Expr::Binary { left, op: BinaryOp::Assign, right } => {
+                self.expr(right);
+                self.store(left);
+            }
+            Expr::Binary { left, op: BinaryOp::LogicalAnd | BinaryOp::LogicalOr, right } => {
+                self.expr(left);
+                let before = self.state.clone();
+                self.expr(right);
+                // The right operand may not run
+                self.state = before.merge(std::mem::take(&mut self.state));
+            }
+            Expr::Binary { left, op, right } => {
+                self.expr(left);
+                self.expr(right);
+                if TypeEnv::compound_assign_op(op).is_some() {
+                    self.store(left);
+                }
+            }
+            Expr::Unary { op: UnaryOp::AddrOf, expr } => match &**expr {
+                // The callee or a pointer may initialize it
+                Expr::Variable(name) => self.assign(name),
+                other => self.expr(other),
+            },
+            Expr::Unary { expr, .. }
+            | Expr::PostfixIncrement(expr)
+            | Expr::PostfixDecrement(expr)
+            | Expr::PrefixIncrement(expr)
+            | Expr::PrefixDecrement(expr)
+            | Expr::Cast(_, expr)
+            | Expr::Real(expr)
+            | Expr::Imag(expr)
+            | Expr::Member { expr, .. }
+            | Expr::PtrMember { expr, .. }
+            | Expr::Expect { expr, .. } => self.expr(expr),
+            Expr::Index { array, index } => {
+                self.expr(array);
+                self.expr(index);
+            }
+            Expr::Call { func, args } => {
+                self.expr(func);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Conditional { condition, then_expr, else_expr } => {
+                self.expr(condition);
+                let before = self.state.clone();
+                self.expr(then_expr);
+                let after_then = std::mem::replace(&mut self.state, before);
+                self.expr(else_expr);
+                self.state = after_then.merge(std::mem::take(&mut self.state));
+            }
+            Expr::Comma(exprs) => {
+                for e in exprs {
+                    self.expr(e);
+                }
+            }
+            Expr::InitList(items) | Expr::CompoundLiteral { init: items, .. } => {
+                for item in items {
+                    self.expr(&item.value);
+                }
+            }
+            Expr::StmtExpr(stmts) => self.block(stmts),
+            Expr::VaArg { list, .. } => self.expr(list),
+            // Not evaluated: sizeof operands and _Generic selections
+            Expr::SizeOfExpr(_) | Expr::Generic { .. } => {}
+            _ => {}
+        }
+    }
+}
+
+/// Whether a loop condition is absent or a nonzero constant.
+fn cond_is_true(cond: Option<&Expr>) -> bool {
+    match cond {
+        None => true,
+        Some(Expr::Constant(v) | Expr::TypedConstant(v, _)) => *v != 0,
+        Some(_) => false,
+    }
+}
+
+/// Whether a switch body has a `default` label of its own (not one belonging
+/// to a nested switch).
+fn contains_default(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Default => true,
+        Stmt::Block(block) => block.statements.iter().any(contains_default),
+        Stmt::If { then_branch, else_branch, .. } => {
+            contains_default(then_branch) || else_branch.as_deref().is_some_and(contains_default)
+        }
+        Stmt::While { body, .. } | Stmt::DoWhile { body, .. } | Stmt::For { body, .. } => contains_default(body),
+        _ => false,
+    }
+}
@@ -0,0 +1,26 @@
+// Test brace initializers for nested local and global aggregates
+// EXPECT: 72
+struct In { short a; char b; };
+struct Out { int k; struct In in; int arr[3]; double d; int tail; };
+struct Out go = {1, {2, 3}, {4, 5, 6}, 7.5, 9};
+struct Out gos[2] = {{1, {2, 3}, {4, 5, 6}, 1.5, 2}, {8}};
+enum Color { RED = 5, GREEN };
+int g = 3;
+int *gptrs[2] = {&g, 0};
+long gneg[3] = {-1, 2 * 3, GREEN};
+double gd[2] = {1.25, 2};
+struct Named { char name[8]; int id; };
+struct Named gn = {"abc", 4};
+int main(void) {
+    struct Out o = {1, {2, 3}, {4, 5, 6}, 7, 11};
+    int v = o.k + o.in.a + o.in.b + o.arr[2] + o.tail;         // 1+2+3+6+11 = 23
+    double od = o.d;
+    if (od == 7.0) v += 1;                                     // 24
+    v += go.in.b + go.arr[0] + go.tail + gos[1].k + gos[0].tail; // 3+4+9+8+2 = 26 -> 50
+    v += *gptrs[0] + (gptrs[1] == 0);                          // 4 -> 54
+    v += gneg[0] + gneg[1] + gneg[2];                          // -1+6+6 = 11 -> 65
+    double d1 = gd[1];
+    if (d1 == 2.0) v += 1;                                     // 66
+    v += gn.name[2] - 'a' + gn.id;                             // 2+4 -> 72
+    return v;
+}
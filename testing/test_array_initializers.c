@@ -0,0 +1,38 @@
+// EXPECT: 222
+// Local array initializers: short lists and strings zero-fill the rest,
+// nested lists follow source dimension order, and strings initialize
+// character arrays inside arrays and structs.
+struct S { int n; char name[8]; };
+
+static int sum(const char *p, int n) {
+    int s = 0;
+    for (int i = 0; i < n; i++) s += p[i];
+    return s;
+}
+
+static int dirty(void) {
+    char junk[64];
+    for (int i = 0; i < 64; i++) junk[i] = 7;
+    return sum(junk, 64);
+}
+
+static int f(void) {
+    char buf[16] = "hi";
+    int a[8] = {1, 2, 3};
+    int b[2][3] = {{1}, {2, 3}};
+    short w[5] = {[3] = 9};
+    char t[] = "abc";
+    char m[2][4] = {"ab", "c"};
+    struct S s = {1, "xy"};
+    if (sum(m[0], 8) != 'a' + 'b' + 'c' || sum(s.name, 8) != 'x' + 'y') return 1;
+    return sum(buf, 16) + a[3] + a[7] + b[0][2] + b[1][2] + w[0] + w[3] + (int)sizeof t + t[3];
+}
+
+int main(void) {
+    int r = 0;
+    for (int k = 0; k < 3; k++) {
+        dirty();
+        r = f();
+    }
+    return r;
+}
@@ -0,0 +1,49 @@
+// Test System V bit-field layout: storage units, straddling, zero-width
+// and unnamed bit-fields, and initializers that pack several fields
+// EXPECT: 42
+
+struct Small { int a : 4; char c; };              // c shares a's int unit: size 4
+struct Mixed { char a : 4; int b : 4; };           // both fit in one int unit: size 4
+struct Straddle { int a : 30; int b : 4; };        // b cannot straddle: size 8
+struct Zero { char a : 3; int : 0; char b : 3; };  // b starts at the next int: size 5
+struct Unnamed { int a : 3; int : 5; int b : 8; }; // b at bit 8
+struct Pair { unsigned lo : 12; unsigned hi : 20; };
+
+struct Pair g_pair = { 0xabc, 0x12345 };
+struct Small g_small = { 5, 'x' };
+
+int main(void) {
+    int score = 0;
+
+    if (sizeof(struct Small) == 4) score += 2;
+    if (sizeof(struct Mixed) == 4) score += 2;
+    if (sizeof(struct Straddle) == 8) score += 2;
+    if (sizeof(struct Zero) == 5) score += 2;
+    if (sizeof(struct Unnamed) == 4) score += 2;
+
+    struct Small s;
+    s.a = 3;
+    s.c = 'z';
+    s.a = 6;                                        // must not clobber c
+    if (s.a == 6 && s.c == 'z') score += 4;
+    if (((char *)&s)[1] == 'z') score += 2;
+
+    struct Straddle st;
+    st.a = 1000000;
+    st.b = 5;
+    if (st.a == 1000000 && st.b == 5) score += 4;
+    if (((int *)&st)[1] == 5) score += 2;
+
+    struct Unnamed u = { 3, 100 };                  // positional init skips the unnamed field
+    if (u.a == 3 && u.b == 100) score += 4;
+    if ((((int *)&u)[0] >> 8 & 0xff) == 100) score += 2;
+
+    struct Pair p = { 0x123, 0xfffff };
+    if (p.lo == 0x123 && p.hi == 0xfffff) score += 4;
+
+    if (g_pair.lo == 0xabc && g_pair.hi == 0x12345) score += 4;
+    if (*(unsigned *)&g_pair == 0x12345abc) score += 2;
+    if (g_small.a == 5 && g_small.c == 'x') score += 4;
+
+    return score;
+}
@@ -0,0 +1,53 @@
+// Test _Bool conversions: every store into a _Bool normalizes to 0/1,
+// and && / || yield 0/1 rather than an operand's value
+// EXPECT: 42
+
+struct Flags { _Bool on; int pad; };
+
+_Bool global_flag = 7;
+_Bool global_off = 0;
+
+_Bool to_bool(int x) { return x; }
+int take_bool(_Bool b) { return b; }
+int identity(int x) { return x; }
+
+int main(void) {
+    int score = 0;
+
+    _Bool a = 256;            // low byte is 0, but the value is nonzero
+    score += a;               // 1
+    _Bool d = 0.5;
+    score += d;               // 2
+    int x = 0;
+    int *p = &x;
+    _Bool from_ptr = p;
+    score += from_ptr;        // 3
+
+    _Bool b = 1;
+    b += 1;
+    score += b;               // 4
+    b = 0;
+    b--;
+    score += b;               // 5
+    b++;
+    b++;
+    score += b;               // 6
+
+    score += (_Bool)512;      // 7
+    score += to_bool(512);    // 8
+    score += take_bool(512);  // 9
+
+    struct Flags f;
+    f.on = 1024;
+    score += f.on;            // 10
+    _Bool arr[2];
+    arr[1] = 4096;
+    score += arr[1];          // 11
+
+    score += global_flag + global_off;          // 12
+    score += identity(2) && identity(5);        // 13
+    score += identity(0) || identity(7);        // 14
+    score += sizeof(_Bool) + sizeof(global_flag); // 16
+
+    return score + 26;
+}
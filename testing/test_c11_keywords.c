@@ -0,0 +1,33 @@
+// EXPECT: 42
+#include <stdio.h>
+#include <stdlib.h>
+
+_Alignas(16) static char buffer[32];
+_Alignas(long) int counter = 2;
+_Thread_local int tls_value = 5;
+static __thread int gnu_tls;
+_Atomic int hits;
+_Atomic(long) total = 10;
+
+_Noreturn static void die(int code) {
+    exit(code);
+}
+
+static int add(int _Atomic *p, int n) {
+    *p += n;
+    return *p;
+}
+
+int main(void) {
+    _Alignas(8) int local = 3;
+    _Atomic(int) a = 4;
+    int * _Atomic p = &local;
+    if (((unsigned long)buffer & 15) != 0) return 1;
+    if (((unsigned long)&counter & 7) != 0) return 2;
+    gnu_tls = 1;
+    hits = add(&a, *p);
+    total += hits + counter + tls_value + gnu_tls + (int)_Alignof(_Atomic(long));
+    if (total != 33) die(3);
+    printf("total=%ld\n", total);
+    return (int)total + 9;
+}
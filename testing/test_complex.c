@@ -0,0 +1,41 @@
+// EXPECT: 42
+// Test _Complex arithmetic, part access, and comparison
+
+int main() {
+    double _Complex a = 1.0 + 2.0i;
+    double _Complex b = 3.0 - 1.0i;
+    double _Complex sum = a + b;
+    double _Complex prod = a * b;
+    double _Complex quot = prod / b;
+    float _Complex f = 2.0f + 0.5if;
+    f *= 2.0f;
+
+    double re = __real__ prod;
+    double im = __imag__ prod;
+    int r = (int)re;
+    int i = (int)im;
+    if (r != 5 || i != 5) return 1;
+
+    double qr = __real__ quot;
+    double qi = __imag__ quot;
+    int qri = (int)qr;
+    int qii = (int)qi;
+    if (qri != 1 || qii != 2) return 2;
+
+    if (sum != 4.0 + 1.0i) return 3;
+    if (!(sum == 4.0 + 1.0i)) return 4;
+
+    double _Complex c = ~a;
+    double ci = __imag__ c;
+    int cii = (int)ci;
+    if (cii != -2) return 5;
+
+    float fr = __real__ f;
+    float fi = __imag__ f;
+    int fri = (int)fr;
+    int fii = (int)fi;
+    if (fri != 4 || fii != 1) return 6;
+
+    if (sizeof(double _Complex) != 16 || sizeof(f) != 8) return 7;
+    return 42;
+}
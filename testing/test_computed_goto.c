@@ -0,0 +1,71 @@
+// EXPECT: 83
+// GNU computed goto: label addresses (&&label) and indirect jumps (goto *expr)
+enum { OP_PUSH, OP_ADD, OP_MUL, OP_DUP, OP_JNZ, OP_DEC, OP_SWAP, OP_HALT };
+
+int execute(const int *code) {
+    static void *dispatch[] = {
+        &&op_push, &&op_add, &&op_mul, &&op_dup, &&op_jnz, &&op_dec, &&op_swap, &&op_halt,
+    };
+    int stack[16];
+    int sp = 0;
+    int pc = 0;
+
+#define NEXT goto *dispatch[code[pc++]]
+    NEXT;
+op_push:
+    stack[sp++] = code[pc++];
+    NEXT;
+op_add:
+    sp--;
+    stack[sp - 1] += stack[sp];
+    NEXT;
+op_mul:
+    sp--;
+    stack[sp - 1] *= stack[sp];
+    NEXT;
+op_dup:
+    stack[sp] = stack[sp - 1];
+    sp++;
+    NEXT;
+op_jnz:
+    pc = stack[--sp] ? code[pc] : pc + 1;
+    NEXT;
+op_dec:
+    stack[sp - 1]--;
+    NEXT;
+op_swap: {
+        int t = stack[sp - 1];
+        stack[sp - 1] = stack[sp - 2];
+        stack[sp - 2] = t;
+    }
+    NEXT;
+op_halt:
+    return stack[sp - 1];
+#undef NEXT
+}
+
+int pick(int which) {
+    void *target = which ? &&one : &&two;
+    goto *target;
+one:
+    return 1;
+two:
+    return 2;
+}
+
+int main(void) {
+    // acc = 3; n = 4; do { acc = acc * 2 + 1; } while (--n); => 63
+    int program[] = {
+        OP_PUSH, 3, OP_PUSH, 4,
+        OP_SWAP, OP_PUSH, 2, OP_MUL, OP_PUSH, 1, OP_ADD, OP_SWAP,
+        OP_DEC, OP_DUP, OP_JNZ, 4,
+        OP_SWAP, OP_HALT,
+    };
+    int result = execute(program);
+    // 63 + 10 * 1 + 5 * 2 = 83
+    for (int i = 0; i < 10; i++)
+        result += pick(1);
+    for (int i = 0; i < 5; i++)
+        result += pick(0);
+    return result;
+}
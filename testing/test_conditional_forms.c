@@ -0,0 +1,25 @@
+// EXPECT: 53
+// The conditional operator in every position: initializer list elements,
+// compound literals, void arms run for their side effects, arms converted to
+// double or long, pointer arms with a null constant, GNU `a ?: b`, and nesting.
+static int calls;
+static void hit(void) { calls++; }
+static int side(int v) { calls += 10; return v; }
+
+int main(int argc, char **argv) {
+    int arr[3] = {argc ? 4 : 5, argc > 1 ? 6 : 8, (int){argc ? 1 : 2}};
+    double d = argc > 1 ? 1 : 2.5;
+    float f = argc ? 0.5f : 3;
+    long l = argc > 1 ? 1 : -1;
+    unsigned u = argc ? 3u : -1;
+    char *s = argc > 5 ? 0 : "ok";
+    int g = argc ?: 9;
+    int z = 0 ?: side(4);
+    argc ? hit() : (void)0;
+    argc > 5 ? hit() : hit();
+    int n = argc > 1 ? 1 : argc > 0 ? 2 : 3;
+    int k = (argc ? side(1) : side(2)) + (argc > 5 ? side(3) : 0);
+
+    return arr[0] + arr[1] + arr[2] + (int)(d * 2) + (int)(f * 4) + (int)l + (int)u
+        + (s[0] == 'o') + g + z + n + k + calls;
+}
@@ -0,0 +1,34 @@
+// EXPECT: 42
+// The branches of ?: are converted to a common type
+struct Pair { int a; int b; };
+
+static int *pick(int c, int *p) {
+    return c ? p : 0;
+}
+
+int main(void) {
+    int c = 1;
+    int x = 7;
+    int score = 0;
+
+    // Arithmetic operands meet in their common type
+    char ch = 'a';
+    if (sizeof(c ? ch : ch) == sizeof(int) && sizeof(c ? x : 2L) == sizeof(long) && sizeof(c ? x : 0.5) == sizeof(double))
+        score += 10;
+
+    // A null pointer constant takes the type of the other pointer
+    if (pick(1, &x) == &x && pick(0, &x) == 0) score += 10;
+
+    // Structs of the same type can be chosen between
+    struct Pair p = { 1, 2 };
+    struct Pair q = { 3, 4 };
+    struct Pair r = c ? q : p;
+    score += r.a + r.b;
+
+    // char operands are promoted, so the result does not wrap at 8 bits
+    char big = 100;
+    char small = 1;
+    score += (c ? big : small) + 100 > 127 ? 15 : 0;
+
+    return score;
+}
@@ -0,0 +1,41 @@
+// EXPECT: 209
+// Constant expressions in every position that requires one
+struct Pair { char tag; long value; };
+
+enum Size { SMALL = sizeof(char), LARGE = (int)sizeof(struct Pair) / 2, HUGE = LARGE << 2 };
+
+_Static_assert(sizeof(struct Pair) == 16, "struct Pair is padded to 16 bytes");
+_Static_assert(HUGE == 32 && (unsigned char)300 == 44, "enum and cast arithmetic");
+
+struct Flags {
+    unsigned int low : SMALL + 2;
+    unsigned int high : LARGE > 4 ? 5 : 1;
+};
+
+int table[LARGE - SMALL];
+long scaled = (long)2.75 * HUGE + (-1 < 0u);
+
+int classify(int x) {
+    switch (x) {
+    case SMALL:
+        return 10;
+    case LARGE + 1:
+        return 20;
+    case 'a' - 'A':
+        return 30;
+    case -HUGE:
+        return 40;
+    default:
+        return 0;
+    }
+}
+
+int main(void) {
+    struct Flags f;
+    char buf[12];
+    _Static_assert(sizeof buf == 12, "sizeof a local");
+    f.low = 7;
+    f.high = 31;
+    int n = sizeof(table) / sizeof(table[0]);
+    return classify(1) + classify(9) + classify(32) + classify(-32) + f.low + f.high + n + (int)scaled;
+}
@@ -0,0 +1,18 @@
+// EXPECT: 42
+// Digraphs: <% %> for braces, <: :> for brackets, %: for #
+%:include <stdio.h>
+%:define SIZE 4
+
+static int table<:SIZE:> = <% 3, 9, 12, 18 %>;
+
+struct point <% int x; int y; %>;
+
+int main(void) <%
+    struct point p = <% .x = 1, .y = 2 %>;
+    int sum = 0;
+    for (int i = 0; i < SIZE; i++) <%
+        sum += table<:i:>;
+    %>
+    printf("sum=%d\n", sum);
+    return sum + p.x + p.y - 3 + (p.y > p.x ? 0 : 1);
+%>
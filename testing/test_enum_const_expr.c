@@ -0,0 +1,10 @@
+// Test constant expressions in enum values, referring to earlier constants
+// EXPECT: 101
+enum Flags { A = 1 << 4, B = A + 1, C = (B * 2) | 1, D, E = sizeof(int) * 2, F = -C };
+enum { ANON = 7 };
+int arr[B];
+int main(void) {
+    int x = A + B + C + D + E + F + ANON + (int)sizeof(arr)/4;
+    switch (x) { case A: return 1; default: break; }
+    return x;
+}
@@ -0,0 +1,24 @@
+// EXPECT: 42
+// Test enum underlying types: sizeof follows the range of the enumerators
+enum Small { A, B, C };
+enum Neg { M = -1, N = 1 };
+enum Wide { W0, WBIG = 0x100000000 };
+enum Wide gw;
+
+long high_word(enum Wide w) { return w >> 32; }
+
+int main() {
+    enum Wide w = WBIG;
+    enum Small s = C;
+    if (sizeof(enum Small) != 4) return 1;
+    if (sizeof(enum Neg) != 4) return 2;
+    if (sizeof(enum Wide) != 8) return 3;
+    if (sizeof w != 8 || sizeof gw != 8) return 4;
+    if (high_word(w) != 1) return 5;
+    switch (s) {
+        case A: return 6;
+        case B: return 7;
+        case C: break;
+    }
+    return 42;
+}
@@ -0,0 +1,24 @@
+// EXPECT: 47
+// Extended asm: symbolic operand names, constraint modifiers, immediate
+// operands, concatenated templates, and clobber lists.
+
+int add_named(int a, int b) {
+    int result;
+    __asm__ volatile("mov %[res], %[lhs]\n\t"
+                     "add %[res], %[rhs]"
+                     : [res] "=&r"(result)
+                     : [lhs] "r"(a), [rhs] "r"(b)
+                     : "cc");
+    return result;
+}
+
+int add_five(int x) {
+    __asm__ ("add %0, %1" : "+r"(x) : "i"(5) : "cc", "memory");
+    return x;
+}
+
+int main(void) {
+    int sum = add_named(30, 7);
+    int bumped = add_five(sum);
+    return sum + bumped - 32;
+}
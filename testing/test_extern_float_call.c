@@ -0,0 +1,17 @@
+// EXPECT: 27
+// Calls to library functions known only by their prototype must use the
+// declared return type, including through a function pointer.
+double atof(const char *s);
+float strtof(const char *s, char **end);
+
+static float scale(float x) { return x * 2; }
+
+int main() {
+    int a = (int)(atof("2.5") * 4);
+    int b = (int)strtof("3.75", 0);
+    float (*f)(float) = scale;
+    int c = (int)f(strtof("7.25", 0));
+    unsigned u = 3;
+    long n = u + 1;
+    return a + b + c + (int)n - 4;
+}
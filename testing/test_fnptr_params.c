@@ -0,0 +1,33 @@
+// Test function pointer parameters and functions returning function pointers
+// EXPECT: 42
+void qsort(void *base, unsigned long n, unsigned long size, int (*cmp)(const void *, const void *));
+int twice(int (*)(int), int x);
+
+static int add(int a, int b) { return a + b; }
+static int sub(int a, int b) { return a - b; }
+static int inc(int x) { return x + 1; }
+
+static int apply(int (*op)(int, int), int a, int b) {
+    return op(a, b);
+}
+
+static int (*pick(int which))(int, int) {
+    return which ? add : sub;
+}
+
+int twice(int (*f)(int), int x) {
+    return f(f(x));
+}
+
+static int cmp_int(const void *a, const void *b) {
+    return *(const int *)a - *(const int *)b;
+}
+
+int main(void) {
+    int v[4] = {9, 3, 7, 1};
+    qsort(v, 4, sizeof(int), cmp_int);          // 1 3 7 9
+    int (*f)(int, int) = pick(1);
+    int r = apply(f, 20, 10);                   // 30
+    r = r + apply(pick(0), v[3], v[2]);         // 30 + 2 = 32
+    return r + twice(inc, v[2]) + v[0];         // 32 + 9 + 1 = 42
+}
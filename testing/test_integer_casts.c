@@ -0,0 +1,39 @@
+// EXPECT: 0
+// Conversions between integer widths and signedness, and between integers
+// and floating point, applied by casts, initializers, assignments, returns,
+// and arguments. Values come through functions so nothing folds.
+static int id(int x) { return x; }
+static long lid(long x) { return x; }
+static unsigned long ulid(unsigned long x) { return x; }
+static double did(double x) { return x; }
+static unsigned char narrow(int x) { return x; }
+static long widen(unsigned short s) { return s; }
+
+int main(int argc, char **argv) {
+    int n = id(-argc - 299);
+    long big = lid(0x1234567890L + argc);
+    int fails = 0;
+
+    unsigned u = n;
+    if ((long)u != 4294966996L) fails |= 1;
+    if ((long)n != -300) fails |= 2;
+    if ((char)n != -44 || (unsigned char)n != 212) fails |= 4;
+    if ((short)big != 0x7891 || (int)big != 0x34567891) fails |= 8;
+    if ((unsigned long)(unsigned char)n != 212 || (long)(signed char)n != -44) fails |= 16;
+    if (narrow(n) != 212 || widen(n) != 65236) fails |= 32;
+
+    unsigned long top = ulid(0xF000000000000001UL + argc);
+    double d = top;
+    if (d < 1.7e19 || d > 1.8e19) fails |= 64;
+    if ((unsigned long)did(1.5e19) != 15000000000000000000UL) fails |= 128;
+    if ((unsigned)did(3e9) != 3000000000u || (int)did(-7.9) != -7) fails |= 256;
+    if ((double)(unsigned)(-argc) != 4294967295.0) fails |= 512;
+    if ((float)(unsigned char)n != 212.0f) fails |= 1024;
+
+    unsigned char c = 250;
+    c = c + 10;
+    long l = -1;
+    unsigned long ul = (unsigned)l;
+    if (c != 4 || ul != 4294967295UL) fails |= 2048;
+    return fails;
+}
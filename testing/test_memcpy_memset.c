@@ -0,0 +1,43 @@
+// EXPECT: 93
+// Struct assignment copies the whole object and partial initializers zero the rest
+
+struct Small { char tag; short code; int value; };
+struct Wide { long a, b, c; int d; char e; };
+struct Huge { long v[12]; };
+
+// Pointer parameters live in RDI/RSI, which the large copy borrows
+long copy_all(struct Huge *dst, struct Huge *src, int n) {
+    long total = 0;
+    for (int i = 0; i < n; i++) {
+        dst[i] = src[i];
+        total += dst[i].v[11];
+    }
+    return total + src[0].v[0];
+}
+
+int main(void) {
+    struct Wide w = {1, 2, 3, 4, 5};
+    struct Wide x;
+    x = w;
+    w.a = 100;
+
+    struct Small s = {.value = 7};
+    struct Small t = {1, 2, 3};
+    t = s;
+
+    struct Huge src[2] = {{{1}}, {{0}}};
+    src[1].v[11] = 20;
+    src[0].v[11] = 30;
+    struct Huge dst[2];
+    long copied = copy_all(dst, src, 2);
+
+    int arr[10] = {1, 2};
+    char buf[7] = {0};
+    int sum = 0;
+    for (int i = 0; i < 10; i++) sum += arr[i];
+    for (int i = 0; i < 7; i++) sum += buf[i];
+
+    // 15 + 7 + 0 + 51 + 3 + 0 + 17
+    return (int)(x.a + x.b + x.c + x.d + x.e) + t.value + t.tag + (int)copied + sum
+        + dst[1].v[5] + (int)dst[0].v[0] * 17;
+}
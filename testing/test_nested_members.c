@@ -0,0 +1,42 @@
+// EXPECT: 42
+// Struct and union members declared with their own bodies, including
+// anonymous members whose fields belong to the enclosing struct
+
+struct Shape {
+    int kind;
+    union {
+        int radius;
+        struct {
+            short w, h;
+        };
+    };
+    struct Origin {
+        int x;
+        int y;
+    } origin;
+};
+
+int area(struct Shape *s) {
+    if (s->kind == 0)
+        return 3 * s->radius * s->radius;
+    return s->w * s->h;
+}
+
+int main() {
+    struct Shape circle;
+    circle.kind = 0;
+    circle.radius = 2;
+    circle.origin.x = 1;
+    circle.origin.y = 2;
+
+    struct Shape rect;
+    rect.kind = 1;
+    rect.w = 3;
+    rect.h = 5;
+
+    struct Origin o = rect.origin;
+    o.x = circle.origin.x + circle.origin.y;
+    // 12 + 15 + 3 + 4 (union starts after kind) + 8 (offset of h)
+    return area(&circle) + area(&rect) + o.x + (int)((char *)&rect.radius - (char *)&rect)
+        + (int)((char *)&rect.h - (char *)&rect.w) + 6 * (sizeof(struct Shape) == 16);
+}
@@ -0,0 +1,31 @@
+// EXPECT: 58
+// #pragma pack caps member alignment for the structs that follow it
+#pragma pack(push, 1)
+struct A { char c; int i; };
+#pragma pack(push, 2)
+struct B { char c; long l; int i; };
+#pragma pack(pop)
+struct C { char c; short s; };
+#pragma pack(pop)
+struct D { char c; int i; };
+#pragma pack(4)
+struct E { char c; double d; };
+#pragma pack()
+struct F { char c; double d; };
+
+struct B global_b = { 1, 40, 3 };
+
+int main(void) {
+    struct B b;
+    b.c = 7;
+    b.l = 40;
+    b.i = 3;
+    struct B *p = &global_b;
+    if (b.l + b.i != 43 || p->l + p->i != 43)
+        return 1;
+    if (__builtin_offsetof(struct B, i) != 10 || __builtin_offsetof(struct E, d) != 4)
+        return 2;
+    // 5 + 14 + 3 + 8 + 12 + 16
+    return sizeof(struct A) + sizeof(struct B) + sizeof(struct C)
+         + sizeof(struct D) + sizeof(struct E) + sizeof(struct F);
+}
@@ -40,5 +40,9 @@ int main() {
     if (sizeof(float) != 4) return 13;
     if (sizeof(double) != 8) return 14;
 
+    // a string literal is an array, terminator included
+    if (sizeof "abc" != 4) return 15;
+    if (sizeof("") != 1) return 16;
+
     return 42;
 }
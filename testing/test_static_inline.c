@@ -0,0 +1,24 @@
+// EXPECT: 58
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int square(int x) {
+    return x * x;
+}
+
+inline static int cube(int x) {
+    return x * square(x);
+}
+
+static inline __attribute__((always_inline)) int clamp(int v, int lo, int hi) {
+    if (v < lo) return lo;
+    if (v > hi) return hi;
+    return v;
+}
+
+int main(void) {
+    int total = square(3) + cube(2) + clamp(100, 0, 40) + clamp(-5, 1, 9);
+    if (abs(-total) != total) return 1;
+    printf("%d\n", total);
+    return total;
+}
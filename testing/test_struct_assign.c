@@ -0,0 +1,34 @@
+// EXPECT: 26
+// Struct and union assignment copies the whole object, whatever the
+// source: a variable, a member, an array element, a dereference, a chained
+// assignment, a conditional, a comma expression, or a small call result.
+struct S { long a, b, c; };
+struct W { int tag; struct S in; };
+union U { struct S s; long l; };
+struct Pair { int x, y; };
+
+static struct Pair make_pair(int x) {
+    struct Pair p = {x, x * 2};
+    return p;
+}
+
+int main(int argc, char **argv) {
+    struct S a = {1, 2, 3};
+    struct S b = a;
+    struct S c, d;
+    c = d = b;
+    struct S e = argc > 5 ? a : (d, c);
+    struct W w = {7, e};
+    struct S arr[3] = {e, a};
+    struct S *p = &arr[2];
+    *p = w.in;
+    w.in = arr[1];
+    union U u = {a};
+    union U v;
+    v = u;
+    struct Pair pr = make_pair(4);
+    a.a = 100;
+
+    long sum = b.a + c.b + d.c + e.a + w.in.c + arr[0].b + p->c + v.s.c + pr.y + a.a;
+    return (int)(sum - 100);
+}
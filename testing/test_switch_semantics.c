@@ -0,0 +1,89 @@
+// EXPECT: 0
+// Switch statements: fallthrough across blocks and declarations, `default`
+// in the middle, `break` inside a loop that leaves only the switch,
+// `continue` through a nested switch, Duff's device, and case values
+// converted to the controlling type (`unsigned`, `long`).
+static int f(int x, int y) {
+    int acc = 100;
+    switch (x) {
+        acc = 999;               /* never runs: before the first label */
+    case 0:
+        acc += 1;
+    case 1: {
+        int t = acc * 2;
+        acc = t;
+    }
+    case 2:
+        acc += 3;
+        break;
+    default:
+        acc -= 7;
+    case 4:
+        switch (y) {
+        case 0: acc += 50; break;
+        case 1: acc += 60;
+        default: acc += 70;
+        }
+        acc += 5;
+        break;
+    case 5:
+        for (int i = 0; i < 3; i++) {
+            if (i == 1) continue;
+            acc += i;
+            if (i == 2) break;
+        }
+        acc += 1000;
+    }
+    return acc;
+}
+
+static int in_loop(int x) {
+    int r = 0;
+    for (int i = 0; i < 4; i++) {
+        switch (i + x) {
+        case 2: continue;
+        case 3: r += 100; break;
+        default:
+            switch (i) { case 0: r += 1; break; case 1: r += 2; continue; }
+            r += 1000;
+        }
+        r += 10;
+    }
+    return r;
+}
+
+static int duff(int n) {
+    int count = 0, k = (n + 3) / 4;
+    switch (n % 4) {
+    case 0: do { count++;
+    case 3:      count++;
+    case 2:      count++;
+    case 1:      count++;
+            } while (--k > 0);
+    }
+    return count;
+}
+
+static int converted(int argc) {
+    int r = 0;
+    unsigned u = 0;
+    u -= argc;
+    switch (u) { case -1: r += 1; break; case 1: r += 100; }
+    unsigned char c = 255;
+    switch (c) { case -1: r += 100; break; case 255: r += 2; }
+    long v = argc;
+    v <<= 32;
+    switch (v) { case 0x100000000L: r += 4; break; case 0: r += 100; }
+    return r;
+}
+
+int main(int argc, char **argv) {
+    unsigned s = 0;
+    for (int x = -1; x < 7; x++)
+        for (int y = 0; y < 3; y++)
+            s = s * 7 + f(x, y);
+    for (int x = 0; x < 5; x++) s = s * 5 + in_loop(x);
+    for (int n = 1; n < 10; n++) s = s * 3 + duff(n);
+    if (s != 3931036134u) return 1;
+    return converted(argc) == 7 ? 0 : 2;
+}
@@ -0,0 +1,26 @@
+// EXPECT: 127
+typedef int vec4[4];
+typedef int handler(int);
+typedef long long i64;
+
+static int twice(int x) { return x * 2; }
+static int inc(int x) { return x + 1; }
+
+static int dot(vec4 a, vec4 b) {
+    int s = 0;
+    for (int i = 0; i < 4; i++) s += a[i] * b[i];
+    return s + (int)sizeof(a);
+}
+
+static int apply(handler *h, int x) { return h(x); }
+static int apply_decayed(handler h, int x) { return h(x); }
+
+int main(void) {
+    vec4 v = {1, 2, 3, 4};
+    vec4 w;
+    for (int i = 0; i < 4; i++) w[i] = 10 * (i + 1);
+    handler *table[2] = {twice, inc};
+    i64 big = 1;
+    return sizeof(vec4) + sizeof(v) + sizeof(big) + dot(v, w) + apply(table[0], 5) + apply_decayed(inc, 6)
+        + apply(table[1], 1) + (int)sizeof(table);
+}
@@ -0,0 +1,21 @@
+// EXPECT: 42
+// Universal character names in identifiers, strings, and character constants
+
+int café = 40;
+
+int über(int x) { return x + 1; }
+
+int main() {
+    const char *utf8 = "é\U0001F600";
+    const int *wide = L"é\U0001F600";
+    int n = 0;
+    while (utf8[n]) n++;
+
+    if (n != 6) return 1;
+    if ((utf8[0] & 0xff) != 0xc3 || (utf8[2] & 0xff) != 0xf0) return 2;
+    if (wide[0] != 0xe9 || wide[1] != 0x1f600 || wide[2] != 0) return 3;
+    if (L'€' != 0x20ac || 'é' != 0xc3a9) return 4;
+    /* The two spellings name the same variable */
+    if (caf\u00e9 != 40 || &café != &caf\u00e9) return 5;
+    return über(café) + 1;
+}
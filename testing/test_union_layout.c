@@ -0,0 +1,34 @@
+// EXPECT: 57
+// Test union layout and initializers: size rounded up to alignment, local
+// and typedef'd definitions, and members sharing storage
+union Mixed {
+    char tag[5];
+    int value;
+};
+
+union Mixed table[3] = { { "abcd" }, { .value = 7 }, { "xy" } };
+
+typedef union {
+    float f;
+    unsigned int bits;
+} FloatBits;
+
+struct Point { int x, y; } origin = { 3, 4 }, spare;
+
+int main() {
+    union Word { int whole; char bytes[4]; } w;
+    w.whole = 0x01020304;
+
+    FloatBits fb;
+    fb.f = 1.0f;
+
+    int result = 0;
+    result += sizeof(union Mixed);              // 8
+    result += sizeof(table) / sizeof(table[0]); // 3
+    result += table[1].value;                   // 7
+    result += table[2].tag[1] - 'x';            // 1
+    result += w.bytes[0];                       // 4
+    result += (fb.bits >> 23) - 100;            // 27
+    result += origin.x + origin.y + spare.x;    // 7
+    return result;
+}
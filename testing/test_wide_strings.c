@@ -0,0 +1,29 @@
+// EXPECT: 42
+// Wide, UTF-16, UTF-32, and UTF-8 string and character literals
+
+const int greeting[] = L"héllo";
+unsigned short padded[6] = u"a😀";
+
+int length(const int *s) {
+    int n = 0;
+    while (s[n]) n++;
+    return n;
+}
+
+int main() {
+    const int *wide = L"wide é";
+    const unsigned int *utf32 = U"x😀";
+    const unsigned short *utf16 = u"x😀";
+    const char *utf8 = u8"é";
+    int local[] = L"ab€";
+
+    if (length(wide) != 6 || wide[5] != 0xe9) return 1;
+    if (utf32[1] != 0x1f600) return 2;
+    if (utf16[1] != 0xd83d || utf16[2] != 0xde00 || utf16[3] != 0) return 3;
+    if ((utf8[0] & 0xff) != 0xc3 || (utf8[1] & 0xff) != 0xa9 || utf8[2] != 0) return 4;
+    if (sizeof(local) != 4 * sizeof(int) || local[2] != 0x20ac) return 5;
+    if (greeting[1] != 0xe9 || length(greeting) != 5) return 6;
+    if (padded[2] != 0xde00 || padded[3] != 0 || padded[5] != 0) return 7;
+    if (L'a' != 97 || u'€' != 0x20ac || U'😀' != 0x1f600) return 8;
+    return L'*';
+}